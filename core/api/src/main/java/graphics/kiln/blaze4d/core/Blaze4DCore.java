@@ -1,45 +1,1313 @@
 package graphics.kiln.blaze4d.core;
 
+import graphics.kiln.blaze4d.core.natives.MeshDataNative;
 import graphics.kiln.blaze4d.core.natives.Natives;
+import graphics.kiln.blaze4d.core.types.B4DConfig;
 import graphics.kiln.blaze4d.core.types.B4DFormat;
 import graphics.kiln.blaze4d.core.types.B4DImageData;
 import graphics.kiln.blaze4d.core.types.B4DMeshData;
+import graphics.kiln.blaze4d.core.types.B4DUniform;
+import graphics.kiln.blaze4d.core.types.B4DUniformData;
 import graphics.kiln.blaze4d.core.types.B4DVertexFormat;
+import jdk.incubator.foreign.FunctionDescriptor;
 import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.NativeSymbol;
+import jdk.incubator.foreign.ValueLayout;
 import org.apache.logging.log4j.LogManager;
 import org.apache.logging.log4j.Logger;
 import org.apache.logging.log4j.message.StringFormatterMessageFactory;
 
+import java.lang.invoke.MethodHandle;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
+import java.nio.charset.StandardCharsets;
+import java.util.EnumMap;
+import java.util.Map;
+import java.util.Set;
+import java.util.concurrent.ConcurrentHashMap;
+import java.util.concurrent.Executor;
+import java.util.function.Consumer;
+import java.util.function.LongConsumer;
+
 public class Blaze4DCore implements AutoCloseable {
     public static final Logger LOGGER = LogManager.getLogger("Blaze4DCore", new StringFormatterMessageFactory());
 
     private final MemoryAddress handle;
 
+    private final ResourceScope deviceLostCallbackScope;
+    private volatile Consumer<DeviceLostReason> deviceLostCallback;
+    private volatile DeviceGeneration deviceGeneration = new DeviceGeneration();
+    private ResourceScope memoryPressureCallbackScope;
+    private ResourceScope meshEvictionCallbackScope;
+    private ResourceScope eventLogCallbackScope;
+    private ResourceScope jobExecutorScope;
+
+    /**
+     * Keeps the upcall stubs of pending probe captures alive until their callback has been called.
+     */
+    private static final Set<ResourceScope> PROBE_CALLBACK_SCOPES = ConcurrentHashMap.newKeySet();
+
+    /**
+     * Keeps the upcall stubs of pending panorama captures alive until their callback has been called.
+     */
+    private static final Set<ResourceScope> PANORAMA_CALLBACK_SCOPES = ConcurrentHashMap.newKeySet();
+
+    /**
+     * Creates a new instance using the default configuration. Diagnostic options are read from
+     * system properties (see {@link B4DConfig}).
+     */
     public Blaze4DCore(long glfwWindow) {
-        boolean enableValidation = System.getProperty("b4d.enable_validation") != null;
+        String seed = System.getProperty("b4d.seeded_ids");
+        if (seed != null) {
+            enableSeededIds(Long.parseLong(seed));
+        }
 
         MemoryAddress surfaceProvider = Natives.b4dCreateGlfwSurfaceProvider(glfwWindow);
-        this.handle = Natives.b4dInit(surfaceProvider, enableValidation);
+
+        try (B4DConfig config = new B4DConfig()) {
+            config.setEnableValidation(System.getProperty("b4d.enable_validation") != null);
+            this.handle = Natives.b4dInitWithConfig(surfaceProvider, config.getAddress());
+        } catch (Exception e) {
+            throw new RuntimeException("Failed to create Blaze4D instance", e);
+        }
+        this.deviceLostCallbackScope = this.hookDeviceLost();
+    }
+
+    /**
+     * Makes all ids (shaders, vertex formats, meshes...) depend only on the seed and the order in which they are
+     * created so golden image and replay tests produce identical ids across runs. Must be called before any
+     * instance is created.
+     */
+    public static void enableSeededIds(long seed) {
+        Natives.b4dEnableSeededIds(seed);
+    }
+
+    /**
+     * Sets the native log level of a subsystem. Levels are shared by all instances and may be set before any
+     * instance is created. Native logs are additionally filtered by the log4j configuration.
+     */
+    public static void setLogLevel(LogSubsystem subsystem, LogLevel level) {
+        Natives.b4dSetLogLevel(subsystem.raw, level.raw);
+    }
+
+    public Blaze4DCore(long glfwWindow, B4DConfig config) {
+        MemoryAddress surfaceProvider = Natives.b4dCreateGlfwSurfaceProvider(glfwWindow);
+        this.handle = Natives.b4dInitWithConfig(surfaceProvider, config.getAddress());
+        this.deviceLostCallbackScope = this.hookDeviceLost();
+    }
+
+    private Blaze4DCore(MemoryAddress surfaceProvider, B4DConfig config) {
+        this.handle = Natives.b4dInitWithConfig(surfaceProvider, config.getAddress());
+        this.deviceLostCallbackScope = this.hookDeviceLost();
+    }
+
+    /**
+     * Creates a new instance which presents to a headless surface of the specified size instead
+     * of a window. Requires VK_EXT_headless_surface. Intended for benchmarks and CI where no
+     * display server is available.
+     */
+    public static Blaze4DCore createHeadless(int width, int height, B4DConfig config) {
+        return new Blaze4DCore(Natives.b4dCreateHeadlessSurface(width, height), config);
     }
 
     public void setDebugMode(DebugMode mode) {
         Natives.b4dSetDebugMode(this.handle, mode.raw);
     }
 
+    /**
+     * Returns all display modes supported by the monitor the window is fullscreen on or the primary monitor if the
+     * window is not fullscreen.
+     */
+    public DisplayMode[] getDisplayModes() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            int count = Natives.b4dGetDisplayModes(this.handle, MemoryAddress.NULL, 0);
+            MemorySegment modes = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * 4 * Math.max(count, 1), scope);
+            count = Math.min(count, Natives.b4dGetDisplayModes(this.handle, modes.address(), count));
+
+            int[] values = modes.toArray(ValueLayout.JAVA_INT);
+            DisplayMode[] result = new DisplayMode[count];
+            for (int i = 0; i < count; i++) {
+                result[i] = new DisplayMode(values[i * 4], values[i * 4 + 1], values[i * 4 + 2], values[i * 4 + 3]);
+            }
+            return result;
+        }
+    }
+
+    /**
+     * Requests the window to enter exclusive fullscreen with the specified display mode. If the mode is not supported
+     * by the monitor the closest supported mode is used. Must be called on the main thread.
+     *
+     * @param mode The display mode or null to leave fullscreen.
+     * @return False if the window does not support exclusive fullscreen.
+     */
+    public boolean setExclusiveFullscreen(DisplayMode mode) {
+        if (mode == null) {
+            return Natives.b4dSetExclusiveFullscreen(this.handle, MemoryAddress.NULL);
+        }
+
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment segment = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * 4, scope);
+            segment.setAtIndex(ValueLayout.JAVA_INT, 0, mode.width());
+            segment.setAtIndex(ValueLayout.JAVA_INT, 1, mode.height());
+            segment.setAtIndex(ValueLayout.JAVA_INT, 2, mode.refreshRate());
+            segment.setAtIndex(ValueLayout.JAVA_INT, 3, mode.bitDepth());
+            return Natives.b4dSetExclusiveFullscreen(this.handle, segment.address());
+        }
+    }
+
+    /**
+     * Returns the content scale of the main window. The first element is the horizontal scale
+     * and the second element is the vertical scale.
+     */
+    public float[] getContentScale() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment scale = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 2, scope);
+            Natives.b4dGetContentScale(this.handle, scale.address());
+            return scale.toArray(ValueLayout.JAVA_FLOAT);
+        }
+    }
+
+    /**
+     * Returns the world space position at a cursor position in logical pixels using the camera matrices of the last
+     * frame or null if no frame has set camera matrices.
+     *
+     * @param depth The depth in the range [0, 1] read back by the caller or a negative value to use the near plane.
+     */
+    public float[] unproject(float x, float y, float depth) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment position = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 3, scope);
+            if (!Natives.b4dUnproject(this.handle, x, y, depth, position.address())) {
+                return null;
+            }
+            return position.toArray(ValueLayout.JAVA_FLOAT);
+        }
+    }
+
+    /**
+     * Returns the world space ray at a cursor position in logical pixels or null if no frame has set camera
+     * matrices. The first 3 elements are the origin on the near plane and the last 3 elements the normalized
+     * direction.
+     */
+    public float[] unprojectRay(float x, float y) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment ray = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 6, scope);
+            if (!Natives.b4dUnprojectRay(this.handle, x, y, ray.address(), ray.address().addOffset(ValueLayout.JAVA_FLOAT.byteSize() * 3))) {
+                return null;
+            }
+            return ray.toArray(ValueLayout.JAVA_FLOAT);
+        }
+    }
+
+    /**
+     * Projects world space points onto the screen using the camera matrices of the last frame. Returns null if no
+     * frame has set camera matrices.
+     *
+     * @param positions Consecutive xyz triples of world space positions.
+     * @return For every point the x and y position in logical pixels and the depth. Points behind the camera are NaN.
+     */
+    public float[] projectPoints(float[] positions) {
+        if (positions.length % 3 != 0) {
+            throw new IllegalArgumentException("Positions must contain xyz triples");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment points = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * positions.length, scope);
+            points.copyFrom(MemorySegment.ofArray(positions));
+            MemorySegment results = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * positions.length, scope);
+            if (!Natives.b4dProjectPoints(this.handle, points.address(), positions.length / 3, results.address())) {
+                return null;
+            }
+            return results.toArray(ValueLayout.JAVA_FLOAT);
+        }
+    }
+
+    /**
+     * Registers a callback which is called after the device has been recreated because it was lost.
+     * All global meshes, images and shaders must be recreated by the callback. Objects created before the device was
+     * lost report {@link GlobalMesh#isDeviceLost()} and are ignored by frames.
+     *
+     * If null is passed the current callback is removed.
+     */
+    public void setDeviceLostCallback(Consumer<DeviceLostReason> callback) {
+        this.deviceLostCallback = callback;
+    }
+
+    /**
+     * Registers the native device lost callback. The callback stays registered until the instance is destroyed.
+     */
+    private ResourceScope hookDeviceLost() {
+        try {
+            MethodHandle target = MethodHandles.lookup().findVirtual(Blaze4DCore.class, "onDeviceLost",
+                    MethodType.methodType(Void.TYPE, Integer.TYPE, MemoryAddress.class)).bindTo(this);
+
+            ResourceScope scope = ResourceScope.newSharedScope();
+            NativeSymbol symbol = Natives.linker.upcallStub(target,
+                    FunctionDescriptor.ofVoid(ValueLayout.JAVA_INT, ValueLayout.ADDRESS),
+                    scope
+            );
+            Natives.b4dSetDeviceLostCallback(this.handle, symbol);
+            return scope;
+        } catch (NoSuchMethodException | IllegalAccessException e) {
+            throw new RuntimeException("Failed to create device lost callback", e);
+        }
+    }
+
+    private void onDeviceLost(int reason, MemoryAddress userData) {
+        DeviceLostReason lostReason = DeviceLostReason.fromValue(reason);
+
+        // Objects created from now on belong to the new device
+        DeviceGeneration lost = this.deviceGeneration;
+        this.deviceGeneration = new DeviceGeneration();
+        lost.markLost();
+        LOGGER.warn("Device has been recreated because of %s. All global objects must be recreated", lostReason);
+
+        Consumer<DeviceLostReason> callback = this.deviceLostCallback;
+        if (callback != null) {
+            try {
+                callback.accept(lostReason);
+            } catch (Throwable e) {
+                LOGGER.error("Device lost callback threw exception", e);
+            }
+        }
+    }
+
+    /**
+     * Returns the device fault information collected the last time the device was lost or null if
+     * the device has never been lost. Intended to be included in crash reports.
+     */
+    public String getLastFaultReport() {
+        long length = Natives.b4dGetLastFaultReport(this.handle, MemoryAddress.NULL, 0);
+        if (length == 0) {
+            return null;
+        }
+
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment buffer = MemorySegment.allocateNative(length + 1, scope);
+            Natives.b4dGetLastFaultReport(this.handle, buffer.address(), length + 1);
+            return new String(buffer.asSlice(0, length).toArray(ValueLayout.JAVA_BYTE), StandardCharsets.UTF_8);
+        }
+    }
+
+    /**
+     * Registers a callback which is called whenever the device memory pressure level changes.
+     *
+     * If null is passed the current callback is removed.
+     */
+    public void setMemoryPressureCallback(MemoryPressureCallback callback) {
+        ResourceScope oldScope = this.memoryPressureCallbackScope;
+
+        if (callback == null) {
+            Natives.b4dSetMemoryPressureCallback(this.handle, MemoryAddress.NULL);
+            this.memoryPressureCallbackScope = null;
+        } else {
+            try {
+                MethodHandle target = MethodHandles.lookup().findStatic(Blaze4DCore.class, "onMemoryPressure",
+                        MethodType.methodType(Void.TYPE, MemoryPressureCallback.class, Integer.TYPE, Long.TYPE, Long.TYPE, MemoryAddress.class)).bindTo(callback);
+
+                ResourceScope scope = ResourceScope.newSharedScope();
+                NativeSymbol symbol = Natives.linker.upcallStub(target,
+                        FunctionDescriptor.ofVoid(ValueLayout.JAVA_INT, ValueLayout.JAVA_LONG, ValueLayout.JAVA_LONG, ValueLayout.ADDRESS),
+                        scope
+                );
+                Natives.b4dSetMemoryPressureCallback(this.handle, symbol);
+                this.memoryPressureCallbackScope = scope;
+            } catch (NoSuchMethodException | IllegalAccessException e) {
+                throw new RuntimeException("Failed to create memory pressure callback", e);
+            }
+        }
+
+        if (oldScope != null) {
+            oldScope.close();
+        }
+    }
+
+    private static void onMemoryPressure(MemoryPressureCallback callback, int pressure, long usage, long budget, MemoryAddress userData) {
+        try {
+            callback.onMemoryPressure(MemoryPressure.fromValue(pressure), usage, budget);
+        } catch (Throwable e) {
+            LOGGER.error("Memory pressure callback threw exception", e);
+        }
+    }
+
+    /**
+     * Registers a callback which is called with the id of each evictable mesh selected for eviction.
+     * The callback should close the mesh and recreate it the next time it is needed. Meshes are only
+     * evicted while a callback is registered.
+     *
+     * If null is passed the current callback is removed.
+     */
+    public void setMeshEvictionCallback(LongConsumer callback) {
+        ResourceScope oldScope = this.meshEvictionCallbackScope;
+
+        if (callback == null) {
+            Natives.b4dSetMeshEvictionCallback(this.handle, MemoryAddress.NULL);
+            this.meshEvictionCallbackScope = null;
+        } else {
+            try {
+                MethodHandle target = MethodHandles.lookup().findStatic(Blaze4DCore.class, "onMeshEvicted",
+                        MethodType.methodType(Void.TYPE, LongConsumer.class, Long.TYPE, MemoryAddress.class)).bindTo(callback);
+
+                ResourceScope scope = ResourceScope.newSharedScope();
+                NativeSymbol symbol = Natives.linker.upcallStub(target,
+                        FunctionDescriptor.ofVoid(ValueLayout.JAVA_LONG, ValueLayout.ADDRESS),
+                        scope
+                );
+                Natives.b4dSetMeshEvictionCallback(this.handle, symbol);
+                this.meshEvictionCallbackScope = scope;
+            } catch (NoSuchMethodException | IllegalAccessException e) {
+                throw new RuntimeException("Failed to create mesh eviction callback", e);
+            }
+        }
+
+        if (oldScope != null) {
+            oldScope.close();
+        }
+    }
+
+    private static void onMeshEvicted(LongConsumer callback, long meshId, MemoryAddress userData) {
+        try {
+            callback.accept(meshId);
+        } catch (Throwable e) {
+            LOGGER.error("Mesh eviction callback threw exception", e);
+        }
+    }
+
+    public void setMeshEvictable(GlobalMesh mesh, boolean evictable) {
+        Natives.b4dSetMeshEvictable(this.handle, mesh.getHandle(), evictable);
+    }
+
+    /**
+     * Returns the current device local memory usage and budget in bytes. The first element is the
+     * usage and the second element is the budget.
+     */
+    public long[] getMemoryUsage() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment usage = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 2, scope);
+            Natives.b4dGetMemoryUsage(this.handle, usage.address(), usage.address().addOffset(ValueLayout.JAVA_LONG.byteSize()));
+            return usage.toArray(ValueLayout.JAVA_LONG);
+        }
+    }
+
+    /**
+     * Limits the number of bytes uploaded to global meshes and images per frame. A budget of 0
+     * disables the limit.
+     */
+    public void setUploadBudget(long budget) {
+        Natives.b4dSetUploadBudget(this.handle, budget);
+    }
+
+    public void setVsync(boolean vsync) {
+        Natives.b4dSetVsync(this.handle, vsync);
+    }
+
+    public void setFramesInFlight(int framesInFlight) {
+        Natives.b4dSetFramesInFlight(this.handle, framesInFlight);
+    }
+
+    /**
+     * Sets the number of threads used for cpu side work like culling and mesh optimization. A value of 0 derives the
+     * count from the number of available cores. Replaces any executor set using {@link #setJobExecutor}.
+     */
+    public void setJobThreadCount(int threadCount) {
+        ResourceScope oldScope = this.jobExecutorScope;
+        Natives.b4dSetJobThreadCount(this.handle, threadCount);
+        this.jobExecutorScope = null;
+
+        if (oldScope != null) {
+            oldScope.close();
+        }
+    }
+
+    /**
+     * Runs cpu side jobs on an executor managed by the host instead of the internal threads. Jobs may block while
+     * waiting for other jobs of the same batch so the executor should not run them on the render thread.
+     *
+     * If null is passed the internal threads are restored.
+     *
+     * @param parallelism The number of jobs the executor can run concurrently.
+     */
+    public void setJobExecutor(Executor executor, int parallelism) {
+        ResourceScope oldScope = this.jobExecutorScope;
+
+        if (executor == null) {
+            Natives.b4dSetJobExecutor(this.handle, MemoryAddress.NULL, 0);
+            this.jobExecutorScope = null;
+        } else {
+            try {
+                MethodHandle target = MethodHandles.lookup().findStatic(Blaze4DCore.class, "onExecuteJob",
+                        MethodType.methodType(Void.TYPE, Executor.class, MemoryAddress.class, MemoryAddress.class)).bindTo(executor);
+
+                ResourceScope scope = ResourceScope.newSharedScope();
+                NativeSymbol symbol = Natives.linker.upcallStub(target,
+                        FunctionDescriptor.ofVoid(ValueLayout.ADDRESS, ValueLayout.ADDRESS),
+                        scope
+                );
+                Natives.b4dSetJobExecutor(this.handle, symbol, parallelism);
+                this.jobExecutorScope = scope;
+            } catch (NoSuchMethodException | IllegalAccessException e) {
+                throw new RuntimeException("Failed to create job executor callback", e);
+            }
+        }
+
+        if (oldScope != null) {
+            oldScope.close();
+        }
+    }
+
+    private static void onExecuteJob(Executor executor, MemoryAddress job, MemoryAddress userData) {
+        try {
+            executor.execute(() -> Natives.b4dRunJob(job));
+        } catch (Throwable e) {
+            // The job must still be run to release it. The work itself is done by the thread which started the batch.
+            LOGGER.error("Job executor threw exception", e);
+            Natives.b4dRunJob(job);
+        }
+    }
+
+    /**
+     * Returns the accumulated timing statistics of all job batches of a kind.
+     *
+     * @param reset If true the statistics of all kinds are reset afterwards.
+     */
+    public JobStats getJobStats(JobKind kind, boolean reset) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment stats = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 5, scope);
+            Natives.b4dGetJobStats(this.handle, kind.raw, reset, stats.address());
+            long[] values = stats.toArray(ValueLayout.JAVA_LONG);
+            return new JobStats(values[0], values[1], values[2], values[3], values[4]);
+        }
+    }
+
+    public void setLatencyMode(LatencyMode mode) {
+        Natives.b4dSetLatencyMode(this.handle, mode.raw);
+    }
+
+    /**
+     * Selects when the commands of a frame are handed to the render worker. Vulkan recording, submission and
+     * presentation always happen on the worker thread. Applies to all frames started after this call.
+     */
+    public void setSubmitMode(SubmitMode mode) {
+        Natives.b4dSetSubmitMode(this.handle, mode.raw);
+    }
+
+    /**
+     * Sets the view bobbing and screen shake of all following frames. Frames which enable camera effects using
+     * {@link Frame#setCameraEffectsEnabled(boolean)} apply them to every projection matrix so the host can keep passing
+     * the unmodified matrices. Effects with an amplitude or intensity of 0 are disabled. Views computed from the camera
+     * by the host must use {@link Frame#applyCameraEffectsToView(float[])}. The nausea and portal distortion is set
+     * using {@link #setScreenDistortion(float, float, float)}.
+     *
+     * @param bobPhase The walk cycle in radians.
+     * @param bobAmplitude The interpolated bob of the player.
+     * @param shakeIntensity The roll of the camera in radians.
+     * @param shakeDirection The direction the camera rolls away from in degrees.
+     */
+    public void setCameraEffects(float bobPhase, float bobAmplitude, float shakeIntensity, float shakeDirection) {
+        Natives.b4dSetCameraEffects(this.handle, bobPhase, bobAmplitude, shakeIntensity, shakeDirection);
+    }
+
+    public void clearCameraEffects() {
+        this.setCameraEffects(0.0f, 0.0f, 0.0f, 0.0f);
+    }
+
+    /**
+     * Returns true while frames are skipped because the window has no size, for example because it is minimized.
+     * Starting a frame returns null in this state until the window is restored.
+     */
+    public boolean isSurfacePaused() {
+        return Natives.b4dIsSurfacePaused(this.handle);
+    }
+
+    /**
+     * Returns the timing of recently displayed frames or null if VK_GOOGLE_display_timing is not
+     * supported.
+     */
+    public DisplayTiming getDisplayTiming() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment timing = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 3, scope);
+            if (!Natives.b4dGetDisplayTiming(this.handle, timing.address())) {
+                return null;
+            }
+
+            int variableRefresh = timing.get(ValueLayout.JAVA_INT, 16);
+            return new DisplayTiming(
+                    timing.get(ValueLayout.JAVA_LONG, 0),
+                    timing.get(ValueLayout.JAVA_LONG, 8),
+                    variableRefresh == 0 ? null : variableRefresh == 2
+            );
+        }
+    }
+
+    public DeviceInfo getDeviceInfo() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment info = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * 5, scope);
+            Natives.b4dGetDeviceInfo(this.handle, info.address());
+
+            int apiVersion = info.get(ValueLayout.JAVA_INT, 0);
+            return new DeviceInfo(
+                    (apiVersion >>> 22) & 0x7F,
+                    (apiVersion >>> 12) & 0x3FF,
+                    apiVersion & 0xFFF,
+                    FeatureTier.fromValue(info.get(ValueLayout.JAVA_INT, 4)),
+                    FeatureSource.fromValue(info.get(ValueLayout.JAVA_INT, 8)),
+                    FeatureSource.fromValue(info.get(ValueLayout.JAVA_INT, 12)),
+                    FeatureSource.fromValue(info.get(ValueLayout.JAVA_INT, 16))
+            );
+        }
+    }
+
+    /**
+     * Returns the host memory allocated by the vulkan driver for each allocation scope or null if
+     * host memory tracking has not been enabled in the config.
+     */
+    public Map<HostMemoryScope, HostMemoryStats> getHostMemoryReport() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            long statsSize = ValueLayout.JAVA_LONG.byteSize() * 5;
+            MemorySegment stats = MemorySegment.allocateNative(statsSize * HostMemoryScope.values().length, scope);
+            if (!Natives.b4dGetHostMemoryReport(this.handle, stats.address())) {
+                return null;
+            }
+
+            Map<HostMemoryScope, HostMemoryStats> report = new EnumMap<>(HostMemoryScope.class);
+            for (HostMemoryScope memoryScope : HostMemoryScope.values()) {
+                long offset = statsSize * memoryScope.raw;
+                report.put(memoryScope, new HostMemoryStats(
+                        stats.get(ValueLayout.JAVA_LONG, offset),
+                        stats.get(ValueLayout.JAVA_LONG, offset + 8),
+                        stats.get(ValueLayout.JAVA_LONG, offset + 16),
+                        stats.get(ValueLayout.JAVA_LONG, offset + 24),
+                        stats.get(ValueLayout.JAVA_LONG, offset + 32)
+                ));
+            }
+            return report;
+        }
+    }
+
+    public void setColorGradingPreset(ColorGradingPreset preset) {
+        Natives.b4dSetColorGradingPreset(this.handle, preset.raw);
+    }
+
+    /**
+     * Sets the per channel lift, gamma and gain curves. Each array must contain 3 values for the
+     * red, green and blue channel.
+     */
+    public void setColorGradingCurves(float[] lift, float[] gamma, float[] gain) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment liftSegment = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 3, scope);
+            MemorySegment gammaSegment = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 3, scope);
+            MemorySegment gainSegment = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 3, scope);
+            liftSegment.copyFrom(MemorySegment.ofArray(lift).asSlice(0, ValueLayout.JAVA_FLOAT.byteSize() * 3));
+            gammaSegment.copyFrom(MemorySegment.ofArray(gamma).asSlice(0, ValueLayout.JAVA_FLOAT.byteSize() * 3));
+            gainSegment.copyFrom(MemorySegment.ofArray(gain).asSlice(0, ValueLayout.JAVA_FLOAT.byteSize() * 3));
+            Natives.b4dSetColorGradingCurves(this.handle, liftSegment.address(), gammaSegment.address(), gainSegment.address());
+        }
+    }
+
+    /**
+     * Sets the LUT applied after the curves. The image must be a strip of size * size by size
+     * texels where each square slice holds one blue value. Passing null removes the LUT.
+     *
+     * The LUT is removed if the device is recreated.
+     */
+    public void setColorGradingLut(GlobalImage lut, int size, float strength) {
+        Natives.b4dSetColorGradingLut(this.handle, lut == null ? MemoryAddress.NULL : lut.getHandle(), size, strength);
+    }
+
+    /**
+     * Enables auto exposure. The average luminance of each frame is measured on the gpu and the
+     * exposure of following frames adapts towards mapping it to the target luminance. The
+     * luminance range is given in log2 units and limits how far the exposure can change.
+     *
+     * The adaptation speed is the rate per second at which the exposure approaches its target.
+     */
+    public void enableAutoExposure(float minLogLuminance, float maxLogLuminance, float targetLuminance, float adaptationSpeed) {
+        Natives.b4dSetAutoExposure(this.handle, 1, minLogLuminance, maxLogLuminance, targetLuminance, adaptationSpeed);
+    }
+
+    public void disableAutoExposure() {
+        Natives.b4dSetAutoExposure(this.handle, 0, -4f, 1f, 0.18f, 1.5f);
+    }
+
+    /**
+     * Records all resource creations and frames into a command stream file which can be replayed
+     * with the replay_stream tool to reproduce rendering bugs. Should be started before the world
+     * is loaded so all used resources are part of the stream.
+     *
+     * @param path The file the stream is written to once all frames have been recorded.
+     * @param frameCount The number of frames to record.
+     * @param includeMeshData If true the full mesh data is recorded instead of only a hash.
+     */
+    public void startCommandStreamRecording(String path, int frameCount, boolean includeMeshData) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            byte[] bytes = path.getBytes(StandardCharsets.UTF_8);
+            MemorySegment string = MemorySegment.allocateNative(bytes.length + 1, scope);
+            string.copyFrom(MemorySegment.ofArray(bytes));
+            string.set(ValueLayout.JAVA_BYTE, bytes.length, (byte) 0);
+            Natives.b4dStartCommandStreamRecording(this.handle, string.address(), frameCount, includeMeshData ? 1 : 0);
+        }
+    }
+
+    /**
+     * Starts appending a JSONL log of frame boundaries, uploads, swapchain events and errors to the
+     * file. Replaces any active event log.
+     *
+     * @return False if the file could not be opened.
+     */
+    public boolean startEventLog(String path) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            byte[] bytes = path.getBytes(StandardCharsets.UTF_8);
+            MemorySegment string = MemorySegment.allocateNative(bytes.length + 1, scope);
+            string.copyFrom(MemorySegment.ofArray(bytes));
+            string.set(ValueLayout.JAVA_BYTE, bytes.length, (byte) 0);
+            return Natives.b4dStartEventLogFile(this.handle, string.address());
+        }
+    }
+
+    /**
+     * Delivers each line of the JSONL event log to the callback. The callback may be called from
+     * any thread. Replaces any active event log.
+     *
+     * If null is passed the event log is stopped.
+     */
+    public void setEventLogCallback(Consumer<String> callback) {
+        ResourceScope oldScope = this.eventLogCallbackScope;
+
+        if (callback == null) {
+            Natives.b4dSetEventLogCallback(this.handle, MemoryAddress.NULL);
+            this.eventLogCallbackScope = null;
+        } else {
+            try {
+                MethodHandle target = MethodHandles.lookup().findStatic(Blaze4DCore.class, "onEventLogLine",
+                        MethodType.methodType(Void.TYPE, Consumer.class, MemoryAddress.class, Integer.TYPE, MemoryAddress.class)).bindTo(callback);
+
+                ResourceScope scope = ResourceScope.newSharedScope();
+                NativeSymbol symbol = Natives.linker.upcallStub(target,
+                        FunctionDescriptor.ofVoid(ValueLayout.ADDRESS, ValueLayout.JAVA_INT, ValueLayout.ADDRESS),
+                        scope
+                );
+                Natives.b4dSetEventLogCallback(this.handle, symbol);
+                this.eventLogCallbackScope = scope;
+            } catch (NoSuchMethodException | IllegalAccessException e) {
+                throw new RuntimeException("Failed to create event log callback", e);
+            }
+        }
+
+        if (oldScope != null) {
+            oldScope.close();
+        }
+    }
+
+    private static void onEventLogLine(Consumer<String> callback, MemoryAddress line, int length, MemoryAddress userData) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            byte[] data = MemorySegment.ofAddress(line, length, scope).toArray(ValueLayout.JAVA_BYTE);
+            callback.accept(new String(data, StandardCharsets.UTF_8));
+        } catch (Throwable e) {
+            LOGGER.error("Event log callback threw exception", e);
+        }
+    }
+
+    /**
+     * Registers a vanilla style color-matrix post-process chain (for example the creeper spectator
+     * vision). Only chains built from the blit, color_convolve and invert programs without
+     * auxiliary targets are supported. Chains using other programs like the spider or enderman
+     * vision are rejected.
+     *
+     * @param json The json description of the chain in the vanilla format.
+     * @return The chain id or 0 if the chain is invalid or not supported.
+     */
+    public long registerPostChain(String json) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            byte[] bytes = json.getBytes(StandardCharsets.UTF_8);
+            MemorySegment string = MemorySegment.allocateNative(bytes.length + 1, scope);
+            string.copyFrom(MemorySegment.ofArray(bytes));
+            string.set(ValueLayout.JAVA_BYTE, bytes.length, (byte) 0);
+            return Natives.b4dRegisterPostChain(this.handle, string.address());
+        }
+    }
+
+    public void unregisterPostChain(long chainId) {
+        Natives.b4dUnregisterPostChain(this.handle, chainId);
+    }
+
+    /**
+     * Activates a color-matrix chain for all following frames. Passing 0 deactivates the current
+     * chain.
+     */
+    public void setActivePostChain(long chainId) {
+        Natives.b4dSetActivePostChain(this.handle, chainId);
+    }
+
+    /**
+     * Sets the accessibility filter for all following frames. The filter is applied after the
+     * active post-process chain.
+     */
+    public void setAccessibilityFilter(AccessibilityFilter filter) {
+        Natives.b4dSetAccessibilityFilter(this.handle, filter.raw);
+    }
+
+    /**
+     * Sets the nausea and portal screen distortion for all following frames. The time should be updated every frame
+     * while the distortion is active.
+     *
+     * @param intensity The strength of the distortion in the range [0, 1]. 0 disables the distortion.
+     * @param time The animation time in ticks.
+     * @param speed The animation speed in degrees per tick. Vanilla uses 7 for nausea and 20 for nether portals.
+     */
+    public void setScreenDistortion(float intensity, float time, float speed) {
+        Natives.b4dSetScreenDistortion(this.handle, intensity, time, speed);
+    }
+
+    /**
+     * Sets the shader used to draw the built-in debug overlays at the end of every frame. The shader must store the
+     * position as 3 floats and the color as 4 unsigned normalized bytes. Passing 0 disables all overlays.
+     */
+    public void setDebugOverlayShader(long shaderId) {
+        Natives.b4dSetDebugOverlayShader(this.handle, shaderId);
+    }
+
+    /**
+     * Sets the origin debug overlay positions are made relative to. Hosts using camera relative matrices pass the
+     * block containing the camera and the offset of the camera inside of it.
+     */
+    public void setDebugOverlayOrigin(int x, int y, int z, float offsetX, float offsetY, float offsetZ) {
+        Natives.b4dSetDebugOverlayOrigin(this.handle, x, y, z, offsetX, offsetY, offsetZ);
+    }
+
+    /**
+     * Shows the chunk borders around a chunk and the section boundaries of the chunk. Colors are packed as
+     * 0xAARRGGBB.
+     */
+    public void setDebugChunkGrid(int chunkX, int chunkZ, int radius, int minY, int maxY, int color, int sectionColor) {
+        Natives.b4dSetDebugChunkGrid(this.handle, true, chunkX, chunkZ, radius, minY, maxY, color, sectionColor);
+    }
+
+    public void clearDebugChunkGrid() {
+        Natives.b4dSetDebugChunkGrid(this.handle, false, 0, 0, 0, 0, 0, 0, 0);
+    }
+
+    /**
+     * Outlines sections in a color mapped from a value. Values equal to minValue are blue and values equal to
+     * maxValue are red. Passing empty arrays removes the heatmap.
+     *
+     * @param sections Consecutive xyz triples of section positions.
+     * @param values One value per section.
+     */
+    public void setDebugHeatmap(int[] sections, float[] values, float minValue, float maxValue, int alpha) {
+        if (sections.length != values.length * 3) {
+            throw new IllegalArgumentException("Sections must contain one xyz triple per value");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment sectionSegment = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * Math.max(sections.length, 1), scope);
+            sectionSegment.copyFrom(MemorySegment.ofArray(sections));
+            MemorySegment valueSegment = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * Math.max(values.length, 1), scope);
+            valueSegment.copyFrom(MemorySegment.ofArray(values));
+            Natives.b4dSetDebugHeatmap(this.handle, sectionSegment.address(), valueSegment.address(), values.length, minValue, maxValue, alpha);
+        }
+    }
+
     public long createShader(B4DVertexFormat vertexFormat, long usedUniforms) {
         return Natives.b4dCreateShader(this.handle, vertexFormat.getAddress(), usedUniforms);
     }
 
+    /**
+     * Creates a shader using a vertex format registered with {@link #registerVertexFormat(B4DVertexFormat)}.
+     *
+     * @return The shader id or 0 if the vertex format is not registered.
+     */
+    public long createShader(long vertexFormatId, long usedUniforms) {
+        return Natives.b4dCreateShaderWithFormat(this.handle, vertexFormatId, usedUniforms);
+    }
+
+    /**
+     * Creates a shader variant using a vertex format registered with {@link #registerVertexFormat(B4DVertexFormat)}.
+     * Variants share the same shader code and only differ in the specialization constants used by their pipelines.
+     *
+     * @param alphaTestThreshold Fragments with a lower alpha value are discarded. 0 disables the alpha test.
+     * @return The shader id or 0 if the vertex format is not registered.
+     */
+    public long createShader(long vertexFormatId, long usedUniforms, FogMode fogMode, float alphaTestThreshold, boolean lightmapEnable) {
+        return this.createShader(vertexFormatId, usedUniforms, fogMode, alphaTestThreshold, AlphaMode.TEST, lightmapEnable);
+    }
+
+    /**
+     * Creates a shader variant using a vertex format registered with {@link #registerVertexFormat(B4DVertexFormat)}.
+     *
+     * @param alphaMode The preferred alpha handling. {@link AlphaMode#COVERAGE} falls back to {@link AlphaMode#TEST} if the pipeline is not multisampled.
+     * @return The shader id or 0 if the vertex format is not registered.
+     */
+    public long createShader(long vertexFormatId, long usedUniforms, FogMode fogMode, float alphaTestThreshold, AlphaMode alphaMode, boolean lightmapEnable) {
+        return this.createShader(vertexFormatId, usedUniforms, fogMode, alphaTestThreshold, alphaMode, lightmapEnable, PolygonMode.FILL, false);
+    }
+
+    /**
+     * Creates a shader variant using a vertex format registered with {@link #registerVertexFormat(B4DVertexFormat)}.
+     *
+     * @param polygonMode The polygon mode used to rasterize triangles.
+     * @param primitiveRestart If set a special index value restarts strip and fan primitives. Ignored for list topologies.
+     * @return The shader id or 0 if the vertex format is not registered.
+     */
+    public long createShader(long vertexFormatId, long usedUniforms, FogMode fogMode, float alphaTestThreshold, AlphaMode alphaMode, boolean lightmapEnable, PolygonMode polygonMode, boolean primitiveRestart) {
+        return this.createShader(vertexFormatId, usedUniforms, fogMode, alphaTestThreshold, alphaMode, lightmapEnable, polygonMode, primitiveRestart, false, -1);
+    }
+
+    /**
+     * Creates a shader variant using a vertex format registered with {@link #registerVertexFormat(B4DVertexFormat)}.
+     *
+     * @param emissive If set the material is rendered at full brightness without lightmap darkening. Draws using
+     *                 emissive shaders should be submitted to the builtin emissive render layer.
+     * @param emissiveImage The texture slot of a mask selecting the emissive parts of the material using its alpha or
+     *                      -1 if the whole material is emissive.
+     * @return The shader id or 0 if the vertex format is not registered.
+     */
+    public long createShader(long vertexFormatId, long usedUniforms, FogMode fogMode, float alphaTestThreshold, AlphaMode alphaMode, boolean lightmapEnable, PolygonMode polygonMode, boolean primitiveRestart, boolean emissive, int emissiveImage) {
+        return this.createShader(vertexFormatId, usedUniforms, fogMode, alphaTestThreshold, alphaMode, lightmapEnable, polygonMode, primitiveRestart, emissive, emissiveImage, false);
+    }
+
+    /**
+     * Creates a shader variant using a vertex format registered with {@link #registerVertexFormat(B4DVertexFormat)}.
+     *
+     * @param itemAnimation If set the vertex shader applies the bob and spin animation of dropped items using the
+     *                      parameters set by {@link B4DUniformData#setItemAnimation(float, float)}. The used uniforms
+     *                      must include {@link B4DUniform#GAME_TIME} and {@link B4DUniform#ITEM_ANIMATION}.
+     * @return The shader id or 0 if the vertex format is not registered.
+     */
+    public long createShader(long vertexFormatId, long usedUniforms, FogMode fogMode, float alphaTestThreshold, AlphaMode alphaMode, boolean lightmapEnable, PolygonMode polygonMode, boolean primitiveRestart, boolean emissive, int emissiveImage, boolean itemAnimation) {
+        return Natives.b4dCreateShaderSpecialized(this.handle, vertexFormatId, usedUniforms, fogMode.raw, alphaTestThreshold, alphaMode.raw, lightmapEnable, polygonMode.raw, primitiveRestart, emissive, emissiveImage, itemAnimation);
+    }
+
     public void destroyShader(long shaderId) {
         Natives.b4dDestroyShader(this.handle, shaderId);
     }
 
+    /**
+     * Validates and registers a vertex format. The returned id stays valid until the format is unregistered.
+     *
+     * @return The vertex format id or 0 if the format is not supported by the device.
+     */
+    /**
+     * Registers a render layer. The vanilla layers solid, cutout_mipped, cutout, translucent and tripwire are always
+     * registered and can be looked up using {@link #findRenderLayer(String)}.
+     *
+     * @param depthWriteEnable If false depth writes are disabled for all draws of the layer.
+     * @param alphaCutout The alpha test threshold expected by the shaders of the layer. 0 disables the alpha test.
+     * @return The layer id or 0 if a layer with the same name already exists.
+     */
+    public long registerRenderLayer(String name, boolean depthWriteEnable, boolean translucent, float alphaCutout, boolean mipmap, boolean affectsCrumbling) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            return Natives.b4dRegisterRenderLayer(this.handle, allocateString(name, scope), depthWriteEnable, translucent, alphaCutout, mipmap, affectsCrumbling);
+        }
+    }
+
+    public void unregisterRenderLayer(long layerId) {
+        Natives.b4dUnregisterRenderLayer(this.handle, layerId);
+    }
+
+    /**
+     * @return The id of the render layer with the name or 0 if no such layer is registered.
+     */
+    public long findRenderLayer(String name) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            return Natives.b4dFindRenderLayer(this.handle, allocateString(name, scope));
+        }
+    }
+
+    /**
+     * Replaces the order in which render layers are drawn within a frame.
+     *
+     * @param order Must contain every registered layer and every insertion point exactly once.
+     * @return False if the order is invalid.
+     */
+    public boolean setRenderOrder(RenderOrderEntry... order) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment layerIds = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * order.length, scope);
+            MemorySegment points = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * order.length, scope);
+            for (int i = 0; i < order.length; i++) {
+                layerIds.setAtIndex(ValueLayout.JAVA_LONG, i, order[i].layerId());
+                points.setAtIndex(ValueLayout.JAVA_INT, i, order[i].point() != null ? order[i].point().raw : 0);
+            }
+            return Natives.b4dSetRenderOrder(this.handle, layerIds.address(), points.address(), order.length);
+        }
+    }
+
+    /**
+     * Moves a render layer to an insertion point. Layers inserted at the same point are drawn in insertion order.
+     *
+     * @return False if the layer is not registered.
+     */
+    public boolean insertRenderLayer(long layerId, InsertionPoint point) {
+        return Natives.b4dInsertRenderLayer(this.handle, layerId, point.raw);
+    }
+
+    /**
+     * Enables or disables a render layer. Draws submitted to a disabled layer are skipped.
+     *
+     * @return False if the layer is not registered.
+     */
+    public boolean setLayerEnabled(long layerId, boolean enabled) {
+        return Natives.b4dSetLayerEnabled(this.handle, layerId, enabled);
+    }
+
+    /**
+     * Disables all render layers except the specified one. Passing 0 enables all layers again.
+     */
+    public void isolateLayer(long layerId) {
+        Natives.b4dIsolateLayer(this.handle, layerId);
+    }
+
+    /**
+     * Marks all global images as stale. Stale images remain valid and keep being sampled until they are replaced in
+     * place using {@link GlobalImage#update(graphics.kiln.blaze4d.core.types.B4DImageData)}, so resource packs can be
+     * switched without a black frame. Images whose size or format changed must be recreated.
+     *
+     * @return The new resource generation.
+     */
+    public long bumpResourceGeneration() {
+        return Natives.b4dBumpResourceGeneration(this.handle);
+    }
+
+    /**
+     * Keeps all deduplicated meshes alive until {@link MeshRetention#release()} is called. Meshes recreated with
+     * identical data in the meantime are not uploaded again. Intended to be called before a resource reload.
+     */
+    public MeshRetention retainCachedMeshes() {
+        return new MeshRetention(Natives.b4dRetainCachedMeshes(this.handle));
+    }
+
+    /**
+     * Writes the render layer setup to the file and saves the pipeline cache.
+     *
+     * @return False if the state could not be written.
+     */
+    public boolean saveState(String path) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            return Natives.b4dSaveState(this.handle, allocateString(path, scope));
+        }
+    }
+
+    /**
+     * Restores the render layer setup written by {@link #saveState(String)}. Should be called before any render
+     * layers are registered.
+     *
+     * @return False if the state could not be restored.
+     */
+    public boolean loadState(String path) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            return Natives.b4dLoadState(this.handle, allocateString(path, scope));
+        }
+    }
+
+    /**
+     * Returns the statistics of every render layer drawn to in the last ended frame.
+     */
+    public LayerStats[] getFrameLayerStats() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            int count = Natives.b4dGetFrameLayerStats(this.handle, MemoryAddress.NULL, 0);
+            MemorySegment stats = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 5 * Math.max(count, 1), scope);
+            count = Math.min(count, Natives.b4dGetFrameLayerStats(this.handle, stats.address(), count));
+
+            long[] values = stats.toArray(ValueLayout.JAVA_LONG);
+            LayerStats[] result = new LayerStats[count];
+            for (int i = 0; i < count; i++) {
+                result[i] = new LayerStats(values[i * 5], values[i * 5 + 1], values[i * 5 + 2], values[i * 5 + 3], values[i * 5 + 4]);
+            }
+            return result;
+        }
+    }
+
+    /**
+     * Limits the number of immediate meshes uploaded per frame. Once the limit is reached further uploads are
+     * dropped unless they are submitted with {@link Frame#REQUIRED_DRAW_PRIORITY}. A limit of 0 disables the limit.
+     */
+    public void setImmediateUploadLimit(int limit) {
+        Natives.b4dSetImmediateUploadLimit(this.handle, limit);
+    }
+
+    /**
+     * Limits the number of draws per frame in a render layer. If the layer exceeds the limit the draws with the
+     * lowest priority are dropped. Particles can be limited using the builtin {@code particles} layer.
+     */
+    public void setLayerDrawLimit(long layerId, int limit) {
+        Natives.b4dSetLayerDrawLimit(this.handle, layerId, true, limit);
+    }
+
+    public void clearLayerDrawLimit(long layerId) {
+        Natives.b4dSetLayerDrawLimit(this.handle, layerId, false, 0);
+    }
+
+    /**
+     * Returns the work dropped because of the render budget in the last ended frame.
+     */
+    public BudgetReport getFrameBudgetReport() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment report = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * 3, scope);
+            Natives.b4dGetFrameBudgetReport(this.handle, report.address());
+
+            int[] values = report.toArray(ValueLayout.JAVA_INT);
+            return new BudgetReport(values[0], values[1], values[2]);
+        }
+    }
+
+    /**
+     * Returns the statistics of every draw tag used in the last ended frame sorted by descending index count.
+     */
+    public DrawTagStats[] getFrameTagStats() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            int count = Natives.b4dGetFrameTagStats(this.handle, MemoryAddress.NULL, 0);
+            MemorySegment stats = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 3 * Math.max(count, 1), scope);
+            count = Math.min(count, Natives.b4dGetFrameTagStats(this.handle, stats.address(), count));
+
+            long[] values = stats.toArray(ValueLayout.JAVA_LONG);
+            DrawTagStats[] result = new DrawTagStats[count];
+            for (int i = 0; i < count; i++) {
+                result[i] = new DrawTagStats(values[i * 3], values[i * 3 + 1], values[i * 3 + 2]);
+            }
+            return result;
+        }
+    }
+
+    /**
+     * Returns percentiles and stutter counts of the cpu and gpu frame times of recent frames.
+     */
+    public FrameTimeReport getFrameTimeReport() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment report = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 14, scope);
+            Natives.b4dGetFrameTimeReport(this.handle, report.address());
+
+            long[] values = report.toArray(ValueLayout.JAVA_LONG);
+            return new FrameTimeReport(FrameTimeSummary.fromValues(values, 0), FrameTimeSummary.fromValues(values, 7));
+        }
+    }
+
+    /**
+     * Returns the histogram of recent cpu or gpu frame times. Each bucket is 1ms wide and the last
+     * bucket contains all longer frames.
+     */
+    public int[] getFrameTimeHistogram(boolean gpu) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            int count = Natives.b4dGetFrameTimeHistogram(this.handle, gpu, MemoryAddress.NULL, 0);
+            MemorySegment buckets = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * Math.max(count, 1), scope);
+            Natives.b4dGetFrameTimeHistogram(this.handle, gpu, buckets.address(), count);
+
+            int[] result = new int[count];
+            MemorySegment.ofArray(result).copyFrom(buckets.asSlice(0, ValueLayout.JAVA_INT.byteSize() * count));
+            return result;
+        }
+    }
+
+    private static MemoryAddress allocateString(String str, ResourceScope scope) {
+        byte[] bytes = str.getBytes(StandardCharsets.UTF_8);
+        MemorySegment string = MemorySegment.allocateNative(bytes.length + 1, scope);
+        string.copyFrom(MemorySegment.ofArray(bytes));
+        string.set(ValueLayout.JAVA_BYTE, bytes.length, (byte) 0);
+        return string.address();
+    }
+
+    public long registerVertexFormat(B4DVertexFormat vertexFormat) {
+        return Natives.b4dRegisterVertexFormat(this.handle, vertexFormat.getAddress());
+    }
+
+    public void unregisterVertexFormat(long vertexFormatId) {
+        Natives.b4dUnregisterVertexFormat(this.handle, vertexFormatId);
+    }
+
     public GlobalMesh createGlobalMesh(B4DMeshData meshData) {
-        return new GlobalMesh(Natives.b4dCreateGlobalMesh(this.handle, meshData.getAddress()));
+        return new GlobalMesh(this.deviceGeneration, Natives.b4dCreateGlobalMesh(this.handle, meshData.getAddress()));
+    }
+
+    /**
+     * Creates multiple global meshes with a single native call. This is more efficient than calling
+     * {@link #createGlobalMesh(B4DMeshData)} for each mesh.
+     */
+    public GlobalMesh[] createGlobalMeshes(B4DMeshData... meshDatas) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            long dataSize = MeshDataNative.LAYOUT.byteSize();
+            MemorySegment datas = MemorySegment.allocateNative(dataSize * meshDatas.length, MeshDataNative.LAYOUT.byteAlignment(), scope);
+            for (int i = 0; i < meshDatas.length; i++) {
+                meshDatas[i].copyTo(datas.asSlice(dataSize * i, dataSize));
+            }
+
+            MemorySegment handles = MemorySegment.allocateNative(ValueLayout.ADDRESS.byteSize() * meshDatas.length, scope);
+            Natives.b4dCreateGlobalMeshes(this.handle, datas.address(), meshDatas.length, handles.address());
+
+            GlobalMesh[] meshes = new GlobalMesh[meshDatas.length];
+            for (int i = 0; i < meshDatas.length; i++) {
+                meshes[i] = new GlobalMesh(this.deviceGeneration, handles.getAtIndex(ValueLayout.ADDRESS, i));
+            }
+            return meshes;
+        }
+    }
+
+    /**
+     * Closes multiple global meshes with a single native call. The meshes must not be used afterwards.
+     */
+    public void destroyGlobalMeshes(GlobalMesh... meshes) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment handles = MemorySegment.allocateNative(ValueLayout.ADDRESS.byteSize() * meshes.length, scope);
+            for (int i = 0; i < meshes.length; i++) {
+                handles.setAtIndex(ValueLayout.ADDRESS, i, meshes[i].getHandle());
+            }
+            Natives.b4dDestroyGlobalMeshes(handles.address(), meshes.length);
+        }
+    }
+
+    /**
+     * Creates a global mesh or returns a new handle to an existing mesh with identical data. Each returned
+     * {@link GlobalMesh} must be closed separately. The mesh is only destroyed once all handles are closed.
+     */
+    public GlobalMesh createGlobalMeshDeduplicated(B4DMeshData meshData) {
+        return new GlobalMesh(this.deviceGeneration, Natives.b4dCreateGlobalMeshDeduplicated(this.handle, meshData.getAddress()));
+    }
+
+    /**
+     * Creates a cache for map item textures. The cache must be recreated if the device is recreated.
+     */
+    public MapTextureCache createMapTextureCache() {
+        return new MapTextureCache(this.deviceGeneration, Natives.b4dCreateMapTextureCache(this.handle));
+    }
+
+    /**
+     * Creates the static unit column mesh used by {@link Frame#drawBeams}. Shaders with the same vertex format share
+     * the same mesh.
+     *
+     * @return The mesh or null if the vertex format of the shader cannot be used to draw beams.
+     */
+    public GlobalMesh createBeamMesh(long shaderId) {
+        MemoryAddress mesh = Natives.b4dCreateBeamMesh(this.handle, shaderId);
+        if(mesh.toRawLongValue() == 0L) {
+            return null;
+        } else {
+            return new GlobalMesh(this.deviceGeneration, mesh);
+        }
+    }
+
+    /**
+     * Creates a new image by compositing tinted layers on the gpu, for example banner patterns or skin overlays. The
+     * first layer is the base layer replacing the content of the image, all other layers are alpha blended on top of
+     * it. The layer images must not be array images. The composite is rendered before the image is first sampled.
+     *
+     * @param images The source image of each layer.
+     * @param uvRects 4 floats per layer. The minimum u, v and maximum u, v of the region of the source image which is
+     *                stretched over the whole composite. If null the whole source images are used.
+     * @param tints The color multiplied with each layer packed as 0xAARRGGBB.
+     * @return The image or null if the device cannot render to images of the format.
+     */
+    public GlobalImage createCompositeImage(int width, int height, B4DFormat format, GlobalImage[] images, float[] uvRects, int[] tints) {
+        int count = images.length;
+        if (count == 0) {
+            throw new IllegalArgumentException("At least a base layer is required");
+        }
+        if (tints.length != count || (uvRects != null && uvRects.length != count * 4)) {
+            throw new IllegalArgumentException("Expected 4 uv floats and a tint per layer");
+        }
+        long floatSize = ValueLayout.JAVA_FLOAT.byteSize();
+        // The image handle, uv rect and tint padded to the alignment of the handle
+        long stride = 32;
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment data = MemorySegment.allocateNative(stride * count, ValueLayout.ADDRESS.byteAlignment(), scope);
+            for (int i = 0; i < count; i++) {
+                MemorySegment layer = data.asSlice(stride * i, stride);
+                long offset = ValueLayout.ADDRESS.byteSize();
+                layer.set(ValueLayout.ADDRESS, 0, images[i].getHandle());
+                if (uvRects != null) {
+                    layer.asSlice(offset, floatSize * 4).copyFrom(MemorySegment.ofArray(uvRects).asSlice(floatSize * 4 * i, floatSize * 4));
+                } else {
+                    layer.set(ValueLayout.JAVA_FLOAT, offset, 0.0f);
+                    layer.set(ValueLayout.JAVA_FLOAT, offset + floatSize, 0.0f);
+                    layer.set(ValueLayout.JAVA_FLOAT, offset + floatSize * 2, 1.0f);
+                    layer.set(ValueLayout.JAVA_FLOAT, offset + floatSize * 3, 1.0f);
+                }
+                layer.set(ValueLayout.JAVA_INT, offset + floatSize * 4, tints[i]);
+            }
+
+            MemoryAddress image = Natives.b4dCreateCompositeImage(this.handle, width, height, format.getValue(), data.address(), count);
+            if(image.toRawLongValue() == 0L) {
+                return null;
+            } else {
+                return new GlobalImage(this.deviceGeneration, image);
+            }
+        }
+    }
+
+    /**
+     * Creates a global mesh after reordering its data for better rendering performance. The optimization runs on the
+     * calling thread so this should be called from a worker thread.
+     *
+     * @param positionFormat The format of the vertex position or {@code null} if no position data should be used.
+     */
+    public GlobalMesh createGlobalMeshOptimized(B4DMeshData meshData, int positionOffset, B4DFormat positionFormat) {
+        int format = positionFormat != null ? positionFormat.getValue() : 0;
+        return new GlobalMesh(this.deviceGeneration, Natives.b4dCreateGlobalMeshOptimized(this.handle, meshData.getAddress(), positionOffset, format));
+    }
+
+    /**
+     * Optimizes multiple meshes in parallel using the job threads before creating them. All meshes must store the
+     * position at the same offset and format. The calling thread participates in the optimization and blocks until all
+     * meshes are created.
+     *
+     * @param positionFormat The format of the vertex position or {@code null} if no position data should be used.
+     */
+    public GlobalMesh[] createGlobalMeshesOptimized(int positionOffset, B4DFormat positionFormat, B4DMeshData... meshDatas) {
+        int format = positionFormat != null ? positionFormat.getValue() : 0;
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            long dataSize = MeshDataNative.LAYOUT.byteSize();
+            MemorySegment datas = MemorySegment.allocateNative(dataSize * meshDatas.length, MeshDataNative.LAYOUT.byteAlignment(), scope);
+            for (int i = 0; i < meshDatas.length; i++) {
+                meshDatas[i].copyTo(datas.asSlice(dataSize * i, dataSize));
+            }
+
+            MemorySegment handles = MemorySegment.allocateNative(ValueLayout.ADDRESS.byteSize() * meshDatas.length, scope);
+            Natives.b4dCreateGlobalMeshesOptimized(this.handle, datas.address(), meshDatas.length, positionOffset, format, handles.address());
+
+            GlobalMesh[] meshes = new GlobalMesh[meshDatas.length];
+            for (int i = 0; i < meshDatas.length; i++) {
+                meshes[i] = new GlobalMesh(this.deviceGeneration, handles.getAtIndex(ValueLayout.ADDRESS, i));
+            }
+            return meshes;
+        }
     }
 
     public GlobalImage createGlobalImage(int width, int height, B4DFormat format) {
-        return new GlobalImage(Natives.b4dCreateGlobalImage(this.handle, width, height, format.getValue()));
+        return new GlobalImage(this.deviceGeneration, Natives.b4dCreateGlobalImage(this.handle, width, height, format.getValue()));
+    }
+
+    /**
+     * Creates a 2d array image with explicitly addressed layers.
+     *
+     * @param width The width of a single layer.
+     * @param height The height of a single layer.
+     */
+    public GlobalImage createGlobalImageArray(int width, int height, int mipLevels, int layerCount, B4DFormat format) {
+        return new GlobalImage(this.deviceGeneration, Natives.b4dCreateGlobalImageArray(this.handle, width, height, mipLevels, layerCount, 0, 0, format.getValue()));
+    }
+
+    /**
+     * Creates an array image which is used like an atlas of {@code columns} x {@code rows} tiles. Uploads and texture
+     * coordinates address the full atlas however each tile is stored in its own layer so mipmaps do not bleed between
+     * neighbouring tiles.
+     *
+     * @param tileWidth The width of a single tile.
+     * @param tileHeight The height of a single tile.
+     */
+    public GlobalImage createGlobalImageAtlas(int tileWidth, int tileHeight, int columns, int rows, int mipLevels, B4DFormat format) {
+        return new GlobalImage(this.deviceGeneration, Natives.b4dCreateGlobalImageArray(this.handle, tileWidth, tileHeight, mipLevels, 0, columns, rows, format.getValue()));
+    }
+
+    /**
+     * Creates a partially resident atlas image. Only the regions which are written or touched using
+     * {@link GlobalImage#touchRegion} use memory. Returns null if the device does not support sparse images of the
+     * format.
+     *
+     * @param tileWidth The width of a single tile.
+     * @param tileHeight The height of a single tile.
+     */
+    public GlobalImage createGlobalImageSparseAtlas(int tileWidth, int tileHeight, int columns, int rows, int mipLevels, B4DFormat format) {
+        MemoryAddress image = Natives.b4dCreateGlobalImageSparse(this.handle, tileWidth, tileHeight, mipLevels, 0, columns, rows, format.getValue());
+        if(image.toRawLongValue() == 0L) {
+            return null;
+        } else {
+            return new GlobalImage(this.deviceGeneration, image);
+        }
     }
 
     public Frame startFrame(int windowWidth, int windowHeight) {
@@ -51,9 +1319,458 @@ public class Blaze4DCore implements AutoCloseable {
         }
     }
 
+    /**
+     * Starts a new frame using a size in logical units. The size in pixels is calculated by
+     * multiplying the logical size with the scale factor.
+     */
+    public Frame startFrameScaled(int logicalWidth, int logicalHeight, float scaleFactor) {
+        MemoryAddress frame = Natives.b4dStartFrameScaled(this.handle, logicalWidth, logicalHeight, scaleFactor);
+        if(frame.toRawLongValue() == 0L) {
+            return null;
+        } else {
+            return new Frame(frame);
+        }
+    }
+
+    /**
+     * Starts an environment probe capture at the specified position with faces of resolution by resolution pixels.
+     * The resolution must be a power of two. Each frame the scene must be rendered into the faces returned by
+     * {@link ProbeCapture#nextFaces()} until all faces have been started, after which {@link ProbeCapture#finish()}
+     * must be called. The callback receives the prefiltered probe image which can be used with
+     * {@link Frame#updateEnvironmentProbe(int, GlobalImage, long)}.
+     */
+    public ProbeCapture captureProbe(float x, float y, float z, int resolution, int facesPerFrame, ProbeCapture.ProbeCallback callback) {
+        try {
+            ResourceScope scope = ResourceScope.newImplicitScope();
+            MethodHandle target = MethodHandles.lookup().findStatic(Blaze4DCore.class, "onProbe",
+                    MethodType.methodType(Void.TYPE, ProbeCapture.ProbeCallback.class, ResourceScope.class, DeviceGeneration.class, MemoryAddress.class, MemoryAddress.class))
+                    .bindTo(callback).bindTo(scope).bindTo(this.deviceGeneration);
+
+            NativeSymbol symbol = Natives.linker.upcallStub(target,
+                    FunctionDescriptor.ofVoid(ValueLayout.ADDRESS, ValueLayout.ADDRESS),
+                    scope
+            );
+            PROBE_CALLBACK_SCOPES.add(scope);
+            return new ProbeCapture(Natives.b4dCaptureProbe(this.handle, x, y, z, resolution, facesPerFrame, symbol));
+        } catch (NoSuchMethodException | IllegalAccessException e) {
+            throw new RuntimeException("Failed to create probe callback", e);
+        }
+    }
+
+    private static void onProbe(ProbeCapture.ProbeCallback callback, ResourceScope scope, DeviceGeneration generation, MemoryAddress image, MemoryAddress userData) {
+        try {
+            callback.onProbe(new GlobalImage(generation, image));
+        } catch (Throwable e) {
+            LOGGER.error("Probe callback threw exception", e);
+        } finally {
+            PROBE_CALLBACK_SCOPES.remove(scope);
+        }
+    }
+
+    /**
+     * Starts a panorama capture with faces of resolution by resolution pixels. The callback receives
+     * a 2 * resolution by resolution equirectangular image once all faces have been rendered and
+     * {@link PanoramaCapture#finish()} has been called.
+     */
+    public PanoramaCapture capturePanorama(int resolution, PanoramaCapture.PanoramaCallback callback) {
+        try {
+            ResourceScope scope = ResourceScope.newImplicitScope();
+            MethodHandle target = MethodHandles.lookup().findStatic(Blaze4DCore.class, "onPanorama",
+                    MethodType.methodType(Void.TYPE, PanoramaCapture.PanoramaCallback.class, ResourceScope.class, Integer.TYPE, Integer.TYPE, MemoryAddress.class, MemoryAddress.class))
+                    .bindTo(callback).bindTo(scope);
+
+            NativeSymbol symbol = Natives.linker.upcallStub(target,
+                    FunctionDescriptor.ofVoid(ValueLayout.JAVA_INT, ValueLayout.JAVA_INT, ValueLayout.ADDRESS, ValueLayout.ADDRESS),
+                    scope
+            );
+            PANORAMA_CALLBACK_SCOPES.add(scope);
+            return new PanoramaCapture(Natives.b4dCapturePanorama(this.handle, resolution, symbol));
+        } catch (NoSuchMethodException | IllegalAccessException e) {
+            throw new RuntimeException("Failed to create panorama callback", e);
+        }
+    }
+
+    private static void onPanorama(PanoramaCapture.PanoramaCallback callback, ResourceScope scope, int width, int height, MemoryAddress data, MemoryAddress userData) {
+        try {
+            MemorySegment segment = MemorySegment.ofAddress(data, (long) width * height * 4, ResourceScope.globalScope());
+            callback.onPanorama(width, height, segment.toArray(ValueLayout.JAVA_BYTE));
+        } catch (Throwable e) {
+            LOGGER.error("Panorama callback threw exception", e);
+        } finally {
+            PANORAMA_CALLBACK_SCOPES.remove(scope);
+        }
+    }
+
     @Override
     public void close() throws Exception {
         Natives.b4dDestroy(this.handle);
+        this.deviceLostCallbackScope.close();
+        if (this.memoryPressureCallbackScope != null) {
+            this.memoryPressureCallbackScope.close();
+        }
+        if (this.meshEvictionCallbackScope != null) {
+            this.meshEvictionCallbackScope.close();
+        }
+        if (this.eventLogCallbackScope != null) {
+            this.eventLogCallbackScope.close();
+        }
+        if (this.jobExecutorScope != null) {
+            this.jobExecutorScope.close();
+        }
+    }
+
+    public enum JobKind {
+        CULLING(0),
+        SORTING(1),
+        MESH_OPTIMIZATION(2),
+        MESH_DECOMPRESSION(3);
+
+        final int raw;
+
+        JobKind(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    /**
+     * Timing statistics of all job batches of one {@link JobKind}. All times are in nanoseconds.
+     */
+    public record JobStats(long batchCount, long taskCount, long totalTaskNanos, long maxTaskNanos, long totalBatchNanos) {
+        /**
+         * The average number of threads working on a batch.
+         */
+        public double averageParallelism() {
+            return this.totalBatchNanos == 0 ? 1.0 : (double) this.totalTaskNanos / this.totalBatchNanos;
+        }
+    }
+
+    /**
+     * Tracks whether objects were created before the device was last lost. Every device recreation starts a new
+     * generation.
+     */
+    static final class DeviceGeneration {
+        private volatile boolean lost = false;
+
+        void markLost() {
+            this.lost = true;
+        }
+
+        boolean isLost() {
+            return this.lost;
+        }
+    }
+
+    public enum DeviceLostReason {
+        DEVICE_LOST(0),
+        SURFACE_LOST(1),
+        ADAPTER_CHANGED(2);
+
+        final int raw;
+
+        DeviceLostReason(int raw) {
+            this.raw = raw;
+        }
+
+        static DeviceLostReason fromValue(int value) {
+            for (DeviceLostReason reason : values()) {
+                if (reason.raw == value) {
+                    return reason;
+                }
+            }
+            throw new RuntimeException("Invalid device lost reason " + value);
+        }
+    }
+
+    /**
+     * A display mode of a monitor.
+     *
+     * @param refreshRate The refresh rate in hz.
+     */
+    public record DisplayMode(int width, int height, int refreshRate, int bitDepth) {
+    }
+
+    public record LayerStats(long layerId, long drawCount, long indexCount, long skippedDrawCount, long culledDrawCount) {
+    }
+
+    public record BudgetReport(int droppedUploads, int droppedUploadDraws, int culledDraws) {
+    }
+
+    public record DrawTagStats(long tag, long drawCount, long indexCount) {
+    }
+
+    /**
+     * Frame time statistics of recent frames. All times are in microseconds. If no samples are
+     * available all values are 0.
+     */
+    public record FrameTimeSummary(long sampleCount, long stutterCount, long averageUs, long p50Us, long p95Us, long p99Us, long maxUs) {
+        static FrameTimeSummary fromValues(long[] values, int offset) {
+            return new FrameTimeSummary(values[offset], values[offset + 1], values[offset + 2], values[offset + 3], values[offset + 4], values[offset + 5], values[offset + 6]);
+        }
+    }
+
+    public record FrameTimeReport(FrameTimeSummary cpu, FrameTimeSummary gpu) {
+    }
+
+    public enum InsertionPoint {
+        AFTER_OPAQUE(0),
+        BEFORE_TRANSLUCENT(1),
+        AFTER_GUI(2),
+        AFTER_EMISSIVE(3);
+
+        final int raw;
+
+        InsertionPoint(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    /**
+     * An entry of the render order. Either a layer id or an insertion point.
+     */
+    public record RenderOrderEntry(long layerId, InsertionPoint point) {
+        public static RenderOrderEntry layer(long layerId) {
+            return new RenderOrderEntry(layerId, null);
+        }
+
+        public static RenderOrderEntry point(InsertionPoint point) {
+            return new RenderOrderEntry(0, point);
+        }
+    }
+
+    public enum FogMode {
+        DISABLED(0),
+        SPHERICAL(1),
+        CYLINDRICAL(2);
+
+        final int raw;
+
+        FogMode(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum AlphaMode {
+        TEST(0),
+        COVERAGE(1);
+
+        final int raw;
+
+        AlphaMode(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum SubmitMode {
+        /**
+         * Commands are handed to the worker while they are recorded.
+         */
+        STREAMING(0),
+        /**
+         * Commands are collected and handed to the worker in a single batch when the frame is closed, which
+         * returns immediately. Reduces synchronization overhead for draw heavy frames.
+         */
+        DEFERRED(1);
+
+        final int raw;
+
+        SubmitMode(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum LatencyMode {
+        DEFAULT(0),
+        LOW_LATENCY(1),
+        /**
+         * Pacing for variable refresh rate displays. See {@link DisplayTiming#variableRefresh()}.
+         */
+        VARIABLE_REFRESH(2);
+
+        final int raw;
+
+        LatencyMode(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    /**
+     * @param refreshDurationNs The duration of a refresh cycle of the display.
+     * @param presentMarginNs How early the last present was processed or 0 if unknown.
+     * @param variableRefresh True if the display follows the frame rate or null if not enough
+     *                        frames have been displayed yet.
+     */
+    public record DisplayTiming(long refreshDurationNs, long presentMarginNs, Boolean variableRefresh) {
+    }
+
+    public enum FeatureTier {
+        VULKAN_1_1(0),
+        VULKAN_1_2(1),
+        VULKAN_1_3(2);
+
+        final int raw;
+
+        FeatureTier(int raw) {
+            this.raw = raw;
+        }
+
+        static FeatureTier fromValue(int value) {
+            for (FeatureTier tier : values()) {
+                if (tier.raw == value) {
+                    return tier;
+                }
+            }
+            throw new RuntimeException("Invalid feature tier " + value);
+        }
+    }
+
+    public enum FeatureSource {
+        UNSUPPORTED(0),
+        EXTENSION(1),
+        CORE(2);
+
+        final int raw;
+
+        FeatureSource(int raw) {
+            this.raw = raw;
+        }
+
+        static FeatureSource fromValue(int value) {
+            for (FeatureSource source : values()) {
+                if (source.raw == value) {
+                    return source;
+                }
+            }
+            throw new RuntimeException("Invalid feature source " + value);
+        }
+    }
+
+    /**
+     * @param synchronization2 If unsupported barriers and submissions use legacy synchronization.
+     */
+    public record DeviceInfo(int apiMajor, int apiMinor, int apiPatch, FeatureTier tier, FeatureSource dynamicRendering, FeatureSource timelineSemaphore, FeatureSource synchronization2) {
+    }
+
+    public enum LogSubsystem {
+        OBJECT_MANAGER(0),
+        ALLOCATOR(1),
+        EMULATOR(2),
+        SWAPCHAIN(3),
+        C_API(4);
+
+        final int raw;
+
+        LogSubsystem(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum LogLevel {
+        /**
+         * Uses the default level of all native logs.
+         */
+        DEFAULT(-1),
+        TRACE(0),
+        DEBUG(1),
+        INFO(2),
+        WARN(3),
+        ERROR(4),
+        OFF(5);
+
+        final int raw;
+
+        LogLevel(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum HostMemoryScope {
+        COMMAND(0),
+        OBJECT(1),
+        CACHE(2),
+        DEVICE(3),
+        INSTANCE(4);
+
+        final int raw;
+
+        HostMemoryScope(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    /**
+     * @param internalBytes Memory allocated by the driver itself and only reported to the tracker.
+     */
+    public record HostMemoryStats(long currentBytes, long peakBytes, long liveAllocations, long totalAllocations, long internalBytes) {
+    }
+
+    public enum ColorGradingPreset {
+        NONE(0),
+        GRAYSCALE(1),
+        SEPIA(2),
+        NIGHT_VISION(3);
+
+        final int raw;
+
+        ColorGradingPreset(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum AccessibilityFilter {
+        NONE(0),
+        SIMULATE_PROTANOPIA(1),
+        SIMULATE_DEUTERANOPIA(2),
+        SIMULATE_TRITANOPIA(3),
+        /**
+         * Daltonization shifting the colors lost with protanopia into distinguishable channels.
+         */
+        CORRECT_PROTANOPIA(4),
+        CORRECT_DEUTERANOPIA(5),
+        CORRECT_TRITANOPIA(6);
+
+        final int raw;
+
+        AccessibilityFilter(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum PolygonMode {
+        FILL(0),
+        LINE(1),
+        POINT(2);
+
+        final int raw;
+
+        PolygonMode(int raw) {
+            this.raw = raw;
+        }
+    }
+
+    public enum MemoryPressure {
+        NORMAL(0),
+        ELEVATED(1),
+        CRITICAL(2);
+
+        final int raw;
+
+        MemoryPressure(int raw) {
+            this.raw = raw;
+        }
+
+        static MemoryPressure fromValue(int value) {
+            for (MemoryPressure pressure : values()) {
+                if (pressure.raw == value) {
+                    return pressure;
+                }
+            }
+            throw new RuntimeException("Invalid memory pressure " + value);
+        }
+    }
+
+    @FunctionalInterface
+    public interface MemoryPressureCallback {
+        void onMemoryPressure(MemoryPressure pressure, long usage, long budget);
     }
 
     public enum DebugMode {
@@ -74,5 +1791,9 @@ public class Blaze4DCore implements AutoCloseable {
         DebugMode(int raw) {
             this.raw = raw;
         }
+
+        public int getValue() {
+            return this.raw;
+        }
     }
 }
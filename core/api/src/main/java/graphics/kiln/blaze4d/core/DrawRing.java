@@ -0,0 +1,196 @@
+package graphics.kiln.blaze4d.core;
+
+import graphics.kiln.blaze4d.core.natives.Natives;
+import graphics.kiln.blaze4d.core.types.B4DMeshData;
+import jdk.incubator.foreign.*;
+
+import java.lang.invoke.VarHandle;
+import java.nio.ByteOrder;
+
+/**
+ * A ring buffer in shared memory used to submit draws without a native call per draw. Commands written to the ring
+ * are recorded into a frame when {@link Frame#consumeDrawRing(DrawRing)} is called. The ring must be consumed
+ * before it fills up, usually once per frame.
+ *
+ * Only one thread may write to a ring at a time.
+ */
+public class DrawRing implements AutoCloseable {
+
+    private static final int HEADER_SIZE = 16;
+    private static final int RECORD_HEADER_SIZE = 8;
+
+    private static final int OP_WRAP = 0;
+    private static final int OP_DRAW_GLOBAL = 1;
+    private static final int OP_DRAW_IMMEDIATE = 2;
+    private static final int OP_SET_DRAW_TAG = 3;
+    private static final int OP_SET_RENDER_LAYER = 4;
+    private static final int OP_SET_DRAW_PRIORITY = 5;
+
+    private static final ValueLayout.OfInt INT = ValueLayout.JAVA_INT.withOrder(ByteOrder.LITTLE_ENDIAN);
+    private static final ValueLayout.OfLong LONG = ValueLayout.JAVA_LONG.withOrder(ByteOrder.LITTLE_ENDIAN);
+
+    private static final MemoryLayout HEADER_LAYOUT = MemoryLayout.structLayout(
+            ValueLayout.JAVA_INT.withName("write_offset"),
+            ValueLayout.JAVA_INT.withName("read_offset"),
+            MemoryLayout.paddingLayout(64)
+    );
+    private static final VarHandle WRITE_OFFSET_HANDLE = HEADER_LAYOUT.varHandle(MemoryLayout.PathElement.groupElement("write_offset"));
+    private static final VarHandle READ_OFFSET_HANDLE = HEADER_LAYOUT.varHandle(MemoryLayout.PathElement.groupElement("read_offset"));
+
+    private final MemoryAddress handle;
+    private final MemorySegment memory;
+    private final MemorySegment data;
+    private final ResourceScope ownedScope;
+
+    private int writeOffset = 0;
+
+    /**
+     * Creates a ring in host owned memory, for example a mapped file or a direct byte buffer. The segment must be
+     * 8 byte aligned, at least 272 bytes large and stay valid until the ring is closed.
+     */
+    public DrawRing(MemorySegment memory) {
+        this(memory, null);
+    }
+
+    private DrawRing(MemorySegment memory, ResourceScope ownedScope) {
+        this.memory = memory;
+        this.data = memory.asSlice(HEADER_SIZE);
+        this.ownedScope = ownedScope;
+        this.handle = Natives.b4dCreateDrawRing(memory.address(), memory.byteSize());
+    }
+
+    /**
+     * Allocates a ring in native memory which is freed when the ring is closed.
+     *
+     * @param dataSize The number of bytes available for commands. Must be a multiple of 8 and at least 256.
+     */
+    public static DrawRing allocate(long dataSize) {
+        ResourceScope scope = ResourceScope.newSharedScope();
+        return new DrawRing(MemorySegment.allocateNative(HEADER_SIZE + dataSize, 8, scope), scope);
+    }
+
+    public void drawGlobal(GlobalMesh mesh, long shaderId, boolean depthWrite) {
+        long offset = this.begin(OP_DRAW_GLOBAL, 24);
+        this.data.set(LONG, offset, mesh.getHandle().toRawLongValue());
+        this.data.set(LONG, offset + 8, shaderId);
+        this.data.set(INT, offset + 16, depthWrite ? 1 : 0);
+        this.data.set(INT, offset + 20, 0);
+        this.publish();
+    }
+
+    /**
+     * Copies the mesh data into the ring and draws it as an immediate mesh. The data must not be compressed.
+     */
+    public void drawImmediate(B4DMeshData mesh, long shaderId, boolean depthWrite) {
+        if (mesh.getCompression() != B4DMeshData.COMPRESSION_NONE) {
+            throw new IllegalArgumentException("Compressed mesh data cannot be drawn through a draw ring");
+        }
+
+        long vertexLen = mesh.getVertexDataLen();
+        long indexLen = mesh.getIndexDataLen();
+        long offset = this.begin(OP_DRAW_IMMEDIATE, 40 + vertexLen + indexLen);
+        this.data.set(LONG, offset, shaderId);
+        this.data.set(INT, offset + 8, depthWrite ? 1 : 0);
+        this.data.set(INT, offset + 12, mesh.getVertexStride());
+        this.data.set(INT, offset + 16, mesh.getIndexCount());
+        this.data.set(INT, offset + 20, mesh.getIndexTypeRaw());
+        this.data.set(INT, offset + 24, mesh.getPrimitiveTopologyRaw());
+        this.data.set(INT, offset + 28, (int) vertexLen);
+        this.data.set(INT, offset + 32, (int) indexLen);
+        this.data.set(INT, offset + 36, 0);
+
+        this.data.asSlice(offset + 40, vertexLen).copyFrom(MemorySegment.ofAddress(mesh.getVertexDataPtr(), vertexLen, ResourceScope.globalScope()));
+        this.data.asSlice(offset + 40 + vertexLen, indexLen).copyFrom(MemorySegment.ofAddress(mesh.getIndexDataPtr(), indexLen, ResourceScope.globalScope()));
+        this.publish();
+    }
+
+    public void setDrawTag(long tag) {
+        this.writeDrawTag(true, tag);
+    }
+
+    public void clearDrawTag() {
+        this.writeDrawTag(false, 0);
+    }
+
+    private void writeDrawTag(boolean enable, long tag) {
+        long offset = this.begin(OP_SET_DRAW_TAG, 16);
+        this.data.set(INT, offset, enable ? 1 : 0);
+        this.data.set(INT, offset + 4, 0);
+        this.data.set(LONG, offset + 8, tag);
+        this.publish();
+    }
+
+    /**
+     * Selects the render layer of all following draws. Passing 0 draws without a layer.
+     */
+    public void setRenderLayer(long layerId) {
+        long offset = this.begin(OP_SET_RENDER_LAYER, 8);
+        this.data.set(LONG, offset, layerId);
+        this.publish();
+    }
+
+    /**
+     * See {@link Frame#setDrawPriority(int)}.
+     */
+    public void setDrawPriority(int priority) {
+        long offset = this.begin(OP_SET_DRAW_PRIORITY, 8);
+        this.data.set(INT, offset, priority);
+        this.data.set(INT, offset + 4, 0);
+        this.publish();
+    }
+
+    MemoryAddress getHandle() {
+        return this.handle;
+    }
+
+    /**
+     * Reserves space for a record and writes its header. Returns the offset of the record payload.
+     */
+    private long begin(int opcode, long payloadSize) {
+        long capacity = this.data.byteSize();
+        long length = (RECORD_HEADER_SIZE + payloadSize + 7) & ~7L;
+        if (length + RECORD_HEADER_SIZE > capacity) {
+            throw new IllegalArgumentException("Record of " + length + " bytes does not fit into a draw ring of " + capacity + " bytes");
+        }
+
+        long readOffset = (int) READ_OFFSET_HANDLE.getAcquire(this.memory);
+        long used = (this.writeOffset - readOffset + capacity) % capacity;
+        // The ring is empty if both offsets are equal so one record header is always kept free
+        long free = capacity - used - RECORD_HEADER_SIZE;
+
+        long offset = this.writeOffset;
+        long required = length;
+        if (offset + length > capacity) {
+            required += capacity - offset;
+        }
+        if (required > free) {
+            throw new IllegalStateException("Draw ring is full. It must be consumed using Frame#consumeDrawRing");
+        }
+
+        if (offset + length > capacity) {
+            this.data.set(INT, offset, OP_WRAP);
+            this.data.set(INT, offset + 4, 0);
+            offset = 0;
+        }
+
+        this.data.set(INT, offset, opcode);
+        this.data.set(INT, offset + 4, (int) length);
+        this.writeOffset = (int) ((offset + length) % capacity);
+        return offset + RECORD_HEADER_SIZE;
+    }
+
+    private void publish() {
+        WRITE_OFFSET_HANDLE.setRelease(this.memory, this.writeOffset);
+    }
+
+    /**
+     * Destroys the ring. Commands which have not been consumed are discarded.
+     */
+    @Override
+    public void close() throws Exception {
+        Natives.b4dDestroyDrawRing(this.handle);
+        if (this.ownedScope != null) {
+            this.ownedScope.close();
+        }
+    }
+}
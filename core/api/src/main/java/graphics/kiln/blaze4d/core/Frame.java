@@ -4,19 +4,240 @@ import graphics.kiln.blaze4d.core.natives.Natives;
 import graphics.kiln.blaze4d.core.types.B4DMeshData;
 import graphics.kiln.blaze4d.core.types.B4DUniformData;
 import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.ValueLayout;
+
+import java.nio.charset.StandardCharsets;
 
 public class Frame implements AutoCloseable {
 
+    /**
+     * The priority of draws which have not set a priority.
+     */
+    public static final int DEFAULT_DRAW_PRIORITY = 128;
+
+    /**
+     * Draws and uploads with this priority are never dropped by the render budget.
+     */
+    public static final int REQUIRED_DRAW_PRIORITY = 255;
+
     private final MemoryAddress handle;
 
     Frame(MemoryAddress handle) {
         this.handle = handle;
     }
 
+    public void setPartialTick(float partialTick) {
+        Natives.b4dPassSetPartialTick(this.handle, partialTick);
+    }
+
+    /**
+     * Sets the viewport at some index in pixels. Viewports which have not been set cover the full frame.
+     *
+     * @param index The viewport index. Must be smaller than 16.
+     */
+    public void setViewport(int index, float x, float y, float width, float height, float minDepth, float maxDepth) {
+        Natives.b4dPassSetViewport(this.handle, index, x, y, width, height, minDepth, maxDepth);
+    }
+
+    /**
+     * Selects the viewport used by all following draws.
+     */
+    public void setViewportIndex(int index) {
+        Natives.b4dPassSetViewportIndex(this.handle, index);
+    }
+
+    /**
+     * Enables cascaded shadow maps for all following draws. Only the first call per frame has an effect.
+     * The light direction is the direction the light travels in.
+     *
+     * @param view The column major world to view matrix of the camera. Must contain 16 values.
+     * @param fovY The vertical field of view in radians.
+     */
+    public void setShadowCamera(float[] view, float fovY, float aspect, float near, float far, float lightX, float lightY, float lightZ) {
+        if (view.length != 16) {
+            throw new IllegalArgumentException("View matrix must contain 16 values");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment matrix = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 16, scope);
+            matrix.copyFrom(MemorySegment.ofArray(view));
+            Natives.b4dPassSetShadowCamera(this.handle, matrix.address(), fovY, aspect, near, far, lightX, lightY, lightZ);
+        }
+    }
+
+    /**
+     * Restricts all following draws to a rect in pixels, like the scissor used by scrollable gui lists.
+     */
+    public void setScissor(int x, int y, int width, int height) {
+        Natives.b4dPassSetScissor(this.handle, true, x, y, width, height);
+    }
+
+    public void clearScissor() {
+        Natives.b4dPassSetScissor(this.handle, false, 0, 0, 0, 0);
+    }
+
+    /**
+     * Starts drawing a 3d item or block model into a gui slot. All following draws are rendered into the slot
+     * using the depth range until {@link #endGuiItem()} is called. Item meshes must be transformed into the unit
+     * cube centered at the origin and use {@link #getGuiItemProjection()} as their projection.
+     *
+     * @param x The left edge of the slot in logical gui pixels.
+     * @param y The top edge of the slot in logical gui pixels.
+     * @param size The width and height of the slot in logical gui pixels.
+     */
+    public void beginGuiItem(float x, float y, float size, float minDepth, float maxDepth) {
+        Natives.b4dPassBeginGuiItem(this.handle, x, y, size, minDepth, maxDepth);
+    }
+
+    public void endGuiItem() {
+        Natives.b4dPassEndGuiItem(this.handle);
+    }
+
+    /**
+     * Returns the column major projection matrix used for gui items.
+     */
+    public static float[] getGuiItemProjection() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment matrix = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 16, scope);
+            Natives.b4dGetGuiItemProjection(matrix.address());
+            return matrix.toArray(ValueLayout.JAVA_FLOAT);
+        }
+    }
+
+    /**
+     * Sets the camera matrices of this frame used by {@link Blaze4DCore#unproject}. Does not affect rendering.
+     *
+     * @param modelView A column major 4x4 matrix transforming world space into view space.
+     * @param projection A column major 4x4 matrix transforming view space into vulkan clip space.
+     */
+    public void setViewMatrices(float[] modelView, float[] projection) {
+        if (modelView.length != 16 || projection.length != 16) {
+            throw new IllegalArgumentException("Matrices must contain 16 elements");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment matrices = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 32, scope);
+            matrices.copyFrom(MemorySegment.ofArray(modelView));
+            matrices.asSlice(ValueLayout.JAVA_FLOAT.byteSize() * 16).copyFrom(MemorySegment.ofArray(projection));
+            Natives.b4dPassSetViewMatrices(this.handle, matrices.address(), matrices.address().addOffset(ValueLayout.JAVA_FLOAT.byteSize() * 16));
+        }
+    }
+
+    /**
+     * Starts a portal. All following draws until {@link #endPortal()} is called are only visible through the visible
+     * parts of the mask and are not occluded by anything drawn behind it, allowing a sub-scene to be rendered with its
+     * own camera. The uniforms of the mask shader must be those of the parent camera again when the portal ends.
+     *
+     * @param modelView The column major view matrix of the portal camera.
+     * @param projection The column major projection matrix of the portal camera.
+     * @param maskId An immediate mesh id returned by {@link #uploadImmediate(B4DMeshData)}.
+     * @param shaderId The shader used to draw the mask.
+     */
+    public void beginPortal(float[] modelView, float[] projection, int maskId, long shaderId) {
+        if (modelView.length != 16 || projection.length != 16) {
+            throw new IllegalArgumentException("Matrices must contain 16 elements");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment matrices = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 32, scope);
+            matrices.copyFrom(MemorySegment.ofArray(modelView));
+            matrices.asSlice(ValueLayout.JAVA_FLOAT.byteSize() * 16).copyFrom(MemorySegment.ofArray(projection));
+            Natives.b4dPassBeginPortal(this.handle, matrices.address(), matrices.address().addOffset(ValueLayout.JAVA_FLOAT.byteSize() * 16), maskId, shaderId);
+        }
+    }
+
+    public void endPortal() {
+        Natives.b4dPassEndPortal(this.handle);
+    }
+
+    /**
+     * Attaches a tag (for example a block position hash or entity id) to all following draws. Tags show up as
+     * debug labels in graphics debuggers and in {@link Blaze4DCore#getFrameTagStats()}.
+     */
+    public void setDrawTag(long tag) {
+        Natives.b4dPassSetDrawTag(this.handle, true, tag);
+    }
+
+    public void clearDrawTag() {
+        Natives.b4dPassSetDrawTag(this.handle, false, 0);
+    }
+
+    /**
+     * Sets the priority of all following draws and uploads in the range [0, 255]. If the render budget is exceeded
+     * draws with lower priority are dropped first. Draws with {@link #REQUIRED_DRAW_PRIORITY} are never dropped.
+     */
+    public void setDrawPriority(int priority) {
+        Natives.b4dPassSetDrawPriority(this.handle, priority);
+    }
+
+    /**
+     * Enables or disables applying the camera effects set using {@link Blaze4DCore#setCameraEffects} to all
+     * following projection matrix uniforms and view matrices. Should be enabled while rendering the world and
+     * disabled for the gui.
+     */
+    public void setCameraEffectsEnabled(boolean enable) {
+        Natives.b4dPassSetCameraEffectsEnabled(this.handle, enable);
+    }
+
+    /**
+     * Returns the column major world to view matrix of the camera with the camera effects of this frame applied if
+     * they are enabled. Views computed from the camera by the host must be derived from the returned matrix so they
+     * stay aligned with the world while the camera shakes. Shadow cascades set using {@link #setShadowCamera} already
+     * follow the camera effects.
+     */
+    public float[] applyCameraEffectsToView(float[] view) {
+        if (view.length != 16) {
+            throw new IllegalArgumentException("View matrix must contain 16 values");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment matrix = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 16, scope);
+            matrix.copyFrom(MemorySegment.ofArray(view));
+            Natives.b4dPassApplyCameraEffectsToView(this.handle, matrix.address());
+            return matrix.toArray(ValueLayout.JAVA_FLOAT);
+        }
+    }
+
+    /**
+     * Selects the render layer of all following draws. Passing 0 draws without a layer.
+     */
+    public void setRenderLayer(long layerId) {
+        Natives.b4dPassSetRenderLayer(this.handle, layerId);
+    }
+
+    /**
+     * Returns the draws submitted to a render layer in this frame so far or null if the layer has not been drawn to.
+     */
+    public LayerStats getLayerStats(long layerId) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment counts = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 2, scope);
+            if (!Natives.b4dPassGetLayerStats(this.handle, layerId, counts.address(), counts.address().addOffset(ValueLayout.JAVA_LONG.byteSize()))) {
+                return null;
+            }
+            long[] values = counts.toArray(ValueLayout.JAVA_LONG);
+            return new LayerStats(values[0], values[1]);
+        }
+    }
+
     public void updateUniform(long shaderId, B4DUniformData data) {
         Natives.b4dPassUpdateUniform(this.handle, data.getAddress(), shaderId);
     }
 
+    /**
+     * Sets a prefiltered environment probe created by {@link Blaze4DCore#captureProbe} as the texture at some index
+     * of a shader.
+     */
+    public void updateEnvironmentProbe(int index, GlobalImage probe, long shaderId) {
+        Natives.b4dPassUpdateEnvironmentProbe(this.handle, index, probe.getHandle(), shaderId);
+    }
+
+    /**
+     * Records all commands written to the ring since it was last consumed into this frame.
+     *
+     * @return The number of consumed commands.
+     */
+    public int consumeDrawRing(DrawRing ring) {
+        return Natives.b4dPassConsumeDrawRing(this.handle, ring.getHandle());
+    }
+
     public void drawGlobal(GlobalMesh mesh, long shaderId, boolean depthWrite) {
         Natives.b4dPassDrawGlobal(this.handle, mesh.getHandle(), shaderId, depthWrite);
     }
@@ -29,8 +250,151 @@ public class Frame implements AutoCloseable {
         Natives.b4dPassDrawImmediate(this.handle, meshId, shaderId, depthWrite);
     }
 
+    /**
+     * Draws a small mesh. If small draw merging is enabled consecutive small draws with the same state are merged
+     * into a single draw call.
+     *
+     * @param transform A column major 4x4 matrix applied to the vertex positions and normals or null.
+     */
+    public void drawSmall(B4DMeshData data, float[] transform, long shaderId, boolean depthWrite) {
+        if (transform == null) {
+            Natives.b4dPassDrawSmall(this.handle, data.getAddress(), MemoryAddress.NULL, shaderId, depthWrite);
+            return;
+        }
+        if (transform.length != 16) {
+            throw new IllegalArgumentException("Transform must contain 16 elements");
+        }
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment matrix = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 16, scope);
+            matrix.copyFrom(MemorySegment.ofArray(transform));
+            Natives.b4dPassDrawSmall(this.handle, data.getAddress(), matrix.address(), shaderId, depthWrite);
+        }
+    }
+
+    /**
+     * Draws a batch of textured gui rects with a single draw call. The texture is sampled with nearest filtering and
+     * set as texture 0 of the shader. The shader must use the position, tex and color vertex format.
+     *
+     * @param rects 8 floats per rect. The min and max corner of the destination in logical gui pixels followed by
+     *              the min and max texture coordinates.
+     * @param tints One 0xAARRGGBB color per rect.
+     */
+    public void drawTexturedRects(GlobalImage image, float[] rects, int[] tints, float depth, long shaderId, boolean depthWrite) {
+        drawGuiRects(image, rects, 8, tints, depth, shaderId, depthWrite, false);
+    }
+
+    /**
+     * Draws a batch of nine slice gui panels with a single draw call. See {@link #drawTexturedRects}.
+     *
+     * @param panels 16 floats per panel. The min and max corner of the destination in logical gui pixels, the min
+     *               and max texture coordinates, the left, top, right and bottom border in logical gui pixels and
+     *               the same borders in texture coordinates.
+     * @param tints One 0xAARRGGBB color per panel.
+     */
+    public void drawNineSlices(GlobalImage image, float[] panels, int[] tints, float depth, long shaderId, boolean depthWrite) {
+        drawGuiRects(image, panels, 16, tints, depth, shaderId, depthWrite, true);
+    }
+
+    private void drawGuiRects(GlobalImage image, float[] data, int floatCount, int[] tints, float depth, long shaderId, boolean depthWrite, boolean nineSlice) {
+        int count = tints.length;
+        if (data.length != count * floatCount) {
+            throw new IllegalArgumentException("Expected " + floatCount + " floats per tint");
+        }
+        long stride = (floatCount * ValueLayout.JAVA_FLOAT.byteSize()) + ValueLayout.JAVA_INT.byteSize();
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment rects = MemorySegment.allocateNative(Math.max(stride * count, 1), scope);
+            for (int i = 0; i < count; i++) {
+                MemorySegment rect = rects.asSlice(stride * i, stride);
+                rect.copyFrom(MemorySegment.ofArray(data).asSlice(ValueLayout.JAVA_FLOAT.byteSize() * floatCount * i, ValueLayout.JAVA_FLOAT.byteSize() * floatCount));
+                rect.set(ValueLayout.JAVA_INT, ValueLayout.JAVA_FLOAT.byteSize() * floatCount, tints[i]);
+            }
+            if (nineSlice) {
+                Natives.b4dPassDrawNineSlices(this.handle, image.getHandle(), MemoryAddress.NULL, rects.address(), count, depth, shaderId, depthWrite);
+            } else {
+                Natives.b4dPassDrawTexturedRects(this.handle, image.getHandle(), MemoryAddress.NULL, rects.address(), count, depth, shaderId, depthWrite);
+            }
+        }
+    }
+
+    /**
+     * Draws animated beams like beacon beams. Each beam is a draw of the mesh created by
+     * {@link Blaze4DCore#createBeamMesh} with its own model view matrix, texture matrix and color modulator. Afterwards
+     * these uniforms of the shader are reset. Requires the view matrices of the frame to be set.
+     *
+     * @param beams 7 floats per beam. The bottom center, the height, the half width, the rotation in radians and the
+     *              texture scroll.
+     * @param colors One 0xAARRGGBB color per beam.
+     */
+    public void drawBeams(GlobalMesh mesh, float[] beams, int[] colors, long shaderId, boolean depthWrite) {
+        int count = colors.length;
+        if (beams.length != count * 7) {
+            throw new IllegalArgumentException("Expected 7 floats per color");
+        }
+        long stride = (7 * ValueLayout.JAVA_FLOAT.byteSize()) + ValueLayout.JAVA_INT.byteSize();
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment data = MemorySegment.allocateNative(Math.max(stride * count, 1), scope);
+            for (int i = 0; i < count; i++) {
+                MemorySegment beam = data.asSlice(stride * i, stride);
+                beam.copyFrom(MemorySegment.ofArray(beams).asSlice(ValueLayout.JAVA_FLOAT.byteSize() * 7 * i, ValueLayout.JAVA_FLOAT.byteSize() * 7));
+                beam.set(ValueLayout.JAVA_INT, ValueLayout.JAVA_FLOAT.byteSize() * 7, colors[i]);
+            }
+            Natives.b4dPassDrawBeams(this.handle, mesh.getHandle(), data.address(), count, shaderId, depthWrite);
+        }
+    }
+
+    /**
+     * Draws soft round shadows below entities with a single draw call into the current render layer. Replaces the
+     * per entity shadow quads generated by vanilla. The shader must use the position and color vertex format.
+     *
+     * @param shadows 6 floats per shadow. The camera relative position of the ground below the entity, the radius,
+     *                the strength in the range [0, 1] and the height of the entity above the ground.
+     */
+    public void drawBlobShadows(float[] shadows, long shaderId) {
+        if (shadows.length % 6 != 0) {
+            throw new IllegalArgumentException("Expected 6 floats per shadow");
+        }
+        int count = shadows.length / 6;
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment data = MemorySegment.allocateNative(Math.max(ValueLayout.JAVA_FLOAT.byteSize() * shadows.length, 1), scope);
+            data.copyFrom(MemorySegment.ofArray(shadows));
+            Natives.b4dPassDrawBlobShadows(this.handle, data.address(), count, MemoryAddress.NULL, shaderId);
+        }
+    }
+
+    /**
+     * Enables or disables merging of small draws submitted using {@link #drawSmall}. Disabled by default.
+     */
+    public void setSmallDrawMerging(boolean enable) {
+        Natives.b4dPassSetSmallDrawMerging(this.handle, enable);
+    }
+
+    /**
+     * Starts logging all following commands of this frame so they can be replayed later.
+     */
+    public void startCommandLog() {
+        Natives.b4dPassStartCommandLog(this.handle);
+    }
+
+    /**
+     * Stops logging commands and writes the log to a file.
+     *
+     * @return False if logging was not started or the file could not be written.
+     */
+    public boolean saveCommandLog(String path) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            byte[] bytes = path.getBytes(StandardCharsets.UTF_8);
+            MemorySegment string = MemorySegment.allocateNative(bytes.length + 1, scope);
+            string.copyFrom(MemorySegment.ofArray(bytes));
+            string.set(ValueLayout.JAVA_BYTE, bytes.length, (byte) 0);
+            return Natives.b4dPassSaveCommandLog(this.handle, string.address());
+        }
+    }
+
     @Override
     public void close() throws Exception {
         Natives.b4dEndFrame(this.handle);
     }
+
+    public record LayerStats(long drawCount, long indexCount) {
+    }
 }
@@ -3,19 +3,125 @@ package graphics.kiln.blaze4d.core;
 import graphics.kiln.blaze4d.core.natives.Natives;
 import graphics.kiln.blaze4d.core.types.B4DImageData;
 import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.ValueLayout;
 
 public class GlobalImage implements AutoCloseable {
 
+    private final Blaze4DCore.DeviceGeneration generation;
     private final MemoryAddress handle;
 
-    GlobalImage(MemoryAddress handle) {
+    GlobalImage(Blaze4DCore.DeviceGeneration generation, MemoryAddress handle) {
+        this.generation = generation;
         this.handle = handle;
     }
 
+    /**
+     * Returns true if the device has been lost since this image was created. Frames ignore images of lost devices so
+     * the image must be recreated. The handle must still be closed.
+     */
+    public boolean isDeviceLost() {
+        return this.generation.isLost();
+    }
+
+    /**
+     * Writes a region of the image. For atlas array images the region is in atlas coordinates and may span multiple
+     * tiles. For all other images the first layer is written.
+     */
     public void update(B4DImageData data) {
         Natives.b4DUpdateGlobalImage(this.handle, data.getAddress(), 1);
     }
 
+    /**
+     * Writes a region of a single layer of an array image.
+     */
+    public void updateLayer(int layer, B4DImageData data) {
+        Natives.b4dUpdateGlobalImageLayer(this.handle, layer, data.getAddress(), 1);
+    }
+
+    /**
+     * Generates all mip levels from the first mip level. Each layer is processed independently.
+     */
+    public void generateMipmaps() {
+        Natives.b4dGlobalImageGenerateMipmaps(this.handle);
+    }
+
+    /**
+     * Sets the priority of pending uploads to this image. Higher priorities are uploaded first if
+     * the upload budget is limited.
+     */
+    public void setUploadPriority(int priority) {
+        Natives.b4dGlobalImageSetUploadPriority(this.handle, priority);
+    }
+
+    /**
+     * Returns true if the image has not been written or marked current since the last call to
+     * {@link Blaze4DCore#bumpResourceGeneration()}.
+     */
+    public boolean isStale() {
+        return Natives.b4dGlobalImageIsStale(this.handle);
+    }
+
+    /**
+     * Marks the image as current without writing it. Intended for images whose content did not change during a
+     * resource reload.
+     */
+    public void markCurrent() {
+        Natives.b4dGlobalImageMarkCurrent(this.handle);
+    }
+
+    /**
+     * Makes a region of a sparse image resident and marks it as used. For atlas images the region is in atlas
+     * coordinates. Regions sampled in a frame should be touched every frame so they are not evicted. Has no effect on
+     * images which are not sparse.
+     */
+    public void touchRegion(int x, int y, int width, int height) {
+        Natives.b4dGlobalImageTouchRegion(this.handle, x, y, width, height);
+    }
+
+    /**
+     * Makes a region of a single layer of a sparse image resident and marks it as used.
+     */
+    public void touchLayerRegion(int layer, int x, int y, int width, int height) {
+        Natives.b4dGlobalImageTouchLayerRegion(this.handle, layer, x, y, width, height);
+    }
+
+    /**
+     * Limits the memory used by the pages of a sparse image. The least recently used pages are evicted once the
+     * budget is exceeded. A budget of 0 disables the limit.
+     */
+    public void setResidencyBudget(long budget) {
+        Natives.b4dGlobalImageSetResidencyBudget(this.handle, budget);
+    }
+
+    /**
+     * Returns the residency statistics of a sparse image or null if the image is not sparse.
+     */
+    public ResidencyStats getResidencyStats() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment stats = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 7, scope);
+            if (!Natives.b4dGlobalImageGetResidencyStats(this.handle, stats.address())) {
+                return null;
+            }
+            long[] values = stats.toArray(ValueLayout.JAVA_LONG);
+            return new ResidencyStats(values[0], values[1], values[2], values[3], values[4], values[5], values[6]);
+        }
+    }
+
+    /**
+     * Returns statistics about how often and how recently this image has been sampled by draws. Can be used to find
+     * and unload images which have not been used for a long time.
+     */
+    public UsageStats getUsageStats() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment stats = MemorySegment.allocateNative(ValueLayout.JAVA_LONG.byteSize() * 5, scope);
+            Natives.b4dGlobalImageGetUsageStats(this.handle, stats.address());
+            long[] values = stats.toArray(ValueLayout.JAVA_LONG);
+            return new UsageStats(values[0], values[1], values[2], values[3], values[4]);
+        }
+    }
+
     MemoryAddress getHandle() {
         return this.handle;
     }
@@ -24,4 +130,20 @@ public class GlobalImage implements AutoCloseable {
     public void close() throws Exception {
         Natives.b4dDestroyGlobalImage(this.handle);
     }
+
+    /**
+     * Residency statistics of a sparse image. All sizes are in bytes. A budget of 0 means the image has no budget.
+     */
+    public record ResidencyStats(long pageSize, long totalPages, long residentPages, long evictingPages, long residentBytes, long mipTailBytes, long budget) {
+    }
+
+    /**
+     * Sampling statistics of an image. If the image has never been sampled lastSampledPass is 0 and passesSinceSampled
+     * and millisSinceSampled are -1.
+     */
+    public record UsageStats(long lastSampledPass, long passesSinceSampled, long millisSinceSampled, long sampledPassCount, long sampledDrawCount) {
+        public boolean wasSampled() {
+            return this.lastSampledPass != 0;
+        }
+    }
 }
@@ -5,18 +5,54 @@ import jdk.incubator.foreign.MemoryAddress;
 
 public class GlobalMesh implements AutoCloseable {
 
+    private final Blaze4DCore.DeviceGeneration generation;
     private final MemoryAddress handle;
 
-    GlobalMesh(MemoryAddress handle) {
+    GlobalMesh(Blaze4DCore.DeviceGeneration generation, MemoryAddress handle) {
+        this.generation = generation;
         this.handle = handle;
     }
 
+    /**
+     * Returns true if the device has been lost since this mesh was created. Frames ignore meshes of lost devices so
+     * the mesh must be recreated. The handle must still be closed.
+     */
+    public boolean isDeviceLost() {
+        return this.generation.isLost();
+    }
+
+    /**
+     * Returns the id used to identify this mesh in the mesh eviction callback.
+     */
+    public long getId() {
+        return Natives.b4dGlobalMeshGetId(this.handle);
+    }
+
+    /**
+     * Creates a new handle to the same mesh. The mesh is only destroyed once all handles have been closed.
+     */
+    public GlobalMesh retain() {
+        return new GlobalMesh(this.generation, Natives.b4dGlobalMeshRetain(this.handle));
+    }
+
+    /**
+     * Sets the priority of pending uploads to this mesh. Higher priorities are uploaded first if
+     * the upload budget is limited.
+     */
+    public void setUploadPriority(int priority) {
+        Natives.b4dGlobalMeshSetUploadPriority(this.handle, priority);
+    }
+
     MemoryAddress getHandle() {
         return this.handle;
     }
 
+    /**
+     * Releases this handle. The device memory is freed once all handles have been closed and no pending frame
+     * uses the mesh anymore.
+     */
     @Override
     public void close() throws Exception {
-        Natives.b4dDestroyGlobalMesh(this.handle);
+        Natives.b4dGlobalMeshRelease(this.handle);
     }
 }
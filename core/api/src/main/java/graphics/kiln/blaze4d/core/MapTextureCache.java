@@ -0,0 +1,113 @@
+package graphics.kiln.blaze4d.core;
+
+import graphics.kiln.blaze4d.core.natives.Natives;
+import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.ValueLayout;
+
+/**
+ * Stores 128x128 map item textures keyed by map id. Maps are packed into shared atlas pages and all updates between
+ * two calls to {@link #flush()} are uploaded together. Created by {@link Blaze4DCore#createMapTextureCache()}.
+ *
+ * Updates may be written from any thread.
+ */
+public class MapTextureCache implements AutoCloseable {
+
+    /**
+     * The size in bytes of the rgba data of a single map.
+     */
+    public static final int MAP_DATA_SIZE = 128 * 128 * 4;
+
+    private final Blaze4DCore.DeviceGeneration generation;
+    private final MemoryAddress handle;
+
+    MapTextureCache(Blaze4DCore.DeviceGeneration generation, MemoryAddress handle) {
+        this.generation = generation;
+        this.handle = handle;
+    }
+
+    /**
+     * Replaces the content of a map. The map is added if it does not exist yet.
+     *
+     * @param data {@link #MAP_DATA_SIZE} bytes of tightly packed rgba data.
+     */
+    public void update(int mapId, MemorySegment data) {
+        if (data.byteSize() != MAP_DATA_SIZE) {
+            throw new IllegalArgumentException("Map data must be " + MAP_DATA_SIZE + " bytes");
+        }
+        Natives.b4dMapCacheUpdate(this.handle, mapId, data.address(), data.byteSize());
+    }
+
+    /**
+     * Uploads all updates since the last flush. Should be called once per frame before maps are drawn.
+     *
+     * @return The number of uploaded maps.
+     */
+    public int flush() {
+        return Natives.b4dMapCacheFlush(this.handle);
+    }
+
+    /**
+     * Returns the location of a map and marks it as used or null if the map does not exist.
+     */
+    public MapSlot get(int mapId) {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment page = MemorySegment.allocateNative(ValueLayout.JAVA_INT, scope);
+            MemorySegment uvRect = MemorySegment.allocateNative(ValueLayout.JAVA_FLOAT.byteSize() * 4, scope);
+            if (!Natives.b4dMapCacheGet(this.handle, mapId, page.address(), uvRect.address())) {
+                return null;
+            }
+            return new MapSlot(
+                    page.get(ValueLayout.JAVA_INT, 0),
+                    uvRect.getAtIndex(ValueLayout.JAVA_FLOAT, 0),
+                    uvRect.getAtIndex(ValueLayout.JAVA_FLOAT, 1),
+                    uvRect.getAtIndex(ValueLayout.JAVA_FLOAT, 2),
+                    uvRect.getAtIndex(ValueLayout.JAVA_FLOAT, 3)
+            );
+        }
+    }
+
+    /**
+     * Returns a page image or null if the page does not exist. Pages are never destroyed so the returned image can be
+     * kept until the cache is closed. The image must be closed separately.
+     */
+    public GlobalImage getPage(int page) {
+        MemoryAddress image = Natives.b4dMapCacheGetPage(this.handle, page);
+        if(image.toRawLongValue() == 0L) {
+            return null;
+        } else {
+            return new GlobalImage(this.generation, image);
+        }
+    }
+
+    /**
+     * Removes a map which has been unloaded.
+     *
+     * @return True if the map existed.
+     */
+    public boolean remove(int mapId) {
+        return Natives.b4dMapCacheRemove(this.handle, mapId);
+    }
+
+    /**
+     * Removes all maps which have not been updated or returned by {@link #get(int)} during the last
+     * {@code maxIdleFlushes} flushes.
+     *
+     * @return The number of removed maps.
+     */
+    public int evictIdle(long maxIdleFlushes) {
+        return Natives.b4dMapCacheEvictIdle(this.handle, maxIdleFlushes);
+    }
+
+    @Override
+    public void close() throws Exception {
+        Natives.b4dDestroyMapTextureCache(this.handle);
+    }
+
+    /**
+     * The page containing a map and the min and max texture coordinates of the map in the page.
+     */
+    public record MapSlot(int page, float minU, float minV, float maxU, float maxV) {
+    }
+}
@@ -0,0 +1,26 @@
+package graphics.kiln.blaze4d.core;
+
+import graphics.kiln.blaze4d.core.natives.Natives;
+import jdk.incubator.foreign.MemoryAddress;
+
+/**
+ * Keeps the deduplicated meshes of a renderer alive across a resource reload. See
+ * {@link Blaze4DCore#retainCachedMeshes()}.
+ */
+public class MeshRetention {
+
+    private final MemoryAddress handle;
+
+    MeshRetention(MemoryAddress handle) {
+        this.handle = handle;
+    }
+
+    /**
+     * Releases all retained meshes. Must be called exactly once.
+     *
+     * @return The number of retained meshes which have been reused.
+     */
+    public int release() {
+        return Natives.b4dReleaseCachedMeshes(this.handle);
+    }
+}
@@ -0,0 +1,62 @@
+package graphics.kiln.blaze4d.core;
+
+import graphics.kiln.blaze4d.core.natives.Natives;
+import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.ValueLayout;
+
+/**
+ * An in progress panorama capture. The scene must be rendered once for each face returned by
+ * {@link #nextFace()} after which {@link #finish()} must be called.
+ */
+public class PanoramaCapture {
+
+    private final MemoryAddress handle;
+    private ProbeCapture.CubeFace currentFace;
+
+    PanoramaCapture(MemoryAddress handle) {
+        this.handle = handle;
+    }
+
+    /**
+     * Starts the frame for the next face. The face can be queried with {@link #getCurrentFace()}.
+     *
+     * @return The frame or null if all faces have been started.
+     */
+    public Frame nextFace() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment face = MemorySegment.allocateNative(ValueLayout.JAVA_INT, scope);
+            MemoryAddress frame = Natives.b4dPanoramaNextFace(this.handle, face.address());
+            if (frame.toRawLongValue() == 0L) {
+                this.currentFace = null;
+                return null;
+            }
+            this.currentFace = ProbeCapture.CubeFace.fromValue(face.get(ValueLayout.JAVA_INT, 0));
+            return new Frame(frame);
+        }
+    }
+
+    /**
+     * Returns the face of the frame last returned by {@link #nextFace()}.
+     */
+    public ProbeCapture.CubeFace getCurrentFace() {
+        return this.currentFace;
+    }
+
+    /**
+     * Starts stitching the panorama. Must only be called after all faces have been started and
+     * their frames have been closed.
+     */
+    public void finish() {
+        Natives.b4dPanoramaFinish(this.handle);
+    }
+
+    @FunctionalInterface
+    public interface PanoramaCallback {
+        /**
+         * Called from a background thread with the tightly packed rgba pixels of the panorama.
+         */
+        void onPanorama(int width, int height, byte[] data);
+    }
+}
@@ -0,0 +1,89 @@
+package graphics.kiln.blaze4d.core;
+
+import graphics.kiln.blaze4d.core.natives.Natives;
+import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.ValueLayout;
+
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * An in progress environment probe capture. Each frame the scene must be rendered once for each face returned by
+ * {@link #nextFaces()} until all faces have been started after which {@link #finish()} must be called.
+ */
+public class ProbeCapture {
+
+    private final MemoryAddress handle;
+
+    ProbeCapture(MemoryAddress handle) {
+        this.handle = handle;
+    }
+
+    /**
+     * Returns the faces which should be rendered this frame. Each face must be rendered using {@link #startFace}.
+     *
+     * @return The faces to render. Empty once all faces have been scheduled.
+     */
+    public List<CubeFace> nextFaces() {
+        try (ResourceScope scope = ResourceScope.newConfinedScope()) {
+            MemorySegment faces = MemorySegment.allocateNative(ValueLayout.JAVA_INT.byteSize() * 6, scope);
+            int count = Natives.b4dProbeNextFaces(this.handle, faces.address());
+
+            List<CubeFace> result = new ArrayList<>(count);
+            for (int i = 0; i < count; i++) {
+                result.add(CubeFace.fromValue(faces.getAtIndex(ValueLayout.JAVA_INT, i)));
+            }
+            return result;
+        }
+    }
+
+    /**
+     * Starts the frame of a face returned by {@link #nextFaces()}. Faces must be started in order and the frame must
+     * be closed before the next face or frame is started.
+     */
+    public Frame startFace(CubeFace face) {
+        return new Frame(Natives.b4dProbeStartFace(this.handle, face.raw));
+    }
+
+    /**
+     * Starts prefiltering the probe. Must only be called after all faces have been started and their frames have been
+     * closed.
+     */
+    public void finish() {
+        Natives.b4dProbeFinish(this.handle);
+    }
+
+    public enum CubeFace {
+        POSITIVE_X(0),
+        NEGATIVE_X(1),
+        POSITIVE_Y(2),
+        NEGATIVE_Y(3),
+        POSITIVE_Z(4),
+        NEGATIVE_Z(5);
+
+        final int raw;
+
+        CubeFace(int raw) {
+            this.raw = raw;
+        }
+
+        static CubeFace fromValue(int value) {
+            for (CubeFace face : values()) {
+                if (face.raw == value) {
+                    return face;
+                }
+            }
+            throw new RuntimeException("Invalid cube face " + value);
+        }
+    }
+
+    @FunctionalInterface
+    public interface ProbeCallback {
+        /**
+         * Called from a background thread with the prefiltered probe image. The image must be closed by the callee.
+         */
+        void onProbe(GlobalImage probe);
+    }
+}
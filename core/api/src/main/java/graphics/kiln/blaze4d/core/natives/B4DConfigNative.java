@@ -0,0 +1,99 @@
+package graphics.kiln.blaze4d.core.natives;
+
+import jdk.incubator.foreign.MemoryLayout;
+import jdk.incubator.foreign.ValueLayout;
+
+import java.lang.invoke.VarHandle;
+
+public class B4DConfigNative {
+    public static final MemoryLayout LAYOUT;
+
+    public static final MemoryLayout.PathElement ENABLE_VALIDATION_PATH;
+    public static final MemoryLayout.PathElement DEVICE_PREFERENCE_PATH;
+    public static final MemoryLayout.PathElement MSAA_SAMPLES_PATH;
+    public static final MemoryLayout.PathElement VSYNC_PATH;
+    public static final MemoryLayout.PathElement FRAMES_IN_FLIGHT_PATH;
+    public static final MemoryLayout.PathElement DEBUG_MODE_PATH;
+    public static final MemoryLayout.PathElement MEMORY_BUDGET_PATH;
+    public static final MemoryLayout.PathElement PIPELINE_CACHE_PATH_PATH;
+    public static final MemoryLayout.PathElement WATCHDOG_TIMEOUT_MS_PATH;
+    public static final MemoryLayout.PathElement WATCHDOG_RECOVER_PATH;
+    public static final MemoryLayout.PathElement GPU_ASSISTED_VALIDATION_PATH;
+    public static final MemoryLayout.PathElement ROBUST_ACCESS_PATH;
+    public static final MemoryLayout.PathElement VERTEX_PULLING_PATH;
+    public static final MemoryLayout.PathElement DEVICE_GROUP_PATH;
+    public static final MemoryLayout.PathElement TRACK_HOST_MEMORY_PATH;
+    public static final MemoryLayout.PathElement JOB_THREADS_PATH;
+
+    public static final VarHandle ENABLE_VALIDATION_HANDLE;
+    public static final VarHandle DEVICE_PREFERENCE_HANDLE;
+    public static final VarHandle MSAA_SAMPLES_HANDLE;
+    public static final VarHandle VSYNC_HANDLE;
+    public static final VarHandle FRAMES_IN_FLIGHT_HANDLE;
+    public static final VarHandle DEBUG_MODE_HANDLE;
+    public static final VarHandle MEMORY_BUDGET_HANDLE;
+    public static final VarHandle PIPELINE_CACHE_PATH_HANDLE;
+    public static final VarHandle WATCHDOG_TIMEOUT_MS_HANDLE;
+    public static final VarHandle WATCHDOG_RECOVER_HANDLE;
+    public static final VarHandle GPU_ASSISTED_VALIDATION_HANDLE;
+    public static final VarHandle ROBUST_ACCESS_HANDLE;
+    public static final VarHandle VERTEX_PULLING_HANDLE;
+    public static final VarHandle DEVICE_GROUP_HANDLE;
+    public static final VarHandle TRACK_HOST_MEMORY_HANDLE;
+    public static final VarHandle JOB_THREADS_HANDLE;
+
+    static {
+        LAYOUT = MemoryLayout.structLayout(
+                ValueLayout.JAVA_INT.withName("enable_validation"),
+                ValueLayout.JAVA_INT.withName("device_preference"),
+                ValueLayout.JAVA_INT.withName("msaa_samples"),
+                ValueLayout.JAVA_INT.withName("vsync"),
+                ValueLayout.JAVA_INT.withName("frames_in_flight"),
+                ValueLayout.JAVA_INT.withName("debug_mode"),
+                ValueLayout.JAVA_LONG.withName("memory_budget"),
+                ValueLayout.ADDRESS.withName("pipeline_cache_path"),
+                ValueLayout.JAVA_INT.withName("watchdog_timeout_ms"),
+                ValueLayout.JAVA_INT.withName("watchdog_recover"),
+                ValueLayout.JAVA_INT.withName("gpu_assisted_validation"),
+                ValueLayout.JAVA_INT.withName("robust_access"),
+                ValueLayout.JAVA_INT.withName("vertex_pulling"),
+                ValueLayout.JAVA_INT.withName("device_group"),
+                ValueLayout.JAVA_INT.withName("track_host_memory"),
+                ValueLayout.JAVA_INT.withName("job_threads")
+        );
+
+        ENABLE_VALIDATION_PATH = MemoryLayout.PathElement.groupElement("enable_validation");
+        DEVICE_PREFERENCE_PATH = MemoryLayout.PathElement.groupElement("device_preference");
+        MSAA_SAMPLES_PATH = MemoryLayout.PathElement.groupElement("msaa_samples");
+        VSYNC_PATH = MemoryLayout.PathElement.groupElement("vsync");
+        FRAMES_IN_FLIGHT_PATH = MemoryLayout.PathElement.groupElement("frames_in_flight");
+        DEBUG_MODE_PATH = MemoryLayout.PathElement.groupElement("debug_mode");
+        MEMORY_BUDGET_PATH = MemoryLayout.PathElement.groupElement("memory_budget");
+        PIPELINE_CACHE_PATH_PATH = MemoryLayout.PathElement.groupElement("pipeline_cache_path");
+        WATCHDOG_TIMEOUT_MS_PATH = MemoryLayout.PathElement.groupElement("watchdog_timeout_ms");
+        WATCHDOG_RECOVER_PATH = MemoryLayout.PathElement.groupElement("watchdog_recover");
+        GPU_ASSISTED_VALIDATION_PATH = MemoryLayout.PathElement.groupElement("gpu_assisted_validation");
+        ROBUST_ACCESS_PATH = MemoryLayout.PathElement.groupElement("robust_access");
+        VERTEX_PULLING_PATH = MemoryLayout.PathElement.groupElement("vertex_pulling");
+        DEVICE_GROUP_PATH = MemoryLayout.PathElement.groupElement("device_group");
+        TRACK_HOST_MEMORY_PATH = MemoryLayout.PathElement.groupElement("track_host_memory");
+        JOB_THREADS_PATH = MemoryLayout.PathElement.groupElement("job_threads");
+
+        ENABLE_VALIDATION_HANDLE = LAYOUT.varHandle(ENABLE_VALIDATION_PATH);
+        DEVICE_PREFERENCE_HANDLE = LAYOUT.varHandle(DEVICE_PREFERENCE_PATH);
+        MSAA_SAMPLES_HANDLE = LAYOUT.varHandle(MSAA_SAMPLES_PATH);
+        VSYNC_HANDLE = LAYOUT.varHandle(VSYNC_PATH);
+        FRAMES_IN_FLIGHT_HANDLE = LAYOUT.varHandle(FRAMES_IN_FLIGHT_PATH);
+        DEBUG_MODE_HANDLE = LAYOUT.varHandle(DEBUG_MODE_PATH);
+        MEMORY_BUDGET_HANDLE = LAYOUT.varHandle(MEMORY_BUDGET_PATH);
+        PIPELINE_CACHE_PATH_HANDLE = LAYOUT.varHandle(PIPELINE_CACHE_PATH_PATH);
+        WATCHDOG_TIMEOUT_MS_HANDLE = LAYOUT.varHandle(WATCHDOG_TIMEOUT_MS_PATH);
+        WATCHDOG_RECOVER_HANDLE = LAYOUT.varHandle(WATCHDOG_RECOVER_PATH);
+        GPU_ASSISTED_VALIDATION_HANDLE = LAYOUT.varHandle(GPU_ASSISTED_VALIDATION_PATH);
+        ROBUST_ACCESS_HANDLE = LAYOUT.varHandle(ROBUST_ACCESS_PATH);
+        VERTEX_PULLING_HANDLE = LAYOUT.varHandle(VERTEX_PULLING_PATH);
+        DEVICE_GROUP_HANDLE = LAYOUT.varHandle(DEVICE_GROUP_PATH);
+        TRACK_HOST_MEMORY_HANDLE = LAYOUT.varHandle(TRACK_HOST_MEMORY_PATH);
+        JOB_THREADS_HANDLE = LAYOUT.varHandle(JOB_THREADS_PATH);
+    }
+}
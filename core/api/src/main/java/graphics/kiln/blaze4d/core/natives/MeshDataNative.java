@@ -15,6 +15,9 @@ public class MeshDataNative {
     public static final MemoryLayout.PathElement INDEX_COUNT_PATH;
     public static final MemoryLayout.PathElement INDEX_TYPE_PATH;
     public static final MemoryLayout.PathElement PRIMITIVE_TOPOLOGY_PATH;
+    public static final MemoryLayout.PathElement COMPRESSION_PATH;
+    public static final MemoryLayout.PathElement VERTEX_DATA_UNCOMPRESSED_LEN_PATH;
+    public static final MemoryLayout.PathElement INDEX_DATA_UNCOMPRESSED_LEN_PATH;
 
     public static final VarHandle VERTEX_DATA_PTR_HANDLE;
     public static final VarHandle VERTEX_DATA_LEN_HANDLE;
@@ -24,6 +27,9 @@ public class MeshDataNative {
     public static final VarHandle INDEX_COUNT_HANDLE;
     public static final VarHandle INDEX_TYPE_HANDLE;
     public static final VarHandle PRIMITIVE_TOPOLOGY_HANDLE;
+    public static final VarHandle COMPRESSION_HANDLE;
+    public static final VarHandle VERTEX_DATA_UNCOMPRESSED_LEN_HANDLE;
+    public static final VarHandle INDEX_DATA_UNCOMPRESSED_LEN_HANDLE;
 
     static {
         LAYOUT = MemoryLayout.structLayout(
@@ -34,7 +40,11 @@ public class MeshDataNative {
                 ValueLayout.JAVA_INT.withName("vertex_stride"),
                 ValueLayout.JAVA_INT.withName("index_count"),
                 ValueLayout.JAVA_INT.withName("index_type"),
-                ValueLayout.JAVA_INT.withName("primitive_topology")
+                ValueLayout.JAVA_INT.withName("primitive_topology"),
+                ValueLayout.JAVA_INT.withName("compression"),
+                MemoryLayout.paddingLayout(32),
+                Natives.getSizeLayout().withName("vertex_data_uncompressed_len"),
+                Natives.getSizeLayout().withName("index_data_uncompressed_len")
         );
 
         VERTEX_DATA_PTR_PATH = MemoryLayout.PathElement.groupElement("vertex_data_ptr");
@@ -45,6 +55,9 @@ public class MeshDataNative {
         INDEX_COUNT_PATH = MemoryLayout.PathElement.groupElement("index_count");
         INDEX_TYPE_PATH = MemoryLayout.PathElement.groupElement("index_type");
         PRIMITIVE_TOPOLOGY_PATH = MemoryLayout.PathElement.groupElement("primitive_topology");
+        COMPRESSION_PATH = MemoryLayout.PathElement.groupElement("compression");
+        VERTEX_DATA_UNCOMPRESSED_LEN_PATH = MemoryLayout.PathElement.groupElement("vertex_data_uncompressed_len");
+        INDEX_DATA_UNCOMPRESSED_LEN_PATH = MemoryLayout.PathElement.groupElement("index_data_uncompressed_len");
 
         VERTEX_DATA_PTR_HANDLE = LAYOUT.varHandle(VERTEX_DATA_PTR_PATH);
         VERTEX_DATA_LEN_HANDLE = LAYOUT.varHandle(VERTEX_DATA_LEN_PATH);
@@ -54,5 +67,8 @@ public class MeshDataNative {
         INDEX_COUNT_HANDLE = LAYOUT.varHandle(INDEX_COUNT_PATH);
         INDEX_TYPE_HANDLE = LAYOUT.varHandle(INDEX_TYPE_PATH);
         PRIMITIVE_TOPOLOGY_HANDLE = LAYOUT.varHandle(PRIMITIVE_TOPOLOGY_PATH);
+        COMPRESSION_HANDLE = LAYOUT.varHandle(COMPRESSION_PATH);
+        VERTEX_DATA_UNCOMPRESSED_LEN_HANDLE = LAYOUT.varHandle(VERTEX_DATA_UNCOMPRESSED_LEN_PATH);
+        INDEX_DATA_UNCOMPRESSED_LEN_HANDLE = LAYOUT.varHandle(INDEX_DATA_UNCOMPRESSED_LEN_PATH);
     }
 }
@@ -26,23 +26,161 @@ public class Natives {
 
     public static final NativeMetadata nativeMetadata;
 
+    public static final MethodHandle B4D_TAKE_LAST_ERROR_HANDLE;
     public static final MethodHandle B4D_CREATE_GLFW_SURFACE_PROVIDER_HANDLE;
+    public static final MethodHandle B4D_CREATE_HEADLESS_SURFACE_HANDLE;
+    public static final MethodHandle B4D_ENABLE_SEEDED_IDS_HANDLE;
+    public static final MethodHandle B4D_SET_LOG_LEVEL_HANDLE;
     public static final MethodHandle B4D_INIT_HANDLE;
+    public static final MethodHandle B4D_INIT_WITH_CONFIG_HANDLE;
     public static final MethodHandle B4D_DESTROY_HANDLE;
     public static final MethodHandle B4D_SET_DEBUG_MODE_HANDLE;
+    public static final MethodHandle B4D_GET_DISPLAY_MODES_HANDLE;
+    public static final MethodHandle B4D_SET_EXCLUSIVE_FULLSCREEN_HANDLE;
+    public static final MethodHandle B4D_GET_CONTENT_SCALE_HANDLE;
+    public static final MethodHandle B4D_UNPROJECT_HANDLE;
+    public static final MethodHandle B4D_UNPROJECT_RAY_HANDLE;
+    public static final MethodHandle B4D_PROJECT_POINTS_HANDLE;
+    public static final MethodHandle B4D_SET_DEVICE_LOST_CALLBACK_HANDLE;
+    public static final MethodHandle B4D_GET_LAST_FAULT_REPORT_HANDLE;
+    public static final MethodHandle B4D_SET_MEMORY_PRESSURE_CALLBACK_HANDLE;
+    public static final MethodHandle B4D_SET_MESH_EVICTION_CALLBACK_HANDLE;
+    public static final MethodHandle B4D_SET_JOB_THREAD_COUNT_HANDLE;
+    public static final MethodHandle B4D_SET_JOB_EXECUTOR_HANDLE;
+    public static final MethodHandle B4D_RUN_JOB_HANDLE;
+    public static final MethodHandle B4D_GET_JOB_STATS_HANDLE;
+    public static final MethodHandle B4D_GET_MEMORY_USAGE_HANDLE;
+    public static final MethodHandle B4D_SET_UPLOAD_BUDGET_HANDLE;
+    public static final MethodHandle B4D_SET_VSYNC_HANDLE;
+    public static final MethodHandle B4D_SET_FRAMES_IN_FLIGHT_HANDLE;
+    public static final MethodHandle B4D_SET_LATENCY_MODE_HANDLE;
+    public static final MethodHandle B4D_SET_SUBMIT_MODE_HANDLE;
+    public static final MethodHandle B4D_SET_CAMERA_EFFECTS_HANDLE;
+    public static final MethodHandle B4D_GET_DISPLAY_TIMING_HANDLE;
+    public static final MethodHandle B4D_IS_SURFACE_PAUSED_HANDLE;
+    public static final MethodHandle B4D_GET_DEVICE_INFO_HANDLE;
+    public static final MethodHandle B4D_GET_HOST_MEMORY_REPORT_HANDLE;
+    public static final MethodHandle B4D_SET_COLOR_GRADING_PRESET_HANDLE;
+    public static final MethodHandle B4D_SET_COLOR_GRADING_CURVES_HANDLE;
+    public static final MethodHandle B4D_SET_COLOR_GRADING_LUT_HANDLE;
+    public static final MethodHandle B4D_SET_AUTO_EXPOSURE_HANDLE;
+    public static final MethodHandle B4D_START_COMMAND_STREAM_RECORDING_HANDLE;
+    public static final MethodHandle B4D_START_EVENT_LOG_FILE_HANDLE;
+    public static final MethodHandle B4D_SET_EVENT_LOG_CALLBACK_HANDLE;
+    public static final MethodHandle B4D_REGISTER_POST_CHAIN_HANDLE;
+    public static final MethodHandle B4D_UNREGISTER_POST_CHAIN_HANDLE;
+    public static final MethodHandle B4D_SET_ACTIVE_POST_CHAIN_HANDLE;
+    public static final MethodHandle B4D_SET_ACCESSIBILITY_FILTER_HANDLE;
+    public static final MethodHandle B4D_SET_SCREEN_DISTORTION_HANDLE;
+    public static final MethodHandle B4D_SET_DEBUG_OVERLAY_SHADER_HANDLE;
+    public static final MethodHandle B4D_SET_DEBUG_OVERLAY_ORIGIN_HANDLE;
+    public static final MethodHandle B4D_SET_DEBUG_CHUNK_GRID_HANDLE;
+    public static final MethodHandle B4D_SET_DEBUG_HEATMAP_HANDLE;
     public static final MethodHandle B4D_CREATE_GLOBAL_MESH_HANDLE;
+    public static final MethodHandle B4D_CREATE_GLOBAL_MESHES_HANDLE;
+    public static final MethodHandle B4D_DESTROY_GLOBAL_MESHES_HANDLE;
+    public static final MethodHandle B4D_CREATE_GLOBAL_MESH_OPTIMIZED_HANDLE;
+    public static final MethodHandle B4D_CREATE_GLOBAL_MESHES_OPTIMIZED_HANDLE;
+    public static final MethodHandle B4D_CREATE_GLOBAL_MESH_DEDUPLICATED_HANDLE;
+    public static final MethodHandle B4D_CREATE_BEAM_MESH_HANDLE;
+    public static final MethodHandle B4D_CREATE_COMPOSITE_IMAGE_HANDLE;
     public static final MethodHandle B4D_DESTROY_GLOBAL_MESH_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_MESH_GET_ID_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_MESH_RETAIN_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_MESH_RELEASE_HANDLE;
+    public static final MethodHandle B4D_SET_MESH_EVICTABLE_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_MESH_SET_UPLOAD_PRIORITY_HANDLE;
     public static final MethodHandle B4D_CREATE_GLOBAL_IMAGE_HANDLE;
+    public static final MethodHandle B4D_CREATE_GLOBAL_IMAGE_ARRAY_HANDLE;
     public static final MethodHandle B4D_UPDATE_GLOBAL_IMAGE_HANDLE;
+    public static final MethodHandle B4D_UPDATE_GLOBAL_IMAGE_LAYER_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_GENERATE_MIPMAPS_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_SET_UPLOAD_PRIORITY_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_IS_STALE_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_MARK_CURRENT_HANDLE;
+    public static final MethodHandle B4D_BUMP_RESOURCE_GENERATION_HANDLE;
+    public static final MethodHandle B4D_CREATE_GLOBAL_IMAGE_SPARSE_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_TOUCH_REGION_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_TOUCH_LAYER_REGION_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_SET_RESIDENCY_BUDGET_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_GET_RESIDENCY_STATS_HANDLE;
+    public static final MethodHandle B4D_GLOBAL_IMAGE_GET_USAGE_STATS_HANDLE;
     public static final MethodHandle B4D_DESTROY_GLOBAL_IMAGE_HANDLE;
     public static final MethodHandle B4D_CREATE_SHADER_HANDLE;
     public static final MethodHandle B4D_DESTROY_SHADER_HANDLE;
+    public static final MethodHandle B4D_REGISTER_RENDER_LAYER_HANDLE;
+    public static final MethodHandle B4D_UNREGISTER_RENDER_LAYER_HANDLE;
+    public static final MethodHandle B4D_FIND_RENDER_LAYER_HANDLE;
+    public static final MethodHandle B4D_SET_RENDER_ORDER_HANDLE;
+    public static final MethodHandle B4D_INSERT_RENDER_LAYER_HANDLE;
+    public static final MethodHandle B4D_SET_LAYER_ENABLED_HANDLE;
+    public static final MethodHandle B4D_ISOLATE_LAYER_HANDLE;
+    public static final MethodHandle B4D_GET_FRAME_LAYER_STATS_HANDLE;
+    public static final MethodHandle B4D_RETAIN_CACHED_MESHES_HANDLE;
+    public static final MethodHandle B4D_RELEASE_CACHED_MESHES_HANDLE;
+    public static final MethodHandle B4D_SAVE_STATE_HANDLE;
+    public static final MethodHandle B4D_LOAD_STATE_HANDLE;
+    public static final MethodHandle B4D_GET_FRAME_TAG_STATS_HANDLE;
+    public static final MethodHandle B4D_SET_IMMEDIATE_UPLOAD_LIMIT_HANDLE;
+    public static final MethodHandle B4D_SET_LAYER_DRAW_LIMIT_HANDLE;
+    public static final MethodHandle B4D_GET_FRAME_BUDGET_REPORT_HANDLE;
+    public static final MethodHandle B4D_GET_FRAME_TIME_REPORT_HANDLE;
+    public static final MethodHandle B4D_GET_FRAME_TIME_HISTOGRAM_HANDLE;
+    public static final MethodHandle B4D_CREATE_SHADER_WITH_FORMAT_HANDLE;
+    public static final MethodHandle B4D_CREATE_SHADER_SPECIALIZED_HANDLE;
+    public static final MethodHandle B4D_REGISTER_VERTEX_FORMAT_HANDLE;
+    public static final MethodHandle B4D_UNREGISTER_VERTEX_FORMAT_HANDLE;
     public static final MethodHandle B4D_START_FRAME_HANDLE;
+    public static final MethodHandle B4D_START_FRAME_SCALED_HANDLE;
+    public static final MethodHandle B4D_CAPTURE_PROBE_HANDLE;
+    public static final MethodHandle B4D_PROBE_NEXT_FACES_HANDLE;
+    public static final MethodHandle B4D_PROBE_START_FACE_HANDLE;
+    public static final MethodHandle B4D_PROBE_FINISH_HANDLE;
+    public static final MethodHandle B4D_CAPTURE_PANORAMA_HANDLE;
+    public static final MethodHandle B4D_PANORAMA_NEXT_FACE_HANDLE;
+    public static final MethodHandle B4D_PANORAMA_FINISH_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_PARTIAL_TICK_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_VIEWPORT_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_VIEWPORT_INDEX_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_SHADOW_CAMERA_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_SCISSOR_HANDLE;
+    public static final MethodHandle B4D_PASS_BEGIN_GUI_ITEM_HANDLE;
+    public static final MethodHandle B4D_PASS_END_GUI_ITEM_HANDLE;
+    public static final MethodHandle B4D_GET_GUI_ITEM_PROJECTION_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_VIEW_MATRICES_HANDLE;
+    public static final MethodHandle B4D_PASS_BEGIN_PORTAL_HANDLE;
+    public static final MethodHandle B4D_PASS_END_PORTAL_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_DRAW_TAG_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_DRAW_PRIORITY_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_CAMERA_EFFECTS_ENABLED_HANDLE;
+    public static final MethodHandle B4D_PASS_APPLY_CAMERA_EFFECTS_TO_VIEW_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_RENDER_LAYER_HANDLE;
+    public static final MethodHandle B4D_PASS_GET_LAYER_STATS_HANDLE;
     public static final MethodHandle B4D_PASS_UPDATE_UNIFORM_HANDLE;
+    public static final MethodHandle B4D_PASS_UPDATE_ENVIRONMENT_PROBE_HANDLE;
     public static final MethodHandle B4D_PASS_DRAW_GLOBAL_HANDLE;
     public static final MethodHandle B4D_PASS_UPLOAD_IMMEDIATE_HANDLE;
     public static final MethodHandle B4D_PASS_DRAW_IMMEDIATE_HANDLE;
+    public static final MethodHandle B4D_PASS_DRAW_SMALL_HANDLE;
+    public static final MethodHandle B4D_PASS_DRAW_TEXTURED_RECTS_HANDLE;
+    public static final MethodHandle B4D_PASS_DRAW_NINE_SLICES_HANDLE;
+    public static final MethodHandle B4D_PASS_DRAW_BLOB_SHADOWS_HANDLE;
+    public static final MethodHandle B4D_PASS_DRAW_BEAMS_HANDLE;
+    public static final MethodHandle B4D_PASS_SET_SMALL_DRAW_MERGING_HANDLE;
+    public static final MethodHandle B4D_PASS_START_COMMAND_LOG_HANDLE;
+    public static final MethodHandle B4D_PASS_SAVE_COMMAND_LOG_HANDLE;
     public static final MethodHandle B4D_END_FRAME_HANDLE;
+    public static final MethodHandle B4D_CREATE_DRAW_RING_HANDLE;
+    public static final MethodHandle B4D_DESTROY_DRAW_RING_HANDLE;
+    public static final MethodHandle B4D_CREATE_MAP_TEXTURE_CACHE_HANDLE;
+    public static final MethodHandle B4D_DESTROY_MAP_TEXTURE_CACHE_HANDLE;
+    public static final MethodHandle B4D_MAP_CACHE_UPDATE_HANDLE;
+    public static final MethodHandle B4D_MAP_CACHE_FLUSH_HANDLE;
+    public static final MethodHandle B4D_MAP_CACHE_GET_HANDLE;
+    public static final MethodHandle B4D_MAP_CACHE_GET_PAGE_HANDLE;
+    public static final MethodHandle B4D_MAP_CACHE_REMOVE_HANDLE;
+    public static final MethodHandle B4D_MAP_CACHE_EVICT_IDLE_HANDLE;
+    public static final MethodHandle B4D_PASS_CONSUME_DRAW_RING_HANDLE;
 
     static {
         Lib.loadNatives();
@@ -52,172 +190,1872 @@ public class Natives {
         initNativeLogger();
         preInitGlfw();
 
+        B4D_TAKE_LAST_ERROR_HANDLE = lookupFunction("b4d_take_last_error",
+                FunctionDescriptor.of(JAVA_INT)
+        );
+
         B4D_CREATE_GLFW_SURFACE_PROVIDER_HANDLE = lookupFunction("b4d_create_glfw_surface_provider",
-                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS, ADDRESS)
+                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_CREATE_HEADLESS_SURFACE_HANDLE = lookupFunction("b4d_create_headless_surface",
+                FunctionDescriptor.of(ADDRESS, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_ENABLE_SEEDED_IDS_HANDLE = lookupFunction("b4d_enable_seeded_ids",
+                FunctionDescriptor.ofVoid(JAVA_LONG)
+        );
+
+        B4D_SET_LOG_LEVEL_HANDLE = lookupFunction("b4d_set_log_level",
+                FunctionDescriptor.ofVoid(JAVA_INT, JAVA_INT)
         );
 
         B4D_INIT_HANDLE = lookupFunction("b4d_init",
                 FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT)
         );
 
-        B4D_DESTROY_HANDLE = lookupFunction("b4d_destroy",
-                FunctionDescriptor.ofVoid(ADDRESS)
-        );
+        B4D_INIT_WITH_CONFIG_HANDLE = lookupFunction("b4d_init_with_config",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_DESTROY_HANDLE = lookupFunction("b4d_destroy",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_SET_DEBUG_MODE_HANDLE = lookupFunction("b4d_set_debug_mode",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_GET_DISPLAY_MODES_HANDLE = lookupFunction("b4d_get_display_modes",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_EXCLUSIVE_FULLSCREEN_HANDLE = lookupFunction("b4d_set_exclusive_fullscreen",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_GET_CONTENT_SCALE_HANDLE = lookupFunction("b4d_get_content_scale",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS)
+        );
+
+        B4D_UNPROJECT_HANDLE = lookupFunction("b4d_unproject",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, ADDRESS)
+        );
+
+        B4D_UNPROJECT_RAY_HANDLE = lookupFunction("b4d_unproject_ray",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_FLOAT, JAVA_FLOAT, ADDRESS, ADDRESS)
+        );
+
+        B4D_PROJECT_POINTS_HANDLE = lookupFunction("b4d_project_points",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS, JAVA_INT, ADDRESS)
+        );
+
+        B4D_SET_DEVICE_LOST_CALLBACK_HANDLE = lookupFunction("b4d_set_device_lost_callback",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_GET_LAST_FAULT_REPORT_HANDLE = lookupFunction("b4d_get_last_fault_report",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_SET_MEMORY_PRESSURE_CALLBACK_HANDLE = lookupFunction("b4d_set_memory_pressure_callback",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_MESH_EVICTION_CALLBACK_HANDLE = lookupFunction("b4d_set_mesh_eviction_callback",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_JOB_THREAD_COUNT_HANDLE = lookupFunction("b4d_set_job_thread_count",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_JOB_EXECUTOR_HANDLE = lookupFunction("b4d_set_job_executor",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT, ADDRESS)
+        );
+
+        B4D_RUN_JOB_HANDLE = lookupFunction("b4d_run_job",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_GET_JOB_STATS_HANDLE = lookupFunction("b4d_get_job_stats",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_INT, ADDRESS)
+        );
+
+        B4D_GET_MEMORY_USAGE_HANDLE = lookupFunction("b4d_get_memory_usage",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_UPLOAD_BUDGET_HANDLE = lookupFunction("b4d_set_upload_budget",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_SET_VSYNC_HANDLE = lookupFunction("b4d_set_vsync",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_FRAMES_IN_FLIGHT_HANDLE = lookupFunction("b4d_set_frames_in_flight",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_LATENCY_MODE_HANDLE = lookupFunction("b4d_set_latency_mode",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_SUBMIT_MODE_HANDLE = lookupFunction("b4d_set_submit_mode",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_CAMERA_EFFECTS_HANDLE = lookupFunction("b4d_set_camera_effects",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_GET_DISPLAY_TIMING_HANDLE = lookupFunction("b4d_get_display_timing",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_IS_SURFACE_PAUSED_HANDLE = lookupFunction("b4d_is_surface_paused",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS)
+        );
+
+        B4D_GET_DEVICE_INFO_HANDLE = lookupFunction("b4d_get_device_info",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS)
+        );
+
+        B4D_GET_HOST_MEMORY_REPORT_HANDLE = lookupFunction("b4d_get_host_memory_report",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_COLOR_GRADING_PRESET_HANDLE = lookupFunction("b4d_set_color_grading_preset",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_COLOR_GRADING_CURVES_HANDLE = lookupFunction("b4d_set_color_grading_curves",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_COLOR_GRADING_LUT_HANDLE = lookupFunction("b4d_set_color_grading_lut",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT, JAVA_FLOAT)
+        );
+
+        B4D_SET_AUTO_EXPOSURE_HANDLE = lookupFunction("b4d_set_auto_exposure",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_START_COMMAND_STREAM_RECORDING_HANDLE = lookupFunction("b4d_start_command_stream_recording",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_START_EVENT_LOG_FILE_HANDLE = lookupFunction("b4d_start_event_log_file",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_EVENT_LOG_CALLBACK_HANDLE = lookupFunction("b4d_set_event_log_callback",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_REGISTER_POST_CHAIN_HANDLE = lookupFunction("b4d_register_post_chain",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS)
+        );
+
+        B4D_UNREGISTER_POST_CHAIN_HANDLE = lookupFunction("b4d_unregister_post_chain",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_SET_ACTIVE_POST_CHAIN_HANDLE = lookupFunction("b4d_set_active_post_chain",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_SET_ACCESSIBILITY_FILTER_HANDLE = lookupFunction("b4d_set_accessibility_filter",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_SCREEN_DISTORTION_HANDLE = lookupFunction("b4d_set_screen_distortion",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_SET_DEBUG_OVERLAY_SHADER_HANDLE = lookupFunction("b4d_set_debug_overlay_shader",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_SET_DEBUG_OVERLAY_ORIGIN_HANDLE = lookupFunction("b4d_set_debug_overlay_origin",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_SET_DEBUG_CHUNK_GRID_HANDLE = lookupFunction("b4d_set_debug_chunk_grid",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_SET_DEBUG_HEATMAP_HANDLE = lookupFunction("b4d_set_debug_heatmap",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, JAVA_INT, JAVA_FLOAT, JAVA_FLOAT, JAVA_INT)
+        );
+
+        B4D_CREATE_GLOBAL_MESH_HANDLE = lookupFunction("b4d_create_global_mesh",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_CREATE_GLOBAL_MESH_OPTIMIZED_HANDLE = lookupFunction("b4d_create_global_mesh_optimized",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_CREATE_GLOBAL_MESHES_OPTIMIZED_HANDLE = lookupFunction("b4d_create_global_meshes_optimized",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, ADDRESS)
+        );
+
+        B4D_CREATE_GLOBAL_MESHES_HANDLE = lookupFunction("b4d_create_global_meshes",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT, ADDRESS)
+        );
+
+        B4D_DESTROY_GLOBAL_MESHES_HANDLE = lookupFunction("b4d_destroy_global_meshes",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_CREATE_GLOBAL_MESH_DEDUPLICATED_HANDLE = lookupFunction("b4d_create_global_mesh_deduplicated",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_CREATE_BEAM_MESH_HANDLE = lookupFunction("b4d_create_beam_mesh",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_CREATE_COMPOSITE_IMAGE_HANDLE = lookupFunction("b4d_create_composite_image",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, ADDRESS, JAVA_INT)
+        );
+
+        B4D_DESTROY_GLOBAL_MESH_HANDLE = lookupFunction("b4d_destroy_global_mesh",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_GLOBAL_MESH_RETAIN_HANDLE = lookupFunction("b4d_global_mesh_retain",
+                FunctionDescriptor.of(ADDRESS, ADDRESS)
+        );
+
+        B4D_GLOBAL_MESH_RELEASE_HANDLE = lookupFunction("b4d_global_mesh_release",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_GLOBAL_MESH_GET_ID_HANDLE = lookupFunction("b4d_global_mesh_get_id",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS)
+        );
+
+        B4D_SET_MESH_EVICTABLE_HANDLE = lookupFunction("b4d_set_mesh_evictable",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_GLOBAL_MESH_SET_UPLOAD_PRIORITY_HANDLE = lookupFunction("b4d_global_mesh_set_upload_priority",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_CREATE_GLOBAL_IMAGE_HANDLE = lookupFunction("b4d_create_global_image",
+                FunctionDescriptor.of(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_CREATE_GLOBAL_IMAGE_ARRAY_HANDLE = lookupFunction("b4d_create_global_image_array",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_UPDATE_GLOBAL_IMAGE_HANDLE = lookupFunction("b4d_update_global_image",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_UPDATE_GLOBAL_IMAGE_LAYER_HANDLE = lookupFunction("b4d_update_global_image_layer",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, ADDRESS, JAVA_INT)
+        );
+
+        B4D_GLOBAL_IMAGE_GENERATE_MIPMAPS_HANDLE = lookupFunction("b4d_global_image_generate_mipmaps",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_GLOBAL_IMAGE_SET_UPLOAD_PRIORITY_HANDLE = lookupFunction("b4d_global_image_set_upload_priority",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_GLOBAL_IMAGE_IS_STALE_HANDLE = lookupFunction("b4d_global_image_is_stale",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS)
+        );
+
+        B4D_GLOBAL_IMAGE_MARK_CURRENT_HANDLE = lookupFunction("b4d_global_image_mark_current",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_BUMP_RESOURCE_GENERATION_HANDLE = lookupFunction("b4d_bump_resource_generation",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS)
+        );
+
+        B4D_CREATE_GLOBAL_IMAGE_SPARSE_HANDLE = lookupFunction("b4d_create_global_image_sparse",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_GLOBAL_IMAGE_TOUCH_REGION_HANDLE = lookupFunction("b4d_global_image_touch_region",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_GLOBAL_IMAGE_TOUCH_LAYER_REGION_HANDLE = lookupFunction("b4d_global_image_touch_layer_region",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_GLOBAL_IMAGE_SET_RESIDENCY_BUDGET_HANDLE = lookupFunction("b4d_global_image_set_residency_budget",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_GLOBAL_IMAGE_GET_RESIDENCY_STATS_HANDLE = lookupFunction("b4d_global_image_get_residency_stats",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_GLOBAL_IMAGE_GET_USAGE_STATS_HANDLE = lookupFunction("b4d_global_image_get_usage_stats",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS)
+        );
+
+        B4D_DESTROY_GLOBAL_IMAGE_HANDLE = lookupFunction("b4d_destroy_global_image",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_CREATE_SHADER_HANDLE = lookupFunction("b4d_create_shader",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_DESTROY_SHADER_HANDLE = lookupFunction("b4d_destroy_shader",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_REGISTER_RENDER_LAYER_HANDLE = lookupFunction("b4d_register_render_layer",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS, JAVA_INT, JAVA_INT, JAVA_FLOAT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_UNREGISTER_RENDER_LAYER_HANDLE = lookupFunction("b4d_unregister_render_layer",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_FIND_RENDER_LAYER_HANDLE = lookupFunction("b4d_find_render_layer",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS)
+        );
+
+        B4D_SET_RENDER_ORDER_HANDLE = lookupFunction("b4d_set_render_order",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_INSERT_RENDER_LAYER_HANDLE = lookupFunction("b4d_insert_render_layer",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_SET_LAYER_ENABLED_HANDLE = lookupFunction("b4d_set_layer_enabled",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_ISOLATE_LAYER_HANDLE = lookupFunction("b4d_isolate_layer",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_GET_FRAME_LAYER_STATS_HANDLE = lookupFunction("b4d_get_frame_layer_stats",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_RETAIN_CACHED_MESHES_HANDLE = lookupFunction("b4d_retain_cached_meshes",
+                FunctionDescriptor.of(ADDRESS, ADDRESS)
+        );
+
+        B4D_RELEASE_CACHED_MESHES_HANDLE = lookupFunction("b4d_release_cached_meshes",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS)
+        );
+
+        B4D_SAVE_STATE_HANDLE = lookupFunction("b4d_save_state",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_LOAD_STATE_HANDLE = lookupFunction("b4d_load_state",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_GET_FRAME_TAG_STATS_HANDLE = lookupFunction("b4d_get_frame_tag_stats",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_IMMEDIATE_UPLOAD_LIMIT_HANDLE = lookupFunction("b4d_set_immediate_upload_limit",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_SET_LAYER_DRAW_LIMIT_HANDLE = lookupFunction("b4d_set_layer_draw_limit",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_GET_FRAME_BUDGET_REPORT_HANDLE = lookupFunction("b4d_get_frame_budget_report",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS)
+        );
+
+        B4D_GET_FRAME_TIME_REPORT_HANDLE = lookupFunction("b4d_get_frame_time_report",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS)
+        );
+
+        B4D_GET_FRAME_TIME_HISTOGRAM_HANDLE = lookupFunction("b4d_get_frame_time_histogram",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_INT, ADDRESS, JAVA_INT)
+        );
+
+        B4D_CREATE_SHADER_WITH_FORMAT_HANDLE = lookupFunction("b4d_create_shader_with_format",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, JAVA_LONG, JAVA_LONG)
+        );
+
+        B4D_CREATE_SHADER_SPECIALIZED_HANDLE = lookupFunction("b4d_create_shader_specialized",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, JAVA_LONG, JAVA_LONG, JAVA_INT, JAVA_FLOAT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_REGISTER_VERTEX_FORMAT_HANDLE = lookupFunction("b4d_register_vertex_format",
+                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS)
+        );
+
+        B4D_UNREGISTER_VERTEX_FORMAT_HANDLE = lookupFunction("b4d_unregister_vertex_format",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_START_FRAME_HANDLE = lookupFunction("b4d_start_frame",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_START_FRAME_SCALED_HANDLE = lookupFunction("b4d_start_frame_scaled",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT, JAVA_FLOAT)
+        );
+
+        B4D_CAPTURE_PROBE_HANDLE = lookupFunction("b4d_capture_probe",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_INT, JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_PROBE_NEXT_FACES_HANDLE = lookupFunction("b4d_probe_next_faces",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_PROBE_START_FACE_HANDLE = lookupFunction("b4d_probe_start_face",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_PROBE_FINISH_HANDLE = lookupFunction("b4d_probe_finish",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_CAPTURE_PANORAMA_HANDLE = lookupFunction("b4d_capture_panorama",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_PANORAMA_NEXT_FACE_HANDLE = lookupFunction("b4d_panorama_next_face",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_PANORAMA_FINISH_HANDLE = lookupFunction("b4d_panorama_finish",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_PASS_SET_PARTIAL_TICK_HANDLE = lookupFunction("b4d_pass_set_partial_tick",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_FLOAT)
+        );
+
+        B4D_PASS_SET_VIEWPORT_HANDLE = lookupFunction("b4d_pass_set_viewport",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_PASS_SET_VIEWPORT_INDEX_HANDLE = lookupFunction("b4d_pass_set_viewport_index",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_PASS_SET_SHADOW_CAMERA_HANDLE = lookupFunction("b4d_pass_set_shadow_camera",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_PASS_SET_SCISSOR_HANDLE = lookupFunction("b4d_pass_set_scissor",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT, JAVA_INT)
+        );
+
+        B4D_PASS_BEGIN_GUI_ITEM_HANDLE = lookupFunction("b4d_pass_begin_gui_item",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT, JAVA_FLOAT)
+        );
+
+        B4D_PASS_END_GUI_ITEM_HANDLE = lookupFunction("b4d_pass_end_gui_item",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_GET_GUI_ITEM_PROJECTION_HANDLE = lookupFunction("b4d_get_gui_item_projection",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_PASS_SET_VIEW_MATRICES_HANDLE = lookupFunction("b4d_pass_set_view_matrices",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS)
+        );
+
+        B4D_PASS_BEGIN_PORTAL_HANDLE = lookupFunction("b4d_pass_begin_portal",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, JAVA_INT, JAVA_LONG)
+        );
+
+        B4D_PASS_END_PORTAL_HANDLE = lookupFunction("b4d_pass_end_portal",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_PASS_SET_DRAW_TAG_HANDLE = lookupFunction("b4d_pass_set_draw_tag",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_LONG)
+        );
+
+        B4D_PASS_SET_DRAW_PRIORITY_HANDLE = lookupFunction("b4d_pass_set_draw_priority",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_PASS_SET_CAMERA_EFFECTS_ENABLED_HANDLE = lookupFunction("b4d_pass_set_camera_effects_enabled",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_PASS_APPLY_CAMERA_EFFECTS_TO_VIEW_HANDLE = lookupFunction("b4d_pass_apply_camera_effects_to_view",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS)
+        );
+
+        B4D_PASS_SET_RENDER_LAYER_HANDLE = lookupFunction("b4d_pass_set_render_layer",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
+        );
+
+        B4D_PASS_GET_LAYER_STATS_HANDLE = lookupFunction("b4d_pass_get_layer_stats",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_LONG, ADDRESS, ADDRESS)
+        );
+
+        B4D_PASS_UPDATE_UNIFORM_HANDLE = lookupFunction("b4d_pass_update_uniform",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_PASS_UPDATE_ENVIRONMENT_PROBE_HANDLE = lookupFunction("b4d_pass_update_environment_probe",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_PASS_DRAW_GLOBAL_HANDLE = lookupFunction("b4d_pass_draw_global",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_PASS_UPLOAD_IMMEDIATE_HANDLE = lookupFunction("b4d_pass_upload_immediate",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_PASS_DRAW_IMMEDIATE_HANDLE = lookupFunction("b4d_pass_draw_immediate",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_PASS_DRAW_SMALL_HANDLE = lookupFunction("b4d_pass_draw_small",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_PASS_DRAW_TEXTURED_RECTS_HANDLE = lookupFunction("b4d_pass_draw_textured_rects",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, ADDRESS, JAVA_INT, JAVA_FLOAT, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_PASS_DRAW_NINE_SLICES_HANDLE = lookupFunction("b4d_pass_draw_nine_slices",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, ADDRESS, JAVA_INT, JAVA_FLOAT, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_PASS_DRAW_BEAMS_HANDLE = lookupFunction("b4d_pass_draw_beams",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, ADDRESS, JAVA_INT, JAVA_LONG, JAVA_INT)
+        );
+
+        B4D_PASS_DRAW_BLOB_SHADOWS_HANDLE = lookupFunction("b4d_pass_draw_blob_shadows",
+                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_PASS_SET_SMALL_DRAW_MERGING_HANDLE = lookupFunction("b4d_pass_set_small_draw_merging",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
+        );
+
+        B4D_PASS_START_COMMAND_LOG_HANDLE = lookupFunction("b4d_pass_start_command_log",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_PASS_SAVE_COMMAND_LOG_HANDLE = lookupFunction("b4d_pass_save_command_log",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_END_FRAME_HANDLE = lookupFunction("b4d_end_frame",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_CREATE_DRAW_RING_HANDLE = lookupFunction("b4d_create_draw_ring",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_DESTROY_DRAW_RING_HANDLE = lookupFunction("b4d_destroy_draw_ring",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_CREATE_MAP_TEXTURE_CACHE_HANDLE = lookupFunction("b4d_create_map_texture_cache",
+                FunctionDescriptor.of(ADDRESS, ADDRESS)
+        );
+
+        B4D_DESTROY_MAP_TEXTURE_CACHE_HANDLE = lookupFunction("b4d_destroy_map_texture_cache",
+                FunctionDescriptor.ofVoid(ADDRESS)
+        );
+
+        B4D_MAP_CACHE_UPDATE_HANDLE = lookupFunction("b4d_map_cache_update",
+                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_MAP_CACHE_FLUSH_HANDLE = lookupFunction("b4d_map_cache_flush",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS)
+        );
+
+        B4D_MAP_CACHE_GET_HANDLE = lookupFunction("b4d_map_cache_get",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_INT, ADDRESS, ADDRESS)
+        );
+
+        B4D_MAP_CACHE_GET_PAGE_HANDLE = lookupFunction("b4d_map_cache_get_page",
+                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT)
+        );
+
+        B4D_MAP_CACHE_REMOVE_HANDLE = lookupFunction("b4d_map_cache_remove",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_INT)
+        );
+
+        B4D_MAP_CACHE_EVICT_IDLE_HANDLE = lookupFunction("b4d_map_cache_evict_idle",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, JAVA_LONG)
+        );
+
+        B4D_PASS_CONSUME_DRAW_RING_HANDLE = lookupFunction("b4d_pass_consume_draw_ring",
+                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
+        );
+    }
+
+    public static MemoryAddress b4dCreateGlfwSurfaceProvider(long glfwWindow) {
+        MemoryAddress pfnGlfwGetRequiredInstanceExtensions = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetRequiredInstanceExtensions"));
+        MemoryAddress pfnGlfwCreateWindowSurface = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwCreateWindowSurface"));
+        MemoryAddress pfnGlfwGetFramebufferSize = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetFramebufferSize"));
+        MemoryAddress pfnGlfwGetWindowContentScale = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetWindowContentScale"));
+        MemoryAddress pfnGlfwGetPrimaryMonitor = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetPrimaryMonitor"));
+        MemoryAddress pfnGlfwGetWindowMonitor = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetWindowMonitor"));
+        MemoryAddress pfnGlfwGetVideoModes = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetVideoModes"));
+        MemoryAddress pfnGlfwSetWindowMonitor = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwSetWindowMonitor"));
+        MemoryAddress pfnGlfwGetWindowPos = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetWindowPos"));
+        MemoryAddress pfnGlfwGetWindowSize = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetWindowSize"));
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLFW_SURFACE_PROVIDER_HANDLE.invoke(MemoryAddress.ofLong(glfwWindow), pfnGlfwGetRequiredInstanceExtensions, pfnGlfwCreateWindowSurface, pfnGlfwGetFramebufferSize, pfnGlfwGetWindowContentScale,
+                    pfnGlfwGetPrimaryMonitor, pfnGlfwGetWindowMonitor, pfnGlfwGetVideoModes, pfnGlfwSetWindowMonitor, pfnGlfwGetWindowPos, pfnGlfwGetWindowSize);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_glfw_surface_provider", e);
+        }
+        checkLastError("b4d_create_glfw_surface_provider");
+        return result;
+    }
+
+    public static MemoryAddress b4dCreateHeadlessSurface(int width, int height) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_HEADLESS_SURFACE_HANDLE.invoke(width, height);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_headless_surface", e);
+        }
+        checkLastError("b4d_create_headless_surface");
+        return result;
+    }
+
+    public static void b4dEnableSeededIds(long seed) {
+        try {
+            B4D_ENABLE_SEEDED_IDS_HANDLE.invoke(seed);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_enable_seeded_ids", e);
+        }
+        checkLastError("b4d_enable_seeded_ids");
+    }
+
+    public static void b4dSetLogLevel(int subsystem, int level) {
+        try {
+            B4D_SET_LOG_LEVEL_HANDLE.invoke(subsystem, level);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_log_level", e);
+        }
+        checkLastError("b4d_set_log_level");
+    }
+
+    public static MemoryAddress b4dInit(MemoryAddress surface, boolean enableValidation) {
+        int enableValidationInt = enableValidation ? 1 : 0;
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_INIT_HANDLE.invoke(surface, enableValidationInt);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_init", e);
+        }
+        checkLastError("b4d_init");
+        return result;
+    }
+
+    public static MemoryAddress b4dInitWithConfig(MemoryAddress surface, MemoryAddress config) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_INIT_WITH_CONFIG_HANDLE.invoke(surface, config);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_init_with_config", e);
+        }
+        checkLastError("b4d_init_with_config");
+        return result;
+    }
+
+    public static void b4dDestroy(MemoryAddress b4d) {
+        try {
+            B4D_DESTROY_HANDLE.invoke(b4d);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy", e);
+        }
+        checkLastError("b4d_destroy");
+    }
+
+    public static void b4dSetDebugMode(MemoryAddress b4d, int debugMode) {
+        try {
+            B4D_SET_DEBUG_MODE_HANDLE.invoke(b4d, debugMode);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_debug_mode", e);
+        }
+        checkLastError("b4d_set_debug_mode");
+    }
+
+    public static int b4dGetDisplayModes(MemoryAddress b4d, MemoryAddress modes, int capacity) {
+        int result;
+        try {
+            result = (int) B4D_GET_DISPLAY_MODES_HANDLE.invoke(b4d, modes, capacity);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_display_modes", e);
+        }
+        checkLastError("b4d_get_display_modes");
+        return result;
+    }
+
+    public static boolean b4dSetExclusiveFullscreen(MemoryAddress b4d, MemoryAddress mode) {
+        int result;
+        try {
+            result = (int) B4D_SET_EXCLUSIVE_FULLSCREEN_HANDLE.invoke(b4d, mode);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_exclusive_fullscreen", e);
+        }
+        checkLastError("b4d_set_exclusive_fullscreen");
+        return result != 0;
+    }
+
+    public static void b4dGetContentScale(MemoryAddress b4d, MemoryAddress scale) {
+        try {
+            B4D_GET_CONTENT_SCALE_HANDLE.invoke(b4d, scale);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_content_scale", e);
+        }
+        checkLastError("b4d_get_content_scale");
+    }
+
+    public static boolean b4dUnproject(MemoryAddress b4d, float x, float y, float depth, MemoryAddress position) {
+        try {
+            return ((int) B4D_UNPROJECT_HANDLE.invoke(b4d, x, y, depth, position)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_unproject", e);
+        }
+        checkLastError("b4d_unproject");
+    }
+
+    public static boolean b4dUnprojectRay(MemoryAddress b4d, float x, float y, MemoryAddress origin, MemoryAddress direction) {
+        try {
+            return ((int) B4D_UNPROJECT_RAY_HANDLE.invoke(b4d, x, y, origin, direction)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_unproject_ray", e);
+        }
+        checkLastError("b4d_unproject_ray");
+    }
+
+    public static boolean b4dProjectPoints(MemoryAddress b4d, MemoryAddress points, int count, MemoryAddress results) {
+        try {
+            return ((int) B4D_PROJECT_POINTS_HANDLE.invoke(b4d, points, count, results)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_project_points", e);
+        }
+        checkLastError("b4d_project_points");
+    }
+
+    public static void b4dSetDeviceLostCallback(MemoryAddress b4d, Addressable callback) {
+        try {
+            B4D_SET_DEVICE_LOST_CALLBACK_HANDLE.invoke(b4d, callback, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_device_lost_callback", e);
+        }
+        checkLastError("b4d_set_device_lost_callback");
+    }
+
+    public static long b4dGetLastFaultReport(MemoryAddress b4d, MemoryAddress buffer, long size) {
+        long result;
+        try {
+            result = (long) B4D_GET_LAST_FAULT_REPORT_HANDLE.invoke(b4d, buffer, size);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_last_fault_report", e);
+        }
+        checkLastError("b4d_get_last_fault_report");
+        return result;
+    }
+
+    public static void b4dSetMemoryPressureCallback(MemoryAddress b4d, Addressable callback) {
+        try {
+            B4D_SET_MEMORY_PRESSURE_CALLBACK_HANDLE.invoke(b4d, callback, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_memory_pressure_callback", e);
+        }
+        checkLastError("b4d_set_memory_pressure_callback");
+    }
+
+    public static void b4dSetMeshEvictionCallback(MemoryAddress b4d, Addressable callback) {
+        try {
+            B4D_SET_MESH_EVICTION_CALLBACK_HANDLE.invoke(b4d, callback, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_mesh_eviction_callback", e);
+        }
+        checkLastError("b4d_set_mesh_eviction_callback");
+    }
+
+    public static void b4dSetJobThreadCount(MemoryAddress b4d, int threadCount) {
+        try {
+            B4D_SET_JOB_THREAD_COUNT_HANDLE.invoke(b4d, threadCount);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_job_thread_count", e);
+        }
+        checkLastError("b4d_set_job_thread_count");
+    }
+
+    public static void b4dSetJobExecutor(MemoryAddress b4d, Addressable callback, int parallelism) {
+        try {
+            B4D_SET_JOB_EXECUTOR_HANDLE.invoke(b4d, callback, parallelism, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_job_executor", e);
+        }
+        checkLastError("b4d_set_job_executor");
+    }
+
+    public static void b4dRunJob(MemoryAddress job) {
+        try {
+            B4D_RUN_JOB_HANDLE.invoke(job);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_run_job", e);
+        }
+        checkLastError("b4d_run_job");
+    }
+
+    public static void b4dGetJobStats(MemoryAddress b4d, int kind, boolean reset, MemoryAddress stats) {
+        try {
+            B4D_GET_JOB_STATS_HANDLE.invoke(b4d, kind, reset ? 1 : 0, stats);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_job_stats", e);
+        }
+        checkLastError("b4d_get_job_stats");
+    }
+
+    public static void b4dGetMemoryUsage(MemoryAddress b4d, MemoryAddress usage, MemoryAddress budget) {
+        try {
+            B4D_GET_MEMORY_USAGE_HANDLE.invoke(b4d, usage, budget);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_memory_usage", e);
+        }
+        checkLastError("b4d_get_memory_usage");
+    }
+
+    public static void b4dSetUploadBudget(MemoryAddress b4d, long budget) {
+        try {
+            B4D_SET_UPLOAD_BUDGET_HANDLE.invoke(b4d, budget);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_upload_budget", e);
+        }
+        checkLastError("b4d_set_upload_budget");
+    }
+
+    public static void b4dSetVsync(MemoryAddress b4d, boolean vsync) {
+        try {
+            B4D_SET_VSYNC_HANDLE.invoke(b4d, vsync ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_vsync", e);
+        }
+        checkLastError("b4d_set_vsync");
+    }
+
+    public static void b4dSetFramesInFlight(MemoryAddress b4d, int framesInFlight) {
+        try {
+            B4D_SET_FRAMES_IN_FLIGHT_HANDLE.invoke(b4d, framesInFlight);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_frames_in_flight", e);
+        }
+        checkLastError("b4d_set_frames_in_flight");
+    }
+
+    public static void b4dSetLatencyMode(MemoryAddress b4d, int mode) {
+        try {
+            B4D_SET_LATENCY_MODE_HANDLE.invoke(b4d, mode);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_latency_mode", e);
+        }
+        checkLastError("b4d_set_latency_mode");
+    }
+
+    public static void b4dSetSubmitMode(MemoryAddress b4d, int mode) {
+        try {
+            B4D_SET_SUBMIT_MODE_HANDLE.invoke(b4d, mode);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_submit_mode", e);
+        }
+        checkLastError("b4d_set_submit_mode");
+    }
+
+    public static void b4dSetCameraEffects(MemoryAddress b4d, float bobPhase, float bobAmplitude, float shakeIntensity, float shakeDirection) {
+        try {
+            B4D_SET_CAMERA_EFFECTS_HANDLE.invoke(b4d, bobPhase, bobAmplitude, shakeIntensity, shakeDirection);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_camera_effects", e);
+        }
+        checkLastError("b4d_set_camera_effects");
+    }
+
+    public static boolean b4dIsSurfacePaused(MemoryAddress b4d) {
+        try {
+            return ((int) B4D_IS_SURFACE_PAUSED_HANDLE.invoke(b4d)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_is_surface_paused", e);
+        }
+        checkLastError("b4d_is_surface_paused");
+    }
+
+    public static boolean b4dGetDisplayTiming(MemoryAddress b4d, MemoryAddress timing) {
+        try {
+            return ((int) B4D_GET_DISPLAY_TIMING_HANDLE.invoke(b4d, timing)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_display_timing", e);
+        }
+        checkLastError("b4d_get_display_timing");
+    }
+
+    public static void b4dGetDeviceInfo(MemoryAddress b4d, MemoryAddress info) {
+        try {
+            B4D_GET_DEVICE_INFO_HANDLE.invoke(b4d, info);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_device_info", e);
+        }
+        checkLastError("b4d_get_device_info");
+    }
+
+    public static boolean b4dGetHostMemoryReport(MemoryAddress b4d, MemoryAddress stats) {
+        try {
+            return ((int) B4D_GET_HOST_MEMORY_REPORT_HANDLE.invoke(b4d, stats)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_host_memory_report", e);
+        }
+        checkLastError("b4d_get_host_memory_report");
+    }
+
+    public static void b4dSetColorGradingPreset(MemoryAddress b4d, int preset) {
+        try {
+            B4D_SET_COLOR_GRADING_PRESET_HANDLE.invoke(b4d, preset);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_color_grading_preset", e);
+        }
+        checkLastError("b4d_set_color_grading_preset");
+    }
+
+    public static void b4dSetColorGradingCurves(MemoryAddress b4d, MemoryAddress lift, MemoryAddress gamma, MemoryAddress gain) {
+        try {
+            B4D_SET_COLOR_GRADING_CURVES_HANDLE.invoke(b4d, lift, gamma, gain);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_color_grading_curves", e);
+        }
+        checkLastError("b4d_set_color_grading_curves");
+    }
+
+    public static void b4dSetColorGradingLut(MemoryAddress b4d, MemoryAddress image, int size, float strength) {
+        try {
+            B4D_SET_COLOR_GRADING_LUT_HANDLE.invoke(b4d, image, size, strength);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_color_grading_lut", e);
+        }
+        checkLastError("b4d_set_color_grading_lut");
+    }
+
+    public static void b4dSetAutoExposure(MemoryAddress b4d, int enabled, float minLogLuminance, float maxLogLuminance, float targetLuminance, float adaptationSpeed) {
+        try {
+            B4D_SET_AUTO_EXPOSURE_HANDLE.invoke(b4d, enabled, minLogLuminance, maxLogLuminance, targetLuminance, adaptationSpeed);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_auto_exposure", e);
+        }
+        checkLastError("b4d_set_auto_exposure");
+    }
+
+    public static void b4dStartCommandStreamRecording(MemoryAddress b4d, MemoryAddress path, int frameCount, int includeMeshData) {
+        try {
+            B4D_START_COMMAND_STREAM_RECORDING_HANDLE.invoke(b4d, path, frameCount, includeMeshData);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_start_command_stream_recording", e);
+        }
+        checkLastError("b4d_start_command_stream_recording");
+    }
+
+    public static boolean b4dStartEventLogFile(MemoryAddress b4d, MemoryAddress path) {
+        try {
+            return ((int) B4D_START_EVENT_LOG_FILE_HANDLE.invoke(b4d, path)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_start_event_log_file", e);
+        }
+        checkLastError("b4d_start_event_log_file");
+    }
+
+    public static void b4dSetEventLogCallback(MemoryAddress b4d, Addressable callback) {
+        try {
+            B4D_SET_EVENT_LOG_CALLBACK_HANDLE.invoke(b4d, callback, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_event_log_callback", e);
+        }
+        checkLastError("b4d_set_event_log_callback");
+    }
+
+    public static long b4dRegisterPostChain(MemoryAddress b4d, MemoryAddress json) {
+        long result;
+        try {
+            result = (long) B4D_REGISTER_POST_CHAIN_HANDLE.invoke(b4d, json);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_register_post_chain", e);
+        }
+        checkLastError("b4d_register_post_chain");
+        return result;
+    }
+
+    public static void b4dUnregisterPostChain(MemoryAddress b4d, long chainId) {
+        try {
+            B4D_UNREGISTER_POST_CHAIN_HANDLE.invoke(b4d, chainId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_unregister_post_chain", e);
+        }
+        checkLastError("b4d_unregister_post_chain");
+    }
+
+    public static void b4dSetActivePostChain(MemoryAddress b4d, long chainId) {
+        try {
+            B4D_SET_ACTIVE_POST_CHAIN_HANDLE.invoke(b4d, chainId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_active_post_chain", e);
+        }
+        checkLastError("b4d_set_active_post_chain");
+    }
+
+    public static void b4dSetAccessibilityFilter(MemoryAddress b4d, int filter) {
+        try {
+            B4D_SET_ACCESSIBILITY_FILTER_HANDLE.invoke(b4d, filter);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_accessibility_filter", e);
+        }
+        checkLastError("b4d_set_accessibility_filter");
+    }
+
+    public static void b4dSetScreenDistortion(MemoryAddress b4d, float intensity, float time, float speed) {
+        try {
+            B4D_SET_SCREEN_DISTORTION_HANDLE.invoke(b4d, intensity, time, speed);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_screen_distortion", e);
+        }
+        checkLastError("b4d_set_screen_distortion");
+    }
+
+    public static void b4dSetDebugOverlayShader(MemoryAddress b4d, long shaderId) {
+        try {
+            B4D_SET_DEBUG_OVERLAY_SHADER_HANDLE.invoke(b4d, shaderId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_debug_overlay_shader", e);
+        }
+        checkLastError("b4d_set_debug_overlay_shader");
+    }
+
+    public static void b4dSetDebugOverlayOrigin(MemoryAddress b4d, int x, int y, int z, float offsetX, float offsetY, float offsetZ) {
+        try {
+            B4D_SET_DEBUG_OVERLAY_ORIGIN_HANDLE.invoke(b4d, x, y, z, offsetX, offsetY, offsetZ);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_debug_overlay_origin", e);
+        }
+        checkLastError("b4d_set_debug_overlay_origin");
+    }
+
+    public static void b4dSetDebugChunkGrid(MemoryAddress b4d, boolean enable, int chunkX, int chunkZ, int radius, int minY, int maxY, int color, int sectionColor) {
+        try {
+            B4D_SET_DEBUG_CHUNK_GRID_HANDLE.invoke(b4d, enable ? 1 : 0, chunkX, chunkZ, radius, minY, maxY, color, sectionColor);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_debug_chunk_grid", e);
+        }
+        checkLastError("b4d_set_debug_chunk_grid");
+    }
+
+    public static void b4dSetDebugHeatmap(MemoryAddress b4d, MemoryAddress sections, MemoryAddress values, int count, float minValue, float maxValue, int alpha) {
+        try {
+            B4D_SET_DEBUG_HEATMAP_HANDLE.invoke(b4d, sections, values, count, minValue, maxValue, alpha);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_debug_heatmap", e);
+        }
+        checkLastError("b4d_set_debug_heatmap");
+    }
+
+    public static MemoryAddress b4dCreateGlobalMesh(MemoryAddress b4d, MemoryAddress meshData) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLOBAL_MESH_HANDLE.invoke(b4d, meshData);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_mesh", e);
+        }
+        checkLastError("b4d_create_global_mesh");
+        return result;
+    }
+
+    public static MemoryAddress b4dCreateGlobalMeshOptimized(MemoryAddress b4d, MemoryAddress meshData, int positionOffset, int positionFormat) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLOBAL_MESH_OPTIMIZED_HANDLE.invoke(b4d, meshData, positionOffset, positionFormat);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_mesh_optimized", e);
+        }
+        checkLastError("b4d_create_global_mesh_optimized");
+        return result;
+    }
+
+    public static void b4dCreateGlobalMeshesOptimized(MemoryAddress b4d, MemoryAddress meshDatas, int count, int positionOffset, int positionFormat, MemoryAddress outMeshes) {
+        try {
+            B4D_CREATE_GLOBAL_MESHES_OPTIMIZED_HANDLE.invoke(b4d, meshDatas, count, positionOffset, positionFormat, outMeshes);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_meshes_optimized", e);
+        }
+        checkLastError("b4d_create_global_meshes_optimized");
+    }
+
+    public static void b4dCreateGlobalMeshes(MemoryAddress b4d, MemoryAddress meshDatas, int count, MemoryAddress outMeshes) {
+        try {
+            B4D_CREATE_GLOBAL_MESHES_HANDLE.invoke(b4d, meshDatas, count, outMeshes);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_meshes", e);
+        }
+        checkLastError("b4d_create_global_meshes");
+    }
+
+    public static void b4dDestroyGlobalMeshes(MemoryAddress meshes, int count) {
+        try {
+            B4D_DESTROY_GLOBAL_MESHES_HANDLE.invoke(meshes, count);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy_global_meshes", e);
+        }
+        checkLastError("b4d_destroy_global_meshes");
+    }
+
+    public static MemoryAddress b4dCreateGlobalMeshDeduplicated(MemoryAddress b4d, MemoryAddress meshData) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLOBAL_MESH_DEDUPLICATED_HANDLE.invoke(b4d, meshData);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_mesh_deduplicated", e);
+        }
+        checkLastError("b4d_create_global_mesh_deduplicated");
+        return result;
+    }
+
+    public static MemoryAddress b4dCreateBeamMesh(MemoryAddress b4d, long shaderId) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_BEAM_MESH_HANDLE.invoke(b4d, shaderId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_beam_mesh", e);
+        }
+        checkLastError("b4d_create_beam_mesh");
+        return result;
+    }
+
+    public static MemoryAddress b4dCreateCompositeImage(MemoryAddress b4d, int width, int height, int format, MemoryAddress layers, int layerCount) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_COMPOSITE_IMAGE_HANDLE.invoke(b4d, width, height, format, layers, layerCount);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_composite_image", e);
+        }
+        checkLastError("b4d_create_composite_image");
+        return result;
+    }
+
+    public static void b4dDestroyGlobalMesh(MemoryAddress mesh) {
+        try {
+            B4D_DESTROY_GLOBAL_MESH_HANDLE.invoke(mesh);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy_global_mesh", e);
+        }
+        checkLastError("b4d_destroy_global_mesh");
+    }
+
+    public static MemoryAddress b4dGlobalMeshRetain(MemoryAddress mesh) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_GLOBAL_MESH_RETAIN_HANDLE.invoke(mesh);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_mesh_retain", e);
+        }
+        checkLastError("b4d_global_mesh_retain");
+        return result;
+    }
+
+    public static void b4dGlobalMeshRelease(MemoryAddress mesh) {
+        try {
+            B4D_GLOBAL_MESH_RELEASE_HANDLE.invoke(mesh);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_mesh_release", e);
+        }
+        checkLastError("b4d_global_mesh_release");
+    }
+
+    public static long b4dGlobalMeshGetId(MemoryAddress mesh) {
+        long result;
+        try {
+            result = (long) B4D_GLOBAL_MESH_GET_ID_HANDLE.invoke(mesh);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_mesh_get_id", e);
+        }
+        checkLastError("b4d_global_mesh_get_id");
+        return result;
+    }
+
+    public static void b4dSetMeshEvictable(MemoryAddress b4d, MemoryAddress mesh, boolean evictable) {
+        try {
+            B4D_SET_MESH_EVICTABLE_HANDLE.invoke(b4d, mesh, evictable ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_mesh_evictable", e);
+        }
+        checkLastError("b4d_set_mesh_evictable");
+    }
+
+    public static void b4dGlobalMeshSetUploadPriority(MemoryAddress mesh, int priority) {
+        try {
+            B4D_GLOBAL_MESH_SET_UPLOAD_PRIORITY_HANDLE.invoke(mesh, priority);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_mesh_set_upload_priority", e);
+        }
+        checkLastError("b4d_global_mesh_set_upload_priority");
+    }
+
+    public static MemoryAddress b4dCreateGlobalImage(MemoryAddress b4d, int width, int height, int format) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLOBAL_IMAGE_HANDLE.invoke(b4d, width, height, format);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_image", e);
+        }
+        checkLastError("b4d_create_global_image");
+        return result;
+    }
+
+    public static MemoryAddress b4dCreateGlobalImageArray(MemoryAddress b4d, int width, int height, int mipLevels, int layerCount, int atlasColumns, int atlasRows, int format) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLOBAL_IMAGE_ARRAY_HANDLE.invoke(b4d, width, height, mipLevels, layerCount, atlasColumns, atlasRows, format);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_image_array", e);
+        }
+        checkLastError("b4d_create_global_image_array");
+        return result;
+    }
+
+    public static void b4DUpdateGlobalImage(MemoryAddress image, MemoryAddress data, int dataCount) {
+        try {
+            B4D_UPDATE_GLOBAL_IMAGE_HANDLE.invoke(image, data, dataCount);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_update_global_image", e);
+        }
+        checkLastError("b4d_update_global_image");
+    }
+
+    public static void b4dUpdateGlobalImageLayer(MemoryAddress image, int layer, MemoryAddress data, int dataCount) {
+        try {
+            B4D_UPDATE_GLOBAL_IMAGE_LAYER_HANDLE.invoke(image, layer, data, dataCount);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_update_global_image_layer", e);
+        }
+        checkLastError("b4d_update_global_image_layer");
+    }
+
+    public static void b4dGlobalImageGenerateMipmaps(MemoryAddress image) {
+        try {
+            B4D_GLOBAL_IMAGE_GENERATE_MIPMAPS_HANDLE.invoke(image);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_generate_mipmaps", e);
+        }
+        checkLastError("b4d_global_image_generate_mipmaps");
+    }
+
+    public static void b4dGlobalImageSetUploadPriority(MemoryAddress image, int priority) {
+        try {
+            B4D_GLOBAL_IMAGE_SET_UPLOAD_PRIORITY_HANDLE.invoke(image, priority);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_set_upload_priority", e);
+        }
+        checkLastError("b4d_global_image_set_upload_priority");
+    }
+
+    public static boolean b4dGlobalImageIsStale(MemoryAddress image) {
+        try {
+            return ((int) B4D_GLOBAL_IMAGE_IS_STALE_HANDLE.invoke(image)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_is_stale", e);
+        }
+        checkLastError("b4d_global_image_is_stale");
+    }
+
+    public static void b4dGlobalImageMarkCurrent(MemoryAddress image) {
+        try {
+            B4D_GLOBAL_IMAGE_MARK_CURRENT_HANDLE.invoke(image);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_mark_current", e);
+        }
+        checkLastError("b4d_global_image_mark_current");
+    }
+
+    public static long b4dBumpResourceGeneration(MemoryAddress b4d) {
+        long result;
+        try {
+            result = (long) B4D_BUMP_RESOURCE_GENERATION_HANDLE.invoke(b4d);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_bump_resource_generation", e);
+        }
+        checkLastError("b4d_bump_resource_generation");
+        return result;
+    }
+
+    public static MemoryAddress b4dCreateGlobalImageSparse(MemoryAddress b4d, int width, int height, int mipLevels, int layerCount, int atlasColumns, int atlasRows, int format) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_GLOBAL_IMAGE_SPARSE_HANDLE.invoke(b4d, width, height, mipLevels, layerCount, atlasColumns, atlasRows, format);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_global_image_sparse", e);
+        }
+        checkLastError("b4d_create_global_image_sparse");
+        return result;
+    }
+
+    public static void b4dGlobalImageTouchRegion(MemoryAddress image, int x, int y, int width, int height) {
+        try {
+            B4D_GLOBAL_IMAGE_TOUCH_REGION_HANDLE.invoke(image, x, y, width, height);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_touch_region", e);
+        }
+        checkLastError("b4d_global_image_touch_region");
+    }
+
+    public static void b4dGlobalImageTouchLayerRegion(MemoryAddress image, int layer, int x, int y, int width, int height) {
+        try {
+            B4D_GLOBAL_IMAGE_TOUCH_LAYER_REGION_HANDLE.invoke(image, layer, x, y, width, height);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_touch_layer_region", e);
+        }
+        checkLastError("b4d_global_image_touch_layer_region");
+    }
+
+    public static void b4dGlobalImageSetResidencyBudget(MemoryAddress image, long budget) {
+        try {
+            B4D_GLOBAL_IMAGE_SET_RESIDENCY_BUDGET_HANDLE.invoke(image, budget);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_set_residency_budget", e);
+        }
+        checkLastError("b4d_global_image_set_residency_budget");
+    }
+
+    public static boolean b4dGlobalImageGetResidencyStats(MemoryAddress image, MemoryAddress stats) {
+        try {
+            return ((int) B4D_GLOBAL_IMAGE_GET_RESIDENCY_STATS_HANDLE.invoke(image, stats)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_get_residency_stats", e);
+        }
+        checkLastError("b4d_global_image_get_residency_stats");
+    }
+
+    public static void b4dGlobalImageGetUsageStats(MemoryAddress image, MemoryAddress stats) {
+        try {
+            B4D_GLOBAL_IMAGE_GET_USAGE_STATS_HANDLE.invoke(image, stats);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_global_image_get_usage_stats", e);
+        }
+        checkLastError("b4d_global_image_get_usage_stats");
+    }
+
+    public static void b4dDestroyGlobalImage(MemoryAddress image) {
+        try {
+            B4D_DESTROY_GLOBAL_IMAGE_HANDLE.invoke(image);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy_global_image", e);
+        }
+        checkLastError("b4d_destroy_global_image");
+    }
+
+    public static long b4dCreateShader(MemoryAddress b4d, MemoryAddress vertexFormat, long usedUniforms) {
+        long result;
+        try {
+            result = (long) B4D_CREATE_SHADER_HANDLE.invoke(b4d, vertexFormat, usedUniforms);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_shader", e);
+        }
+        checkLastError("b4d_create_shader");
+        return result;
+    }
+
+    public static void b4dDestroyShader(MemoryAddress b4d, long shaderId) {
+        try {
+            B4D_DESTROY_SHADER_HANDLE.invoke(b4d, shaderId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy_shader", e);
+        }
+        checkLastError("b4d_destroy_shader");
+    }
+
+    public static long b4dRegisterRenderLayer(MemoryAddress b4d, MemoryAddress name, boolean depthWriteEnable, boolean translucent, float alphaCutout, boolean mipmap, boolean affectsCrumbling) {
+        long result;
+        try {
+            result = (long) B4D_REGISTER_RENDER_LAYER_HANDLE.invoke(b4d, name, depthWriteEnable ? 1 : 0, translucent ? 1 : 0, alphaCutout, mipmap ? 1 : 0, affectsCrumbling ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_register_render_layer", e);
+        }
+        checkLastError("b4d_register_render_layer");
+        return result;
+    }
+
+    public static void b4dUnregisterRenderLayer(MemoryAddress b4d, long layerId) {
+        try {
+            B4D_UNREGISTER_RENDER_LAYER_HANDLE.invoke(b4d, layerId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_unregister_render_layer", e);
+        }
+        checkLastError("b4d_unregister_render_layer");
+    }
+
+    public static long b4dFindRenderLayer(MemoryAddress b4d, MemoryAddress name) {
+        long result;
+        try {
+            result = (long) B4D_FIND_RENDER_LAYER_HANDLE.invoke(b4d, name);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_find_render_layer", e);
+        }
+        checkLastError("b4d_find_render_layer");
+        return result;
+    }
+
+    public static boolean b4dSetRenderOrder(MemoryAddress b4d, MemoryAddress layerIds, MemoryAddress points, int count) {
+        try {
+            return ((int) B4D_SET_RENDER_ORDER_HANDLE.invoke(b4d, layerIds, points, count)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_render_order", e);
+        }
+        checkLastError("b4d_set_render_order");
+    }
+
+    public static boolean b4dInsertRenderLayer(MemoryAddress b4d, long layerId, int point) {
+        try {
+            return ((int) B4D_INSERT_RENDER_LAYER_HANDLE.invoke(b4d, layerId, point)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_insert_render_layer", e);
+        }
+        checkLastError("b4d_insert_render_layer");
+    }
+
+    public static boolean b4dSetLayerEnabled(MemoryAddress b4d, long layerId, boolean enabled) {
+        try {
+            return ((int) B4D_SET_LAYER_ENABLED_HANDLE.invoke(b4d, layerId, enabled ? 1 : 0)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_layer_enabled", e);
+        }
+        checkLastError("b4d_set_layer_enabled");
+    }
+
+    public static void b4dIsolateLayer(MemoryAddress b4d, long layerId) {
+        try {
+            B4D_ISOLATE_LAYER_HANDLE.invoke(b4d, layerId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_isolate_layer", e);
+        }
+        checkLastError("b4d_isolate_layer");
+    }
+
+    public static int b4dGetFrameTagStats(MemoryAddress b4d, MemoryAddress stats, int capacity) {
+        int result;
+        try {
+            result = (int) B4D_GET_FRAME_TAG_STATS_HANDLE.invoke(b4d, stats, capacity);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_frame_tag_stats", e);
+        }
+        checkLastError("b4d_get_frame_tag_stats");
+        return result;
+    }
+
+    public static void b4dSetImmediateUploadLimit(MemoryAddress b4d, int limit) {
+        try {
+            B4D_SET_IMMEDIATE_UPLOAD_LIMIT_HANDLE.invoke(b4d, limit);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_immediate_upload_limit", e);
+        }
+        checkLastError("b4d_set_immediate_upload_limit");
+    }
+
+    public static void b4dSetLayerDrawLimit(MemoryAddress b4d, long layerId, boolean enable, int limit) {
+        try {
+            B4D_SET_LAYER_DRAW_LIMIT_HANDLE.invoke(b4d, layerId, enable ? 1 : 0, limit);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_set_layer_draw_limit", e);
+        }
+        checkLastError("b4d_set_layer_draw_limit");
+    }
+
+    public static void b4dGetFrameBudgetReport(MemoryAddress b4d, MemoryAddress report) {
+        try {
+            B4D_GET_FRAME_BUDGET_REPORT_HANDLE.invoke(b4d, report);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_frame_budget_report", e);
+        }
+        checkLastError("b4d_get_frame_budget_report");
+    }
+
+    public static MemoryAddress b4dRetainCachedMeshes(MemoryAddress b4d) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_RETAIN_CACHED_MESHES_HANDLE.invoke(b4d);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_retain_cached_meshes", e);
+        }
+        checkLastError("b4d_retain_cached_meshes");
+        return result;
+    }
+
+    public static int b4dReleaseCachedMeshes(MemoryAddress retention) {
+        int result;
+        try {
+            result = (int) B4D_RELEASE_CACHED_MESHES_HANDLE.invoke(retention);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_release_cached_meshes", e);
+        }
+        checkLastError("b4d_release_cached_meshes");
+        return result;
+    }
+
+    public static boolean b4dSaveState(MemoryAddress b4d, MemoryAddress path) {
+        try {
+            return ((int) B4D_SAVE_STATE_HANDLE.invoke(b4d, path)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_save_state", e);
+        }
+        checkLastError("b4d_save_state");
+    }
+
+    public static boolean b4dLoadState(MemoryAddress b4d, MemoryAddress path) {
+        try {
+            return ((int) B4D_LOAD_STATE_HANDLE.invoke(b4d, path)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_load_state", e);
+        }
+        checkLastError("b4d_load_state");
+    }
+
+    public static int b4dGetFrameLayerStats(MemoryAddress b4d, MemoryAddress stats, int capacity) {
+        int result;
+        try {
+            result = (int) B4D_GET_FRAME_LAYER_STATS_HANDLE.invoke(b4d, stats, capacity);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_frame_layer_stats", e);
+        }
+        checkLastError("b4d_get_frame_layer_stats");
+        return result;
+    }
+
+    public static void b4dGetFrameTimeReport(MemoryAddress b4d, MemoryAddress report) {
+        try {
+            B4D_GET_FRAME_TIME_REPORT_HANDLE.invoke(b4d, report);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_frame_time_report", e);
+        }
+        checkLastError("b4d_get_frame_time_report");
+    }
+
+    public static int b4dGetFrameTimeHistogram(MemoryAddress b4d, boolean gpu, MemoryAddress buckets, int capacity) {
+        int result;
+        try {
+            result = (int) B4D_GET_FRAME_TIME_HISTOGRAM_HANDLE.invoke(b4d, gpu ? 1 : 0, buckets, capacity);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_get_frame_time_histogram", e);
+        }
+        checkLastError("b4d_get_frame_time_histogram");
+        return result;
+    }
+
+    public static long b4dCreateShaderWithFormat(MemoryAddress b4d, long vertexFormatId, long usedUniforms) {
+        long result;
+        try {
+            result = (long) B4D_CREATE_SHADER_WITH_FORMAT_HANDLE.invoke(b4d, vertexFormatId, usedUniforms);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_shader_with_format", e);
+        }
+        checkLastError("b4d_create_shader_with_format");
+        return result;
+    }
+
+    public static long b4dCreateShaderSpecialized(MemoryAddress b4d, long vertexFormatId, long usedUniforms, int fogMode, float alphaTestThreshold, int alphaMode, boolean lightmapEnable, int polygonMode, boolean primitiveRestart, boolean emissive, int emissiveImage, boolean itemAnimation) {
+        long result;
+        try {
+            result = (long) B4D_CREATE_SHADER_SPECIALIZED_HANDLE.invoke(b4d, vertexFormatId, usedUniforms, fogMode, alphaTestThreshold, alphaMode, lightmapEnable ? 1 : 0, polygonMode, primitiveRestart ? 1 : 0, emissive ? 1 : 0, emissiveImage, itemAnimation ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_shader_specialized", e);
+        }
+        checkLastError("b4d_create_shader_specialized");
+        return result;
+    }
+
+    public static long b4dRegisterVertexFormat(MemoryAddress b4d, MemoryAddress vertexFormat) {
+        long result;
+        try {
+            result = (long) B4D_REGISTER_VERTEX_FORMAT_HANDLE.invoke(b4d, vertexFormat);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_register_vertex_format", e);
+        }
+        checkLastError("b4d_register_vertex_format");
+        return result;
+    }
 
-        B4D_SET_DEBUG_MODE_HANDLE = lookupFunction("b4d_set_debug_mode",
-                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT)
-        );
+    public static void b4dUnregisterVertexFormat(MemoryAddress b4d, long vertexFormatId) {
+        try {
+            B4D_UNREGISTER_VERTEX_FORMAT_HANDLE.invoke(b4d, vertexFormatId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_unregister_vertex_format", e);
+        }
+        checkLastError("b4d_unregister_vertex_format");
+    }
 
-        B4D_CREATE_GLOBAL_MESH_HANDLE = lookupFunction("b4d_create_global_mesh",
-                FunctionDescriptor.of(ADDRESS, ADDRESS, ADDRESS)
-        );
+    public static MemoryAddress b4dStartFrame(MemoryAddress b4d, int windowWidth, int windowHeight) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_START_FRAME_HANDLE.invoke(b4d, windowWidth, windowHeight);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_start_frame", e);
+        }
+        checkLastError("b4d_start_frame");
+        return result;
+    }
 
-        B4D_DESTROY_GLOBAL_MESH_HANDLE = lookupFunction("b4d_destroy_global_mesh",
-                FunctionDescriptor.ofVoid(ADDRESS)
-        );
+    public static MemoryAddress b4dStartFrameScaled(MemoryAddress b4d, int logicalWidth, int logicalHeight, float scaleFactor) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_START_FRAME_SCALED_HANDLE.invoke(b4d, logicalWidth, logicalHeight, scaleFactor);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_start_frame_scaled", e);
+        }
+        checkLastError("b4d_start_frame_scaled");
+        return result;
+    }
 
-        B4D_CREATE_GLOBAL_IMAGE_HANDLE = lookupFunction("b4d_create_global_image",
-                FunctionDescriptor.of(ADDRESS, JAVA_INT, JAVA_INT, JAVA_INT)
-        );
+    public static MemoryAddress b4dCaptureProbe(MemoryAddress b4d, float x, float y, float z, int resolution, int facesPerFrame, Addressable callback) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CAPTURE_PROBE_HANDLE.invoke(b4d, x, y, z, resolution, facesPerFrame, callback, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_capture_probe", e);
+        }
+        checkLastError("b4d_capture_probe");
+        return result;
+    }
 
-        B4D_UPDATE_GLOBAL_IMAGE_HANDLE = lookupFunction("b4d_update_global_image",
-                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_INT)
-        );
+    public static int b4dProbeNextFaces(MemoryAddress capture, MemoryAddress faces) {
+        int result;
+        try {
+            result = (int) B4D_PROBE_NEXT_FACES_HANDLE.invoke(capture, faces);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_probe_next_faces", e);
+        }
+        checkLastError("b4d_probe_next_faces");
+        return result;
+    }
 
-        B4D_DESTROY_GLOBAL_IMAGE_HANDLE = lookupFunction("b4d_destroy_global_image",
-                FunctionDescriptor.ofVoid(ADDRESS)
-        );
+    public static MemoryAddress b4dProbeStartFace(MemoryAddress capture, int face) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_PROBE_START_FACE_HANDLE.invoke(capture, face);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_probe_start_face", e);
+        }
+        checkLastError("b4d_probe_start_face");
+        return result;
+    }
 
-        B4D_CREATE_SHADER_HANDLE = lookupFunction("b4d_create_shader",
-                FunctionDescriptor.of(JAVA_LONG, ADDRESS, ADDRESS, JAVA_LONG)
-        );
+    public static void b4dProbeFinish(MemoryAddress capture) {
+        try {
+            B4D_PROBE_FINISH_HANDLE.invoke(capture);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_probe_finish", e);
+        }
+        checkLastError("b4d_probe_finish");
+    }
 
-        B4D_DESTROY_SHADER_HANDLE = lookupFunction("b4d_destroy_shader",
-                FunctionDescriptor.ofVoid(ADDRESS, JAVA_LONG)
-        );
+    public static MemoryAddress b4dCapturePanorama(MemoryAddress b4d, int resolution, Addressable callback) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CAPTURE_PANORAMA_HANDLE.invoke(b4d, resolution, callback, MemoryAddress.NULL);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_capture_panorama", e);
+        }
+        checkLastError("b4d_capture_panorama");
+        return result;
+    }
 
-        B4D_START_FRAME_HANDLE = lookupFunction("b4d_start_frame",
-                FunctionDescriptor.of(ADDRESS, ADDRESS, JAVA_INT, JAVA_INT)
-        );
+    public static MemoryAddress b4dPanoramaNextFace(MemoryAddress capture, MemoryAddress face) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_PANORAMA_NEXT_FACE_HANDLE.invoke(capture, face);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_panorama_next_face", e);
+        }
+        checkLastError("b4d_panorama_next_face");
+        return result;
+    }
 
-        B4D_PASS_UPDATE_UNIFORM_HANDLE = lookupFunction("b4d_pass_update_uniform",
-                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG)
-        );
+    public static void b4dPanoramaFinish(MemoryAddress capture) {
+        try {
+            B4D_PANORAMA_FINISH_HANDLE.invoke(capture);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_panorama_finish", e);
+        }
+        checkLastError("b4d_panorama_finish");
+    }
 
-        B4D_PASS_DRAW_GLOBAL_HANDLE = lookupFunction("b4d_pass_draw_global",
-                FunctionDescriptor.ofVoid(ADDRESS, ADDRESS, JAVA_LONG, JAVA_INT)
-        );
+    public static void b4dPassSetPartialTick(MemoryAddress frame, float partialTick) {
+        try {
+            B4D_PASS_SET_PARTIAL_TICK_HANDLE.invoke(frame, partialTick);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_set_partial_tick", e);
+        }
+        checkLastError("b4d_pass_set_partial_tick");
+    }
 
-        B4D_PASS_UPLOAD_IMMEDIATE_HANDLE = lookupFunction("b4d_pass_upload_immediate",
-                FunctionDescriptor.of(JAVA_INT, ADDRESS, ADDRESS)
-        );
+    public static void b4dPassSetViewport(MemoryAddress frame, int index, float x, float y, float width, float height, float minDepth, float maxDepth) {
+        try {
+            B4D_PASS_SET_VIEWPORT_HANDLE.invoke(frame, index, x, y, width, height, minDepth, maxDepth);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_set_viewport", e);
+        }
+        checkLastError("b4d_pass_set_viewport");
+    }
 
-        B4D_PASS_DRAW_IMMEDIATE_HANDLE = lookupFunction("b4d_pass_draw_immediate",
-                FunctionDescriptor.ofVoid(ADDRESS, JAVA_INT, JAVA_LONG, JAVA_INT)
-        );
+    public static void b4dPassSetRenderLayer(MemoryAddress frame, long layerId) {
+        try {
+            B4D_PASS_SET_RENDER_LAYER_HANDLE.invoke(frame, layerId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_set_render_layer", e);
+        }
+        checkLastError("b4d_pass_set_render_layer");
+    }
 
-        B4D_END_FRAME_HANDLE = lookupFunction("b4d_end_frame",
-                FunctionDescriptor.ofVoid(ADDRESS)
-        );
+    public static boolean b4dPassGetLayerStats(MemoryAddress frame, long layerId, MemoryAddress drawCount, MemoryAddress indexCount) {
+        try {
+            return ((int) B4D_PASS_GET_LAYER_STATS_HANDLE.invoke(frame, layerId, drawCount, indexCount)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_get_layer_stats", e);
+        }
+        checkLastError("b4d_pass_get_layer_stats");
     }
 
-    public static MemoryAddress b4dCreateGlfwSurfaceProvider(long glfwWindow) {
-        MemoryAddress pfnGlfwGetRequiredInstanceExtensions = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwGetRequiredInstanceExtensions"));
-        MemoryAddress pfnGlfwCreateWindowSurface = MemoryAddress.ofLong(APIUtil.apiGetFunctionAddress(GLFW.getLibrary(), "glfwCreateWindowSurface"));
+    public static void b4dPassSetViewportIndex(MemoryAddress frame, int index) {
         try {
-            return (MemoryAddress) B4D_CREATE_GLFW_SURFACE_PROVIDER_HANDLE.invoke(MemoryAddress.ofLong(glfwWindow), pfnGlfwGetRequiredInstanceExtensions, pfnGlfwCreateWindowSurface);
+            B4D_PASS_SET_VIEWPORT_INDEX_HANDLE.invoke(frame, index);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_create_glfw_surface_provider", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_viewport_index", e);
         }
+        checkLastError("b4d_pass_set_viewport_index");
     }
 
-    public static MemoryAddress b4dInit(MemoryAddress surface, boolean enableValidation) {
-        int enableValidationInt = enableValidation ? 1 : 0;
+    public static void b4dPassSetShadowCamera(MemoryAddress frame, MemoryAddress view, float fovY, float aspect, float near, float far, float lightX, float lightY, float lightZ) {
         try {
-            return (MemoryAddress) B4D_INIT_HANDLE.invoke(surface, enableValidationInt);
+            B4D_PASS_SET_SHADOW_CAMERA_HANDLE.invoke(frame, view, fovY, aspect, near, far, lightX, lightY, lightZ);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_init", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_shadow_camera", e);
         }
+        checkLastError("b4d_pass_set_shadow_camera");
     }
 
-    public static void b4dDestroy(MemoryAddress b4d) {
+    public static void b4dPassSetScissor(MemoryAddress frame, boolean enable, int x, int y, int width, int height) {
         try {
-            B4D_DESTROY_HANDLE.invoke(b4d);
+            B4D_PASS_SET_SCISSOR_HANDLE.invoke(frame, enable ? 1 : 0, x, y, width, height);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_destroy", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_scissor", e);
         }
+        checkLastError("b4d_pass_set_scissor");
     }
 
-    public static void b4dSetDebugMode(MemoryAddress b4d, int debugMode) {
+    public static void b4dPassBeginGuiItem(MemoryAddress frame, float x, float y, float size, float minDepth, float maxDepth) {
         try {
-            B4D_SET_DEBUG_MODE_HANDLE.invoke(b4d, debugMode);
+            B4D_PASS_BEGIN_GUI_ITEM_HANDLE.invoke(frame, x, y, size, minDepth, maxDepth);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_set_debug_mode", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_begin_gui_item", e);
         }
+        checkLastError("b4d_pass_begin_gui_item");
     }
 
-    public static MemoryAddress b4dCreateGlobalMesh(MemoryAddress b4d, MemoryAddress meshData) {
+    public static void b4dPassEndGuiItem(MemoryAddress frame) {
         try {
-            return (MemoryAddress) B4D_CREATE_GLOBAL_MESH_HANDLE.invoke(b4d, meshData);
+            B4D_PASS_END_GUI_ITEM_HANDLE.invoke(frame);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_create_global_mesh", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_end_gui_item", e);
         }
+        checkLastError("b4d_pass_end_gui_item");
     }
 
-    public static void b4dDestroyGlobalMesh(MemoryAddress mesh) {
+    public static void b4dGetGuiItemProjection(MemoryAddress matrix) {
         try {
-            B4D_DESTROY_GLOBAL_MESH_HANDLE.invoke(mesh);
+            B4D_GET_GUI_ITEM_PROJECTION_HANDLE.invoke(matrix);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_destroy_global_mesh", e);
+            throw new RuntimeException("Failed to invoke b4d_get_gui_item_projection", e);
         }
+        checkLastError("b4d_get_gui_item_projection");
     }
 
-    public static MemoryAddress b4dCreateGlobalImage(MemoryAddress b4d, int width, int height, int format) {
+    public static void b4dPassSetViewMatrices(MemoryAddress frame, MemoryAddress modelView, MemoryAddress projection) {
         try {
-            return (MemoryAddress) B4D_CREATE_GLOBAL_IMAGE_HANDLE.invoke(b4d, width, height, format);
+            B4D_PASS_SET_VIEW_MATRICES_HANDLE.invoke(frame, modelView, projection);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_create_global_image", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_view_matrices", e);
         }
+        checkLastError("b4d_pass_set_view_matrices");
     }
 
-    public static void b4DUpdateGlobalImage(MemoryAddress image, MemoryAddress data, int dataCount) {
+    public static void b4dPassBeginPortal(MemoryAddress frame, MemoryAddress modelView, MemoryAddress projection, int maskId, long shaderId) {
         try {
-            B4D_UPDATE_GLOBAL_IMAGE_HANDLE.invoke(image, data, dataCount);
+            B4D_PASS_BEGIN_PORTAL_HANDLE.invoke(frame, modelView, projection, maskId, shaderId);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_update_global_image", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_begin_portal", e);
         }
+        checkLastError("b4d_pass_begin_portal");
     }
 
-    public static void b4dDestroyGlobalImage(MemoryAddress image) {
+    public static void b4dPassEndPortal(MemoryAddress frame) {
         try {
-            B4D_DESTROY_GLOBAL_IMAGE_HANDLE.invoke(image);
+            B4D_PASS_END_PORTAL_HANDLE.invoke(frame);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_destroy_global_image", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_end_portal", e);
         }
+        checkLastError("b4d_pass_end_portal");
     }
 
-    public static long b4dCreateShader(MemoryAddress b4d, MemoryAddress vertexFormat, long usedUniforms) {
+    public static void b4dPassSetDrawTag(MemoryAddress frame, boolean enable, long tag) {
         try {
-            return (long) B4D_CREATE_SHADER_HANDLE.invoke(b4d, vertexFormat, usedUniforms);
+            B4D_PASS_SET_DRAW_TAG_HANDLE.invoke(frame, enable ? 1 : 0, tag);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_create_shader", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_draw_tag", e);
         }
+        checkLastError("b4d_pass_set_draw_tag");
     }
 
-    public static void b4dDestroyShader(MemoryAddress b4d, long shaderId) {
+    public static void b4dPassSetDrawPriority(MemoryAddress frame, int priority) {
         try {
-            B4D_DESTROY_SHADER_HANDLE.invoke(b4d, shaderId);
+            B4D_PASS_SET_DRAW_PRIORITY_HANDLE.invoke(frame, priority);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_destroy_shader", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_draw_priority", e);
         }
+        checkLastError("b4d_pass_set_draw_priority");
     }
 
-    public static MemoryAddress b4dStartFrame(MemoryAddress b4d, int windowWidth, int windowHeight) {
+    public static void b4dPassSetCameraEffectsEnabled(MemoryAddress frame, boolean enable) {
         try {
-            return (MemoryAddress) B4D_START_FRAME_HANDLE.invoke(b4d, windowWidth, windowHeight);
+            B4D_PASS_SET_CAMERA_EFFECTS_ENABLED_HANDLE.invoke(frame, enable ? 1 : 0);
         } catch (Throwable e) {
-            throw new RuntimeException("Failed to invoke b4d_start_frame", e);
+            throw new RuntimeException("Failed to invoke b4d_pass_set_camera_effects_enabled", e);
+        }
+        checkLastError("b4d_pass_set_camera_effects_enabled");
+    }
+
+    public static void b4dPassApplyCameraEffectsToView(MemoryAddress frame, MemoryAddress view) {
+        try {
+            B4D_PASS_APPLY_CAMERA_EFFECTS_TO_VIEW_HANDLE.invoke(frame, view);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_apply_camera_effects_to_view", e);
         }
+        checkLastError("b4d_pass_apply_camera_effects_to_view");
     }
 
     public static void b4dPassUpdateUniform(MemoryAddress frame, MemoryAddress data, long shaderId) {
@@ -226,6 +2064,16 @@ public class Natives {
         } catch (Throwable e) {
             throw new RuntimeException("Failed to invoke b4d_pass_update_uniform", e);
         }
+        checkLastError("b4d_pass_update_uniform");
+    }
+
+    public static void b4dPassUpdateEnvironmentProbe(MemoryAddress frame, int index, MemoryAddress image, long shaderId) {
+        try {
+            B4D_PASS_UPDATE_ENVIRONMENT_PROBE_HANDLE.invoke(frame, index, image, shaderId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_update_environment_probe", e);
+        }
+        checkLastError("b4d_pass_update_environment_probe");
     }
 
     public static void b4dPassDrawGlobal(MemoryAddress frame, MemoryAddress mesh, long shaderId, boolean depthWrite) {
@@ -240,14 +2088,18 @@ public class Natives {
         } catch (Throwable e) {
             throw new RuntimeException("Failed to invoke b4d_pass_draw_global", e);
         }
+        checkLastError("b4d_pass_draw_global");
     }
 
     public static int b4dPassUploadImmediate(MemoryAddress frame, MemoryAddress data) {
+        int result;
         try {
-            return (int) B4D_PASS_UPLOAD_IMMEDIATE_HANDLE.invoke(frame, data);
+            result = (int) B4D_PASS_UPLOAD_IMMEDIATE_HANDLE.invoke(frame, data);
         } catch (Throwable e) {
             throw new RuntimeException("Failed to invoke b4d_pass_upload_immediate", e);
         }
+        checkLastError("b4d_pass_upload_immediate");
+        return result;
     }
 
     public static void b4dPassDrawImmediate(MemoryAddress frame, int meshId, long shaderId, boolean depthWrite) {
@@ -262,6 +2114,85 @@ public class Natives {
         } catch (Throwable e) {
             throw new RuntimeException("Failed to invoke b4d_pass_draw_immediate", e);
         }
+        checkLastError("b4d_pass_draw_immediate");
+    }
+
+    public static void b4dPassDrawSmall(MemoryAddress frame, MemoryAddress data, MemoryAddress transform, long shaderId, boolean depthWrite) {
+        int depthWriteInt;
+        if (depthWrite) {
+            depthWriteInt = 1;
+        } else {
+            depthWriteInt = 0;
+        }
+        try {
+            B4D_PASS_DRAW_SMALL_HANDLE.invoke(frame, data, transform, shaderId, depthWriteInt);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_draw_small", e);
+        }
+        checkLastError("b4d_pass_draw_small");
+    }
+
+    public static void b4dPassDrawTexturedRects(MemoryAddress frame, MemoryAddress image, MemoryAddress samplerInfo, MemoryAddress rects, int rectCount, float depth, long shaderId, boolean depthWrite) {
+        try {
+            B4D_PASS_DRAW_TEXTURED_RECTS_HANDLE.invoke(frame, image, samplerInfo, rects, rectCount, depth, shaderId, depthWrite ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_draw_textured_rects", e);
+        }
+        checkLastError("b4d_pass_draw_textured_rects");
+    }
+
+    public static void b4dPassDrawNineSlices(MemoryAddress frame, MemoryAddress image, MemoryAddress samplerInfo, MemoryAddress panels, int panelCount, float depth, long shaderId, boolean depthWrite) {
+        try {
+            B4D_PASS_DRAW_NINE_SLICES_HANDLE.invoke(frame, image, samplerInfo, panels, panelCount, depth, shaderId, depthWrite ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_draw_nine_slices", e);
+        }
+        checkLastError("b4d_pass_draw_nine_slices");
+    }
+
+    public static void b4dPassDrawBeams(MemoryAddress frame, MemoryAddress mesh, MemoryAddress beams, int beamCount, long shaderId, boolean depthWrite) {
+        try {
+            B4D_PASS_DRAW_BEAMS_HANDLE.invoke(frame, mesh, beams, beamCount, shaderId, depthWrite ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_draw_beams", e);
+        }
+        checkLastError("b4d_pass_draw_beams");
+    }
+
+    public static void b4dPassDrawBlobShadows(MemoryAddress frame, MemoryAddress shadows, int shadowCount, MemoryAddress config, long shaderId) {
+        try {
+            B4D_PASS_DRAW_BLOB_SHADOWS_HANDLE.invoke(frame, shadows, shadowCount, config, shaderId);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_draw_blob_shadows", e);
+        }
+        checkLastError("b4d_pass_draw_blob_shadows");
+    }
+
+    public static void b4dPassSetSmallDrawMerging(MemoryAddress frame, boolean enable) {
+        try {
+            B4D_PASS_SET_SMALL_DRAW_MERGING_HANDLE.invoke(frame, enable ? 1 : 0);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_set_small_draw_merging", e);
+        }
+        checkLastError("b4d_pass_set_small_draw_merging");
+    }
+
+    public static void b4dPassStartCommandLog(MemoryAddress frame) {
+        try {
+            B4D_PASS_START_COMMAND_LOG_HANDLE.invoke(frame);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_start_command_log", e);
+        }
+        checkLastError("b4d_pass_start_command_log");
+    }
+
+    public static boolean b4dPassSaveCommandLog(MemoryAddress frame, MemoryAddress path) {
+        try {
+            return ((int) B4D_PASS_SAVE_COMMAND_LOG_HANDLE.invoke(frame, path)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_save_command_log", e);
+        }
+        checkLastError("b4d_pass_save_command_log");
     }
 
     public static void b4dEndFrame(MemoryAddress frame) {
@@ -270,6 +2201,134 @@ public class Natives {
         } catch (Throwable e) {
             throw new RuntimeException("Failed to invoke b4d_end_frame", e);
         }
+        checkLastError("b4d_end_frame");
+    }
+
+    /**
+     * Throws if the last call on this thread was rejected by the natives because of invalid arguments.
+     * Rejected calls return null or 0.
+     */
+    private static void checkLastError(String function) {
+        int error;
+        try {
+            error = (int) B4D_TAKE_LAST_ERROR_HANDLE.invoke();
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_take_last_error", e);
+        }
+        if (error != 0) {
+            throw new IllegalArgumentException("Blaze4D natives rejected call to " + function + " with error code " + error);
+        }
+    }
+
+    public static MemoryAddress b4dCreateDrawRing(MemoryAddress memory, long len) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_DRAW_RING_HANDLE.invoke(memory, len);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_draw_ring", e);
+        }
+        checkLastError("b4d_create_draw_ring");
+        return result;
+    }
+
+    public static void b4dDestroyDrawRing(MemoryAddress ring) {
+        try {
+            B4D_DESTROY_DRAW_RING_HANDLE.invoke(ring);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy_draw_ring", e);
+        }
+        checkLastError("b4d_destroy_draw_ring");
+    }
+
+    public static MemoryAddress b4dCreateMapTextureCache(MemoryAddress b4d) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_CREATE_MAP_TEXTURE_CACHE_HANDLE.invoke(b4d);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_create_map_texture_cache", e);
+        }
+        checkLastError("b4d_create_map_texture_cache");
+        return result;
+    }
+
+    public static void b4dDestroyMapTextureCache(MemoryAddress cache) {
+        try {
+            B4D_DESTROY_MAP_TEXTURE_CACHE_HANDLE.invoke(cache);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_destroy_map_texture_cache", e);
+        }
+        checkLastError("b4d_destroy_map_texture_cache");
+    }
+
+    public static void b4dMapCacheUpdate(MemoryAddress cache, int mapId, MemoryAddress data, long len) {
+        try {
+            B4D_MAP_CACHE_UPDATE_HANDLE.invoke(cache, mapId, data, len);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_map_cache_update", e);
+        }
+        checkLastError("b4d_map_cache_update");
+    }
+
+    public static int b4dMapCacheFlush(MemoryAddress cache) {
+        int result;
+        try {
+            result = (int) B4D_MAP_CACHE_FLUSH_HANDLE.invoke(cache);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_map_cache_flush", e);
+        }
+        checkLastError("b4d_map_cache_flush");
+        return result;
+    }
+
+    public static boolean b4dMapCacheGet(MemoryAddress cache, int mapId, MemoryAddress page, MemoryAddress uvRect) {
+        try {
+            return ((int) B4D_MAP_CACHE_GET_HANDLE.invoke(cache, mapId, page, uvRect)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_map_cache_get", e);
+        }
+        checkLastError("b4d_map_cache_get");
+    }
+
+    public static MemoryAddress b4dMapCacheGetPage(MemoryAddress cache, int page) {
+        MemoryAddress result;
+        try {
+            result = (MemoryAddress) B4D_MAP_CACHE_GET_PAGE_HANDLE.invoke(cache, page);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_map_cache_get_page", e);
+        }
+        checkLastError("b4d_map_cache_get_page");
+        return result;
+    }
+
+    public static boolean b4dMapCacheRemove(MemoryAddress cache, int mapId) {
+        try {
+            return ((int) B4D_MAP_CACHE_REMOVE_HANDLE.invoke(cache, mapId)) != 0;
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_map_cache_remove", e);
+        }
+        checkLastError("b4d_map_cache_remove");
+    }
+
+    public static int b4dMapCacheEvictIdle(MemoryAddress cache, long maxIdleFlushes) {
+        int result;
+        try {
+            result = (int) B4D_MAP_CACHE_EVICT_IDLE_HANDLE.invoke(cache, maxIdleFlushes);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_map_cache_evict_idle", e);
+        }
+        checkLastError("b4d_map_cache_evict_idle");
+        return result;
+    }
+
+    public static int b4dPassConsumeDrawRing(MemoryAddress frame, MemoryAddress ring) {
+        int result;
+        try {
+            result = (int) B4D_PASS_CONSUME_DRAW_RING_HANDLE.invoke(frame, ring);
+        } catch (Throwable e) {
+            throw new RuntimeException("Failed to invoke b4d_pass_consume_draw_ring", e);
+        }
+        checkLastError("b4d_pass_consume_draw_ring");
+        return result;
     }
 
     public record NativeMetadata(int sizeBytes) {
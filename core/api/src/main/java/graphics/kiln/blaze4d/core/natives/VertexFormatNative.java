@@ -21,11 +21,15 @@ public class VertexFormatNative {
     public static final MemoryLayout.PathElement UV1_FORMAT_PATH;
     public static final MemoryLayout.PathElement UV2_OFFSET_PATH;
     public static final MemoryLayout.PathElement UV2_FORMAT_PATH;
+    public static final MemoryLayout.PathElement NORMAL_ENCODING_PATH;
+    public static final MemoryLayout.PathElement POSITION_QUANTIZATION_OFFSET_PATH;
+    public static final MemoryLayout.PathElement POSITION_QUANTIZATION_SCALE_PATH;
     public static final MemoryLayout.PathElement HAS_NORMAL_PATH;
     public static final MemoryLayout.PathElement HAS_COLOR_PATH;
     public static final MemoryLayout.PathElement HAS_UV0_PATH;
     public static final MemoryLayout.PathElement HAS_UV1_PATH;
     public static final MemoryLayout.PathElement HAS_UV2_PATH;
+    public static final MemoryLayout.PathElement HAS_POSITION_QUANTIZATION_PATH;
 
     public static final VarHandle STRIDE_HANDLE;
     public static final VarHandle POSITION_OFFSET_HANDLE;
@@ -40,11 +44,15 @@ public class VertexFormatNative {
     public static final VarHandle UV1_FORMAT_HANDLE;
     public static final VarHandle UV2_OFFSET_HANDLE;
     public static final VarHandle UV2_FORMAT_HANDLE;
+    public static final VarHandle NORMAL_ENCODING_HANDLE;
+    public static final VarHandle POSITION_QUANTIZATION_OFFSET_HANDLE;
+    public static final VarHandle POSITION_QUANTIZATION_SCALE_HANDLE;
     public static final VarHandle HAS_NORMAL_HANDLE;
     public static final VarHandle HAS_COLOR_HANDLE;
     public static final VarHandle HAS_UV0_HANDLE;
     public static final VarHandle HAS_UV1_HANDLE;
     public static final VarHandle HAS_UV2_HANDLE;
+    public static final VarHandle HAS_POSITION_QUANTIZATION_HANDLE;
 
     static {
         LAYOUT = MemoryLayout.structLayout(
@@ -61,11 +69,15 @@ public class VertexFormatNative {
                 ValueLayout.JAVA_INT.withName("uv1_format"),
                 ValueLayout.JAVA_INT.withName("uv2_offset"),
                 ValueLayout.JAVA_INT.withName("uv2_format"),
+                ValueLayout.JAVA_INT.withName("normal_encoding"),
+                MemoryLayout.sequenceLayout(3, ValueLayout.JAVA_FLOAT).withName("position_quantization_offset"),
+                MemoryLayout.sequenceLayout(3, ValueLayout.JAVA_FLOAT).withName("position_quantization_scale"),
                 ValueLayout.JAVA_BOOLEAN.withName("has_normal"),
                 ValueLayout.JAVA_BOOLEAN.withName("has_color"),
                 ValueLayout.JAVA_BOOLEAN.withName("has_uv0"),
                 ValueLayout.JAVA_BOOLEAN.withName("has_uv1"),
-                ValueLayout.JAVA_BOOLEAN.withName("has_uv2")
+                ValueLayout.JAVA_BOOLEAN.withName("has_uv2"),
+                ValueLayout.JAVA_BOOLEAN.withName("has_position_quantization")
         );
 
         STRIDE_PATH = MemoryLayout.PathElement.groupElement("stride");
@@ -81,11 +93,15 @@ public class VertexFormatNative {
         UV1_FORMAT_PATH = MemoryLayout.PathElement.groupElement("uv1_format");
         UV2_OFFSET_PATH = MemoryLayout.PathElement.groupElement("uv2_offset");
         UV2_FORMAT_PATH = MemoryLayout.PathElement.groupElement("uv2_format");
+        NORMAL_ENCODING_PATH = MemoryLayout.PathElement.groupElement("normal_encoding");
+        POSITION_QUANTIZATION_OFFSET_PATH = MemoryLayout.PathElement.groupElement("position_quantization_offset");
+        POSITION_QUANTIZATION_SCALE_PATH = MemoryLayout.PathElement.groupElement("position_quantization_scale");
         HAS_NORMAL_PATH = MemoryLayout.PathElement.groupElement("has_normal");
         HAS_COLOR_PATH = MemoryLayout.PathElement.groupElement("has_color");
         HAS_UV0_PATH = MemoryLayout.PathElement.groupElement("has_uv0");
         HAS_UV1_PATH = MemoryLayout.PathElement.groupElement("has_uv1");
         HAS_UV2_PATH = MemoryLayout.PathElement.groupElement("has_uv2");
+        HAS_POSITION_QUANTIZATION_PATH = MemoryLayout.PathElement.groupElement("has_position_quantization");
 
         STRIDE_HANDLE = LAYOUT.varHandle(STRIDE_PATH);
         POSITION_OFFSET_HANDLE = LAYOUT.varHandle(POSITION_OFFSET_PATH);
@@ -100,10 +116,14 @@ public class VertexFormatNative {
         UV1_FORMAT_HANDLE = LAYOUT.varHandle(UV1_FORMAT_PATH);
         UV2_OFFSET_HANDLE = LAYOUT.varHandle(UV2_OFFSET_PATH);
         UV2_FORMAT_HANDLE = LAYOUT.varHandle(UV2_FORMAT_PATH);
+        NORMAL_ENCODING_HANDLE = LAYOUT.varHandle(NORMAL_ENCODING_PATH);
+        POSITION_QUANTIZATION_OFFSET_HANDLE = LAYOUT.varHandle(POSITION_QUANTIZATION_OFFSET_PATH, MemoryLayout.PathElement.sequenceElement());
+        POSITION_QUANTIZATION_SCALE_HANDLE = LAYOUT.varHandle(POSITION_QUANTIZATION_SCALE_PATH, MemoryLayout.PathElement.sequenceElement());
         HAS_NORMAL_HANDLE = LAYOUT.varHandle(HAS_NORMAL_PATH);
         HAS_COLOR_HANDLE = LAYOUT.varHandle(HAS_COLOR_PATH);
         HAS_UV0_HANDLE = LAYOUT.varHandle(HAS_UV0_PATH);
         HAS_UV1_HANDLE = LAYOUT.varHandle(HAS_UV1_PATH);
         HAS_UV2_HANDLE = LAYOUT.varHandle(HAS_UV2_PATH);
+        HAS_POSITION_QUANTIZATION_HANDLE = LAYOUT.varHandle(HAS_POSITION_QUANTIZATION_PATH);
     }
 }
@@ -0,0 +1,204 @@
+package graphics.kiln.blaze4d.core.types;
+
+import graphics.kiln.blaze4d.core.Blaze4DCore;
+import graphics.kiln.blaze4d.core.natives.B4DConfigNative;
+import jdk.incubator.foreign.MemoryAddress;
+import jdk.incubator.foreign.MemorySegment;
+import jdk.incubator.foreign.ResourceScope;
+import jdk.incubator.foreign.ValueLayout;
+
+import java.nio.charset.StandardCharsets;
+
+public class B4DConfig implements AutoCloseable {
+
+    private final ResourceScope resourceScope;
+    private final MemorySegment memory;
+
+    public B4DConfig() {
+        this.resourceScope = ResourceScope.newSharedScope();
+        this.memory = MemorySegment.allocateNative(B4DConfigNative.LAYOUT, this.resourceScope);
+
+        this.setDevicePreference(B4DDevicePreference.DEFAULT);
+        this.setMsaaSamples(1);
+        this.setFramesInFlight(2);
+        this.setDebugMode(Blaze4DCore.DebugMode.COLOR);
+
+        // Diagnostic options can be enabled by the launcher without changing the mod config
+        this.setGpuAssistedValidation(System.getProperty("b4d.gpu_assisted_validation") != null);
+        this.setRobustAccess(System.getProperty("b4d.robust_access") != null);
+    }
+
+    public void setEnableValidation(boolean enable) {
+        B4DConfigNative.ENABLE_VALIDATION_HANDLE.set(this.memory, enable ? 1 : 0);
+    }
+
+    public boolean getEnableValidation() {
+        return ((int) B4DConfigNative.ENABLE_VALIDATION_HANDLE.get(this.memory)) != 0;
+    }
+
+    public void setDevicePreference(B4DDevicePreference preference) {
+        B4DConfigNative.DEVICE_PREFERENCE_HANDLE.set(this.memory, preference.getValue());
+    }
+
+    public B4DDevicePreference getDevicePreference() {
+        return B4DDevicePreference.fromValue((int) B4DConfigNative.DEVICE_PREFERENCE_HANDLE.get(this.memory));
+    }
+
+    public void setMsaaSamples(int samples) {
+        B4DConfigNative.MSAA_SAMPLES_HANDLE.set(this.memory, samples);
+    }
+
+    public int getMsaaSamples() {
+        return (int) B4DConfigNative.MSAA_SAMPLES_HANDLE.get(this.memory);
+    }
+
+    public void setVsync(boolean vsync) {
+        B4DConfigNative.VSYNC_HANDLE.set(this.memory, vsync ? 1 : 0);
+    }
+
+    public boolean getVsync() {
+        return ((int) B4DConfigNative.VSYNC_HANDLE.get(this.memory)) != 0;
+    }
+
+    public void setFramesInFlight(int framesInFlight) {
+        B4DConfigNative.FRAMES_IN_FLIGHT_HANDLE.set(this.memory, framesInFlight);
+    }
+
+    public int getFramesInFlight() {
+        return (int) B4DConfigNative.FRAMES_IN_FLIGHT_HANDLE.get(this.memory);
+    }
+
+    public void setDebugMode(Blaze4DCore.DebugMode mode) {
+        B4DConfigNative.DEBUG_MODE_HANDLE.set(this.memory, mode.getValue());
+    }
+
+    /**
+     * Sets the device memory budget in bytes. A value of 0 disables the budget.
+     */
+    public void setMemoryBudget(long budget) {
+        B4DConfigNative.MEMORY_BUDGET_HANDLE.set(this.memory, budget);
+    }
+
+    public long getMemoryBudget() {
+        return (long) B4DConfigNative.MEMORY_BUDGET_HANDLE.get(this.memory);
+    }
+
+    /**
+     * Sets the path of the pipeline cache file. If null is passed no pipeline cache file is used.
+     */
+    public void setPipelineCachePath(String path) {
+        if (path == null) {
+            B4DConfigNative.PIPELINE_CACHE_PATH_HANDLE.set(this.memory, MemoryAddress.NULL);
+        } else {
+            byte[] bytes = path.getBytes(StandardCharsets.UTF_8);
+            MemorySegment string = MemorySegment.allocateNative(bytes.length + 1, this.resourceScope);
+            string.copyFrom(MemorySegment.ofArray(bytes));
+            string.set(ValueLayout.JAVA_BYTE, bytes.length, (byte) 0);
+            B4DConfigNative.PIPELINE_CACHE_PATH_HANDLE.set(this.memory, string.address());
+        }
+    }
+
+    /**
+     * Sets the time in milliseconds after which a frame which has not completed on the gpu is
+     * reported as hung. A value of 0 disables the watchdog.
+     */
+    public void setWatchdogTimeout(int timeoutMs) {
+        B4DConfigNative.WATCHDOG_TIMEOUT_MS_HANDLE.set(this.memory, timeoutMs);
+    }
+
+    public int getWatchdogTimeout() {
+        return (int) B4DConfigNative.WATCHDOG_TIMEOUT_MS_HANDLE.get(this.memory);
+    }
+
+    /**
+     * If enabled the device is recreated when the watchdog detects a hang.
+     */
+    public void setWatchdogRecover(boolean recover) {
+        B4DConfigNative.WATCHDOG_RECOVER_HANDLE.set(this.memory, recover ? 1 : 0);
+    }
+
+    public boolean getWatchdogRecover() {
+        return ((int) B4DConfigNative.WATCHDOG_RECOVER_HANDLE.get(this.memory)) != 0;
+    }
+
+    /**
+     * Enables gpu assisted validation. Only has an effect if validation is enabled. Reports are
+     * written to the log like all other validation messages.
+     */
+    public void setGpuAssistedValidation(boolean enable) {
+        B4DConfigNative.GPU_ASSISTED_VALIDATION_HANDLE.set(this.memory, enable ? 1 : 0);
+    }
+
+    public boolean getGpuAssistedValidation() {
+        return ((int) B4DConfigNative.GPU_ASSISTED_VALIDATION_HANDLE.get(this.memory)) != 0;
+    }
+
+    /**
+     * Enables robust buffer access including robustBufferAccess2 and nullDescriptor if supported.
+     */
+    public void setRobustAccess(boolean enable) {
+        B4DConfigNative.ROBUST_ACCESS_HANDLE.set(this.memory, enable ? 1 : 0);
+    }
+
+    public boolean getRobustAccess() {
+        return ((int) B4DConfigNative.ROBUST_ACCESS_HANDLE.get(this.memory)) != 0;
+    }
+
+    /**
+     * Fetches vertex data in the vertex shader instead of the vertex input state. All vertex
+     * formats then share one vertex input state which avoids pipeline permutations for custom
+     * vertex formats.
+     */
+    public void setVertexPulling(boolean enable) {
+        B4DConfigNative.VERTEX_PULLING_HANDLE.set(this.memory, enable ? 1 : 0);
+    }
+
+    public boolean getVertexPulling() {
+        return ((int) B4DConfigNative.VERTEX_PULLING_HANDLE.get(this.memory)) != 0;
+    }
+
+    /**
+     * Experimental. Distributes the main pass of each frame over the devices of a device group
+     * if the selected device is part of one.
+     */
+    public void setDeviceGroupMode(B4DDeviceGroupMode mode) {
+        B4DConfigNative.DEVICE_GROUP_HANDLE.set(this.memory, mode.getValue());
+    }
+
+    public B4DDeviceGroupMode getDeviceGroupMode() {
+        return B4DDeviceGroupMode.fromValue((int) B4DConfigNative.DEVICE_GROUP_HANDLE.get(this.memory));
+    }
+
+    /**
+     * Tracks host memory allocated by the vulkan driver. Use {@code Blaze4DCore.getHostMemoryReport}
+     * to query the usage.
+     */
+    public void setTrackHostMemory(boolean enable) {
+        B4DConfigNative.TRACK_HOST_MEMORY_HANDLE.set(this.memory, enable ? 1 : 0);
+    }
+
+    public boolean getTrackHostMemory() {
+        return ((int) B4DConfigNative.TRACK_HOST_MEMORY_HANDLE.get(this.memory)) != 0;
+    }
+
+    /**
+     * Sets the number of threads used for cpu side work like culling and mesh optimization. A
+     * value of 0 derives the count from the number of available cores.
+     */
+    public void setJobThreads(int threads) {
+        B4DConfigNative.JOB_THREADS_HANDLE.set(this.memory, threads);
+    }
+
+    public int getJobThreads() {
+        return (int) B4DConfigNative.JOB_THREADS_HANDLE.get(this.memory);
+    }
+
+    public MemoryAddress getAddress() {
+        return this.memory.address();
+    }
+
+    @Override
+    public void close() throws Exception {
+        this.resourceScope.close();
+    }
+}
@@ -0,0 +1,37 @@
+package graphics.kiln.blaze4d.core.types;
+
+/**
+ * How the main pass of each frame is distributed over the devices of a device group.
+ * Experimental.
+ */
+public enum B4DDeviceGroupMode {
+    DISABLED(0),
+    ALTERNATE_FRAME(1),
+    SPLIT_FRAME(2);
+
+    private final int value;
+
+    B4DDeviceGroupMode(int value) {
+        this.value = value;
+    }
+
+    public int getValue() {
+        return this.value;
+    }
+
+    public static B4DDeviceGroupMode fromValue(int value) {
+        switch (value) {
+            case 0 -> {
+                return B4DDeviceGroupMode.DISABLED;
+            }
+            case 1 -> {
+                return B4DDeviceGroupMode.ALTERNATE_FRAME;
+            }
+            case 2 -> {
+                return B4DDeviceGroupMode.SPLIT_FRAME;
+            }
+            default ->
+                throw new RuntimeException("Invalid device group mode value " + value);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+package graphics.kiln.blaze4d.core.types;
+
+public enum B4DDevicePreference {
+    DEFAULT(0),
+    DISCRETE(1),
+    INTEGRATED(2);
+
+    private final int value;
+
+    B4DDevicePreference(int value) {
+        this.value = value;
+    }
+
+    public int getValue() {
+        return this.value;
+    }
+
+    public static B4DDevicePreference fromValue(int value) {
+        switch (value) {
+            case 0 -> {
+                return B4DDevicePreference.DEFAULT;
+            }
+            case 1 -> {
+                return B4DDevicePreference.DISCRETE;
+            }
+            case 2 -> {
+                return B4DDevicePreference.INTEGRATED;
+            }
+            default ->
+                throw new RuntimeException("Invalid device preference value " + value);
+        }
+    }
+}
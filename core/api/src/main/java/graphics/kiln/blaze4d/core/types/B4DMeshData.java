@@ -10,16 +10,24 @@ import jdk.incubator.foreign.ResourceScope;
  */
 public class B4DMeshData implements AutoCloseable {
 
+    public static final int COMPRESSION_NONE = 0;
+
+    /**
+     * The LZ4 block format without a size prefix. Only supported when creating global meshes.
+     */
+    public static final int COMPRESSION_LZ4 = 1;
+
     private final ResourceScope resourceScope;
     private final MemorySegment memory;
 
     /**
      * Allocates a new mesh data instance with native backing memory.
-     * All data will be uninitialized.
+     * All data except the compression (which is set to none) will be uninitialized.
      */
     public B4DMeshData() {
         this.resourceScope = ResourceScope.newSharedScope();
         this.memory = MemorySegment.allocateNative(MeshDataNative.LAYOUT, this.resourceScope);
+        this.setCompression(COMPRESSION_NONE);
     }
 
     private void setVertexDataMem(MemoryAddress data, long dataLen) {
@@ -108,10 +116,35 @@ public class B4DMeshData implements AutoCloseable {
         return (int) MeshDataNative.PRIMITIVE_TOPOLOGY_HANDLE.get(this.memory);
     }
 
+    /**
+     * Sets the compression of the vertex and index data. If not {@link #COMPRESSION_NONE} the data
+     * lengths must be the compressed sizes and the uncompressed sizes must be set using
+     * {@link #setUncompressedLengths(long, long)}.
+     */
+    public void setCompression(int compression) {
+        MeshDataNative.COMPRESSION_HANDLE.set(this.memory, compression);
+    }
+
+    public int getCompression() {
+        return (int) MeshDataNative.COMPRESSION_HANDLE.get(this.memory);
+    }
+
+    public void setUncompressedLengths(long vertexDataLen, long indexDataLen) {
+        MeshDataNative.VERTEX_DATA_UNCOMPRESSED_LEN_HANDLE.set(this.memory, vertexDataLen);
+        MeshDataNative.INDEX_DATA_UNCOMPRESSED_LEN_HANDLE.set(this.memory, indexDataLen);
+    }
+
     public MemoryAddress getAddress() {
         return this.memory.address();
     }
 
+    /**
+     * Copies the native CMeshData struct into the destination segment.
+     */
+    public void copyTo(MemorySegment dst) {
+        dst.copyFrom(this.memory);
+    }
+
     @Override
     public void close() throws Exception {
         this.resourceScope.close();
@@ -15,7 +15,8 @@ public enum B4DUniform {
     FOG_SHAPE(1L << 11),
     LINE_WIDTH(1L << 12),
     GAME_TIME(1L << 13),
-    CHUNK_OFFSET(1L << 14);
+    CHUNK_OFFSET(1L << 14),
+    ITEM_ANIMATION(1L << 15);
 
     private final long value;
 
@@ -30,6 +30,17 @@ public class B4DUniformData implements AutoCloseable {
         this.setVec3f32(x, y, z);
     }
 
+    /**
+     * Sets the animation parameters of a dropped item drawn with a shader using item animation.
+     *
+     * @param phase The phase offset of the bob and spin animation in radians.
+     * @param spinRate The rotation speed around the vertical axis in radians per tick.
+     */
+    public void setItemAnimation(float phase, float spinRate) {
+        McUniformDataNative.UNIFORM_HANDLE.set(this.memory, B4DUniform.ITEM_ANIMATION.getValue());
+        this.setVec2f32(phase, spinRate);
+    }
+
     public MemoryAddress getAddress() {
         return this.memory.address();
     }
@@ -39,6 +50,11 @@ public class B4DUniformData implements AutoCloseable {
         this.resourceScope.close();
     }
 
+    private void setVec2f32(float x, float y) {
+        McUniformDataNative.PAYLOAD_VEC2F32_HANDLE.set(this.memory, 0, x);
+        McUniformDataNative.PAYLOAD_VEC2F32_HANDLE.set(this.memory, 1, y);
+    }
+
     private void setVec3f32(float x, float y, float z) {
         McUniformDataNative.PAYLOAD_VEC3F32_HANDLE.set(this.memory, 0, x);
         McUniformDataNative.PAYLOAD_VEC3F32_HANDLE.set(this.memory, 1, y);
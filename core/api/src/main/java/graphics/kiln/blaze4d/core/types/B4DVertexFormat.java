@@ -25,6 +25,8 @@ public class B4DVertexFormat implements AutoCloseable {
         this.setUV0();
         this.setUV1();
         this.setUV2();
+        this.setPositionQuantization();
+        this.setOctahedralNormals(false);
     }
 
     public void setStride(int stride) {
@@ -185,6 +187,36 @@ public class B4DVertexFormat implements AutoCloseable {
         return Optional.empty();
     }
 
+    /**
+     * Disables position quantization.
+     */
+    public void setPositionQuantization() {
+        VertexFormatNative.HAS_POSITION_QUANTIZATION_HANDLE.set(this.memory, false);
+        for (long i = 0; i < 3; i++) {
+            VertexFormatNative.POSITION_QUANTIZATION_OFFSET_HANDLE.set(this.memory, i, 0.0f);
+            VertexFormatNative.POSITION_QUANTIZATION_SCALE_HANDLE.set(this.memory, i, 1.0f);
+        }
+    }
+
+    /**
+     * Enables position quantization. Positions are decoded as {@code offset + (position * scale)}.
+     * See {@link VertexQuantization} for encoding helpers.
+     */
+    public void setPositionQuantization(VertexQuantization quantization) {
+        VertexFormatNative.HAS_POSITION_QUANTIZATION_HANDLE.set(this.memory, true);
+        for (int i = 0; i < 3; i++) {
+            VertexFormatNative.POSITION_QUANTIZATION_OFFSET_HANDLE.set(this.memory, (long) i, quantization.offset()[i]);
+            VertexFormatNative.POSITION_QUANTIZATION_SCALE_HANDLE.set(this.memory, (long) i, quantization.scale()[i]);
+        }
+    }
+
+    /**
+     * If true the normal attribute is decoded as a octahedral encoded normal.
+     */
+    public void setOctahedralNormals(boolean octahedral) {
+        VertexFormatNative.NORMAL_ENCODING_HANDLE.set(this.memory, octahedral ? 1 : 0);
+    }
+
     public MemoryAddress getAddress() {
         return this.memory.address();
     }
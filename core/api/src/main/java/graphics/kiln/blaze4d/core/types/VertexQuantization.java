@@ -0,0 +1,67 @@
+package graphics.kiln.blaze4d.core.types;
+
+/**
+ * Helpers to encode quantized vertex data.
+ *
+ * Positions are encoded as 4 16 bit normalized integers relative to a bounding box. Normals are
+ * encoded as 2 16 bit normalized integers using octahedral encoding.
+ */
+public record VertexQuantization(float[] offset, float[] scale) {
+
+    /**
+     * Creates a quantization covering the axis aligned box from min to max.
+     */
+    public static VertexQuantization fromBounds(float minX, float minY, float minZ, float maxX, float maxY, float maxZ) {
+        return new VertexQuantization(
+                new float[]{ (minX + maxX) / 2.0f, (minY + maxY) / 2.0f, (minZ + maxZ) / 2.0f },
+                new float[]{ halfExtent(minX, maxX), halfExtent(minY, maxY), halfExtent(minZ, maxZ) }
+        );
+    }
+
+    /**
+     * A quantization suitable for chunk sections covering the range [-8, 24] on each axis.
+     */
+    public static VertexQuantization chunkSection() {
+        return fromBounds(-8.0f, -8.0f, -8.0f, 24.0f, 24.0f, 24.0f);
+    }
+
+    /**
+     * Encodes a position into the first 3 elements of dst starting at index. The 4th element is set to 0.
+     */
+    public void encodePosition(float x, float y, float z, short[] dst, int index) {
+        dst[index] = encodeSnorm16((x - this.offset[0]) / this.scale[0]);
+        dst[index + 1] = encodeSnorm16((y - this.offset[1]) / this.scale[1]);
+        dst[index + 2] = encodeSnorm16((z - this.offset[2]) / this.scale[2]);
+        dst[index + 3] = 0;
+    }
+
+    /**
+     * Encodes a normal using octahedral encoding into 2 elements of dst starting at index. The normal
+     * does not have to be normalized but must not be 0.
+     */
+    public static void encodeOctahedralNormal(float x, float y, float z, short[] dst, int index) {
+        float sum = Math.abs(x) + Math.abs(y) + Math.abs(z);
+        float nx = x / sum;
+        float ny = y / sum;
+        if (z < 0.0f) {
+            float tx = (1.0f - Math.abs(ny)) * signNotZero(nx);
+            float ty = (1.0f - Math.abs(nx)) * signNotZero(ny);
+            nx = tx;
+            ny = ty;
+        }
+        dst[index] = encodeSnorm16(nx);
+        dst[index + 1] = encodeSnorm16(ny);
+    }
+
+    private static float halfExtent(float min, float max) {
+        return Math.max((max - min) / 2.0f, Float.MIN_NORMAL);
+    }
+
+    private static float signNotZero(float v) {
+        return v >= 0.0f ? 1.0f : -1.0f;
+    }
+
+    private static short encodeSnorm16(float v) {
+        return (short) Math.round(Math.max(-1.0f, Math.min(1.0f, v)) * Short.MAX_VALUE);
+    }
+}
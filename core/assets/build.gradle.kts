@@ -9,10 +9,13 @@ configure<graphics.kiln.blaze4d.build.assets.AssetsPluginExtension> {
 
             addModule("debug/position.vert")
             addModule("debug/color.vert")
+            addModule("debug/normal.vert")
             addModule("debug/uv.vert")
             addModule("debug/null.vert")
+            addModule("debug/pulled.vert")
             addModule("debug/debug.frag")
             addModule("debug/textured.frag")
+            addModule("debug/shadow.vert")
             addModule("debug/background.vert")
             addModule("debug/background.frag")
         }
@@ -22,6 +25,9 @@ configure<graphics.kiln.blaze4d.build.assets.AssetsPluginExtension> {
 
             addModule("full_screen_quad.vert")
             addModule("blit.frag")
+            addModule("color_grade.frag")
+            addModule("luminance_histogram.comp")
+            addModule("composite.frag")
         }
 
         addProject("Debug") {
@@ -1,9 +1,15 @@
 #version 450
 
+#include <mc_uniforms.glsl>
+#include <mc_shadow.glsl>
+
 layout(location=0) in vec4 in_color;
+layout(location=2) in vec4 in_shadow_position;
 
 layout(location=0) out vec4 out_color;
 
 void main() {
     out_color = in_color;
+    mc_alpha_test(out_color.a);
+    out_color = mc_apply_shadow(out_color, in_shadow_position);
 }
\ No newline at end of file
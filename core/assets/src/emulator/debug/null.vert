@@ -4,12 +4,16 @@
  */
 
 #include <mc_uniforms.glsl>
+#include <mc_shadow.glsl>
 
 layout(location=0) in vec3 in_position;
 
 layout(location=0) out vec4 out_color;
+layout(location=2) out vec4 out_shadow_position;
 
 void main() {
     gl_Position = mc_transform_position(in_position);
+    gl_PointSize = 1.0;
+    out_shadow_position = mc_shadow_position(mc_decode_position(in_position) + mc_chunk_offset());
     out_color = vec4(0.0, 0.0, 0.0, 0.0);
 }
\ No newline at end of file
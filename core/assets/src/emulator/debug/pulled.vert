@@ -0,0 +1,39 @@
+#version 450
+/**
+ * A debug shader fetching its vertex data from a storage buffer. Replaces all other debug vertex
+ * shaders if vertex pulling is enabled. The output is selected by a specialization constant.
+ */
+
+#define MC_VERTEX_PULLING
+#include <mc_uniforms.glsl>
+#include <mc_vertex_pulling.glsl>
+
+#define OUTPUT_POSITION 0
+#define OUTPUT_COLOR 1
+#define OUTPUT_NORMAL 2
+#define OUTPUT_UV 3
+
+layout(constant_id=1) const uint OUTPUT_MODE = OUTPUT_POSITION;
+
+layout(location=0) out vec4 out_color;
+layout(location=1) out vec2 out_uv;
+
+void main() {
+    gl_Position = mc_transform_position(mc_fetch_position());
+    gl_PointSize = 1.0;
+
+    vec4 attribute = mc_fetch_attribute();
+    out_uv = attribute.xy;
+
+    if (!mc_has_attribute()) {
+        out_color = vec4(0.0, 0.0, 0.0, 0.0);
+    } else if (OUTPUT_MODE == OUTPUT_COLOR) {
+        out_color = attribute;
+    } else if (OUTPUT_MODE == OUTPUT_NORMAL) {
+        out_color = vec4((mc_decode_normal(attribute.xyz) * 0.5) + 0.5, 1.0);
+    } else if (OUTPUT_MODE == OUTPUT_UV) {
+        out_color = vec4(attribute.xy, 0.0, 1.0);
+    } else {
+        out_color = vec4(0.0, 0.0, 0.0, 1.0);
+    }
+}
@@ -0,0 +1,20 @@
+#version 450
+/**
+ * Renders the depth of shadow casters into a shadow map cascade. The transform maps the decoded
+ * position into the clip space of the cascade. See ShadowPipeline in debug_pipeline.rs.
+ */
+
+layout(location=0) in vec3 in_position;
+
+layout(push_constant)
+uniform PushConstant {
+    mat4 transform;
+    vec4 position_offset;
+    vec4 position_scale;
+} push_constant;
+
+void main() {
+    vec3 position = push_constant.position_offset.xyz + (in_position * push_constant.position_scale.xyz);
+    gl_Position = push_constant.transform * vec4(position, 1.0);
+    gl_PointSize = 1.0;
+}
\ No newline at end of file
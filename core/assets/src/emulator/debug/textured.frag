@@ -1,8 +1,10 @@
 #version 450
 
 #include <mc_uniforms.glsl>
+#include <mc_shadow.glsl>
 
 layout(location=1) in vec2 in_uv;
+layout(location=2) in vec4 in_shadow_position;
 
 layout(location=0) out vec4 out_color;
 
@@ -10,4 +12,6 @@ layout(constant_id=0) const uint IMAGE_INDEX = 0;
 
 void main() {
     out_color = mc_image(IMAGE_INDEX, in_uv);
+    mc_alpha_test(out_color.a);
+    out_color = mc_apply_shadow(out_color, in_shadow_position);
 }
\ No newline at end of file
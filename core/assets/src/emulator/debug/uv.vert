@@ -4,15 +4,19 @@
  */
 
 #include <mc_uniforms.glsl>
+#include <mc_shadow.glsl>
 
 layout(location=0) in vec3 in_position;
 layout(location=1) in vec2 in_uv;
 
 layout(location=0) out vec4 out_color;
 layout(location=1) out vec2 out_uv;
+layout(location=2) out vec4 out_shadow_position;
 
 void main() {
     gl_Position = mc_transform_position(in_position);
+    gl_PointSize = 1.0;
+    out_shadow_position = mc_shadow_position(mc_decode_position(in_position) + mc_chunk_offset());
     out_color = vec4(in_uv, 0.0, 1.0);
     out_uv = in_uv;
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+/**
+ * Cascaded shadow map sampling. See shadow.rs for how the cascades are computed.
+ *
+ * The cascades are stored as layers of a depth array image. Must be included after
+ * mc_uniforms.glsl.
+ */
+
+#define MC_MAX_CASCADES 4
+
+/*
+ * The ambient light factor of fully shadowed fragments.
+ */
+#define MC_SHADOW_AMBIENT 0.5
+
+layout(set=0, binding=2) uniform sampler2DArrayShadow _mc_shadow_map;
+
+layout(set=0, binding=3, std140)
+uniform _McShadowUniforms {
+    mat4 cascades[MC_MAX_CASCADES];
+    mat4 inverse_camera_view;
+    vec4 split_far;
+    uint cascade_count;
+    float blend_fraction;
+} _mc_shadow_uniforms;
+
+/*
+ * Returns the shadow factor of a single cascade. 1.0 means fully lit.
+ */
+float mc_sample_cascade(sampler2DArrayShadow shadow_map, mat4 view_projection, uint cascade, vec3 world_position) {
+    vec4 clip = view_projection * vec4(world_position, 1.0);
+    vec3 ndc = clip.xyz / clip.w;
+    vec2 uv = ndc.xy * 0.5 + 0.5;
+    return texture(shadow_map, vec4(uv, float(cascade), ndc.z));
+}
+
+/*
+ * Selects the cascade based on the view distance and blends into the next cascade over the last
+ * blend_fraction of each cascade range to hide the seams.
+ *
+ * split_far contains the view space distance at which each cascade ends.
+ */
+float mc_shadow_factor(sampler2DArrayShadow shadow_map, mat4 cascades[MC_MAX_CASCADES], vec4 split_far, uint cascade_count, float blend_fraction, float view_distance, vec3 world_position) {
+    uint cascade = cascade_count;
+    for (uint i = 0; i < cascade_count; i++) {
+        if (view_distance < split_far[i]) {
+            cascade = i;
+            break;
+        }
+    }
+    if (cascade >= cascade_count) {
+        return 1.0;
+    }
+
+    float shadow = mc_sample_cascade(shadow_map, cascades[cascade], cascade, world_position);
+
+    if (cascade + 1 < cascade_count) {
+        float split_near = cascade == 0 ? 0.0 : split_far[cascade - 1];
+        float blend_start = split_far[cascade] - (split_far[cascade] - split_near) * blend_fraction;
+        if (view_distance > blend_start) {
+            float next = mc_sample_cascade(shadow_map, cascades[cascade + 1], cascade + 1, world_position);
+            float t = smoothstep(blend_start, split_far[cascade], view_distance);
+            shadow = mix(shadow, next, t);
+        }
+    }
+
+    return shadow;
+}
+
+/*
+ * Returns the position in the space the cascades are computed in as xyz and the view distance as
+ * w. The position must already be decoded and include the chunk offset. Only valid in vertex
+ * shaders.
+ */
+vec4 mc_shadow_position(vec3 position) {
+    vec4 view = mc_model_view_matrix() * vec4(position, 1.0);
+    vec3 world = (_mc_shadow_uniforms.inverse_camera_view * view).xyz;
+    return vec4(world, -view.z);
+}
+
+/*
+ * Returns the shadow factor of a position returned by mc_shadow_position. 1.0 means fully lit.
+ * Always returns 1.0 if no shadow camera has been set.
+ */
+float mc_shadow(vec4 shadow_position) {
+    return mc_shadow_factor(_mc_shadow_map, _mc_shadow_uniforms.cascades, _mc_shadow_uniforms.split_far, _mc_shadow_uniforms.cascade_count, _mc_shadow_uniforms.blend_fraction, shadow_position.w, shadow_position.xyz);
+}
+
+/*
+ * Darkens a color by the shadow factor of a position returned by mc_shadow_position.
+ */
+vec4 mc_apply_shadow(vec4 color, vec4 shadow_position) {
+    float light = mix(MC_SHADOW_AMBIENT, 1.0, mc_shadow(shadow_position));
+    return vec4(color.rgb * light, color.a);
+}
@@ -3,6 +3,7 @@
  */
 
 layout(set=0, binding=1) uniform sampler2D[3] _mc_image;
+layout(set=0, binding=4) uniform sampler2DArray[3] _mc_image_array;
 
 layout(set=0, binding=0, std140)
 uniform _McStaticUniforms {
@@ -28,6 +29,11 @@ layout(push_constant)
 uniform _PushConstant {
     mat4 model_view_matrix;
     vec3 chunk_offset;
+    layout(offset=80) vec2 item_animation;
+    layout(offset=96) uvec3 image_atlas_grids;
+#ifdef MC_VERTEX_PULLING
+    layout(offset=112) uvec4 vertex_fetch;
+#endif
 } _push_constant;
 
 mat4 mc_model_view_matrix() {
@@ -91,25 +97,184 @@ vec3 mc_chunk_offset() {
     return _push_constant.chunk_offset;
 }
 
+/*
+ * Vertex quantization parameters. If quantization is disabled the offset is 0 and the scale is 1.
+ */
+layout(constant_id=100) const float _MC_POSITION_OFFSET_X = 0.0;
+layout(constant_id=101) const float _MC_POSITION_OFFSET_Y = 0.0;
+layout(constant_id=102) const float _MC_POSITION_OFFSET_Z = 0.0;
+layout(constant_id=103) const float _MC_POSITION_SCALE_X = 1.0;
+layout(constant_id=104) const float _MC_POSITION_SCALE_Y = 1.0;
+layout(constant_id=105) const float _MC_POSITION_SCALE_Z = 1.0;
+layout(constant_id=106) const bool _MC_OCTAHEDRAL_NORMALS = false;
+
+/*
+ * Dropped item animation. See ItemAnimation in item_animation.rs.
+ */
+layout(constant_id=116) const bool _MC_ITEM_ANIMATION = false;
+
+#define MC_TICKS_PER_GAME_TIME 24000.0
+#define MC_ITEM_BOB_SPEED 0.1
+#define MC_ITEM_BOB_HEIGHT 0.1
+
+vec3 mc_decode_position(vec3 position) {
+    vec3 offset = vec3(_MC_POSITION_OFFSET_X, _MC_POSITION_OFFSET_Y, _MC_POSITION_OFFSET_Z);
+    vec3 scale = vec3(_MC_POSITION_SCALE_X, _MC_POSITION_SCALE_Y, _MC_POSITION_SCALE_Z);
+    return offset + (position * scale);
+}
+
+vec3 mc_decode_normal(vec3 normal) {
+    if (_MC_OCTAHEDRAL_NORMALS) {
+        vec3 n = vec3(normal.xy, 1.0 - abs(normal.x) - abs(normal.y));
+        float t = max(-n.z, 0.0);
+        n.x += n.x >= 0.0 ? -t : t;
+        n.y += n.y >= 0.0 ? -t : t;
+        return normalize(n);
+    } else {
+        return normal;
+    }
+}
+
+/*
+ * Applies the bob and spin animation of a dropped item to a position in model space. The item
+ * spins around the vertical axis through the model origin. Does nothing if item animation is
+ * disabled.
+ */
+vec3 mc_animate_item(vec3 position) {
+    if (!_MC_ITEM_ANIMATION) {
+        return position;
+    }
+
+    float ticks = _mc_static_uniforms.fog_range_and_game_time.z * MC_TICKS_PER_GAME_TIME;
+    float phase = _push_constant.item_animation.x;
+    float spin = ticks * _push_constant.item_animation.y + phase;
+    float bob = sin(ticks * MC_ITEM_BOB_SPEED + phase) * MC_ITEM_BOB_HEIGHT + MC_ITEM_BOB_HEIGHT;
+
+    float s = sin(spin);
+    float c = cos(spin);
+    return vec3(c * position.x + s * position.z, position.y + bob, c * position.z - s * position.x);
+}
+
 vec4 mc_transform_position(vec3 position) {
-    vec4 tmp = mc_projection_matrix() * (mc_model_view_matrix() * vec4(position + mc_chunk_offset(), 1.0));
+    vec4 tmp = mc_projection_matrix() * (mc_model_view_matrix() * vec4(mc_animate_item(mc_decode_position(position)) + mc_chunk_offset(), 1.0));
     tmp.z = (tmp.z + tmp.w) / 2.0;
     tmp.y *= -1.0;
     return tmp;
 }
 
-vec4 mc_image(uint index, vec2 coord) {
-    return texture(_mc_image[index], coord);
+/*
+ * Shader variant constants. See ShaderSpecialization in mc_shaders.rs.
+ */
+layout(constant_id=110) const uint _MC_FOG_MODE = 0;
+layout(constant_id=111) const float _MC_ALPHA_TEST_THRESHOLD = 0.0;
+layout(constant_id=112) const bool _MC_LIGHTMAP_ENABLE = false;
+layout(constant_id=113) const uint _MC_ALPHA_MODE = 0;
+layout(constant_id=114) const bool _MC_EMISSIVE = false;
+layout(constant_id=115) const uint _MC_EMISSIVE_IMAGE_INDEX = 0xFFFFFFFFu;
+
+#define MC_FOG_MODE_DISABLED 0
+#define MC_FOG_MODE_SPHERICAL 1
+#define MC_FOG_MODE_CYLINDRICAL 2
+
+#define MC_ALPHA_MODE_TEST 0
+#define MC_ALPHA_MODE_COVERAGE 1
+
+#define MC_NO_EMISSIVE_IMAGE 0xFFFFFFFFu
+
+/*
+ * Fully emissive materials are never darkened by the lightmap. Materials with an emissive mask
+ * still sample the lightmap for their non emissive parts.
+ */
+bool mc_lightmap_enabled() {
+    return _MC_LIGHTMAP_ENABLE && !(_MC_EMISSIVE && _MC_EMISSIVE_IMAGE_INDEX == MC_NO_EMISSIVE_IMAGE);
 }
 
-vec4 mc_image_0(vec2 coord) {
-    return texture(_mc_image[0], coord);
+bool mc_is_emissive() {
+    return _MC_EMISSIVE;
 }
 
-vec4 mc_image_1(vec2 coord) {
-    return texture(_mc_image[1], coord);
+/*
+ * Discards the fragment if the alpha value is below the alpha test threshold. Does nothing if
+ * alpha to coverage is used instead. Only valid in fragment shaders.
+ */
+#define mc_alpha_test(alpha) if (_MC_ALPHA_MODE == MC_ALPHA_MODE_TEST && (alpha) < _MC_ALPHA_TEST_THRESHOLD) { discard; }
+
+/*
+ * Calculates the fog distance of a position in view space.
+ */
+float mc_fog_distance(vec3 position) {
+    if (_MC_FOG_MODE == MC_FOG_MODE_CYLINDRICAL) {
+        return max(length(position.xz), abs(position.y));
+    } else {
+        return length(position);
+    }
 }
 
-vec4 mc_image_2(vec2 coord) {
-    return texture(_mc_image[2], coord);
+vec4 mc_apply_fog(vec4 color, float distance) {
+    if (_MC_FOG_MODE == MC_FOG_MODE_DISABLED) {
+        return color;
+    }
+
+    float start = _mc_static_uniforms.fog_range_and_game_time.x;
+    float end = _mc_static_uniforms.fog_range_and_game_time.y;
+    vec4 fog_color = _mc_static_uniforms.fog_color;
+
+    if (distance <= start) {
+        return color;
+    }
+    float value = distance < end ? smoothstep(start, end, distance) : 1.0;
+    return vec4(mix(color.rgb, fog_color.rgb, value * fog_color.a), color.a);
+}
+
+vec4 _mc_image(uint index, vec2 coord, vec2 coord_dx, vec2 coord_dy) {
+    uint grid = _push_constant.image_atlas_grids[index];
+    if (grid == 0) {
+        return textureGrad(_mc_image[index], coord, coord_dx, coord_dy);
+    }
+
+    vec2 tiles = vec2(grid & 0xFFFFu, grid >> 16);
+    vec2 scaled = coord * tiles;
+    vec2 tile = clamp(floor(scaled), vec2(0.0), tiles - 1.0);
+    float layer = tile.x + tile.y * tiles.x;
+    return textureGrad(_mc_image_array[index], vec3(scaled - tile, layer), coord_dx * tiles, coord_dy * tiles);
+}
+
+/*
+ * Samples an image. If the image is an atlas array image the coordinate is in atlas space and the
+ * tile containing the coordinate is sampled from its own layer. Derivatives are taken in atlas
+ * space so mip selection is continuous across tile borders. Only valid in fragment shaders.
+ */
+#define mc_image(index, coord) _mc_image(index, coord, dFdx(coord), dFdy(coord))
+
+#define mc_image_0(coord) mc_image(0, coord)
+#define mc_image_1(coord) mc_image(1, coord)
+#define mc_image_2(coord) mc_image(2, coord)
+
+/*
+ * Returns how strongly a fragment emits light in the range [0, 1]. Non emissive materials return
+ * 0, emissive materials without a mask return 1. Only valid in fragment shaders.
+ */
+float mc_emissive_strength(vec2 coord) {
+    if (!_MC_EMISSIVE) {
+        return 0.0;
+    }
+    if (_MC_EMISSIVE_IMAGE_INDEX == MC_NO_EMISSIVE_IMAGE) {
+        return 1.0;
+    }
+    return mc_image(_MC_EMISSIVE_IMAGE_INDEX, coord).a;
+}
+
+/*
+ * Blends between the lit and the unlit color of a fragment by its emissive strength. Only valid
+ * in fragment shaders.
+ */
+vec4 mc_apply_emissive(vec4 lit_color, vec4 unlit_color, vec2 coord) {
+    return mix(lit_color, unlit_color, mc_emissive_strength(coord));
+}
+
+/*
+ * Samples a layer of an array image. Single layer images can be sampled using layer 0.
+ */
+vec4 mc_image_layer(uint index, vec2 coord, uint layer) {
+    return texture(_mc_image_array[index], vec3(coord, float(layer)));
 }
\ No newline at end of file
@@ -0,0 +1,114 @@
+/**
+ * Fetches vertex attributes from a storage buffer instead of using the vertex input state. The
+ * layout of the vertex format is provided by push constants so that one pipeline can be used for
+ * all vertex formats. See VertexFetchConstants in debug_pipeline.rs.
+ *
+ * Must be included after mc_uniforms.glsl with MC_VERTEX_PULLING defined.
+ */
+
+layout(set=0, binding=5, std430) readonly buffer _McVertexData {
+    uint data[];
+} _mc_vertex_data;
+
+#define MC_FETCH_TYPE_NONE 0
+#define MC_FETCH_TYPE_SFLOAT32 1
+#define MC_FETCH_TYPE_UNORM8 2
+#define MC_FETCH_TYPE_SNORM8 3
+#define MC_FETCH_TYPE_UINT8 4
+#define MC_FETCH_TYPE_SINT8 5
+#define MC_FETCH_TYPE_UNORM16 6
+#define MC_FETCH_TYPE_SNORM16 7
+#define MC_FETCH_TYPE_UINT16 8
+#define MC_FETCH_TYPE_SINT16 9
+#define MC_FETCH_TYPE_SFLOAT16 10
+
+uint _mc_load_u8(uint address) {
+    return (_mc_vertex_data.data[address >> 2] >> ((address & 3u) * 8u)) & 0xFFu;
+}
+
+uint _mc_load_u16(uint address) {
+    return _mc_load_u8(address) | (_mc_load_u8(address + 1u) << 8);
+}
+
+uint _mc_load_u32(uint address) {
+    return _mc_load_u16(address) | (_mc_load_u16(address + 2u) << 16);
+}
+
+float _mc_load_component(uint type, uint address) {
+    switch (type) {
+        case MC_FETCH_TYPE_SFLOAT32:
+            return uintBitsToFloat(_mc_load_u32(address));
+        case MC_FETCH_TYPE_UNORM8:
+            return float(_mc_load_u8(address)) / 255.0;
+        case MC_FETCH_TYPE_SNORM8:
+            return max(float(bitfieldExtract(int(_mc_load_u8(address)), 0, 8)) / 127.0, -1.0);
+        case MC_FETCH_TYPE_UINT8:
+            return float(_mc_load_u8(address));
+        case MC_FETCH_TYPE_SINT8:
+            return float(bitfieldExtract(int(_mc_load_u8(address)), 0, 8));
+        case MC_FETCH_TYPE_UNORM16:
+            return float(_mc_load_u16(address)) / 65535.0;
+        case MC_FETCH_TYPE_SNORM16:
+            return max(float(bitfieldExtract(int(_mc_load_u16(address)), 0, 16)) / 32767.0, -1.0);
+        case MC_FETCH_TYPE_UINT16:
+            return float(_mc_load_u16(address));
+        case MC_FETCH_TYPE_SINT16:
+            return float(bitfieldExtract(int(_mc_load_u16(address)), 0, 16));
+        case MC_FETCH_TYPE_SFLOAT16:
+            return unpackHalf2x16(_mc_load_u16(address)).x;
+        default:
+            return 0.0;
+    }
+}
+
+uint _mc_fetch_type_size(uint type) {
+    switch (type) {
+        case MC_FETCH_TYPE_SFLOAT32:
+            return 4u;
+        case MC_FETCH_TYPE_UNORM8:
+        case MC_FETCH_TYPE_SNORM8:
+        case MC_FETCH_TYPE_UINT8:
+        case MC_FETCH_TYPE_SINT8:
+            return 1u;
+        default:
+            return 2u;
+    }
+}
+
+/*
+ * Fetches a packed attribute of the current vertex. The packed value contains the offset in the
+ * low 16 bits, the component type in bits 16-23 and the component count in bits 24-31. Missing
+ * components are filled like the vertex input state would (0, 0, 0, 1).
+ */
+vec4 _mc_fetch_attribute(uint packed) {
+    vec4 result = vec4(0.0, 0.0, 0.0, 1.0);
+
+    uint type = (packed >> 16) & 0xFFu;
+    if (type == MC_FETCH_TYPE_NONE) {
+        return result;
+    }
+
+    uint count = packed >> 24;
+    uint size = _mc_fetch_type_size(type);
+    uint address = uint(gl_VertexIndex) * _push_constant.vertex_fetch.x + (packed & 0xFFFFu);
+    for (uint i = 0u; i < count; i++) {
+        result[i] = _mc_load_component(type, address + (i * size));
+    }
+    return result;
+}
+
+vec3 mc_fetch_position() {
+    return _mc_fetch_attribute(_push_constant.vertex_fetch.y).xyz;
+}
+
+/*
+ * Fetches the attribute selected by the debug mode. Returns (0, 0, 0, 1) if the vertex format
+ * does not contain it.
+ */
+vec4 mc_fetch_attribute() {
+    return _mc_fetch_attribute(_push_constant.vertex_fetch.z);
+}
+
+bool mc_has_attribute() {
+    return ((_push_constant.vertex_fetch.z >> 16) & 0xFFu) != MC_FETCH_TYPE_NONE;
+}
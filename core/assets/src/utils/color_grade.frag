@@ -0,0 +1,78 @@
+#version 450
+/**
+ * A blit applying an exposure, a color matrix, per channel lift/gamma/gain curves, a 3D LUT and
+ * a wobbling screen distortion.
+ *
+ * The LUT is stored as a horizontal strip of size * size by size texels where each square slice
+ * holds one blue value.
+ */
+
+layout(location=0) in vec2 uv;
+
+layout(location=0) out vec4 out_color;
+
+layout(set=0, binding=0) uniform sampler2D image;
+layout(set=0, binding=1) uniform sampler2D lut;
+
+layout(push_constant) uniform PushConstants {
+    vec4 matrix_r;
+    vec4 matrix_g;
+    vec4 matrix_b;
+    vec4 lift;
+    vec4 gamma;
+    vec4 gain;
+    float lut_size;
+    float lut_strength;
+    float exposure;
+    vec4 distortion; // amplitude, phase, frequency
+} pc;
+
+vec3 to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, greaterThan(color, vec3(0.0031308)));
+}
+
+vec3 from_srgb(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), greaterThan(color, vec3(0.04045)));
+}
+
+vec3 sample_lut(vec3 color) {
+    float size = pc.lut_size;
+    float blue = color.b * (size - 1.0);
+    float slice0 = floor(blue);
+    float slice1 = min(slice0 + 1.0, size - 1.0);
+
+    vec2 texel = vec2(color.r * (size - 1.0) + 0.5, color.g * (size - 1.0) + 0.5);
+    vec2 scale = vec2(1.0 / (size * size), 1.0 / size);
+
+    vec3 a = texture(lut, (texel + vec2(slice0 * size, 0.0)) * scale).rgb;
+    vec3 b = texture(lut, (texel + vec2(slice1 * size, 0.0)) * scale).rgb;
+    return mix(a, b, blue - slice0);
+}
+
+vec2 distort(vec2 coord) {
+    float amplitude = pc.distortion.x;
+    float phase = pc.distortion.y;
+    float frequency = pc.distortion.z;
+
+    vec2 offset = vec2(sin(coord.y * frequency + phase), cos(coord.x * frequency + phase * 0.8)) * amplitude;
+    return (coord - 0.5) * (1.0 - 2.0 * amplitude) + 0.5 + offset;
+}
+
+void main() {
+    vec2 source_uv = pc.distortion.x > 0.0 ? distort(uv) : uv;
+    vec4 source = texture(image, source_uv);
+    vec4 color = vec4(source.rgb * pc.exposure, 1.0);
+
+    vec3 graded = vec3(dot(pc.matrix_r, color), dot(pc.matrix_g, color), dot(pc.matrix_b, color));
+    graded = clamp(graded, 0.0, 1.0);
+
+    graded = clamp(graded * pc.gain.rgb + pc.lift.rgb * (1.0 - graded), 0.0, 1.0);
+    graded = pow(graded, 1.0 / max(pc.gamma.rgb, vec3(0.0001)));
+
+    if (pc.lut_strength > 0.0) {
+        vec3 srgb = to_srgb(graded);
+        graded = mix(graded, from_srgb(sample_lut(srgb)), pc.lut_strength);
+    }
+
+    out_color = vec4(graded, source.a);
+}
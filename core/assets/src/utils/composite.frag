@@ -0,0 +1,20 @@
+#version 450
+/**
+ * Draws a single tinted layer of a composited texture. The source rect selects the region of the
+ * source image which is stretched over the whole target.
+ */
+
+layout(location=0) in vec2 uv;
+
+layout(location=0) out vec4 out_color;
+
+layout(set=0, binding=0) uniform sampler2D image;
+
+layout(push_constant) uniform PushConstants {
+    vec4 source_rect;
+    vec4 tint;
+} pc;
+
+void main() {
+    out_color = texture(image, mix(pc.source_rect.xy, pc.source_rect.zw, uv)) * pc.tint;
+}
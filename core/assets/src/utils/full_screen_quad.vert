@@ -1,5 +1,8 @@
 #version 450
 
+// Rotates the quad by rotation * 90 degrees to pre-rotate the content for the surface transform
+layout(constant_id=0) const uint rotation = 0;
+
 layout(location=0) out vec2 uv;
 
 vec2 positions[4] = vec2[](
@@ -16,7 +19,14 @@ vec2 uvs[4] = vec2[](
     vec2(1.0, 0.0)
 );
 
+mat2 rotations[4] = mat2[](
+    mat2(1.0, 0.0, 0.0, 1.0),
+    mat2(0.0, 1.0, -1.0, 0.0),
+    mat2(-1.0, 0.0, 0.0, -1.0),
+    mat2(0.0, -1.0, 1.0, 0.0)
+);
+
 void main() {
-    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
+    gl_Position = vec4(rotations[rotation & 3u] * positions[gl_VertexIndex], 0.0, 1.0);
     uv = uvs[gl_VertexIndex];
-}
\ No newline at end of file
+}
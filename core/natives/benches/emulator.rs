@@ -0,0 +1,245 @@
+//! Micro benchmarks of the emulator running on a headless device.
+//!
+//! Run with `cargo bench --features benchmarks`. Validation is disabled so the results are
+//! representative of release builds.
+
+use std::ffi::CString;
+use std::sync::Arc;
+
+use ash::vk;
+use bytemuck::{cast_slice, Pod, Zeroable};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+use b4d_core::allocator::HostAccess;
+use b4d_core::device::init::{create_device, DeviceCreateConfig};
+use b4d_core::instance::init::{create_instance, InstanceCreateConfig};
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::{EmulatorRenderer, MeshData, PassRecorder};
+use b4d_core::renderer::emulator::debug_pipeline::{DebugPipeline, DebugPipelineMode};
+use b4d_core::renderer::emulator::mc_shaders::{McUniform, McUniformData, ShaderId, VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::pipeline::OffscreenOutput;
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+const SIZE: Vec2u32 = Vec2u32::new(256, 256);
+
+fn make_headless_renderer() -> Arc<EmulatorRenderer> {
+    let mut config = InstanceCreateConfig::new(
+        CString::new("B4D Benchmarks").unwrap(),
+        vk::make_api_version(0, 0, 1, 0)
+    );
+
+    // The LunarG desktop profile requires the swapchain extension which in turn requires the surface extensions
+    config.require_surface_khr();
+
+    let instance = create_instance(config).unwrap();
+
+    let mut config = DeviceCreateConfig::new();
+    config.disable_robustness(); // We do this in b4d so we should use it for our benchmarks as well
+    let device: Arc<DeviceContext> = create_device(config, instance).unwrap();
+
+    Arc::new(EmulatorRenderer::new(device))
+}
+
+/// Records a pass into an offscreen target and waits for it to complete.
+fn run_pass<F>(renderer: &Arc<EmulatorRenderer>, pipeline: &Arc<DebugPipeline>, output: &OffscreenOutput, scene: F) where F: FnOnce(&mut PassRecorder) {
+    let (output_instance, readback) = output.next_output();
+    let mut recorder = renderer.start_pass(pipeline.clone());
+    recorder.use_output(output_instance);
+    scene(&mut recorder);
+    drop(recorder);
+
+    readback.wait().expect("Offscreen pass was aborted");
+}
+
+fn draw_submission(c: &mut Criterion) {
+    let renderer = make_headless_renderer();
+    let pipeline = DebugPipeline::new(renderer.clone(), DebugPipelineMode::Color, SIZE).unwrap();
+    let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), SIZE);
+    let shader = create_shader(&renderer);
+
+    let vertices = make_quad();
+    let data = make_mesh_data(&vertices);
+
+    let mut group = c.benchmark_group("draw_submission");
+    for draw_count in [100u64, 1000, 10000] {
+        group.throughput(Throughput::Elements(draw_count));
+        group.bench_with_input(BenchmarkId::new("immediate", draw_count), &draw_count, |b, draw_count| {
+            b.iter(|| run_pass(&renderer, &pipeline, &output, |recorder| {
+                recorder.update_uniform(&McUniformData::ProjectionMatrix(Mat4f32::identity()), shader);
+                for index in 0..*draw_count {
+                    let offset = (index % 16) as f32 / 16f32;
+                    recorder.update_uniform(&McUniformData::ModelViewMatrix(Mat4f32::new_translation(&Vec3f32::new(offset, offset, 0f32))), shader);
+                    let id = recorder.upload_immediate(&data);
+                    recorder.draw_immediate(id, shader, true);
+                }
+            }));
+        });
+
+        let mesh = renderer.create_global_mesh(&data);
+        group.bench_with_input(BenchmarkId::new("global", draw_count), &draw_count, |b, draw_count| {
+            b.iter(|| run_pass(&renderer, &pipeline, &output, |recorder| {
+                recorder.update_uniform(&McUniformData::ProjectionMatrix(Mat4f32::identity()), shader);
+                recorder.update_uniform(&McUniformData::ModelViewMatrix(Mat4f32::identity()), shader);
+                for _ in 0..*draw_count {
+                    recorder.draw_global(mesh.clone(), shader, true);
+                }
+            }));
+        });
+    }
+    group.finish();
+
+    renderer.drop_shader(shader);
+}
+
+fn mesh_upload(c: &mut Criterion) {
+    let renderer = make_headless_renderer();
+    let pipeline = DebugPipeline::new(renderer.clone(), DebugPipelineMode::Color, SIZE).unwrap();
+    let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), SIZE);
+    let shader = create_shader(&renderer);
+
+    let mut group = c.benchmark_group("mesh_upload");
+    for quad_count in [256usize, 4096, 65536] {
+        let vertices: Vec<Vertex> = std::iter::repeat(make_quad()).take(quad_count).flatten().collect();
+        let indices: Vec<u32> = (0..quad_count as u32).flat_map(|quad| QUAD_INDICES.map(|index| index + quad * 4)).collect();
+        let data = MeshData {
+            vertex_data: cast_slice(&vertices),
+            index_data: cast_slice(&indices),
+            vertex_stride: std::mem::size_of::<Vertex>() as u32,
+            index_count: indices.len() as u32,
+            index_type: vk::IndexType::UINT32,
+            primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        };
+
+        group.throughput(Throughput::Bytes((data.vertex_data.len() + data.index_data.len()) as u64));
+        group.bench_with_input(BenchmarkId::new("global", quad_count), &data, |b, data| {
+            // The pass draws the mesh so the measurement includes the upload completing on the gpu
+            b.iter(|| {
+                let mesh = renderer.create_global_mesh(data);
+                run_pass(&renderer, &pipeline, &output, |recorder| {
+                    recorder.update_uniform(&McUniformData::ProjectionMatrix(Mat4f32::identity()), shader);
+                    recorder.update_uniform(&McUniformData::ModelViewMatrix(Mat4f32::identity()), shader);
+                    recorder.draw_global(mesh, shader, true);
+                });
+            });
+        });
+    }
+    group.finish();
+
+    renderer.drop_shader(shader);
+}
+
+fn pipeline_creation(c: &mut Criterion) {
+    let renderer = make_headless_renderer();
+    let pipeline = DebugPipeline::new(renderer.clone(), DebugPipelineMode::Color, SIZE).unwrap();
+    let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), SIZE);
+
+    let vertices = make_quad();
+    let data = make_mesh_data(&vertices);
+
+    // Every iteration uses a new shader which forces a new pipeline to be created by the first
+    // draw. A pass with an existing shader is measured as the baseline.
+    let baseline_shader = create_shader(&renderer);
+    let mut group = c.benchmark_group("pipeline_creation");
+    group.bench_function("existing_shader", |b| {
+        b.iter(|| run_pass(&renderer, &pipeline, &output, |recorder| {
+            let id = recorder.upload_immediate(&data);
+            recorder.draw_immediate(id, baseline_shader, true);
+        }));
+    });
+    group.bench_function("new_shader", |b| {
+        b.iter_batched(|| create_shader(&renderer), |shader| {
+            run_pass(&renderer, &pipeline, &output, |recorder| {
+                let id = recorder.upload_immediate(&data);
+                recorder.draw_immediate(id, shader, true);
+            });
+            renderer.drop_shader(shader);
+        }, BatchSize::PerIteration);
+    });
+    group.finish();
+
+    renderer.drop_shader(baseline_shader);
+}
+
+fn allocator(c: &mut Criterion) {
+    let renderer = make_headless_renderer();
+    let device = renderer.get_device().clone();
+    let allocator = device.get_allocator();
+
+    let mut group = c.benchmark_group("allocator");
+    for size in [256u64, 64 * 1024, 16 * 1024 * 1024] {
+        let info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        group.bench_with_input(BenchmarkId::new("gpu_buffer", size), &info, |b, info| {
+            b.iter(|| unsafe {
+                let (buffer, allocation) = allocator.create_gpu_buffer(info, &format_args!("BenchmarkBuffer")).unwrap();
+                allocator.destroy_buffer(buffer, allocation);
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("host_buffer", size), &info, |b, info| {
+            b.iter(|| unsafe {
+                let (buffer, allocation, _) = allocator.create_buffer(info, HostAccess::SequentialWrite, &format_args!("BenchmarkBuffer")).unwrap();
+                allocator.destroy_buffer(buffer, allocation);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, draw_submission, mesh_upload, pipeline_creation, allocator);
+criterion_main!(benches);
+
+fn create_shader(renderer: &EmulatorRenderer) -> ShaderId {
+    renderer.create_shader(&Vertex::make_b4d_vertex_format(), McUniform::MODEL_VIEW_MATRIX | McUniform::PROJECTION_MATRIX)
+}
+
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
+
+fn make_quad() -> [Vertex; 4] {
+    [
+        Vertex { position: Vec3f32::new(-0.5f32, -0.5f32, 0.5f32), color: Vec4f32::new(1f32, 0f32, 0f32, 1f32) },
+        Vertex { position: Vec3f32::new(0.5f32, -0.5f32, 0.5f32), color: Vec4f32::new(0f32, 1f32, 0f32, 1f32) },
+        Vertex { position: Vec3f32::new(-0.5f32, 0.5f32, 0.5f32), color: Vec4f32::new(0f32, 0f32, 1f32, 1f32) },
+        Vertex { position: Vec3f32::new(0.5f32, 0.5f32, 0.5f32), color: Vec4f32::new(1f32, 1f32, 1f32, 1f32) },
+    ]
+}
+
+fn make_mesh_data(vertices: &[Vertex]) -> MeshData {
+    MeshData {
+        vertex_data: cast_slice(vertices),
+        index_data: cast_slice(&QUAD_INDICES),
+        vertex_stride: std::mem::size_of::<Vertex>() as u32,
+        index_count: QUAD_INDICES.len() as u32,
+        index_type: vk::IndexType::UINT32,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    #[allow(unused)]
+    position: Vec3f32,
+    #[allow(unused)]
+    color: Vec4f32,
+}
+
+impl Vertex {
+    fn make_b4d_vertex_format() -> VertexFormat {
+        VertexFormat {
+            stride: std::mem::size_of::<Vertex>() as u32,
+            position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+            normal: None,
+            color: Some(VertexFormatEntry { offset: std::mem::size_of::<Vec3f32>() as u32, format: vk::Format::R32G32B32A32_SFLOAT }),
+            uv0: None,
+            uv1: None,
+            uv2: None,
+            position_quantization: None,
+            normal_encoding: NormalEncoding::Direct,
+        }
+    }
+}
+
+unsafe impl Zeroable for Vertex {}
+unsafe impl Pod for Vertex {}
@@ -2,23 +2,21 @@ extern crate b4d_core;
 
 use ash::vk;
 use bytemuck::{cast_slice, Pod, Zeroable};
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
 
 use b4d_core::prelude::*;
 use b4d_core::renderer::emulator::debug_pipeline::DebugPipelineMode;
 use b4d_core::renderer::emulator::mc_shaders::{McUniform, McUniformData, VertexFormat, VertexFormatEntry};
 use b4d_core::renderer::emulator::MeshData;
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
 
-use b4d_core::window::WinitWindow;
+use b4d_core::window::WinitRunner;
 
 fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let event_loop = EventLoop::new();
-    let window = Box::new(WinitWindow::new("ImmediateCube", 800.0, 600.0, &event_loop));
+    let runner = WinitRunner::new("ImmediateCube", 800.0, 600.0, true);
 
-    let b4d = b4d_core::b4d::Blaze4D::new(window, true);
+    let b4d = runner.get_b4d();
     b4d.set_debug_mode(Some(DebugPipelineMode::Textured0));
     let vertex_format = Vertex::make_b4d_vertex_format();
     let mut shader = b4d.create_shader(&vertex_format, McUniform::MODEL_VIEW_MATRIX | McUniform::PROJECTION_MATRIX);
@@ -32,82 +30,48 @@ fn main() {
         primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
     };
 
-    let mut mesh = b4d.create_global_mesh(&data);
-
     let mut draw_times = Vec::with_capacity(1000);
     let mut last_update = std::time::Instant::now();
 
-    let mut current_size = Vec2u32::new(800, 600);
-
-    let start = std::time::Instant::now();
-
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
-
-        match event {
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => {
-                *control_flow = ControlFlow::Exit
-            },
-            Event::WindowEvent {
-                event: WindowEvent::Resized(new_size),
-                ..
-            } => {
-                current_size[0] = new_size.width;
-                current_size[1] = new_size.height;
-            }
-            Event::MainEventsCleared => {
-                let now = std::time::Instant::now();
-
-                mesh = b4d.create_global_mesh(&data);
+    runner.run(move |b4d, recorder, info| {
+        let now = std::time::Instant::now();
 
-                if let Some(mut recorder) = b4d.try_start_frame(current_size) {
+        recorder.update_uniform(&McUniformData::ProjectionMatrix(make_projection_matrix(info.window_size, 90f32)), shader);
 
-                    recorder.update_uniform(&McUniformData::ProjectionMatrix(make_projection_matrix(current_size, 90f32)), shader);
+        let elapsed = info.elapsed.as_secs_f32();
+        let rotation = Mat4f32::new_rotation(Vec3f32::new(elapsed / 2.34f32, elapsed / 2.783f32, elapsed / 2.593f32));
 
-                    let elapsed = start.elapsed().as_secs_f32();
-                    let rotation = Mat4f32::new_rotation(Vec3f32::new(elapsed / 2.34f32, elapsed / 2.783f32, elapsed / 2.593f32));
+        for x in -5i32..=5i32 {
+            for y in -5i32..=5i32 {
+                for z in 1i32..=11i32 {
+                    let translation = Mat4f32::new_translation(&Vec3f32::new(
+                        0f32 + ((x as f32) / 1f32),
+                        0f32 + ((y as f32) / 1f32),
+                        5f32 + ((z as f32) / 1f32)
+                    ));
+                    recorder.update_uniform(&McUniformData::ModelViewMatrix(translation * rotation), shader);
 
-                    for x in -5i32..=5i32 {
-                        for y in -5i32..=5i32 {
-                            for z in 1i32..=11i32 {
-                                let translation = Mat4f32::new_translation(&Vec3f32::new(
-                                    0f32 + ((x as f32) / 1f32),
-                                    0f32 + ((y as f32) / 1f32),
-                                    5f32 + ((z as f32) / 1f32)
-                                ));
-                                recorder.update_uniform(&McUniformData::ModelViewMatrix(translation * rotation), shader);
-
-                                let id = recorder.upload_immediate(&data);
-                                recorder.draw_immediate(id, shader, true);
-                                // recorder.draw_global(mesh.clone(), shader, true);
-                            }
-                        }
-                    }
+                    let id = recorder.upload_immediate(&data);
+                    recorder.draw_immediate(id, shader, true);
+                }
+            }
+        }
 
-                    drop(recorder);
+        // Stress test the shader stuff
+        b4d.drop_shader(shader);
+        shader = b4d.create_shader(&vertex_format, McUniform::MODEL_VIEW_MATRIX | McUniform::PROJECTION_MATRIX);
 
-                    // Stress test the shader stuff
-                    b4d.drop_shader(shader);
-                    shader = b4d.create_shader(&vertex_format, McUniform::MODEL_VIEW_MATRIX | McUniform::PROJECTION_MATRIX);
-                }
-                draw_times.push(now.elapsed());
+        draw_times.push(now.elapsed());
 
-                if last_update.elapsed().as_secs() >= 2 {
-                    let sum = draw_times.iter().fold(0f64, |sum, time| sum + time.as_secs_f64());
-                    let avg = sum / (draw_times.len() as f64);
-                    let fps = 1f64 / avg;
-                    draw_times.clear();
+        if last_update.elapsed().as_secs() >= 2 {
+            let sum = draw_times.iter().fold(0f64, |sum, time| sum + time.as_secs_f64());
+            let avg = sum / (draw_times.len() as f64);
+            let fps = 1f64 / avg;
+            draw_times.clear();
 
-                    log::error!("Average frame time over last 2 seconds: {:?} ({:?})", avg, fps);
+            log::error!("Average frame time over last 2 seconds: {:?} ({:?})", avg, fps);
 
-                    last_update = std::time::Instant::now();
-                }
-            }
-            _ => {
-            }
+            last_update = std::time::Instant::now();
         }
     });
 }
@@ -183,7 +147,9 @@ impl Vertex {
             color: Some(VertexFormatEntry { offset: std::mem::size_of::<Vec3f32>() as u32, format: vk::Format::R32G32B32A32_SFLOAT }),
             uv0: Some(VertexFormatEntry { offset: std::mem::size_of::<Vec3f32>() as u32 + std::mem::size_of::<Vec4f32>() as u32, format: vk::Format::R32G32_SFLOAT }),
             uv1: None,
-            uv2: None
+            uv2: None,
+            position_quantization: None,
+            normal_encoding: NormalEncoding::Direct,
         }
     }
 }
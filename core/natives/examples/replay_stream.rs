@@ -0,0 +1,88 @@
+//! Replays a command stream recorded with `Blaze4D::start_command_stream_recording` and writes
+//! each frame as a png.
+//!
+//! Usage: `replay_stream <stream file> [output directory]`
+
+extern crate b4d_core;
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ash::vk;
+
+use b4d_core::device::init::{create_device, DeviceCreateConfig};
+use b4d_core::instance::init::{create_instance, InstanceCreateConfig};
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::command_stream::CommandStream;
+use b4d_core::renderer::emulator::debug_pipeline::{DebugPipeline, DebugPipelineMode};
+use b4d_core::renderer::emulator::EmulatorRenderer;
+
+fn main() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut args = std::env::args().skip(1);
+    let stream_path = PathBuf::from(args.next().unwrap_or_else(|| {
+        log::error!("Usage: replay_stream <stream file> [output directory]");
+        std::process::exit(1);
+    }));
+    let output_dir = PathBuf::from(args.next().unwrap_or_else(|| "replay".to_string()));
+
+    let data = std::fs::read(&stream_path).unwrap_or_else(|err| {
+        log::error!("Failed to read {:?}: {:?}", stream_path, err);
+        std::process::exit(1);
+    });
+    let stream = CommandStream::deserialize(&data).unwrap_or_else(|err| {
+        log::error!("Failed to parse command stream {:?}: {:?}", stream_path, err);
+        std::process::exit(1);
+    });
+    log::info!("Replaying {} frames from {:?}", stream.get_frame_count(), stream_path);
+
+    let renderer = make_headless_renderer();
+    let readbacks = stream.replay(&renderer, |size| {
+        DebugPipeline::new(renderer.clone(), DebugPipelineMode::Textured0, size).unwrap()
+    });
+
+    std::fs::create_dir_all(&output_dir).unwrap();
+    for (index, readback) in readbacks.into_iter().enumerate() {
+        let size = readback.get_size();
+        match readback.wait() {
+            Some(data) => {
+                let path = output_dir.join(format!("frame_{:04}.png", index));
+                save_png(&path, size, &data);
+                log::info!("Wrote {:?}", path);
+            }
+            None => log::warn!("Frame {} was aborted", index),
+        }
+    }
+}
+
+fn make_headless_renderer() -> Arc<EmulatorRenderer> {
+    let mut config = InstanceCreateConfig::new(
+        CString::new("B4D Replay").unwrap(),
+        vk::make_api_version(0, 0, 1, 0)
+    );
+
+    // The LunarG desktop profile requires the swapchain extension which in turn requires the surface extensions
+    config.require_surface_khr();
+
+    let instance = create_instance(config).unwrap();
+
+    let mut config = DeviceCreateConfig::new();
+    config.disable_robustness(); // Match the device configuration used by b4d
+    let device = create_device(config, instance).unwrap();
+
+    Arc::new(EmulatorRenderer::new(device))
+}
+
+fn save_png(path: &PathBuf, size: Vec2u32, data: &[u8]) {
+    let file = File::create(path).unwrap();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size[0], size[1]);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(data).unwrap();
+}
@@ -0,0 +1,245 @@
+//! Exercises the validation of the c api with arbitrary input.
+//!
+//! The conversion of the structs passed by the java side and the handle tables are tested
+//! directly so no vulkan device is required. Entry points which only operate on handles are
+//! called with arbitrary handles. Rejected input unwinds out of the entry points with a
+//! [`CApiRejected`] payload when the `fuzzing` feature is enabled. Any other failure aborts the
+//! process.
+//!
+//! Run with `cargo fuzz run c_validation`.
+
+#![no_main]
+
+use std::ffi::c_void;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use b4d_core::{CMeshData, CVertexFormat};
+use b4d_core::c_validation::{CApiError, CApiRejected, HandleMut, HandleRef, HandleTable, take_last_error};
+use b4d_core::prelude::Vec3f32;
+
+extern "C-unwind" {
+    fn b4d_destroy_global_mesh(mesh: *mut c_void);
+    fn b4d_destroy_global_image(image: *mut c_void);
+    fn b4d_end_frame(recorder: *mut c_void);
+    fn b4d_init(surface: *mut c_void, enable_validation: u32) -> *mut c_void;
+    fn b4d_create_headless_surface(width: u32, height: u32) -> *mut c_void;
+}
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    ConvertMesh {
+        vertex_data: Vec<u8>,
+        index_data: Vec<u8>,
+        vertex_stride: u32,
+        index_count: u32,
+        index_type: i32,
+        primitive_topology: i32,
+        compression: u32,
+    },
+    ConvertVertexFormat {
+        stride: u32,
+        entries: [(bool, u32, i32); 6],
+        normal_encoding: u32,
+        position_quantization: Option<([f32; 3], [f32; 3])>,
+    },
+    Insert(u32),
+    InsertMany(Vec<u32>),
+    Get(u8),
+    GetMut(u8),
+    Release(u8),
+    Remove(u8),
+    RemoveMany(Vec<u8>),
+    RemoveRaw(usize),
+    DestroyRaw(usize),
+    CreateEmptyHeadlessSurface(u32),
+}
+
+/// Calls the entry point and returns [`None`] if the input was rejected.
+fn call<T>(f: impl FnOnce() -> T) -> Option<T> {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            assert!(payload.is::<CApiRejected>(), "entry point unwound with unexpected payload");
+            assert!(take_last_error().is_some(), "rejected call did not set the last error");
+            None
+        }
+    }
+}
+
+fn pick<T: Copy>(values: &[T], index: u8) -> Option<T> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values[(index as usize) % values.len()])
+    }
+}
+
+fuzz_target!(|operations: Vec<Operation>| {
+    let table: HandleTable<u32> = HandleTable::new("fuzz");
+    let mut handles: Vec<*mut u32> = Vec::new();
+    let mut shared: Vec<(*mut u32, HandleRef<u32>)> = Vec::new();
+    let mut exclusive: Vec<(*mut u32, HandleMut<u32>)> = Vec::new();
+
+    for operation in operations {
+        match operation {
+            Operation::ConvertMesh { vertex_data, index_data, vertex_stride, index_count, index_type, primitive_topology, compression } => {
+                let data = CMeshData {
+                    vertex_data_ptr: vertex_data.as_ptr(),
+                    vertex_data_len: vertex_data.len(),
+                    index_data_ptr: index_data.as_ptr(),
+                    index_data_len: index_data.len(),
+                    vertex_stride,
+                    index_count,
+                    index_type,
+                    primitive_topology,
+                    compression,
+                    vertex_data_uncompressed_len: vertex_data.len(),
+                    index_data_uncompressed_len: index_data.len(),
+                };
+                if let Ok(mesh) = unsafe { data.to_mesh_data() } {
+                    assert_eq!(compression, 0);
+                    let vertex_count = mesh.vertex_data.len() / (mesh.vertex_stride as usize);
+                    assert!(mesh.vertex_data.len() % (mesh.vertex_stride as usize) == 0);
+                    assert!(mesh.index_data.len() >= (mesh.index_count as usize) * 2);
+                    assert!(vertex_count > 0);
+                }
+            }
+            Operation::ConvertVertexFormat { stride, entries, normal_encoding, position_quantization } => {
+                let (quantization_offset, quantization_scale) = position_quantization.unwrap_or(([0f32; 3], [1f32; 3]));
+                let format = CVertexFormat {
+                    stride,
+                    position_offset: entries[0].1,
+                    position_format: entries[0].2,
+                    normal_offset: entries[1].1,
+                    normal_format: entries[1].2,
+                    color_offset: entries[2].1,
+                    color_format: entries[2].2,
+                    uv0_offset: entries[3].1,
+                    uv0_format: entries[3].2,
+                    uv1_offset: entries[4].1,
+                    uv1_format: entries[4].2,
+                    uv2_offset: entries[5].1,
+                    uv2_format: entries[5].2,
+                    normal_encoding,
+                    position_quantization_offset: Vec3f32::from(quantization_offset),
+                    position_quantization_scale: Vec3f32::from(quantization_scale),
+                    has_normal: entries[1].0,
+                    has_color: entries[2].0,
+                    has_uv0: entries[3].0,
+                    has_uv1: entries[4].0,
+                    has_uv2: entries[5].0,
+                    has_position_quantization: position_quantization.is_some(),
+                };
+                let _ = format.to_vertex_format();
+            }
+            Operation::Insert(value) => {
+                handles.push(table.insert(Box::new(value)));
+            }
+            Operation::InsertMany(values) => {
+                handles.extend(table.insert_many(values.into_iter().map(Box::new).collect()));
+            }
+            Operation::Get(index) => {
+                if let Some(handle) = pick(&handles, index) {
+                    let exclusively_borrowed = exclusive.iter().any(|(h, _)| *h == handle);
+                    match table.get(handle) {
+                        Ok(guard) => {
+                            assert!(!exclusively_borrowed);
+                            shared.push((handle, guard));
+                        }
+                        Err(err) => {
+                            assert!(exclusively_borrowed);
+                            assert!(matches!(err, CApiError::HandleInUse(_, _)));
+                        }
+                    }
+                }
+            }
+            Operation::GetMut(index) => {
+                if let Some(handle) = pick(&handles, index) {
+                    let borrowed = shared.iter().any(|(h, _)| *h == handle) || exclusive.iter().any(|(h, _)| *h == handle);
+                    match table.get_mut(handle) {
+                        Ok(mut guard) => {
+                            assert!(!borrowed);
+                            *guard = guard.wrapping_add(1);
+                            exclusive.push((handle, guard));
+                        }
+                        Err(err) => {
+                            assert!(borrowed);
+                            assert!(matches!(err, CApiError::HandleInUse(_, _)));
+                        }
+                    }
+                }
+            }
+            Operation::Release(index) => {
+                if (index as usize) % 2 == 0 && !shared.is_empty() {
+                    drop(shared.swap_remove((index as usize) % shared.len()));
+                } else if !exclusive.is_empty() {
+                    drop(exclusive.swap_remove((index as usize) % exclusive.len()));
+                }
+            }
+            Operation::Remove(index) => {
+                if !handles.is_empty() {
+                    let position = (index as usize) % handles.len();
+                    let handle = handles[position];
+                    let borrowed = shared.iter().any(|(h, _)| *h == handle) || exclusive.iter().any(|(h, _)| *h == handle);
+                    match table.remove(handle) {
+                        Ok(_) => {
+                            assert!(!borrowed);
+                            handles.swap_remove(position);
+                            assert!(!table.is_live(handle));
+                            assert!(matches!(table.get(handle), Err(CApiError::InvalidHandle(_, _))));
+                        }
+                        Err(err) => {
+                            assert!(borrowed);
+                            assert!(matches!(err, CApiError::HandleInUse(_, _)));
+                        }
+                    }
+                }
+            }
+            Operation::RemoveMany(indices) => {
+                let removed: Vec<_> = indices.iter().filter_map(|index| pick(&handles, *index)).collect();
+                let mut unique = removed.clone();
+                unique.sort();
+                unique.dedup();
+                let borrowed = removed.iter().any(|handle| shared.iter().any(|(h, _)| h == handle) || exclusive.iter().any(|(h, _)| h == handle));
+
+                // Duplicate or borrowed handles must reject the whole call
+                let result = table.remove_many(&removed);
+                assert_eq!(result.is_ok(), unique.len() == removed.len() && !borrowed);
+                if result.is_ok() {
+                    handles.retain(|handle| !removed.contains(handle));
+                } else {
+                    assert!(removed.iter().all(|handle| table.is_live(*handle)));
+                }
+            }
+            Operation::RemoveRaw(raw) => {
+                let handle = raw as *mut u32;
+                if !handles.contains(&handle) {
+                    assert!(table.remove(handle).is_err());
+                }
+            }
+            Operation::DestroyRaw(raw) => {
+                // No object has been created through the c api so every handle must be rejected
+                let handle = raw as *mut c_void;
+                assert!(call(|| unsafe { b4d_destroy_global_mesh(handle) }).is_none());
+                assert!(call(|| unsafe { b4d_destroy_global_image(handle) }).is_none());
+                assert!(call(|| unsafe { b4d_end_frame(handle) }).is_none());
+                assert!(call(|| unsafe { b4d_init(handle, 0) }).is_none());
+            }
+            Operation::CreateEmptyHeadlessSurface(size) => {
+                // Surfaces can only be consumed by creating a instance so only invalid sizes are
+                // exercised
+                assert!(call(|| unsafe { b4d_create_headless_surface(size, 0) }).is_none());
+                assert!(call(|| unsafe { b4d_create_headless_surface(0, size) }).is_none());
+            }
+        }
+    }
+
+    drop(shared);
+    drop(exclusive);
+    for handle in handles {
+        assert!(table.remove(handle).is_ok());
+    }
+});
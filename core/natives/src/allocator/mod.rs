@@ -1,32 +1,90 @@
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::fmt;
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use ash::vk;
 
 use crate::prelude::*;
 
+mod pool;
 mod vma;
 
+pub use pool::{MemoryPool, MemoryPoolInfo, MemoryPoolStats};
+
 pub struct Allocator {
     vma_allocator: vma::Allocator,
 
     debug: bool,
     functions: Arc<DeviceFunctions>,
+    memory_heaps: Box<[vk::MemoryHeap]>,
+    pools: Mutex<HashMap<String, Arc<MemoryPool>>>,
 }
 
 impl Allocator {
     pub fn new(functions: Arc<DeviceFunctions>) -> Result<Self, vk::Result> {
-        let vma_allocator = vma::Allocator::new(&functions, vma::AllocatorCreateFlags::empty())?;
+        let mut create_flags = vma::AllocatorCreateFlags::empty();
+        if functions.buffer_device_address {
+            create_flags |= vma::AllocatorCreateFlags::BUFFER_DEVICE_ADDRESS;
+        }
+        let vma_allocator = vma::Allocator::new(&functions, create_flags)?;
+
+        let memory_properties = unsafe {
+            functions.instance.vk().get_physical_device_memory_properties(functions.physical_device)
+        };
+        let memory_heaps = memory_properties.memory_heaps[0..(memory_properties.memory_heap_count as usize)].into();
 
         Ok(Self {
             vma_allocator,
             debug: true,
-            functions
+            functions,
+            memory_heaps,
+            pools: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Creates a new named memory pool. Returns [`None`] if a pool with the same name already
+    /// exists.
+    pub fn create_memory_pool(&self, name: &str, info: MemoryPoolInfo) -> Option<Arc<MemoryPool>> {
+        let mut guard = self.pools.lock().unwrap();
+        if guard.contains_key(name) {
+            return None;
+        }
+        let pool = Arc::new(MemoryPool::new(name.to_string(), info));
+        guard.insert(name.to_string(), pool.clone());
+        Some(pool)
+    }
+
+    pub fn find_memory_pool(&self, name: &str) -> Option<Arc<MemoryPool>> {
+        self.pools.lock().unwrap().get(name).cloned()
+    }
+
+    /// Returns the memory usage of every memory pool.
+    pub fn get_memory_pool_stats(&self) -> Vec<(String, MemoryPoolStats)> {
+        self.pools.lock().unwrap().values().map(|pool| {
+            (pool.get_name().to_string(), pool.get_stats(&self.vma_allocator))
+        }).collect()
+    }
+
+    /// Returns the combined usage and budget of all device local memory heaps.
+    ///
+    /// The budget is an estimate of how much memory the process can use without causing
+    /// performance problems. It is not a hard limit.
+    pub fn get_device_local_budget(&self) -> MemoryBudget {
+        let mut budgets = vec![vma::Budget::default(); self.memory_heaps.len()];
+        unsafe { self.vma_allocator.get_heap_budgets(&mut budgets) };
+
+        let mut result = MemoryBudget { usage: 0, budget: 0 };
+        for (heap, budget) in self.memory_heaps.iter().zip(budgets.iter()) {
+            if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
+                result.usage += budget.usage;
+                result.budget += budget.budget;
+            }
+        }
+        result
+    }
+
     /// Allocates vulkan memory for some requirements.
     ///
     /// Returns the allocation and a [`AllocationBindingInfo`] containing information necessary to
@@ -36,7 +94,7 @@ impl Allocator {
     ///
     /// `requirements` must be a valid [`vk::MemoryRequirements`] instance.
     pub unsafe fn allocate_memory(&self, requirements: &vk::MemoryRequirements, host_access: HostAccess, name: &fmt::Arguments) -> Option<(Allocation, AllocationBindingInfo)> {
-        let create_info = Self::make_default_info(host_access);
+        let create_info = Self::make_memory_info(host_access);
         let mut allocation_info = vma::AllocationInfo::default();
         match self.vma_allocator.allocate_memory(requirements, &create_info, Some(&mut allocation_info)) {
             Ok(allocation) => {
@@ -62,7 +120,7 @@ impl Allocator {
     ///
     /// Every entry in `requirements` must be a valid [`vk::MemoryRequirements`] instance.
     pub unsafe fn allocate_memory_pages(&self, requirements: &[vk::MemoryRequirements], host_access: HostAccess) -> Option<Vec<(Allocation, AllocationBindingInfo)>> {
-        let create_info: Box<_> = std::iter::repeat(Self::make_default_info(host_access).build()).take(requirements.len()).collect();
+        let create_info: Box<_> = std::iter::repeat(Self::make_memory_info(host_access).build()).take(requirements.len()).collect();
         let mut allocation_info = Vec::new();
         allocation_info.resize(requirements.len(), vma::AllocationInfo::default());
         match self.vma_allocator.allocate_memory_pages(requirements, create_info.as_ref(), Some(&mut allocation_info)) {
@@ -104,7 +162,24 @@ impl Allocator {
     ///
     /// `create_info` must be a valid [`vk::BufferCreateInfo`] instance.
     pub unsafe fn create_gpu_buffer(&self, create_info: &vk::BufferCreateInfo, name: &fmt::Arguments) -> Option<(vk::Buffer, Allocation)> {
-        let allocation_create_info = Self::make_default_info(HostAccess::None);
+        self.create_gpu_buffer_in(None, create_info, name)
+    }
+
+    /// Creates a gpu only buffer in a memory pool and binds memory to it. If no pool is specified
+    /// the default placement is used.
+    ///
+    /// If creation, allocation or binding fails [`None`] is returned.
+    ///
+    /// # Safety
+    ///
+    /// `create_info` must be a valid [`vk::BufferCreateInfo`] instance.
+    pub unsafe fn create_gpu_buffer_in(&self, pool: Option<&MemoryPool>, create_info: &vk::BufferCreateInfo, name: &fmt::Arguments) -> Option<(vk::Buffer, Allocation)> {
+        let mut allocation_create_info = Self::make_default_info(HostAccess::None);
+        if let Some(pool) = pool {
+            allocation_create_info = self.apply_pool(pool, allocation_create_info, |info| {
+                self.vma_allocator.find_memory_type_index_for_buffer_info(create_info, info)
+            })?;
+        }
         match self.vma_allocator.create_buffer(create_info, &allocation_create_info, None) {
             Ok((buffer, allocation)) => {
                 if self.debug {
@@ -147,6 +222,50 @@ impl Allocator {
         }
     }
 
+    /// Creates a buffer backed by host visible and host coherent memory which stays mapped for the
+    /// lifetime of the allocation. Writes by the host do not need to be flushed.
+    ///
+    /// `host_access` must be [`HostAccess::Random`] or [`HostAccess::SequentialWrite`]. For random
+    /// access host cached memory is preferred so that reading back gpu results is fast.
+    ///
+    /// Returns the buffer, allocation and a pointer to the mapped memory. If creation, allocation
+    /// or binding fails [`None`] is returned.
+    ///
+    /// # Safety
+    ///
+    /// `create_info` must be a valid [`vk::BufferCreateInfo`] instance.
+    pub unsafe fn create_mapped_buffer(&self, create_info: &vk::BufferCreateInfo, host_access: HostAccess, name: &fmt::Arguments) -> Option<(vk::Buffer, Allocation, NonNull<u8>)> {
+        let preferred_flags = match host_access {
+            HostAccess::Random => vk::MemoryPropertyFlags::HOST_CACHED,
+            HostAccess::SequentialWrite => vk::MemoryPropertyFlags::empty(),
+            _ => panic!("Mapped buffers require host access but got {:?}", host_access),
+        };
+        let allocation_create_info = Self::make_default_info(host_access)
+            .required_flags(vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT)
+            .preferred_flags(preferred_flags);
+
+        let mut allocation_info = vma::AllocationInfo::default();
+        match self.vma_allocator.create_buffer(create_info, &allocation_create_info, Some(&mut allocation_info)) {
+            Ok((buffer, allocation)) => {
+                if self.debug {
+                    self.set_allocation_name(allocation, name);
+                }
+                match NonNull::new(allocation_info.p_mapped_data as *mut u8) {
+                    Some(mapped) => Some((buffer, Allocation::new(allocation), mapped)),
+                    None => {
+                        log::warn!("Mapped vulkan buffer {:?} has no mapped memory", name);
+                        self.vma_allocator.destroy_buffer(buffer, allocation);
+                        None
+                    }
+                }
+            },
+            Err(err) => {
+                log::warn!("Failed to create mapped vulkan buffer {:?}. {:?}", name, err);
+                None
+            }
+        }
+    }
+
     /// Creates a gpu only image and binds memory to it.
     ///
     /// If creation, allocation or binding fails [`None`] is returned.
@@ -155,7 +274,24 @@ impl Allocator {
     ///
     /// `create_info` must be a valid [`vk::ImageCreateInfo`] instance.
     pub unsafe fn create_gpu_image(&self, create_info: &vk::ImageCreateInfo, name: &fmt::Arguments) -> Option<(vk::Image, Allocation)> {
-        let allocation_create_info = Self::make_default_info(HostAccess::None);
+        self.create_gpu_image_in(None, create_info, name)
+    }
+
+    /// Creates a gpu only image in a memory pool and binds memory to it. If no pool is specified
+    /// the default placement is used.
+    ///
+    /// If creation, allocation or binding fails [`None`] is returned.
+    ///
+    /// # Safety
+    ///
+    /// `create_info` must be a valid [`vk::ImageCreateInfo`] instance.
+    pub unsafe fn create_gpu_image_in(&self, pool: Option<&MemoryPool>, create_info: &vk::ImageCreateInfo, name: &fmt::Arguments) -> Option<(vk::Image, Allocation)> {
+        let mut allocation_create_info = Self::make_default_info(HostAccess::None);
+        if let Some(pool) = pool {
+            allocation_create_info = self.apply_pool(pool, allocation_create_info, |info| {
+                self.vma_allocator.find_memory_type_index_for_image_info(create_info, info)
+            })?;
+        }
         match self.vma_allocator.create_image(create_info, &allocation_create_info, None) {
             Ok((image, allocation)) => {
                 if self.debug {
@@ -228,6 +364,29 @@ impl Allocator {
         }
     }
 
+    /// Applies the strategy of a memory pool to `info` and selects the vma pool for the memory
+    /// type returned by `find_memory_type`.
+    fn apply_pool<'a, F>(&self, pool: &MemoryPool, info: vma::AllocationCreateInfoBuilder<'a>, find_memory_type: F) -> Option<vma::AllocationCreateInfoBuilder<'a>>
+        where F: FnOnce(&vma::AllocationCreateInfo) -> Result<u32, vk::Result> {
+
+        let mut info = info;
+        if pool.get_info().dedicated {
+            let flags = info.flags;
+            info = info.flags(flags | vma::AllocationCreateFlags::DEDICATED_MEMORY);
+        }
+        if pool.get_info().prefer_host_visible {
+            info = info.preferred_flags(vk::MemoryPropertyFlags::HOST_VISIBLE);
+        }
+
+        let vma_pool = find_memory_type(&info).and_then(|memory_type| {
+            pool.get_or_create_vma_pool(&self.vma_allocator, memory_type)
+        }).map_err(|err| {
+            log::warn!("Failed to select vma pool for memory pool {:?}. {:?}", pool.get_name(), err);
+        }).ok()?;
+
+        Some(info.pool(vma_pool.as_ptr()))
+    }
+
     fn make_default_info<'a>(host_access: HostAccess) -> vma::AllocationCreateInfoBuilder<'a> {
         vma::AllocationCreateInfo::builder()
             .flags(host_access.to_vma_flags())
@@ -237,6 +396,40 @@ impl Allocator {
             .memory_type_bits(0)
             .priority(0.5f32)
     }
+
+    /// Vma cannot select a memory type using [`vma::MemoryUsage::AUTO`] for raw memory allocations
+    /// since it does not know the resource the memory will be bound to. The memory property flags
+    /// are therefore derived directly from the host access.
+    fn make_memory_info<'a>(host_access: HostAccess) -> vma::AllocationCreateInfoBuilder<'a> {
+        let (required_flags, preferred_flags) = match host_access {
+            HostAccess::None => (vk::MemoryPropertyFlags::empty(), vk::MemoryPropertyFlags::DEVICE_LOCAL),
+            HostAccess::Random | HostAccess::SequentialWrite => (vk::MemoryPropertyFlags::HOST_VISIBLE, vk::MemoryPropertyFlags::empty()),
+            HostAccess::RandomOptional | HostAccess::SequentialWriteOptional => (vk::MemoryPropertyFlags::empty(), vk::MemoryPropertyFlags::HOST_VISIBLE),
+        };
+
+        vma::AllocationCreateInfo::builder()
+            .flags(host_access.to_vma_flags())
+            .usage(vma::MemoryUsage::UNKNOWN)
+            .required_flags(required_flags)
+            .preferred_flags(preferred_flags)
+            .memory_type_bits(0)
+            .priority(0.5f32)
+    }
+}
+
+impl Drop for Allocator {
+    fn drop(&mut self) {
+        for (_, pool) in self.pools.get_mut().unwrap().drain() {
+            unsafe { pool.destroy(&self.vma_allocator) };
+        }
+    }
+}
+
+/// Memory usage and budget in bytes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct MemoryBudget {
+    pub usage: u64,
+    pub budget: u64,
 }
 
 /// Handle of a allocation. This is only a handle and as such any instance must be manually freed.
@@ -274,6 +467,22 @@ impl AllocationBindingInfo {
             mapped_data: NonNull::new(info.p_mapped_data as *mut u8)
         }
     }
+
+    pub fn get_device_memory(&self) -> vk::DeviceMemory {
+        self.device_memory
+    }
+
+    pub fn get_offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    pub fn get_size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn get_mapped_data(&self) -> Option<NonNull<u8>> {
+        self.mapped_data
+    }
 }
 
 /// Describes how the host will access some vulkan memory.
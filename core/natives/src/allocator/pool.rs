@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use ash::vk;
+
+use super::vma;
+
+/// Placement strategy of a [`MemoryPool`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct MemoryPoolInfo {
+    /// If set every allocation of the pool uses its own device memory allocation.
+    pub dedicated: bool,
+
+    /// The size of the memory blocks allocated by the pool. 0 selects the allocator default.
+    pub block_size: u64,
+
+    /// If set host visible memory is preferred for allocations of the pool.
+    pub prefer_host_visible: bool,
+}
+
+/// Memory usage of a [`MemoryPool`] in bytes.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct MemoryPoolStats {
+    pub block_count: u32,
+    pub allocation_count: u32,
+    pub block_bytes: u64,
+    pub allocation_bytes: u64,
+}
+
+/// A named group of allocations sharing the same placement strategy. Keeping related resources
+/// (for example all chunk geometry) in the same pool improves locality and allows their memory
+/// usage to be reported separately.
+///
+/// Pools are created using [`Allocator::create_memory_pool`](super::Allocator::create_memory_pool)
+/// and live as long as the allocator.
+pub struct MemoryPool {
+    name: String,
+    info: MemoryPoolInfo,
+
+    /// Vma pools are bound to a single memory type so one is created for every memory type used
+    /// by allocations of the pool.
+    vma_pools: Mutex<HashMap<u32, vma::Pool>>,
+}
+
+impl MemoryPool {
+    pub(super) fn new(name: String, info: MemoryPoolInfo) -> Self {
+        Self {
+            name,
+            info,
+            vma_pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_info(&self) -> &MemoryPoolInfo {
+        &self.info
+    }
+
+    pub(super) fn get_or_create_vma_pool(&self, allocator: &vma::Allocator, memory_type: u32) -> Result<vma::Pool, vk::Result> {
+        let mut guard = self.vma_pools.lock().unwrap();
+        if let Some(pool) = guard.get(&memory_type) {
+            return Ok(*pool);
+        }
+
+        let info = vma::PoolCreateInfo {
+            memory_type_index: memory_type,
+            block_size: self.info.block_size,
+            ..Default::default()
+        };
+        let pool = unsafe { allocator.create_pool(&info) }?;
+        guard.insert(memory_type, pool);
+        Ok(pool)
+    }
+
+    pub(super) fn get_stats(&self, allocator: &vma::Allocator) -> MemoryPoolStats {
+        let mut result = MemoryPoolStats::default();
+        for pool in self.vma_pools.lock().unwrap().values() {
+            let mut statistics = vma::Statistics::default();
+            unsafe { allocator.get_pool_statistics(*pool, &mut statistics) };
+            result.block_count += statistics.block_count;
+            result.allocation_count += statistics.allocation_count;
+            result.block_bytes += statistics.block_bytes;
+            result.allocation_bytes += statistics.allocation_bytes;
+        }
+        result
+    }
+
+    /// Destroys all vma pools. All allocations of the pool must have been freed.
+    pub(super) unsafe fn destroy(&self, allocator: &vma::Allocator) {
+        for (_, pool) in self.vma_pools.lock().unwrap().drain() {
+            allocator.destroy_pool(pool);
+        }
+    }
+}
@@ -188,6 +188,50 @@ impl Default for AllocationInfo {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Statistics {
+    pub block_count: u32,
+    pub allocation_count: u32,
+    pub block_bytes: vk::DeviceSize,
+    pub allocation_bytes: vk::DeviceSize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Budget {
+    pub statistics: Statistics,
+    pub usage: vk::DeviceSize,
+    pub budget: vk::DeviceSize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PoolCreateInfo {
+    pub memory_type_index: u32,
+    pub flags: u32,
+    pub block_size: vk::DeviceSize,
+    pub min_block_count: usize,
+    pub max_block_count: usize,
+    pub priority: f32,
+    pub min_allocation_alignment: vk::DeviceSize,
+    pub p_memory_allocate_next: *mut c_void,
+}
+impl Default for PoolCreateInfo {
+    fn default() -> Self {
+        Self {
+            memory_type_index: 0,
+            flags: 0,
+            block_size: 0,
+            min_block_count: 0,
+            max_block_count: 0,
+            priority: 0.5,
+            min_allocation_alignment: 0,
+            p_memory_allocate_next: std::ptr::null_mut()
+        }
+    }
+}
+
 #[repr(C)]
 struct VulkanFunctions {
     vk_get_instance_proc_addr: vk::PFN_vkGetInstanceProcAddr,
@@ -348,6 +392,12 @@ impl Allocator {
         sys::vmaSetAllocationName(self.handle, allocation, name.as_ptr())
     }
 
+    /// Writes the budget of each memory heap into `budgets`. The slice must have at least as many
+    /// elements as there are memory heaps.
+    pub unsafe fn get_heap_budgets(&self, budgets: &mut [Budget]) {
+        sys::vmaGetHeapBudgets(self.handle, budgets.as_mut_ptr())
+    }
+
     pub unsafe fn create_buffer(&self, buffer_create_info: &vk::BufferCreateInfo, allocation_create_info: &AllocationCreateInfo, allocation_info: Option<&mut AllocationInfo>) -> Result<(vk::Buffer, Allocation), vk::Result> {
         let mut buffer_handle = vk::Buffer::null();
         let mut allocation_handle = Allocation::null();
@@ -379,6 +429,44 @@ impl Allocator {
     pub unsafe fn destroy_image(&self, image: vk::Image, allocation: Allocation) {
         sys::vmaDestroyImage(self.handle, image, allocation)
     }
+
+    pub unsafe fn find_memory_type_index_for_buffer_info(&self, buffer_create_info: &vk::BufferCreateInfo, allocation_create_info: &AllocationCreateInfo) -> Result<u32, vk::Result> {
+        let mut index = 0u32;
+        let result = sys::vmaFindMemoryTypeIndexForBufferInfo(self.handle, buffer_create_info, allocation_create_info, &mut index);
+        if result == vk::Result::SUCCESS {
+            Ok(index)
+        } else {
+            Err(result)
+        }
+    }
+
+    pub unsafe fn find_memory_type_index_for_image_info(&self, image_create_info: &vk::ImageCreateInfo, allocation_create_info: &AllocationCreateInfo) -> Result<u32, vk::Result> {
+        let mut index = 0u32;
+        let result = sys::vmaFindMemoryTypeIndexForImageInfo(self.handle, image_create_info, allocation_create_info, &mut index);
+        if result == vk::Result::SUCCESS {
+            Ok(index)
+        } else {
+            Err(result)
+        }
+    }
+
+    pub unsafe fn create_pool(&self, create_info: &PoolCreateInfo) -> Result<Pool, vk::Result> {
+        let mut handle = Pool::null();
+        let result = sys::vmaCreatePool(self.handle, create_info, &mut handle);
+        if result == vk::Result::SUCCESS {
+            Ok(handle)
+        } else {
+            Err(result)
+        }
+    }
+
+    pub unsafe fn destroy_pool(&self, pool: Pool) {
+        sys::vmaDestroyPool(self.handle, pool)
+    }
+
+    pub unsafe fn get_pool_statistics(&self, pool: Pool, statistics: &mut Statistics) {
+        sys::vmaGetPoolStatistics(self.handle, pool, statistics)
+    }
 }
 
 unsafe impl Send for Allocator {}
@@ -415,6 +503,24 @@ unsafe impl Send for Allocation {
 unsafe impl Sync for Allocation {
 }
 
+#[derive(Copy, Clone, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct Pool(*const u8);
+
+impl Pool {
+    pub const fn null() -> Self {
+        Pool(std::ptr::null())
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        self.0
+    }
+}
+unsafe impl Send for Pool {
+}
+unsafe impl Sync for Pool {
+}
+
 mod sys {
     use super::*;
 
@@ -463,6 +569,11 @@ mod sys {
             p_allocation_info: *mut AllocationInfo,
         );
 
+        pub(super) fn vmaGetHeapBudgets(
+            allocator: AllocatorHandle,
+            p_budgets: *mut Budget,
+        );
+
         pub(super) fn vmaSetAllocationName(
             allocator: AllocatorHandle,
             allocation: Allocation,
@@ -498,5 +609,36 @@ mod sys {
             image: vk::Image,
             allocation: Allocation,
         );
+
+        pub(super) fn vmaFindMemoryTypeIndexForBufferInfo(
+            allocator: AllocatorHandle,
+            p_buffer_create_info: *const vk::BufferCreateInfo,
+            p_allocation_create_info: *const AllocationCreateInfo,
+            p_memory_type_index: *mut u32,
+        ) -> vk::Result;
+
+        pub(super) fn vmaFindMemoryTypeIndexForImageInfo(
+            allocator: AllocatorHandle,
+            p_image_create_info: *const vk::ImageCreateInfo,
+            p_allocation_create_info: *const AllocationCreateInfo,
+            p_memory_type_index: *mut u32,
+        ) -> vk::Result;
+
+        pub(super) fn vmaCreatePool(
+            allocator: AllocatorHandle,
+            p_create_info: *const PoolCreateInfo,
+            p_pool: *mut Pool,
+        ) -> vk::Result;
+
+        pub(super) fn vmaDestroyPool(
+            allocator: AllocatorHandle,
+            pool: Pool,
+        );
+
+        pub(super) fn vmaGetPoolStatistics(
+            allocator: AllocatorHandle,
+            pool: Pool,
+            p_pool_stats: *mut Statistics,
+        );
     }
 }
\ No newline at end of file
@@ -1,76 +1,525 @@
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
 use ash::vk;
+use log::LevelFilter;
 use crate::BUILD_INFO;
 
 use crate::instance::debug_messenger::RustLogDebugMessenger;
-use crate::device::init::{create_device, DeviceCreateConfig};
-use crate::device::surface::{DeviceSurface, SurfaceSwapchain, SwapchainConfig};
+use crate::device::capabilities::DeviceCapabilities;
+use crate::device::device_group::DeviceGroupMode;
+use crate::device::device_utils::OutputRotation;
+use crate::device::init::{create_device, DeviceCreateConfig, DeviceCreateError, DevicePreference};
+use crate::device::fault::{collect_fault_report, FaultReport};
+use crate::device::surface::{DeviceSurface, DisplayTiming, FullScreenExclusiveMode, SurfaceSwapchain, SwapchainConfig, SwapchainCreateError};
+use crate::instance::host_memory::HostMemoryReport;
 use crate::instance::init::{create_instance, InstanceCreateConfig};
-use crate::vk::objects::surface::SurfaceProvider;
+use crate::vk::objects::surface::{DisplayMode, SurfaceProvider};
 
 use crate::prelude::*;
-use crate::renderer::emulator::{EmulatorRenderer, GlobalImage, GlobalMesh, MeshData};
-use crate::renderer::emulator::debug_pipeline::{DebugPipeline, DebugPipelineMode};
-use crate::renderer::emulator::mc_shaders::{McUniform, ShaderId, VertexFormat};
+use crate::renderer::emulator::{EmulatorRenderer, FrameSize, GlobalImage, GlobalMesh, GlobalMeshId, ImageArrayMode, MeshData, SubmitMode};
+use crate::renderer::emulator::auto_exposure::ExposureAdaptation;
+use crate::renderer::emulator::beam::BeamMesh;
+use crate::renderer::emulator::camera_effects::CameraEffects;
+use crate::renderer::emulator::command_log::{PassCommandLog, ReplayResources};
+use crate::renderer::emulator::command_stream::{StreamEvent, StreamRecorder, StreamRecorderConfig};
+use crate::renderer::emulator::color_grading::{ColorGrading, ColorMatrix};
+use crate::renderer::emulator::compositor::CompositeLayer;
+use crate::renderer::emulator::debug_overlay::{ChunkGridOverlay, DebugOverlays, SectionHeatmap};
+use crate::renderer::emulator::draw_tag::DrawTagStats;
+use crate::renderer::emulator::render_budget::{BudgetReport, RenderBudget};
+use crate::renderer::emulator::debug_pipeline::{DebugPipeline, DebugPipelineMode, VertexFetchMode};
+use crate::renderer::emulator::event_log::{EventLog, EventLogTarget, RendererEvent};
+use crate::renderer::emulator::jobs::{JobExecutor, JobStats, JobSystem};
+use crate::renderer::emulator::map_texture::MapTextureCache;
+use crate::renderer::emulator::frame_times::{FrameTimeReport, FrameTimeTracker, DEFAULT_SAMPLE_WINDOW, HISTOGRAM_BUCKET_COUNT};
+use crate::renderer::emulator::mc_shaders::{McUniform, ShaderId, ShaderSpecialization, VertexFormat, VertexFormatEntry, VertexFormatError, VertexFormatId};
+use crate::renderer::emulator::memory::{MemoryBudget, MemoryMonitor, MemoryPressure, MemoryPressureThresholds};
 use crate::renderer::emulator::PassRecorder;
-use crate::renderer::emulator::pipeline::{EmulatorPipeline, SwapchainOutput};
+use crate::renderer::emulator::panorama::{PanoramaCallback, PanoramaCapture};
+use crate::renderer::emulator::pipeline::{EmulatorPipeline, OffscreenOutput, OffscreenReadback, SwapchainOutput};
+use crate::renderer::emulator::probe::{ProbeCallback, ProbeCapture};
+use crate::renderer::emulator::post_chain::{AccessibilityFilter, PostChain, PostChainError, PostChainId, ScreenDistortion};
+use crate::renderer::emulator::render_layer::{InsertionPoint, RenderLayerError, RenderLayerId, RenderLayerState, RenderLayerStats, RenderOrderEntry};
+use crate::renderer::emulator::unproject::{ScreenPoint, ViewMatrices, WorldRay};
+use crate::renderer::emulator::warm_state::{MeshRetention, RendererSnapshot};
+use crate::renderer::emulator::watchdog::{HangCallback, Watchdog, WatchdogConfig};
 use crate::util::format::Format;
+use crate::util::log_filter::{self, LogSubsystem};
+
+/// Configuration used to create a [`Blaze4D`] instance.
+///
+/// Some options can be changed after creation using [`Blaze4D::set_vsync`],
+/// [`Blaze4D::set_frames_in_flight`] and [`Blaze4D::set_debug_mode`].
+#[derive(Clone, Debug)]
+pub struct B4DConfig {
+    /// Enables the vulkan validation layers.
+    pub enable_validation: bool,
+
+    /// Enables gpu assisted validation. Only used if [`B4DConfig::enable_validation`] is set.
+    pub gpu_assisted_validation: bool,
+
+    /// Keeps robust buffer access enabled and additionally enables robustBufferAccess2 and
+    /// nullDescriptor if supported. Useful to isolate memory corruption bugs but reduces
+    /// performance.
+    pub robust_access: bool,
+
+    /// The type of device which should be preferred if multiple devices are supported.
+    pub device_preference: DevicePreference,
+
+    /// The number of samples per pixel used for multisampled render targets. Clamped to the
+    /// largest sample count supported by the device.
+    pub msaa_samples: u32,
+
+    /// If true presentation is synchronized to the vertical blank of the display.
+    pub vsync: bool,
+
+    /// The maximum number of frames which may be queued for presentation. The emulator keeps host
+    /// side state for one additional frame so the next frame can be recorded while this many
+    /// frames are processed by the gpu.
+    pub frames_in_flight: u32,
+
+    /// The maximum number of bytes of device memory which should be used or [`None`] if no limit
+    /// should be applied.
+    pub memory_budget: Option<u64>,
+
+    /// The debug mode used for all frames until changed by [`Blaze4D::set_debug_mode`].
+    pub debug_mode: Option<DebugPipelineMode>,
+
+    /// Fetches vertex data in the vertex shader instead of using the vertex input state. All
+    /// vertex formats then share the same vertex input state. See [`VertexFetchMode::Pulling`].
+    pub vertex_pulling: bool,
+
+    /// If set the pipeline cache is loaded from this file during creation and written back when
+    /// the instance is dropped.
+    pub pipeline_cache_path: Option<PathBuf>,
+
+    /// If set a watchdog thread reports passes which do not complete within the timeout. See
+    /// [`Blaze4D::set_hang_callback`].
+    pub watchdog: Option<WatchdogConfig>,
+
+    /// Experimental. If set and the selected device is part of a device group with more than one
+    /// device the main pass of each frame is distributed over the group. See
+    /// [`crate::device::device_group`].
+    pub device_group: Option<DeviceGroupMode>,
+
+    /// Tracks host memory allocated by the vulkan implementation. See
+    /// [`Blaze4D::get_host_memory_report`].
+    pub track_host_memory: bool,
+
+    /// The number of threads used for cpu side work like culling and mesh optimization. If 0 the
+    /// count is derived from the number of available cores. See [`Blaze4D::set_job_thread_count`].
+    pub job_threads: u32,
+}
+
+impl B4DConfig {
+    pub fn new() -> Self {
+        Self {
+            enable_validation: false,
+            gpu_assisted_validation: false,
+            robust_access: false,
+            device_preference: DevicePreference::Default,
+            msaa_samples: 1,
+            vsync: false,
+            frames_in_flight: 2,
+            memory_budget: None,
+            debug_mode: Some(DebugPipelineMode::Color),
+            vertex_pulling: false,
+            pipeline_cache_path: None,
+            watchdog: None,
+            device_group: None,
+            track_host_memory: false,
+            job_threads: JobSystem::AUTOMATIC_THREAD_COUNT,
+        }
+    }
+}
+
+impl Default for B4DConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 pub struct Blaze4D {
     instance: Arc<InstanceContext>,
-    device: Arc<DeviceContext>,
-    emulator: Arc<EmulatorRenderer>,
+    config: B4DConfig,
+
+    /// Always [`Some`] except while the device is being recreated.
+    render_config: Mutex<Option<RenderConfig>>,
+
+    /// Shared by all emulators so it survives device recreation.
+    job_system: Arc<JobSystem>,
+
+    device_lost_callback: Mutex<Option<Box<dyn Fn(DeviceLostReason) + Send>>>,
+
+    memory_monitor: Mutex<MemoryMonitor>,
+    memory_pressure_callback: Mutex<Option<Box<dyn Fn(MemoryPressure, MemoryBudget) + Send>>>,
+    mesh_eviction_callback: Mutex<Option<Box<dyn Fn(GlobalMeshId) + Send>>>,
 
-    render_config: Mutex<RenderConfig>,
+    stream_recorder: Arc<Mutex<Option<StreamRecorder>>>,
+
+    /// The per layer statistics of the last ended frame.
+    last_frame_layer_stats: Arc<Mutex<Vec<(RenderLayerId, RenderLayerStats)>>>,
+    /// The per draw tag statistics of the last ended frame.
+    last_frame_tag_stats: Arc<Mutex<Vec<(u64, DrawTagStats)>>>,
+    /// The work dropped because of the render budget in the last ended frame.
+    last_frame_budget_report: Arc<Mutex<BudgetReport>>,
+
+    /// Rolling cpu and gpu frame times of recent frames.
+    frame_times: Arc<Mutex<FrameTimeTracker>>,
+
+    /// The frame size and camera matrices of the last ended frame which set camera matrices.
+    last_view_matrices: Arc<Mutex<Option<(FrameSize, ViewMatrices)>>>,
+
+    /// The built-in debug overlays drawn at the end of every frame if a shader is set.
+    debug_overlays: Mutex<Arc<DebugOverlays>>,
+    debug_overlay_shader: Mutex<Option<ShaderId>>,
+
+    hang_callback: Arc<Mutex<Option<HangCallback>>>,
+
+    /// Shared with the render config so swapchain events can be logged.
+    event_log: Arc<Mutex<Option<EventLog>>>,
+    /// The number of frames started. Used to identify frames in the event log.
+    frame_index: AtomicU64,
+
+    last_fault_report: Mutex<Option<FaultReport>>,
 }
 
 impl Blaze4D {
-    /// Creates a new Blaze4D instance and starts all engine modules.
-    ///
-    /// The supported vertex formats for the [`EmulatorRenderer`] must be provided here.
-    pub fn new(mut main_window: Box<dyn SurfaceProvider>, enable_validation: bool) -> Self {
-        log::info!("Creating Blaze4D instance {:?}", BUILD_INFO);
+    /// Creates a new Blaze4D instance using the default configuration and starts all engine
+    /// modules.
+    pub fn new(main_window: Box<dyn SurfaceProvider>, enable_validation: bool) -> Self {
+        let mut config = B4DConfig::new();
+        config.enable_validation = enable_validation;
+
+        Self::new_with_config(main_window, config)
+    }
+
+    /// Creates a new Blaze4D instance using the provided configuration and starts all engine
+    /// modules.
+    pub fn new_with_config(main_window: Box<dyn SurfaceProvider>, config: B4DConfig) -> Self {
+        log::info!("Creating Blaze4D instance {:?} with config {:?}", BUILD_INFO, config);
 
         let mut instance_config = InstanceCreateConfig::new(
             CString::new("Minecraft").unwrap(),
             vk::make_api_version(0, 0, 1, 0)
         );
-        if enable_validation {
+        if config.enable_validation {
             instance_config.enable_validation();
         }
+        if config.gpu_assisted_validation {
+            instance_config.enable_gpu_assisted_validation();
+        }
         instance_config.add_debug_messenger(Box::new(RustLogDebugMessenger::new()));
         for ext in main_window.get_required_instance_extensions() {
             instance_config.add_required_extension(&ext);
         }
+        instance_config.add_optional_extension(&CString::new("VK_KHR_get_surface_capabilities2").unwrap());
+        #[cfg(feature = "portability")]
+        instance_config.enable_portability();
+        if config.track_host_memory {
+            instance_config.enable_host_memory_tracking();
+        }
 
         let instance = create_instance(instance_config).unwrap();
 
+        let job_system = Arc::new(JobSystem::new(config.job_threads));
+        let hang_callback = Arc::new(Mutex::new(None));
+        let event_log = Arc::new(Mutex::new(None));
+        let mut render_config = Self::create_render_config(&instance, &config, main_window, &job_system, &hang_callback, &event_log).unwrap_or_else(|err| {
+            log::error!("Failed to create device in Blaze4D::new(): {:?}", err);
+            panic!()
+        });
+        render_config.debug_mode = config.debug_mode;
+        render_config.vsync = config.vsync;
+        render_config.frames_in_flight = std::cmp::max(config.frames_in_flight, 1);
+
+        let memory_monitor = MemoryMonitor::new(config.memory_budget);
+
+        Self {
+            instance,
+            config,
+
+            render_config: Mutex::new(Some(render_config)),
+            job_system,
+
+            device_lost_callback: Mutex::new(None),
+
+            memory_monitor: Mutex::new(memory_monitor),
+            memory_pressure_callback: Mutex::new(None),
+            mesh_eviction_callback: Mutex::new(None),
+
+            stream_recorder: Arc::new(Mutex::new(None)),
+            last_frame_layer_stats: Arc::new(Mutex::new(Vec::new())),
+            last_frame_tag_stats: Arc::new(Mutex::new(Vec::new())),
+            last_frame_budget_report: Arc::new(Mutex::new(BudgetReport::default())),
+            last_view_matrices: Arc::new(Mutex::new(None)),
+            debug_overlays: Mutex::new(Arc::new(DebugOverlays::new())),
+            debug_overlay_shader: Mutex::new(None),
+            frame_times: Arc::new(Mutex::new(FrameTimeTracker::new(DEFAULT_SAMPLE_WINDOW))),
+
+            hang_callback,
+            event_log,
+            frame_index: AtomicU64::new(0),
+
+            last_fault_report: Mutex::new(None),
+        }
+    }
+
+    /// Initializes the surface of the main window and creates all device level objects.
+    fn create_render_config(instance: &Arc<InstanceContext>, config: &B4DConfig, mut main_window: Box<dyn SurfaceProvider>, job_system: &Arc<JobSystem>, hang_callback: &Arc<Mutex<Option<HangCallback>>>, event_log: &Arc<Mutex<Option<EventLog>>>) -> Result<RenderConfig, DeviceCreateError> {
         let window_surface = main_window.init(instance.get_entry(), instance.vk()).unwrap();
 
         let mut device_config = DeviceCreateConfig::new();
         device_config.require_swapchain();
         device_config.add_surface(window_surface);
-        device_config.disable_robustness();
+        if config.robust_access {
+            device_config.enable_robustness_2();
+        } else {
+            device_config.disable_robustness();
+        }
+        device_config.enable_full_screen_exclusive();
+        device_config.enable_present_wait();
+        device_config.enable_display_timing();
+        device_config.enable_fault_reporting();
+        device_config.enable_sparse_residency();
+        device_config.enable_buffer_device_address();
+        device_config.set_device_preference(config.device_preference);
+        if let Some(mode) = config.device_group {
+            device_config.enable_device_group(mode);
+        }
+        if let Some(path) = &config.pipeline_cache_path {
+            match std::fs::read(path) {
+                Ok(data) => device_config.set_pipeline_cache_data(data),
+                Err(err) => log::info!("Failed to read pipeline cache {:?}: {:?}", path, err),
+            }
+        }
 
-        let device = create_device(device_config, instance.clone()).unwrap_or_else(|err| {
-            log::error!("Failed to create device in Blaze4D::new(): {:?}", err);
-            panic!()
-        });
+        let device = create_device(device_config, instance.clone())?;
         let main_surface = DeviceSurface::new(device.get_functions().clone(), main_window);
 
-        let emulator = Arc::new(EmulatorRenderer::new(device.clone()));
+        // One additional frame slot is used by the frame currently recorded by the host
+        let emulator = Arc::new(EmulatorRenderer::with_job_system(device.clone(), std::cmp::max(config.frames_in_flight, 1) + 1, job_system.clone()));
 
-        let render_config = Mutex::new(RenderConfig::new(device.clone(), emulator.clone(), main_surface));
+        let msaa_samples = Self::find_msaa_samples(&device, config.msaa_samples);
 
-        Self {
-            instance,
-            device,
-            emulator,
+        let watchdog = config.watchdog.map(|watchdog| emulator.start_watchdog(watchdog, hang_callback.clone()));
+
+        let mut render_config = RenderConfig::new(device, emulator, main_surface, msaa_samples);
+        render_config.watchdog = watchdog;
+        render_config.event_log = event_log.clone();
+        if config.vertex_pulling {
+            render_config.vertex_fetch_mode = VertexFetchMode::Pulling;
+        }
+        Ok(render_config)
+    }
 
-            render_config,
+    fn find_msaa_samples(device: &DeviceContext, requested: u32) -> u32 {
+        let limits = unsafe {
+            device.get_instance().vk().get_physical_device_properties(device.get_functions().physical_device)
+        }.limits;
+        let supported = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+        let mut samples = 1u32;
+        while samples * 2 <= requested && supported.contains(vk::SampleCountFlags::from_raw(samples * 2)) {
+            samples *= 2;
+        }
+        if samples != requested {
+            log::warn!("Requested {} msaa samples but only {} are supported", requested, samples);
+        }
+        samples
+    }
+
+    fn with_render_config<R, F: FnOnce(&mut RenderConfig) -> R>(&self, func: F) -> R {
+        let mut guard = self.render_config.lock().unwrap();
+        func(guard.as_mut().unwrap())
+    }
+
+    fn get_emulator(&self) -> Arc<EmulatorRenderer> {
+        self.with_render_config(|config| config.emulator.clone())
+    }
+
+    /// Registers a callback which is called after the device has been recreated because it was
+    /// lost or the adapter changed.
+    ///
+    /// All global meshes, images and shaders created before the callback was called are invalid
+    /// and must be recreated by the callback.
+    pub fn set_device_lost_callback(&self, callback: Option<Box<dyn Fn(DeviceLostReason) + Send>>) {
+        *self.device_lost_callback.lock().unwrap() = callback;
+    }
+
+    /// Returns the fault information collected the last time the device was lost or [`None`] if
+    /// the device has never been lost. Intended to be included in crash reports.
+    pub fn get_last_fault_report(&self) -> Option<FaultReport> {
+        self.last_fault_report.lock().unwrap().clone()
+    }
+
+    /// Registers a callback which is called from the watchdog thread whenever a pass has not
+    /// completed within [`WatchdogConfig::timeout`]. Only used if the watchdog has been enabled in
+    /// the [`B4DConfig`]. The report is always written to the log.
+    pub fn set_hang_callback(&self, callback: Option<HangCallback>) {
+        *self.hang_callback.lock().unwrap() = callback;
+    }
+
+    /// Registers a callback which is called whenever the device memory pressure level changes.
+    ///
+    /// The callback is called from inside [`Blaze4D::try_start_frame_scaled`] before the frame is
+    /// started and may create or drop global objects.
+    pub fn set_memory_pressure_callback(&self, callback: Option<Box<dyn Fn(MemoryPressure, MemoryBudget) + Send>>) {
+        *self.memory_pressure_callback.lock().unwrap() = callback;
+    }
+
+    /// Registers a callback which is called for each evictable mesh selected for eviction.
+    ///
+    /// Eviction only happens if a callback is registered. The callback should drop all references
+    /// to the mesh and recreate it the next time it is needed. Evicted meshes are no longer flagged
+    /// as evictable.
+    pub fn set_mesh_eviction_callback(&self, callback: Option<Box<dyn Fn(GlobalMeshId) + Send>>) {
+        *self.mesh_eviction_callback.lock().unwrap() = callback;
+    }
+
+    /// Sets the number of threads used for cpu side work like culling and mesh optimization. If 0
+    /// the count is derived from the number of available cores. Replaces any executor set using
+    /// [`Blaze4D::set_job_executor`].
+    pub fn set_job_thread_count(&self, thread_count: u32) {
+        self.job_system.set_thread_count(thread_count);
+    }
+
+    /// Runs cpu side jobs using a host provided executor instead of the internal threads. Passing
+    /// [`None`] restores the internal threads using the count configured in [`B4DConfig`].
+    pub fn set_job_executor(&self, executor: Option<Arc<dyn JobExecutor>>) {
+        match executor {
+            Some(executor) => self.job_system.set_executor(executor),
+            None => self.job_system.set_thread_count(self.config.job_threads),
+        }
+    }
+
+    /// Returns the accumulated timing statistics of all job batches since the last call to
+    /// [`Blaze4D::reset_job_stats`].
+    pub fn get_job_stats(&self) -> JobStats {
+        self.job_system.get_stats()
+    }
+
+    pub fn reset_job_stats(&self) {
+        self.job_system.reset_stats();
+    }
+
+    pub fn get_job_system(&self) -> &Arc<JobSystem> {
+        &self.job_system
+    }
+
+    /// Flags a global mesh as evictable or removes the flag.
+    pub fn set_mesh_evictable(&self, mesh: &Arc<GlobalMesh>, evictable: bool) {
+        self.memory_monitor.lock().unwrap().set_evictable(mesh, evictable);
+    }
+
+    /// Configures the fractions of the memory budget at which the memory pressure level changes.
+    pub fn set_memory_pressure_thresholds(&self, thresholds: MemoryPressureThresholds) {
+        self.memory_monitor.lock().unwrap().set_thresholds(thresholds);
+    }
+
+    /// Returns the current device local memory usage and budget. If a memory budget has been
+    /// configured the returned budget is clamped to it.
+    pub fn get_memory_usage(&self) -> MemoryBudget {
+        let device = self.with_render_config(|config| config.device.clone());
+        self.memory_monitor.lock().unwrap().get_budget(&device)
+    }
+
+    /// Returns the vulkan version and feature tier of the current device and how optional
+    /// features like synchronization2 are provided.
+    pub fn get_device_capabilities(&self) -> DeviceCapabilities {
+        self.with_render_config(|config| *config.device.get_capabilities())
+    }
+
+    /// Sets the log level of a subsystem. If [`None`] the subsystem uses the default level. The
+    /// levels are global and shared by all instances.
+    pub fn set_log_level(&self, subsystem: LogSubsystem, level: Option<LevelFilter>) {
+        log_filter::set_level(subsystem, level);
+    }
+
+    /// Returns the host memory currently allocated by the vulkan implementation or [`None`] if
+    /// [`B4DConfig::track_host_memory`] is not set.
+    pub fn get_host_memory_report(&self) -> Option<HostMemoryReport> {
+        self.instance.get_host_memory_report()
+    }
+
+    /// Returns the number of msaa samples selected during creation.
+    pub fn get_msaa_samples(&self) -> u32 {
+        self.with_render_config(|config| config.msaa_samples)
+    }
+
+    /// Returns the device memory budget or [`None`] if no budget has been configured.
+    pub fn get_memory_budget(&self) -> Option<u64> {
+        self.config.memory_budget
+    }
+
+    /// Limits the number of bytes uploaded to global meshes and images per frame. Uploads are
+    /// processed in order of their upload priority. Objects used in a frame are always uploaded
+    /// before the frame regardless of the budget.
+    ///
+    /// If [`None`] is passed uploads are not limited.
+    pub fn set_upload_budget(&self, budget: Option<u64>) {
+        self.get_emulator().set_upload_budget(budget);
+    }
+
+    /// Sets the per frame limits on immediate uploads and per layer draws. See
+    /// [`render_budget`](crate::renderer::emulator::render_budget).
+    ///
+    /// The upload limit is preserved if the device is recreated. Layer limits are discarded
+    /// together with the render layers.
+    pub fn set_render_budget(&self, budget: RenderBudget) {
+        self.get_emulator().set_render_budget(budget);
+    }
+
+    pub fn get_render_budget(&self) -> RenderBudget {
+        self.get_emulator().get_render_budget()
+    }
+
+    /// Selects when recorded frames are handed to the render worker. See [`SubmitMode`]. Applies
+    /// to all frames started after this call and is preserved if the device is recreated.
+    pub fn set_submit_mode(&self, mode: SubmitMode) {
+        self.get_emulator().set_submit_mode(mode);
+    }
+
+    /// Sets the camera effects applied by all following frames which enable them. See
+    /// [`camera_effects`](crate::renderer::emulator::camera_effects).
+    pub fn set_camera_effects(&self, effects: CameraEffects) {
+        self.get_emulator().set_camera_effects(effects);
+    }
+
+    /// Enables or disables vsync. The swapchain is recreated before the next frame.
+    pub fn set_vsync(&self, vsync: bool) {
+        self.with_render_config(|config| config.set_vsync(vsync));
+    }
+
+    /// Configures the maximum number of frames which may be in flight. The swapchain, the
+    /// pipeline and all per frame resources of the emulator are rebuilt before the next frame
+    /// once all frames currently in flight have completed.
+    ///
+    /// The emulator keeps one additional frame slot for the frame currently recorded by the host.
+    pub fn set_frames_in_flight(&self, frames_in_flight: u32) {
+        self.with_render_config(|config| config.set_frames_in_flight(frames_in_flight));
+    }
+
+    /// Writes the current pipeline cache to the configured pipeline cache path. Does nothing if no
+    /// path has been configured.
+    pub fn save_pipeline_cache(&self) {
+        if let Some(path) = &self.config.pipeline_cache_path {
+            let data = match self.with_render_config(|config| config.device.get_pipeline_cache_data()) {
+                Ok(data) => data,
+                Err(err) => {
+                    log::warn!("Failed to retrieve pipeline cache data {:?}", err);
+                    return;
+                }
+            };
+
+            if let Err(err) = std::fs::write(path, data) {
+                log::warn!("Failed to write pipeline cache {:?}: {:?}", path, err);
+            }
         }
     }
 
@@ -79,60 +528,975 @@ impl Blaze4D {
     ///
     /// If [`None`] is passed the debug mode is disabled.
     pub fn set_debug_mode(&self, mode: Option<DebugPipelineMode>) {
-        self.render_config.lock().unwrap().set_debug_mode(mode);
+        self.with_render_config(|config| config.set_debug_mode(mode));
+    }
+
+    /// Returns all display modes supported by the monitor the main window is currently on.
+    pub fn get_display_modes(&self) -> Vec<DisplayMode> {
+        self.with_render_config(|config| config.main_surface.get_surface_provider().get_display_modes())
+    }
+
+    /// Requests the main window to enter exclusive fullscreen with the specified display mode or
+    /// to leave fullscreen if [`None`] is passed.
+    ///
+    /// If VK_EXT_full_screen_exclusive is supported the swapchain will also be recreated to allow
+    /// full screen exclusive mode. Returns false if the window does not support exclusive fullscreen.
+    ///
+    /// Glfw windows must only be modified on the main thread so for them this must be called on
+    /// the main thread.
+    pub fn set_exclusive_fullscreen(&self, mode: Option<DisplayMode>) -> bool {
+        self.with_render_config(|config| {
+            if !config.main_surface.get_surface_provider().set_exclusive_fullscreen(mode) {
+                return false;
+            }
+
+            let full_screen_exclusive = if mode.is_some() {
+                FullScreenExclusiveMode::Allowed
+            } else {
+                FullScreenExclusiveMode::Default
+            };
+            config.set_full_screen_exclusive(full_screen_exclusive);
+
+            true
+        })
+    }
+
+    /// Returns the content scale of the main window.
+    ///
+    /// This is the ratio between the size of the window in pixels and its size in screen
+    /// coordinates and should be used to scale any ui elements.
+    pub fn get_content_scale(&self) -> Vec2f32 {
+        self.with_render_config(|config| config.main_surface.get_surface_provider().get_content_scale())
+    }
+
+    /// Configures the color grading applied to all following frames.
+    ///
+    /// A LUT image is only valid until the device is recreated. After that the LUT is removed and
+    /// must be set again.
+    pub fn set_color_grading(&self, grading: ColorGrading) {
+        self.with_render_config(|config| config.color_grading = grading);
+    }
+
+    /// Returns the current color grading configuration.
+    pub fn get_color_grading(&self) -> ColorGrading {
+        self.with_render_config(|config| config.color_grading.clone())
+    }
+
+    /// Registers a color-matrix post-process chain which can later be activated using
+    /// [`Blaze4D::set_active_post_chain`]. Fails if the chain is not a color-matrix chain, see
+    /// [`PostChain::resolve_color_matrix`].
+    pub fn register_post_chain(&self, chain: &PostChain) -> Result<PostChainId, PostChainError> {
+        let matrix = chain.resolve_color_matrix()?;
+        let id = PostChainId::new();
+        self.with_render_config(|config| config.post_chains.insert(id, matrix));
+        Ok(id)
+    }
+
+    /// Unregisters a color-matrix chain. If the chain is currently active it is deactivated.
+    pub fn unregister_post_chain(&self, id: PostChainId) {
+        self.with_render_config(|config| {
+            config.post_chains.remove(&id);
+            if config.active_post_chain == Some(id) {
+                config.active_post_chain = None;
+            }
+        })
+    }
+
+    /// Sets the color-matrix chain applied to all following frames. The matrix is applied after
+    /// the color grading preset and before the curves.
+    pub fn set_active_post_chain(&self, id: Option<PostChainId>) {
+        self.with_render_config(|config| {
+            if let Some(id) = id {
+                if !config.post_chains.contains_key(&id) {
+                    log::error!("Attempted to activate unknown post chain {:?}", id);
+                    panic!();
+                }
+            }
+            config.active_post_chain = id;
+        })
+    }
+
+    /// Sets the accessibility filter applied to all following frames. The filter is applied after
+    /// the active post-process chain.
+    pub fn set_accessibility_filter(&self, filter: AccessibilityFilter) {
+        self.with_render_config(|config| config.accessibility_filter = filter);
+    }
+
+    /// Returns the current accessibility filter.
+    pub fn get_accessibility_filter(&self) -> AccessibilityFilter {
+        self.with_render_config(|config| config.accessibility_filter)
+    }
+
+    /// Sets the nausea and portal screen distortion applied to all following frames. The host
+    /// should update the time every frame while the distortion is active.
+    pub fn set_screen_distortion(&self, distortion: ScreenDistortion) {
+        self.with_render_config(|config| config.screen_distortion = distortion);
+    }
+
+    /// Returns the current screen distortion.
+    pub fn get_screen_distortion(&self) -> ScreenDistortion {
+        self.with_render_config(|config| config.screen_distortion)
+    }
+
+    /// Configures the latency mode used for all following frames.
+    pub fn set_latency_mode(&self, mode: LatencyMode) {
+        self.with_render_config(|config| config.set_latency_mode(mode));
+    }
+
+    /// Returns true if frames are currently skipped because the main surface has no size, for
+    /// example because the window is minimized. [`Blaze4D::try_start_frame`] returns [`None`]
+    /// without logging until the surface has a size again. Hosts may use this to throttle their
+    /// render loop.
+    pub fn is_surface_paused(&self) -> bool {
+        self.with_render_config(|config| config.surface_paused)
+    }
+
+    /// Returns statistics about previously rendered frames.
+    pub fn get_frame_stats(&self) -> FrameStats {
+        let (present_latency, display_timing) = self.with_render_config(|config| match config.current_swapchain.as_ref() {
+            Some(swapchain) => (swapchain.get_present_latency(), swapchain.get_display_timing()),
+            None => (None, None),
+        });
+        FrameStats {
+            present_latency,
+            display_timing,
+            layers: self.last_frame_layer_stats.lock().unwrap().clone(),
+            tags: self.last_frame_tag_stats.lock().unwrap().clone(),
+            budget: *self.last_frame_budget_report.lock().unwrap(),
+        }
+    }
+
+    /// Returns percentiles and stutter counts of the cpu and gpu frame times of recent frames.
+    pub fn get_frame_time_report(&self) -> FrameTimeReport {
+        self.frame_times.lock().unwrap().get_report()
+    }
+
+    /// Returns the histogram of recent cpu or gpu frame times. See
+    /// [`crate::renderer::emulator::frame_times::HISTOGRAM_BUCKET_WIDTH`] for the bucket layout.
+    pub fn get_frame_time_histogram(&self, gpu: bool) -> [u32; HISTOGRAM_BUCKET_COUNT] {
+        let frame_times = self.frame_times.lock().unwrap();
+        if gpu {
+            *frame_times.get_gpu_histogram().get_buckets()
+        } else {
+            *frame_times.get_cpu_histogram().get_buckets()
+        }
+    }
+
+    /// Returns the world space position at `screen_pos` in logical pixels using the camera matrices
+    /// of the last ended frame. The depth in the `[0, 1]` range must be read back by the host. If no
+    /// depth is provided the position on the near plane is returned.
+    ///
+    /// Returns [`None`] if no frame has set camera matrices yet. See
+    /// [`PassRecorder::set_view_matrices`].
+    pub fn unproject(&self, screen_pos: Vec2f32, depth: Option<f32>) -> Option<Vec3f32> {
+        let (frame_size, matrices) = (*self.last_view_matrices.lock().unwrap())?;
+        matrices.unproject(&frame_size, screen_pos, depth.unwrap_or(0f32))
+    }
+
+    /// Returns the world space ray at `screen_pos` in logical pixels using the camera matrices of
+    /// the last ended frame. See [`Blaze4D::unproject`].
+    pub fn unproject_ray(&self, screen_pos: Vec2f32) -> Option<WorldRay> {
+        let (frame_size, matrices) = (*self.last_view_matrices.lock().unwrap())?;
+        matrices.unproject_ray(&frame_size, screen_pos)
+    }
+
+    /// Projects a batch of world space points onto the screen using the camera matrices of the
+    /// last ended frame. Points behind the camera are [`None`].
+    ///
+    /// Returns [`None`] if no frame has set camera matrices yet. See [`ViewMatrices::project`].
+    pub fn project_points(&self, points: &[Vec3f32]) -> Option<Vec<Option<ScreenPoint>>> {
+        let (frame_size, matrices) = (*self.last_view_matrices.lock().unwrap())?;
+        Some(matrices.project_points(&frame_size, points))
+    }
+
+    /// Sets the shader used to draw the built-in debug overlays. Overlays are only drawn while a
+    /// shader is set. See [`debug_overlay`](crate::renderer::emulator::debug_overlay).
+    pub fn set_debug_overlay_shader(&self, shader: Option<ShaderId>) {
+        *self.debug_overlay_shader.lock().unwrap() = shader;
+    }
+
+    /// Sets the origin all debug overlay positions are made relative to. See
+    /// [`DebugOverlays::origin_block`].
+    pub fn set_debug_overlay_origin(&self, block: Vec3i32, offset: Vec3f32) {
+        let mut guard = self.debug_overlays.lock().unwrap();
+        let overlays = Arc::make_mut(&mut guard);
+        overlays.origin_block = block;
+        overlays.origin_offset = offset;
+    }
+
+    pub fn set_debug_chunk_grid(&self, grid: Option<ChunkGridOverlay>) {
+        Arc::make_mut(&mut self.debug_overlays.lock().unwrap()).chunk_grid = grid;
+    }
+
+    pub fn set_debug_heatmap(&self, heatmap: Option<SectionHeatmap>) {
+        Arc::make_mut(&mut self.debug_overlays.lock().unwrap()).heatmap = heatmap;
+    }
+
+    /// Discards all collected frame time samples.
+    pub fn reset_frame_times(&self) {
+        self.frame_times.lock().unwrap().reset();
     }
 
     pub fn create_global_mesh(&self, data: &MeshData) -> Arc<GlobalMesh> {
-        self.emulator.create_global_mesh(data)
+        let mesh = self.get_emulator().create_global_mesh(data);
+        self.record_global_mesh(&mesh, data);
+        mesh
+    }
+
+    pub fn create_global_meshes(&self, datas: &[MeshData]) -> Vec<Arc<GlobalMesh>> {
+        let meshes = self.get_emulator().create_global_meshes(datas);
+        for (mesh, data) in meshes.iter().zip(datas) {
+            self.record_global_mesh(mesh, data);
+        }
+        meshes
+    }
+
+    /// Creates a global mesh or returns an existing mesh with identical data. See
+    /// [`EmulatorRenderer::create_global_mesh_deduplicated`].
+    pub fn create_global_mesh_deduplicated(&self, data: &MeshData) -> Arc<GlobalMesh> {
+        let mesh = self.get_emulator().create_global_mesh_deduplicated(data);
+        self.record_global_mesh(&mesh, data);
+        mesh
+    }
+
+    /// Creates a global mesh after optimizing its data for rendering. See
+    /// [`EmulatorRenderer::create_global_mesh_optimized`].
+    pub fn create_global_mesh_optimized(&self, data: &MeshData, position: Option<&VertexFormatEntry>) -> Arc<GlobalMesh> {
+        let mesh = self.get_emulator().create_global_mesh_optimized(data, position);
+        self.record_global_mesh(&mesh, data);
+        mesh
+    }
+
+    /// Optimizes a batch of meshes in parallel before creating them. See
+    /// [`EmulatorRenderer::create_global_meshes_optimized`].
+    pub fn create_global_meshes_optimized(&self, datas: &[MeshData], positions: &[Option<VertexFormatEntry>]) -> Vec<Arc<GlobalMesh>> {
+        let meshes = self.get_emulator().create_global_meshes_optimized(datas, positions);
+        for (mesh, data) in meshes.iter().zip(datas) {
+            self.record_global_mesh(mesh, data);
+        }
+        meshes
+    }
+
+    /// Creates the unit column mesh used to draw beams with `shader`. Shaders with the same vertex
+    /// format share the same mesh. Returns [`None`] if the shader does not exist or its vertex
+    /// format is not supported by [`BeamMesh`].
+    pub fn create_beam_mesh(&self, shader: ShaderId) -> Option<Arc<GlobalMesh>> {
+        let format = *self.get_emulator().get_shader(shader)?.get_vertex_format();
+        if !BeamMesh::supports_format(&format) {
+            return None;
+        }
+        Some(self.create_global_mesh_deduplicated(&BeamMesh::new(&format).as_mesh_data()))
+    }
+
+    /// Creates a cache storing map item textures keyed by map id. See
+    /// [`map_texture`](crate::renderer::emulator::map_texture).
+    ///
+    /// The cache uses the current device and must be recreated if the device is recreated.
+    pub fn create_map_texture_cache(&self) -> MapTextureCache {
+        MapTextureCache::new(self.get_emulator())
     }
 
     pub fn create_global_image(&self, size:Vec2u32, format: &'static Format) -> Arc<GlobalImage> {
-        self.emulator.create_global_image(size, format)
+        self.create_global_image_array(size, 1, ImageArrayMode::Single, format)
+    }
+
+    /// Creates a 2d array image. See [`EmulatorRenderer::create_global_image_array`].
+    pub fn create_global_image_array(&self, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) -> Arc<GlobalImage> {
+        let image = self.get_emulator().create_global_image_array(size, mip_levels, array_mode, format);
+        self.record_global_image(&image, size, mip_levels, array_mode, format);
+        image
+    }
+
+    /// Creates an image from layers composited on the gpu. See
+    /// [`EmulatorRenderer::create_composite_image`].
+    pub fn create_composite_image(&self, size: Vec2u32, format: &'static Format, layers: &[CompositeLayer]) -> Option<Arc<GlobalImage>> {
+        let image = self.get_emulator().create_composite_image(size, format, layers)?;
+        self.record_global_image(&image, size, 1, ImageArrayMode::Single, format);
+        Some(image)
+    }
+
+    /// Creates a partially resident 2d array image. See [`EmulatorRenderer::create_global_image_sparse`].
+    ///
+    /// Command streams record sparse images as regular images.
+    pub fn create_global_image_sparse(&self, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) -> Option<Arc<GlobalImage>> {
+        let image = self.get_emulator().create_global_image_sparse(size, mip_levels, array_mode, format)?;
+        self.record_global_image(&image, size, mip_levels, array_mode, format);
+        Some(image)
     }
 
     pub fn create_shader(&self, vertex_format: &VertexFormat, used_uniforms: McUniform) -> ShaderId {
-        self.emulator.create_shader(vertex_format, used_uniforms)
+        self.create_shader_specialized(vertex_format, used_uniforms, ShaderSpecialization::default())
+    }
+
+    pub fn create_shader_specialized(&self, vertex_format: &VertexFormat, used_uniforms: McUniform, specialization: ShaderSpecialization) -> ShaderId {
+        let id = self.get_emulator().create_shader_specialized(vertex_format, used_uniforms, specialization);
+        self.record_event(|| StreamEvent::CreateShader {
+            id,
+            vertex_format: *vertex_format,
+            used_uniforms,
+            specialization,
+        });
+        id
+    }
+
+    pub fn create_shader_with_format(&self, format: VertexFormatId, used_uniforms: McUniform, specialization: ShaderSpecialization) -> Option<ShaderId> {
+        let emulator = self.get_emulator();
+        let id = emulator.create_shader_with_format(format, used_uniforms, specialization)?;
+        if let Some(vertex_format) = emulator.get_vertex_format(format) {
+            self.record_event(|| StreamEvent::CreateShader {
+                id,
+                vertex_format,
+                used_uniforms,
+                specialization,
+            });
+        }
+        Some(id)
     }
 
     pub fn drop_shader(&self, id: ShaderId) {
-        self.emulator.drop_shader(id);
+        let mut overlay_shader = self.debug_overlay_shader.lock().unwrap();
+        if *overlay_shader == Some(id) {
+            *overlay_shader = None;
+        }
+        drop(overlay_shader);
+        self.get_emulator().drop_shader(id);
+    }
+
+    pub fn register_vertex_format(&self, vertex_format: &VertexFormat) -> Result<VertexFormatId, VertexFormatError> {
+        self.get_emulator().register_vertex_format(vertex_format)
     }
 
+    pub fn unregister_vertex_format(&self, id: VertexFormatId) {
+        self.get_emulator().unregister_vertex_format(id)
+    }
+
+    /// Starts an environment probe capture at `position` with faces of `resolution` by
+    /// `resolution` pixels. The resolution must be a power of two. Each frame the caller must
+    /// render the scene once for each face returned by [`ProbeCapture::next_faces`] until all
+    /// faces have been started and then call [`ProbeCapture::finish`]. The callback receives the
+    /// prefiltered probe image which can be used with [`PassRecorder::update_environment_probe`].
+    ///
+    /// The capture uses the current debug mode or [`DebugPipelineMode::Textured0`] if none is set.
+    pub fn capture_probe(&self, position: Vec3f32, resolution: u32, faces_per_frame: u32, callback: ProbeCallback) -> ProbeCapture {
+        if !resolution.is_power_of_two() {
+            log::error!("Probe resolution {:?} is not a power of two", resolution);
+            panic!();
+        }
+
+        let (emulator, mode, fetch_mode) = self.with_render_config(|config| (config.emulator.clone(), config.debug_mode, config.vertex_fetch_mode));
+        let pipeline = DebugPipeline::new_with_fetch_mode(emulator.clone(), mode.unwrap_or(DebugPipelineMode::Textured0), fetch_mode, Vec2u32::new(resolution, resolution)).unwrap();
+
+        ProbeCapture::new(emulator, pipeline, position, resolution, faces_per_frame, callback)
+    }
+
+    pub fn register_render_layer(&self, name: &str, state: &RenderLayerState) -> Result<RenderLayerId, RenderLayerError> {
+        self.get_emulator().register_render_layer(name, state)
+    }
+
+    pub fn unregister_render_layer(&self, id: RenderLayerId) {
+        self.get_emulator().unregister_render_layer(id)
+    }
+
+    pub fn find_render_layer(&self, name: &str) -> Option<RenderLayerId> {
+        self.get_emulator().find_render_layer(name)
+    }
+
+    /// Enables or disables a render layer for debugging and benchmarking. Returns false if the
+    /// layer is not registered.
+    pub fn set_layer_enabled(&self, id: RenderLayerId, enabled: bool) -> bool {
+        self.get_emulator().set_render_layer_enabled(id, enabled)
+    }
+
+    /// Disables all render layers except `id`. Passing [`None`] enables all layers again.
+    pub fn isolate_layer(&self, id: Option<RenderLayerId>) {
+        self.get_emulator().isolate_render_layer(id)
+    }
+
+    pub fn set_render_order(&self, order: &[RenderOrderEntry]) -> Result<(), RenderLayerError> {
+        self.get_emulator().set_render_order(order)
+    }
+
+    pub fn insert_render_layer(&self, id: RenderLayerId, point: InsertionPoint) -> Result<(), RenderLayerError> {
+        self.get_emulator().insert_render_layer(id, point)
+    }
+
+    /// Marks all global images as stale so they can be replaced in place during a resource reload.
+    /// See [`EmulatorRenderer::bump_resource_generation`].
+    pub fn bump_resource_generation(&self) -> u64 {
+        self.get_emulator().bump_resource_generation()
+    }
+
+    /// Keeps all deduplicated meshes alive until the returned retention is dropped so meshes which
+    /// are recreated with identical data during a resource reload are not uploaded again. See
+    /// [`warm_state`](crate::renderer::emulator::warm_state).
+    pub fn retain_cached_meshes(&self) -> MeshRetention {
+        self.get_emulator().retain_cached_meshes()
+    }
+
+    pub fn snapshot_state(&self) -> RendererSnapshot {
+        self.get_emulator().snapshot_state()
+    }
+
+    pub fn restore_state(&self, snapshot: &RendererSnapshot) -> Result<(), RenderLayerError> {
+        self.get_emulator().restore_state(snapshot)
+    }
+
+    /// Writes a snapshot of the renderer state to `path` and the pipeline cache to the configured
+    /// pipeline cache path. Returns false if the snapshot could not be written.
+    pub fn save_state(&self, path: &Path) -> bool {
+        self.save_pipeline_cache();
+        if let Err(err) = std::fs::write(path, self.snapshot_state().to_json()) {
+            log::warn!("Failed to write renderer state {:?}: {:?}", path, err);
+            return false;
+        }
+        true
+    }
+
+    /// Restores a snapshot written using [`Blaze4D::save_state`]. Should be called before the host
+    /// registers its own render layers. Returns false if the snapshot could not be read or
+    /// restored.
+    pub fn load_state(&self, path: &Path) -> bool {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                log::info!("Failed to read renderer state {:?}: {:?}", path, err);
+                return false;
+            }
+        };
+        let snapshot = match RendererSnapshot::from_json(&source) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                log::warn!("Failed to parse renderer state {:?}: {:?}", path, err);
+                return false;
+            }
+        };
+        if let Err(err) = self.restore_state(&snapshot) {
+            log::warn!("Failed to restore renderer state {:?}: {:?}", path, err);
+            return false;
+        }
+        true
+    }
+
+    /// Starts a panorama capture with faces of `resolution` by `resolution` pixels. The caller
+    /// must render the scene once for each face returned by [`PanoramaCapture::next_face`] and then
+    /// call [`PanoramaCapture::finish`]. The callback receives a `2 * resolution` by `resolution`
+    /// equirectangular image once stitching has completed.
+    ///
+    /// The capture uses the current debug mode or [`DebugPipelineMode::Textured0`] if none is set.
+    pub fn capture_panorama(&self, resolution: u32, callback: PanoramaCallback) -> PanoramaCapture {
+        if resolution == 0 {
+            log::error!("Panorama resolution must not be 0");
+            panic!();
+        }
+
+        let (emulator, mode, fetch_mode) = self.with_render_config(|config| (config.emulator.clone(), config.debug_mode, config.vertex_fetch_mode));
+        let pipeline = DebugPipeline::new_with_fetch_mode(emulator.clone(), mode.unwrap_or(DebugPipelineMode::Textured0), fetch_mode, Vec2u32::new(resolution, resolution)).unwrap();
+
+        PanoramaCapture::new(emulator, pipeline, resolution, callback)
+    }
+
+    /// Re-renders a recorded pass into an offscreen image of `size` pixels. Viewports are scaled
+    /// from the recorded frame size so the log can be replayed at a higher resolution.
+    ///
+    /// The replay uses the current debug mode or [`DebugPipelineMode::Textured0`] if none is set.
+    /// Must not be called while another pass is running.
+    pub fn replay_pass(&self, log: &PassCommandLog, size: Vec2u32, resources: &ReplayResources) -> OffscreenReadback {
+        let (emulator, mode, fetch_mode) = self.with_render_config(|config| (config.emulator.clone(), config.debug_mode, config.vertex_fetch_mode));
+        let pipeline = DebugPipeline::new_with_fetch_mode(emulator.clone(), mode.unwrap_or(DebugPipelineMode::Textured0), fetch_mode, size).unwrap();
+        let output = OffscreenOutput::new(emulator.get_device().clone(), pipeline.clone(), size);
+
+        let (output, readback) = output.next_output();
+        let mut recorder = emulator.start_pass(pipeline);
+        // Keep the logical size of the recorded frame so ui layouts stay the same
+        let scale_factor = log.frame_size.map_or(1f32, |frame_size| {
+            frame_size.scale_factor * (size[0] as f32) / (frame_size.physical_size[0].max(1) as f32)
+        });
+        recorder.set_frame_size(FrameSize::from_physical(size, scale_factor));
+        recorder.use_output(output);
+        log.replay(&mut recorder, resources);
+        drop(recorder);
+
+        readback
+    }
+
+    /// Starts recording all resource creations and the commands of the next
+    /// [`StreamRecorderConfig::frame_count`] frames. The stream is written to the configured path
+    /// once all frames have been recorded and can be replayed with the `replay_stream` example.
+    ///
+    /// Resources created before recording started are not part of the stream, so recording should
+    /// be started before the world is loaded. Any recording in progress is discarded.
+    pub fn start_command_stream_recording(&self, config: StreamRecorderConfig) {
+        if config.frame_count == 0 {
+            log::error!("Command stream frame count must not be 0");
+            panic!();
+        }
+        log::info!("Starting command stream recording of {} frames to {:?}", config.frame_count, config.path);
+        *self.stream_recorder.lock().unwrap() = Some(StreamRecorder::new(config));
+    }
+
+    /// Starts writing a JSONL event log of frame boundaries, uploads, swapchain events and errors
+    /// to the target. Any previously started event log is stopped. See
+    /// [`crate::renderer::emulator::event_log`] for the format.
+    pub fn start_event_log(&self, target: EventLogTarget) -> std::io::Result<()> {
+        let log = EventLog::new(target)?;
+        *self.event_log.lock().unwrap() = Some(log);
+        Ok(())
+    }
+
+    /// Stops the event log and flushes any buffered events.
+    pub fn stop_event_log(&self) {
+        *self.event_log.lock().unwrap() = None;
+    }
+
+    fn log_event<F: FnOnce() -> RendererEvent>(&self, event: F) {
+        if let Some(log) = self.event_log.lock().unwrap().as_mut() {
+            log.push(&event());
+        }
+    }
+
+    fn record_global_mesh(&self, mesh: &GlobalMesh, data: &MeshData) {
+        self.log_event(|| RendererEvent::MeshUpload {
+            id: mesh.get_id().as_uuid().get_raw(),
+            vertex_bytes: data.vertex_data.len(),
+            index_bytes: data.index_data.len(),
+        });
+
+        let mut guard = self.stream_recorder.lock().unwrap();
+        if let Some(recorder) = guard.as_mut() {
+            let include_data = recorder.includes_mesh_data();
+            recorder.push(StreamEvent::create_global_mesh(mesh.get_id(), data, include_data));
+        }
+    }
+
+    fn record_global_image(&self, image: &GlobalImage, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) {
+        self.log_event(|| RendererEvent::ImageCreate {
+            id: image.get_id().as_uuid().get_raw(),
+            width: size[0],
+            height: size[1],
+            mip_levels,
+            format: format.get_format(),
+        });
+        self.record_event(|| StreamEvent::CreateGlobalImage {
+            id: image.get_id(),
+            size,
+            mip_levels,
+            array_mode,
+            format: format.get_format(),
+        });
+    }
+
+    fn record_event<F: FnOnce() -> StreamEvent>(&self, event: F) {
+        if let Some(recorder) = self.stream_recorder.lock().unwrap().as_mut() {
+            recorder.push(event());
+        }
+    }
+
+    /// Attempts to start a new frame. The window size must be specified in pixels. The logical size
+    /// of the frame is derived from the content scale of the main window.
     pub fn try_start_frame(&self, window_size: Vec2u32) -> Option<PassRecorder> {
-        if let Some(recorder) = self.render_config.lock().unwrap().try_start_frame(&self.emulator, window_size) {
-            Some(recorder)
-        } else {
-            None
+        let scale_factor = self.get_content_scale()[0];
+        self.try_start_frame_scaled(FrameSize::from_physical(window_size, scale_factor))
+    }
+
+    /// Attempts to start a new frame using an explicit physical and logical size.
+    ///
+    /// The swapchain is always created with the physical size. The frame size can be queried from
+    /// the returned [`PassRecorder`] to lay out ui elements.
+    ///
+    /// If the device or surface has been lost they are recreated and the device lost callback is
+    /// called before [`None`] is returned.
+    pub fn try_start_frame_scaled(&self, frame_size: FrameSize) -> Option<PassRecorder> {
+        self.update_memory_pressure();
+
+        let mut guard = self.render_config.lock().unwrap();
+
+        if let Some(reason) = guard.as_ref().unwrap().check_lost() {
+            if let Err(err) = self.recreate_device(&mut guard, reason) {
+                log::warn!("Failed to recreate device after {:?}: {:?}. Retrying next frame", reason, err);
+                return None;
+            }
+            drop(guard);
+
+            if let Some(callback) = self.device_lost_callback.lock().unwrap().as_ref() {
+                callback(reason);
+            }
+            return None;
+        }
+
+        let config = guard.as_mut().unwrap();
+        let emulator = config.emulator.clone();
+        let mut recorder = config.try_start_frame(&emulator, frame_size)?;
+        drop(guard);
+
+        let frame = self.frame_index.fetch_add(1, Ordering::Relaxed);
+        let frame_start = Instant::now();
+        self.log_event(|| {
+            let size = recorder.get_frame_size().unwrap_or(frame_size).physical_size;
+            RendererEvent::FrameStart { frame, width: size[0], height: size[1] }
+        });
+
+        // The layer stats sink is called once the frame has been submitted
+        let layer_stats = self.last_frame_layer_stats.clone();
+        let event_log = self.event_log.clone();
+        recorder.set_layer_stats_sink(Box::new(move |stats| {
+            *layer_stats.lock().unwrap() = stats;
+            if let Some(log) = event_log.lock().unwrap().as_mut() {
+                log.push(&RendererEvent::FrameEnd { frame, cpu_time: frame_start.elapsed() });
+            }
+        }));
+
+        let tag_stats = self.last_frame_tag_stats.clone();
+        recorder.set_tag_stats_sink(Box::new(move |mut stats| {
+            stats.sort_by(|a, b| b.1.index_count.cmp(&a.1.index_count).then(a.0.cmp(&b.0)));
+            *tag_stats.lock().unwrap() = stats;
+        }));
+
+        let budget_report = self.last_frame_budget_report.clone();
+        recorder.set_budget_report_sink(Box::new(move |report| *budget_report.lock().unwrap() = report));
+
+        let last_view_matrices = self.last_view_matrices.clone();
+        recorder.set_view_matrices_sink(Box::new(move |frame_size, matrices| {
+            *last_view_matrices.lock().unwrap() = Some((frame_size, matrices));
+        }));
+
+        if let Some(shader) = *self.debug_overlay_shader.lock().unwrap() {
+            let overlays = self.debug_overlays.lock().unwrap().clone();
+            if !overlays.is_empty() {
+                recorder.set_debug_overlays(overlays, shader);
+            }
+        }
+
+        self.frame_times.lock().unwrap().start_frame();
+        let frame_times = self.frame_times.clone();
+        recorder.set_gpu_time_sink(Box::new(move |time| frame_times.lock().unwrap().push_gpu_time(time)));
+
+        if self.stream_recorder.lock().unwrap().is_some() {
+            recorder.start_command_log();
+
+            let stream_recorder = self.stream_recorder.clone();
+            recorder.set_command_log_sink(Box::new(move |log| {
+                let mut guard = stream_recorder.lock().unwrap();
+                if let Some(recorder) = guard.as_mut() {
+                    if recorder.push_frame(log) {
+                        *guard = None;
+                    }
+                }
+            }));
+        }
+
+        Some(recorder)
+    }
+
+    /// Updates the memory pressure level and calls the registered callbacks. No locks are held
+    /// while the callbacks are called.
+    fn update_memory_pressure(&self) {
+        let device = self.with_render_config(|config| config.device.clone());
+        let evict = self.mesh_eviction_callback.lock().unwrap().is_some();
+
+        let update = self.memory_monitor.lock().unwrap().update(&device, evict);
+
+        if let Some((pressure, budget)) = update.pressure_changed {
+            if let Some(callback) = self.memory_pressure_callback.lock().unwrap().as_ref() {
+                callback(pressure, budget);
+            }
+        }
+        if !update.evicted.is_empty() {
+            if let Some(callback) = self.mesh_eviction_callback.lock().unwrap().as_ref() {
+                for id in update.evicted {
+                    callback(id);
+                }
+            }
+        }
+    }
+
+    /// Recreates the device and all swapchain objects. If passes still reference the main surface
+    /// after [`DEVICE_RECREATE_TIMEOUT`] the old device is kept and
+    /// [`DeviceRecreateError::SurfaceBusy`] is returned. The caller should try again on the next
+    /// frame.
+    fn recreate_device(&self, render_config: &mut Option<RenderConfig>, reason: DeviceLostReason) -> Result<(), DeviceRecreateError> {
+        let old = render_config.as_mut().unwrap();
+        if !old.recreate_pending {
+            log::warn!("Recreating device because of {:?}", reason);
+            self.log_event(|| RendererEvent::DeviceRecreate { reason: format!("{:?}", reason) });
+            old.recreate_pending = true;
+        }
+
+        // Release our own references to the surface
+        old.current_pipeline = None;
+        old.debug_pipeline = None;
+        old.current_swapchain = None;
+
+        if reason == DeviceLostReason::DeviceLost {
+            let report = collect_fault_report(&old.device);
+            report.log();
+            self.log_event(|| RendererEvent::Error { message: format!("Device lost: {}", report) });
+            *self.last_fault_report.lock().unwrap() = Some(report);
+        }
+
+        // Wait for all outstanding work to finish. Errors are expected here if the device has been
+        // lost.
+        unsafe { old.device.get_main_queue().wait_idle() }.ok();
+
+        // Passes which are still in flight may hold references to the surface for a short time
+        let start = Instant::now();
+        while Arc::strong_count(&old.main_surface) > 1 {
+            if start.elapsed() > DEVICE_RECREATE_TIMEOUT {
+                log::error!("Main surface is still in use {:?} after starting to recreate device", DEVICE_RECREATE_TIMEOUT);
+                return Err(DeviceRecreateError::SurfaceBusy);
+            }
+            std::thread::sleep(Duration::from_millis(10));
         }
+
+        let old = render_config.take().unwrap();
+        let settings = old.get_settings();
+        // The time spent recreating the device would otherwise be counted as a frame
+        self.frame_times.lock().unwrap().reset();
+
+        // No new references can be created while we hold the render config
+        let main_window = old.into_main_surface().try_into_surface_provider().unwrap_or_else(|_| {
+            log::error!("Main surface was referenced while recreating the device");
+            panic!()
+        });
+
+        let mut new = Self::create_render_config(&self.instance, &self.config, main_window, &self.job_system, &self.hang_callback, &self.event_log).unwrap_or_else(|err| {
+            log::error!("Failed to recreate device after {:?}: {:?}", reason, err);
+            panic!()
+        });
+        new.apply_settings(settings);
+
+        *render_config = Some(new);
+
+        // Shaders must be recreated by the host
+        *self.debug_overlay_shader.lock().unwrap() = None;
+
+        self.memory_monitor.lock().unwrap().reset();
+
+        Ok(())
     }
 }
 
+impl Drop for Blaze4D {
+    fn drop(&mut self) {
+        self.save_pipeline_cache();
+    }
+}
+
+/// The time [`Blaze4D::recreate_device`] waits for passes in flight to release the main surface.
+const DEVICE_RECREATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error returned if the device could not be recreated.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DeviceRecreateError {
+    /// The main surface is still referenced by passes in flight. The old device is kept.
+    SurfaceBusy,
+}
+
+/// The reason the device was recreated.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum DeviceLostReason {
+    /// A function returned VK_ERROR_DEVICE_LOST. For example after a driver reset.
+    DeviceLost = 0,
+    /// A function returned VK_ERROR_SURFACE_LOST_KHR.
+    SurfaceLost = 1,
+    /// The physical device is no longer available. For example after an external gpu was unplugged.
+    AdapterChanged = 2,
+}
+
 struct RenderConfig {
     device: Arc<DeviceContext>,
     emulator: Arc<EmulatorRenderer>,
     main_surface: Arc<DeviceSurface>,
+    msaa_samples: u32,
+
+    /// Set if the physical device is no longer available.
+    adapter_lost: bool,
+    /// Set once recreating the device has been started. Recreation may need multiple attempts if
+    /// the main surface is still in use.
+    recreate_pending: bool,
+
+    #[allow(unused)] // The watchdog thread is stopped when dropped
+    watchdog: Option<Watchdog>,
+    event_log: Arc<Mutex<Option<EventLog>>>,
 
     last_rebuild: Instant,
+    /// Set while the main surface has no size. No swapchain is created or acquired in this state.
+    surface_paused: bool,
     current_swapchain: Option<Arc<SurfaceSwapchain>>,
     current_pipeline: Option<(Arc<dyn EmulatorPipeline>, Arc<SwapchainOutput>)>,
 
     debug_mode: Option<DebugPipelineMode>,
     debug_pipeline: Option<(Arc<dyn EmulatorPipeline>, Arc<SwapchainOutput>)>,
+    vertex_fetch_mode: VertexFetchMode,
+
+    full_screen_exclusive: FullScreenExclusiveMode,
+    latency_mode: LatencyMode,
+    color_grading: ColorGrading,
+    exposure_adaptation: Arc<ExposureAdaptation>,
+    post_chains: HashMap<PostChainId, ColorMatrix>,
+    active_post_chain: Option<PostChainId>,
+    accessibility_filter: AccessibilityFilter,
+    screen_distortion: ScreenDistortion,
+
+    vsync: bool,
+    frames_in_flight: u32,
+
+    /// The content scale of the main surface when the current swapchain was created.
+    content_scale: Vec2f32,
 }
 
 impl RenderConfig {
-    fn new(device: Arc<DeviceContext>, emulator: Arc<EmulatorRenderer>, main_surface: Arc<DeviceSurface>) -> Self {
+    fn new(device: Arc<DeviceContext>, emulator: Arc<EmulatorRenderer>, main_surface: Arc<DeviceSurface>, msaa_samples: u32) -> Self {
         Self {
             device,
             emulator,
             main_surface,
+            msaa_samples,
+
+            adapter_lost: false,
+            recreate_pending: false,
+
+            watchdog: None,
+            event_log: Arc::new(Mutex::new(None)),
 
             last_rebuild: Instant::now() - Duration::from_secs(100),
+            surface_paused: false,
             current_swapchain: None,
             current_pipeline: None,
 
             debug_mode: Some(DebugPipelineMode::Color),
-            debug_pipeline: None
+            debug_pipeline: None,
+            vertex_fetch_mode: VertexFetchMode::InputAssembly,
+
+            full_screen_exclusive: FullScreenExclusiveMode::Default,
+            latency_mode: LatencyMode::Default,
+            color_grading: ColorGrading::default(),
+            exposure_adaptation: Arc::new(ExposureAdaptation::new()),
+            post_chains: HashMap::new(),
+            active_post_chain: None,
+            accessibility_filter: AccessibilityFilter::None,
+            screen_distortion: ScreenDistortion::NONE,
+
+            vsync: false,
+            frames_in_flight: 2,
+
+            content_scale: Vec2f32::new(1f32, 1f32),
+        }
+    }
+
+    fn log_event(&self, event: RendererEvent) {
+        if let Some(log) = self.event_log.lock().unwrap().as_mut() {
+            log.push(&event);
+        }
+    }
+
+    /// Returns the reason the device must be recreated or [`None`] if the device is usable.
+    fn check_lost(&self) -> Option<DeviceLostReason> {
+        if self.device.is_device_lost() {
+            Some(DeviceLostReason::DeviceLost)
+        } else if self.main_surface.is_surface_lost() {
+            Some(DeviceLostReason::SurfaceLost)
+        } else if self.adapter_lost {
+            Some(DeviceLostReason::AdapterChanged)
+        } else {
+            None
+        }
+    }
+
+    /// Checks if the physical device is still available.
+    fn check_adapter(&mut self) {
+        let instance = self.device.get_instance();
+        let available = match unsafe { instance.vk().enumerate_physical_devices() } {
+            Ok(devices) => devices.contains(&self.device.get_functions().physical_device),
+            Err(err) => {
+                log::warn!("vkEnumeratePhysicalDevices returned {:?} in RenderConfig::check_adapter", err);
+                true
+            }
+        };
+
+        if !available {
+            log::error!("Physical device is no longer available");
+            self.adapter_lost = true;
+        }
+    }
+
+    fn get_settings(&self) -> RenderSettings {
+        RenderSettings {
+            debug_mode: self.debug_mode,
+            full_screen_exclusive: self.full_screen_exclusive,
+            latency_mode: self.latency_mode,
+            // Global images do not survive device recreation
+            color_grading: ColorGrading { lut: None, ..self.color_grading.clone() },
+            post_chains: self.post_chains.clone(),
+            active_post_chain: self.active_post_chain,
+            accessibility_filter: self.accessibility_filter,
+            screen_distortion: self.screen_distortion,
+            vsync: self.vsync,
+            frames_in_flight: self.frames_in_flight,
+            upload_budget: self.emulator.get_upload_budget(),
+            // Render layers do not survive device recreation
+            render_budget: RenderBudget { layer_draw_limits: HashMap::new(), ..self.emulator.get_render_budget() },
+            submit_mode: self.emulator.get_submit_mode(),
+        }
+    }
+
+    fn apply_settings(&mut self, settings: RenderSettings) {
+        self.debug_mode = settings.debug_mode;
+        self.full_screen_exclusive = settings.full_screen_exclusive;
+        self.latency_mode = settings.latency_mode;
+        self.color_grading = settings.color_grading;
+        self.post_chains = settings.post_chains;
+        self.active_post_chain = settings.active_post_chain;
+        self.accessibility_filter = settings.accessibility_filter;
+        self.screen_distortion = settings.screen_distortion;
+        self.vsync = settings.vsync;
+        self.frames_in_flight = settings.frames_in_flight;
+        self.emulator.set_upload_budget(settings.upload_budget);
+        self.emulator.set_render_budget(settings.render_budget);
+        self.emulator.set_submit_mode(settings.submit_mode);
+    }
+
+    /// Destroys all swapchain objects and returns the main surface.
+    fn into_main_surface(self) -> Arc<DeviceSurface> {
+        self.main_surface
+    }
+
+    fn set_full_screen_exclusive(&mut self, mode: FullScreenExclusiveMode) {
+        if self.full_screen_exclusive != mode {
+            self.full_screen_exclusive = mode;
+            self.current_pipeline = None;
+            self.debug_pipeline = None;
+            self.current_swapchain = None;
+        }
+    }
+
+    fn set_vsync(&mut self, vsync: bool) {
+        if self.vsync != vsync {
+            self.vsync = vsync;
+            self.current_pipeline = None;
+            self.debug_pipeline = None;
+            self.current_swapchain = None;
+        }
+    }
+
+    fn set_frames_in_flight(&mut self, frames_in_flight: u32) {
+        let frames_in_flight = std::cmp::max(frames_in_flight, 1);
+        if self.frames_in_flight != frames_in_flight {
+            self.frames_in_flight = frames_in_flight;
+            self.current_pipeline = None;
+            self.debug_pipeline = None;
+            self.current_swapchain = None;
+        }
+    }
+
+    fn set_latency_mode(&mut self, mode: LatencyMode) {
+        // Variable refresh uses a different present mode so the swapchain must be recreated
+        let recreate = (self.latency_mode == LatencyMode::VariableRefresh) != (mode == LatencyMode::VariableRefresh);
+        self.latency_mode = mode;
+        if recreate {
+            self.current_pipeline = None;
+            self.debug_pipeline = None;
+            self.current_swapchain = None;
         }
     }
 
@@ -143,16 +1507,45 @@ impl RenderConfig {
         }
     }
 
-    fn try_start_frame(&mut self, renderer: &EmulatorRenderer, size: Vec2u32) -> Option<PassRecorder> {
+    fn try_start_frame(&mut self, renderer: &EmulatorRenderer, frame_size: FrameSize) -> Option<PassRecorder> {
         let mut force_rebuild = false;
 
+        // Wayland surfaces have no size until the first swapchain is created so we must use
+        // the framebuffer size reported by the windowing system instead.
+        let frame_size = match self.main_surface.get_surface_provider().get_framebuffer_size() {
+            Some(size) => FrameSize::from_physical(size, frame_size.scale_factor),
+            None => frame_size,
+        };
+        let size = frame_size.physical_size;
+        if size[0] == 0 || size[1] == 0 {
+            self.set_surface_paused(true);
+            return None;
+        }
+        if self.surface_paused {
+            // Some platforms report the framebuffer size before the surface has an extent again
+            if !self.surface_has_extent() {
+                return None;
+            }
+            self.set_surface_paused(false);
+            force_rebuild = true;
+        }
+
         // This if block only exists because of wayland
         if let Some(current) = self.current_swapchain.as_ref() {
-            if current.get_image_size() != size {
+            if current.get_logical_size() != size {
                 force_rebuild = true;
             }
         }
 
+        // Wayland compositors do not necessarily report a suboptimal swapchain if the scale
+        // changes so we have to detect this ourselves.
+        let content_scale = self.main_surface.get_surface_provider().get_content_scale();
+        if content_scale != self.content_scale {
+            log::info!("Content scale changed from {:?} to {:?}", self.content_scale, content_scale);
+            self.content_scale = content_scale;
+            force_rebuild = true;
+        }
+
         if self.current_swapchain.is_none() || force_rebuild {
             if !self.try_create_swapchain(size) {
                 return None;
@@ -161,10 +1554,36 @@ impl RenderConfig {
             self.debug_pipeline = None;
         }
 
+        if let Some(swapchain) = self.current_swapchain.as_ref() {
+            // In low latency and variable refresh mode we wait for all previous frames to be
+            // presented before starting a new one. This delays the point at which the caller
+            // samples input and camera matrices so they are as recent as possible when displayed.
+            let wait_all = self.latency_mode != LatencyMode::Default;
+            if let Err(err) = swapchain.wait_for_presents(1000000000, wait_all) {
+                log::warn!("vkWaitForPresentKHR returned {:?} in RenderConfig::try_start_frame", err);
+            }
+            if let Err(err) = swapchain.update_display_timing() {
+                log::warn!("vkGetPastPresentationTimingGOOGLE returned {:?} in RenderConfig::try_start_frame", err);
+            }
+        }
+
+        let post_matrix = self.active_post_chain.and_then(|id| self.post_chains.get(&id));
+        let post_matrix = self.accessibility_filter.apply_to(post_matrix);
+        let grading = self.color_grading.make_state(post_matrix.as_ref(), &self.screen_distortion, &self.exposure_adaptation);
+        let grading_lut = grading.as_ref().and_then(|_| self.color_grading.lut.as_ref().map(|(lut, _)| lut.clone()));
+
+        // One additional frame slot is used by the frame currently recorded by the host
+        let emulator_frames_in_flight = self.frames_in_flight + 1;
+        if self.emulator.get_frames_in_flight() != emulator_frames_in_flight {
+            log::info!("Resizing emulator frame slots from {} to {}", self.emulator.get_frames_in_flight(), emulator_frames_in_flight);
+            self.emulator.set_frames_in_flight(emulator_frames_in_flight);
+        }
+
         let (pipeline, output) = self.prepare_pipeline(size);
 
-        let (output, suboptimal) = match output.next_image() {
+        let (output, suboptimal) = match output.next_image_graded(grading) {
             None => {
+                self.log_event(RendererEvent::SwapchainInvalidate { suboptimal: false });
                 self.current_pipeline = None;
                 self.debug_pipeline = None;
                 self.current_swapchain = None;
@@ -173,10 +1592,15 @@ impl RenderConfig {
             Some(result) => result,
         };
 
-        let mut recorder = renderer.start_pass(pipeline.clone());
+        let mut recorder = renderer.start_main_pass(pipeline.clone());
+        recorder.set_frame_size(frame_size);
+        if let Some(lut) = &grading_lut {
+            recorder.use_global_image(lut);
+        }
         recorder.use_output(output);
 
         if suboptimal {
+            self.log_event(RendererEvent::SwapchainInvalidate { suboptimal: true });
             self.current_pipeline = None;
             self.debug_pipeline = None;
             self.current_swapchain = None;
@@ -190,7 +1614,7 @@ impl RenderConfig {
             if self.debug_pipeline.is_none() {
                 log::info!("No debug pipeline present. Rebuilding for size {:?}", output_size);
 
-                let pipeline = DebugPipeline::new(self.emulator.clone(), *debug_mode, output_size).unwrap();
+                let pipeline = DebugPipeline::new_with_fetch_mode(self.emulator.clone(), *debug_mode, self.vertex_fetch_mode, output_size).unwrap();
                 let swapchain_output = SwapchainOutput::new(&self.device, pipeline.clone(), self.current_swapchain.as_ref().cloned().unwrap());
 
                 self.debug_pipeline = Some((pipeline, swapchain_output));
@@ -203,6 +1627,33 @@ impl RenderConfig {
         }
     }
 
+    /// Pauses or resumes frames because of a zero sized surface. Only state changes are logged.
+    fn set_surface_paused(&mut self, paused: bool) {
+        if self.surface_paused == paused {
+            return;
+        }
+        self.surface_paused = paused;
+
+        if paused {
+            log::info!("Main surface has no size. Skipping frames until it is restored");
+            self.log_event(RendererEvent::SurfacePause);
+        } else {
+            log::info!("Main surface restored. Resuming frames");
+            self.log_event(RendererEvent::SurfaceResume);
+        }
+    }
+
+    fn surface_has_extent(&self) -> bool {
+        match self.main_surface.get_surface_capabilities() {
+            Ok(capabilities) => {
+                capabilities.max_image_extent.width != 0 && capabilities.max_image_extent.height != 0 &&
+                    capabilities.current_extent.width != 0 && capabilities.current_extent.height != 0
+            }
+            // Errors are reported once the swapchain is created
+            Err(_) => true,
+        }
+    }
+
     fn try_create_swapchain(&mut self, size: Vec2u32) -> bool {
         log::info!("Attempting to rebuild swapchain with size {:?}", size);
 
@@ -214,30 +1665,109 @@ impl RenderConfig {
         self.last_rebuild = Instant::now();
 
         let config = SwapchainConfig {
-            allow_tearing: true, // We set this to true to unlock fps for testing
+            allow_tearing: !self.vsync,
             formats: Box::new([
                 vk::SurfaceFormatKHR{ format: vk::Format::R8G8B8A8_SRGB, color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR },
                 vk::SurfaceFormatKHR{ format: vk::Format::B8G8R8A8_SRGB, color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR },
             ]),
             required_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
             optional_usage: vk::ImageUsageFlags::empty(),
-            clipped: true
+            clipped: true,
+            preferred_image_count: self.frames_in_flight + 1,
+            full_screen_exclusive: self.full_screen_exclusive,
+            variable_refresh: self.latency_mode == LatencyMode::VariableRefresh,
         };
 
         match self.main_surface.create_swapchain(&config, size) {
             Ok(swapchain) => {
+                let rotation = swapchain.get_rotation();
+                if rotation != OutputRotation::None {
+                    log::info!("Surface uses transform {:?}. Output is pre-rotated by {:?}", swapchain.get_transform(), rotation);
+                }
+                self.log_event(RendererEvent::SwapchainCreate { width: size[0], height: size[1] });
                 self.current_swapchain = Some(swapchain);
                 true
             }
+            Err(SwapchainCreateError::NoExtent) => {
+                self.set_surface_paused(true);
+                self.current_swapchain = None;
+                false
+            }
             Err(err) => {
                 log::info!("Failed to create swapchain of size {:?}: {:?}", size, err);
+                self.log_event(RendererEvent::SwapchainCreateFailed { width: size[0], height: size[1], error: format!("{:?}", err) });
                 self.current_swapchain = None;
+                self.check_adapter();
                 false
             }
         }
     }
 }
 
+/// The user configurable state of a [`RenderConfig`] which is preserved if the device is recreated.
+struct RenderSettings {
+    debug_mode: Option<DebugPipelineMode>,
+    full_screen_exclusive: FullScreenExclusiveMode,
+    latency_mode: LatencyMode,
+    color_grading: ColorGrading,
+    post_chains: HashMap<PostChainId, ColorMatrix>,
+    active_post_chain: Option<PostChainId>,
+    accessibility_filter: AccessibilityFilter,
+    screen_distortion: ScreenDistortion,
+    vsync: bool,
+    frames_in_flight: u32,
+    upload_budget: Option<u64>,
+    render_budget: RenderBudget,
+    submit_mode: SubmitMode,
+}
+
+/// Controls the tradeoff between latency and throughput.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LatencyMode {
+    /// Frames are queued as fast as the swapchain accepts them.
+    Default,
+    /// Frames are only started once all previous frames have been presented. Only has an effect
+    /// if VK_KHR_present_wait is supported.
+    LowLatency,
+    /// Pacing for variable refresh rate (FreeSync / G-Sync) displays. Frames are not capped to a
+    /// fixed rate and are started once all previous frames have been presented so input and
+    /// camera matrices are sampled as late as possible. Use [`FrameStats::display_timing`] to
+    /// detect if the display supports variable refresh.
+    VariableRefresh,
+}
+
+impl LatencyMode {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Default),
+            1 => Some(Self::LowLatency),
+            2 => Some(Self::VariableRefresh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    /// The time between queueing the last completed present and it completing. This is a proxy
+    /// for the present to photon latency. Only available if VK_KHR_present_wait is supported.
+    pub present_latency: Option<Duration>,
+
+    /// The timing of recently displayed frames. Only available if VK_GOOGLE_display_timing is
+    /// supported.
+    pub display_timing: Option<DisplayTiming>,
+
+    /// The statistics of every render layer drawn to in the last ended frame.
+    pub layers: Vec<(RenderLayerId, RenderLayerStats)>,
+
+    /// The statistics of every draw tag used in the last ended frame sorted by descending index
+    /// count.
+    pub tags: Vec<(u64, DrawTagStats)>,
+
+    /// The work dropped because of the render budget in the last ended frame.
+    pub budget: BudgetReport,
+}
+
 pub struct B4DVertexFormat {
     pub topology: vk::PrimitiveTopology,
     pub stride: u32,
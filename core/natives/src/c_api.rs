@@ -1,14 +1,48 @@
+use std::any::Any;
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
 use std::panic::catch_unwind;
-use std::process::exit;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use ash::vk;
-use crate::b4d::Blaze4D;
-use crate::glfw_surface::GLFWSurfaceProvider;
+use lazy_static::lazy_static;
+use crate::b4d::{B4DConfig, Blaze4D, DeviceLostReason, LatencyMode};
+use crate::c_validation::{CApiError, CApiRejected, HandleTable, make_slice, set_last_error, take_last_error, validate_image_region, validate_image_write, validate_index_type, validate_mesh_indices, validate_mesh_sizes, validate_primitive_topology, validate_vertex_entry};
+use crate::device::init::DevicePreference;
+use crate::device::capabilities::{FeatureSource, FeatureTier};
+use crate::device::device_group::DeviceGroupMode;
 use crate::prelude::{Mat4f32, UUID, Vec2f32, Vec2u32, Vec3f32, Vec4f32};
 
-use crate::renderer::emulator::{MeshData, PassRecorder, ImmediateMeshId, GlobalMesh, ImageData, GlobalImage, SamplerInfo};
+use crate::renderer::emulator::{FrameSize, MAX_TEXTURE_SLOTS, MAX_VIEWPORTS, MeshData, PassRecorder, ImmediateMeshId, SubmitMode, GlobalMesh, GlobalMeshId, ImageArrayMode, ImageData, GlobalImage, ImageUsageStats, SamplerInfo, SparseResidencyStats};
+use crate::renderer::emulator::beam::BeamColumn;
+use crate::renderer::emulator::blob_shadow::{BlobShadow, BlobShadowConfig};
+use crate::renderer::emulator::camera_effects::{CameraEffects, ScreenShake, ViewBob};
+use crate::renderer::emulator::auto_exposure::AutoExposure;
+use crate::renderer::emulator::color_grading::ColorGradingPreset;
+use crate::renderer::emulator::compositor::CompositeLayer;
+use crate::renderer::emulator::command_stream::StreamRecorderConfig;
+use crate::renderer::emulator::event_log::EventLogTarget;
+use crate::renderer::emulator::gui_item::{make_gui_item_projection, GuiItemPlacement};
+use crate::renderer::emulator::gui_rect::{GuiRect, NineSlice, TexturedRect};
+use crate::renderer::emulator::debug_overlay::{ChunkGridOverlay, SectionHeatmap, SectionValue};
 use crate::renderer::emulator::debug_pipeline::DebugPipelineMode;
-use crate::renderer::emulator::mc_shaders::{McUniform, McUniformData, ShaderId, VertexFormat, VertexFormatEntry};
+use crate::renderer::emulator::frame_times::{FrameTimeSummary, HISTOGRAM_BUCKET_COUNT};
+use crate::renderer::emulator::mc_shaders::{AlphaMode, FogMode, McUniform, McUniformData, ShaderId, ShaderSpecialization, VertexFormat, VertexFormatEntry, VertexFormatId};
+use crate::renderer::emulator::memory::{MemoryBudget, MemoryPressure};
+use crate::renderer::emulator::jobs::{Job, JobExecutor, JobKind, JobKindStats, JobSystem};
+use crate::renderer::emulator::map_texture::{MapTextureCache, MAP_DATA_SIZE};
+use crate::renderer::emulator::mesh_compression::{decompress_meshes, CompressedMesh, DecompressedMesh, MeshCompression};
+use crate::renderer::emulator::probe::{probe_image_size, CubeFace, ProbeCapture};
+use crate::renderer::emulator::panorama::PanoramaCapture;
+use crate::renderer::emulator::post_chain::{AccessibilityFilter, PostChain, PostChainId, ScreenDistortion};
+use crate::renderer::emulator::quantization::{NormalEncoding, PositionQuantization};
+use crate::renderer::emulator::render_layer::{InsertionPoint, RenderLayerId, RenderLayerState, RenderOrderEntry};
+use crate::renderer::emulator::shadow::CameraFrustum;
+use crate::renderer::emulator::unproject::ViewMatrices;
+use crate::renderer::emulator::warm_state::MeshRetention;
+use crate::renderer::emulator::draw_ring::{DrawRing, RingCommand};
+use crate::renderer::emulator::watchdog::WatchdogConfig;
 use crate::util::format::Format;
 use crate::vk::objects::surface::SurfaceProvider;
 
@@ -22,6 +56,94 @@ const NATIVE_METADATA: NativeMetadata = NativeMetadata {
     size_bytes: std::mem::size_of::<usize>() as u32,
 };
 
+lazy_static! {
+    static ref B4D_HANDLES: HandleTable<Blaze4D> = HandleTable::new("b4d");
+    static ref MESH_HANDLES: HandleTable<Arc<GlobalMesh>> = HandleTable::new("mesh");
+    static ref IMAGE_HANDLES: HandleTable<Arc<GlobalImage>> = HandleTable::new("image");
+    static ref PASS_HANDLES: HandleTable<PassRecorder> = HandleTable::new("pass");
+    pub(crate) static ref SURFACE_HANDLES: HandleTable<Box<dyn SurfaceProvider>> = HandleTable::new("surface");
+    static ref PANORAMA_HANDLES: HandleTable<PanoramaCapture> = HandleTable::new("panorama");
+    static ref PROBE_HANDLES: HandleTable<ProbeCapture> = HandleTable::new("probe");
+    static ref RETENTION_HANDLES: HandleTable<MeshRetention> = HandleTable::new("mesh retention");
+    static ref RING_HANDLES: HandleTable<DrawRing> = HandleTable::new("draw ring");
+    static ref MAP_CACHE_HANDLES: HandleTable<MapTextureCache> = HandleTable::new("map texture cache");
+    static ref JOB_HANDLES: HandleTable<Job> = HandleTable::new("job");
+}
+
+/// Unwraps the result or logs the error and rejects the call if the c api was used incorrectly.
+pub(crate) fn check<T>(result: Result<T, CApiError>, function: &str) -> T {
+    result.unwrap_or_else(|err| {
+        log::error!("Invalid argument {:?} passed to {}", err, function);
+        reject(err)
+    })
+}
+
+/// Rejects the current call by unwinding to the entry point which then returns
+/// [`RejectedValue::rejected`]. The error can be queried with `b4d_take_last_error`.
+///
+/// The entry points use the `C-unwind` abi so that fuzzing builds can unwind all the way to the
+/// fuzz target and continue after rejected input.
+pub(crate) fn reject(error: CApiError) -> ! {
+    set_last_error(error);
+    std::panic::resume_unwind(Box::new(CApiRejected))
+}
+
+/// Handles a unwind caught by a entry point.
+///
+/// Rejected calls return [`RejectedValue::rejected`] or are propagated to the fuzz target in
+/// fuzzing builds. Any other panic is a bug and exits the process.
+pub(crate) fn on_panic<T: RejectedValue>(payload: Box<dyn Any + Send>, function: &str) -> T {
+    if payload.is::<CApiRejected>() {
+        #[cfg(feature = "fuzzing")]
+        std::panic::resume_unwind(payload);
+        #[cfg(not(feature = "fuzzing"))]
+        return T::rejected();
+    }
+
+    log::error!("panic in {}", function);
+    std::process::exit(1);
+}
+
+/// The value returned by a entry point if the call was rejected.
+pub(crate) trait RejectedValue {
+    fn rejected() -> Self;
+}
+
+impl RejectedValue for () {
+    fn rejected() -> Self {
+    }
+}
+
+impl RejectedValue for u32 {
+    fn rejected() -> Self {
+        0
+    }
+}
+
+impl RejectedValue for u64 {
+    fn rejected() -> Self {
+        0
+    }
+}
+
+impl RejectedValue for usize {
+    fn rejected() -> Self {
+        0
+    }
+}
+
+impl<T> RejectedValue for *const T {
+    fn rejected() -> Self {
+        std::ptr::null()
+    }
+}
+
+impl<T> RejectedValue for *mut T {
+    fn rejected() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, PartialEq, Eq)]
 struct CDebugMode(u32);
@@ -39,8 +161,8 @@ impl CDebugMode {
     pub const TEXTURED1: CDebugMode = CDebugMode(9);
     pub const TEXTURED2: CDebugMode = CDebugMode(10);
 
-    pub fn to_debug_pipeline_mode(&self) -> Option<DebugPipelineMode> {
-        match *self {
+    pub fn to_debug_pipeline_mode(&self) -> Result<Option<DebugPipelineMode>, CApiError> {
+        Ok(match *self {
             Self::NONE => None,
             Self::DEPTH => Some(DebugPipelineMode::Depth),
             Self::POSITION => Some(DebugPipelineMode::Position),
@@ -52,8 +174,79 @@ impl CDebugMode {
             Self::TEXTURED0 => Some(DebugPipelineMode::Textured0),
             Self::TEXTURED1 => Some(DebugPipelineMode::Textured1),
             Self::TEXTURED2 => Some(DebugPipelineMode::Textured2),
-            _ => panic!()
-        }
+            _ => return Err(CApiError::InvalidEnum("debug_mode", self.0 as i64))
+        })
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct CB4DConfig {
+    enable_validation: u32,
+    device_preference: u32,
+    msaa_samples: u32,
+    vsync: u32,
+    frames_in_flight: u32,
+    debug_mode: CDebugMode,
+    /// The memory budget in bytes or 0 if no budget should be used.
+    memory_budget: u64,
+    /// Null terminated utf8 path or null if no pipeline cache should be used.
+    pipeline_cache_path: *const c_char,
+    /// The watchdog timeout in milliseconds or 0 if the watchdog should be disabled.
+    watchdog_timeout_ms: u32,
+    watchdog_recover: u32,
+    gpu_assisted_validation: u32,
+    robust_access: u32,
+    vertex_pulling: u32,
+    /// The raw [`DeviceGroupMode`] or 0 if no device group should be used.
+    device_group: u32,
+    track_host_memory: u32,
+    /// The number of job threads or 0 to derive it from the number of cores.
+    job_threads: u32,
+}
+
+impl CB4DConfig {
+    unsafe fn to_config(&self) -> Result<B4DConfig, CApiError> {
+        let device_preference = match self.device_preference {
+            0 => DevicePreference::Default,
+            1 => DevicePreference::Discrete,
+            2 => DevicePreference::Integrated,
+            other => return Err(CApiError::InvalidEnum("device_preference", other as i64)),
+        };
+
+        let device_group = match self.device_group {
+            0 => None,
+            other => Some(DeviceGroupMode::from_raw(other).ok_or(CApiError::InvalidEnum("device_group", other as i64))?),
+        };
+
+        let pipeline_cache_path = if self.pipeline_cache_path.is_null() {
+            None
+        } else {
+            Some(PathBuf::from(CStr::from_ptr(self.pipeline_cache_path).to_string_lossy().into_owned()))
+        };
+
+        Ok(B4DConfig {
+            enable_validation: self.enable_validation != 0,
+            gpu_assisted_validation: self.gpu_assisted_validation != 0,
+            robust_access: self.robust_access != 0,
+            device_preference,
+            msaa_samples: self.msaa_samples,
+            vsync: self.vsync != 0,
+            frames_in_flight: self.frames_in_flight,
+            memory_budget: if self.memory_budget == 0 { None } else { Some(self.memory_budget) },
+            debug_mode: self.debug_mode.to_debug_pipeline_mode()?,
+            vertex_pulling: self.vertex_pulling != 0,
+            pipeline_cache_path,
+            watchdog: if self.watchdog_timeout_ms == 0 { None } else {
+                Some(WatchdogConfig {
+                    timeout: Duration::from_millis(self.watchdog_timeout_ms as u64),
+                    recover: self.watchdog_recover != 0,
+                })
+            },
+            device_group,
+            track_host_memory: self.track_host_memory != 0,
+            job_threads: self.job_threads,
+        })
     }
 }
 
@@ -74,146 +267,194 @@ struct CPipelineConfiguration {
 
 #[repr(C)]
 #[derive(Debug)]
-struct CMeshData {
-    vertex_data_ptr: *const u8,
-    vertex_data_len: usize,
-    index_data_ptr: *const u8,
-    index_data_len: usize,
-    vertex_stride: u32,
-    index_count: u32,
-    index_type: i32,
-    primitive_topology: i32,
+pub struct CMeshData {
+    pub vertex_data_ptr: *const u8,
+    pub vertex_data_len: usize,
+    pub index_data_ptr: *const u8,
+    pub index_data_len: usize,
+    pub vertex_stride: u32,
+    pub index_count: u32,
+    pub index_type: i32,
+    pub primitive_topology: i32,
+    /// A [`MeshCompression`] value. If not 0 the data lengths are the compressed sizes. Only
+    /// supported when creating global meshes.
+    pub compression: u32,
+    pub vertex_data_uncompressed_len: usize,
+    pub index_data_uncompressed_len: usize,
 }
 
 impl CMeshData {
-    unsafe fn to_mesh_data(&self) -> MeshData {
-        if self.vertex_data_ptr.is_null() {
-            log::error!("Vertex data pointer is null");
-            panic!();
-        }
-        if self.index_data_ptr.is_null() {
-            log::error!("Index data pointer is null");
-            panic!();
+    /// Validates the mesh data and converts it into a [`MeshData`].
+    ///
+    /// # Safety
+    ///
+    /// The data pointers must either be null or point to the specified number of bytes.
+    pub unsafe fn to_mesh_data(&self) -> Result<MeshData, CApiError> {
+        if self.compression != 0 {
+            return Err(CApiError::InvalidEnum("compression", self.compression as i64));
         }
+        let index_type = validate_index_type(self.index_type)?;
+        let primitive_topology = validate_primitive_topology(self.primitive_topology)?;
+        validate_mesh_sizes(self.vertex_data_len, self.index_data_len, self.vertex_stride, self.index_count, index_type)?;
+
+        let vertex_data = make_slice("vertex_data", self.vertex_data_ptr, self.vertex_data_len)?;
+        let index_data = make_slice("index_data", self.index_data_ptr, self.index_data_len)?;
+        validate_mesh_indices(index_data, self.index_count, index_type, self.vertex_data_len / (self.vertex_stride as usize))?;
 
-        MeshData {
-            vertex_data: std::slice::from_raw_parts(self.vertex_data_ptr, self.vertex_data_len as usize),
-            index_data: std::slice::from_raw_parts(self.index_data_ptr, self.index_data_len as usize),
+        Ok(MeshData {
+            vertex_data,
+            index_data,
             vertex_stride: self.vertex_stride,
             index_count: self.index_count,
-            index_type: vk::IndexType::from_raw(self.index_type),
-            primitive_topology: vk::PrimitiveTopology::from_raw(self.primitive_topology),
+            index_type,
+            primitive_topology,
+        })
+    }
+
+    unsafe fn to_compressed_mesh(&self) -> Result<CompressedMesh, CApiError> {
+        let compression = MeshCompression::from_raw(self.compression).ok_or(CApiError::InvalidEnum("compression", self.compression as i64))?;
+        let index_type = validate_index_type(self.index_type)?;
+        validate_primitive_topology(self.primitive_topology)?;
+        validate_mesh_sizes(self.vertex_data_uncompressed_len, self.index_data_uncompressed_len, self.vertex_stride, self.index_count, index_type)?;
+
+        Ok(CompressedMesh {
+            vertex_data: make_slice("vertex_data", self.vertex_data_ptr, self.vertex_data_len)?,
+            index_data: make_slice("index_data", self.index_data_ptr, self.index_data_len)?,
+            vertex_data_len: self.vertex_data_uncompressed_len,
+            index_data_len: self.index_data_uncompressed_len,
+            compression,
+        })
+    }
+
+    /// Uses the decompressed data if the mesh is compressed. `decompressed` must be the result of
+    /// [`decompress_c_meshes`] for this mesh.
+    unsafe fn to_mesh_data_decompressed<'a>(&'a self, decompressed: &'a Option<DecompressedMesh>) -> Result<MeshData<'a>, CApiError> {
+        match decompressed {
+            Some(decompressed) => Ok(MeshData {
+                vertex_data: &decompressed.vertex_data,
+                index_data: &decompressed.index_data,
+                vertex_stride: self.vertex_stride,
+                index_count: self.index_count,
+                index_type: validate_index_type(self.index_type)?,
+                primitive_topology: validate_primitive_topology(self.primitive_topology)?,
+            }),
+            None => self.to_mesh_data(),
         }
     }
 }
 
+/// Decompresses the data of all compressed meshes in `datas` using the job system. Contains
+/// [`None`] for meshes which are not compressed.
+unsafe fn decompress_c_meshes(jobs: &JobSystem, datas: &[CMeshData], function: &str) -> Vec<Option<DecompressedMesh>> {
+    let compressed: Vec<(usize, CompressedMesh)> = datas.iter().enumerate()
+        .filter(|(_, data)| data.compression != 0)
+        .map(|(index, data)| (index, check(data.to_compressed_mesh(), function)))
+        .collect();
+    let meshes: Vec<CompressedMesh> = compressed.iter().map(|(_, mesh)| *mesh).collect();
+
+    let mut result: Vec<Option<DecompressedMesh>> = datas.iter().map(|_| None).collect();
+    for ((index, _), decompressed) in compressed.iter().zip(decompress_meshes(jobs, &meshes)) {
+        result[*index] = Some(check(decompressed.map_err(|err| {
+            log::error!("Failed to decompress mesh data passed to {}: {:?}", function, err);
+            CApiError::InvalidArgument("compressed mesh data")
+        }), function));
+    }
+    result
+}
+
 #[derive(Debug)]
 #[repr(C)]
-struct CVertexFormat {
-    stride: u32,
-    position_offset: u32,
-    position_format: i32,
-    normal_offset: u32,
-    normal_format: i32,
-    color_offset: u32,
-    color_format: i32,
-    uv0_offset: u32,
-    uv0_format: i32,
-    uv1_offset: u32,
-    uv1_format: i32,
-    uv2_offset: u32,
-    uv2_format: i32,
-    has_normal: bool,
-    has_color: bool,
-    has_uv0: bool,
-    has_uv1: bool,
-    has_uv2: bool,
+pub struct CVertexFormat {
+    pub stride: u32,
+    pub position_offset: u32,
+    pub position_format: i32,
+    pub normal_offset: u32,
+    pub normal_format: i32,
+    pub color_offset: u32,
+    pub color_format: i32,
+    pub uv0_offset: u32,
+    pub uv0_format: i32,
+    pub uv1_offset: u32,
+    pub uv1_format: i32,
+    pub uv2_offset: u32,
+    pub uv2_format: i32,
+    pub normal_encoding: u32,
+    pub position_quantization_offset: Vec3f32,
+    pub position_quantization_scale: Vec3f32,
+    pub has_normal: bool,
+    pub has_color: bool,
+    pub has_uv0: bool,
+    pub has_uv1: bool,
+    pub has_uv2: bool,
+    pub has_position_quantization: bool,
 }
 
 impl CVertexFormat {
-    fn to_vertex_format(&self) -> VertexFormat {
-        let normal = if self.has_normal {
-            Some(VertexFormatEntry {
-                offset: self.normal_offset,
-                format: vk::Format::from_raw(self.normal_format)
-            })
-        } else {
-            None
-        };
-
-        let color = if self.has_color {
-            Some(VertexFormatEntry {
-                offset: self.color_offset,
-                format: vk::Format::from_raw(self.color_format)
-            })
-        } else {
-            None
-        };
-
-        let uv0 = if self.has_uv0 {
-            Some( VertexFormatEntry {
-                offset: self.uv0_offset,
-                format: vk::Format::from_raw(self.uv0_format)
-            })
-        } else {
-            None
-        };
-
-        let uv1 = if self.has_uv1 {
-            Some(VertexFormatEntry {
-                offset: self.uv1_offset,
-                format: vk::Format::from_raw(self.uv1_format)
-            })
-        } else {
-            None
+    pub fn to_vertex_format(&self) -> Result<VertexFormat, CApiError> {
+        let entry = |name: &'static str, present: bool, offset: u32, format: i32| -> Result<Option<VertexFormatEntry>, CApiError> {
+            if present {
+                Ok(Some(VertexFormatEntry {
+                    offset,
+                    format: validate_vertex_entry(name, self.stride, offset, format)?
+                }))
+            } else {
+                Ok(None)
+            }
         };
 
-        let uv2 = if self.has_uv2 {
-            Some(VertexFormatEntry {
-                offset: self.uv2_offset,
-                format: vk::Format::from_raw(self.uv2_format)
+        let position_quantization = if self.has_position_quantization {
+            let scale = self.position_quantization_scale;
+            if !scale.iter().all(|v| v.is_finite() && *v > 0f32) || !self.position_quantization_offset.iter().all(|v| v.is_finite()) {
+                return Err(CApiError::InvalidSize("position_quantization"));
+            }
+            Some(PositionQuantization {
+                offset: self.position_quantization_offset,
+                scale,
             })
         } else {
             None
         };
+        let normal_encoding = NormalEncoding::from_raw(self.normal_encoding)
+            .ok_or(CApiError::InvalidEnum("normal_encoding", self.normal_encoding as i64))?;
 
-        VertexFormat {
+        Ok(VertexFormat {
             stride: self.stride,
-            position: VertexFormatEntry {
-                offset: self.position_offset,
-                format: vk::Format::from_raw(self.position_format)
-            },
-            normal,
-            color,
-            uv0,
-            uv1,
-            uv2
-        }
+            position: entry("position", true, self.position_offset, self.position_format)?.unwrap(),
+            normal: entry("normal", self.has_normal, self.normal_offset, self.normal_format)?,
+            color: entry("color", self.has_color, self.color_offset, self.color_format)?,
+            uv0: entry("uv0", self.has_uv0, self.uv0_offset, self.uv0_format)?,
+            uv1: entry("uv1", self.has_uv1, self.uv1_offset, self.uv1_format)?,
+            uv2: entry("uv2", self.has_uv2, self.uv2_offset, self.uv2_format)?,
+            position_quantization,
+            normal_encoding,
+        })
     }
 }
 
 #[repr(C)]
-struct CImageData {
-    data_ptr: *const u8,
-    data_ptr_len: usize,
-    row_stride: u32,
-    offset: [u32; 2],
-    extent: [u32; 2],
+pub struct CImageData {
+    pub data_ptr: *const u8,
+    pub data_ptr_len: usize,
+    pub row_stride: u32,
+    pub offset: [u32; 2],
+    pub extent: [u32; 2],
 }
 
 impl CImageData {
-    unsafe fn to_image_data(&self) -> ImageData {
-        if self.data_ptr.is_null() {
-            log::error!("Data pointer is null");
-            panic!();
-        }
+    /// Converts the write validating it against an area of `image_size` texels.
+    unsafe fn to_image_data(&self, image: &GlobalImage, image_size: Vec2u32) -> Result<ImageData, CApiError> {
+        let offset = Vec2u32::new(self.offset[0], self.offset[1]);
+        let extent = Vec2u32::new(self.extent[0], self.extent[1]);
+
+        let texel_size = image.get_format().get_compatibility_class().get_texel_size().ok_or(CApiError::InvalidEnum("image_format", image.get_format().get_format().as_raw() as i64))?;
+        validate_image_write(image_size, texel_size, self.data_ptr_len, 0, offset, extent)?;
 
-        ImageData {
-            data: std::slice::from_raw_parts(self.data_ptr, self.data_ptr_len),
+        Ok(ImageData {
+            data: make_slice("image_data", self.data_ptr, self.data_ptr_len)?,
             row_stride: 0,
-            offset: Vec2u32::new(self.offset[0], self.offset[1]),
-            extent: Vec2u32::new(self.extent[0], self.extent[1])
-        }
+            offset,
+            extent
+        })
     }
 }
 
@@ -234,8 +475,8 @@ struct CMcUniformData {
 }
 
 impl CMcUniformData {
-    unsafe fn to_mc_uniform_data(&self) -> McUniformData {
-        match McUniform::from_raw(self.uniform) {
+    unsafe fn to_mc_uniform_data(&self) -> Result<McUniformData, CApiError> {
+        Ok(match McUniform::from_raw(self.uniform) {
             McUniform::MODEL_VIEW_MATRIX => {
                 McUniformData::ModelViewMatrix(self.payload.mat4f32)
             },
@@ -281,11 +522,11 @@ impl CMcUniformData {
             McUniform::CHUNK_OFFSET => {
                 McUniformData::ChunkOffset(self.payload.vec3f32)
             },
-            _ => {
-                log::error!("Invalid uniform type {:?}", self.uniform);
-                panic!()
-            }
-        }
+            McUniform::ITEM_ANIMATION => {
+                McUniformData::ItemAnimation(self.payload.vec2f32)
+            },
+            _ => return Err(CApiError::InvalidEnum("uniform", self.uniform as i64))
+        })
     }
 }
 
@@ -300,343 +541,2907 @@ struct CSamplerInfo {
 }
 
 impl CSamplerInfo {
-    fn to_sampler_info(&self) -> SamplerInfo {
-        SamplerInfo {
-            mag_filter: vk::Filter::from_raw(self.mag_filter),
-            min_filter: vk::Filter::from_raw(self.min_filter),
-            mipmap_mode: vk::SamplerMipmapMode::from_raw(self.mipmap_mode),
-            address_mode_u: vk::SamplerAddressMode::from_raw(self.address_mode_u),
-            address_mode_v: vk::SamplerAddressMode::from_raw(self.address_mode_v),
+    fn to_sampler_info(&self) -> Result<SamplerInfo, CApiError> {
+        let filter = |name: &'static str, raw: i32| match vk::Filter::from_raw(raw) {
+            vk::Filter::NEAREST | vk::Filter::LINEAR => Ok(vk::Filter::from_raw(raw)),
+            _ => Err(CApiError::InvalidEnum(name, raw as i64)),
+        };
+        let address_mode = |name: &'static str, raw: i32| {
+            if (vk::SamplerAddressMode::REPEAT.as_raw()..=vk::SamplerAddressMode::CLAMP_TO_BORDER.as_raw()).contains(&raw) {
+                Ok(vk::SamplerAddressMode::from_raw(raw))
+            } else {
+                Err(CApiError::InvalidEnum(name, raw as i64))
+            }
+        };
+        let mipmap_mode = match vk::SamplerMipmapMode::from_raw(self.mipmap_mode) {
+            vk::SamplerMipmapMode::NEAREST | vk::SamplerMipmapMode::LINEAR => vk::SamplerMipmapMode::from_raw(self.mipmap_mode),
+            _ => return Err(CApiError::InvalidEnum("mipmap_mode", self.mipmap_mode as i64)),
+        };
+
+        Ok(SamplerInfo {
+            mag_filter: filter("mag_filter", self.mag_filter)?,
+            min_filter: filter("min_filter", self.min_filter)?,
+            mipmap_mode,
+            address_mode_u: address_mode("address_mode_u", self.address_mode_u)?,
+            address_mode_v: address_mode("address_mode_v", self.address_mode_v)?,
             anisotropy_enable: self.anisotropy_enable != 0,
+        })
+    }
+}
+
+/// A gui rect in the layout written by the java gui helpers. Rects are given by their minimum and
+/// maximum corner. The tint is packed as `0xAARRGGBB` like vanilla colors.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CTexturedRect {
+    dst: [f32; 4],
+    uv: [f32; 4],
+    tint: u32,
+}
+
+impl CTexturedRect {
+    fn to_textured_rect(&self) -> TexturedRect {
+        TexturedRect {
+            dst: make_gui_rect(&self.dst),
+            uv: make_gui_rect(&self.uv),
+            tint: unpack_argb(self.tint),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CNineSlice {
+    dst: [f32; 4],
+    uv: [f32; 4],
+    border: [f32; 4],
+    uv_border: [f32; 4],
+    tint: u32,
+}
+
+impl CNineSlice {
+    fn to_nine_slice(&self) -> NineSlice {
+        NineSlice {
+            dst: make_gui_rect(&self.dst),
+            uv: make_gui_rect(&self.uv),
+            border: self.border,
+            uv_border: self.uv_border,
+            tint: unpack_argb(self.tint),
+        }
+    }
+}
+
+/// A beam in the layout written by the java beam helpers. The color is packed as `0xAARRGGBB`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CBeamColumn {
+    position: [f32; 3],
+    height: f32,
+    radius: f32,
+    rotation: f32,
+    scroll: f32,
+    color: u32,
+}
+
+impl CBeamColumn {
+    fn to_beam_column(&self) -> BeamColumn {
+        let color = unpack_argb(self.color);
+        BeamColumn {
+            position: Vec3f32::from_column_slice(&self.position),
+            height: self.height,
+            radius: self.radius,
+            rotation: self.rotation,
+            scroll: self.scroll,
+            color: Vec4f32::from_iterator(color.iter().map(|c| (*c as f32) / 255f32)),
+        }
+    }
+}
+
+/// A composite layer in the layout written by the java compositing helpers. The tint is packed as
+/// `0xAARRGGBB`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CCompositeLayer {
+    image: *const Arc<GlobalImage>,
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    tint: u32,
+}
+
+impl CCompositeLayer {
+    unsafe fn to_composite_layer(&self, name: &'static str) -> CompositeLayer {
+        let image = check(IMAGE_HANDLES.get(self.image), name);
+        let tint = unpack_argb(self.tint);
+        CompositeLayer {
+            image: image.clone(),
+            uv_min: Vec2f32::from_column_slice(&self.uv_min),
+            uv_max: Vec2f32::from_column_slice(&self.uv_max),
+            tint: Vec4f32::new(tint[0] as f32, tint[1] as f32, tint[2] as f32, tint[3] as f32) / 255f32,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CBlobShadow {
+    ground_position: [f32; 3],
+    radius: f32,
+    strength: f32,
+    height: f32,
+}
+
+impl CBlobShadow {
+    fn to_blob_shadow(&self) -> BlobShadow {
+        BlobShadow {
+            ground_position: Vec3f32::from_column_slice(&self.ground_position),
+            radius: self.radius,
+            strength: self.strength,
+            height: self.height,
+        }
+    }
+}
+
+/// The color is packed as `0xAARRGGBB` like vanilla colors. The alpha is ignored.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CBlobShadowConfig {
+    camera_position: [f32; 3],
+    color: u32,
+    depth_bias: f32,
+    softness: f32,
+    max_height: f32,
+    fade_start: f32,
+    fade_end: f32,
+}
+
+impl CBlobShadowConfig {
+    fn to_blob_shadow_config(&self) -> BlobShadowConfig {
+        let [r, g, b, _] = unpack_argb(self.color);
+        BlobShadowConfig {
+            camera_position: Vec3f32::from_column_slice(&self.camera_position),
+            color: [r, g, b],
+            depth_bias: self.depth_bias,
+            softness: self.softness,
+            max_height: self.max_height,
+            fade_start: self.fade_start,
+            fade_end: self.fade_end,
         }
     }
 }
 
+fn make_gui_rect(rect: &[f32; 4]) -> GuiRect {
+    GuiRect::new(Vec2f32::new(rect[0], rect[1]), Vec2f32::new(rect[2], rect[3]))
+}
+
+fn unpack_argb(color: u32) -> [u8; 4] {
+    let [b, g, r, a] = color.to_le_bytes();
+    [r, g, b, a]
+}
+
+/// The sampler used by the gui helpers if no sampler is passed. Matches the vanilla gui textures.
+const GUI_SAMPLER_INFO: SamplerInfo = SamplerInfo {
+    mag_filter: vk::Filter::NEAREST,
+    min_filter: vk::Filter::NEAREST,
+    mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+    address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    anisotropy_enable: false,
+};
+
 /// Returns static information about the natives.
 #[no_mangle]
-unsafe extern "C" fn b4d_get_native_metadata() -> *const NativeMetadata {
+unsafe extern "C-unwind" fn b4d_get_native_metadata() -> *const NativeMetadata {
     &NATIVE_METADATA
 }
 
+/// Returns and clears the error code of the last rejected call on this thread or 0 if no call was
+/// rejected. Rejected calls return null or 0 and log the reason.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_take_last_error() -> u32 {
+    take_last_error().map_or(0, |err| err.get_code())
+}
+
+/// Switches id generation to a seeded counter. See [`UUID::enable_seeded_mode`]. Must be called
+/// before [`b4d_init`] to produce reproducible ids.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_enable_seeded_ids(seed: u64) {
+    catch_unwind(|| {
+        UUID::enable_seeded_mode(seed);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_enable_seeded_ids"))
+}
+
 /// Creates a new [`Blaze4D`] instance.
 ///
-/// This function will take ownership of the provided surface and vertex format set builder. The
-/// pointers must not be used again afterwards.
+/// This function will take ownership of the provided surface provider. The pointer must not be
+/// used again afterwards. Surface providers are created by `b4d_create_glfw_surface_provider` or
+/// `b4d_create_headless_surface`.
 #[no_mangle]
-unsafe extern "C" fn b4d_init(surface: *mut GLFWSurfaceProvider, enable_validation: u32) -> *mut Blaze4D {
+unsafe extern "C-unwind" fn b4d_init(surface: *mut Box<dyn SurfaceProvider>, enable_validation: u32) -> *mut Blaze4D {
     catch_unwind(|| {
-        if surface.is_null() {
-            log::error!("Passed null surface to b4d_init");
-            exit(1);
-        }
-
-        let surface_provider: Box<dyn SurfaceProvider> = Box::from_raw(surface);
+        let surface_provider = *check(SURFACE_HANDLES.remove(surface), "b4d_init");
 
         let enable_validation = enable_validation != 0;
 
-        Box::leak(Box::new(Blaze4D::new(surface_provider, enable_validation)))
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_init");
-        exit(1);
-    })
+        B4D_HANDLES.insert(Box::new(Blaze4D::new(surface_provider, enable_validation)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_init"))
 }
 
-/// Destroys a [`Blaze4D`] instance.
+/// Creates a new [`Blaze4D`] instance using the provided configuration.
+///
+/// This function will take ownership of the provided surface provider.
 #[no_mangle]
-unsafe extern "C" fn b4d_destroy(b4d: *mut Blaze4D) {
+unsafe extern "C-unwind" fn b4d_init_with_config(surface: *mut Box<dyn SurfaceProvider>, config: *const CB4DConfig) -> *mut Blaze4D {
     catch_unwind(|| {
-        if b4d.is_null() {
-            log::error!("Passed null to b4d_destroy");
-            exit(1);
-        }
-        Box::from_raw(b4d);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_destroy");
-        exit(1);
-    })
+        let config = check(config.as_ref().ok_or(CApiError::NullPointer("config")), "b4d_init_with_config");
+        let config = check(config.to_config(), "b4d_init_with_config");
+        let surface_provider = *check(SURFACE_HANDLES.remove(surface), "b4d_init_with_config");
+
+        B4D_HANDLES.insert(Box::new(Blaze4D::new_with_config(surface_provider, config)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_init_with_config"))
 }
 
+/// Destroys a [`Blaze4D`] instance.
 #[no_mangle]
-unsafe extern "C" fn b4d_set_debug_mode(b4d: *const Blaze4D, mode: CDebugMode) {
+unsafe extern "C-unwind" fn b4d_destroy(b4d: *mut Blaze4D) {
     catch_unwind(|| {
-        let b4d = b4d.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null b4d to b4d_set_debug_mode");
-            exit(1);
-        });
-
-        b4d.set_debug_mode(mode.to_debug_pipeline_mode());
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_set_debug_mode");
-        exit(1);
-    })
+        drop(check(B4D_HANDLES.remove(b4d), "b4d_destroy"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy"))
 }
 
 #[no_mangle]
-unsafe extern "C" fn b4d_create_global_mesh(b4d: *const Blaze4D, data: *const CMeshData) -> *mut Arc<GlobalMesh> {
+unsafe extern "C-unwind" fn b4d_set_debug_mode(b4d: *const Blaze4D, mode: CDebugMode) {
     catch_unwind(|| {
-        let b4d = b4d.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null b4d to b4d_create_global_mesh");
-            exit(1);
-        });
-        let data = data.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null mesh data to b4d_create_global_mesh");
-            exit(1);
-        });
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_debug_mode");
 
-        let mesh_data = data.to_mesh_data();
+        b4d.set_debug_mode(check(mode.to_debug_pipeline_mode(), "b4d_set_debug_mode"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_debug_mode"))
+}
 
-        Box::leak(Box::new(b4d.create_global_mesh(&mesh_data)))
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_create_global_mesh");
-        exit(1);
-    })
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct CDisplayMode {
+    width: u32,
+    height: u32,
+    refresh_rate: u32,
+    bit_depth: u32,
+}
+
+impl CDisplayMode {
+    fn from_display_mode(mode: &DisplayMode) -> Self {
+        Self {
+            width: mode.size[0],
+            height: mode.size[1],
+            refresh_rate: mode.refresh_rate,
+            bit_depth: mode.bit_depth,
+        }
+    }
+
+    fn to_display_mode(&self) -> DisplayMode {
+        DisplayMode {
+            size: Vec2u32::new(self.width, self.height),
+            refresh_rate: self.refresh_rate,
+            bit_depth: self.bit_depth,
+        }
+    }
 }
 
+/// Writes the display modes of the monitor the main window is on to `modes`. At most `capacity`
+/// entries are written. Returns the total number of display modes.
 #[no_mangle]
-unsafe extern "C" fn b4d_destroy_global_mesh(mesh: *mut Arc<GlobalMesh>) {
+unsafe extern "C-unwind" fn b4d_get_display_modes(b4d: *const Blaze4D, modes: *mut CDisplayMode, capacity: u32) -> u32 {
     catch_unwind(|| {
-        if mesh.is_null() {
-            log::error!("Passed null mesh to b4d_destroy_global_mesh");
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_display_modes");
+        if modes.is_null() && capacity != 0 {
+            log::error!("Passed null modes to b4d_get_display_modes");
+            reject(CApiError::InvalidArgument("b4d_get_display_modes"));
         }
 
-        Box::from_raw(mesh);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_destroy_global_mesh");
-        exit(1);
-    })
+        let display_modes = b4d.get_display_modes();
+        for (index, mode) in display_modes.iter().take(capacity as usize).enumerate() {
+            modes.add(index).write(CDisplayMode::from_display_mode(mode));
+        }
+        display_modes.len() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_display_modes"))
 }
 
+/// Requests the main window to enter exclusive fullscreen with `mode` or to leave fullscreen if
+/// `mode` is null. Returns 1 if the window supports exclusive fullscreen and 0 otherwise.
+///
+/// Glfw windows must only be modified on the main thread so this must be called on the main thread.
 #[no_mangle]
-unsafe extern "C" fn b4d_create_global_image(b4d: *const Blaze4D, width: u32, height: u32, format: i32) -> *mut Arc<GlobalImage> {
+unsafe extern "C-unwind" fn b4d_set_exclusive_fullscreen(b4d: *const Blaze4D, mode: *const CDisplayMode) -> u32 {
     catch_unwind(|| {
-        let b4d = b4d.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null b4d to b4d_create_global_image");
-            exit(1);
-        });
-
-        let size = Vec2u32::new(width, height);
-        let format = Format::format_for(vk::Format::from_raw(format));
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_exclusive_fullscreen");
 
-        Box::leak(Box::new(b4d.create_global_image(size, format)))
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_create_global_image");
-        exit(1);
-    })
+        let mode = mode.as_ref().map(CDisplayMode::to_display_mode);
+        b4d.set_exclusive_fullscreen(mode) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_exclusive_fullscreen"))
 }
 
+/// Writes the content scale of the main window into `scale`.
 #[no_mangle]
-unsafe extern "C" fn b4d_update_global_image(image: *mut Arc<GlobalImage>, writes: *const CImageData, count: u32) {
+unsafe extern "C-unwind" fn b4d_get_content_scale(b4d: *const Blaze4D, scale: *mut Vec2f32) {
     catch_unwind(|| {
-        let image = image.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null image to b4d_update_global_image");
-            exit(1);
-        });
-        if writes.is_null() {
-            log::error!("Passed null writes to b4d_update_global_image");
-            exit(1);
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_content_scale");
+        if scale.is_null() {
+            log::error!("Passed null scale to b4d_get_content_scale");
+            reject(CApiError::NullPointer("scale"));
         }
 
-        let writes = std::slice::from_raw_parts(writes, count as usize);
-        let writes: Box<_> = writes.iter().map(|w| w.to_image_data()).collect();
-
-        image.update_regions(writes.as_ref());
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_update_global_image");
-        exit(1);
-    })
+        scale.write(b4d.get_content_scale());
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_content_scale"))
 }
 
+/// Writes the world space position at the logical pixel position `x`, `y` into `position` using
+/// the camera matrices of the last ended frame. If `depth` is negative the position on the near
+/// plane is returned. Returns 0 if no frame has set camera matrices. See [`Blaze4D::unproject`].
 #[no_mangle]
-unsafe extern "C" fn b4d_destroy_global_image(image: *mut Arc<GlobalImage>) {
+unsafe extern "C-unwind" fn b4d_unproject(b4d: *const Blaze4D, x: f32, y: f32, depth: f32, position: *mut Vec3f32) -> u32 {
     catch_unwind(|| {
-        if image.is_null() {
-            log::error!("Passed null image to b4d_destroy_global_image");
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_unproject");
+        if position.is_null() {
+            log::error!("Passed null position to b4d_unproject");
+            reject(CApiError::NullPointer("position"));
         }
 
-        Box::from_raw(image);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_destroy_global_image");
-        exit(1);
-    })
+        let depth = if depth < 0f32 { None } else { Some(depth.min(1f32)) };
+        match b4d.unproject(Vec2f32::new(x, y), depth) {
+            Some(result) => {
+                position.write(result);
+                1
+            }
+            None => 0,
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_unproject"))
 }
 
+/// Writes the origin and normalized direction of the world space ray at the logical pixel position
+/// `x`, `y`. Returns 0 if no frame has set camera matrices. See [`Blaze4D::unproject_ray`].
 #[no_mangle]
-unsafe extern "C" fn b4d_create_shader(b4d: *const Blaze4D, vertex_format: *const CVertexFormat, used_uniforms: u64) -> u64 {
+unsafe extern "C-unwind" fn b4d_unproject_ray(b4d: *const Blaze4D, x: f32, y: f32, origin: *mut Vec3f32, direction: *mut Vec3f32) -> u32 {
     catch_unwind(|| {
-        let b4d = b4d.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null b4d to b4d_create_shader");
-            exit(1);
-        });
-        let vertex_format = vertex_format.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null vertex_format to b4d_create_shader");
-            exit(1);
-        });
-
-        let vertex_format = vertex_format.to_vertex_format();
-        let mc_uniform = McUniform::from_raw(used_uniforms);
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_unproject_ray");
+        if origin.is_null() || direction.is_null() {
+            log::error!("Passed null pointer to b4d_unproject_ray");
+            reject(CApiError::InvalidArgument("b4d_unproject_ray"));
+        }
 
-        b4d.create_shader(&vertex_format, mc_uniform).as_uuid().get_raw()
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_create_shader");
-        exit(1);
-    })
+        match b4d.unproject_ray(Vec2f32::new(x, y)) {
+            Some(ray) => {
+                origin.write(ray.origin);
+                direction.write(ray.direction);
+                1
+            }
+            None => 0,
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_unproject_ray"))
 }
 
+/// Projects `count` world space points stored as consecutive xyz triples in `points` onto the
+/// screen using the camera matrices of the last ended frame. For every point the x and y position
+/// in logical pixels and the depth are written to `results`. Points behind the camera are written
+/// as NaN. Returns 0 if no frame has set camera matrices. See [`Blaze4D::project_points`].
 #[no_mangle]
-unsafe extern "C" fn b4d_destroy_shader(b4d: *const Blaze4D, shader_id: u64) {
+unsafe extern "C-unwind" fn b4d_project_points(b4d: *const Blaze4D, points: *const f32, count: u32, results: *mut f32) -> u32 {
     catch_unwind(|| {
-        let b4d = b4d.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null b4d to b4d_destroy_shader");
-            exit(1);
-        });
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_project_points");
+        let points = check(make_slice("points", points, count as usize * 3), "b4d_project_points");
+        if results.is_null() && count != 0 {
+            log::error!("Passed null results to b4d_project_points");
+            reject(CApiError::InvalidArgument("b4d_project_points"));
+        }
 
-        b4d.drop_shader(ShaderId::from_uuid(UUID::from_raw(shader_id)));
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_destroy_shader");
-        exit(1);
-    })
+        let points: Vec<_> = points.chunks_exact(3).map(Vec3f32::from_column_slice).collect();
+        match b4d.project_points(&points) {
+            Some(projected) => {
+                for (index, point) in projected.into_iter().enumerate() {
+                    let value = match point {
+                        Some(point) => [point.position[0], point.position[1], point.depth],
+                        None => [f32::NAN; 3],
+                    };
+                    std::ptr::copy_nonoverlapping(value.as_ptr(), results.add(index * 3), 3);
+                }
+                1
+            }
+            None => 0,
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_project_points"))
 }
 
-/// Calls [`Blaze4D::try_start_frame`].
+/// Registers a callback which is called after the device has been recreated. The callback
+/// receives the reason (see [`DeviceLostReason`]) and the provided user data. All global meshes,
+/// images and shaders must be recreated by the host after the callback has been called.
 ///
-/// If [`Blaze4D::try_start_frame`] returns [`None`] this function returns null.
+/// If the callback is null any previously registered callback is removed.
 #[no_mangle]
-unsafe extern "C" fn b4d_start_frame(b4d: *mut Blaze4D, window_width: u32, window_height: u32) -> *mut PassRecorder {
+unsafe extern "C-unwind" fn b4d_set_device_lost_callback(b4d: *const Blaze4D, callback: Option<unsafe extern "C" fn(u32, *mut c_void)>, user_data: *mut c_void) {
     catch_unwind(|| {
-        let b4d = b4d.as_mut().unwrap_or_else(|| {
-            log::error!("Passed null b4d to b4d_start_frame");
-            exit(1);
-        });
-
-        let frame = b4d.try_start_frame(Vec2u32::new(window_width, window_height));
-        frame.map_or(std::ptr::null_mut(), |recorder| {
-            Box::leak(Box::new(recorder))
-        })
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_start_frame");
-        exit(1);
-    })
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_device_lost_callback");
+
+        // Raw pointers are not Send so we have to pass the user data as an integer
+        let user_data = user_data as usize;
+        b4d.set_device_lost_callback(callback.map(|callback| -> Box<dyn Fn(DeviceLostReason) + Send> {
+            Box::new(move |reason| callback(reason as u32, user_data as *mut c_void))
+        }));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_device_lost_callback"))
 }
 
+/// Writes the fault report collected the last time the device was lost as a null terminated utf8
+/// string into `buffer`. At most `size` bytes including the null terminator are written. Returns
+/// the length of the full report excluding the null terminator or 0 if the device has never been
+/// lost. `buffer` may be null to only query the length.
 #[no_mangle]
-unsafe extern "C" fn b4d_pass_update_uniform(pass: *mut PassRecorder, data: *const CMcUniformData, shader_id: u64) {
+unsafe extern "C-unwind" fn b4d_get_last_fault_report(b4d: *const Blaze4D, buffer: *mut u8, size: usize) -> usize {
     catch_unwind(|| {
-        let pass = pass.as_mut().unwrap_or_else(|| {
-            log::error!("Passed null pass to b4d_pass_update_dev_uniform");
-            exit(1);
-        });
-        let data = data.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null data to b4d_pass_update_dev_uniform");
-            exit(1);
-        });
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_last_fault_report");
 
-        let data = data.to_mc_uniform_data();
-        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+        let report = match b4d.get_last_fault_report() {
+            Some(report) => report.to_string(),
+            None => return 0,
+        };
 
-        pass.update_uniform(&data, shader_id);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_pass_update_dev_uniform");
-        exit(1);
-    })
+        if !buffer.is_null() && size > 0 {
+            let len = std::cmp::min(report.len(), size - 1);
+            std::ptr::copy_nonoverlapping(report.as_ptr(), buffer, len);
+            buffer.add(len).write(0);
+        }
+        report.len()
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_last_fault_report"))
 }
 
+/// Registers a callback which is called whenever the device memory pressure level changes. The
+/// callback receives the new level (see [`MemoryPressure`]), the current usage and budget in bytes
+/// and the provided user data.
+///
+/// If the callback is null any previously registered callback is removed.
 #[no_mangle]
-unsafe extern "C" fn b4d_pass_update_texture(pass: *mut PassRecorder, index: u32, image: *const Arc<GlobalImage>, sampler_info: *const CSamplerInfo, shader_id: u64) {
+unsafe extern "C-unwind" fn b4d_set_memory_pressure_callback(b4d: *const Blaze4D, callback: Option<unsafe extern "C" fn(u32, u64, u64, *mut c_void)>, user_data: *mut c_void) {
     catch_unwind(|| {
-        let pass = pass.as_mut().unwrap_or_else(|| {
-            log::error!("Passed null pass to b4d_pass_update_texture");
-            exit(1);
-        });
-        let image = image.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null image to b4d_pass_update_texture");
-            exit(1);
-        });
-        let sampler_info = sampler_info.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null sampler_info to b4d_pass_update_texture");
-            exit(1);
-        });
-
-        let sampler_info = sampler_info.to_sampler_info();
-        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_memory_pressure_callback");
 
-        pass.update_texture(index, image, &sampler_info, shader_id);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_pass_update_texture");
-        exit(1);
-    })
+        let user_data = user_data as usize;
+        b4d.set_memory_pressure_callback(callback.map(|callback| -> Box<dyn Fn(MemoryPressure, MemoryBudget) + Send> {
+            Box::new(move |pressure, budget| callback(pressure as u32, budget.usage, budget.budget, user_data as *mut c_void))
+        }));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_memory_pressure_callback"))
 }
 
+/// Registers a callback which is called for each evictable mesh selected for eviction. The callback
+/// receives the id of the mesh (see [`b4d_global_mesh_get_id`]) and the provided user data.
+///
+/// If the callback is null any previously registered callback is removed and no meshes are evicted.
 #[no_mangle]
-unsafe extern "C" fn b4d_pass_draw_global(pass: *mut PassRecorder, mesh: *const Arc<GlobalMesh>, shader_id: u64, depth_write_enable: u32) {
+unsafe extern "C-unwind" fn b4d_set_mesh_eviction_callback(b4d: *const Blaze4D, callback: Option<unsafe extern "C" fn(u64, *mut c_void)>, user_data: *mut c_void) {
     catch_unwind(|| {
-        let pass = pass.as_mut().unwrap_or_else(|| {
-            log::error!("Passed null pass to b4d_pass_draw_global");
-            exit(1);
-        });
-        let mesh = mesh.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null mesh to b4d_pass_draw_global");
-            exit(1);
-        });
-        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
-
-        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_mesh_eviction_callback");
 
-        pass.draw_global(mesh.clone(), shader_id, depth_write_enable);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_pass_draw_global");
-        exit(1);
-    })
+        let user_data = user_data as usize;
+        b4d.set_mesh_eviction_callback(callback.map(|callback| -> Box<dyn Fn(GlobalMeshId) + Send> {
+            Box::new(move |id| callback(id.as_uuid().get_raw(), user_data as *mut c_void))
+        }));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_mesh_eviction_callback"))
 }
 
+/// Sets the number of threads used for cpu side jobs. If 0 the count is derived from the number
+/// of available cores. Replaces any executor set using [`b4d_set_job_executor`].
 #[no_mangle]
-unsafe extern "C" fn b4d_pass_upload_immediate(pass: *mut PassRecorder, data: *const CMeshData) -> u32 {
+unsafe extern "C-unwind" fn b4d_set_job_thread_count(b4d: *const Blaze4D, thread_count: u32) {
     catch_unwind(|| {
-        let pass = pass.as_mut().unwrap_or_else(|| {
-            log::error!("Passed null pass to b4d_pass_upload_immediate");
-            exit(1);
-        });
-        let data = data.as_ref().unwrap_or_else(|| {
-            log::error!("Passed null mesh data to b4d_pass_upload_immediate");
-            exit(1);
-        });
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_job_thread_count");
+        b4d.set_job_thread_count(thread_count);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_job_thread_count"))
+}
 
-        let mesh_data = data.to_mesh_data();
+/// Calls a host provided callback with a job and user data.
+struct CallbackJobExecutor {
+    callback: unsafe extern "C" fn(*mut c_void, *mut c_void),
+    // Raw pointers are not Send so we have to store the user data as an integer
+    user_data: usize,
+    parallelism: usize,
+}
 
-        pass.upload_immediate(&mesh_data).get_raw()
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_pass_upload_immediate");
-        exit(1);
-    })
+impl JobExecutor for CallbackJobExecutor {
+    fn execute(&self, job: Job) {
+        let job = JOB_HANDLES.insert(Box::new(job));
+        unsafe { (self.callback)(job as *mut c_void, self.user_data as *mut c_void) };
+    }
+
+    fn get_parallelism(&self) -> usize {
+        self.parallelism
+    }
 }
 
+/// Runs cpu side jobs using a host provided executor. The callback receives a job and the provided
+/// user data and may be called from any thread. Each job must be passed to [`b4d_run_job`] exactly
+/// once from any thread. `parallelism` is the number of jobs the host can run concurrently.
+///
+/// If the callback is null the internal job threads are restored.
 #[no_mangle]
-unsafe extern "C" fn b4d_pass_draw_immediate(pass: *mut PassRecorder, id: u32, shader_id: u64, depth_write_enable: u32) {
+unsafe extern "C-unwind" fn b4d_set_job_executor(b4d: *const Blaze4D, callback: Option<unsafe extern "C" fn(*mut c_void, *mut c_void)>, parallelism: u32, user_data: *mut c_void) {
     catch_unwind(|| {
-        let pass = pass.as_mut().unwrap_or_else(|| {
-            log::error!("Passed null pass to b4d_pass_draw_immediate");
-            exit(1);
-        });
-        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
-
-        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_job_executor");
+
+        b4d.set_job_executor(callback.map(|callback| -> Arc<dyn JobExecutor> {
+            Arc::new(CallbackJobExecutor {
+                callback,
+                user_data: user_data as usize,
+                parallelism: parallelism as usize,
+            })
+        }));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_job_executor"))
+}
+
+/// Runs and destroys a job passed to the callback registered with [`b4d_set_job_executor`]. Jobs
+/// which have already been run are rejected.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_run_job(job: *mut c_void) {
+    catch_unwind(|| {
+        let job = check(JOB_HANDLES.remove(job as *mut Job), "b4d_run_job");
+        job();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_run_job"))
+}
+
+#[repr(C)]
+struct CJobStats {
+    batch_count: u64,
+    task_count: u64,
+    total_task_nanos: u64,
+    max_task_nanos: u64,
+    total_batch_nanos: u64,
+}
+
+impl From<&JobKindStats> for CJobStats {
+    fn from(stats: &JobKindStats) -> Self {
+        Self {
+            batch_count: stats.batch_count,
+            task_count: stats.task_count,
+            total_task_nanos: stats.total_task_time.as_nanos() as u64,
+            max_task_nanos: stats.max_task_time.as_nanos() as u64,
+            total_batch_nanos: stats.total_batch_time.as_nanos() as u64,
+        }
+    }
+}
+
+/// Writes the accumulated job statistics of a [`JobKind`] to `stats`. If `reset` is not 0 the
+/// statistics of all kinds are reset afterwards.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_job_stats(b4d: *const Blaze4D, kind: u32, reset: u32, stats: *mut CJobStats) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_job_stats");
+        let kind = check(JobKind::from_raw(kind).ok_or(CApiError::InvalidEnum("kind", kind as i64)), "b4d_get_job_stats");
+        if stats.is_null() {
+            log::error!("Passed null stats to b4d_get_job_stats");
+            reject(CApiError::NullPointer("stats"));
+        }
+
+        stats.write(b4d.get_job_stats().get(kind).into());
+        if reset != 0 {
+            b4d.reset_job_stats();
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_job_stats"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_memory_usage(b4d: *const Blaze4D, usage: *mut u64, budget: *mut u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_memory_usage");
+        if usage.is_null() || budget.is_null() {
+            log::error!("Passed null pointer to b4d_get_memory_usage");
+            reject(CApiError::InvalidArgument("b4d_get_memory_usage"));
+        }
+
+        let memory = b4d.get_memory_usage();
+        usage.write(memory.usage);
+        budget.write(memory.budget);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_memory_usage"))
+}
+
+/// Limits the number of bytes uploaded to global objects per frame. A budget of 0 disables the
+/// limit.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_upload_budget(b4d: *const Blaze4D, budget: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_upload_budget");
+
+        b4d.set_upload_budget(if budget == 0 { None } else { Some(budget) });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_upload_budget"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_vsync(b4d: *const Blaze4D, vsync: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_vsync");
+
+        b4d.set_vsync(vsync != 0);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_vsync"))
+}
+
+/// Sets the submit mode of all following frames. 0 streams commands to the render worker while
+/// they are recorded, 1 hands them over in one batch when the frame ends.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_submit_mode(b4d: *const Blaze4D, mode: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_submit_mode");
+        let mode = check(SubmitMode::from_raw(mode).ok_or(CApiError::InvalidEnum("mode", mode as i64)), "b4d_set_submit_mode");
+
+        b4d.set_submit_mode(mode);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_submit_mode"))
+}
+
+/// Sets the camera effects of all following frames. Effects with an amplitude or intensity of 0
+/// are disabled. The direction of the shake is given in degrees. See
+/// [`CameraEffects`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_camera_effects(b4d: *const Blaze4D, bob_phase: f32, bob_amplitude: f32, shake_intensity: f32, shake_direction: f32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_camera_effects");
+        let values = [bob_phase, bob_amplitude, shake_intensity, shake_direction];
+        if !values.iter().all(|value| value.is_finite()) {
+            check(Err(CApiError::InvalidSize("camera_effects")), "b4d_set_camera_effects")
+        }
+
+        b4d.set_camera_effects(CameraEffects {
+            bob: (bob_amplitude != 0f32).then(|| ViewBob { phase: bob_phase, amplitude: bob_amplitude }),
+            shake: (shake_intensity != 0f32).then(|| ScreenShake { intensity: shake_intensity, direction: shake_direction.to_radians() }),
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_camera_effects"))
+}
+
+/// Sets the latency mode. 0 is the default mode, 1 is low latency and 2 is variable refresh pacing.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_latency_mode(b4d: *const Blaze4D, mode: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_latency_mode");
+        let mode = check(LatencyMode::from_raw(mode).ok_or(CApiError::InvalidEnum("mode", mode as i64)), "b4d_set_latency_mode");
+
+        b4d.set_latency_mode(mode);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_latency_mode"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_frames_in_flight(b4d: *const Blaze4D, frames_in_flight: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_frames_in_flight");
+
+        b4d.set_frames_in_flight(frames_in_flight);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_frames_in_flight"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_color_grading_preset(b4d: *const Blaze4D, preset: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_color_grading_preset");
+        let preset = check(ColorGradingPreset::from_raw(preset).ok_or(CApiError::InvalidEnum("preset", preset as i64)), "b4d_set_color_grading_preset");
+
+        let mut grading = b4d.get_color_grading();
+        grading.preset = preset;
+        b4d.set_color_grading(grading);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_color_grading_preset"))
+}
+
+/// Sets the per channel lift, gamma and gain curves. Each pointer must point to 3 floats.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_color_grading_curves(b4d: *const Blaze4D, lift: *const f32, gamma: *const f32, gain: *const f32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_color_grading_curves");
+        let lift = Vec3f32::from_column_slice(check(make_slice("lift", lift, 3), "b4d_set_color_grading_curves"));
+        let gamma = Vec3f32::from_column_slice(check(make_slice("gamma", gamma, 3), "b4d_set_color_grading_curves"));
+        let gain = Vec3f32::from_column_slice(check(make_slice("gain", gain, 3), "b4d_set_color_grading_curves"));
+
+        if !lift.iter().chain(gain.iter()).all(|v| v.is_finite()) {
+            check(Err(CApiError::InvalidSize("lift/gain")), "b4d_set_color_grading_curves")
+        }
+        if !gamma.iter().all(|v| v.is_finite() && *v > 0f32) {
+            check(Err(CApiError::InvalidSize("gamma")), "b4d_set_color_grading_curves")
+        }
+
+        let mut grading = b4d.get_color_grading();
+        grading.lift = lift;
+        grading.gamma = gamma;
+        grading.gain = gain;
+        b4d.set_color_grading(grading);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_color_grading_curves"))
+}
+
+/// Sets the color grading LUT. Passing a null image removes the LUT.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_color_grading_lut(b4d: *const Blaze4D, image: *const Arc<GlobalImage>, size: u32, strength: f32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_color_grading_lut");
+
+        let lut = if image.is_null() {
+            None
+        } else {
+            let image = check(IMAGE_HANDLES.get(image), "b4d_set_color_grading_lut");
+            let image_size = image.get_size();
+            if size < 2 || image_size[0] != size * size || image_size[1] != size {
+                check(Err(CApiError::InvalidSize("size")), "b4d_set_color_grading_lut")
+            }
+            Some((image.clone(), size))
+        };
+        if !strength.is_finite() {
+            check(Err(CApiError::InvalidSize("strength")), "b4d_set_color_grading_lut")
+        }
+
+        let mut grading = b4d.get_color_grading();
+        grading.lut = lut;
+        grading.lut_strength = strength.clamp(0f32, 1f32);
+        b4d.set_color_grading(grading);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_color_grading_lut"))
+}
+
+/// Starts recording a command stream of the next `frame_count` frames to the file at `path`. If
+/// `include_mesh_data` is not 0 the full data of global meshes is recorded instead of only a hash.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_start_command_stream_recording(b4d: *const Blaze4D, path: *const c_char, frame_count: u32, include_mesh_data: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_start_command_stream_recording");
+        if path.is_null() {
+            log::error!("Passed null path to b4d_start_command_stream_recording");
+            reject(CApiError::NullPointer("path"));
+        }
+        if frame_count == 0 {
+            check(Err(CApiError::InvalidSize("frame_count")), "b4d_start_command_stream_recording")
+        }
+        let path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+
+        b4d.start_command_stream_recording(StreamRecorderConfig {
+            path,
+            frame_count,
+            include_mesh_data: include_mesh_data != 0,
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_start_command_stream_recording"))
+}
+
+/// Starts appending a JSONL event log to the file at `path`. Returns 0 if the file could not be
+/// opened.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_start_event_log_file(b4d: *const Blaze4D, path: *const c_char) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_start_event_log_file");
+        if path.is_null() {
+            log::error!("Passed null path to b4d_start_event_log_file");
+            reject(CApiError::NullPointer("path"));
+        }
+        let path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+
+        match b4d.start_event_log(EventLogTarget::File(path.clone())) {
+            Ok(_) => 1,
+            Err(err) => {
+                log::error!("Failed to open event log {:?}: {:?}", path, err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_start_event_log_file"))
+}
+
+/// Starts delivering the JSONL event log to a callback. The callback is called with a pointer to
+/// the utf8 line without the trailing newline and its length in bytes. The line is only valid for
+/// the duration of the call. The callback may be called from any thread.
+///
+/// If the callback is null the event log is stopped.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_event_log_callback(b4d: *const Blaze4D, callback: Option<unsafe extern "C" fn(*const u8, u32, *mut c_void)>, user_data: *mut c_void) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_event_log_callback");
+
+        // Raw pointers are not Send so we have to pass the user data as an integer
+        let user_data = user_data as usize;
+        match callback {
+            Some(callback) => {
+                let target = EventLogTarget::Callback(Box::new(move |line: &str| callback(line.as_ptr(), line.len() as u32, user_data as *mut c_void)));
+                // Callback targets can not fail
+                b4d.start_event_log(target).ok();
+            }
+            None => b4d.stop_event_log(),
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_event_log_callback"))
+}
+
+/// Registers a vanilla style color-matrix post-process chain from its json description. Returns 0
+/// if the chain is invalid, uses programs other than blit, color_convolve and invert or samples
+/// auxiliary targets.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_register_post_chain(b4d: *const Blaze4D, json: *const c_char) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_register_post_chain");
+        if json.is_null() {
+            log::error!("Passed null json to b4d_register_post_chain");
+            reject(CApiError::NullPointer("json"));
+        }
+        let json = CStr::from_ptr(json).to_string_lossy();
+
+        match PostChain::parse(&json).and_then(|chain| b4d.register_post_chain(&chain)) {
+            Ok(id) => id.as_uuid().get_raw(),
+            Err(err) => {
+                log::error!("Failed to register post chain: {:?}", err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_register_post_chain"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_unregister_post_chain(b4d: *const Blaze4D, chain_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_unregister_post_chain");
+
+        b4d.unregister_post_chain(PostChainId::from_uuid(UUID::from_raw(chain_id)));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_unregister_post_chain"))
+}
+
+/// Activates a registered color-matrix chain. Passing 0 deactivates the current chain.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_active_post_chain(b4d: *const Blaze4D, chain_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_active_post_chain");
+
+        let id = if chain_id == 0 { None } else { Some(PostChainId::from_uuid(UUID::from_raw(chain_id))) };
+        b4d.set_active_post_chain(id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_active_post_chain"))
+}
+
+/// Sets the accessibility filter. See [`AccessibilityFilter::from_raw`] for the values.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_accessibility_filter(b4d: *const Blaze4D, filter: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_accessibility_filter");
+        let filter = check(AccessibilityFilter::from_raw(filter).ok_or(CApiError::InvalidEnum("filter", filter as i64)), "b4d_set_accessibility_filter");
+
+        b4d.set_accessibility_filter(filter);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_accessibility_filter"))
+}
+
+/// Sets the nausea and portal screen distortion. An intensity of 0 disables the distortion. The
+/// speed is given in degrees per tick. See [`ScreenDistortion`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_screen_distortion(b4d: *const Blaze4D, intensity: f32, time: f32, speed: f32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_screen_distortion");
+        if !(intensity.is_finite() && time.is_finite() && speed.is_finite()) {
+            check(Err(CApiError::InvalidSize("screen_distortion")), "b4d_set_screen_distortion")
+        }
+
+        b4d.set_screen_distortion(ScreenDistortion { intensity, time, speed });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_screen_distortion"))
+}
+
+/// Sets the shader used to draw the built-in debug overlays. Passing 0 disables all overlays.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_debug_overlay_shader(b4d: *const Blaze4D, shader_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_debug_overlay_shader");
+
+        let shader = if shader_id == 0 { None } else { Some(ShaderId::from_uuid(UUID::from_raw(shader_id))) };
+        b4d.set_debug_overlay_shader(shader);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_debug_overlay_shader"))
+}
+
+/// Sets the block and the offset inside of it all debug overlay positions are relative to.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_debug_overlay_origin(b4d: *const Blaze4D, x: i32, y: i32, z: i32, offset_x: f32, offset_y: f32, offset_z: f32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_debug_overlay_origin");
+
+        b4d.set_debug_overlay_origin(Vec3i32::new(x, y, z), Vec3f32::new(offset_x, offset_y, offset_z));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_debug_overlay_origin"))
+}
+
+/// Sets the chunk grid overlay. If `enable` is 0 the grid is removed. Colors are packed as
+/// `0xAARRGGBB`.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_debug_chunk_grid(b4d: *const Blaze4D, enable: u32, chunk_x: i32, chunk_z: i32, radius: u32, min_y: i32, max_y: i32, color: u32, section_color: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_debug_chunk_grid");
+
+        let grid = if enable != 0 {
+            Some(ChunkGridOverlay {
+                center_chunk: Vec2i32::new(chunk_x, chunk_z),
+                radius,
+                min_y,
+                max_y,
+                color: unpack_argb(color),
+                section_color: unpack_argb(section_color),
+            })
+        } else {
+            None
+        };
+        b4d.set_debug_chunk_grid(grid);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_debug_chunk_grid"))
+}
+
+/// Sets the section heatmap overlay. `sections` must point to `count` xyz triples of section
+/// positions and `values` to `count` values. Passing a count of 0 removes the heatmap.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_debug_heatmap(b4d: *const Blaze4D, sections: *const i32, values: *const f32, count: u32, min_value: f32, max_value: f32, alpha: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_debug_heatmap");
+        let sections = check(make_slice("sections", sections, count as usize * 3), "b4d_set_debug_heatmap");
+        let values = check(make_slice("values", values, count as usize), "b4d_set_debug_heatmap");
+
+        let heatmap = if count != 0 {
+            Some(SectionHeatmap {
+                values: sections.chunks_exact(3).zip(values).map(|(section, value)| SectionValue {
+                    section: Vec3i32::from_column_slice(section),
+                    value: *value,
+                }).collect(),
+                min_value,
+                max_value,
+                alpha: alpha.min(u8::MAX as u32) as u8,
+            })
+        } else {
+            None
+        };
+        b4d.set_debug_heatmap(heatmap);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_debug_heatmap"))
+}
+
+/// Configures auto exposure. If `enabled` is 0 the remaining parameters are still stored but auto
+/// exposure is disabled. The luminance range is given in log2 units.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_auto_exposure(b4d: *const Blaze4D, enabled: u32, min_log_luminance: f32, max_log_luminance: f32, target_luminance: f32, adaptation_speed: f32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_auto_exposure");
+
+        if !min_log_luminance.is_finite() || !max_log_luminance.is_finite() || min_log_luminance >= max_log_luminance {
+            check(Err(CApiError::InvalidSize("log_luminance")), "b4d_set_auto_exposure")
+        }
+        if !target_luminance.is_finite() || target_luminance <= 0f32 {
+            check(Err(CApiError::InvalidSize("target_luminance")), "b4d_set_auto_exposure")
+        }
+        if !adaptation_speed.is_finite() || adaptation_speed < 0f32 {
+            check(Err(CApiError::InvalidSize("adaptation_speed")), "b4d_set_auto_exposure")
+        }
+
+        let mut grading = b4d.get_color_grading();
+        grading.auto_exposure = AutoExposure {
+            enabled: enabled != 0,
+            min_log_luminance,
+            max_log_luminance,
+            target_luminance,
+            adaptation_speed,
+        };
+        b4d.set_color_grading(grading);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_auto_exposure"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_mesh(b4d: *const Blaze4D, data: *const CMeshData) -> *mut Arc<GlobalMesh> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_mesh");
+        let data = check(data.as_ref().ok_or(CApiError::NullPointer("data")), "b4d_create_global_mesh");
+
+        let decompressed = decompress_c_meshes(b4d.get_job_system(), std::slice::from_ref(data), "b4d_create_global_mesh");
+        let mesh_data = check(data.to_mesh_data_decompressed(&decompressed[0]), "b4d_create_global_mesh");
+
+        MESH_HANDLES.insert(Box::new(b4d.create_global_mesh(&mesh_data)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_mesh"))
+}
+
+/// Creates `count` global meshes and writes their handles to `out_meshes`. This is more efficient
+/// than calling [`b4d_create_global_mesh`] for each mesh.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_meshes(b4d: *const Blaze4D, datas: *const CMeshData, count: u32, out_meshes: *mut *mut Arc<GlobalMesh>) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_meshes");
+        let datas = check(make_slice("datas", datas, count as usize), "b4d_create_global_meshes");
+        if count != 0 && out_meshes.is_null() {
+            log::error!("Passed null out_meshes to b4d_create_global_meshes");
+            reject(CApiError::NullPointer("out_meshes"));
+        }
+
+        let decompressed = decompress_c_meshes(b4d.get_job_system(), datas, "b4d_create_global_meshes");
+        let mesh_datas: Vec<_> = datas.iter().zip(decompressed.iter())
+            .map(|(data, decompressed)| check(data.to_mesh_data_decompressed(decompressed), "b4d_create_global_meshes"))
+            .collect();
+        let meshes = b4d.create_global_meshes(&mesh_datas);
+
+        let handles = MESH_HANDLES.insert_many(meshes.into_iter().map(Box::new).collect());
+        std::slice::from_raw_parts_mut(out_meshes, handles.len()).copy_from_slice(&handles);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_meshes"))
+}
+
+/// Destroys `count` global mesh handles. Equivalent to calling [`b4d_destroy_global_mesh`] for
+/// each handle.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_destroy_global_meshes(meshes: *const *mut Arc<GlobalMesh>, count: u32) {
+    catch_unwind(|| {
+        let handles = check(make_slice("meshes", meshes, count as usize), "b4d_destroy_global_meshes");
+
+        drop(check(MESH_HANDLES.remove_many(handles), "b4d_destroy_global_meshes"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy_global_meshes"))
+}
+
+/// Creates a global mesh or returns a new handle to an existing mesh if identical data has
+/// previously been passed to this function. Each returned handle must be destroyed separately.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_mesh_deduplicated(b4d: *const Blaze4D, data: *const CMeshData) -> *mut Arc<GlobalMesh> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_mesh_deduplicated");
+        let data = check(data.as_ref().ok_or(CApiError::NullPointer("data")), "b4d_create_global_mesh_deduplicated");
+
+        let decompressed = decompress_c_meshes(b4d.get_job_system(), std::slice::from_ref(data), "b4d_create_global_mesh_deduplicated");
+        let mesh_data = check(data.to_mesh_data_decompressed(&decompressed[0]), "b4d_create_global_mesh_deduplicated");
+
+        MESH_HANDLES.insert(Box::new(b4d.create_global_mesh_deduplicated(&mesh_data)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_mesh_deduplicated"))
+}
+
+/// Creates a global mesh after optimizing the mesh data. If `position_format` is
+/// `VK_FORMAT_UNDEFINED` no position information is used.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_mesh_optimized(b4d: *const Blaze4D, data: *const CMeshData, position_offset: u32, position_format: i32) -> *mut Arc<GlobalMesh> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_mesh_optimized");
+        let data = check(data.as_ref().ok_or(CApiError::NullPointer("data")), "b4d_create_global_mesh_optimized");
+
+        let decompressed = decompress_c_meshes(b4d.get_job_system(), std::slice::from_ref(data), "b4d_create_global_mesh_optimized");
+        let mesh_data = check(data.to_mesh_data_decompressed(&decompressed[0]), "b4d_create_global_mesh_optimized");
+        let position = if position_format != vk::Format::UNDEFINED.as_raw() {
+            Some(VertexFormatEntry {
+                offset: position_offset,
+                format: check(validate_vertex_entry("position_format", mesh_data.vertex_stride, position_offset, position_format), "b4d_create_global_mesh_optimized"),
+            })
+        } else {
+            None
+        };
+
+        MESH_HANDLES.insert(Box::new(b4d.create_global_mesh_optimized(&mesh_data, position.as_ref())))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_mesh_optimized"))
+}
+
+/// Optimizes `count` meshes in parallel before creating them. All meshes must use the same position
+/// entry. If `position_format` is `VK_FORMAT_UNDEFINED` no position information is used.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_meshes_optimized(b4d: *const Blaze4D, datas: *const CMeshData, count: u32, position_offset: u32, position_format: i32, out_meshes: *mut *mut Arc<GlobalMesh>) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_meshes_optimized");
+        let datas = check(make_slice("datas", datas, count as usize), "b4d_create_global_meshes_optimized");
+        if count != 0 && out_meshes.is_null() {
+            log::error!("Passed null out_meshes to b4d_create_global_meshes_optimized");
+            reject(CApiError::NullPointer("out_meshes"));
+        }
+
+        let decompressed = decompress_c_meshes(b4d.get_job_system(), datas, "b4d_create_global_meshes_optimized");
+        let mesh_datas: Vec<_> = datas.iter().zip(decompressed.iter())
+            .map(|(data, decompressed)| check(data.to_mesh_data_decompressed(decompressed), "b4d_create_global_meshes_optimized"))
+            .collect();
+        let positions: Vec<_> = mesh_datas.iter().map(|mesh_data| {
+            if position_format != vk::Format::UNDEFINED.as_raw() {
+                Some(VertexFormatEntry {
+                    offset: position_offset,
+                    format: check(validate_vertex_entry("position_format", mesh_data.vertex_stride, position_offset, position_format), "b4d_create_global_meshes_optimized"),
+                })
+            } else {
+                None
+            }
+        }).collect();
+        let meshes = b4d.create_global_meshes_optimized(&mesh_datas, &positions);
+
+        let handles = MESH_HANDLES.insert_many(meshes.into_iter().map(Box::new).collect());
+        std::slice::from_raw_parts_mut(out_meshes, handles.len()).copy_from_slice(&handles);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_meshes_optimized"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_destroy_global_mesh(mesh: *mut Arc<GlobalMesh>) {
+    catch_unwind(|| {
+        drop(check(MESH_HANDLES.remove(mesh), "b4d_destroy_global_mesh"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy_global_mesh"))
+}
+
+/// Creates a new handle to the same mesh. The mesh is kept alive until all handles have been
+/// released. Returns the new handle.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_mesh_retain(mesh: *const Arc<GlobalMesh>) -> *mut Arc<GlobalMesh> {
+    catch_unwind(|| {
+        let mesh = check(MESH_HANDLES.get(mesh), "b4d_global_mesh_retain");
+
+        MESH_HANDLES.insert(Box::new(mesh.clone()))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_mesh_retain"))
+}
+
+/// Releases a handle to a mesh. The device memory of the mesh is freed once all handles have been
+/// released and all passes using the mesh have completed. Equivalent to
+/// [`b4d_destroy_global_mesh`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_mesh_release(mesh: *mut Arc<GlobalMesh>) {
+    catch_unwind(|| {
+        drop(check(MESH_HANDLES.remove(mesh), "b4d_global_mesh_release"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_mesh_release"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_mesh_get_id(mesh: *const Arc<GlobalMesh>) -> u64 {
+    catch_unwind(|| {
+        let mesh = check(MESH_HANDLES.get(mesh), "b4d_global_mesh_get_id");
+
+        mesh.get_id().as_uuid().get_raw()
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_mesh_get_id"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_mesh_evictable(b4d: *const Blaze4D, mesh: *const Arc<GlobalMesh>, evictable: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_mesh_evictable");
+        let mesh = check(MESH_HANDLES.get(mesh), "b4d_set_mesh_evictable");
+
+        b4d.set_mesh_evictable(&mesh, evictable != 0);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_mesh_evictable"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_mesh_set_upload_priority(mesh: *const Arc<GlobalMesh>, priority: u32) {
+    catch_unwind(|| {
+        let mesh = check(MESH_HANDLES.get(mesh), "b4d_global_mesh_set_upload_priority");
+
+        mesh.set_upload_priority(priority);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_mesh_set_upload_priority"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_image(b4d: *const Blaze4D, width: u32, height: u32, format: i32) -> *mut Arc<GlobalImage> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_image");
+
+        if width == 0 || height == 0 {
+            log::error!("Passed empty size to b4d_create_global_image");
+            reject(CApiError::InvalidArgument("b4d_create_global_image"));
+        }
+        let size = Vec2u32::new(width, height);
+        let format = check(Format::try_format_for(vk::Format::from_raw(format)).ok_or(CApiError::InvalidEnum("format", format as i64)), "b4d_create_global_image");
+
+        IMAGE_HANDLES.insert(Box::new(b4d.create_global_image(size, format)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_image"))
+}
+
+/// Validates the arguments of the array image creation functions.
+fn make_image_array_info(name: &'static str, width: u32, height: u32, mip_levels: u32, layer_count: u32, atlas_columns: u32, atlas_rows: u32, format: i32) -> (Vec2u32, ImageArrayMode, &'static Format) {
+    if width == 0 || height == 0 {
+        log::error!("Passed empty size to {}", name);
+        reject(CApiError::InvalidArgument(name));
+    }
+    let size = Vec2u32::new(width, height);
+    let max_mip_levels = 32 - std::cmp::max(width, height).leading_zeros();
+    if mip_levels == 0 || mip_levels > max_mip_levels {
+        check(Err(CApiError::InvalidSize("mip_levels")), name)
+    }
+
+    let array_mode = match (atlas_columns, atlas_rows) {
+        (0, 0) if layer_count == 0 => check(Err(CApiError::InvalidSize("layer_count")), name),
+        (0, 0) => ImageArrayMode::Layers(layer_count),
+        (columns, rows) if columns == 0 || rows == 0 || columns > u16::MAX as u32 || rows > u16::MAX as u32 => {
+            check(Err(CApiError::InvalidSize("atlas_grid")), name)
+        }
+        (columns, rows) => ImageArrayMode::Atlas(Vec2u32::new(columns, rows)),
+    };
+
+    let format = check(Format::try_format_for(vk::Format::from_raw(format)).ok_or(CApiError::InvalidEnum("format", format as i64)), name);
+    if matches!(array_mode, ImageArrayMode::Atlas(_)) && format.get_compatibility_class().get_texel_size().is_none() {
+        check(Err(CApiError::InvalidEnum("format", format.get_format().as_raw() as i64)), name)
+    }
+
+    (size, array_mode, format)
+}
+
+/// Creates a 2d array image. If `atlas_columns` and `atlas_rows` are not 0 the image uses
+/// [`ImageArrayMode::Atlas`] and `layer_count` is ignored.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_image_array(b4d: *const Blaze4D, width: u32, height: u32, mip_levels: u32, layer_count: u32, atlas_columns: u32, atlas_rows: u32, format: i32) -> *mut Arc<GlobalImage> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_image_array");
+
+        let (size, array_mode, format) = make_image_array_info("b4d_create_global_image_array", width, height, mip_levels, layer_count, atlas_columns, atlas_rows, format);
+
+        IMAGE_HANDLES.insert(Box::new(b4d.create_global_image_array(size, mip_levels, array_mode, format)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_image_array"))
+}
+
+/// Creates a partially resident 2d array image. The arguments are the same as for
+/// [`b4d_create_global_image_array`]. Returns null if the device does not support sparse images of
+/// the format.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_global_image_sparse(b4d: *const Blaze4D, width: u32, height: u32, mip_levels: u32, layer_count: u32, atlas_columns: u32, atlas_rows: u32, format: i32) -> *mut Arc<GlobalImage> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_global_image_sparse");
+        let (size, array_mode, format) = make_image_array_info("b4d_create_global_image_sparse", width, height, mip_levels, layer_count, atlas_columns, atlas_rows, format);
+
+        match b4d.create_global_image_sparse(size, mip_levels, array_mode, format) {
+            Some(image) => IMAGE_HANDLES.insert(Box::new(image)),
+            None => std::ptr::null_mut(),
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_global_image_sparse"))
+}
+
+/// Creates an image from `layer_count` layers composited on the gpu. The first layer is the base
+/// layer. Returns null if the device cannot render to images of the format. See
+/// [`Blaze4D::create_composite_image`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_composite_image(b4d: *const Blaze4D, width: u32, height: u32, format: i32, layers: *const CCompositeLayer, layer_count: u32) -> *mut Arc<GlobalImage> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_composite_image");
+
+        if width == 0 || height == 0 {
+            log::error!("Passed empty size to b4d_create_composite_image");
+            reject(CApiError::InvalidArgument("b4d_create_composite_image"));
+        }
+        if layer_count == 0 {
+            check(Err(CApiError::InvalidSize("layer_count")), "b4d_create_composite_image")
+        }
+        let size = Vec2u32::new(width, height);
+        let format = check(Format::try_format_for(vk::Format::from_raw(format)).ok_or(CApiError::InvalidEnum("format", format as i64)), "b4d_create_composite_image");
+        let layers: Vec<_> = check(make_slice("layers", layers, layer_count as usize), "b4d_create_composite_image")
+            .iter().map(|layer| layer.to_composite_layer("b4d_create_composite_image")).collect();
+
+        match b4d.create_composite_image(size, format, &layers) {
+            Some(image) => IMAGE_HANDLES.insert(Box::new(image)),
+            None => std::ptr::null_mut(),
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_composite_image"))
+}
+
+/// Creates the unit column mesh used to draw beams with a shader. Returns null if the vertex
+/// format of the shader is not supported. See [`Blaze4D::create_beam_mesh`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_beam_mesh(b4d: *const Blaze4D, shader_id: u64) -> *mut Arc<GlobalMesh> {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_beam_mesh");
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        match b4d.create_beam_mesh(shader_id) {
+            Some(mesh) => MESH_HANDLES.insert(Box::new(mesh)),
+            None => std::ptr::null_mut(),
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_beam_mesh"))
+}
+
+/// Creates a cache for map item textures. See [`Blaze4D::create_map_texture_cache`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_map_texture_cache(b4d: *const Blaze4D) -> *mut MapTextureCache {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_map_texture_cache");
+        MAP_CACHE_HANDLES.insert(Box::new(b4d.create_map_texture_cache()))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_map_texture_cache"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_destroy_map_texture_cache(cache: *mut MapTextureCache) {
+    catch_unwind(|| {
+        drop(check(MAP_CACHE_HANDLES.remove(cache), "b4d_destroy_map_texture_cache"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy_map_texture_cache"))
+}
+
+/// Replaces the content of a map. `data` must contain 128x128 tightly packed rgba texels. See
+/// [`MapTextureCache::update_map`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_map_cache_update(cache: *const MapTextureCache, map_id: i32, data: *const u8, len: usize) {
+    catch_unwind(|| {
+        let cache = check(MAP_CACHE_HANDLES.get(cache), "b4d_map_cache_update");
+        let data = check(make_slice("data", data, len), "b4d_map_cache_update");
+        if data.len() != MAP_DATA_SIZE {
+            log::error!("Passed {:?} bytes of map data to b4d_map_cache_update but expected {:?}", data.len(), MAP_DATA_SIZE);
+            reject(CApiError::InvalidArgument("b4d_map_cache_update"));
+        }
+
+        cache.update_map(map_id, data);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_map_cache_update"))
+}
+
+/// Uploads all pending map updates. Returns the number of uploaded maps.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_map_cache_flush(cache: *const MapTextureCache) -> u32 {
+    catch_unwind(|| {
+        let cache = check(MAP_CACHE_HANDLES.get(cache), "b4d_map_cache_flush");
+        cache.flush() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_map_cache_flush"))
+}
+
+/// Writes the page index and the min and max texture coordinates of a map. Returns 0 if the map
+/// does not exist.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_map_cache_get(cache: *const MapTextureCache, map_id: i32, page: *mut u32, uv_rect: *mut f32) -> u32 {
+    catch_unwind(|| {
+        let cache = check(MAP_CACHE_HANDLES.get(cache), "b4d_map_cache_get");
+        if page.is_null() || uv_rect.is_null() {
+            log::error!("Passed null output to b4d_map_cache_get");
+            reject(CApiError::InvalidArgument("b4d_map_cache_get"));
+        }
+
+        match cache.get_map(map_id) {
+            Some(slot) => {
+                let (min, max) = slot.get_uv_rect();
+                *page = slot.page;
+                std::slice::from_raw_parts_mut(uv_rect, 4).copy_from_slice(&[min[0], min[1], max[0], max[1]]);
+                1
+            }
+            None => 0,
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_map_cache_get"))
+}
+
+/// Returns a new image handle for a page of the cache or null if the page does not exist. The
+/// handle must be destroyed using [`b4d_destroy_global_image`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_map_cache_get_page(cache: *const MapTextureCache, page: u32) -> *mut Arc<GlobalImage> {
+    catch_unwind(|| {
+        let cache = check(MAP_CACHE_HANDLES.get(cache), "b4d_map_cache_get_page");
+        match cache.get_page(page) {
+            Some(image) => IMAGE_HANDLES.insert(Box::new(image)),
+            None => std::ptr::null_mut(),
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_map_cache_get_page"))
+}
+
+/// Removes a map. Returns 0 if the map did not exist.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_map_cache_remove(cache: *const MapTextureCache, map_id: i32) -> u32 {
+    catch_unwind(|| {
+        let cache = check(MAP_CACHE_HANDLES.get(cache), "b4d_map_cache_remove");
+        cache.remove_map(map_id) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_map_cache_remove"))
+}
+
+/// Removes all maps which have not been used during the last `max_idle_flushes` flushes. Returns
+/// the number of removed maps.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_map_cache_evict_idle(cache: *const MapTextureCache, max_idle_flushes: u64) -> u32 {
+    catch_unwind(|| {
+        let cache = check(MAP_CACHE_HANDLES.get(cache), "b4d_map_cache_evict_idle");
+        cache.evict_idle(max_idle_flushes) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_map_cache_evict_idle"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_update_global_image(image: *mut Arc<GlobalImage>, writes: *const CImageData, count: u32) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_update_global_image");
+        let writes = check(make_slice("writes", writes, count as usize), "b4d_update_global_image");
+        let writes: Box<_> = check(writes.iter().map(|w| w.to_image_data(&image, image.get_region_size())).collect(), "b4d_update_global_image");
+
+        image.update_regions(writes.as_ref());
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_update_global_image"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_update_global_image_layer(image: *mut Arc<GlobalImage>, layer: u32, writes: *const CImageData, count: u32) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_update_global_image_layer");
+        if layer >= image.get_array_mode().get_layer_count() {
+            check(Err(CApiError::InvalidSize("layer")), "b4d_update_global_image_layer")
+        }
+        let writes = check(make_slice("writes", writes, count as usize), "b4d_update_global_image_layer");
+        let writes: Box<_> = check(writes.iter().map(|w| w.to_image_data(&image, image.get_size())).collect(), "b4d_update_global_image_layer");
+
+        image.update_layer_regions(layer, writes.as_ref());
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_update_global_image_layer"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_generate_mipmaps(image: *const Arc<GlobalImage>) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_generate_mipmaps");
+        image.generate_mipmaps();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_generate_mipmaps"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_set_upload_priority(image: *const Arc<GlobalImage>, priority: u32) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_set_upload_priority");
+
+        image.set_upload_priority(priority);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_set_upload_priority"))
+}
+
+/// Returns 1 if the image has not been written or marked current since the last call to
+/// [`b4d_bump_resource_generation`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_is_stale(image: *const Arc<GlobalImage>) -> u32 {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_is_stale");
+
+        image.is_stale() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_is_stale"))
+}
+
+/// Marks an image as current without writing it.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_mark_current(image: *const Arc<GlobalImage>) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_mark_current");
+
+        image.mark_current();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_mark_current"))
+}
+
+/// Makes a region of a sparse image resident and marks it as used. For atlas images the region is
+/// in atlas coordinates. Has no effect on images which are not sparse.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_touch_region(image: *const Arc<GlobalImage>, x: u32, y: u32, width: u32, height: u32) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_touch_region");
+        let offset = Vec2u32::new(x, y);
+        let extent = Vec2u32::new(width, height);
+        check(validate_image_region(image.get_region_size(), offset, extent), "b4d_global_image_touch_region");
+
+        image.touch_regions(&[(offset, extent)]);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_touch_region"))
+}
+
+/// Makes a region of a single layer of a sparse image resident and marks it as used.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_touch_layer_region(image: *const Arc<GlobalImage>, layer: u32, x: u32, y: u32, width: u32, height: u32) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_touch_layer_region");
+        if layer >= image.get_array_mode().get_layer_count() {
+            check(Err(CApiError::InvalidSize("layer")), "b4d_global_image_touch_layer_region")
+        }
+        let offset = Vec2u32::new(x, y);
+        let extent = Vec2u32::new(width, height);
+        check(validate_image_region(image.get_size(), offset, extent), "b4d_global_image_touch_layer_region");
+
+        image.touch_layer_regions(layer, &[(offset, extent)]);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_touch_layer_region"))
+}
+
+/// Sets the residency budget of a sparse image in bytes. A budget of 0 disables the limit.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_set_residency_budget(image: *const Arc<GlobalImage>, budget: u64) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_set_residency_budget");
+
+        image.set_residency_budget(if budget == 0 { None } else { Some(budget) });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_set_residency_budget"))
+}
+
+#[repr(C)]
+struct CSparseResidencyStats {
+    page_size: u64,
+    total_pages: u64,
+    resident_pages: u64,
+    evicting_pages: u64,
+    resident_bytes: u64,
+    mip_tail_bytes: u64,
+    /// 0 if the image has no budget.
+    budget_bytes: u64,
+}
+
+impl From<SparseResidencyStats> for CSparseResidencyStats {
+    fn from(stats: SparseResidencyStats) -> Self {
+        Self {
+            page_size: stats.page_size,
+            total_pages: stats.total_pages as u64,
+            resident_pages: stats.resident_pages as u64,
+            evicting_pages: stats.evicting_pages as u64,
+            resident_bytes: stats.resident_bytes,
+            mip_tail_bytes: stats.mip_tail_bytes,
+            budget_bytes: stats.budget_bytes.unwrap_or(0),
+        }
+    }
+}
+
+/// Writes the residency statistics of a sparse image to `stats`. Returns 0 and leaves `stats`
+/// unmodified if the image is not sparse.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_get_residency_stats(image: *const Arc<GlobalImage>, stats: *mut CSparseResidencyStats) -> u32 {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_get_residency_stats");
+        if stats.is_null() {
+            log::error!("Passed null stats to b4d_global_image_get_residency_stats");
+            reject(CApiError::NullPointer("stats"));
+        }
+
+        match image.get_residency_stats() {
+            Some(residency) => {
+                stats.write(residency.into());
+                1
+            }
+            None => 0,
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_get_residency_stats"))
+}
+
+#[repr(C)]
+struct CImageUsageStats {
+    /// 0 if the image has never been sampled.
+    last_sampled_pass: u64,
+    /// u64::MAX if the image has never been sampled.
+    passes_since_sampled: u64,
+    /// u64::MAX if the image has never been sampled.
+    millis_since_sampled: u64,
+    sampled_pass_count: u64,
+    sampled_draw_count: u64,
+}
+
+impl From<ImageUsageStats> for CImageUsageStats {
+    fn from(stats: ImageUsageStats) -> Self {
+        Self {
+            last_sampled_pass: stats.last_sampled_pass.map_or(0, |pass| pass.get_raw()),
+            passes_since_sampled: stats.passes_since_sampled.unwrap_or(u64::MAX),
+            millis_since_sampled: stats.time_since_sampled.map_or(u64::MAX, |time| time.as_millis() as u64),
+            sampled_pass_count: stats.sampled_pass_count,
+            sampled_draw_count: stats.sampled_draw_count,
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_global_image_get_usage_stats(image: *const Arc<GlobalImage>, stats: *mut CImageUsageStats) {
+    catch_unwind(|| {
+        let image = check(IMAGE_HANDLES.get(image), "b4d_global_image_get_usage_stats");
+        if stats.is_null() {
+            log::error!("Passed null stats to b4d_global_image_get_usage_stats");
+            reject(CApiError::NullPointer("stats"));
+        }
+
+        stats.write(image.get_usage_stats().into());
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_global_image_get_usage_stats"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_destroy_global_image(image: *mut Arc<GlobalImage>) {
+    catch_unwind(|| {
+        drop(check(IMAGE_HANDLES.remove(image), "b4d_destroy_global_image"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy_global_image"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_shader(b4d: *const Blaze4D, vertex_format: *const CVertexFormat, used_uniforms: u64) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_shader");
+        let vertex_format = check(vertex_format.as_ref().ok_or(CApiError::NullPointer("vertex_format")), "b4d_create_shader");
+
+        let vertex_format = check(vertex_format.to_vertex_format(), "b4d_create_shader");
+        let mc_uniform = McUniform::from_raw(used_uniforms);
+
+        b4d.create_shader(&vertex_format, mc_uniform).as_uuid().get_raw()
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_shader"))
+}
+
+/// Creates a shader using a registered vertex format. Returns 0 if the format is not registered.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_shader_with_format(b4d: *const Blaze4D, vertex_format_id: u64, used_uniforms: u64) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_shader_with_format");
+        let mc_uniform = McUniform::from_raw(used_uniforms);
+
+        match b4d.create_shader_with_format(VertexFormatId::from_uuid(UUID::from_raw(vertex_format_id)), mc_uniform, ShaderSpecialization::default()) {
+            Some(id) => id.as_uuid().get_raw(),
+            None => {
+                log::error!("Passed unregistered vertex format {:#x} to b4d_create_shader_with_format", vertex_format_id);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_shader_with_format"))
+}
+
+/// Creates a shader variant using a registered vertex format. Returns 0 if the format is not
+/// registered. If `emissive_image` is negative an emissive shader has no emissive mask.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_shader_specialized(b4d: *const Blaze4D, vertex_format_id: u64, used_uniforms: u64, fog_mode: u32, alpha_test_threshold: f32, alpha_mode: u32, lightmap_enable: u32, polygon_mode: i32, primitive_restart: u32, emissive: u32, emissive_image: i32, item_animation: u32) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_create_shader_specialized");
+        let mc_uniform = McUniform::from_raw(used_uniforms);
+        let fog_mode = check(FogMode::from_raw(fog_mode).ok_or(CApiError::InvalidEnum("fog_mode", fog_mode as i64)), "b4d_create_shader_specialized");
+        let alpha_mode = check(AlphaMode::from_raw(alpha_mode).ok_or(CApiError::InvalidEnum("alpha_mode", alpha_mode as i64)), "b4d_create_shader_specialized");
+        let polygon_mode = vk::PolygonMode::from_raw(polygon_mode);
+        if !matches!(polygon_mode, vk::PolygonMode::FILL | vk::PolygonMode::LINE | vk::PolygonMode::POINT) {
+            check(Err(CApiError::InvalidEnum("polygon_mode", polygon_mode.as_raw() as i64)), "b4d_create_shader_specialized")
+        }
+        if !alpha_test_threshold.is_finite() {
+            check(Err(CApiError::InvalidSize("alpha_test_threshold")), "b4d_create_shader_specialized")
+        }
+        let emissive_image = match emissive_image {
+            index if index < 0 => None,
+            index if (index as u32) < MAX_TEXTURE_SLOTS => Some(index as u32),
+            _ => check(Err(CApiError::InvalidSize("emissive_image")), "b4d_create_shader_specialized"),
+        };
+
+        let specialization = ShaderSpecialization {
+            fog_mode,
+            alpha_test_threshold,
+            alpha_mode,
+            lightmap_enable: lightmap_enable != 0,
+            polygon_mode,
+            primitive_restart: primitive_restart != 0,
+            emissive: emissive != 0,
+            emissive_image,
+            item_animation: item_animation != 0,
+        };
+
+        match b4d.create_shader_with_format(VertexFormatId::from_uuid(UUID::from_raw(vertex_format_id)), mc_uniform, specialization) {
+            Some(id) => id.as_uuid().get_raw(),
+            None => {
+                log::error!("Passed unregistered vertex format {:#x} to b4d_create_shader_specialized", vertex_format_id);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_shader_specialized"))
+}
+
+/// Registers a vertex format. Returns 0 if the format is not supported by the device.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_register_vertex_format(b4d: *const Blaze4D, vertex_format: *const CVertexFormat) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_register_vertex_format");
+        let vertex_format = check(vertex_format.as_ref().ok_or(CApiError::NullPointer("vertex_format")), "b4d_register_vertex_format");
+
+        let vertex_format = check(vertex_format.to_vertex_format(), "b4d_register_vertex_format");
+        match b4d.register_vertex_format(&vertex_format) {
+            Ok(id) => id.as_uuid().get_raw(),
+            Err(err) => {
+                log::error!("Failed to register vertex format {:?}: {:?}", vertex_format, err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_register_vertex_format"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_unregister_vertex_format(b4d: *const Blaze4D, vertex_format_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_unregister_vertex_format");
+
+        b4d.unregister_vertex_format(VertexFormatId::from_uuid(UUID::from_raw(vertex_format_id)));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_unregister_vertex_format"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_destroy_shader(b4d: *const Blaze4D, shader_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_destroy_shader");
+
+        b4d.drop_shader(ShaderId::from_uuid(UUID::from_raw(shader_id)));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy_shader"))
+}
+
+/// Registers a render layer. Returns 0 if a layer with the same name already exists.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_register_render_layer(b4d: *const Blaze4D, name: *const c_char, depth_write_enable: u32, translucent: u32, alpha_cutout: f32, mipmap: u32, affects_crumbling: u32) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_register_render_layer");
+        if name.is_null() {
+            log::error!("Passed null name to b4d_register_render_layer");
+            reject(CApiError::NullPointer("name"));
+        }
+        if !alpha_cutout.is_finite() || alpha_cutout < 0f32 {
+            check(Err(CApiError::InvalidSize("alpha_cutout")), "b4d_register_render_layer")
+        }
+        let name = CStr::from_ptr(name).to_string_lossy();
+
+        let state = RenderLayerState {
+            depth_write_enable: depth_write_enable != 0,
+            translucent: translucent != 0,
+            alpha_cutout,
+            mipmap: mipmap != 0,
+            affects_crumbling: affects_crumbling != 0,
+        };
+
+        match b4d.register_render_layer(&name, &state) {
+            Ok(id) => id.as_uuid().get_raw(),
+            Err(err) => {
+                log::error!("Failed to register render layer {:?}: {:?}", name, err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_register_render_layer"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_unregister_render_layer(b4d: *const Blaze4D, layer_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_unregister_render_layer");
+
+        b4d.unregister_render_layer(RenderLayerId::from_uuid(UUID::from_raw(layer_id)));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_unregister_render_layer"))
+}
+
+/// Returns the id of the render layer registered with `name` or 0 if no such layer exists.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_find_render_layer(b4d: *const Blaze4D, name: *const c_char) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_find_render_layer");
+        if name.is_null() {
+            log::error!("Passed null name to b4d_find_render_layer");
+            reject(CApiError::NullPointer("name"));
+        }
+        let name = CStr::from_ptr(name).to_string_lossy();
+
+        b4d.find_render_layer(&name).map_or(0, |id| id.as_uuid().get_raw())
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_find_render_layer"))
+}
+
+/// Replaces the render order. Entry `i` is the layer `layer_ids[i]` or the insertion point
+/// `points[i]` if the layer id is 0. Returns 0 if the order does not contain every registered layer
+/// and insertion point exactly once.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_render_order(b4d: *const Blaze4D, layer_ids: *const u64, points: *const u32, count: u32) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_render_order");
+        let layer_ids = check(make_slice("layer_ids", layer_ids, count as usize), "b4d_set_render_order");
+        let points = check(make_slice("points", points, count as usize), "b4d_set_render_order");
+
+        let order: Vec<_> = layer_ids.iter().zip(points).map(|(layer_id, point)| {
+            if *layer_id == 0 {
+                RenderOrderEntry::Point(check(InsertionPoint::from_raw(*point).ok_or(CApiError::InvalidEnum("point", *point as i64)), "b4d_set_render_order"))
+            } else {
+                RenderOrderEntry::Layer(RenderLayerId::from_uuid(UUID::from_raw(*layer_id)))
+            }
+        }).collect();
+
+        match b4d.set_render_order(&order) {
+            Ok(_) => 1,
+            Err(err) => {
+                log::error!("Failed to set render order: {:?}", err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_render_order"))
+}
+
+/// Enables or disables a render layer. Returns 0 if the layer is not registered.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_layer_enabled(b4d: *const Blaze4D, layer_id: u64, enabled: u32) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_layer_enabled");
+
+        b4d.set_layer_enabled(RenderLayerId::from_uuid(UUID::from_raw(layer_id)), enabled != 0) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_layer_enabled"))
+}
+
+/// Disables all render layers except the specified one. Passing 0 enables all layers again.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_isolate_layer(b4d: *const Blaze4D, layer_id: u64) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_isolate_layer");
+
+        let id = if layer_id == 0 { None } else { Some(RenderLayerId::from_uuid(UUID::from_raw(layer_id))) };
+        b4d.isolate_layer(id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_isolate_layer"))
+}
+
+/// Marks all global images as stale. Stale images remain valid until they are replaced in place.
+/// Returns the new resource generation.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_bump_resource_generation(b4d: *const Blaze4D) -> u64 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_bump_resource_generation");
+
+        b4d.bump_resource_generation()
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_bump_resource_generation"))
+}
+
+/// Keeps all deduplicated meshes alive until [`b4d_release_cached_meshes`] is called. Intended to
+/// be called before a resource reload.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_retain_cached_meshes(b4d: *const Blaze4D) -> *mut MeshRetention {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_retain_cached_meshes");
+
+        RETENTION_HANDLES.insert(Box::new(b4d.retain_cached_meshes()))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_retain_cached_meshes"))
+}
+
+/// Releases the meshes retained by [`b4d_retain_cached_meshes`] and destroys the handle. Returns
+/// the number of retained meshes which have been reused.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_release_cached_meshes(retention: *mut MeshRetention) -> u32 {
+    catch_unwind(|| {
+        let retention = check(RETENTION_HANDLES.remove(retention), "b4d_release_cached_meshes");
+
+        retention.get_reused_count() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_release_cached_meshes"))
+}
+
+/// Writes the render layer setup to `path` and saves the pipeline cache. Returns 0 if the state
+/// could not be written.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_save_state(b4d: *const Blaze4D, path: *const c_char) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_save_state");
+        if path.is_null() {
+            log::error!("Passed null path to b4d_save_state");
+            reject(CApiError::NullPointer("path"));
+        }
+        let path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+
+        b4d.save_state(&path) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_save_state"))
+}
+
+/// Restores the render layer setup written by [`b4d_save_state`]. Returns 0 if the state could not
+/// be restored.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_load_state(b4d: *const Blaze4D, path: *const c_char) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_load_state");
+        if path.is_null() {
+            log::error!("Passed null path to b4d_load_state");
+            reject(CApiError::NullPointer("path"));
+        }
+        let path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+
+        b4d.load_state(&path) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_load_state"))
+}
+
+#[repr(C)]
+struct CDrawTagStats {
+    tag: u64,
+    draw_count: u64,
+    index_count: u64,
+}
+
+/// Writes the per draw tag statistics of the last ended frame sorted by descending index count to
+/// `stats`. At most `capacity` entries are written. Returns the total number of tags used in the
+/// frame.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_frame_tag_stats(b4d: *const Blaze4D, stats: *mut CDrawTagStats, capacity: u32) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_frame_tag_stats");
+        if stats.is_null() && capacity != 0 {
+            log::error!("Passed null stats to b4d_get_frame_tag_stats");
+            reject(CApiError::InvalidArgument("b4d_get_frame_tag_stats"));
+        }
+
+        let tags = b4d.get_frame_stats().tags;
+        for (index, (tag, tag_stats)) in tags.iter().take(capacity as usize).enumerate() {
+            stats.add(index).write(CDrawTagStats {
+                tag: *tag,
+                draw_count: tag_stats.draw_count,
+                index_count: tag_stats.index_count,
+            });
+        }
+        tags.len() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_frame_tag_stats"))
+}
+
+#[repr(C)]
+struct CLayerStats {
+    layer_id: u64,
+    draw_count: u64,
+    index_count: u64,
+    skipped_draw_count: u64,
+    culled_draw_count: u64,
+}
+
+/// Writes the per layer statistics of the last ended frame to `stats`. At most `capacity` entries
+/// are written. Returns the total number of layers drawn to in the frame.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_frame_layer_stats(b4d: *const Blaze4D, stats: *mut CLayerStats, capacity: u32) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_frame_layer_stats");
+        if stats.is_null() && capacity != 0 {
+            log::error!("Passed null stats to b4d_get_frame_layer_stats");
+            reject(CApiError::InvalidArgument("b4d_get_frame_layer_stats"));
+        }
+
+        let layers = b4d.get_frame_stats().layers;
+        for (index, (id, layer)) in layers.iter().take(capacity as usize).enumerate() {
+            stats.add(index).write(CLayerStats {
+                layer_id: id.as_uuid().get_raw(),
+                draw_count: layer.draw_count,
+                index_count: layer.index_count,
+                skipped_draw_count: layer.skipped_draw_count,
+                culled_draw_count: layer.culled_draw_count,
+            });
+        }
+        layers.len() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_frame_layer_stats"))
+}
+
+/// Limits the number of immediate meshes uploaded per frame. A limit of 0 disables the limit. See
+/// [`render_budget`](crate::renderer::emulator::render_budget).
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_immediate_upload_limit(b4d: *const Blaze4D, limit: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_immediate_upload_limit");
+
+        let mut budget = b4d.get_render_budget();
+        budget.max_immediate_uploads = if limit == 0 { None } else { Some(limit) };
+        b4d.set_render_budget(budget);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_immediate_upload_limit"))
+}
+
+/// Limits the number of draws per frame in a render layer. If `enable` is 0 the limit is removed.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_layer_draw_limit(b4d: *const Blaze4D, layer_id: u64, enable: u32, limit: u32) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_set_layer_draw_limit");
+        let layer = RenderLayerId::from_uuid(UUID::from_raw(layer_id));
+
+        let mut budget = b4d.get_render_budget();
+        if enable != 0 {
+            budget.layer_draw_limits.insert(layer, limit);
+        } else {
+            budget.layer_draw_limits.remove(&layer);
+        }
+        b4d.set_render_budget(budget);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_layer_draw_limit"))
+}
+
+#[repr(C)]
+struct CBudgetReport {
+    dropped_uploads: u32,
+    dropped_upload_draws: u32,
+    culled_draws: u32,
+}
+
+/// Writes the work dropped because of the render budget in the last ended frame to `report`.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_frame_budget_report(b4d: *const Blaze4D, report: *mut CBudgetReport) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_frame_budget_report");
+        if report.is_null() {
+            log::error!("Passed null report to b4d_get_frame_budget_report");
+            reject(CApiError::NullPointer("report"));
+        }
+
+        let budget = b4d.get_frame_stats().budget;
+        report.write(CBudgetReport {
+            dropped_uploads: budget.dropped_uploads,
+            dropped_upload_draws: budget.dropped_upload_draws,
+            culled_draws: budget.culled_draws,
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_frame_budget_report"))
+}
+
+#[repr(C)]
+struct CDisplayTiming {
+    refresh_duration_ns: u64,
+    /// 0 if no present has completed yet.
+    present_margin_ns: u64,
+    /// 0 if unknown, 1 if the display uses a fixed refresh rate and 2 if it uses a variable
+    /// refresh rate.
+    variable_refresh: u32,
+}
+
+/// Returns 1 if frames are skipped because the main surface has no size, for example because the
+/// window is minimized.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_is_surface_paused(b4d: *const Blaze4D) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_is_surface_paused");
+
+        b4d.is_surface_paused() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_is_surface_paused"))
+}
+
+/// Writes the timing of recently displayed frames to `timing`. Returns 0 if display timing is not
+/// available in which case `timing` is not written.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_display_timing(b4d: *const Blaze4D, timing: *mut CDisplayTiming) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_display_timing");
+        if timing.is_null() {
+            log::error!("Passed null timing to b4d_get_display_timing");
+            reject(CApiError::NullPointer("timing"));
+        }
+
+        if let Some(display_timing) = b4d.get_frame_stats().display_timing {
+            timing.write(CDisplayTiming {
+                refresh_duration_ns: display_timing.refresh_duration.as_nanos() as u64,
+                present_margin_ns: display_timing.present_margin.map_or(0, |margin| margin.as_nanos() as u64),
+                variable_refresh: match display_timing.variable_refresh {
+                    None => 0,
+                    Some(false) => 1,
+                    Some(true) => 2,
+                },
+            });
+            1
+        } else {
+            0
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_display_timing"))
+}
+
+#[repr(C)]
+struct CDeviceInfo {
+    /// The vulkan version used by the device encoded using `VK_MAKE_API_VERSION`.
+    api_version: u32,
+    /// 0 for vulkan 1.1, 1 for vulkan 1.2 and 2 for vulkan 1.3.
+    feature_tier: u32,
+    /// For each feature 0 if unsupported, 1 if provided by an extension and 2 if provided by core.
+    dynamic_rendering: u32,
+    timeline_semaphore: u32,
+    synchronization_2: u32,
+}
+
+fn feature_source_to_raw(source: FeatureSource) -> u32 {
+    match source {
+        FeatureSource::Unsupported => 0,
+        FeatureSource::Extension => 1,
+        FeatureSource::Core => 2,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_device_info(b4d: *const Blaze4D, info: *mut CDeviceInfo) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_device_info");
+        if info.is_null() {
+            log::error!("Passed null info to b4d_get_device_info");
+            reject(CApiError::NullPointer("info"));
+        }
+
+        let capabilities = b4d.get_device_capabilities();
+        info.write(CDeviceInfo {
+            api_version: capabilities.api_version.get_raw(),
+            feature_tier: match capabilities.tier {
+                FeatureTier::Vulkan11 => 0,
+                FeatureTier::Vulkan12 => 1,
+                FeatureTier::Vulkan13 => 2,
+            },
+            dynamic_rendering: feature_source_to_raw(capabilities.dynamic_rendering),
+            timeline_semaphore: feature_source_to_raw(capabilities.timeline_semaphore),
+            synchronization_2: feature_source_to_raw(capabilities.synchronization_2),
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_device_info"))
+}
+
+#[repr(C)]
+struct CHostMemoryStats {
+    current_bytes: u64,
+    peak_bytes: u64,
+    live_allocations: u64,
+    total_allocations: u64,
+    internal_bytes: u64,
+}
+
+/// Writes the host memory stats of each allocation scope in the order command, object, cache,
+/// device, instance into `stats` which must point to an array of 5 elements. Returns 0 if host
+/// memory tracking is disabled in which case nothing is written.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_host_memory_report(b4d: *const Blaze4D, stats: *mut CHostMemoryStats) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_host_memory_report");
+        if stats.is_null() {
+            log::error!("Passed null stats to b4d_get_host_memory_report");
+            reject(CApiError::NullPointer("stats"));
+        }
+
+        if let Some(report) = b4d.get_host_memory_report() {
+            for (index, scope) in report.scopes.iter().enumerate() {
+                stats.add(index).write(CHostMemoryStats {
+                    current_bytes: scope.current_bytes,
+                    peak_bytes: scope.peak_bytes,
+                    live_allocations: scope.live_allocations,
+                    total_allocations: scope.total_allocations,
+                    internal_bytes: scope.internal_bytes,
+                });
+            }
+            1
+        } else {
+            0
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_host_memory_report"))
+}
+
+/// Summary of recent frame times. All times are in microseconds. If no samples are available all
+/// fields are 0.
+#[repr(C)]
+#[derive(Default)]
+struct CFrameTimeSummary {
+    sample_count: u64,
+    stutter_count: u64,
+    average_us: u64,
+    p50_us: u64,
+    p95_us: u64,
+    p99_us: u64,
+    max_us: u64,
+}
+
+impl CFrameTimeSummary {
+    fn from_summary(summary: Option<FrameTimeSummary>) -> Self {
+        summary.map(|summary| Self {
+            sample_count: summary.sample_count as u64,
+            stutter_count: summary.stutter_count as u64,
+            average_us: summary.average.as_micros() as u64,
+            p50_us: summary.p50.as_micros() as u64,
+            p95_us: summary.p95.as_micros() as u64,
+            p99_us: summary.p99.as_micros() as u64,
+            max_us: summary.max.as_micros() as u64,
+        }).unwrap_or_default()
+    }
+}
+
+#[repr(C)]
+struct CFrameTimeReport {
+    cpu: CFrameTimeSummary,
+    gpu: CFrameTimeSummary,
+}
+
+/// Writes percentiles and stutter counts of the cpu and gpu frame times of recent frames to
+/// `report`.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_frame_time_report(b4d: *const Blaze4D, report: *mut CFrameTimeReport) {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_frame_time_report");
+        if report.is_null() {
+            log::error!("Passed null report to b4d_get_frame_time_report");
+            reject(CApiError::NullPointer("report"));
+        }
+
+        let frame_times = b4d.get_frame_time_report();
+        report.write(CFrameTimeReport {
+            cpu: CFrameTimeSummary::from_summary(frame_times.cpu),
+            gpu: CFrameTimeSummary::from_summary(frame_times.gpu),
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_frame_time_report"))
+}
+
+/// Writes the histogram of recent cpu frame times or gpu frame times if `gpu` is not 0 to
+/// `buckets`. Each bucket is 1ms wide. At most `capacity` buckets are written. Returns the total
+/// number of buckets.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_frame_time_histogram(b4d: *const Blaze4D, gpu: u32, buckets: *mut u32, capacity: u32) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_get_frame_time_histogram");
+        if buckets.is_null() && capacity != 0 {
+            log::error!("Passed null buckets to b4d_get_frame_time_histogram");
+            reject(CApiError::InvalidArgument("b4d_get_frame_time_histogram"));
+        }
+
+        let histogram = b4d.get_frame_time_histogram(gpu != 0);
+        let count = std::cmp::min(capacity as usize, HISTOGRAM_BUCKET_COUNT);
+        if count != 0 {
+            std::ptr::copy_nonoverlapping(histogram.as_ptr(), buckets, count);
+        }
+        HISTOGRAM_BUCKET_COUNT as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_frame_time_histogram"))
+}
+
+/// Moves a render layer to an insertion point. Returns 0 if the layer is not registered.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_insert_render_layer(b4d: *const Blaze4D, layer_id: u64, point: u32) -> u32 {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_insert_render_layer");
+        let point = check(InsertionPoint::from_raw(point).ok_or(CApiError::InvalidEnum("point", point as i64)), "b4d_insert_render_layer");
+
+        match b4d.insert_render_layer(RenderLayerId::from_uuid(UUID::from_raw(layer_id)), point) {
+            Ok(_) => 1,
+            Err(err) => {
+                log::error!("Failed to insert render layer: {:?}", err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_insert_render_layer"))
+}
+
+/// Calls [`Blaze4D::try_start_frame`].
+///
+/// If [`Blaze4D::try_start_frame`] returns [`None`] this function returns null.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_start_frame(b4d: *mut Blaze4D, window_width: u32, window_height: u32) -> *mut PassRecorder {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_start_frame");
+
+        let frame = b4d.try_start_frame(Vec2u32::new(window_width, window_height));
+        frame.map_or(std::ptr::null_mut(), |recorder| {
+            PASS_HANDLES.insert(Box::new(recorder))
+        })
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_start_frame"))
+}
+
+/// Calls [`Blaze4D::try_start_frame_scaled`] with a size in logical units.
+///
+/// The physical size is calculated by multiplying the logical size with the scale factor.
+/// If [`Blaze4D::try_start_frame_scaled`] returns [`None`] this function returns null.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_start_frame_scaled(b4d: *mut Blaze4D, logical_width: u32, logical_height: u32, scale_factor: f32) -> *mut PassRecorder {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_start_frame_scaled");
+
+        let frame_size = FrameSize::from_logical(Vec2u32::new(logical_width, logical_height), scale_factor);
+        let frame = b4d.try_start_frame_scaled(frame_size);
+        frame.map_or(std::ptr::null_mut(), |recorder| {
+            PASS_HANDLES.insert(Box::new(recorder))
+        })
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_start_frame_scaled"))
+}
+
+/// Calls [`Blaze4D::capture_panorama`]. The callback receives the width, height and tightly packed
+/// rgba pixels of the panorama as well as the provided user data. The pixel data is only valid
+/// for the duration of the callback which is called from a background thread.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_capture_panorama(b4d: *const Blaze4D, resolution: u32, callback: Option<unsafe extern "C" fn(u32, u32, *const u8, *mut c_void)>, user_data: *mut c_void) -> *mut PanoramaCapture {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_capture_panorama");
+        if resolution == 0 {
+            check(Err(CApiError::InvalidSize("resolution")), "b4d_capture_panorama")
+        }
+        let callback = callback.unwrap_or_else(|| {
+            log::error!("Passed null callback to b4d_capture_panorama");
+            reject(CApiError::InvalidArgument("b4d_capture_panorama"));
+        });
+
+        // Raw pointers are not Send so we have to pass the user data as an integer
+        let user_data = user_data as usize;
+        let capture = b4d.capture_panorama(resolution, Box::new(move |size, data| {
+            callback(size[0], size[1], data.as_ptr(), user_data as *mut c_void)
+        }));
+        PANORAMA_HANDLES.insert(Box::new(capture))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_capture_panorama"))
+}
+
+/// Calls [`PanoramaCapture::next_face`] and writes the face index into `face`. The returned pass
+/// must be ended with [`b4d_end_frame`].
+///
+/// Returns null once all faces have been started.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_panorama_next_face(capture: *mut PanoramaCapture, face: *mut u32) -> *mut PassRecorder {
+    catch_unwind(|| {
+        let mut capture = check(PANORAMA_HANDLES.get_mut(capture), "b4d_panorama_next_face");
+        let face = check(face.as_mut().ok_or(CApiError::NullPointer("face")), "b4d_panorama_next_face");
+
+        capture.next_face().map_or(std::ptr::null_mut(), |(next, recorder)| {
+            *face = next as u32;
+            PASS_HANDLES.insert(Box::new(recorder))
+        })
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_panorama_next_face"))
+}
+
+/// Calls [`PanoramaCapture::finish`] and destroys the capture handle.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_panorama_finish(capture: *mut PanoramaCapture) {
+    catch_unwind(|| {
+        let capture = check(PANORAMA_HANDLES.remove(capture), "b4d_panorama_finish");
+        if !capture.is_complete() {
+            log::error!("Called b4d_panorama_finish before all faces were started");
+            reject(CApiError::InvalidArgument("b4d_panorama_finish"));
+        }
+
+        capture.finish();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_panorama_finish"))
+}
+
+/// Calls [`Blaze4D::capture_probe`]. The callback receives a new image handle of the prefiltered
+/// probe as well as the provided user data. It is called from a background thread and the image
+/// must be destroyed using [`b4d_destroy_global_image`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_capture_probe(b4d: *const Blaze4D, x: f32, y: f32, z: f32, resolution: u32, faces_per_frame: u32, callback: Option<unsafe extern "C" fn(*mut Arc<GlobalImage>, *mut c_void)>, user_data: *mut c_void) -> *mut ProbeCapture {
+    catch_unwind(|| {
+        let b4d = check(B4D_HANDLES.get(b4d), "b4d_capture_probe");
+        if !resolution.is_power_of_two() {
+            check(Err(CApiError::InvalidSize("resolution")), "b4d_capture_probe")
+        }
+        let callback = callback.unwrap_or_else(|| {
+            log::error!("Passed null callback to b4d_capture_probe");
+            reject(CApiError::InvalidArgument("b4d_capture_probe"));
+        });
+
+        // Raw pointers are not Send so we have to pass the user data as an integer
+        let user_data = user_data as usize;
+        let capture = b4d.capture_probe(Vec3f32::new(x, y, z), resolution, faces_per_frame, Box::new(move |image| {
+            callback(IMAGE_HANDLES.insert(Box::new(image)), user_data as *mut c_void)
+        }));
+        PROBE_HANDLES.insert(Box::new(capture))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_capture_probe"))
+}
+
+/// Calls [`ProbeCapture::next_faces`]. Writes the index of each face which should be rendered this
+/// frame into `faces` which must have space for 6 entries. Each face must be rendered using
+/// [`b4d_probe_start_face`].
+///
+/// Returns the number of faces. 0 once all faces have been scheduled.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_probe_next_faces(capture: *mut ProbeCapture, faces: *mut u32) -> u32 {
+    catch_unwind(|| {
+        let mut capture = check(PROBE_HANDLES.get_mut(capture), "b4d_probe_next_faces");
+        if faces.is_null() {
+            log::error!("Passed null faces to b4d_probe_next_faces");
+            reject(CApiError::NullPointer("faces"));
+        }
+        let faces = std::slice::from_raw_parts_mut(faces, 6);
+
+        let next = capture.next_faces();
+        for (index, face) in next.iter().enumerate() {
+            faces[index] = *face as u32;
+        }
+        next.len() as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_probe_next_faces"))
+}
+
+/// Calls [`ProbeCapture::start_face`]. The pass must be ended with [`b4d_end_frame`] before the
+/// next face or frame is started.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_probe_start_face(capture: *mut ProbeCapture, face: u32) -> *mut PassRecorder {
+    catch_unwind(|| {
+        let mut capture = check(PROBE_HANDLES.get_mut(capture), "b4d_probe_start_face");
+        let face = *CubeFace::ALL.get(face as usize).unwrap_or_else(|| {
+            check(Err(CApiError::InvalidSize("face")), "b4d_probe_start_face")
+        });
+
+        PASS_HANDLES.insert(Box::new(capture.start_face(face)))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_probe_start_face"))
+}
+
+/// Calls [`ProbeCapture::finish`] and destroys the capture handle.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_probe_finish(capture: *mut ProbeCapture) {
+    catch_unwind(|| {
+        let capture = check(PROBE_HANDLES.remove(capture), "b4d_probe_finish");
+        if !capture.is_complete() {
+            log::error!("Called b4d_probe_finish before all faces were started");
+            reject(CApiError::InvalidArgument("b4d_probe_finish"));
+        }
+
+        capture.finish();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_probe_finish"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_partial_tick(pass: *mut PassRecorder, partial_tick: f32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_partial_tick");
+
+        pass.set_partial_tick(partial_tick);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_partial_tick"))
+}
+
+/// Sets the viewport at some index in pixels. Viewports which have not been set cover the full
+/// output.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_viewport(pass: *mut PassRecorder, index: u32, x: f32, y: f32, width: f32, height: f32, min_depth: f32, max_depth: f32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_viewport");
+        if index >= MAX_VIEWPORTS {
+            check(Err(CApiError::InvalidSize("index")), "b4d_pass_set_viewport")
+        }
+        if !(x.is_finite() && y.is_finite() && width.is_finite() && height.is_finite()) || width <= 0f32 || height <= 0f32 {
+            check(Err(CApiError::InvalidSize("viewport")), "b4d_pass_set_viewport")
+        }
+        if !(0f32..=1f32).contains(&min_depth) || !(0f32..=1f32).contains(&max_depth) {
+            check(Err(CApiError::InvalidSize("depth")), "b4d_pass_set_viewport")
+        }
+
+        pass.set_viewport(index, vk::Viewport { x, y, width, height, min_depth, max_depth });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_viewport"))
+}
+
+/// Selects the viewport used by all following draws.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_viewport_index(pass: *mut PassRecorder, index: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_viewport_index");
+        if index >= MAX_VIEWPORTS {
+            check(Err(CApiError::InvalidSize("index")), "b4d_pass_set_viewport_index")
+        }
+
+        pass.set_viewport_index(index);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_viewport_index"))
+}
+
+/// Enables cascaded shadow maps for all following draws. `view` points to the 16 floats of the
+/// column major world to view matrix of the camera. The light direction is the direction the
+/// light travels in.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_shadow_camera(pass: *mut PassRecorder, view: *const f32, fov_y: f32, aspect: f32, near: f32, far: f32, light_x: f32, light_y: f32, light_z: f32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_shadow_camera");
+        if view.is_null() {
+            log::error!("Passed null view to b4d_pass_set_shadow_camera");
+            reject(CApiError::NullPointer("view"));
+        }
+        let view = Mat4f32::from_column_slice(std::slice::from_raw_parts(view, 16));
+        if !view.iter().all(|v| v.is_finite()) {
+            check(Err(CApiError::InvalidArgument("view")), "b4d_pass_set_shadow_camera")
+        }
+        if !(fov_y > 0f32 && fov_y < std::f32::consts::PI) || !(aspect > 0f32 && aspect.is_finite()) || !(near > 0f32 && far > near && far.is_finite()) {
+            check(Err(CApiError::InvalidArgument("frustum")), "b4d_pass_set_shadow_camera")
+        }
+        let light_direction = Vec3f32::new(light_x, light_y, light_z);
+        if !light_direction.iter().all(|v| v.is_finite()) || light_direction.norm() <= f32::EPSILON {
+            check(Err(CApiError::InvalidArgument("light_direction")), "b4d_pass_set_shadow_camera")
+        }
+
+        pass.set_shadow_camera(&CameraFrustum { view, fov_y, aspect, near, far }, light_direction);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_shadow_camera"))
+}
+
+/// Restricts all following draws to a rect in pixels. If `enable` is 0 the restriction is removed
+/// and the rect is ignored.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_scissor(pass: *mut PassRecorder, enable: u32, x: i32, y: i32, width: u32, height: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_scissor");
+
+        let scissor = if enable != 0 {
+            Some(vk::Rect2D {
+                offset: vk::Offset2D { x, y },
+                extent: vk::Extent2D { width, height },
+            })
+        } else {
+            None
+        };
+        pass.set_scissor(scissor);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_scissor"))
+}
+
+/// Starts drawing a 3d gui item at a position in logical gui pixels. See
+/// [`PassRecorder::begin_gui_item`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_begin_gui_item(pass: *mut PassRecorder, x: f32, y: f32, size: f32, min_depth: f32, max_depth: f32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_begin_gui_item");
+        if !(x.is_finite() && y.is_finite() && size.is_finite()) || size <= 0f32 {
+            check(Err(CApiError::InvalidSize("size")), "b4d_pass_begin_gui_item")
+        }
+        if !(0f32..=1f32).contains(&min_depth) || !(min_depth..=1f32).contains(&max_depth) {
+            check(Err(CApiError::InvalidSize("depth")), "b4d_pass_begin_gui_item")
+        }
+
+        pass.begin_gui_item(&GuiItemPlacement {
+            position: Vec2f32::new(x, y),
+            size,
+            depth_range: (min_depth, max_depth),
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_begin_gui_item"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_end_gui_item(pass: *mut PassRecorder) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_end_gui_item");
+
+        pass.end_gui_item();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_end_gui_item"))
+}
+
+/// Writes the column major projection matrix used for gui items into `matrix` which must point
+/// to 16 floats.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_get_gui_item_projection(matrix: *mut f32) {
+    catch_unwind(|| {
+        if matrix.is_null() {
+            log::error!("Passed null matrix to b4d_get_gui_item_projection");
+            reject(CApiError::NullPointer("matrix"));
+        }
+
+        let projection = make_gui_item_projection();
+        std::ptr::copy_nonoverlapping(projection.as_slice().as_ptr(), matrix, 16);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_get_gui_item_projection"))
+}
+
+/// Sets the column major camera matrices of the pass used by [`b4d_unproject`]. Both pointers must
+/// point to 16 floats.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_view_matrices(pass: *mut PassRecorder, model_view: *const f32, projection: *const f32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_view_matrices");
+        let model_view = check(make_slice("model_view", model_view, 16), "b4d_pass_set_view_matrices");
+        let projection = check(make_slice("projection", projection, 16), "b4d_pass_set_view_matrices");
+
+        pass.set_view_matrices(ViewMatrices {
+            model_view: Mat4f32::from_column_slice(model_view),
+            projection: Mat4f32::from_column_slice(projection),
+        });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_view_matrices"))
+}
+
+/// Starts a portal masked by an immediate mesh drawn with `shader_id`. The column major matrices
+/// are the camera of the portal and must point to 16 floats each. See
+/// [`PassRecorder::begin_portal`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_begin_portal(pass: *mut PassRecorder, model_view: *const f32, projection: *const f32, mask_id: u32, shader_id: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_begin_portal");
+        let model_view = check(make_slice("model_view", model_view, 16), "b4d_pass_begin_portal");
+        let projection = check(make_slice("projection", projection, 16), "b4d_pass_begin_portal");
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let matrices = ViewMatrices {
+            model_view: Mat4f32::from_column_slice(model_view),
+            projection: Mat4f32::from_column_slice(projection),
+        };
+        pass.begin_portal(matrices, ImmediateMeshId::form_raw(mask_id), shader_id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_begin_portal"))
+}
+
+/// Ends the innermost portal. See [`PassRecorder::end_portal`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_end_portal(pass: *mut PassRecorder) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_end_portal");
+
+        pass.end_portal();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_end_portal"))
+}
+
+/// Sets the priority of all following draws and uploads. Values above 255 are clamped. See
+/// [`PassRecorder::set_draw_priority`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_draw_priority(pass: *mut PassRecorder, priority: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_draw_priority");
+
+        pass.set_draw_priority(priority.min(u8::MAX as u32) as u8);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_draw_priority"))
+}
+
+/// Enables or disables the camera effects for all following projection matrix uniforms and view
+/// matrices. See [`PassRecorder::set_camera_effects_enabled`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_camera_effects_enabled(pass: *mut PassRecorder, enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_camera_effects_enabled");
+
+        pass.set_camera_effects_enabled(enable != 0);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_camera_effects_enabled"))
+}
+
+/// Applies the camera effects of the pass to the 16 floats of a column major world to view matrix
+/// in place. Views computed from the camera by the host must use the result. See
+/// [`PassRecorder::apply_camera_effects_to_view`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_apply_camera_effects_to_view(pass: *const PassRecorder, view: *mut f32) {
+    catch_unwind(|| {
+        let pass = check(PASS_HANDLES.get(pass), "b4d_pass_apply_camera_effects_to_view");
+        if view.is_null() {
+            log::error!("Passed null view to b4d_pass_apply_camera_effects_to_view");
+            reject(CApiError::NullPointer("view"));
+        }
+
+        let result = pass.apply_camera_effects_to_view(&Mat4f32::from_column_slice(std::slice::from_raw_parts(view, 16)));
+        std::ptr::copy_nonoverlapping(result.as_slice().as_ptr(), view, 16);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_apply_camera_effects_to_view"))
+}
+
+/// Attaches `tag` to all following draws. If `enable` is 0 the tag is removed. See
+/// [`PassRecorder::set_draw_tag`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_draw_tag(pass: *mut PassRecorder, enable: u32, tag: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_draw_tag");
+
+        pass.set_draw_tag(if enable != 0 { Some(tag) } else { None });
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_draw_tag"))
+}
+
+/// Selects the render layer of all following draws. Passing 0 draws without a layer.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_render_layer(pass: *mut PassRecorder, layer_id: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_render_layer");
+
+        let layer = if layer_id == 0 { None } else { Some(RenderLayerId::from_uuid(UUID::from_raw(layer_id))) };
+        pass.set_render_layer(layer);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_render_layer"))
+}
+
+/// Writes the number of draws and indices submitted to a render layer in this pass. Returns 0 if
+/// no draws have been submitted to the layer.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_get_layer_stats(pass: *mut PassRecorder, layer_id: u64, draw_count: *mut u64, index_count: *mut u64) -> u32 {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_get_layer_stats");
+        if draw_count.is_null() || index_count.is_null() {
+            log::error!("Passed null output to b4d_pass_get_layer_stats");
+            reject(CApiError::InvalidArgument("b4d_pass_get_layer_stats"));
+        }
+
+        match pass.get_layer_stats(RenderLayerId::from_uuid(UUID::from_raw(layer_id))) {
+            Some(stats) => {
+                draw_count.write(stats.draw_count);
+                index_count.write(stats.index_count);
+                1
+            }
+            None => 0,
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_get_layer_stats"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_update_uniform(pass: *mut PassRecorder, data: *const CMcUniformData, shader_id: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_update_dev_uniform");
+        let data = check(data.as_ref().ok_or(CApiError::NullPointer("data")), "b4d_pass_update_dev_uniform");
+
+        let data = check(data.to_mc_uniform_data(), "b4d_pass_update_uniform");
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        pass.update_uniform(&data, shader_id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_update_dev_uniform"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_update_texture(pass: *mut PassRecorder, index: u32, image: *const Arc<GlobalImage>, sampler_info: *const CSamplerInfo, shader_id: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_update_texture");
+        let image = check(IMAGE_HANDLES.get(image), "b4d_pass_update_texture");
+        let sampler_info = check(sampler_info.as_ref().ok_or(CApiError::NullPointer("sampler_info")), "b4d_pass_update_texture");
+
+        let sampler_info = check(sampler_info.to_sampler_info(), "b4d_pass_update_texture");
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        pass.update_texture(index, &image, &sampler_info, shader_id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_update_texture"))
+}
+
+/// Calls [`PassRecorder::update_environment_probe`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_update_environment_probe(pass: *mut PassRecorder, index: u32, image: *const Arc<GlobalImage>, shader_id: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_update_environment_probe");
+        let image = check(IMAGE_HANDLES.get(image), "b4d_pass_update_environment_probe");
+        let size = image.get_size();
+        if size != probe_image_size(size[1]) {
+            check(Err(CApiError::InvalidSize("image")), "b4d_pass_update_environment_probe")
+        }
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        pass.update_environment_probe(index, &image, shader_id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_update_environment_probe"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_bind_texture(pass: *mut PassRecorder, slot: u32, image: *const Arc<GlobalImage>, sampler_info: *const CSamplerInfo) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_bind_texture");
+        if slot >= MAX_TEXTURE_SLOTS {
+            check(Err(CApiError::InvalidSize("slot")), "b4d_pass_bind_texture")
+        }
+
+        if image.is_null() {
+            pass.unbind_texture(slot);
+            return;
+        }
+
+        let image = check(IMAGE_HANDLES.get(image), "b4d_pass_bind_texture");
+        let sampler_info = check(sampler_info.as_ref().ok_or(CApiError::NullPointer("sampler_info")), "b4d_pass_bind_texture");
+
+        let sampler_info = check(sampler_info.to_sampler_info(), "b4d_pass_bind_texture");
+
+        pass.bind_texture(slot, &image, &sampler_info);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_bind_texture"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_global(pass: *mut PassRecorder, mesh: *const Arc<GlobalMesh>, shader_id: u64, depth_write_enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_global");
+        let mesh = check(MESH_HANDLES.get(mesh), "b4d_pass_draw_global");
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+
+        pass.draw_global(mesh.clone(), shader_id, depth_write_enable);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_global"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_upload_immediate(pass: *mut PassRecorder, data: *const CMeshData) -> u32 {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_upload_immediate");
+        let data = check(data.as_ref().ok_or(CApiError::NullPointer("data")), "b4d_pass_upload_immediate");
+
+        let mesh_data = check(data.to_mesh_data(), "b4d_pass_upload_immediate");
+
+        pass.upload_immediate(&mesh_data).get_raw()
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_upload_immediate"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_immediate(pass: *mut PassRecorder, id: u32, shader_id: u64, depth_write_enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_immediate");
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
 
         pass.draw_immediate(ImmediateMeshId::form_raw(id), shader_id, depth_write_enable);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_pass_draw_immediate");
-        exit(1);
-    })
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_immediate"))
+}
+
+/// Calls [`PassRecorder::draw_small`]. `transform` may be null or point to a column major 4x4
+/// matrix.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_small(pass: *mut PassRecorder, data: *const CMeshData, transform: *const f32, shader_id: u64, depth_write_enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_small");
+        let data = check(data.as_ref().ok_or(CApiError::NullPointer("data")), "b4d_pass_draw_small");
+
+        let mesh_data = check(data.to_mesh_data(), "b4d_pass_draw_small");
+        let transform = if transform.is_null() {
+            None
+        } else {
+            Some(Mat4f32::from_column_slice(check(make_slice("transform", transform, 16), "b4d_pass_draw_small")))
+        };
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+
+        pass.draw_small(&mesh_data, transform.as_ref(), shader_id, depth_write_enable);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_small"))
+}
+
+/// Draws `rect_count` textured gui rects. If `sampler_info` is null nearest filtering with
+/// clamped edges is used. See [`PassRecorder::draw_textured_rects`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_textured_rects(pass: *mut PassRecorder, image: *const Arc<GlobalImage>, sampler_info: *const CSamplerInfo, rects: *const CTexturedRect, rect_count: u32, depth: f32, shader_id: u64, depth_write_enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_textured_rects");
+        let image = check(IMAGE_HANDLES.get(image), "b4d_pass_draw_textured_rects");
+        let sampler_info = match sampler_info.as_ref() {
+            Some(sampler_info) => check(sampler_info.to_sampler_info(), "b4d_pass_draw_textured_rects"),
+            None => GUI_SAMPLER_INFO,
+        };
+        let rects: Vec<_> = check(make_slice("rects", rects, rect_count as usize), "b4d_pass_draw_textured_rects")
+            .iter().map(CTexturedRect::to_textured_rect).collect();
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+
+        pass.draw_textured_rects(&image, &sampler_info, &rects, depth, shader_id, depth_write_enable);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_textured_rects"))
+}
+
+/// Draws `panel_count` nine slice gui panels. See [`b4d_pass_draw_textured_rects`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_nine_slices(pass: *mut PassRecorder, image: *const Arc<GlobalImage>, sampler_info: *const CSamplerInfo, panels: *const CNineSlice, panel_count: u32, depth: f32, shader_id: u64, depth_write_enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_nine_slices");
+        let image = check(IMAGE_HANDLES.get(image), "b4d_pass_draw_nine_slices");
+        let sampler_info = match sampler_info.as_ref() {
+            Some(sampler_info) => check(sampler_info.to_sampler_info(), "b4d_pass_draw_nine_slices"),
+            None => GUI_SAMPLER_INFO,
+        };
+        let panels: Vec<_> = check(make_slice("panels", panels, panel_count as usize), "b4d_pass_draw_nine_slices")
+            .iter().map(CNineSlice::to_nine_slice).collect();
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+
+        pass.draw_nine_slices(&image, &sampler_info, &panels, depth, shader_id, depth_write_enable);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_nine_slices"))
+}
+
+/// Draws `beam_count` beams using a mesh created with [`b4d_create_beam_mesh`]. See
+/// [`PassRecorder::draw_beams`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_beams(pass: *mut PassRecorder, mesh: *const Arc<GlobalMesh>, beams: *const CBeamColumn, beam_count: u32, shader_id: u64, depth_write_enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_beams");
+        let mesh = check(MESH_HANDLES.get(mesh), "b4d_pass_draw_beams");
+        let beams: Vec<_> = check(make_slice("beams", beams, beam_count as usize), "b4d_pass_draw_beams")
+            .iter().map(CBeamColumn::to_beam_column).collect();
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        let depth_write_enable = if depth_write_enable == 1 { true } else { false };
+
+        pass.draw_beams(&mesh, &beams, shader_id, depth_write_enable);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_beams"))
+}
+
+/// Draws `shadow_count` blob shadows. If `config` is null the default config is used. See
+/// [`PassRecorder::draw_blob_shadows`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_draw_blob_shadows(pass: *mut PassRecorder, shadows: *const CBlobShadow, shadow_count: u32, config: *const CBlobShadowConfig, shader_id: u64) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_draw_blob_shadows");
+        let shadows: Vec<_> = check(make_slice("shadows", shadows, shadow_count as usize), "b4d_pass_draw_blob_shadows")
+            .iter().map(CBlobShadow::to_blob_shadow).collect();
+        let config = match config.as_ref() {
+            Some(config) => config.to_blob_shadow_config(),
+            None => BlobShadowConfig::new(),
+        };
+        let shader_id = ShaderId::from_uuid(UUID::from_raw(shader_id));
+
+        pass.draw_blob_shadows(&shadows, &config, shader_id);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_draw_blob_shadows"))
+}
+
+/// Calls [`PassRecorder::set_small_draw_merging`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_set_small_draw_merging(pass: *mut PassRecorder, enable: u32) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_set_small_draw_merging");
+
+        pass.set_small_draw_merging(enable != 0);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_set_small_draw_merging"))
+}
+
+/// Calls [`PassRecorder::start_command_log`].
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_start_command_log(pass: *mut PassRecorder) {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_start_command_log");
+
+        pass.start_command_log();
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_start_command_log"))
 }
 
+/// Stops logging commands and writes the serialized log to the file at `path`. Returns 0 if
+/// logging was not started or the file could not be written.
 #[no_mangle]
-unsafe extern "C" fn b4d_end_frame(recorder: *mut PassRecorder) {
+unsafe extern "C-unwind" fn b4d_pass_save_command_log(pass: *mut PassRecorder, path: *const c_char) -> u32 {
     catch_unwind(|| {
-        if recorder.is_null() {
-            log::error!("Passed null to b4d_end_frame");
-            exit(1);
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_save_command_log");
+        if path.is_null() {
+            log::error!("Passed null path to b4d_pass_save_command_log");
+            reject(CApiError::NullPointer("path"));
         }
-        Box::from_raw(recorder);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_end_frame");
-        exit(1);
-    })
+        let path = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+
+        let log = match pass.take_command_log() {
+            Some(log) => log,
+            None => {
+                log::warn!("Called b4d_pass_save_command_log without starting a command log");
+                return 0;
+            }
+        };
+
+        let mut data = Vec::new();
+        log.serialize(&mut data);
+        match std::fs::write(&path, data) {
+            Ok(_) => 1,
+            Err(err) => {
+                log::error!("Failed to write command log {:?}: {:?}", path, err);
+                0
+            }
+        }
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_save_command_log"))
+}
+
+/// Creates a draw ring in host memory. `memory` must be 8 byte aligned and stay valid until
+/// [`b4d_destroy_draw_ring`] is called. See [`DrawRing`] for the memory layout.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_draw_ring(memory: *mut u8, len: usize) -> *mut DrawRing {
+    catch_unwind(|| {
+        if memory.is_null() {
+            log::error!("Passed null memory to b4d_create_draw_ring");
+            reject(CApiError::NullPointer("memory"));
+        }
+
+        let ring = DrawRing::new(memory, len).unwrap_or_else(|err| {
+            log::error!("Invalid memory passed to b4d_create_draw_ring: {:?}", err);
+            reject(CApiError::InvalidArgument("b4d_create_draw_ring"));
+        });
+        RING_HANDLES.insert(Box::new(ring))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_draw_ring"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_destroy_draw_ring(ring: *mut DrawRing) {
+    catch_unwind(|| {
+        drop(check(RING_HANDLES.remove(ring), "b4d_destroy_draw_ring"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_destroy_draw_ring"))
+}
+
+/// Records all commands written to the ring since the last call into the pass. Returns the number
+/// of consumed records.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_pass_consume_draw_ring(pass: *mut PassRecorder, ring: *const DrawRing) -> u32 {
+    catch_unwind(|| {
+        let mut pass = check(PASS_HANDLES.get_mut(pass), "b4d_pass_consume_draw_ring");
+        let ring = check(RING_HANDLES.get(ring), "b4d_pass_consume_draw_ring");
+
+        let result = ring.consume(|command| {
+            match command {
+                RingCommand::DrawGlobal { mesh, shader, depth_write_enable } => {
+                    let mesh = check(MESH_HANDLES.get(mesh as usize as *const Arc<GlobalMesh>), "b4d_pass_consume_draw_ring");
+                    pass.draw_global(mesh.clone(), shader, depth_write_enable);
+                }
+                RingCommand::DrawImmediate { data, shader, depth_write_enable } => {
+                    let id = pass.upload_immediate(&data);
+                    pass.draw_immediate(id, shader, depth_write_enable);
+                }
+                RingCommand::SetDrawTag(tag) => pass.set_draw_tag(tag),
+                RingCommand::SetRenderLayer(layer) => pass.set_render_layer(layer),
+                RingCommand::SetDrawPriority(priority) => pass.set_draw_priority(priority),
+            }
+        });
+
+        result.unwrap_or_else(|err| {
+            log::error!("Invalid draw ring content passed to b4d_pass_consume_draw_ring: {:?}", err);
+            reject(CApiError::InvalidArgument("b4d_pass_consume_draw_ring"));
+        }) as u32
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pass_consume_draw_ring"))
+}
+
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_end_frame(recorder: *mut PassRecorder) {
+    catch_unwind(|| {
+        drop(check(PASS_HANDLES.remove(recorder), "b4d_end_frame"));
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_end_frame"))
 }
\ No newline at end of file
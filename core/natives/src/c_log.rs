@@ -1,9 +1,10 @@
 //! Forwards rust logs to some external handler.
 
 use std::panic::catch_unwind;
-use std::process::exit;
 use log::{Level, LevelFilter, Log, Metadata, Record};
 
+use crate::util::log_filter::{self, LogSubsystem};
+
 // target_ptr, msg_ptr, target_len, msg_len, level
 type PfnLog = unsafe extern "C" fn(*const u8, *const u8, u32, u32, u32);
 
@@ -37,11 +38,14 @@ impl CLogger {
 }
 
 impl Log for CLogger {
-    fn enabled(&self, _: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        log_filter::is_enabled(metadata)
     }
 
     fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
         if let Some(msg) = record.args().as_str() {
             self.log_internal(record.target(), msg, record.level());
         } else {
@@ -53,19 +57,49 @@ impl Log for CLogger {
     }
 }
 
+/// Forwards all logs to `pfn`. Only the first call has any effect, later calls keep the already
+/// installed logger.
 #[no_mangle]
-unsafe extern "C" fn b4d_init_external_logger(pfn: PfnLog) {
+unsafe extern "C-unwind" fn b4d_init_external_logger(pfn: PfnLog) {
     catch_unwind(|| {
         let logger = Box::new(CLogger::new(pfn));
-        log::set_boxed_logger(logger).unwrap_or_else(|err| {
+        if let Err(err) = log::set_boxed_logger(logger) {
             println!("Failed to set logger in b4d_init_external_logger. {:?}", err);
-            exit(1);
-        });
+            return;
+        }
 
-        log::set_max_level(LevelFilter::Info);
+        log_filter::set_default_level(LevelFilter::Info);
     }).unwrap_or_else(|_| {
         // Log is not going to work here so we use print instead
         println!("panic in b4d_init_external_logger");
-        exit(1);
+        std::process::exit(1);
     })
-}
\ No newline at end of file
+}
+
+/// Sets the log level of a subsystem. Levels use the same values as the log callback with 5
+/// disabling all logs of the subsystem and -1 resetting it to the default level. May be called
+/// before the logger is initialized.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_set_log_level(subsystem: u32, level: i32) {
+    catch_unwind(|| {
+        let subsystem = LogSubsystem::from_raw(subsystem).unwrap_or_else(|| {
+            log::error!("Invalid subsystem {:?} passed to b4d_set_log_level", subsystem);
+            reject(CApiError::InvalidArgument("b4d_set_log_level"));
+        });
+        let level = match level {
+            -1 => None,
+            0 => Some(LevelFilter::Trace),
+            1 => Some(LevelFilter::Debug),
+            2 => Some(LevelFilter::Info),
+            3 => Some(LevelFilter::Warn),
+            4 => Some(LevelFilter::Error),
+            5 => Some(LevelFilter::Off),
+            other => {
+                log::error!("Invalid level {:?} passed to b4d_set_log_level", other);
+                reject(CApiError::InvalidArgument("b4d_set_log_level"));
+            }
+        };
+
+        log_filter::set_level(subsystem, level);
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_set_log_level"))
+}
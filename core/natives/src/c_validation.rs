@@ -0,0 +1,377 @@
+//! Validation of data passed through the c api.
+//!
+//! Everything passed by the java side must be validated before it is used. Invalid handles, enum
+//! values or sizes must result in a [`CApiError`] instead of undefined behaviour. This module
+//! does not depend on a device and is exposed with the `fuzzing` feature so it can be tested
+//! with arbitrary input.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+use ash::vk;
+
+use crate::prelude::*;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CApiError {
+    NullPointer(&'static str),
+    InvalidHandle(&'static str, usize),
+    /// The handle is currently used by another call and cannot be accessed or removed.
+    HandleInUse(&'static str, usize),
+    InvalidEnum(&'static str, i64),
+    InvalidSize(&'static str),
+    /// Some other argument of the named function was invalid.
+    InvalidArgument(&'static str),
+}
+
+impl CApiError {
+    /// Returns the error code reported to the java side by `b4d_take_last_error`. 0 is reserved
+    /// for no error.
+    pub fn get_code(&self) -> u32 {
+        match self {
+            CApiError::NullPointer(_) => 1,
+            CApiError::InvalidHandle(_, _) => 2,
+            CApiError::HandleInUse(_, _) => 3,
+            CApiError::InvalidEnum(_, _) => 4,
+            CApiError::InvalidSize(_) => 5,
+            CApiError::InvalidArgument(_) => 6,
+        }
+    }
+}
+
+/// Panic payload used to unwind out of a c api entry point when input is rejected. The entry
+/// point catches it and returns a default value. Fuzzing builds propagate it to the fuzz target.
+#[derive(Debug)]
+pub struct CApiRejected;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CApiError>> = RefCell::new(None);
+}
+
+/// Stores the error of the last rejected call on this thread.
+pub fn set_last_error(error: CApiError) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// Returns and clears the error of the last rejected call on this thread.
+pub fn take_last_error() -> Option<CApiError> {
+    LAST_ERROR.with(|last| last.borrow_mut().take())
+}
+
+/// Keeps track of all live handles of one type handed out to the java side.
+///
+/// Handles are boxed rust objects leaked as raw pointers. Before a pointer is dereferenced it is
+/// checked against this table to prevent use after free or type confusion.
+///
+/// Accessing a handle returns a guard which borrows the handle similar to a [`RefCell`]. While a
+/// guard is alive the handle cannot be removed, so a concurrent destroy call is rejected instead
+/// of freeing the object while it is in use.
+pub struct HandleTable<T> {
+    name: &'static str,
+    /// Maps live handles to their borrow state. 0 means unused, a positive value is the number of
+    /// shared borrows and -1 is a exclusive borrow.
+    live: Mutex<HashMap<usize, isize>>,
+    _phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T> HandleTable<T> {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            live: Mutex::new(HashMap::new()),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Leaks the object and registers the returned pointer as a live handle.
+    pub fn insert(&self, object: Box<T>) -> *mut T {
+        let ptr = Box::into_raw(object);
+        self.live.lock().unwrap().insert(ptr as usize, 0);
+        ptr
+    }
+
+    /// Leaks all objects and registers the returned pointers as live handles. The table is only
+    /// locked once.
+    pub fn insert_many(&self, objects: Vec<Box<T>>) -> Vec<*mut T> {
+        let ptrs: Vec<_> = objects.into_iter().map(Box::into_raw).collect();
+        self.live.lock().unwrap().extend(ptrs.iter().map(|ptr| (*ptr as usize, 0)));
+        ptrs
+    }
+
+    /// Validates the handle and returns a guard giving shared access to the object.
+    ///
+    /// Fails if the handle is currently borrowed exclusively.
+    pub fn get(&self, handle: *const T) -> Result<HandleRef<'_, T>, CApiError> {
+        self.borrow(handle as *mut T, false)?;
+        Ok(HandleRef {
+            table: self,
+            ptr: handle as *mut T,
+        })
+    }
+
+    /// Validates the handle and returns a guard giving exclusive access to the object.
+    ///
+    /// Fails if the handle is currently borrowed.
+    pub fn get_mut(&self, handle: *mut T) -> Result<HandleMut<'_, T>, CApiError> {
+        self.borrow(handle, true)?;
+        Ok(HandleMut {
+            table: self,
+            ptr: handle,
+        })
+    }
+
+    /// Unregisters the handle and returns ownership of the object.
+    ///
+    /// Fails if the handle is currently borrowed.
+    pub fn remove(&self, handle: *mut T) -> Result<Box<T>, CApiError> {
+        if handle.is_null() {
+            return Err(CApiError::NullPointer(self.name));
+        }
+
+        let mut guard = self.live.lock().unwrap();
+        match guard.get(&(handle as usize)) {
+            None => return Err(CApiError::InvalidHandle(self.name, handle as usize)),
+            Some(0) => {},
+            Some(_) => return Err(CApiError::HandleInUse(self.name, handle as usize)),
+        }
+        guard.remove(&(handle as usize));
+        drop(guard);
+
+        // The handle was live and not borrowed so we have exclusive ownership of the object
+        Ok(unsafe { Box::from_raw(handle) })
+    }
+
+    /// Unregisters all handles and returns ownership of the objects. If any handle is invalid,
+    /// currently borrowed or passed multiple times no handle is removed.
+    pub fn remove_many(&self, handles: &[*mut T]) -> Result<Vec<Box<T>>, CApiError> {
+        let mut guard = self.live.lock().unwrap();
+
+        let mut seen = HashSet::with_capacity(handles.len());
+        for handle in handles {
+            if handle.is_null() {
+                return Err(CApiError::NullPointer(self.name));
+            }
+            match guard.get(&(*handle as usize)) {
+                None => return Err(CApiError::InvalidHandle(self.name, *handle as usize)),
+                Some(0) => {},
+                Some(_) => return Err(CApiError::HandleInUse(self.name, *handle as usize)),
+            }
+            if !seen.insert(*handle as usize) {
+                return Err(CApiError::InvalidHandle(self.name, *handle as usize));
+            }
+        }
+        for handle in handles {
+            guard.remove(&(*handle as usize));
+        }
+        drop(guard);
+
+        Ok(handles.iter().map(|handle| unsafe { Box::from_raw(*handle) }).collect())
+    }
+
+    pub fn is_live(&self, handle: *const T) -> bool {
+        self.live.lock().unwrap().contains_key(&(handle as usize))
+    }
+
+    fn borrow(&self, handle: *mut T, exclusive: bool) -> Result<(), CApiError> {
+        if handle.is_null() {
+            return Err(CApiError::NullPointer(self.name));
+        }
+
+        let mut guard = self.live.lock().unwrap();
+        let state = guard.get_mut(&(handle as usize)).ok_or(CApiError::InvalidHandle(self.name, handle as usize))?;
+        if exclusive && *state == 0 {
+            *state = -1;
+        } else if !exclusive && *state >= 0 {
+            *state += 1;
+        } else {
+            return Err(CApiError::HandleInUse(self.name, handle as usize));
+        }
+        Ok(())
+    }
+
+    fn release(&self, handle: *mut T) {
+        let mut guard = self.live.lock().unwrap();
+        let state = guard.get_mut(&(handle as usize)).expect("Released handle is not live");
+        if *state < 0 {
+            *state = 0;
+        } else {
+            *state -= 1;
+        }
+    }
+}
+
+/// Shared access to the object of a handle. The handle cannot be removed while this is alive.
+pub struct HandleRef<'a, T> {
+    table: &'a HandleTable<T>,
+    ptr: *mut T,
+}
+
+impl<'a, T> Deref for HandleRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // The handle is live and not borrowed exclusively as long as we exist
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<'a, T> Drop for HandleRef<'a, T> {
+    fn drop(&mut self) {
+        self.table.release(self.ptr);
+    }
+}
+
+/// Exclusive access to the object of a handle. The handle cannot be accessed by any other call or
+/// removed while this is alive.
+pub struct HandleMut<'a, T> {
+    table: &'a HandleTable<T>,
+    ptr: *mut T,
+}
+
+impl<'a, T> Deref for HandleMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // The handle is live and borrowed exclusively by us
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<'a, T> DerefMut for HandleMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // The handle is live and borrowed exclusively by us
+        unsafe { &mut *self.ptr }
+    }
+}
+
+impl<'a, T> Drop for HandleMut<'a, T> {
+    fn drop(&mut self) {
+        self.table.release(self.ptr);
+    }
+}
+
+/// Converts a pointer and length into a slice.
+///
+/// A null pointer is only accepted if the length is 0.
+///
+/// # Safety
+///
+/// If the pointer is not null it must point to `len` valid elements.
+pub unsafe fn make_slice<'a, T>(name: &'static str, ptr: *const T, len: usize) -> Result<&'a [T], CApiError> {
+    if len == 0 {
+        Ok(&[])
+    } else if ptr.is_null() {
+        Err(CApiError::NullPointer(name))
+    } else if len.checked_mul(std::mem::size_of::<T>()).map_or(true, |size| size > isize::MAX as usize) {
+        Err(CApiError::InvalidSize(name))
+    } else {
+        Ok(std::slice::from_raw_parts(ptr, len))
+    }
+}
+
+pub fn validate_index_type(index_type: i32) -> Result<vk::IndexType, CApiError> {
+    match vk::IndexType::from_raw(index_type) {
+        vk::IndexType::UINT16 => Ok(vk::IndexType::UINT16),
+        vk::IndexType::UINT32 => Ok(vk::IndexType::UINT32),
+        _ => Err(CApiError::InvalidEnum("index_type", index_type as i64)),
+    }
+}
+
+pub fn validate_primitive_topology(topology: i32) -> Result<vk::PrimitiveTopology, CApiError> {
+    let topology = vk::PrimitiveTopology::from_raw(topology);
+    if (vk::PrimitiveTopology::POINT_LIST.as_raw()..=vk::PrimitiveTopology::PATCH_LIST.as_raw()).contains(&topology.as_raw()) {
+        Ok(topology)
+    } else {
+        Err(CApiError::InvalidEnum("primitive_topology", topology.as_raw() as i64))
+    }
+}
+
+/// Validates that the buffers of a mesh are large enough for the specified counts.
+pub fn validate_mesh_sizes(vertex_data_len: usize, index_data_len: usize, vertex_stride: u32, index_count: u32, index_type: vk::IndexType) -> Result<(), CApiError> {
+    if vertex_stride == 0 {
+        return Err(CApiError::InvalidSize("vertex_stride"));
+    }
+    if vertex_data_len == 0 || vertex_data_len % (vertex_stride as usize) != 0 {
+        return Err(CApiError::InvalidSize("vertex_data"));
+    }
+    if index_count == 0 {
+        return Err(CApiError::InvalidSize("index_count"));
+    }
+
+    let index_size = if index_type == vk::IndexType::UINT16 { 2usize } else { 4usize };
+    if (index_count as usize).checked_mul(index_size).map_or(true, |size| size > index_data_len) {
+        return Err(CApiError::InvalidSize("index_data"));
+    }
+
+    Ok(())
+}
+
+/// Validates that all indices of a mesh reference a vertex that exists.
+///
+/// Robust buffer access is not guaranteed to be enabled so out of range indices would result in
+/// out of bounds reads on the gpu. `index_data` must already have been validated to contain at
+/// least `index_count` indices by [`validate_mesh_sizes`].
+pub fn validate_mesh_indices(index_data: &[u8], index_count: u32, index_type: vk::IndexType, vertex_count: usize) -> Result<(), CApiError> {
+    let index_size = if index_type == vk::IndexType::UINT16 { 2usize } else { 4usize };
+    let max_index = index_data[..(index_count as usize) * index_size].chunks_exact(index_size).map(|index| {
+        if index_size == 2 {
+            u16::from_ne_bytes([index[0], index[1]]) as usize
+        } else {
+            u32::from_ne_bytes([index[0], index[1], index[2], index[3]]) as usize
+        }
+    }).max().unwrap_or(0);
+
+    if max_index >= vertex_count {
+        return Err(CApiError::InvalidSize("index_data"));
+    }
+
+    Ok(())
+}
+
+/// Validates that a non empty region is fully contained inside the image.
+pub fn validate_image_region(image_size: Vec2u32, offset: Vec2u32, extent: Vec2u32) -> Result<(), CApiError> {
+    if extent[0] == 0 || extent[1] == 0 {
+        return Err(CApiError::InvalidSize("extent"));
+    }
+    if offset[0].checked_add(extent[0]).map_or(true, |x| x > image_size[0]) ||
+        offset[1].checked_add(extent[1]).map_or(true, |y| y > image_size[1]) {
+        return Err(CApiError::InvalidSize("extent"));
+    }
+    Ok(())
+}
+
+/// Validates that a image write is fully contained inside the image and the data is large enough.
+///
+/// The row stride is specified in texels. A row stride of 0 means the rows are tightly packed.
+pub fn validate_image_write(image_size: Vec2u32, texel_size: u32, data_len: usize, row_stride: u32, offset: Vec2u32, extent: Vec2u32) -> Result<(), CApiError> {
+    validate_image_region(image_size, offset, extent)?;
+    if row_stride != 0 && row_stride < extent[0] {
+        return Err(CApiError::InvalidSize("row_stride"));
+    }
+
+    let row_length = if row_stride == 0 { extent[0] } else { row_stride } as usize;
+    let required_len = row_length.checked_mul((extent[1] - 1) as usize)
+        .and_then(|len| len.checked_add(extent[0] as usize))
+        .and_then(|len| len.checked_mul(texel_size as usize))
+        .ok_or(CApiError::InvalidSize("data"))?;
+    if data_len < required_len {
+        return Err(CApiError::InvalidSize("data"));
+    }
+
+    Ok(())
+}
+
+/// Validates a vertex attribute. The attribute must be fully contained in the vertex.
+pub fn validate_vertex_entry(name: &'static str, stride: u32, offset: u32, format: i32) -> Result<vk::Format, CApiError> {
+    let format = vk::Format::from_raw(format);
+    let format_size = crate::util::format::Format::try_format_for(format)
+        .and_then(|format| format.get_compatibility_class().get_texel_size())
+        .ok_or(CApiError::InvalidEnum(name, format.as_raw() as i64))?;
+    if offset.checked_add(format_size).map_or(true, |end| end > stride) {
+        return Err(CApiError::InvalidSize(name));
+    }
+    Ok(format)
+}
@@ -0,0 +1,87 @@
+//! Vulkan api version negotiation and the feature tier of a device.
+//!
+//! Features which have been promoted to core in newer vulkan versions are probed when configuring
+//! a device and recorded as [`FeatureSource`]s so the rest of the renderer can select code paths
+//! without checking api versions and extensions itself. If timeline semaphores are not available
+//! binary semaphores and fences are used instead.
+
+use crate::instance::instance::VulkanVersion;
+
+/// The capability tier of a device derived from the vulkan version used by the device.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum FeatureTier {
+    Vulkan11,
+    Vulkan12,
+    Vulkan13,
+}
+
+impl FeatureTier {
+    pub fn from_version(version: VulkanVersion) -> Self {
+        if version >= VulkanVersion::VK_1_3 {
+            Self::Vulkan13
+        } else if version >= VulkanVersion::VK_1_2 {
+            Self::Vulkan12
+        } else {
+            Self::Vulkan11
+        }
+    }
+}
+
+/// Where a feature used by the renderer is provided from.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FeatureSource {
+    Unsupported,
+    Extension,
+    Core,
+}
+
+impl FeatureSource {
+    /// Returns the source of a feature promoted to core in `promoted`. `extension_supported` must
+    /// be true if the extension providing the feature is supported by the device.
+    pub fn probe(version: VulkanVersion, promoted: VulkanVersion, extension_supported: bool) -> Self {
+        if version >= promoted {
+            Self::Core
+        } else if extension_supported {
+            Self::Extension
+        } else {
+            Self::Unsupported
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        *self != Self::Unsupported
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DeviceCapabilities {
+    /// The vulkan version used by the device. This is the lower of the instance and device
+    /// version.
+    pub api_version: VulkanVersion,
+    pub tier: FeatureTier,
+
+    /// The dynamicRendering feature of VK_KHR_dynamic_rendering.
+    pub dynamic_rendering: FeatureSource,
+
+    /// The timelineSemaphore feature of VK_KHR_timeline_semaphore. If unsupported binary
+    /// semaphores and fences are used instead.
+    pub timeline_semaphore: FeatureSource,
+
+    /// The synchronization2 feature of VK_KHR_synchronization2. If unsupported barriers and
+    /// submissions are converted to legacy commands. See [`crate::device::legacy_sync`].
+    pub synchronization_2: FeatureSource,
+}
+
+impl DeviceCapabilities {
+    pub fn has_dynamic_rendering(&self) -> bool {
+        self.dynamic_rendering.is_supported()
+    }
+
+    pub fn has_timeline_semaphore(&self) -> bool {
+        self.timeline_semaphore.is_supported()
+    }
+
+    pub fn has_synchronization_2(&self) -> bool {
+        self.synchronization_2.is_supported()
+    }
+}
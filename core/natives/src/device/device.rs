@@ -2,12 +2,16 @@ use core::panic::{UnwindSafe, RefUnwindSafe};
 
 use std::cmp::Ordering;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use ash::prelude::VkResult;
 
 use ash::vk;
 
 use crate::allocator::Allocator;
+use crate::device::capabilities::DeviceCapabilities;
+use crate::device::device_group::DeviceGroup;
 use crate::device::device_utils::DeviceUtils;
+use crate::device::legacy_sync;
 use crate::instance::instance::InstanceContext;
 
 use crate::prelude::*;
@@ -16,17 +20,96 @@ pub struct DeviceFunctions {
     pub instance: Arc<InstanceContext>,
     pub physical_device: vk::PhysicalDevice,
     pub vk: ash::Device,
-    pub synchronization_2_khr: ash::extensions::khr::Synchronization2,
+    /// The pipeline cache used for all pipelines created on this device.
+    pub pipeline_cache: vk::PipelineCache,
+    /// [`None`] if VK_KHR_synchronization2 is not supported. See [`crate::device::legacy_sync`].
+    pub synchronization_2_khr: Option<ash::extensions::khr::Synchronization2>,
     pub timeline_semaphore_khr: ash::extensions::khr::TimelineSemaphore,
     pub push_descriptor_khr: ash::extensions::khr::PushDescriptor,
     pub swapchain_khr: Option<ash::extensions::khr::Swapchain>,
     pub maintenance_4_khr: Option<ash::extensions::khr::Maintenance4>,
+    pub sampler_ycbcr_conversion: bool,
+    /// The maximum sampler anisotropy or [`None`] if anisotropic filtering is not supported.
+    pub max_sampler_anisotropy: Option<f32>,
+    /// True if the device is a non-conformant portability implementation (i.e. MoltenVK).
+    pub portability_subset: bool,
+    pub external_memory: bool,
+    pub dma_buf_import: bool,
+    pub full_screen_exclusive_ext: Option<ash::extensions::ext::FullScreenExclusive>,
+    pub present_wait_khr: Option<ash::extensions::khr::PresentWait>,
+    /// Set if VK_GOOGLE_display_timing is enabled.
+    pub display_timing_google: Option<vk::GoogleDisplayTimingFn>,
+    /// Set if the device has been created from a device group. See [`crate::device::device_group`].
+    pub device_group: Option<DeviceGroup>,
+    /// True if VK_EXT_graphics_pipeline_library is enabled.
+    pub graphics_pipeline_library: bool,
+    #[cfg(unix)]
+    pub external_semaphore_fd_khr: Option<ash::extensions::khr::ExternalSemaphoreFd>,
+    #[cfg(target_os = "linux")]
+    pub external_memory_fd_khr: Option<ash::extensions::khr::ExternalMemoryFd>,
+    #[cfg(windows)]
+    pub external_semaphore_win32_khr: Option<ash::extensions::khr::ExternalSemaphoreWin32>,
+    /// Set if VK_EXT_device_fault is enabled.
+    pub device_fault_ext: Option<vk::ExtDeviceFaultFn>,
+    /// Set if VK_EXT_debug_utils is enabled on the instance. Used to insert debug labels.
+    pub debug_utils_ext: Option<ash::extensions::ext::DebugUtils>,
+    /// Set if VK_NV_device_diagnostic_checkpoints is enabled.
+    pub diagnostic_checkpoints_nv: Option<vk::NvDeviceDiagnosticCheckpointsFn>,
+    /// True if the robustBufferAccess2 feature of VK_EXT_robustness2 is enabled.
+    pub robust_buffer_access_2: bool,
+    /// True if the nullDescriptor feature of VK_EXT_robustness2 is enabled.
+    pub null_descriptor: bool,
+    /// True if the sparseBinding and sparseResidencyImage2D features are enabled.
+    pub sparse_residency: bool,
+    /// True if the bufferDeviceAddress feature is enabled.
+    pub buffer_device_address: bool,
+    /// The api version and feature tier of the device.
+    pub capabilities: DeviceCapabilities,
+    /// Set once any function returned VK_ERROR_DEVICE_LOST.
+    pub device_lost: AtomicBool,
+}
+
+impl DeviceFunctions {
+    /// Marks the device as lost if the result is VK_ERROR_DEVICE_LOST. The result is returned
+    /// unchanged.
+    pub fn check_device_lost<T>(&self, result: VkResult<T>) -> VkResult<T> {
+        if let Err(vk::Result::ERROR_DEVICE_LOST) = &result {
+            if !self.device_lost.swap(true, AtomicOrdering::SeqCst) {
+                log::error!("Device has been lost");
+            }
+        }
+        result
+    }
+
+    /// Records a pipeline barrier using synchronization2 if supported and the legacy barrier
+    /// command otherwise.
+    pub unsafe fn cmd_pipeline_barrier2(&self, command_buffer: vk::CommandBuffer, info: &vk::DependencyInfo) {
+        if let Some(synchronization_2_khr) = &self.synchronization_2_khr {
+            synchronization_2_khr.cmd_pipeline_barrier2(command_buffer, info)
+        } else {
+            legacy_sync::cmd_pipeline_barrier(&self.vk, command_buffer, info)
+        }
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(AtomicOrdering::SeqCst)
+    }
+
+    /// Marks the device as lost without a vulkan function reporting it. Used to force device
+    /// recreation if the device stopped making progress.
+    pub fn mark_device_lost(&self) {
+        if !self.device_lost.swap(true, AtomicOrdering::SeqCst) {
+            log::error!("Device has been marked as lost");
+        }
+    }
 }
 
 impl Drop for DeviceFunctions {
     fn drop(&mut self) {
         unsafe {
-            self.vk.destroy_device(None);
+            self.vk.destroy_pipeline_cache(self.pipeline_cache, None);
+            let allocation_callbacks = self.instance.get_allocation_callbacks();
+            self.vk.destroy_device(allocation_callbacks.as_ref());
         }
     }
 }
@@ -35,6 +118,7 @@ pub struct DeviceContext {
     id: NamedUUID,
     functions: Arc<DeviceFunctions>,
     main_queue: Arc<Queue>,
+    background_queue: Option<Arc<Queue>>,
     async_compute_queue: Option<Arc<Queue>>,
     async_transfer_queue: Option<Arc<Queue>>,
     allocator: Arc<Allocator>,
@@ -45,6 +129,7 @@ impl DeviceContext {
     pub(crate) fn new(
         functions: Arc<DeviceFunctions>,
         main_queue: Arc<Queue>,
+        background_queue: Option<Arc<Queue>>,
         async_compute_queue: Option<Arc<Queue>>,
         async_transfer_queue: Option<Arc<Queue>>,
     ) -> Arc<Self> {
@@ -55,6 +140,7 @@ impl DeviceContext {
             id: NamedUUID::with_str("Device"),
             functions,
             main_queue,
+            background_queue,
             async_compute_queue,
             async_transfer_queue,
             allocator,
@@ -82,8 +168,13 @@ impl DeviceContext {
         &self.functions.vk
     }
 
-    pub fn synchronization_2_khr(&self) -> &ash::extensions::khr::Synchronization2 {
-        &self.functions.synchronization_2_khr
+    pub fn synchronization_2_khr(&self) -> Option<&ash::extensions::khr::Synchronization2> {
+        self.functions.synchronization_2_khr.as_ref()
+    }
+
+    /// See [`DeviceFunctions::cmd_pipeline_barrier2`].
+    pub unsafe fn cmd_pipeline_barrier2(&self, command_buffer: vk::CommandBuffer, info: &vk::DependencyInfo) {
+        self.functions.cmd_pipeline_barrier2(command_buffer, info)
     }
 
     pub fn timeline_semaphore_khr(&self) -> &ash::extensions::khr::TimelineSemaphore {
@@ -94,18 +185,134 @@ impl DeviceContext {
         &self.functions.push_descriptor_khr
     }
 
+    /// Returns the pipeline cache which should be used for all pipelines created on this device.
+    pub fn get_pipeline_cache(&self) -> vk::PipelineCache {
+        self.functions.pipeline_cache
+    }
+
+    /// Returns the current contents of the pipeline cache which can be used to initialize the
+    /// cache of a later device.
+    pub fn get_pipeline_cache_data(&self) -> VkResult<Vec<u8>> {
+        unsafe { self.functions.vk.get_pipeline_cache_data(self.functions.pipeline_cache) }
+    }
+
+    /// Returns true if the device has been lost. Once lost the device must be recreated.
+    pub fn is_device_lost(&self) -> bool {
+        self.functions.is_device_lost()
+    }
+
+    /// Inserts a diagnostic checkpoint into the command buffer if
+    /// VK_NV_device_diagnostic_checkpoints is enabled. See [`crate::device::fault`].
+    pub unsafe fn cmd_set_checkpoint(&self, command_buffer: vk::CommandBuffer, marker: u64) {
+        if let Some(checkpoints) = &self.functions.diagnostic_checkpoints_nv {
+            (checkpoints.cmd_set_checkpoint_nv)(command_buffer, marker as *const std::ffi::c_void);
+        }
+    }
+
+    /// See [`DeviceFunctions::mark_device_lost`].
+    pub fn mark_device_lost(&self) {
+        self.functions.mark_device_lost()
+    }
+
     pub fn swapchain_khr(&self) -> Option<&ash::extensions::khr::Swapchain> {
         self.functions.swapchain_khr.as_ref()
     }
 
+    pub fn debug_utils_ext(&self) -> Option<&ash::extensions::ext::DebugUtils> {
+        self.functions.debug_utils_ext.as_ref()
+    }
+
     pub fn maintenance_4(&self) -> Option<&ash::extensions::khr::Maintenance4> {
         self.functions.maintenance_4_khr.as_ref()
     }
 
+    /// Returns true if the sampler ycbcr conversion feature is enabled on this device.
+    pub fn supports_sampler_ycbcr_conversion(&self) -> bool {
+        self.functions.sampler_ycbcr_conversion
+    }
+
+    /// Returns the maximum sampler anisotropy if the sampler anisotropy feature is enabled.
+    pub fn get_max_sampler_anisotropy(&self) -> Option<f32> {
+        self.functions.max_sampler_anisotropy
+    }
+
+    /// Returns true if the device is a portability subset implementation (for example MoltenVK).
+    pub fn is_portability_subset(&self) -> bool {
+        self.functions.portability_subset
+    }
+
+    /// Returns true if external memory and semaphores can be imported on this device.
+    pub fn supports_external_memory(&self) -> bool {
+        self.functions.external_memory
+    }
+
+    /// Returns true if dma-buf file descriptors can be imported on this device.
+    pub fn supports_dma_buf_import(&self) -> bool {
+        self.functions.dma_buf_import
+    }
+
+    /// Returns true if pipelines can be created from pipeline libraries using
+    /// VK_EXT_graphics_pipeline_library.
+    pub fn supports_graphics_pipeline_library(&self) -> bool {
+        self.functions.graphics_pipeline_library
+    }
+
+    /// Returns true if buffers can be created with
+    /// [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`] and shaders may use physical storage
+    /// buffer pointers.
+    pub fn supports_buffer_device_address(&self) -> bool {
+        self.functions.buffer_device_address
+    }
+
+    pub fn full_screen_exclusive_ext(&self) -> Option<&ash::extensions::ext::FullScreenExclusive> {
+        self.functions.full_screen_exclusive_ext.as_ref()
+    }
+
+    pub fn present_wait_khr(&self) -> Option<&ash::extensions::khr::PresentWait> {
+        self.functions.present_wait_khr.as_ref()
+    }
+
+    pub fn display_timing_google(&self) -> Option<&vk::GoogleDisplayTimingFn> {
+        self.functions.display_timing_google.as_ref()
+    }
+
+    pub fn get_capabilities(&self) -> &DeviceCapabilities {
+        &self.functions.capabilities
+    }
+
+    /// Returns the device group used to distribute the main pass or [`None`] if the device uses a
+    /// single physical device.
+    pub fn get_device_group(&self) -> Option<&DeviceGroup> {
+        self.functions.device_group.as_ref()
+    }
+
     pub fn get_main_queue(&self) -> &Arc<Queue> {
         &self.main_queue
     }
 
+    /// Returns a low priority queue in the same family as the main queue used for work which
+    /// should not delay frames. If the device only supports a single queue the main queue is
+    /// returned.
+    pub fn get_background_queue(&self) -> &Arc<Queue> {
+        self.background_queue.as_ref().unwrap_or(&self.main_queue)
+    }
+
+    /// Returns true if [`DeviceContext::get_background_queue`] is a different queue than the main
+    /// queue.
+    pub fn has_background_queue(&self) -> bool {
+        self.background_queue.is_some()
+    }
+
+    /// Returns the queue used for sparse binding operations if sparse residency is enabled on this
+    /// device. This is the background queue so bindings do not delay frames.
+    pub fn get_sparse_queue(&self) -> Option<&Arc<Queue>> {
+        if self.functions.sparse_residency {
+            Some(self.get_background_queue())
+        } else {
+            None
+        }
+    }
+
     pub fn get_async_compute_queue(&self) -> Option<&Arc<Queue>> {
         self.async_compute_queue.as_ref()
     }
@@ -169,32 +376,37 @@ impl Queue {
         let fence = fence.unwrap_or(vk::Fence::null());
 
         let queue = self.queue.lock().unwrap();
-        self.functions.vk.queue_submit(*queue, submits, fence)
+        self.functions.check_device_lost(self.functions.vk.queue_submit(*queue, submits, fence))
     }
 
     pub unsafe fn submit_2(&self, submits: &[vk::SubmitInfo2], fence: Option<vk::Fence>) -> VkResult<()> {
         let fence = fence.unwrap_or(vk::Fence::null());
 
         let queue = self.queue.lock().unwrap();
-        self.functions.synchronization_2_khr.queue_submit2(*queue, submits, fence)
+        let result = if let Some(synchronization_2_khr) = &self.functions.synchronization_2_khr {
+            synchronization_2_khr.queue_submit2(*queue, submits, fence)
+        } else {
+            legacy_sync::queue_submit(&self.functions, *queue, submits, fence)
+        };
+        self.functions.check_device_lost(result)
     }
 
     pub unsafe fn wait_idle(&self) -> VkResult<()> {
         let queue = self.queue.lock().unwrap();
-        self.functions.vk.queue_wait_idle(*queue)
+        self.functions.check_device_lost(self.functions.vk.queue_wait_idle(*queue))
     }
 
     pub unsafe fn bind_sparse(&self, bindings: &[vk::BindSparseInfo], fence: Option<vk::Fence>) -> VkResult<()> {
         let fence = fence.unwrap_or(vk::Fence::null());
 
         let queue = self.queue.lock().unwrap();
-        self.functions.vk.queue_bind_sparse(*queue, bindings, fence)
+        self.functions.check_device_lost(self.functions.vk.queue_bind_sparse(*queue, bindings, fence))
     }
 
     // TODO this also needs to lock the swapchain. How do we properly deal with this?
     pub unsafe fn present(&self, present_info: &vk::PresentInfoKHR) -> VkResult<bool> {
         let queue = self.queue.lock().unwrap();
-        self.functions.swapchain_khr.as_ref().unwrap().queue_present(*queue, present_info)
+        self.functions.check_device_lost(self.functions.swapchain_khr.as_ref().unwrap().queue_present(*queue, present_info))
     }
 
     pub fn lock_queue(&self) -> MutexGuard<vk::Queue> {
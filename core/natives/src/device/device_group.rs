@@ -0,0 +1,113 @@
+//! Experimental support for linked gpus exposed as a vulkan device group.
+//!
+//! If enabled the main pass of each frame is either rendered on alternating physical devices or
+//! split into horizontal strips rendered by every device of the group. All other work (global
+//! object uploads, offscreen and background passes) is executed on all devices so every device
+//! holds a full copy of global resources and the per frame resources of any frame slot can be used
+//! by any device.
+
+use ash::prelude::VkResult;
+use ash::vk;
+
+use crate::prelude::*;
+
+/// How the main pass is distributed over the devices of a device group.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum DeviceGroupMode {
+    /// Successive main passes are rendered on successive devices. Requires the
+    /// VK_DEVICE_GROUP_PRESENT_MODE_LOCAL_MULTI_DEVICE_BIT_KHR present mode.
+    AlternateFrame = 1,
+    /// Every device renders a horizontal strip of each main pass. The strips are combined by the
+    /// presentation engine which requires the VK_DEVICE_GROUP_PRESENT_MODE_SUM_BIT_KHR present
+    /// mode.
+    SplitFrame = 2,
+}
+
+impl DeviceGroupMode {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::AlternateFrame),
+            2 => Some(Self::SplitFrame),
+            _ => None,
+        }
+    }
+
+    /// Returns the device group present mode required by this mode.
+    pub fn get_required_present_mode(&self) -> vk::DeviceGroupPresentModeFlagsKHR {
+        match self {
+            Self::AlternateFrame => vk::DeviceGroupPresentModeFlagsKHR::LOCAL_MULTI_DEVICE,
+            Self::SplitFrame => vk::DeviceGroupPresentModeFlagsKHR::SUM,
+        }
+    }
+}
+
+/// The device group a device has been created with.
+#[derive(Clone, Debug)]
+pub struct DeviceGroup {
+    mode: DeviceGroupMode,
+    physical_devices: Box<[vk::PhysicalDevice]>,
+}
+
+impl DeviceGroup {
+    pub(crate) fn new(mode: DeviceGroupMode, physical_devices: Box<[vk::PhysicalDevice]>) -> Self {
+        Self {
+            mode,
+            physical_devices,
+        }
+    }
+
+    pub fn get_mode(&self) -> DeviceGroupMode {
+        self.mode
+    }
+
+    /// Returns the physical devices of the group ordered by their device index.
+    pub fn get_physical_devices(&self) -> &[vk::PhysicalDevice] {
+        &self.physical_devices
+    }
+
+    pub fn get_device_count(&self) -> u32 {
+        self.physical_devices.len() as u32
+    }
+
+    /// Returns a device mask containing all devices of the group.
+    pub fn get_all_devices_mask(&self) -> u32 {
+        (1u32 << self.get_device_count()) - 1
+    }
+
+    /// Returns the device mask of the main pass with index `pass_index` where the index counts
+    /// the main passes started on this device.
+    pub fn get_pass_device_mask(&self, pass_index: u64) -> u32 {
+        match self.mode {
+            DeviceGroupMode::AlternateFrame => 1u32 << (pass_index % (self.get_device_count() as u64)),
+            DeviceGroupMode::SplitFrame => self.get_all_devices_mask(),
+        }
+    }
+
+    /// Returns the area of a framebuffer of the provided size rendered by each device in split
+    /// frame mode indexed by the device index.
+    pub fn get_split_areas(&self, size: Vec2u32) -> Box<[vk::Rect2D]> {
+        let count = self.get_device_count();
+        (0..count).map(|index| {
+            let min_y = size[1] * index / count;
+            let max_y = size[1] * (index + 1) / count;
+            vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: min_y as i32 },
+                extent: vk::Extent2D { width: size[0], height: max_y - min_y },
+            }
+        }).collect()
+    }
+}
+
+/// Returns the physical devices of the device group containing `physical_device` if the group
+/// contains more than one device.
+pub(crate) fn find_device_group(instance: &InstanceContext, physical_device: vk::PhysicalDevice) -> VkResult<Option<Box<[vk::PhysicalDevice]>>> {
+    let count = unsafe { instance.vk().enumerate_physical_device_groups_len() }?;
+    let mut groups = vec![vk::PhysicalDeviceGroupProperties::default(); count];
+    unsafe { instance.vk().enumerate_physical_device_groups(&mut groups) }?;
+
+    Ok(groups.iter()
+        .map(|group| &group.physical_devices[0..(group.physical_device_count as usize)])
+        .find(|devices| devices.len() > 1 && devices.contains(&physical_device))
+        .map(|devices| devices.into()))
+}
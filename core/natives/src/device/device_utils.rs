@@ -37,6 +37,73 @@ impl DeviceUtils {
     }
 }
 
+/// A clockwise rotation applied by full screen passes to pre-rotate their output for a surface
+/// transform. Rotated outputs must use a framebuffer with swapped width and height.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum OutputRotation {
+    None = 0,
+    Rotate90 = 1,
+    Rotate180 = 2,
+    Rotate270 = 3,
+}
+
+impl OutputRotation {
+    /// Returns the rotation required for a swapchain created with the transform. Mirrored
+    /// transforms are not supported and treated like their unmirrored counterpart.
+    pub fn from_surface_transform(transform: vk::SurfaceTransformFlagsKHR) -> Self {
+        if transform.intersects(vk::SurfaceTransformFlagsKHR::ROTATE_90 | vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_90) {
+            Self::Rotate90
+        } else if transform.intersects(vk::SurfaceTransformFlagsKHR::ROTATE_180 | vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_180) {
+            Self::Rotate180
+        } else if transform.intersects(vk::SurfaceTransformFlagsKHR::ROTATE_270 | vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_270) {
+            Self::Rotate270
+        } else {
+            Self::None
+        }
+    }
+
+    /// Returns true if the rotation swaps the width and height of the output.
+    pub fn swaps_extent(&self) -> bool {
+        matches!(self, Self::Rotate90 | Self::Rotate270)
+    }
+
+    /// Converts a size between the rotated and unrotated space.
+    pub fn apply_to_size(&self, size: Vec2u32) -> Vec2u32 {
+        if self.swaps_extent() {
+            Vec2u32::new(size[1], size[0])
+        } else {
+            size
+        }
+    }
+}
+
+/// Specialization data selecting the rotation of the full screen quad vertex shader.
+pub(crate) struct RotationSpecialization {
+    entry: vk::SpecializationMapEntry,
+    data: [u8; 4],
+}
+
+impl RotationSpecialization {
+    pub(crate) fn new(rotation: OutputRotation) -> Self {
+        Self {
+            entry: vk::SpecializationMapEntry {
+                constant_id: 0,
+                offset: 0,
+                size: 4,
+            },
+            data: (rotation as u32).to_ne_bytes(),
+        }
+    }
+
+    pub(crate) fn get_info(&self) -> vk::SpecializationInfo {
+        vk::SpecializationInfo::builder()
+            .map_entries(std::slice::from_ref(&self.entry))
+            .data(&self.data)
+            .build()
+    }
+}
+
 pub struct BlitUtils {
     utils: Weak<DeviceUtils>,
     device: Arc<DeviceFunctions>,
@@ -66,9 +133,10 @@ impl BlitUtils {
         }
     }
 
-    pub fn create_blit_pass(&self, dst_format: vk::Format, load_op: vk::AttachmentLoadOp, initial_layout: vk::ImageLayout, final_layout: vk::ImageLayout) -> BlitPass {
+    /// Creates a blit pass. The rotation is applied to the blitted image, see [`OutputRotation`].
+    pub fn create_blit_pass(&self, dst_format: vk::Format, load_op: vk::AttachmentLoadOp, initial_layout: vk::ImageLayout, final_layout: vk::ImageLayout, rotation: OutputRotation) -> BlitPass {
         let render_pass = self.create_render_pass(dst_format, load_op, initial_layout, final_layout);
-        let pipeline = self.create_pipeline(render_pass);
+        let pipeline = self.create_pipeline(render_pass, rotation);
 
         BlitPass {
             utils: self.utils.upgrade().unwrap(),
@@ -104,12 +172,16 @@ impl BlitUtils {
         }.unwrap()
     }
 
-    fn create_pipeline(&self, render_pass: vk::RenderPass) -> vk::Pipeline {
+    fn create_pipeline(&self, render_pass: vk::RenderPass, rotation: OutputRotation) -> vk::Pipeline {
+        let specialization = RotationSpecialization::new(rotation);
+        let specialization_info = specialization.get_info();
+
         let shader_stages = [
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(self.vertex_shader)
                 .name(CStr::from_bytes_with_nul(b"main\0").unwrap())
+                .specialization_info(&specialization_info)
                 .build(),
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::FRAGMENT)
@@ -174,7 +246,7 @@ impl BlitUtils {
             .render_pass(render_pass);
 
         let pipeline = * unsafe {
-            self.device.vk.create_graphics_pipelines(vk::PipelineCache::null(), std::slice::from_ref(&info), None)
+            self.device.vk.create_graphics_pipelines(self.device.pipeline_cache, std::slice::from_ref(&info), None)
         }.unwrap().get(0).unwrap();
 
         pipeline
@@ -303,7 +375,10 @@ impl BlitPass {
     ///
     /// The framebuffer image will be used in the COLOR_ATTACHMENT_OUTPUT stage and the sampled image
     /// in the FRAGMENT_SHADER stage. The sampled image must be in the SHADER_READ_OPTIMAL layout.
-    pub fn record_blit(&self, command_buffer: vk::CommandBuffer, descriptor_set: vk::DescriptorSet, framebuffer: vk::Framebuffer, size: Vec2u32, clear_value: Option<&vk::ClearValue>) {
+    ///
+    /// If device scissors are provided the command buffer must be executed by a device group and
+    /// each device only draws into the scissor at its device index.
+    pub fn record_blit(&self, command_buffer: vk::CommandBuffer, descriptor_set: vk::DescriptorSet, framebuffer: vk::Framebuffer, size: Vec2u32, clear_value: Option<&vk::ClearValue>, device_scissors: Option<&[vk::Rect2D]>) {
         let device = &self.utils.blit_utils.device;
 
         let mut info = vk::RenderPassBeginInfo::builder()
@@ -348,7 +423,16 @@ impl BlitPass {
                 &[]
             );
 
-            device.vk.cmd_draw(command_buffer, 4, 1, 0, 0);
+            if let Some(device_scissors) = device_scissors {
+                for (index, scissor) in device_scissors.iter().enumerate() {
+                    device.vk.cmd_set_device_mask(command_buffer, 1u32 << index);
+                    device.vk.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(scissor));
+                    device.vk.cmd_draw(command_buffer, 4, 1, 0, 0);
+                }
+                device.vk.cmd_set_device_mask(command_buffer, (1u32 << device_scissors.len()) - 1);
+            } else {
+                device.vk.cmd_draw(command_buffer, 4, 1, 0, 0);
+            }
 
             device.vk.cmd_end_render_pass(command_buffer);
         }
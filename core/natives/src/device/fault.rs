@@ -0,0 +1,165 @@
+//! Collection of device fault information after the device has been lost.
+//!
+//! If VK_EXT_device_fault is enabled the driver provides a description of the fault, the faulting
+//! addresses and vendor specific fault codes. If VK_NV_device_diagnostic_checkpoints is enabled
+//! the worker inserts a checkpoint at the start of every pass using the raw
+//! [`crate::renderer::emulator::PassId`] as marker so the last pass reached by the gpu can be
+//! identified.
+
+use std::ffi::CStr;
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_char;
+
+use ash::prelude::VkResult;
+use ash::vk;
+
+use crate::prelude::*;
+
+/// A faulting address reported by the driver.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FaultAddress {
+    pub address_type: vk::DeviceFaultAddressTypeEXT,
+    pub address: vk::DeviceAddress,
+    pub precision: vk::DeviceSize,
+}
+
+/// A vendor specific fault code reported by the driver.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FaultVendorInfo {
+    pub description: String,
+    pub code: u64,
+    pub data: u64,
+}
+
+/// The last checkpoint reached by the gpu in some pipeline stage.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Checkpoint {
+    pub stage: vk::PipelineStageFlags,
+    /// The raw id of the pass which set the checkpoint.
+    pub marker: u64,
+}
+
+/// All fault information which could be collected after the device has been lost.
+#[derive(Clone, Debug, Default)]
+pub struct FaultReport {
+    /// The driver provided description of the fault or [`None`] if VK_EXT_device_fault is not
+    /// available.
+    pub description: Option<String>,
+    pub addresses: Vec<FaultAddress>,
+    pub vendor_infos: Vec<FaultVendorInfo>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl FaultReport {
+    /// Returns true if no information could be collected.
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none() && self.addresses.is_empty() && self.vendor_infos.is_empty() && self.checkpoints.is_empty()
+    }
+
+    /// Writes the report to the log.
+    pub fn log(&self) {
+        for line in self.to_string().lines() {
+            log::error!("{}", line);
+        }
+    }
+}
+
+impl Display for FaultReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No device fault information available");
+        }
+
+        if let Some(description) = &self.description {
+            writeln!(f, "Device fault: {}", description)?;
+        }
+        for address in &self.addresses {
+            writeln!(f, "    Fault address {:#018x} (precision {:#x}) type {:?}", address.address, address.precision, address.address_type)?;
+        }
+        for info in &self.vendor_infos {
+            writeln!(f, "    Vendor fault {:#x} data {:#x}: {}", info.code, info.data, info.description)?;
+        }
+        for checkpoint in &self.checkpoints {
+            writeln!(f, "    Last checkpoint in stage {:?}: pass {}", checkpoint.stage, checkpoint.marker)?;
+        }
+        Ok(())
+    }
+}
+
+/// Queries all available fault information. Must only be called after the device has been lost.
+pub fn collect_fault_report(device: &DeviceContext) -> FaultReport {
+    let mut report = FaultReport::default();
+
+    let functions = device.get_functions();
+    if let Some(device_fault) = &functions.device_fault_ext {
+        match unsafe { query_device_fault(functions.vk.handle(), device_fault) } {
+            Ok((description, addresses, vendor_infos)) => {
+                report.description = Some(description);
+                report.addresses = addresses;
+                report.vendor_infos = vendor_infos;
+            }
+            Err(err) => log::warn!("vkGetDeviceFaultInfoEXT returned {:?}", err),
+        }
+    }
+
+    if let Some(checkpoints) = &functions.diagnostic_checkpoints_nv {
+        let queue = device.get_main_queue().lock_queue();
+        report.checkpoints = unsafe { query_checkpoints(*queue, checkpoints) };
+    }
+
+    report
+}
+
+unsafe fn query_device_fault(device: vk::Device, device_fault: &vk::ExtDeviceFaultFn) -> VkResult<(String, Vec<FaultAddress>, Vec<FaultVendorInfo>)> {
+    let mut counts = vk::DeviceFaultCountsEXT::default();
+    (device_fault.get_device_fault_info_ext)(device, &mut counts, std::ptr::null_mut()).result()?;
+
+    let mut addresses = vec![vk::DeviceFaultAddressInfoEXT::default(); counts.address_info_count as usize];
+    let mut vendor_infos = vec![vk::DeviceFaultVendorInfoEXT::default(); counts.vendor_info_count as usize];
+    // We do not request the vendor binary since its format is only understood by vendor tools
+    counts.vendor_binary_size = 0;
+
+    let mut info = vk::DeviceFaultInfoEXT::default();
+    info.p_address_infos = addresses.as_mut_ptr();
+    info.p_vendor_infos = vendor_infos.as_mut_ptr();
+    match (device_fault.get_device_fault_info_ext)(device, &mut counts, &mut info) {
+        // Incomplete is returned if the counts changed between the calls. The returned data is still valid
+        vk::Result::SUCCESS | vk::Result::INCOMPLETE => {},
+        err => return Err(err),
+    }
+
+    addresses.truncate(counts.address_info_count as usize);
+    vendor_infos.truncate(counts.vendor_info_count as usize);
+
+    Ok((
+        c_string(&info.description),
+        addresses.into_iter().map(|address| FaultAddress {
+            address_type: address.address_type,
+            address: address.reported_address,
+            precision: address.address_precision,
+        }).collect(),
+        vendor_infos.into_iter().map(|info| FaultVendorInfo {
+            description: c_string(&info.description),
+            code: info.vendor_fault_code,
+            data: info.vendor_fault_data,
+        }).collect()
+    ))
+}
+
+unsafe fn query_checkpoints(queue: vk::Queue, checkpoints: &vk::NvDeviceDiagnosticCheckpointsFn) -> Vec<Checkpoint> {
+    let mut count = 0u32;
+    (checkpoints.get_queue_checkpoint_data_nv)(queue, &mut count, std::ptr::null_mut());
+
+    let mut data = vec![vk::CheckpointDataNV::default(); count as usize];
+    (checkpoints.get_queue_checkpoint_data_nv)(queue, &mut count, data.as_mut_ptr());
+    data.truncate(count as usize);
+
+    data.into_iter().map(|data| Checkpoint {
+        stage: data.stage,
+        marker: data.p_checkpoint_marker as u64,
+    }).collect()
+}
+
+fn c_string(data: &[c_char]) -> String {
+    unsafe { CStr::from_ptr(data.as_ptr()) }.to_string_lossy().into_owned()
+}
@@ -1,13 +1,18 @@
 use std::collections::HashSet;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
+use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
+use ash::prelude::VkResult;
 use ash::vk;
 use bumpalo::Bump;
 use vk_profiles_rs::{vp, VulkanProfiles};
 
 use crate::device::device::{DeviceFunctions, Queue};
+use crate::device::capabilities::{DeviceCapabilities, FeatureSource, FeatureTier};
+use crate::device::device_group::{find_device_group, DeviceGroup, DeviceGroupMode};
 use crate::instance::instance::{InstanceContext, VulkanVersion};
 
 use crate::prelude::*;
@@ -16,6 +21,17 @@ use crate::prelude::*;
 pub struct DeviceCreateConfig {
     used_surfaces: Vec<vk::SurfaceKHR>,
     disable_robustness: bool,
+    robustness_2: bool,
+    external_memory: bool,
+    full_screen_exclusive: bool,
+    present_wait: bool,
+    display_timing: bool,
+    fault_reporting: bool,
+    sparse_residency: bool,
+    buffer_device_address: bool,
+    device_group: Option<DeviceGroupMode>,
+    device_preference: DevicePreference,
+    pipeline_cache_data: Option<PipelineCacheData>,
     required_extensions: HashSet<CString>,
 }
 
@@ -25,9 +41,32 @@ impl DeviceCreateConfig {
             used_surfaces: Vec::new(),
             required_extensions: HashSet::new(),
             disable_robustness: false,
+            robustness_2: false,
+            external_memory: false,
+            full_screen_exclusive: false,
+            present_wait: false,
+            display_timing: false,
+            fault_reporting: false,
+            sparse_residency: false,
+            buffer_device_address: false,
+            device_group: None,
+            device_preference: DevicePreference::Default,
+            pipeline_cache_data: None,
         }
     }
 
+    /// Configures which type of physical device should be preferred if multiple devices are
+    /// supported.
+    pub fn set_device_preference(&mut self, preference: DevicePreference) {
+        self.device_preference = preference;
+    }
+
+    /// Sets the initial data of the device pipeline cache. If the data is not compatible with the
+    /// selected device it is ignored.
+    pub fn set_pipeline_cache_data(&mut self, data: Vec<u8>) {
+        self.pipeline_cache_data = Some(PipelineCacheData(data));
+    }
+
     pub fn add_surface(&mut self, surface: vk::SurfaceKHR) {
         self.used_surfaces.push(surface);
     }
@@ -36,6 +75,63 @@ impl DeviceCreateConfig {
         self.disable_robustness = true;
     }
 
+    /// Enables the robustBufferAccess2 and nullDescriptor features of VK_EXT_robustness2 if they
+    /// are supported. Out of bounds accesses then return zero instead of undefined values which
+    /// helps to isolate memory corruption bugs. Has no effect if robustness is disabled.
+    pub fn enable_robustness_2(&mut self) {
+        self.robustness_2 = true;
+    }
+
+    /// Requires support for importing external memory and semaphores using the platform native
+    /// handle types.
+    pub fn require_external_memory(&mut self) {
+        self.external_memory = true;
+    }
+
+    /// Enables VK_EXT_full_screen_exclusive if it is supported. The instance must have
+    /// VK_KHR_get_surface_capabilities2 enabled for the extension to be used.
+    pub fn enable_full_screen_exclusive(&mut self) {
+        self.full_screen_exclusive = true;
+    }
+
+    /// Enables VK_KHR_present_id and VK_KHR_present_wait if they are supported.
+    pub fn enable_present_wait(&mut self) {
+        self.present_wait = true;
+    }
+
+    /// Enables VK_GOOGLE_display_timing if it is supported.
+    pub fn enable_display_timing(&mut self) {
+        self.display_timing = true;
+    }
+
+    /// Enables VK_EXT_device_fault and VK_NV_device_diagnostic_checkpoints if they are supported.
+    /// See [`crate::device::fault`].
+    pub fn enable_fault_reporting(&mut self) {
+        self.fault_reporting = true;
+    }
+
+    /// Enables the sparseBinding and sparseResidencyImage2D features if they are supported and the
+    /// main queue family supports sparse binding operations. See
+    /// [`DeviceContext::get_sparse_queue`].
+    pub fn enable_sparse_residency(&mut self) {
+        self.sparse_residency = true;
+    }
+
+    /// Enables the bufferDeviceAddress feature if it is supported. This allows shaders to access
+    /// buffers through physical storage buffer pointers. See
+    /// [`DeviceContext::supports_buffer_device_address`].
+    pub fn enable_buffer_device_address(&mut self) {
+        self.buffer_device_address = true;
+    }
+
+    /// Experimental. Creates the device from all physical devices of the device group containing
+    /// the selected physical device. The group is only used if it contains multiple devices and
+    /// supports the present mode required by `mode` on all used surfaces. See
+    /// [`crate::device::device_group`].
+    pub fn enable_device_group(&mut self, mode: DeviceGroupMode) {
+        self.device_group = Some(mode);
+    }
+
     pub fn add_required_extension(&mut self, extension: &CStr) {
         self.required_extensions.insert(CString::from(extension));
     }
@@ -45,6 +141,51 @@ impl DeviceCreateConfig {
     }
 }
 
+/// Returns the device group present modes supported by the device and all surfaces.
+fn query_device_group_present_modes(swapchain_khr: &ash::extensions::khr::Swapchain, surfaces: &[vk::SurfaceKHR]) -> VkResult<vk::DeviceGroupPresentModeFlagsKHR> {
+    let mut capabilities = vk::DeviceGroupPresentCapabilitiesKHR::default();
+    unsafe { swapchain_khr.get_device_group_present_capabilities(&mut capabilities) }?;
+
+    let mut modes = capabilities.modes;
+    for surface in surfaces {
+        modes &= unsafe { swapchain_khr.get_device_group_surface_present_modes(*surface) }?;
+    }
+    Ok(modes)
+}
+
+/// Wrapper to avoid printing the full cache contents in debug output.
+struct PipelineCacheData(Vec<u8>);
+
+impl Debug for PipelineCacheData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("PipelineCacheData({} bytes)", self.0.len()))
+    }
+}
+
+/// The type of physical device which should be preferred during device selection.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum DevicePreference {
+    /// Use the first supported device.
+    Default = 0,
+    /// Prefer discrete gpus over all other devices.
+    Discrete = 1,
+    /// Prefer integrated gpus over all other devices. Useful to reduce power usage on laptops.
+    Integrated = 2,
+}
+
+impl DevicePreference {
+    fn rate(&self, device_type: vk::PhysicalDeviceType) -> f32 {
+        match (self, device_type) {
+            (DevicePreference::Discrete, vk::PhysicalDeviceType::DISCRETE_GPU) => 2.0,
+            (DevicePreference::Discrete, vk::PhysicalDeviceType::INTEGRATED_GPU) => 1.0,
+            (DevicePreference::Integrated, vk::PhysicalDeviceType::INTEGRATED_GPU) => 2.0,
+            (DevicePreference::Integrated, vk::PhysicalDeviceType::DISCRETE_GPU) => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum DeviceCreateError {
     Vulkan(vk::Result),
@@ -76,10 +217,13 @@ pub fn create_device(config: DeviceCreateConfig, instance: Arc<InstanceContext>)
     )?;
 
     let priority = 1f32;
+    // The background queue uses the lowest priority so it only uses resources the main queue leaves idle
+    let main_family_priorities = [1f32, 0f32];
+    let main_family_queue_count = if device_config.has_background_queue { 2 } else { 1 };
     let mut queue_create_infos = Vec::with_capacity(3);
     queue_create_infos.push(vk::DeviceQueueCreateInfo::builder()
         .queue_family_index(device_config.main_queue_family)
-        .queue_priorities(std::slice::from_ref(&priority))
+        .queue_priorities(&main_family_priorities[0..main_family_queue_count])
         .build()
     );
     if let Some(family) = &device_config.async_compute_family {
@@ -97,7 +241,21 @@ pub fn create_device(config: DeviceCreateConfig, instance: Arc<InstanceContext>)
         );
     }
 
-    let device_create_info = device_create_info.queue_create_infos(queue_create_infos.as_slice());
+    let mut device_create_info = device_create_info.queue_create_infos(queue_create_infos.as_slice());
+
+    let group_devices = match config.device_group {
+        Some(_) => find_device_group(&instance, physical_device)?,
+        None => None,
+    };
+    let mut group_info = group_devices.as_ref().map(|devices| {
+        vk::DeviceGroupDeviceCreateInfo::builder()
+            .physical_devices(devices)
+    });
+    if let Some(group_info) = group_info.as_mut() {
+        device_create_info = device_create_info.push_next(group_info);
+    } else if config.device_group.is_some() {
+        log::info!("Selected physical device is not part of a device group with multiple devices");
+    }
 
     let mut flags = vp::DeviceCreateFlagBits::MERGE_EXTENSIONS | vp::DeviceCreateFlagBits::OVERRIDE_FEATURES;
     if config.disable_robustness {
@@ -112,9 +270,14 @@ pub fn create_device(config: DeviceCreateConfig, instance: Arc<InstanceContext>)
     let selected_properties = unsafe { instance.vk().get_physical_device_properties(physical_device) };
     let selected_device_name = unsafe { CStr::from_ptr(selected_properties.device_name.as_ptr()) };
     log::info!("Selected device {:?} with config {:?}", selected_device_name, device_config);
-    let device = unsafe { vk_vp.create_device(instance.vk(), physical_device, &vp_device_create_info, None)? };
+    let allocation_callbacks = instance.get_allocation_callbacks();
+    let device = unsafe { vk_vp.create_device(instance.vk(), physical_device, &vp_device_create_info, allocation_callbacks.as_ref())? };
 
-    let synchronization_2_khr = ash::extensions::khr::Synchronization2::new(instance.vk(), &device);
+    let synchronization_2_khr = if device_config.capabilities.has_synchronization_2() {
+        Some(ash::extensions::khr::Synchronization2::new(instance.vk(), &device))
+    } else {
+        None
+    };
     let timeline_semaphore_khr = ash::extensions::khr::TimelineSemaphore::new(instance.vk(), &device);
     let push_descriptor_khr = ash::extensions::khr::PushDescriptor::new(instance.vk(), &device);
 
@@ -130,18 +293,133 @@ pub fn create_device(config: DeviceCreateConfig, instance: Arc<InstanceContext>)
         None
     };
 
+    #[cfg(unix)]
+    let external_semaphore_fd_khr = if device_config.has_external_memory {
+        Some(ash::extensions::khr::ExternalSemaphoreFd::new(instance.vk(), &device))
+    } else {
+        None
+    };
+    #[cfg(target_os = "linux")]
+    let external_memory_fd_khr = if device_config.has_dma_buf_import {
+        Some(ash::extensions::khr::ExternalMemoryFd::new(instance.vk(), &device))
+    } else {
+        None
+    };
+    #[cfg(windows)]
+    let external_semaphore_win32_khr = if device_config.has_external_memory {
+        Some(ash::extensions::khr::ExternalSemaphoreWin32::new(instance.vk(), &device))
+    } else {
+        None
+    };
+
+    let device_group = match (config.device_group, group_devices) {
+        (Some(mode), Some(devices)) => {
+            let supported = swapchain_khr.as_ref().map_or(Ok(vk::DeviceGroupPresentModeFlagsKHR::empty()), |swapchain_khr| {
+                query_device_group_present_modes(swapchain_khr, &config.used_surfaces)
+            }).unwrap_or_else(|err| {
+                log::warn!("Failed to query device group present modes {:?}", err);
+                vk::DeviceGroupPresentModeFlagsKHR::empty()
+            });
+            if supported.contains(mode.get_required_present_mode()) {
+                log::info!("Using device group of {} devices in {:?} mode", devices.len(), mode);
+                Some(DeviceGroup::new(mode, devices))
+            } else {
+                // The device has already been created with the group so all work is executed on
+                // all devices but the main pass is not distributed
+                log::warn!("Device group does not support present mode {:?} required by {:?} (supported {:?})", mode.get_required_present_mode(), mode, supported);
+                None
+            }
+        }
+        _ => None,
+    };
+
+    let debug_utils_ext = if instance.is_extension_enabled(ash::extensions::ext::DebugUtils::name()) {
+        Some(ash::extensions::ext::DebugUtils::new(instance.get_entry(), instance.vk()))
+    } else {
+        None
+    };
+
+    let full_screen_exclusive_ext = if device_config.has_full_screen_exclusive {
+        Some(ash::extensions::ext::FullScreenExclusive::new(instance.vk(), &device))
+    } else {
+        None
+    };
+
+    let present_wait_khr = if device_config.has_present_wait {
+        Some(ash::extensions::khr::PresentWait::new(instance.vk(), &device))
+    } else {
+        None
+    };
+
+    let display_timing_google = if device_config.has_display_timing {
+        Some(vk::GoogleDisplayTimingFn::load(|name| unsafe {
+            std::mem::transmute((instance.vk().fp_v1_0().get_device_proc_addr)(device.handle(), name.as_ptr()))
+        }))
+    } else {
+        None
+    };
+
+    let device_fault_ext = if device_config.has_device_fault {
+        Some(vk::ExtDeviceFaultFn::load(|name| unsafe {
+            std::mem::transmute((instance.vk().fp_v1_0().get_device_proc_addr)(device.handle(), name.as_ptr()))
+        }))
+    } else {
+        None
+    };
+
+    let diagnostic_checkpoints_nv = if device_config.has_diagnostic_checkpoints {
+        Some(vk::NvDeviceDiagnosticCheckpointsFn::load(|name| unsafe {
+            std::mem::transmute((instance.vk().fp_v1_0().get_device_proc_addr)(device.handle(), name.as_ptr()))
+        }))
+    } else {
+        None
+    };
+
+    let pipeline_cache = create_pipeline_cache(&device, config.pipeline_cache_data.as_ref().map(|data| data.0.as_slice()))?;
+
     let functions = Arc::new(DeviceFunctions {
         instance,
         physical_device,
         vk: device,
+        pipeline_cache,
         synchronization_2_khr,
         timeline_semaphore_khr,
         push_descriptor_khr,
         swapchain_khr,
-        maintenance_4_khr
+        maintenance_4_khr,
+        sampler_ycbcr_conversion: device_config.has_sampler_ycbcr_conversion,
+        max_sampler_anisotropy: device_config.max_sampler_anisotropy,
+        portability_subset: device_config.has_portability_subset,
+        external_memory: device_config.has_external_memory,
+        dma_buf_import: device_config.has_dma_buf_import,
+        full_screen_exclusive_ext,
+        present_wait_khr,
+        display_timing_google,
+        device_group,
+        graphics_pipeline_library: device_config.has_graphics_pipeline_library,
+        #[cfg(unix)]
+        external_semaphore_fd_khr,
+        #[cfg(target_os = "linux")]
+        external_memory_fd_khr,
+        #[cfg(windows)]
+        external_semaphore_win32_khr,
+        device_fault_ext,
+        debug_utils_ext,
+        diagnostic_checkpoints_nv,
+        robust_buffer_access_2: device_config.has_robust_buffer_access_2,
+        null_descriptor: device_config.has_null_descriptor,
+        sparse_residency: device_config.has_sparse_residency,
+        buffer_device_address: device_config.has_buffer_device_address,
+        capabilities: device_config.capabilities,
+        device_lost: AtomicBool::new(false),
     });
 
     let main_queue = Arc::new(Queue::new(functions.clone(), device_config.main_queue_family, 0));
+    let background_queue = if device_config.has_background_queue {
+        Some(Arc::new(Queue::new(functions.clone(), device_config.main_queue_family, 1)))
+    } else {
+        None
+    };
     let async_compute_queue = device_config.async_compute_family.map(|family| {
         Arc::new(Queue::new(functions.clone(), family, 0))
     });
@@ -152,11 +430,27 @@ pub fn create_device(config: DeviceCreateConfig, instance: Arc<InstanceContext>)
     Ok(DeviceContext::new(
         functions,
         main_queue,
+        background_queue,
         async_compute_queue,
         async_transfer_queue
     ))
 }
 
+fn create_pipeline_cache(device: &ash::Device, initial_data: Option<&[u8]>) -> Result<vk::PipelineCache, DeviceCreateError> {
+    if let Some(data) = initial_data {
+        let info = vk::PipelineCacheCreateInfo::builder()
+            .initial_data(data);
+
+        match unsafe { device.create_pipeline_cache(&info, None) } {
+            Ok(cache) => return Ok(cache),
+            Err(err) => log::warn!("Failed to create pipeline cache with initial data {:?}. Falling back to empty cache", err),
+        }
+    }
+
+    let info = vk::PipelineCacheCreateInfo::builder();
+    Ok(unsafe { device.create_pipeline_cache(&info, None) }?)
+}
+
 fn filter_devices<'a>(
     devices: Vec<vk::PhysicalDevice>,
     instance: &InstanceContext,
@@ -201,6 +495,8 @@ struct DeviceConfigurator<'a, 'b> {
     available_extensions: HashSet<CString>,
     used_extensions: HashSet<CString>,
     queue_family_surface_support: Box<[bool]>,
+    /// The core features enabled by the profile
+    profile_features: vk::PhysicalDeviceFeatures,
     alloc: &'b Bump,
     create_info: vk::DeviceCreateInfoBuilder<'b>,
 }
@@ -234,6 +530,11 @@ impl<'a, 'b> DeviceConfigurator<'a, 'b> {
             return Ok(None);
         }
 
+        let mut profile_features = vk::PhysicalDeviceFeatures2::default();
+        unsafe {
+            vk_vp.get_profile_features(profile, &mut *(&mut profile_features as *mut vk::PhysicalDeviceFeatures2 as *mut vk::BaseOutStructure))
+        };
+
         let queue_family_count = unsafe {
             instance.vk().get_physical_device_queue_family_properties(physical_device)
         }.len();
@@ -261,6 +562,7 @@ impl<'a, 'b> DeviceConfigurator<'a, 'b> {
             available_extensions,
             used_extensions,
             queue_family_surface_support,
+            profile_features: profile_features.features,
             alloc,
             create_info: vk::DeviceCreateInfo::builder()
         }))
@@ -270,6 +572,11 @@ impl<'a, 'b> DeviceConfigurator<'a, 'b> {
         self.device_name.as_c_str()
     }
 
+    /// Returns the core features enabled by the profile.
+    fn get_profile_features(&self) -> vk::PhysicalDeviceFeatures {
+        self.profile_features
+    }
+
     fn get_properties(&self, mut properties: vk::PhysicalDeviceProperties2Builder) -> vk::PhysicalDeviceProperties {
         unsafe {
             self.instance.vk().get_physical_device_properties2(self.physical_device, &mut properties)
@@ -340,12 +647,32 @@ impl<'a, 'b> DeviceConfigurator<'a, 'b> {
 #[derive(Debug)]
 struct DeviceConfigInfo {
     rating: f32,
+    capabilities: DeviceCapabilities,
     has_maintenance4: bool,
+    has_sampler_ycbcr_conversion: bool,
+    max_sampler_anisotropy: Option<f32>,
+    has_portability_subset: bool,
+    has_external_memory: bool,
+    has_dma_buf_import: bool,
+    has_full_screen_exclusive: bool,
+    has_present_wait: bool,
+    has_display_timing: bool,
+    has_graphics_pipeline_library: bool,
+    has_device_fault: bool,
+    has_diagnostic_checkpoints: bool,
+    has_robust_buffer_access_2: bool,
+    has_null_descriptor: bool,
+    has_sparse_residency: bool,
+    has_buffer_device_address: bool,
 
     /// The main queue family. It is guaranteed to support presentation to all surfaces as well as
     /// graphics, compute and transfer operations.
     main_queue_family: u32,
 
+    /// If true a second low priority queue is created in the main queue family. See
+    /// [`DeviceContext::get_background_queue`].
+    has_background_queue: bool,
+
     /// The queue family used for async compute operations. It is guaranteed to support compute and
     /// transfer operations and must be a different queue family than the main queue family.
     async_compute_family: Option<u32>,
@@ -360,12 +687,10 @@ fn configure_device(device: &mut DeviceConfigurator) -> Result<Option<DeviceConf
     let mut features = vk::PhysicalDeviceFeatures2::builder();
     let mut properties = vk::PhysicalDeviceProperties2::builder();
 
+    // The functions are loaded using the extension names so synchronization2 is only used if the
+    // extension is supported even if the feature is core
     let synchronization_2_name = CString::new("VK_KHR_synchronization2").unwrap();
-    if !device.is_extension_supported(&synchronization_2_name) {
-        log::info!("Physical device {:?} does not support VK_KHR_synchronization2", device.get_name());
-        return Ok(None);
-    }
-    device.add_extension(&synchronization_2_name);
+    let has_synchronization_2_extension = device.is_extension_supported(&synchronization_2_name);
 
     let push_descriptor_name = CString::new("VK_KHR_push_descriptor").unwrap();
     if !device.is_extension_supported(&push_descriptor_name) {
@@ -374,6 +699,60 @@ fn configure_device(device: &mut DeviceConfigurator) -> Result<Option<DeviceConf
     }
     device.add_extension(&push_descriptor_name);
 
+    let mut has_dma_buf_import = false;
+    let has_external_memory = if device.config.external_memory {
+        let extensions = if cfg!(unix) {
+            ["VK_KHR_external_memory_fd", "VK_KHR_external_semaphore_fd"]
+        } else if cfg!(windows) {
+            ["VK_KHR_external_memory_win32", "VK_KHR_external_semaphore_win32"]
+        } else {
+            log::info!("External memory is not supported on this platform");
+            return Ok(None);
+        };
+
+        for extension in extensions {
+            let name = CString::new(extension).unwrap();
+            if !device.is_extension_supported(&name) {
+                log::info!("Physical device {:?} does not support {}", device.get_name(), extension);
+                return Ok(None);
+            }
+            device.add_extension(&name);
+        }
+
+        // Dma-buf images can only be imported with an explicit drm format modifier so all
+        // extensions needed for that have to be present.
+        if cfg!(target_os = "linux") {
+            let dma_buf_extensions = [
+                CString::new("VK_EXT_external_memory_dma_buf").unwrap(),
+                CString::new("VK_EXT_image_drm_format_modifier").unwrap(),
+                CString::new("VK_KHR_image_format_list").unwrap(),
+            ];
+            has_dma_buf_import = dma_buf_extensions.iter().all(|name| device.is_extension_supported(name));
+            if has_dma_buf_import {
+                for name in &dma_buf_extensions {
+                    device.add_extension(name);
+                }
+            }
+        }
+
+        true
+    } else {
+        false
+    };
+
+    // Full screen exclusive is optional. If it is not available we fall back to whatever the
+    // compositor decides to do.
+    let full_screen_exclusive_name = CString::new("VK_EXT_full_screen_exclusive").unwrap();
+    let surface_capabilities_2_name = CString::new("VK_KHR_get_surface_capabilities2").unwrap();
+    let has_full_screen_exclusive = device.config.full_screen_exclusive &&
+        device.instance.is_extension_enabled(&surface_capabilities_2_name) &&
+        device.is_extension_supported(&full_screen_exclusive_name);
+    if has_full_screen_exclusive {
+        device.add_extension(&full_screen_exclusive_name);
+    } else if device.config.full_screen_exclusive {
+        log::info!("Physical device {:?} does not support VK_EXT_full_screen_exclusive", device.get_name());
+    }
+
     let maintenance_4_name = CString::new("VK_KHR_maintenance4").unwrap();
     let mut maintenance4;
     if !device.is_extension_supported(&maintenance_4_name) {
@@ -388,9 +767,66 @@ fn configure_device(device: &mut DeviceConfigurator) -> Result<Option<DeviceConf
         maintenance4 = None;
     }
 
+    // Graphics pipeline libraries are optional and only used to speed up pipeline creation
+    let pipeline_library_name = CString::new("VK_KHR_pipeline_library").unwrap();
+    let graphics_pipeline_library_name = CString::new("VK_EXT_graphics_pipeline_library").unwrap();
+    let mut graphics_pipeline_library = if device.is_extension_supported(&pipeline_library_name) && device.is_extension_supported(&graphics_pipeline_library_name) {
+        Some(vk::PhysicalDeviceGraphicsPipelineLibraryFeaturesEXT::builder())
+    } else {
+        None
+    };
+    if let Some(f) = graphics_pipeline_library.as_mut() {
+        features = features.push_next(f);
+    }
+
+    let robustness_2_name = CString::new("VK_EXT_robustness2").unwrap();
+    let mut robustness_2 = if device.config.robustness_2 && !device.config.disable_robustness && device.is_extension_supported(&robustness_2_name) {
+        Some(vk::PhysicalDeviceRobustness2FeaturesEXT::builder())
+    } else {
+        None
+    };
+    if let Some(f) = robustness_2.as_mut() {
+        features = features.push_next(f);
+    }
+
+    // Fault reporting is only used for diagnostics after the device has been lost
+    let device_fault_name = CString::new("VK_EXT_device_fault").unwrap();
+    let mut device_fault = if device.config.fault_reporting && device.is_extension_supported(&device_fault_name) {
+        Some(vk::PhysicalDeviceFaultFeaturesEXT::builder())
+    } else {
+        None
+    };
+    if let Some(f) = device_fault.as_mut() {
+        features = features.push_next(f);
+    }
+
+    // Features promoted to core are queried if either the core version or the extension is
+    // available. See [`crate::device::capabilities`].
+    let device_version = VulkanVersion::from_raw(unsafe {
+        device.instance.vk().get_physical_device_properties(device.physical_device)
+    }.api_version);
+    let api_version = std::cmp::min(device.instance.get_version(), device_version);
+
+    let timeline_semaphore_name = CString::new("VK_KHR_timeline_semaphore").unwrap();
+    let timeline_source = FeatureSource::probe(api_version, VulkanVersion::VK_1_2, device.is_extension_supported(&timeline_semaphore_name));
+
+    let dynamic_rendering_name = CString::new("VK_KHR_dynamic_rendering").unwrap();
+    let dynamic_rendering_source = FeatureSource::probe(api_version, VulkanVersion::VK_1_3, device.is_extension_supported(&dynamic_rendering_name));
+    let mut dynamic_rendering_features = if dynamic_rendering_source.is_supported() {
+        Some(vk::PhysicalDeviceDynamicRenderingFeatures::builder())
+    } else {
+        None
+    };
+    if let Some(f) = dynamic_rendering_features.as_mut() {
+        features = features.push_next(f);
+    }
+
     let mut timeline_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::builder();
     features = features.push_next(&mut timeline_features);
 
+    let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder();
+    features = features.push_next(&mut buffer_device_address_features);
+
     let mut timeline_properties = vk::PhysicalDeviceTimelineSemaphoreProperties::builder();
     properties = properties.push_next(&mut timeline_properties);
 
@@ -400,38 +836,99 @@ fn configure_device(device: &mut DeviceConfigurator) -> Result<Option<DeviceConf
     let mut push_descriptor_properties = vk::PhysicalDevicePushDescriptorPropertiesKHR::builder();
     properties = properties.push_next(&mut push_descriptor_properties);
 
+    let mut ycbcr_features = vk::PhysicalDeviceSamplerYcbcrConversionFeatures::builder();
+    features = features.push_next(&mut ycbcr_features);
+
+    // Portability implementations must enable this extension if it is supported
+    let portability_subset_name = CString::new("VK_KHR_portability_subset").unwrap();
+    let mut portability_features = if device.is_extension_supported(&portability_subset_name) {
+        Some(vk::PhysicalDevicePortabilitySubsetFeaturesKHR::builder())
+    } else {
+        None
+    };
+    if let Some(portability_features) = portability_features.as_mut() {
+        features = features.push_next(portability_features);
+    }
+
+    let present_id_name = CString::new("VK_KHR_present_id").unwrap();
+    let present_wait_name = CString::new("VK_KHR_present_wait").unwrap();
+    let mut present_wait = if device.config.present_wait && device.is_extension_supported(&present_id_name) && device.is_extension_supported(&present_wait_name) {
+        Some((
+            vk::PhysicalDevicePresentIdFeaturesKHR::builder(),
+            vk::PhysicalDevicePresentWaitFeaturesKHR::builder()
+        ))
+    } else {
+        None
+    };
+    if let Some((id, wait)) = present_wait.as_mut() {
+        features = features.push_next(id);
+        features = features.push_next(wait);
+    }
+
     // Read supported features and properties
-    device.get_features(features);
-    device.get_properties(properties);
+    let core_features = device.get_features(features);
+    let core_properties = device.get_properties(properties);
     let timeline_features = timeline_features.build();
     let timeline_properties = timeline_properties.build();
+    let buffer_device_address_features = buffer_device_address_features.build();
     let synchronization2_features = synchronization2_features.build();
     let push_descriptor_properties = push_descriptor_properties.build();
     let maintenance4 = maintenance4.map(|(f, p)| (f.build(), p.build()));
+    let ycbcr_features = ycbcr_features.build();
+    let present_wait = present_wait.map(|(id, wait)| (id.build(), wait.build()));
+    let graphics_pipeline_library = graphics_pipeline_library.map(|f| f.build());
+    let device_fault = device_fault.map(|f| f.build());
+    let robustness_2 = robustness_2.map(|f| f.build());
+    let portability_features = portability_features.map(|f| f.build());
+    let dynamic_rendering_features = dynamic_rendering_features.map(|f| f.build());
 
     // Process the supported features and properties
-    if timeline_features.timeline_semaphore != vk::TRUE {
-        log::info!("Physical device {:?} does not support the timeline semaphore feature", device.get_name());
-        return Ok(None);
+    let timeline_source = if !timeline_source.is_supported() || timeline_features.timeline_semaphore != vk::TRUE {
+        log::info!("Physical device {:?} does not support the timeline semaphore feature. Falling back to binary semaphores", device.get_name());
+        FeatureSource::Unsupported
+    } else if timeline_properties.max_timeline_semaphore_value_difference < u8::MAX as u64 {
+        log::info!("Physical device {:?} max_timeline_semaphore_value_difference is too low {:?}. Falling back to binary semaphores", device.get_name(), timeline_properties.max_timeline_semaphore_value_difference);
+        FeatureSource::Unsupported
     } else {
+        // The functions are loaded using the extension names so the extension is enabled even
+        // if the feature is core
+        if device.is_extension_supported(&timeline_semaphore_name) {
+            device.add_extension(&timeline_semaphore_name);
+        }
         device.push_next(vk::PhysicalDeviceTimelineSemaphoreFeatures::builder()
             .timeline_semaphore(true)
         );
-    }
+        timeline_source
+    };
 
-    if timeline_properties.max_timeline_semaphore_value_difference < u8::MAX as u64 {
-        log::info!("Physical device {:?} max_timeline_semaphore_value_difference is too low {:?}", device.get_name(), timeline_properties.max_timeline_semaphore_value_difference);
+    // External semaphores are always imported as timeline semaphores
+    if has_external_memory && !timeline_source.is_supported() {
+        log::info!("Physical device {:?} does not support timeline semaphores required for external memory", device.get_name());
         return Ok(None);
     }
 
-    if synchronization2_features.synchronization2 != vk::TRUE {
-        log::info!("Physical device {:?} does not support the synchronization2 feature", device.get_name());
-        return Ok(None);
+    let dynamic_rendering_source = if dynamic_rendering_features.map_or(false, |f| f.dynamic_rendering == vk::TRUE) {
+        if dynamic_rendering_source == FeatureSource::Extension {
+            device.add_extension(&dynamic_rendering_name);
+        }
+        device.push_next(vk::PhysicalDeviceDynamicRenderingFeatures::builder()
+            .dynamic_rendering(true)
+        );
+        dynamic_rendering_source
     } else {
+        FeatureSource::Unsupported
+    };
+
+    let synchronization_2_source = if has_synchronization_2_extension && synchronization2_features.synchronization2 == vk::TRUE {
+        device.add_extension(&synchronization_2_name);
         device.push_next(vk::PhysicalDeviceSynchronization2Features::builder()
             .synchronization2(true)
         );
-    }
+        FeatureSource::probe(api_version, VulkanVersion::VK_1_3, true)
+    } else {
+        log::info!("Physical device {:?} does not support synchronization2. Falling back to legacy synchronization", device.get_name());
+        FeatureSource::Unsupported
+    };
 
     if push_descriptor_properties.max_push_descriptors < 8 {
         log::info!("Physical device {:?} max_push_descriptors is too low {:?}", device.get_name(), push_descriptor_properties.max_push_descriptors);
@@ -453,6 +950,124 @@ fn configure_device(device: &mut DeviceConfigurator) -> Result<Option<DeviceConf
         has_maintenance4 = false;
     }
 
+    // Ycbcr conversion is optional and only needed for multi-planar images
+    let has_sampler_ycbcr_conversion = ycbcr_features.sampler_ycbcr_conversion == vk::TRUE;
+    if has_sampler_ycbcr_conversion {
+        device.push_next(vk::PhysicalDeviceSamplerYcbcrConversionFeatures::builder()
+            .sampler_ycbcr_conversion(true)
+        );
+    }
+
+    // Anisotropic filtering is not guaranteed to be available on portability implementations
+    let max_sampler_anisotropy = if core_features.sampler_anisotropy == vk::TRUE {
+        Some(core_properties.limits.max_sampler_anisotropy)
+    } else {
+        log::info!("Physical device {:?} does not support sampler anisotropy", device.get_name());
+        None
+    };
+
+    let has_portability_subset = if let Some(f) = portability_features.as_ref() {
+        log::info!("Physical device {:?} is a portability subset device with features {:?}", device.get_name(), f);
+        device.add_extension(&portability_subset_name);
+        device.push_next(vk::PhysicalDevicePortabilitySubsetFeaturesKHR::builder()
+            .constant_alpha_color_blend_factors(f.constant_alpha_color_blend_factors == vk::TRUE)
+            .events(f.events == vk::TRUE)
+            .image_view_format_reinterpretation(f.image_view_format_reinterpretation == vk::TRUE)
+            .image_view_format_swizzle(f.image_view_format_swizzle == vk::TRUE)
+            .image_view2_d_on3_d_image(f.image_view2_d_on3_d_image == vk::TRUE)
+            .multisample_array_image(f.multisample_array_image == vk::TRUE)
+            .mutable_comparison_samplers(f.mutable_comparison_samplers == vk::TRUE)
+            .point_polygons(f.point_polygons == vk::TRUE)
+            .sampler_mip_lod_bias(f.sampler_mip_lod_bias == vk::TRUE)
+            .separate_stencil_mask_ref(f.separate_stencil_mask_ref == vk::TRUE)
+            .shader_sample_rate_interpolation_functions(f.shader_sample_rate_interpolation_functions == vk::TRUE)
+            .tessellation_isolines(f.tessellation_isolines == vk::TRUE)
+            .tessellation_point_mode(f.tessellation_point_mode == vk::TRUE)
+            .triangle_fans(f.triangle_fans == vk::TRUE)
+            .vertex_attribute_access_beyond_stride(f.vertex_attribute_access_beyond_stride == vk::TRUE)
+        );
+        true
+    } else {
+        false
+    };
+
+    let has_present_wait = if let Some((id, wait)) = present_wait.as_ref() {
+        id.present_id == vk::TRUE && wait.present_wait == vk::TRUE
+    } else {
+        false
+    };
+    if has_present_wait {
+        device.add_extension(&present_id_name);
+        device.add_extension(&present_wait_name);
+        device.push_next(vk::PhysicalDevicePresentIdFeaturesKHR::builder()
+            .present_id(true)
+        );
+        device.push_next(vk::PhysicalDevicePresentWaitFeaturesKHR::builder()
+            .present_wait(true)
+        );
+    } else if device.config.present_wait {
+        log::info!("Physical device {:?} does not support VK_KHR_present_wait", device.get_name());
+    }
+
+    let has_graphics_pipeline_library = graphics_pipeline_library.map_or(false, |f| f.graphics_pipeline_library == vk::TRUE);
+    if has_graphics_pipeline_library {
+        device.add_extension(&pipeline_library_name);
+        device.add_extension(&graphics_pipeline_library_name);
+        device.push_next(vk::PhysicalDeviceGraphicsPipelineLibraryFeaturesEXT::builder()
+            .graphics_pipeline_library(true)
+        );
+    }
+
+    let (has_robust_buffer_access_2, has_null_descriptor) = robustness_2.map_or((false, false), |f| {
+        (f.robust_buffer_access2 == vk::TRUE && core_features.robust_buffer_access == vk::TRUE, f.null_descriptor == vk::TRUE)
+    });
+    if has_robust_buffer_access_2 || has_null_descriptor {
+        device.add_extension(&robustness_2_name);
+        device.push_next(vk::PhysicalDeviceRobustness2FeaturesEXT::builder()
+            .robust_buffer_access2(has_robust_buffer_access_2)
+            .null_descriptor(has_null_descriptor)
+        );
+    }
+    if device.config.robustness_2 {
+        log::info!("Physical device {:?} robustness2: robustBufferAccess2 {}, nullDescriptor {}", device.get_name(), has_robust_buffer_access_2, has_null_descriptor);
+    }
+
+    let has_device_fault = device_fault.map_or(false, |f| f.device_fault == vk::TRUE);
+    if has_device_fault {
+        device.add_extension(&device_fault_name);
+        device.push_next(vk::PhysicalDeviceFaultFeaturesEXT::builder()
+            .device_fault(true)
+        );
+    }
+
+    let display_timing_name = CString::new("VK_GOOGLE_display_timing").unwrap();
+    let has_display_timing = device.config.display_timing && device.is_extension_supported(&display_timing_name);
+    if has_display_timing {
+        device.add_extension(&display_timing_name);
+    } else if device.config.display_timing {
+        log::info!("Physical device {:?} does not support VK_GOOGLE_display_timing", device.get_name());
+    }
+
+    let diagnostic_checkpoints_name = CString::new("VK_NV_device_diagnostic_checkpoints").unwrap();
+    let has_diagnostic_checkpoints = device.config.fault_reporting && device.is_extension_supported(&diagnostic_checkpoints_name);
+    if has_diagnostic_checkpoints {
+        device.add_extension(&diagnostic_checkpoints_name);
+    }
+
+    if device.config.fault_reporting {
+        log::info!("Physical device {:?} fault reporting: VK_EXT_device_fault {}, VK_NV_device_diagnostic_checkpoints {}", device.get_name(), has_device_fault, has_diagnostic_checkpoints);
+    }
+
+    // Buffer device addresses are optional and only used by gpu driven rendering
+    let has_buffer_device_address = device.config.buffer_device_address && buffer_device_address_features.buffer_device_address == vk::TRUE;
+    if has_buffer_device_address {
+        device.push_next(vk::PhysicalDeviceBufferDeviceAddressFeatures::builder()
+            .buffer_device_address(true)
+        );
+    } else if device.config.buffer_device_address {
+        log::info!("Physical device {:?} does not support the bufferDeviceAddress feature", device.get_name());
+    }
+
     // Calculate queue family assignments
     let main_families = device.filter_sort_queues(|family, properties, surface_support| {
         Some(family)
@@ -465,10 +1080,75 @@ fn configure_device(device: &mut DeviceConfigurator) -> Result<Option<DeviceConf
         return Ok(None);
     }
 
+    let main_queue_count = unsafe {
+        device.instance.vk().get_physical_device_queue_family_properties(device.physical_device)
+    }[main_queue_family as usize].queue_count;
+    let has_background_queue = main_queue_count >= 2;
+    if !has_background_queue {
+        log::info!("Physical device {:?} main queue family only supports 1 queue. Background work will use the main queue", device.get_name());
+    }
+
+    // Sparse binding operations are submitted to the background queue so the main family has to support them
+    let main_family_sparse_binding = unsafe {
+        device.instance.vk().get_physical_device_queue_family_properties(device.physical_device)
+    }[main_queue_family as usize].queue_flags.contains(vk::QueueFlags::SPARSE_BINDING);
+    // Sparse binding operations are synchronized using timeline semaphores
+    let has_sparse_residency = device.config.sparse_residency &&
+        timeline_source.is_supported() &&
+        main_family_sparse_binding &&
+        core_features.sparse_binding == vk::TRUE &&
+        core_features.sparse_residency_image2_d == vk::TRUE;
+    if has_sparse_residency {
+        // The profile features are overridden by any features in the create info chain so the
+        // profile features have to be enabled in addition to the sparse features
+        let mut enabled_features = device.get_profile_features();
+        enabled_features.sparse_binding = vk::TRUE;
+        enabled_features.sparse_residency_image2_d = vk::TRUE;
+        if device.config.disable_robustness {
+            enabled_features.robust_buffer_access = vk::FALSE;
+        }
+        device.push_next(vk::PhysicalDeviceFeatures2::builder()
+            .features(enabled_features)
+        );
+    } else if device.config.sparse_residency {
+        log::info!("Physical device {:?} does not support sparse residency (sparseBinding {}, sparseResidencyImage2D {}, main family sparse binding {}, timeline semaphores {})",
+            device.get_name(),
+            core_features.sparse_binding == vk::TRUE,
+            core_features.sparse_residency_image2_d == vk::TRUE,
+            main_family_sparse_binding,
+            timeline_source.is_supported()
+        );
+    }
+
+    let capabilities = DeviceCapabilities {
+        api_version,
+        tier: FeatureTier::from_version(api_version),
+        dynamic_rendering: dynamic_rendering_source,
+        timeline_semaphore: timeline_source,
+        synchronization_2: synchronization_2_source,
+    };
+
     Ok(Some(DeviceConfigInfo {
-        rating: 0.0,
+        rating: device.config.device_preference.rate(core_properties.device_type),
+        capabilities,
         has_maintenance4,
+        has_sampler_ycbcr_conversion,
+        max_sampler_anisotropy,
+        has_portability_subset,
+        has_external_memory,
+        has_dma_buf_import,
+        has_full_screen_exclusive,
+        has_present_wait,
+        has_display_timing,
+        has_graphics_pipeline_library,
+        has_device_fault,
+        has_diagnostic_checkpoints,
+        has_robust_buffer_access_2,
+        has_null_descriptor,
+        has_sparse_residency,
+        has_buffer_device_address,
         main_queue_family,
+        has_background_queue,
         async_compute_family: None,
         async_transfer_family: None
     }))
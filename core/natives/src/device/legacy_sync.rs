@@ -0,0 +1,181 @@
+//! Fallback for devices which do not support VK_KHR_synchronization2.
+//!
+//! All barriers and submissions are recorded using the synchronization2 structs. If the device
+//! does not support synchronization2 they are converted to the equivalent legacy commands here.
+//! Since legacy barriers only have a single source and destination stage mask per command the
+//! stage masks of all barriers are merged which may introduce additional dependencies.
+
+use ash::prelude::VkResult;
+use ash::vk;
+
+use crate::device::device::DeviceFunctions;
+
+/// Converts synchronization2 stage flags to legacy stage flags. Stages which only exist in
+/// synchronization2 are mapped to the legacy stage containing them.
+pub fn convert_stage_mask(stages: vk::PipelineStageFlags2) -> vk::PipelineStageFlags {
+    // All legacy stages use the same bit in both flag types
+    let mut result = vk::PipelineStageFlags::from_raw((stages.as_raw() & (u32::MAX as u64)) as u32);
+
+    if stages.intersects(vk::PipelineStageFlags2::COPY | vk::PipelineStageFlags2::RESOLVE | vk::PipelineStageFlags2::BLIT | vk::PipelineStageFlags2::CLEAR) {
+        result |= vk::PipelineStageFlags::TRANSFER;
+    }
+    if stages.intersects(vk::PipelineStageFlags2::INDEX_INPUT | vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT) {
+        result |= vk::PipelineStageFlags::VERTEX_INPUT;
+    }
+    if stages.contains(vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS) {
+        // Tessellation and geometry shaders are never used
+        result |= vk::PipelineStageFlags::VERTEX_SHADER;
+    }
+
+    result
+}
+
+/// Converts synchronization2 access flags to legacy access flags. Accesses which only exist in
+/// synchronization2 are mapped to the legacy access containing them.
+pub fn convert_access_mask(access: vk::AccessFlags2) -> vk::AccessFlags {
+    let mut result = vk::AccessFlags::from_raw((access.as_raw() & (u32::MAX as u64)) as u32);
+
+    if access.intersects(vk::AccessFlags2::SHADER_SAMPLED_READ | vk::AccessFlags2::SHADER_STORAGE_READ) {
+        result |= vk::AccessFlags::SHADER_READ;
+    }
+    if access.contains(vk::AccessFlags2::SHADER_STORAGE_WRITE) {
+        result |= vk::AccessFlags::SHADER_WRITE;
+    }
+
+    result
+}
+
+/// Records the dependency using vkCmdPipelineBarrier.
+pub(crate) unsafe fn cmd_pipeline_barrier(device: &ash::Device, command_buffer: vk::CommandBuffer, info: &vk::DependencyInfo) {
+    let memory = slice_from_raw(info.p_memory_barriers, info.memory_barrier_count);
+    let buffers = slice_from_raw(info.p_buffer_memory_barriers, info.buffer_memory_barrier_count);
+    let images = slice_from_raw(info.p_image_memory_barriers, info.image_memory_barrier_count);
+
+    let mut src_stages = vk::PipelineStageFlags2::empty();
+    let mut dst_stages = vk::PipelineStageFlags2::empty();
+
+    let memory: Vec<_> = memory.iter().map(|barrier| {
+        src_stages |= barrier.src_stage_mask;
+        dst_stages |= barrier.dst_stage_mask;
+        vk::MemoryBarrier::builder()
+            .src_access_mask(convert_access_mask(barrier.src_access_mask))
+            .dst_access_mask(convert_access_mask(barrier.dst_access_mask))
+            .build()
+    }).collect();
+
+    let buffers: Vec<_> = buffers.iter().map(|barrier| {
+        src_stages |= barrier.src_stage_mask;
+        dst_stages |= barrier.dst_stage_mask;
+        vk::BufferMemoryBarrier::builder()
+            .src_access_mask(convert_access_mask(barrier.src_access_mask))
+            .dst_access_mask(convert_access_mask(barrier.dst_access_mask))
+            .src_queue_family_index(barrier.src_queue_family_index)
+            .dst_queue_family_index(barrier.dst_queue_family_index)
+            .buffer(barrier.buffer)
+            .offset(barrier.offset)
+            .size(barrier.size)
+            .build()
+    }).collect();
+
+    let images: Vec<_> = images.iter().map(|barrier| {
+        src_stages |= barrier.src_stage_mask;
+        dst_stages |= barrier.dst_stage_mask;
+        vk::ImageMemoryBarrier::builder()
+            .src_access_mask(convert_access_mask(barrier.src_access_mask))
+            .dst_access_mask(convert_access_mask(barrier.dst_access_mask))
+            .old_layout(barrier.old_layout)
+            .new_layout(barrier.new_layout)
+            .src_queue_family_index(barrier.src_queue_family_index)
+            .dst_queue_family_index(barrier.dst_queue_family_index)
+            .image(barrier.image)
+            .subresource_range(barrier.subresource_range)
+            .build()
+    }).collect();
+
+    // Legacy barriers must not use empty stage masks
+    let mut src_stages = convert_stage_mask(src_stages);
+    if src_stages.is_empty() {
+        src_stages = vk::PipelineStageFlags::TOP_OF_PIPE;
+    }
+    let mut dst_stages = convert_stage_mask(dst_stages);
+    if dst_stages.is_empty() {
+        dst_stages = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+    }
+
+    device.cmd_pipeline_barrier(command_buffer, src_stages, dst_stages, info.dependency_flags, &memory, &buffers, &images);
+}
+
+/// The data of a legacy submission. Must not be moved after the submit info has been created.
+struct LegacySubmit {
+    waits: Vec<vk::Semaphore>,
+    wait_values: Vec<u64>,
+    wait_stages: Vec<vk::PipelineStageFlags>,
+    command_buffers: Vec<vk::CommandBuffer>,
+    device_masks: Vec<u32>,
+    signals: Vec<vk::Semaphore>,
+    signal_values: Vec<u64>,
+}
+
+/// Submits the batches using vkQueueSubmit.
+pub(crate) unsafe fn queue_submit(device: &DeviceFunctions, queue: vk::Queue, submits: &[vk::SubmitInfo2], fence: vk::Fence) -> VkResult<()> {
+    let all_devices_mask = device.device_group.as_ref().map(|group| group.get_all_devices_mask());
+
+    let legacy: Vec<_> = submits.iter().map(|submit| {
+        let waits = slice_from_raw(submit.p_wait_semaphore_infos, submit.wait_semaphore_info_count);
+        let command_buffers = slice_from_raw(submit.p_command_buffer_infos, submit.command_buffer_info_count);
+        let signals = slice_from_raw(submit.p_signal_semaphore_infos, submit.signal_semaphore_info_count);
+
+        LegacySubmit {
+            waits: waits.iter().map(|wait| wait.semaphore).collect(),
+            wait_values: waits.iter().map(|wait| wait.value).collect(),
+            wait_stages: waits.iter().map(|wait| {
+                let stages = convert_stage_mask(wait.stage_mask);
+                if stages.is_empty() { vk::PipelineStageFlags::ALL_COMMANDS } else { stages }
+            }).collect(),
+            command_buffers: command_buffers.iter().map(|info| info.command_buffer).collect(),
+            device_masks: command_buffers.iter().map(|info| {
+                if info.device_mask == 0 { all_devices_mask.unwrap_or(0) } else { info.device_mask }
+            }).collect(),
+            signals: signals.iter().map(|signal| signal.semaphore).collect(),
+            signal_values: signals.iter().map(|signal| signal.value).collect(),
+        }
+    }).collect();
+
+    let use_timeline = device.capabilities.has_timeline_semaphore();
+    let mut timeline_infos: Vec<_> = legacy.iter().map(|submit| {
+        vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&submit.wait_values)
+            .signal_semaphore_values(&submit.signal_values)
+            .build()
+    }).collect();
+    let mut group_infos: Vec<_> = legacy.iter().map(|submit| {
+        vk::DeviceGroupSubmitInfo::builder()
+            .command_buffer_device_masks(&submit.device_masks)
+            .build()
+    }).collect();
+
+    let infos: Vec<_> = legacy.iter().zip(timeline_infos.iter_mut()).zip(group_infos.iter_mut()).map(|((submit, timeline_info), group_info)| {
+        let mut info = vk::SubmitInfo::builder()
+            .wait_semaphores(&submit.waits)
+            .wait_dst_stage_mask(&submit.wait_stages)
+            .command_buffers(&submit.command_buffers)
+            .signal_semaphores(&submit.signals);
+        if use_timeline {
+            info = info.push_next(timeline_info);
+        }
+        if all_devices_mask.is_some() {
+            info = info.push_next(group_info);
+        }
+        info.build()
+    }).collect();
+
+    device.vk.queue_submit(queue, &infos, fence)
+}
+
+unsafe fn slice_from_raw<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, count as usize)
+    }
+}
@@ -2,3 +2,7 @@ pub mod device;
 pub mod init;
 pub mod device_utils;
 pub mod surface;
+pub mod fault;
+pub mod device_group;
+pub mod capabilities;
+pub mod legacy_sync;
@@ -1,12 +1,15 @@
+use std::collections::VecDeque;
 use std::fmt::{Debug, Formatter};
 use std::ops::{BitAnd, BitOr};
 use std::sync::{Arc, Mutex, MutexGuard, Weak};
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use ash::prelude::VkResult;
 use ash::vk;
 use ash::vk::Flags;
 
+use crate::device::device_utils::OutputRotation;
 use crate::objects::sync::{Semaphore, SemaphoreOp};
 use crate::vk::objects::surface::SurfaceProvider;
 
@@ -16,10 +19,12 @@ use crate::vk::objects::image::Image;
 pub struct DeviceSurface {
     device: Arc<DeviceFunctions>,
     weak: Weak<DeviceSurface>,
-    #[allow(unused)] // Just here to keep the provider alive
     surface_provider: Box<dyn SurfaceProvider>,
     surface: vk::SurfaceKHR,
 
+    /// Set once any function returned VK_ERROR_SURFACE_LOST_KHR.
+    surface_lost: AtomicBool,
+
     /// The current swapchain info.
     ///
     /// If both the swapchain mutex and the info mutex must be lock simultaneously (for example during
@@ -34,10 +39,37 @@ impl DeviceSurface {
             weak: weak.clone(),
             surface: surface.get_handle().unwrap(),
             surface_provider: surface,
+            surface_lost: AtomicBool::new(false),
             current_swapchain: Mutex::new(SurfaceSwapchainInfo::new())
         })
     }
 
+    pub fn get_surface_provider(&self) -> &dyn SurfaceProvider {
+        self.surface_provider.as_ref()
+    }
+
+    /// Returns the surface provider. Fails if any other reference to the surface (for example by
+    /// a swapchain) still exists.
+    pub fn try_into_surface_provider(self: Arc<Self>) -> Result<Box<dyn SurfaceProvider>, Arc<Self>> {
+        Arc::try_unwrap(self).map(|surface| surface.surface_provider)
+    }
+
+    /// Marks the surface as lost if the result is VK_ERROR_SURFACE_LOST_KHR. The result is
+    /// returned unchanged.
+    pub fn check_surface_lost<T>(&self, result: VkResult<T>) -> VkResult<T> {
+        if let Err(vk::Result::ERROR_SURFACE_LOST_KHR) = &result {
+            if !self.surface_lost.swap(true, Ordering::SeqCst) {
+                log::error!("Surface has been lost");
+            }
+        }
+        result
+    }
+
+    /// Returns true if the surface has been lost. Once lost the surface must be recreated.
+    pub fn is_surface_lost(&self) -> bool {
+        self.surface_lost.load(Ordering::SeqCst)
+    }
+
     pub fn get_surface_present_modes(&self) -> VkResult<Vec<vk::PresentModeKHR>> {
         unsafe {
             self.device.instance.surface_khr().unwrap().get_physical_device_surface_present_modes(self.device.physical_device, self.surface)
@@ -45,9 +77,9 @@ impl DeviceSurface {
     }
 
     pub fn get_surface_capabilities(&self) -> VkResult<vk::SurfaceCapabilitiesKHR> {
-        unsafe {
+        self.check_surface_lost(unsafe {
             self.device.instance.surface_khr().unwrap().get_physical_device_surface_capabilities(self.device.physical_device, self.surface)
-        }
+        })
     }
 
     pub fn get_surface_formats(&self) -> VkResult<Vec<vk::SurfaceFormatKHR>> {
@@ -67,10 +99,16 @@ impl DeviceSurface {
     ///
     /// If some part of the config is not supported by the surface [`SwapchainCreateError::Unsupported`]
     /// is returned.
+    ///
+    /// The extent is the unrotated size of the content. If the current transform of the surface is
+    /// rotated by 90 or 270 degrees the swapchain is created with a swapped extent and the content
+    /// must be pre-rotated, see [`SurfaceSwapchain::get_rotation`].
     pub fn create_swapchain(&self, config: &SwapchainConfig, extent: Vec2u32) -> Result<Arc<SurfaceSwapchain>, SwapchainCreateError> {
         let capabilities = self.get_surface_capabilities()?;
 
         let format = self.find_best_format(&config)?;
+        let transform = self.find_best_transform(&capabilities, &config)?;
+        let extent = OutputRotation::from_surface_transform(transform).apply_to_size(extent);
 
         let mut info = vk::SwapchainCreateInfoKHR::builder()
             .min_image_count(self.find_best_image_count(&capabilities, &config)?)
@@ -80,12 +118,36 @@ impl DeviceSurface {
             .image_array_layers(1)
             .image_usage(self.find_best_usage_flags(&capabilities, &config)?)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .pre_transform(self.find_best_transform(&capabilities, &config)?)
+            .pre_transform(transform)
             .composite_alpha(self.find_best_composite_alpha(&capabilities, &config)?)
             .present_mode(self.find_best_present_mode(&config)?)
             .clipped(config.clipped);
 
-        Ok(self.create_swapchain_direct(&mut info)?)
+        let mut full_screen_exclusive_info = config.full_screen_exclusive.as_vk().map(|mode| {
+            vk::SurfaceFullScreenExclusiveInfoEXT::builder()
+                .full_screen_exclusive(mode)
+        });
+        if self.device.full_screen_exclusive_ext.is_some() {
+            if let Some(full_screen_exclusive_info) = full_screen_exclusive_info.as_mut() {
+                info = info.push_next(full_screen_exclusive_info);
+            }
+        }
+
+        // Presents without device group info use the local mode so it must always be included
+        let mut device_group_info = self.device.device_group.as_ref().map(|group| {
+            vk::DeviceGroupSwapchainCreateInfoKHR::builder()
+                .modes(vk::DeviceGroupPresentModeFlagsKHR::LOCAL | group.get_mode().get_required_present_mode())
+        });
+        if let Some(device_group_info) = device_group_info.as_mut() {
+            info = info.push_next(device_group_info);
+        }
+
+        let swapchain = self.create_swapchain_direct(&mut info)?;
+        if self.device.full_screen_exclusive_ext.is_some() {
+            swapchain.full_screen_exclusive.store(config.full_screen_exclusive as usize, Ordering::SeqCst);
+        }
+
+        Ok(swapchain)
     }
 
     /// Creates a swapchain from a [`ash::vk::SwapchainCreateInfoKHR`].
@@ -130,19 +192,19 @@ impl DeviceSurface {
 
         let size = Vec2u32::new(info.image_extent.width, info.image_extent.height);
 
-        let new_swapchain = Arc::new(SurfaceSwapchain::new(self.weak.upgrade().unwrap(), new_swapchain, images.as_slice(), size, format, info.image_usage));
+        let new_swapchain = Arc::new(SurfaceSwapchain::new(self.weak.upgrade().unwrap(), new_swapchain, images.as_slice(), size, format, info.image_usage, info.pre_transform));
         guard.set_current(&new_swapchain);
         drop(guard);
 
         Ok(new_swapchain)
     }
 
-    fn find_best_image_count(&self, capabilities: &vk::SurfaceCapabilitiesKHR, _: &SwapchainConfig) -> Result<u32, SwapchainCreateError> {
+    fn find_best_image_count(&self, capabilities: &vk::SurfaceCapabilitiesKHR, config: &SwapchainConfig) -> Result<u32, SwapchainCreateError> {
         if capabilities.max_image_count == 0 {
-            Ok(std::cmp::max(capabilities.min_image_count, 3))
+            Ok(std::cmp::max(capabilities.min_image_count, config.preferred_image_count))
 
         } else {
-            Ok(std::cmp::min(capabilities.max_image_count, std::cmp::max(capabilities.min_image_count, 3)))
+            Ok(std::cmp::min(capabilities.max_image_count, std::cmp::max(capabilities.min_image_count, config.preferred_image_count)))
         }
     }
 
@@ -162,6 +224,15 @@ impl DeviceSurface {
             return Err(SwapchainCreateError::NoExtent)
         }
 
+        // Some platforms (for example MoltenVK) define the surface size through the window. In that
+        // case we must use the current extent.
+        if capabilities.current_extent.width != u32::MAX && capabilities.current_extent.height != u32::MAX {
+            if capabilities.current_extent.width == 0 || capabilities.current_extent.height == 0 {
+                return Err(SwapchainCreateError::NoExtent)
+            }
+            return Ok(capabilities.current_extent);
+        }
+
         if capabilities.max_image_extent.width < extent[0] ||
             capabilities.min_image_extent.width > extent[0] ||
             capabilities.max_image_extent.height < extent[1] ||
@@ -184,6 +255,18 @@ impl DeviceSurface {
     fn find_best_present_mode(&self, config: &SwapchainConfig) -> Result<vk::PresentModeKHR, SwapchainCreateError> {
         let supported = self.get_surface_present_modes()?;
 
+        if config.variable_refresh {
+            // Mailbox discards frames which prevents the display from following the frame rate so
+            // we present every frame and only tear if a frame is late.
+            if config.allow_tearing && supported.contains(&vk::PresentModeKHR::IMMEDIATE) {
+                return Ok(vk::PresentModeKHR::IMMEDIATE);
+            }
+            if supported.contains(&vk::PresentModeKHR::FIFO_RELAXED) {
+                return Ok(vk::PresentModeKHR::FIFO_RELAXED);
+            }
+            return Ok(vk::PresentModeKHR::FIFO);
+        }
+
         if supported.contains(&vk::PresentModeKHR::MAILBOX) {
             return Ok(vk::PresentModeKHR::MAILBOX);
         }
@@ -301,6 +384,43 @@ pub struct SwapchainConfig {
     pub required_usage: vk::ImageUsageFlags,
     pub optional_usage: vk::ImageUsageFlags,
     pub clipped: bool,
+    /// The number of swapchain images to request. Clamped to the range supported by the surface.
+    pub preferred_image_count: u32,
+    /// The full screen exclusive mode to request. Ignored if VK_EXT_full_screen_exclusive is not
+    /// enabled on the device.
+    pub full_screen_exclusive: FullScreenExclusiveMode,
+    /// Selects a present mode suitable for variable refresh rate displays.
+    pub variable_refresh: bool,
+}
+
+/// Controls if the swapchain may use full screen exclusive mode.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(usize)]
+pub enum FullScreenExclusiveMode {
+    /// The implementation decides. This is also used if full screen exclusive is not supported.
+    Default = 0,
+    /// The implementation should use full screen exclusive mode if possible.
+    Allowed = 1,
+    /// The implementation must not use full screen exclusive mode.
+    Disallowed = 2,
+}
+
+impl FullScreenExclusiveMode {
+    fn from_raw(value: usize) -> Self {
+        match value {
+            1 => Self::Allowed,
+            2 => Self::Disallowed,
+            _ => Self::Default,
+        }
+    }
+
+    fn as_vk(&self) -> Option<vk::FullScreenExclusiveEXT> {
+        match self {
+            Self::Default => None,
+            Self::Allowed => Some(vk::FullScreenExclusiveEXT::ALLOWED),
+            Self::Disallowed => Some(vk::FullScreenExclusiveEXT::DISALLOWED),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -335,10 +455,14 @@ pub struct SurfaceSwapchain {
     size: Vec2u32,
     format: vk::SurfaceFormatKHR,
     usage: vk::ImageUsageFlags,
+    transform: vk::SurfaceTransformFlagsKHR,
+    full_screen_exclusive: AtomicUsize,
+    present_tracker: Mutex<PresentTracker>,
+    display_timing: Mutex<DisplayTimingTracker>,
 }
 
 impl SurfaceSwapchain {
-    fn new(surface: Arc<DeviceSurface>, swapchain: vk::SwapchainKHR, images: &[vk::Image], size: Vec2u32, format: vk::SurfaceFormatKHR, usage: vk::ImageUsageFlags) -> Self {
+    fn new(surface: Arc<DeviceSurface>, swapchain: vk::SwapchainKHR, images: &[vk::Image], size: Vec2u32, format: vk::SurfaceFormatKHR, usage: vk::ImageUsageFlags, transform: vk::SurfaceTransformFlagsKHR) -> Self {
         let device = &surface.device;
 
         let acquire_objects = images.iter().map(|_| AcquireObjects::new(device)).collect();
@@ -357,7 +481,11 @@ impl SurfaceSwapchain {
 
             size,
             format,
-            usage
+            usage,
+            transform,
+            full_screen_exclusive: AtomicUsize::new(FullScreenExclusiveMode::Default as usize),
+            present_tracker: Mutex::new(PresentTracker::new()),
+            display_timing: Mutex::new(DisplayTimingTracker::new()),
         }
     }
 
@@ -383,6 +511,22 @@ impl SurfaceSwapchain {
         self.size
     }
 
+    /// Returns the size of the content before it is pre-rotated for the surface transform. This is
+    /// the size content should be rendered at.
+    pub fn get_logical_size(&self) -> Vec2u32 {
+        self.get_rotation().apply_to_size(self.size)
+    }
+
+    /// Returns the transform the swapchain was created with.
+    pub fn get_transform(&self) -> vk::SurfaceTransformFlagsKHR {
+        self.transform
+    }
+
+    /// Returns the rotation which must be applied to content presented to this swapchain.
+    pub fn get_rotation(&self) -> OutputRotation {
+        OutputRotation::from_surface_transform(self.transform)
+    }
+
     /// Returns the format of the swapchain images
     pub fn get_image_format(&self) -> &vk::SurfaceFormatKHR {
         &self.format
@@ -393,9 +537,19 @@ impl SurfaceSwapchain {
         self.usage
     }
 
+    /// Returns the full screen exclusive mode used to create this swapchain. If full screen
+    /// exclusive is not supported by the device [`FullScreenExclusiveMode::Default`] is returned.
+    pub fn get_full_screen_exclusive_mode(&self) -> FullScreenExclusiveMode {
+        FullScreenExclusiveMode::from_raw(self.full_screen_exclusive.load(Ordering::SeqCst))
+    }
+
+    /// Acquires the next image of the swapchain.
+    ///
+    /// If full screen exclusive mode is lost `ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT` is
+    /// returned and the swapchain should be recreated.
     pub fn acquire_next_image(&self, timeout: u64, fence: Option<vk::Fence>) -> VkResult<(AcquiredImageInfo, bool)> {
         let acquire = self.acquire_objects.get(self.get_next_acquire()).unwrap();
-        let (ready_op, acquire_semaphore) = match acquire.wait_and_get(&self.surface.device, timeout) {
+        let (ready_op, ready_fence, acquire_semaphore) = match acquire.wait_and_get(&self.surface.device, timeout) {
             None => {
                 return Err(vk::Result::TIMEOUT)
             }
@@ -405,14 +559,29 @@ impl SurfaceSwapchain {
         let swapchain_khr = self.surface.device.swapchain_khr.as_ref().unwrap();
 
         let guard = self.swapchain.lock().unwrap();
-        let (image_index, suboptimal) = unsafe {
-            swapchain_khr.acquire_next_image(*guard, timeout, acquire_semaphore.get_handle(), fence.unwrap_or(vk::Fence::null()))
-        }?;
+        let result = if let Some(group) = self.surface.device.device_group.as_ref() {
+            // The image must be available on every device which may render the main pass
+            let info = vk::AcquireNextImageInfoKHR::builder()
+                .swapchain(*guard)
+                .timeout(timeout)
+                .semaphore(acquire_semaphore.get_handle())
+                .fence(fence.unwrap_or(vk::Fence::null()))
+                .device_mask(group.get_all_devices_mask());
+            unsafe {
+                swapchain_khr.acquire_next_image2(&info)
+            }
+        } else {
+            unsafe {
+                swapchain_khr.acquire_next_image(*guard, timeout, acquire_semaphore.get_handle(), fence.unwrap_or(vk::Fence::null()))
+            }
+        };
+        let (image_index, suboptimal) = self.surface.check_surface_lost(self.surface.device.check_device_lost(result))?;
         drop(guard);
 
         Ok((AcquiredImageInfo {
             acquire_semaphore: SemaphoreOp::new_binary(acquire_semaphore),
             acquire_ready_semaphore: ready_op,
+            acquire_ready_fence: ready_fence,
             image_index,
         }, suboptimal))
     }
@@ -421,6 +590,143 @@ impl SurfaceSwapchain {
         &self.surface.device
     }
 
+    /// Returns true if the device supports waiting for presents of this swapchain.
+    pub fn supports_present_wait(&self) -> bool {
+        self.surface.device.present_wait_khr.is_some()
+    }
+
+    /// Allocates a new present id for a present operation that is about to be queued.
+    ///
+    /// Returns [`None`] if present wait is not supported. Otherwise the id must be passed to the
+    /// present operation using [`vk::PresentIdKHR`].
+    pub fn begin_present(&self) -> Option<u64> {
+        if !self.supports_present_wait() {
+            return None;
+        }
+
+        let mut guard = self.present_tracker.lock().unwrap();
+        guard.next_id += 1;
+        let id = guard.next_id;
+        guard.pending.push_back((id, Instant::now()));
+
+        Some(id)
+    }
+
+    /// Waits for queued presents to complete.
+    ///
+    /// If `all` is true waits until all queued presents are completed otherwise only processes
+    /// presents that have already completed. Completed presents are used to update the present
+    /// latency returned by [`SurfaceSwapchain::get_present_latency`].
+    pub fn wait_for_presents(&self, timeout: u64, all: bool) -> VkResult<()> {
+        let present_wait_khr = match self.surface.device.present_wait_khr.as_ref() {
+            Some(present_wait_khr) => present_wait_khr,
+            None => return Ok(()),
+        };
+
+        loop {
+            // We must not hold the lock while waiting as that would block present operations
+            let (id, start) = match self.present_tracker.lock().unwrap().pending.front() {
+                Some(front) => *front,
+                None => return Ok(()),
+            };
+
+            let swapchain = *self.swapchain.lock().unwrap();
+            match unsafe {
+                present_wait_khr.wait_for_present(swapchain, id, if all { timeout } else { 0 })
+            } {
+                Ok(_) => {
+                    let mut guard = self.present_tracker.lock().unwrap();
+                    if guard.pending.front().map(|(front, _)| *front) == Some(id) {
+                        guard.pending.pop_front();
+                        guard.last_latency = Some(start.elapsed());
+                    }
+                }
+                Err(vk::Result::TIMEOUT) => return if all { Err(vk::Result::TIMEOUT) } else { Ok(()) },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Returns the time between queueing the last completed present and the present completing.
+    /// This is a approximation of the present to photon latency.
+    ///
+    /// Returns [`None`] if present wait is not supported or no present has completed yet.
+    pub fn get_present_latency(&self) -> Option<Duration> {
+        self.present_tracker.lock().unwrap().last_latency
+    }
+
+    /// Returns true if the device supports VK_GOOGLE_display_timing.
+    pub fn supports_display_timing(&self) -> bool {
+        self.surface.device.display_timing_google.is_some()
+    }
+
+    /// Allocates the present time for a present operation that is about to be queued.
+    ///
+    /// Returns [`None`] if display timing is not supported. Otherwise the present time must be
+    /// passed to the present operation using [`vk::PresentTimesInfoGOOGLE`]. No desired present
+    /// time is set so frames are displayed as early as possible.
+    pub fn begin_display_timing(&self) -> Option<vk::PresentTimeGOOGLE> {
+        if !self.supports_display_timing() {
+            return None;
+        }
+
+        let mut guard = self.display_timing.lock().unwrap();
+        guard.next_id = guard.next_id.wrapping_add(1);
+
+        Some(vk::PresentTimeGOOGLE {
+            present_id: guard.next_id,
+            desired_present_time: 0,
+        })
+    }
+
+    /// Collects the timing information of completed presents. The results are returned by
+    /// [`SurfaceSwapchain::get_display_timing`].
+    pub fn update_display_timing(&self) -> VkResult<()> {
+        let display_timing = match self.surface.device.display_timing_google.as_ref() {
+            Some(display_timing) => display_timing,
+            None => return Ok(()),
+        };
+        let device = self.surface.device.vk.handle();
+        let swapchain = *self.swapchain.lock().unwrap();
+
+        let mut guard = self.display_timing.lock().unwrap();
+        if guard.refresh_duration.is_none() {
+            let mut refresh = vk::RefreshCycleDurationGOOGLE::default();
+            unsafe {
+                (display_timing.get_refresh_cycle_duration_google)(device, swapchain, &mut refresh)
+            }.result()?;
+            guard.refresh_duration = Some(refresh.refresh_duration);
+        }
+
+        let mut count = 0u32;
+        unsafe {
+            (display_timing.get_past_presentation_timing_google)(device, swapchain, &mut count, std::ptr::null_mut())
+        }.result()?;
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut timings = vec![vk::PastPresentationTimingGOOGLE::default(); count as usize];
+        let result = unsafe {
+            (display_timing.get_past_presentation_timing_google)(device, swapchain, &mut count, timings.as_mut_ptr())
+        };
+        // Incomplete only means more timings are available which we collect next time
+        if result != vk::Result::INCOMPLETE {
+            result.result()?;
+        }
+
+        for timing in &timings[0..(count as usize)] {
+            guard.push_timing(timing);
+        }
+        Ok(())
+    }
+
+    /// Returns the timing of recent presents or [`None`] if display timing is not supported or no
+    /// refresh duration has been queried yet.
+    pub fn get_display_timing(&self) -> Option<DisplayTiming> {
+        self.display_timing.lock().unwrap().get_display_timing()
+    }
+
     fn get_next_acquire(&self) -> usize {
         loop {
             let old = self.acquire_next_index.load(Ordering::SeqCst);
@@ -464,24 +770,148 @@ impl Drop for SurfaceSwapchain {
     }
 }
 
+struct PresentTracker {
+    next_id: u64,
+    pending: VecDeque<(u64, Instant)>,
+    last_latency: Option<Duration>,
+}
+
+impl PresentTracker {
+    fn new() -> Self {
+        Self {
+            next_id: 0,
+            pending: VecDeque::new(),
+            last_latency: None,
+        }
+    }
+}
+
+/// Timing information of presents collected using VK_GOOGLE_display_timing.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DisplayTiming {
+    /// The duration of a single refresh cycle of the display. For variable refresh rate displays
+    /// this is the shortest possible cycle.
+    pub refresh_duration: Duration,
+
+    /// How early the last completed present was processed relative to the latest point it could
+    /// have been processed and still be displayed at the same time.
+    pub present_margin: Option<Duration>,
+
+    /// True if the intervals between displayed frames are not multiples of the refresh duration
+    /// which means the display follows the frame rate. [`None`] until enough frames have been
+    /// displayed. Variable refresh displays running at their maximum refresh rate are reported as
+    /// fixed rate since their present intervals are indistinguishable.
+    pub variable_refresh: Option<bool>,
+}
+
+/// The number of present intervals needed before variable refresh is reported.
+const VARIABLE_REFRESH_MIN_SAMPLES: u32 = 60;
+
+/// Present intervals within this fraction of a multiple of the refresh duration count as quantized.
+const VARIABLE_REFRESH_TOLERANCE: f64 = 0.1;
+
+struct DisplayTimingTracker {
+    next_id: u32,
+    refresh_duration: Option<u64>,
+    last_present_time: Option<u64>,
+    last_margin: Option<u64>,
+
+    /// The number of present intervals which were and were not multiples of the refresh duration.
+    quantized_intervals: u32,
+    unquantized_intervals: u32,
+}
+
+impl DisplayTimingTracker {
+    fn new() -> Self {
+        Self {
+            next_id: 0,
+            refresh_duration: None,
+            last_present_time: None,
+            last_margin: None,
+            quantized_intervals: 0,
+            unquantized_intervals: 0,
+        }
+    }
+
+    fn push_timing(&mut self, timing: &vk::PastPresentationTimingGOOGLE) {
+        self.last_margin = Some(timing.present_margin);
+
+        let last = self.last_present_time.replace(timing.actual_present_time);
+        let (last, refresh) = match (last, self.refresh_duration) {
+            (Some(last), Some(refresh)) if refresh != 0 && timing.actual_present_time > last => (last, refresh),
+            _ => return,
+        };
+
+        let cycles = (timing.actual_present_time - last) as f64 / refresh as f64;
+        if cycles.round() >= 1f64 && (cycles - cycles.round()).abs() < VARIABLE_REFRESH_TOLERANCE {
+            self.quantized_intervals += 1;
+        } else {
+            self.unquantized_intervals += 1;
+        }
+
+        // Keep the window rolling so changes of the display mode are eventually detected
+        if self.quantized_intervals + self.unquantized_intervals >= VARIABLE_REFRESH_MIN_SAMPLES * 4 {
+            self.quantized_intervals /= 2;
+            self.unquantized_intervals /= 2;
+        }
+    }
+
+    fn get_display_timing(&self) -> Option<DisplayTiming> {
+        let total = self.quantized_intervals + self.unquantized_intervals;
+        let variable_refresh = if total >= VARIABLE_REFRESH_MIN_SAMPLES {
+            Some(self.unquantized_intervals * 4 > total)
+        } else {
+            None
+        };
+
+        Some(DisplayTiming {
+            refresh_duration: Duration::from_nanos(self.refresh_duration?),
+            present_margin: self.last_margin.map(Duration::from_nanos),
+            variable_refresh,
+        })
+    }
+}
+
+/// Signaled once the acquire semaphore of the previous use of the acquire objects can be reused.
+enum AcquireReady {
+    Timeline {
+        semaphore: Semaphore,
+        wait_value: AtomicU64,
+    },
+    /// Used if timeline semaphores are not supported. The fence is signaled by an empty
+    /// submission queued after all submissions waiting on the acquire semaphore.
+    Fence(vk::Fence),
+}
+
 struct AcquireObjects {
-    ready_semaphore: Semaphore,
-    ready_wait_value: AtomicU64,
+    ready: AcquireReady,
     acquire_semaphore: Semaphore,
 }
 
 impl AcquireObjects {
     fn new(device: &DeviceFunctions) -> Self {
-        let mut timeline = vk::SemaphoreTypeCreateInfo::builder()
-            .semaphore_type(vk::SemaphoreType::TIMELINE)
-            .initial_value(0);
-
-        let info = vk::SemaphoreCreateInfo::builder()
-            .push_next(&mut timeline);
+        let ready = if device.capabilities.has_timeline_semaphore() {
+            let mut timeline = vk::SemaphoreTypeCreateInfo::builder()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+
+            let info = vk::SemaphoreCreateInfo::builder()
+                .push_next(&mut timeline);
+
+            AcquireReady::Timeline {
+                semaphore: Semaphore::new(unsafe {
+                    device.vk.create_semaphore(&info, None)
+                }.unwrap()),
+                wait_value: AtomicU64::new(0),
+            }
+        } else {
+            let info = vk::FenceCreateInfo::builder()
+                .flags(vk::FenceCreateFlags::SIGNALED);
 
-        let ready_semaphore = Semaphore::new(unsafe {
-            device.vk.create_semaphore(&info, None)
-        }.unwrap());
+            AcquireReady::Fence(unsafe {
+                device.vk.create_fence(&info, None)
+            }.unwrap())
+        };
 
         let info = vk::SemaphoreCreateInfo::builder();
 
@@ -490,34 +920,56 @@ impl AcquireObjects {
         }.unwrap());
 
         Self {
-            ready_semaphore,
-            ready_wait_value: AtomicU64::new(0),
+            ready,
             acquire_semaphore
         }
     }
 
-    fn wait_and_get(&self, device: &DeviceFunctions, timeout: u64) -> Option<(SemaphoreOp, Semaphore)> {
-        let semaphore = self.ready_semaphore.get_handle();
-        loop {
-            let value = self.ready_wait_value.load(Ordering::SeqCst);
-            let wait = vk::SemaphoreWaitInfo::builder()
-                .semaphores(std::slice::from_ref(&semaphore))
-                .values(std::slice::from_ref(&value));
-
-            match unsafe {
-                device.timeline_semaphore_khr.wait_semaphores(&wait, timeout)
-            } {
-                Ok(_) => {
-                    let next = value + 1;
-                    if self.ready_wait_value.compare_exchange(value, next, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
-                        return Some((SemaphoreOp::new_timeline(self.ready_semaphore, next), self.acquire_semaphore));
+    /// Waits until the acquire semaphore can be reused. Returns the semaphore signal op or fence
+    /// which must be signaled once the acquire semaphore can be used again and the acquire
+    /// semaphore itself.
+    fn wait_and_get(&self, device: &DeviceFunctions, timeout: u64) -> Option<(Option<SemaphoreOp>, Option<vk::Fence>, Semaphore)> {
+        match &self.ready {
+            AcquireReady::Timeline { semaphore, wait_value } => {
+                let handle = semaphore.get_handle();
+                loop {
+                    let value = wait_value.load(Ordering::SeqCst);
+                    let wait = vk::SemaphoreWaitInfo::builder()
+                        .semaphores(std::slice::from_ref(&handle))
+                        .values(std::slice::from_ref(&value));
+
+                    match unsafe {
+                        device.timeline_semaphore_khr.wait_semaphores(&wait, timeout)
+                    } {
+                        Ok(_) => {
+                            let next = value + 1;
+                            if wait_value.compare_exchange(value, next, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                                return Some((Some(SemaphoreOp::new_timeline(*semaphore, next)), None, self.acquire_semaphore));
+                            }
+                        }
+                        Err(vk::Result::TIMEOUT) => {
+                            return None;
+                        },
+                        Err(err) => {
+                            panic!("Error while waiting for semaphore {:?}", err);
+                        }
                     }
                 }
-                Err(vk::Result::TIMEOUT) => {
-                    return None;
-                },
-                Err(err) => {
-                    panic!("Error while waiting for semaphore {:?}", err);
+            }
+            AcquireReady::Fence(fence) => {
+                match unsafe {
+                    device.vk.wait_for_fences(std::slice::from_ref(fence), true, timeout)
+                } {
+                    Ok(_) => {
+                        unsafe {
+                            device.vk.reset_fences(std::slice::from_ref(fence))
+                        }.unwrap();
+                        Some((None, Some(*fence), self.acquire_semaphore))
+                    }
+                    Err(vk::Result::TIMEOUT) => None,
+                    Err(err) => {
+                        panic!("Error while waiting for fence {:?}", err);
+                    }
                 }
             }
         }
@@ -526,7 +978,10 @@ impl AcquireObjects {
     fn destroy(&mut self, device: &DeviceFunctions) {
         unsafe {
             device.vk.destroy_semaphore(self.acquire_semaphore.get_handle(), None);
-            device.vk.destroy_semaphore(self.ready_semaphore.get_handle(), None);
+            match &self.ready {
+                AcquireReady::Timeline { semaphore, .. } => device.vk.destroy_semaphore(semaphore.get_handle(), None),
+                AcquireReady::Fence(fence) => device.vk.destroy_fence(*fence, None),
+            }
         }
     }
 }
@@ -598,7 +1053,11 @@ pub struct AcquiredImageInfo {
     /// Semaphore wait op waiting for the acquire operation to complete.
     pub acquire_semaphore: SemaphoreOp,
     /// Semaphore signal op which should be signaled when the acquire semaphore can be used again.
-    pub acquire_ready_semaphore: SemaphoreOp,
+    /// [`None`] if timeline semaphores are not supported.
+    pub acquire_ready_semaphore: Option<SemaphoreOp>,
+    /// Set if timeline semaphores are not supported. The fence must be signaled by an empty
+    /// submission queued after all submissions waiting on the acquire semaphore.
+    pub acquire_ready_fence: Option<vk::Fence>,
     /// The index of the swapchain image acquired.
     pub image_index: u32,
 }
\ No newline at end of file
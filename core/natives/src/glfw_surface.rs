@@ -1,9 +1,12 @@
 use std::ffi::{c_void, CStr, CString};
 use std::os::raw::c_char;
 use std::panic::catch_unwind;
-use std::process::exit;
+use std::sync::Mutex;
 use ash::vk;
-use crate::vk::objects::surface::{SurfaceInitError, SurfaceProvider};
+use crate::c_api::{on_panic, SURFACE_HANDLES};
+use crate::vk::objects::surface::{DisplayMode, SurfaceInitError, SurfaceProvider};
+
+use crate::prelude::*;
 
 #[allow(non_camel_case_types)]
 pub type PFN_glfwInitVulkanLoader = unsafe extern "C" fn(vk::PFN_vkGetInstanceProcAddr);
@@ -14,11 +17,99 @@ pub type PFN_glfwGetRequiredInstanceExtensions = unsafe extern "C" fn(*mut u32)
 #[allow(non_camel_case_types)]
 pub type PFN_glfwCreateWindowSurface = unsafe extern "C" fn(vk::Instance, *const c_void, *const vk::AllocationCallbacks, *mut vk::SurfaceKHR) -> vk::Result;
 
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetFramebufferSize = unsafe extern "C" fn(*const c_void, *mut i32, *mut i32);
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetWindowContentScale = unsafe extern "C" fn(*const c_void, *mut f32, *mut f32);
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetPrimaryMonitor = unsafe extern "C" fn() -> *const c_void;
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetWindowMonitor = unsafe extern "C" fn(*const c_void) -> *const c_void;
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetVideoModes = unsafe extern "C" fn(*const c_void, *mut i32) -> *const GLFWVidMode;
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwSetWindowMonitor = unsafe extern "C" fn(*const c_void, *const c_void, i32, i32, i32, i32, i32);
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetWindowPos = unsafe extern "C" fn(*const c_void, *mut i32, *mut i32);
+
+#[allow(non_camel_case_types)]
+pub type PFN_glfwGetWindowSize = unsafe extern "C" fn(*const c_void, *mut i32, *mut i32);
+
+/// The GLFWvidmode struct of glfw.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GLFWVidMode {
+    width: i32,
+    height: i32,
+    red_bits: i32,
+    green_bits: i32,
+    blue_bits: i32,
+    refresh_rate: i32,
+}
+
+impl GLFWVidMode {
+    fn to_display_mode(&self) -> DisplayMode {
+        DisplayMode {
+            size: Vec2u32::new(self.width.max(0) as u32, self.height.max(0) as u32),
+            refresh_rate: self.refresh_rate.max(0) as u32,
+            bit_depth: (self.red_bits + self.green_bits + self.blue_bits).max(0) as u32,
+        }
+    }
+}
+
+/// The windowing platform glfw is using. Detected based on the required instance extensions.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GLFWPlatform {
+    Wayland,
+    X11,
+    Other,
+}
+
+impl GLFWPlatform {
+    fn from_extensions(extensions: &[CString]) -> Self {
+        let has_extension = |name: &str| extensions.iter().any(|ext| ext.as_bytes() == name.as_bytes());
+
+        if has_extension("VK_KHR_wayland_surface") {
+            Self::Wayland
+        } else if has_extension("VK_KHR_xlib_surface") || has_extension("VK_KHR_xcb_surface") {
+            Self::X11
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// The glfw functions needed to enumerate display modes and control exclusive fullscreen.
+#[derive(Copy, Clone)]
+pub struct GLFWDisplayFunctions {
+    pub get_primary_monitor: PFN_glfwGetPrimaryMonitor,
+    pub get_window_monitor: PFN_glfwGetWindowMonitor,
+    pub get_video_modes: PFN_glfwGetVideoModes,
+    pub set_window_monitor: PFN_glfwSetWindowMonitor,
+    pub get_window_pos: PFN_glfwGetWindowPos,
+    pub get_window_size: PFN_glfwGetWindowSize,
+}
+
+/// glfw uses -1 to let the monitor pick the refresh rate
+const GLFW_DONT_CARE: i32 = -1;
+
 pub struct GLFWSurfaceProvider {
     required_extension: Vec<CString>,
+    platform: GLFWPlatform,
     create_surface_fn: PFN_glfwCreateWindowSurface,
+    get_framebuffer_size_fn: Option<PFN_glfwGetFramebufferSize>,
+    get_window_content_scale_fn: Option<PFN_glfwGetWindowContentScale>,
+    display_fns: Option<GLFWDisplayFunctions>,
     glfw_window: *const c_void,
     surface: Option<(vk::SurfaceKHR, ash::extensions::khr::Surface)>,
+    /// The position and size of the window before it entered exclusive fullscreen
+    windowed_rect: Mutex<Option<(Vec2i32, Vec2i32)>>,
 }
 
 impl GLFWSurfaceProvider {
@@ -26,6 +117,9 @@ impl GLFWSurfaceProvider {
         window: *const c_void,
         glfw_get_required_instance_extensions: PFN_glfwGetRequiredInstanceExtensions,
         glfw_create_window_surface: PFN_glfwCreateWindowSurface,
+        glfw_get_framebuffer_size: Option<PFN_glfwGetFramebufferSize>,
+        glfw_get_window_content_scale: Option<PFN_glfwGetWindowContentScale>,
+        glfw_display_functions: Option<GLFWDisplayFunctions>,
     ) -> Self {
         let mut count = 0u32;
         let extensions = unsafe { glfw_get_required_instance_extensions(&mut count) };
@@ -39,13 +133,53 @@ impl GLFWSurfaceProvider {
             unsafe { CString::from(CStr::from_ptr(*str)) }
         }).collect();
 
+        let platform = GLFWPlatform::from_extensions(&extensions);
+        log::info!("Detected glfw platform {:?}", platform);
+
         Self {
             required_extension: extensions,
+            platform,
             create_surface_fn: glfw_create_window_surface,
+            get_framebuffer_size_fn: glfw_get_framebuffer_size,
+            get_window_content_scale_fn: glfw_get_window_content_scale,
+            display_fns: glfw_display_functions,
             glfw_window: window,
-            surface: None
+            surface: None,
+            windowed_rect: Mutex::new(None),
+        }
+    }
+
+    pub fn get_platform(&self) -> GLFWPlatform {
+        self.platform
+    }
+
+    /// Returns the monitor the window is fullscreen on. Windowed glfw windows are not associated
+    /// with any monitor so the primary monitor is used for them.
+    fn get_current_monitor(&self, fns: &GLFWDisplayFunctions) -> Option<*const c_void> {
+        let monitor = unsafe { (fns.get_window_monitor)(self.glfw_window) };
+        let monitor = if monitor.is_null() {
+            unsafe { (fns.get_primary_monitor)() }
+        } else {
+            monitor
+        };
+
+        if monitor.is_null() {
+            None
+        } else {
+            Some(monitor)
         }
     }
+
+    fn get_video_modes(fns: &GLFWDisplayFunctions, monitor: *const c_void) -> &'static [GLFWVidMode] {
+        let mut count = 0i32;
+        let modes = unsafe { (fns.get_video_modes)(monitor, &mut count) };
+        if modes.is_null() || count <= 0 {
+            return &[];
+        }
+
+        // glfw keeps the array alive until the monitor is disconnected or the function is called again
+        unsafe { std::slice::from_raw_parts(modes, count as usize) }
+    }
 }
 
 impl SurfaceProvider for GLFWSurfaceProvider {
@@ -54,6 +188,11 @@ impl SurfaceProvider for GLFWSurfaceProvider {
     }
 
     fn init(&mut self, entry: &ash::Entry, instance: &ash::Instance) -> Result<vk::SurfaceKHR, SurfaceInitError> {
+        // The provider may be reinitialized if the surface has been lost
+        if let Some(old) = self.surface.take() {
+            unsafe { old.1.destroy_surface(old.0, None) };
+        }
+
         let surface_khr = ash::extensions::khr::Surface::new(entry, instance);
 
         let mut surface = vk::SurfaceKHR::null();
@@ -66,6 +205,104 @@ impl SurfaceProvider for GLFWSurfaceProvider {
     fn get_handle(&self) -> Option<vk::SurfaceKHR> {
         self.surface.as_ref().map(|s| s.0)
     }
+
+    fn get_framebuffer_size(&self) -> Option<Vec2u32> {
+        // Only wayland surfaces lack a current extent. On all other platforms the surface
+        // capabilities are authoritative.
+        if self.platform != GLFWPlatform::Wayland {
+            return None;
+        }
+
+        let func = self.get_framebuffer_size_fn?;
+        let mut width = 0i32;
+        let mut height = 0i32;
+        unsafe { func(self.glfw_window, &mut width, &mut height) };
+
+        Some(Vec2u32::new(width.max(0) as u32, height.max(0) as u32))
+    }
+
+    fn get_content_scale(&self) -> Vec2f32 {
+        let func = match self.get_window_content_scale_fn {
+            Some(func) => func,
+            None => return Vec2f32::new(1f32, 1f32),
+        };
+
+        let mut x = 1f32;
+        let mut y = 1f32;
+        unsafe { func(self.glfw_window, &mut x, &mut y) };
+
+        if x.is_finite() && x > 0f32 && y.is_finite() && y > 0f32 {
+            Vec2f32::new(x, y)
+        } else {
+            Vec2f32::new(1f32, 1f32)
+        }
+    }
+
+    fn get_display_modes(&self) -> Vec<DisplayMode> {
+        let fns = match &self.display_fns {
+            Some(fns) => fns,
+            None => return Vec::new(),
+        };
+
+        match self.get_current_monitor(fns) {
+            Some(monitor) => Self::get_video_modes(fns, monitor).iter().map(GLFWVidMode::to_display_mode).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn set_exclusive_fullscreen(&self, mode: Option<DisplayMode>) -> bool {
+        let fns = match &self.display_fns {
+            Some(fns) => fns,
+            None => return false,
+        };
+
+        let fullscreen = !unsafe { (fns.get_window_monitor)(self.glfw_window) }.is_null();
+        let mut windowed_rect = self.windowed_rect.lock().unwrap();
+
+        let mode = match mode {
+            Some(mode) => mode,
+            None => {
+                if fullscreen {
+                    let (position, size) = windowed_rect.take().unwrap_or_else(|| {
+                        let mut size = Vec2i32::new(0, 0);
+                        unsafe { (fns.get_window_size)(self.glfw_window, &mut size[0], &mut size[1]) };
+                        (Vec2i32::new(0, 0), size)
+                    });
+                    unsafe { (fns.set_window_monitor)(self.glfw_window, std::ptr::null(), position[0], position[1], size[0], size[1], GLFW_DONT_CARE) };
+                }
+                return true;
+            }
+        };
+
+        let monitor = match self.get_current_monitor(fns) {
+            Some(monitor) => monitor,
+            None => {
+                log::warn!("Failed to find a monitor for exclusive fullscreen");
+                return false;
+            }
+        };
+
+        let supported = Self::get_video_modes(fns, monitor).iter().any(|video_mode| video_mode.to_display_mode() == mode);
+        let refresh_rate = if supported {
+            mode.refresh_rate as i32
+        } else {
+            log::warn!("Display mode {:?} is not supported. Falling back to the closest display mode", mode);
+            GLFW_DONT_CARE
+        };
+
+        if !fullscreen {
+            let mut position = Vec2i32::new(0, 0);
+            let mut size = Vec2i32::new(0, 0);
+            unsafe {
+                (fns.get_window_pos)(self.glfw_window, &mut position[0], &mut position[1]);
+                (fns.get_window_size)(self.glfw_window, &mut size[0], &mut size[1]);
+            }
+            *windowed_rect = Some((position, size));
+        }
+
+        unsafe { (fns.set_window_monitor)(self.glfw_window, monitor, 0, 0, mode.size[0] as i32, mode.size[1] as i32, refresh_rate) };
+        true
+    }
 }
 
 // THIS IS NOT CORRECT!!! TODO find a better way
@@ -83,30 +320,50 @@ impl Drop for GLFWSurfaceProvider {
 }
 
 #[no_mangle]
-unsafe extern "C" fn b4d_pre_init_glfw(func: PFN_glfwInitVulkanLoader) {
+unsafe extern "C-unwind" fn b4d_pre_init_glfw(func: PFN_glfwInitVulkanLoader) {
     catch_unwind(|| {
         let entry = ash::Entry::linked();
         func(entry.static_fn().get_instance_proc_addr);
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_pre_init_glfw");
-        exit(1);
-    })
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_pre_init_glfw"))
 }
 
+/// Creates a surface provider for a glfw window. The returned handle is consumed by `b4d_init`.
 #[no_mangle]
-unsafe extern "C" fn b4d_create_glfw_surface_provider(
+unsafe extern "C-unwind" fn b4d_create_glfw_surface_provider(
     window: *const c_void,
     glfw_get_required_instance_extensions: PFN_glfwGetRequiredInstanceExtensions,
     glfw_create_window_surface: PFN_glfwCreateWindowSurface,
-) -> *mut GLFWSurfaceProvider {
+    glfw_get_framebuffer_size: Option<PFN_glfwGetFramebufferSize>,
+    glfw_get_window_content_scale: Option<PFN_glfwGetWindowContentScale>,
+    glfw_get_primary_monitor: Option<PFN_glfwGetPrimaryMonitor>,
+    glfw_get_window_monitor: Option<PFN_glfwGetWindowMonitor>,
+    glfw_get_video_modes: Option<PFN_glfwGetVideoModes>,
+    glfw_set_window_monitor: Option<PFN_glfwSetWindowMonitor>,
+    glfw_get_window_pos: Option<PFN_glfwGetWindowPos>,
+    glfw_get_window_size: Option<PFN_glfwGetWindowSize>,
+) -> *mut Box<dyn SurfaceProvider> {
     catch_unwind(|| {
-        Box::leak(Box::new(GLFWSurfaceProvider::new(
+        // Display modes and exclusive fullscreen are only supported if all functions are provided
+        let display_functions = match (glfw_get_primary_monitor, glfw_get_window_monitor, glfw_get_video_modes, glfw_set_window_monitor, glfw_get_window_pos, glfw_get_window_size) {
+            (Some(get_primary_monitor), Some(get_window_monitor), Some(get_video_modes), Some(set_window_monitor), Some(get_window_pos), Some(get_window_size)) => Some(GLFWDisplayFunctions {
+                get_primary_monitor,
+                get_window_monitor,
+                get_video_modes,
+                set_window_monitor,
+                get_window_pos,
+                get_window_size,
+            }),
+            _ => None,
+        };
+
+        let provider: Box<dyn SurfaceProvider> = Box::new(GLFWSurfaceProvider::new(
             window,
             glfw_get_required_instance_extensions,
-            glfw_create_window_surface
-        )))
-    }).unwrap_or_else(|_| {
-        log::error!("panic in b4d_create_glfw_surface_provider");
-        exit(1);
-    })
+            glfw_create_window_surface,
+            glfw_get_framebuffer_size,
+            glfw_get_window_content_scale,
+            display_functions
+        ));
+        SURFACE_HANDLES.insert(Box::new(provider))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_glfw_surface_provider"))
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+//! A [`SurfaceProvider`] which is not backed by any window.
+//!
+//! Uses VK_EXT_headless_surface so the full swapchain path can be used in benchmarks and CI
+//! environments without a display server.
+
+use std::ffi::CString;
+use std::panic::catch_unwind;
+use ash::vk;
+use crate::c_api::{on_panic, reject, SURFACE_HANDLES};
+use crate::c_validation::CApiError;
+use crate::vk::objects::surface::{SurfaceInitError, SurfaceProvider};
+
+use crate::prelude::*;
+
+pub struct HeadlessSurfaceProvider {
+    size: Vec2u32,
+    surface: Option<(vk::SurfaceKHR, ash::extensions::khr::Surface)>,
+}
+
+impl HeadlessSurfaceProvider {
+    /// Creates a new headless surface provider. Since headless surfaces have no extent the
+    /// swapchain is always created with the provided size.
+    pub fn new(size: Vec2u32) -> Self {
+        Self {
+            size,
+            surface: None,
+        }
+    }
+
+    pub fn get_size(&self) -> Vec2u32 {
+        self.size
+    }
+}
+
+impl SurfaceProvider for HeadlessSurfaceProvider {
+    fn get_required_instance_extensions(&self) -> Vec<CString> {
+        vec![
+            CString::from(ash::extensions::khr::Surface::name()),
+            CString::from(ash::extensions::ext::HeadlessSurface::name()),
+        ]
+    }
+
+    fn init(&mut self, entry: &ash::Entry, instance: &ash::Instance) -> Result<vk::SurfaceKHR, SurfaceInitError> {
+        // The provider may be reinitialized if the surface has been lost
+        if let Some(old) = self.surface.take() {
+            unsafe { old.1.destroy_surface(old.0, None) };
+        }
+
+        let surface_khr = ash::extensions::khr::Surface::new(entry, instance);
+        let headless_ext = ash::extensions::ext::HeadlessSurface::new(entry, instance);
+
+        let info = vk::HeadlessSurfaceCreateInfoEXT::builder();
+        let surface = unsafe { headless_ext.create_headless_surface(&info, None) }?;
+        self.surface = Some((surface, surface_khr));
+
+        Ok(surface)
+    }
+
+    fn get_handle(&self) -> Option<vk::SurfaceKHR> {
+        self.surface.as_ref().map(|s| s.0)
+    }
+
+    fn get_framebuffer_size(&self) -> Option<Vec2u32> {
+        // Headless surfaces report an undefined current extent
+        Some(self.size)
+    }
+}
+
+impl Drop for HeadlessSurfaceProvider {
+    fn drop(&mut self) {
+        self.surface.take().map(|s| {
+            unsafe { s.1.destroy_surface(s.0, None) };
+        });
+    }
+}
+
+/// Creates a headless surface provider with a size of `width` by `height` pixels. The returned
+/// provider can be passed to `b4d_init` and `b4d_init_with_config` like any other provider.
+#[no_mangle]
+unsafe extern "C-unwind" fn b4d_create_headless_surface(width: u32, height: u32) -> *mut Box<dyn SurfaceProvider> {
+    catch_unwind(|| {
+        if width == 0 || height == 0 {
+            log::error!("Passed zero size {}x{} to b4d_create_headless_surface", width, height);
+            reject(CApiError::InvalidSize("size"));
+        }
+
+        let provider: Box<dyn SurfaceProvider> = Box::new(HeadlessSurfaceProvider::new(Vec2u32::new(width, height)));
+        SURFACE_HANDLES.insert(Box::new(provider))
+    }).unwrap_or_else(|payload| on_panic(payload, "b4d_create_headless_surface"))
+}
@@ -0,0 +1,242 @@
+//! Tracking of host memory allocated by the vulkan implementation.
+//!
+//! If enabled the instance and device are created with allocation callbacks which forward to the
+//! rust allocator and count all allocations. Objects created with a null allocator use the
+//! callbacks of their parent so this covers all objects created by the renderer. Vulkan only
+//! reports the allocation scope of each allocation so usage is tracked per scope.
+
+use std::alloc::Layout;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ash::vk;
+
+/// The allocation scopes reported by the implementation. The discriminants match
+/// `VkSystemAllocationScope`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum HostMemoryScope {
+    Command = 0,
+    Object = 1,
+    Cache = 2,
+    Device = 3,
+    Instance = 4,
+}
+
+impl HostMemoryScope {
+    pub const ALL: [HostMemoryScope; 5] = [Self::Command, Self::Object, Self::Cache, Self::Device, Self::Instance];
+
+    pub fn from_raw(scope: vk::SystemAllocationScope) -> Option<Self> {
+        match scope {
+            vk::SystemAllocationScope::COMMAND => Some(Self::Command),
+            vk::SystemAllocationScope::OBJECT => Some(Self::Object),
+            vk::SystemAllocationScope::CACHE => Some(Self::Cache),
+            vk::SystemAllocationScope::DEVICE => Some(Self::Device),
+            vk::SystemAllocationScope::INSTANCE => Some(Self::Instance),
+            _ => None,
+        }
+    }
+}
+
+/// A snapshot of the host memory usage of a single scope.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct HostMemoryStats {
+    /// The number of bytes currently allocated.
+    pub current_bytes: u64,
+    /// The highest number of bytes allocated at the same time.
+    pub peak_bytes: u64,
+    /// The number of allocations currently alive.
+    pub live_allocations: u64,
+    /// The number of allocations made since the instance has been created.
+    pub total_allocations: u64,
+    /// The number of bytes of internal (executable) allocations made by the implementation
+    /// itself and reported through the notification callbacks.
+    pub internal_bytes: u64,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HostMemoryReport {
+    /// The stats of each scope indexed by [`HostMemoryScope`].
+    pub scopes: [HostMemoryStats; 5],
+}
+
+impl HostMemoryReport {
+    pub fn get(&self, scope: HostMemoryScope) -> &HostMemoryStats {
+        &self.scopes[scope as usize]
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.scopes.iter().map(|stats| stats.current_bytes + stats.internal_bytes).sum()
+    }
+}
+
+#[derive(Default)]
+struct ScopeCounters {
+    current_bytes: AtomicU64,
+    peak_bytes: AtomicU64,
+    live_allocations: AtomicU64,
+    total_allocations: AtomicU64,
+    internal_bytes: AtomicU64,
+}
+
+impl ScopeCounters {
+    fn on_alloc(&self, size: u64) {
+        let current = self.current_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_bytes.fetch_max(current, Ordering::Relaxed);
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        self.total_allocations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_free(&self, size: u64) {
+        self.current_bytes.fetch_sub(size, Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HostMemoryStats {
+        HostMemoryStats {
+            current_bytes: self.current_bytes.load(Ordering::Relaxed),
+            peak_bytes: self.peak_bytes.load(Ordering::Relaxed),
+            live_allocations: self.live_allocations.load(Ordering::Relaxed),
+            total_allocations: self.total_allocations.load(Ordering::Relaxed),
+            internal_bytes: self.internal_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts host allocations made through the allocation callbacks returned by
+/// [`HostMemoryTracker::get_allocation_callbacks`]. The tracker must outlive all objects created
+/// with the callbacks.
+#[derive(Default)]
+pub struct HostMemoryTracker {
+    scopes: [ScopeCounters; 5],
+}
+
+impl HostMemoryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_report(&self) -> HostMemoryReport {
+        HostMemoryReport {
+            scopes: [
+                self.scopes[0].snapshot(),
+                self.scopes[1].snapshot(),
+                self.scopes[2].snapshot(),
+                self.scopes[3].snapshot(),
+                self.scopes[4].snapshot(),
+            ]
+        }
+    }
+
+    /// Returns allocation callbacks which record into this tracker. The same callbacks must be
+    /// passed when destroying objects created with them.
+    pub fn get_allocation_callbacks(&self) -> vk::AllocationCallbacks {
+        vk::AllocationCallbacks {
+            p_user_data: self as *const Self as *mut c_void,
+            pfn_allocation: Some(allocation),
+            pfn_reallocation: Some(reallocation),
+            pfn_free: Some(free),
+            pfn_internal_allocation: Some(internal_allocation),
+            pfn_internal_free: Some(internal_free),
+        }
+    }
+
+    fn counters(&self, scope: vk::SystemAllocationScope) -> &ScopeCounters {
+        let scope = HostMemoryScope::from_raw(scope).unwrap_or(HostMemoryScope::Object);
+        &self.scopes[scope as usize]
+    }
+}
+
+/// Each allocation is prefixed by a header storing the information needed to free it.
+#[repr(C)]
+struct AllocationHeader {
+    size: usize,
+    alignment: usize,
+    scope: vk::SystemAllocationScope,
+}
+
+/// The offset from the start of the allocation to the returned pointer. Must be a multiple of
+/// the alignment and large enough to fit the header.
+fn header_offset(alignment: usize) -> usize {
+    std::cmp::max(alignment, std::mem::size_of::<AllocationHeader>().next_power_of_two())
+}
+
+unsafe fn header_of(memory: *mut c_void) -> *mut AllocationHeader {
+    (memory as *mut u8).sub(std::mem::size_of::<AllocationHeader>()) as *mut AllocationHeader
+}
+
+unsafe fn tracked_alloc(tracker: &HostMemoryTracker, size: usize, alignment: usize, scope: vk::SystemAllocationScope) -> *mut c_void {
+    if size == 0 || !alignment.is_power_of_two() {
+        return std::ptr::null_mut();
+    }
+    let offset = header_offset(alignment);
+    let layout = match Layout::from_size_align(size + offset, std::cmp::max(alignment, std::mem::align_of::<AllocationHeader>())) {
+        Ok(layout) => layout,
+        Err(_) => return std::ptr::null_mut(),
+    };
+
+    let base = std::alloc::alloc(layout);
+    if base.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let memory = base.add(offset) as *mut c_void;
+    header_of(memory).write(AllocationHeader { size, alignment, scope });
+    tracker.counters(scope).on_alloc(size as u64);
+
+    memory
+}
+
+unsafe fn tracked_free(tracker: &HostMemoryTracker, memory: *mut c_void) {
+    if memory.is_null() {
+        return;
+    }
+    let header = header_of(memory).read();
+    tracker.counters(header.scope).on_free(header.size as u64);
+
+    let offset = header_offset(header.alignment);
+    let layout = Layout::from_size_align_unchecked(header.size + offset, std::cmp::max(header.alignment, std::mem::align_of::<AllocationHeader>()));
+    std::alloc::dealloc((memory as *mut u8).sub(offset), layout);
+}
+
+unsafe extern "system" fn allocation(user_data: *mut c_void, size: usize, alignment: usize, scope: vk::SystemAllocationScope) -> *mut c_void {
+    let tracker = &*(user_data as *const HostMemoryTracker);
+    tracked_alloc(tracker, size, alignment, scope)
+}
+
+unsafe extern "system" fn reallocation(user_data: *mut c_void, original: *mut c_void, size: usize, alignment: usize, scope: vk::SystemAllocationScope) -> *mut c_void {
+    let tracker = &*(user_data as *const HostMemoryTracker);
+    if original.is_null() {
+        return tracked_alloc(tracker, size, alignment, scope);
+    }
+    if size == 0 {
+        tracked_free(tracker, original);
+        return std::ptr::null_mut();
+    }
+
+    // The spec requires the original scope to be kept
+    let old = header_of(original).read();
+    let memory = tracked_alloc(tracker, size, alignment, old.scope);
+    if memory.is_null() {
+        // The original allocation must stay valid if reallocation fails
+        return std::ptr::null_mut();
+    }
+    std::ptr::copy_nonoverlapping(original as *const u8, memory as *mut u8, std::cmp::min(old.size, size));
+    tracked_free(tracker, original);
+
+    memory
+}
+
+unsafe extern "system" fn free(user_data: *mut c_void, memory: *mut c_void) {
+    let tracker = &*(user_data as *const HostMemoryTracker);
+    tracked_free(tracker, memory);
+}
+
+unsafe extern "system" fn internal_allocation(user_data: *mut c_void, size: usize, _: vk::InternalAllocationType, scope: vk::SystemAllocationScope) {
+    let tracker = &*(user_data as *const HostMemoryTracker);
+    tracker.counters(scope).internal_bytes.fetch_add(size as u64, Ordering::Relaxed);
+}
+
+unsafe extern "system" fn internal_free(user_data: *mut c_void, size: usize, _: vk::InternalAllocationType, scope: vk::SystemAllocationScope) {
+    let tracker = &*(user_data as *const HostMemoryTracker);
+    tracker.counters(scope).internal_bytes.fetch_sub(size as u64, Ordering::Relaxed);
+}
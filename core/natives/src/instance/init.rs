@@ -10,6 +10,7 @@ use vk_profiles_rs::vp;
 use crate::{BUILD_INFO, CRATE_NAME};
 
 use crate::instance::debug_messenger::DebugMessengerCallback;
+use crate::instance::host_memory::HostMemoryTracker;
 use crate::instance::instance::VulkanVersion;
 
 use crate::prelude::*;
@@ -20,8 +21,12 @@ pub struct InstanceCreateConfig {
     application_version: u32,
     debug_messengers: Vec<DebugUtilsMessengerWrapper>,
     enable_validation: bool,
+    gpu_assisted_validation: bool,
     required_extensions: HashSet<CString>,
+    optional_extensions: HashSet<CString>,
     require_surface_khr: bool,
+    enable_portability: bool,
+    track_host_memory: bool,
 }
 
 impl InstanceCreateConfig {
@@ -31,8 +36,12 @@ impl InstanceCreateConfig {
             application_version,
             debug_messengers: Vec::new(),
             enable_validation: false,
+            gpu_assisted_validation: false,
             required_extensions: HashSet::new(),
+            optional_extensions: HashSet::new(),
             require_surface_khr: false,
+            enable_portability: false,
+            track_host_memory: false,
         }
     }
 
@@ -44,13 +53,39 @@ impl InstanceCreateConfig {
         self.enable_validation = true;
     }
 
+    /// Enables gpu assisted validation if the validation layers are enabled and support
+    /// VK_EXT_validation_features. This detects out of bounds accesses in shaders but has a
+    /// significant performance cost.
+    pub fn enable_gpu_assisted_validation(&mut self) {
+        self.gpu_assisted_validation = true;
+    }
+
     pub fn add_required_extension(&mut self, extension: &CStr) {
         self.required_extensions.insert(CString::from(extension));
     }
 
+    /// Adds a extension which will be enabled if it is supported. Use
+    /// [`InstanceContext::is_extension_enabled`] to check if the extension has been enabled.
+    pub fn add_optional_extension(&mut self, extension: &CStr) {
+        self.optional_extensions.insert(CString::from(extension));
+    }
+
     pub fn require_surface_khr(&mut self) {
         self.require_surface_khr = true;
     }
+
+    /// Enables VK_KHR_portability_enumeration if it is supported. This is required to use
+    /// non-conformant implementations like MoltenVK.
+    #[cfg(feature = "portability")]
+    pub fn enable_portability(&mut self) {
+        self.enable_portability = true;
+    }
+
+    /// Installs allocation callbacks tracking all host memory allocated by the implementation.
+    /// See [`crate::instance::host_memory`].
+    pub fn enable_host_memory_tracking(&mut self) {
+        self.track_host_memory = true;
+    }
 }
 
 #[derive(Debug)]
@@ -117,15 +152,62 @@ pub fn create_instance(config: InstanceCreateConfig) -> Result<Arc<InstanceConte
         }
     }
 
+    let mut enabled_extensions = required_extensions.clone();
+    for name in &config.optional_extensions {
+        if available_extensions.contains(name) {
+            if enabled_extensions.insert(name.clone()) {
+                required_extensions_str.push(name.as_c_str().as_ptr())
+            }
+        } else {
+            log::info!("Optional instance extension {:?} is not supported", name);
+        }
+    }
+
+    let mut create_flags = vk::InstanceCreateFlags::empty();
+    let portability_enumeration_name = CString::new("VK_KHR_portability_enumeration").unwrap();
+    if config.enable_portability {
+        if available_extensions.contains(&portability_enumeration_name) {
+            if enabled_extensions.insert(portability_enumeration_name.clone()) {
+                required_extensions_str.push(portability_enumeration_name.as_c_str().as_ptr());
+            }
+            create_flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
+        } else {
+            log::info!("VK_KHR_portability_enumeration is not supported. Portability devices will not be available");
+        }
+    }
+
+    let validation_layer_name = CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap();
     let required_layers = if config.enable_validation {
         log::info!("Validation layers enabled");
-        vec![CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap().as_ptr()]
+        vec![validation_layer_name.as_ptr()]
     } else {
         log::info!("Validation layers disabled");
         Vec::new()
     };
 
-    let max_api_version = VulkanVersion::VK_1_1;
+    // VK_EXT_validation_features is provided by the validation layer itself
+    let validation_features_name = CString::new("VK_EXT_validation_features").unwrap();
+    let gpu_assisted_validation = config.enable_validation && config.gpu_assisted_validation && {
+        let layer_extensions = entry.enumerate_instance_extension_properties(Some(validation_layer_name)).unwrap_or_default();
+        layer_extensions.iter().any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == validation_features_name.as_c_str())
+    };
+    if gpu_assisted_validation {
+        log::info!("Gpu assisted validation enabled");
+        if enabled_extensions.insert(validation_features_name.clone()) {
+            required_extensions_str.push(validation_features_name.as_c_str().as_ptr());
+        }
+    } else if config.gpu_assisted_validation {
+        log::warn!("Gpu assisted validation requested but not supported. Validation layers must be enabled and support VK_EXT_validation_features");
+    }
+    let enabled_validation_features = [
+        vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+        vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+    ];
+    let mut validation_features = vk::ValidationFeaturesEXT::builder()
+        .enabled_validation_features(&enabled_validation_features);
+
+    // Devices use the lower of this and their own version. See [`crate::device::capabilities`].
+    let max_api_version = VulkanVersion::VK_1_3;
     let name = CString::new(CRATE_NAME).unwrap();
     let application_info = vk::ApplicationInfo::builder()
         .application_name(config.application_name.as_c_str())
@@ -135,6 +217,7 @@ pub fn create_instance(config: InstanceCreateConfig) -> Result<Arc<InstanceConte
         .api_version(max_api_version.into());
 
     let mut instance_create_info = vk::InstanceCreateInfo::builder()
+        .flags(create_flags)
         .application_info(&application_info)
         .enabled_layer_names(required_layers.as_slice())
         .enabled_extension_names(required_extensions_str.as_slice());
@@ -151,13 +234,23 @@ pub fn create_instance(config: InstanceCreateConfig) -> Result<Arc<InstanceConte
     for debug_messenger in debug_messenger_create_infos.iter_mut() {
         instance_create_info = instance_create_info.push_next(debug_messenger);
     }
+    if gpu_assisted_validation {
+        instance_create_info = instance_create_info.push_next(&mut validation_features);
+    }
 
     let vp_instance_create_info = vp::InstanceCreateInfo::builder()
         .profile(&profile)
         .create_info(&instance_create_info)
         .flags(vp::InstanceCreateFlagBits::MERGE_EXTENSIONS);
 
-    let instance = unsafe { vp_fn.create_instance(&entry, &vp_instance_create_info, None) }?;
+    let host_memory_tracker = if config.track_host_memory {
+        Some(Box::new(HostMemoryTracker::new()))
+    } else {
+        None
+    };
+    let allocation_callbacks = host_memory_tracker.as_ref().map(|tracker| tracker.get_allocation_callbacks());
+
+    let instance = unsafe { vp_fn.create_instance(&entry, &vp_instance_create_info, allocation_callbacks.as_ref()) }?;
 
     let surface_khr = if required_extensions.contains(CStr::from_bytes_with_nul(b"VK_KHR_surface\0").unwrap()) {
         Some(ash::extensions::khr::Surface::new(&entry, &instance))
@@ -172,7 +265,9 @@ pub fn create_instance(config: InstanceCreateConfig) -> Result<Arc<InstanceConte
         entry,
         instance,
         surface_khr,
-        debug_messengers
+        enabled_extensions,
+        debug_messengers,
+        host_memory_tracker
     ))
 }
 
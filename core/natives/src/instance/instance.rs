@@ -1,12 +1,15 @@
 use core::panic::{UnwindSafe, RefUnwindSafe};
 
 use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ffi::{CStr, CString};
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 
 use ash::vk;
 use vk_profiles_rs::vp;
 
+use crate::instance::host_memory::{HostMemoryReport, HostMemoryTracker};
 use crate::instance::init::DebugUtilsMessengerWrapper;
 
 use crate::prelude::*;
@@ -67,7 +70,10 @@ pub struct InstanceContext {
     entry: ash::Entry,
     instance: ash::Instance,
     surface_khr: Option<ash::extensions::khr::Surface>,
+    enabled_extensions: HashSet<CString>,
     _debug_messengers: Box<[DebugUtilsMessengerWrapper]>,
+    /// Boxed since the allocation callbacks reference it. Must be dropped after the instance.
+    host_memory_tracker: Option<Box<HostMemoryTracker>>,
 }
 
 impl InstanceContext {
@@ -77,7 +83,9 @@ impl InstanceContext {
         entry: ash::Entry,
         instance: ash::Instance,
         surface_khr: Option<ash::extensions::khr::Surface>,
-        debug_messengers: Box<[DebugUtilsMessengerWrapper]>
+        enabled_extensions: HashSet<CString>,
+        debug_messengers: Box<[DebugUtilsMessengerWrapper]>,
+        host_memory_tracker: Option<Box<HostMemoryTracker>>,
     ) -> Arc<Self> {
         Arc::new(Self {
             id: NamedUUID::with_str("Instance"),
@@ -86,7 +94,9 @@ impl InstanceContext {
             entry,
             instance,
             surface_khr,
+            enabled_extensions,
             _debug_messengers: debug_messengers,
+            host_memory_tracker,
         })
     }
 
@@ -106,6 +116,11 @@ impl InstanceContext {
         self.surface_khr.as_ref()
     }
 
+    /// Returns true if the extension has been enabled on this instance.
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        self.enabled_extensions.contains(name)
+    }
+
     pub fn get_version(&self) -> VulkanVersion {
         self.version
     }
@@ -113,12 +128,25 @@ impl InstanceContext {
     pub fn get_profile(&self) -> &vp::ProfileProperties {
         &self.profile
     }
+
+    /// Returns the allocation callbacks used to create the instance and device or [`None`] if
+    /// host memory tracking is disabled.
+    pub fn get_allocation_callbacks(&self) -> Option<vk::AllocationCallbacks> {
+        self.host_memory_tracker.as_ref().map(|tracker| tracker.get_allocation_callbacks())
+    }
+
+    /// Returns the current host memory usage of the implementation or [`None`] if host memory
+    /// tracking is disabled.
+    pub fn get_host_memory_report(&self) -> Option<HostMemoryReport> {
+        self.host_memory_tracker.as_ref().map(|tracker| tracker.get_report())
+    }
 }
 
 impl Drop for InstanceContext {
     fn drop(&mut self) {
         unsafe {
-            self.instance.destroy_instance(None);
+            let allocation_callbacks = self.get_allocation_callbacks();
+            self.instance.destroy_instance(allocation_callbacks.as_ref());
         }
     }
 }
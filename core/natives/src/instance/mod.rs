@@ -1,3 +1,4 @@
 pub mod init;
 pub mod instance;
 pub mod debug_messenger;
+pub mod host_memory;
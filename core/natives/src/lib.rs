@@ -13,9 +13,21 @@ pub mod util;
 pub mod b4d;
 
 mod glfw_surface;
+pub mod headless_surface;
 pub mod window;
 mod c_api;
 mod c_log;
+/// The structs passed to the c api. Exported in fuzzing builds so the fuzz target uses the same
+/// layout as the entry points.
+#[cfg(feature = "fuzzing")]
+pub use c_api::{CImageData, CMeshData, CVertexFormat};
+#[cfg(feature = "fuzzing")]
+pub mod c_validation;
+#[cfg(not(feature = "fuzzing"))]
+mod c_validation;
+#[cfg(feature = "benchmarks")]
+pub mod allocator;
+#[cfg(not(feature = "benchmarks"))]
 mod allocator;
 
 pub struct BuildInfo {
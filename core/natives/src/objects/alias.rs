@@ -0,0 +1,106 @@
+//! Memory aliasing between objects of a object set.
+//!
+//! Transient objects which are never used at the same time (for example a depth buffer and a
+//! post-process target) can be placed in the same alias group using
+//! [`ResourceObjectSetBuilder::add_alias_group`](super::ResourceObjectSetBuilder::add_alias_group).
+//! All objects of a group are bound to the same memory so only one of them holds valid content at
+//! any time. Before using a object the user must activate it using
+//! [`ObjectSet::activate_aliased`](super::ObjectSet::activate_aliased) and record the returned
+//! [`AliasingBarrier`].
+
+use std::collections::HashMap;
+
+use ash::vk;
+
+use crate::define_uuid_type;
+
+use crate::prelude::*;
+
+define_uuid_type!(pub, AliasGroupId);
+
+/// A switch of the object using the memory of a alias group.
+///
+/// The barrier must wait for all accesses to `previous` before `next` is accessed. The content of
+/// `next` is undefined, images must therefore be transitioned from
+/// [`vk::ImageLayout::UNDEFINED`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AliasingBarrier {
+    pub group: AliasGroupId,
+
+    /// The object previously using the memory. [`None`] if the memory has not been used yet.
+    pub previous: Option<UUID>,
+
+    pub next: UUID,
+}
+
+/// Tracks which object of each alias group currently uses the memory of the group.
+pub struct AliasTracker {
+    groups: HashMap<UUID, AliasGroupId>,
+    active: HashMap<AliasGroupId, UUID>,
+}
+
+impl AliasTracker {
+    pub fn new() -> Self {
+        Self {
+            groups: HashMap::new(),
+            active: HashMap::new(),
+        }
+    }
+
+    /// Registers a object as member of a alias group.
+    pub fn add(&mut self, group: AliasGroupId, object: UUID) {
+        self.groups.insert(object, group);
+    }
+
+    /// Returns the alias group of a object or [`None`] if the object is not aliased.
+    pub fn get_group(&self, object: UUID) -> Option<AliasGroupId> {
+        self.groups.get(&object).copied()
+    }
+
+    /// Returns the object currently using the memory of a group.
+    pub fn get_active(&self, group: AliasGroupId) -> Option<UUID> {
+        self.active.get(&group).copied()
+    }
+
+    /// Marks a object as the user of the memory of its group. Returns the barrier required before
+    /// the object can be used or [`None`] if the object is not aliased or is already active.
+    pub fn activate(&mut self, object: UUID) -> Option<AliasingBarrier> {
+        let group = self.get_group(object)?;
+        let previous = self.active.insert(group, object);
+        if previous == Some(object) {
+            return None;
+        }
+
+        Some(AliasingBarrier {
+            group,
+            previous,
+            next: object,
+        })
+    }
+}
+
+impl Default for AliasTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns memory requirements satisfying the requirements of all objects of a alias group.
+/// Returns [`None`] if the objects do not share a common memory type.
+pub fn combine_memory_requirements(requirements: &[vk::MemoryRequirements]) -> Option<vk::MemoryRequirements> {
+    let combined = requirements.iter().fold(vk::MemoryRequirements {
+        size: 0,
+        alignment: 1,
+        memory_type_bits: u32::MAX,
+    }, |combined, requirements| vk::MemoryRequirements {
+        size: combined.size.max(requirements.size),
+        alignment: combined.alignment.max(requirements.alignment),
+        memory_type_bits: combined.memory_type_bits & requirements.memory_type_bits,
+    });
+
+    if combined.memory_type_bits == 0 {
+        None
+    } else {
+        Some(combined)
+    }
+}
@@ -0,0 +1,171 @@
+use ash::prelude::VkResult;
+use ash::vk;
+
+use crate::prelude::*;
+
+/// A handle to memory or a semaphore that has been exported by some other api or process.
+///
+/// Ownership of the handle is transferred to vulkan once it has been successfully imported.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ExternalHandle {
+    /// A opaque posix file descriptor exported by vulkan.
+    #[cfg(unix)]
+    OpaqueFd(std::os::unix::io::RawFd),
+
+    /// A linux dma-buf file descriptor.
+    ///
+    /// Images can only be imported from a dma-buf together with a [`DrmFormatModifierLayout`].
+    #[cfg(target_os = "linux")]
+    DmaBuf(std::os::unix::io::RawFd),
+
+    /// A opaque win32 handle exported by vulkan.
+    #[cfg(windows)]
+    OpaqueWin32(vk::HANDLE),
+}
+
+impl ExternalHandle {
+    /// Returns true if this handle is a dma-buf file descriptor.
+    pub fn is_dma_buf(&self) -> bool {
+        self.get_memory_handle_type() == vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT
+    }
+
+    pub fn get_memory_handle_type(&self) -> vk::ExternalMemoryHandleTypeFlags {
+        match *self {
+            #[cfg(unix)]
+            ExternalHandle::OpaqueFd(_) => vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+            #[cfg(target_os = "linux")]
+            ExternalHandle::DmaBuf(_) => vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+            #[cfg(windows)]
+            ExternalHandle::OpaqueWin32(_) => vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32,
+        }
+    }
+
+    /// Returns the semaphore handle type of this handle or [`None`] if semaphores cannot be
+    /// imported from this type of handle.
+    pub fn get_semaphore_handle_type(&self) -> Option<vk::ExternalSemaphoreHandleTypeFlags> {
+        match *self {
+            #[cfg(unix)]
+            ExternalHandle::OpaqueFd(_) => Some(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_FD),
+            #[cfg(target_os = "linux")]
+            ExternalHandle::DmaBuf(_) => None,
+            #[cfg(windows)]
+            ExternalHandle::OpaqueWin32(_) => Some(vk::ExternalSemaphoreHandleTypeFlags::OPAQUE_WIN32),
+        }
+    }
+}
+
+/// The explicit memory layout of a image imported from a dma-buf.
+///
+/// Dma-buf images must be created with the drm format modifier and plane layouts used by the
+/// exporter. There must be one plane layout for every memory plane of the modifier.
+#[derive(Clone, Debug)]
+pub struct DrmFormatModifierLayout {
+    pub drm_format_modifier: u64,
+    pub plane_layouts: Box<[vk::SubresourceLayout]>,
+}
+
+/// Allocates device memory backed by the external handle for either a image or buffer.
+///
+/// The memory type is selected from the types supported by both the object and the handle. If
+/// `dedicated` is true a dedicated allocation for the object is made.
+///
+/// # Safety
+///
+/// `requirements` must be the requirements of the object and the object must have been created
+/// with the handle type of `handle` in its external memory create info.
+pub(super) unsafe fn import_memory(device: &DeviceContext, handle: ExternalHandle, requirements: &vk::MemoryRequirements, dedicated: bool, image: vk::Image, buffer: vk::Buffer) -> VkResult<vk::DeviceMemory> {
+    let properties = device.get_instance().vk().get_physical_device_memory_properties(device.get_functions().physical_device);
+
+    let memory_type_bits = requirements.memory_type_bits & get_handle_memory_type_bits(device, handle)?;
+    let memory_type = (0..properties.memory_type_count).find(|index| {
+        (memory_type_bits & (1u32 << index)) != 0 &&
+            properties.memory_types[*index as usize].property_flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+    }).or_else(|| {
+        (0..properties.memory_type_count).find(|index| (memory_type_bits & (1u32 << index)) != 0)
+    }).ok_or(vk::Result::ERROR_INVALID_EXTERNAL_HANDLE)?;
+
+    let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder()
+        .image(image)
+        .buffer(buffer);
+
+    let mut info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type);
+
+    if dedicated {
+        info = info.push_next(&mut dedicated_info);
+    }
+
+    match handle {
+        #[cfg(unix)]
+        ExternalHandle::OpaqueFd(fd) => {
+            let mut import = vk::ImportMemoryFdInfoKHR::builder()
+                .handle_type(handle.get_memory_handle_type())
+                .fd(fd);
+            device.vk().allocate_memory(&info.push_next(&mut import), None)
+        }
+        #[cfg(target_os = "linux")]
+        ExternalHandle::DmaBuf(fd) => {
+            let mut import = vk::ImportMemoryFdInfoKHR::builder()
+                .handle_type(handle.get_memory_handle_type())
+                .fd(fd);
+            device.vk().allocate_memory(&info.push_next(&mut import), None)
+        }
+        #[cfg(windows)]
+        ExternalHandle::OpaqueWin32(win32_handle) => {
+            let mut import = vk::ImportMemoryWin32HandleInfoKHR::builder()
+                .handle_type(handle.get_memory_handle_type())
+                .handle(win32_handle);
+            device.vk().allocate_memory(&info.push_next(&mut import), None)
+        }
+    }
+}
+
+/// Returns the memory types that memory backed by the handle can be imported into.
+///
+/// Opaque handles can only be imported into the memory types of the object they are bound to so
+/// all memory types are returned for them.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+unsafe fn get_handle_memory_type_bits(device: &DeviceContext, handle: ExternalHandle) -> VkResult<u32> {
+    match handle {
+        #[cfg(target_os = "linux")]
+        ExternalHandle::DmaBuf(fd) => {
+            let external_memory_fd = device.get_functions().external_memory_fd_khr.as_ref().ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+            let properties = external_memory_fd.get_memory_fd_properties(handle.get_memory_handle_type(), fd)?;
+            Ok(properties.memory_type_bits)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Ok(u32::MAX)
+    }
+}
+
+/// Imports the payload of a external semaphore handle into a semaphore.
+///
+/// # Safety
+///
+/// `semaphore` must be a valid semaphore created on the device and must not currently be in use.
+pub(super) unsafe fn import_semaphore(device: &DeviceContext, handle: ExternalHandle, semaphore: vk::Semaphore) -> VkResult<()> {
+    let handle_type = handle.get_semaphore_handle_type().ok_or(vk::Result::ERROR_INVALID_EXTERNAL_HANDLE)?;
+    let functions = device.get_functions();
+
+    match handle {
+        #[cfg(unix)]
+        ExternalHandle::OpaqueFd(fd) => {
+            let info = vk::ImportSemaphoreFdInfoKHR::builder()
+                .semaphore(semaphore)
+                .handle_type(handle_type)
+                .fd(fd);
+            functions.external_semaphore_fd_khr.as_ref().ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?.import_semaphore_fd(&info)
+        }
+        #[cfg(windows)]
+        ExternalHandle::OpaqueWin32(win32_handle) => {
+            let info = vk::ImportSemaphoreWin32HandleInfoKHR::builder()
+                .semaphore(semaphore)
+                .handle_type(handle_type)
+                .handle(win32_handle);
+            functions.external_semaphore_win32_khr.as_ref().ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?.import_semaphore_win32_handle(&info)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(vk::Result::ERROR_INVALID_EXTERNAL_HANDLE)
+    }
+}
@@ -7,16 +7,33 @@ use ash::vk::Handle;
 
 use crate::prelude::*;
 
+/// The vulkan object type identified by a [`ObjectId`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ObjectKind {
+    Buffer,
+    BufferView,
+    Image,
+    ImageView,
+    Surface,
+    Swapchain,
+    Semaphore,
+    SamplerYcbcrConversion,
+    Framebuffer,
+    Sampler,
+}
+
 pub trait ObjectId: Copy + Clone + PartialEq + Eq + PartialOrd + Ord + Hash + Debug {
     type HandleType: Handle + Copy;
 
+    const KIND: ObjectKind;
+
     fn from_raw(id: UUID) -> Self;
 
     fn as_uuid(&self) -> UUID;
 }
 
 macro_rules! declare_object_id {
-    ($name:ident, $handle_type:ty) => {
+    ($name:ident, $handle_type:ty, $kind:ident) => {
         #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
         pub struct $name(UUID);
 
@@ -29,6 +46,8 @@ macro_rules! declare_object_id {
         impl ObjectId for $name {
             type HandleType = $handle_type;
 
+            const KIND: ObjectKind = ObjectKind::$kind;
+
             fn from_raw(id: UUID) -> Self {
                 Self(id)
             }
@@ -60,10 +79,13 @@ macro_rules! declare_object_id {
     }
 }
 
-declare_object_id!(BufferId, vk::Buffer);
-declare_object_id!(BufferViewId, vk::BufferView);
-declare_object_id!(ImageId, vk::Image);
-declare_object_id!(ImageViewId, vk::ImageView);
-declare_object_id!(SurfaceId, vk::SurfaceKHR);
-declare_object_id!(SwapchainId, vk::SwapchainKHR);
-declare_object_id!(SemaphoreId, vk::Semaphore);
\ No newline at end of file
+declare_object_id!(BufferId, vk::Buffer, Buffer);
+declare_object_id!(BufferViewId, vk::BufferView, BufferView);
+declare_object_id!(ImageId, vk::Image, Image);
+declare_object_id!(ImageViewId, vk::ImageView, ImageView);
+declare_object_id!(SurfaceId, vk::SurfaceKHR, Surface);
+declare_object_id!(SwapchainId, vk::SwapchainKHR, Swapchain);
+declare_object_id!(SemaphoreId, vk::Semaphore, Semaphore);
+declare_object_id!(SamplerYcbcrConversionId, vk::SamplerYcbcrConversion, SamplerYcbcrConversion);
+declare_object_id!(FramebufferId, vk::Framebuffer, Framebuffer);
+declare_object_id!(SamplerId, vk::Sampler, Sampler);
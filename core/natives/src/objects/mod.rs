@@ -1,7 +1,16 @@
+pub mod alias;
 pub mod id;
 pub mod sync;
 
+mod external;
 mod object_set;
+mod resource_object_set;
+mod swapchain_object_set;
 
+pub use external::{DrmFormatModifierLayout, ExternalHandle};
 pub use object_set::ObjectSetProvider;
-pub use object_set::ObjectSet;
\ No newline at end of file
+pub use object_set::ObjectDescription;
+pub use object_set::ObjectSet;
+pub use object_set::GuardedHandle;
+pub use resource_object_set::{ResourceObjectSetBuilder, ImageViewChainType, ObjectValidationError, ObjectBuildError};
+pub use swapchain_object_set::{SwapchainObjectSetBuilder, SwapchainObjectSet};
\ No newline at end of file
@@ -1,11 +1,19 @@
 use std::cmp::Ordering;
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
+use std::ptr::NonNull;
 use std::sync::Arc;
 
+use ash::vk;
 use ash::vk::Handle;
 
-use super::id::ObjectId;
+use super::alias::AliasingBarrier;
+use super::id::{BufferId, ObjectId, ObjectKind, SamplerId};
+use super::resource_object_set::ObjectBuildError;
+use super::sync::Semaphore;
+
+use crate::vk::objects::buffer::BufferViewDescription;
+use crate::vk::objects::image::{ImageDescription, ImageSubresourceRange, ImageViewDescription, SamplerDescription, SamplerYcbcrConversionDescription};
 
 use crate::prelude::*;
 
@@ -14,11 +22,113 @@ pub trait ObjectSetProvider: Debug {
 
     fn get_handle(&self, id: UUID) -> Option<u64>;
 
+    /// Returns the semaphore used to synchronize access to the objects of this set if the set has
+    /// one.
+    fn get_semaphore(&self) -> Option<Semaphore> {
+        None
+    }
+
+    /// Calls `f` with the id and kind of every object contained in this set. The order is
+    /// unspecified.
+    fn for_each_object(&self, f: &mut dyn FnMut(UUID, ObjectKind));
+
+    /// Returns the number of objects contained in this set.
+    fn object_count(&self) -> usize {
+        let mut count = 0;
+        self.for_each_object(&mut |_, _| count += 1);
+        count
+    }
+
+    /// Returns the description of an object in this set. Returns [`None`] if the object is not part
+    /// of this set or the set does not provide descriptions for it.
+    fn description(&self, _id: UUID) -> Option<ObjectDescription> {
+        None
+    }
+
+    /// Returns the device address of a buffer in this set. Returns [`None`] if the buffer is not
+    /// part of this set or was not created with
+    /// [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`].
+    fn get_buffer_address(&self, _id: UUID) -> Option<vk::DeviceAddress> {
+        None
+    }
+
+    /// Called whenever a [`GuardedHandle`] of the object is created or cloned.
+    fn on_export(&self, _id: UUID) {
+    }
+
+    /// Called whenever a [`GuardedHandle`] of the object is dropped.
+    fn on_export_dropped(&self, _id: UUID) {
+    }
+
+    /// Returns the persistently mapped memory of a buffer in this set. Returns [`None`] if the
+    /// buffer is not part of this set or is not host visible.
+    fn get_buffer_mapping(&self, _id: UUID) -> Option<NonNull<u8>> {
+        None
+    }
+
+    /// Marks a aliased object of this set as the user of the memory of its alias group. See
+    /// [`ObjectSet::activate_aliased`].
+    fn activate_aliased(&self, _id: UUID) -> Option<AliasingBarrier> {
+        None
+    }
+
+    /// Recreates a single object of this set using a new description. See
+    /// [`ObjectSet::rebuild_object`].
+    fn rebuild_object(&self, id: UUID, _description: &ObjectDescription, _last_use: u64) -> Result<(), ObjectBuildError> {
+        Err(ObjectBuildError::RebuildUnsupported(id))
+    }
+
     fn get<ID: ObjectId>(&self, id: ID) -> Option<ID::HandleType> where Self: Sized {
         self.get_handle(id.as_uuid()).map(|handle| ID::HandleType::from_raw(handle))
     }
 }
 
+/// Describes an object contained in a [`ObjectSetProvider`].
+#[derive(Copy, Clone, Debug)]
+pub enum ObjectDescription {
+    Image(ImageDescription),
+    ImageView(ImageViewDescription),
+    Buffer {
+        size: u64,
+        usage_flags: vk::BufferUsageFlags,
+    },
+    BufferView(BufferViewDescription),
+    SamplerYcbcrConversion(SamplerYcbcrConversionDescription),
+    Sampler(SamplerDescription),
+    /// A image owned by a swapchain. Swapchain images use vulkan formats which may not have a
+    /// matching [`Format`](crate::vk::objects::Format).
+    SwapchainImage {
+        size: Vec2u32,
+        format: vk::Format,
+        usage_flags: vk::ImageUsageFlags,
+    },
+    SwapchainImageView {
+        format: vk::Format,
+        subresource_range: ImageSubresourceRange,
+    },
+    /// A framebuffer created for a swapchain image. The framebuffer has the size of the swapchain
+    /// images and a single layer.
+    SwapchainFramebuffer {
+        render_pass: vk::RenderPass,
+        size: Vec2u32,
+        attachment_count: u32,
+    },
+}
+
+impl ObjectDescription {
+    pub fn get_kind(&self) -> ObjectKind {
+        match self {
+            Self::Image(_) | Self::SwapchainImage { .. } => ObjectKind::Image,
+            Self::ImageView(_) | Self::SwapchainImageView { .. } => ObjectKind::ImageView,
+            Self::Buffer { .. } => ObjectKind::Buffer,
+            Self::BufferView(_) => ObjectKind::BufferView,
+            Self::SamplerYcbcrConversion(_) => ObjectKind::SamplerYcbcrConversion,
+            Self::SwapchainFramebuffer { .. } => ObjectKind::Framebuffer,
+            Self::Sampler(_) => ObjectKind::Sampler,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ObjectSet(Arc<dyn ObjectSetProvider + Send + Sync>);
 
@@ -30,6 +140,64 @@ impl ObjectSet {
     pub fn get_provider(&self) -> &Arc<dyn ObjectSetProvider + Send + Sync> {
         &self.0
     }
+
+    /// Exports the raw handle of an object in this set.
+    ///
+    /// The returned [`GuardedHandle`] keeps the set (and with it the sets semaphore) alive until it
+    /// is dropped. This allows external code to record vulkan commands using the handle without
+    /// having to track the lifetime of the set manually.
+    pub fn export<ID: ObjectId>(&self, id: ID) -> Option<GuardedHandle<ID::HandleType>> {
+        self.0.get_handle(id.as_uuid()).map(|handle| {
+            self.0.on_export(id.as_uuid());
+            GuardedHandle {
+                set: self.clone(),
+                id: id.as_uuid(),
+                handle: ID::HandleType::from_raw(handle),
+            }
+        })
+    }
+
+    /// Returns the device address of a buffer in this set. The address can be passed to shaders
+    /// using physical storage buffer pointers. It remains valid as long as the set is alive and
+    /// the buffer is not rebuilt.
+    pub fn get_buffer_address(&self, id: BufferId) -> Option<vk::DeviceAddress> {
+        self.0.get_buffer_address(id.as_uuid())
+    }
+
+    /// Returns a pointer to the persistently mapped memory of a host visible buffer in this set.
+    /// The memory is host coherent so writes do not need to be flushed, but the host must
+    /// synchronize with the gpu (for example using the semaphore of the set) before reusing or
+    /// reading it. The pointer remains valid as long as the set is alive.
+    pub fn get_buffer_mapping(&self, id: BufferId) -> Option<NonNull<u8>> {
+        self.0.get_buffer_mapping(id.as_uuid())
+    }
+
+    /// Marks a aliased object as the user of the memory of its alias group. Must be called before
+    /// recording any commands using the object. If another object of the group was used before
+    /// the returned [`AliasingBarrier`] must be recorded first. Returns [`None`] if the object is
+    /// not aliased or already active.
+    pub fn activate_aliased<ID: ObjectId>(&self, id: ID) -> Option<AliasingBarrier> {
+        self.0.activate_aliased(id.as_uuid())
+    }
+
+    /// Returns the handle of a sampler in this set. The sampler remains valid as long as the set is
+    /// alive.
+    pub fn get_sampler_handle(&self, id: SamplerId) -> Option<vk::Sampler> {
+        self.0.get_handle(id.as_uuid()).map(vk::Sampler::from_raw)
+    }
+
+    /// Recreates a single object of this set using a new description while all other objects
+    /// keep their handles. Useful for render targets which need to be resized.
+    ///
+    /// Blocks until the timeline semaphore of the set reached `last_use` so that no pending gpu
+    /// work uses the object anymore. The wait is bounded and returns
+    /// [`ObjectBuildError::Semaphore`] with [`vk::Result::TIMEOUT`] if the value is not reached in
+    /// time. Sets without a semaphore and objects with live [`GuardedHandle`]s (including views
+    /// of a rebuilt image) cannot be rebuilt. Handles of the object previously returned by this
+    /// set become invalid. If rebuilding fails the old object is kept.
+    pub fn rebuild_object<ID: ObjectId>(&self, id: ID, description: ObjectDescription, last_use: u64) -> Result<(), ObjectBuildError> {
+        self.0.rebuild_object(id.as_uuid(), &description, last_use)
+    }
 }
 
 impl ObjectSetProvider for ObjectSet {
@@ -40,6 +208,46 @@ impl ObjectSetProvider for ObjectSet {
     fn get_handle(&self, id: UUID) -> Option<u64> {
         self.0.get_handle(id)
     }
+
+    fn get_semaphore(&self) -> Option<Semaphore> {
+        self.0.get_semaphore()
+    }
+
+    fn for_each_object(&self, f: &mut dyn FnMut(UUID, ObjectKind)) {
+        self.0.for_each_object(f)
+    }
+
+    fn object_count(&self) -> usize {
+        self.0.object_count()
+    }
+
+    fn description(&self, id: UUID) -> Option<ObjectDescription> {
+        self.0.description(id)
+    }
+
+    fn get_buffer_address(&self, id: UUID) -> Option<vk::DeviceAddress> {
+        self.0.get_buffer_address(id)
+    }
+
+    fn on_export(&self, id: UUID) {
+        self.0.on_export(id)
+    }
+
+    fn on_export_dropped(&self, id: UUID) {
+        self.0.on_export_dropped(id)
+    }
+
+    fn get_buffer_mapping(&self, id: UUID) -> Option<NonNull<u8>> {
+        self.0.get_buffer_mapping(id)
+    }
+
+    fn activate_aliased(&self, id: UUID) -> Option<AliasingBarrier> {
+        self.0.activate_aliased(id)
+    }
+
+    fn rebuild_object(&self, id: UUID, description: &ObjectDescription, last_use: u64) -> Result<(), ObjectBuildError> {
+        self.0.rebuild_object(id, description, last_use)
+    }
 }
 
 impl PartialEq for ObjectSet {
@@ -73,4 +281,53 @@ impl Debug for ObjectSet {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         (*self.0).fmt(f)
     }
-}
\ No newline at end of file
+}
+
+/// A raw vulkan handle exported from a [`ObjectSet`].
+///
+/// The guard holds a reference to the set the handle originates from. As long as the guard is
+/// alive the handle and the semaphore of the set remain valid.
+pub struct GuardedHandle<H: Handle + Copy> {
+    set: ObjectSet,
+    id: UUID,
+    handle: H,
+}
+
+impl<H: Handle + Copy> GuardedHandle<H> {
+    pub fn get_handle(&self) -> H {
+        self.handle
+    }
+
+    pub fn get_set(&self) -> &ObjectSet {
+        &self.set
+    }
+
+    /// Returns the semaphore of the set the handle originates from. Any external work using the
+    /// handle must be synchronized using this semaphore.
+    pub fn get_semaphore(&self) -> Option<Semaphore> {
+        self.set.get_semaphore()
+    }
+}
+
+impl<H: Handle + Copy> Clone for GuardedHandle<H> {
+    fn clone(&self) -> Self {
+        self.set.0.on_export(self.id);
+        Self {
+            set: self.set.clone(),
+            id: self.id,
+            handle: self.handle,
+        }
+    }
+}
+
+impl<H: Handle + Copy> Drop for GuardedHandle<H> {
+    fn drop(&mut self) {
+        self.set.0.on_export_dropped(self.id);
+    }
+}
+
+impl<H: Handle + Copy> Debug for GuardedHandle<H> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("GuardedHandle(Handle: {:#016X}, Set: {:?})", self.handle.as_raw(), self.set))
+    }
+}
@@ -0,0 +1,1621 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+
+use ash::prelude::VkResult;
+use ash::vk;
+use ash::vk::Handle;
+
+use super::alias::{combine_memory_requirements, AliasGroupId, AliasTracker, AliasingBarrier};
+use super::id::{BufferId, BufferViewId, ImageId, ImageViewId, ObjectId, ObjectKind, SamplerId, SamplerYcbcrConversionId};
+use super::external;
+use super::external::{DrmFormatModifierLayout, ExternalHandle};
+use super::object_set::{ObjectDescription, ObjectSet, ObjectSetProvider};
+use super::sync::Semaphore;
+
+use crate::allocator::{Allocation, HostAccess, MemoryPool};
+use crate::vk::objects::buffer::{BufferRange, BufferViewDescription};
+use crate::vk::objects::image::{ImageDescription, ImageViewDescription, SamplerDescription, SamplerYcbcrConversionDescription};
+
+use crate::prelude::*;
+
+/// Utility to create object sets containing device owned resources (images, image views, samplers,
+/// buffers and buffer views).
+///
+/// Objects are only registered in the builder and created when [`ResourceObjectSetBuilder::build`]
+/// is called. All objects of the set are destroyed when the set is dropped.
+pub struct ResourceObjectSetBuilder {
+    device: Arc<DeviceContext>,
+    set_id: UUID,
+    images: Vec<ImageRequest>,
+    image_views: Vec<ImageViewRequest>,
+    buffers: Vec<BufferRequest>,
+    buffer_views: Vec<BufferViewRequest>,
+    ycbcr_conversions: Vec<(SamplerYcbcrConversionId, SamplerYcbcrConversionDescription)>,
+    samplers: Vec<(SamplerId, SamplerDescription)>,
+    alias_groups: Vec<AliasGroupId>,
+    external_semaphore: Option<ExternalHandle>,
+    memory_pool: Option<Arc<MemoryPool>>,
+}
+
+impl ResourceObjectSetBuilder {
+    pub fn new(device: Arc<DeviceContext>) -> Self {
+        Self {
+            device,
+            set_id: UUID::new(),
+            images: Vec::new(),
+            image_views: Vec::new(),
+            buffers: Vec::new(),
+            buffer_views: Vec::new(),
+            ycbcr_conversions: Vec::new(),
+            samplers: Vec::new(),
+            alias_groups: Vec::new(),
+            external_semaphore: None,
+            memory_pool: None,
+        }
+    }
+
+    /// Allocates the memory of all gpu only images and buffers of the set from a memory pool.
+    pub fn set_memory_pool(&mut self, pool: Arc<MemoryPool>) {
+        self.memory_pool = Some(pool);
+    }
+
+    /// Adds a gpu only image to the set.
+    pub fn add_default_gpu_only_image(&mut self, description: ImageDescription) -> ImageId {
+        let id = ImageId::new();
+        self.images.push(ImageRequest {
+            id,
+            description,
+            external: None,
+            drm_layout: None,
+            alias_group: None,
+        });
+
+        id
+    }
+
+    /// Adds a alias group to the set. All objects added to the group share the same memory. See
+    /// the [`alias`](super::alias) module docs.
+    ///
+    /// The memory of a group is never placed in the memory pool of the set.
+    pub fn add_alias_group(&mut self) -> AliasGroupId {
+        let id = AliasGroupId::new();
+        self.alias_groups.push(id);
+        id
+    }
+
+    /// Adds a gpu only image to the set which shares its memory with all other objects of a alias
+    /// group. Aliased images cannot be rebuilt.
+    ///
+    /// # Panics
+    ///
+    /// If the alias group is not part of this set.
+    pub fn add_aliased_image(&mut self, group: AliasGroupId, description: ImageDescription) -> ImageId {
+        if !self.alias_groups.contains(&group) {
+            panic!("Alias group {:?} is not part of this set", group);
+        }
+
+        let id = ImageId::new();
+        self.images.push(ImageRequest {
+            id,
+            description,
+            external: None,
+            drm_layout: None,
+            alias_group: Some(group),
+        });
+
+        id
+    }
+
+    /// Adds a gpu only buffer to the set which shares its memory with all other objects of a alias
+    /// group. Aliased buffers cannot be rebuilt.
+    ///
+    /// # Panics
+    ///
+    /// If the alias group is not part of this set or device addresses are requested but the
+    /// device does not support buffer device addresses.
+    pub fn add_aliased_buffer(&mut self, group: AliasGroupId, size: u64, usage_flags: vk::BufferUsageFlags) -> BufferId {
+        if !self.alias_groups.contains(&group) {
+            panic!("Alias group {:?} is not part of this set", group);
+        }
+        if usage_flags.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) && !self.device.supports_buffer_device_address() {
+            panic!("Device does not support buffer device addresses");
+        }
+
+        let id = BufferId::new();
+        self.buffers.push(BufferRequest {
+            id,
+            size,
+            usage_flags,
+            external: None,
+            host_access: None,
+            alias_group: Some(group),
+        });
+
+        id
+    }
+
+    /// Adds a image backed by externally allocated memory to the set.
+    ///
+    /// Ownership of the handle is transferred to the set once the set has been built.
+    ///
+    /// # Panics
+    ///
+    /// If the device does not support external memory or the handle is a dma-buf. Dma-buf images
+    /// must be imported using [`ResourceObjectSetBuilder::import_dma_buf_image`].
+    pub fn import_external_image(&mut self, description: ImageDescription, handle: ExternalHandle) -> ImageId {
+        self.validate_external_handle(handle);
+        if handle.is_dma_buf() {
+            panic!("Dma-buf images must be imported with an explicit drm format modifier layout");
+        }
+
+        let id = ImageId::new();
+        self.images.push(ImageRequest {
+            id,
+            description,
+            external: Some(handle),
+            drm_layout: None,
+            alias_group: None,
+        });
+
+        id
+    }
+
+    /// Adds a image backed by a dma-buf to the set. The image is created with the drm format
+    /// modifier and plane layouts of `layout`.
+    ///
+    /// Ownership of the handle is transferred to the set once the set has been built.
+    ///
+    /// # Panics
+    ///
+    /// If the device does not support dma-buf import, the handle is not a dma-buf or the layout
+    /// does not contain any planes.
+    pub fn import_dma_buf_image(&mut self, description: ImageDescription, handle: ExternalHandle, layout: DrmFormatModifierLayout) -> ImageId {
+        if !handle.is_dma_buf() {
+            panic!("Handle {:?} is not a dma-buf", handle);
+        }
+        self.validate_external_handle(handle);
+        if layout.plane_layouts.is_empty() {
+            panic!("Drm format modifier layout must contain at least one plane");
+        }
+
+        let id = ImageId::new();
+        self.images.push(ImageRequest {
+            id,
+            description,
+            external: Some(handle),
+            drm_layout: Some(layout),
+            alias_group: None,
+        });
+
+        id
+    }
+
+    /// Adds a image view of a image that is part of this set.
+    ///
+    /// # Panics
+    ///
+    /// If the source image is not part of this set.
+    pub fn add_internal_image_view(&mut self, image: ImageId, description: ImageViewDescription) -> ImageViewId {
+        match self.find_image(image) {
+            Some(request) => Self::validate_plane_aspects(request, &description, false),
+            None => panic!("Image {:?} is not part of this set", image)
+        }
+
+        let id = ImageViewId::new();
+        self.image_views.push(ImageViewRequest {
+            id,
+            image,
+            description,
+            ycbcr_conversion: None,
+        });
+
+        id
+    }
+
+    /// Adds a sampler ycbcr conversion to the set. The conversion can be used to create image views
+    /// of multi-planar images.
+    ///
+    /// # Panics
+    ///
+    /// If the device does not support the sampler ycbcr conversion feature.
+    pub fn add_sampler_ycbcr_conversion(&mut self, description: SamplerYcbcrConversionDescription) -> SamplerYcbcrConversionId {
+        if !self.device.supports_sampler_ycbcr_conversion() {
+            panic!("Device does not support sampler ycbcr conversion");
+        }
+
+        let id = SamplerYcbcrConversionId::new();
+        self.ycbcr_conversions.push((id, description));
+
+        id
+    }
+
+    /// Adds a image view of a multi-planar image that is part of this set using a ycbcr conversion
+    /// that is part of this set.
+    ///
+    /// # Panics
+    ///
+    /// If either the source image or the conversion is not part of this set.
+    pub fn add_internal_ycbcr_image_view(&mut self, image: ImageId, description: ImageViewDescription, conversion: SamplerYcbcrConversionId) -> ImageViewId {
+        match self.find_image(image) {
+            Some(request) => Self::validate_plane_aspects(request, &description, true),
+            None => panic!("Image {:?} is not part of this set", image)
+        }
+        if !self.ycbcr_conversions.iter().any(|(id, _)| *id == conversion) {
+            panic!("Ycbcr conversion {:?} is not part of this set", conversion);
+        }
+
+        let id = ImageViewId::new();
+        self.image_views.push(ImageViewRequest {
+            id,
+            image,
+            description,
+            ycbcr_conversion: Some(conversion),
+        });
+
+        id
+    }
+
+    /// Adds one image view for every mip level or array layer of a image that is part of this set.
+    ///
+    /// All views use the provided description with the subresource range being restricted to a
+    /// single mip level or array layer starting at the base of the description. The returned ids
+    /// are ordered by mip level or array layer.
+    ///
+    /// # Panics
+    ///
+    /// If the source image is not part of this set.
+    pub fn add_image_view_chain(&mut self, image: ImageId, description: ImageViewDescription, chain_type: ImageViewChainType) -> Box<[ImageViewId]> {
+        let size = match self.find_image(image) {
+            Some(request) => request.description.spec.size,
+            None => panic!("Image {:?} is not part of this set", image)
+        };
+
+        let range = description.subresource_range;
+        match chain_type {
+            ImageViewChainType::MipLevels => {
+                let end = Self::resolve_count(range.base_mip_level, range.mip_level_count, size.get_mip_levels());
+                (range.base_mip_level..end).map(|level| {
+                    let mut view = description;
+                    view.subresource_range.base_mip_level = level;
+                    view.subresource_range.mip_level_count = 1;
+                    self.add_internal_image_view(image, view)
+                }).collect()
+            }
+            ImageViewChainType::ArrayLayers => {
+                let end = Self::resolve_count(range.base_array_layer, range.array_layer_count, size.get_array_layers());
+                (range.base_array_layer..end).map(|layer| {
+                    let mut view = description;
+                    view.subresource_range.base_array_layer = layer;
+                    view.subresource_range.array_layer_count = 1;
+                    self.add_internal_image_view(image, view)
+                }).collect()
+            }
+        }
+    }
+
+    /// Adds a sampler to the set.
+    ///
+    /// If the description contains a ycbcr conversion the conversion is chained into the sampler.
+    /// Such samplers must clamp to the edge, may not use anisotropic filtering and must use the
+    /// chroma filter of the conversion unless the format supports separate reconstruction filters.
+    ///
+    /// # Panics
+    ///
+    /// If anisotropic filtering is requested but the device does not support sampler anisotropy
+    /// or if the ycbcr conversion is not part of this set or the description is not valid for it.
+    pub fn add_sampler(&mut self, description: SamplerDescription) -> SamplerId {
+        if description.max_anisotropy.is_some() && self.device.get_max_sampler_anisotropy().is_none() {
+            panic!("Device does not support sampler anisotropy");
+        }
+        if let Some(conversion) = description.ycbcr_conversion {
+            let conversion_description = self.ycbcr_conversions.iter().find(|(id, _)| *id == conversion).map(|(_, description)| description).unwrap_or_else(|| {
+                panic!("Ycbcr conversion {:?} is not part of this set", conversion);
+            });
+            self.validate_ycbcr_sampler(&description, conversion_description);
+        }
+
+        let id = SamplerId::new();
+        self.samplers.push((id, description));
+
+        id
+    }
+
+    /// Adds a gpu only buffer to the set.
+    ///
+    /// If the usage flags contain [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`] the device
+    /// address of the buffer can be queried using [`ObjectSet::get_buffer_address`].
+    ///
+    /// # Panics
+    ///
+    /// If device addresses are requested but the device does not support buffer device addresses.
+    pub fn add_default_gpu_only_buffer(&mut self, size: u64, usage_flags: vk::BufferUsageFlags) -> BufferId {
+        if usage_flags.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) && !self.device.supports_buffer_device_address() {
+            panic!("Device does not support buffer device addresses");
+        }
+
+        let id = BufferId::new();
+        self.buffers.push(BufferRequest {
+            id,
+            size,
+            usage_flags,
+            external: None,
+            host_access: None,
+            alias_group: None,
+        });
+
+        id
+    }
+
+    /// Adds a persistently mapped buffer the host writes to, for example to stream uniform or
+    /// staging data to the gpu. The buffer is allocated from host visible and host coherent
+    /// memory and is never placed in the memory pool of the set.
+    ///
+    /// The mapped memory can be accessed using [`ObjectSet::get_buffer_mapping`].
+    ///
+    /// # Panics
+    ///
+    /// If device addresses are requested but the device does not support buffer device addresses.
+    pub fn add_host_visible_upload_buffer(&mut self, size: u64, usage_flags: vk::BufferUsageFlags) -> BufferId {
+        self.add_host_visible_buffer(size, usage_flags, HostAccess::SequentialWrite)
+    }
+
+    /// Adds a persistently mapped buffer the host reads gpu results from. The buffer is allocated
+    /// from host visible and host coherent memory, preferring host cached memory, and is never
+    /// placed in the memory pool of the set.
+    ///
+    /// The mapped memory can be accessed using [`ObjectSet::get_buffer_mapping`].
+    ///
+    /// # Panics
+    ///
+    /// If device addresses are requested but the device does not support buffer device addresses.
+    pub fn add_host_visible_readback_buffer(&mut self, size: u64, usage_flags: vk::BufferUsageFlags) -> BufferId {
+        self.add_host_visible_buffer(size, usage_flags, HostAccess::Random)
+    }
+
+    fn add_host_visible_buffer(&mut self, size: u64, usage_flags: vk::BufferUsageFlags, host_access: HostAccess) -> BufferId {
+        if usage_flags.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) && !self.device.supports_buffer_device_address() {
+            panic!("Device does not support buffer device addresses");
+        }
+
+        let id = BufferId::new();
+        self.buffers.push(BufferRequest {
+            id,
+            size,
+            usage_flags,
+            external: None,
+            host_access: Some(host_access),
+            alias_group: None,
+        });
+
+        id
+    }
+
+    /// Adds a buffer backed by externally allocated memory to the set.
+    ///
+    /// Ownership of the handle is transferred to the set once the set has been built.
+    ///
+    /// # Panics
+    ///
+    /// If the device does not support external memory or the usage flags contain
+    /// [`vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS`].
+    pub fn import_external_buffer(&mut self, size: u64, usage_flags: vk::BufferUsageFlags, handle: ExternalHandle) -> BufferId {
+        self.validate_external_handle(handle);
+        if usage_flags.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            panic!("External buffers do not support device addresses");
+        }
+
+        let id = BufferId::new();
+        self.buffers.push(BufferRequest {
+            id,
+            size,
+            usage_flags,
+            external: Some(handle),
+            host_access: None,
+            alias_group: None,
+        });
+
+        id
+    }
+
+    /// Adds a texel buffer view of a buffer that is part of this set.
+    ///
+    /// # Panics
+    ///
+    /// If the source buffer is not part of this set.
+    pub fn add_internal_buffer_view(&mut self, buffer: BufferId, description: BufferViewDescription) -> BufferViewId {
+        if self.find_buffer(buffer).is_none() {
+            panic!("Buffer {:?} is not part of this set", buffer);
+        }
+
+        let id = BufferViewId::new();
+        self.buffer_views.push(BufferViewRequest {
+            id,
+            buffer,
+            description,
+        });
+
+        id
+    }
+
+    /// Imports the timeline semaphore of the set from a external handle. This allows the set to be
+    /// synchronized with the api or process that exported the objects of the set.
+    ///
+    /// Ownership of the handle is transferred to the set once the set has been built.
+    ///
+    /// # Panics
+    ///
+    /// If the device does not support external memory.
+    pub fn import_external_semaphore(&mut self, handle: ExternalHandle) {
+        self.validate_external_handle(handle);
+
+        self.external_semaphore = Some(handle);
+    }
+
+    /// Validates that all image and buffer views are compatible with the usage flags and format of
+    /// their source object. Called by [`ResourceObjectSetBuilder::build`] so that invalid views are
+    /// reported before any vulkan object is created.
+    pub fn validate(&self) -> Result<(), ObjectValidationError> {
+        for request in &self.image_views {
+            let image = self.find_image(request.image).unwrap();
+            // Images with a drm format modifier report their features per modifier
+            let check_features = image.drm_layout.is_none();
+            Self::validate_image_view(&self.device, request.id, request.image, &image.description, &request.description, check_features)?;
+        }
+
+        for request in &self.buffer_views {
+            let buffer = self.find_buffer(request.buffer).unwrap();
+            Self::validate_buffer_view(&self.device, request.id, request.buffer, buffer.size, buffer.usage_flags, &request.description)?;
+        }
+
+        Ok(())
+    }
+
+    /// Creates all objects of the set.
+    ///
+    /// If any object fails to be created all already created objects are destroyed and a
+    /// [`ObjectBuildError`] describing the failed object is returned. Callers may retry with
+    /// different descriptions (for example a lower resolution).
+    pub fn build(self) -> Result<ObjectSet, ObjectBuildError> {
+        self.validate().map_err(ObjectBuildError::Validation)?;
+
+        let mut set = ResourceObjectSet {
+            device: self.device.clone(),
+            set_id: self.set_id,
+            semaphore: None,
+            memory_pool: self.memory_pool.clone(),
+            objects: Mutex::new(ResourceObjects {
+                images: Vec::with_capacity(self.images.len()),
+                image_views: Vec::with_capacity(self.image_views.len()),
+                buffers: Vec::with_capacity(self.buffers.len()),
+                buffer_views: Vec::with_capacity(self.buffer_views.len()),
+                ycbcr_conversions: Vec::with_capacity(self.ycbcr_conversions.len()),
+                samplers: Vec::with_capacity(self.samplers.len()),
+                external_images: Vec::new(),
+                external_buffers: Vec::new(),
+                mapped_buffers: Vec::new(),
+                aliased_images: Vec::new(),
+                aliased_buffers: Vec::new(),
+                alias_memory: Vec::with_capacity(self.alias_groups.len()),
+                alias_tracker: AliasTracker::new(),
+                handles: HashMap::new(),
+                objects: Vec::new(),
+                view_sources: HashMap::new(),
+                buffer_view_sources: HashMap::new(),
+                buffer_addresses: HashMap::new(),
+                exports: HashMap::new(),
+                buffer_mappings: HashMap::new(),
+            }),
+        };
+
+        // If any of these fail the already created objects are destroyed when set is dropped.
+        // Without timeline semaphores the set has no semaphore and users of the set must
+        // synchronize using fences.
+        if self.device.get_capabilities().has_timeline_semaphore() {
+            let mut timeline = vk::SemaphoreTypeCreateInfo::builder()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+
+            let info = vk::SemaphoreCreateInfo::builder()
+                .push_next(&mut timeline);
+
+            set.semaphore = Some(Semaphore::new(unsafe {
+                self.device.vk().create_semaphore(&info, None)
+            }.map_err(ObjectBuildError::Semaphore)?));
+        }
+
+        if let Some(handle) = self.external_semaphore {
+            let semaphore = set.semaphore.ok_or(ObjectBuildError::Semaphore(vk::Result::ERROR_FEATURE_NOT_PRESENT))?;
+            unsafe {
+                external::import_semaphore(&self.device, handle, semaphore.get_handle())
+            }.map_err(ObjectBuildError::Semaphore)?;
+        }
+
+        let objects = set.objects.get_mut().unwrap();
+        for group in &self.alias_groups {
+            self.build_alias_group(objects, *group)?;
+        }
+
+        for request in self.images.iter().filter(|request| request.alias_group.is_none()) {
+            let description = ObjectDescription::Image(request.description);
+
+            if let Some(handle) = request.external {
+                let mut external_info = vk::ExternalMemoryImageCreateInfo::builder()
+                    .handle_types(handle.get_memory_handle_type());
+                let mut info = Self::make_image_info(&request.description)
+                    .push_next(&mut external_info);
+
+                let mut drm_info = request.drm_layout.as_ref().map(|layout| {
+                    vk::ImageDrmFormatModifierExplicitCreateInfoEXT::builder()
+                        .drm_format_modifier(layout.drm_format_modifier)
+                        .plane_layouts(&layout.plane_layouts)
+                });
+                if let Some(drm_info) = drm_info.as_mut() {
+                    info = info.tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT).push_next(drm_info);
+                }
+
+                let image = unsafe { Self::create_external_image(&self.device, &info, handle) }.map_err(|err| {
+                    ObjectBuildError::object(request.id.as_uuid(), description, Some(err))
+                })?;
+
+                objects.external_images.push(image);
+                objects.insert(request.id.as_uuid(), image.0.as_raw(), description);
+                continue;
+            }
+
+            let (image, allocation) = unsafe {
+                Self::create_image(&self.device, self.memory_pool.as_deref(), self.set_id, request.id.as_uuid(), &request.description)
+            }?;
+
+            objects.images.push((image, allocation));
+            objects.insert(request.id.as_uuid(), image.as_raw(), description);
+        }
+
+        for (id, description) in &self.ycbcr_conversions {
+            let info = vk::SamplerYcbcrConversionCreateInfo::builder()
+                .format(description.format.get_format())
+                .ycbcr_model(description.model)
+                .ycbcr_range(description.range)
+                .components(description.components)
+                .x_chroma_offset(description.x_chroma_offset)
+                .y_chroma_offset(description.y_chroma_offset)
+                .chroma_filter(description.chroma_filter)
+                .force_explicit_reconstruction(description.force_explicit_reconstruction);
+
+            let conversion = unsafe {
+                self.device.vk().create_sampler_ycbcr_conversion(&info, None)
+            }.map_err(|err| {
+                ObjectBuildError::object(id.as_uuid(), ObjectDescription::SamplerYcbcrConversion(*description), Some(err))
+            })?;
+
+            objects.ycbcr_conversions.push(conversion);
+            objects.insert(id.as_uuid(), conversion.as_raw(), ObjectDescription::SamplerYcbcrConversion(*description));
+        }
+
+        for (id, description) in &self.samplers {
+            let conversion = description.ycbcr_conversion.map(|conversion| {
+                vk::SamplerYcbcrConversion::from_raw(*objects.handles.get(&conversion.as_uuid()).unwrap())
+            });
+            let sampler = unsafe {
+                Self::create_sampler(&self.device, id.as_uuid(), conversion, description)
+            }?;
+
+            objects.samplers.push(sampler);
+            objects.insert(id.as_uuid(), sampler.as_raw(), ObjectDescription::Sampler(*description));
+        }
+
+        for request in &self.image_views {
+            let image = vk::Image::from_raw(*objects.handles.get(&request.image.as_uuid()).unwrap());
+            let conversion = request.ycbcr_conversion.map(|conversion| {
+                vk::SamplerYcbcrConversion::from_raw(*objects.handles.get(&conversion.as_uuid()).unwrap())
+            });
+
+            let view = unsafe {
+                Self::create_image_view(&self.device, request.id.as_uuid(), image, conversion, &request.description)
+            }?;
+
+            objects.image_views.push(view);
+            objects.insert(request.id.as_uuid(), view.as_raw(), ObjectDescription::ImageView(request.description));
+            objects.view_sources.insert(request.id.as_uuid(), (request.image.as_uuid(), request.ycbcr_conversion.map(|conversion| conversion.as_uuid())));
+        }
+
+        for request in self.buffers.iter().filter(|request| request.alias_group.is_none()) {
+            let description = ObjectDescription::Buffer { size: request.size, usage_flags: request.usage_flags };
+
+            if let Some(handle) = request.external {
+                let mut external_info = vk::ExternalMemoryBufferCreateInfo::builder()
+                    .handle_types(handle.get_memory_handle_type());
+                let info = Self::make_buffer_info(request.size, request.usage_flags)
+                    .push_next(&mut external_info);
+
+                let buffer = unsafe { Self::create_external_buffer(&self.device, &info, handle) }.map_err(|err| {
+                    ObjectBuildError::object(request.id.as_uuid(), description, Some(err))
+                })?;
+
+                objects.external_buffers.push(buffer);
+                objects.insert(request.id.as_uuid(), buffer.0.as_raw(), description);
+                continue;
+            }
+
+            if let Some(host_access) = request.host_access {
+                let (buffer, allocation, mapped) = unsafe {
+                    Self::create_mapped_buffer(&self.device, self.set_id, request.id.as_uuid(), request.size, request.usage_flags, host_access)
+                }?;
+
+                objects.mapped_buffers.push((buffer, allocation));
+                objects.insert(request.id.as_uuid(), buffer.as_raw(), description);
+                objects.update_buffer_address(&self.device, request.id.as_uuid(), buffer, request.usage_flags);
+                objects.buffer_mappings.insert(request.id.as_uuid(), mapped);
+                continue;
+            }
+
+            let (buffer, allocation) = unsafe {
+                Self::create_buffer(&self.device, self.memory_pool.as_deref(), self.set_id, request.id.as_uuid(), request.size, request.usage_flags)
+            }?;
+
+            objects.buffers.push((buffer, allocation));
+            objects.insert(request.id.as_uuid(), buffer.as_raw(), description);
+            objects.update_buffer_address(&self.device, request.id.as_uuid(), buffer, request.usage_flags);
+        }
+
+        for request in &self.buffer_views {
+            let buffer = vk::Buffer::from_raw(*objects.handles.get(&request.buffer.as_uuid()).unwrap());
+
+            let view = unsafe {
+                Self::create_buffer_view(&self.device, request.id.as_uuid(), buffer, &request.description)
+            }?;
+
+            objects.buffer_views.push(view);
+            objects.insert(request.id.as_uuid(), view.as_raw(), ObjectDescription::BufferView(request.description));
+            objects.buffer_view_sources.insert(request.id.as_uuid(), request.buffer.as_uuid());
+        }
+
+        Ok(ObjectSet::new(Arc::new(set)))
+    }
+
+    /// Panics if the device cannot import the handle.
+    fn validate_external_handle(&self, handle: ExternalHandle) {
+        if !self.device.supports_external_memory() {
+            panic!("Device does not support external memory");
+        }
+        if handle.is_dma_buf() && !self.device.supports_dma_buf_import() {
+            panic!("Device does not support dma-buf import");
+        }
+    }
+
+    /// Creates all objects of a alias group and binds them to a single shared allocation. Objects
+    /// are registered in `objects` as soon as they are created so they are destroyed if a later
+    /// step fails.
+    fn build_alias_group(&self, objects: &mut ResourceObjects, group: AliasGroupId) -> Result<(), ObjectBuildError> {
+        let mut requirements = Vec::new();
+
+        for request in self.images.iter().filter(|request| request.alias_group == Some(group)) {
+            let description = ObjectDescription::Image(request.description);
+            let image = unsafe {
+                self.device.vk().create_image(&Self::make_image_info(&request.description), None)
+            }.map_err(|err| {
+                ObjectBuildError::object(request.id.as_uuid(), description, Some(err))
+            })?;
+
+            objects.aliased_images.push(image);
+            objects.insert(request.id.as_uuid(), image.as_raw(), description);
+            objects.alias_tracker.add(group, request.id.as_uuid());
+            requirements.push(unsafe { self.device.vk().get_image_memory_requirements(image) });
+        }
+
+        for request in self.buffers.iter().filter(|request| request.alias_group == Some(group)) {
+            let description = ObjectDescription::Buffer { size: request.size, usage_flags: request.usage_flags };
+            let buffer = unsafe {
+                self.device.vk().create_buffer(&Self::make_buffer_info(request.size, request.usage_flags), None)
+            }.map_err(|err| {
+                ObjectBuildError::object(request.id.as_uuid(), description, Some(err))
+            })?;
+
+            objects.aliased_buffers.push(buffer);
+            objects.insert(request.id.as_uuid(), buffer.as_raw(), description);
+            objects.alias_tracker.add(group, request.id.as_uuid());
+            requirements.push(unsafe { self.device.vk().get_buffer_memory_requirements(buffer) });
+        }
+
+        if requirements.is_empty() {
+            return Ok(());
+        }
+
+        let requirements = combine_memory_requirements(&requirements).ok_or(ObjectBuildError::AliasGroup(group, None))?;
+        let (allocation, binding) = unsafe {
+            self.device.get_allocator().allocate_memory(&requirements, HostAccess::None, &format_args!("ResourceObjectSet {:?} alias group {:?}", self.set_id, group))
+        }.ok_or(ObjectBuildError::AliasGroup(group, None))?;
+        objects.alias_memory.push(allocation);
+
+        for request in self.images.iter().filter(|request| request.alias_group == Some(group)) {
+            let image = vk::Image::from_raw(objects.handles[&request.id.as_uuid()]);
+            unsafe {
+                self.device.vk().bind_image_memory(image, binding.get_device_memory(), binding.get_offset())
+            }.map_err(|err| ObjectBuildError::AliasGroup(group, Some(err)))?;
+        }
+
+        for request in self.buffers.iter().filter(|request| request.alias_group == Some(group)) {
+            let buffer = vk::Buffer::from_raw(objects.handles[&request.id.as_uuid()]);
+            unsafe {
+                self.device.vk().bind_buffer_memory(buffer, binding.get_device_memory(), binding.get_offset())
+            }.map_err(|err| ObjectBuildError::AliasGroup(group, Some(err)))?;
+            objects.update_buffer_address(&self.device, request.id.as_uuid(), buffer, request.usage_flags);
+        }
+
+        Ok(())
+    }
+
+    /// Validates a image view against its source image. The format features are only checked if
+    /// `check_features` is true.
+    fn validate_image_view(device: &DeviceContext, view: ImageViewId, image: ImageId, image_description: &ImageDescription, view_description: &ImageViewDescription, check_features: bool) -> Result<(), ObjectValidationError> {
+        let usage_flags = image_description.usage_flags;
+        if !usage_flags.intersects(VIEW_USAGE_FLAGS) {
+            return Err(ObjectValidationError::MissingImageUsage {
+                view,
+                image,
+                usage_flags,
+            });
+        }
+
+        // Images are never created with MUTABLE_FORMAT so views must use the image format
+        let image_format = image_description.spec.format.get_format();
+        let view_format = view_description.format.get_format();
+        if image_format != view_format {
+            return Err(ObjectValidationError::IncompatibleFormat {
+                view,
+                image_format,
+                view_format,
+            });
+        }
+        if !check_features {
+            return Ok(());
+        }
+
+        let required = Self::required_format_features(usage_flags);
+        let properties = unsafe {
+            device.get_instance().vk().get_physical_device_format_properties(device.get_functions().physical_device, view_format)
+        };
+        if !properties.optimal_tiling_features.contains(required) {
+            return Err(ObjectValidationError::UnsupportedFormatFeatures {
+                view,
+                format: view_format,
+                required,
+                supported: properties.optimal_tiling_features,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn validate_buffer_view(device: &DeviceContext, view: BufferViewId, buffer: BufferId, buffer_size: u64, usage_flags: vk::BufferUsageFlags, description: &BufferViewDescription) -> Result<(), ObjectValidationError> {
+        let required = Self::required_buffer_format_features(usage_flags);
+        if required.is_empty() {
+            return Err(ObjectValidationError::MissingBufferUsage {
+                view,
+                buffer,
+                usage_flags,
+            });
+        }
+
+        let instance = device.get_instance().vk();
+        let physical_device = device.get_functions().physical_device;
+        let limits = unsafe { instance.get_physical_device_properties(physical_device) }.limits;
+
+        let range = description.range;
+        let in_bounds = range.offset.checked_add(range.length).map_or(false, |end| end <= buffer_size);
+        if range.length == 0 || !in_bounds || range.offset % limits.min_texel_buffer_offset_alignment != 0 {
+            return Err(ObjectValidationError::InvalidBufferRange {
+                view,
+                buffer_size,
+                range,
+            });
+        }
+
+        let format = description.format.get_format();
+        let properties = unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+        if !properties.buffer_features.contains(required) {
+            return Err(ObjectValidationError::UnsupportedBufferFormatFeatures {
+                view,
+                format,
+                required,
+                supported: properties.buffer_features,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn make_image_info<'a>(description: &ImageDescription) -> vk::ImageCreateInfoBuilder<'a> {
+        let spec = &description.spec;
+        vk::ImageCreateInfo::builder()
+            .image_type(spec.size.get_vulkan_type())
+            .format(spec.format.get_format())
+            .extent(spec.size.as_extent_3d())
+            .mip_levels(spec.size.get_mip_levels())
+            .array_layers(spec.size.get_array_layers())
+            .samples(spec.sample_count)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(description.usage_flags)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+    }
+
+    fn make_buffer_info<'a>(size: u64, usage_flags: vk::BufferUsageFlags) -> vk::BufferCreateInfoBuilder<'a> {
+        vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(usage_flags)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+    }
+
+    unsafe fn create_image(device: &DeviceContext, pool: Option<&MemoryPool>, set_id: UUID, id: UUID, description: &ImageDescription) -> Result<(vk::Image, Allocation), ObjectBuildError> {
+        device.get_allocator().create_gpu_image_in(pool, &Self::make_image_info(description), &format_args!("ResourceObjectSet {:?} image {:?}", set_id, id)).ok_or_else(|| {
+            ObjectBuildError::object(id, ObjectDescription::Image(*description), None)
+        })
+    }
+
+    unsafe fn create_image_view(device: &DeviceContext, id: UUID, image: vk::Image, conversion: Option<vk::SamplerYcbcrConversion>, description: &ImageViewDescription) -> Result<vk::ImageView, ObjectBuildError> {
+        let mut ycbcr_info = conversion.map(|conversion| {
+            vk::SamplerYcbcrConversionInfo::builder()
+                .conversion(conversion)
+        });
+
+        let mut info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(description.view_type)
+            .format(description.format.get_format())
+            .components(description.components)
+            .subresource_range(description.subresource_range.as_vk_subresource_range());
+
+        if let Some(ycbcr_info) = ycbcr_info.as_mut() {
+            info = info.push_next(ycbcr_info);
+        }
+
+        device.vk().create_image_view(&info, None).map_err(|err| {
+            ObjectBuildError::object(id, ObjectDescription::ImageView(*description), Some(err))
+        })
+    }
+
+    unsafe fn create_sampler(device: &DeviceContext, id: UUID, conversion: Option<vk::SamplerYcbcrConversion>, description: &SamplerDescription) -> Result<vk::Sampler, ObjectBuildError> {
+        let max_anisotropy = match (description.max_anisotropy, device.get_max_sampler_anisotropy()) {
+            (Some(requested), Some(limit)) => Some(requested.clamp(1f32, limit)),
+            _ => None,
+        };
+
+        let mut ycbcr_info = conversion.map(|conversion| {
+            vk::SamplerYcbcrConversionInfo::builder()
+                .conversion(conversion)
+        });
+
+        let mut info = vk::SamplerCreateInfo::builder()
+            .mag_filter(description.mag_filter)
+            .min_filter(description.min_filter)
+            .mipmap_mode(description.mipmap_mode)
+            .address_mode_u(description.address_mode_u)
+            .address_mode_v(description.address_mode_v)
+            .address_mode_w(description.address_mode_w)
+            .mip_lod_bias(description.mip_lod_bias)
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1f32))
+            .compare_enable(false)
+            .min_lod(description.min_lod)
+            .max_lod(description.max_lod)
+            .border_color(description.border_color)
+            .unnormalized_coordinates(false);
+        if let Some(ycbcr_info) = ycbcr_info.as_mut() {
+            info = info.push_next(ycbcr_info);
+        }
+
+        device.vk().create_sampler(&info, None).map_err(|err| {
+            ObjectBuildError::object(id, ObjectDescription::Sampler(*description), Some(err))
+        })
+    }
+
+    unsafe fn create_buffer(device: &DeviceContext, pool: Option<&MemoryPool>, set_id: UUID, id: UUID, size: u64, usage_flags: vk::BufferUsageFlags) -> Result<(vk::Buffer, Allocation), ObjectBuildError> {
+        device.get_allocator().create_gpu_buffer_in(pool, &Self::make_buffer_info(size, usage_flags), &format_args!("ResourceObjectSet {:?} buffer {:?}", set_id, id)).ok_or_else(|| {
+            ObjectBuildError::object(id, ObjectDescription::Buffer { size, usage_flags }, None)
+        })
+    }
+
+    unsafe fn create_buffer_view(device: &DeviceContext, id: UUID, buffer: vk::Buffer, description: &BufferViewDescription) -> Result<vk::BufferView, ObjectBuildError> {
+        let info = vk::BufferViewCreateInfo::builder()
+            .buffer(buffer)
+            .format(description.format.get_format())
+            .offset(description.range.offset)
+            .range(description.range.length);
+
+        device.vk().create_buffer_view(&info, None).map_err(|err| {
+            ObjectBuildError::object(id, ObjectDescription::BufferView(*description), Some(err))
+        })
+    }
+
+    unsafe fn create_mapped_buffer(device: &DeviceContext, set_id: UUID, id: UUID, size: u64, usage_flags: vk::BufferUsageFlags, host_access: HostAccess) -> Result<(vk::Buffer, Allocation, NonNull<u8>), ObjectBuildError> {
+        device.get_allocator().create_mapped_buffer(&Self::make_buffer_info(size, usage_flags), host_access, &format_args!("ResourceObjectSet {:?} mapped buffer {:?}", set_id, id)).ok_or_else(|| {
+            ObjectBuildError::object(id, ObjectDescription::Buffer { size, usage_flags }, None)
+        })
+    }
+
+    unsafe fn create_external_image(device: &DeviceContext, info: &vk::ImageCreateInfo, handle: ExternalHandle) -> VkResult<(vk::Image, vk::DeviceMemory)> {
+        let image = device.vk().create_image(info, None)?;
+
+        let requirements_info = vk::ImageMemoryRequirementsInfo2::builder().image(image);
+        let mut dedicated_requirements = vk::MemoryDedicatedRequirements::builder();
+        let mut requirements = vk::MemoryRequirements2::builder().push_next(&mut dedicated_requirements);
+        device.vk().get_image_memory_requirements2(&requirements_info, &mut requirements);
+        let requirements = requirements.memory_requirements;
+        let dedicated = dedicated_requirements.requires_dedicated_allocation == vk::TRUE || dedicated_requirements.prefers_dedicated_allocation == vk::TRUE;
+
+        let memory = match external::import_memory(device, handle, &requirements, dedicated, image, vk::Buffer::null()) {
+            Ok(memory) => memory,
+            Err(err) => {
+                device.vk().destroy_image(image, None);
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = device.vk().bind_image_memory(image, memory, 0) {
+            device.vk().destroy_image(image, None);
+            device.vk().free_memory(memory, None);
+            return Err(err);
+        }
+
+        Ok((image, memory))
+    }
+
+    unsafe fn create_external_buffer(device: &DeviceContext, info: &vk::BufferCreateInfo, handle: ExternalHandle) -> VkResult<(vk::Buffer, vk::DeviceMemory)> {
+        let buffer = device.vk().create_buffer(info, None)?;
+
+        let requirements_info = vk::BufferMemoryRequirementsInfo2::builder().buffer(buffer);
+        let mut dedicated_requirements = vk::MemoryDedicatedRequirements::builder();
+        let mut requirements = vk::MemoryRequirements2::builder().push_next(&mut dedicated_requirements);
+        device.vk().get_buffer_memory_requirements2(&requirements_info, &mut requirements);
+        let requirements = requirements.memory_requirements;
+        let dedicated = dedicated_requirements.requires_dedicated_allocation == vk::TRUE || dedicated_requirements.prefers_dedicated_allocation == vk::TRUE;
+
+        let memory = match external::import_memory(device, handle, &requirements, dedicated, vk::Image::null(), buffer) {
+            Ok(memory) => memory,
+            Err(err) => {
+                device.vk().destroy_buffer(buffer, None);
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = device.vk().bind_buffer_memory(buffer, memory, 0) {
+            device.vk().destroy_buffer(buffer, None);
+            device.vk().free_memory(memory, None);
+            return Err(err);
+        }
+
+        Ok((buffer, memory))
+    }
+
+    fn find_image(&self, id: ImageId) -> Option<&ImageRequest> {
+        self.images.iter().find(|request| request.id == id)
+    }
+
+    fn find_buffer(&self, id: BufferId) -> Option<&BufferRequest> {
+        self.buffers.iter().find(|request| request.id == id)
+    }
+
+    /// Validates the aspect mask of a view of a possibly multi-planar image.
+    ///
+    /// Views of a single plane must reference a plane that exists in the image format. Views of the
+    /// color aspect of a multi-planar image must use a ycbcr conversion.
+    fn validate_plane_aspects(image: &ImageRequest, description: &ImageViewDescription, has_conversion: bool) {
+        let format = image.description.spec.format;
+        let aspect_mask = description.subresource_range.aspect_mask;
+        let plane_count = format.get_plane_count();
+
+        let planes = [vk::ImageAspectFlags::PLANE_0, vk::ImageAspectFlags::PLANE_1, vk::ImageAspectFlags::PLANE_2];
+        for (plane, aspect) in planes.iter().enumerate() {
+            if aspect_mask.contains(*aspect) && (plane as u32) >= plane_count {
+                panic!("Image view aspect mask {:?} references plane {} but image {:?} with format {:?} only has {} planes", aspect_mask, plane, image.id, format, plane_count);
+            }
+        }
+
+        if format.is_multi_planar() && aspect_mask.contains(vk::ImageAspectFlags::COLOR) && !has_conversion {
+            panic!("Image view of the color aspect of multi-planar image {:?} requires a ycbcr conversion", image.id);
+        }
+    }
+
+    /// Validates a sampler using a ycbcr conversion against the restrictions of the conversion.
+    fn validate_ycbcr_sampler(&self, description: &SamplerDescription, conversion: &SamplerYcbcrConversionDescription) {
+        let address_modes = [description.address_mode_u, description.address_mode_v, description.address_mode_w];
+        if address_modes.iter().any(|mode| *mode != vk::SamplerAddressMode::CLAMP_TO_EDGE) {
+            panic!("Samplers using a ycbcr conversion must clamp to the edge but got {:?}", address_modes);
+        }
+        if description.max_anisotropy.is_some() {
+            panic!("Samplers using a ycbcr conversion do not support anisotropic filtering");
+        }
+
+        let properties = unsafe {
+            self.device.get_instance().vk().get_physical_device_format_properties(self.device.get_functions().physical_device, conversion.format.get_format())
+        };
+        let separate_filter = properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_YCBCR_CONVERSION_SEPARATE_RECONSTRUCTION_FILTER);
+        if !separate_filter && (description.min_filter != conversion.chroma_filter || description.mag_filter != conversion.chroma_filter) {
+            panic!("Format {:?} does not support separate reconstruction filters so the sampler filters must match the chroma filter {:?}", conversion.format, conversion.chroma_filter);
+        }
+    }
+
+    /// Returns the format features a image view format must support for a image with the provided
+    /// usage flags.
+    fn required_format_features(usage_flags: vk::ImageUsageFlags) -> vk::FormatFeatureFlags {
+        let mut features = vk::FormatFeatureFlags::empty();
+        if usage_flags.contains(vk::ImageUsageFlags::SAMPLED) {
+            features |= vk::FormatFeatureFlags::SAMPLED_IMAGE;
+        }
+        if usage_flags.contains(vk::ImageUsageFlags::STORAGE) {
+            features |= vk::FormatFeatureFlags::STORAGE_IMAGE;
+        }
+        if usage_flags.contains(vk::ImageUsageFlags::COLOR_ATTACHMENT) {
+            features |= vk::FormatFeatureFlags::COLOR_ATTACHMENT;
+        }
+        if usage_flags.contains(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT) {
+            features |= vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT;
+        }
+        features
+    }
+
+    /// Returns the format features a buffer view format must support for a buffer with the
+    /// provided usage flags. Returns empty flags if the usage does not allow buffer views.
+    fn required_buffer_format_features(usage_flags: vk::BufferUsageFlags) -> vk::FormatFeatureFlags {
+        let mut features = vk::FormatFeatureFlags::empty();
+        if usage_flags.contains(vk::BufferUsageFlags::UNIFORM_TEXEL_BUFFER) {
+            features |= vk::FormatFeatureFlags::UNIFORM_TEXEL_BUFFER;
+        }
+        if usage_flags.contains(vk::BufferUsageFlags::STORAGE_TEXEL_BUFFER) {
+            features |= vk::FormatFeatureFlags::STORAGE_TEXEL_BUFFER;
+        }
+        features
+    }
+
+    fn resolve_count(base: u32, count: u32, total: u32) -> u32 {
+        // REMAINING_MIP_LEVELS and REMAINING_ARRAY_LAYERS have the same value
+        if count == vk::REMAINING_MIP_LEVELS {
+            total
+        } else {
+            std::cmp::min(base + count, total)
+        }
+    }
+}
+
+/// The image usage flags which allow image views to be created for a image.
+const VIEW_USAGE_FLAGS: vk::ImageUsageFlags = vk::ImageUsageFlags::from_raw(
+    vk::ImageUsageFlags::SAMPLED.as_raw() |
+    vk::ImageUsageFlags::STORAGE.as_raw() |
+    vk::ImageUsageFlags::COLOR_ATTACHMENT.as_raw() |
+    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT.as_raw() |
+    vk::ImageUsageFlags::INPUT_ATTACHMENT.as_raw() |
+    vk::ImageUsageFlags::TRANSIENT_ATTACHMENT.as_raw()
+);
+
+/// Errors detected by [`ResourceObjectSetBuilder::validate`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ObjectValidationError {
+    /// The source image of a view was not created with any usage flag that allows image views.
+    MissingImageUsage {
+        view: ImageViewId,
+        image: ImageId,
+        usage_flags: vk::ImageUsageFlags,
+    },
+    /// The format of a view differs from the format of its source image.
+    IncompatibleFormat {
+        view: ImageViewId,
+        image_format: vk::Format,
+        view_format: vk::Format,
+    },
+    /// The format of a view does not support the features required by the usage flags of its
+    /// source image.
+    UnsupportedFormatFeatures {
+        view: ImageViewId,
+        format: vk::Format,
+        required: vk::FormatFeatureFlags,
+        supported: vk::FormatFeatureFlags,
+    },
+    /// The source buffer of a view was not created with a texel buffer usage flag.
+    MissingBufferUsage {
+        view: BufferViewId,
+        buffer: BufferId,
+        usage_flags: vk::BufferUsageFlags,
+    },
+    /// The range of a buffer view is empty, not fully contained in its source buffer or its offset
+    /// is not aligned to `minTexelBufferOffsetAlignment`.
+    InvalidBufferRange {
+        view: BufferViewId,
+        buffer_size: u64,
+        range: BufferRange,
+    },
+    /// The format of a buffer view does not support the texel buffer features required by the
+    /// usage flags of its source buffer.
+    UnsupportedBufferFormatFeatures {
+        view: BufferViewId,
+        format: vk::Format,
+        required: vk::FormatFeatureFlags,
+        supported: vk::FormatFeatureFlags,
+    },
+}
+
+/// Errors returned by [`ResourceObjectSetBuilder::build`].
+#[derive(Copy, Clone, Debug)]
+pub enum ObjectBuildError {
+    Validation(ObjectValidationError),
+    /// Creating or importing the timeline semaphore of the set failed or waiting for it before
+    /// rebuilding a object failed or timed out.
+    Semaphore(vk::Result),
+    /// The object is not part of the set.
+    UnknownObject(UUID),
+    /// The object can not be rebuilt, has live exported handles, the set has no semaphore or the
+    /// new description is of a different kind.
+    RebuildUnsupported(UUID),
+    /// The objects of a alias group have no common memory type or allocating or binding the
+    /// shared memory failed. The result is [`None`] if the allocator failed since it does not
+    /// report the cause.
+    AliasGroup(AliasGroupId, Option<vk::Result>),
+    /// Creating a object of the set failed.
+    Object {
+        id: UUID,
+        description: ObjectDescription,
+        /// The error returned by vulkan. [`None`] if the allocator failed to create the object
+        /// since it does not report the cause.
+        result: Option<vk::Result>,
+    },
+}
+
+impl ObjectBuildError {
+    fn object(id: UUID, description: ObjectDescription, result: Option<vk::Result>) -> Self {
+        Self::Object {
+            id,
+            description,
+            result,
+        }
+    }
+}
+
+/// Selects which subresources [`ResourceObjectSetBuilder::add_image_view_chain`] creates views for.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ImageViewChainType {
+    /// One view per mip level
+    MipLevels,
+    /// One view per array layer
+    ArrayLayers,
+}
+
+struct ImageRequest {
+    id: ImageId,
+    description: ImageDescription,
+    external: Option<ExternalHandle>,
+    drm_layout: Option<DrmFormatModifierLayout>,
+    alias_group: Option<AliasGroupId>,
+}
+
+struct ImageViewRequest {
+    id: ImageViewId,
+    image: ImageId,
+    description: ImageViewDescription,
+    ycbcr_conversion: Option<SamplerYcbcrConversionId>,
+}
+
+struct BufferRequest {
+    id: BufferId,
+    size: u64,
+    usage_flags: vk::BufferUsageFlags,
+    external: Option<ExternalHandle>,
+    /// Set for persistently mapped host visible buffers
+    host_access: Option<HostAccess>,
+    alias_group: Option<AliasGroupId>,
+}
+
+struct BufferViewRequest {
+    id: BufferViewId,
+    buffer: BufferId,
+    description: BufferViewDescription,
+}
+
+struct ResourceObjectSet {
+    device: Arc<DeviceContext>,
+    set_id: UUID,
+    semaphore: Option<Semaphore>,
+    memory_pool: Option<Arc<MemoryPool>>,
+    objects: Mutex<ResourceObjects>,
+}
+
+impl ResourceObjectSet {
+    /// The maximum time [`ObjectSetProvider::rebuild_object`] waits for pending gpu work in
+    /// nanoseconds.
+    const REBUILD_WAIT_TIMEOUT: u64 = 5_000_000_000;
+
+    /// Waits until the timeline semaphore of the set reached `value` or the timeout elapsed.
+    fn wait_for(&self, semaphore: Semaphore, value: u64, timeout: u64) -> Result<(), ObjectBuildError> {
+        let handle = semaphore.get_handle();
+        let info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(std::slice::from_ref(&handle))
+            .values(std::slice::from_ref(&value));
+
+        unsafe {
+            self.device.timeline_semaphore_khr().wait_semaphores(&info, timeout)
+        }.map_err(ObjectBuildError::Semaphore)
+    }
+
+    /// Recreates a image and all image views of it. The views keep their current descriptions.
+    fn rebuild_image(&self, objects: &mut ResourceObjects, id: UUID, description: &ImageDescription) -> Result<(), ObjectBuildError> {
+        let old = vk::Image::from_raw(objects.handles[&id]);
+        let index = objects.images.iter().position(|(image, _)| *image == old).ok_or(ObjectBuildError::RebuildUnsupported(id))?;
+
+        let views: Vec<_> = objects.view_sources.iter()
+            .filter(|(_, (image, _))| *image == id)
+            .map(|(view, (_, conversion))| (*view, objects.get_view_description(*view), *conversion))
+            .collect();
+        for (view, view_description, _) in &views {
+            ResourceObjectSetBuilder::validate_image_view(&self.device, ImageViewId::from_raw(*view), ImageId::from_raw(id), description, view_description, true)
+                .map_err(ObjectBuildError::Validation)?;
+        }
+
+        let (image, allocation) = unsafe {
+            ResourceObjectSetBuilder::create_image(&self.device, self.memory_pool.as_deref(), self.set_id, id, description)
+        }?;
+
+        let mut new_views = Vec::with_capacity(views.len());
+        for (view, view_description, conversion) in &views {
+            let conversion = conversion.map(|conversion| vk::SamplerYcbcrConversion::from_raw(objects.handles[&conversion]));
+            match unsafe { ResourceObjectSetBuilder::create_image_view(&self.device, *view, image, conversion, view_description) } {
+                Ok(handle) => new_views.push((*view, handle)),
+                Err(err) => {
+                    unsafe {
+                        for (_, handle) in new_views {
+                            self.device.vk().destroy_image_view(handle, None);
+                        }
+                        self.device.get_allocator().destroy_image(image, allocation);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        // The old views reference the old image so they must be destroyed first
+        for (view, handle) in new_views {
+            unsafe { objects.replace_image_view(&self.device, view, handle) };
+        }
+        let (old_image, old_allocation) = std::mem::replace(&mut objects.images[index], (image, allocation));
+        unsafe { self.device.get_allocator().destroy_image(old_image, old_allocation) };
+        objects.replace(id, image.as_raw(), ObjectDescription::Image(*description));
+
+        Ok(())
+    }
+
+    fn rebuild_image_view(&self, objects: &mut ResourceObjects, id: UUID, description: &ImageViewDescription) -> Result<(), ObjectBuildError> {
+        let (image_id, conversion) = objects.view_sources[&id];
+        let image_description = match objects.get_description(image_id) {
+            Some(ObjectDescription::Image(image_description)) => image_description,
+            _ => return Err(ObjectBuildError::RebuildUnsupported(id)),
+        };
+        // The drm layout of external images is not retained so only views of gpu only images are feature checked
+        let check_features = objects.images.iter().any(|(image, _)| image.as_raw() == objects.handles[&image_id]);
+        ResourceObjectSetBuilder::validate_image_view(&self.device, ImageViewId::from_raw(id), ImageId::from_raw(image_id), &image_description, description, check_features)
+            .map_err(ObjectBuildError::Validation)?;
+
+        let image = vk::Image::from_raw(objects.handles[&image_id]);
+        let conversion = conversion.map(|conversion| vk::SamplerYcbcrConversion::from_raw(objects.handles[&conversion]));
+        let view = unsafe {
+            ResourceObjectSetBuilder::create_image_view(&self.device, id, image, conversion, description)
+        }?;
+
+        unsafe { objects.replace_image_view(&self.device, id, view) };
+        objects.replace(id, view.as_raw(), ObjectDescription::ImageView(*description));
+
+        Ok(())
+    }
+
+    /// Recreates a buffer and all buffer views of it. The views keep their current descriptions.
+    fn rebuild_buffer(&self, objects: &mut ResourceObjects, id: UUID, size: u64, usage_flags: vk::BufferUsageFlags) -> Result<(), ObjectBuildError> {
+        if usage_flags.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) && !self.device.supports_buffer_device_address() {
+            return Err(ObjectBuildError::RebuildUnsupported(id));
+        }
+
+        let old = vk::Buffer::from_raw(objects.handles[&id]);
+        let index = objects.buffers.iter().position(|(buffer, _)| *buffer == old).ok_or(ObjectBuildError::RebuildUnsupported(id))?;
+
+        let views: Vec<_> = objects.buffer_view_sources.iter()
+            .filter(|(_, buffer)| **buffer == id)
+            .map(|(view, _)| (*view, objects.get_buffer_view_description(*view)))
+            .collect();
+        for (view, view_description) in &views {
+            ResourceObjectSetBuilder::validate_buffer_view(&self.device, BufferViewId::from_raw(*view), BufferId::from_raw(id), size, usage_flags, view_description)
+                .map_err(ObjectBuildError::Validation)?;
+        }
+
+        let (buffer, allocation) = unsafe {
+            ResourceObjectSetBuilder::create_buffer(&self.device, self.memory_pool.as_deref(), self.set_id, id, size, usage_flags)
+        }?;
+
+        let mut new_views = Vec::with_capacity(views.len());
+        for (view, view_description) in &views {
+            match unsafe { ResourceObjectSetBuilder::create_buffer_view(&self.device, *view, buffer, view_description) } {
+                Ok(handle) => new_views.push((*view, handle)),
+                Err(err) => {
+                    unsafe {
+                        for (_, handle) in new_views {
+                            self.device.vk().destroy_buffer_view(handle, None);
+                        }
+                        self.device.get_allocator().destroy_buffer(buffer, allocation);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        // The old views reference the old buffer so they must be destroyed first
+        for (view, handle) in new_views {
+            unsafe { objects.replace_buffer_view(&self.device, view, handle) };
+        }
+        let (old_buffer, old_allocation) = std::mem::replace(&mut objects.buffers[index], (buffer, allocation));
+        unsafe { self.device.get_allocator().destroy_buffer(old_buffer, old_allocation) };
+        objects.replace(id, buffer.as_raw(), ObjectDescription::Buffer { size, usage_flags });
+        objects.update_buffer_address(&self.device, id, buffer, usage_flags);
+
+        Ok(())
+    }
+
+    fn rebuild_buffer_view(&self, objects: &mut ResourceObjects, id: UUID, description: &BufferViewDescription) -> Result<(), ObjectBuildError> {
+        let buffer_id = objects.buffer_view_sources[&id];
+        let (size, usage_flags) = match objects.get_description(buffer_id) {
+            Some(ObjectDescription::Buffer { size, usage_flags }) => (size, usage_flags),
+            _ => return Err(ObjectBuildError::RebuildUnsupported(id)),
+        };
+        ResourceObjectSetBuilder::validate_buffer_view(&self.device, BufferViewId::from_raw(id), BufferId::from_raw(buffer_id), size, usage_flags, description)
+            .map_err(ObjectBuildError::Validation)?;
+
+        let buffer = vk::Buffer::from_raw(objects.handles[&buffer_id]);
+        let view = unsafe {
+            ResourceObjectSetBuilder::create_buffer_view(&self.device, id, buffer, description)
+        }?;
+
+        unsafe { objects.replace_buffer_view(&self.device, id, view) };
+        objects.replace(id, view.as_raw(), ObjectDescription::BufferView(*description));
+
+        Ok(())
+    }
+}
+
+impl ObjectSetProvider for ResourceObjectSet {
+    fn get_id(&self) -> UUID {
+        self.set_id
+    }
+
+    fn get_handle(&self, id: UUID) -> Option<u64> {
+        self.objects.lock().unwrap().handles.get(&id).cloned()
+    }
+
+    fn get_semaphore(&self) -> Option<Semaphore> {
+        self.semaphore
+    }
+
+    fn for_each_object(&self, f: &mut dyn FnMut(UUID, ObjectKind)) {
+        for (id, description) in &self.objects.lock().unwrap().objects {
+            f(*id, description.get_kind());
+        }
+    }
+
+    fn object_count(&self) -> usize {
+        self.objects.lock().unwrap().objects.len()
+    }
+
+    fn description(&self, id: UUID) -> Option<ObjectDescription> {
+        self.objects.lock().unwrap().get_description(id)
+    }
+
+    fn get_buffer_address(&self, id: UUID) -> Option<vk::DeviceAddress> {
+        self.objects.lock().unwrap().buffer_addresses.get(&id).copied()
+    }
+
+    fn on_export(&self, id: UUID) {
+        *self.objects.lock().unwrap().exports.entry(id).or_insert(0) += 1;
+    }
+
+    fn on_export_dropped(&self, id: UUID) {
+        let mut objects = self.objects.lock().unwrap();
+        if let Some(count) = objects.exports.get_mut(&id) {
+            *count -= 1;
+            if *count == 0 {
+                objects.exports.remove(&id);
+            }
+        }
+    }
+
+    fn get_buffer_mapping(&self, id: UUID) -> Option<NonNull<u8>> {
+        self.objects.lock().unwrap().buffer_mappings.get(&id).copied()
+    }
+
+    fn activate_aliased(&self, id: UUID) -> Option<AliasingBarrier> {
+        self.objects.lock().unwrap().alias_tracker.activate(id)
+    }
+
+    /// Gpu only images, image views, gpu only buffers and buffer views can be rebuilt. Rebuilding a
+    /// image or buffer also recreates all views of it. Aliased objects cannot be rebuilt.
+    ///
+    /// Without a timeline semaphore there is no way to know when the gpu stopped using the object
+    /// so sets without a semaphore do not support rebuilding.
+    fn rebuild_object(&self, id: UUID, description: &ObjectDescription, last_use: u64) -> Result<(), ObjectBuildError> {
+        let semaphore = self.semaphore.ok_or(ObjectBuildError::RebuildUnsupported(id))?;
+
+        // Wait before locking the objects so that other users of the set are not blocked
+        self.wait_for(semaphore, last_use, Self::REBUILD_WAIT_TIMEOUT)?;
+
+        let mut guard = self.objects.lock().unwrap();
+        let objects = &mut *guard;
+
+        let current = objects.get_description(id).ok_or(ObjectBuildError::UnknownObject(id))?;
+        if current.get_kind() != description.get_kind() {
+            return Err(ObjectBuildError::RebuildUnsupported(id));
+        }
+
+        // Exported handles would dangle. Rebuilding a image or buffer also recreates its views.
+        let exported = objects.exports.contains_key(&id) || objects.view_sources.iter()
+            .any(|(view, (image, _))| *image == id && objects.exports.contains_key(view)) || objects.buffer_view_sources.iter()
+            .any(|(view, buffer)| *buffer == id && objects.exports.contains_key(view));
+        if exported {
+            return Err(ObjectBuildError::RebuildUnsupported(id));
+        }
+
+        match description {
+            ObjectDescription::Image(description) => self.rebuild_image(objects, id, description),
+            ObjectDescription::ImageView(description) => self.rebuild_image_view(objects, id, description),
+            ObjectDescription::Buffer { size, usage_flags } => self.rebuild_buffer(objects, id, *size, *usage_flags),
+            ObjectDescription::BufferView(description) => self.rebuild_buffer_view(objects, id, description),
+            _ => Err(ObjectBuildError::RebuildUnsupported(id)),
+        }
+    }
+}
+
+impl Drop for ResourceObjectSet {
+    fn drop(&mut self) {
+        let allocator = self.device.get_allocator();
+        let objects = self.objects.get_mut().unwrap();
+        unsafe {
+            for view in objects.buffer_views.drain(..) {
+                self.device.vk().destroy_buffer_view(view, None);
+            }
+            for (buffer, allocation) in objects.buffers.drain(..) {
+                allocator.destroy_buffer(buffer, allocation);
+            }
+            for (buffer, memory) in objects.external_buffers.drain(..) {
+                self.device.vk().destroy_buffer(buffer, None);
+                self.device.vk().free_memory(memory, None);
+            }
+            objects.buffer_mappings.clear();
+            for (buffer, allocation) in objects.mapped_buffers.drain(..) {
+                allocator.destroy_buffer(buffer, allocation);
+            }
+            for buffer in objects.aliased_buffers.drain(..) {
+                self.device.vk().destroy_buffer(buffer, None);
+            }
+            for view in objects.image_views.drain(..) {
+                self.device.vk().destroy_image_view(view, None);
+            }
+            for sampler in objects.samplers.drain(..) {
+                self.device.vk().destroy_sampler(sampler, None);
+            }
+            for conversion in objects.ycbcr_conversions.drain(..) {
+                self.device.vk().destroy_sampler_ycbcr_conversion(conversion, None);
+            }
+            for (image, allocation) in objects.images.drain(..) {
+                allocator.destroy_image(image, allocation);
+            }
+            for (image, memory) in objects.external_images.drain(..) {
+                self.device.vk().destroy_image(image, None);
+                self.device.vk().free_memory(memory, None);
+            }
+            for image in objects.aliased_images.drain(..) {
+                self.device.vk().destroy_image(image, None);
+            }
+            for allocation in objects.alias_memory.drain(..) {
+                allocator.free_memory(allocation);
+            }
+            if let Some(semaphore) = self.semaphore.take() {
+                self.device.vk().destroy_semaphore(semaphore.get_handle(), None);
+            }
+        }
+    }
+}
+
+/// The objects owned by a [`ResourceObjectSet`].
+struct ResourceObjects {
+    images: Vec<(vk::Image, Allocation)>,
+    image_views: Vec<vk::ImageView>,
+    buffers: Vec<(vk::Buffer, Allocation)>,
+    buffer_views: Vec<vk::BufferView>,
+    ycbcr_conversions: Vec<vk::SamplerYcbcrConversion>,
+    samplers: Vec<vk::Sampler>,
+    external_images: Vec<(vk::Image, vk::DeviceMemory)>,
+    external_buffers: Vec<(vk::Buffer, vk::DeviceMemory)>,
+    mapped_buffers: Vec<(vk::Buffer, Allocation)>,
+    aliased_images: Vec<vk::Image>,
+    aliased_buffers: Vec<vk::Buffer>,
+    /// The shared memory of every alias group
+    alias_memory: Vec<Allocation>,
+    alias_tracker: AliasTracker,
+    handles: HashMap<UUID, u64>,
+    /// All objects in the order they were created
+    objects: Vec<(UUID, ObjectDescription)>,
+    /// The source image and ycbcr conversion of every image view
+    view_sources: HashMap<UUID, (UUID, Option<UUID>)>,
+    /// The source buffer of every buffer view
+    buffer_view_sources: HashMap<UUID, UUID>,
+    /// The device addresses of all buffers created with SHADER_DEVICE_ADDRESS usage
+    buffer_addresses: HashMap<UUID, vk::DeviceAddress>,
+    /// The number of live guarded handles of every exported object
+    exports: HashMap<UUID, usize>,
+    /// The mapped memory of all persistently mapped buffers
+    buffer_mappings: HashMap<UUID, NonNull<u8>>,
+}
+
+unsafe impl Send for ResourceObjects { // Needed because of NonNull<u8>
+}
+unsafe impl Sync for ResourceObjects { // Needed because of NonNull<u8>
+}
+
+impl ResourceObjects {
+    fn insert(&mut self, id: UUID, handle: u64, description: ObjectDescription) {
+        self.handles.insert(id, handle);
+        self.objects.push((id, description));
+    }
+
+    fn replace(&mut self, id: UUID, handle: u64, description: ObjectDescription) {
+        self.handles.insert(id, handle);
+        if let Some(entry) = self.objects.iter_mut().find(|(object, _)| *object == id) {
+            entry.1 = description;
+        }
+    }
+
+    fn update_buffer_address(&mut self, device: &DeviceContext, id: UUID, buffer: vk::Buffer, usage_flags: vk::BufferUsageFlags) {
+        if usage_flags.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            let info = vk::BufferDeviceAddressInfo::builder()
+                .buffer(buffer);
+            let address = unsafe { device.vk().get_buffer_device_address(&info) };
+            self.buffer_addresses.insert(id, address);
+        } else {
+            self.buffer_addresses.remove(&id);
+        }
+    }
+
+    fn get_description(&self, id: UUID) -> Option<ObjectDescription> {
+        self.objects.iter().find(|(object, _)| *object == id).map(|(_, description)| *description)
+    }
+
+    fn get_view_description(&self, id: UUID) -> ImageViewDescription {
+        match self.get_description(id) {
+            Some(ObjectDescription::ImageView(description)) => description,
+            _ => panic!("Object {:?} is not a image view", id),
+        }
+    }
+
+    fn get_buffer_view_description(&self, id: UUID) -> BufferViewDescription {
+        match self.get_description(id) {
+            Some(ObjectDescription::BufferView(description)) => description,
+            _ => panic!("Object {:?} is not a buffer view", id),
+        }
+    }
+
+    /// Destroys the current handle of a image view and replaces it with `view`.
+    unsafe fn replace_image_view(&mut self, device: &DeviceContext, id: UUID, view: vk::ImageView) {
+        let old = vk::ImageView::from_raw(self.handles[&id]);
+        let index = self.image_views.iter().position(|handle| *handle == old).unwrap();
+        device.vk().destroy_image_view(old, None);
+        self.image_views[index] = view;
+        self.handles.insert(id, view.as_raw());
+    }
+
+    /// Destroys the current handle of a buffer view and replaces it with `view`.
+    unsafe fn replace_buffer_view(&mut self, device: &DeviceContext, id: UUID, view: vk::BufferView) {
+        let old = vk::BufferView::from_raw(self.handles[&id]);
+        let index = self.buffer_views.iter().position(|handle| *handle == old).unwrap();
+        device.vk().destroy_buffer_view(old, None);
+        self.buffer_views[index] = view;
+        self.handles.insert(id, view.as_raw());
+    }
+}
+
+impl Debug for ResourceObjectSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("ResourceObjectSet({:?})", self.set_id))
+    }
+}
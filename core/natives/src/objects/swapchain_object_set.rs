@@ -0,0 +1,329 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use ash::prelude::VkResult;
+use ash::vk;
+use ash::vk::Handle;
+
+use super::id::{FramebufferId, ImageId, ImageViewId, ObjectId, ObjectKind};
+use super::object_set::{ObjectDescription, ObjectSet, ObjectSetProvider};
+use super::sync::Semaphore;
+
+use crate::device::surface::SurfaceSwapchain;
+use crate::vk::objects::image::ImageSubresourceRange;
+
+use crate::prelude::*;
+
+/// Utility to create object sets for the images of a [`SurfaceSwapchain`].
+///
+/// Since the objects of a swapchain are duplicated for every swapchain image the built
+/// [`SwapchainObjectSet`] contains one [`ObjectSet`] per image. All image sets use the same ids
+/// so code can be written independent of the image currently acquired.
+pub struct SwapchainObjectSetBuilder {
+    swapchain: Arc<SurfaceSwapchain>,
+    image_id: ImageId,
+    views: Vec<(ImageViewId, SwapchainImageViewInfo)>,
+    framebuffers: Vec<(FramebufferId, SwapchainFramebufferInfo)>,
+}
+
+impl SwapchainObjectSetBuilder {
+    pub fn new(swapchain: Arc<SurfaceSwapchain>) -> Self {
+        Self {
+            swapchain,
+            image_id: ImageId::new(),
+            views: Vec::new(),
+            framebuffers: Vec::new(),
+        }
+    }
+
+    /// Returns the id used for the swapchain image itself.
+    pub fn get_image_id(&self) -> ImageId {
+        self.image_id
+    }
+
+    /// Adds a image view which is created for every swapchain image.
+    ///
+    /// If no format is specified the format of the swapchain is used.
+    pub fn add_image_view(&mut self, format: Option<vk::Format>, subresource_range: ImageSubresourceRange) -> ImageViewId {
+        let id = ImageViewId::new();
+        self.views.push((id, SwapchainImageViewInfo {
+            format: format.unwrap_or(self.swapchain.get_image_format().format),
+            subresource_range,
+        }));
+
+        id
+    }
+
+    /// Adds a image view covering the full color subresource range using the swapchain format.
+    /// This view can directly be used as a framebuffer attachment.
+    pub fn add_framebuffer_view(&mut self) -> ImageViewId {
+        self.add_image_view(None, ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            mip_level_count: 1,
+            base_array_layer: 0,
+            array_layer_count: 1
+        })
+    }
+
+    /// Adds a framebuffer which is created for every swapchain image. The framebuffer has the size
+    /// of the swapchain images and uses the image views of the respective image as attachments.
+    ///
+    /// All attachments must be image views previously added to this builder.
+    pub fn add_framebuffer(&mut self, render_pass: vk::RenderPass, attachments: &[ImageViewId]) -> FramebufferId {
+        for attachment in attachments {
+            if !self.views.iter().any(|(id, _)| id == attachment) {
+                log::error!("Framebuffer attachment {:?} is not a image view of this swapchain object set", attachment);
+                panic!()
+            }
+        }
+
+        let id = FramebufferId::new();
+        self.framebuffers.push((id, SwapchainFramebufferInfo {
+            render_pass,
+            attachments: attachments.to_vec().into_boxed_slice(),
+        }));
+
+        id
+    }
+
+    pub fn build(self) -> VkResult<SwapchainObjectSet> {
+        let mut images: Vec<ObjectSet> = Vec::with_capacity(self.swapchain.get_images().len());
+
+        for (index, image) in self.swapchain.get_images().iter().enumerate() {
+            match SwapchainImageSet::new(self.swapchain.clone(), index, image.get_image().get_handle(), image.get_present_semaphore(), self.image_id, &self.views, &self.framebuffers) {
+                Ok(set) => images.push(ObjectSet::new(Arc::new(set))),
+                Err(err) => {
+                    log::error!("Failed to create swapchain image objects {:?}", err);
+                    // Already created sets will be destroyed when dropped
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(SwapchainObjectSet {
+            swapchain: self.swapchain,
+            image_id: self.image_id,
+            view_ids: self.views.into_iter().map(|(id, _)| id).collect(),
+            framebuffer_ids: self.framebuffers.into_iter().map(|(id, _)| id).collect(),
+            images: images.into_boxed_slice(),
+        })
+    }
+}
+
+/// A collection of object sets for each image of a swapchain.
+///
+/// Every image set uses the present semaphore of its image as its semaphore.
+pub struct SwapchainObjectSet {
+    swapchain: Arc<SurfaceSwapchain>,
+    image_id: ImageId,
+    view_ids: Box<[ImageViewId]>,
+    framebuffer_ids: Box<[FramebufferId]>,
+    images: Box<[ObjectSet]>,
+}
+
+impl SwapchainObjectSet {
+    pub fn get_swapchain(&self) -> &Arc<SurfaceSwapchain> {
+        &self.swapchain
+    }
+
+    pub fn get_image_id(&self) -> ImageId {
+        self.image_id
+    }
+
+    /// Returns the ids of all image views in the order they were added to the builder.
+    pub fn get_image_view_ids(&self) -> &[ImageViewId] {
+        self.view_ids.as_ref()
+    }
+
+    /// Returns the ids of all framebuffers in the order they were added to the builder.
+    pub fn get_framebuffer_ids(&self) -> &[FramebufferId] {
+        self.framebuffer_ids.as_ref()
+    }
+
+    /// Returns the object set for the swapchain image with the specified index.
+    pub fn get_image_set(&self, image_index: u32) -> Option<&ObjectSet> {
+        self.images.get(image_index as usize)
+    }
+
+    /// Returns the object sets of all swapchain images ordered by image index.
+    pub fn get_image_sets(&self) -> &[ObjectSet] {
+        self.images.as_ref()
+    }
+}
+
+#[derive(Copy, Clone)]
+struct SwapchainImageViewInfo {
+    format: vk::Format,
+    subresource_range: ImageSubresourceRange,
+}
+
+struct SwapchainFramebufferInfo {
+    render_pass: vk::RenderPass,
+    attachments: Box<[ImageViewId]>,
+}
+
+struct SwapchainImageSet {
+    swapchain: Arc<SurfaceSwapchain>,
+    set_id: UUID,
+    image_index: usize,
+    image_id: ImageId,
+    image: vk::Image,
+    present_semaphore: Semaphore,
+    views: Box<[(ImageViewId, vk::ImageView, SwapchainImageViewInfo)]>,
+    framebuffers: Box<[(FramebufferId, vk::Framebuffer, vk::RenderPass, u32)]>,
+}
+
+impl SwapchainImageSet {
+    fn new(swapchain: Arc<SurfaceSwapchain>, image_index: usize, image: vk::Image, present_semaphore: Semaphore, image_id: ImageId, views: &[(ImageViewId, SwapchainImageViewInfo)], framebuffers: &[(FramebufferId, SwapchainFramebufferInfo)]) -> VkResult<Self> {
+        let device = swapchain.get_device();
+
+        let mut created: Vec<(ImageViewId, vk::ImageView, SwapchainImageViewInfo)> = Vec::with_capacity(views.len());
+        for (id, view) in views {
+            let info = vk::ImageViewCreateInfo::builder()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(view.format)
+                .components(vk::ComponentMapping {
+                    r: vk::ComponentSwizzle::IDENTITY,
+                    g: vk::ComponentSwizzle::IDENTITY,
+                    b: vk::ComponentSwizzle::IDENTITY,
+                    a: vk::ComponentSwizzle::IDENTITY
+                })
+                .subresource_range(view.subresource_range.as_vk_subresource_range());
+
+            match unsafe {
+                device.vk.create_image_view(&info, None)
+            } {
+                Ok(handle) => created.push((*id, handle, *view)),
+                Err(err) => {
+                    for (_, handle, _) in created {
+                        unsafe { device.vk.destroy_image_view(handle, None) };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        let size = swapchain.get_image_size();
+        let mut created_framebuffers: Vec<(FramebufferId, vk::Framebuffer, vk::RenderPass, u32)> = Vec::with_capacity(framebuffers.len());
+        for (id, framebuffer) in framebuffers {
+            let attachments: Vec<vk::ImageView> = framebuffer.attachments.iter().map(|attachment| {
+                created.iter().find(|(view_id, _, _)| view_id == attachment).unwrap().1
+            }).collect();
+
+            let info = vk::FramebufferCreateInfo::builder()
+                .render_pass(framebuffer.render_pass)
+                .attachments(&attachments)
+                .width(size[0])
+                .height(size[1])
+                .layers(1);
+
+            match unsafe {
+                device.vk.create_framebuffer(&info, None)
+            } {
+                Ok(handle) => created_framebuffers.push((*id, handle, framebuffer.render_pass, attachments.len() as u32)),
+                Err(err) => {
+                    for (_, handle, _, _) in created_framebuffers {
+                        unsafe { device.vk.destroy_framebuffer(handle, None) };
+                    }
+                    for (_, handle, _) in created {
+                        unsafe { device.vk.destroy_image_view(handle, None) };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(Self {
+            swapchain,
+            set_id: UUID::new(),
+            image_index,
+            image_id,
+            image,
+            present_semaphore,
+            views: created.into_boxed_slice(),
+            framebuffers: created_framebuffers.into_boxed_slice(),
+        })
+    }
+}
+
+impl ObjectSetProvider for SwapchainImageSet {
+    fn get_id(&self) -> UUID {
+        self.set_id
+    }
+
+    fn get_handle(&self, id: UUID) -> Option<u64> {
+        if id == self.image_id.as_uuid() {
+            return Some(self.image.as_raw());
+        }
+        if let Some((_, handle, _)) = self.views.iter().find(|(view_id, _, _)| view_id.as_uuid() == id) {
+            return Some(handle.as_raw());
+        }
+        self.framebuffers.iter().find(|(framebuffer_id, _, _, _)| framebuffer_id.as_uuid() == id).map(|(_, handle, _, _)| handle.as_raw())
+    }
+
+    fn get_semaphore(&self) -> Option<Semaphore> {
+        Some(self.present_semaphore)
+    }
+
+    fn for_each_object(&self, f: &mut dyn FnMut(UUID, ObjectKind)) {
+        f(self.image_id.as_uuid(), ObjectKind::Image);
+        for (id, _, _) in self.views.iter() {
+            f(id.as_uuid(), ObjectKind::ImageView);
+        }
+        for (id, _, _, _) in self.framebuffers.iter() {
+            f(id.as_uuid(), ObjectKind::Framebuffer);
+        }
+    }
+
+    fn object_count(&self) -> usize {
+        self.views.len() + self.framebuffers.len() + 1
+    }
+
+    fn description(&self, id: UUID) -> Option<ObjectDescription> {
+        if id == self.image_id.as_uuid() {
+            return Some(ObjectDescription::SwapchainImage {
+                size: self.swapchain.get_image_size(),
+                format: self.swapchain.get_image_format().format,
+                usage_flags: self.swapchain.get_image_usage(),
+            });
+        }
+        if let Some((_, _, info)) = self.views.iter().find(|(view_id, _, _)| view_id.as_uuid() == id) {
+            return Some(ObjectDescription::SwapchainImageView {
+                format: info.format,
+                subresource_range: info.subresource_range,
+            });
+        }
+        self.framebuffers.iter().find(|(framebuffer_id, _, _, _)| framebuffer_id.as_uuid() == id).map(|(_, _, render_pass, attachment_count)| {
+            ObjectDescription::SwapchainFramebuffer {
+                render_pass: *render_pass,
+                size: self.swapchain.get_image_size(),
+                attachment_count: *attachment_count,
+            }
+        })
+    }
+}
+
+impl Drop for SwapchainImageSet {
+    fn drop(&mut self) {
+        let device = self.swapchain.get_device();
+        // Framebuffers must be destroyed before the views they reference
+        for (_, framebuffer, _, _) in self.framebuffers.iter() {
+            unsafe {
+                device.vk.destroy_framebuffer(*framebuffer, None);
+            }
+        }
+        for (_, view, _) in self.views.iter() {
+            unsafe {
+                device.vk.destroy_image_view(*view, None);
+            }
+        }
+    }
+}
+
+impl Debug for SwapchainImageSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("SwapchainImageSet(Set: {:?}, Index: {})", self.set_id, self.image_index))
+    }
+}
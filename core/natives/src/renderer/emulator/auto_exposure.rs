@@ -0,0 +1,396 @@
+//! Automatic exposure driven by a luminance histogram of the pipeline output.
+//!
+//! If enabled the output records a compute pass building a histogram of the log2 luminance of
+//! the pipeline output before the color grading pass. Once the frame has completed execution the
+//! histogram is read back and the exposure of following frames adapts towards the exposure mapping
+//! the average luminance to [`AutoExposure::target_luminance`]. The exposure therefore always lags
+//! at least one frame behind the image it is applied to.
+
+use std::ffi::CStr;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use ash::vk;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use include_bytes_aligned::include_bytes_aligned;
+
+use crate::allocator::{Allocation, Allocator, HostAccess};
+use crate::device::device_utils::create_shader_from_bytes;
+
+use crate::prelude::*;
+
+/// The number of bins of the luminance histogram. Bin 0 counts all pixels darker than the
+/// histogram range.
+pub const HISTOGRAM_BIN_COUNT: usize = 256;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+/// The auto exposure configuration of the output.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AutoExposure {
+    pub enabled: bool,
+
+    /// The lower end of the log2 luminance range covered by the histogram. Average luminances
+    /// outside of the range are clamped which limits the maximum exposure.
+    pub min_log_luminance: f32,
+
+    /// The upper end of the log2 luminance range covered by the histogram. Limits the minimum
+    /// exposure.
+    pub max_log_luminance: f32,
+
+    /// The average luminance the exposure maps the image to.
+    pub target_luminance: f32,
+
+    /// The rate per second at which the exposure approaches its target. A value of 0 disables
+    /// adaptation after the first frame.
+    pub adaptation_speed: f32,
+}
+
+impl AutoExposure {
+    /// Returns the average log2 luminance of all pixels counted by the histogram ignoring pixels
+    /// darker than the histogram range. Returns [`None`] if no pixel is inside the range.
+    pub fn average_log_luminance(&self, histogram: &[u32]) -> Option<f32> {
+        let bin_range = (self.max_log_luminance - self.min_log_luminance) / ((HISTOGRAM_BIN_COUNT - 2) as f32);
+
+        let mut weighted_sum = 0f64;
+        let mut count = 0u64;
+        for (bin, bin_count) in histogram.iter().enumerate().skip(1) {
+            // The shader truncates so the center of a bin lies half a bin above its lower edge
+            let log_luminance = self.min_log_luminance + ((bin - 1) as f32 + 0.5f32) * bin_range;
+            weighted_sum += (log_luminance as f64) * (*bin_count as f64);
+            count += *bin_count as u64;
+        }
+
+        if count == 0 {
+            None
+        } else {
+            Some((weighted_sum / (count as f64)) as f32)
+        }
+    }
+
+    /// Returns the exposure mapping an image with the average log2 luminance to the target
+    /// luminance.
+    pub fn target_exposure(&self, average_log_luminance: f32) -> f32 {
+        let average_log_luminance = average_log_luminance.clamp(self.min_log_luminance, self.max_log_luminance);
+        self.target_luminance / average_log_luminance.exp2()
+    }
+
+    fn get_push_constants(&self, size: Vec2u32) -> HistogramPushConstants {
+        HistogramPushConstants {
+            min_log_luminance: self.min_log_luminance,
+            inv_log_luminance_range: 1f32 / (self.max_log_luminance - self.min_log_luminance),
+            size: [size[0], size[1]],
+        }
+    }
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_log_luminance: -4f32,
+            max_log_luminance: 1f32,
+            target_luminance: 0.18f32,
+            adaptation_speed: 1.5f32,
+        }
+    }
+}
+
+/// Moves the exposure from `current` towards `target` after `delta` seconds. The exposure
+/// approaches the target exponentially in log space so brightening and darkening take equally long.
+pub fn adapt_exposure(current: f32, target: f32, delta: f32, speed: f32) -> f32 {
+    let factor = 1f32 - (-delta.max(0f32) * speed.max(0f32)).exp();
+    let current_log = current.log2();
+    (current_log + (target.log2() - current_log) * factor).exp2()
+}
+
+/// The exposure shared between the frames of a renderer.
+#[derive(Debug)]
+pub(crate) struct ExposureAdaptation {
+    state: Mutex<AdaptationState>,
+}
+
+#[derive(Debug)]
+struct AdaptationState {
+    exposure: f32,
+    last_update: Option<Instant>,
+}
+
+impl ExposureAdaptation {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(AdaptationState {
+                exposure: 1f32,
+                last_update: None,
+            }),
+        }
+    }
+
+    pub(crate) fn get_exposure(&self) -> f32 {
+        self.state.lock().unwrap().exposure
+    }
+
+    /// Resets the exposure to 1. The next histogram sets the exposure directly to its target.
+    pub(crate) fn reset(&self) {
+        let mut guard = self.state.lock().unwrap();
+        guard.exposure = 1f32;
+        guard.last_update = None;
+    }
+
+    /// Adapts the exposure to the histogram of a completed frame.
+    pub(crate) fn update(&self, settings: &AutoExposure, histogram: &[u32]) {
+        let target = match settings.average_log_luminance(histogram) {
+            Some(average) => settings.target_exposure(average),
+            None => settings.target_exposure(settings.min_log_luminance),
+        };
+
+        let now = Instant::now();
+        let mut guard = self.state.lock().unwrap();
+        guard.exposure = match guard.last_update {
+            Some(last_update) => adapt_exposure(guard.exposure, target, (now - last_update).as_secs_f32(), settings.adaptation_speed),
+            None => target,
+        };
+        guard.last_update = Some(now);
+    }
+}
+
+/// A host visible buffer the histogram of one frame is written to.
+pub(crate) struct HistogramBuffer {
+    buffer: vk::Buffer,
+    allocation: Option<Allocation>,
+    mapped: NonNull<u8>,
+}
+
+impl HistogramBuffer {
+    /// Returns the histogram. Must only be called once the frame writing the buffer has
+    /// completed execution.
+    pub(crate) fn read(&self) -> &[u32] {
+        unsafe { std::slice::from_raw_parts(self.mapped.as_ptr() as *const u32, HISTOGRAM_BIN_COUNT) }
+    }
+}
+
+unsafe impl Send for HistogramBuffer { // Needed because of NonNull<u8>
+}
+
+/// A compute pass building the luminance histogram of an image.
+pub(crate) struct LuminanceHistogramPass {
+    device: Arc<DeviceFunctions>,
+    allocator: Arc<Allocator>,
+    shader: vk::ShaderModule,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    free_buffers: Mutex<Vec<HistogramBuffer>>,
+}
+
+impl LuminanceHistogramPass {
+    pub(crate) fn new(device: Arc<DeviceFunctions>, allocator: Arc<Allocator>) -> Self {
+        let shader = create_shader_from_bytes(&device, LUMINANCE_HISTOGRAM_COMPUTE_SHADER).unwrap();
+
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(false)
+            .compare_enable(false)
+            .unnormalized_coordinates(false);
+        let sampler = unsafe { device.vk.create_sampler(&sampler_info, None) }.unwrap();
+
+        let samplers = [sampler];
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .immutable_samplers(&samplers)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .build(),
+        ];
+        let set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
+            .bindings(&bindings);
+        let set_layout = unsafe { device.vk.create_descriptor_set_layout(&set_layout_info, None) }.unwrap();
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            offset: 0,
+            size: std::mem::size_of::<HistogramPushConstants>() as u32,
+        };
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(std::slice::from_ref(&set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe { device.vk.create_pipeline_layout(&pipeline_layout_info, None) }.unwrap();
+
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader)
+            .name(SHADER_ENTRY);
+        let info = vk::ComputePipelineCreateInfo::builder()
+            .stage(*stage)
+            .layout(pipeline_layout);
+        let pipeline = *unsafe {
+            device.vk.create_compute_pipelines(device.pipeline_cache, std::slice::from_ref(&info), None)
+        }.unwrap().get(0).unwrap();
+
+        Self {
+            device,
+            allocator,
+            shader,
+            sampler,
+            set_layout,
+            pipeline_layout,
+            pipeline,
+            free_buffers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns a buffer which can be passed to [`LuminanceHistogramPass::record`]. The buffer
+    /// should be returned with [`LuminanceHistogramPass::release_buffer`] once the frame using it
+    /// has completed execution.
+    pub(crate) fn acquire_buffer(&self) -> HistogramBuffer {
+        if let Some(buffer) = self.free_buffers.lock().unwrap().pop() {
+            return buffer;
+        }
+
+        let info = vk::BufferCreateInfo::builder()
+            .size((HISTOGRAM_BIN_COUNT * std::mem::size_of::<u32>()) as vk::DeviceSize)
+            .usage(vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let (buffer, allocation, mapped) = unsafe {
+            self.allocator.create_buffer(&info, HostAccess::Random, &format_args!("LuminanceHistogramBuffer"))
+        }.unwrap_or_else(|| {
+            log::error!("Failed to allocate luminance histogram buffer");
+            panic!()
+        });
+
+        HistogramBuffer {
+            buffer,
+            allocation: Some(allocation),
+            mapped: mapped.unwrap(),
+        }
+    }
+
+    pub(crate) fn release_buffer(&self, buffer: HistogramBuffer) {
+        self.free_buffers.lock().unwrap().push(buffer);
+    }
+
+    /// Records the histogram of `source_view` into the buffer followed by a barrier making the
+    /// result available to the host.
+    ///
+    /// The source image must be in the SHADER_READ_ONLY_OPTIMAL layout and any writes to it must
+    /// be visible to the compute shader stage.
+    pub(crate) fn record(&self, command_buffer: vk::CommandBuffer, source_view: vk::ImageView, source_size: Vec2u32, buffer: &HistogramBuffer, settings: &AutoExposure) {
+        let clear_barrier = [
+            vk::BufferMemoryBarrier2::builder()
+                .src_stage_mask(vk::PipelineStageFlags2::TRANSFER)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .dst_access_mask(vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE)
+                .buffer(buffer.buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .build()
+        ];
+        let clear_info = vk::DependencyInfo::builder()
+            .buffer_memory_barriers(&clear_barrier);
+
+        let host_barrier = [
+            vk::BufferMemoryBarrier2::builder()
+                .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .src_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::HOST)
+                .dst_access_mask(vk::AccessFlags2::HOST_READ)
+                .buffer(buffer.buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .build()
+        ];
+        let host_info = vk::DependencyInfo::builder()
+            .buffer_memory_barriers(&host_barrier);
+
+        let image_info = [
+            vk::DescriptorImageInfo {
+                sampler: vk::Sampler::null(),
+                image_view: source_view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            }
+        ];
+        let buffer_info = [
+            vk::DescriptorBufferInfo {
+                buffer: buffer.buffer,
+                offset: 0,
+                range: vk::WHOLE_SIZE
+            }
+        ];
+        let writes = [
+            vk::WriteDescriptorSet::builder()
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&image_info)
+                .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&buffer_info)
+                .build(),
+        ];
+
+        let push_constants = settings.get_push_constants(source_size);
+        let group_count_x = (source_size[0] + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+        let group_count_y = (source_size[1] + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE;
+
+        unsafe {
+            self.device.vk.cmd_fill_buffer(command_buffer, buffer.buffer, 0, vk::WHOLE_SIZE, 0);
+            self.device.cmd_pipeline_barrier2(command_buffer, &clear_info);
+
+            self.device.vk.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            self.device.push_descriptor_khr.cmd_push_descriptor_set(command_buffer, vk::PipelineBindPoint::COMPUTE, self.pipeline_layout, 0, &writes);
+            self.device.vk.cmd_push_constants(command_buffer, self.pipeline_layout, vk::ShaderStageFlags::COMPUTE, 0, bytes_of(&push_constants));
+            self.device.vk.cmd_dispatch(command_buffer, group_count_x, group_count_y, 1);
+
+            self.device.cmd_pipeline_barrier2(command_buffer, &host_info);
+        }
+    }
+}
+
+impl Drop for LuminanceHistogramPass {
+    fn drop(&mut self) {
+        unsafe {
+            for mut buffer in self.free_buffers.get_mut().unwrap().drain(..) {
+                self.allocator.destroy_buffer(buffer.buffer, buffer.allocation.take().unwrap());
+            }
+            self.device.vk.destroy_pipeline(self.pipeline, None);
+            self.device.vk.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.vk.destroy_descriptor_set_layout(self.set_layout, None);
+            self.device.vk.destroy_sampler(self.sampler, None);
+            self.device.vk.destroy_shader_module(self.shader, None);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct HistogramPushConstants {
+    min_log_luminance: f32,
+    inv_log_luminance_range: f32,
+    size: [u32; 2],
+}
+const_assert_eq!(std::mem::size_of::<HistogramPushConstants>(), 16);
+
+unsafe impl Zeroable for HistogramPushConstants {}
+unsafe impl Pod for HistogramPushConstants {}
+
+const SHADER_ENTRY: &'static CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") };
+static LUMINANCE_HISTOGRAM_COMPUTE_SHADER: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "utils/luminance_histogram_comp.spv"));
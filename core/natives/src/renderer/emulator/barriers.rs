@@ -0,0 +1,229 @@
+//! Batching and optimization of pipeline barriers.
+//!
+//! A [`BarrierBatch`] collects barriers which are allowed to execute at the same point of a command
+//! buffer. Before recording, barriers affecting the same resource range are merged, image barriers
+//! without a layout transition are converted into global memory barriers and if many buffer
+//! barriers exist they are converted into a single global memory barrier as well. When merging the
+//! stage and access masks are combined so the resulting barriers cover exactly the union of the
+//! original dependencies.
+
+use std::collections::HashMap;
+
+use ash::vk;
+
+use crate::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct BufferKey {
+    buffer: vk::Buffer,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    src_queue_family: u32,
+    dst_queue_family: u32,
+}
+
+impl BufferKey {
+    fn new(barrier: &vk::BufferMemoryBarrier2) -> Self {
+        Self {
+            buffer: barrier.buffer,
+            offset: barrier.offset,
+            size: barrier.size,
+            src_queue_family: barrier.src_queue_family_index,
+            dst_queue_family: barrier.dst_queue_family_index,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct ImageKey {
+    image: vk::Image,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    aspect_mask: vk::ImageAspectFlags,
+    base_mip_level: u32,
+    level_count: u32,
+    base_array_layer: u32,
+    layer_count: u32,
+    src_queue_family: u32,
+    dst_queue_family: u32,
+}
+
+impl ImageKey {
+    fn new(barrier: &vk::ImageMemoryBarrier2) -> Self {
+        let range = &barrier.subresource_range;
+        Self {
+            image: barrier.image,
+            old_layout: barrier.old_layout,
+            new_layout: barrier.new_layout,
+            aspect_mask: range.aspect_mask,
+            base_mip_level: range.base_mip_level,
+            level_count: range.level_count,
+            base_array_layer: range.base_array_layer,
+            layer_count: range.layer_count,
+            src_queue_family: barrier.src_queue_family_index,
+            dst_queue_family: barrier.dst_queue_family_index,
+        }
+    }
+}
+
+/// A set of barriers which are recorded together.
+pub(super) struct BarrierBatch {
+    memory: Option<vk::MemoryBarrier2>,
+    buffers: Vec<vk::BufferMemoryBarrier2>,
+    images: Vec<vk::ImageMemoryBarrier2>,
+}
+
+impl BarrierBatch {
+    /// If more buffer barriers than this exist they are converted into a single global barrier.
+    const GLOBAL_BUFFER_THRESHOLD: usize = 16;
+
+    // If we have too many barriers in a single command the driver may fail to record (Yes this limit has been hit at 4000 barriers during testing in minecraft)
+    const CHUNK_SIZE: usize = 256;
+
+    pub(super) fn new() -> Self {
+        Self {
+            memory: None,
+            buffers: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.buffers.is_empty() && self.images.is_empty()
+    }
+
+    pub(super) fn extend_buffers(&mut self, barriers: &[vk::BufferMemoryBarrier2]) {
+        self.buffers.extend_from_slice(barriers);
+    }
+
+    pub(super) fn extend_images(&mut self, barriers: &[vk::ImageMemoryBarrier2]) {
+        self.images.extend_from_slice(barriers);
+    }
+
+    /// Merges and converts barriers to reduce the number of barriers which need to be recorded.
+    fn optimize(&mut self) {
+        self.merge_buffers();
+        self.merge_images();
+
+        // Image barriers without a layout transition or ownership transfer only provide a memory
+        // dependency which a global barrier provides as well
+        let mut images = std::mem::take(&mut self.images);
+        images.retain(|barrier| {
+            if barrier.old_layout == barrier.new_layout && !Self::is_ownership_transfer(barrier.src_queue_family_index, barrier.dst_queue_family_index) {
+                self.add_global(barrier.src_stage_mask, barrier.src_access_mask, barrier.dst_stage_mask, barrier.dst_access_mask);
+                false
+            } else {
+                true
+            }
+        });
+        self.images = images;
+
+        if self.buffers.len() > Self::GLOBAL_BUFFER_THRESHOLD {
+            let mut buffers = std::mem::take(&mut self.buffers);
+            buffers.retain(|barrier| {
+                if Self::is_ownership_transfer(barrier.src_queue_family_index, barrier.dst_queue_family_index) {
+                    true
+                } else {
+                    self.add_global(barrier.src_stage_mask, barrier.src_access_mask, barrier.dst_stage_mask, barrier.dst_access_mask);
+                    false
+                }
+            });
+            self.buffers = buffers;
+        }
+    }
+
+    /// Optimizes and records all barriers into the command buffer and clears the batch.
+    pub(super) fn record(&mut self, device: &DeviceContext, cmd: vk::CommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        self.optimize();
+
+        let memory = self.memory.take();
+        let memory = match &memory {
+            Some(memory) => std::slice::from_ref(memory),
+            None => &[],
+        };
+        let buffers = self.buffers.as_slice();
+        let images = self.images.as_slice();
+
+        let chunk_count = std::cmp::max((buffers.len() / Self::CHUNK_SIZE) + 1, (images.len() / Self::CHUNK_SIZE) + 1);
+        for chunk in 0..chunk_count {
+            let min = chunk * Self::CHUNK_SIZE;
+            let max = min + Self::CHUNK_SIZE;
+            let mut info = vk::DependencyInfo::builder();
+            if chunk == 0 {
+                info = info.memory_barriers(memory);
+            }
+            if min < buffers.len() {
+                let max = std::cmp::min(max, buffers.len());
+                info = info.buffer_memory_barriers(&buffers[min..max]);
+            }
+            if min < images.len() {
+                let max = std::cmp::min(max, images.len());
+                info = info.image_memory_barriers(&images[min..max]);
+            }
+
+            unsafe {
+                device.cmd_pipeline_barrier2(cmd, &info);
+            }
+        }
+
+        self.buffers.clear();
+        self.images.clear();
+    }
+
+    fn merge_buffers(&mut self) {
+        let mut indices = HashMap::with_capacity(self.buffers.len());
+        let mut merged: Vec<vk::BufferMemoryBarrier2> = Vec::with_capacity(self.buffers.len());
+        for barrier in self.buffers.drain(..) {
+            match indices.get(&BufferKey::new(&barrier)) {
+                Some(index) => {
+                    let dst: &mut vk::BufferMemoryBarrier2 = &mut merged[*index];
+                    dst.src_stage_mask |= barrier.src_stage_mask;
+                    dst.src_access_mask |= barrier.src_access_mask;
+                    dst.dst_stage_mask |= barrier.dst_stage_mask;
+                    dst.dst_access_mask |= barrier.dst_access_mask;
+                }
+                None => {
+                    indices.insert(BufferKey::new(&barrier), merged.len());
+                    merged.push(barrier);
+                }
+            }
+        }
+        self.buffers = merged;
+    }
+
+    fn merge_images(&mut self) {
+        let mut indices = HashMap::with_capacity(self.images.len());
+        let mut merged: Vec<vk::ImageMemoryBarrier2> = Vec::with_capacity(self.images.len());
+        for barrier in self.images.drain(..) {
+            match indices.get(&ImageKey::new(&barrier)) {
+                Some(index) => {
+                    let dst: &mut vk::ImageMemoryBarrier2 = &mut merged[*index];
+                    dst.src_stage_mask |= barrier.src_stage_mask;
+                    dst.src_access_mask |= barrier.src_access_mask;
+                    dst.dst_stage_mask |= barrier.dst_stage_mask;
+                    dst.dst_access_mask |= barrier.dst_access_mask;
+                }
+                None => {
+                    indices.insert(ImageKey::new(&barrier), merged.len());
+                    merged.push(barrier);
+                }
+            }
+        }
+        self.images = merged;
+    }
+
+    fn add_global(&mut self, src_stage_mask: vk::PipelineStageFlags2, src_access_mask: vk::AccessFlags2, dst_stage_mask: vk::PipelineStageFlags2, dst_access_mask: vk::AccessFlags2) {
+        let memory = self.memory.get_or_insert_with(vk::MemoryBarrier2::default);
+        memory.src_stage_mask |= src_stage_mask;
+        memory.src_access_mask |= src_access_mask;
+        memory.dst_stage_mask |= dst_stage_mask;
+        memory.dst_access_mask |= dst_access_mask;
+    }
+
+    fn is_ownership_transfer(src_queue_family: u32, dst_queue_family: u32) -> bool {
+        src_queue_family != dst_queue_family
+    }
+}
@@ -0,0 +1,167 @@
+//! Animated emissive columns like beacon and end gateway beams.
+//!
+//! Vanilla rebuilds the quads of every beam each frame to apply the rotation and texture scroll.
+//! Instead all beams of a shader share a single static unit column mesh created using
+//! [`BeamMesh`] and each beam is described by a small [`BeamColumn`]. The position, size and
+//! rotation of a beam are passed to the shader through the model view matrix, the texture scroll
+//! through the texture matrix and the color through the color modulator (see
+//! [`PassRecorder::draw_beams`](crate::renderer::emulator::PassRecorder::draw_beams)).
+//!
+//! The unit column is a square prism with its bottom center at the origin, a half width of 1 and a
+//! height of 1. The texture coordinate v runs from 1 at the bottom to 0 at the top and is scaled by
+//! the height of the beam so the texture repeats once per block.
+//!
+//! The vertex format of the shader used to draw the beams must store the position as
+//! `R32G32B32_SFLOAT` and the first texture coordinate as `R32G32_SFLOAT`. If the format has a
+//! color it must be stored as `R8G8B8A8_UNORM`. See [`BeamMesh::supports_format`].
+
+use ash::vk;
+
+use crate::renderer::emulator::mc_shaders::VertexFormat;
+use crate::renderer::emulator::MeshData;
+
+use crate::prelude::*;
+
+/// A single animated beam.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BeamColumn {
+    /// The bottom center of the beam in the space of the view matrices of the pass.
+    pub position: Vec3f32,
+
+    /// The height of the beam in blocks.
+    pub height: f32,
+
+    /// The half width of the beam in blocks.
+    pub radius: f32,
+
+    /// The rotation around the vertical axis in radians.
+    pub rotation: f32,
+
+    /// The offset added to the texture coordinate v.
+    pub scroll: f32,
+
+    /// The rgba color multiplied with the beam.
+    pub color: Vec4f32,
+}
+
+impl BeamColumn {
+    /// Sets the rotation and scroll of the beam from an animation time. `rotation_speed` is given
+    /// in radians and `scroll_speed` in texture repeats per unit of time.
+    pub fn animate(&mut self, time: f32, rotation_speed: f32, scroll_speed: f32) {
+        self.rotation = (time * rotation_speed).rem_euclid(std::f32::consts::TAU);
+        self.scroll = -(time * scroll_speed).rem_euclid(1f32);
+    }
+
+    /// Returns the matrix transforming the unit column into the beam.
+    pub fn get_model_matrix(&self) -> Mat4f32 {
+        let (sin, cos) = self.rotation.sin_cos();
+        let r = self.radius;
+        let p = self.position;
+        Mat4f32::new(
+            cos * r, 0f32, sin * r, p[0],
+            0f32, self.height, 0f32, p[1],
+            -sin * r, 0f32, cos * r, p[2],
+            0f32, 0f32, 0f32, 1f32
+        )
+    }
+
+    /// Returns the texture matrix applying the height and scroll of the beam.
+    pub fn get_texture_matrix(&self) -> Mat4f32 {
+        Mat4f32::new(
+            1f32, 0f32, 0f32, 0f32,
+            0f32, self.height, 0f32, self.scroll,
+            0f32, 0f32, 1f32, 0f32,
+            0f32, 0f32, 0f32, 1f32
+        )
+    }
+}
+
+/// The static unit column mesh shared by all beams drawn with shaders of the same vertex format.
+/// The mesh is a triangle list using 32bit indices. Vertex colors are white.
+pub struct BeamMesh {
+    format: VertexFormat,
+    vertex_data: Vec<u8>,
+    index_data: Vec<u8>,
+}
+
+impl BeamMesh {
+    /// The number of sides of the column.
+    pub const SIDE_COUNT: u32 = 4;
+
+    /// Creates the unit column mesh for a vertex format.
+    ///
+    /// Panics if the vertex format is not supported. See [`BeamMesh::supports_format`].
+    pub fn new(format: &VertexFormat) -> Self {
+        if !Self::supports_format(format) {
+            log::error!("Vertex format {:?} cannot be used to draw beams", format);
+            panic!();
+        }
+
+        let mut mesh = Self {
+            format: *format,
+            vertex_data: Vec::new(),
+            index_data: Vec::new(),
+        };
+
+        const CORNERS: [[f32; 2]; 4] = [[-1f32, -1f32], [-1f32, 1f32], [1f32, 1f32], [1f32, -1f32]];
+        for side in 0..Self::SIDE_COUNT {
+            let [x0, z0] = CORNERS[side as usize];
+            let [x1, z1] = CORNERS[((side + 1) % Self::SIDE_COUNT) as usize];
+
+            let base = side * 4;
+            mesh.push_vertex([x0, 0f32, z0], [0f32, 1f32]);
+            mesh.push_vertex([x1, 0f32, z1], [1f32, 1f32]);
+            mesh.push_vertex([x1, 1f32, z1], [1f32, 0f32]);
+            mesh.push_vertex([x0, 1f32, z0], [0f32, 0f32]);
+            for index in [0, 1, 2, 2, 3, 0] {
+                mesh.index_data.extend_from_slice(&(base + index).to_ne_bytes());
+            }
+        }
+
+        mesh
+    }
+
+    /// Returns true if the vertex format stores the position, texture coordinate and color in a
+    /// format which can be written by the mesh.
+    pub fn supports_format(format: &VertexFormat) -> bool {
+        if format.position_quantization.is_some() || format.position.format != vk::Format::R32G32B32_SFLOAT {
+            return false;
+        }
+        if !matches!(&format.uv0, Some(uv0) if uv0.format == vk::Format::R32G32_SFLOAT) {
+            return false;
+        }
+        format.color.as_ref().map_or(true, |color| color.format == vk::Format::R8G8B8A8_UNORM)
+    }
+
+    pub fn as_mesh_data(&self) -> MeshData {
+        MeshData {
+            vertex_data: &self.vertex_data,
+            index_data: &self.index_data,
+            vertex_stride: self.format.stride,
+            index_count: Self::SIDE_COUNT * 6,
+            index_type: vk::IndexType::UINT32,
+            primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        }
+    }
+
+    fn push_vertex(&mut self, position: [f32; 3], uv: [f32; 2]) {
+        let base = self.vertex_data.len();
+        self.vertex_data.resize(base + (self.format.stride as usize), 0u8);
+        let vertex = &mut self.vertex_data[base..];
+
+        let offset = self.format.position.offset as usize;
+        for (i, value) in position.iter().enumerate() {
+            vertex[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        let offset = self.format.uv0.as_ref().unwrap().offset as usize;
+        for (i, value) in uv.iter().enumerate() {
+            vertex[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        if let Some(color) = &self.format.color {
+            let offset = color.offset as usize;
+            vertex[offset..(offset + 4)].copy_from_slice(&[255u8; 4]);
+        }
+    }
+}
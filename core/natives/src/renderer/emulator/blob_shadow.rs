@@ -0,0 +1,236 @@
+//! Cheap round shadows below entities.
+//!
+//! Vanilla draws the shadow of every entity as a set of textured immediate quads projected onto the
+//! surrounding blocks. Instead the host passes one [`BlobShadow`] per entity and a
+//! [`BlobShadowBatch`] generates a soft disk for each of them which is drawn with a single small
+//! draw (see [`PassRecorder::draw_blob_shadows`](crate::renderer::emulator::PassRecorder::draw_blob_shadows)).
+//!
+//! The disks lie flat on the ground and are raised by [`BlobShadowConfig::depth_bias`] to avoid
+//! z-fighting. The softness of the edge is stored in the vertex alpha so the shader only needs to
+//! output the vertex color. Shadows fade out as the entity moves away from the ground and as the
+//! shadow moves away from the camera.
+//!
+//! The vertex format of the shader used to draw the shadows must store the position as
+//! `R32G32B32_SFLOAT` and the color as `R8G8B8A8_UNORM`. See [`BlobShadowBatch::supports_format`].
+
+use ash::vk;
+
+use crate::renderer::emulator::mc_shaders::VertexFormat;
+use crate::renderer::emulator::MeshData;
+
+use crate::prelude::*;
+
+/// The number of segments of the generated disks.
+pub const BLOB_SHADOW_SEGMENTS: u32 = 12;
+
+/// The number of vertices generated for each visible shadow.
+pub const BLOB_SHADOW_VERTEX_COUNT: u32 = 1 + 2 * BLOB_SHADOW_SEGMENTS;
+
+/// The number of indices generated for each visible shadow.
+pub const BLOB_SHADOW_INDEX_COUNT: u32 = 9 * BLOB_SHADOW_SEGMENTS;
+
+/// The shadow of a single entity.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlobShadow {
+    /// The point on the ground below the entity.
+    pub ground_position: Vec3f32,
+
+    /// The radius of the shadow in blocks.
+    pub radius: f32,
+
+    /// The opacity of the shadow in the range [0, 1] before any fading is applied.
+    pub strength: f32,
+
+    /// The distance between the entity and the ground in blocks.
+    pub height: f32,
+}
+
+/// Settings shared by all shadows of a batch.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlobShadowConfig {
+    /// The position of the camera in the same space as the shadow positions. Hosts using camera
+    /// relative positions leave this at zero.
+    pub camera_position: Vec3f32,
+
+    /// The rgb color of the shadows.
+    pub color: [u8; 3],
+
+    /// The distance in blocks the disks are raised above the ground.
+    pub depth_bias: f32,
+
+    /// The fraction of the radius over which the opacity falls off to 0 at the edge of the disk.
+    pub softness: f32,
+
+    /// The height above the ground at which shadows are fully faded out.
+    pub max_height: f32,
+
+    /// The distance from the camera at which shadows start to fade and are fully faded out.
+    pub fade_start: f32,
+    pub fade_end: f32,
+}
+
+impl BlobShadowConfig {
+    pub fn new() -> Self {
+        Self {
+            camera_position: Vec3f32::zeros(),
+            color: [0, 0, 0],
+            depth_bias: 0.015625f32,
+            softness: 0.5f32,
+            max_height: 4f32,
+            fade_start: 24f32,
+            fade_end: 32f32,
+        }
+    }
+
+    /// Returns the opacity of a shadow in the range [0, 1] after applying the height and distance
+    /// fade.
+    pub fn get_opacity(&self, shadow: &BlobShadow) -> f32 {
+        if !(shadow.radius > 0f32) || !shadow.strength.is_finite() {
+            return 0f32;
+        }
+
+        let height_fade = if self.max_height > 0f32 {
+            1f32 - (shadow.height.max(0f32) / self.max_height)
+        } else {
+            1f32
+        };
+
+        let distance = (shadow.ground_position - self.camera_position).norm();
+        let distance_fade = if self.fade_end > self.fade_start {
+            1f32 - ((distance - self.fade_start) / (self.fade_end - self.fade_start))
+        } else if distance > self.fade_end {
+            0f32
+        } else {
+            1f32
+        };
+
+        (shadow.strength * height_fade.clamp(0f32, 1f32) * distance_fade.clamp(0f32, 1f32)).clamp(0f32, 1f32)
+    }
+}
+
+impl Default for BlobShadowConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates the disks of [`BlobShadow`]s as a triangle list mesh using 32bit indices.
+pub struct BlobShadowBatch {
+    format: VertexFormat,
+    config: BlobShadowConfig,
+    vertex_data: Vec<u8>,
+    index_data: Vec<u8>,
+    vertex_count: u32,
+    shadow_count: u32,
+}
+
+impl BlobShadowBatch {
+    /// Creates a new batch for a vertex format.
+    ///
+    /// Panics if the vertex format is not supported. See [`BlobShadowBatch::supports_format`].
+    pub fn new(format: &VertexFormat, config: &BlobShadowConfig) -> Self {
+        if !Self::supports_format(format) {
+            log::error!("Vertex format {:?} cannot be used to draw blob shadows", format);
+            panic!();
+        }
+
+        Self {
+            format: *format,
+            config: *config,
+            vertex_data: Vec::new(),
+            index_data: Vec::new(),
+            vertex_count: 0,
+            shadow_count: 0,
+        }
+    }
+
+    /// Returns true if the vertex format stores the position and color in a format which can be
+    /// written by the batch.
+    pub fn supports_format(format: &VertexFormat) -> bool {
+        if format.position_quantization.is_some() || format.position.format != vk::Format::R32G32B32_SFLOAT {
+            return false;
+        }
+        matches!(&format.color, Some(color) if color.format == vk::Format::R8G8B8A8_UNORM)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shadow_count == 0
+    }
+
+    /// Returns the number of shadows which have generated geometry so far.
+    pub fn get_shadow_count(&self) -> u32 {
+        self.shadow_count
+    }
+
+    /// Adds the disk of a shadow. Shadows which are fully faded out do not generate any geometry
+    /// and return false.
+    pub fn push_shadow(&mut self, shadow: &BlobShadow) -> bool {
+        let opacity = self.config.get_opacity(shadow);
+        let alpha = (opacity * 255f32).round() as u8;
+        if alpha == 0 {
+            return false;
+        }
+
+        let [r, g, b] = self.config.color;
+        let center = shadow.ground_position + Vec3f32::new(0f32, self.config.depth_bias, 0f32);
+        let inner_radius = shadow.radius * (1f32 - self.config.softness.clamp(0f32, 1f32));
+
+        let base = self.vertex_count;
+        self.push_vertex(center, [r, g, b, alpha]);
+        for segment in 0..BLOB_SHADOW_SEGMENTS {
+            let angle = (segment as f32) * std::f32::consts::TAU / (BLOB_SHADOW_SEGMENTS as f32);
+            let direction = Vec3f32::new(angle.cos(), 0f32, angle.sin());
+            self.push_vertex(center + direction * inner_radius, [r, g, b, alpha]);
+            self.push_vertex(center + direction * shadow.radius, [r, g, b, 0]);
+        }
+
+        for segment in 0..BLOB_SHADOW_SEGMENTS {
+            let next = (segment + 1) % BLOB_SHADOW_SEGMENTS;
+            let inner = base + 1 + segment * 2;
+            let outer = inner + 1;
+            let next_inner = base + 1 + next * 2;
+            let next_outer = next_inner + 1;
+
+            self.push_indices(&[base, next_inner, inner]);
+            self.push_indices(&[inner, next_inner, next_outer]);
+            self.push_indices(&[inner, next_outer, outer]);
+        }
+
+        self.shadow_count += 1;
+        true
+    }
+
+    /// Returns the mesh containing all shadows pushed so far.
+    pub fn as_mesh_data(&self) -> MeshData {
+        MeshData {
+            vertex_data: &self.vertex_data,
+            index_data: &self.index_data,
+            vertex_stride: self.format.stride,
+            index_count: self.shadow_count * BLOB_SHADOW_INDEX_COUNT,
+            index_type: vk::IndexType::UINT32,
+            primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        }
+    }
+
+    fn push_vertex(&mut self, position: Vec3f32, color: [u8; 4]) {
+        let base = self.vertex_data.len();
+        self.vertex_data.resize(base + (self.format.stride as usize), 0u8);
+        let vertex = &mut self.vertex_data[base..];
+
+        let offset = self.format.position.offset as usize;
+        for i in 0..3 {
+            vertex[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&position[i].to_ne_bytes());
+        }
+
+        let offset = self.format.color.as_ref().unwrap().offset as usize;
+        vertex[offset..(offset + 4)].copy_from_slice(&color);
+
+        self.vertex_count += 1;
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) {
+        for index in indices {
+            self.index_data.extend_from_slice(&index.to_ne_bytes());
+        }
+    }
+}
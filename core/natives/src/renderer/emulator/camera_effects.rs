@@ -0,0 +1,124 @@
+//! View bobbing and screen shake applied by the renderer.
+//!
+//! Vanilla applies these effects by modifying the projection matrix while rendering the world.
+//! Hosts applying them themselves must make sure every pass of a frame (and the shadow cascades
+//! computed from the camera) uses the same modified matrices, otherwise the shadows and overlays
+//! drift relative to the world while the camera shakes. Instead the host sets the effect
+//! parameters once per frame using
+//! [`EmulatorRenderer::set_camera_effects`](crate::renderer::emulator::EmulatorRenderer::set_camera_effects)
+//! and keeps passing the unmodified base matrices. Passes with camera effects enabled (see
+//! [`PassRecorder::set_camera_effects_enabled`](crate::renderer::emulator::PassRecorder::set_camera_effects_enabled))
+//! apply the effects to every projection matrix uniform and to their view matrices. Their shadow
+//! cascades are fitted to the camera moved by [`CameraEffects::apply_to_frustum`]. Hosts computing
+//! other views from the camera should derive them from
+//! [`PassRecorder::apply_camera_effects_to_view`](crate::renderer::emulator::PassRecorder::apply_camera_effects_to_view).
+//!
+//! All effects are transforms in view space applied between the model view and projection matrix.
+//! The nausea and portal distortion does not move the camera and is applied by the post-process
+//! stage instead, see [`ScreenDistortion`](crate::renderer::emulator::post_chain::ScreenDistortion).
+
+use crate::renderer::emulator::shadow::CameraFrustum;
+use crate::renderer::emulator::unproject::ViewMatrices;
+
+use crate::prelude::*;
+
+/// The camera sway while walking.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ViewBob {
+    /// The walk cycle in radians. Vanilla uses the negated walk distance multiplied by pi.
+    pub phase: f32,
+
+    /// The strength of the bob. Vanilla interpolates the bob of the player which is at most 0.1.
+    pub amplitude: f32,
+}
+
+impl ViewBob {
+    pub fn get_matrix(&self) -> Mat4f32 {
+        let sin = self.phase.sin();
+        let translation = Vec3f32::new(sin * self.amplitude * 0.5f32, -(self.phase.cos() * self.amplitude).abs(), 0f32);
+        let roll = (sin * self.amplitude * 3f32).to_radians();
+        let pitch = ((self.phase - 0.2f32).cos() * self.amplitude).abs() * 5f32.to_radians();
+
+        Mat4f32::new_translation(&translation)
+            * Mat4f32::from_axis_angle(&Vec3f32::z_axis(), roll)
+            * Mat4f32::from_axis_angle(&Vec3f32::x_axis(), pitch)
+    }
+}
+
+/// Rolls the camera away from a direction, for example when the player is hurt.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScreenShake {
+    /// The roll in radians.
+    pub intensity: f32,
+
+    /// The horizontal direction in radians the camera rolls away from.
+    pub direction: f32,
+}
+
+impl ScreenShake {
+    /// The maximum roll in radians of the vanilla hurt effect.
+    pub const HURT_ROLL: f32 = 14f32 * (std::f32::consts::PI / 180f32);
+
+    /// Creates the vanilla hurt effect. `remaining` is the interpolated remaining hurt time and
+    /// `duration` the total hurt time in ticks. `direction` is the hurt direction in degrees.
+    pub fn from_hurt(remaining: f32, duration: f32, direction: f32) -> Self {
+        let progress = (remaining / duration.max(f32::EPSILON)).clamp(0f32, 1f32);
+        Self {
+            intensity: (progress.powi(4) * std::f32::consts::PI).sin() * Self::HURT_ROLL,
+            direction: direction.to_radians(),
+        }
+    }
+
+    pub fn get_matrix(&self) -> Mat4f32 {
+        Mat4f32::from_axis_angle(&Vec3f32::y_axis(), -self.direction)
+            * Mat4f32::from_axis_angle(&Vec3f32::z_axis(), -self.intensity)
+            * Mat4f32::from_axis_angle(&Vec3f32::y_axis(), self.direction)
+    }
+}
+
+/// The camera effects of a frame. See the [module docs](self).
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct CameraEffects {
+    pub bob: Option<ViewBob>,
+    pub shake: Option<ScreenShake>,
+}
+
+impl CameraEffects {
+    pub fn is_empty(&self) -> bool {
+        self.bob.is_none() && self.shake.is_none()
+    }
+
+    /// Returns the view space transform of all effects. Multiplied onto the right side of the
+    /// projection matrix.
+    pub fn get_matrix(&self) -> Mat4f32 {
+        let shake = self.shake.map_or_else(Mat4f32::identity, |shake| shake.get_matrix());
+        let bob = self.bob.map_or_else(Mat4f32::identity, |bob| bob.get_matrix());
+        shake * bob
+    }
+
+    /// Applies the effects to a projection matrix.
+    pub fn apply_to_projection(&self, projection: &Mat4f32) -> Mat4f32 {
+        projection * self.get_matrix()
+    }
+
+    /// Applies the effects to camera matrices.
+    pub fn apply_to_view_matrices(&self, matrices: &ViewMatrices) -> ViewMatrices {
+        ViewMatrices {
+            model_view: matrices.model_view,
+            projection: self.apply_to_projection(&matrices.projection),
+        }
+    }
+
+    /// Applies the effects to a world to view matrix.
+    pub fn apply_to_view(&self, view: &Mat4f32) -> Mat4f32 {
+        self.get_matrix() * view
+    }
+
+    /// Applies the effects to a camera frustum so shadow cascades cover the moved camera.
+    pub fn apply_to_frustum(&self, frustum: &CameraFrustum) -> CameraFrustum {
+        CameraFrustum {
+            view: self.apply_to_view(&frustum.view),
+            ..*frustum
+        }
+    }
+}
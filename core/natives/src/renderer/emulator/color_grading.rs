@@ -0,0 +1,495 @@
+//! Color grading applied when copying the output of a pipeline to its final destination.
+//!
+//! Grading consists of a color matrix (used by the built-in presets), per channel lift, gamma and
+//! gain curves and an optional 3D LUT supplied by the host as a [`GlobalImage`]. The same pass
+//! applies the exposure computed by [`crate::renderer::emulator::auto_exposure`] and the
+//! [`ScreenDistortion`] of the post-process stage.
+
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use ash::vk;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use include_bytes_aligned::include_bytes_aligned;
+
+use crate::device::device_utils::{create_shader_from_bytes, OutputRotation, RotationSpecialization};
+use crate::renderer::emulator::GlobalImage;
+use crate::renderer::emulator::post_chain::ScreenDistortion;
+use crate::renderer::emulator::auto_exposure::{AutoExposure, ExposureAdaptation};
+
+use crate::prelude::*;
+
+/// The rows of an affine color transform. The 4th column is a constant offset.
+pub type ColorMatrix = [[f32; 4]; 3];
+
+pub const IDENTITY_COLOR_MATRIX: ColorMatrix = [
+    [1f32, 0f32, 0f32, 0f32],
+    [0f32, 1f32, 0f32, 0f32],
+    [0f32, 0f32, 1f32, 0f32],
+];
+
+/// Returns the matrix applying `first` and then `second`.
+pub fn compose_color_matrices(first: &ColorMatrix, second: &ColorMatrix) -> ColorMatrix {
+    let mut result = [[0f32; 4]; 3];
+    for row in 0..3 {
+        for column in 0..4 {
+            let mut value = if column == 3 { second[row][3] } else { 0f32 };
+            for k in 0..3 {
+                value += second[row][k] * first[k][column];
+            }
+            result[row][column] = value;
+        }
+    }
+    result
+}
+
+/// Built-in color transformations.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum ColorGradingPreset {
+    None = 0,
+    Grayscale = 1,
+    Sepia = 2,
+    /// A bright green tinted monochrome image.
+    NightVision = 3,
+}
+
+impl ColorGradingPreset {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::None),
+            1 => Some(Self::Grayscale),
+            2 => Some(Self::Sepia),
+            3 => Some(Self::NightVision),
+            _ => None,
+        }
+    }
+
+    pub fn get_matrix(&self) -> ColorMatrix {
+        match self {
+            Self::None => IDENTITY_COLOR_MATRIX,
+            Self::Grayscale => [
+                [0.2126f32, 0.7152f32, 0.0722f32, 0f32],
+                [0.2126f32, 0.7152f32, 0.0722f32, 0f32],
+                [0.2126f32, 0.7152f32, 0.0722f32, 0f32],
+            ],
+            Self::Sepia => [
+                [0.393f32, 0.769f32, 0.189f32, 0f32],
+                [0.349f32, 0.686f32, 0.168f32, 0f32],
+                [0.272f32, 0.534f32, 0.131f32, 0f32],
+            ],
+            Self::NightVision => [
+                [0.1f32, 0.3f32, 0.05f32, 0.02f32],
+                [0.4f32, 1.2f32, 0.2f32, 0.05f32],
+                [0.1f32, 0.3f32, 0.05f32, 0.02f32],
+            ],
+        }
+    }
+}
+
+/// The color grading configuration of the output.
+#[derive(Clone, Debug)]
+pub struct ColorGrading {
+    pub preset: ColorGradingPreset,
+
+    /// Raises the dark end of each channel.
+    pub lift: Vec3f32,
+
+    /// Applied as `pow(color, 1 / gamma)` per channel.
+    pub gamma: Vec3f32,
+
+    /// Scales the bright end of each channel.
+    pub gain: Vec3f32,
+
+    /// A 3D LUT stored as a strip of `size * size` by `size` texels where each square slice holds
+    /// one blue value. The LUT maps sRGB encoded colors.
+    pub lut: Option<(Arc<GlobalImage>, u32)>,
+
+    /// How strongly the LUT is applied in the range `[0, 1]`.
+    pub lut_strength: f32,
+
+    /// Scales the image before the preset matrix based on its average luminance.
+    pub auto_exposure: AutoExposure,
+}
+
+impl ColorGrading {
+    /// Returns true if applying this grading does not change the image.
+    pub fn is_identity(&self) -> bool {
+        self.preset == ColorGradingPreset::None &&
+            self.lift == Vec3f32::zeros() &&
+            self.gamma == Vec3f32::from_element(1f32) &&
+            self.gain == Vec3f32::from_element(1f32) &&
+            (self.lut.is_none() || self.lut_strength <= 0f32) &&
+            !self.auto_exposure.enabled
+    }
+
+    /// Creates the state needed to record the grading. The post matrix is applied after the
+    /// preset matrix. Returns [`None`] if the grading does not change the image and the
+    /// distortion is inactive.
+    ///
+    /// If auto exposure is disabled the adaptation is reset so enabling it again starts from the
+    /// target exposure of the first frame.
+    pub(crate) fn make_state(&self, post_matrix: Option<&ColorMatrix>, distortion: &ScreenDistortion, adaptation: &Arc<ExposureAdaptation>) -> Option<ColorGradingState> {
+        let exposure = if self.auto_exposure.enabled {
+            adaptation.get_exposure()
+        } else {
+            adaptation.reset();
+            1f32
+        };
+
+        if self.is_identity() && post_matrix.is_none() && !distortion.is_active() {
+            return None;
+        }
+
+        let matrix = match post_matrix {
+            Some(post_matrix) => compose_color_matrices(&self.preset.get_matrix(), post_matrix),
+            None => self.preset.get_matrix(),
+        };
+        let (lut_view, lut_size, lut_strength) = match &self.lut {
+            Some((image, size)) if self.lut_strength > 0f32 => (Some(image.get_sampler_view()), *size as f32, self.lut_strength.min(1f32)),
+            _ => (None, 1f32, 0f32),
+        };
+
+        Some(ColorGradingState {
+            push_constants: GradePushConstants {
+                matrix_r: matrix[0],
+                matrix_g: matrix[1],
+                matrix_b: matrix[2],
+                lift: self.lift.push(0f32).into(),
+                gamma: self.gamma.push(1f32).into(),
+                gain: self.gain.push(1f32).into(),
+                lut_size,
+                lut_strength,
+                exposure,
+                _padding: 0f32,
+                distortion: [distortion.get_amplitude(), distortion.get_phase(), ScreenDistortion::FREQUENCY, 0f32],
+            },
+            lut_view,
+            auto_exposure: Some((self.auto_exposure, adaptation.clone())).filter(|(settings, _)| settings.enabled),
+        })
+    }
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            preset: ColorGradingPreset::None,
+            lift: Vec3f32::zeros(),
+            gamma: Vec3f32::from_element(1f32),
+            gain: Vec3f32::from_element(1f32),
+            lut: None,
+            lut_strength: 0f32,
+            auto_exposure: AutoExposure::default(),
+        }
+    }
+}
+
+/// A snapshot of a [`ColorGrading`] used to record one frame. Any LUT image must be kept alive by
+/// the pass using it.
+#[derive(Clone, Debug)]
+pub(crate) struct ColorGradingState {
+    push_constants: GradePushConstants,
+    lut_view: Option<vk::ImageView>,
+    auto_exposure: Option<(AutoExposure, Arc<ExposureAdaptation>)>,
+}
+
+impl ColorGradingState {
+    /// Returns the auto exposure settings and the adaptation the histogram of the frame must be
+    /// passed to if auto exposure is enabled.
+    pub(crate) fn get_auto_exposure(&self) -> Option<&(AutoExposure, Arc<ExposureAdaptation>)> {
+        self.auto_exposure.as_ref()
+    }
+}
+
+/// A full screen pass applying a [`ColorGradingState`]. Its render pass is compatible with
+/// framebuffers created for a blit pass of the same format.
+pub(crate) struct ColorGradingPass {
+    device: Arc<DeviceFunctions>,
+    vertex_shader: vk::ShaderModule,
+    fragment_shader: vk::ShaderModule,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+}
+
+impl ColorGradingPass {
+    pub(crate) fn new(device: Arc<DeviceFunctions>, dst_format: vk::Format, load_op: vk::AttachmentLoadOp, initial_layout: vk::ImageLayout, final_layout: vk::ImageLayout, rotation: OutputRotation) -> Self {
+        let vertex_shader = create_shader_from_bytes(&device, FULL_SCREEN_QUAD_VERTEX_SHADER).unwrap();
+        let fragment_shader = create_shader_from_bytes(&device, COLOR_GRADE_FRAGMENT_SHADER).unwrap();
+
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .anisotropy_enable(false)
+            .compare_enable(false)
+            .unnormalized_coordinates(false);
+        let sampler = unsafe { device.vk.create_sampler(&sampler_info, None) }.unwrap();
+
+        let samplers = [sampler];
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .immutable_samplers(&samplers)
+                .build(),
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .immutable_samplers(&samplers)
+                .build(),
+        ];
+        let set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
+            .bindings(&bindings);
+        let set_layout = unsafe { device.vk.create_descriptor_set_layout(&set_layout_info, None) }.unwrap();
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<GradePushConstants>() as u32,
+        };
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(std::slice::from_ref(&set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe { device.vk.create_pipeline_layout(&pipeline_layout_info, None) }.unwrap();
+
+        let render_pass = Self::create_render_pass(&device, dst_format, load_op, initial_layout, final_layout);
+        let pipeline = Self::create_pipeline(&device, vertex_shader, fragment_shader, pipeline_layout, render_pass, rotation);
+
+        Self {
+            device,
+            vertex_shader,
+            fragment_shader,
+            sampler,
+            set_layout,
+            pipeline_layout,
+            render_pass,
+            pipeline,
+        }
+    }
+
+    /// Records the grading of `source_view` into the framebuffer. No memory barriers are generated.
+    ///
+    /// The source image and LUT must be in the SHADER_READ_ONLY_OPTIMAL layout. See
+    /// [`crate::device::device_utils::BlitPass::record_blit`] for the clear value and device
+    /// scissors.
+    pub(crate) fn record(&self, command_buffer: vk::CommandBuffer, source_view: vk::ImageView, framebuffer: vk::Framebuffer, size: Vec2u32, state: &ColorGradingState, clear_value: Option<&vk::ClearValue>, device_scissors: Option<&[vk::Rect2D]>) {
+        let mut info = vk::RenderPassBeginInfo::builder()
+            .render_pass(self.render_pass)
+            .framebuffer(framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D { width: size[0], height: size[1] }
+            });
+
+        if let Some(clear_value) = clear_value {
+            info = info.clear_values(std::slice::from_ref(clear_value))
+        }
+
+        let viewport = vk::Viewport {
+            x: 0f32,
+            y: 0f32,
+            width: size[0] as f32,
+            height: size[1] as f32,
+            min_depth: 0f32,
+            max_depth: 1f32
+        };
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D { width: size[0], height: size[1] }
+        };
+
+        // If no LUT is used the strength is 0 and the binding is never read
+        let image_infos = [
+            vk::DescriptorImageInfo {
+                sampler: vk::Sampler::null(),
+                image_view: source_view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            },
+            vk::DescriptorImageInfo {
+                sampler: vk::Sampler::null(),
+                image_view: state.lut_view.unwrap_or(source_view),
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            },
+        ];
+        let writes = [
+            vk::WriteDescriptorSet::builder()
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&image_infos[0..1])
+                .build(),
+            vk::WriteDescriptorSet::builder()
+                .dst_binding(1)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&image_infos[1..2])
+                .build(),
+        ];
+
+        unsafe {
+            self.device.vk.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            self.device.vk.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+
+            self.device.vk.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+            self.device.vk.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            self.device.push_descriptor_khr.cmd_push_descriptor_set(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout, 0, &writes);
+            self.device.vk.cmd_push_constants(command_buffer, self.pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, bytes_of(&state.push_constants));
+            if let Some(device_scissors) = device_scissors {
+                for (index, scissor) in device_scissors.iter().enumerate() {
+                    self.device.vk.cmd_set_device_mask(command_buffer, 1u32 << index);
+                    self.device.vk.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(scissor));
+                    self.device.vk.cmd_draw(command_buffer, 4, 1, 0, 0);
+                }
+                self.device.vk.cmd_set_device_mask(command_buffer, (1u32 << device_scissors.len()) - 1);
+            } else {
+                self.device.vk.cmd_draw(command_buffer, 4, 1, 0, 0);
+            }
+            self.device.vk.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    fn create_render_pass(device: &DeviceFunctions, dst_format: vk::Format, load_op: vk::AttachmentLoadOp, initial_layout: vk::ImageLayout, final_layout: vk::ImageLayout) -> vk::RenderPass {
+        let attachment = vk::AttachmentDescription::builder()
+            .format(dst_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(load_op)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(initial_layout)
+            .final_layout(final_layout);
+
+        let attachment_reference = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        };
+
+        let subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&attachment_reference));
+
+        let info = vk::RenderPassCreateInfo::builder()
+            .attachments(std::slice::from_ref(&attachment))
+            .subpasses(std::slice::from_ref(&subpass));
+
+        unsafe {
+            device.vk.create_render_pass(&info, None)
+        }.unwrap()
+    }
+
+    fn create_pipeline(device: &DeviceFunctions, vertex_shader: vk::ShaderModule, fragment_shader: vk::ShaderModule, layout: vk::PipelineLayout, render_pass: vk::RenderPass, rotation: OutputRotation) -> vk::Pipeline {
+        let specialization = RotationSpecialization::new(rotation);
+        let specialization_info = specialization.get_info();
+
+        let shader_stages = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vertex_shader)
+                .name(SHADER_ENTRY)
+                .specialization_info(&specialization_info)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(fragment_shader)
+                .name(SHADER_ENTRY)
+                .build()
+        ];
+
+        let input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP);
+
+        let viewport = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::CLOCKWISE)
+            .line_width(1.0);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder();
+
+        let attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .blend_enable(false)
+            .color_write_mask(vk::ColorComponentFlags::RGBA);
+
+        let color_blend = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(std::slice::from_ref(&attachment));
+
+        let dynamic_states = [
+            vk::DynamicState::VIEWPORT,
+            vk::DynamicState::SCISSOR
+        ];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&dynamic_states);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stages)
+            .vertex_input_state(&input_state)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend)
+            .dynamic_state(&dynamic_state)
+            .layout(layout)
+            .render_pass(render_pass);
+
+        *unsafe {
+            device.vk.create_graphics_pipelines(device.pipeline_cache, std::slice::from_ref(&info), None)
+        }.unwrap().get(0).unwrap()
+    }
+}
+
+impl Drop for ColorGradingPass {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.vk.destroy_pipeline(self.pipeline, None);
+            self.device.vk.destroy_render_pass(self.render_pass, None);
+            self.device.vk.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.vk.destroy_descriptor_set_layout(self.set_layout, None);
+            self.device.vk.destroy_sampler(self.sampler, None);
+            self.device.vk.destroy_shader_module(self.fragment_shader, None);
+            self.device.vk.destroy_shader_module(self.vertex_shader, None);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+struct GradePushConstants {
+    matrix_r: [f32; 4],
+    matrix_g: [f32; 4],
+    matrix_b: [f32; 4],
+    lift: [f32; 4],
+    gamma: [f32; 4],
+    gain: [f32; 4],
+    lut_size: f32,
+    lut_strength: f32,
+    exposure: f32,
+    _padding: f32,
+    /// Amplitude, phase and frequency of the screen distortion
+    distortion: [f32; 4],
+}
+const_assert_eq!(std::mem::size_of::<GradePushConstants>(), 128);
+
+unsafe impl Zeroable for GradePushConstants {}
+unsafe impl Pod for GradePushConstants {}
+
+const SHADER_ENTRY: &'static CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") };
+static FULL_SCREEN_QUAD_VERTEX_SHADER: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "utils/full_screen_quad_vert.spv"));
+static COLOR_GRADE_FRAGMENT_SHADER: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "utils/color_grade_frag.spv"));
@@ -0,0 +1,592 @@
+//! Recording and replaying of [`PassRecorder`] commands.
+//!
+//! A [`PassCommandLog`] stores every call made on a [`PassRecorder`] while logging is enabled.
+//! Immediate mesh data is stored in full while global meshes, images and shaders are referenced by
+//! id. When replaying the host provides [`ReplayResources`] mapping those ids to live objects which
+//! allows a pass to be re-rendered deterministically, for example at a higher resolution.
+//!
+//! Logs can be serialized into a compact little endian binary format.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::renderer::emulator::{FrameSize, GlobalImage, GlobalImageId, GlobalMesh, GlobalMeshId, ImmediateMeshId, MeshData, PassRecorder, SamplerInfo};
+use crate::renderer::emulator::mc_shaders::{McUniformData, ShaderId};
+use crate::renderer::emulator::unproject::ViewMatrices;
+
+use crate::prelude::*;
+
+const MAGIC: [u8; 4] = *b"B4DP";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug)]
+pub enum PassCommand {
+    SetPartialTick(f32),
+    SetViewport(u32, vk::Viewport),
+    SetViewportIndex(u32),
+    SetScissor(Option<vk::Rect2D>),
+    SetDrawTag(Option<u64>),
+    UpdateUniform(ShaderId, McUniformData),
+    UpdateTexture {
+        index: u32,
+        image: GlobalImageId,
+        sampler: SamplerInfo,
+        shader: ShaderId,
+    },
+    BindTexture {
+        slot: u32,
+        image: GlobalImageId,
+        sampler: SamplerInfo,
+    },
+    UnbindTexture(u32),
+    UploadImmediate {
+        id: u32,
+        vertex_data: Vec<u8>,
+        index_data: Vec<u8>,
+        vertex_stride: u32,
+        index_count: u32,
+        index_type: vk::IndexType,
+        primitive_topology: vk::PrimitiveTopology,
+    },
+    DrawImmediate {
+        id: u32,
+        shader: ShaderId,
+        depth_write_enable: bool,
+    },
+    DrawGlobal {
+        mesh: GlobalMeshId,
+        shader: ShaderId,
+        depth_write_enable: bool,
+    },
+    BeginPortal {
+        matrices: ViewMatrices,
+        mask: u32,
+        shader: ShaderId,
+    },
+    EndPortal,
+}
+
+/// The objects referenced by a [`PassCommandLog`] during replay.
+#[derive(Default)]
+pub struct ReplayResources {
+    pub meshes: HashMap<GlobalMeshId, Arc<GlobalMesh>>,
+    pub images: HashMap<GlobalImageId, Arc<GlobalImage>>,
+
+    /// Maps recorded shader ids to the shaders used for replay. Shaders without an entry are used
+    /// as recorded.
+    pub shaders: HashMap<ShaderId, ShaderId>,
+}
+
+impl ReplayResources {
+    fn get_shader(&self, shader: ShaderId) -> ShaderId {
+        self.shaders.get(&shader).copied().unwrap_or(shader)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PassCommandLog {
+    /// The frame size of the pass when logging was started.
+    pub frame_size: Option<FrameSize>,
+    pub commands: Vec<PassCommand>,
+}
+
+impl PassCommandLog {
+    pub fn new(frame_size: Option<FrameSize>) -> Self {
+        Self {
+            frame_size,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, command: PassCommand) {
+        self.commands.push(command);
+    }
+
+    /// Replays all commands into the recorder.
+    ///
+    /// If both the log and the recorder have a frame size viewports and the screen size uniform
+    /// are scaled to the size of the recorder. Draws referencing meshes or images missing from
+    /// `resources` are skipped. Returns the number of skipped commands.
+    pub fn replay(&self, recorder: &mut PassRecorder, resources: &ReplayResources) -> usize {
+        let scale = match (self.frame_size, recorder.get_frame_size()) {
+            (Some(recorded), Some(current)) => Vec2f32::new(
+                (current.physical_size[0] as f32) / (recorded.physical_size[0].max(1) as f32),
+                (current.physical_size[1] as f32) / (recorded.physical_size[1].max(1) as f32)
+            ),
+            _ => Vec2f32::new(1f32, 1f32),
+        };
+
+        let mut immediate_ids: HashMap<u32, ImmediateMeshId> = HashMap::new();
+        let mut skipped = 0;
+
+        for command in &self.commands {
+            match command {
+                PassCommand::SetPartialTick(partial_tick) => recorder.set_partial_tick(*partial_tick),
+                PassCommand::SetViewport(index, viewport) => {
+                    let viewport = vk::Viewport {
+                        x: viewport.x * scale[0],
+                        y: viewport.y * scale[1],
+                        width: viewport.width * scale[0],
+                        height: viewport.height * scale[1],
+                        min_depth: viewport.min_depth,
+                        max_depth: viewport.max_depth,
+                    };
+                    recorder.set_viewport(*index, viewport);
+                }
+                PassCommand::SetViewportIndex(index) => recorder.set_viewport_index(*index),
+                PassCommand::SetScissor(scissor) => {
+                    let scissor = scissor.map(|scissor| {
+                        let min_x = ((scissor.offset.x as f32) * scale[0]).floor() as i32;
+                        let min_y = ((scissor.offset.y as f32) * scale[1]).floor() as i32;
+                        let max_x = (((scissor.offset.x as f32) + (scissor.extent.width as f32)) * scale[0]).ceil() as i32;
+                        let max_y = (((scissor.offset.y as f32) + (scissor.extent.height as f32)) * scale[1]).ceil() as i32;
+                        vk::Rect2D {
+                            offset: vk::Offset2D { x: min_x, y: min_y },
+                            extent: vk::Extent2D { width: (max_x - min_x) as u32, height: (max_y - min_y) as u32 },
+                        }
+                    });
+                    recorder.set_scissor(scissor);
+                }
+                PassCommand::SetDrawTag(tag) => recorder.set_draw_tag(*tag),
+                PassCommand::UpdateUniform(shader, data) => {
+                    let data = match data {
+                        McUniformData::ScreenSize(size) => McUniformData::ScreenSize(size.component_mul(&scale)),
+                        other => *other,
+                    };
+                    recorder.update_uniform(&data, resources.get_shader(*shader));
+                }
+                PassCommand::UpdateTexture { index, image, sampler, shader } => {
+                    match resources.images.get(image) {
+                        Some(image) => recorder.update_texture(*index, image, sampler, resources.get_shader(*shader)),
+                        None => skipped += 1,
+                    }
+                }
+                PassCommand::BindTexture { slot, image, sampler } => {
+                    match resources.images.get(image) {
+                        Some(image) => recorder.bind_texture(*slot, image, sampler),
+                        None => skipped += 1,
+                    }
+                }
+                PassCommand::UnbindTexture(slot) => recorder.unbind_texture(*slot),
+                PassCommand::UploadImmediate { id, vertex_data, index_data, vertex_stride, index_count, index_type, primitive_topology } => {
+                    let data = MeshData {
+                        vertex_data,
+                        index_data,
+                        vertex_stride: *vertex_stride,
+                        index_count: *index_count,
+                        index_type: *index_type,
+                        primitive_topology: *primitive_topology,
+                    };
+                    immediate_ids.insert(*id, recorder.upload_immediate(&data));
+                }
+                PassCommand::DrawImmediate { id, shader, depth_write_enable } => {
+                    match immediate_ids.get(id) {
+                        Some(id) => recorder.draw_immediate(*id, resources.get_shader(*shader), *depth_write_enable),
+                        None => skipped += 1,
+                    }
+                }
+                PassCommand::DrawGlobal { mesh, shader, depth_write_enable } => {
+                    match resources.meshes.get(mesh) {
+                        Some(mesh) => recorder.draw_global(mesh.clone(), resources.get_shader(*shader), *depth_write_enable),
+                        None => skipped += 1,
+                    }
+                }
+                // Skipping a portal would unbalance the following end so a missing mask is a
+                // corrupt log
+                PassCommand::BeginPortal { matrices, mask, shader } => {
+                    let mask = *immediate_ids.get(mask).unwrap_or_else(|| {
+                        log::error!("Replayed portal references unknown immediate mesh {:?}", mask);
+                        panic!()
+                    });
+                    recorder.begin_portal(*matrices, mask, resources.get_shader(*shader));
+                }
+                PassCommand::EndPortal => recorder.end_portal(),
+            }
+        }
+
+        if skipped != 0 {
+            log::warn!("Skipped {} commands with missing resources during replay", skipped);
+        }
+        skipped
+    }
+
+    /// Appends the serialized log to `out`.
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        let mut writer = Writer(out);
+        writer.bytes(&MAGIC);
+        writer.u32(VERSION);
+
+        match &self.frame_size {
+            Some(frame_size) => {
+                writer.u8(1);
+                writer.u32(frame_size.physical_size[0]);
+                writer.u32(frame_size.physical_size[1]);
+                writer.f32(frame_size.scale_factor);
+            }
+            None => writer.u8(0),
+        }
+
+        writer.u32(self.commands.len() as u32);
+        for command in &self.commands {
+            match command {
+                PassCommand::SetPartialTick(partial_tick) => {
+                    writer.u8(0);
+                    writer.f32(*partial_tick);
+                }
+                PassCommand::SetViewport(index, viewport) => {
+                    writer.u8(1);
+                    writer.u32(*index);
+                    for value in [viewport.x, viewport.y, viewport.width, viewport.height, viewport.min_depth, viewport.max_depth] {
+                        writer.f32(value);
+                    }
+                }
+                PassCommand::SetViewportIndex(index) => {
+                    writer.u8(2);
+                    writer.u32(*index);
+                }
+                PassCommand::SetScissor(scissor) => {
+                    writer.u8(10);
+                    match scissor {
+                        Some(scissor) => {
+                            writer.u8(1);
+                            writer.i32(scissor.offset.x);
+                            writer.i32(scissor.offset.y);
+                            writer.u32(scissor.extent.width);
+                            writer.u32(scissor.extent.height);
+                        }
+                        None => writer.u8(0),
+                    }
+                }
+                PassCommand::SetDrawTag(tag) => {
+                    writer.u8(11);
+                    match tag {
+                        Some(tag) => {
+                            writer.u8(1);
+                            writer.u64(*tag);
+                        }
+                        None => writer.u8(0),
+                    }
+                }
+                PassCommand::UpdateUniform(shader, data) => {
+                    writer.u8(3);
+                    writer.u64(shader.as_uuid().get_raw());
+                    writer.uniform(data);
+                }
+                PassCommand::UpdateTexture { index, image, sampler, shader } => {
+                    writer.u8(4);
+                    writer.u32(*index);
+                    writer.u64(image.as_uuid().get_raw());
+                    writer.sampler(sampler);
+                    writer.u64(shader.as_uuid().get_raw());
+                }
+                PassCommand::BindTexture { slot, image, sampler } => {
+                    writer.u8(8);
+                    writer.u32(*slot);
+                    writer.u64(image.as_uuid().get_raw());
+                    writer.sampler(sampler);
+                }
+                PassCommand::UnbindTexture(slot) => {
+                    writer.u8(9);
+                    writer.u32(*slot);
+                }
+                PassCommand::UploadImmediate { id, vertex_data, index_data, vertex_stride, index_count, index_type, primitive_topology } => {
+                    writer.u8(5);
+                    writer.u32(*id);
+                    writer.u32(vertex_data.len() as u32);
+                    writer.bytes(vertex_data);
+                    writer.u32(index_data.len() as u32);
+                    writer.bytes(index_data);
+                    writer.u32(*vertex_stride);
+                    writer.u32(*index_count);
+                    writer.i32(index_type.as_raw());
+                    writer.i32(primitive_topology.as_raw());
+                }
+                PassCommand::DrawImmediate { id, shader, depth_write_enable } => {
+                    writer.u8(6);
+                    writer.u32(*id);
+                    writer.u64(shader.as_uuid().get_raw());
+                    writer.u8(*depth_write_enable as u8);
+                }
+                PassCommand::DrawGlobal { mesh, shader, depth_write_enable } => {
+                    writer.u8(7);
+                    writer.u64(mesh.as_uuid().get_raw());
+                    writer.u64(shader.as_uuid().get_raw());
+                    writer.u8(*depth_write_enable as u8);
+                }
+                PassCommand::BeginPortal { matrices, mask, shader } => {
+                    writer.u8(12);
+                    writer.floats(matrices.model_view.as_slice());
+                    writer.floats(matrices.projection.as_slice());
+                    writer.u32(*mask);
+                    writer.u64(shader.as_uuid().get_raw());
+                }
+                PassCommand::EndPortal => writer.u8(13),
+            }
+        }
+    }
+
+    /// Deserializes a log from the start of `data`. Returns the log and the number of bytes read.
+    pub fn deserialize(data: &[u8]) -> Result<(Self, usize), CommandLogError> {
+        let mut reader = Reader { data, offset: 0 };
+        if reader.bytes(4)? != MAGIC {
+            return Err(CommandLogError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(CommandLogError::UnsupportedVersion(version));
+        }
+
+        let frame_size = match reader.u8()? {
+            0 => None,
+            _ => {
+                let physical_size = Vec2u32::new(reader.u32()?, reader.u32()?);
+                Some(FrameSize::from_physical(physical_size, reader.f32()?))
+            }
+        };
+
+        let count = reader.u32()?;
+        let mut commands = Vec::with_capacity((count as usize).min(1 << 16));
+        for _ in 0..count {
+            let command = match reader.u8()? {
+                0 => PassCommand::SetPartialTick(reader.f32()?),
+                1 => {
+                    let index = reader.u32()?;
+                    PassCommand::SetViewport(index, vk::Viewport {
+                        x: reader.f32()?,
+                        y: reader.f32()?,
+                        width: reader.f32()?,
+                        height: reader.f32()?,
+                        min_depth: reader.f32()?,
+                        max_depth: reader.f32()?,
+                    })
+                }
+                2 => PassCommand::SetViewportIndex(reader.u32()?),
+                10 => match reader.u8()? {
+                    0 => PassCommand::SetScissor(None),
+                    _ => PassCommand::SetScissor(Some(vk::Rect2D {
+                        offset: vk::Offset2D { x: reader.i32()?, y: reader.i32()? },
+                        extent: vk::Extent2D { width: reader.u32()?, height: reader.u32()? },
+                    })),
+                },
+                11 => match reader.u8()? {
+                    0 => PassCommand::SetDrawTag(None),
+                    _ => PassCommand::SetDrawTag(Some(reader.u64()?)),
+                },
+                3 => {
+                    let shader = ShaderId::from_uuid(UUID::from_raw(reader.u64()?));
+                    PassCommand::UpdateUniform(shader, reader.uniform()?)
+                }
+                4 => PassCommand::UpdateTexture {
+                    index: reader.u32()?,
+                    image: GlobalImageId::from_uuid(UUID::from_raw(reader.u64()?)),
+                    sampler: reader.sampler()?,
+                    shader: ShaderId::from_uuid(UUID::from_raw(reader.u64()?)),
+                },
+                8 => PassCommand::BindTexture {
+                    slot: reader.u32()?,
+                    image: GlobalImageId::from_uuid(UUID::from_raw(reader.u64()?)),
+                    sampler: reader.sampler()?,
+                },
+                9 => PassCommand::UnbindTexture(reader.u32()?),
+                5 => {
+                    let id = reader.u32()?;
+                    let vertex_len = reader.u32()? as usize;
+                    let vertex_data = reader.bytes(vertex_len)?.to_vec();
+                    let index_len = reader.u32()? as usize;
+                    let index_data = reader.bytes(index_len)?.to_vec();
+                    PassCommand::UploadImmediate {
+                        id,
+                        vertex_data,
+                        index_data,
+                        vertex_stride: reader.u32()?,
+                        index_count: reader.u32()?,
+                        index_type: vk::IndexType::from_raw(reader.i32()?),
+                        primitive_topology: vk::PrimitiveTopology::from_raw(reader.i32()?),
+                    }
+                }
+                6 => PassCommand::DrawImmediate {
+                    id: reader.u32()?,
+                    shader: ShaderId::from_uuid(UUID::from_raw(reader.u64()?)),
+                    depth_write_enable: reader.u8()? != 0,
+                },
+                7 => PassCommand::DrawGlobal {
+                    mesh: GlobalMeshId::from_uuid(UUID::from_raw(reader.u64()?)),
+                    shader: ShaderId::from_uuid(UUID::from_raw(reader.u64()?)),
+                    depth_write_enable: reader.u8()? != 0,
+                },
+                12 => PassCommand::BeginPortal {
+                    matrices: ViewMatrices {
+                        model_view: reader.mat4()?,
+                        projection: reader.mat4()?,
+                    },
+                    mask: reader.u32()?,
+                    shader: ShaderId::from_uuid(UUID::from_raw(reader.u64()?)),
+                },
+                13 => PassCommand::EndPortal,
+                other => return Err(CommandLogError::InvalidCommand(other)),
+            };
+            commands.push(command);
+        }
+
+        Ok((Self { frame_size, commands }, reader.offset))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CommandLogError {
+    UnexpectedEnd,
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    InvalidCommand(u8),
+    InvalidUniform(u8),
+    InvalidEnum(&'static str),
+}
+
+pub(crate) struct Writer<'a>(pub(crate) &'a mut Vec<u8>);
+
+impl<'a> Writer<'a> {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, value: &[u8]) {
+        self.0.extend_from_slice(value);
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        for value in values {
+            self.f32(*value);
+        }
+    }
+
+    fn uniform(&mut self, data: &McUniformData) {
+        match data {
+            McUniformData::ModelViewMatrix(m) => { self.u8(0); self.floats(m.as_slice()); }
+            McUniformData::ProjectionMatrix(m) => { self.u8(1); self.floats(m.as_slice()); }
+            McUniformData::InverseViewRotationMatrix(m) => { self.u8(2); self.floats(m.as_slice()); }
+            McUniformData::TextureMatrix(m) => { self.u8(3); self.floats(m.as_slice()); }
+            McUniformData::ScreenSize(v) => { self.u8(4); self.floats(v.as_slice()); }
+            McUniformData::ColorModulator(v) => { self.u8(5); self.floats(v.as_slice()); }
+            McUniformData::Light0Direction(v) => { self.u8(6); self.floats(v.as_slice()); }
+            McUniformData::Light1Direction(v) => { self.u8(7); self.floats(v.as_slice()); }
+            McUniformData::FogStart(v) => { self.u8(8); self.f32(*v); }
+            McUniformData::FogEnd(v) => { self.u8(9); self.f32(*v); }
+            McUniformData::FogColor(v) => { self.u8(10); self.floats(v.as_slice()); }
+            McUniformData::FogShape(v) => { self.u8(11); self.u32(*v); }
+            McUniformData::LineWidth(v) => { self.u8(12); self.f32(*v); }
+            McUniformData::GameTime(v) => { self.u8(13); self.f32(*v); }
+            McUniformData::ChunkOffset(v) => { self.u8(14); self.floats(v.as_slice()); }
+            McUniformData::ItemAnimation(v) => { self.u8(15); self.floats(v.as_slice()); }
+        }
+    }
+
+    fn sampler(&mut self, sampler: &SamplerInfo) {
+        self.i32(sampler.mag_filter.as_raw());
+        self.i32(sampler.min_filter.as_raw());
+        self.i32(sampler.mipmap_mode.as_raw());
+        self.i32(sampler.address_mode_u.as_raw());
+        self.i32(sampler.address_mode_v.as_raw());
+        self.u8(sampler.anisotropy_enable as u8);
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], CommandLogError> {
+        let end = self.offset.checked_add(len).ok_or(CommandLogError::UnexpectedEnd)?;
+        let result = self.data.get(self.offset..end).ok_or(CommandLogError::UnexpectedEnd)?;
+        self.offset = end;
+        Ok(result)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, CommandLogError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, CommandLogError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn i32(&mut self) -> Result<i32, CommandLogError> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, CommandLogError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, CommandLogError> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn mat4(&mut self) -> Result<Mat4f32, CommandLogError> {
+        let mut values = [0f32; 16];
+        for value in values.iter_mut() {
+            *value = self.f32()?;
+        }
+        Ok(Mat4f32::from_column_slice(&values))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3f32, CommandLogError> {
+        Ok(Vec3f32::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn vec4(&mut self) -> Result<Vec4f32, CommandLogError> {
+        Ok(Vec4f32::new(self.f32()?, self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn uniform(&mut self) -> Result<McUniformData, CommandLogError> {
+        Ok(match self.u8()? {
+            0 => McUniformData::ModelViewMatrix(self.mat4()?),
+            1 => McUniformData::ProjectionMatrix(self.mat4()?),
+            2 => McUniformData::InverseViewRotationMatrix(self.mat4()?),
+            3 => McUniformData::TextureMatrix(self.mat4()?),
+            4 => McUniformData::ScreenSize(Vec2f32::new(self.f32()?, self.f32()?)),
+            5 => McUniformData::ColorModulator(self.vec4()?),
+            6 => McUniformData::Light0Direction(self.vec3()?),
+            7 => McUniformData::Light1Direction(self.vec3()?),
+            8 => McUniformData::FogStart(self.f32()?),
+            9 => McUniformData::FogEnd(self.f32()?),
+            10 => McUniformData::FogColor(self.vec4()?),
+            11 => McUniformData::FogShape(self.u32()?),
+            12 => McUniformData::LineWidth(self.f32()?),
+            13 => McUniformData::GameTime(self.f32()?),
+            14 => McUniformData::ChunkOffset(self.vec3()?),
+            15 => McUniformData::ItemAnimation(Vec2f32::new(self.f32()?, self.f32()?)),
+            other => return Err(CommandLogError::InvalidUniform(other)),
+        })
+    }
+
+    fn sampler(&mut self) -> Result<SamplerInfo, CommandLogError> {
+        Ok(SamplerInfo {
+            mag_filter: vk::Filter::from_raw(self.i32()?),
+            min_filter: vk::Filter::from_raw(self.i32()?),
+            mipmap_mode: vk::SamplerMipmapMode::from_raw(self.i32()?),
+            address_mode_u: vk::SamplerAddressMode::from_raw(self.i32()?),
+            address_mode_v: vk::SamplerAddressMode::from_raw(self.i32()?),
+            anisotropy_enable: self.u8()? != 0,
+        })
+    }
+}
@@ -0,0 +1,416 @@
+//! Recording of complete command streams for bug reports.
+//!
+//! A [`CommandStream`] contains the creation of global objects and shaders as well as the
+//! [`PassCommandLog`] of a number of frames. Global mesh data is only stored as a hash unless
+//! requested. Streams can be replayed headless using [`CommandStream::replay`] which allows
+//! maintainers to reproduce rendering issues without the host application.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::renderer::emulator::{EmulatorRenderer, GlobalImageId, GlobalMeshId, ImageArrayMode, MeshData};
+use crate::renderer::emulator::command_log::{CommandLogError, PassCommandLog, Reader, ReplayResources, Writer};
+use crate::renderer::emulator::mc_shaders::{AlphaMode, FogMode, McUniform, ShaderId, ShaderSpecialization, VertexFormat, VertexFormatEntry};
+use crate::renderer::emulator::pipeline::{EmulatorPipeline, OffscreenOutput, OffscreenReadback};
+use crate::renderer::emulator::quantization::{NormalEncoding, PositionQuantization};
+use crate::util::format::Format;
+
+use crate::prelude::*;
+
+const MAGIC: [u8; 4] = *b"B4DS";
+const VERSION: u32 = 4;
+
+#[derive(Clone, Debug)]
+pub enum StreamEvent {
+    CreateGlobalMesh {
+        id: GlobalMeshId,
+        vertex_hash: u128,
+        index_hash: u128,
+        vertex_stride: u32,
+        index_count: u32,
+        index_type: vk::IndexType,
+        primitive_topology: vk::PrimitiveTopology,
+
+        /// The vertex and index data if mesh data recording was enabled.
+        data: Option<(Vec<u8>, Vec<u8>)>,
+    },
+    CreateGlobalImage {
+        id: GlobalImageId,
+        size: Vec2u32,
+        mip_levels: u32,
+        array_mode: ImageArrayMode,
+        format: vk::Format,
+    },
+    CreateShader {
+        id: ShaderId,
+        vertex_format: VertexFormat,
+        used_uniforms: McUniform,
+        specialization: ShaderSpecialization,
+    },
+    Frame(PassCommandLog),
+}
+
+impl StreamEvent {
+    pub fn create_global_mesh(id: GlobalMeshId, data: &MeshData, include_data: bool) -> Self {
+        Self::CreateGlobalMesh {
+            id,
+            vertex_hash: xxhash_rust::xxh3::xxh3_128(data.vertex_data),
+            index_hash: xxhash_rust::xxh3::xxh3_128(data.index_data),
+            vertex_stride: data.vertex_stride,
+            index_count: data.index_count,
+            index_type: data.index_type,
+            primitive_topology: data.primitive_topology,
+            data: if include_data { Some((data.vertex_data.to_vec(), data.index_data.to_vec())) } else { None },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CommandStream {
+    pub events: Vec<StreamEvent>,
+}
+
+impl CommandStream {
+    pub fn get_frame_count(&self) -> usize {
+        self.events.iter().filter(|event| matches!(event, StreamEvent::Frame(_))).count()
+    }
+
+    /// Replays the stream rendering each frame into an offscreen image of its recorded size.
+    ///
+    /// Global meshes are only recreated if their data was recorded. Global images are recreated
+    /// with undefined content. `make_pipeline` is called to create the pipeline of each frame.
+    pub fn replay<F>(&self, renderer: &Arc<EmulatorRenderer>, mut make_pipeline: F) -> Vec<OffscreenReadback> where F: FnMut(Vec2u32) -> Arc<dyn EmulatorPipeline> {
+        let mut resources = ReplayResources::default();
+        let mut readbacks = Vec::new();
+
+        for event in &self.events {
+            match event {
+                StreamEvent::CreateGlobalMesh { id, vertex_stride, index_count, index_type, primitive_topology, data, .. } => {
+                    if let Some((vertex_data, index_data)) = data {
+                        let mesh = renderer.create_global_mesh(&MeshData {
+                            vertex_data,
+                            index_data,
+                            vertex_stride: *vertex_stride,
+                            index_count: *index_count,
+                            index_type: *index_type,
+                            primitive_topology: *primitive_topology,
+                        });
+                        resources.meshes.insert(*id, mesh);
+                    }
+                }
+                StreamEvent::CreateGlobalImage { id, size, mip_levels, array_mode, format } => {
+                    match Format::try_format_for(*format) {
+                        Some(format) => {
+                            resources.images.insert(*id, renderer.create_global_image_array(*size, *mip_levels, *array_mode, format));
+                        }
+                        None => log::warn!("Skipping global image with unsupported format {:?}", format),
+                    }
+                }
+                StreamEvent::CreateShader { id, vertex_format, used_uniforms, specialization } => {
+                    let shader = renderer.create_shader_specialized(vertex_format, *used_uniforms, *specialization);
+                    resources.shaders.insert(*id, shader);
+                }
+                StreamEvent::Frame(log) => {
+                    let size = log.frame_size.map_or(Vec2u32::new(800, 600), |frame_size| frame_size.physical_size);
+                    let pipeline = make_pipeline(size);
+                    let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), size);
+
+                    let (output, readback) = output.next_output();
+                    let mut recorder = renderer.start_pass(pipeline);
+                    if let Some(frame_size) = log.frame_size {
+                        recorder.set_frame_size(frame_size);
+                    }
+                    recorder.use_output(output);
+                    log.replay(&mut recorder, &resources);
+                    drop(recorder);
+
+                    readbacks.push(readback);
+                }
+            }
+        }
+
+        readbacks
+    }
+
+    pub fn serialize(&self, out: &mut Vec<u8>) {
+        let mut writer = Writer(&mut *out);
+        writer.bytes(&MAGIC);
+        writer.u32(VERSION);
+        writer.u32(self.events.len() as u32);
+
+        for event in &self.events {
+            let mut writer = Writer(&mut *out);
+            match event {
+                StreamEvent::CreateGlobalMesh { id, vertex_hash, index_hash, vertex_stride, index_count, index_type, primitive_topology, data } => {
+                    writer.u8(0);
+                    writer.u64(id.as_uuid().get_raw());
+                    writer.bytes(&vertex_hash.to_le_bytes());
+                    writer.bytes(&index_hash.to_le_bytes());
+                    writer.u32(*vertex_stride);
+                    writer.u32(*index_count);
+                    writer.i32(index_type.as_raw());
+                    writer.i32(primitive_topology.as_raw());
+                    match data {
+                        Some((vertex_data, index_data)) => {
+                            writer.u8(1);
+                            writer.u32(vertex_data.len() as u32);
+                            writer.bytes(vertex_data);
+                            writer.u32(index_data.len() as u32);
+                            writer.bytes(index_data);
+                        }
+                        None => writer.u8(0),
+                    }
+                }
+                StreamEvent::CreateGlobalImage { id, size, mip_levels, array_mode, format } => {
+                    writer.u8(1);
+                    writer.u64(id.as_uuid().get_raw());
+                    writer.u32(size[0]);
+                    writer.u32(size[1]);
+                    writer.u32(*mip_levels);
+                    match array_mode {
+                        ImageArrayMode::Single => writer.u8(0),
+                        ImageArrayMode::Layers(count) => {
+                            writer.u8(1);
+                            writer.u32(*count);
+                        }
+                        ImageArrayMode::Atlas(grid) => {
+                            writer.u8(2);
+                            writer.u32(grid[0]);
+                            writer.u32(grid[1]);
+                        }
+                    }
+                    writer.i32(format.as_raw());
+                }
+                StreamEvent::CreateShader { id, vertex_format, used_uniforms, specialization } => {
+                    writer.u8(2);
+                    writer.u64(id.as_uuid().get_raw());
+                    write_vertex_format(&mut writer, vertex_format);
+                    writer.u64(used_uniforms.as_raw());
+                    writer.u32(specialization.fog_mode as u32);
+                    writer.f32(specialization.alpha_test_threshold);
+                    writer.u32(specialization.alpha_mode as u32);
+                    writer.u8(specialization.lightmap_enable as u8);
+                    writer.i32(specialization.polygon_mode.as_raw());
+                    writer.u8(specialization.primitive_restart as u8);
+                    writer.u8(specialization.emissive as u8);
+                    writer.u32(specialization.emissive_image.unwrap_or(ShaderSpecialization::NO_EMISSIVE_IMAGE));
+                    writer.u8(specialization.item_animation as u8);
+                }
+                StreamEvent::Frame(log) => {
+                    writer.u8(3);
+                    log.serialize(out);
+                }
+            }
+        }
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Self, CommandLogError> {
+        let mut reader = Reader { data, offset: 0 };
+        if reader.bytes(4)? != MAGIC {
+            return Err(CommandLogError::InvalidMagic);
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(CommandLogError::UnsupportedVersion(version));
+        }
+
+        let count = reader.u32()?;
+        let mut events = Vec::with_capacity((count as usize).min(1 << 16));
+        for _ in 0..count {
+            let event = match reader.u8()? {
+                0 => {
+                    let id = GlobalMeshId::from_uuid(UUID::from_raw(reader.u64()?));
+                    let vertex_hash = u128::from_le_bytes(reader.bytes(16)?.try_into().unwrap());
+                    let index_hash = u128::from_le_bytes(reader.bytes(16)?.try_into().unwrap());
+                    let vertex_stride = reader.u32()?;
+                    let index_count = reader.u32()?;
+                    let index_type = vk::IndexType::from_raw(reader.i32()?);
+                    let primitive_topology = vk::PrimitiveTopology::from_raw(reader.i32()?);
+                    let data = match reader.u8()? {
+                        0 => None,
+                        _ => {
+                            let vertex_len = reader.u32()? as usize;
+                            let vertex_data = reader.bytes(vertex_len)?.to_vec();
+                            let index_len = reader.u32()? as usize;
+                            let index_data = reader.bytes(index_len)?.to_vec();
+                            Some((vertex_data, index_data))
+                        }
+                    };
+                    StreamEvent::CreateGlobalMesh { id, vertex_hash, index_hash, vertex_stride, index_count, index_type, primitive_topology, data }
+                }
+                1 => StreamEvent::CreateGlobalImage {
+                    id: GlobalImageId::from_uuid(UUID::from_raw(reader.u64()?)),
+                    size: Vec2u32::new(reader.u32()?, reader.u32()?),
+                    mip_levels: reader.u32()?,
+                    array_mode: match reader.u8()? {
+                        0 => ImageArrayMode::Single,
+                        1 => ImageArrayMode::Layers(reader.u32()?),
+                        2 => ImageArrayMode::Atlas(Vec2u32::new(reader.u32()?, reader.u32()?)),
+                        _ => return Err(CommandLogError::InvalidEnum("array_mode")),
+                    },
+                    format: vk::Format::from_raw(reader.i32()?),
+                },
+                2 => {
+                    let id = ShaderId::from_uuid(UUID::from_raw(reader.u64()?));
+                    let vertex_format = read_vertex_format(&mut reader)?;
+                    let used_uniforms = McUniform::from_raw(reader.u64()?);
+                    let specialization = ShaderSpecialization {
+                        fog_mode: FogMode::from_raw(reader.u32()?).ok_or(CommandLogError::InvalidEnum("fog_mode"))?,
+                        alpha_test_threshold: reader.f32()?,
+                        alpha_mode: AlphaMode::from_raw(reader.u32()?).ok_or(CommandLogError::InvalidEnum("alpha_mode"))?,
+                        lightmap_enable: reader.u8()? != 0,
+                        polygon_mode: vk::PolygonMode::from_raw(reader.i32()?),
+                        primitive_restart: reader.u8()? != 0,
+                        emissive: reader.u8()? != 0,
+                        emissive_image: match reader.u32()? {
+                            ShaderSpecialization::NO_EMISSIVE_IMAGE => None,
+                            index => Some(index),
+                        },
+                        item_animation: reader.u8()? != 0,
+                    };
+                    StreamEvent::CreateShader { id, vertex_format, used_uniforms, specialization }
+                }
+                3 => {
+                    let (log, len) = PassCommandLog::deserialize(&data[reader.offset..])?;
+                    reader.offset += len;
+                    StreamEvent::Frame(log)
+                }
+                other => return Err(CommandLogError::InvalidCommand(other)),
+            };
+            events.push(event);
+        }
+
+        Ok(Self { events })
+    }
+}
+
+fn write_entry(writer: &mut Writer, entry: &Option<VertexFormatEntry>) {
+    match entry {
+        Some(entry) => {
+            writer.u8(1);
+            writer.u32(entry.offset);
+            writer.i32(entry.format.as_raw());
+        }
+        None => writer.u8(0),
+    }
+}
+
+fn read_entry(reader: &mut Reader) -> Result<Option<VertexFormatEntry>, CommandLogError> {
+    Ok(match reader.u8()? {
+        0 => None,
+        _ => Some(VertexFormatEntry {
+            offset: reader.u32()?,
+            format: vk::Format::from_raw(reader.i32()?),
+        }),
+    })
+}
+
+fn write_vertex_format(writer: &mut Writer, format: &VertexFormat) {
+    writer.u32(format.stride);
+    write_entry(writer, &Some(format.position));
+    write_entry(writer, &format.normal);
+    write_entry(writer, &format.color);
+    write_entry(writer, &format.uv0);
+    write_entry(writer, &format.uv1);
+    write_entry(writer, &format.uv2);
+    match &format.position_quantization {
+        Some(quantization) => {
+            writer.u8(1);
+            for value in quantization.offset.iter().chain(quantization.scale.iter()) {
+                writer.f32(*value);
+            }
+        }
+        None => writer.u8(0),
+    }
+    writer.u32(format.normal_encoding as u32);
+}
+
+fn read_vertex_format(reader: &mut Reader) -> Result<VertexFormat, CommandLogError> {
+    let stride = reader.u32()?;
+    let position = read_entry(reader)?.ok_or(CommandLogError::InvalidEnum("position"))?;
+    let normal = read_entry(reader)?;
+    let color = read_entry(reader)?;
+    let uv0 = read_entry(reader)?;
+    let uv1 = read_entry(reader)?;
+    let uv2 = read_entry(reader)?;
+    let position_quantization = match reader.u8()? {
+        0 => None,
+        _ => Some(PositionQuantization {
+            offset: Vec3f32::new(reader.f32()?, reader.f32()?, reader.f32()?),
+            scale: Vec3f32::new(reader.f32()?, reader.f32()?, reader.f32()?),
+        }),
+    };
+    let normal_encoding = NormalEncoding::from_raw(reader.u32()?).ok_or(CommandLogError::InvalidEnum("normal_encoding"))?;
+
+    Ok(VertexFormat {
+        stride,
+        position,
+        normal,
+        color,
+        uv0,
+        uv1,
+        uv2,
+        position_quantization,
+        normal_encoding,
+    })
+}
+
+/// Configuration of a command stream recording.
+#[derive(Clone, Debug)]
+pub struct StreamRecorderConfig {
+    /// The file the stream is written to once all frames have been recorded.
+    pub path: PathBuf,
+
+    /// The number of frames to record.
+    pub frame_count: u32,
+
+    /// If set the full data of global meshes is recorded instead of only a hash.
+    pub include_mesh_data: bool,
+}
+
+/// Collects events until the configured number of frames has been recorded and then writes the
+/// stream to disk.
+pub(crate) struct StreamRecorder {
+    config: StreamRecorderConfig,
+    stream: CommandStream,
+    recorded_frames: u32,
+}
+
+impl StreamRecorder {
+    pub(crate) fn new(config: StreamRecorderConfig) -> Self {
+        Self {
+            config,
+            stream: CommandStream::default(),
+            recorded_frames: 0,
+        }
+    }
+
+    pub(crate) fn includes_mesh_data(&self) -> bool {
+        self.config.include_mesh_data
+    }
+
+    pub(crate) fn push(&mut self, event: StreamEvent) {
+        self.stream.events.push(event);
+    }
+
+    /// Records a frame. Returns true once all frames have been recorded and the stream was
+    /// written.
+    pub(crate) fn push_frame(&mut self, log: PassCommandLog) -> bool {
+        self.stream.events.push(StreamEvent::Frame(log));
+        self.recorded_frames += 1;
+
+        if self.recorded_frames < self.config.frame_count {
+            return false;
+        }
+
+        let mut data = Vec::new();
+        self.stream.serialize(&mut data);
+        match std::fs::write(&self.config.path, data) {
+            Ok(_) => log::info!("Wrote command stream with {} frames to {:?}", self.recorded_frames, self.config.path),
+            Err(err) => log::error!("Failed to write command stream {:?}: {:?}", self.config.path, err),
+        }
+        true
+    }
+}
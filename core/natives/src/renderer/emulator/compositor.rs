@@ -0,0 +1,390 @@
+//! Gpu compositing of layered textures like banners and player skins with overlays.
+//!
+//! A composite texture is built from a base layer followed by any number of layers blended on top
+//! of it. Each layer selects a region of a source image which is stretched over the whole target
+//! and multiplied with a tint (for example the dye color of a banner pattern). The base layer
+//! replaces the content of the target, all other layers are alpha blended.
+//!
+//! Compositing is requested using
+//! [`EmulatorRenderer::create_composite_image`](crate::renderer::emulator::EmulatorRenderer::create_composite_image)
+//! which creates a new [`GlobalImage`] and queues a render job on the worker. The job is executed
+//! before the next pass after any pending uploads of the source images. The returned image can be
+//! used immediately, the worker guarantees that the composite is complete before the image is
+//! sampled by any later pass.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use ash::vk;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use include_bytes_aligned::include_bytes_aligned;
+
+use crate::device::device_utils::create_shader_from_bytes;
+use crate::renderer::emulator::GlobalImage;
+
+use crate::prelude::*;
+
+/// A single layer of a composite texture.
+#[derive(Clone)]
+pub struct CompositeLayer {
+    /// The source image. Must use [`ImageArrayMode::Single`](crate::renderer::emulator::ImageArrayMode::Single).
+    pub image: Arc<GlobalImage>,
+
+    /// The minimum texture coordinate of the region of the source image.
+    pub uv_min: Vec2f32,
+
+    /// The maximum texture coordinate of the region of the source image.
+    pub uv_max: Vec2f32,
+
+    /// The rgba color multiplied with the source image.
+    pub tint: Vec4f32,
+}
+
+impl CompositeLayer {
+    /// Creates a layer using the whole source image without any tint.
+    pub fn new(image: Arc<GlobalImage>) -> Self {
+        Self {
+            image,
+            uv_min: Vec2f32::zeros(),
+            uv_max: Vec2f32::new(1f32, 1f32),
+            tint: Vec4f32::new(1f32, 1f32, 1f32, 1f32),
+        }
+    }
+
+    /// Creates a layer using the whole source image multiplied with `tint`.
+    pub fn new_tinted(image: Arc<GlobalImage>, tint: Vec4f32) -> Self {
+        Self {
+            tint,
+            ..Self::new(image)
+        }
+    }
+
+    /// Creates a layer using a region of the source image specified in texels.
+    pub fn new_region(image: Arc<GlobalImage>, offset: Vec2u32, size: Vec2u32) -> Self {
+        let (uv_min, uv_max) = texel_region_to_uv(image.get_size(), offset, size);
+        Self {
+            uv_min,
+            uv_max,
+            ..Self::new(image)
+        }
+    }
+
+    pub(crate) fn get_push_constants(&self) -> CompositePushConstants {
+        CompositePushConstants {
+            source_rect: [self.uv_min[0], self.uv_min[1], self.uv_max[0], self.uv_max[1]],
+            tint: self.tint.into(),
+        }
+    }
+}
+
+/// Returns the minimum and maximum texture coordinate of a region of an image of `image_size`
+/// texels.
+pub fn texel_region_to_uv(image_size: Vec2u32, offset: Vec2u32, size: Vec2u32) -> (Vec2f32, Vec2f32) {
+    let image_size = Vec2f32::new(image_size[0] as f32, image_size[1] as f32);
+    let min = Vec2f32::new(offset[0] as f32, offset[1] as f32).component_div(&image_size);
+    let max = Vec2f32::new((offset[0] + size[0]) as f32, (offset[1] + size[1]) as f32).component_div(&image_size);
+    (min, max)
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub(crate) struct CompositePushConstants {
+    source_rect: [f32; 4],
+    tint: [f32; 4],
+}
+const_assert_eq!(std::mem::size_of::<CompositePushConstants>(), 32);
+
+unsafe impl Zeroable for CompositePushConstants {}
+unsafe impl Pod for CompositePushConstants {}
+
+/// The render pass and pipelines used to composite into images of a single format.
+struct CompositeTarget {
+    render_pass: vk::RenderPass,
+    /// Used for the base layer. Blending is disabled.
+    replace_pipeline: vk::Pipeline,
+    /// Used for all other layers.
+    blend_pipeline: vk::Pipeline,
+}
+
+/// Records composite jobs. Owned by the worker, the render passes and pipelines for each target
+/// format are created on first use.
+pub(crate) struct Compositor {
+    device: Arc<DeviceFunctions>,
+    vertex_shader: vk::ShaderModule,
+    fragment_shader: vk::ShaderModule,
+    sampler: vk::Sampler,
+    set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    targets: HashMap<vk::Format, CompositeTarget>,
+}
+
+impl Compositor {
+    pub(crate) fn new(device: Arc<DeviceFunctions>) -> Self {
+        let vertex_shader = create_shader_from_bytes(&device, FULL_SCREEN_QUAD_VERTEX_SHADER).unwrap();
+        let fragment_shader = create_shader_from_bytes(&device, COMPOSITE_FRAGMENT_SHADER).unwrap();
+
+        // Textures composited by the host are pixel art so no filtering is applied
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .max_lod(0f32)
+            .anisotropy_enable(false)
+            .compare_enable(false)
+            .unnormalized_coordinates(false);
+        let sampler = unsafe { device.vk.create_sampler(&sampler_info, None) }.unwrap();
+
+        let samplers = [sampler];
+        let binding = vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+            .immutable_samplers(&samplers);
+        let set_layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
+            .bindings(std::slice::from_ref(&binding));
+        let set_layout = unsafe { device.vk.create_descriptor_set_layout(&set_layout_info, None) }.unwrap();
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<CompositePushConstants>() as u32,
+        };
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(std::slice::from_ref(&set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let pipeline_layout = unsafe { device.vk.create_pipeline_layout(&pipeline_layout_info, None) }.unwrap();
+
+        Self {
+            device,
+            vertex_shader,
+            fragment_shader,
+            sampler,
+            set_layout,
+            pipeline_layout,
+            targets: HashMap::new(),
+        }
+    }
+
+    /// Creates a framebuffer for a target view. The framebuffer must be kept alive until the
+    /// commands recorded into it have completed.
+    pub(crate) fn create_framebuffer(&mut self, format: vk::Format, view: vk::ImageView, size: Vec2u32) -> vk::Framebuffer {
+        let render_pass = self.get_target(format).render_pass;
+
+        let info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(std::slice::from_ref(&view))
+            .width(size[0])
+            .height(size[1])
+            .layers(1);
+
+        unsafe {
+            self.device.vk.create_framebuffer(&info, None)
+        }.unwrap()
+    }
+
+    /// Records the layers into a framebuffer created by [`Compositor::create_framebuffer`]. The
+    /// first layer is the base layer. No memory barriers are generated.
+    ///
+    /// The target must be in the COLOR_ATTACHMENT_OPTIMAL layout and all source views in the
+    /// SHADER_READ_ONLY_OPTIMAL layout.
+    pub(crate) fn record(&mut self, command_buffer: vk::CommandBuffer, format: vk::Format, framebuffer: vk::Framebuffer, size: Vec2u32, layers: &[(vk::ImageView, CompositePushConstants)]) {
+        let target = self.get_target(format);
+        let render_pass = target.render_pass;
+        let replace_pipeline = target.replace_pipeline;
+        let blend_pipeline = target.blend_pipeline;
+
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D { width: size[0], height: size[1] }
+        };
+        let info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass)
+            .framebuffer(framebuffer)
+            .render_area(render_area);
+
+        let viewport = vk::Viewport {
+            x: 0f32,
+            y: 0f32,
+            width: size[0] as f32,
+            height: size[1] as f32,
+            min_depth: 0f32,
+            max_depth: 1f32
+        };
+
+        unsafe {
+            self.device.vk.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            self.device.vk.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&render_area));
+            self.device.vk.cmd_begin_render_pass(command_buffer, &info, vk::SubpassContents::INLINE);
+        }
+
+        for (index, (view, push_constants)) in layers.iter().enumerate() {
+            let image_info = vk::DescriptorImageInfo {
+                sampler: vk::Sampler::null(),
+                image_view: *view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            };
+            let write = vk::WriteDescriptorSet::builder()
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(std::slice::from_ref(&image_info));
+
+            unsafe {
+                if index < 2 {
+                    let pipeline = if index == 0 { replace_pipeline } else { blend_pipeline };
+                    self.device.vk.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                }
+                self.device.push_descriptor_khr.cmd_push_descriptor_set(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline_layout, 0, std::slice::from_ref(&write));
+                self.device.vk.cmd_push_constants(command_buffer, self.pipeline_layout, vk::ShaderStageFlags::FRAGMENT, 0, bytes_of(push_constants));
+                self.device.vk.cmd_draw(command_buffer, 4, 1, 0, 0);
+            }
+        }
+
+        unsafe {
+            self.device.vk.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    fn get_target(&mut self, format: vk::Format) -> &CompositeTarget {
+        let device = &self.device;
+        let vertex_shader = self.vertex_shader;
+        let fragment_shader = self.fragment_shader;
+        let pipeline_layout = self.pipeline_layout;
+
+        self.targets.entry(format).or_insert_with(|| {
+            let render_pass = Self::create_render_pass(device, format);
+            CompositeTarget {
+                render_pass,
+                replace_pipeline: Self::create_pipeline(device, vertex_shader, fragment_shader, pipeline_layout, render_pass, false),
+                blend_pipeline: Self::create_pipeline(device, vertex_shader, fragment_shader, pipeline_layout, render_pass, true),
+            }
+        })
+    }
+
+    fn create_render_pass(device: &DeviceFunctions, format: vk::Format) -> vk::RenderPass {
+        // The base layer covers the whole target so the previous content is never needed
+        let attachment = vk::AttachmentDescription::builder()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let attachment_reference = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        };
+
+        let subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&attachment_reference));
+
+        let info = vk::RenderPassCreateInfo::builder()
+            .attachments(std::slice::from_ref(&attachment))
+            .subpasses(std::slice::from_ref(&subpass));
+
+        unsafe {
+            device.vk.create_render_pass(&info, None)
+        }.unwrap()
+    }
+
+    fn create_pipeline(device: &DeviceFunctions, vertex_shader: vk::ShaderModule, fragment_shader: vk::ShaderModule, layout: vk::PipelineLayout, render_pass: vk::RenderPass, blend: bool) -> vk::Pipeline {
+        let shader_stages = [
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vertex_shader)
+                .name(SHADER_ENTRY)
+                .build(),
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(fragment_shader)
+                .name(SHADER_ENTRY)
+                .build()
+        ];
+
+        let input_state = vk::PipelineVertexInputStateCreateInfo::builder();
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP);
+
+        let viewport = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::CLOCKWISE)
+            .line_width(1.0);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder();
+
+        // The alpha of the target accumulates the coverage of all layers
+        let attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .blend_enable(blend)
+            .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::RGBA);
+
+        let color_blend = vk::PipelineColorBlendStateCreateInfo::builder()
+            .attachments(std::slice::from_ref(&attachment));
+
+        let dynamic_states = [
+            vk::DynamicState::VIEWPORT,
+            vk::DynamicState::SCISSOR
+        ];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&dynamic_states);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&shader_stages)
+            .vertex_input_state(&input_state)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend)
+            .dynamic_state(&dynamic_state)
+            .layout(layout)
+            .render_pass(render_pass);
+
+        *unsafe {
+            device.vk.create_graphics_pipelines(device.pipeline_cache, std::slice::from_ref(&info), None)
+        }.unwrap().get(0).unwrap()
+    }
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        unsafe {
+            for (_, target) in self.targets.drain() {
+                self.device.vk.destroy_pipeline(target.blend_pipeline, None);
+                self.device.vk.destroy_pipeline(target.replace_pipeline, None);
+                self.device.vk.destroy_render_pass(target.render_pass, None);
+            }
+            self.device.vk.destroy_pipeline_layout(self.pipeline_layout, None);
+            self.device.vk.destroy_descriptor_set_layout(self.set_layout, None);
+            self.device.vk.destroy_sampler(self.sampler, None);
+            self.device.vk.destroy_shader_module(self.fragment_shader, None);
+            self.device.vk.destroy_shader_module(self.vertex_shader, None);
+        }
+    }
+}
+
+const SHADER_ENTRY: &'static CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") };
+static FULL_SCREEN_QUAD_VERTEX_SHADER: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "utils/full_screen_quad_vert.spv"));
+static COMPOSITE_FRAGMENT_SHADER: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "utils/composite_frag.spv"));
@@ -0,0 +1,302 @@
+//! Built-in debug overlays for chunk borders and per section data.
+//!
+//! Instead of building line meshes on the host every frame the host describes the overlays using
+//! [`DebugOverlays`] and the lines are generated by a [`DebugOverlayBatch`]. The overlays are drawn
+//! after all other draws of the main frame using the camera matrices set with
+//! [`PassRecorder::set_view_matrices`](crate::renderer::emulator::PassRecorder::set_view_matrices).
+//!
+//! All positions are given in blocks. To keep the generated vertices precise far away from the
+//! world origin they are made relative to [`DebugOverlays::origin_block`] and
+//! [`DebugOverlays::origin_offset`]. Hosts using camera relative matrices pass the block containing
+//! the camera and the offset of the camera inside that block. Hosts using world space matrices
+//! leave both at zero.
+//!
+//! The vertex format of the shader used to draw the overlays must store the position as
+//! `R32G32B32_SFLOAT` and the color as `R8G8B8A8_UNORM`. See [`DebugOverlayBatch::supports_format`].
+
+use ash::vk;
+
+use crate::renderer::emulator::mc_shaders::VertexFormat;
+use crate::renderer::emulator::MeshData;
+
+use crate::prelude::*;
+
+/// The width, height and depth of a chunk section in blocks.
+pub const SECTION_SIZE: i32 = 16;
+
+/// The largest radius in chunks of a [`ChunkGridOverlay`]. Larger radii are clamped.
+pub const MAX_CHUNK_GRID_RADIUS: u32 = 32;
+
+/// Vertical lines along the corners of all chunks around a center chunk and horizontal lines along
+/// the section boundaries of the center chunk.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ChunkGridOverlay {
+    /// The x and z coordinate of the center chunk in chunks.
+    pub center_chunk: Vec2i32,
+
+    /// The number of chunks drawn around the center chunk in each direction.
+    pub radius: u32,
+
+    /// The lowest and highest block y coordinate covered by the vertical lines.
+    pub min_y: i32,
+    pub max_y: i32,
+
+    /// The rgba color of the chunk corner lines.
+    pub color: [u8; 4],
+
+    /// The rgba color of the section boundaries of the center chunk.
+    pub section_color: [u8; 4],
+}
+
+/// A host provided scalar value of a chunk section.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SectionValue {
+    /// The position of the section in sections.
+    pub section: Vec3i32,
+    pub value: f32,
+}
+
+/// Outlines sections in a color mapped from a host provided value, for example the light level or
+/// the number of block entities of the section.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SectionHeatmap {
+    pub values: Vec<SectionValue>,
+
+    /// The values mapped to the coldest and hottest color. Values outside of the range are clamped.
+    pub min_value: f32,
+    pub max_value: f32,
+
+    /// The alpha of all outlines.
+    pub alpha: u8,
+}
+
+impl SectionHeatmap {
+    /// Returns the color of a value. Values which are not finite return [`None`] and are not drawn.
+    pub fn get_color(&self, value: f32) -> Option<[u8; 4]> {
+        if !value.is_finite() {
+            return None;
+        }
+        let range = self.max_value - self.min_value;
+        let t = if range > 0f32 { (value - self.min_value) / range } else { 0f32 };
+        let [r, g, b] = heatmap_color(t);
+        Some([r, g, b, self.alpha])
+    }
+}
+
+/// Maps a value in the range [0, 1] to a color going from blue through cyan, green and yellow to
+/// red. Values outside of the range are clamped.
+pub fn heatmap_color(t: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [
+        [0f32, 0f32, 1f32],
+        [0f32, 1f32, 1f32],
+        [0f32, 1f32, 0f32],
+        [1f32, 1f32, 0f32],
+        [1f32, 0f32, 0f32],
+    ];
+
+    let t = if t.is_finite() { t.clamp(0f32, 1f32) } else { 0f32 };
+    let scaled = t * ((STOPS.len() - 1) as f32);
+    let index = (scaled.floor() as usize).min(STOPS.len() - 2);
+    let fraction = scaled - (index as f32);
+
+    let from = STOPS[index];
+    let to = STOPS[index + 1];
+    [0, 1, 2].map(|i| ((from[i] + (to[i] - from[i]) * fraction) * 255f32).round() as u8)
+}
+
+/// The debug overlays drawn at the end of the main frame.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DebugOverlays {
+    pub origin_block: Vec3i32,
+    pub origin_offset: Vec3f32,
+    pub chunk_grid: Option<ChunkGridOverlay>,
+    pub heatmap: Option<SectionHeatmap>,
+}
+
+impl DebugOverlays {
+    pub fn new() -> Self {
+        Self {
+            origin_block: Vec3i32::zeros(),
+            origin_offset: Vec3f32::zeros(),
+            chunk_grid: None,
+            heatmap: None,
+        }
+    }
+
+    /// Returns true if no overlay is enabled.
+    pub fn is_empty(&self) -> bool {
+        self.chunk_grid.is_none() && self.heatmap.as_ref().map_or(true, |heatmap| heatmap.values.is_empty())
+    }
+}
+
+impl Default for DebugOverlays {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates the lines of [`DebugOverlays`] as a line list mesh using 32bit indices.
+pub struct DebugOverlayBatch {
+    format: VertexFormat,
+    origin_block: Vec3i32,
+    origin_offset: Vec3f32,
+    vertex_data: Vec<u8>,
+    index_data: Vec<u8>,
+    vertex_count: u32,
+}
+
+impl DebugOverlayBatch {
+    /// Creates a new batch for a vertex format. All positions are made relative to the origin.
+    ///
+    /// Panics if the vertex format is not supported. See [`DebugOverlayBatch::supports_format`].
+    pub fn new(format: &VertexFormat, origin_block: Vec3i32, origin_offset: Vec3f32) -> Self {
+        if !Self::supports_format(format) {
+            log::error!("Vertex format {:?} cannot be used to draw debug overlays", format);
+            panic!();
+        }
+
+        Self {
+            format: *format,
+            origin_block,
+            origin_offset,
+            vertex_data: Vec::new(),
+            index_data: Vec::new(),
+            vertex_count: 0,
+        }
+    }
+
+    /// Returns true if the vertex format stores the position and color in a format which can be
+    /// written by the batch.
+    pub fn supports_format(format: &VertexFormat) -> bool {
+        if format.position_quantization.is_some() || format.position.format != vk::Format::R32G32B32_SFLOAT {
+            return false;
+        }
+        matches!(&format.color, Some(color) if color.format == vk::Format::R8G8B8A8_UNORM)
+    }
+
+    /// Creates a batch containing all enabled overlays.
+    pub fn from_overlays(format: &VertexFormat, overlays: &DebugOverlays) -> Self {
+        let mut batch = Self::new(format, overlays.origin_block, overlays.origin_offset);
+        if let Some(grid) = &overlays.chunk_grid {
+            batch.push_chunk_grid(grid);
+        }
+        if let Some(heatmap) = &overlays.heatmap {
+            batch.push_heatmap(heatmap);
+        }
+        batch
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index_data.is_empty()
+    }
+
+    /// Returns the number of lines pushed so far.
+    pub fn get_line_count(&self) -> u32 {
+        self.vertex_count / 2
+    }
+
+    /// Adds a line between two block positions.
+    pub fn push_line(&mut self, from: Vec3i32, to: Vec3i32, color: [u8; 4]) {
+        let from = self.make_position(from);
+        let to = self.make_position(to);
+        self.push_vertex(from, color);
+        self.push_vertex(to, color);
+    }
+
+    /// Adds the 12 edges of an axis aligned box given by its minimum and maximum block corner.
+    pub fn push_box(&mut self, min: Vec3i32, max: Vec3i32, color: [u8; 4]) {
+        let corner = |x: bool, y: bool, z: bool| Vec3i32::new(
+            if x { max[0] } else { min[0] },
+            if y { max[1] } else { min[1] },
+            if z { max[2] } else { min[2] }
+        );
+
+        for a in [false, true] {
+            for b in [false, true] {
+                self.push_line(corner(false, a, b), corner(true, a, b), color);
+                self.push_line(corner(a, false, b), corner(a, true, b), color);
+                self.push_line(corner(a, b, false), corner(a, b, true), color);
+            }
+        }
+    }
+
+    /// Adds the lines of a chunk grid. The radius is clamped to [`MAX_CHUNK_GRID_RADIUS`].
+    pub fn push_chunk_grid(&mut self, grid: &ChunkGridOverlay) {
+        if grid.max_y <= grid.min_y {
+            return;
+        }
+        let radius = grid.radius.min(MAX_CHUNK_GRID_RADIUS) as i32;
+
+        for x in (grid.center_chunk[0] - radius)..=(grid.center_chunk[0] + radius + 1) {
+            for z in (grid.center_chunk[1] - radius)..=(grid.center_chunk[1] + radius + 1) {
+                let (x, z) = (x * SECTION_SIZE, z * SECTION_SIZE);
+                self.push_line(Vec3i32::new(x, grid.min_y, z), Vec3i32::new(x, grid.max_y, z), grid.color);
+            }
+        }
+
+        let min_x = grid.center_chunk[0] * SECTION_SIZE;
+        let min_z = grid.center_chunk[1] * SECTION_SIZE;
+        let (max_x, max_z) = (min_x + SECTION_SIZE, min_z + SECTION_SIZE);
+
+        let mut y = grid.min_y.div_euclid(SECTION_SIZE) * SECTION_SIZE;
+        while y <= grid.max_y {
+            if y >= grid.min_y {
+                let corners = [
+                    Vec3i32::new(min_x, y, min_z),
+                    Vec3i32::new(max_x, y, min_z),
+                    Vec3i32::new(max_x, y, max_z),
+                    Vec3i32::new(min_x, y, max_z),
+                ];
+                for i in 0..4 {
+                    self.push_line(corners[i], corners[(i + 1) % 4], grid.section_color);
+                }
+            }
+            y += SECTION_SIZE;
+        }
+    }
+
+    /// Adds the outlines of all sections of a heatmap.
+    pub fn push_heatmap(&mut self, heatmap: &SectionHeatmap) {
+        for value in &heatmap.values {
+            if let Some(color) = heatmap.get_color(value.value) {
+                let min = value.section * SECTION_SIZE;
+                let max = min.add_scalar(SECTION_SIZE);
+                self.push_box(min, max, color);
+            }
+        }
+    }
+
+    /// Returns the mesh containing all lines pushed so far.
+    pub fn as_mesh_data(&self) -> MeshData {
+        MeshData {
+            vertex_data: &self.vertex_data,
+            index_data: &self.index_data,
+            vertex_stride: self.format.stride,
+            index_count: self.vertex_count,
+            index_type: vk::IndexType::UINT32,
+            primitive_topology: vk::PrimitiveTopology::LINE_LIST,
+        }
+    }
+
+    fn make_position(&self, block: Vec3i32) -> [f32; 3] {
+        let relative = block - self.origin_block;
+        [0, 1, 2].map(|i| (relative[i] as f32) - self.origin_offset[i])
+    }
+
+    fn push_vertex(&mut self, position: [f32; 3], color: [u8; 4]) {
+        let base = self.vertex_data.len();
+        self.vertex_data.resize(base + (self.format.stride as usize), 0u8);
+        let vertex = &mut self.vertex_data[base..];
+
+        let offset = self.format.position.offset as usize;
+        for (i, value) in position.iter().enumerate() {
+            vertex[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        let offset = self.format.color.as_ref().unwrap().offset as usize;
+        vertex[offset..(offset + 4)].copy_from_slice(&color);
+
+        self.index_data.extend_from_slice(&self.vertex_count.to_ne_bytes());
+        self.vertex_count += 1;
+    }
+}
@@ -11,12 +11,17 @@ use bytemuck::{bytes_of, cast_slice, Pod, Zeroable};
 use include_bytes_aligned::include_bytes_aligned;
 use crate::allocator::Allocation;
 use crate::device::device::Queue;
+use crate::device::device_group::DeviceGroupMode;
 use crate::device::device_utils::create_shader_from_bytes;
 
 use crate::prelude::*;
 use crate::renderer::emulator::EmulatorRenderer;
-use crate::renderer::emulator::mc_shaders::{McUniform, McUniformData, ShaderDropListener, ShaderId, ShaderListener, VertexFormat, VertexFormatEntry};
-use crate::renderer::emulator::pipeline::{DrawTask, EmulatorPipeline, EmulatorPipelinePass, PipelineTask, PooledObjectProvider, SubmitRecorder};
+use crate::renderer::emulator::draw_tag::make_debug_label;
+use crate::renderer::emulator::mc_shaders::{AlphaMode, McUniform, McUniformData, ShaderDropListener, ShaderId, ShaderListener, ShaderSpecialization, VertexFormat, VertexFormatEntry};
+use crate::renderer::emulator::quantization::NormalEncoding;
+use crate::renderer::emulator::pass::{MAX_TEXTURE_SLOTS, MAX_VIEWPORTS};
+use crate::renderer::emulator::pipeline::{DrawTask, EmulatorPipeline, EmulatorPipelinePass, PipelineTask, PooledObjectProvider, SubmitRecorder, TextureBinding};
+use crate::renderer::emulator::shadow::{compute_cascades, transform_aabb, CameraFrustum, CascadeConfig, ShadowCascade, ShadowUniforms, MAX_CASCADES};
 use crate::util::vk::{make_full_rect, make_full_viewport};
 
 pub struct DepthTypeInfo {
@@ -54,6 +59,20 @@ pub enum DebugPipelineMode {
     Textured2,
 }
 
+/// How the debug pipeline fetches vertex data.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum VertexFetchMode {
+    /// Vertex data is fetched by the vertex input state. Every vertex format needs its own vertex
+    /// input state and its formats must support [`vk::FormatFeatureFlags::VERTEX_BUFFER`].
+    InputAssembly,
+
+    /// Vertex data is fetched in the vertex shader from a storage buffer. The layout of the
+    /// vertex format is passed as push constants so the vertex input state is empty and shared
+    /// by all vertex formats. Only 8, 16 and 32 bit per component formats with 1 to 4 components
+    /// can be decoded. Unsupported attributes are treated as missing.
+    Pulling,
+}
+
 /// A [`EmulatorPipeline`] which provides debug information.
 ///
 /// The following outputs are supported:
@@ -70,13 +89,26 @@ pub struct DebugPipeline {
     weak: Weak<Self>,
 
     framebuffer_size: Vec2u32,
+    depth_format: vk::Format,
 
     shader_modules: ShaderModules,
+    fetch_mode: VertexFetchMode,
     render_pass: vk::RenderPass,
     draw_pipeline: DrawPipeline,
     background_pipeline: BackgroundPipeline,
+    shadow_pipeline: ShadowPipeline,
+    shadow_config: CascadeConfig,
     descriptor_pool: vk::DescriptorPool,
 
+    /// The fragment output interface library used to link pipelines if
+    /// VK_EXT_graphics_pipeline_library is supported. It is shared by all shaders.
+    output_library: Option<vk::Pipeline>,
+
+    /// Vertex input interface libraries keyed by (primitive topology, primitive restart) used if
+    /// vertex pulling is enabled. Since the vertex input state is empty they are shared by all
+    /// shaders.
+    pulled_vertex_input: Mutex<HashMap<(vk::PrimitiveTopology, bool), vk::Pipeline>>,
+
     pipelines: Mutex<HashMap<ShaderId, ShaderPipelines>>,
     next_index: AtomicUsize,
     pass_objects: Box<[PassObjects]>,
@@ -86,12 +118,17 @@ assert_impl_all!(DebugPipeline: Send, Sync);
 
 impl DebugPipeline {
     pub fn new(emulator: Arc<EmulatorRenderer>, mode: DebugPipelineMode, framebuffer_size: Vec2u32) -> Result<Arc<Self>, ObjectCreateError> {
-        let concurrent_passes = 2usize;
-        let depth_format = vk::Format::D32_SFLOAT;
+        Self::new_with_fetch_mode(emulator, mode, VertexFetchMode::InputAssembly, framebuffer_size)
+    }
 
+    pub fn new_with_fetch_mode(emulator: Arc<EmulatorRenderer>, mode: DebugPipelineMode, fetch_mode: VertexFetchMode, framebuffer_size: Vec2u32) -> Result<Arc<Self>, ObjectCreateError> {
+        // Pass objects are only reused once the pass using them completed so there is no point in
+        // having more than the emulator can keep in flight
+        let concurrent_passes = emulator.get_frames_in_flight() as usize;
         let device = emulator.get_device();
+        let depth_format = Self::select_depth_format(device);
 
-        let mut shader_modules = ShaderModules::new(device, mode)?;
+        let mut shader_modules = ShaderModules::new(device, mode, fetch_mode)?;
 
         let render_pass = match Self::create_render_pass(&device, depth_format) {
             Ok(render_pass) => render_pass,
@@ -101,7 +138,7 @@ impl DebugPipeline {
             }
         };
 
-        let mut draw_pipeline = match DrawPipeline::new(device) {
+        let mut draw_pipeline = match DrawPipeline::new(device, fetch_mode) {
             Ok(pipeline) => pipeline,
             Err(err) => {
                 unsafe { device.vk().destroy_render_pass(render_pass, None) };
@@ -120,9 +157,22 @@ impl DebugPipeline {
             }
         };
 
+        let shadow_config = CascadeConfig::default();
+        let mut shadow_pipeline = match ShadowPipeline::new(device, shadow_config.resolution) {
+            Ok(pipeline) => pipeline,
+            Err(err) => {
+                background_pipeline.destroy(device);
+                draw_pipeline.destroy(device);
+                unsafe { device.vk().destroy_render_pass(render_pass, None) };
+                shader_modules.destroy(device);
+                return Err(err);
+            }
+        };
+
         let descriptor_pool = match Self::create_descriptor_pool(device, concurrent_passes) {
             Ok(pool) => pool,
             Err(err) => {
+                shadow_pipeline.destroy(device);
                 background_pipeline.destroy(device);
                 draw_pipeline.destroy(device);
                 unsafe { device.vk().destroy_render_pass(render_pass, None) };
@@ -131,6 +181,23 @@ impl DebugPipeline {
             }
         };
 
+        let output_library = if device.supports_graphics_pipeline_library() {
+            match Self::create_output_library(device, render_pass) {
+                Ok(library) => Some(library),
+                Err(err) => {
+                    unsafe { device.vk().destroy_descriptor_pool(descriptor_pool, None) };
+                    shadow_pipeline.destroy(device);
+                    background_pipeline.destroy(device);
+                    draw_pipeline.destroy(device);
+                    unsafe { device.vk().destroy_render_pass(render_pass, None) };
+                    shader_modules.destroy(device);
+                    return Err(err);
+                }
+            }
+        } else {
+            None
+        };
+
         let layouts: Box<[_]> = std::iter::repeat(background_pipeline.descriptor_set_layout).take(concurrent_passes).collect();
         let info = vk::DescriptorSetAllocateInfo::builder()
             .descriptor_pool(descriptor_pool)
@@ -141,7 +208,9 @@ impl DebugPipeline {
         } {
             Ok(layouts) => layouts,
             Err(err) => {
+                Self::destroy_output_library(device, output_library);
                 unsafe { device.vk().destroy_descriptor_pool(descriptor_pool, None) };
+                shadow_pipeline.destroy(device);
                 background_pipeline.destroy(device);
                 draw_pipeline.destroy(device);
                 unsafe { device.vk().destroy_render_pass(render_pass, None) };
@@ -152,13 +221,15 @@ impl DebugPipeline {
 
         let mut pass_objects: Vec<PassObjects> = Vec::with_capacity(layouts.len());
         for descriptor_set in descriptor_sets {
-            let objects = match PassObjects::new(device, framebuffer_size, depth_format, vk::Format::R8G8B8A8_SRGB, render_pass, descriptor_set) {
+            let objects = match PassObjects::new(device, framebuffer_size, depth_format, vk::Format::R8G8B8A8_SRGB, render_pass, descriptor_set, &shadow_pipeline) {
                 Ok(objects) => objects,
                 Err(err) => {
                     for mut pass_object in pass_objects {
                         pass_object.destroy(device);
                     }
+                    Self::destroy_output_library(device, output_library);
                     unsafe { device.vk().destroy_descriptor_pool(descriptor_pool, None) };
+                    shadow_pipeline.destroy(device);
                     background_pipeline.destroy(device);
                     draw_pipeline.destroy(device);
                     unsafe { device.vk().destroy_render_pass(render_pass, None) };
@@ -182,13 +253,21 @@ impl DebugPipeline {
                 weak: weak.clone(),
 
                 framebuffer_size,
+                depth_format,
 
                 shader_modules,
+                fetch_mode,
                 render_pass,
                 draw_pipeline,
                 background_pipeline,
+                shadow_pipeline,
+                shadow_config,
                 descriptor_pool,
 
+                output_library,
+
+                pulled_vertex_input: Mutex::new(HashMap::new()),
+
                 pipelines: Mutex::new(HashMap::new()),
                 next_index: AtomicUsize::new(0),
                 pass_objects,
@@ -197,6 +276,23 @@ impl DebugPipeline {
         }))
     }
 
+    /// Selects the format of the depth attachment. A format with a stencil aspect is needed to
+    /// mask portals. If no such format can be sampled portals are drawn without a mask.
+    fn select_depth_format(device: &DeviceContext) -> vk::Format {
+        let required = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+        let format = [vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D24_UNORM_S8_UINT].into_iter().find(|format| {
+            let properties = unsafe {
+                device.get_instance().vk().get_physical_device_format_properties(device.get_functions().physical_device, *format)
+            };
+            properties.optimal_tiling_features.contains(required)
+        });
+
+        format.unwrap_or_else(|| {
+            log::warn!("No sampled depth stencil format is supported. Portals will not be masked");
+            vk::Format::D32_SFLOAT
+        })
+    }
+
     /// Returns the next index to be used for a pass and increments the internal counter.
     fn next_index(&self) -> usize {
         loop {
@@ -217,31 +313,211 @@ impl DebugPipeline {
             panic!()
         });
 
-        pipelines.get_or_create_pipeline(config, |format| self.create_pipeline(config, format))
+        // The output library always writes color. Portal pipelines are rare so they are created as
+        // complete pipelines instead of needing a second output library.
+        pipelines.get_or_create_pipeline(config, |pipelines| {
+            match self.output_library {
+                Some(output_library) if config.stencil_mode.writes_color() => self.link_pipeline(config, pipelines, output_library),
+                Some(_) => self.create_pipeline(config, &pipelines.vertex_format, &pipelines.specialization, None),
+                None => self.create_pipeline(config, &pipelines.vertex_format, &pipelines.specialization, pipelines.base_pipeline),
+            }
+        })
+    }
+
+    /// Links a new pipeline from pipeline libraries. Any library of the shader that does not exist
+    /// yet is created and cached in `pipelines` so that later state combinations only need to
+    /// link.
+    ///
+    /// Link time optimization is not requested since the goal is to avoid hitches when a new
+    /// state combination is first used.
+    fn link_pipeline(&self, config: &PipelineConfig, pipelines: &mut ShaderPipelines, output_library: vk::Pipeline) -> vk::Pipeline {
+        let vertex_input = match pipelines.libraries.vertex_input.get(&config.primitive_topology) {
+            Some(library) => *library,
+            None if self.fetch_mode == VertexFetchMode::Pulling => {
+                let key = (config.primitive_topology, pipelines.specialization.resolve_primitive_restart(config.primitive_topology));
+                let mut guard = self.pulled_vertex_input.lock().unwrap();
+                *guard.entry(key).or_insert_with(|| {
+                    self.create_vertex_input_library(config, &pipelines.vertex_format, &pipelines.specialization)
+                })
+            }
+            None => {
+                let library = self.create_vertex_input_library(config, &pipelines.vertex_format, &pipelines.specialization);
+                pipelines.libraries.vertex_input.insert(config.primitive_topology, library);
+                library
+            }
+        };
+
+        let pre_rasterization = match pipelines.libraries.pre_rasterization {
+            Some(library) => library,
+            None => {
+                let library = self.create_pre_rasterization_library(&pipelines.vertex_format, &pipelines.specialization);
+                pipelines.libraries.pre_rasterization = Some(library);
+                library
+            }
+        };
+
+        let depth_key = (config.depth_test_enable, config.depth_write_enable);
+        let fragment_shader = match pipelines.libraries.fragment_shader.get(&depth_key) {
+            Some(library) => *library,
+            None => {
+                let library = self.create_fragment_shader_library(config, &pipelines.vertex_format, &pipelines.specialization);
+                pipelines.libraries.fragment_shader.insert(depth_key, library);
+                library
+            }
+        };
+
+        let libraries = [vertex_input, pre_rasterization, fragment_shader, output_library];
+        let mut library_info = vk::PipelineLibraryCreateInfoKHR::builder()
+            .libraries(&libraries);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .layout(self.draw_pipeline.pipeline_layout)
+            .push_next(&mut library_info);
+
+        self.create_graphics_pipeline(&info)
     }
 
-    fn create_pipeline(&self, config: &PipelineConfig, vertex_format: &VertexFormat) -> vk::Pipeline {
+    /// Creates the vertex input interface library for a topology.
+    fn create_vertex_input_library(&self, config: &PipelineConfig, vertex_format: &VertexFormat, specialization: &ShaderSpecialization) -> vk::Pipeline {
         let alloc = Bump::new();
-        let (shader_stages, input_state) = self.shader_modules.configure_pipeline(vertex_format, &alloc);
+        let (_, input_state) = self.shader_modules.configure_pipeline(vertex_format, specialization, &alloc);
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(config.primitive_topology)
+            .primitive_restart_enable(specialization.resolve_primitive_restart(config.primitive_topology));
+
+        let mut library_info = vk::GraphicsPipelineLibraryCreateInfoEXT::builder()
+            .flags(vk::GraphicsPipelineLibraryFlagsEXT::VERTEX_INPUT_INTERFACE);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .flags(vk::PipelineCreateFlags::LIBRARY_KHR)
+            .vertex_input_state(input_state)
+            .input_assembly_state(&input_assembly_state)
+            .push_next(&mut library_info);
 
-        let viewport = make_full_viewport(self.framebuffer_size);
-        let scissor = make_full_rect(self.framebuffer_size);
+        self.create_graphics_pipeline(&info)
+    }
+
+    /// Creates the pre-rasterization shaders library containing the vertex shader.
+    fn create_pre_rasterization_library(&self, vertex_format: &VertexFormat, specialization: &ShaderSpecialization) -> vk::Pipeline {
+        let alloc = Bump::new();
+        let (shader_stages, _) = self.shader_modules.configure_pipeline(vertex_format, specialization, &alloc);
 
+        // Viewport and scissor are dynamic to allow rendering to multiple viewports in one pass
         let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(std::slice::from_ref(&viewport))
-            .scissors(std::slice::from_ref(&scissor));
+            .viewport_count(1)
+            .scissor_count(1);
 
         let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
-            .polygon_mode(vk::PolygonMode::FILL)
+            .polygon_mode(specialization.polygon_mode)
             .cull_mode(vk::CullModeFlags::BACK)
             .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
             .line_width(1f32);
 
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&PRE_RASTERIZATION_DYNAMIC_STATES);
+
+        let mut library_info = vk::GraphicsPipelineLibraryCreateInfoEXT::builder()
+            .flags(vk::GraphicsPipelineLibraryFlagsEXT::PRE_RASTERIZATION_SHADERS);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .flags(vk::PipelineCreateFlags::LIBRARY_KHR)
+            .stages(&shader_stages[0..1])
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .dynamic_state(&dynamic_state)
+            .layout(self.draw_pipeline.pipeline_layout)
+            .render_pass(self.render_pass)
+            .subpass(0)
+            .push_next(&mut library_info);
+
+        self.create_graphics_pipeline(&info)
+    }
+
+    /// Creates the fragment shader library for a depth configuration.
+    fn create_fragment_shader_library(&self, config: &PipelineConfig, vertex_format: &VertexFormat, specialization: &ShaderSpecialization) -> vk::Pipeline {
+        let alloc = Bump::new();
+        let (shader_stages, _) = self.shader_modules.configure_pipeline(vertex_format, specialization, &alloc);
+
         let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(RASTERIZATION_SAMPLES)
+            .sample_shading_enable(false)
+            .alpha_to_coverage_enable(specialization.resolve_alpha_mode(RASTERIZATION_SAMPLES) == AlphaMode::Coverage);
+
+        let stencil_op_state = config.make_stencil_op_state();
+        let depth_stencil_state = config.make_depth_stencil_state(&stencil_op_state);
+
+        // The stencil reference is the portal level which changes within a pass
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&FRAGMENT_DYNAMIC_STATES);
+
+        let mut library_info = vk::GraphicsPipelineLibraryCreateInfoEXT::builder()
+            .flags(vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_SHADER);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .flags(vk::PipelineCreateFlags::LIBRARY_KHR)
+            .stages(&shader_stages[1..2])
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .dynamic_state(&dynamic_state)
+            .layout(self.draw_pipeline.pipeline_layout)
+            .render_pass(self.render_pass)
+            .subpass(0)
+            .push_next(&mut library_info);
+
+        self.create_graphics_pipeline(&info)
+    }
+
+    fn create_graphics_pipeline(&self, info: &vk::GraphicsPipelineCreateInfo) -> vk::Pipeline {
+        *unsafe {
+            self.emulator.get_device().vk().create_graphics_pipelines(self.emulator.get_device().get_pipeline_cache(), std::slice::from_ref(info), None)
+        }.unwrap_or_else(|(_, err)| {
+            log::error!("Failed to create graphics pipeline {:?}", err);
+            panic!();
+        }).get(0).unwrap()
+    }
+
+    /// Creates the fragment output interface library. Since all shaders use the same blend state
+    /// and render pass only one is needed.
+    ///
+    /// Alpha to coverage is never enabled here since it is only resolved to for multisampled
+    /// pipelines which the debug pipeline does not create.
+    fn create_output_library(device: &DeviceContext, render_pass: vk::RenderPass) -> Result<vk::Pipeline, ObjectCreateError> {
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(RASTERIZATION_SAMPLES)
             .sample_shading_enable(false);
 
-        let attachment_blend_state = [
+        let attachment_blend_state = Self::make_attachment_blend_state();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .attachments(&attachment_blend_state);
+
+        let mut library_info = vk::GraphicsPipelineLibraryCreateInfoEXT::builder()
+            .flags(vk::GraphicsPipelineLibraryFlagsEXT::FRAGMENT_OUTPUT_INTERFACE);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .flags(vk::PipelineCreateFlags::LIBRARY_KHR)
+            .multisample_state(&multisample_state)
+            .color_blend_state(&color_blend_state)
+            .render_pass(render_pass)
+            .subpass(0)
+            .push_next(&mut library_info);
+
+        let pipeline = *unsafe {
+            device.vk().create_graphics_pipelines(device.get_pipeline_cache(), std::slice::from_ref(&info), None)
+        }.map_err(|(_, err)| err)?.get(0).unwrap();
+
+        Ok(pipeline)
+    }
+
+    fn destroy_output_library(device: &DeviceContext, output_library: Option<vk::Pipeline>) {
+        if let Some(library) = output_library {
+            unsafe { device.vk().destroy_pipeline(library, None) };
+        }
+    }
+
+    fn make_attachment_blend_state() -> [vk::PipelineColorBlendAttachmentState; 1] {
+        [
             vk::PipelineColorBlendAttachmentState::builder()
                 .blend_enable(true)
                 .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
@@ -252,24 +528,58 @@ impl DebugPipeline {
                 .color_blend_op(vk::BlendOp::ADD)
                 .color_write_mask(vk::ColorComponentFlags::RGBA)
                 .build(),
-        ];
+        ]
+    }
+
+    /// Creates a new pipeline. If `base_pipeline` is provided the new pipeline is created as a
+    /// derivative of it which allows drivers to reuse compilation results of the base pipeline.
+    fn create_pipeline(&self, config: &PipelineConfig, vertex_format: &VertexFormat, specialization: &ShaderSpecialization, base_pipeline: Option<vk::Pipeline>) -> vk::Pipeline {
+        let alloc = Bump::new();
+        let (shader_stages, input_state) = self.shader_modules.configure_pipeline(vertex_format, specialization, &alloc);
+
+        // Viewport and scissor are dynamic to allow rendering to multiple viewports in one pass
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewport_count(1)
+            .scissor_count(1);
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(specialization.polygon_mode)
+            .cull_mode(vk::CullModeFlags::BACK)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .line_width(1f32);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(RASTERIZATION_SAMPLES)
+            .sample_shading_enable(false)
+            .alpha_to_coverage_enable(specialization.resolve_alpha_mode(RASTERIZATION_SAMPLES) == AlphaMode::Coverage);
+
+        let mut attachment_blend_state = Self::make_attachment_blend_state();
+        if !config.stencil_mode.writes_color() {
+            attachment_blend_state[0].color_write_mask = vk::ColorComponentFlags::empty();
+        }
 
         let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .attachments(&attachment_blend_state);
 
-        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder();
+        // The stencil reference is dynamic since it is the portal level which changes within a pass
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&DYNAMIC_STATES);
 
         let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
             .topology(config.primitive_topology)
-            .primitive_restart_enable(false);
+            .primitive_restart_enable(specialization.resolve_primitive_restart(config.primitive_topology));
 
-        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
-            .depth_test_enable(config.depth_test_enable)
-            .depth_write_enable(config.depth_write_enable)
-            .depth_compare_op(vk::CompareOp::LESS);
+        let stencil_op_state = config.make_stencil_op_state();
+        let depth_stencil_state = config.make_depth_stencil_state(&stencil_op_state);
+
+        let (flags, base_pipeline) = match base_pipeline {
+            Some(base) => (vk::PipelineCreateFlags::DERIVATIVE, base),
+            None => (vk::PipelineCreateFlags::ALLOW_DERIVATIVES, vk::Pipeline::null()),
+        };
 
         let info = vk::GraphicsPipelineCreateInfo::builder()
+            .flags(flags)
             .stages(shader_stages)
             .vertex_input_state(input_state)
             .input_assembly_state(&input_assembly_state)
@@ -281,16 +591,11 @@ impl DebugPipeline {
             .dynamic_state(&dynamic_state)
             .layout(self.draw_pipeline.pipeline_layout)
             .render_pass(self.render_pass)
-            .subpass(0);
+            .subpass(0)
+            .base_pipeline_handle(base_pipeline)
+            .base_pipeline_index(-1);
 
-        let pipeline = *unsafe {
-            self.emulator.get_device().vk().create_graphics_pipelines(vk::PipelineCache::null(), std::slice::from_ref(&info), None)
-        }.unwrap_or_else(|(_, err)| {
-            log::error!("Failed to create graphics pipeline {:?}", err);
-            panic!();
-        }).get(0).unwrap();
-
-        pipeline
+        self.create_graphics_pipeline(&info)
     }
 
     fn create_render_pass(device: &DeviceContext, depth_format: vk::Format) -> Result<vk::RenderPass, ObjectCreateError> {
@@ -300,6 +605,8 @@ impl DebugPipeline {
                 .samples(vk::SampleCountFlags::TYPE_1)
                 .load_op(vk::AttachmentLoadOp::CLEAR)
                 .store_op(vk::AttachmentStoreOp::STORE)
+                .stencil_load_op(vk::AttachmentLoadOp::CLEAR)
+                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
                 .initial_layout(vk::ImageLayout::UNDEFINED)
                 .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                 .build(),
@@ -442,8 +749,14 @@ impl EmulatorPipeline for DebugPipeline {
             let shader_obj = self.emulator.get_shader(shader).unwrap();
             let vertex_format = shader_obj.get_vertex_format().clone();
             let used_uniforms = shader_obj.get_used_uniforms();
+            let specialization = *shader_obj.get_specialization();
+
+            let fetch_constants = match self.fetch_mode {
+                VertexFetchMode::InputAssembly => None,
+                VertexFetchMode::Pulling => Some(self.shader_modules.make_fetch_constants(&vertex_format)),
+            };
 
-            let mut  pipelines = ShaderPipelines::new(self.emulator.get_device().clone(), vertex_format, used_uniforms, listener);
+            let mut  pipelines = ShaderPipelines::new(self.emulator.get_device().clone(), vertex_format, used_uniforms, specialization, fetch_constants, listener);
             pipelines.inc_used();
 
             guard.insert(shader, pipelines);
@@ -485,9 +798,16 @@ impl Drop for DebugPipeline {
             objects.destroy(device);
         }
         self.pipelines.get_mut().unwrap().clear();
+        for library in self.pulled_vertex_input.get_mut().unwrap().drain().map(|(_, library)| library) {
+            unsafe {
+                device.vk().destroy_pipeline(library, None);
+            }
+        }
+        Self::destroy_output_library(device, self.output_library.take());
         unsafe {
             device.vk().destroy_descriptor_pool(self.descriptor_pool, None);
         }
+        self.shadow_pipeline.destroy(device);
         self.background_pipeline.destroy(device);
         self.draw_pipeline.destroy(device);
         unsafe {
@@ -500,6 +820,7 @@ impl Drop for DebugPipeline {
 /// The shader modules needed to create vulkan pipelines for the debug pipeline
 struct ShaderModules {
     mode: DebugPipelineMode,
+    fetch_mode: VertexFetchMode,
     vertex_module: vk::ShaderModule,
     null_module: vk::ShaderModule,
     fragment_module: vk::ShaderModule,
@@ -507,7 +828,7 @@ struct ShaderModules {
 }
 
 impl ShaderModules {
-    fn new(device: &DeviceContext, mode: DebugPipelineMode) -> Result<Self, ObjectCreateError> {
+    fn new(device: &DeviceContext, mode: DebugPipelineMode, fetch_mode: VertexFetchMode) -> Result<Self, ObjectCreateError> {
         let null_module = try_create_shader_module(device, DEBUG_NULL_VERTEX_BIN, "null_vertex")?;
 
         let fragment_module = try_create_shader_module(device, DEBUG_FRAGMENT_BIN, "fragment").map_err(|err| {
@@ -515,17 +836,18 @@ impl ShaderModules {
             err
         })?;
 
-        let vertex_module = match mode {
-            DebugPipelineMode::Depth => try_create_shader_module(device, DEBUG_POSITION_VERTEX_BIN, "position_vertex"),
-            DebugPipelineMode::Position => try_create_shader_module(device, DEBUG_POSITION_VERTEX_BIN, "position_vertex"),
-            DebugPipelineMode::Color => try_create_shader_module(device, DEBUG_COLOR_VERTEX_BIN, "color_vertex"),
-            DebugPipelineMode::Normal => { todo!() }
-            DebugPipelineMode::UV0 |
-            DebugPipelineMode::UV1 |
-            DebugPipelineMode::UV2 |
-            DebugPipelineMode::Textured0 |
-            DebugPipelineMode::Textured1 |
-            DebugPipelineMode::Textured2 => try_create_shader_module(device, DEBUG_UV_VERTEX_BIN, "uv_vertex"),
+        let vertex_module = match (fetch_mode, mode) {
+            (VertexFetchMode::Pulling, _) => try_create_shader_module(device, DEBUG_PULLED_VERTEX_BIN, "pulled_vertex"),
+            (_, DebugPipelineMode::Depth) => try_create_shader_module(device, DEBUG_POSITION_VERTEX_BIN, "position_vertex"),
+            (_, DebugPipelineMode::Position) => try_create_shader_module(device, DEBUG_POSITION_VERTEX_BIN, "position_vertex"),
+            (_, DebugPipelineMode::Color) => try_create_shader_module(device, DEBUG_COLOR_VERTEX_BIN, "color_vertex"),
+            (_, DebugPipelineMode::Normal) => try_create_shader_module(device, DEBUG_NORMAL_VERTEX_BIN, "normal_vertex"),
+            (_, DebugPipelineMode::UV0) |
+            (_, DebugPipelineMode::UV1) |
+            (_, DebugPipelineMode::UV2) |
+            (_, DebugPipelineMode::Textured0) |
+            (_, DebugPipelineMode::Textured1) |
+            (_, DebugPipelineMode::Textured2) => try_create_shader_module(device, DEBUG_UV_VERTEX_BIN, "uv_vertex"),
         }.map_err(|err| {
             unsafe {
                 device.vk().destroy_shader_module(null_module, None);
@@ -548,6 +870,7 @@ impl ShaderModules {
 
         Ok(Self {
             mode,
+            fetch_mode,
             vertex_module,
             null_module,
             fragment_module,
@@ -555,7 +878,11 @@ impl ShaderModules {
         })
     }
 
-    fn configure_pipeline<'s, 'a: 's>(&'s self, vertex_format: &VertexFormat, alloc: &'a Bump) -> (&'a [vk::PipelineShaderStageCreateInfo], &'a vk::PipelineVertexInputStateCreateInfo) {
+    fn configure_pipeline<'s, 'a: 's>(&'s self, vertex_format: &VertexFormat, specialization: &ShaderSpecialization, alloc: &'a Bump) -> (&'a [vk::PipelineShaderStageCreateInfo], &'a vk::PipelineVertexInputStateCreateInfo) {
+        if self.fetch_mode == VertexFetchMode::Pulling {
+            return self.configure_pulled_pipeline(vertex_format, specialization, alloc);
+        }
+
         let input_bindings: &[_] = alloc.alloc([
             vk::VertexInputBindingDescription {
                 binding: 0,
@@ -599,45 +926,17 @@ impl ShaderModules {
             ]);
         }
 
-        let (fragment_module, fragment_specialization) = match (self.mode, vertex_format_supported) {
-            (DebugPipelineMode::Textured0, true) |
-            (DebugPipelineMode::Textured1, true) |
-            (DebugPipelineMode::Textured2, true) => {
-                let data = alloc.alloc(match self.mode {
-                    DebugPipelineMode::Textured0 => 0u32,
-                    DebugPipelineMode::Textured1 => 1u32,
-                    DebugPipelineMode::Textured2 => 2u32,
-                    _ => panic!(),
-                });
-                let entries = alloc.alloc([
-                    vk::SpecializationMapEntry {
-                        constant_id: 0,
-                        offset: 0,
-                        size: 4
-                    }
-                ]);
-                (*self.texture_module.as_ref().unwrap(), alloc.alloc(vk::SpecializationInfo::builder()
-                    .map_entries(entries)
-                    .data(bytes_of(data))
-                ))
-            }
-            _ => {
-                (self.fragment_module, alloc.alloc(vk::SpecializationInfo::builder()))
-            }
-        };
+        let fragment_stage = self.make_fragment_stage(vertex_format_supported, specialization, alloc);
+        let vertex_specialization = Self::make_vertex_specialization(vertex_format, specialization, alloc);
 
         let shader_stages: &[_] = alloc.alloc([
             vk::PipelineShaderStageCreateInfo::builder()
                 .stage(vk::ShaderStageFlags::VERTEX)
                 .module(vertex_module)
                 .name(SHADER_ENTRY)
+                .specialization_info(vertex_specialization)
                 .build(),
-            vk::PipelineShaderStageCreateInfo::builder()
-                .stage(vk::ShaderStageFlags::FRAGMENT)
-                .module(fragment_module)
-                .name(SHADER_ENTRY)
-                .specialization_info(fragment_specialization)
-                .build(),
+            fragment_stage,
         ]);
 
         let input_state: &_ = alloc.alloc(vk::PipelineVertexInputStateCreateInfo::builder()
@@ -649,6 +948,181 @@ impl ShaderModules {
         (shader_stages, input_state)
     }
 
+    /// Configures a pipeline using vertex pulling. The vertex input state is empty and the layout
+    /// of the vertex format is provided at draw time by [`VertexFetchConstants`].
+    fn configure_pulled_pipeline<'s, 'a: 's>(&'s self, vertex_format: &VertexFormat, specialization: &ShaderSpecialization, alloc: &'a Bump) -> (&'a [vk::PipelineShaderStageCreateInfo], &'a vk::PipelineVertexInputStateCreateInfo) {
+        let vertex_format_supported = self.process_vertex_format(vertex_format)
+            .and_then(|entry| VertexFetchConstants::pack_entry(entry))
+            .is_some();
+
+        let fragment_stage = self.make_fragment_stage(vertex_format_supported, specialization, alloc);
+        let vertex_specialization = self.make_pulled_vertex_specialization(vertex_format, specialization, alloc);
+
+        let shader_stages: &[_] = alloc.alloc([
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(self.vertex_module)
+                .name(SHADER_ENTRY)
+                .specialization_info(vertex_specialization)
+                .build(),
+            fragment_stage,
+        ]);
+
+        let input_state: &_ = alloc.alloc(vk::PipelineVertexInputStateCreateInfo::builder().build());
+
+        (shader_stages, input_state)
+    }
+
+    /// Creates the fragment shader stage. The textured modes fall back to the plain fragment
+    /// shader if the vertex format does not contain the needed uv attribute.
+    fn make_fragment_stage<'a>(&self, vertex_format_supported: bool, specialization: &ShaderSpecialization, alloc: &'a Bump) -> vk::PipelineShaderStageCreateInfo {
+        let (fragment_module, image_index) = match (self.mode, vertex_format_supported) {
+            (DebugPipelineMode::Textured0, true) => (*self.texture_module.as_ref().unwrap(), 0u32),
+            (DebugPipelineMode::Textured1, true) => (*self.texture_module.as_ref().unwrap(), 1u32),
+            (DebugPipelineMode::Textured2, true) => (*self.texture_module.as_ref().unwrap(), 2u32),
+            _ => (self.fragment_module, 0u32),
+        };
+        let fragment_specialization = Self::make_fragment_specialization(image_index, specialization, alloc);
+
+        vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::FRAGMENT)
+            .module(fragment_module)
+            .name(SHADER_ENTRY)
+            .specialization_info(fragment_specialization)
+            .build()
+    }
+
+    /// Creates the specialization info for the vertex quantization and item animation constants
+    /// defined in mc_uniforms.glsl.
+    fn make_vertex_specialization<'a>(vertex_format: &VertexFormat, specialization: &ShaderSpecialization, alloc: &'a Bump) -> &'a vk::SpecializationInfo {
+        let (offset, scale) = match &vertex_format.position_quantization {
+            Some(quantization) => (quantization.offset, quantization.scale),
+            None => (Vec3f32::zeros(), Vec3f32::from_element(1f32)),
+        };
+        let octahedral = (vertex_format.normal_encoding == NormalEncoding::Octahedral) as vk::Bool32;
+
+        let data = alloc.alloc(VertexSpecializationData {
+            position_offset: offset.into(),
+            position_scale: scale.into(),
+            octahedral_normals: octahedral,
+            item_animation: specialization.item_animation as vk::Bool32,
+        });
+        let entries = alloc.alloc_slice_fill_iter((0..7u32).map(|index| {
+            vk::SpecializationMapEntry {
+                constant_id: 100 + index,
+                offset: index * 4,
+                size: 4
+            }
+        }).chain(std::iter::once(VertexSpecializationData::ITEM_ANIMATION_ENTRY)));
+
+        alloc.alloc(vk::SpecializationInfo::builder()
+            .map_entries(entries)
+            .data(bytes_of(data))
+            .build()
+        )
+    }
+
+    /// Creates the specialization info for the pulled vertex shader. Contains the vertex
+    /// quantization and item animation constants and the output selected by the debug mode.
+    fn make_pulled_vertex_specialization<'a>(&self, vertex_format: &VertexFormat, specialization: &ShaderSpecialization, alloc: &'a Bump) -> &'a vk::SpecializationInfo {
+        let (offset, scale) = match &vertex_format.position_quantization {
+            Some(quantization) => (quantization.offset, quantization.scale),
+            None => (Vec3f32::zeros(), Vec3f32::from_element(1f32)),
+        };
+        let octahedral = (vertex_format.normal_encoding == NormalEncoding::Octahedral) as vk::Bool32;
+
+        let output_mode = match self.mode {
+            DebugPipelineMode::Depth |
+            DebugPipelineMode::Position => 0u32,
+            DebugPipelineMode::Color => 1u32,
+            DebugPipelineMode::Normal => 2u32,
+            DebugPipelineMode::UV0 |
+            DebugPipelineMode::UV1 |
+            DebugPipelineMode::UV2 |
+            DebugPipelineMode::Textured0 |
+            DebugPipelineMode::Textured1 |
+            DebugPipelineMode::Textured2 => 3u32,
+        };
+
+        let data = alloc.alloc(PulledVertexSpecializationData {
+            vertex: VertexSpecializationData {
+                position_offset: offset.into(),
+                position_scale: scale.into(),
+                octahedral_normals: octahedral,
+                item_animation: specialization.item_animation as vk::Bool32,
+            },
+            output_mode,
+        });
+        let entries = alloc.alloc_slice_fill_iter((0..7u32).map(|index| {
+            vk::SpecializationMapEntry {
+                constant_id: 100 + index,
+                offset: index * 4,
+                size: 4
+            }
+        }).chain([
+            VertexSpecializationData::ITEM_ANIMATION_ENTRY,
+            vk::SpecializationMapEntry {
+                constant_id: 1,
+                offset: 32,
+                size: 4
+            }
+        ]));
+
+        alloc.alloc(vk::SpecializationInfo::builder()
+            .map_entries(entries)
+            .data(bytes_of(data))
+            .build()
+        )
+    }
+
+    /// Creates the push constants describing the layout of a vertex format for the pulled vertex
+    /// shader.
+    fn make_fetch_constants(&self, vertex_format: &VertexFormat) -> VertexFetchConstants {
+        let position = VertexFetchConstants::pack_entry(&vertex_format.position).unwrap_or_else(|| {
+            log::warn!("Vertex position format {:?} cannot be fetched by the pulled vertex shader", vertex_format.position.format);
+            0
+        });
+        let attribute = self.process_vertex_format(vertex_format)
+            .and_then(|entry| VertexFetchConstants::pack_entry(entry))
+            .unwrap_or(0);
+
+        VertexFetchConstants {
+            stride: vertex_format.stride,
+            position,
+            attribute,
+            _padding0: Default::default(),
+        }
+    }
+
+    /// Creates the specialization info for the fragment shader. Includes the image index used by
+    /// the textured modes and the shader variant constants defined in mc_uniforms.glsl.
+    fn make_fragment_specialization<'a>(image_index: u32, specialization: &ShaderSpecialization, alloc: &'a Bump) -> &'a vk::SpecializationInfo {
+        let data = alloc.alloc(FragmentSpecializationData {
+            image_index,
+            fog_mode: specialization.fog_mode as u32,
+            alpha_test_threshold: specialization.alpha_test_threshold,
+            lightmap_enable: specialization.lightmap_enable as vk::Bool32,
+            alpha_mode: specialization.resolve_alpha_mode(RASTERIZATION_SAMPLES) as u32,
+            emissive: specialization.emissive as vk::Bool32,
+            emissive_image_index: specialization.resolve_emissive_image(),
+        });
+        let entries = alloc.alloc([
+            vk::SpecializationMapEntry { constant_id: 0, offset: 0, size: 4 },
+            vk::SpecializationMapEntry { constant_id: 110, offset: 4, size: 4 },
+            vk::SpecializationMapEntry { constant_id: 111, offset: 8, size: 4 },
+            vk::SpecializationMapEntry { constant_id: 112, offset: 12, size: 4 },
+            vk::SpecializationMapEntry { constant_id: 113, offset: 16, size: 4 },
+            vk::SpecializationMapEntry { constant_id: 114, offset: 20, size: 4 },
+            vk::SpecializationMapEntry { constant_id: 115, offset: 24, size: 4 },
+        ]);
+
+        alloc.alloc(vk::SpecializationInfo::builder()
+            .map_entries(entries)
+            .data(bytes_of(data))
+            .build()
+        )
+    }
+
     fn process_vertex_format<'a>(&self, vertex_format: &'a VertexFormat) -> Option<&'a VertexFormatEntry> {
         match self.mode {
             DebugPipelineMode::Depth |
@@ -676,29 +1150,130 @@ impl ShaderModules {
     }
 }
 
-struct DrawPipeline {
-    set0_layout: vk::DescriptorSetLayout,
-    pipeline_layout: vk::PipelineLayout,
-}
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct VertexSpecializationData {
+    #[allow(unused)]
+    position_offset: [f32; 3],
 
-impl DrawPipeline {
-    fn new(device: &DeviceContext) -> Result<Self, ObjectCreateError> {
-        let bindings = [
-            vk::DescriptorSetLayoutBinding {
-                binding: 0,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: 1,
-                stage_flags: vk::ShaderStageFlags::ALL,
-                p_immutable_samplers: std::ptr::null(),
-            },
-            vk::DescriptorSetLayoutBinding {
-                binding: 1,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: 3,
-                stage_flags: vk::ShaderStageFlags::ALL_GRAPHICS,
-                p_immutable_samplers: std::ptr::null(),
-            },
-        ];
+    #[allow(unused)]
+    position_scale: [f32; 3],
+
+    #[allow(unused)]
+    octahedral_normals: vk::Bool32,
+
+    #[allow(unused)]
+    item_animation: vk::Bool32,
+}
+const_assert_eq!(std::mem::size_of::<VertexSpecializationData>(), 32);
+
+unsafe impl Zeroable for VertexSpecializationData {}
+unsafe impl Pod for VertexSpecializationData {}
+
+impl VertexSpecializationData {
+    const ITEM_ANIMATION_ENTRY: vk::SpecializationMapEntry = vk::SpecializationMapEntry {
+        constant_id: 116,
+        offset: 28,
+        size: 4
+    };
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct PulledVertexSpecializationData {
+    vertex: VertexSpecializationData,
+
+    #[allow(unused)]
+    output_mode: u32,
+}
+const_assert_eq!(std::mem::size_of::<PulledVertexSpecializationData>(), 36);
+
+unsafe impl Zeroable for PulledVertexSpecializationData {}
+unsafe impl Pod for PulledVertexSpecializationData {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct FragmentSpecializationData {
+    #[allow(unused)]
+    image_index: u32,
+
+    #[allow(unused)]
+    fog_mode: u32,
+
+    #[allow(unused)]
+    alpha_test_threshold: f32,
+
+    #[allow(unused)]
+    lightmap_enable: vk::Bool32,
+
+    #[allow(unused)]
+    alpha_mode: u32,
+
+    #[allow(unused)]
+    emissive: vk::Bool32,
+
+    #[allow(unused)]
+    emissive_image_index: u32,
+}
+const_assert_eq!(std::mem::size_of::<FragmentSpecializationData>(), 28);
+
+unsafe impl Zeroable for FragmentSpecializationData {}
+unsafe impl Pod for FragmentSpecializationData {}
+
+struct DrawPipeline {
+    set0_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+}
+
+impl DrawPipeline {
+    fn new(device: &DeviceContext, fetch_mode: VertexFetchMode) -> Result<Self, ObjectCreateError> {
+        let mut bindings = vec![
+            vk::DescriptorSetLayoutBinding {
+                binding: 0,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::ALL,
+                p_immutable_samplers: std::ptr::null(),
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 1,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 3,
+                stage_flags: vk::ShaderStageFlags::ALL_GRAPHICS,
+                p_immutable_samplers: std::ptr::null(),
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 2,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                p_immutable_samplers: std::ptr::null(),
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 3,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                p_immutable_samplers: std::ptr::null(),
+            },
+            vk::DescriptorSetLayoutBinding {
+                binding: 4,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 3,
+                stage_flags: vk::ShaderStageFlags::ALL_GRAPHICS,
+                p_immutable_samplers: std::ptr::null(),
+            },
+        ];
+        if fetch_mode == VertexFetchMode::Pulling {
+            // The vertex buffer of the current draw
+            bindings.push(vk::DescriptorSetLayoutBinding {
+                binding: 5,
+                descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                p_immutable_samplers: std::ptr::null(),
+            });
+        }
 
         let info = vk::DescriptorSetLayoutCreateInfo::builder()
             .flags(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
@@ -711,10 +1286,14 @@ impl DrawPipeline {
             err
         })?;
 
+        let fetch_constants_size = match fetch_mode {
+            VertexFetchMode::InputAssembly => 0,
+            VertexFetchMode::Pulling => std::mem::size_of::<VertexFetchConstants>(),
+        };
         let push_constant_range = vk::PushConstantRange {
             stage_flags: vk::ShaderStageFlags::ALL_GRAPHICS,
             offset: 0,
-            size: std::mem::size_of::<PushConstants>() as u32,
+            size: (std::mem::size_of::<PushConstants>() + std::mem::size_of::<ImageLayoutConstants>() + fetch_constants_size) as u32,
         };
 
         let layouts = [
@@ -905,7 +1484,7 @@ impl BackgroundPipeline {
             .subpass(subpass);
 
         let pipeline = *unsafe {
-            device.vk().create_graphics_pipelines(vk::PipelineCache::null(), std::slice::from_ref(&info), None)
+            device.vk().create_graphics_pipelines(device.get_pipeline_cache(), std::slice::from_ref(&info), None)
         }.map_err(|(_, err)| {
             log::error!("vkCreateGraphicsPipelines returned {:?} in BackgroundPipeline::create_pipeline", err);
             unsafe {
@@ -925,6 +1504,264 @@ impl BackgroundPipeline {
     }
 }
 
+/// Renders the depth of shadow casters into the layers of the shadow map array. The vertex
+/// shader only reads the position so pipelines are shared by all shaders with the same position
+/// layout and are created the first time they are needed.
+struct ShadowPipeline {
+    resolution: u32,
+    render_pass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    vertex_module: vk::ShaderModule,
+
+    /// The comparison sampler used by the lighting shaders to sample the shadow map.
+    sampler: vk::Sampler,
+    pipelines: Mutex<HashMap<ShadowPipelineKey, vk::Pipeline>>,
+}
+
+impl ShadowPipeline {
+    const FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+    fn new(device: &DeviceContext, resolution: u32) -> Result<Self, ObjectCreateError> {
+        let render_pass = Self::create_render_pass(device)?;
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: std::mem::size_of::<ShadowPushConstants>() as u32,
+        };
+
+        let info = vk::PipelineLayoutCreateInfo::builder()
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+
+        let pipeline_layout = unsafe {
+            device.vk().create_pipeline_layout(&info, None)
+        }.map_err(|err| {
+            log::error!("vkCreatePipelineLayout returned {:?} in ShadowPipeline::new", err);
+            unsafe { device.vk().destroy_render_pass(render_pass, None) };
+            err
+        })?;
+
+        let vertex_module = try_create_shader_module(device, SHADOW_VERTEX_BIN, "shadow_vertex").map_err(|err| {
+            unsafe {
+                device.vk().destroy_pipeline_layout(pipeline_layout, None);
+                device.vk().destroy_render_pass(render_pass, None);
+            }
+            err
+        })?;
+
+        let sampler = Self::create_sampler(device).map_err(|err| {
+            unsafe {
+                device.vk().destroy_shader_module(vertex_module, None);
+                device.vk().destroy_pipeline_layout(pipeline_layout, None);
+                device.vk().destroy_render_pass(render_pass, None);
+            }
+            err
+        })?;
+
+        Ok(Self {
+            resolution,
+            render_pass,
+            pipeline_layout,
+            vertex_module,
+            sampler,
+            pipelines: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn destroy(&mut self, device: &DeviceContext) {
+        unsafe {
+            for pipeline in self.pipelines.get_mut().unwrap().drain().map(|(_, pipeline)| pipeline) {
+                device.vk().destroy_pipeline(pipeline, None);
+            }
+            device.vk().destroy_sampler(self.sampler, None);
+            device.vk().destroy_shader_module(self.vertex_module, None);
+            device.vk().destroy_pipeline_layout(self.pipeline_layout, None);
+            device.vk().destroy_render_pass(self.render_pass, None);
+        }
+    }
+
+    /// Returns the pipeline for a position layout. If the pipeline doesnt exist yet a new one is
+    /// created.
+    fn get_pipeline(&self, device: &DeviceContext, key: &ShadowPipelineKey) -> vk::Pipeline {
+        let mut guard = self.pipelines.lock().unwrap();
+        *guard.entry(*key).or_insert_with(|| self.create_pipeline(device, key))
+    }
+
+    fn create_pipeline(&self, device: &DeviceContext, key: &ShadowPipelineKey) -> vk::Pipeline {
+        let shader_stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(self.vertex_module)
+            .name(SHADER_ENTRY)
+            .build();
+
+        let input_binding = vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: key.stride,
+            input_rate: vk::VertexInputRate::VERTEX
+        };
+        let input_attribute = vk::VertexInputAttributeDescription {
+            location: 0,
+            binding: 0,
+            format: key.position_format,
+            offset: key.position_offset
+        };
+        let input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(std::slice::from_ref(&input_binding))
+            .vertex_attribute_descriptions(std::slice::from_ref(&input_attribute));
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(key.topology)
+            .primitive_restart_enable(key.primitive_restart);
+
+        let size = Vec2u32::new(self.resolution, self.resolution);
+        let viewport = make_full_viewport(size);
+        let scissor = make_full_rect(size);
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(std::slice::from_ref(&viewport))
+            .scissors(std::slice::from_ref(&scissor));
+
+        // Casters are rendered double sided and the depth bias avoids self shadowing acne
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(true)
+            .depth_bias_constant_factor(1f32)
+            .depth_bias_slope_factor(1.75f32)
+            .line_width(1f32);
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .sample_shading_enable(false);
+
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::LESS);
+
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(std::slice::from_ref(&shader_stage))
+            .vertex_input_state(&input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .layout(self.pipeline_layout)
+            .render_pass(self.render_pass)
+            .subpass(0);
+
+        *unsafe {
+            device.vk().create_graphics_pipelines(device.get_pipeline_cache(), std::slice::from_ref(&info), None)
+        }.unwrap_or_else(|(_, err)| {
+            log::error!("Failed to create shadow pipeline {:?}", err);
+            panic!();
+        }).get(0).unwrap()
+    }
+
+    fn create_render_pass(device: &DeviceContext) -> Result<vk::RenderPass, ObjectCreateError> {
+        let attachment = vk::AttachmentDescription::builder()
+            .format(Self::FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build();
+
+        let depth = vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        };
+
+        let subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .depth_stencil_attachment(&depth)
+            .build();
+
+        // The shadow map is read by the fragment shaders of the main render pass of this and the
+        // previous use of the pass objects
+        let dependencies = [
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dependency_flags: vk::DependencyFlags::empty()
+            },
+            vk::SubpassDependency {
+                src_subpass: 0,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                dependency_flags: vk::DependencyFlags::empty()
+            },
+        ];
+
+        let info = vk::RenderPassCreateInfo::builder()
+            .attachments(std::slice::from_ref(&attachment))
+            .subpasses(std::slice::from_ref(&subpass))
+            .dependencies(&dependencies);
+
+        let render_pass = unsafe {
+            device.vk().create_render_pass(&info, None)
+        }.map_err(|err| {
+            log::error!("vkCreateRenderPass returned {:?} in ShadowPipeline::create_render_pass", err);
+            err
+        })?;
+
+        Ok(render_pass)
+    }
+
+    /// Creates the comparison sampler. Samples outside of the shadow map are treated as lit. Uses
+    /// hardware pcf if the shadow map format supports linear filtering.
+    fn create_sampler(device: &DeviceContext) -> Result<vk::Sampler, ObjectCreateError> {
+        let properties = unsafe {
+            device.get_instance().vk().get_physical_device_format_properties(device.get_functions().physical_device, Self::FORMAT)
+        };
+        let filter = if properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+            vk::Filter::LINEAR
+        } else {
+            vk::Filter::NEAREST
+        };
+
+        let info = vk::SamplerCreateInfo::builder()
+            .mag_filter(filter)
+            .min_filter(filter)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .min_lod(0f32)
+            .max_lod(0f32)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE);
+
+        let sampler = unsafe {
+            device.vk().create_sampler(&info, None)
+        }.map_err(|err| {
+            log::error!("vkCreateSampler returned {:?} in ShadowPipeline::create_sampler", err);
+            err
+        })?;
+
+        Ok(sampler)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+struct ShadowPipelineKey {
+    stride: u32,
+    position_offset: u32,
+    position_format: vk::Format,
+    topology: vk::PrimitiveTopology,
+    primitive_restart: bool,
+}
+
 struct PassObjects {
     ready: AtomicBool,
 
@@ -941,11 +1778,17 @@ struct PassObjects {
     bg_descriptor_set: vk::DescriptorSet,
     framebuffer: vk::Framebuffer,
 
+    /// The depth array image containing one layer per shadow cascade.
+    shadow_image: vk::Image,
+    shadow_array_view: vk::ImageView,
+    shadow_layer_views: [vk::ImageView; MAX_CASCADES],
+    shadow_framebuffers: [vk::Framebuffer; MAX_CASCADES],
+
     allocations: Vec<Allocation>,
 }
 
 impl PassObjects {
-    fn new(device: &DeviceContext, framebuffer_size: Vec2u32, depth_format: vk::Format, color_format: vk::Format, render_pass: vk::RenderPass, bg_descriptor_set: vk::DescriptorSet) -> Result<Self, ObjectCreateError> {
+    fn new(device: &DeviceContext, framebuffer_size: Vec2u32, depth_format: vk::Format, color_format: vk::Format, render_pass: vk::RenderPass, bg_descriptor_set: vk::DescriptorSet, shadow_pipeline: &ShadowPipeline) -> Result<Self, ObjectCreateError> {
         let mut result = PassObjects {
             ready: AtomicBool::new(true),
 
@@ -962,14 +1805,19 @@ impl PassObjects {
             bg_descriptor_set,
             framebuffer: vk::Framebuffer::null(),
 
-            allocations: Vec::with_capacity(3)
+            shadow_image: vk::Image::null(),
+            shadow_array_view: vk::ImageView::null(),
+            shadow_layer_views: [vk::ImageView::null(); MAX_CASCADES],
+            shadow_framebuffers: [vk::Framebuffer::null(); MAX_CASCADES],
+
+            allocations: Vec::with_capacity(4)
         };
 
         let (depth_image, allocation) = Self::create_image(device, framebuffer_size, depth_format, vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)?;
         result.depth_image = depth_image;
         result.allocations.push(allocation);
 
-        let depth_framebuffer_view = Self::create_image_view(device, depth_image, depth_format, vk::ImageAspectFlags::DEPTH, false).map_err(|err| {
+        let depth_framebuffer_view = Self::create_image_view(device, depth_image, depth_format, get_depth_aspects(depth_format), false).map_err(|err| {
             result.destroy(device);
             err
         })?;
@@ -1013,6 +1861,11 @@ impl PassObjects {
         })?;
         result.framebuffer = framebuffer;
 
+        result.create_shadow_objects(device, shadow_pipeline).map_err(|err| {
+            result.destroy(device);
+            err
+        })?;
+
         let info = vk::DescriptorImageInfo::builder()
             .image_view(pass_view)
             .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
@@ -1031,6 +1884,81 @@ impl PassObjects {
         Ok(result)
     }
 
+    /// Creates the shadow map array and a framebuffer for each of its layers. On failure the
+    /// objects created so far are stored in self so that [`PassObjects::destroy`] can clean them
+    /// up.
+    fn create_shadow_objects(&mut self, device: &DeviceContext, shadow_pipeline: &ShadowPipeline) -> Result<(), ObjectCreateError> {
+        let size = Vec2u32::new(shadow_pipeline.resolution, shadow_pipeline.resolution);
+
+        let info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(ShadowPipeline::FORMAT)
+            .extent(vk::Extent3D {
+                width: size[0],
+                height: size[1],
+                depth: 1
+            })
+            .mip_levels(1)
+            .array_layers(MAX_CASCADES as u32)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        let (shadow_image, allocation) = unsafe {
+            device.get_allocator().create_gpu_image(&info, &format_args!("DebugPipelineShadowMap"))
+        }.ok_or(ObjectCreateError::Allocation)?;
+        self.shadow_image = shadow_image;
+        self.allocations.push(allocation);
+
+        self.shadow_array_view = Self::create_shadow_view(device, shadow_image, vk::ImageViewType::TYPE_2D_ARRAY, 0, MAX_CASCADES as u32)?;
+
+        for layer in 0..MAX_CASCADES {
+            let view = Self::create_shadow_view(device, shadow_image, vk::ImageViewType::TYPE_2D, layer as u32, 1)?;
+            self.shadow_layer_views[layer] = view;
+
+            let info = vk::FramebufferCreateInfo::builder()
+                .render_pass(shadow_pipeline.render_pass)
+                .attachments(std::slice::from_ref(&view))
+                .width(size[0])
+                .height(size[1])
+                .layers(1);
+
+            self.shadow_framebuffers[layer] = unsafe {
+                device.vk().create_framebuffer(&info, None)
+            }.map_err(|err| {
+                log::error!("vkCreateFramebuffer returned {:?} in PassObjects::create_shadow_objects", err);
+                err
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn create_shadow_view(device: &DeviceContext, image: vk::Image, view_type: vk::ImageViewType, base_array_layer: u32, layer_count: u32) -> Result<vk::ImageView, ObjectCreateError> {
+        let info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(view_type)
+            .format(ShadowPipeline::FORMAT)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer,
+                layer_count
+            });
+
+        let image_view = unsafe {
+            device.vk().create_image_view(&info, None)
+        }.map_err(|err| {
+            log::error!("vkCreateImageView returned {:?} in PassObjects::create_shadow_view", err);
+            err
+        })?;
+
+        Ok(image_view)
+    }
+
     fn wait_and_take(&self) {
         let mut start = Instant::now();
         loop {
@@ -1047,6 +1975,22 @@ impl PassObjects {
 
     fn destroy(&mut self, device: &DeviceContext) {
         unsafe {
+            for framebuffer in self.shadow_framebuffers {
+                if framebuffer != vk::Framebuffer::null() {
+                    device.vk().destroy_framebuffer(framebuffer, None);
+                }
+            }
+            for view in self.shadow_layer_views {
+                if view != vk::ImageView::null() {
+                    device.vk().destroy_image_view(view, None);
+                }
+            }
+            if self.shadow_array_view != vk::ImageView::null() {
+                device.vk().destroy_image_view(self.shadow_array_view, None);
+            }
+            if self.shadow_image != vk::Image::null() {
+                device.vk().destroy_image(self.shadow_image, None);
+            }
             if self.framebuffer != vk::Framebuffer::null() {
                 device.vk().destroy_framebuffer(self.framebuffer, None);
             }
@@ -1166,13 +2110,83 @@ struct PipelineConfig {
     primitive_topology: vk::PrimitiveTopology,
     depth_test_enable: bool,
     depth_write_enable: bool,
+    stencil_mode: StencilMode,
+}
+
+impl PipelineConfig {
+    fn make_stencil_op_state(&self) -> vk::StencilOpState {
+        let pass_op = match self.stencil_mode {
+            StencilMode::PortalMark => vk::StencilOp::INCREMENT_AND_CLAMP,
+            StencilMode::PortalSeal => vk::StencilOp::DECREMENT_AND_CLAMP,
+            StencilMode::Draw | StencilMode::PortalClearDepth => vk::StencilOp::KEEP,
+        };
+
+        vk::StencilOpState {
+            fail_op: vk::StencilOp::KEEP,
+            pass_op,
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: vk::CompareOp::EQUAL,
+            compare_mask: 0xFF,
+            write_mask: 0xFF,
+            reference: 0,
+        }
+    }
+
+    fn make_depth_stencil_state<'a>(&self, stencil_op_state: &'a vk::StencilOpState) -> vk::PipelineDepthStencilStateCreateInfoBuilder<'a> {
+        // Portal masks overwrite the depth of everything behind them independent of the depth
+        // already stored
+        let depth_compare_op = match self.stencil_mode {
+            StencilMode::Draw | StencilMode::PortalMark => vk::CompareOp::LESS,
+            StencilMode::PortalClearDepth | StencilMode::PortalSeal => vk::CompareOp::ALWAYS,
+        };
+
+        vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(self.depth_test_enable)
+            .depth_write_enable(self.depth_write_enable)
+            .depth_compare_op(depth_compare_op)
+            .stencil_test_enable(true)
+            .front(*stencil_op_state)
+            .back(*stencil_op_state)
+    }
+}
+
+/// How a draw interacts with the stencil aspect used to mask portals. The stencil value of a pixel
+/// is the number of portals it is inside of. Draws only pass the stencil test if the value equals
+/// the current portal level which is set as the dynamic stencil reference.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+enum StencilMode {
+    Draw,
+    /// Draws the mask of a new portal incrementing the stencil value of visible pixels.
+    PortalMark,
+    /// Draws the mask of a new portal resetting the depth inside of it to the far plane. The depth
+    /// value is forced by the viewport depth range.
+    PortalClearDepth,
+    /// Draws the mask of a portal to write its depth and decrement the stencil value back to the
+    /// parent level.
+    PortalSeal,
+}
+
+impl StencilMode {
+    fn writes_color(&self) -> bool {
+        *self == StencilMode::Draw
+    }
 }
 
 struct ShaderPipelines {
     device: Arc<DeviceContext>,
     vertex_format: VertexFormat,
     used_uniforms: McUniform,
+    specialization: ShaderSpecialization,
+    /// The vertex format layout pushed for every draw if vertex pulling is enabled.
+    fetch_constants: Option<VertexFetchConstants>,
     pipelines: HashMap<PipelineConfig, vk::Pipeline>,
+
+    /// The first pipeline created for this shader. All later pipelines are created as derivatives
+    /// of it.
+    base_pipeline: Option<vk::Pipeline>,
+
+    /// Pipeline libraries used to link pipelines if VK_EXT_graphics_pipeline_library is supported.
+    libraries: PipelineLibraries,
     #[allow(unused)]
     listener: ShaderListener,
     used_counter: u32,
@@ -1180,24 +2194,31 @@ struct ShaderPipelines {
 }
 
 impl ShaderPipelines {
-    fn new(device: Arc<DeviceContext>, vertex_format: VertexFormat, used_uniforms: McUniform, listener: ShaderListener) -> Self {
+    fn new(device: Arc<DeviceContext>, vertex_format: VertexFormat, used_uniforms: McUniform, specialization: ShaderSpecialization, fetch_constants: Option<VertexFetchConstants>, listener: ShaderListener) -> Self {
         Self {
             device,
             vertex_format,
             used_uniforms,
+            specialization,
+            fetch_constants,
             pipelines: HashMap::new(),
+            base_pipeline: None,
+            libraries: PipelineLibraries::default(),
             listener,
             used_counter: 0,
             marked: false,
         }
     }
 
-    fn get_or_create_pipeline<T: FnOnce(&VertexFormat) -> vk::Pipeline>(&mut self, config: &PipelineConfig, create_fn: T) -> vk::Pipeline {
+    fn get_or_create_pipeline<T: FnOnce(&mut Self) -> vk::Pipeline>(&mut self, config: &PipelineConfig, create_fn: T) -> vk::Pipeline {
         if let Some(pipeline) = self.pipelines.get(config) {
             *pipeline
         } else {
-            let pipeline = create_fn(&self.vertex_format);
+            let pipeline = create_fn(self);
             self.pipelines.insert(*config, pipeline);
+            if self.base_pipeline.is_none() {
+                self.base_pipeline = Some(pipeline);
+            }
             pipeline
         }
     }
@@ -1226,68 +2247,198 @@ impl Drop for ShaderPipelines {
                 self.device.vk().destroy_pipeline(*pipeline, None);
             }
         }
+        let libraries = self.libraries.vertex_input.values()
+            .chain(self.libraries.pre_rasterization.iter())
+            .chain(self.libraries.fragment_shader.values());
+        for library in libraries {
+            unsafe {
+                self.device.vk().destroy_pipeline(*library, None);
+            }
+        }
     }
 }
 
+/// The partial pipelines of a shader. Each is created the first time a pipeline needing it is
+/// linked.
+#[derive(Default)]
+struct PipelineLibraries {
+    /// Vertex input interface libraries keyed by primitive topology.
+    vertex_input: HashMap<vk::PrimitiveTopology, vk::Pipeline>,
+    pre_rasterization: Option<vk::Pipeline>,
+    /// Fragment shader libraries keyed by (depth test enable, depth write enable).
+    fragment_shader: HashMap<(bool, bool), vk::Pipeline>,
+}
+
 struct DebugPipelinePass {
     parent: Arc<DebugPipeline>,
     index: usize,
 
-    placeholder_texture: vk::ImageView,
-    placeholder_sampler: vk::Sampler,
+    placeholder_texture: Option<TextureBinding>,
     shader_uniforms: HashMap<ShaderId, UniformStateTracker>,
+    partial_tick: f32,
+    bound_textures: [Option<TextureBinding>; MAX_TEXTURE_SLOTS as usize],
 
     command_buffer: Option<vk::CommandBuffer>,
+    /// The textures last written to the push descriptor set.
+    current_textures: Option<[TextureBinding; MAX_TEXTURE_SLOTS as usize]>,
+    viewports: [vk::Viewport; MAX_VIEWPORTS as usize],
+    scissor: Option<vk::Rect2D>,
+    current_viewport: Option<u32>,
     current_pipeline: Option<(ShaderId, PipelineConfig)>,
+    /// The number of currently started portals. Used as the stencil reference of all draws.
+    portal_level: u32,
     current_vertex_buffer: Option<vk::Buffer>,
     current_index_buffer: Option<vk::Buffer>,
+    current_fetch_constants: Option<VertexFetchConstants>,
+
+    /// The cascades and the inverse camera view matrix if a shadow camera has been set.
+    shadow_cascades: Option<(Vec<ShadowCascade>, Mat4f32)>,
+    shadow_draws: Vec<ShadowDraw>,
+    /// The vertex formats of the shaders used by this pass. Cached to avoid locking the pipeline
+    /// map for every shadow draw.
+    shadow_formats: HashMap<ShaderId, (VertexFormat, ShaderSpecialization)>,
 }
 
 impl DebugPipelinePass {
     fn new(parent: Arc<DebugPipeline>, index: usize) -> Self {
+        let viewports = [make_full_viewport(parent.framebuffer_size); MAX_VIEWPORTS as usize];
+
         Self {
             parent,
             index,
 
-            placeholder_texture: vk::ImageView::null(),
-            placeholder_sampler: vk::Sampler::null(),
+            placeholder_texture: None,
             shader_uniforms: HashMap::new(),
+            partial_tick: 0f32,
+            bound_textures: [None; MAX_TEXTURE_SLOTS as usize],
 
             command_buffer: None,
+            current_textures: None,
+            viewports,
+            scissor: None,
+            current_viewport: None,
             current_pipeline: None,
+            portal_level: 0,
             current_vertex_buffer: None,
-            current_index_buffer: None
+            current_index_buffer: None,
+            current_fetch_constants: None,
+
+            shadow_cascades: None,
+            shadow_draws: Vec::new(),
+            shadow_formats: HashMap::new(),
         }
     }
 
     fn update_uniform(&mut self, shader: ShaderId, data: &McUniformData) {
         if !self.shader_uniforms.contains_key(&shader) {
             let uniforms = self.parent.pipelines.lock().unwrap().get(&shader).unwrap().used_uniforms;
-            self.shader_uniforms.insert(shader, UniformStateTracker::new(uniforms, self.placeholder_texture, self.placeholder_sampler));
+            self.shader_uniforms.insert(shader, UniformStateTracker::new(uniforms, self.placeholder_texture.unwrap(), self.partial_tick));
         }
         let tracker = self.shader_uniforms.get_mut(&shader).unwrap();
         tracker.update_uniform(data);
     }
 
-    fn update_texture(&mut self, shader: ShaderId, index: u32, view: vk::ImageView, sampler: vk::Sampler) {
+    fn update_texture(&mut self, shader: ShaderId, index: u32, texture: TextureBinding) {
         if !self.shader_uniforms.contains_key(&shader) {
             let uniforms = self.parent.pipelines.lock().unwrap().get(&shader).unwrap().used_uniforms;
-            self.shader_uniforms.insert(shader, UniformStateTracker::new(uniforms, self.placeholder_texture, self.placeholder_sampler));
+            self.shader_uniforms.insert(shader, UniformStateTracker::new(uniforms, self.placeholder_texture.unwrap(), self.partial_tick));
         }
         let tracker = self.shader_uniforms.get_mut(&shader).unwrap();
-        tracker.update_texture(index, view, sampler);
+        tracker.update_texture(index, texture);
     }
 
     fn draw(&mut self, task: &DrawTask, obj: &mut PooledObjectProvider) {
-        let device = self.parent.emulator.get_device();
-        let cmd = *self.command_buffer.as_ref().unwrap();
-
         let pipeline_config = PipelineConfig {
             primitive_topology: task.primitive_topology,
             depth_test_enable: true,
-            depth_write_enable: task.depth_write_enable
+            depth_write_enable: task.depth_write_enable,
+            stencil_mode: StencilMode::Draw,
+        };
+        self.draw_with_config(task, pipeline_config, obj);
+    }
+
+    /// Starts a new portal level. The mask is drawn twice, first to mark the visible pixels of the
+    /// mask in the stencil aspect and then to reset the depth of the marked pixels to the far
+    /// plane so the portal contents are not occluded by the parent scene behind the mask.
+    fn begin_portal(&mut self, mask: Option<&DrawTask>, obj: &mut PooledObjectProvider) {
+        let mask = match mask {
+            Some(mask) => mask,
+            None => {
+                self.set_portal_level(self.portal_level + 1);
+                return;
+            }
         };
 
+        self.draw_with_config(mask, PipelineConfig {
+            primitive_topology: mask.primitive_topology,
+            depth_test_enable: true,
+            depth_write_enable: false,
+            stencil_mode: StencilMode::PortalMark,
+        }, obj);
+        self.set_portal_level(self.portal_level + 1);
+
+        let device = self.parent.emulator.get_device();
+        let cmd = *self.command_buffer.as_ref().unwrap();
+        let mut viewport = self.viewports[mask.viewport_index as usize];
+        viewport.min_depth = 1f32;
+        viewport.max_depth = 1f32;
+        let scissor = Self::make_viewport_scissor(&viewport, self.parent.framebuffer_size, self.scissor.as_ref());
+        unsafe {
+            device.vk().cmd_set_viewport(cmd, 0, std::slice::from_ref(&viewport));
+            device.vk().cmd_set_scissor(cmd, 0, std::slice::from_ref(&scissor));
+        }
+        self.current_viewport = Some(mask.viewport_index);
+
+        self.draw_with_config(mask, PipelineConfig {
+            primitive_topology: mask.primitive_topology,
+            depth_test_enable: true,
+            depth_write_enable: true,
+            stencil_mode: StencilMode::PortalClearDepth,
+        }, obj);
+        // Restore the depth range of the viewport for the next draw
+        self.current_viewport = None;
+    }
+
+    /// Ends the current portal level. The mask is drawn with the camera of the parent level to
+    /// write its depth and return the marked pixels to the parent level.
+    fn end_portal(&mut self, mask: Option<&DrawTask>, obj: &mut PooledObjectProvider) {
+        if self.portal_level == 0 {
+            log::error!("Called end_portal without a started portal");
+            panic!();
+        }
+        if let Some(mask) = mask {
+            self.draw_with_config(mask, PipelineConfig {
+                primitive_topology: mask.primitive_topology,
+                depth_test_enable: true,
+                depth_write_enable: true,
+                stencil_mode: StencilMode::PortalSeal,
+            }, obj);
+        }
+        self.set_portal_level(self.portal_level - 1);
+    }
+
+    fn set_portal_level(&mut self, level: u32) {
+        self.portal_level = level;
+        unsafe {
+            self.parent.emulator.get_device().vk().cmd_set_stencil_reference(*self.command_buffer.as_ref().unwrap(), vk::StencilFaceFlags::FRONT_AND_BACK, level);
+        }
+    }
+
+    fn draw_with_config(&mut self, task: &DrawTask, pipeline_config: PipelineConfig, obj: &mut PooledObjectProvider) {
+        let device = self.parent.emulator.get_device();
+        let cmd = *self.command_buffer.as_ref().unwrap();
+
+        if self.current_viewport != Some(task.viewport_index) {
+            self.current_viewport = Some(task.viewport_index);
+
+            let viewport = self.viewports[task.viewport_index as usize];
+            let scissor = Self::make_viewport_scissor(&viewport, self.parent.framebuffer_size, self.scissor.as_ref());
+            unsafe {
+                device.vk().cmd_set_viewport(cmd, 0, std::slice::from_ref(&viewport));
+                device.vk().cmd_set_scissor(cmd, 0, std::slice::from_ref(&scissor));
+            }
+        }
+
         if self.current_pipeline != Some((task.shader, pipeline_config)) {
             self.current_pipeline = Some((task.shader, pipeline_config));
 
@@ -1295,12 +2446,28 @@ impl DebugPipelinePass {
             unsafe {
                 device.vk().cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, new_pipeline);
             }
+
+            let fetch_constants = self.parent.pipelines.lock().unwrap().get(&task.shader).unwrap().fetch_constants;
+            if let Some(fetch_constants) = fetch_constants {
+                if self.current_fetch_constants != Some(fetch_constants) {
+                    self.current_fetch_constants = Some(fetch_constants);
+                    unsafe {
+                        device.vk().cmd_push_constants(
+                            cmd,
+                            self.parent.draw_pipeline.pipeline_layout,
+                            vk::ShaderStageFlags::ALL_GRAPHICS,
+                            (std::mem::size_of::<PushConstants>() + std::mem::size_of::<ImageLayoutConstants>()) as u32,
+                            bytes_of(&fetch_constants)
+                        );
+                    }
+                }
+            }
         }
 
         if !self.shader_uniforms.contains_key(&task.shader) {
             log::warn!("Called draw without any shader uniforms. Using default values!");
             let uniforms = self.parent.pipelines.lock().unwrap().get(&task.shader).unwrap().used_uniforms;
-            self.shader_uniforms.insert(task.shader, UniformStateTracker::new(uniforms, self.placeholder_texture, self.placeholder_sampler));
+            self.shader_uniforms.insert(task.shader, UniformStateTracker::new(uniforms, self.placeholder_texture.unwrap(), self.partial_tick));
         }
         if let Some(tracker) = self.shader_uniforms.get_mut(&task.shader) {
             if let Some(push_constants) = tracker.validate_push_constants() {
@@ -1338,65 +2505,100 @@ impl DebugPipelinePass {
                     );
                 }
             }
+        }
 
-            if let Some(textures) = tracker.validate_textures() {
-                let image_info0 = vk::DescriptorImageInfo {
-                    sampler: textures[0].1,
-                    image_view: textures[0].0,
-                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-                };
-                let image_info1 = vk::DescriptorImageInfo {
-                    sampler: textures[1].1,
-                    image_view: textures[1].0,
-                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-                };
-                let image_info2 = vk::DescriptorImageInfo {
-                    sampler: textures[2].1,
-                    image_view: textures[2].0,
-                    image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-                };
-                let writes = [
-                    vk::WriteDescriptorSet::builder()
-                        .dst_binding(1)
-                        .dst_array_element(0)
-                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                        .image_info(std::slice::from_ref(&image_info0))
-                        .build(),
-                    vk::WriteDescriptorSet::builder()
-                        .dst_binding(1)
-                        .dst_array_element(1)
-                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                        .image_info(std::slice::from_ref(&image_info1))
-                        .build(),
-                    vk::WriteDescriptorSet::builder()
-                        .dst_binding(1)
-                        .dst_array_element(2)
-                        .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                        .image_info(std::slice::from_ref(&image_info2))
-                        .build(),
-                ];
-
-                unsafe {
-                    device.push_descriptor_khr().cmd_push_descriptor_set(
-                        self.command_buffer.unwrap(),
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.parent.draw_pipeline.pipeline_layout,
-                        0,
-                        &writes
-                    );
-                }
+        // Textures bound to the pass override the per shader textures. Descriptors are only
+        // written if the effective textures changed since the last draw.
+        let mut textures = *self.shader_uniforms.get(&task.shader).unwrap().get_textures();
+        for (texture, bound) in textures.iter_mut().zip(self.bound_textures.iter()) {
+            if let Some(bound) = bound {
+                *texture = *bound;
             }
         }
+        if self.current_textures != Some(textures) {
+            self.current_textures = Some(textures);
+
+            let image_infos = textures.map(|texture| vk::DescriptorImageInfo {
+                sampler: texture.sampler,
+                image_view: texture.view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            });
+            let array_image_infos = textures.map(|texture| vk::DescriptorImageInfo {
+                sampler: texture.sampler,
+                image_view: texture.array_view,
+                image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            });
+            let writes = [
+                vk::WriteDescriptorSet::builder()
+                    .dst_binding(1)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&image_infos)
+                    .build(),
+                vk::WriteDescriptorSet::builder()
+                    .dst_binding(4)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&array_image_infos)
+                    .build(),
+            ];
+
+            let image_layouts = ImageLayoutConstants {
+                packed_atlas_grids: textures.map(|texture| texture.packed_atlas_grid),
+                _padding0: Default::default(),
+            };
 
-        if self.current_vertex_buffer != Some(task.vertex_buffer) {
             unsafe {
-                device.vk().cmd_bind_vertex_buffers(
-                    cmd,
+                device.push_descriptor_khr().cmd_push_descriptor_set(
+                    self.command_buffer.unwrap(),
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.parent.draw_pipeline.pipeline_layout,
                     0,
-                    std::slice::from_ref(&task.vertex_buffer),
-                    std::slice::from_ref(&0)
+                    &writes
+                );
+                device.vk().cmd_push_constants(
+                    self.command_buffer.unwrap(),
+                    self.parent.draw_pipeline.pipeline_layout,
+                    vk::ShaderStageFlags::ALL_GRAPHICS,
+                    std::mem::size_of::<PushConstants>() as u32,
+                    bytes_of(&image_layouts)
                 );
             }
+        }
+
+        if self.current_vertex_buffer != Some(task.vertex_buffer) {
+            match self.parent.fetch_mode {
+                VertexFetchMode::InputAssembly => unsafe {
+                    device.vk().cmd_bind_vertex_buffers(
+                        cmd,
+                        0,
+                        std::slice::from_ref(&task.vertex_buffer),
+                        std::slice::from_ref(&0)
+                    );
+                },
+                VertexFetchMode::Pulling => {
+                    let buffer_info = vk::DescriptorBufferInfo {
+                        buffer: task.vertex_buffer,
+                        offset: 0,
+                        range: vk::WHOLE_SIZE
+                    };
+                    let write = vk::WriteDescriptorSet::builder()
+                        .dst_binding(5)
+                        .dst_array_element(0)
+                        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                        .buffer_info(std::slice::from_ref(&buffer_info));
+
+                    unsafe {
+                        device.push_descriptor_khr().cmd_push_descriptor_set(
+                            cmd,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            self.parent.draw_pipeline.pipeline_layout,
+                            0,
+                            std::slice::from_ref(&write)
+                        );
+                    }
+                }
+            }
             self.current_vertex_buffer = Some(task.vertex_buffer);
         }
 
@@ -1407,16 +2609,274 @@ impl DebugPipelinePass {
             self.current_index_buffer = Some(task.index_buffer);
         }
 
+        if let (Some(tag), Some(debug_utils)) = (task.tag, device.debug_utils_ext()) {
+            let label = make_debug_label(tag);
+            let info = vk::DebugUtilsLabelEXT::builder().label_name(&label);
+            unsafe {
+                debug_utils.cmd_insert_debug_utils_label(cmd, &info);
+            }
+        }
+
         unsafe {
             device.vk().cmd_draw_indexed(cmd, task.index_count, 1, task.first_index, task.vertex_offset, 0);
         }
+
+        if task.depth_write_enable {
+            self.push_shadow_draw(task);
+        }
+    }
+
+    /// Records a draw to be rendered into the shadow map cascades. Every depth writing draw of the
+    /// pass casts shadows even if it was recorded before the shadow camera was set.
+    fn push_shadow_draw(&mut self, task: &DrawTask) {
+        let parent = &self.parent;
+        let (vertex_format, specialization) = *self.shadow_formats.entry(task.shader).or_insert_with(|| {
+            let guard = parent.pipelines.lock().unwrap();
+            let pipelines = guard.get(&task.shader).unwrap();
+            (pipelines.vertex_format, pipelines.specialization)
+        });
+
+        let push_constants = self.shader_uniforms.get(&task.shader).unwrap().get_push_constants();
+        let model_view = push_constants.model_view_matrix * Mat4f32::new_translation(&push_constants.chunk_offset);
+
+        let (position_offset, position_scale, bounds) = match &vertex_format.position_quantization {
+            Some(quantization) => (quantization.offset, quantization.scale, Some((quantization.offset - quantization.scale, quantization.offset + quantization.scale))),
+            None => (Vec3f32::zeros(), Vec3f32::from_element(1f32), None),
+        };
+
+        self.shadow_draws.push(ShadowDraw {
+            key: ShadowPipelineKey {
+                stride: vertex_format.stride,
+                position_offset: vertex_format.position.offset,
+                position_format: vertex_format.position.format,
+                topology: task.primitive_topology,
+                primitive_restart: specialization.resolve_primitive_restart(task.primitive_topology),
+            },
+            vertex_buffer: task.vertex_buffer,
+            index_buffer: task.index_buffer,
+            index_type: task.index_type,
+            index_count: task.index_count,
+            first_index: task.first_index,
+            vertex_offset: task.vertex_offset,
+            model_view,
+            position_offset,
+            position_scale,
+            bounds,
+        });
+    }
+
+    /// Computes the cascades for the camera and pushes the shadow uniforms used by all following
+    /// draws. Only the first shadow camera of a pass is used since the shadow maps are rendered
+    /// once at the end of the pass.
+    ///
+    /// The cascades are fitted to the camera moved by `effects`. Shadow lookups use the view space
+    /// of the draws so the uniforms use the unmodified camera.
+    fn set_shadow_camera(&mut self, camera: &CameraFrustum, light_direction: &Vec3f32, effects: &Mat4f32, obj: &mut PooledObjectProvider) {
+        if self.shadow_cascades.is_some() {
+            log::warn!("Shadow camera has already been set for this pass. Ignoring!");
+            return;
+        }
+
+        let config = &self.parent.shadow_config;
+        let fit_camera = CameraFrustum {
+            view: effects * camera.view,
+            ..*camera
+        };
+        let result = compute_cascades(&fit_camera, light_direction, config).and_then(|cascades| {
+            let uniforms = ShadowUniforms::new(camera, &cascades, config)?;
+            Some((cascades, uniforms))
+        });
+        let (cascades, uniforms) = match result {
+            Some(result) => result,
+            None => {
+                log::warn!("Failed to compute shadow cascades for camera {:?}", camera);
+                return;
+            }
+        };
+
+        self.push_shadow_uniforms(&uniforms, obj);
+        self.shadow_cascades = Some((cascades, *uniforms.get_inverse_camera_view()));
+    }
+
+    fn push_shadow_uniforms(&self, uniforms: &ShadowUniforms, obj: &mut PooledObjectProvider) {
+        let device = self.parent.emulator.get_device();
+
+        let (buffer, offset) = obj.allocate_uniform(bytes_of(uniforms));
+        let buffer_info = vk::DescriptorBufferInfo {
+            buffer,
+            offset,
+            range: std::mem::size_of::<ShadowUniforms>() as vk::DeviceSize
+        };
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_binding(3)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(std::slice::from_ref(&buffer_info));
+
+        unsafe {
+            device.push_descriptor_khr().cmd_push_descriptor_set(
+                self.command_buffer.unwrap(),
+                vk::PipelineBindPoint::GRAPHICS,
+                self.parent.draw_pipeline.pipeline_layout,
+                0,
+                std::slice::from_ref(&write)
+            );
+        }
+    }
+
+    /// Records the shadow map rendering into `cmd`. If no shadow camera has been set the shadow
+    /// map is only transitioned into the layout expected by the lighting shaders.
+    fn record_shadows(&self, cmd: vk::CommandBuffer) {
+        let device = self.parent.emulator.get_device();
+        let objects = &self.parent.pass_objects[self.index];
+        let shadow_pipeline = &self.parent.shadow_pipeline;
+
+        let (cascades, inverse_view) = match &self.shadow_cascades {
+            Some(cascades) => cascades,
+            None => {
+                let image_barrier = vk::ImageMemoryBarrier2::builder()
+                    .src_stage_mask(vk::PipelineStageFlags2::FRAGMENT_SHADER)
+                    .src_access_mask(vk::AccessFlags2::NONE)
+                    .dst_stage_mask(vk::PipelineStageFlags2::FRAGMENT_SHADER)
+                    .dst_access_mask(vk::AccessFlags2::SHADER_READ)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(objects.shadow_image)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::DEPTH,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: MAX_CASCADES as u32
+                    })
+                    .build();
+
+                let info = vk::DependencyInfo::builder()
+                    .image_memory_barriers(std::slice::from_ref(&image_barrier));
+
+                unsafe {
+                    device.synchronization_2_khr().cmd_pipeline_barrier2(cmd, &info);
+                }
+                return;
+            }
+        };
+
+        // Bounds are only known for quantized formats. Other draws are never culled.
+        let world_bounds: Vec<_> = self.shadow_draws.iter().map(|draw| {
+            draw.bounds.map(|(min, max)| transform_aabb(&(inverse_view * draw.model_view), &min, &max))
+        }).collect();
+
+        let clear_value = vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue {
+                depth: 1.0,
+                stencil: 0
+            }
+        };
+
+        // Unused layers are still cleared so that the whole array is in the expected layout
+        for layer in 0..MAX_CASCADES {
+            let info = vk::RenderPassBeginInfo::builder()
+                .render_pass(shadow_pipeline.render_pass)
+                .framebuffer(objects.shadow_framebuffers[layer])
+                .render_area(make_full_rect(Vec2u32::new(shadow_pipeline.resolution, shadow_pipeline.resolution)))
+                .clear_values(std::slice::from_ref(&clear_value));
+
+            unsafe {
+                device.vk().cmd_begin_render_pass(cmd, &info, vk::SubpassContents::INLINE);
+            }
+
+            if let Some(cascade) = cascades.get(layer) {
+                let world_to_cascade = cascade.view_projection * inverse_view;
+
+                let mut current_pipeline = None;
+                let mut current_vertex_buffer = None;
+                let mut current_index_buffer = None;
+                for (draw, bounds) in self.shadow_draws.iter().zip(world_bounds.iter()) {
+                    if let Some((min, max)) = bounds {
+                        if !cascade.intersects_aabb(min, max) {
+                            continue;
+                        }
+                    }
+
+                    if current_pipeline != Some(draw.key) {
+                        current_pipeline = Some(draw.key);
+                        let pipeline = shadow_pipeline.get_pipeline(device, &draw.key);
+                        unsafe {
+                            device.vk().cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                        }
+                    }
+
+                    if current_vertex_buffer != Some(draw.vertex_buffer) {
+                        current_vertex_buffer = Some(draw.vertex_buffer);
+                        unsafe {
+                            device.vk().cmd_bind_vertex_buffers(cmd, 0, std::slice::from_ref(&draw.vertex_buffer), std::slice::from_ref(&0));
+                        }
+                    }
+
+                    if current_index_buffer != Some((draw.index_buffer, draw.index_type)) {
+                        current_index_buffer = Some((draw.index_buffer, draw.index_type));
+                        unsafe {
+                            device.vk().cmd_bind_index_buffer(cmd, draw.index_buffer, 0, draw.index_type);
+                        }
+                    }
+
+                    let push_constants = ShadowPushConstants {
+                        transform: world_to_cascade * draw.model_view,
+                        position_offset: draw.position_offset.push(0f32),
+                        position_scale: draw.position_scale.push(0f32),
+                    };
+
+                    unsafe {
+                        device.vk().cmd_push_constants(cmd, shadow_pipeline.pipeline_layout, vk::ShaderStageFlags::VERTEX, 0, bytes_of(&push_constants));
+                        device.vk().cmd_draw_indexed(cmd, draw.index_count, 1, draw.first_index, draw.vertex_offset, 0);
+                    }
+                }
+            }
+
+            unsafe {
+                device.vk().cmd_end_render_pass(cmd);
+            }
+        }
+    }
+}
+
+impl DebugPipelinePass {
+    fn set_viewport(&mut self, index: u32, viewport: vk::Viewport) {
+        self.viewports[index as usize] = viewport;
+        if self.current_viewport == Some(index) {
+            self.current_viewport = None;
+        }
+    }
+
+    /// Returns the scissor rect covering the intersection of a viewport, the framebuffer and the
+    /// scissor set by the pass.
+    fn make_viewport_scissor(viewport: &vk::Viewport, framebuffer_size: Vec2u32, scissor: Option<&vk::Rect2D>) -> vk::Rect2D {
+        let mut min_x = viewport.x.max(0f32).min(framebuffer_size[0] as f32) as u32;
+        let mut min_y = viewport.y.max(0f32).min(framebuffer_size[1] as f32) as u32;
+        let mut max_x = (viewport.x + viewport.width).ceil().max(0f32).min(framebuffer_size[0] as f32) as u32;
+        let mut max_y = (viewport.y + viewport.height).ceil().max(0f32).min(framebuffer_size[1] as f32) as u32;
+
+        if let Some(scissor) = scissor {
+            let scissor_max_x = (scissor.offset.x as i64) + (scissor.extent.width as i64);
+            let scissor_max_y = (scissor.offset.y as i64) + (scissor.extent.height as i64);
+            min_x = min_x.max(scissor.offset.x.max(0) as u32);
+            min_y = min_y.max(scissor.offset.y.max(0) as u32);
+            max_x = max_x.min(scissor_max_x.clamp(0, u32::MAX as i64) as u32);
+            max_y = max_y.min(scissor_max_y.clamp(0, u32::MAX as i64) as u32);
+        }
+
+        vk::Rect2D {
+            offset: vk::Offset2D { x: min_x as i32, y: min_y as i32 },
+            extent: vk::Extent2D { width: max_x.saturating_sub(min_x), height: max_y.saturating_sub(min_y) }
+        }
     }
 }
 
 impl EmulatorPipelinePass for DebugPipelinePass {
-    fn init(&mut self, _: &Queue, obj: &mut PooledObjectProvider, placeholder_texture: vk::ImageView, placeholder_sampler: vk::Sampler) {
-        self.placeholder_texture = placeholder_texture;
-        self.placeholder_sampler = placeholder_sampler;
+    fn init(&mut self, _: &Queue, obj: &mut PooledObjectProvider, placeholder_texture: TextureBinding) {
+        self.placeholder_texture = Some(placeholder_texture);
 
         let cmd = obj.get_begin_command_buffer().unwrap();
         self.command_buffer = Some(cmd);
@@ -1441,35 +2901,106 @@ impl EmulatorPipelinePass for DebugPipelinePass {
                 }
             }
         ];
-        let info = vk::RenderPassBeginInfo::builder()
+        let mut info = vk::RenderPassBeginInfo::builder()
             .render_pass(self.parent.render_pass)
             .framebuffer(self.parent.pass_objects[self.index].framebuffer)
             .render_area(make_full_rect(self.parent.framebuffer_size))
             .clear_values(&clear_values);
 
+        // In split frame mode each device only renders its own strip of the framebuffer
+        let split_areas = device.get_device_group()
+            .filter(|group| group.get_mode() == DeviceGroupMode::SplitFrame && obj.get_device_mask() != 0)
+            .map(|group| group.get_split_areas(self.parent.framebuffer_size));
+        let mut device_group_info = split_areas.as_ref().map(|areas| {
+            vk::DeviceGroupRenderPassBeginInfo::builder()
+                .device_mask(obj.get_device_mask())
+                .device_render_areas(areas)
+        });
+        if let Some(device_group_info) = device_group_info.as_mut() {
+            info = info.push_next(device_group_info);
+        }
+
         unsafe {
             device.vk().cmd_begin_render_pass(cmd, &info, vk::SubpassContents::INLINE);
+            device.vk().cmd_set_stencil_reference(cmd, vk::StencilFaceFlags::FRONT_AND_BACK, 0);
+        }
+
+        let shadow_info = vk::DescriptorImageInfo {
+            sampler: self.parent.shadow_pipeline.sampler,
+            image_view: self.parent.pass_objects[self.index].shadow_array_view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        };
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_binding(2)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(std::slice::from_ref(&shadow_info));
+
+        unsafe {
+            device.push_descriptor_khr().cmd_push_descriptor_set(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.parent.draw_pipeline.pipeline_layout,
+                0,
+                std::slice::from_ref(&write)
+            );
         }
+
+        // Draws recorded before a shadow camera is set are not shadowed
+        self.push_shadow_uniforms(&ShadowUniforms::disabled(), obj);
     }
 
     fn process_task(&mut self, task: &PipelineTask, obj: &mut PooledObjectProvider) {
         match task {
+            PipelineTask::SetPartialTick(partial_tick) => {
+                self.partial_tick = *partial_tick;
+                for tracker in self.shader_uniforms.values_mut() {
+                    tracker.set_partial_tick(*partial_tick);
+                }
+            }
             PipelineTask::UpdateUniform(shader, data) => {
                 self.update_uniform(*shader, data);
             }
-            PipelineTask::UpdateTexture(shader, index, view, sampler) => {
-                self.update_texture(*shader, *index, *view, *sampler);
+            PipelineTask::UpdateTexture(shader, index, texture) => {
+                self.update_texture(*shader, *index, *texture);
+            }
+            PipelineTask::BindTexture(slot, texture) => {
+                self.bound_textures[*slot as usize] = *texture;
+            }
+            PipelineTask::SetViewport(index, viewport) => {
+                self.set_viewport(*index, *viewport);
+            }
+            PipelineTask::SetShadowCamera(camera, light_direction, effects) => {
+                self.set_shadow_camera(camera, light_direction, effects, obj);
+            }
+            PipelineTask::SetScissor(scissor) => {
+                self.scissor = *scissor;
+                self.current_viewport = None;
             }
             PipelineTask::Draw(task) => {
                 self.draw(task, obj);
             }
+            PipelineTask::BeginPortal(mask) => {
+                self.begin_portal(mask.as_ref(), obj);
+            }
+            PipelineTask::EndPortal(mask) => {
+                self.end_portal(mask.as_ref(), obj);
+            }
         }
     }
 
-    fn record<'a>(&mut self, _: &mut PooledObjectProvider, submits: &mut SubmitRecorder<'a>, alloc: &'a Bump) {
+    fn record<'a>(&mut self, obj: &mut PooledObjectProvider, submits: &mut SubmitRecorder<'a>, alloc: &'a Bump) {
         let device = self.parent.emulator.get_device();
         let cmd = self.command_buffer.take().unwrap();
 
+        // The shadow maps are rendered in a separate command buffer submitted before the main one
+        // since all draws of the pass need to be known first
+        let shadow_cmd = obj.get_begin_command_buffer().unwrap();
+        self.record_shadows(shadow_cmd);
+        unsafe {
+            device.vk().end_command_buffer(shadow_cmd).unwrap();
+        }
+
         let bg_descriptor_sets = [self.parent.pass_objects[self.index].bg_descriptor_set];
 
         unsafe {
@@ -1480,18 +3011,20 @@ impl EmulatorPipelinePass for DebugPipelinePass {
         }
 
         let image_barrier = [
+            // Both images are only sampled by the output blit and post passes after the render pass.
+            // The output image is also read by the auto exposure histogram.
             vk::ImageMemoryBarrier2::builder()
-                .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
-                .src_access_mask(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE)
-                .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
-                .dst_access_mask(vk::AccessFlags2::MEMORY_READ)
+                .src_stage_mask(vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS)
+                .src_access_mask(vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags2::SHADER_SAMPLED_READ)
                 .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                 .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                 .src_queue_family_index(0)
                 .dst_queue_family_index(0)
                 .image(self.parent.pass_objects[self.index].depth_image)
                 .subresource_range(vk::ImageSubresourceRange {
-                    aspect_mask: vk::ImageAspectFlags::DEPTH,
+                    aspect_mask: get_depth_aspects(self.parent.depth_format),
                     base_mip_level: 0,
                     level_count: 1,
                     base_array_layer: 0,
@@ -1501,8 +3034,8 @@ impl EmulatorPipelinePass for DebugPipelinePass {
             vk::ImageMemoryBarrier2::builder()
                 .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
                 .src_access_mask(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE)
-                .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
-                .dst_access_mask(vk::AccessFlags2::MEMORY_READ)
+                .dst_stage_mask(vk::PipelineStageFlags2::FRAGMENT_SHADER | vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .dst_access_mask(vk::AccessFlags2::SHADER_SAMPLED_READ)
                 .old_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                 .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                 .src_queue_family_index(0)
@@ -1524,17 +3057,25 @@ impl EmulatorPipelinePass for DebugPipelinePass {
         unsafe {
             device.vk().cmd_end_render_pass(cmd);
 
-            device.synchronization_2_khr().cmd_pipeline_barrier2(cmd, &info);
+            device.cmd_pipeline_barrier2(cmd, &info);
 
             device.vk().end_command_buffer(cmd).unwrap();
         }
 
-        let command_buffer_info = alloc.alloc(vk::CommandBufferSubmitInfo::builder()
-            .command_buffer(cmd)
-        );
+        let device_mask = submits.get_device_mask();
+        let command_buffer_infos = alloc.alloc([
+            vk::CommandBufferSubmitInfo::builder()
+                .command_buffer(shadow_cmd)
+                .device_mask(device_mask)
+                .build(),
+            vk::CommandBufferSubmitInfo::builder()
+                .command_buffer(cmd)
+                .device_mask(device_mask)
+                .build(),
+        ]);
 
         submits.push(vk::SubmitInfo2::builder()
-            .command_buffer_infos(std::slice::from_ref(command_buffer_info))
+            .command_buffer_infos(command_buffer_infos)
         );
     }
 
@@ -1555,25 +3096,32 @@ impl Drop for DebugPipelinePass {
 
 struct UniformStateTracker {
     used_uniforms: McUniform,
+    game_time: f32,
+    partial_tick: f32,
     push_constants_dirty: bool,
     static_uniforms_dirty: bool,
-    textures_dirty: bool,
     push_constant_cache: PushConstants,
     static_uniform_cache: StaticUniforms,
-    textures: [(vk::ImageView, vk::Sampler); 3],
+    textures: [TextureBinding; 3],
 }
 
 impl UniformStateTracker {
-    fn new(used_uniforms: McUniform, initial_texture: vk::ImageView, initial_sampler: vk::Sampler) -> Self {
+    /// The number of game ticks in one cycle of the game time uniform.
+    const TICKS_PER_GAME_TIME: f32 = 24000f32;
+
+    fn new(used_uniforms: McUniform, initial_texture: TextureBinding, partial_tick: f32) -> Self {
         Self {
             used_uniforms,
+            game_time: 0f32,
+            partial_tick,
             push_constants_dirty: true,
             static_uniforms_dirty: true,
-            textures_dirty: true,
             push_constant_cache: PushConstants {
                 model_view_matrix: Mat4f32::identity(),
                 chunk_offset: Vec3f32::zeros(),
                 _padding0: Default::default(),
+                item_animation: Vec2f32::zeros(),
+                _padding1: Default::default(),
             },
             static_uniform_cache: StaticUniforms {
                 projection_matrix: Mat4f32::identity(),
@@ -1585,7 +3133,7 @@ impl UniformStateTracker {
                 fog_shape: 0,
                 _padding2: Default::default(),
             },
-            textures: [(initial_texture, initial_sampler); 3],
+            textures: [initial_texture; 3],
         }
     }
 
@@ -1641,8 +3189,8 @@ impl UniformStateTracker {
             McUniformData::LineWidth(_) => {}
             McUniformData::GameTime(time) => {
                 if self.used_uniforms.contains(&McUniform::GAME_TIME) {
-                    self.static_uniform_cache.fog_range_and_game_time[2] = *time;
-                    self.static_uniforms_dirty = true;
+                    self.game_time = *time;
+                    self.update_game_time();
                 }
             }
             McUniformData::ChunkOffset(offset) => {
@@ -1651,27 +3199,45 @@ impl UniformStateTracker {
                     self.push_constants_dirty = true;
                 }
             }
+            McUniformData::ItemAnimation(animation) => {
+                if self.used_uniforms.contains(&McUniform::ITEM_ANIMATION) {
+                    self.push_constant_cache.item_animation = *animation;
+                    self.push_constants_dirty = true;
+                }
+            }
+        }
+    }
+
+    fn set_partial_tick(&mut self, partial_tick: f32) {
+        if self.partial_tick != partial_tick {
+            self.partial_tick = partial_tick;
+            if self.used_uniforms.contains(&McUniform::GAME_TIME) {
+                self.update_game_time();
+            }
         }
     }
 
-    fn update_texture(&mut self, index: u32, view: vk::ImageView, sampler: vk::Sampler) {
+    /// The game time uniform only changes once per tick. To get smooth animations we add the
+    /// partial tick to it.
+    fn update_game_time(&mut self) {
+        let time = self.game_time + (self.partial_tick / Self::TICKS_PER_GAME_TIME);
+        self.static_uniform_cache.fog_range_and_game_time[2] = time.fract();
+        self.static_uniforms_dirty = true;
+    }
+
+    fn update_texture(&mut self, index: u32, texture: TextureBinding) {
         match index {
-            0 => {
-                self.textures[0] = (view, sampler);
-                self.textures_dirty = true;
-            },
-            1 => {
-                self.textures[1] = (view, sampler);
-                self.textures_dirty = true;
-            },
-            2 => {
-                self.textures[2] = (view, sampler);
-                self.textures_dirty = true;
-            },
+            0 => self.textures[0] = texture,
+            1 => self.textures[1] = texture,
+            2 => self.textures[2] = texture,
             _ => log::warn!("Called updated texture on index {:?} which is out of bounds", index),
         }
     }
 
+    fn get_push_constants(&self) -> &PushConstants {
+        &self.push_constant_cache
+    }
+
     fn validate_push_constants(&mut self) -> Option<&PushConstants> {
         if self.push_constants_dirty {
             self.push_constants_dirty = false;
@@ -1690,13 +3256,8 @@ impl UniformStateTracker {
         }
     }
 
-    fn validate_textures(&mut self) -> Option<&[(vk::ImageView, vk::Sampler); 3]> {
-        if self.textures_dirty {
-            self.textures_dirty = false;
-            Some(&self.textures)
-        } else {
-            None
-        }
+    fn get_textures(&self) -> &[TextureBinding; 3] {
+        &self.textures
     }
 }
 
@@ -1710,13 +3271,149 @@ struct PushConstants {
     chunk_offset: Vec3f32,
 
     _padding0: [u8; 4],
+
+    #[allow(unused)]
+    item_animation: Vec2f32,
+
+    _padding1: [u8; 8],
 }
-const_assert_eq!(std::mem::size_of::<PushConstants>(), 80);
+const_assert_eq!(std::mem::size_of::<PushConstants>(), 96);
 const_assert_eq!(std::mem::size_of::<PushConstants>() % 16, 0);
 
 unsafe impl Zeroable for PushConstants {}
 unsafe impl Pod for PushConstants {}
 
+/// A depth writing draw of the pass rendered into the shadow map cascades.
+struct ShadowDraw {
+    key: ShadowPipelineKey,
+    vertex_buffer: vk::Buffer,
+    index_buffer: vk::Buffer,
+    index_type: vk::IndexType,
+    index_count: u32,
+    first_index: u32,
+    vertex_offset: i32,
+
+    /// The model view matrix including the chunk offset.
+    model_view: Mat4f32,
+    position_offset: Vec3f32,
+    position_scale: Vec3f32,
+
+    /// The bounds of the decoded positions if the vertex format is quantized.
+    bounds: Option<(Vec3f32, Vec3f32)>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ShadowPushConstants {
+    #[allow(unused)]
+    transform: Mat4f32,
+
+    #[allow(unused)]
+    position_offset: Vec4f32,
+
+    #[allow(unused)]
+    position_scale: Vec4f32,
+}
+const_assert_eq!(std::mem::size_of::<ShadowPushConstants>(), 96);
+
+unsafe impl Zeroable for ShadowPushConstants {}
+unsafe impl Pod for ShadowPushConstants {}
+
+/// Pushed directly after [`PushConstants`] whenever the bound textures change.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+struct ImageLayoutConstants {
+    #[allow(unused)]
+    packed_atlas_grids: [u32; 3],
+
+    _padding0: [u8; 4],
+}
+const_assert_eq!(std::mem::size_of::<ImageLayoutConstants>(), 16);
+
+unsafe impl Zeroable for ImageLayoutConstants {}
+unsafe impl Pod for ImageLayoutConstants {}
+
+/// Pushed directly after [`ImageLayoutConstants`] if vertex pulling is enabled. Describes the
+/// layout of the vertex format of the current shader. See mc_vertex_pulling.glsl.
+#[repr(C)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+struct VertexFetchConstants {
+    #[allow(unused)]
+    stride: u32,
+
+    #[allow(unused)]
+    position: u32,
+
+    #[allow(unused)]
+    attribute: u32,
+
+    _padding0: [u8; 4],
+}
+const_assert_eq!(std::mem::size_of::<VertexFetchConstants>(), 16);
+const_assert_eq!(std::mem::size_of::<PushConstants>() + std::mem::size_of::<ImageLayoutConstants>() + std::mem::size_of::<VertexFetchConstants>(), 128);
+
+unsafe impl Zeroable for VertexFetchConstants {}
+unsafe impl Pod for VertexFetchConstants {}
+
+impl VertexFetchConstants {
+    /// Packs a vertex format entry into the format used by mc_vertex_pulling.glsl. The offset is
+    /// stored in the low 16 bits, the component type in bits 16-23 and the component count in
+    /// bits 24-31.
+    ///
+    /// Returns [`None`] if the pulled vertex shader cannot decode the format or the offset does
+    /// not fit into 16 bits.
+    fn pack_entry(entry: &VertexFormatEntry) -> Option<u32> {
+        let (ty, count) = match entry.format {
+            vk::Format::R32_SFLOAT => (1u32, 1u32),
+            vk::Format::R32G32_SFLOAT => (1, 2),
+            vk::Format::R32G32B32_SFLOAT => (1, 3),
+            vk::Format::R32G32B32A32_SFLOAT => (1, 4),
+            vk::Format::R8_UNORM => (2, 1),
+            vk::Format::R8G8_UNORM => (2, 2),
+            vk::Format::R8G8B8_UNORM => (2, 3),
+            vk::Format::R8G8B8A8_UNORM => (2, 4),
+            vk::Format::R8_SNORM => (3, 1),
+            vk::Format::R8G8_SNORM => (3, 2),
+            vk::Format::R8G8B8_SNORM => (3, 3),
+            vk::Format::R8G8B8A8_SNORM => (3, 4),
+            vk::Format::R8_UINT => (4, 1),
+            vk::Format::R8G8_UINT => (4, 2),
+            vk::Format::R8G8B8_UINT => (4, 3),
+            vk::Format::R8G8B8A8_UINT => (4, 4),
+            vk::Format::R8_SINT => (5, 1),
+            vk::Format::R8G8_SINT => (5, 2),
+            vk::Format::R8G8B8_SINT => (5, 3),
+            vk::Format::R8G8B8A8_SINT => (5, 4),
+            vk::Format::R16_UNORM => (6, 1),
+            vk::Format::R16G16_UNORM => (6, 2),
+            vk::Format::R16G16B16_UNORM => (6, 3),
+            vk::Format::R16G16B16A16_UNORM => (6, 4),
+            vk::Format::R16_SNORM => (7, 1),
+            vk::Format::R16G16_SNORM => (7, 2),
+            vk::Format::R16G16B16_SNORM => (7, 3),
+            vk::Format::R16G16B16A16_SNORM => (7, 4),
+            vk::Format::R16_UINT => (8, 1),
+            vk::Format::R16G16_UINT => (8, 2),
+            vk::Format::R16G16B16_UINT => (8, 3),
+            vk::Format::R16G16B16A16_UINT => (8, 4),
+            vk::Format::R16_SINT => (9, 1),
+            vk::Format::R16G16_SINT => (9, 2),
+            vk::Format::R16G16B16_SINT => (9, 3),
+            vk::Format::R16G16B16A16_SINT => (9, 4),
+            vk::Format::R16_SFLOAT => (10, 1),
+            vk::Format::R16G16_SFLOAT => (10, 2),
+            vk::Format::R16G16B16_SFLOAT => (10, 3),
+            vk::Format::R16G16B16A16_SFLOAT => (10, 4),
+            _ => return None,
+        };
+        if entry.offset > 0xFFFF {
+            return None;
+        }
+
+        Some(entry.offset | (ty << 16) | (count << 24))
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Default)]
 struct StaticUniforms {
@@ -1756,13 +3453,37 @@ fn try_create_shader_module(device: &DeviceContext, data: &[u8], name: &str) ->
     })
 }
 
+/// Returns the aspects of a depth format which are used as a framebuffer attachment.
+fn get_depth_aspects(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT => vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+        _ => vk::ImageAspectFlags::DEPTH,
+    }
+}
+
+/// The dynamic states of all draw pipelines.
+const DYNAMIC_STATES: [vk::DynamicState; 3] = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR, vk::DynamicState::STENCIL_REFERENCE];
+
+/// The dynamic states of pre-rasterization shader libraries.
+const PRE_RASTERIZATION_DYNAMIC_STATES: [vk::DynamicState; 2] = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+
+/// The dynamic states of fragment shader libraries.
+const FRAGMENT_DYNAMIC_STATES: [vk::DynamicState; 1] = [vk::DynamicState::STENCIL_REFERENCE];
+
+/// The sample count of all draw pipelines created by the debug pipeline.
+const RASTERIZATION_SAMPLES: vk::SampleCountFlags = vk::SampleCountFlags::TYPE_1;
+
 const SHADER_ENTRY: &'static CStr = unsafe { CStr::from_bytes_with_nul_unchecked(b"main\0") }; // GOD I LOVE RUSTS FFI API IT IS SO NICE AND DEFINITELY NOT STUPID WITH WHICH FUNCTIONS ARE CONST AND WHICH AREN'T
 static DEBUG_POSITION_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/position_vert.spv"));
 static DEBUG_COLOR_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/color_vert.spv"));
+static DEBUG_NORMAL_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/normal_vert.spv"));
 static DEBUG_UV_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/uv_vert.spv"));
+static DEBUG_PULLED_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/pulled_vert.spv"));
 static DEBUG_NULL_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/null_vert.spv"));
 static DEBUG_FRAGMENT_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/debug_frag.spv"));
 static TEXTURED_FRAGMENT_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/textured_frag.spv"));
 
+static SHADOW_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/shadow_vert.spv"));
+
 static BACKGROUND_VERTEX_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/background_vert.spv"));
 static BACKGROUND_FRAGMENT_BIN: &'static [u8] = include_bytes_aligned!(4, concat!(env!("B4D_RESOURCE_DIR"), "emulator/debug/background_frag.spv"));
\ No newline at end of file
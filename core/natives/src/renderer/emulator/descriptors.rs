@@ -12,16 +12,22 @@ pub(super) struct DescriptorPool {
 }
 
 impl DescriptorPool {
-    pub(super) fn new(device: Arc<DeviceContext>) -> Self {
-        let uniform_buffer_pool = UniformBufferPool::new(&device);
+    pub(super) fn new(device: Arc<DeviceContext>, frames_in_flight: u32) -> Self {
+        let uniform_buffer_pool = UniformBufferPool::new(&device, frames_in_flight);
         Self {
             device,
             uniform_buffer_pool,
         }
     }
 
-    pub(super) fn allocate_uniform(&mut self, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
-        self.uniform_buffer_pool.allocate_write(data)
+    pub(super) fn allocate_uniform(&mut self, frame_index: u32, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
+        self.uniform_buffer_pool.allocate_write(frame_index, data)
+    }
+
+    /// Resets the uniform region of a frame. Must only be called once all passes which used the
+    /// frame index have completed.
+    pub(super) fn reset_frame(&mut self, frame_index: u32) {
+        self.uniform_buffer_pool.reset(frame_index);
     }
 }
 
@@ -31,17 +37,24 @@ impl Drop for DescriptorPool {
     }
 }
 
+/// A uniform buffer split into one region per frame in flight. Allocations of a frame are
+/// linearly suballocated from its region which is reset once the frame slot is reused.
 struct UniformBufferPool {
     buffer_allocation: Allocation,
     buffer: vk::Buffer,
-    buffer_size: usize,
-    current_offset: usize,
+    region_size: usize,
+    current_offsets: Box<[usize]>,
     mapped_ptr: NonNull<u8>,
 }
 
 impl UniformBufferPool {
-    fn new(device: &DeviceContext) -> Self {
+    // We align to 256bytes because that was the highest in the gpuinfo database
+    const ALIGNMENT: usize = 256;
+
+    fn new(device: &DeviceContext, frames_in_flight: u32) -> Self {
         let target_size = 2usize.pow(25); // ~32MB
+        let region_size = (target_size / frames_in_flight as usize) & !(Self::ALIGNMENT - 1);
+
         let info = vk::BufferCreateInfo::builder()
             .size(target_size as vk::DeviceSize)
             .usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
@@ -54,39 +67,41 @@ impl UniformBufferPool {
         Self {
             buffer_allocation,
             buffer,
-            buffer_size: target_size,
-            current_offset: 0,
+            region_size,
+            current_offsets: vec![0usize; frames_in_flight as usize].into_boxed_slice(),
             mapped_ptr: ptr.unwrap(),
         }
     }
 
-    fn allocate_write(&mut self, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
-        // We just allocate a new slot hoping that it isn't in use anymore. This is not that dangerous right now since we have a 32MB buffer which equates to roughly 100k slots
-        // but it for sure can't become a permanent solution.
+    fn allocate_write(&mut self, frame_index: u32, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
         let src = data;
         if src.len() > 1024 { // Just a sanity check all of our uniforms currently are < 256
             panic!("Wtf are you doing???");
         }
 
-        // We align to 256bytes because that was the highest in the gpuinfo database (Yes this is entire module is very much a TODO)
-        let add = 256 - (self.current_offset % 256);
-        let mut base_offset = self.current_offset + add;
-
-        if base_offset + src.len() > self.buffer_size {
-            base_offset = 0;
+        let current_offset = &mut self.current_offsets[frame_index as usize];
+        let mut offset = (*current_offset + Self::ALIGNMENT - 1) & !(Self::ALIGNMENT - 1);
+        if offset + src.len() > self.region_size {
+            // The region of this frame is exhausted. Wrapping around may overwrite uniforms which
+            // are still used by this frame but ~100k slots per frame should never be exhausted.
+            log::warn!("Uniform region of frame {} exhausted. Wrapping around", frame_index);
+            offset = 0;
         }
-        let base_offset = base_offset;
-
-        self.current_offset = base_offset + src.len();
+        *current_offset = offset + src.len();
 
+        let base_offset = (frame_index as usize * self.region_size) + offset;
         let dst = unsafe {
-            std::slice::from_raw_parts_mut(self.mapped_ptr.as_ptr().offset(base_offset as isize), src.len())
+            std::slice::from_raw_parts_mut(self.mapped_ptr.as_ptr().add(base_offset), src.len())
         };
         dst.copy_from_slice(src);
 
         (self.buffer, base_offset as vk::DeviceSize)
     }
 
+    fn reset(&mut self, frame_index: u32) {
+        self.current_offsets[frame_index as usize] = 0;
+    }
+
     fn destroy(&mut self, device: &DeviceContext) {
         unsafe {
             device.get_allocator().destroy_buffer(self.buffer, self.buffer_allocation)
@@ -0,0 +1,231 @@
+//! Cpu side merging of small draws into a shared transient mesh.
+//!
+//! Item heavy guis and holograms issue a large number of draws with only a few vertices each. The
+//! [`DrawMerger`] concatenates consecutive small draws with identical state into a single mesh so
+//! they can be rendered with one draw call. Each draw may provide a transform which is applied to
+//! the vertex positions (and normals) on the cpu, allowing draws which would otherwise require
+//! different model view matrices to be merged.
+//!
+//! Only consecutive draws are merged so the resulting draw order is identical to the submitted one.
+
+use ash::vk;
+
+use crate::renderer::emulator::mc_shaders::{ShaderId, VertexFormat, VertexFormatEntry};
+use crate::renderer::emulator::mesh_optimizer::read_indices;
+use crate::renderer::emulator::quantization::NormalEncoding;
+use crate::renderer::emulator::MeshData;
+
+use crate::prelude::*;
+
+/// The state which must be identical for draws to be merged.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct MergeKey {
+    pub shader: ShaderId,
+    pub depth_write_enable: bool,
+    pub viewport_index: u32,
+    pub vertex_stride: u32,
+    pub primitive_topology: vk::PrimitiveTopology,
+}
+
+/// The result of merging multiple draws. Always uses 32bit indices.
+pub struct MergedMesh {
+    pub key: MergeKey,
+    pub vertex_data: Vec<u8>,
+    pub index_data: Vec<u8>,
+    pub index_count: u32,
+    /// The number of draws merged into this mesh.
+    pub draw_count: u32,
+}
+
+impl MergedMesh {
+    pub fn as_mesh_data(&self) -> MeshData {
+        MeshData {
+            vertex_data: &self.vertex_data,
+            index_data: &self.index_data,
+            vertex_stride: self.key.vertex_stride,
+            index_count: self.index_count,
+            index_type: vk::IndexType::UINT32,
+            primitive_topology: self.key.primitive_topology,
+        }
+    }
+}
+
+/// Collects consecutive small draws with the same [`MergeKey`].
+pub struct DrawMerger {
+    key: Option<MergeKey>,
+    vertex_data: Vec<u8>,
+    index_data: Vec<u8>,
+    vertex_count: u32,
+    index_count: u32,
+    draw_count: u32,
+}
+
+impl DrawMerger {
+    /// Draws with more vertices than this are not considered small and are never merged.
+    pub const MAX_DRAW_VERTICES: u32 = 256;
+
+    /// A merged mesh is flushed once it contains this many vertices.
+    pub const MAX_MERGED_VERTICES: u32 = 65536;
+
+    pub fn new() -> Self {
+        Self {
+            key: None,
+            vertex_data: Vec::new(),
+            index_data: Vec::new(),
+            vertex_count: 0,
+            index_count: 0,
+            draw_count: 0,
+        }
+    }
+
+    /// Returns true if no draws are pending.
+    pub fn is_empty(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// Returns true if the draw is small enough and uses a topology which can be merged. If a
+    /// transform is used the position (and normal if present) must be stored in a supported
+    /// format. See [`transform_vertices`].
+    pub fn can_merge(data: &MeshData, format: &VertexFormat, transform: bool) -> bool {
+        if data.vertex_stride == 0 || data.vertex_stride != format.stride {
+            return false;
+        }
+        match data.primitive_topology {
+            vk::PrimitiveTopology::TRIANGLE_LIST | vk::PrimitiveTopology::LINE_LIST | vk::PrimitiveTopology::POINT_LIST => {},
+            _ => return false,
+        }
+        let vertex_count = data.vertex_data.len() / (data.vertex_stride as usize);
+        if vertex_count > Self::MAX_DRAW_VERTICES as usize {
+            return false;
+        }
+        !transform || can_transform(format)
+    }
+
+    /// Adds a draw to the merger. If pending draws with a different key exist or the merged mesh
+    /// would become too large the pending draws are returned as a [`MergedMesh`] and must be drawn
+    /// before any later draw.
+    ///
+    /// Returns `Err` with any flushed mesh if the draw references vertices outside of its vertex
+    /// data. In that case the draw was not added and must be drawn unmerged.
+    pub fn push(&mut self, key: MergeKey, data: &MeshData, format: &VertexFormat, transform: Option<&Mat4f32>) -> Result<Option<MergedMesh>, Option<MergedMesh>> {
+        let vertex_count = (data.vertex_data.len() / (data.vertex_stride as usize)) as u32;
+
+        let flushed = if self.key != Some(key) || self.vertex_count + vertex_count > Self::MAX_MERGED_VERTICES {
+            self.flush()
+        } else {
+            None
+        };
+
+        let indices = match read_indices(data) {
+            Some(indices) if indices.iter().all(|index| *index < vertex_count) => indices,
+            _ => return Err(flushed),
+        };
+
+        let vertex_base = self.vertex_data.len();
+        self.vertex_data.extend_from_slice(&data.vertex_data[0..((vertex_count * data.vertex_stride) as usize)]);
+        if let Some(transform) = transform {
+            transform_vertices(&mut self.vertex_data[vertex_base..], format, transform);
+        }
+
+        for index in indices {
+            self.index_data.extend_from_slice(&(index + self.vertex_count).to_ne_bytes());
+        }
+
+        self.key = Some(key);
+        self.vertex_count += vertex_count;
+        self.index_count += data.index_count;
+        self.draw_count += 1;
+
+        Ok(flushed)
+    }
+
+    /// Returns all pending draws as a single mesh.
+    pub fn flush(&mut self) -> Option<MergedMesh> {
+        let key = self.key.take()?;
+
+        let mesh = MergedMesh {
+            key,
+            vertex_data: std::mem::take(&mut self.vertex_data),
+            index_data: std::mem::take(&mut self.index_data),
+            index_count: self.index_count,
+            draw_count: self.draw_count,
+        };
+        self.vertex_count = 0;
+        self.index_count = 0;
+        self.draw_count = 0;
+
+        Some(mesh)
+    }
+}
+
+impl Default for DrawMerger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns true if [`transform_vertices`] supports the position and normal format of the vertex
+/// format. Quantized positions and octahedral encoded normals are not supported.
+pub fn can_transform(format: &VertexFormat) -> bool {
+    if format.position_quantization.is_some() || format.normal_encoding != NormalEncoding::Direct {
+        return false;
+    }
+    is_transformable_position(&format.position) && format.normal.as_ref().map_or(true, is_transformable_normal)
+}
+
+/// Applies a transform to the positions and normals of tightly packed vertex data. Positions
+/// must be stored as `R32G32B32_SFLOAT` or `R32G32B32A32_SFLOAT`. Normals are transformed by the
+/// upper 3x3 matrix of the transform and renormalized and must be stored as `R32G32B32_SFLOAT`,
+/// `R8G8B8_SNORM` or `R8G8B8A8_SNORM`. Unsupported formats are left unchanged. See
+/// [`can_transform`].
+pub fn transform_vertices(vertex_data: &mut [u8], format: &VertexFormat, transform: &Mat4f32) {
+    let stride = format.stride as usize;
+    let normal_matrix = transform.fixed_slice::<3, 3>(0, 0).into_owned();
+
+    for vertex in vertex_data.chunks_exact_mut(stride) {
+        if is_transformable_position(&format.position) {
+            let offset = format.position.offset as usize;
+            let position = read_f32x3(&vertex[offset..]);
+            let position = transform.transform_point(&position.into());
+            write_f32x3(&mut vertex[offset..], &position.coords);
+        }
+
+        if let Some(normal) = &format.normal {
+            let offset = normal.offset as usize;
+            match normal.format {
+                vk::Format::R32G32B32_SFLOAT => {
+                    let value = normal_matrix * read_f32x3(&vertex[offset..]);
+                    write_f32x3(&mut vertex[offset..], &value.try_normalize(f32::EPSILON).unwrap_or(value));
+                }
+                vk::Format::R8G8B8_SNORM | vk::Format::R8G8B8A8_SNORM => {
+                    let read = |i: usize| ((vertex[offset + i] as i8) as f32 / (i8::MAX as f32)).max(-1f32);
+                    let value = normal_matrix * Vec3f32::new(read(0), read(1), read(2));
+                    let value = value.try_normalize(f32::EPSILON).unwrap_or(value);
+                    for i in 0..3 {
+                        vertex[offset + i] = ((value[i].clamp(-1f32, 1f32) * (i8::MAX as f32)).round() as i8) as u8;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_transformable_position(position: &VertexFormatEntry) -> bool {
+    matches!(position.format, vk::Format::R32G32B32_SFLOAT | vk::Format::R32G32B32A32_SFLOAT)
+}
+
+fn is_transformable_normal(normal: &VertexFormatEntry) -> bool {
+    matches!(normal.format, vk::Format::R32G32B32_SFLOAT | vk::Format::R8G8B8_SNORM | vk::Format::R8G8B8A8_SNORM)
+}
+
+fn read_f32x3(data: &[u8]) -> Vec3f32 {
+    let c = |i: usize| f32::from_ne_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    Vec3f32::new(c(0), c(4), c(8))
+}
+
+fn write_f32x3(data: &mut [u8], value: &Vec3f32) {
+    for i in 0..3 {
+        data[(i * 4)..((i * 4) + 4)].copy_from_slice(&value[i].to_ne_bytes());
+    }
+}
@@ -0,0 +1,263 @@
+//! Draw submission through a ring buffer shared with the host.
+//!
+//! Draw heavy frames spend a significant amount of time crossing the JNI boundary for every draw.
+//! A [`DrawRing`] lets the host write draw commands and immediate mesh data into a region of memory
+//! it owns (for example a mapped file or a direct byte buffer) which the renderer consumes in one
+//! call per frame.
+//!
+//! The region starts with a [`HEADER_SIZE`] byte header containing the write offset (written by
+//! the host) and the read offset (written by the renderer) as u32 values in native byte order.
+//! Both are offsets into the data area following the header. The ring is empty if both are equal,
+//! so the host must always leave at least 8 bytes free.
+//!
+//! Records are little endian and 8 byte aligned. Every record starts with a u32 opcode and the u32
+//! length of the record including this header. If a record does not fit before the end of the data
+//! area the host writes a [`OP_WRAP`] record and continues at offset 0. Mesh handles are the same
+//! values passed to the c api functions.
+
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::c_validation::{validate_index_type, validate_mesh_sizes, validate_primitive_topology};
+use crate::renderer::emulator::MeshData;
+use crate::renderer::emulator::command_log::{CommandLogError, Reader};
+use crate::renderer::emulator::mc_shaders::ShaderId;
+use crate::renderer::emulator::render_layer::RenderLayerId;
+
+use crate::prelude::*;
+
+pub const HEADER_SIZE: usize = 16;
+const WRITE_OFFSET: usize = 0;
+const READ_OFFSET: usize = 4;
+
+/// The smallest data area a ring can be created with.
+pub const MIN_DATA_SIZE: usize = 256;
+
+/// Skips the rest of the data area. The length of this record is ignored.
+pub const OP_WRAP: u32 = 0;
+/// `u64 mesh, u64 shader, u32 depth_write_enable, u32 padding`
+pub const OP_DRAW_GLOBAL: u32 = 1;
+/// `u64 shader, u32 depth_write_enable, u32 vertex_stride, u32 index_count, i32 index_type,
+/// i32 primitive_topology, u32 vertex_data_len, u32 index_data_len, u32 padding` followed by the
+/// vertex and index data.
+pub const OP_DRAW_IMMEDIATE: u32 = 2;
+/// `u32 enable, u32 padding, u64 tag`
+pub const OP_SET_DRAW_TAG: u32 = 3;
+/// `u64 layer` where 0 draws without a layer.
+pub const OP_SET_RENDER_LAYER: u32 = 4;
+/// `u32 priority, u32 padding`
+pub const OP_SET_DRAW_PRIORITY: u32 = 5;
+
+/// A decoded record of a [`DrawRing`].
+pub enum RingCommand<'a> {
+    /// The mesh is the raw handle written by the host and must be validated before it is used.
+    DrawGlobal {
+        mesh: u64,
+        shader: ShaderId,
+        depth_write_enable: bool,
+    },
+    DrawImmediate {
+        data: MeshData<'a>,
+        shader: ShaderId,
+        depth_write_enable: bool,
+    },
+    SetDrawTag(Option<u64>),
+    SetRenderLayer(Option<RenderLayerId>),
+    SetDrawPriority(u8),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RingError {
+    /// The memory is not 8 byte aligned.
+    Misaligned,
+    /// The memory is smaller than the header and [`MIN_DATA_SIZE`] or the data area is not a
+    /// multiple of 8 bytes.
+    InvalidSize,
+    /// The offsets or a record length are invalid.
+    Corrupt(&'static str),
+    InvalidOpcode(u32),
+    /// The data of an immediate draw is invalid.
+    InvalidMesh,
+}
+
+impl From<CommandLogError> for RingError {
+    fn from(_: CommandLogError) -> Self {
+        Self::Corrupt("record")
+    }
+}
+
+/// A ring buffer in host memory. See the [module docs](self).
+pub struct DrawRing {
+    memory: NonNull<u8>,
+    len: usize,
+}
+
+// The ring only accesses the host memory through atomics or in regions owned by the renderer.
+unsafe impl Send for DrawRing {
+}
+unsafe impl Sync for DrawRing {
+}
+
+impl DrawRing {
+    /// Creates a ring in host memory and resets both offsets to 0.
+    ///
+    /// # Safety
+    /// `memory` must be valid for reads and writes of `len` bytes until the ring is dropped.
+    pub unsafe fn new(memory: *mut u8, len: usize) -> Result<Self, RingError> {
+        let memory = NonNull::new(memory).ok_or(RingError::Misaligned)?;
+        if (memory.as_ptr() as usize) % 8 != 0 {
+            return Err(RingError::Misaligned);
+        }
+        if len < HEADER_SIZE + MIN_DATA_SIZE || len % 8 != 0 || (len - HEADER_SIZE) > (u32::MAX as usize) {
+            return Err(RingError::InvalidSize);
+        }
+
+        let ring = Self {
+            memory,
+            len,
+        };
+        ring.get_offset(WRITE_OFFSET).store(0, Ordering::Release);
+        ring.get_offset(READ_OFFSET).store(0, Ordering::Release);
+        Ok(ring)
+    }
+
+    /// Returns the size of the data area in bytes.
+    pub fn get_capacity(&self) -> usize {
+        self.len - HEADER_SIZE
+    }
+
+    /// Returns true if all written records have been consumed.
+    pub fn is_empty(&self) -> bool {
+        self.get_offset(WRITE_OFFSET).load(Ordering::Acquire) == self.get_offset(READ_OFFSET).load(Ordering::Relaxed)
+    }
+
+    /// Decodes all records written since the last call and passes them to `f` in order. Returns
+    /// the number of records passed to `f`.
+    ///
+    /// All records are decoded before any is passed to `f`. On error no record is passed to `f`
+    /// and the read offset is left unchanged so the host can inspect the ring.
+    pub fn consume<F>(&self, mut f: F) -> Result<usize, RingError> where F: FnMut(RingCommand) {
+        let capacity = self.get_capacity();
+        let write = self.get_offset(WRITE_OFFSET).load(Ordering::Acquire) as usize;
+        let read = self.get_offset(READ_OFFSET).load(Ordering::Relaxed) as usize;
+        if write >= capacity || write % 8 != 0 {
+            return Err(RingError::Corrupt("write_offset"));
+        }
+        if read >= capacity || read % 8 != 0 {
+            return Err(RingError::Corrupt("read_offset"));
+        }
+        if read == write {
+            return Ok(0);
+        }
+
+        // The published records are copied out of the host memory. The host does not write to the
+        // range between the offsets until the read offset has been advanced but may write to the
+        // rest of the data area at any time so no reference to the host memory is created.
+        let wrapped = write < read;
+        let first_len = if wrapped { capacity - read } else { write - read };
+        let second_len = if wrapped { write } else { 0 };
+        let mut records = vec![0u8; first_len + second_len];
+        unsafe {
+            let data = self.memory.as_ptr().add(HEADER_SIZE);
+            std::ptr::copy_nonoverlapping(data.add(read), records.as_mut_ptr(), first_len);
+            std::ptr::copy_nonoverlapping(data, records.as_mut_ptr().add(first_len), second_len);
+        }
+
+        let mut commands = Vec::new();
+        decode_records(&records[..first_len], wrapped, &mut commands)?;
+        decode_records(&records[first_len..], false, &mut commands)?;
+
+        let count = commands.len();
+        for command in commands {
+            f(command);
+        }
+
+        self.get_offset(READ_OFFSET).store(write as u32, Ordering::Release);
+        Ok(count)
+    }
+
+    fn get_offset(&self, offset: usize) -> &AtomicU32 {
+        unsafe { &*(self.memory.as_ptr().add(offset) as *const AtomicU32) }
+    }
+}
+
+/// Decodes all records in `data`. If `allow_wrap` is true a [`OP_WRAP`] record skips the rest of
+/// the data.
+fn decode_records<'a>(data: &'a [u8], allow_wrap: bool, commands: &mut Vec<RingCommand<'a>>) -> Result<(), RingError> {
+    let mut offset = 0;
+    while offset != data.len() {
+        let available = &data[offset..];
+
+        let mut reader = Reader { data: available, offset: 0 };
+        let opcode = reader.u32()?;
+        let length = reader.u32()? as usize;
+
+        if opcode == OP_WRAP {
+            if !allow_wrap {
+                return Err(RingError::Corrupt("wrap"));
+            }
+            return Ok(());
+        }
+        if length < 8 || length % 8 != 0 || length > available.len() {
+            return Err(RingError::Corrupt("length"));
+        }
+
+        let mut reader = Reader { data: &available[..length], offset: 8 };
+        commands.push(decode(opcode, &mut reader)?);
+        offset += length;
+    }
+    Ok(())
+}
+
+fn decode<'a>(opcode: u32, reader: &mut Reader<'a>) -> Result<RingCommand<'a>, RingError> {
+    Ok(match opcode {
+        OP_DRAW_GLOBAL => {
+            let mesh = reader.u64()?;
+            let shader = ShaderId::from_uuid(UUID::from_raw(reader.u64()?));
+            let depth_write_enable = reader.u32()? != 0;
+            RingCommand::DrawGlobal { mesh, shader, depth_write_enable }
+        }
+        OP_DRAW_IMMEDIATE => {
+            let shader = ShaderId::from_uuid(UUID::from_raw(reader.u64()?));
+            let depth_write_enable = reader.u32()? != 0;
+            let vertex_stride = reader.u32()?;
+            let index_count = reader.u32()?;
+            let index_type = reader.i32()?;
+            let primitive_topology = reader.i32()?;
+            let vertex_data_len = reader.u32()? as usize;
+            let index_data_len = reader.u32()? as usize;
+            reader.u32()?;
+
+            let index_type = validate_index_type(index_type).map_err(|_| RingError::InvalidMesh)?;
+            let primitive_topology = validate_primitive_topology(primitive_topology).map_err(|_| RingError::InvalidMesh)?;
+            validate_mesh_sizes(vertex_data_len, index_data_len, vertex_stride, index_count, index_type).map_err(|_| RingError::InvalidMesh)?;
+
+            RingCommand::DrawImmediate {
+                data: MeshData {
+                    vertex_data: reader.bytes(vertex_data_len)?,
+                    index_data: reader.bytes(index_data_len)?,
+                    vertex_stride,
+                    index_count,
+                    index_type,
+                    primitive_topology,
+                },
+                shader,
+                depth_write_enable,
+            }
+        }
+        OP_SET_DRAW_TAG => {
+            let enable = reader.u32()? != 0;
+            reader.u32()?;
+            let tag = reader.u64()?;
+            RingCommand::SetDrawTag(if enable { Some(tag) } else { None })
+        }
+        OP_SET_RENDER_LAYER => {
+            let layer = reader.u64()?;
+            RingCommand::SetRenderLayer(if layer == 0 { None } else { Some(RenderLayerId::from_uuid(UUID::from_raw(layer))) })
+        }
+        OP_SET_DRAW_PRIORITY => {
+            RingCommand::SetDrawPriority(reader.u32()?.min(u8::MAX as u32) as u8)
+        }
+        other => return Err(RingError::InvalidOpcode(other)),
+    })
+}
@@ -0,0 +1,35 @@
+//! Host provided tags attached to draws.
+//!
+//! The host can attach an arbitrary `u64` (for example a hash of the chunk position or an entity
+//! id) to all following draws using [`PassRecorder::set_draw_tag`]. Tags are recorded in command
+//! logs, inserted as debug labels into the command buffer if VK_EXT_debug_utils is enabled and
+//! accumulated into per tag statistics so the cost of individual chunks or entities can be
+//! attributed.
+//!
+//! [`PassRecorder::set_draw_tag`]: crate::renderer::emulator::PassRecorder::set_draw_tag
+
+use std::ffi::CString;
+
+/// The statistics of all draws submitted with the same tag in a pass.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct DrawTagStats {
+    pub draw_count: u64,
+    pub index_count: u64,
+}
+
+impl DrawTagStats {
+    pub(super) fn add_draw(&mut self, index_count: u32) {
+        self.draw_count += 1;
+        self.index_count += index_count as u64;
+    }
+
+    pub(super) fn remove_draw(&mut self, index_count: u32) {
+        self.draw_count -= 1;
+        self.index_count -= index_count as u64;
+    }
+}
+
+/// Returns the debug label inserted before draws with `tag`.
+pub fn make_debug_label(tag: u64) -> CString {
+    CString::new(format!("b4d draw tag {:#018x}", tag)).unwrap()
+}
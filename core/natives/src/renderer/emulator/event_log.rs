@@ -0,0 +1,204 @@
+//! Machine readable log of renderer events for external tools.
+//!
+//! Each event is serialized as a single line json object (JSONL) containing at least the event
+//! `type`, a sequence number `seq` and the time in microseconds since the log was started
+//! `time_us`. Lines are either appended to a file or passed to a callback.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use ash::vk;
+use json::JsonValue;
+
+#[derive(Clone, Debug)]
+pub enum RendererEvent {
+    FrameStart {
+        frame: u64,
+        width: u32,
+        height: u32,
+    },
+    /// The frame has been submitted. `cpu_time` is the time since the frame was started.
+    FrameEnd {
+        frame: u64,
+        cpu_time: Duration,
+    },
+    MeshUpload {
+        id: u64,
+        vertex_bytes: usize,
+        index_bytes: usize,
+    },
+    ImageCreate {
+        id: u64,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        format: vk::Format,
+    },
+    SwapchainCreate {
+        width: u32,
+        height: u32,
+    },
+    /// The swapchain could not be created. Frames are skipped until a swapchain can be created.
+    SwapchainCreateFailed {
+        width: u32,
+        height: u32,
+        error: String,
+    },
+    /// The swapchain is out of date or suboptimal and will be recreated.
+    SwapchainInvalidate {
+        suboptimal: bool,
+    },
+    /// The main surface has no size, usually because the window was minimized. Frames are skipped
+    /// until [`RendererEvent::SurfaceResume`].
+    SurfacePause,
+    SurfaceResume,
+    /// The device is being recreated. `reason` is the debug name of the
+    /// [`crate::b4d::DeviceLostReason`].
+    DeviceRecreate {
+        reason: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl RendererEvent {
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            Self::FrameStart { .. } => "frame_start",
+            Self::FrameEnd { .. } => "frame_end",
+            Self::MeshUpload { .. } => "mesh_upload",
+            Self::ImageCreate { .. } => "image_create",
+            Self::SwapchainCreate { .. } => "swapchain_create",
+            Self::SwapchainCreateFailed { .. } => "swapchain_create_failed",
+            Self::SwapchainInvalidate { .. } => "swapchain_invalidate",
+            Self::SurfacePause => "surface_pause",
+            Self::SurfaceResume => "surface_resume",
+            Self::DeviceRecreate { .. } => "device_recreate",
+            Self::Error { .. } => "error",
+        }
+    }
+
+    /// Serializes the event fields into `object`.
+    fn write_fields(&self, object: &mut JsonValue) {
+        match self {
+            Self::FrameStart { frame, width, height } => {
+                object["frame"] = (*frame).into();
+                object["width"] = (*width).into();
+                object["height"] = (*height).into();
+            }
+            Self::FrameEnd { frame, cpu_time } => {
+                object["frame"] = (*frame).into();
+                object["cpu_time_us"] = (cpu_time.as_micros() as u64).into();
+            }
+            Self::MeshUpload { id, vertex_bytes, index_bytes } => {
+                object["id"] = (*id).into();
+                object["vertex_bytes"] = (*vertex_bytes).into();
+                object["index_bytes"] = (*index_bytes).into();
+            }
+            Self::ImageCreate { id, width, height, mip_levels, format } => {
+                object["id"] = (*id).into();
+                object["width"] = (*width).into();
+                object["height"] = (*height).into();
+                object["mip_levels"] = (*mip_levels).into();
+                object["format"] = format!("{:?}", format).into();
+            }
+            Self::SwapchainCreate { width, height } => {
+                object["width"] = (*width).into();
+                object["height"] = (*height).into();
+            }
+            Self::SwapchainCreateFailed { width, height, error } => {
+                object["width"] = (*width).into();
+                object["height"] = (*height).into();
+                object["error"] = error.as_str().into();
+            }
+            Self::SwapchainInvalidate { suboptimal } => {
+                object["suboptimal"] = (*suboptimal).into();
+            }
+            Self::SurfacePause | Self::SurfaceResume => {}
+            Self::DeviceRecreate { reason } => {
+                object["reason"] = reason.as_str().into();
+            }
+            Self::Error { message } => {
+                object["message"] = message.as_str().into();
+            }
+        }
+    }
+}
+
+pub enum EventLogTarget {
+    /// Events are appended to the file. The file is created if it does not exist.
+    File(PathBuf),
+    /// Called with each serialized line without the trailing newline.
+    Callback(Box<dyn Fn(&str) + Send>),
+}
+
+enum EventSink {
+    File(BufWriter<File>),
+    Callback(Box<dyn Fn(&str) + Send>),
+}
+
+pub(crate) struct EventLog {
+    sink: EventSink,
+    start: Instant,
+    sequence: u64,
+}
+
+impl EventLog {
+    pub(crate) fn new(target: EventLogTarget) -> std::io::Result<Self> {
+        let sink = match target {
+            EventLogTarget::File(path) => {
+                let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+                EventSink::File(BufWriter::new(file))
+            }
+            EventLogTarget::Callback(callback) => EventSink::Callback(callback),
+        };
+
+        Ok(Self {
+            sink,
+            start: Instant::now(),
+            sequence: 0,
+        })
+    }
+
+    pub(crate) fn push(&mut self, event: &RendererEvent) {
+        let line = serialize_event(event, self.sequence, self.start.elapsed());
+        self.sequence += 1;
+
+        match &mut self.sink {
+            EventSink::File(writer) => {
+                let result = writer.write_all(line.as_bytes()).and_then(|_| writer.write_all(b"\n"));
+                // Flush once per frame so tools tailing the file see complete frames
+                let result = result.and_then(|_| if matches!(event, RendererEvent::FrameEnd { .. } | RendererEvent::Error { .. }) {
+                    writer.flush()
+                } else {
+                    Ok(())
+                });
+                if let Err(err) = result {
+                    log::warn!("Failed to write event log: {:?}", err);
+                }
+            }
+            EventSink::Callback(callback) => callback(&line),
+        }
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        if let EventSink::File(writer) = &mut self.sink {
+            writer.flush().ok();
+        }
+    }
+}
+
+/// Serializes a event into a single json line.
+pub fn serialize_event(event: &RendererEvent, sequence: u64, time: Duration) -> String {
+    let mut object = JsonValue::new_object();
+    object["type"] = event.get_type_name().into();
+    object["seq"] = sequence.into();
+    object["time_us"] = (time.as_micros() as u64).into();
+    event.write_fields(&mut object);
+    object.dump()
+}
@@ -0,0 +1,180 @@
+//! Rolling statistics of cpu and gpu frame times.
+//!
+//! The [`FrameTimeTracker`] keeps the most recent samples of each clock in a [`FrameTimeHistogram`]
+//! from which percentiles and stutter counts are computed on demand. This is intended for in game
+//! performance overlays and is cheap enough to be queried every frame.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The number of buckets of a [`FrameTimeHistogram`].
+pub const HISTOGRAM_BUCKET_COUNT: usize = 64;
+
+/// The width of each histogram bucket. The last bucket additionally contains all samples which
+/// would fall outside of the histogram.
+pub const HISTOGRAM_BUCKET_WIDTH: Duration = Duration::from_millis(1);
+
+/// The default number of samples kept by a [`FrameTimeTracker`].
+pub const DEFAULT_SAMPLE_WINDOW: usize = 512;
+
+/// A frame is counted as a stutter if it takes longer than this factor times the median frame
+/// time of the window.
+pub const STUTTER_FACTOR: u32 = 2;
+
+/// A fixed size window of frame time samples and a histogram of the samples in the window.
+pub struct FrameTimeHistogram {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+    buckets: [u32; HISTOGRAM_BUCKET_COUNT],
+}
+
+impl FrameTimeHistogram {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = std::cmp::max(capacity, 1);
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            buckets: [0; HISTOGRAM_BUCKET_COUNT],
+        }
+    }
+
+    /// Adds a sample to the window. If the window is full the oldest sample is removed.
+    pub fn push(&mut self, sample: Duration) {
+        if self.samples.len() == self.capacity {
+            let old = self.samples.pop_front().unwrap();
+            self.buckets[Self::bucket_index(old)] -= 1;
+        }
+        self.samples.push_back(sample);
+        self.buckets[Self::bucket_index(sample)] += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.buckets = [0; HISTOGRAM_BUCKET_COUNT];
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns the number of samples in each bucket. Bucket `i` contains all samples in the range
+    /// `[i * HISTOGRAM_BUCKET_WIDTH, (i + 1) * HISTOGRAM_BUCKET_WIDTH)`.
+    pub fn get_buckets(&self) -> &[u32; HISTOGRAM_BUCKET_COUNT] {
+        &self.buckets
+    }
+
+    /// Computes the summary of all samples in the window. Returns [`None`] if the window is
+    /// empty.
+    pub fn summarize(&self) -> Option<FrameTimeSummary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+
+        let total: Duration = sorted.iter().sum();
+        let p50 = Self::percentile(&sorted, 50);
+        let stutter_threshold = p50 * STUTTER_FACTOR;
+        let stutter_count = sorted.iter().rev().take_while(|sample| **sample > stutter_threshold).count();
+
+        Some(FrameTimeSummary {
+            sample_count: sorted.len() as u32,
+            stutter_count: stutter_count as u32,
+            average: total / (sorted.len() as u32),
+            p50,
+            p95: Self::percentile(&sorted, 95),
+            p99: Self::percentile(&sorted, 99),
+            max: *sorted.last().unwrap(),
+        })
+    }
+
+    /// Nearest rank percentile of a sorted non empty slice.
+    fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+        let rank = (sorted.len() * percentile + 99) / 100;
+        sorted[std::cmp::max(rank, 1) - 1]
+    }
+
+    fn bucket_index(sample: Duration) -> usize {
+        let index = sample.as_nanos() / HISTOGRAM_BUCKET_WIDTH.as_nanos();
+        std::cmp::min(index, (HISTOGRAM_BUCKET_COUNT - 1) as u128) as usize
+    }
+}
+
+/// Summary of the samples of a [`FrameTimeHistogram`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FrameTimeSummary {
+    pub sample_count: u32,
+
+    /// The number of samples which took longer than [`STUTTER_FACTOR`] times the median.
+    pub stutter_count: u32,
+
+    pub average: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct FrameTimeReport {
+    /// The time between the start of successive frames. [`None`] if less than 2 frames have been
+    /// started.
+    pub cpu: Option<FrameTimeSummary>,
+
+    /// The time the gpu spent executing each frame measured using timestamp queries. [`None`] if
+    /// no frame has completed yet or the queue does not support timestamps.
+    pub gpu: Option<FrameTimeSummary>,
+}
+
+/// Collects cpu and gpu frame times.
+pub struct FrameTimeTracker {
+    last_frame_start: Option<Instant>,
+    cpu: FrameTimeHistogram,
+    gpu: FrameTimeHistogram,
+}
+
+impl FrameTimeTracker {
+    pub fn new(window: usize) -> Self {
+        Self {
+            last_frame_start: None,
+            cpu: FrameTimeHistogram::new(window),
+            gpu: FrameTimeHistogram::new(window),
+        }
+    }
+
+    /// Must be called whenever a new frame is started.
+    pub fn start_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame_start.replace(now) {
+            self.cpu.push(now - last);
+        }
+    }
+
+    /// Adds the gpu execution time of a completed frame.
+    pub fn push_gpu_time(&mut self, time: Duration) {
+        self.gpu.push(time);
+    }
+
+    /// Discards all samples. The next started frame does not produce a cpu sample.
+    pub fn reset(&mut self) {
+        self.last_frame_start = None;
+        self.cpu.clear();
+        self.gpu.clear();
+    }
+
+    pub fn get_cpu_histogram(&self) -> &FrameTimeHistogram {
+        &self.cpu
+    }
+
+    pub fn get_gpu_histogram(&self) -> &FrameTimeHistogram {
+        &self.gpu
+    }
+
+    pub fn get_report(&self) -> FrameTimeReport {
+        FrameTimeReport {
+            cpu: self.cpu.summarize(),
+            gpu: self.gpu.summarize(),
+        }
+    }
+}
@@ -2,16 +2,21 @@ use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex, Weak};
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU32, AtomicU64};
+use std::time::{Duration, Instant};
 
 use ash::vk;
 use crate::allocator::Allocation;
 use crate::define_uuid_type;
 
 use crate::renderer::emulator::{MeshData, PassId};
+use crate::renderer::emulator::pipeline::TextureBinding;
 
 use crate::prelude::*;
 use crate::renderer::emulator::share::Share;
+use crate::renderer::emulator::sparse::{SparseImageBind, SparseResidency, SparseResidencyStats};
+use crate::renderer::emulator::staging::StagingMemoryPool;
+use crate::renderer::emulator::upload::DEFAULT_UPLOAD_PRIORITY;
 use crate::renderer::emulator::worker::{GlobalImageClear, GlobalImageWrite, GlobalMeshWrite, WorkerTask};
 use crate::util::alloc::next_aligned;
 use crate::util::format::Format;
@@ -22,6 +27,9 @@ define_uuid_type!(pub, GlobalMeshId);
 pub enum GlobalObjectCreateError {
     Vulkan(vk::Result),
     Allocation,
+    InvalidArrayMode,
+    SparseNotSupported,
+    RenderTargetNotSupported,
 }
 
 impl From<vk::Result> for GlobalObjectCreateError {
@@ -30,30 +38,105 @@ impl From<vk::Result> for GlobalObjectCreateError {
     }
 }
 
+/// A mesh stored in device memory which can be used across passes.
+///
+/// Global meshes are reference counted through [`Arc`]. Every pass which draws a mesh keeps a
+/// reference to it until the pass has finished executing on the device. The device memory of a
+/// mesh is therefore only freed once all references held by the host have been dropped **and** no
+/// submitted pass uses the mesh anymore. It is always safe to drop a mesh immediately after
+/// recording a draw using it.
 pub struct GlobalMesh {
     share: Arc<Share>,
     id: GlobalMeshId,
 
     last_used_pass: AtomicU64,
+    upload_priority: AtomicU32,
 
     buffer: vk::Buffer,
     allocation: Allocation,
     buffer_size: vk::DeviceSize,
 
     draw_info: GlobalMeshDrawInfo,
+
+    /// Set if this mesh has been created with deduplication enabled.
+    content_key: Option<MeshContentKey>,
 }
 
 impl GlobalMesh {
     pub(super) fn new(share: Arc<Share>, data: &MeshData) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        Self::new_internal(share, data, None)
+    }
+
+    /// Creates multiple meshes at once. The staging memory lock and worker channel are only
+    /// acquired once for all meshes.
+    ///
+    /// If any mesh fails to be created the error is returned and all previously created meshes of
+    /// this batch are dropped.
+    pub(super) fn new_batch(share: Arc<Share>, datas: &[MeshData]) -> Result<Vec<Arc<Self>>, GlobalObjectCreateError> {
+        let mut meshes = Vec::with_capacity(datas.len());
+        let mut tasks = Vec::with_capacity(datas.len());
+
+        let mut guard = share.get_staging_pool().lock().unwrap_or_else(|_| {
+            log::error!("Poisoned staging memory mutex in GlobalMesh::new_batch");
+            panic!()
+        });
+        let mut result = Ok(());
+        for data in datas {
+            match Self::create(&share, &mut guard, data, None) {
+                Ok((mesh, task)) => {
+                    meshes.push(mesh);
+                    tasks.push(task);
+                }
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        drop(guard);
+
+        // The tasks own the staging allocations so they must be submitted even if creation failed
+        share.push_tasks(tasks);
+
+        result.map(|_| meshes)
+    }
+
+    /// Returns an existing mesh if a mesh with identical data has previously been created with
+    /// this function and is still alive. Otherwise a new mesh is created.
+    pub(super) fn new_deduplicated(share: Arc<Share>, data: &MeshData) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        let key = MeshContentKey::from_data(data);
+        if let Some(mesh) = share.get_cached_mesh(&key) {
+            return Ok(mesh);
+        }
+
+        let mesh = Self::new_internal(share.clone(), data, Some(key))?;
+
+        // Another thread may have created the same mesh in the meantime. In that case our mesh is
+        // dropped and the existing one returned
+        Ok(share.insert_cached_mesh(key, &mesh).unwrap_or(mesh))
+    }
+
+    fn new_internal(share: Arc<Share>, data: &MeshData, content_key: Option<MeshContentKey>) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        let mut guard = share.get_staging_pool().lock().unwrap_or_else(|_| {
+            log::error!("Poisoned staging memory mutex in GlobalMesh::new");
+            panic!()
+        });
+        let (mesh, task) = Self::create(&share, &mut guard, data, content_key)?;
+        drop(guard);
+
+        share.push_task(task);
+
+        Ok(mesh)
+    }
+
+    /// Creates the mesh and the task uploading its data. The task must be pushed to the worker.
+    fn create(share: &Arc<Share>, staging_pool: &mut StagingMemoryPool, data: &MeshData, content_key: Option<MeshContentKey>) -> Result<(Arc<Self>, WorkerTask), GlobalObjectCreateError> {
         let index_offset = next_aligned(data.vertex_data.len() as vk::DeviceSize, data.get_index_size() as vk::DeviceSize);
         let required_size = index_offset + (data.index_data.len() as vk::DeviceSize);
 
         let (buffer, allocation) = Self::create_buffer(share.get_device(), required_size)?;
 
-        let (staging, staging_allocation) = share.get_staging_pool().lock().unwrap_or_else(|_| {
-            log::error!("Poisoned staging memory mutex in GlobalMesh::new");
-            panic!()
-        }).allocate(required_size, 1);
+        let (staging, staging_allocation) = staging_pool.allocate(required_size, 1);
 
         unsafe {
             let dst = std::slice::from_raw_parts_mut(staging.mapped.as_ptr(), required_size as usize);
@@ -71,19 +154,22 @@ impl GlobalMesh {
         };
 
         let mesh = Arc::new(GlobalMesh {
-            share,
+            share: share.clone(),
             id: GlobalMeshId::new(),
 
             last_used_pass: AtomicU64::new(0),
+            upload_priority: AtomicU32::new(DEFAULT_UPLOAD_PRIORITY),
 
             buffer,
             allocation,
             buffer_size: required_size,
 
-            draw_info
+            draw_info,
+
+            content_key,
         });
 
-        mesh.share.push_task(WorkerTask::WriteGlobalMesh(GlobalMeshWrite {
+        let task = WorkerTask::WriteGlobalMesh(GlobalMeshWrite {
             after_pass: PassId::from_raw(0),
             staging_allocation,
             staging_range: (staging.offset, required_size),
@@ -94,9 +180,40 @@ impl GlobalMesh {
                 dst_offset: 0,
                 size: required_size
             }])
-        }, true));
+        }, true);
 
-        Ok(mesh)
+        Ok((mesh, task))
+    }
+
+    /// Returns the id of the renderer instance which created this mesh.
+    pub(super) fn get_share_id(&self) -> UUID {
+        self.share.get_id()
+    }
+
+    pub fn get_id(&self) -> GlobalMeshId {
+        self.id
+    }
+
+    /// Returns the size in bytes of the device memory used by this mesh.
+    pub fn get_size(&self) -> vk::DeviceSize {
+        self.buffer_size
+    }
+
+    /// Sets the priority of pending uploads to this mesh. Uploads with a higher priority are
+    /// processed first if the upload budget is limited. Uploads to meshes used in a pass are always
+    /// processed before the pass.
+    pub fn set_upload_priority(&self, priority: u32) {
+        self.upload_priority.store(priority, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(super) fn get_upload_priority(&self) -> u32 {
+        self.upload_priority.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the raw id of the last pass this mesh has been used in or 0 if it has never been
+    /// used.
+    pub(super) fn get_last_used_pass(&self) -> u64 {
+        self.last_used_pass.load(std::sync::atomic::Ordering::Acquire)
     }
 
     pub(super) fn update_used_in(&self, pass: PassId) {
@@ -123,7 +240,7 @@ impl GlobalMesh {
     fn create_buffer(device: &DeviceContext, size: vk::DeviceSize) -> Result<(vk::Buffer, Allocation), GlobalObjectCreateError> {
         let info = vk::BufferCreateInfo::builder()
             .size(size)
-            .usage(vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER)
             .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
         unsafe {
@@ -161,12 +278,45 @@ impl Hash for GlobalMesh {
 
 impl Drop for GlobalMesh {
     fn drop(&mut self) {
+        if let Some(key) = &self.content_key {
+            self.share.remove_cached_mesh(key);
+        }
+
         unsafe {
             self.share.get_device().get_allocator().destroy_buffer(self.buffer, self.allocation)
         }
     }
 }
 
+/// Identifies the content of a mesh for deduplication. Uses 128 bit hashes of the vertex and index
+/// data so collisions can be ignored in practice.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub(super) struct MeshContentKey {
+    vertex_hash: u128,
+    index_hash: u128,
+    vertex_size: usize,
+    index_size: usize,
+    vertex_stride: u32,
+    index_count: u32,
+    index_type: vk::IndexType,
+    primitive_topology: vk::PrimitiveTopology,
+}
+
+impl MeshContentKey {
+    fn from_data(data: &MeshData) -> Self {
+        Self {
+            vertex_hash: xxhash_rust::xxh3::xxh3_128(data.vertex_data),
+            index_hash: xxhash_rust::xxh3::xxh3_128(data.index_data),
+            vertex_size: data.vertex_data.len(),
+            index_size: data.index_data.len(),
+            vertex_stride: data.vertex_stride,
+            index_count: data.index_count,
+            index_type: data.index_type,
+            primitive_topology: data.primitive_topology,
+        }
+    }
+}
+
 pub(super) struct GlobalMeshDrawInfo {
     pub(super) buffer: vk::Buffer,
     pub(super) first_index: u32,
@@ -229,42 +379,171 @@ impl<'a> ImageData<'a> {
 
 define_uuid_type!(pub, GlobalImageId);
 
+/// Describes how the layers of a [`GlobalImage`] are addressed.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ImageArrayMode {
+    /// A 2d image with a single layer.
+    Single,
+
+    /// A 2d array image with the specified number of layers. Layers are addressed explicitly using
+    /// [`GlobalImage::update_layer_regions`] and `mc_image_layer` in shaders.
+    Layers(u32),
+
+    /// A 2d array image where each layer stores one tile of an atlas with the specified number of
+    /// tiles in each dimension. Tiles are stored in row major order. Uploads using
+    /// [`GlobalImage::update_regions`] and texture coordinates passed to `mc_image` address the
+    /// full atlas, however each tile is sampled and mipmapped independently which prevents
+    /// bleeding between neighbouring tiles.
+    ///
+    /// This allows existing atlas based code to use array images without any changes.
+    Atlas(Vec2u32),
+}
+
+impl ImageArrayMode {
+    pub fn get_layer_count(&self) -> u32 {
+        match self {
+            ImageArrayMode::Single => 1,
+            ImageArrayMode::Layers(count) => *count,
+            ImageArrayMode::Atlas(grid) => grid[0] * grid[1],
+        }
+    }
+
+    /// Returns the atlas grid packed into a single value as used by the shaders. The number of
+    /// columns is stored in the low 16 bits and the number of rows in the high 16 bits. Returns 0
+    /// if the image is not an atlas.
+    pub(super) fn get_packed_atlas_grid(&self) -> u32 {
+        match self {
+            ImageArrayMode::Atlas(grid) => grid[0] | (grid[1] << 16),
+            _ => 0,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            ImageArrayMode::Single => true,
+            ImageArrayMode::Layers(count) => *count != 0,
+            ImageArrayMode::Atlas(grid) => grid[0] != 0 && grid[1] != 0 && grid[0] <= u16::MAX as u32 && grid[1] <= u16::MAX as u32,
+        }
+    }
+}
+
 pub struct GlobalImage {
     weak: Weak<Self>,
     share: Arc<Share>,
     id: GlobalImageId,
 
     last_used_pass: AtomicU64,
+    upload_priority: AtomicU32,
+    usage: Mutex<ImageUsage>,
+    /// The resource generation in which the image was last written or marked current.
+    generation: AtomicU64,
 
     image: vk::Image,
     sampler_view: vk::ImageView,
-    allocation: Allocation,
+    array_view: vk::ImageView,
+    /// The memory of the image. [`None`] for sparse images.
+    allocation: Option<Allocation>,
+    /// The page residency state of sparse images.
+    sparse: Option<Mutex<SparseResidency>>,
     size: Vec2u32,
     mip_levels: u32,
+    array_mode: ImageArrayMode,
+    format: &'static Format,
 
     sampler_database: Mutex<HashMap<SamplerInfo, vk::Sampler>>,
 }
 
 impl GlobalImage {
-    pub(super) fn new(share: Arc<Share>, size: Vec2u32, mip_levels: u32, format: &'static Format) -> Result<Arc<Self>, GlobalObjectCreateError> {
-        let (image, allocation, sampler_view) = Self::create_image(share.get_device(), format.into(), size, mip_levels)?;
+    pub(super) const IMAGE_USAGE: vk::ImageUsageFlags = vk::ImageUsageFlags::from_raw(
+        vk::ImageUsageFlags::TRANSFER_SRC.as_raw() | vk::ImageUsageFlags::TRANSFER_DST.as_raw() | vk::ImageUsageFlags::SAMPLED.as_raw()
+    );
+
+    /// Creates a new image. For array images `size` is the size of a single layer.
+    pub(super) fn new(share: Arc<Share>, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        Self::new_internal(share, size, mip_levels, array_mode, format, Self::IMAGE_USAGE, false)
+    }
+
+    /// Creates a new image which can additionally be used as a color attachment by the worker.
+    /// Render targets always have a single mip level and layer.
+    ///
+    /// Returns [`GlobalObjectCreateError::RenderTargetNotSupported`] if the device does not
+    /// support blending into images of the format.
+    pub(super) fn new_render_target(share: Arc<Share>, size: Vec2u32, format: &'static Format) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        let device = share.get_device();
+        let properties = unsafe {
+            device.get_instance().vk().get_physical_device_format_properties(device.get_functions().physical_device, format.get_format())
+        };
+        if !properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND) {
+            return Err(GlobalObjectCreateError::RenderTargetNotSupported);
+        }
+
+        let usage = Self::IMAGE_USAGE | vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        Self::new_internal(share, size, 1, ImageArrayMode::Single, format, usage, false)
+    }
+
+    /// Creates a new partially resident image. Memory is only bound to the pages of the image
+    /// which have been written or touched using [`GlobalImage::touch_regions`]. See
+    /// [`crate::renderer::emulator::sparse`].
+    ///
+    /// Returns [`GlobalObjectCreateError::SparseNotSupported`] if the device does not support
+    /// sparse images of the format.
+    pub(super) fn new_sparse(share: Arc<Share>, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        if !SparseResidency::is_format_supported(share.get_device(), format.into()) {
+            return Err(GlobalObjectCreateError::SparseNotSupported);
+        }
+        Self::new_internal(share, size, mip_levels, array_mode, format, Self::IMAGE_USAGE, true)
+    }
+
+    fn new_internal(share: Arc<Share>, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format, usage: vk::ImageUsageFlags, sparse: bool) -> Result<Arc<Self>, GlobalObjectCreateError> {
+        if !array_mode.is_valid() {
+            log::error!("Invalid image array mode {:?}", array_mode);
+            return Err(GlobalObjectCreateError::InvalidArrayMode);
+        }
+        if matches!(array_mode, ImageArrayMode::Atlas(_)) && format.get_compatibility_class().get_texel_size().is_none() {
+            log::error!("Atlas array images are not supported for format {:?}", format.get_format());
+            return Err(GlobalObjectCreateError::InvalidArrayMode);
+        }
+
+        let (image, allocation, sparse, sampler_view, array_view) = Self::create_image(share.get_device(), format.into(), size, mip_levels, array_mode.get_layer_count(), usage, sparse)?;
+        let (sparse, tail_binds) = match sparse {
+            Some((residency, tail_binds)) => (Some(Mutex::new(residency)), Some(tail_binds)),
+            None => (None, None),
+        };
 
+        let generation = share.get_resource_generation();
         let image = Arc::new_cyclic(|weak| GlobalImage {
             weak: weak.clone(),
             share,
             id: GlobalImageId::new(),
 
             last_used_pass: AtomicU64::new(0),
+            upload_priority: AtomicU32::new(DEFAULT_UPLOAD_PRIORITY),
+            usage: Mutex::new(ImageUsage::new()),
+            generation: AtomicU64::new(generation),
 
             image,
             sampler_view,
+            array_view,
             allocation,
+            sparse,
             size,
             mip_levels,
+            array_mode,
+            format,
 
             sampler_database: Mutex::new(HashMap::new())
         });
 
+        if let Some(tail_binds) = tail_binds {
+            if !tail_binds.is_empty() {
+                image.share.push_task(WorkerTask::BindSparseImage(SparseImageBind {
+                    image: image.clone(),
+                    opaque_binds: tail_binds,
+                    image_binds: Box::from([]),
+                }));
+            }
+        }
+
         image.share.push_task(WorkerTask::ClearGlobalImage(GlobalImageClear {
             after_pass: PassId::from_raw(0),
             clear_value: format.get_clear_color_type().unwrap().make_zero_clear(),
@@ -291,14 +570,135 @@ impl GlobalImage {
         self.id
     }
 
+    /// Returns the id of the renderer instance which created this image.
+    pub(super) fn get_share_id(&self) -> UUID {
+        self.share.get_id()
+    }
+
+    /// Returns statistics about how this image has been sampled by draws. See
+    /// [`ImageUsageStats`].
+    pub fn get_usage_stats(&self) -> ImageUsageStats {
+        let usage = self.usage.lock().unwrap();
+        let current_pass = self.share.get_last_pass_id();
+        ImageUsageStats {
+            last_sampled_pass: usage.last_sampled_pass,
+            passes_since_sampled: usage.last_sampled_pass.map(|pass| current_pass.saturating_sub(pass.get_raw())),
+            time_since_sampled: usage.last_sampled_at.map(|at| at.elapsed()),
+            sampled_pass_count: usage.sampled_pass_count,
+            sampled_draw_count: usage.sampled_draw_count,
+        }
+    }
+
+    /// Records that `draw_count` draws of a pass had this image bound. Called by the pass recorder
+    /// once the pass is ended.
+    pub(super) fn record_sampled(&self, pass: PassId, draw_count: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        if usage.last_sampled_pass.map_or(true, |last| last < pass) {
+            usage.last_sampled_pass = Some(pass);
+            usage.sampled_pass_count += 1;
+        }
+        usage.last_sampled_at = Some(Instant::now());
+        usage.sampled_draw_count += draw_count;
+    }
+
+    /// Returns the size of the image. For array images this is the size of a single layer.
     pub fn get_size(&self) -> Vec2u32 {
         self.size
     }
 
+    /// Returns the size of the area addressed by [`GlobalImage::update_regions`]. This is the size
+    /// of the full atlas for [`ImageArrayMode::Atlas`] images and the size of the image otherwise.
+    pub fn get_region_size(&self) -> Vec2u32 {
+        match self.array_mode {
+            ImageArrayMode::Atlas(grid) => self.size.component_mul(&grid),
+            _ => self.size,
+        }
+    }
+
+    pub fn get_array_mode(&self) -> ImageArrayMode {
+        self.array_mode
+    }
+
+    pub fn get_format(&self) -> &'static Format {
+        self.format
+    }
+
+    /// Sets the priority of pending uploads to this image. Uploads with a higher priority are
+    /// processed first if the upload budget is limited. Uploads to images used in a pass are always
+    /// processed before the pass.
+    pub fn set_upload_priority(&self, priority: u32) {
+        self.upload_priority.store(priority, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(super) fn get_upload_priority(&self) -> u32 {
+        self.upload_priority.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the resource generation in which the image was last written or marked current.
+    pub fn get_generation(&self) -> u64 {
+        self.generation.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Returns true if the image has not been written or marked current since the last call to
+    /// [`EmulatorRenderer::bump_resource_generation`].
+    ///
+    /// [`EmulatorRenderer::bump_resource_generation`]: crate::renderer::emulator::EmulatorRenderer::bump_resource_generation
+    pub fn is_stale(&self) -> bool {
+        self.get_generation() < self.share.get_resource_generation()
+    }
+
+    /// Marks the image as current without writing it. Intended for images whose content did not
+    /// change during a resource reload.
+    pub fn mark_current(&self) {
+        self.generation.fetch_max(self.share.get_resource_generation(), std::sync::atomic::Ordering::AcqRel);
+    }
+
+    /// Writes regions of the image. For [`ImageArrayMode::Atlas`] images the regions are in atlas
+    /// coordinates and may span multiple tiles. For all other images the first layer is written.
+    ///
+    /// Writes are ordered after all passes which already used the image so frames in flight keep
+    /// sampling the previous content. Marks the image as current.
     pub fn update_regions(&self, regions: &[ImageData]) {
+        self.write_regions(regions, None, 0);
+    }
+
+    /// Writes regions of a single layer of the image.
+    pub fn update_layer_regions(&self, layer: u32, regions: &[ImageData]) {
+        if layer >= self.array_mode.get_layer_count() {
+            log::error!("Layer {:?} is out of range for image with {:?} layers", layer, self.array_mode.get_layer_count());
+            panic!();
+        }
+        self.write_regions(regions, Some(layer), 0);
+    }
+
+    /// Writes regions of a single mip level. The regions are in the coordinates of the mip level.
+    /// Intended for images whose mip levels are not derived from the first level, for example
+    /// prefiltered environment probes. Not supported for sparse images.
+    pub fn update_mip_regions(&self, mip_level: u32, regions: &[ImageData]) {
+        if mip_level >= self.mip_levels {
+            log::error!("Mip level {:?} is out of range for image with {:?} mip levels", mip_level, self.mip_levels);
+            panic!();
+        }
+        if self.is_sparse() {
+            log::error!("Called GlobalImage::update_mip_regions on a sparse image");
+            panic!();
+        }
+        self.write_regions(regions, Some(0), mip_level);
+    }
+
+    /// Generates all mip levels from the first mip level. Each layer is processed independently.
+    pub fn generate_mipmaps(&self) {
+        if self.mip_levels > 1 {
+            let after_pass = PassId::from_raw(self.last_used_pass.load(std::sync::atomic::Ordering::Acquire));
+            self.share.push_task(WorkerTask::GenerateGlobalImageMipmaps(self.weak.upgrade().unwrap(), after_pass));
+        }
+    }
+
+    fn write_regions(&self, regions: &[ImageData], layer: Option<u32>, mip_level: u32) {
         if regions.is_empty() {
             return;
         }
+        self.mark_current();
 
         let required_memory = regions.iter().map(|r| r.data.len()).sum::<usize>() as u64;
 
@@ -307,23 +707,15 @@ impl GlobalImage {
         let mut copies = Vec::with_capacity(regions.len());
         let mut current_offset = 0;
         for region in regions {
-            copies.push(vk::BufferImageCopy {
-                buffer_offset: staging.offset + current_offset,
-                buffer_row_length: region.row_stride,
-                buffer_image_height: 0,
-                image_subresource: vk::ImageSubresourceLayers {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    mip_level: 0,
-                    base_array_layer: 0,
-                    layer_count: 1
-                },
-                image_offset: vk::Offset3D { x: region.offset[0] as i32, y: region.offset[1] as i32, z: 0 },
-                image_extent: vk::Extent3D {
-                    width: region.extent[0],
-                    height: region.extent[1],
-                    depth: 1
+            let buffer_offset = staging.offset + current_offset;
+            match (layer, self.array_mode) {
+                (None, ImageArrayMode::Atlas(grid)) => self.push_atlas_copies(region, buffer_offset, grid, &mut copies),
+                (layer, _) => {
+                    let mut copy = Self::make_copy(buffer_offset, region.row_stride, layer.unwrap_or(0), region.offset, region.extent);
+                    copy.image_subresource.mip_level = mip_level;
+                    copies.push(copy);
                 }
-            });
+            }
 
             unsafe {
                 let mapped = std::slice::from_raw_parts_mut(staging.mapped.as_ptr().offset(current_offset as isize), region.data.len());
@@ -333,6 +725,8 @@ impl GlobalImage {
             current_offset += region.data.len() as u64;
         }
 
+        self.make_copies_resident(&copies);
+
         self.share.push_task(WorkerTask::WriteGlobalImage(GlobalImageWrite {
             after_pass: PassId::from_raw(self.last_used_pass.load(std::sync::atomic::Ordering::Acquire)),
             staging_allocation: allocation,
@@ -343,6 +737,160 @@ impl GlobalImage {
         }));
     }
 
+    pub fn is_sparse(&self) -> bool {
+        self.sparse.is_some()
+    }
+
+    /// Makes all pages of sparse images covering the regions resident and marks them as used in the
+    /// current pass. Regions are addressed the same way as in [`GlobalImage::update_regions`].
+    /// Regions sampled in a pass should be touched every pass to prevent them from being evicted.
+    ///
+    /// Has no effect on images which are not sparse.
+    pub fn touch_regions(&self, regions: &[(Vec2u32, Vec2u32)]) {
+        if self.sparse.is_none() {
+            return;
+        }
+
+        let mut copies = Vec::with_capacity(regions.len());
+        for (offset, extent) in regions {
+            let region = ImageData::new_extent(&[], *offset, *extent);
+            match self.array_mode {
+                ImageArrayMode::Atlas(grid) => self.push_atlas_copies(&region, 0, grid, &mut copies),
+                _ => copies.push(Self::make_copy(0, 0, 0, *offset, *extent)),
+            }
+        }
+        self.make_copies_resident(&copies);
+    }
+
+    /// Like [`GlobalImage::touch_regions`] but touches regions of a single layer.
+    pub fn touch_layer_regions(&self, layer: u32, regions: &[(Vec2u32, Vec2u32)]) {
+        if self.sparse.is_none() {
+            return;
+        }
+        if layer >= self.array_mode.get_layer_count() {
+            log::error!("Layer {:?} is out of range for image with {:?} layers", layer, self.array_mode.get_layer_count());
+            panic!();
+        }
+
+        let copies: Vec<_> = regions.iter().map(|(offset, extent)| Self::make_copy(0, 0, layer, *offset, *extent)).collect();
+        self.make_copies_resident(&copies);
+    }
+
+    /// Sets the maximum amount of memory used by the pages of a sparse image. The mip tail is not
+    /// included. If the budget is exceeded the least recently used pages which have not been used
+    /// by any pass in flight are evicted. Pages which are still in use are never evicted so the
+    /// budget may temporarily be exceeded.
+    ///
+    /// Has no effect on images which are not sparse.
+    pub fn set_residency_budget(&self, budget: Option<u64>) {
+        if let Some(sparse) = &self.sparse {
+            sparse.lock().unwrap().set_budget(budget);
+            self.evict_sparse_pages();
+        }
+    }
+
+    /// Returns the residency statistics of sparse images or [`None`] if the image is not sparse.
+    pub fn get_residency_stats(&self) -> Option<SparseResidencyStats> {
+        self.sparse.as_ref().map(|sparse| sparse.lock().unwrap().get_stats())
+    }
+
+    /// Makes the destination pages of copies into mip level 0 of sparse images resident.
+    fn make_copies_resident(&self, copies: &[vk::BufferImageCopy]) {
+        let sparse = match &self.sparse {
+            Some(sparse) => sparse,
+            None => return,
+        };
+
+        let tick = self.share.get_last_pass_id();
+        let device = self.share.get_device();
+        let mut binds = Vec::new();
+        {
+            let mut guard = sparse.lock().unwrap();
+            for copy in copies {
+                let offset = Vec2u32::new(copy.image_offset.x as u32, copy.image_offset.y as u32);
+                let extent = Vec2u32::new(copy.image_extent.width, copy.image_extent.height);
+                guard.make_resident(device, copy.image_subresource.base_array_layer, offset, extent, tick, &mut binds);
+            }
+        }
+
+        if !binds.is_empty() {
+            self.share.push_task(WorkerTask::BindSparseImage(SparseImageBind {
+                image: self.weak.upgrade().unwrap(),
+                opaque_binds: Box::from([]),
+                image_binds: binds.into_boxed_slice(),
+            }));
+        }
+
+        self.evict_sparse_pages();
+    }
+
+    /// Selects pages for eviction if the residency budget is exceeded. The pages are unbound by
+    /// the worker once all passes which used the image so far have completed.
+    fn evict_sparse_pages(&self) {
+        if let Some(sparse) = &self.sparse {
+            let keep_after = self.share.get_last_pass_id().saturating_sub(self.share.get_frames_in_flight() as u64);
+            if sparse.lock().unwrap().select_evictions(keep_after) {
+                let after_pass = PassId::from_raw(self.last_used_pass.load(std::sync::atomic::Ordering::Acquire));
+                self.share.push_task(WorkerTask::EvictSparseImage(self.weak.upgrade().unwrap(), after_pass));
+            }
+        }
+    }
+
+    /// Marks all pages selected for eviction as non resident and returns the binds unbinding them
+    /// and their memory. Called by the worker once the eviction can be executed.
+    pub(super) fn take_sparse_evictions(&self) -> (Box<[vk::SparseImageMemoryBind]>, Box<[Allocation]>) {
+        match &self.sparse {
+            Some(sparse) => sparse.lock().unwrap().take_evictions(),
+            None => (Box::from([]), Box::from([])),
+        }
+    }
+
+    /// Splits a region in atlas coordinates into one copy per affected tile.
+    fn push_atlas_copies(&self, region: &ImageData, buffer_offset: vk::DeviceSize, grid: Vec2u32, copies: &mut Vec<vk::BufferImageCopy>) {
+        let texel_size = self.format.get_compatibility_class().get_texel_size().unwrap() as vk::DeviceSize;
+        let row_length = if region.row_stride == 0 { region.extent[0] } else { region.row_stride };
+        let tile = self.size;
+
+        let min = region.offset;
+        let max = region.offset + region.extent;
+        for tile_y in (min[1] / tile[1])..std::cmp::min((max[1] + tile[1] - 1) / tile[1], grid[1]) {
+            for tile_x in (min[0] / tile[0])..std::cmp::min((max[0] + tile[0] - 1) / tile[0], grid[0]) {
+                let tile_base = Vec2u32::new(tile_x * tile[0], tile_y * tile[1]);
+                let copy_min = Vec2u32::new(std::cmp::max(min[0], tile_base[0]), std::cmp::max(min[1], tile_base[1]));
+                let copy_max = Vec2u32::new(std::cmp::min(max[0], tile_base[0] + tile[0]), std::cmp::min(max[1], tile_base[1] + tile[1]));
+
+                let texel_offset = ((copy_min[1] - min[1]) as vk::DeviceSize) * (row_length as vk::DeviceSize) + ((copy_min[0] - min[0]) as vk::DeviceSize);
+                copies.push(Self::make_copy(
+                    buffer_offset + texel_offset * texel_size,
+                    row_length,
+                    tile_y * grid[0] + tile_x,
+                    copy_min - tile_base,
+                    copy_max - copy_min
+                ));
+            }
+        }
+    }
+
+    fn make_copy(buffer_offset: vk::DeviceSize, row_stride: u32, layer: u32, offset: Vec2u32, extent: Vec2u32) -> vk::BufferImageCopy {
+        vk::BufferImageCopy {
+            buffer_offset,
+            buffer_row_length: row_stride,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: layer,
+                layer_count: 1
+            },
+            image_offset: vk::Offset3D { x: offset[0] as i32, y: offset[1] as i32, z: 0 },
+            image_extent: vk::Extent3D {
+                width: extent[0],
+                height: extent[1],
+                depth: 1
+            }
+        }
+    }
+
     pub(super) fn get_image_handle(&self) -> vk::Image {
         self.image
     }
@@ -351,15 +899,30 @@ impl GlobalImage {
         self.mip_levels
     }
 
+    pub(super) fn get_layer_count(&self) -> u32 {
+        self.array_mode.get_layer_count()
+    }
+
     pub(super) fn get_sampler_view(&self) -> vk::ImageView {
         self.sampler_view
     }
 
+    /// Returns the binding used to sample this image with the specified sampler in a pass.
+    pub(super) fn get_texture_binding(&self, sampler_info: &SamplerInfo) -> TextureBinding {
+        TextureBinding {
+            view: self.sampler_view,
+            array_view: self.array_view,
+            sampler: self.get_sampler(sampler_info),
+            packed_atlas_grid: self.array_mode.get_packed_atlas_grid(),
+        }
+    }
+
     pub(super) fn get_sampler(&self, sampler_info: &SamplerInfo) -> vk::Sampler {
         let mut guard = self.sampler_database.lock().unwrap();
         if let Some(sampler) = guard.get(sampler_info) {
             *sampler
         } else {
+            let max_anisotropy = self.share.get_device().get_max_sampler_anisotropy();
             let info = vk::SamplerCreateInfo::builder()
                 .mag_filter(sampler_info.mag_filter)
                 .min_filter(sampler_info.min_filter)
@@ -368,8 +931,8 @@ impl GlobalImage {
                 .address_mode_v(sampler_info.address_mode_v)
                 .address_mode_w(vk::SamplerAddressMode::REPEAT)
                 .mip_lod_bias(0f32)
-                .anisotropy_enable(sampler_info.anisotropy_enable)
-                .max_anisotropy(0f32)
+                .anisotropy_enable(sampler_info.anisotropy_enable && max_anisotropy.is_some())
+                .max_anisotropy(max_anisotropy.unwrap_or(1f32))
                 .compare_enable(false)
                 .min_lod(0f32)
                 .max_lod(vk::LOD_CLAMP_NONE)
@@ -387,8 +950,20 @@ impl GlobalImage {
         }
     }
 
-    fn create_image(device: &DeviceContext, format: vk::Format, size: Vec2u32, mip_levels: u32) -> Result<(vk::Image, Allocation, vk::ImageView), GlobalObjectCreateError> {
+    /// Creates the image and its views. The sampler view is a 2d view of the first layer while the
+    /// array view covers all layers.
+    ///
+    /// Sparse images are created without memory. Instead the residency state and the binds of the
+    /// mip tail are returned.
+    fn create_image(device: &DeviceContext, format: vk::Format, size: Vec2u32, mip_levels: u32, array_layers: u32, usage: vk::ImageUsageFlags, sparse: bool) -> Result<(vk::Image, Option<Allocation>, Option<(SparseResidency, Box<[vk::SparseMemoryBind]>)>, vk::ImageView, vk::ImageView), GlobalObjectCreateError> {
+        let flags = if sparse {
+            vk::ImageCreateFlags::SPARSE_BINDING | vk::ImageCreateFlags::SPARSE_RESIDENCY
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+
         let info = vk::ImageCreateInfo::builder()
+            .flags(flags)
             .image_type(vk::ImageType::TYPE_2D)
             .format(format)
             .extent(vk::Extent3D {
@@ -397,20 +972,68 @@ impl GlobalImage {
                 depth: 1
             })
             .mip_levels(mip_levels)
-            .array_layers(1)
+            .array_layers(array_layers)
             .samples(vk::SampleCountFlags::TYPE_1)
             .tiling(vk::ImageTiling::OPTIMAL)
-            .usage(vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+            .usage(usage)
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
             .initial_layout(vk::ImageLayout::UNDEFINED);
 
-        let (image, allocation) = unsafe {
-            device.get_allocator().create_gpu_image(&info, &format_args!("GlobalImage"))
-        }.ok_or(GlobalObjectCreateError::Allocation)?;
+        let (image, allocation, mut sparse) = if sparse {
+            let image = unsafe {
+                device.vk().create_image(&info, None)
+            }.map_err(|err| {
+                log::error!("vkCreateImage returned {:?} in GlobalImage::create_image", err);
+                GlobalObjectCreateError::Vulkan(err)
+            })?;
+
+            match SparseResidency::new(device, image, size, mip_levels, array_layers) {
+                Ok(sparse) => (image, None, Some(sparse)),
+                Err(err) => {
+                    unsafe { device.vk().destroy_image(image, None) };
+                    return Err(err);
+                }
+            }
+        } else {
+            let (image, allocation) = unsafe {
+                device.get_allocator().create_gpu_image(&info, &format_args!("GlobalImage"))
+            }.ok_or(GlobalObjectCreateError::Allocation)?;
+            (image, Some(allocation), None)
+        };
+
+        let views = Self::create_view(device, image, format, vk::ImageViewType::TYPE_2D, mip_levels, 1).and_then(|sampler_view| {
+            match Self::create_view(device, image, format, vk::ImageViewType::TYPE_2D_ARRAY, mip_levels, array_layers) {
+                Ok(array_view) => Ok((sampler_view, array_view)),
+                Err(err) => {
+                    unsafe { device.vk().destroy_image_view(sampler_view, None) };
+                    Err(err)
+                }
+            }
+        });
+
+        match views {
+            Ok((sampler_view, array_view)) => Ok((image, allocation, sparse, sampler_view, array_view)),
+            Err(err) => {
+                unsafe { Self::destroy_image(device, image, allocation, sparse.as_mut().map(|(sparse, _)| sparse)) };
+                Err(GlobalObjectCreateError::Vulkan(err))
+            }
+        }
+    }
 
+    unsafe fn destroy_image(device: &DeviceContext, image: vk::Image, allocation: Option<Allocation>, sparse: Option<&mut SparseResidency>) {
+        match allocation {
+            Some(allocation) => device.get_allocator().destroy_image(image, allocation),
+            None => device.vk().destroy_image(image, None),
+        }
+        if let Some(sparse) = sparse {
+            sparse.free_all(device);
+        }
+    }
+
+    fn create_view(device: &DeviceContext, image: vk::Image, format: vk::Format, view_type: vk::ImageViewType, mip_levels: u32, array_layers: u32) -> Result<vk::ImageView, vk::Result> {
         let info = vk::ImageViewCreateInfo::builder()
             .image(image)
-            .view_type(vk::ImageViewType::TYPE_2D)
+            .view_type(view_type)
             .format(format)
             .components(vk::ComponentMapping {
                 r: vk::ComponentSwizzle::IDENTITY,
@@ -423,21 +1046,15 @@ impl GlobalImage {
                 base_mip_level: 0,
                 level_count: mip_levels,
                 base_array_layer: 0,
-                layer_count: 1
+                layer_count: array_layers
             });
 
-        let sampler_view = match unsafe {
+        unsafe {
             device.vk().create_image_view(&info, None)
-        } {
-            Ok(view) => view,
-            Err(err) => {
-                log::error!("vkCreateImageView returned {:?} in GlobalImage::create_image", err);
-                unsafe { device.get_allocator().destroy_image(image, allocation) }
-                return Err(GlobalObjectCreateError::Vulkan(err));
-            }
-        };
-
-        Ok((image, allocation, sampler_view))
+        }.map_err(|err| {
+            log::error!("vkCreateImageView returned {:?} in GlobalImage::create_image", err);
+            err
+        })
     }
 }
 
@@ -472,8 +1089,55 @@ impl Drop for GlobalImage {
     fn drop(&mut self) {
         let device = self.share.get_device();
         unsafe {
+            device.vk().destroy_image_view(self.array_view, None);
             device.vk().destroy_image_view(self.sampler_view, None);
-            device.get_allocator().destroy_image(self.image, self.allocation);
+            Self::destroy_image(device, self.image, self.allocation, self.sparse.as_mut().map(|sparse| sparse.get_mut().unwrap()));
+        }
+    }
+}
+
+/// Statistics about how a [`GlobalImage`] has been sampled.
+///
+/// The statistics are collected using draw level bookkeeping. An image counts as sampled by a draw
+/// if it is set for the shader of the draw using [`PassRecorder::update_texture`] or bound using
+/// [`PassRecorder::bind_texture`] when the draw is recorded. Whether the shader actually reads the
+/// texture is not known, so the statistics are conservative. Images which have not been sampled
+/// for a long time can safely be destroyed by the host and recreated once needed again.
+///
+/// [`PassRecorder::update_texture`]: crate::renderer::emulator::PassRecorder::update_texture
+/// [`PassRecorder::bind_texture`]: crate::renderer::emulator::PassRecorder::bind_texture
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ImageUsageStats {
+    /// The most recent pass which sampled the image or [`None`] if it has never been sampled.
+    pub last_sampled_pass: Option<PassId>,
+
+    /// The number of passes started since the image was last sampled.
+    pub passes_since_sampled: Option<u64>,
+
+    /// The time since the last pass sampling the image has been ended.
+    pub time_since_sampled: Option<Duration>,
+
+    /// The total number of passes which sampled the image.
+    pub sampled_pass_count: u64,
+
+    /// The total number of draws which sampled the image.
+    pub sampled_draw_count: u64,
+}
+
+struct ImageUsage {
+    last_sampled_pass: Option<PassId>,
+    last_sampled_at: Option<Instant>,
+    sampled_pass_count: u64,
+    sampled_draw_count: u64,
+}
+
+impl ImageUsage {
+    fn new() -> Self {
+        Self {
+            last_sampled_pass: None,
+            last_sampled_at: None,
+            sampled_pass_count: 0,
+            sampled_draw_count: 0,
         }
     }
 }
@@ -0,0 +1,75 @@
+//! Rendering of 3d items and block models inside the gui.
+//!
+//! Minecraft draws held items and inventory block models as small 3d meshes in between the 2d
+//! gui elements. Each item is rendered into its own viewport covering the item slot which uses a
+//! sub-range of the depth buffer so items never intersect each other or the surrounding gui. The
+//! mesh is transformed by the host into the unit cube centered at the origin and projected using
+//! [`make_gui_item_projection`]. Scissors set using [`PassRecorder::set_scissor`] clip items like
+//! any other gui element.
+//!
+//! [`PassRecorder::set_scissor`]: crate::renderer::emulator::PassRecorder::set_scissor
+
+use ash::vk;
+
+use crate::renderer::emulator::FrameSize;
+
+use crate::prelude::*;
+
+/// The viewport index reserved for gui items by [`PassRecorder::begin_gui_item`].
+///
+/// [`PassRecorder::begin_gui_item`]: crate::renderer::emulator::PassRecorder::begin_gui_item
+pub const GUI_ITEM_VIEWPORT: u32 = crate::renderer::emulator::MAX_VIEWPORTS - 1;
+
+/// The placement of a gui item on screen.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GuiItemPlacement {
+    /// The top left corner of the item in logical gui pixels.
+    pub position: Vec2f32,
+
+    /// The width and height of the item in logical gui pixels. Vanilla item slots are 16.
+    pub size: f32,
+
+    /// The range of the depth buffer used by the item. Must be a sub-range of `[0, 1]`. Items
+    /// with a lower range are drawn in front of items with a higher range.
+    pub depth_range: (f32, f32),
+}
+
+impl GuiItemPlacement {
+    /// Returns the viewport covering the item in physical pixels.
+    pub fn make_viewport(&self, frame_size: &FrameSize) -> vk::Viewport {
+        let scale = frame_size.scale_factor;
+        let min_depth = self.depth_range.0.clamp(0f32, 1f32);
+        let max_depth = self.depth_range.1.clamp(min_depth, 1f32);
+
+        vk::Viewport {
+            x: self.position[0] * scale,
+            y: self.position[1] * scale,
+            width: self.size * scale,
+            height: self.size * scale,
+            min_depth,
+            max_depth,
+        }
+    }
+}
+
+/// Returns the depth range of the item at `layer` if the depth buffer is split into `layer_count`
+/// equal slices. Higher layers are drawn in front of lower layers.
+pub fn gui_depth_slice(layer: u32, layer_count: u32) -> (f32, f32) {
+    let layer_count = std::cmp::max(layer_count, 1);
+    let layer = std::cmp::min(layer, layer_count - 1);
+    let slice = 1f32 / (layer_count as f32);
+
+    let max = 1f32 - (layer as f32) * slice;
+    (max - slice, max)
+}
+
+/// Returns the projection used for gui items. Maps the unit cube centered at the origin with y up
+/// and z pointing towards the viewer to the full viewport and depth range.
+pub fn make_gui_item_projection() -> Mat4f32 {
+    Mat4f32::new(
+        2f32, 0f32, 0f32, 0f32,
+        0f32, -2f32, 0f32, 0f32,
+        0f32, 0f32, -1f32, 0.5f32,
+        0f32, 0f32, 0f32, 1f32
+    )
+}
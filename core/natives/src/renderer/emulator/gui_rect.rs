@@ -0,0 +1,213 @@
+//! Batched textured rectangles and nine-slice panels for the gui.
+//!
+//! Most of the vanilla gui consists of textured rectangles (`blit`) and panels stretched from a
+//! small texture by keeping the corners fixed. Instead of building an immediate mesh on the host for
+//! every element the host submits a list of rects which are expanded into quads by a
+//! [`GuiRectBatch`] and drawn with a single draw call.
+//!
+//! The vertex format of the shader used to draw the batch must store the position as
+//! `R32G32B32_SFLOAT` and uv0 as `R32G32_SFLOAT`. If the format has a color entry it must be stored
+//! as `R8G8B8A8_UNORM` and receives the tint of the rect. See [`GuiRectBatch::supports_format`].
+
+use ash::vk;
+
+use crate::renderer::emulator::mc_shaders::VertexFormat;
+use crate::renderer::emulator::MeshData;
+
+use crate::prelude::*;
+
+/// An axis aligned rect given by its minimum and maximum corner.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GuiRect {
+    pub min: Vec2f32,
+    pub max: Vec2f32,
+}
+
+impl GuiRect {
+    pub fn new(min: Vec2f32, max: Vec2f32) -> Self {
+        Self { min, max }
+    }
+
+    /// Creates a rect from its top left corner and size.
+    pub fn from_position_size(position: Vec2f32, size: Vec2f32) -> Self {
+        Self { min: position, max: position + size }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !(self.max[0] > self.min[0] && self.max[1] > self.min[1])
+    }
+}
+
+/// A rect drawn using a region of a texture.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TexturedRect {
+    /// The destination of the rect in logical gui pixels.
+    pub dst: GuiRect,
+
+    /// The normalized texture coordinates mapped to the destination.
+    pub uv: GuiRect,
+
+    /// The rgba color multiplied with the texture.
+    pub tint: [u8; 4],
+}
+
+/// A panel stretched from a texture region while keeping its borders unscaled.
+///
+/// The panel is split into 9 rects. The corners are drawn at their native size, the edges are
+/// stretched along one axis and the center is stretched along both axes. If the destination is
+/// smaller than the sum of two opposite borders both borders are shrunk proportionally.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct NineSlice {
+    /// The destination of the panel in logical gui pixels.
+    pub dst: GuiRect,
+
+    /// The normalized texture coordinates of the full panel.
+    pub uv: GuiRect,
+
+    /// The size of the left, top, right and bottom border in logical gui pixels.
+    pub border: [f32; 4],
+
+    /// The size of the left, top, right and bottom border in normalized texture coordinates.
+    pub uv_border: [f32; 4],
+
+    /// The rgba color multiplied with the texture.
+    pub tint: [u8; 4],
+}
+
+impl NineSlice {
+    /// Calls `f` for every non empty rect of the panel.
+    pub fn for_each_rect<F: FnMut(TexturedRect)>(&self, mut f: F) {
+        let [left, top, right, bottom] = self.border.map(|border| border.max(0f32));
+        let width = (self.dst.max[0] - self.dst.min[0]).max(0f32);
+        let height = (self.dst.max[1] - self.dst.min[1]).max(0f32);
+        let scale_x = if left + right > width { width / (left + right) } else { 1f32 };
+        let scale_y = if top + bottom > height { height / (top + bottom) } else { 1f32 };
+
+        let xs = [self.dst.min[0], self.dst.min[0] + left * scale_x, self.dst.max[0] - right * scale_x, self.dst.max[0]];
+        let ys = [self.dst.min[1], self.dst.min[1] + top * scale_y, self.dst.max[1] - bottom * scale_y, self.dst.max[1]];
+        let us = [self.uv.min[0], self.uv.min[0] + self.uv_border[0], self.uv.max[0] - self.uv_border[2], self.uv.max[0]];
+        let vs = [self.uv.min[1], self.uv.min[1] + self.uv_border[1], self.uv.max[1] - self.uv_border[3], self.uv.max[1]];
+
+        for y in 0..3 {
+            for x in 0..3 {
+                let rect = TexturedRect {
+                    dst: GuiRect::new(Vec2f32::new(xs[x], ys[y]), Vec2f32::new(xs[x + 1], ys[y + 1])),
+                    uv: GuiRect::new(Vec2f32::new(us[x], vs[y]), Vec2f32::new(us[x + 1], vs[y + 1])),
+                    tint: self.tint,
+                };
+                if !rect.dst.is_empty() {
+                    f(rect);
+                }
+            }
+        }
+    }
+}
+
+/// Expands rects into a triangle list mesh using 32bit indices.
+pub struct GuiRectBatch {
+    format: VertexFormat,
+    depth: f32,
+    vertex_data: Vec<u8>,
+    index_data: Vec<u8>,
+    vertex_count: u32,
+    index_count: u32,
+}
+
+impl GuiRectBatch {
+    /// Creates a new batch for a vertex format. All quads are placed at `depth` along the z axis.
+    ///
+    /// Panics if the vertex format is not supported. See [`GuiRectBatch::supports_format`].
+    pub fn new(format: &VertexFormat, depth: f32) -> Self {
+        if !Self::supports_format(format) {
+            log::error!("Vertex format {:?} cannot be used to draw gui rects", format);
+            panic!();
+        }
+
+        Self {
+            format: *format,
+            depth,
+            vertex_data: Vec::new(),
+            index_data: Vec::new(),
+            vertex_count: 0,
+            index_count: 0,
+        }
+    }
+
+    /// Returns true if the vertex format stores the position, uv0 and color in a format which can be
+    /// written by the batch.
+    pub fn supports_format(format: &VertexFormat) -> bool {
+        if format.position_quantization.is_some() || format.position.format != vk::Format::R32G32B32_SFLOAT {
+            return false;
+        }
+        if !matches!(&format.uv0, Some(uv0) if uv0.format == vk::Format::R32G32_SFLOAT) {
+            return false;
+        }
+        format.color.as_ref().map_or(true, |color| color.format == vk::Format::R8G8B8A8_UNORM)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index_count == 0
+    }
+
+    /// Adds a rect to the batch. Empty rects are skipped.
+    pub fn push_rect(&mut self, rect: &TexturedRect) {
+        if rect.dst.is_empty() {
+            return;
+        }
+
+        let corners = [
+            (rect.dst.min[0], rect.dst.min[1], rect.uv.min[0], rect.uv.min[1]),
+            (rect.dst.min[0], rect.dst.max[1], rect.uv.min[0], rect.uv.max[1]),
+            (rect.dst.max[0], rect.dst.max[1], rect.uv.max[0], rect.uv.max[1]),
+            (rect.dst.max[0], rect.dst.min[1], rect.uv.max[0], rect.uv.min[1]),
+        ];
+        for (x, y, u, v) in corners {
+            self.push_vertex([x, y, self.depth], [u, v], rect.tint);
+        }
+
+        let base = self.vertex_count;
+        for index in [0, 1, 2, 2, 3, 0] {
+            self.index_data.extend_from_slice(&(base + index).to_ne_bytes());
+        }
+        self.vertex_count += 4;
+        self.index_count += 6;
+    }
+
+    /// Adds all rects of a nine slice panel to the batch.
+    pub fn push_nine_slice(&mut self, panel: &NineSlice) {
+        panel.for_each_rect(|rect| self.push_rect(&rect));
+    }
+
+    /// Returns the mesh containing all rects pushed so far.
+    pub fn as_mesh_data(&self) -> MeshData {
+        MeshData {
+            vertex_data: &self.vertex_data,
+            index_data: &self.index_data,
+            vertex_stride: self.format.stride,
+            index_count: self.index_count,
+            index_type: vk::IndexType::UINT32,
+            primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        }
+    }
+
+    fn push_vertex(&mut self, position: [f32; 3], uv: [f32; 2], tint: [u8; 4]) {
+        let base = self.vertex_data.len();
+        self.vertex_data.resize(base + (self.format.stride as usize), 0u8);
+        let vertex = &mut self.vertex_data[base..];
+
+        let offset = self.format.position.offset as usize;
+        for (i, value) in position.iter().enumerate() {
+            vertex[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        let offset = self.format.uv0.as_ref().unwrap().offset as usize;
+        for (i, value) in uv.iter().enumerate() {
+            vertex[(offset + i * 4)..(offset + i * 4 + 4)].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        if let Some(color) = &self.format.color {
+            let offset = color.offset as usize;
+            vertex[offset..(offset + 4)].copy_from_slice(&tint);
+        }
+    }
+}
@@ -11,30 +11,44 @@ use crate::util::alloc::next_aligned;
 use crate::prelude::*;
 
 pub(super) struct ImmediatePool {
-    buffer_queue: Mutex<VecDeque<Box<ImmediateBuffer>>>,
+    buffer_queue: Mutex<BufferQueue>,
     ready_condvar: Condvar,
 }
 
-impl ImmediatePool {
-    pub(super) fn new(device: Arc<DeviceContext>) -> Self {
-        let mut buffer_queue = VecDeque::with_capacity(2);
-        for _ in 0..2 {
-            buffer_queue.push_back(Box::new(ImmediateBuffer::new(device.clone())));
-        }
+struct BufferQueue {
+    buffers: VecDeque<Box<ImmediateBuffer>>,
+    /// The total number of buffers owned by the pool including buffers currently in use.
+    frames_in_flight: u32,
+}
 
+impl ImmediatePool {
+    /// Creates a pool with one buffer for each frame which may be in flight. Each buffer is
+    /// assigned a unique frame index in `0..frames_in_flight`.
+    pub(super) fn new(device: Arc<DeviceContext>, frames_in_flight: u32) -> Self {
         Self {
-            buffer_queue: Mutex::new(buffer_queue),
+            buffer_queue: Mutex::new(BufferQueue {
+                buffers: Self::create_buffers(&device, frames_in_flight),
+                frames_in_flight,
+            }),
             ready_condvar: Condvar::new(),
         }
     }
 
+    fn create_buffers(device: &Arc<DeviceContext>, frames_in_flight: u32) -> VecDeque<Box<ImmediateBuffer>> {
+        let mut buffers = VecDeque::with_capacity(frames_in_flight as usize);
+        for frame_index in 0..frames_in_flight {
+            buffers.push_back(Box::new(ImmediateBuffer::new(device.clone(), frame_index)));
+        }
+        buffers
+    }
+
     pub(super) fn get_next_buffer(&self) -> Box<ImmediateBuffer> {
         let mut guard = self.buffer_queue.lock().unwrap_or_else(|_| {
             log::error!("Poisoned queue mutex in ImmediatePool::get_next_buffer");
             panic!()
         });
         loop {
-            if let Some(next) = guard.pop_front() {
+            if let Some(next) = guard.buffers.pop_front() {
                 return next;
             }
 
@@ -58,8 +72,39 @@ impl ImmediatePool {
             panic!()
         });
 
-        guard.push_back(buffer);
-        self.ready_condvar.notify_one();
+        guard.buffers.push_back(buffer);
+        // Both buffer requests and resizes wait on the condvar
+        self.ready_condvar.notify_all();
+    }
+
+    /// Changes the number of buffers in the pool. Blocks until all buffers have been returned,
+    /// i.e. until all passes using a frame slot have completed. `on_idle` is called once no frame
+    /// slot is in use and before any new buffer is handed out so other per frame resources can be
+    /// rebuilt.
+    ///
+    /// Must not be called by a thread which is currently recording a pass.
+    pub(super) fn resize<F: FnOnce()>(&self, device: &Arc<DeviceContext>, frames_in_flight: u32, on_idle: F) {
+        let mut guard = self.buffer_queue.lock().unwrap_or_else(|_| {
+            log::error!("Poisoned queue mutex in ImmediatePool::resize");
+            panic!()
+        });
+        while guard.buffers.len() < (guard.frames_in_flight as usize) {
+            let (new_guard, timeout) = self.ready_condvar.wait_timeout(guard, std::time::Duration::from_secs(1)).unwrap_or_else(|_| {
+                log::error!("Poisoned queue mutex in ImmediatePool::resize after waiting for condvar");
+                panic!()
+            });
+            guard = new_guard;
+
+            if timeout.timed_out() {
+                log::warn!("1s timeout hit while waiting for in flight frames in ImmediatePool::resize");
+            }
+        }
+
+        on_idle();
+
+        guard.buffers = Self::create_buffers(device, frames_in_flight);
+        guard.frames_in_flight = frames_in_flight;
+        self.ready_condvar.notify_all();
     }
 }
 
@@ -67,6 +112,7 @@ impl RefUnwindSafe for ImmediatePool {} // Condvar is not RefUnwindSafe
 
 pub(super) struct ImmediateBuffer {
     device: Arc<DeviceContext>,
+    frame_index: u32,
     current_buffer: Buffer,
     old_buffers: Vec<Buffer>,
 }
@@ -75,16 +121,23 @@ impl ImmediateBuffer {
     const MIN_BUFFER_SIZE: vk::DeviceSize = 2u64.pow(24); // 16MB
     const OVER_ALLOCATION: u8 = 77; // 30%
 
-    fn new(device: Arc<DeviceContext>) -> Self {
+    fn new(device: Arc<DeviceContext>, frame_index: u32) -> Self {
         let current_buffer = Buffer::new(device.clone(), Self::MIN_BUFFER_SIZE);
 
         Self {
             device,
+            frame_index,
             current_buffer,
             old_buffers: Vec::new(),
         }
     }
 
+    /// The index of the frame slot owned by this buffer. Per frame resources like the uniform
+    /// ring may be reused by the holder of this buffer.
+    pub(super) fn get_frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
     pub(super) fn generate_copy_commands(&self, cmd: vk::CommandBuffer) {
         self.current_buffer.generate_copy_commands(cmd);
         for old_buffer in &self.old_buffers {
@@ -206,7 +259,7 @@ impl Buffer {
     fn create_main_buffer(device: &DeviceContext, size: vk::DeviceSize) -> (vk::Buffer, Allocation, Option<NonNull<u8>>) {
         let info = vk::BufferCreateInfo::builder()
             .size(size)
-            .usage(vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST)
+            .usage(vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST)
             .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
         let (buffer, allocation, mapped) = unsafe {
@@ -0,0 +1,71 @@
+//! Bob and spin animation of dropped items.
+//!
+//! Vanilla recomputes the model view matrix of every dropped item each frame to apply its bob and
+//! spin animation. Shaders created with
+//! [`ShaderSpecialization::item_animation`](crate::renderer::emulator::mc_shaders::ShaderSpecialization::item_animation)
+//! instead apply the animation in the vertex shader using the game time. The host only needs to
+//! set the static transform of the item once and pass a small [`ItemAnimation`] per draw using
+//! [`McUniformData::ItemAnimation`].
+//!
+//! The animation is applied in model space before the chunk offset and model view matrix. The
+//! item spins around the vertical axis through the model origin and is lifted by the bob offset.
+//! The game time uniform wraps every 24000 ticks so the animation jumps once per day cycle.
+
+use crate::renderer::emulator::mc_shaders::McUniformData;
+
+use crate::prelude::*;
+
+/// The per draw parameters of an animated dropped item.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ItemAnimation {
+    /// The phase offset of the bob and spin in radians. Used to desynchronize items.
+    pub phase: f32,
+
+    /// The rotation speed around the vertical axis in radians per tick.
+    pub spin_rate: f32,
+}
+
+impl ItemAnimation {
+    /// The spin rate used by vanilla in radians per tick.
+    pub const VANILLA_SPIN_RATE: f32 = 1f32 / 20f32;
+
+    /// The angular speed of the bob in radians per tick.
+    pub const BOB_SPEED: f32 = 0.1f32;
+
+    /// Half the distance in blocks the item moves up and down.
+    pub const BOB_HEIGHT: f32 = 0.1f32;
+
+    /// Creates the animation of a vanilla item entity using its random bob offset.
+    pub fn new(bob_offset: f32) -> Self {
+        Self {
+            phase: bob_offset,
+            spin_rate: Self::VANILLA_SPIN_RATE,
+        }
+    }
+
+    /// Returns the height the item is lifted by at a time in ticks.
+    pub fn get_bob(&self, ticks: f32) -> f32 {
+        (ticks * Self::BOB_SPEED + self.phase).sin() * Self::BOB_HEIGHT + Self::BOB_HEIGHT
+    }
+
+    /// Returns the rotation around the vertical axis in radians at a time in ticks.
+    pub fn get_spin(&self, ticks: f32) -> f32 {
+        ticks * self.spin_rate + self.phase
+    }
+
+    /// Returns the matrix applied to the model by the vertex shader at a time in ticks. Can be
+    /// used to draw animated items with shaders not using item animation.
+    pub fn get_model_matrix(&self, ticks: f32) -> Mat4f32 {
+        let (sin, cos) = self.get_spin(ticks).sin_cos();
+        Mat4f32::new(
+            cos, 0f32, sin, 0f32,
+            0f32, 1f32, 0f32, self.get_bob(ticks),
+            -sin, 0f32, cos, 0f32,
+            0f32, 0f32, 0f32, 1f32
+        )
+    }
+
+    pub fn as_uniform_data(&self) -> McUniformData {
+        McUniformData::ItemAnimation(Vec2f32::new(self.phase, self.spin_rate))
+    }
+}
@@ -0,0 +1,511 @@
+//! A small job system used to split cpu side renderer work like culling, sorting and mesh
+//! optimization over multiple threads.
+//!
+//! Work is submitted in batches. A batch is split into tasks which are claimed by the calling
+//! thread and by jobs submitted to a [`JobExecutor`]. Batches are built on [`JobSystem::scope`]:
+//! the calling thread always participates and blocks until all tasks of the scope have completed,
+//! so tasks may borrow data from the caller. Since jobs only claim tasks which have not been
+//! started yet a scope never waits for a job which is still queued. This makes it safe to start
+//! batches from inside a task.
+//!
+//! By default jobs are executed by an internal pool of threads. Hosts which already manage their
+//! own worker threads can provide a [`JobExecutor`] instead.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// A unit of work passed to a [`JobExecutor`].
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Executes jobs on some set of threads.
+pub trait JobExecutor: Send + Sync {
+    /// Runs the job at some point in the future on any thread. Jobs may also be run inline.
+    ///
+    /// Jobs are cheap to drop without running them. Dropping a job does not cause any work to be
+    /// lost since the work is performed by the thread starting the batch instead.
+    fn execute(&self, job: Job);
+
+    /// The number of jobs which can run concurrently. Used to decide how many tasks a batch is
+    /// split into.
+    fn get_parallelism(&self) -> usize;
+}
+
+/// The type of work performed by a batch. Statistics are collected separately for each kind.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum JobKind {
+    Culling = 0,
+    Sorting = 1,
+    MeshOptimization = 2,
+    MeshDecompression = 3,
+}
+
+impl JobKind {
+    pub const COUNT: usize = 4;
+
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Culling),
+            1 => Some(Self::Sorting),
+            2 => Some(Self::MeshOptimization),
+            3 => Some(Self::MeshDecompression),
+            _ => None,
+        }
+    }
+}
+
+/// Timing statistics of all batches of one [`JobKind`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct JobKindStats {
+    pub batch_count: u64,
+    pub task_count: u64,
+    /// The sum of the execution time of all tasks.
+    pub total_task_time: Duration,
+    /// The longest execution time of any single task.
+    pub max_task_time: Duration,
+    /// The time from starting a batch until all of its tasks completed, summed over all batches.
+    pub total_batch_time: Duration,
+}
+
+impl JobKindStats {
+    /// The average number of threads working on a batch. 1 if all work ran on the calling thread.
+    pub fn get_average_parallelism(&self) -> f32 {
+        if self.total_batch_time.is_zero() {
+            return 1f32;
+        }
+        self.total_task_time.as_secs_f32() / self.total_batch_time.as_secs_f32()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct JobStats {
+    kinds: [JobKindStats; JobKind::COUNT],
+}
+
+impl JobStats {
+    pub fn get(&self, kind: JobKind) -> &JobKindStats {
+        &self.kinds[kind as usize]
+    }
+}
+
+/// Distributes batches of work over a [`JobExecutor`] and collects statistics about them.
+pub struct JobSystem {
+    executor: RwLock<Arc<dyn JobExecutor>>,
+    stats: Mutex<JobStats>,
+}
+
+impl JobSystem {
+    /// Passed as thread count to use one thread less than the number of available cores. The
+    /// remaining core is used by the thread starting a batch.
+    pub const AUTOMATIC_THREAD_COUNT: u32 = 0;
+
+    /// Creates a job system using an internal thread pool with `thread_count` threads. See
+    /// [`JobSystem::set_thread_count`].
+    pub fn new(thread_count: u32) -> Self {
+        Self::with_executor(Self::create_pool(thread_count))
+    }
+
+    pub fn with_executor(executor: Arc<dyn JobExecutor>) -> Self {
+        Self {
+            executor: RwLock::new(executor),
+            stats: Mutex::new(JobStats::default()),
+        }
+    }
+
+    /// Replaces the executor with an internal thread pool of `thread_count` threads. If the count
+    /// is 0 (see [`JobSystem::AUTOMATIC_THREAD_COUNT`]) it is derived from the number of available
+    /// cores. The threads of the previous pool exit once all queued jobs have been executed.
+    pub fn set_thread_count(&self, thread_count: u32) {
+        self.set_executor(Self::create_pool(thread_count));
+    }
+
+    /// Replaces the executor used for future batches. Batches which are currently running keep
+    /// using the previous executor.
+    pub fn set_executor(&self, executor: Arc<dyn JobExecutor>) {
+        *self.executor.write().unwrap() = executor;
+    }
+
+    /// The maximum number of threads working on a batch including the calling thread.
+    pub fn get_parallelism(&self) -> usize {
+        self.executor.read().unwrap().get_parallelism() + 1
+    }
+
+    pub fn get_stats(&self) -> JobStats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub fn reset_stats(&self) {
+        *self.stats.lock().unwrap() = JobStats::default();
+    }
+
+    /// Calls `f` for each item and returns the results in the same order as `items`. Items are
+    /// split into tasks of at least `min_task_size` items.
+    pub fn map<T, R, F>(&self, kind: JobKind, items: &[T], min_task_size: usize, f: F) -> Vec<R>
+        where T: Sync, R: Send, F: Fn(&T) -> R + Sync {
+
+        let task_size = self.get_task_size(items.len(), min_task_size);
+        let chunks: Vec<_> = items.chunks(task_size).collect();
+        let results: Vec<Mutex<Vec<R>>> = chunks.iter().map(|_| Mutex::new(Vec::new())).collect();
+
+        self.run(kind, chunks.len(), &|index| {
+            let result: Vec<R> = chunks[index].iter().map(&f).collect();
+            *results[index].lock().unwrap() = result;
+        });
+
+        results.into_iter().flat_map(|result| result.into_inner().unwrap()).collect()
+    }
+
+    /// Calls `f` with a mutable reference to each item. Items are split into tasks of at least
+    /// `min_task_size` items.
+    pub fn for_each_mut<T, F>(&self, kind: JobKind, items: &mut [T], min_task_size: usize, f: F)
+        where T: Send, F: Fn(&mut T) + Sync {
+
+        let task_size = self.get_task_size(items.len(), min_task_size);
+        let chunks: Vec<Mutex<&mut [T]>> = items.chunks_mut(task_size).map(Mutex::new).collect();
+
+        self.run(kind, chunks.len(), &|index| {
+            chunks[index].lock().unwrap().iter_mut().for_each(&f);
+        });
+    }
+
+    /// Sorts the slice using a key extraction function. Runs of at least `min_task_size` items are
+    /// sorted in parallel and then merged on the calling thread. Like [`slice::sort_by_key`] the
+    /// sort is stable.
+    pub fn sort_by_key<T, K, F>(&self, items: &mut [T], min_task_size: usize, f: F)
+        where T: Send + Clone, K: Ord, F: Fn(&T) -> K + Sync {
+
+        let task_size = self.get_task_size(items.len(), min_task_size);
+        if task_size >= items.len() {
+            let start = Instant::now();
+            items.sort_by_key(&f);
+            self.record_inline(JobKind::Sorting, start.elapsed());
+            return;
+        }
+
+        self.for_each_run(items, task_size, |run| run.sort_by_key(&f));
+
+        // Merge pairs of sorted runs until a single run is left
+        let mut run_size = task_size;
+        let mut merged = Vec::with_capacity(items.len());
+        while run_size < items.len() {
+            for pair in items.chunks(run_size * 2) {
+                let (left, right) = pair.split_at(std::cmp::min(run_size, pair.len()));
+                let (mut i, mut j) = (0, 0);
+                while i < left.len() && j < right.len() {
+                    // Taking from the left run on equal keys keeps the sort stable
+                    if f(&right[j]) < f(&left[i]) {
+                        merged.push(right[j].clone());
+                        j += 1;
+                    } else {
+                        merged.push(left[i].clone());
+                        i += 1;
+                    }
+                }
+                merged.extend_from_slice(&left[i..]);
+                merged.extend_from_slice(&right[j..]);
+            }
+            items.clone_from_slice(&merged);
+            merged.clear();
+            run_size *= 2;
+        }
+    }
+
+    fn for_each_run<T: Send, F: Fn(&mut [T]) + Sync>(&self, items: &mut [T], task_size: usize, f: F) {
+        let chunks: Vec<Mutex<&mut [T]>> = items.chunks_mut(task_size).map(Mutex::new).collect();
+        self.run(JobKind::Sorting, chunks.len(), &|index| {
+            let mut chunk = chunks[index].lock().unwrap();
+            f(&mut chunk);
+        });
+    }
+
+    fn get_task_size(&self, item_count: usize, min_task_size: usize) -> usize {
+        let min_task_size = std::cmp::max(min_task_size, 1);
+        let task_count = std::cmp::min(self.get_parallelism(), item_count / min_task_size);
+        if task_count <= 1 {
+            std::cmp::max(item_count, 1)
+        } else {
+            (item_count + task_count - 1) / task_count
+        }
+    }
+
+    /// Runs `f` with a [`JobScope`] in which tasks borrowing data from the caller can be spawned.
+    /// Returns once `f` and all tasks spawned in the scope have completed. Tasks which have not
+    /// been started by a job yet are run on the calling thread. If any task panics the panic is
+    /// propagated to the caller after all other tasks have completed.
+    pub fn scope<'env, F, R>(&self, f: F) -> R where F: for<'scope> FnOnce(&'scope JobScope<'scope, 'env>) -> R {
+        let scope = JobScope {
+            executor: self.executor.read().unwrap().clone(),
+            shared: Arc::new(ScopeShared {
+                state: Mutex::new(ScopeState::default()),
+                condvar: Condvar::new(),
+            }),
+            _scope: PhantomData,
+            _env: PhantomData,
+        };
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        let panicked = scope.shared.join();
+
+        match result {
+            Err(payload) => std::panic::resume_unwind(payload),
+            Ok(_) if panicked => {
+                log::error!("Task of job scope panicked");
+                panic!();
+            }
+            Ok(result) => result,
+        }
+    }
+
+    /// Runs `task_count` tasks by calling `run` with the index of each task. Returns once all tasks
+    /// have completed. If any task panics the panic is propagated to the caller after all other
+    /// tasks have completed.
+    pub fn run(&self, kind: JobKind, task_count: usize, run: &(dyn Fn(usize) + Sync)) {
+        if task_count == 0 {
+            return;
+        }
+        let start = Instant::now();
+
+        let next_task = AtomicUsize::new(0);
+        let progress = Mutex::new(BatchProgress::default());
+        // Claims and runs tasks until no task is left
+        let work = || loop {
+            let index = next_task.fetch_add(1, Ordering::AcqRel);
+            if index >= task_count {
+                return;
+            }
+
+            let start = Instant::now();
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| run(index)));
+            let time = start.elapsed();
+
+            let mut progress = progress.lock().unwrap();
+            progress.task_time += time;
+            progress.max_task_time = std::cmp::max(progress.max_task_time, time);
+            progress.panicked |= result.is_err();
+        };
+
+        let job_count = if task_count > 1 {
+            std::cmp::min(task_count - 1, self.executor.read().unwrap().get_parallelism())
+        } else {
+            0
+        };
+        self.scope(|scope| {
+            for _ in 0..job_count {
+                scope.spawn(&work);
+            }
+            work();
+        });
+        let progress = progress.into_inner().unwrap();
+
+        let mut stats = self.stats.lock().unwrap();
+        let stats = &mut stats.kinds[kind as usize];
+        stats.batch_count += 1;
+        stats.task_count += task_count as u64;
+        stats.total_task_time += progress.task_time;
+        stats.max_task_time = std::cmp::max(stats.max_task_time, progress.max_task_time);
+        stats.total_batch_time += start.elapsed();
+        drop(stats);
+
+        if progress.panicked {
+            log::error!("Task of {:?} job batch panicked", kind);
+            panic!();
+        }
+    }
+
+    fn record_inline(&self, kind: JobKind, time: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let stats = &mut stats.kinds[kind as usize];
+        stats.batch_count += 1;
+        stats.task_count += 1;
+        stats.total_task_time += time;
+        stats.max_task_time = std::cmp::max(stats.max_task_time, time);
+        stats.total_batch_time += time;
+    }
+
+    fn create_pool(thread_count: u32) -> Arc<dyn JobExecutor> {
+        let thread_count = if thread_count == Self::AUTOMATIC_THREAD_COUNT {
+            std::thread::available_parallelism().map_or(1, |count| count.get()).saturating_sub(1)
+        } else {
+            thread_count as usize
+        };
+        Arc::new(ThreadPool::new(thread_count))
+    }
+}
+
+#[derive(Default)]
+struct BatchProgress {
+    task_time: Duration,
+    max_task_time: Duration,
+    panicked: bool,
+}
+
+/// A scope created by [`JobSystem::scope`]. All tasks spawned in the scope complete before the
+/// scope ends.
+pub struct JobScope<'scope, 'env: 'scope> {
+    executor: Arc<dyn JobExecutor>,
+    shared: Arc<ScopeShared>,
+    _scope: PhantomData<&'scope mut &'scope ()>,
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'scope, 'env> JobScope<'scope, 'env> {
+    /// Spawns a task which is run by a job or by the thread which created the scope, whichever
+    /// claims it first.
+    pub fn spawn<F>(&'scope self, task: F) where F: FnOnce() + Send + 'scope {
+        let task: Box<dyn FnOnce() + Send + 'scope> = Box::new(task);
+        // Safety: Jobs are 'static but only access the task after claiming it from the scope.
+        // JobSystem::scope does not return before every task has been claimed and has completed,
+        // so a task never runs or is dropped after 'scope ended.
+        let task: Job = unsafe { std::mem::transmute(task) };
+
+        self.shared.state.lock().unwrap().tasks.push_back(task);
+        self.shared.condvar.notify_all();
+
+        let shared = self.shared.clone();
+        self.executor.execute(Box::new(move || {
+            shared.run_one();
+        }));
+    }
+}
+
+struct ScopeShared {
+    state: Mutex<ScopeState>,
+    condvar: Condvar,
+}
+
+#[derive(Default)]
+struct ScopeState {
+    /// Tasks which have not been claimed yet
+    tasks: VecDeque<Job>,
+    /// The number of claimed tasks which have not completed yet
+    running: usize,
+    panicked: bool,
+}
+
+impl ScopeShared {
+    /// Claims and runs a single task. Returns false if no task was left.
+    fn run_one(&self) -> bool {
+        let task = {
+            let mut state = self.state.lock().unwrap();
+            match state.tasks.pop_front() {
+                Some(task) => {
+                    state.running += 1;
+                    task
+                }
+                None => return false,
+            }
+        };
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(task));
+
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        state.panicked |= result.is_err();
+        self.condvar.notify_all();
+        true
+    }
+
+    /// Runs all unclaimed tasks and waits until all claimed tasks have completed. Returns true if
+    /// any task panicked.
+    fn join(&self) -> bool {
+        loop {
+            while self.run_one() {
+            }
+
+            let mut state = self.state.lock().unwrap();
+            while state.running != 0 && state.tasks.is_empty() {
+                state = self.condvar.wait(state).unwrap();
+            }
+            // Running tasks may have spawned new tasks
+            if state.running == 0 && state.tasks.is_empty() {
+                return std::mem::take(&mut state.panicked);
+            }
+        }
+    }
+}
+
+/// The default executor running jobs on a fixed number of threads.
+struct ThreadPool {
+    shared: Arc<PoolShared>,
+    thread_count: usize,
+}
+
+struct PoolShared {
+    queue: Mutex<PoolQueue>,
+    condvar: Condvar,
+}
+
+struct PoolQueue {
+    jobs: VecDeque<Job>,
+    shutdown: bool,
+}
+
+impl ThreadPool {
+    fn new(thread_count: usize) -> Self {
+        let shared = Arc::new(PoolShared {
+            queue: Mutex::new(PoolQueue {
+                jobs: VecDeque::new(),
+                shutdown: false,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        for index in 0..thread_count {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name(format!("b4d-job-{}", index))
+                .spawn(move || Self::run_thread(&shared))
+                .unwrap_or_else(|err| {
+                    log::error!("Failed to spawn job thread {:?}", err);
+                    panic!()
+                });
+        }
+
+        Self {
+            shared,
+            thread_count,
+        }
+    }
+
+    fn run_thread(shared: &PoolShared) {
+        let mut queue = shared.queue.lock().unwrap();
+        loop {
+            if let Some(job) = queue.jobs.pop_front() {
+                drop(queue);
+                // Panics of tasks are caught by the batch
+                job();
+                queue = shared.queue.lock().unwrap();
+            } else if queue.shutdown {
+                return;
+            } else {
+                queue = shared.condvar.wait(queue).unwrap();
+            }
+        }
+    }
+}
+
+impl JobExecutor for ThreadPool {
+    fn execute(&self, job: Job) {
+        if self.thread_count == 0 {
+            // The calling thread performs all work
+            return;
+        }
+        self.shared.queue.lock().unwrap().jobs.push_back(job);
+        self.shared.condvar.notify_one();
+    }
+
+    fn get_parallelism(&self) -> usize {
+        self.thread_count
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.condvar.notify_all();
+    }
+}
@@ -0,0 +1,298 @@
+//! Streaming target for map item textures.
+//!
+//! Map items are small 128x128 textures which may be fully rewritten every tick, often for many
+//! maps at once (for example item frame map walls). Creating an image per map and uploading each
+//! update separately results in a large number of small images and upload tasks. Instead a
+//! [`MapTextureCache`] stores the maps as tiles of atlas array images (see
+//! [`ImageArrayMode::Atlas`]) called pages and keys them by the map id of the host.
+//!
+//! Updates are collected in a host side staging buffer and uploaded by [`MapTextureCache::flush`]
+//! with a single write per page. The staging buffer is double buffered so other threads can keep
+//! writing updates for the next flush while the previous updates are copied to the gpu.
+//!
+//! Maps are drawn by binding the page returned by [`MapTextureCache::get_page`] and using the
+//! texture coordinates of the [`MapSlot`] returned by [`MapTextureCache::get_map`]. Maps which are
+//! unloaded by the host are removed using [`MapTextureCache::remove_map`] or evicted after not
+//! being used for some time using [`MapTextureCache::evict_idle`]. Freed tiles are reused by new
+//! maps, pages are never destroyed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::renderer::emulator::{EmulatorRenderer, GlobalImage, ImageArrayMode, ImageData};
+use crate::util::format::Format;
+
+use crate::prelude::*;
+
+/// The width and height of a map in texels.
+pub const MAP_SIZE: u32 = 128;
+
+/// The size in bytes of the rgba data of a single map.
+pub const MAP_DATA_SIZE: usize = (MAP_SIZE * MAP_SIZE * 4) as usize;
+
+/// The number of maps in each row and column of a page.
+pub const PAGE_GRID: u32 = 8;
+
+/// The number of maps stored in a single page.
+pub const MAPS_PER_PAGE: u32 = PAGE_GRID * PAGE_GRID;
+
+/// The location of a map in the pages of a [`MapTextureCache`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct MapSlot {
+    pub page: u32,
+
+    /// The index of the tile in the page in row major order.
+    pub tile: u32,
+}
+
+impl MapSlot {
+    /// Returns the offset of the map in atlas texels of its page.
+    pub fn get_texel_offset(&self) -> Vec2u32 {
+        Vec2u32::new(self.tile % PAGE_GRID, self.tile / PAGE_GRID) * MAP_SIZE
+    }
+
+    /// Returns the minimum and maximum texture coordinate of the map in its page.
+    pub fn get_uv_rect(&self) -> (Vec2f32, Vec2f32) {
+        let size = 1f32 / (PAGE_GRID as f32);
+        let min = Vec2f32::new((self.tile % PAGE_GRID) as f32, (self.tile / PAGE_GRID) as f32) * size;
+        (min, min.add_scalar(size))
+    }
+}
+
+struct SlotEntry {
+    slot: MapSlot,
+    last_used: u64,
+}
+
+/// Assigns tiles of pages to map ids. Does not own any images. Used by [`MapTextureCache`].
+pub struct MapSlots {
+    entries: HashMap<i32, SlotEntry>,
+    free: Vec<MapSlot>,
+    page_count: u32,
+    current_flush: u64,
+}
+
+impl MapSlots {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            free: Vec::new(),
+            page_count: 0,
+            current_flush: 0,
+        }
+    }
+
+    /// Returns the slot of a map allocating a new one if the map does not have a slot yet. If all
+    /// pages are full a new page is added. Marks the map as used.
+    pub fn get_or_allocate(&mut self, map_id: i32) -> MapSlot {
+        let current_flush = self.current_flush;
+        if let Some(entry) = self.entries.get_mut(&map_id) {
+            entry.last_used = current_flush;
+            return entry.slot;
+        }
+
+        if self.free.is_empty() {
+            let page = self.page_count;
+            self.page_count += 1;
+            self.free.extend((0..MAPS_PER_PAGE).rev().map(|tile| MapSlot { page, tile }));
+        }
+        let slot = self.free.pop().unwrap();
+        self.entries.insert(map_id, SlotEntry { slot, last_used: current_flush });
+        slot
+    }
+
+    /// Returns the slot of a map and marks it as used.
+    pub fn get(&mut self, map_id: i32) -> Option<MapSlot> {
+        let current_flush = self.current_flush;
+        self.entries.get_mut(&map_id).map(|entry| {
+            entry.last_used = current_flush;
+            entry.slot
+        })
+    }
+
+    /// Frees the slot of a map. Returns the freed slot.
+    pub fn remove(&mut self, map_id: i32) -> Option<MapSlot> {
+        let entry = self.entries.remove(&map_id)?;
+        self.free.push(entry.slot);
+        Some(entry.slot)
+    }
+
+    /// Frees the slots of all maps which have not been used during the last `max_idle_flushes`
+    /// flushes. Returns the ids of the evicted maps.
+    pub fn evict_idle(&mut self, max_idle_flushes: u64) -> Vec<i32> {
+        let current_flush = self.current_flush;
+        let evicted: Vec<_> = self.entries.iter()
+            .filter(|(_, entry)| current_flush - entry.last_used > max_idle_flushes)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &evicted {
+            self.remove(*id);
+        }
+        evicted
+    }
+
+    /// Advances the flush counter used to track idle maps.
+    pub fn next_flush(&mut self) {
+        self.current_flush += 1;
+    }
+
+    pub fn get_map_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get_page_count(&self) -> u32 {
+        self.page_count
+    }
+}
+
+impl Default for MapSlots {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Host side map updates waiting for the next flush. Later updates of the same map replace earlier
+/// ones.
+struct PendingUpdates {
+    offsets: HashMap<i32, usize>,
+    data: Vec<u8>,
+}
+
+impl PendingUpdates {
+    fn new() -> Self {
+        Self {
+            offsets: HashMap::new(),
+            data: Vec::new(),
+        }
+    }
+
+    fn write(&mut self, map_id: i32, data: &[u8]) {
+        let next_offset = self.data.len();
+        let offset = *self.offsets.entry(map_id).or_insert(next_offset);
+        if offset == next_offset {
+            self.data.resize(next_offset + MAP_DATA_SIZE, 0u8);
+        }
+        self.data[offset..(offset + MAP_DATA_SIZE)].copy_from_slice(data);
+    }
+
+    fn clear(&mut self) {
+        self.offsets.clear();
+        self.data.clear();
+    }
+}
+
+struct CacheState {
+    slots: MapSlots,
+    pages: Vec<Arc<GlobalImage>>,
+}
+
+/// Stores map textures keyed by map id. See the [module docs](self).
+pub struct MapTextureCache {
+    emulator: Arc<EmulatorRenderer>,
+    state: Mutex<CacheState>,
+    pending: Mutex<PendingUpdates>,
+    spare: Mutex<PendingUpdates>,
+}
+
+impl MapTextureCache {
+    pub fn new(emulator: Arc<EmulatorRenderer>) -> Self {
+        Self {
+            emulator,
+            state: Mutex::new(CacheState {
+                slots: MapSlots::new(),
+                pages: Vec::new(),
+            }),
+            pending: Mutex::new(PendingUpdates::new()),
+            spare: Mutex::new(PendingUpdates::new()),
+        }
+    }
+
+    /// Replaces the content of a map. `data` must contain [`MAP_DATA_SIZE`] bytes of tightly
+    /// packed rgba data. The map is allocated if it does not exist yet. The new content is uploaded
+    /// during the next call to [`MapTextureCache::flush`].
+    pub fn update_map(&self, map_id: i32, data: &[u8]) {
+        if data.len() != MAP_DATA_SIZE {
+            log::error!("Map data must be {:?} bytes but got {:?} bytes", MAP_DATA_SIZE, data.len());
+            panic!();
+        }
+
+        self.allocate_slot(map_id);
+        self.pending.lock().unwrap().write(map_id, data);
+    }
+
+    /// Returns the slot of a map and marks it as used. Returns [`None`] if the map does not exist.
+    pub fn get_map(&self, map_id: i32) -> Option<MapSlot> {
+        self.state.lock().unwrap().slots.get(map_id)
+    }
+
+    /// Returns a page image. Pages are created on demand when maps are added.
+    pub fn get_page(&self, page: u32) -> Option<Arc<GlobalImage>> {
+        self.state.lock().unwrap().pages.get(page as usize).cloned()
+    }
+
+    pub fn get_page_count(&self) -> u32 {
+        self.state.lock().unwrap().pages.len() as u32
+    }
+
+    pub fn get_map_count(&self) -> usize {
+        self.state.lock().unwrap().slots.get_map_count()
+    }
+
+    /// Removes a map. Pending updates of the map are discarded. Returns true if the map existed.
+    pub fn remove_map(&self, map_id: i32) -> bool {
+        self.state.lock().unwrap().slots.remove(map_id).is_some()
+    }
+
+    /// Removes all maps which have not been updated or returned by [`MapTextureCache::get_map`]
+    /// during the last `max_idle_flushes` flushes. Returns the number of evicted maps.
+    pub fn evict_idle(&self, max_idle_flushes: u64) -> usize {
+        self.state.lock().unwrap().slots.evict_idle(max_idle_flushes).len()
+    }
+
+    /// Uploads all updates since the last flush with a single write per page. Should be called
+    /// once per frame before the maps are drawn. Returns the number of uploaded maps.
+    pub fn flush(&self) -> usize {
+        let mut updates = self.spare.lock().unwrap();
+        std::mem::swap(&mut *updates, &mut *self.pending.lock().unwrap());
+
+        let mut state = self.state.lock().unwrap();
+        state.slots.next_flush();
+
+        let mut page_regions: HashMap<u32, Vec<ImageData>> = HashMap::new();
+        let mut count = 0;
+        for (map_id, offset) in &updates.offsets {
+            // The map may have been removed since it was updated
+            let slot = match state.slots.entries.get(map_id) {
+                Some(entry) => entry.slot,
+                None => continue,
+            };
+            let data = &updates.data[*offset..(*offset + MAP_DATA_SIZE)];
+            page_regions.entry(slot.page).or_default()
+                .push(ImageData::new_extent(data, slot.get_texel_offset(), Vec2u32::new(MAP_SIZE, MAP_SIZE)));
+            count += 1;
+        }
+
+        for (page, regions) in &page_regions {
+            state.pages[*page as usize].update_regions(regions);
+        }
+        drop(page_regions);
+        drop(state);
+
+        updates.clear();
+        count
+    }
+
+    fn allocate_slot(&self, map_id: i32) {
+        let mut state = self.state.lock().unwrap();
+        let slot = state.slots.get_or_allocate(map_id);
+        while state.pages.len() <= slot.page as usize {
+            let page = self.emulator.create_global_image_array(
+                Vec2u32::new(MAP_SIZE, MAP_SIZE),
+                1,
+                ImageArrayMode::Atlas(Vec2u32::new(PAGE_GRID, PAGE_GRID)),
+                &Format::R8G8B8A8_SRGB
+            );
+            state.pages.push(page);
+        }
+    }
+}
@@ -6,10 +6,12 @@ use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, N
 use std::sync::{Arc, Mutex, Weak};
 use ash::vk;
 use crate::define_uuid_type;
+use crate::renderer::emulator::quantization::{NormalEncoding, PositionQuantization};
 
 use crate::prelude::*;
 
 define_uuid_type!(pub, ShaderId);
+define_uuid_type!(pub, VertexFormatId);
 
 pub trait ShaderDropListener {
     fn on_shader_drop(&self, id: ShaderId);
@@ -19,17 +21,23 @@ pub struct Shader {
     id: ShaderId,
     vertex_format: VertexFormat,
     used_uniforms: McUniform,
+    specialization: ShaderSpecialization,
     weak: Weak<Self>,
     listeners: Mutex<HashMap<UUID, Weak<dyn ShaderDropListener + Send + Sync>>>,
 }
 
 impl Shader {
     pub fn new(vertex_format: VertexFormat, used_uniforms: McUniform) -> Arc<Self> {
+        Self::new_specialized(vertex_format, used_uniforms, ShaderSpecialization::default())
+    }
+
+    pub fn new_specialized(vertex_format: VertexFormat, used_uniforms: McUniform, specialization: ShaderSpecialization) -> Arc<Self> {
         Arc::new_cyclic(|weak| {
             Self {
                 id: ShaderId::new(),
                 vertex_format,
                 used_uniforms,
+                specialization,
                 weak: weak.clone(),
                 listeners: Mutex::new(HashMap::new()),
             }
@@ -48,6 +56,10 @@ impl Shader {
         self.used_uniforms
     }
 
+    pub fn get_specialization(&self) -> &ShaderSpecialization {
+        &self.specialization
+    }
+
     /// Registers a drop listener to this shader. If this shader is dropped the listener will be called.
     ///
     /// The returned [`ShaderListener`] is used keep track of the liveliness of the listener. If it is
@@ -84,6 +96,149 @@ impl Drop for ShaderListener {
     }
 }
 
+/// The fog calculation applied by a shader.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum FogMode {
+    Disabled = 0,
+
+    /// The fog is based on the distance to the camera.
+    Spherical = 1,
+
+    /// The fog is based on the horizontal distance to the camera or the vertical distance if it
+    /// is larger.
+    Cylindrical = 2,
+}
+
+impl FogMode {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Disabled),
+            1 => Some(Self::Spherical),
+            2 => Some(Self::Cylindrical),
+            _ => None,
+        }
+    }
+}
+
+/// How a shader handles transparent fragments of cutout geometry like leaves or grass.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum AlphaMode {
+    /// Fragments below the alpha test threshold are discarded.
+    Test = 0,
+
+    /// The fragment alpha is converted into a coverage mask. Only has an effect if the pipeline
+    /// is multisampled, otherwise [`AlphaMode::Test`] is used instead.
+    Coverage = 1,
+}
+
+impl AlphaMode {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Test),
+            1 => Some(Self::Coverage),
+            _ => None,
+        }
+    }
+}
+
+/// Shader options which are baked into pipelines using specialization constants or fixed function
+/// state. This allows a single set of shader modules to cover all variants without any runtime
+/// branching cost.
+///
+/// The constants are defined in mc_uniforms.glsl.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ShaderSpecialization {
+    pub fog_mode: FogMode,
+
+    /// Fragments with an alpha value below this threshold are discarded. A threshold of 0
+    /// disables the alpha test.
+    pub alpha_test_threshold: f32,
+
+    /// The preferred way to handle transparent fragments.
+    pub alpha_mode: AlphaMode,
+
+    /// If set the shader should sample the lightmap.
+    pub lightmap_enable: bool,
+
+    /// The polygon mode used to rasterize triangles. Allows rendering wireframe or point debug
+    /// meshes without a separate shader.
+    pub polygon_mode: vk::PolygonMode,
+
+    /// If set a special index value restarts strip and fan primitives. Ignored for list
+    /// topologies.
+    pub primitive_restart: bool,
+
+    /// If set the material is emissive and rendered at full brightness without lightmap
+    /// darkening, for example glow squids or glowing sign text. Emissive draws should be submitted
+    /// to the builtin `emissive` render layer so bloom stages inserted at
+    /// [`InsertionPoint::AfterEmissive`](crate::renderer::emulator::render_layer::InsertionPoint::AfterEmissive)
+    /// see them.
+    pub emissive: bool,
+
+    /// The texture slot of an emissive mask. The alpha of the mask selects which parts of an
+    /// emissive material are emitting light, all other parts are lit normally. If [`None`] the
+    /// whole material is emissive. Ignored if `emissive` is not set.
+    pub emissive_image: Option<u32>,
+
+    /// If set the vertex shader applies the bob and spin animation of dropped items using the
+    /// [`McUniformData::ItemAnimation`] parameters of the draw and the game time. The shader must
+    /// use the [`McUniform::GAME_TIME`] and [`McUniform::ITEM_ANIMATION`] uniforms.
+    pub item_animation: bool,
+}
+
+impl Default for ShaderSpecialization {
+    fn default() -> Self {
+        Self {
+            fog_mode: FogMode::Disabled,
+            alpha_test_threshold: 0f32,
+            alpha_mode: AlphaMode::Test,
+            lightmap_enable: false,
+            polygon_mode: vk::PolygonMode::FILL,
+            primitive_restart: false,
+            emissive: false,
+            emissive_image: None,
+            item_animation: false,
+        }
+    }
+}
+
+impl ShaderSpecialization {
+    /// The value of the emissive image constant if no emissive mask is used.
+    pub const NO_EMISSIVE_IMAGE: u32 = u32::MAX;
+
+    /// Returns the value of the emissive image specialization constant.
+    pub fn resolve_emissive_image(&self) -> u32 {
+        match self.emissive_image {
+            Some(index) if self.emissive => index,
+            _ => Self::NO_EMISSIVE_IMAGE,
+        }
+    }
+
+    /// Returns the alpha mode to use for a pipeline with the specified sample count.
+    pub fn resolve_alpha_mode(&self, samples: vk::SampleCountFlags) -> AlphaMode {
+        if self.alpha_mode == AlphaMode::Coverage && samples == vk::SampleCountFlags::TYPE_1 {
+            AlphaMode::Test
+        } else {
+            self.alpha_mode
+        }
+    }
+
+    /// Returns true if primitive restart should be enabled for the specified topology.
+    /// Restarting list topologies requires an additional extension and is never enabled.
+    pub fn resolve_primitive_restart(&self, topology: vk::PrimitiveTopology) -> bool {
+        self.primitive_restart && match topology {
+            vk::PrimitiveTopology::LINE_STRIP |
+            vk::PrimitiveTopology::TRIANGLE_STRIP |
+            vk::PrimitiveTopology::TRIANGLE_FAN |
+            vk::PrimitiveTopology::LINE_STRIP_WITH_ADJACENCY |
+            vk::PrimitiveTopology::TRIANGLE_STRIP_WITH_ADJACENCY => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct McUniform(u64);
 
@@ -133,6 +288,7 @@ impl McUniform {
     pub const LINE_WIDTH: Self = Self::from_raw(1u64 << 12);
     pub const GAME_TIME: Self = Self::from_raw(1u64 << 13);
     pub const CHUNK_OFFSET: Self = Self::from_raw(1u64 << 14);
+    pub const ITEM_ANIMATION: Self = Self::from_raw(1u64 << 15);
 }
 
 impl BitOr for McUniform {
@@ -209,6 +365,10 @@ pub enum McUniformData {
     LineWidth(f32),
     GameTime(f32),
     ChunkOffset(Vec3f32),
+
+    /// The phase in radians and spin rate in radians per tick of an animated dropped item. See
+    /// [`ItemAnimation`](crate::renderer::emulator::item_animation::ItemAnimation).
+    ItemAnimation(Vec2f32),
 }
 
 #[repr(C)]
@@ -243,4 +403,55 @@ pub struct VertexFormat {
     pub uv0: Option<VertexFormatEntry>,
     pub uv1: Option<VertexFormatEntry>,
     pub uv2: Option<VertexFormatEntry>,
-}
\ No newline at end of file
+
+    /// If set positions are stored as normalized integers and decoded using the quantization.
+    pub position_quantization: Option<PositionQuantization>,
+
+    /// The encoding of the normal attribute.
+    pub normal_encoding: NormalEncoding,
+}
+
+impl VertexFormat {
+    /// Validates that all attributes are inside the vertex stride and that their formats can be
+    /// used as vertex attributes on the device.
+    pub fn validate(&self, device: &DeviceContext) -> Result<(), VertexFormatError> {
+        if self.stride == 0 {
+            return Err(VertexFormatError::InvalidStride);
+        }
+
+        let entries = [
+            ("position", Some(&self.position)),
+            ("normal", self.normal.as_ref()),
+            ("color", self.color.as_ref()),
+            ("uv0", self.uv0.as_ref()),
+            ("uv1", self.uv1.as_ref()),
+            ("uv2", self.uv2.as_ref()),
+        ];
+        for (name, entry) in entries {
+            if let Some(entry) = entry {
+                let size = crate::util::format::Format::try_format_for(entry.format)
+                    .and_then(|format| format.get_compatibility_class().get_texel_size())
+                    .ok_or(VertexFormatError::UnsupportedFormat(name, entry.format))?;
+                if (entry.offset as u64) + (size as u64) > (self.stride as u64) {
+                    return Err(VertexFormatError::EntryOutOfBounds(name));
+                }
+
+                let properties = unsafe {
+                    device.get_instance().vk().get_physical_device_format_properties(device.get_functions().physical_device, entry.format)
+                };
+                if !properties.buffer_features.contains(vk::FormatFeatureFlags::VERTEX_BUFFER) {
+                    return Err(VertexFormatError::UnsupportedFormat(name, entry.format));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VertexFormatError {
+    InvalidStride,
+    EntryOutOfBounds(&'static str),
+    UnsupportedFormat(&'static str, vk::Format),
+}
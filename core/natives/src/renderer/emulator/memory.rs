@@ -0,0 +1,192 @@
+//! Tracking of device memory pressure and eviction of static meshes.
+//!
+//! The [`MemoryMonitor`] compares the device local memory usage against the budget each frame and
+//! reports changes of the [`MemoryPressure`] level. Global meshes can be flagged as evictable. If
+//! memory pressure becomes critical the least recently used evictable meshes are selected for
+//! eviction. The actual memory is only freed once the host drops all references to the mesh. The
+//! host is expected to re-upload the mesh the next time it is needed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use crate::renderer::emulator::global_objects::{GlobalMesh, GlobalMeshId};
+
+use crate::prelude::*;
+
+pub use crate::allocator::MemoryBudget;
+
+/// Meshes used in any of the last `MIN_EVICTION_AGE` passes are never evicted.
+const MIN_EVICTION_AGE: u64 = 8;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[repr(u32)]
+pub enum MemoryPressure {
+    /// Memory usage is below the elevated threshold.
+    Normal = 0,
+
+    /// Memory usage is above the elevated threshold. The host should avoid creating new long lived
+    /// resources.
+    Elevated = 1,
+
+    /// Memory usage is above the critical threshold. Evictable meshes are evicted until usage
+    /// drops below the elevated threshold.
+    Critical = 2,
+}
+
+/// The fraction of the memory budget at which each [`MemoryPressure`] level is entered.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MemoryPressureThresholds {
+    pub elevated: f32,
+    pub critical: f32,
+}
+
+impl MemoryPressureThresholds {
+    fn classify(&self, budget: &MemoryBudget) -> MemoryPressure {
+        if budget.budget == 0 {
+            return MemoryPressure::Normal;
+        }
+
+        let ratio = (budget.usage as f64) / (budget.budget as f64);
+        if ratio >= self.critical as f64 {
+            MemoryPressure::Critical
+        } else if ratio >= self.elevated as f64 {
+            MemoryPressure::Elevated
+        } else {
+            MemoryPressure::Normal
+        }
+    }
+
+    fn elevated_bytes(&self, budget: u64) -> u64 {
+        ((budget as f64) * (self.elevated as f64)) as u64
+    }
+}
+
+impl Default for MemoryPressureThresholds {
+    fn default() -> Self {
+        Self {
+            elevated: 0.75,
+            critical: 0.9,
+        }
+    }
+}
+
+/// The result of a [`MemoryMonitor::update`] call.
+pub struct MemoryUpdate {
+    /// Set if the pressure level changed since the last update.
+    pub pressure_changed: Option<(MemoryPressure, MemoryBudget)>,
+
+    /// All meshes which have been selected for eviction. The host should drop these meshes and
+    /// recreate them when they are needed again.
+    pub evicted: Vec<GlobalMeshId>,
+}
+
+pub struct MemoryMonitor {
+    budget_limit: Option<u64>,
+    thresholds: MemoryPressureThresholds,
+    pressure: MemoryPressure,
+    evictable: HashMap<GlobalMeshId, Weak<GlobalMesh>>,
+}
+
+impl MemoryMonitor {
+    /// Creates a new monitor. If `budget_limit` is set the budget reported by the device is
+    /// clamped to it.
+    pub fn new(budget_limit: Option<u64>) -> Self {
+        Self {
+            budget_limit,
+            thresholds: MemoryPressureThresholds::default(),
+            pressure: MemoryPressure::Normal,
+            evictable: HashMap::new(),
+        }
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: MemoryPressureThresholds) {
+        self.thresholds = thresholds;
+    }
+
+    pub fn get_pressure(&self) -> MemoryPressure {
+        self.pressure
+    }
+
+    /// Returns the current device local memory usage and budget.
+    pub fn get_budget(&self, device: &DeviceContext) -> MemoryBudget {
+        let mut budget = device.get_allocator().get_device_local_budget();
+        if let Some(limit) = self.budget_limit {
+            budget.budget = std::cmp::min(budget.budget, limit);
+        }
+        budget
+    }
+
+    /// Flags a mesh as evictable or removes the flag. Only a weak reference to the mesh is kept.
+    pub fn set_evictable(&mut self, mesh: &Arc<GlobalMesh>, evictable: bool) {
+        if evictable {
+            self.evictable.insert(mesh.get_id(), Arc::downgrade(mesh));
+        } else {
+            self.evictable.remove(&mesh.get_id());
+        }
+    }
+
+    /// Removes all evictable meshes and resets the pressure level. Must be called after the device
+    /// has been recreated.
+    pub fn reset(&mut self) {
+        self.evictable.clear();
+        self.pressure = MemoryPressure::Normal;
+    }
+
+    /// Updates the pressure level. If `evict` is true and the pressure is critical evictable meshes
+    /// are selected for eviction in least recently used order.
+    pub fn update(&mut self, device: &DeviceContext, evict: bool) -> MemoryUpdate {
+        let budget = self.get_budget(device);
+        let pressure = self.thresholds.classify(&budget);
+
+        let pressure_changed = if pressure != self.pressure {
+            log::info!("Memory pressure changed from {:?} to {:?} ({:?})", self.pressure, pressure, budget);
+            self.pressure = pressure;
+            Some((pressure, budget))
+        } else {
+            None
+        };
+
+        let evicted = if evict && pressure == MemoryPressure::Critical {
+            self.evict(&budget)
+        } else {
+            Vec::new()
+        };
+
+        MemoryUpdate {
+            pressure_changed,
+            evicted,
+        }
+    }
+
+    fn evict(&mut self, budget: &MemoryBudget) -> Vec<GlobalMeshId> {
+        // Meshes dropped by the host are no longer tracked
+        self.evictable.retain(|_, weak| weak.strong_count() > 0);
+
+        let mut meshes: Vec<_> = self.evictable.values().filter_map(Weak::upgrade).collect();
+        meshes.sort_by_key(|mesh| mesh.get_last_used_pass());
+
+        let newest = meshes.last().map_or(0, |mesh| mesh.get_last_used_pass());
+        let target = self.thresholds.elevated_bytes(budget.budget);
+
+        let mut usage = budget.usage;
+        let mut evicted = Vec::new();
+        for mesh in &meshes {
+            if usage <= target || mesh.get_last_used_pass() + MIN_EVICTION_AGE > newest {
+                break;
+            }
+            usage = usage.saturating_sub(mesh.get_size());
+            evicted.push(mesh.get_id());
+        }
+
+        if !evicted.is_empty() {
+            log::info!("Evicting {} meshes to reduce memory usage", evicted.len());
+        }
+
+        // Evicted meshes are removed so that they are reported only once
+        for id in &evicted {
+            self.evictable.remove(id);
+        }
+
+        evicted
+    }
+}
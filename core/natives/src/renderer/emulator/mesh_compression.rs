@@ -0,0 +1,92 @@
+//! Decompression of mesh data passed in compressed form by the host.
+//!
+//! Large chunk batches are mostly vertex data which has to be copied across the JNI boundary and
+//! kept alive on the java side until the upload call returns. Hosts can instead compress the vertex
+//! and index data of a mesh using the LZ4 block format (without a size prefix) and pass the
+//! uncompressed sizes alongside. Batches are decompressed in parallel using the [`JobSystem`]
+//! before the data is copied into staging memory.
+
+use crate::renderer::emulator::jobs::{JobKind, JobSystem};
+
+/// The compression used for the vertex and index data of a mesh.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum MeshCompression {
+    None = 0,
+    /// The LZ4 block format without a size prefix.
+    Lz4 = 1,
+}
+
+impl MeshCompression {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::None),
+            1 => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+}
+
+/// The minimum number of meshes decompressed by a single task.
+const MIN_PARALLEL_BATCH: usize = 4;
+
+/// The compressed data of a mesh.
+#[derive(Copy, Clone, Debug)]
+pub struct CompressedMesh<'a> {
+    pub vertex_data: &'a [u8],
+    pub index_data: &'a [u8],
+    /// The size of the vertex data after decompression.
+    pub vertex_data_len: usize,
+    /// The size of the index data after decompression.
+    pub index_data_len: usize,
+    pub compression: MeshCompression,
+}
+
+pub struct DecompressedMesh {
+    pub vertex_data: Vec<u8>,
+    pub index_data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MeshDecompressError {
+    /// The data is not valid for the compression format.
+    Corrupt(&'static str),
+    /// The data did not decompress to the expected size.
+    SizeMismatch(&'static str),
+}
+
+pub fn decompress_mesh(mesh: &CompressedMesh) -> Result<DecompressedMesh, MeshDecompressError> {
+    Ok(DecompressedMesh {
+        vertex_data: decompress(mesh.vertex_data, mesh.vertex_data_len, mesh.compression, "vertex_data")?,
+        index_data: decompress(mesh.index_data, mesh.index_data_len, mesh.compression, "index_data")?,
+    })
+}
+
+/// Decompresses a batch of meshes. The batch is split over the threads of the job system if it is
+/// large enough. The results are in the same order as `meshes`.
+pub fn decompress_meshes(jobs: &JobSystem, meshes: &[CompressedMesh]) -> Vec<Result<DecompressedMesh, MeshDecompressError>> {
+    jobs.map(JobKind::MeshDecompression, meshes, MIN_PARALLEL_BATCH, decompress_mesh)
+}
+
+fn decompress(data: &[u8], len: usize, compression: MeshCompression, name: &'static str) -> Result<Vec<u8>, MeshDecompressError> {
+    match compression {
+        MeshCompression::None => {
+            if data.len() != len {
+                return Err(MeshDecompressError::SizeMismatch(name));
+            }
+            Ok(data.to_vec())
+        }
+        MeshCompression::Lz4 => {
+            // Meshes without index data pass an empty buffer which is not a valid lz4 block
+            if data.is_empty() && len == 0 {
+                return Ok(Vec::new());
+            }
+            let mut result = vec![0u8; len];
+            let written = lz4_flex::block::decompress_into(data, &mut result).map_err(|_| MeshDecompressError::Corrupt(name))?;
+            if written != len {
+                return Err(MeshDecompressError::SizeMismatch(name));
+            }
+            Ok(result)
+        }
+    }
+}
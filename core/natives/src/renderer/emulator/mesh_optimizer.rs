@@ -0,0 +1,353 @@
+//! Optional optimization of static mesh data before upload.
+//!
+//! The optimizer reorders triangles to improve post transform vertex cache utilization, groups
+//! them into clusters which are sorted to reduce overdraw and finally reorders the vertex data in
+//! the order it is first referenced to improve vertex fetch locality. The set of rendered triangles
+//! and their winding is not changed.
+//!
+//! Optimization is a purely cpu side operation and can take a few milliseconds for large meshes.
+//! It should be run on a worker thread, for example the thread which generated the mesh.
+
+use ash::vk;
+
+use crate::renderer::emulator::MeshData;
+use crate::renderer::emulator::mc_shaders::VertexFormatEntry;
+
+use crate::prelude::*;
+
+/// Mesh data produced by [`optimize_mesh`]. Uses the same vertex stride, index type and primitive
+/// topology as the source mesh.
+pub struct OptimizedMesh {
+    pub vertex_data: Vec<u8>,
+    pub index_data: Vec<u8>,
+    pub vertex_stride: u32,
+    pub index_count: u32,
+    pub index_type: vk::IndexType,
+    pub primitive_topology: vk::PrimitiveTopology,
+}
+
+impl OptimizedMesh {
+    pub fn as_mesh_data(&self) -> MeshData {
+        MeshData {
+            vertex_data: &self.vertex_data,
+            index_data: &self.index_data,
+            vertex_stride: self.vertex_stride,
+            index_count: self.index_count,
+            index_type: self.index_type,
+            primitive_topology: self.primitive_topology,
+        }
+    }
+}
+
+/// Optimizes a mesh for rendering.
+///
+/// If `position` is provided it is used to sort triangle clusters to reduce overdraw. Supported
+/// position formats are `R32G32B32_SFLOAT`, `R32G32B32A32_SFLOAT` and `R16G16B16A16_SNORM`. Other
+/// formats skip the overdraw optimization.
+///
+/// Returns [`None`] if the mesh is not a triangle list or references vertices outside of the
+/// vertex data. In that case the source mesh should be uploaded unmodified.
+pub fn optimize_mesh(data: &MeshData, position: Option<&VertexFormatEntry>) -> Option<OptimizedMesh> {
+    if data.primitive_topology != vk::PrimitiveTopology::TRIANGLE_LIST || data.vertex_stride == 0 {
+        return None;
+    }
+
+    let vertex_count = data.vertex_data.len() / (data.vertex_stride as usize);
+    let mut indices = read_indices(data)?;
+    if indices.len() % 3 != 0 || indices.iter().any(|index| (*index as usize) >= vertex_count) {
+        return None;
+    }
+
+    optimize_vertex_cache(&mut indices, vertex_count);
+
+    if let Some(positions) = position.and_then(|position| read_positions(data, position, vertex_count)) {
+        optimize_overdraw(&mut indices, &positions);
+    }
+
+    let vertex_data = optimize_vertex_fetch(&mut indices, data.vertex_data, data.vertex_stride as usize);
+
+    Some(OptimizedMesh {
+        vertex_data,
+        index_data: write_indices(&indices, data.index_type),
+        vertex_stride: data.vertex_stride,
+        index_count: indices.len() as u32,
+        index_type: data.index_type,
+        primitive_topology: data.primitive_topology,
+    })
+}
+
+pub(super) fn read_indices(data: &MeshData) -> Option<Vec<u32>> {
+    let count = data.index_count as usize;
+    let bytes = data.index_data;
+    let indices: Vec<u32> = match data.index_type {
+        vk::IndexType::UINT8_EXT => bytes.get(0..count)?.iter().map(|v| *v as u32).collect(),
+        vk::IndexType::UINT16 => bytes.get(0..(count * 2))?.chunks_exact(2).map(|v| u16::from_ne_bytes([v[0], v[1]]) as u32).collect(),
+        vk::IndexType::UINT32 => bytes.get(0..(count * 4))?.chunks_exact(4).map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]])).collect(),
+        _ => return None,
+    };
+    Some(indices)
+}
+
+fn write_indices(indices: &[u32], index_type: vk::IndexType) -> Vec<u8> {
+    match index_type {
+        vk::IndexType::UINT8_EXT => indices.iter().map(|v| *v as u8).collect(),
+        vk::IndexType::UINT16 => indices.iter().flat_map(|v| (*v as u16).to_ne_bytes()).collect(),
+        _ => indices.iter().flat_map(|v| v.to_ne_bytes()).collect(),
+    }
+}
+
+fn read_positions(data: &MeshData, position: &VertexFormatEntry, vertex_count: usize) -> Option<Vec<Vec3f32>> {
+    let stride = data.vertex_stride as usize;
+    let offset = position.offset as usize;
+
+    let read: fn(&[u8]) -> Vec3f32 = match position.format {
+        vk::Format::R32G32B32_SFLOAT | vk::Format::R32G32B32A32_SFLOAT => |v| {
+            let c = |i: usize| f32::from_ne_bytes([v[i], v[i + 1], v[i + 2], v[i + 3]]);
+            Vec3f32::new(c(0), c(4), c(8))
+        },
+        vk::Format::R16G16B16A16_SNORM => |v| {
+            let c = |i: usize| (i16::from_ne_bytes([v[i], v[i + 1]]) as f32) / (i16::MAX as f32);
+            Vec3f32::new(c(0), c(2), c(4))
+        },
+        _ => return None,
+    };
+    let size = match position.format {
+        vk::Format::R16G16B16A16_SNORM => 6,
+        _ => 12,
+    };
+
+    (0..vertex_count).map(|vertex| {
+        let base = (vertex * stride) + offset;
+        data.vertex_data.get(base..(base + size)).map(read)
+    }).collect()
+}
+
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+fn vertex_score(cache_position: i32, remaining_triangles: u32) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let mut score = 0.0;
+    if cache_position >= 0 {
+        let position = cache_position as usize;
+        if position < 3 {
+            score = LAST_TRIANGLE_SCORE;
+        } else {
+            let scaler = 1.0 / ((CACHE_SIZE - 3) as f32);
+            score = (1.0 - ((position - 3) as f32 * scaler)).powf(CACHE_DECAY_POWER);
+        }
+    }
+
+    score + (VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER))
+}
+
+/// Reorders triangles to improve vertex cache utilization using Tom Forsyth's linear speed vertex
+/// cache optimization algorithm.
+fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    // Build vertex to triangle adjacency
+    let mut remaining = vec![0u32; vertex_count];
+    for index in indices.iter() {
+        remaining[*index as usize] += 1;
+    }
+    let mut adjacency_offsets = vec![0usize; vertex_count + 1];
+    for vertex in 0..vertex_count {
+        adjacency_offsets[vertex + 1] = adjacency_offsets[vertex] + (remaining[vertex] as usize);
+    }
+    let mut adjacency = vec![0u32; indices.len()];
+    let mut fill = adjacency_offsets.clone();
+    for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+        for vertex in vertices {
+            adjacency[fill[*vertex as usize]] = triangle as u32;
+            fill[*vertex as usize] += 1;
+        }
+    }
+
+    let mut cache_position = vec![-1i32; vertex_count];
+    let mut scores: Vec<f32> = (0..vertex_count).map(|vertex| vertex_score(-1, remaining[vertex])).collect();
+    let mut triangle_scores: Vec<f32> = indices.chunks_exact(3).map(|v| v.iter().map(|i| scores[*i as usize]).sum()).collect();
+    let mut emitted = vec![false; triangle_count];
+
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut result = Vec::with_capacity(indices.len());
+    let mut scan_cursor = 0usize;
+    let mut best: Option<usize> = None;
+
+    for _ in 0..triangle_count {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[scan_cursor] {
+                    scan_cursor += 1;
+                }
+                scan_cursor
+            }
+        };
+
+        emitted[triangle] = true;
+        let vertices = [indices[triangle * 3], indices[triangle * 3 + 1], indices[triangle * 3 + 2]];
+        result.extend_from_slice(&vertices);
+
+        // Remove the triangle from the adjacency of its vertices
+        for vertex in vertices {
+            let vertex = vertex as usize;
+            let start = adjacency_offsets[vertex];
+            let count = remaining[vertex] as usize;
+            let list = &mut adjacency[start..(start + count)];
+            if let Some(position) = list.iter().position(|t| *t as usize == triangle) {
+                list.swap(position, count - 1);
+            }
+            remaining[vertex] -= 1;
+        }
+
+        // Update the simulated cache
+        let mut new_cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+        for vertex in vertices {
+            if !new_cache.contains(&vertex) {
+                new_cache.push(vertex);
+            }
+        }
+        for vertex in &cache {
+            if !vertices.contains(vertex) {
+                new_cache.push(*vertex);
+            }
+        }
+        for vertex in new_cache.iter().skip(CACHE_SIZE) {
+            cache_position[*vertex as usize] = -1;
+        }
+        let evicted: Vec<u32> = new_cache.iter().skip(CACHE_SIZE).copied().collect();
+        new_cache.truncate(CACHE_SIZE);
+        for (position, vertex) in new_cache.iter().enumerate() {
+            cache_position[*vertex as usize] = position as i32;
+        }
+        cache = new_cache;
+
+        // Update scores of all affected vertices and their triangles
+        best = None;
+        let mut best_score = f32::MIN;
+        for vertex in cache.iter().chain(evicted.iter()) {
+            let vertex = *vertex as usize;
+            let new_score = vertex_score(cache_position[vertex], remaining[vertex]);
+            let delta = new_score - scores[vertex];
+            scores[vertex] = new_score;
+
+            let start = adjacency_offsets[vertex];
+            for adjacent in &adjacency[start..(start + (remaining[vertex] as usize))] {
+                let adjacent = *adjacent as usize;
+                triangle_scores[adjacent] += delta;
+            }
+        }
+        for vertex in &cache {
+            let vertex = *vertex as usize;
+            let start = adjacency_offsets[vertex];
+            for adjacent in &adjacency[start..(start + (remaining[vertex] as usize))] {
+                let adjacent = *adjacent as usize;
+                if triangle_scores[adjacent] > best_score {
+                    best_score = triangle_scores[adjacent];
+                    best = Some(adjacent);
+                }
+            }
+        }
+    }
+
+    indices.copy_from_slice(&result);
+}
+
+/// The minimum number of triangles in a cluster used for overdraw optimization.
+const MIN_CLUSTER_SIZE: usize = 16;
+
+/// Splits the triangles into clusters and sorts them so that clusters facing away from the center
+/// of the mesh are drawn first. This approximates a front to back order from most view directions.
+///
+/// Cluster boundaries are placed where the simulated vertex cache misses all vertices of a
+/// triangle, so the vertex cache efficiency is mostly preserved.
+fn optimize_overdraw(indices: &mut [u32], positions: &[Vec3f32]) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count <= MIN_CLUSTER_SIZE {
+        return;
+    }
+
+    let mut cluster_starts = vec![0usize];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE);
+    for (triangle, vertices) in indices.chunks_exact(3).enumerate() {
+        let misses = vertices.iter().filter(|v| !cache.contains(v)).count();
+        if misses == 3 && triangle - cluster_starts.last().unwrap() >= MIN_CLUSTER_SIZE {
+            cluster_starts.push(triangle);
+        }
+        for vertex in vertices {
+            if let Some(position) = cache.iter().position(|v| v == vertex) {
+                cache.remove(position);
+            }
+            cache.insert(0, *vertex);
+        }
+        cache.truncate(CACHE_SIZE);
+    }
+    if cluster_starts.len() < 2 {
+        return;
+    }
+
+    let mesh_center = indices.iter().map(|index| positions[*index as usize]).sum::<Vec3f32>() / (indices.len() as f32);
+
+    let mut clusters: Vec<(f32, usize, usize)> = cluster_starts.iter().enumerate().map(|(cluster, start)| {
+        let end = cluster_starts.get(cluster + 1).copied().unwrap_or(triangle_count);
+
+        let mut center = Vec3f32::zeros();
+        let mut normal = Vec3f32::zeros();
+        for triangle in *start..end {
+            let a = positions[indices[triangle * 3] as usize];
+            let b = positions[indices[triangle * 3 + 1] as usize];
+            let c = positions[indices[triangle * 3 + 2] as usize];
+            center += (a + b + c) / 3.0;
+            normal += (b - a).cross(&(c - a));
+        }
+        center /= (end - start) as f32;
+
+        let length = normal.norm();
+        let sort_key = if length > 0.0 {
+            (center - mesh_center).dot(&(normal / length))
+        } else {
+            0.0
+        };
+
+        (sort_key, *start, end)
+    }).collect();
+
+    clusters.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut result = Vec::with_capacity(indices.len());
+    for (_, start, end) in clusters {
+        result.extend_from_slice(&indices[(start * 3)..(end * 3)]);
+    }
+    indices.copy_from_slice(&result);
+}
+
+/// Reorders the vertex data in the order vertices are first referenced and remaps the indices.
+/// Unreferenced vertices are removed.
+fn optimize_vertex_fetch(indices: &mut [u32], vertex_data: &[u8], stride: usize) -> Vec<u8> {
+    let vertex_count = vertex_data.len() / stride;
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut result = Vec::with_capacity(vertex_data.len());
+    let mut next = 0u32;
+
+    for index in indices.iter_mut() {
+        let old = *index as usize;
+        if remap[old] == u32::MAX {
+            remap[old] = next;
+            next += 1;
+            result.extend_from_slice(&vertex_data[(old * stride)..((old + 1) * stride)]);
+        }
+        *index = remap[old];
+    }
+
+    result
+}
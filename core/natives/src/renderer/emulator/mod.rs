@@ -12,6 +12,7 @@
 //! output of each externally to form a frame. Or use passes asynchronously to the main render loop.
 //! However currently b4d uses a single pass to render a single frame.
 
+mod barriers;
 mod immediate;
 mod worker;
 mod global_objects;
@@ -20,29 +21,74 @@ mod pass;
 pub mod pipeline;
 pub mod debug_pipeline;
 pub mod mc_shaders;
+pub mod memory;
+pub mod quantization;
+pub mod mesh_optimizer;
+pub mod draw_merger;
+pub mod shadow;
+pub mod probe;
+pub mod color_grading;
+pub mod post_chain;
+pub mod panorama;
+pub mod command_log;
+pub mod command_stream;
+pub mod watchdog;
+pub mod sparse;
+pub mod render_layer;
+pub mod frame_times;
+pub mod event_log;
+pub mod gui_item;
+pub mod gui_rect;
+pub mod debug_overlay;
+pub mod beam;
+pub mod map_texture;
+pub mod blob_shadow;
+pub mod unproject;
+pub mod draw_tag;
+pub mod render_budget;
+pub mod warm_state;
+pub mod mesh_compression;
+pub mod draw_ring;
+pub mod jobs;
+pub mod compositor;
+pub mod item_animation;
+pub mod camera_effects;
+pub mod auto_exposure;
 mod descriptors;
 mod share;
 mod staging;
+mod upload;
 
 use std::fmt::{Debug, Formatter};
 use std::panic::RefUnwindSafe;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use ash::vk;
 use bytemuck::cast_slice;
 
-use crate::renderer::emulator::worker::run_worker;
+use crate::renderer::emulator::worker::{run_worker, GlobalImageComposite, WorkerTask};
+use crate::renderer::emulator::camera_effects::CameraEffects;
+use crate::renderer::emulator::compositor::CompositeLayer;
+use crate::renderer::emulator::jobs::{JobKind, JobSystem};
 use crate::renderer::emulator::pipeline::EmulatorPipeline;
+use crate::renderer::emulator::watchdog::{HangCallback, HangReport, Watchdog, WatchdogConfig};
 
 use crate::prelude::*;
 
-pub use global_objects::{GlobalMesh, GlobalImage, ImageData, SamplerInfo};
+pub use global_objects::{GlobalMesh, GlobalMeshId, GlobalImage, GlobalImageId, ImageArrayMode, ImageData, ImageUsageStats, SamplerInfo};
+pub use sparse::SparseResidencyStats;
 
 pub use pass::PassId;
+pub use pass::FrameSize;
 pub use pass::PassRecorder;
 pub use pass::ImmediateMeshId;
+pub use pass::SubmitMode;
+pub use pass::{MAX_PORTAL_DEPTH, MAX_TEXTURE_SLOTS, MAX_VIEWPORTS};
 
 use share::Share;
-use crate::renderer::emulator::mc_shaders::{McUniform, Shader, ShaderId, VertexFormat};
+use crate::renderer::emulator::mc_shaders::{McUniform, Shader, ShaderId, ShaderSpecialization, VertexFormat, VertexFormatEntry, VertexFormatError, VertexFormatId};
+use crate::renderer::emulator::render_budget::RenderBudget;
+use crate::renderer::emulator::warm_state::{LayerSnapshot, MeshRetention, OrderSnapshot, RendererSnapshot};
+use crate::renderer::emulator::render_layer::{InsertionPoint, RenderLayer, RenderLayerError, RenderLayerId, RenderLayerState, RenderOrderEntry};
 use crate::util::format::Format;
 
 pub struct EmulatorRenderer {
@@ -53,8 +99,26 @@ pub struct EmulatorRenderer {
 }
 
 impl EmulatorRenderer {
-    pub(crate) fn new(device: Arc<DeviceContext>) -> Self {
-        let share = Arc::new(Share::new(device.clone()));
+    /// The number of frames whose host side state may exist simultaneously if not specified.
+    pub const DEFAULT_FRAMES_IN_FLIGHT: u32 = 3;
+
+    pub fn new(device: Arc<DeviceContext>) -> Self {
+        Self::with_frames_in_flight(device, Self::DEFAULT_FRAMES_IN_FLIGHT)
+    }
+
+    /// Creates a new renderer which keeps per frame state (immediate buffers and uniform regions)
+    /// for up to `frames_in_flight` passes. Starting a pass only blocks once this many passes
+    /// have been started but not yet completed by the gpu.
+    pub fn with_frames_in_flight(device: Arc<DeviceContext>, frames_in_flight: u32) -> Self {
+        Self::with_job_system(device, frames_in_flight, Arc::new(JobSystem::new(JobSystem::AUTOMATIC_THREAD_COUNT)))
+    }
+
+    /// Like [`EmulatorRenderer::with_frames_in_flight`] but uses the provided job system for cpu
+    /// side work like culling and mesh optimization. The job system may be shared with other
+    /// renderers.
+    pub fn with_job_system(device: Arc<DeviceContext>, frames_in_flight: u32, jobs: Arc<JobSystem>) -> Self {
+        let frames_in_flight = std::cmp::max(frames_in_flight, 1);
+        let share = Arc::new(Share::new(device.clone(), frames_in_flight, jobs));
 
         let share2 = share.clone();
         let worker = std::thread::spawn(move || {
@@ -88,32 +152,404 @@ impl EmulatorRenderer {
         self.share.get_device()
     }
 
+    pub fn get_frames_in_flight(&self) -> u32 {
+        self.share.get_frames_in_flight()
+    }
+
+    pub fn get_job_system(&self) -> &Arc<JobSystem> {
+        self.share.get_job_system()
+    }
+
+    /// Changes the number of passes for which per frame state is kept. The immediate buffers and
+    /// uniform regions of all frame slots are rebuilt and cached worker objects (command buffers,
+    /// fences and timestamp query pools) are trimmed to the new count.
+    ///
+    /// Blocks until all passes currently in flight have completed. Must not be called while the
+    /// calling thread is recording a pass since that pass would never complete. Pipelines keeping
+    /// their own per pass objects sized from [`EmulatorRenderer::get_frames_in_flight`] must be
+    /// recreated afterwards.
+    pub fn set_frames_in_flight(&self, frames_in_flight: u32) {
+        let frames_in_flight = std::cmp::max(frames_in_flight, 1);
+        if self.share.get_frames_in_flight() != frames_in_flight {
+            self.share.set_frames_in_flight(frames_in_flight);
+        }
+    }
+
     pub fn create_global_mesh(&self, data: &MeshData) -> Arc<GlobalMesh> {
         GlobalMesh::new(self.share.clone(), data).unwrap()
     }
 
+    /// Creates multiple global meshes. This is more efficient than calling
+    /// [`EmulatorRenderer::create_global_mesh`] for each mesh since internal locks and the worker
+    /// channel are only acquired once.
+    pub fn create_global_meshes(&self, datas: &[MeshData]) -> Vec<Arc<GlobalMesh>> {
+        GlobalMesh::new_batch(self.share.clone(), datas).unwrap()
+    }
+
+    /// Creates a global mesh or returns an existing one if identical mesh data has previously been
+    /// passed to this function. The mesh is identified by a hash of its content so the returned
+    /// mesh may be shared with other callers. Dropping a reference only releases the mesh once all
+    /// references are dropped.
+    ///
+    /// Meshes created using [`EmulatorRenderer::create_global_mesh`] are never deduplicated.
+    pub fn create_global_mesh_deduplicated(&self, data: &MeshData) -> Arc<GlobalMesh> {
+        GlobalMesh::new_deduplicated(self.share.clone(), data).unwrap()
+    }
+
+    /// Optimizes the mesh data using [`mesh_optimizer::optimize_mesh`] before creating the global
+    /// mesh. If the mesh cannot be optimized it is created unmodified.
+    ///
+    /// The optimization runs on the calling thread. It is recommended to call this function from a
+    /// worker thread.
+    pub fn create_global_mesh_optimized(&self, data: &MeshData, position: Option<&VertexFormatEntry>) -> Arc<GlobalMesh> {
+        match mesh_optimizer::optimize_mesh(data, position) {
+            Some(optimized) => self.create_global_mesh(&optimized.as_mesh_data()),
+            None => self.create_global_mesh(data),
+        }
+    }
+
+    /// Optimizes a batch of meshes in parallel using the job system and creates them using
+    /// [`EmulatorRenderer::create_global_meshes`]. `positions` contains the position entry of the
+    /// vertex format of each mesh. Meshes which cannot be optimized are created unmodified.
+    ///
+    /// The calling thread participates in the optimization and blocks until all meshes are
+    /// optimized.
+    pub fn create_global_meshes_optimized(&self, datas: &[MeshData], positions: &[Option<VertexFormatEntry>]) -> Vec<Arc<GlobalMesh>> {
+        if datas.len() != positions.len() {
+            log::error!("Mesh count {} does not match position count {}", datas.len(), positions.len());
+            panic!()
+        }
+
+        let inputs: Vec<_> = datas.iter().zip(positions).collect();
+        let optimized = self.get_job_system().map(JobKind::MeshOptimization, &inputs, 1, |(data, position)| {
+            mesh_optimizer::optimize_mesh(data, position.as_ref())
+        });
+
+        let mesh_datas: Vec<_> = datas.iter().zip(&optimized).map(|(data, optimized)| match optimized {
+            Some(optimized) => optimized.as_mesh_data(),
+            None => MeshData {
+                vertex_data: data.vertex_data,
+                index_data: data.index_data,
+                vertex_stride: data.vertex_stride,
+                index_count: data.index_count,
+                index_type: data.index_type,
+                primitive_topology: data.primitive_topology,
+            },
+        }).collect();
+        self.create_global_meshes(&mesh_datas)
+    }
+
     pub fn create_global_image(&self, size: Vec2u32, format: &'static Format) -> Arc<GlobalImage> {
-        GlobalImage::new(self.share.clone(), size, 1, format).unwrap()
+        GlobalImage::new(self.share.clone(), size, 1, ImageArrayMode::Single, format).unwrap()
     }
 
     pub fn create_global_image_mips(&self, size: Vec2u32, mip_levels: u32, format: &'static Format) -> Arc<GlobalImage> {
-        GlobalImage::new(self.share.clone(), size, mip_levels, format).unwrap()
+        GlobalImage::new(self.share.clone(), size, mip_levels, ImageArrayMode::Single, format).unwrap()
+    }
+
+    /// Creates a 2d array image. `size` is the size of a single layer. See [`ImageArrayMode`] for
+    /// how the layers are addressed.
+    pub fn create_global_image_array(&self, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) -> Arc<GlobalImage> {
+        GlobalImage::new(self.share.clone(), size, mip_levels, array_mode, format).unwrap()
+    }
+
+    /// Creates a partially resident 2d array image. Only the pages which are written or touched
+    /// using [`GlobalImage::touch_regions`] have memory bound to them. This is intended for very
+    /// large atlases of which only a small part is used at any time.
+    ///
+    /// Returns [`None`] if the device does not support sparse images of the format.
+    pub fn create_global_image_sparse(&self, size: Vec2u32, mip_levels: u32, array_mode: ImageArrayMode, format: &'static Format) -> Option<Arc<GlobalImage>> {
+        match GlobalImage::new_sparse(self.share.clone(), size, mip_levels, array_mode, format) {
+            Ok(image) => Some(image),
+            Err(global_objects::GlobalObjectCreateError::SparseNotSupported) => None,
+            Err(err) => {
+                log::error!("Failed to create sparse global image {:?}", err);
+                panic!()
+            }
+        }
+    }
+
+    /// Creates a new image containing the layers composited on the gpu. The first layer is the
+    /// base layer replacing the content of the image, all other layers are alpha blended on top of
+    /// it. See [`compositor`].
+    ///
+    /// The composite is rendered by the worker before the current pass if possible or otherwise
+    /// before the next pass. Returns [`None`] if the device cannot render to images of the format.
+    pub fn create_composite_image(&self, size: Vec2u32, format: &'static Format, layers: &[CompositeLayer]) -> Option<Arc<GlobalImage>> {
+        if layers.is_empty() {
+            log::error!("Composite images require at least a base layer");
+            panic!();
+        }
+        if let Some(layer) = layers.iter().find(|layer| layer.image.get_array_mode() != ImageArrayMode::Single) {
+            log::error!("Composite layers must use single images but got {:?}", layer.image.get_array_mode());
+            panic!();
+        }
+
+        let image = match GlobalImage::new_render_target(self.share.clone(), size, format) {
+            Ok(image) => image,
+            Err(global_objects::GlobalObjectCreateError::RenderTargetNotSupported) => return None,
+            Err(err) => {
+                log::error!("Failed to create composite image {:?}", err);
+                panic!()
+            }
+        };
+
+        self.share.push_task(WorkerTask::CompositeGlobalImage(GlobalImageComposite {
+            dst_image: image.clone(),
+            layers: layers.into(),
+        }));
+
+        Some(image)
+    }
+
+    /// Limits the number of bytes uploaded to global objects per pass. Pending uploads are
+    /// processed in priority order. If [`None`] is passed all uploads are processed immediately.
+    pub fn set_upload_budget(&self, budget: Option<u64>) {
+        self.share.set_upload_budget(budget.unwrap_or(0));
+    }
+
+    pub fn get_upload_budget(&self) -> Option<u64> {
+        match self.share.get_upload_budget() {
+            0 => None,
+            budget => Some(budget),
+        }
+    }
+
+    /// Marks all global images as stale. Images stay valid and can be used by frames until they
+    /// are rewritten in place using [`GlobalImage::update_regions`] which marks them current
+    /// again. This allows resource packs to be switched without frames sampling missing textures.
+    /// Images whose size or format changed must be recreated. Returns the new generation.
+    ///
+    /// Use [`GlobalImage::is_stale`] to find images which have not been replaced after a reload.
+    pub fn bump_resource_generation(&self) -> u64 {
+        self.share.bump_resource_generation()
+    }
+
+    pub fn get_resource_generation(&self) -> u64 {
+        self.share.get_resource_generation()
+    }
+
+    /// Sets the limits applied to all passes started after this call. See
+    /// [`render_budget`](crate::renderer::emulator::render_budget).
+    pub fn set_render_budget(&self, budget: RenderBudget) {
+        self.share.set_render_budget(budget);
+    }
+
+    pub fn get_render_budget(&self) -> RenderBudget {
+        self.share.get_render_budget()
+    }
+
+    /// Sets the [`SubmitMode`] of all passes started after this call.
+    pub fn set_submit_mode(&self, mode: SubmitMode) {
+        self.share.set_submit_mode(mode);
+    }
+
+    pub fn get_submit_mode(&self) -> SubmitMode {
+        self.share.get_submit_mode()
+    }
+
+    /// Sets the camera effects applied by all passes started after this call which have camera
+    /// effects enabled. See [`camera_effects`].
+    pub fn set_camera_effects(&self, effects: CameraEffects) {
+        self.share.set_camera_effects(effects);
+    }
+
+    pub fn get_camera_effects(&self) -> CameraEffects {
+        self.share.get_camera_effects()
     }
 
     pub fn create_shader(&self, vertex_format: &VertexFormat, used_uniforms: McUniform) -> ShaderId {
-        self.share.create_shader(vertex_format, used_uniforms)
+        self.share.create_shader(vertex_format, used_uniforms, ShaderSpecialization::default())
+    }
+
+    /// Creates a shader variant. Shaders with different specializations share the same shader
+    /// modules but use different pipelines.
+    pub fn create_shader_specialized(&self, vertex_format: &VertexFormat, used_uniforms: McUniform, specialization: ShaderSpecialization) -> ShaderId {
+        self.share.create_shader(vertex_format, used_uniforms, specialization)
+    }
+
+    /// Creates a shader using a vertex format previously registered with
+    /// [`EmulatorRenderer::register_vertex_format`]. Returns [`None`] if the format is not
+    /// registered.
+    pub fn create_shader_with_format(&self, format: VertexFormatId, used_uniforms: McUniform, specialization: ShaderSpecialization) -> Option<ShaderId> {
+        let vertex_format = self.share.get_vertex_format(format)?;
+        Some(self.share.create_shader(&vertex_format, used_uniforms, specialization))
     }
 
     pub fn drop_shader(&self, id: ShaderId) {
         self.share.drop_shader(id)
     }
 
+    /// Validates a vertex format against the device capabilities and registers it. The returned
+    /// id stays valid until the format is unregistered and is never reused.
+    pub fn register_vertex_format(&self, vertex_format: &VertexFormat) -> Result<VertexFormatId, VertexFormatError> {
+        self.share.register_vertex_format(vertex_format)
+    }
+
+    /// Unregisters a vertex format. Shaders already created with the format are not affected.
+    pub fn unregister_vertex_format(&self, id: VertexFormatId) {
+        self.share.unregister_vertex_format(id)
+    }
+
+    pub fn get_vertex_format(&self, id: VertexFormatId) -> Option<VertexFormat> {
+        self.share.get_vertex_format(id)
+    }
+
     pub fn get_shader(&self, id: ShaderId) -> Option<Arc<Shader>> {
         self.share.get_shader(id)
     }
 
+    /// Registers a render layer. Layer names must be unique. The vanilla layers listed in
+    /// [`render_layer::BUILTIN_LAYERS`] are registered when the renderer is created.
+    pub fn register_render_layer(&self, name: &str, state: &RenderLayerState) -> Result<RenderLayerId, RenderLayerError> {
+        self.share.register_render_layer(name, state)
+    }
+
+    /// Unregisters a render layer. Passes which already selected the layer are not affected.
+    pub fn unregister_render_layer(&self, id: RenderLayerId) {
+        self.share.unregister_render_layer(id)
+    }
+
+    pub fn get_render_layer(&self, id: RenderLayerId) -> Option<Arc<RenderLayer>> {
+        self.share.get_render_layer(id)
+    }
+
+    /// Returns the id of the render layer registered with `name`.
+    pub fn find_render_layer(&self, name: &str) -> Option<RenderLayerId> {
+        self.share.find_render_layer(name)
+    }
+
+    /// Enables or disables a render layer. See [`RenderLayer::set_enabled`]. Returns false if the
+    /// layer is not registered.
+    pub fn set_render_layer_enabled(&self, id: RenderLayerId, enabled: bool) -> bool {
+        match self.share.get_render_layer(id) {
+            Some(layer) => {
+                layer.set_enabled(enabled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disables all render layers except `id`. Passing [`None`] enables all layers again. Draws
+    /// without a layer are not affected.
+    pub fn isolate_render_layer(&self, id: Option<RenderLayerId>) {
+        self.share.isolate_render_layer(id)
+    }
+
+    /// Returns the order in which render layers are emitted in a pass. Newly registered layers
+    /// are appended to the end.
+    pub fn get_render_order(&self) -> Vec<RenderOrderEntry> {
+        self.share.get_render_order()
+    }
+
+    /// Replaces the render order. The order must contain every registered layer and every
+    /// [`InsertionPoint`] exactly once. Passes which are currently recorded use the new order.
+    pub fn set_render_order(&self, order: &[RenderOrderEntry]) -> Result<(), RenderLayerError> {
+        self.share.set_render_order(order)
+    }
+
+    /// Moves a render layer to an insertion point. Layers inserted at the same point are rendered
+    /// in insertion order.
+    pub fn insert_render_layer(&self, id: RenderLayerId, point: InsertionPoint) -> Result<(), RenderLayerError> {
+        self.share.insert_render_layer(id, point)
+    }
+
+    /// Captures the registered render layers and the render order. See [`warm_state`].
+    pub fn snapshot_state(&self) -> RendererSnapshot {
+        let mut snapshot = RendererSnapshot::default();
+        for entry in self.share.get_render_order() {
+            match entry {
+                RenderOrderEntry::Layer(id) => {
+                    // The layer may have been unregistered in the meantime
+                    if let Some(layer) = self.share.get_render_layer(id) {
+                        snapshot.layers.push(LayerSnapshot {
+                            name: layer.get_name().to_string(),
+                            state: *layer.get_state(),
+                            enabled: layer.is_enabled(),
+                        });
+                        snapshot.order.push(OrderSnapshot::Layer(layer.get_name().to_string()));
+                    }
+                }
+                RenderOrderEntry::Point(point) => snapshot.order.push(OrderSnapshot::Point(point)),
+            }
+        }
+        snapshot
+    }
+
+    /// Registers all layers of a snapshot which are not registered yet and restores their enabled
+    /// state and the render order. Layers which are already registered keep their state.
+    /// Registered layers missing from the snapshot are appended to the end of the render order.
+    pub fn restore_state(&self, snapshot: &RendererSnapshot) -> Result<(), RenderLayerError> {
+        for layer in &snapshot.layers {
+            let id = match self.share.find_render_layer(&layer.name) {
+                Some(id) => id,
+                None => self.share.register_render_layer(&layer.name, &layer.state)?,
+            };
+            self.set_render_layer_enabled(id, layer.enabled);
+        }
+
+        let mut order: Vec<RenderOrderEntry> = snapshot.order.iter().filter_map(|entry| match entry {
+            OrderSnapshot::Layer(name) => self.share.find_render_layer(name).map(RenderOrderEntry::Layer),
+            OrderSnapshot::Point(point) => Some(RenderOrderEntry::Point(*point)),
+        }).collect();
+        for entry in self.share.get_render_order() {
+            if !order.contains(&entry) {
+                order.push(entry);
+            }
+        }
+        self.share.set_render_order(&order)
+    }
+
+    /// Keeps all meshes in the deduplication cache alive until the returned retention is dropped.
+    /// See [`warm_state`].
+    pub fn retain_cached_meshes(&self) -> MeshRetention {
+        MeshRetention::new(self.share.get_all_cached_meshes())
+    }
+
     pub fn start_pass(&self, pipeline: Arc<dyn EmulatorPipeline>) -> PassRecorder {
-        PassRecorder::new(self.share.clone(), pipeline, self.placeholder_image.clone(), &self.placeholder_sampler)
+        PassRecorder::new(self.share.clone(), pipeline, self.placeholder_image.clone(), &self.placeholder_sampler, false, false)
+    }
+
+    /// Starts the main pass of a frame. Identical to [`EmulatorRenderer::start_pass`] except that
+    /// the pass is distributed over the devices of the device group if one is used. See
+    /// [`crate::device::device_group`].
+    pub fn start_main_pass(&self, pipeline: Arc<dyn EmulatorPipeline>) -> PassRecorder {
+        PassRecorder::new(self.share.clone(), pipeline, self.placeholder_image.clone(), &self.placeholder_sampler, false, true)
+    }
+
+    /// Starts a pass which is submitted to the low priority background queue of the device so it
+    /// does not delay frames. Intended for captures which are not displayed immediately like
+    /// probes and panoramas. Falls back to the main queue if the device has no background queue.
+    ///
+    /// Global objects used by a background pass must not be modified until the pass has completed.
+    pub fn start_background_pass(&self, pipeline: Arc<dyn EmulatorPipeline>) -> PassRecorder {
+        PassRecorder::new(self.share.clone(), pipeline, self.placeholder_image.clone(), &self.placeholder_sampler, true, false)
+    }
+
+    /// Starts a watchdog thread which reports passes that have not completed within the configured
+    /// timeout. See [`watchdog`].
+    pub fn start_watchdog(&self, config: WatchdogConfig, callback: Arc<Mutex<Option<HangCallback>>>) -> Watchdog {
+        let share = self.share.clone();
+        let device = share.get_device().clone();
+
+        Watchdog::start(config, device, move |timeout| {
+            let pending = share.get_submissions().get_pending();
+            let oldest = pending.first()?;
+            let elapsed = oldest.submitted_at.elapsed();
+            if elapsed < timeout {
+                return None;
+            }
+
+            Some(HangReport {
+                pass_id: oldest.pass_id,
+                elapsed,
+                recording_pass: share.get_current_pass_id().map(PassId::from_raw),
+                queue_family: share.get_device().get_main_queue().get_queue_family_index(),
+                device_lost: share.get_device().is_device_lost(),
+                pending,
+            })
+        }, callback)
     }
 
     fn create_placeholder_image(share: Arc<Share>) -> Arc<GlobalImage> {
@@ -137,7 +573,7 @@ impl EmulatorRenderer {
             extent: size
         };
 
-        let image = GlobalImage::new(share, size, 1, &Format::R8G8B8A8_SRGB).unwrap();
+        let image = GlobalImage::new(share, size, 1, ImageArrayMode::Single, &Format::R8G8B8A8_SRGB).unwrap();
         image.update_regions(std::slice::from_ref(&info));
         image
     }
@@ -0,0 +1,132 @@
+//! Panorama capture.
+//!
+//! The host renders the scene once for each face of a cubemap using the matrices provided by
+//! [`CubeFace`]. Once all faces are recorded the readbacks are stitched into an equirectangular
+//! image on a background thread and passed to a callback.
+
+use std::f32::consts::PI;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use crate::renderer::emulator::{EmulatorRenderer, FrameSize, PassRecorder};
+use crate::renderer::emulator::pipeline::{EmulatorPipeline, OffscreenOutput, OffscreenReadback};
+use crate::renderer::emulator::probe::CubeFace;
+
+use crate::prelude::*;
+
+/// Called with the size and tightly packed rgba pixels of the stitched panorama.
+pub type PanoramaCallback = Box<dyn FnOnce(Vec2u32, Box<[u8]>) + Send>;
+
+/// An in progress panorama capture.
+pub struct PanoramaCapture {
+    renderer: Arc<EmulatorRenderer>,
+    pipeline: Arc<dyn EmulatorPipeline>,
+    output: Arc<OffscreenOutput>,
+    readbacks: Vec<OffscreenReadback>,
+    callback: Option<PanoramaCallback>,
+}
+
+impl PanoramaCapture {
+    /// Creates a new capture rendering each face with `resolution` by `resolution` pixels using
+    /// `pipeline`. The pipeline must have been created for that size.
+    pub fn new(renderer: Arc<EmulatorRenderer>, pipeline: Arc<dyn EmulatorPipeline>, resolution: u32, callback: PanoramaCallback) -> Self {
+        let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), Vec2u32::new(resolution, resolution));
+
+        Self {
+            renderer,
+            pipeline,
+            output,
+            readbacks: Vec::with_capacity(6),
+            callback: Some(callback),
+        }
+    }
+
+    pub fn get_resolution(&self) -> u32 {
+        self.output.get_size()[0]
+    }
+
+    /// Starts the pass for the next face. The caller must render the scene using
+    /// [`CubeFace::view_matrix`] and [`crate::renderer::emulator::probe::make_face_projection`].
+    ///
+    /// Returns [`None`] once all faces have been started.
+    pub fn next_face(&mut self) -> Option<(CubeFace, PassRecorder)> {
+        let face = *CubeFace::ALL.get(self.readbacks.len())?;
+
+        let (output, readback) = self.output.next_output();
+        let mut recorder = self.renderer.start_background_pass(self.pipeline.clone());
+        recorder.set_frame_size(FrameSize::from_physical(self.output.get_size(), 1f32));
+        recorder.use_output(output);
+        self.readbacks.push(readback);
+
+        Some((face, recorder))
+    }
+
+    /// Returns true once all faces have been started.
+    pub fn is_complete(&self) -> bool {
+        self.readbacks.len() == 6
+    }
+
+    /// Stitches the panorama on a background thread and calls the callback once done. All face
+    /// passes must have been started and submitted.
+    ///
+    /// If any face pass was aborted the callback is never called.
+    pub fn finish(mut self) {
+        if !self.is_complete() {
+            log::error!("Called PanoramaCapture::finish before all faces were started");
+            panic!();
+        }
+
+        let resolution = self.get_resolution();
+        let readbacks = std::mem::take(&mut self.readbacks);
+        let callback = self.callback.take().unwrap();
+
+        std::thread::spawn(move || {
+            let mut faces = Vec::with_capacity(6);
+            for readback in readbacks {
+                match readback.wait() {
+                    Some(data) => faces.push(data),
+                    None => {
+                        log::warn!("Panorama face pass was aborted");
+                        return;
+                    }
+                }
+            }
+
+            let size = Vec2u32::new(resolution * 2, resolution);
+            let data = stitch_equirectangular(&faces, resolution, size);
+            callback(size, data);
+        });
+    }
+}
+
+impl RefUnwindSafe for PanoramaCapture {} // The callback is never called while borrowed
+
+/// Stitches the 6 rgba cube faces (in [`CubeFace::ALL`] order) into an equirectangular image of
+/// `size`. The center of the image looks along negative z.
+pub fn stitch_equirectangular(faces: &[Box<[u8]>], face_size: u32, size: Vec2u32) -> Box<[u8]> {
+    assert_eq!(faces.len(), 6);
+
+    let mut result = vec![0u8; (size[0] as usize) * (size[1] as usize) * 4];
+
+    for y in 0..size[1] {
+        let latitude = PI / 2f32 - ((y as f32) + 0.5f32) / (size[1] as f32) * PI;
+        for x in 0..size[0] {
+            let longitude = ((x as f32) + 0.5f32) / (size[0] as f32) * 2f32 * PI - PI;
+            let direction = Vec3f32::new(
+                latitude.cos() * longitude.sin(),
+                latitude.sin(),
+                -latitude.cos() * longitude.cos()
+            );
+
+            let (face, uv) = CubeFace::project_direction(&direction);
+            let column = ((uv[0] * face_size as f32) as u32).min(face_size - 1);
+            let row = ((uv[1] * face_size as f32) as u32).min(face_size - 1);
+
+            let src = ((row * face_size + column) as usize) * 4;
+            let dst = ((y * size[0] + x) as usize) * 4;
+            result[dst..dst + 4].copy_from_slice(&faces[face as usize][src..src + 4]);
+        }
+    }
+
+    result.into_boxed_slice()
+}
@@ -1,16 +1,68 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
 use ash::vk;
 
+use crate::renderer::emulator::beam::BeamColumn;
+use crate::renderer::emulator::blob_shadow::{BlobShadow, BlobShadowBatch, BlobShadowConfig};
+use crate::renderer::emulator::camera_effects::CameraEffects;
+use crate::renderer::emulator::command_log::{PassCommand, PassCommandLog};
+use crate::renderer::emulator::debug_overlay::{DebugOverlayBatch, DebugOverlays};
+use crate::renderer::emulator::gui_item::{GuiItemPlacement, GUI_ITEM_VIEWPORT};
+use crate::renderer::emulator::gui_rect::{GuiRectBatch, NineSlice, TexturedRect};
+use crate::renderer::emulator::draw_tag::DrawTagStats;
+use crate::renderer::emulator::draw_merger::{can_transform, transform_vertices, DrawMerger, MergeKey, MergedMesh};
 use crate::renderer::emulator::immediate::ImmediateBuffer;
+use crate::renderer::emulator::jobs::JobKind;
 use crate::renderer::emulator::{GlobalImage, GlobalMesh, MeshData};
 use crate::renderer::emulator::global_objects::{GlobalImageId, SamplerInfo};
-use crate::renderer::emulator::worker::WorkerTask;
+use crate::renderer::emulator::worker::{GpuTimeSink, WorkerTask};
 
-use crate::renderer::emulator::mc_shaders::{McUniformData, ShaderId};
+use crate::renderer::emulator::mc_shaders::{McUniformData, Shader, ShaderId};
 use crate::renderer::emulator::pipeline::{DrawTask, EmulatorOutput, EmulatorPipeline, PipelineTask};
+use crate::renderer::emulator::probe::{probe_image_size, PROBE_SAMPLER};
+use crate::renderer::emulator::render_budget::{select_culled_draws_parallel, BudgetReport, RenderBudget, DEFAULT_DRAW_PRIORITY, REQUIRED_DRAW_PRIORITY};
+use crate::renderer::emulator::render_layer::{RenderLayer, RenderLayerId, RenderLayerStats};
+use crate::renderer::emulator::unproject::{ViewMatrices, WorldRay};
 use crate::renderer::emulator::share::Share;
+use crate::renderer::emulator::shadow::CameraFrustum;
+
+use crate::prelude::*;
+
+/// The number of viewports a pass can render to.
+pub const MAX_VIEWPORTS: u32 = 16;
+
+/// The number of texture slots which can be bound using [`PassRecorder::bind_texture`].
+pub const MAX_TEXTURE_SLOTS: u32 = 3;
+
+/// The number of portals which can be nested using [`PassRecorder::begin_portal`].
+pub const MAX_PORTAL_DEPTH: u32 = 8;
+
+/// Controls when the commands recorded by a [`PassRecorder`] are handed to the worker thread.
+///
+/// Vulkan command recording, submission and presentation always happen on the worker thread.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SubmitMode {
+    /// Commands are handed to the worker as they are recorded so the worker records Vulkan
+    /// commands in parallel with the host.
+    Streaming,
+    /// Commands are collected by the recorder and handed to the worker in a single batch when the
+    /// pass ends. The host never contends with the worker while recording and ending the pass
+    /// returns immediately after the batch has been queued. Useful for draw heavy frames where
+    /// the per command synchronization of the streaming mode dominates.
+    Deferred,
+}
+
+impl SubmitMode {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Streaming),
+            1 => Some(Self::Deferred),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
 pub struct PassId(u64);
@@ -38,77 +90,771 @@ impl ImmediateMeshId {
     }
 }
 
+/// The size of the output of a pass in both physical pixels and logical units.
+///
+/// Ui elements should be laid out using the logical size while all render targets use the
+/// physical size.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FrameSize {
+    /// The size in pixels.
+    pub physical_size: Vec2u32,
+    /// The size in logical units (screen coordinates).
+    pub logical_size: Vec2u32,
+    /// The ratio between the physical and logical size.
+    pub scale_factor: f32,
+}
+
+impl FrameSize {
+    /// Creates a new frame size from a size in pixels. The logical size is derived from the scale
+    /// factor.
+    pub fn from_physical(physical_size: Vec2u32, scale_factor: f32) -> Self {
+        let scale_factor = Self::sanitize_scale_factor(scale_factor);
+        Self {
+            physical_size,
+            logical_size: Self::scale(physical_size, 1f32 / scale_factor),
+            scale_factor,
+        }
+    }
+
+    /// Creates a new frame size from a size in logical units. The physical size is derived from
+    /// the scale factor so that hosts do not have to pre-multiply sizes.
+    pub fn from_logical(logical_size: Vec2u32, scale_factor: f32) -> Self {
+        let scale_factor = Self::sanitize_scale_factor(scale_factor);
+        Self {
+            physical_size: Self::scale(logical_size, scale_factor),
+            logical_size,
+            scale_factor,
+        }
+    }
+
+    fn sanitize_scale_factor(scale_factor: f32) -> f32 {
+        if scale_factor.is_finite() && scale_factor > 0f32 {
+            scale_factor
+        } else {
+            1f32
+        }
+    }
+
+    fn scale(size: Vec2u32, factor: f32) -> Vec2u32 {
+        Vec2u32::new(
+            ((size[0] as f32) * factor).round() as u32,
+            ((size[1] as f32) * factor).round() as u32
+        )
+    }
+}
+
 pub struct PassRecorder {
     id: PassId,
     share: Arc<Share>,
+    frame_size: Option<FrameSize>,
+    viewport_index: u32,
+    /// The viewport index selected before [`PassRecorder::begin_gui_item`] was called.
+    gui_item_previous_viewport: Option<u32>,
+    /// The camera matrices set using [`PassRecorder::set_view_matrices`].
+    view_matrices: Option<ViewMatrices>,
+    view_matrices_sink: Option<Box<dyn FnOnce(FrameSize, ViewMatrices) + Send>>,
+    /// The camera effects at the time the pass was started.
+    camera_effects: CameraEffects,
+    camera_effects_enabled: bool,
+    /// The currently started portals. The innermost portal is last.
+    portals: Vec<OpenPortal>,
+    /// The built-in debug overlays drawn after all other draws of the pass.
+    debug_overlays: Option<(Arc<DebugOverlays>, ShaderId)>,
 
-    used_shaders: HashSet<ShaderId>,
+    /// The shaders used in this pass indexed by their dense index (see
+    /// [`Share::get_shader_index`]).
+    shaders: Vec<Option<PassShader>>,
+    /// Consecutive commands usually use the same shader so the last index is cached to skip the
+    /// lookup.
+    last_shader: Option<(ShaderId, u32)>,
     used_global_image: HashSet<GlobalImageId>,
-    immediate_meshes: Vec<ImmediateMeshInfo>,
+    /// The uploaded immediate meshes. [`None`] if the upload was dropped because of the budget.
+    immediate_meshes: Vec<Option<ImmediateMeshInfo>>,
+    bound_textures: [Option<(GlobalImageId, SamplerInfo)>; MAX_TEXTURE_SLOTS as usize],
+
+    /// The images bound using [`PassRecorder::bind_texture`] used to track which images are
+    /// sampled by draws.
+    bound_images: [Option<Arc<GlobalImage>>; MAX_TEXTURE_SLOTS as usize],
+    /// The number of draws sampling each image in this pass.
+    sampled_images: HashMap<GlobalImageId, (Arc<GlobalImage>, u64)>,
+
+    /// The index of the current render layer in `layers`.
+    render_layer: Option<u32>,
+    /// The render layers selected in this pass indexed by their dense index.
+    layers: Vec<Option<LayerRecording>>,
+    layer_stats_sink: Option<Box<dyn FnOnce(Vec<(RenderLayerId, RenderLayerStats)>) + Send>>,
+    /// The tag attached to all following draws.
+    draw_tag: Option<u64>,
+    /// The statistics of all tags drawn with in this pass.
+    tag_stats: HashMap<u64, DrawTagStats>,
+    tag_stats_sink: Option<Box<dyn FnOnce(Vec<(u64, DrawTagStats)>) + Send>>,
+    /// The budget at the time the pass was started.
+    budget: RenderBudget,
+    /// The priority of all following draws and uploads.
+    draw_priority: u8,
+    /// The number of immediate uploads which have not been dropped.
+    immediate_upload_count: u32,
+    budget_report: BudgetReport,
+    budget_report_sink: Option<Box<dyn FnOnce(BudgetReport) + Send>>,
+    gpu_time_sink: Option<GpuTimeSink>,
 
     immediate_buffer: Option<Box<ImmediateBuffer>>,
+    draw_merger: Option<DrawMerger>,
+    command_log: Option<PassCommandLog>,
+    command_log_sink: Option<Box<dyn FnOnce(PassCommandLog) + Send>>,
+    /// The tasks collected for the worker if the pass uses [`SubmitMode::Deferred`].
+    deferred_tasks: Option<Vec<WorkerTask>>,
 
     #[allow(unused)] // We just need to keep the pipeline alive
     pipeline: Arc<dyn EmulatorPipeline>,
 }
 
 impl PassRecorder {
-    pub(super) fn new(share: Arc<Share>, pipeline: Arc<dyn EmulatorPipeline>, placeholder_image: Arc<GlobalImage>, placeholder_sampler: &SamplerInfo) -> Self {
+    pub(super) fn new(share: Arc<Share>, pipeline: Arc<dyn EmulatorPipeline>, placeholder_image: Arc<GlobalImage>, placeholder_sampler: &SamplerInfo, background: bool, main: bool) -> Self {
         let id = share.try_start_pass_id().unwrap_or_else(|| {
             log::error!("Attempted to start pass with an already running pass!");
             panic!();
         });
         let id = PassId::from_raw(id);
 
-        let immediate_buffer = Some(share.get_next_immediate_buffer());
+        let immediate_buffer = share.get_next_immediate_buffer();
+        let frame_index = immediate_buffer.get_frame_index();
+        let immediate_buffer = Some(immediate_buffer);
+
+        let budget = share.get_render_budget();
+        let camera_effects = share.get_camera_effects();
+        let deferred_tasks = match share.get_submit_mode() {
+            SubmitMode::Streaming => None,
+            SubmitMode::Deferred => Some(Vec::with_capacity(1024)),
+        };
 
-        let placeholder_sampler = placeholder_image.get_sampler(placeholder_sampler);
-        share.push_task(WorkerTask::StartPass(id, pipeline.clone(), pipeline.start_pass(), placeholder_image, placeholder_sampler));
+        let placeholder_texture = placeholder_image.get_texture_binding(placeholder_sampler);
+        share.push_task(WorkerTask::StartPass(id, frame_index, pipeline.clone(), pipeline.start_pass(), placeholder_image, placeholder_texture, background, main));
 
         Self {
             id,
             share,
+            frame_size: None,
+            viewport_index: 0,
+            gui_item_previous_viewport: None,
+            view_matrices: None,
+            view_matrices_sink: None,
+            camera_effects,
+            camera_effects_enabled: false,
+            portals: Vec::new(),
+            debug_overlays: None,
 
-            used_shaders: HashSet::new(),
+            shaders: Vec::new(),
+            last_shader: None,
             used_global_image: HashSet::new(),
             immediate_meshes: Vec::with_capacity(128),
+            bound_textures: [None; MAX_TEXTURE_SLOTS as usize],
+
+            bound_images: [None, None, None],
+            sampled_images: HashMap::new(),
+
+            render_layer: None,
+            layers: Vec::new(),
+            layer_stats_sink: None,
+            draw_tag: None,
+            tag_stats: HashMap::new(),
+            tag_stats_sink: None,
+            budget,
+            draw_priority: DEFAULT_DRAW_PRIORITY,
+            immediate_upload_count: 0,
+            budget_report: BudgetReport::default(),
+            budget_report_sink: None,
+            gpu_time_sink: None,
 
             immediate_buffer,
+            draw_merger: None,
+            command_log: None,
+            command_log_sink: None,
+            deferred_tasks,
 
             pipeline,
         }
     }
 
     pub fn use_output(&mut self, output: Box<dyn EmulatorOutput + Send>) {
-        self.share.push_task(WorkerTask::UseOutput(output));
+        self.push_task(WorkerTask::UseOutput(output));
+    }
+
+    /// Returns the size of the frame this pass renders to if the pass was started for a frame.
+    pub fn get_frame_size(&self) -> Option<FrameSize> {
+        self.frame_size
+    }
+
+    pub(crate) fn set_frame_size(&mut self, frame_size: FrameSize) {
+        self.frame_size = Some(frame_size);
+    }
+
+    /// Starts logging all following commands of this pass. Any previous log is discarded.
+    pub fn start_command_log(&mut self) {
+        self.command_log = Some(PassCommandLog::new(self.frame_size));
+    }
+
+    /// Stops logging and returns the log if logging was started.
+    pub fn take_command_log(&mut self) -> Option<PassCommandLog> {
+        self.flush_merged_draws();
+        self.command_log.take()
+    }
+
+    /// Enables or disables merging of small draws submitted using [`PassRecorder::draw_small`].
+    /// Disabled by default.
+    pub fn set_small_draw_merging(&mut self, enable: bool) {
+        if enable {
+            if self.draw_merger.is_none() {
+                self.draw_merger = Some(DrawMerger::new());
+            }
+        } else {
+            self.flush_merged_draws();
+            self.draw_merger = None;
+        }
+    }
+
+    /// Sets a function which is called with the command log when the pass is dropped. Does
+    /// nothing if no log is active at that point.
+    pub(crate) fn set_command_log_sink(&mut self, sink: Box<dyn FnOnce(PassCommandLog) + Send>) {
+        self.command_log_sink = Some(sink);
+    }
+
+    /// Sets the partial tick used for interpolating built-in animations in all following draws.
+    ///
+    /// The value is clamped to the range [0, 1].
+    pub fn set_partial_tick(&mut self, partial_tick: f32) {
+        let partial_tick = if partial_tick.is_finite() { partial_tick.clamp(0f32, 1f32) } else { 0f32 };
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::SetPartialTick(partial_tick));
+        self.push_pipeline_task(PipelineTask::SetPartialTick(partial_tick))
+    }
+
+    /// Sets the viewport at some index in pixels. Viewports which have not been set cover the full
+    /// output. This allows split screen rendering within a single pass.
+    pub fn set_viewport(&mut self, index: u32, viewport: vk::Viewport) {
+        if index >= MAX_VIEWPORTS {
+            log::error!("Viewport index {:?} is out of range", index);
+            panic!();
+        }
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::SetViewport(index, viewport));
+        self.push_pipeline_task(PipelineTask::SetViewport(index, viewport))
+    }
+
+    /// Enables cascaded shadow maps for all following draws.
+    ///
+    /// The view matrix of the camera must transform into the same view space the model view
+    /// matrices of the draws transform into. `light_direction` is the direction the light travels
+    /// in. Only the first call per pass has an effect. Calls with a zero light direction are
+    /// ignored.
+    ///
+    /// If camera effects are enabled the cascades are fitted to the moved camera.
+    pub fn set_shadow_camera(&mut self, camera: &CameraFrustum, light_direction: Vec3f32) {
+        let light_direction = match light_direction.try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => {
+                log::warn!("Called set_shadow_camera with zero light direction");
+                return;
+            }
+        };
+        let effects = if self.camera_effects_enabled {
+            self.camera_effects.get_matrix()
+        } else {
+            Mat4f32::identity()
+        };
+        self.share.push_task(WorkerTask::PipelineTask(PipelineTask::SetShadowCamera(*camera, light_direction, effects)))
+    }
+
+    /// Selects the viewport used by all following draws.
+    pub fn set_viewport_index(&mut self, index: u32) {
+        if index >= MAX_VIEWPORTS {
+            log::error!("Viewport index {:?} is out of range", index);
+            panic!();
+        }
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::SetViewportIndex(index));
+        self.viewport_index = index;
+    }
+
+    /// Restricts all following draws to a rect in pixels in addition to their viewport. Passing
+    /// [`None`] removes the restriction.
+    pub fn set_scissor(&mut self, scissor: Option<vk::Rect2D>) {
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::SetScissor(scissor));
+        self.push_pipeline_task(PipelineTask::SetScissor(scissor))
+    }
+
+    /// Starts drawing a 3d gui item. All following draws are rendered to the viewport
+    /// [`GUI_ITEM_VIEWPORT`] covering the item until [`PassRecorder::end_gui_item`] is called. The
+    /// host must use [`make_gui_item_projection`](crate::renderer::emulator::gui_item::make_gui_item_projection)
+    /// as the projection of the item. See
+    /// [`gui_item`](crate::renderer::emulator::gui_item).
+    ///
+    /// Panics if the pass has no frame size or a gui item is already started.
+    pub fn begin_gui_item(&mut self, placement: &GuiItemPlacement) {
+        let frame_size = self.frame_size.unwrap_or_else(|| {
+            log::error!("Gui items can only be drawn in passes with a frame size");
+            panic!();
+        });
+        if self.gui_item_previous_viewport.is_some() {
+            log::error!("Called begin_gui_item while a gui item is already started");
+            panic!();
+        }
+
+        self.gui_item_previous_viewport = Some(self.viewport_index);
+        self.set_viewport(GUI_ITEM_VIEWPORT, placement.make_viewport(&frame_size));
+        self.set_viewport_index(GUI_ITEM_VIEWPORT);
+    }
+
+    /// Ends the current gui item and restores the previously selected viewport.
+    pub fn end_gui_item(&mut self) {
+        let previous = self.gui_item_previous_viewport.take().unwrap_or_else(|| {
+            log::error!("Called end_gui_item without a started gui item");
+            panic!();
+        });
+        self.set_viewport_index(previous);
+    }
+
+    /// Sets the camera matrices of this pass used to map screen positions back into world space.
+    /// This does not affect rendering. See [`unproject`](crate::renderer::emulator::unproject).
+    ///
+    /// If camera effects are enabled they are applied to the matrices.
+    pub fn set_view_matrices(&mut self, matrices: ViewMatrices) {
+        self.view_matrices = Some(self.apply_camera_effects(matrices));
+    }
+
+    pub fn get_view_matrices(&self) -> Option<ViewMatrices> {
+        self.view_matrices
+    }
+
+    /// Enables or disables applying the camera effects set using
+    /// [`EmulatorRenderer::set_camera_effects`](crate::renderer::emulator::EmulatorRenderer::set_camera_effects)
+    /// to all following projection matrix uniforms and view matrices. Should be enabled for passes
+    /// rendering the world and disabled for the gui. See
+    /// [`camera_effects`](crate::renderer::emulator::camera_effects).
+    pub fn set_camera_effects_enabled(&mut self, enabled: bool) {
+        self.camera_effects_enabled = enabled;
+    }
+
+    /// Returns the camera effects of this pass.
+    pub fn get_camera_effects(&self) -> &CameraEffects {
+        &self.camera_effects
+    }
+
+    /// Applies the camera effects to a world to view matrix if they are enabled. Hosts computing
+    /// views from the camera for this pass must derive them from the returned matrix so they stay
+    /// aligned with the world while the camera moves.
+    pub fn apply_camera_effects_to_view(&self, view: &Mat4f32) -> Mat4f32 {
+        if self.camera_effects_enabled {
+            self.camera_effects.apply_to_view(view)
+        } else {
+            *view
+        }
+    }
+
+    fn apply_camera_effects(&self, matrices: ViewMatrices) -> ViewMatrices {
+        if self.camera_effects_enabled {
+            self.camera_effects.apply_to_view_matrices(&matrices)
+        } else {
+            matrices
+        }
+    }
+
+    /// Starts a portal. All following draws until [`PassRecorder::end_portal`] is called are only
+    /// visible through the visible parts of the mask and are depth tested independent of anything
+    /// drawn behind the mask. This allows rendering a sub-scene with its own camera, for example
+    /// the destination of a see-through portal.
+    ///
+    /// The mask is drawn with the current uniforms of `shader` and must be drawn again with the
+    /// same uniforms when the portal ends. The host must therefore restore the uniforms of the
+    /// parent camera before calling [`PassRecorder::end_portal`]. `matrices` replace the view
+    /// matrices of the pass (see [`PassRecorder::set_view_matrices`]) until the portal ends.
+    ///
+    /// Panics if a render layer is selected or more than [`MAX_PORTAL_DEPTH`] portals are nested.
+    pub fn begin_portal(&mut self, matrices: ViewMatrices, mask: ImmediateMeshId, shader: ShaderId) {
+        if self.render_layer.is_some() {
+            log::error!("Portals cannot be started while a render layer is selected");
+            panic!();
+        }
+        if self.portals.len() >= MAX_PORTAL_DEPTH as usize {
+            log::error!("Called begin_portal with {:?} portals already started", self.portals.len());
+            panic!();
+        }
+        let matrices = self.apply_camera_effects(matrices);
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::BeginPortal { matrices, mask: mask.get_raw(), shader });
+
+        let mask = self.make_portal_mask(mask, shader);
+        self.portals.push(OpenPortal {
+            mask,
+            previous_matrices: self.view_matrices.replace(matrices),
+        });
+        self.push_pipeline_task(PipelineTask::BeginPortal(mask));
+    }
+
+    /// Ends the innermost portal and restores the view matrices set before it was started. See
+    /// [`PassRecorder::begin_portal`].
+    pub fn end_portal(&mut self) {
+        let portal = self.portals.pop().unwrap_or_else(|| {
+            log::error!("Called end_portal without a started portal");
+            panic!();
+        });
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::EndPortal);
+
+        self.view_matrices = portal.previous_matrices;
+        self.push_pipeline_task(PipelineTask::EndPortal(portal.mask));
+    }
+
+    /// Sets the debug overlays drawn with `shader` once the pass ends. They are drawn after all
+    /// render layers using the view matrices of the pass and are not recorded in the command log.
+    /// See [`debug_overlay`](crate::renderer::emulator::debug_overlay).
+    pub(crate) fn set_debug_overlays(&mut self, overlays: Arc<DebugOverlays>, shader: ShaderId) {
+        self.debug_overlays = Some((overlays, shader));
+    }
+
+    /// Draws the debug overlays set using [`PassRecorder::set_debug_overlays`]. Skipped if the
+    /// pass has no view matrices.
+    fn draw_debug_overlays(&mut self) {
+        let (overlays, shader) = match self.debug_overlays.take() {
+            Some(overlays) => overlays,
+            None => return,
+        };
+        let matrices = match self.view_matrices {
+            Some(matrices) => matrices,
+            None => return,
+        };
+        if overlays.is_empty() {
+            return;
+        }
+
+        let shader_index = self.use_shader(shader);
+        let format = *self.get_pass_shader(shader_index).shader.get_vertex_format();
+        if !DebugOverlayBatch::supports_format(&format) {
+            log::error!("The vertex format of debug overlay shader {:?} does not support debug overlays", shader);
+            return;
+        }
+        let batch = DebugOverlayBatch::from_overlays(&format, &overlays);
+        if batch.is_empty() {
+            return;
+        }
+
+        let command_log = self.command_log.take();
+        self.render_layer = None;
+        self.draw_tag = None;
+        self.draw_priority = REQUIRED_DRAW_PRIORITY;
+        self.set_viewport_index(0);
+        self.set_scissor(None);
+        self.update_uniform(&McUniformData::ModelViewMatrix(matrices.model_view), shader);
+        self.update_uniform(&McUniformData::ProjectionMatrix(matrices.projection), shader);
+        let id = self.upload_immediate(&batch.as_mesh_data());
+        self.draw_immediate_unflushed(id, shader, false);
+        self.command_log = command_log;
+    }
+
+    /// Returns the number of currently started portals.
+    pub fn get_portal_depth(&self) -> u32 {
+        self.portals.len() as u32
+    }
+
+    /// Creates the draw of a portal mask. Returns [`None`] if the upload of the mask was dropped
+    /// in which case the portal contents are not visible.
+    fn make_portal_mask(&mut self, mask: ImmediateMeshId, shader: ShaderId) -> Option<DrawTask> {
+        let mesh_data = self.immediate_meshes.get(mask.get_raw() as usize).unwrap_or_else(|| {
+            log::error!("Called begin_portal with unknown immediate mesh {:?}", mask);
+            panic!();
+        });
+        let mesh_data = match mesh_data {
+            Some(mesh_data) => *mesh_data,
+            None => {
+                self.budget_report.dropped_upload_draws += 1;
+                return None;
+            }
+        };
+        self.use_shader(shader);
+
+        Some(DrawTask {
+            vertex_buffer: mesh_data.vertex_buffer,
+            index_buffer: mesh_data.index_buffer,
+            vertex_offset: mesh_data.vertex_offset,
+            first_index: mesh_data.first_index,
+            index_type: mesh_data.index_type,
+            index_count: mesh_data.index_count,
+            shader,
+            primitive_topology: mesh_data.primitive_topology,
+            depth_write_enable: true,
+            viewport_index: self.viewport_index,
+            tag: self.draw_tag,
+        })
+    }
+
+    /// Returns the world space position at `screen_pos` in logical pixels and `depth` using the
+    /// camera matrices of this pass. Returns [`None`] if the pass has no frame size or no camera
+    /// matrices have been set.
+    pub fn unproject(&self, screen_pos: Vec2f32, depth: f32) -> Option<Vec3f32> {
+        self.view_matrices.as_ref()?.unproject(self.frame_size.as_ref()?, screen_pos, depth)
+    }
+
+    /// Returns the world space ray at `screen_pos` in logical pixels. See
+    /// [`PassRecorder::unproject`].
+    pub fn unproject_ray(&self, screen_pos: Vec2f32) -> Option<WorldRay> {
+        self.view_matrices.as_ref()?.unproject_ray(self.frame_size.as_ref()?, screen_pos)
+    }
+
+    /// Sets a function which is called with the frame size and camera matrices of this pass when
+    /// the pass is dropped. Not called if no camera matrices have been set.
+    pub(crate) fn set_view_matrices_sink(&mut self, sink: Box<dyn FnOnce(FrameSize, ViewMatrices) + Send>) {
+        self.view_matrices_sink = Some(sink);
+    }
+
+    /// Selects the render layer all following draws are submitted to. Passing [`None`] submits
+    /// draws without a layer.
+    ///
+    /// Draws and state changes recorded for a layer are deferred until the pass ends and then
+    /// emitted in the render order of the renderer (see [`render_layer`]). Commands recorded
+    /// without a layer are emitted immediately and therefore before all layers. Textures bound
+    /// using [`PassRecorder::bind_texture`] carry over into the selected layer, any other state
+    /// change only applies to the layer it was recorded in and to layers rendered after it.
+    ///
+    /// Panics if the layer is not registered.
+    ///
+    /// [`render_layer`]: crate::renderer::emulator::render_layer
+    pub fn set_render_layer(&mut self, layer: Option<RenderLayerId>) {
+        if self.get_render_layer() == layer {
+            return;
+        }
+        if !self.portals.is_empty() {
+            log::error!("Render layers cannot be selected while a portal is started");
+            panic!();
+        }
+        let layer = layer.map(|id| {
+            self.share.get_render_layer(id).unwrap_or_else(|| {
+                log::error!("Called PassRecorder::set_render_layer with unknown layer {:?}", id);
+                panic!()
+            })
+        });
+
+        self.flush_merged_draws();
+        self.render_layer = layer.map(|layer| {
+            let index = layer.get_index();
+            if self.layers.len() <= index as usize {
+                self.layers.resize_with((index as usize) + 1, || None);
+            }
+            self.layers[index as usize].get_or_insert_with(|| LayerRecording::new(layer));
+            index
+        });
+
+        for slot in 0..MAX_TEXTURE_SLOTS {
+            let texture = match (&self.bound_textures[slot as usize], &self.bound_images[slot as usize]) {
+                (Some((_, sampler_info)), Some(image)) => Some(image.get_texture_binding(sampler_info)),
+                _ => None,
+            };
+            self.push_pipeline_task(PipelineTask::BindTexture(slot, texture));
+        }
+    }
+
+    pub fn get_render_layer(&self) -> Option<RenderLayerId> {
+        self.render_layer.map(|index| self.get_layer_recording(index).layer.get_id())
+    }
+
+    /// Returns the statistics of a render layer in this pass so far. Returns [`None`] if no draws
+    /// have been submitted to the layer.
+    pub fn get_layer_stats(&self, layer: RenderLayerId) -> Option<RenderLayerStats> {
+        self.layers.iter().flatten()
+            .find(|recording| recording.layer.get_id() == layer && recording.has_draws())
+            .map(|recording| recording.stats)
+    }
+
+    /// Sets a function which is called with the statistics of all layers drawn to in this pass
+    /// when the pass is dropped.
+    pub(crate) fn set_layer_stats_sink(&mut self, sink: Box<dyn FnOnce(Vec<(RenderLayerId, RenderLayerStats)>) + Send>) {
+        self.layer_stats_sink = Some(sink);
+    }
+
+    /// Attaches a tag to all following draws. Passing [`None`] removes the tag. See
+    /// [`draw_tag`](crate::renderer::emulator::draw_tag).
+    pub fn set_draw_tag(&mut self, tag: Option<u64>) {
+        if self.draw_tag == tag {
+            return;
+        }
+
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::SetDrawTag(tag));
+        self.draw_tag = tag;
+    }
+
+    pub fn get_draw_tag(&self) -> Option<u64> {
+        self.draw_tag
+    }
+
+    /// Returns the statistics of all draws submitted with a tag so far or [`None`] if no draws
+    /// have been submitted with the tag.
+    pub fn get_draw_tag_stats(&self, tag: u64) -> Option<DrawTagStats> {
+        self.tag_stats.get(&tag).copied()
+    }
+
+    /// Sets a function which is called with the statistics of all tags drawn with in this pass
+    /// when the pass is dropped.
+    pub(crate) fn set_tag_stats_sink(&mut self, sink: Box<dyn FnOnce(Vec<(u64, DrawTagStats)>) + Send>) {
+        self.tag_stats_sink = Some(sink);
+    }
+
+    /// Sets the priority of all following draws and immediate uploads. If the render budget is
+    /// exceeded draws with lower priority are dropped first. See
+    /// [`render_budget`](crate::renderer::emulator::render_budget).
+    pub fn set_draw_priority(&mut self, priority: u8) {
+        if self.draw_priority == priority {
+            return;
+        }
+
+        self.flush_merged_draws();
+        self.draw_priority = priority;
+    }
+
+    pub fn get_draw_priority(&self) -> u8 {
+        self.draw_priority
+    }
+
+    /// Returns the work dropped so far because of the render budget. Draws dropped because of
+    /// layer limits are only known once the pass ends.
+    pub fn get_budget_report(&self) -> BudgetReport {
+        self.budget_report
     }
 
+    /// Sets a function which is called with the work dropped because of the render budget when
+    /// the pass is dropped.
+    pub(crate) fn set_budget_report_sink(&mut self, sink: Box<dyn FnOnce(BudgetReport) + Send>) {
+        self.budget_report_sink = Some(sink);
+    }
+
+    /// Sets a function which is called from the worker thread with the gpu execution time of this
+    /// pass once it completes. Not called if the queue does not support timestamp queries.
+    pub(crate) fn set_gpu_time_sink(&mut self, sink: Box<dyn FnOnce(Duration) + Send>) {
+        self.gpu_time_sink = Some(sink);
+    }
+
+    /// Updates a uniform of a shader for all following draws. If camera effects are enabled they
+    /// are applied to projection matrices.
     pub fn update_uniform(&mut self, data: &McUniformData, shader: ShaderId) {
+        let data = match data {
+            McUniformData::ProjectionMatrix(projection) if self.camera_effects_enabled => {
+                McUniformData::ProjectionMatrix(self.camera_effects.apply_to_projection(projection))
+            }
+            data => *data,
+        };
+
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::UpdateUniform(shader, data));
         self.use_shader(shader);
-        self.share.push_task(WorkerTask::PipelineTask(PipelineTask::UpdateUniform(shader, *data)))
+        self.push_pipeline_task(PipelineTask::UpdateUniform(shader, data))
     }
 
     pub fn update_texture(&mut self, index: u32, image: &Arc<GlobalImage>, sampler_info: &SamplerInfo, shader: ShaderId) {
-        self.use_shader(shader);
-        let view = image.get_sampler_view();
-        let sampler = image.get_sampler(sampler_info);
+        if self.is_foreign(image.get_share_id(), "PassRecorder::update_texture") {
+            return;
+        }
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::UpdateTexture { index, image: image.get_id(), sampler: *sampler_info, shader });
+        let shader_index = self.use_shader(shader);
+        let texture = image.get_texture_binding(sampler_info);
 
-        if self.used_global_image.insert(image.get_id()) {
-            self.share.push_task(WorkerTask::UseGlobalImage(image.clone()));
+        self.use_global_image(image);
+        if index < MAX_TEXTURE_SLOTS {
+            self.get_pass_shader(shader_index).images[index as usize] = Some(image.clone());
+        }
+
+        self.push_pipeline_task(PipelineTask::UpdateTexture(shader, index, texture));
+    }
+
+    /// Sets a prefiltered environment probe as the texture at some index of a shader using
+    /// [`PROBE_SAMPLER`]. See [`probe`](crate::renderer::emulator::probe).
+    pub fn update_environment_probe(&mut self, index: u32, image: &Arc<GlobalImage>, shader: ShaderId) {
+        let size = image.get_size();
+        if size != probe_image_size(size[1]) {
+            log::error!("Called PassRecorder::update_environment_probe with an image which is not a probe");
+            panic!();
+        }
+        self.update_texture(index, image, &PROBE_SAMPLER, shader);
+    }
+
+    /// Binds a texture to some slot for all following draws independent of the shader used. This
+    /// overrides any texture set for the slot using [`PassRecorder::update_texture`] until
+    /// [`PassRecorder::unbind_texture`] is called. Binding the texture which is already bound is
+    /// free.
+    pub fn bind_texture(&mut self, slot: u32, image: &Arc<GlobalImage>, sampler_info: &SamplerInfo) {
+        if slot >= MAX_TEXTURE_SLOTS {
+            log::error!("Texture slot {:?} is out of range", slot);
+            panic!();
+        }
+        if self.is_foreign(image.get_share_id(), "PassRecorder::bind_texture") {
+            return;
+        }
+        let entry = Some((image.get_id(), *sampler_info));
+        if self.bound_textures[slot as usize] == entry {
+            return;
+        }
+        self.bound_textures[slot as usize] = entry;
+
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::BindTexture { slot, image: image.get_id(), sampler: *sampler_info });
+        let texture = image.get_texture_binding(sampler_info);
+
+        self.use_global_image(image);
+        self.bound_images[slot as usize] = Some(image.clone());
+
+        self.push_pipeline_task(PipelineTask::BindTexture(slot, Some(texture)));
+    }
+
+    /// Removes a texture bound using [`PassRecorder::bind_texture`]. Following draws use the
+    /// texture set for the shader again.
+    pub fn unbind_texture(&mut self, slot: u32) {
+        if slot >= MAX_TEXTURE_SLOTS {
+            log::error!("Texture slot {:?} is out of range", slot);
+            panic!();
         }
+        if self.bound_textures[slot as usize].take().is_none() {
+            return;
+        }
+
+        self.bound_images[slot as usize] = None;
 
-        self.share.push_task(WorkerTask::PipelineTask(PipelineTask::UpdateTexture(shader, index, view, sampler)));
+        self.flush_merged_draws();
+        self.log_command(|| PassCommand::UnbindTexture(slot));
+        self.push_pipeline_task(PipelineTask::BindTexture(slot, None));
     }
 
+    /// Copies a mesh into the immediate buffer of this pass.
+    ///
+    /// If the immediate upload limit of the render budget has been reached the upload is dropped
+    /// unless the current draw priority is [`REQUIRED_DRAW_PRIORITY`]. Draws of dropped uploads
+    /// are skipped.
     pub fn upload_immediate(&mut self, data: &MeshData) -> ImmediateMeshId {
+        let id = self.immediate_meshes.len() as u32;
+        self.log_command(|| PassCommand::UploadImmediate {
+            id,
+            vertex_data: data.vertex_data.to_vec(),
+            index_data: data.index_data.to_vec(),
+            vertex_stride: data.vertex_stride,
+            index_count: data.index_count,
+            index_type: data.index_type,
+            primitive_topology: data.primitive_topology,
+        });
+
+        let over_budget = self.budget.max_immediate_uploads.map_or(false, |max| self.immediate_upload_count >= max);
+        if over_budget && self.draw_priority != REQUIRED_DRAW_PRIORITY {
+            self.budget_report.dropped_uploads += 1;
+            self.immediate_meshes.push(None);
+            return ImmediateMeshId::form_raw(id);
+        }
+        self.immediate_upload_count += 1;
+
         let index_size = data.get_index_size();
 
         let immediate = self.immediate_buffer.as_mut().unwrap();
         let (vertex_buffer, vertex_offset) = immediate.allocate(data.vertex_data, data.vertex_stride as vk::DeviceSize);
         let (index_buffer, index_offset) = immediate.allocate(data.index_data, index_size as vk::DeviceSize);
 
-        let id = self.immediate_meshes.len() as u32;
-        self.immediate_meshes.push(ImmediateMeshInfo {
+        self.immediate_meshes.push(Some(ImmediateMeshInfo {
             vertex_buffer,
             index_buffer,
             vertex_offset: (vertex_offset / (data.vertex_stride as vk::DeviceSize)) as i32,
@@ -116,15 +862,189 @@ impl PassRecorder {
             index_type: data.index_type,
             index_count: data.index_count,
             primitive_topology: data.primitive_topology
-        });
+        }));
 
         ImmediateMeshId::form_raw(id)
     }
 
     pub fn draw_immediate(&mut self, id: ImmediateMeshId, shader: ShaderId, depth_write_enable: bool) {
-        self.use_shader(shader);
+        self.flush_merged_draws();
+        let depth_write_enable = self.layer_depth_write(depth_write_enable);
+        self.draw_immediate_unflushed(id, shader, depth_write_enable);
+    }
+
+    /// Draws a small mesh. The mesh data is copied into the immediate buffer of this pass.
+    ///
+    /// If a transform is provided it is applied to the vertex positions and normals on the cpu
+    /// before drawing. In that case the vertex format of the shader must be supported by
+    /// [`transform_vertices`].
+    ///
+    /// If small draw merging is enabled (see [`PassRecorder::set_small_draw_merging`]) consecutive
+    /// small draws with identical state are merged into a single draw call. Any other command
+    /// flushes the merged draws so the draw order is not changed.
+    pub fn draw_small(&mut self, data: &MeshData, transform: Option<&Mat4f32>, shader: ShaderId, depth_write_enable: bool) {
+        let shader_index = self.use_shader(shader);
+        let format = *self.get_pass_shader(shader_index).shader.get_vertex_format();
+        if transform.is_some() && !can_transform(&format) {
+            log::error!("Called PassRecorder::draw_small with a transform but the vertex format of shader {:?} does not support transforms", shader);
+            panic!()
+        }
+
+        let depth_write_enable = self.layer_depth_write(depth_write_enable);
+
+        if let Some(merger) = &mut self.draw_merger {
+            if DrawMerger::can_merge(data, &format, transform.is_some()) {
+                let key = MergeKey {
+                    shader,
+                    depth_write_enable,
+                    viewport_index: self.viewport_index,
+                    vertex_stride: data.vertex_stride,
+                    primitive_topology: data.primitive_topology,
+                };
+
+                let (flushed, merged) = match merger.push(key, data, &format, transform) {
+                    Ok(flushed) => (flushed, true),
+                    Err(flushed) => (flushed, false),
+                };
+                if let Some(mesh) = flushed {
+                    self.draw_merged(mesh);
+                }
+                if merged {
+                    return;
+                }
+            } else {
+                self.flush_merged_draws();
+            }
+        }
+
+        let id = match transform {
+            Some(transform) => {
+                let mut vertex_data = data.vertex_data.to_vec();
+                transform_vertices(&mut vertex_data, &format, transform);
+                self.upload_immediate(&MeshData { vertex_data: &vertex_data, ..*data })
+            }
+            None => self.upload_immediate(data),
+        };
+        self.draw_immediate_unflushed(id, shader, depth_write_enable);
+    }
+
+    /// Draws textured gui rects using `image` in texture slot 0 of `shader`. All rects are expanded
+    /// into a single mesh at `depth` and drawn with one small draw (see
+    /// [`PassRecorder::draw_small`]).
+    ///
+    /// The vertex format of the shader must be supported by [`GuiRectBatch`].
+    pub fn draw_textured_rects(&mut self, image: &Arc<GlobalImage>, sampler_info: &SamplerInfo, rects: &[TexturedRect], depth: f32, shader: ShaderId, depth_write_enable: bool) {
+        let mut batch = self.create_gui_rect_batch(shader, depth);
+        for rect in rects {
+            batch.push_rect(rect);
+        }
+        self.draw_gui_rect_batch(&batch, image, sampler_info, shader, depth_write_enable);
+    }
+
+    /// Draws nine slice gui panels using `image` in texture slot 0 of `shader`. See
+    /// [`PassRecorder::draw_textured_rects`].
+    pub fn draw_nine_slices(&mut self, image: &Arc<GlobalImage>, sampler_info: &SamplerInfo, panels: &[NineSlice], depth: f32, shader: ShaderId, depth_write_enable: bool) {
+        let mut batch = self.create_gui_rect_batch(shader, depth);
+        for panel in panels {
+            batch.push_nine_slice(panel);
+        }
+        self.draw_gui_rect_batch(&batch, image, sampler_info, shader, depth_write_enable);
+    }
+
+    fn create_gui_rect_batch(&mut self, shader: ShaderId, depth: f32) -> GuiRectBatch {
+        let shader_index = self.use_shader(shader);
+        let format = *self.get_pass_shader(shader_index).shader.get_vertex_format();
+        if !GuiRectBatch::supports_format(&format) {
+            log::error!("Called PassRecorder::draw_textured_rects but the vertex format of shader {:?} does not support gui rects", shader);
+            panic!()
+        }
+        GuiRectBatch::new(&format, depth)
+    }
+
+    fn draw_gui_rect_batch(&mut self, batch: &GuiRectBatch, image: &Arc<GlobalImage>, sampler_info: &SamplerInfo, shader: ShaderId, depth_write_enable: bool) {
+        if batch.is_empty() {
+            return;
+        }
+        self.update_texture(0, image, sampler_info, shader);
+        self.draw_small(&batch.as_mesh_data(), None, shader, depth_write_enable);
+    }
+
+    /// Draws soft round shadows below entities using `shader`. All shadows are expanded into a
+    /// single mesh and drawn with one small draw without writing depth (see
+    /// [`PassRecorder::draw_small`]). Shadows which are fully faded out are skipped.
+    ///
+    /// The shadows are drawn into the current render layer. Hosts usually select a layer drawn
+    /// after the terrain so the shadows blend over the ground.
+    ///
+    /// The vertex format of the shader must be supported by [`BlobShadowBatch`].
+    pub fn draw_blob_shadows(&mut self, shadows: &[BlobShadow], config: &BlobShadowConfig, shader: ShaderId) {
+        let shader_index = self.use_shader(shader);
+        let format = *self.get_pass_shader(shader_index).shader.get_vertex_format();
+        if !BlobShadowBatch::supports_format(&format) {
+            log::error!("Called PassRecorder::draw_blob_shadows but the vertex format of shader {:?} does not support blob shadows", shader);
+            panic!()
+        }
+
+        let mut batch = BlobShadowBatch::new(&format, config);
+        for shadow in shadows {
+            batch.push_shadow(shadow);
+        }
+        if batch.is_empty() {
+            return;
+        }
+        self.draw_small(&batch.as_mesh_data(), None, shader, false);
+    }
 
-        let mesh_data = self.immediate_meshes.get(id.get_raw() as usize).unwrap();
+    /// Draws animated beams using a unit column mesh created with
+    /// [`BeamMesh`](crate::renderer::emulator::beam::BeamMesh) for the vertex format of `shader`.
+    /// Each beam is a draw of the same global mesh with its own model view matrix, texture matrix
+    /// and color modulator so no mesh data is uploaded. See
+    /// [`beam`](crate::renderer::emulator::beam).
+    ///
+    /// The beams are placed using the view matrices of the pass. Once all beams are drawn the
+    /// model view matrix of `shader` is reset to the view matrices of the pass, the texture matrix
+    /// to identity and the color modulator to white. Skipped if the pass has no view matrices.
+    pub fn draw_beams(&mut self, mesh: &Arc<GlobalMesh>, beams: &[BeamColumn], shader: ShaderId, depth_write_enable: bool) {
+        let matrices = match self.view_matrices {
+            Some(matrices) => matrices,
+            None => {
+                log::warn!("Called PassRecorder::draw_beams without view matrices");
+                return;
+            }
+        };
+        if beams.is_empty() {
+            return;
+        }
+
+        for beam in beams {
+            self.update_uniform(&McUniformData::ModelViewMatrix(matrices.model_view * beam.get_model_matrix()), shader);
+            self.update_uniform(&McUniformData::TextureMatrix(beam.get_texture_matrix()), shader);
+            self.update_uniform(&McUniformData::ColorModulator(beam.color), shader);
+            self.draw_global(mesh.clone(), shader, depth_write_enable);
+        }
+
+        self.update_uniform(&McUniformData::ModelViewMatrix(matrices.model_view), shader);
+        self.update_uniform(&McUniformData::TextureMatrix(Mat4f32::identity()), shader);
+        self.update_uniform(&McUniformData::ColorModulator(Vec4f32::new(1f32, 1f32, 1f32, 1f32)), shader);
+    }
+
+    fn draw_immediate_unflushed(&mut self, id: ImmediateMeshId, shader: ShaderId, depth_write_enable: bool) {
+        self.log_command(|| PassCommand::DrawImmediate { id: id.get_raw(), shader, depth_write_enable });
+        let index_count = match self.immediate_meshes.get(id.get_raw() as usize).unwrap() {
+            Some(mesh_data) => mesh_data.index_count,
+            None => {
+                self.budget_report.dropped_upload_draws += 1;
+                return;
+            }
+        };
+        if !self.record_layer_draw(index_count) {
+            return;
+        }
+
+        let shader_index = self.use_shader(shader);
+        self.record_sampled_images(shader_index);
+
+        let mesh_data = self.immediate_meshes.get(id.get_raw() as usize).unwrap().as_ref().unwrap();
 
         let draw_task = DrawTask {
             vertex_buffer: mesh_data.vertex_buffer,
@@ -136,14 +1056,26 @@ impl PassRecorder {
             shader,
             primitive_topology: mesh_data.primitive_topology,
             depth_write_enable,
+            viewport_index: self.viewport_index,
+            tag: self.draw_tag,
         };
-        self.share.push_task(WorkerTask::PipelineTask(PipelineTask::Draw(draw_task)));
+        self.push_pipeline_task(PipelineTask::Draw(draw_task));
     }
 
     pub fn draw_global(&mut self, mesh: Arc<GlobalMesh>, shader: ShaderId, depth_write_enable: bool) {
+        if self.is_foreign(mesh.get_share_id(), "PassRecorder::draw_global") {
+            return;
+        }
+        self.flush_merged_draws();
+        let depth_write_enable = self.layer_depth_write(depth_write_enable);
+        self.log_command(|| PassCommand::DrawGlobal { mesh: mesh.get_id(), shader, depth_write_enable });
+        if !self.record_layer_draw(mesh.get_draw_info().index_count) {
+            return;
+        }
         mesh.update_used_in(self.id);
 
-        self.use_shader(shader);
+        let shader_index = self.use_shader(shader);
+        self.record_sampled_images(shader_index);
 
         let draw_info = mesh.get_draw_info();
 
@@ -157,27 +1089,322 @@ impl PassRecorder {
             shader,
             primitive_topology: draw_info.primitive_topology,
             depth_write_enable,
+            viewport_index: self.viewport_index,
+            tag: self.draw_tag,
         };
 
-        self.share.push_task(WorkerTask::UseGlobalMesh(mesh));
-        self.share.push_task(WorkerTask::PipelineTask(PipelineTask::Draw(draw_task)));
+        self.push_task(WorkerTask::UseGlobalMesh(mesh));
+        self.push_pipeline_task(PipelineTask::Draw(draw_task));
     }
 
-    fn use_shader(&mut self, shader: ShaderId) {
-        if self.used_shaders.insert(shader) {
+    /// Returns true if an object was created by a different renderer instance. This happens if the
+    /// host keeps using objects created before the device was recreated. The command is skipped
+    /// since the object belongs to a different device.
+    fn is_foreign(&self, share_id: UUID, function: &str) -> bool {
+        if share_id == self.share.get_id() {
+            return false;
+        }
+        log::error!("Called {} with an object created by a different renderer instance. Objects must be recreated after the device has been lost", function);
+        true
+    }
+
+    /// Keeps the image alive until the pass has completed execution and ensures all pending
+    /// uploads to it are done before the pass.
+    pub(crate) fn use_global_image(&mut self, image: &Arc<GlobalImage>) {
+        if self.used_global_image.insert(image.get_id()) {
+            image.update_used_in(self.id);
+            self.push_task(WorkerTask::UseGlobalImage(image.clone()));
+        }
+    }
+
+    /// Counts a draw using the shader at `shader_index` for every image it may sample. Bound
+    /// textures override the textures set for the shader.
+    fn record_sampled_images(&mut self, shader_index: u32) {
+        let shader = self.shaders[shader_index as usize].as_ref().unwrap();
+        for slot in 0..MAX_TEXTURE_SLOTS {
+            let image = match &self.bound_images[slot as usize] {
+                Some(image) => Some(image),
+                None => shader.images[slot as usize].as_ref(),
+            };
+            if let Some(image) = image {
+                self.sampled_images.entry(image.get_id()).or_insert_with(|| (image.clone(), 0)).1 += 1;
+            }
+        }
+    }
+
+    fn push_pipeline_task(&mut self, task: PipelineTask) {
+        match self.render_layer {
+            Some(index) => {
+                let recording = self.layers[index as usize].as_mut().unwrap();
+                if let PipelineTask::Draw(draw) = &task {
+                    recording.draws.push(LayerDraw {
+                        task_index: recording.tasks.len(),
+                        priority: self.draw_priority,
+                        index_count: draw.index_count,
+                        tag: draw.tag,
+                    });
+                }
+                recording.tasks.push(WorkerTask::PipelineTask(task));
+            }
+            None => self.push_task(WorkerTask::PipelineTask(task)),
+        }
+    }
+
+    fn push_task(&mut self, task: WorkerTask) {
+        match &mut self.deferred_tasks {
+            Some(tasks) => tasks.push(task),
+            None => self.share.push_task(task),
+        }
+    }
+
+    fn push_tasks(&mut self, tasks: Vec<WorkerTask>) {
+        match &mut self.deferred_tasks {
+            Some(deferred) => deferred.extend(tasks),
+            None => self.share.push_tasks(tasks),
+        }
+    }
+
+    /// Drops the lowest priority draws of all layers exceeding their draw limit.
+    fn cull_layer_draws(&mut self) {
+        let inputs: Vec<_> = self.layers.iter().map(|recording| {
+            let recording = recording.as_ref()?;
+            let limit = *self.budget.layer_draw_limits.get(&recording.layer.get_id())?;
+            if recording.draws.len() <= limit as usize {
+                return None;
+            }
+            let priorities: Vec<u8> = recording.draws.iter().map(|draw| draw.priority).collect();
+            Some((priorities, limit))
+        }).collect();
+        if inputs.iter().all(Option::is_none) {
+            return;
+        }
+
+        // Layers are culled in parallel and the draws of large layers, which may contain tens of
+        // thousands of draws, are sorted in parallel as well
+        let jobs = self.share.get_job_system();
+        let culled_layers = jobs.map(JobKind::Culling, &inputs, 1, |input| {
+            input.as_ref().map(|(priorities, limit)| select_culled_draws_parallel(jobs, priorities, *limit))
+        });
+
+        for (recording, culled) in self.layers.iter_mut().zip(culled_layers) {
+            let (recording, culled) = match (recording, culled) {
+                (Some(recording), Some(culled)) => (recording, culled),
+                _ => continue,
+            };
+
+            let mut culled_tasks = HashSet::new();
+            for (draw, _) in recording.draws.iter().zip(culled).filter(|(_, culled)| *culled) {
+                culled_tasks.insert(draw.task_index);
+                recording.stats.remove_culled_draw(draw.index_count);
+                if let Some(tag) = draw.tag {
+                    self.tag_stats.get_mut(&tag).unwrap().remove_draw(draw.index_count);
+                }
+                self.budget_report.culled_draws += 1;
+            }
+
+            let mut task_index = 0;
+            recording.tasks.retain(|_| {
+                let keep = !culled_tasks.contains(&task_index);
+                task_index += 1;
+                keep
+            });
+        }
+        self.tag_stats.retain(|_, stats| stats.draw_count != 0);
+    }
+
+    /// Emits the tasks of all render layers in render order.
+    fn flush_layer_tasks(&mut self) {
+        let mut tasks = Vec::new();
+        for id in self.share.get_render_layer_order() {
+            if let Some(recording) = self.layers.iter_mut().flatten().find(|recording| recording.layer.get_id() == id) {
+                tasks.append(&mut recording.tasks);
+            }
+        }
+        // Layers which have been unregistered while the pass was recorded
+        for recording in self.layers.iter_mut().flatten() {
+            tasks.append(&mut recording.tasks);
+        }
+        self.push_tasks(tasks);
+    }
+
+    fn get_layer_recording(&self, index: u32) -> &LayerRecording {
+        self.layers[index as usize].as_ref().unwrap()
+    }
+
+    fn layer_depth_write(&self, depth_write_enable: bool) -> bool {
+        depth_write_enable && self.render_layer.map_or(true, |index| self.get_layer_recording(index).layer.get_state().depth_write_enable)
+    }
+
+    /// Records a draw in the stats of the current layer and tag. Returns false if the layer is
+    /// disabled and the draw must be skipped.
+    fn record_layer_draw(&mut self, index_count: u32) -> bool {
+        let enabled = self.record_render_layer_draw(index_count);
+        if let (true, Some(tag)) = (enabled, self.draw_tag) {
+            self.tag_stats.entry(tag).or_default().add_draw(index_count);
+        }
+        enabled
+    }
+
+    fn record_render_layer_draw(&mut self, index_count: u32) -> bool {
+        match self.render_layer {
+            Some(index) => {
+                let recording = self.layers[index as usize].as_mut().unwrap();
+                let enabled = recording.layer.is_enabled();
+                if enabled {
+                    recording.stats.add_draw(index_count);
+                } else {
+                    recording.stats.skipped_draw_count += 1;
+                }
+                enabled
+            }
+            None => true,
+        }
+    }
+
+    /// Draws all pending merged draws.
+    fn flush_merged_draws(&mut self) {
+        if let Some(mesh) = self.draw_merger.as_mut().and_then(DrawMerger::flush) {
+            self.draw_merged(mesh);
+        }
+    }
+
+    fn draw_merged(&mut self, mesh: MergedMesh) {
+        let id = self.upload_immediate(&mesh.as_mesh_data());
+        self.draw_immediate_unflushed(id, mesh.key.shader, mesh.key.depth_write_enable);
+    }
+
+    fn log_command<F: FnOnce() -> PassCommand>(&mut self, command: F) {
+        if let Some(log) = &mut self.command_log {
+            log.push(command());
+        }
+    }
+
+    /// Marks a shader as used by this pass and returns its dense index.
+    fn use_shader(&mut self, shader: ShaderId) -> u32 {
+        if let Some((id, index)) = self.last_shader {
+            if id == shader {
+                return index;
+            }
+        }
+
+        let (index, shader_obj) = self.share.get_shader_index(shader).unwrap_or_else(|| {
+            log::error!("Used unknown shader {:?} in pass", shader);
+            panic!()
+        });
+        if self.shaders.len() <= index as usize {
+            self.shaders.resize_with((index as usize) + 1, || None);
+        }
+        if self.shaders[index as usize].is_none() {
             self.pipeline.inc_shader_used(shader);
-            self.share.push_task(WorkerTask::UseShader(shader));
+            self.push_task(WorkerTask::UseShader(shader));
+            self.shaders[index as usize] = Some(PassShader {
+                shader: shader_obj,
+                images: [None, None, None],
+            });
         }
+
+        self.last_shader = Some((shader, index));
+        index
+    }
+
+    fn get_pass_shader(&mut self, index: u32) -> &mut PassShader {
+        self.shaders[index as usize].as_mut().unwrap()
     }
 }
 
 impl Drop for PassRecorder {
     fn drop(&mut self) {
-        self.share.push_task(WorkerTask::EndPass(self.immediate_buffer.take().unwrap()));
+        if !self.portals.is_empty() {
+            log::warn!("Pass ended with {:?} started portals", self.portals.len());
+            while !self.portals.is_empty() {
+                self.end_portal();
+            }
+        }
+        self.flush_merged_draws();
+        self.cull_layer_draws();
+        self.flush_layer_tasks();
+        self.draw_debug_overlays();
+        let end_pass = WorkerTask::EndPass(self.immediate_buffer.take().unwrap(), self.gpu_time_sink.take());
+        self.push_task(end_pass);
+        if let Some(tasks) = self.deferred_tasks.take() {
+            self.share.push_tasks(tasks);
+        }
         self.share.end_pass_id();
+
+        for (_, (image, draw_count)) in self.sampled_images.drain() {
+            image.record_sampled(self.id, draw_count);
+        }
+        let mut layer_stats = Vec::new();
+        for recording in self.layers.drain(..).flatten() {
+            if recording.has_draws() {
+                recording.layer.record_stats(self.id, recording.stats);
+                layer_stats.push((recording.layer.get_id(), recording.stats));
+            }
+        }
+        if let Some(sink) = self.layer_stats_sink.take() {
+            sink(layer_stats);
+        }
+        if let Some(sink) = self.tag_stats_sink.take() {
+            sink(self.tag_stats.drain().collect());
+        }
+        if let Some(sink) = self.budget_report_sink.take() {
+            sink(self.budget_report);
+        }
+
+        if let (Some(log), Some(sink)) = (self.command_log.take(), self.command_log_sink.take()) {
+            sink(log);
+        }
+
+        if let (Some(frame_size), Some(matrices), Some(sink)) = (self.frame_size, self.view_matrices, self.view_matrices_sink.take()) {
+            sink(frame_size, matrices);
+        }
+    }
+}
+
+struct PassShader {
+    shader: Arc<Shader>,
+    /// The images set using [`PassRecorder::update_texture`].
+    images: [Option<Arc<GlobalImage>>; MAX_TEXTURE_SLOTS as usize],
+}
+
+struct LayerRecording {
+    layer: Arc<RenderLayer>,
+    stats: RenderLayerStats,
+    /// The pipeline tasks recorded for the layer. Emitted in render order once the pass ends.
+    tasks: Vec<WorkerTask>,
+    /// The draw tasks in `tasks` used to apply the draw limit of the layer.
+    draws: Vec<LayerDraw>,
+}
+
+impl LayerRecording {
+    fn new(layer: Arc<RenderLayer>) -> Self {
+        Self {
+            layer,
+            stats: RenderLayerStats::default(),
+            tasks: Vec::new(),
+            draws: Vec::new(),
+        }
+    }
+
+    fn has_draws(&self) -> bool {
+        self.stats.draw_count != 0 || self.stats.skipped_draw_count != 0 || self.stats.culled_draw_count != 0
     }
 }
 
+struct OpenPortal {
+    mask: Option<DrawTask>,
+    /// The view matrices of the pass before the portal was started.
+    previous_matrices: Option<ViewMatrices>,
+}
+
+struct LayerDraw {
+    task_index: usize,
+    priority: u8,
+    index_count: u32,
+    tag: Option<u64>,
+}
+
+#[derive(Copy, Clone)]
 struct ImmediateMeshInfo {
     vertex_buffer: vk::Buffer,
     index_buffer: vk::Buffer,
@@ -1,16 +1,23 @@
 use std::hash::Hash;
 use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::ptr::NonNull;
 use std::sync::{Arc, Weak};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use ash::prelude::VkResult;
 
 use ash::vk;
 use bumpalo::Bump;
+use crate::allocator::{Allocation, HostAccess};
 use crate::device::device::Queue;
-use crate::device::device_utils::BlitPass;
+use crate::device::device_group::{DeviceGroup, DeviceGroupMode};
+use crate::device::device_utils::{BlitPass, OutputRotation};
 use crate::device::surface::{AcquiredImageInfo, SurfaceSwapchain};
 
 use crate::prelude::*;
+use crate::renderer::emulator::auto_exposure::{HistogramBuffer, LuminanceHistogramPass};
+use crate::renderer::emulator::color_grading::{ColorGradingPass, ColorGradingState};
 use crate::renderer::emulator::mc_shaders::{McUniformData, ShaderId};
+use crate::renderer::emulator::shadow::CameraFrustum;
 
 pub use super::worker::SubmitRecorder;
 pub use super::worker::PooledObjectProvider;
@@ -72,9 +79,9 @@ pub trait EmulatorPipelinePass {
     /// The queue which will be used to submit command buffers is provided. All resources (i.e.
     /// buffers, images etc.) passed to this pass will be owned by this queue family.
     ///
-    /// A placeholder image is provided which can be used for sampled images. This image must only
-    /// be used in submits made by [`EmulatorPipelinePass::record`].
-    fn init(&mut self, queue: &Queue, obj: &mut PooledObjectProvider, placeholder_image: vk::ImageView, placeholder_sampler: vk::Sampler);
+    /// A placeholder texture is provided which can be used for sampled images. This texture must
+    /// only be used in submits made by [`EmulatorPipelinePass::record`].
+    fn init(&mut self, queue: &Queue, obj: &mut PooledObjectProvider, placeholder_texture: TextureBinding);
 
     /// Called to process a task.
     ///
@@ -106,11 +113,54 @@ pub trait EmulatorPipelinePass {
     fn get_internal_fences(&self, fences: &mut Vec<vk::Fence>);
 }
 
+/// The views and sampler used to sample a global image.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TextureBinding {
+    /// A 2d view of the first layer of the image.
+    pub view: vk::ImageView,
+
+    /// A 2d array view of all layers of the image. Valid for single layer images as well.
+    pub array_view: vk::ImageView,
+
+    pub sampler: vk::Sampler,
+
+    /// The atlas grid of the image if it is an atlas array image or 0 otherwise. The number of
+    /// columns is stored in the low 16 bits and the number of rows in the high 16 bits.
+    pub packed_atlas_grid: u32,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum PipelineTask {
+    /// Sets the partial tick (a value in the range [0, 1] describing the progress between two game
+    /// ticks) used by all following draws. Built-in animations must use this value for
+    /// interpolation instead of expecting it to be baked into uniforms.
+    SetPartialTick(f32),
     UpdateUniform(ShaderId, McUniformData),
-    UpdateTexture(ShaderId, u32, vk::ImageView, vk::Sampler),
+    UpdateTexture(ShaderId, u32, TextureBinding),
+    /// Overrides the texture at some slot for all following draws independent of the shader. If
+    /// [`None`] the per shader texture set by [`PipelineTask::UpdateTexture`] is used again. The
+    /// slot is guaranteed to be smaller than [`MAX_TEXTURE_SLOTS`](crate::renderer::emulator::pass::MAX_TEXTURE_SLOTS).
+    BindTexture(u32, Option<TextureBinding>),
+    /// Sets the viewport at some index. Viewports which have not been set cover the full output.
+    /// The index is guaranteed to be smaller than [`MAX_VIEWPORTS`](crate::renderer::emulator::pass::MAX_VIEWPORTS).
+    SetViewport(u32, vk::Viewport),
+    /// Enables cascaded shadow maps for all following draws. The vector is the normalized
+    /// direction the light travels in. See [`shadow`](crate::renderer::emulator::shadow).
+    /// The matrix is the view space transform of the camera effects the cascades are fitted with.
+    SetShadowCamera(CameraFrustum, Vec3f32, Mat4f32),
+    /// Restricts all following draws to a rect in addition to their viewport. If [`None`] draws
+    /// are only restricted by their viewport.
+    SetScissor(Option<vk::Rect2D>),
     Draw(DrawTask),
+    /// Starts a portal using the draw as its mask. All following draws until the matching
+    /// [`PipelineTask::EndPortal`] must only be visible inside the visible parts of the mask and
+    /// must be depth tested as if nothing had been drawn behind the mask. Portals may be nested up
+    /// to [`MAX_PORTAL_DEPTH`](crate::renderer::emulator::pass::MAX_PORTAL_DEPTH) levels. If
+    /// [`None`] the mask is empty and no draw inside the portal is visible.
+    BeginPortal(Option<DrawTask>),
+    /// Ends the innermost portal. The draw is the mask of the portal and must write the depth of
+    /// the mask surface so that later draws are occluded by the portal.
+    EndPortal(Option<DrawTask>),
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
@@ -124,6 +174,10 @@ pub struct DrawTask {
     pub shader: ShaderId,
     pub primitive_topology: vk::PrimitiveTopology,
     pub depth_write_enable: bool,
+    /// The index of the viewport to render to.
+    pub viewport_index: u32,
+    /// The tag set using [`PassRecorder::set_draw_tag`](crate::renderer::emulator::PassRecorder::set_draw_tag).
+    pub tag: Option<u64>,
 }
 
 /// Used to process the output of a [`EmulatorPipelinePass`].
@@ -146,29 +200,53 @@ pub trait EmulatorOutput {
     fn on_post_submit(&mut self, queue: &Queue);
 }
 
-/// A utility struct providing a [`BlitPass`] for the output of a [`EmulatorPipeline`].
+/// A utility struct providing a [`BlitPass`] for the output of a [`EmulatorPipeline`]. If color
+/// grading is used a [`ColorGradingPass`] is recorded instead, preceded by a
+/// [`LuminanceHistogramPass`] if auto exposure is enabled.
+///
+/// The blit and grading passes apply the [`OutputRotation`] the util was created with. The output
+/// size passed to the record functions is the size of the (rotated) framebuffer.
 pub struct OutputUtil {
     #[allow(unused)] // We just need to keep the pipeline alive
     pipeline: Arc<dyn EmulatorPipeline>,
+    sampler_views: Box<[vk::ImageView]>,
+    source_size: Vec2u32,
     descriptor_pool: vk::DescriptorPool,
     descriptor_sets: Box<[vk::DescriptorSet]>,
     blit_pass: BlitPass,
+    grading_pass: ColorGradingPass,
+    histogram_pass: LuminanceHistogramPass,
+    /// Set if the device group renders in split frame mode. In this case the output is cleared
+    /// and each device only blits its own strip so the presentation engine can sum the images.
+    split_frame_group: Option<DeviceGroup>,
 }
 
 impl OutputUtil {
-    pub fn new(device: &DeviceContext, pipeline: Arc<dyn EmulatorPipeline>, format: vk::Format, final_layout: vk::ImageLayout) -> Self {
-        let (_, sampler_views) = pipeline.get_output();
+    pub fn new(device: &DeviceContext, pipeline: Arc<dyn EmulatorPipeline>, format: vk::Format, final_layout: vk::ImageLayout, rotation: OutputRotation) -> Self {
+        let (source_size, sampler_views) = pipeline.get_output();
 
-        let blit_pass = device.get_utils().blit_utils().create_blit_pass(format, vk::AttachmentLoadOp::DONT_CARE, vk::ImageLayout::UNDEFINED, final_layout);
+        let split_frame_group = device.get_device_group().filter(|group| group.get_mode() == DeviceGroupMode::SplitFrame).cloned();
+        let load_op = if split_frame_group.is_some() { vk::AttachmentLoadOp::CLEAR } else { vk::AttachmentLoadOp::DONT_CARE };
+
+        let blit_pass = device.get_utils().blit_utils().create_blit_pass(format, load_op, vk::ImageLayout::UNDEFINED, final_layout, rotation);
 
         let descriptor_pool = Self::create_descriptor_pool(device, sampler_views.len());
         let descriptor_sets = blit_pass.create_descriptor_sets(descriptor_pool, sampler_views).unwrap().into_boxed_slice();
 
+        let grading_pass = ColorGradingPass::new(device.get_functions().clone(), format, load_op, vk::ImageLayout::UNDEFINED, final_layout, rotation);
+        let histogram_pass = LuminanceHistogramPass::new(device.get_functions().clone(), device.get_allocator().clone());
+        let sampler_views = sampler_views.into();
+
         Self {
             pipeline,
+            sampler_views,
+            source_size,
             descriptor_pool,
             descriptor_sets,
-            blit_pass
+            blit_pass,
+            grading_pass,
+            histogram_pass,
+            split_frame_group,
         }
     }
 
@@ -184,13 +262,50 @@ impl OutputUtil {
     ///
     /// The pipeline index is the index returned by [`EmulatorPipelinePass::get_output_index`].
     pub fn record(&self, command_buffer: vk::CommandBuffer, output_framebuffer: vk::Framebuffer, output_size: Vec2u32, pipeline_index: usize) {
-        self.blit_pass.record_blit(
-            command_buffer,
-            self.descriptor_sets[pipeline_index],
-            output_framebuffer,
-            output_size,
-            None
-        )
+        self.record_graded(command_buffer, output_framebuffer, output_size, pipeline_index, None, None, 0)
+    }
+
+    /// Records one execution of the blit pass applying color grading if a state is provided.
+    ///
+    /// If a histogram buffer is provided the luminance histogram of the pipeline output is
+    /// recorded into it before the grading. It must only be provided if the grading uses auto
+    /// exposure.
+    ///
+    /// The device mask must be the mask returned by [`PooledObjectProvider::get_device_mask`].
+    pub(crate) fn record_graded(&self, command_buffer: vk::CommandBuffer, output_framebuffer: vk::Framebuffer, output_size: Vec2u32, pipeline_index: usize, grading: Option<&ColorGradingState>, histogram: Option<&HistogramBuffer>, device_mask: u32) {
+        let clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0f32, 0f32, 0f32, 0f32],
+            }
+        };
+        let (clear_value, device_scissors) = match &self.split_frame_group {
+            Some(group) => (Some(&clear_value), if device_mask != 0 { Some(group.get_split_areas(output_size)) } else { None }),
+            None => (None, None),
+        };
+
+        if let Some(grading) = grading {
+            if let (Some(histogram), Some((settings, _))) = (histogram, grading.get_auto_exposure()) {
+                self.histogram_pass.record(command_buffer, self.sampler_views[pipeline_index], self.source_size, histogram, settings);
+            }
+            self.grading_pass.record(
+                command_buffer,
+                self.sampler_views[pipeline_index],
+                output_framebuffer,
+                output_size,
+                grading,
+                clear_value,
+                device_scissors.as_deref()
+            )
+        } else {
+            self.blit_pass.record_blit(
+                command_buffer,
+                self.descriptor_sets[pipeline_index],
+                output_framebuffer,
+                output_size,
+                clear_value,
+                device_scissors.as_deref()
+            )
+        }
     }
 
     fn create_descriptor_pool(device: &DeviceContext, sampler_count: usize) -> vk::DescriptorPool {
@@ -221,6 +336,10 @@ impl Drop for OutputUtil {
 
 /// A [`EmulatorOutput`] implementation which copes the output image to a swapchain image and
 /// presents it.
+///
+/// If the swapchain uses a rotated surface transform the image is pre-rotated while it is copied
+/// so the pipeline always renders at the unrotated size returned by
+/// [`SurfaceSwapchain::get_logical_size`].
 pub struct SwapchainOutput {
     weak: Weak<Self>,
     swapchain: Arc<SurfaceSwapchain>,
@@ -230,7 +349,7 @@ pub struct SwapchainOutput {
 
 impl SwapchainOutput {
     pub fn new(device: &DeviceContext, pipeline: Arc<dyn EmulatorPipeline>, swapchain: Arc<SurfaceSwapchain>) -> Arc<Self> {
-        let util = OutputUtil::new(device, pipeline, swapchain.get_image_format().format, vk::ImageLayout::PRESENT_SRC_KHR);
+        let util = OutputUtil::new(device, pipeline, swapchain.get_image_format().format, vk::ImageLayout::PRESENT_SRC_KHR, swapchain.get_rotation());
 
         let framebuffers = swapchain.get_images().iter().map(|image| {
             util.create_framebuffer(image.get_framebuffer_view(), swapchain.get_image_size()).unwrap()
@@ -251,13 +370,28 @@ impl SwapchainOutput {
     /// If it successfully acquires a image returns a [`EmulatorOutput`] instance for the image as
     /// well as a boolean flag set to true if the swapchain is suboptimal.
     pub fn next_image(&self) -> Option<(Box<dyn EmulatorOutput + Send>, bool)> {
+        self.next_image_graded(None)
+    }
+
+    /// Like [`SwapchainOutput::next_image`] but applies color grading to the image. Any LUT used
+    /// by the grading must be used by the pass the output is used in.
+    pub(crate) fn next_image_graded(&self, grading: Option<ColorGradingState>) -> Option<(Box<dyn EmulatorOutput + Send>, bool)> {
         loop {
             let arc = self.weak.upgrade().unwrap();
             match self.swapchain.acquire_next_image(1000000000, None) {
                 Ok((info, suboptimal)) =>
-                    return Some((Box::new(SwapchainOutputInstance::new(arc, info)), suboptimal)),
+                    return Some((Box::new(SwapchainOutputInstance::new(arc, info, grading)), suboptimal)),
                 Err(vk::Result::TIMEOUT) =>
                     log::warn!("1s timeout reached while waiting for next swapchain image in SwapchainOutput::next_image"),
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) |
+                Err(vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT) |
+                Err(vk::Result::ERROR_SURFACE_LOST_KHR) |
+                Err(vk::Result::ERROR_DEVICE_LOST) => {
+                    // Lost surfaces and devices are recorded by the surface and are handled by the caller.
+                    // Out of date swapchains and lost full screen exclusive mode cause the swapchain
+                    // to be recreated.
+                    return None;
+                }
                 Err(err) => {
                     log::error!("vkAcquireNextImageKHR returned {:?} in SwapchainOutput::next_image", err);
                     panic!()
@@ -281,15 +415,23 @@ impl Drop for SwapchainOutput {
 struct SwapchainOutputInstance {
     output: Arc<SwapchainOutput>,
     image_info: AcquiredImageInfo,
+    grading: Option<ColorGradingState>,
+    histogram: Option<HistogramBuffer>,
     pipeline_index: Option<usize>,
+    device_mask: u32,
+    submitted: bool,
 }
 
 impl SwapchainOutputInstance {
-    fn new(output: Arc<SwapchainOutput>, image_info: AcquiredImageInfo) -> Self {
+    fn new(output: Arc<SwapchainOutput>, image_info: AcquiredImageInfo, grading: Option<ColorGradingState>) -> Self {
         Self {
             output,
             image_info,
+            grading,
+            histogram: None,
             pipeline_index: None,
+            device_mask: 0,
+            submitted: false,
         }
     }
 }
@@ -302,7 +444,11 @@ impl EmulatorOutput for SwapchainOutputInstance {
     fn record<'a>(&mut self, obj: &mut PooledObjectProvider, submits: &mut SubmitRecorder<'a>, alloc: &'a Bump) {
         let cmd = obj.get_begin_command_buffer().unwrap();
 
-        self.output.util.record(cmd, self.output.framebuffers[self.image_info.image_index as usize], self.output.swapchain.get_image_size(), self.pipeline_index.unwrap());
+        self.device_mask = obj.get_device_mask();
+        if self.grading.as_ref().and_then(ColorGradingState::get_auto_exposure).is_some() {
+            self.histogram = Some(self.output.util.histogram_pass.acquire_buffer());
+        }
+        self.output.util.record_graded(cmd, self.output.framebuffers[self.image_info.image_index as usize], self.output.swapchain.get_image_size(), self.pipeline_index.unwrap(), self.grading.as_ref(), self.histogram.as_ref(), self.device_mask);
 
         unsafe {
             self.output.swapchain.get_device().vk.end_command_buffer(cmd)
@@ -315,19 +461,24 @@ impl EmulatorOutput for SwapchainOutputInstance {
                 .build()
         ]);
 
-        let signals = alloc.alloc([
-            vk::SemaphoreSubmitInfo::builder()
-                .semaphore(self.image_info.acquire_ready_semaphore.semaphore.get_handle())
-                .value(self.image_info.acquire_ready_semaphore.value.unwrap_or(0))
-                .build(),
-            vk::SemaphoreSubmitInfo::builder()
-                .semaphore(self.output.swapchain.get_images()[self.image_info.image_index as usize].get_present_semaphore().get_handle())
-                .build()
-        ]);
+        let present_signal = vk::SemaphoreSubmitInfo::builder()
+            .semaphore(self.output.swapchain.get_images()[self.image_info.image_index as usize].get_present_semaphore().get_handle())
+            .build();
+        let signals: &[vk::SemaphoreSubmitInfo] = match &self.image_info.acquire_ready_semaphore {
+            Some(ready) => alloc.alloc([
+                vk::SemaphoreSubmitInfo::builder()
+                    .semaphore(ready.semaphore.get_handle())
+                    .value(ready.value.unwrap_or(0))
+                    .build(),
+                present_signal
+            ]),
+            None => alloc.alloc([present_signal]),
+        };
 
         let commands = alloc.alloc([
             vk::CommandBufferSubmitInfo::builder()
                 .command_buffer(cmd)
+                .device_mask(submits.get_device_mask())
                 .build()
         ]);
 
@@ -339,17 +490,369 @@ impl EmulatorOutput for SwapchainOutputInstance {
     }
 
     fn on_post_submit(&mut self, queue: &Queue) {
+        self.submitted = true;
+
+        // Without timeline semaphores an empty submission signals the fence once the acquire
+        // semaphore has been waited on
+        if let Some(fence) = self.image_info.acquire_ready_fence {
+            unsafe {
+                queue.submit_2(&[], Some(fence))
+            }.unwrap();
+        }
+
         let present_semaphore = self.output.swapchain.get_images()[self.image_info.image_index as usize].get_present_semaphore().get_handle();
 
+        let present_id = self.output.swapchain.begin_present();
+        let mut present_id_info = present_id.as_ref().map(|id| {
+            vk::PresentIdKHR::builder()
+                .present_ids(std::slice::from_ref(id))
+        });
+
+        let present_time = self.output.swapchain.begin_display_timing();
+        let mut present_time_info = present_time.as_ref().map(|time| {
+            vk::PresentTimesInfoGOOGLE::builder()
+                .times(std::slice::from_ref(time))
+        });
+
         let guard = self.output.swapchain.get_swapchain().lock().unwrap();
 
-        let present_info = vk::PresentInfoKHR::builder()
+        let mut present_info = vk::PresentInfoKHR::builder()
             .wait_semaphores(std::slice::from_ref(&present_semaphore))
             .swapchains(std::slice::from_ref(&*guard))
             .image_indices(std::slice::from_ref(&self.image_info.image_index));
 
-        unsafe {
+        if let Some(present_id_info) = present_id_info.as_mut() {
+            present_info = present_info.push_next(present_id_info);
+        }
+        if let Some(present_time_info) = present_time_info.as_mut() {
+            present_info = present_info.push_next(present_time_info);
+        }
+
+        let device_group_mode = self.output.swapchain.get_device().device_group.as_ref().map(|group| group.get_mode().get_required_present_mode());
+        let mut device_group_info = device_group_mode.filter(|_| self.device_mask != 0).map(|mode| {
+            vk::DeviceGroupPresentInfoKHR::builder()
+                .device_masks(std::slice::from_ref(&self.device_mask))
+                .mode(mode)
+        });
+        if let Some(device_group_info) = device_group_info.as_mut() {
+            present_info = present_info.push_next(device_group_info);
+        }
+
+        let result = unsafe {
             queue.present(&present_info)
+        };
+        match self.output.swapchain.get_surface().check_surface_lost(result) {
+            Ok(_) => {},
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) |
+            Err(vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT) |
+            Err(vk::Result::ERROR_SURFACE_LOST_KHR) |
+            Err(vk::Result::ERROR_DEVICE_LOST) => {
+                // The next acquire will fail and cause the swapchain or device to be recreated.
+            }
+            Err(err) => {
+                log::error!("vkQueuePresentKHR returned {:?} in SwapchainOutputInstance::on_post_submit", err);
+                panic!()
+            }
+        }
+    }
+}
+
+impl Drop for SwapchainOutputInstance {
+    fn drop(&mut self) {
+        // Outputs are only dropped once all submissions have completed execution so the histogram
+        // is safe to read here.
+        if let Some(histogram) = self.histogram.take() {
+            if self.submitted {
+                if let Some((settings, adaptation)) = self.grading.as_ref().and_then(ColorGradingState::get_auto_exposure) {
+                    adaptation.update(settings, histogram.read());
+                }
+            }
+            self.output.util.histogram_pass.release_buffer(histogram);
+        }
+    }
+}
+
+/// A [`EmulatorOutput`] implementation which copies the output image into host memory.
+///
+/// This can be used to render without a window for example to take screenshots or for tests.
+pub struct OffscreenOutput {
+    weak: Weak<Self>,
+    device: Arc<DeviceContext>,
+    util: OutputUtil,
+    size: Vec2u32,
+}
+
+impl OffscreenOutput {
+    /// The format of the pixel data returned by [`OffscreenReadback::wait`].
+    pub const FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
+    pub fn new(device: Arc<DeviceContext>, pipeline: Arc<dyn EmulatorPipeline>, size: Vec2u32) -> Arc<Self> {
+        let util = OutputUtil::new(&device, pipeline, Self::FORMAT, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, OutputRotation::None);
+
+        Arc::new_cyclic(|weak| Self {
+            weak: weak.clone(),
+            device,
+            util,
+            size,
+        })
+    }
+
+    pub fn get_size(&self) -> Vec2u32 {
+        self.size
+    }
+
+    /// Creates a new [`EmulatorOutput`] instance as well as a [`OffscreenReadback`] which can be
+    /// used to retrieve the pixels once the pass using the output has completed execution.
+    pub fn next_output(&self) -> (Box<dyn EmulatorOutput + Send>, OffscreenReadback) {
+        let arc = self.weak.upgrade().unwrap();
+        let (sender, receiver) = channel();
+
+        let instance = OffscreenOutputInstance::new(arc, sender).unwrap_or_else(|err| {
+            log::error!("Failed to create offscreen output resources {:?}", err);
+            panic!()
+        });
+
+        (Box::new(instance), OffscreenReadback { size: self.size, receiver })
+    }
+}
+
+/// Used to retrieve the result of a [`OffscreenOutput`].
+pub struct OffscreenReadback {
+    size: Vec2u32,
+    receiver: Receiver<Box<[u8]>>,
+}
+
+impl OffscreenReadback {
+    pub fn get_size(&self) -> Vec2u32 {
+        self.size
+    }
+
+    /// Blocks until the pass has completed execution and returns the tightly packed rgba pixel data
+    /// of the output in [`OffscreenOutput::FORMAT`].
+    ///
+    /// Returns [`None`] if the pass was aborted.
+    pub fn wait(self) -> Option<Box<[u8]>> {
+        self.receiver.recv().ok()
+    }
+}
+
+struct OffscreenOutputInstance {
+    output: Arc<OffscreenOutput>,
+    image: vk::Image,
+    image_allocation: Option<Allocation>,
+    image_view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    buffer: vk::Buffer,
+    buffer_allocation: Option<Allocation>,
+    mapped: NonNull<u8>,
+    pipeline_index: Option<usize>,
+    submitted: bool,
+    sender: Sender<Box<[u8]>>,
+}
+
+impl OffscreenOutputInstance {
+    fn new(output: Arc<OffscreenOutput>, sender: Sender<Box<[u8]>>) -> VkResult<Self> {
+        let device = output.device.clone();
+        let size = output.size;
+
+        let info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(OffscreenOutput::FORMAT)
+            .extent(vk::Extent3D {
+                width: size[0],
+                height: size[1],
+                depth: 1
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        let (image, image_allocation) = unsafe {
+            device.get_allocator().create_gpu_image(&info, &format_args!("OffscreenOutputImage"))
+        }.ok_or(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY)?;
+
+        let info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(OffscreenOutput::FORMAT)
+            .components(vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1
+            });
+
+        let image_view = match unsafe { device.vk().create_image_view(&info, None) } {
+            Ok(view) => view,
+            Err(err) => {
+                unsafe { device.get_allocator().destroy_image(image, image_allocation) };
+                return Err(err);
+            }
+        };
+
+        let framebuffer = match output.util.create_framebuffer(image_view, size) {
+            Ok(framebuffer) => framebuffer,
+            Err(err) => {
+                unsafe {
+                    device.vk().destroy_image_view(image_view, None);
+                    device.get_allocator().destroy_image(image, image_allocation);
+                }
+                return Err(err);
+            }
+        };
+
+        let info = vk::BufferCreateInfo::builder()
+            .size((size[0] as vk::DeviceSize) * (size[1] as vk::DeviceSize) * 4)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let (buffer, buffer_allocation, mapped) = match unsafe {
+            device.get_allocator().create_buffer(&info, HostAccess::Random, &format_args!("OffscreenOutputBuffer"))
+        } {
+            Some(result) => result,
+            None => {
+                unsafe {
+                    device.vk().destroy_framebuffer(framebuffer, None);
+                    device.vk().destroy_image_view(image_view, None);
+                    device.get_allocator().destroy_image(image, image_allocation);
+                }
+                return Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY);
+            }
+        };
+
+        Ok(Self {
+            output,
+            image,
+            image_allocation: Some(image_allocation),
+            image_view,
+            framebuffer,
+            buffer,
+            buffer_allocation: Some(buffer_allocation),
+            mapped: mapped.unwrap(),
+            pipeline_index: None,
+            submitted: false,
+            sender,
+        })
+    }
+}
+
+impl EmulatorOutput for OffscreenOutputInstance {
+    fn init(&mut self, pass: &dyn EmulatorPipelinePass, _: &mut PooledObjectProvider) {
+        self.pipeline_index = Some(pass.get_output_index());
+    }
+
+    fn record<'a>(&mut self, obj: &mut PooledObjectProvider, submits: &mut SubmitRecorder<'a>, alloc: &'a Bump) {
+        let device = &self.output.device;
+        let size = self.output.size;
+        let cmd = obj.get_begin_command_buffer().unwrap();
+
+        self.output.util.record(cmd, self.framebuffer, size, self.pipeline_index.unwrap());
+
+        let image_barrier = [
+            vk::ImageMemoryBarrier2::builder()
+                .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::TRANSFER)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_READ)
+                .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .image(self.image)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1
+                })
+                .build()
+        ];
+        let info = vk::DependencyInfo::builder()
+            .image_memory_barriers(&image_barrier);
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1
+            })
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D { width: size[0], height: size[1], depth: 1 });
+
+        let buffer_barrier = [
+            vk::BufferMemoryBarrier2::builder()
+                .src_stage_mask(vk::PipelineStageFlags2::TRANSFER)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::HOST)
+                .dst_access_mask(vk::AccessFlags2::HOST_READ)
+                .buffer(self.buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .build()
+        ];
+        let host_info = vk::DependencyInfo::builder()
+            .buffer_memory_barriers(&buffer_barrier);
+
+        unsafe {
+            device.cmd_pipeline_barrier2(cmd, &info);
+            device.vk().cmd_copy_image_to_buffer(cmd, self.image, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, self.buffer, std::slice::from_ref(&region));
+            device.cmd_pipeline_barrier2(cmd, &host_info);
+            device.vk().end_command_buffer(cmd)
         }.unwrap();
+
+        let commands = alloc.alloc([
+            vk::CommandBufferSubmitInfo::builder()
+                .command_buffer(cmd)
+                .device_mask(submits.get_device_mask())
+                .build()
+        ]);
+
+        submits.push(vk::SubmitInfo2::builder()
+            .command_buffer_infos(commands)
+        );
+    }
+
+    fn on_post_submit(&mut self, _: &Queue) {
+        self.submitted = true;
     }
-}
\ No newline at end of file
+}
+
+impl Drop for OffscreenOutputInstance {
+    fn drop(&mut self) {
+        // Outputs are only dropped once all submissions have completed execution so the buffer is
+        // safe to read here.
+        if self.submitted {
+            let size = self.output.size;
+            let len = (size[0] as usize) * (size[1] as usize) * 4;
+            let data = unsafe { std::slice::from_raw_parts(self.mapped.as_ptr(), len) };
+
+            // The receiver may have been dropped in which case we just discard the data
+            let _ = self.sender.send(Box::from(data));
+        }
+
+        let device = &self.output.device;
+        unsafe {
+            device.vk().destroy_framebuffer(self.framebuffer, None);
+            device.vk().destroy_image_view(self.image_view, None);
+            device.get_allocator().destroy_image(self.image, self.image_allocation.take().unwrap());
+            device.get_allocator().destroy_buffer(self.buffer, self.buffer_allocation.take().unwrap());
+        }
+    }
+}
+
+unsafe impl Send for OffscreenOutputInstance { // Needed because of NonNull<u8>
+}
@@ -0,0 +1,376 @@
+//! Support for vanilla style post-process chains which only transform colors.
+//!
+//! Minecraft describes fullscreen effects like the creeper, spider and enderman spectator vision
+//! as a json chain of passes each running a program from one target into another. B4D does not
+//! run the vanilla programs. Only color-matrix chains are supported, that is chains built from
+//! programs which can be expressed as an affine color transform. These are collapsed into a single
+//! color matrix which is applied by the color grading stage of the output.
+//!
+//! Supported programs are `blit`, `color_convolve` and `invert`. Chains using any other program
+//! (for example the spider or blur programs) are rejected by [`PostChain::resolve_color_matrix`]
+//! and chains using auxiliary targets are rejected by [`PostChain::parse`].
+//!
+//! Built-in [`AccessibilityFilter`]s are applied after the active chain through the same stage.
+//! The built-in [`ScreenDistortion`] of the nausea effect and nether portals warps the texture
+//! coordinates used by the stage to read the rendered frame.
+
+use crate::renderer::emulator::color_grading::{compose_color_matrices, ColorMatrix, IDENTITY_COLOR_MATRIX};
+
+use crate::prelude::*;
+
+define_uuid_type!(pub, PostChainId);
+
+/// The name of the target containing the rendered frame.
+pub const MAIN_TARGET: &str = "minecraft:main";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PostChainUniform {
+    pub name: String,
+    pub values: Vec<f32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PostChainPass {
+    pub program: String,
+    pub in_target: String,
+    pub out_target: String,
+    pub uniforms: Vec<PostChainUniform>,
+}
+
+impl PostChainPass {
+    fn get_uniform(&self, name: &str) -> Option<&[f32]> {
+        self.uniforms.iter().find(|u| u.name == name).map(|u| u.values.as_slice())
+    }
+
+    fn get_vec3(&self, name: &str, default: [f32; 3]) -> Result<[f32; 3], PostChainError> {
+        match self.get_uniform(name) {
+            None => Ok(default),
+            Some(&[v]) => Ok([v; 3]),
+            Some(&[x, y, z, ..]) => Ok([x, y, z]),
+            Some(_) => Err(PostChainError::InvalidUniform(name.to_string())),
+        }
+    }
+
+    fn get_float(&self, name: &str, default: f32) -> Result<f32, PostChainError> {
+        match self.get_uniform(name) {
+            None => Ok(default),
+            Some(&[v, ..]) => Ok(v),
+            Some(_) => Err(PostChainError::InvalidUniform(name.to_string())),
+        }
+    }
+
+    /// Returns the color matrix equivalent to running this pass.
+    fn to_color_matrix(&self) -> Result<ColorMatrix, PostChainError> {
+        let program = self.program.strip_prefix("minecraft:").unwrap_or(&self.program);
+        match program {
+            "blit" => {
+                let modulate = self.get_vec3("ColorModulate", [1f32; 3])?;
+                Ok([
+                    [modulate[0], 0f32, 0f32, 0f32],
+                    [0f32, modulate[1], 0f32, 0f32],
+                    [0f32, 0f32, modulate[2], 0f32],
+                ])
+            }
+            "color_convolve" => {
+                let rows = [
+                    self.get_vec3("RedMatrix", [1f32, 0f32, 0f32])?,
+                    self.get_vec3("GreenMatrix", [0f32, 1f32, 0f32])?,
+                    self.get_vec3("BlueMatrix", [0f32, 0f32, 1f32])?,
+                ];
+                let offset = self.get_vec3("Offset", [0f32; 3])?;
+                let scale = self.get_vec3("ColorScale", [1f32; 3])?;
+                let saturation = self.get_float("Saturation", 1f32)?;
+
+                let mut convolve = IDENTITY_COLOR_MATRIX;
+                for (index, row) in convolve.iter_mut().enumerate() {
+                    *row = [rows[index][0] * scale[index], rows[index][1] * scale[index], rows[index][2] * scale[index], offset[index]];
+                }
+
+                // Same luma weights as the vanilla shader
+                let gray = [0.3f32, 0.59f32, 0.11f32];
+                let mut saturate = IDENTITY_COLOR_MATRIX;
+                for (index, row) in saturate.iter_mut().enumerate() {
+                    for column in 0..3 {
+                        let identity = if column == index { 1f32 } else { 0f32 };
+                        row[column] = identity * saturation + gray[column] * (1f32 - saturation);
+                    }
+                }
+
+                Ok(compose_color_matrices(&convolve, &saturate))
+            }
+            "invert" => {
+                let amount = self.get_float("InverseAmount", 1f32)?;
+                let diagonal = 1f32 - 2f32 * amount;
+                Ok([
+                    [diagonal, 0f32, 0f32, amount],
+                    [0f32, diagonal, 0f32, amount],
+                    [0f32, 0f32, diagonal, amount],
+                ])
+            }
+            _ => Err(PostChainError::UnsupportedProgram(self.program.clone())),
+        }
+    }
+}
+
+/// A parsed color-matrix post-process chain.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PostChain {
+    pub targets: Vec<String>,
+    pub passes: Vec<PostChainPass>,
+}
+
+impl PostChain {
+    /// Parses a chain from the vanilla json format. Passes sampling auxiliary targets can never be
+    /// collapsed into a color matrix and are rejected.
+    pub fn parse(source: &str) -> Result<Self, PostChainError> {
+        let root = json::parse(source).map_err(|err| PostChainError::InvalidJson(err.to_string()))?;
+
+        let mut targets = Vec::new();
+        for target in root["targets"].members() {
+            let name = if target.is_object() { target["name"].as_str() } else { target.as_str() };
+            targets.push(name.ok_or(PostChainError::MissingField("targets.name"))?.to_string());
+        }
+
+        let mut passes = Vec::new();
+        for pass in root["passes"].members() {
+            let program = pass["name"].as_str().ok_or(PostChainError::MissingField("passes.name"))?.to_string();
+            let in_target = pass["intarget"].as_str().ok_or(PostChainError::MissingField("passes.intarget"))?.to_string();
+            let out_target = pass["outtarget"].as_str().ok_or(PostChainError::MissingField("passes.outtarget"))?.to_string();
+
+            if !pass["auxtargets"].is_empty() {
+                return Err(PostChainError::UnsupportedAuxTargets(program));
+            }
+
+            let mut uniforms = Vec::new();
+            for uniform in pass["uniforms"].members() {
+                let name = uniform["name"].as_str().ok_or(PostChainError::MissingField("passes.uniforms.name"))?.to_string();
+                let values = uniform["values"].members()
+                    .map(|v| v.as_f32().ok_or_else(|| PostChainError::InvalidUniform(name.clone())))
+                    .collect::<Result<Vec<_>, _>>()?;
+                uniforms.push(PostChainUniform { name, values });
+            }
+
+            passes.push(PostChainPass {
+                program,
+                in_target,
+                out_target,
+                uniforms,
+            });
+        }
+
+        Ok(Self {
+            targets,
+            passes,
+        })
+    }
+
+    /// Collapses the chain into a single color matrix applied to the main target.
+    pub fn resolve_color_matrix(&self) -> Result<ColorMatrix, PostChainError> {
+        let mut transforms: Vec<(&str, ColorMatrix)> = vec![(MAIN_TARGET, IDENTITY_COLOR_MATRIX)];
+
+        for pass in &self.passes {
+            let input = transforms.iter().find(|(name, _)| *name == pass.in_target)
+                .map(|(_, matrix)| *matrix)
+                .ok_or_else(|| PostChainError::UnknownTarget(pass.in_target.clone()))?;
+
+            if pass.out_target != MAIN_TARGET && !self.targets.contains(&pass.out_target) {
+                return Err(PostChainError::UnknownTarget(pass.out_target.clone()));
+            }
+
+            let output = compose_color_matrices(&input, &pass.to_color_matrix()?);
+            match transforms.iter_mut().find(|(name, _)| *name == pass.out_target) {
+                Some((_, matrix)) => *matrix = output,
+                None => transforms.push((&pass.out_target, output)),
+            }
+        }
+
+        Ok(transforms[0].1)
+    }
+}
+
+/// The types of color vision deficiency supported by [`AccessibilityFilter`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ColorBlindness {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorBlindness {
+    /// Returns the matrix simulating the deficiency in linear RGB (Machado et al. 2009 at full
+    /// severity).
+    pub fn get_simulation_matrix(&self) -> ColorMatrix {
+        match self {
+            Self::Protanopia => [
+                [0.152286f32, 1.052583f32, -0.204868f32, 0f32],
+                [0.114503f32, 0.786281f32, 0.099216f32, 0f32],
+                [-0.003882f32, -0.048116f32, 1.051998f32, 0f32],
+            ],
+            Self::Deuteranopia => [
+                [0.367322f32, 0.860646f32, -0.227968f32, 0f32],
+                [0.280085f32, 0.672501f32, 0.047413f32, 0f32],
+                [-0.011820f32, 0.042940f32, 0.968881f32, 0f32],
+            ],
+            Self::Tritanopia => [
+                [1.255528f32, -0.076749f32, -0.178779f32, 0f32],
+                [-0.078411f32, 0.930809f32, 0.147602f32, 0f32],
+                [0.004733f32, 0.691367f32, 0.303900f32, 0f32],
+            ],
+        }
+    }
+
+    /// Returns the matrix applying daltonization. The color information lost by the deficiency is
+    /// shifted into channels which can still be distinguished.
+    pub fn get_correction_matrix(&self) -> ColorMatrix {
+        let shift: [[f32; 3]; 3] = match self {
+            Self::Protanopia | Self::Deuteranopia => [
+                [0f32, 0f32, 0f32],
+                [0.7f32, 1f32, 0f32],
+                [0.7f32, 0f32, 1f32],
+            ],
+            Self::Tritanopia => [
+                [1f32, 0f32, 0.7f32],
+                [0f32, 1f32, 0.7f32],
+                [0f32, 0f32, 0f32],
+            ],
+        };
+
+        // color + shift * (color - simulate(color))
+        let simulation = self.get_simulation_matrix();
+        let mut result = IDENTITY_COLOR_MATRIX;
+        for row in 0..3 {
+            for column in 0..3 {
+                for k in 0..3 {
+                    let error = IDENTITY_COLOR_MATRIX[k][column] - simulation[k][column];
+                    result[row][column] += shift[row][k] * error;
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Built-in post-process filters for color blind players.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum AccessibilityFilter {
+    None,
+    /// Shows the image as seen with the deficiency.
+    Simulate(ColorBlindness),
+    /// Adjusts the image to make colors distinguishable with the deficiency.
+    Correct(ColorBlindness),
+}
+
+impl AccessibilityFilter {
+    /// Maps the values used by the c api. 0 is none, 1 to 3 simulate and 4 to 6 correct
+    /// protanopia, deuteranopia and tritanopia respectively.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::None),
+            1 => Some(Self::Simulate(ColorBlindness::Protanopia)),
+            2 => Some(Self::Simulate(ColorBlindness::Deuteranopia)),
+            3 => Some(Self::Simulate(ColorBlindness::Tritanopia)),
+            4 => Some(Self::Correct(ColorBlindness::Protanopia)),
+            5 => Some(Self::Correct(ColorBlindness::Deuteranopia)),
+            6 => Some(Self::Correct(ColorBlindness::Tritanopia)),
+            _ => None,
+        }
+    }
+
+    /// Returns the matrix applied by this filter or [`None`] if the filter does nothing.
+    pub fn get_matrix(&self) -> Option<ColorMatrix> {
+        match self {
+            Self::None => None,
+            Self::Simulate(blindness) => Some(blindness.get_simulation_matrix()),
+            Self::Correct(blindness) => Some(blindness.get_correction_matrix()),
+        }
+    }
+
+    /// Appends this filter to the matrix of a post chain.
+    pub fn apply_to(&self, post_matrix: Option<&ColorMatrix>) -> Option<ColorMatrix> {
+        match (post_matrix, self.get_matrix()) {
+            (Some(post_matrix), Some(filter)) => Some(compose_color_matrices(post_matrix, &filter)),
+            (Some(post_matrix), None) => Some(*post_matrix),
+            (None, filter) => filter,
+        }
+    }
+}
+
+/// The built-in wobbling screen distortion of the nausea effect and nether portals.
+///
+/// The distortion is applied to the finished frame. Unlike the camera effects of
+/// [`camera_effects`](crate::renderer::emulator::camera_effects) it does not modify any projection
+/// matrix and also distorts the gui. The frame is slightly zoomed in so that the warped texture
+/// coordinates never leave the frame.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScreenDistortion {
+    /// The strength of the distortion in the range `[0, 1]`. 0 disables the distortion.
+    pub intensity: f32,
+
+    /// The animation time in ticks.
+    pub time: f32,
+
+    /// The animation speed in degrees per tick.
+    pub speed: f32,
+}
+
+impl ScreenDistortion {
+    /// The speed used while the player has the nausea effect.
+    pub const NAUSEA_SPEED: f32 = 7f32;
+
+    /// The speed used while the player stands in a nether portal.
+    pub const PORTAL_SPEED: f32 = 20f32;
+
+    /// The maximum offset in texture coordinates at full intensity.
+    pub const MAX_AMPLITUDE: f32 = 0.02f32;
+
+    /// The number of waves across the frame.
+    pub const FREQUENCY: f32 = 3f32 * std::f32::consts::TAU;
+
+    pub const NONE: Self = Self {
+        intensity: 0f32,
+        time: 0f32,
+        speed: 0f32,
+    };
+
+    pub fn is_active(&self) -> bool {
+        self.intensity > 0f32
+    }
+
+    /// Returns the offset of the texture coordinates in the range `[0, MAX_AMPLITUDE]`.
+    pub fn get_amplitude(&self) -> f32 {
+        self.intensity.clamp(0f32, 1f32) * Self::MAX_AMPLITUDE
+    }
+
+    /// Returns the phase of the animation in radians.
+    pub fn get_phase(&self) -> f32 {
+        ((self.time * self.speed) % 360f32).to_radians()
+    }
+
+    /// Returns the texture coordinate the frame is read from for a output texture coordinate.
+    /// Matches the color grading shader.
+    pub fn warp(&self, uv: Vec2f32) -> Vec2f32 {
+        let amplitude = self.get_amplitude();
+        let phase = self.get_phase();
+        let offset = Vec2f32::new(
+            (uv[1] * Self::FREQUENCY + phase).sin(),
+            (uv[0] * Self::FREQUENCY + phase * 0.8f32).cos()
+        ) * amplitude;
+        (uv - Vec2f32::new(0.5f32, 0.5f32)) * (1f32 - 2f32 * amplitude) + Vec2f32::new(0.5f32, 0.5f32) + offset
+    }
+}
+
+impl Default for ScreenDistortion {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PostChainError {
+    InvalidJson(String),
+    MissingField(&'static str),
+    InvalidUniform(String),
+    UnknownTarget(String),
+    UnsupportedProgram(String),
+    UnsupportedAuxTargets(String),
+}
@@ -0,0 +1,396 @@
+//! Environment probe capture and prefiltering.
+//!
+//! A probe is captured by rendering the 6 faces of a cubemap from its position into offscreen
+//! outputs. To spread the cost over several frames only a limited number of faces is rendered each
+//! frame. Once all faces are captured the readbacks are prefiltered for image based lighting on a
+//! background thread and uploaded into a [`GlobalImage`] storing the faces side by side in
+//! [`CubeFace::ALL`] order. Each mip level stores the environment convolved with a lobe of
+//! increasing roughness (see [`prefilter_mip_roughness`]).
+//!
+//! Materials sample the probe by setting the image as a texture using
+//! [`PassRecorder::update_environment_probe`]. Shaders select the face and texture coordinates of a
+//! direction the same way as [`CubeFace::project_direction`], sample at
+//! `((face + u) / 6, v)` and select the mip level from the roughness.
+
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::renderer::emulator::{EmulatorRenderer, FrameSize, PassRecorder};
+use crate::renderer::emulator::global_objects::{GlobalImage, ImageData, SamplerInfo};
+use crate::renderer::emulator::pipeline::{EmulatorPipeline, OffscreenOutput, OffscreenReadback};
+use crate::util::format::Format;
+
+use crate::prelude::*;
+
+/// The maximum number of mip levels of a probe image.
+pub const PROBE_MAX_MIP_LEVELS: u32 = 6;
+
+/// The size of the downsampled faces convolved for the mip levels after the first.
+const PREFILTER_SOURCE_SIZE: u32 = 32;
+
+/// Weights below this are skipped while convolving.
+const PREFILTER_MIN_WEIGHT: f32 = 1e-3;
+
+/// The sampler used to bind probes. Mip levels are interpolated so the roughness can be varied
+/// continuously.
+pub const PROBE_SAMPLER: SamplerInfo = SamplerInfo {
+    mag_filter: vk::Filter::LINEAR,
+    min_filter: vk::Filter::LINEAR,
+    mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+    address_mode_u: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    anisotropy_enable: false,
+};
+
+/// The faces of a cubemap in the vulkan layer order.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum CubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [Self::PositiveX, Self::NegativeX, Self::PositiveY, Self::NegativeY, Self::PositiveZ, Self::NegativeZ];
+
+    /// Returns the world to view transform used to render this face from `position`.
+    pub fn view_matrix(&self, position: &Vec3f32) -> Mat4f32 {
+        let (forward, up) = match self {
+            Self::PositiveX => (Vec3f32::new(1f32, 0f32, 0f32), Vec3f32::new(0f32, -1f32, 0f32)),
+            Self::NegativeX => (Vec3f32::new(-1f32, 0f32, 0f32), Vec3f32::new(0f32, -1f32, 0f32)),
+            Self::PositiveY => (Vec3f32::new(0f32, 1f32, 0f32), Vec3f32::new(0f32, 0f32, 1f32)),
+            Self::NegativeY => (Vec3f32::new(0f32, -1f32, 0f32), Vec3f32::new(0f32, 0f32, -1f32)),
+            Self::PositiveZ => (Vec3f32::new(0f32, 0f32, 1f32), Vec3f32::new(0f32, -1f32, 0f32)),
+            Self::NegativeZ => (Vec3f32::new(0f32, 0f32, -1f32), Vec3f32::new(0f32, -1f32, 0f32)),
+        };
+
+        Mat4f32::look_at_rh(&(*position).into(), &(position + forward).into(), &up)
+    }
+
+    /// Returns the world space direction through the texture coordinates `uv` of this face.
+    pub fn direction(&self, uv: Vec2f32) -> Vec3f32 {
+        let rotation = self.view_matrix(&Vec3f32::zeros()).fixed_slice::<3, 3>(0, 0).transpose();
+        (rotation * Vec3f32::new(uv[0] * 2f32 - 1f32, uv[1] * 2f32 - 1f32, -1f32)).normalize()
+    }
+
+    /// Returns the face containing a world space direction and the texture coordinates of the
+    /// direction on that face. This is the inverse of [`CubeFace::direction`].
+    pub fn project_direction(direction: &Vec3f32) -> (CubeFace, Vec2f32) {
+        // The face whose forward direction is closest to the direction contains it
+        let (face, view) = Self::ALL.iter()
+            .map(|face| (*face, face.view_matrix(&Vec3f32::zeros()).transform_vector(direction)))
+            .max_by(|(_, a), (_, b)| (-a[2]).total_cmp(&-b[2]))
+            .unwrap();
+
+        let uv = Vec2f32::new(
+            (view[0] / -view[2]) * 0.5f32 + 0.5f32,
+            (view[1] / -view[2]) * 0.5f32 + 0.5f32
+        );
+        (face, uv)
+    }
+}
+
+/// Returns the projection used to render cube faces. Uses a 90 degree field of view and maps
+/// depth to the vulkan `[0, 1]` range.
+pub fn make_face_projection(near: f32, far: f32) -> Mat4f32 {
+    Mat4f32::new(
+        1f32, 0f32, 0f32, 0f32,
+        0f32, 1f32, 0f32, 0f32,
+        0f32, 0f32, far / (near - far), (near * far) / (near - far),
+        0f32, 0f32, -1f32, 0f32
+    )
+}
+
+/// Returns the size of the image storing a probe with faces of `resolution` by `resolution`
+/// pixels.
+pub fn probe_image_size(resolution: u32) -> Vec2u32 {
+    Vec2u32::new(resolution * 6, resolution)
+}
+
+/// Returns the number of mip levels of a probe with faces of `resolution` by `resolution` pixels.
+pub fn probe_mip_levels(resolution: u32) -> u32 {
+    std::cmp::min(32 - resolution.max(1).leading_zeros(), PROBE_MAX_MIP_LEVELS)
+}
+
+/// Called with the prefiltered probe image once it has been uploaded.
+pub type ProbeCallback = Box<dyn FnOnce(Arc<GlobalImage>) + Send>;
+
+/// An in progress environment probe capture.
+pub struct ProbeCapture {
+    renderer: Arc<EmulatorRenderer>,
+    pipeline: Arc<dyn EmulatorPipeline>,
+    output: Arc<OffscreenOutput>,
+    position: Vec3f32,
+    faces_per_frame: u32,
+    next_face: u32,
+    readbacks: Vec<OffscreenReadback>,
+    callback: Option<ProbeCallback>,
+}
+
+impl ProbeCapture {
+    /// Creates a new capture at `position` rendering each face with `resolution` by `resolution`
+    /// pixels using `pipeline`. At most `faces_per_frame` faces are rendered each frame. The
+    /// pipeline must have been created for that size and the resolution must be a power of two.
+    pub fn new(renderer: Arc<EmulatorRenderer>, pipeline: Arc<dyn EmulatorPipeline>, position: Vec3f32, resolution: u32, faces_per_frame: u32, callback: ProbeCallback) -> Self {
+        if !resolution.is_power_of_two() {
+            log::error!("Probe resolution {:?} is not a power of two", resolution);
+            panic!();
+        }
+        let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), Vec2u32::new(resolution, resolution));
+
+        Self {
+            renderer,
+            pipeline,
+            output,
+            position,
+            faces_per_frame: faces_per_frame.clamp(1, 6),
+            next_face: 0,
+            readbacks: Vec::with_capacity(6),
+            callback: Some(callback),
+        }
+    }
+
+    pub fn get_position(&self) -> &Vec3f32 {
+        &self.position
+    }
+
+    pub fn get_resolution(&self) -> u32 {
+        self.output.get_size()[0]
+    }
+
+    /// Returns the faces which should be rendered this frame and advances the capture. Each face
+    /// must be rendered using [`ProbeCapture::start_face`].
+    pub fn next_faces(&mut self) -> &'static [CubeFace] {
+        let start = self.next_face as usize;
+        let end = (start + self.faces_per_frame as usize).min(6);
+        self.next_face = end as u32;
+        &CubeFace::ALL[start..end]
+    }
+
+    /// Starts the pass of a face returned by [`ProbeCapture::next_faces`]. Faces must be started
+    /// in order. The caller must render the scene into the pass using [`CubeFace::view_matrix`]
+    /// with the probe position and [`make_face_projection`].
+    ///
+    /// Only one pass can be recorded at a time so the pass must be ended before the next face or
+    /// frame is started.
+    pub fn start_face(&mut self, face: CubeFace) -> PassRecorder {
+        if face as usize != self.readbacks.len() || self.readbacks.len() >= self.next_face as usize {
+            log::error!("Called ProbeCapture::start_face with {:?} which is not the next scheduled face", face);
+            panic!();
+        }
+
+        let (output, readback) = self.output.next_output();
+        let mut recorder = self.renderer.start_pass(self.pipeline.clone());
+        recorder.set_frame_size(FrameSize::from_physical(self.output.get_size(), 1f32));
+        recorder.use_output(output);
+        self.readbacks.push(readback);
+
+        recorder
+    }
+
+    /// Returns true once all faces have been started.
+    pub fn is_complete(&self) -> bool {
+        self.readbacks.len() == 6
+    }
+
+    /// Prefilters the captured faces on a background thread, uploads them into a new probe image
+    /// and calls the callback with the image. All face passes must have been started and
+    /// submitted.
+    ///
+    /// If any face pass was aborted the callback is never called.
+    pub fn finish(mut self) {
+        if !self.is_complete() {
+            log::error!("Called ProbeCapture::finish before all faces were started");
+            panic!();
+        }
+
+        let resolution = self.get_resolution();
+        let mip_levels = probe_mip_levels(resolution);
+        let image = self.renderer.create_global_image_mips(probe_image_size(resolution), mip_levels, &Format::R8G8B8A8_SRGB);
+        let readbacks = std::mem::take(&mut self.readbacks);
+        let callback = self.callback.take().unwrap();
+
+        std::thread::spawn(move || {
+            let mut faces = Vec::with_capacity(6);
+            for readback in readbacks {
+                match readback.wait() {
+                    Some(data) => faces.push(data),
+                    None => {
+                        log::warn!("Probe face pass was aborted");
+                        return;
+                    }
+                }
+            }
+
+            let mips = prefilter_faces(&faces, resolution, mip_levels);
+            for (level, faces) in mips.iter().enumerate() {
+                let size = resolution >> level;
+                let regions: Vec<_> = faces.iter().enumerate().map(|(index, face)| {
+                    ImageData::new_extent(face, Vec2u32::new((index as u32) * size, 0), Vec2u32::new(size, size))
+                }).collect();
+                image.update_mip_regions(level as u32, &regions);
+            }
+
+            callback(image);
+        });
+    }
+}
+
+impl RefUnwindSafe for ProbeCapture {} // The callback is never called while borrowed
+
+/// Returns the roughness a prefiltered mip level represents. Mip 0 is a perfect mirror and the
+/// last mip is fully rough.
+pub fn prefilter_mip_roughness(mip_level: u32, mip_count: u32) -> f32 {
+    if mip_count <= 1 {
+        0f32
+    } else {
+        (mip_level as f32) / ((mip_count - 1) as f32)
+    }
+}
+
+/// Prefilters the 6 srgb rgba cube faces (in [`CubeFace::ALL`] order) of `face_size` by
+/// `face_size` pixels. Returns the faces of each of the `mip_count` mip levels, each level half the
+/// size of the previous one.
+///
+/// The first level is the unfiltered capture. All following levels are convolved with a specular
+/// lobe of the roughness returned by [`prefilter_mip_roughness`]. The convolution samples faces
+/// downsampled to at most 32 by 32 pixels and weights every texel by its solid angle. Filtering is
+/// done in linear space.
+pub fn prefilter_faces(faces: &[Box<[u8]>], face_size: u32, mip_count: u32) -> Vec<Vec<Box<[u8]>>> {
+    assert_eq!(faces.len(), 6);
+
+    let mut mips = Vec::with_capacity(mip_count as usize);
+    mips.push(faces.to_vec());
+    if mip_count <= 1 {
+        return mips;
+    }
+
+    let source_size = std::cmp::min(face_size, PREFILTER_SOURCE_SIZE);
+    let source = SourceTexel::collect(faces, face_size, source_size);
+
+    for level in 1..mip_count {
+        let size = std::cmp::max(face_size >> level, 1);
+        let roughness = prefilter_mip_roughness(level, mip_count);
+        let alpha = roughness * roughness;
+        // Phong exponent matching the width of a GGX lobe of the roughness
+        let exponent = (2f32 / (alpha * alpha).max(f32::EPSILON) - 2f32).max(0f32);
+        let min_cos = if exponent > 0f32 { PREFILTER_MIN_WEIGHT.powf(1f32 / exponent) } else { 0f32 };
+
+        let level_faces = CubeFace::ALL.iter().map(|face| {
+            let mut data = vec![0u8; (size * size * 4) as usize];
+            for y in 0..size {
+                for x in 0..size {
+                    let uv = Vec2f32::new(((x as f32) + 0.5f32) / (size as f32), ((y as f32) + 0.5f32) / (size as f32));
+                    let color = convolve(&source, &face.direction(uv), exponent, min_cos);
+                    let dst = ((y * size + x) * 4) as usize;
+                    data[dst..dst + 4].copy_from_slice(&encode_srgb(&color));
+                }
+            }
+            data.into_boxed_slice()
+        }).collect();
+
+        mips.push(level_faces);
+    }
+
+    mips
+}
+
+/// A texel of the downsampled source faces.
+struct SourceTexel {
+    direction: Vec3f32,
+    solid_angle: f32,
+    /// The linear color
+    color: Vec4f32,
+}
+
+impl SourceTexel {
+    fn collect(faces: &[Box<[u8]>], face_size: u32, size: u32) -> Vec<Self> {
+        let block = face_size / size;
+        let texel_area = 4f32 / ((size * size) as f32);
+
+        let mut texels = Vec::with_capacity((size * size * 6) as usize);
+        for (face, data) in CubeFace::ALL.iter().zip(faces) {
+            for y in 0..size {
+                for x in 0..size {
+                    let mut color = Vec4f32::zeros();
+                    for by in 0..block {
+                        for bx in 0..block {
+                            let src = (((y * block + by) * face_size + x * block + bx) * 4) as usize;
+                            color += decode_srgb(&data[src..src + 4]);
+                        }
+                    }
+                    color /= (block * block) as f32;
+
+                    let uv = Vec2f32::new(((x as f32) + 0.5f32) / (size as f32), ((y as f32) + 0.5f32) / (size as f32));
+                    let ndc = uv * 2f32 - Vec2f32::new(1f32, 1f32);
+                    let solid_angle = texel_area / (1f32 + ndc.norm_squared()).powf(1.5f32);
+
+                    texels.push(Self {
+                        direction: face.direction(uv),
+                        solid_angle,
+                        color,
+                    });
+                }
+            }
+        }
+        texels
+    }
+}
+
+fn convolve(source: &[SourceTexel], direction: &Vec3f32, exponent: f32, min_cos: f32) -> Vec4f32 {
+    let mut sum = Vec4f32::zeros();
+    let mut weight_sum = 0f32;
+    let mut nearest = (f32::MIN, Vec4f32::zeros());
+
+    for texel in source {
+        let cos = texel.direction.dot(direction);
+        if cos > nearest.0 {
+            nearest = (cos, texel.color);
+        }
+        if cos <= min_cos {
+            continue;
+        }
+
+        let weight = cos.powf(exponent) * texel.solid_angle;
+        sum += texel.color * weight;
+        weight_sum += weight;
+    }
+
+    if weight_sum > 0f32 {
+        sum / weight_sum
+    } else {
+        // The lobe is narrower than a source texel
+        nearest.1
+    }
+}
+
+fn decode_srgb(data: &[u8]) -> Vec4f32 {
+    let decode = |value: u8| {
+        let value = (value as f32) / 255f32;
+        if value <= 0.04045f32 {
+            value / 12.92f32
+        } else {
+            ((value + 0.055f32) / 1.055f32).powf(2.4f32)
+        }
+    };
+    Vec4f32::new(decode(data[0]), decode(data[1]), decode(data[2]), (data[3] as f32) / 255f32)
+}
+
+fn encode_srgb(color: &Vec4f32) -> [u8; 4] {
+    let encode = |value: f32| {
+        let value = value.clamp(0f32, 1f32);
+        let value = if value <= 0.0031308f32 {
+            value * 12.92f32
+        } else {
+            1.055f32 * value.powf(1f32 / 2.4f32) - 0.055f32
+        };
+        (value * 255f32).round() as u8
+    };
+    [encode(color[0]), encode(color[1]), encode(color[2]), (color[3].clamp(0f32, 1f32) * 255f32).round() as u8]
+}
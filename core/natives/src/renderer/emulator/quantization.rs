@@ -0,0 +1,200 @@
+//! Utilities to create meshes with quantized vertex data.
+//!
+//! Quantized meshes store positions as 16 bit normalized integers relative to a bounding box and
+//! normals as octahedral encoded 16 bit normalized integers. For typical chunk meshes this halves
+//! the vertex size compared to 32 bit floats. The [`VertexFormat`] of a quantized mesh describes how
+//! the data must be decoded and pipelines decode it in the vertex shader.
+
+use ash::vk;
+
+use crate::renderer::emulator::mc_shaders::{VertexFormat, VertexFormatEntry};
+use crate::util::alloc::next_aligned;
+use crate::util::format::Format;
+
+use crate::prelude::*;
+
+/// Describes how positions stored as normalized integers are mapped back to their original range.
+///
+/// The decoded position is calculated as `offset + (encoded * scale)` where `encoded` is in the
+/// range `[-1, 1]`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct PositionQuantization {
+    pub offset: Vec3f32,
+    pub scale: Vec3f32,
+}
+
+impl PositionQuantization {
+    /// Creates a quantization covering the axis aligned box from `min` to `max`.
+    pub fn from_bounds(min: Vec3f32, max: Vec3f32) -> Self {
+        let offset = (min + max) / 2f32;
+        let scale = (max - min).map(|v| f32::max(v / 2f32, f32::MIN_POSITIVE));
+
+        Self {
+            offset,
+            scale,
+        }
+    }
+
+    /// A quantization suitable for minecraft chunk sections. Covers the range `[-8, 24]` on each
+    /// axis to include geometry extending past the section bounds. The precision is roughly half a
+    /// thousandth of a block.
+    pub fn chunk_section() -> Self {
+        Self::from_bounds(Vec3f32::from_element(-8f32), Vec3f32::from_element(24f32))
+    }
+
+    /// Encodes a position as 4 16 bit normalized integers. Positions outside of the bounds are
+    /// clamped. The 4th component is always 0 and only exists for alignment.
+    pub fn encode(&self, position: &Vec3f32) -> [i16; 4] {
+        let normalized = (position - self.offset).component_div(&self.scale);
+        [encode_snorm16(normalized[0]), encode_snorm16(normalized[1]), encode_snorm16(normalized[2]), 0]
+    }
+
+    pub fn decode(&self, encoded: &[i16; 4]) -> Vec3f32 {
+        let normalized = Vec3f32::new(decode_snorm16(encoded[0]), decode_snorm16(encoded[1]), decode_snorm16(encoded[2]));
+        self.offset + normalized.component_mul(&self.scale)
+    }
+}
+
+/// The encoding used for vertex normals.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum NormalEncoding {
+    /// The normal is stored as a 3 component vector.
+    Direct = 0,
+
+    /// The normal is stored as a 2 component octahedral encoded vector.
+    Octahedral = 1,
+}
+
+impl NormalEncoding {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Direct),
+            1 => Some(Self::Octahedral),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes a normal using octahedral encoding into 2 16 bit normalized integers. The normal does
+/// not have to be normalized but must not be 0.
+pub fn encode_octahedral_normal(normal: &Vec3f32) -> [i16; 2] {
+    let n = normal / (normal[0].abs() + normal[1].abs() + normal[2].abs());
+
+    let (x, y) = if n[2] >= 0f32 {
+        (n[0], n[1])
+    } else {
+        ((1f32 - n[1].abs()) * sign_not_zero(n[0]), (1f32 - n[0].abs()) * sign_not_zero(n[1]))
+    };
+
+    [encode_snorm16(x), encode_snorm16(y)]
+}
+
+/// Decodes a octahedral encoded normal. The returned normal is normalized.
+pub fn decode_octahedral_normal(encoded: &[i16; 2]) -> Vec3f32 {
+    let x = decode_snorm16(encoded[0]);
+    let y = decode_snorm16(encoded[1]);
+
+    let z = 1f32 - x.abs() - y.abs();
+    let t = f32::max(-z, 0f32);
+    let n = Vec3f32::new(x - (t * sign_not_zero(x)), y - (t * sign_not_zero(y)), z);
+
+    n.normalize()
+}
+
+fn sign_not_zero(v: f32) -> f32 {
+    if v >= 0f32 { 1f32 } else { -1f32 }
+}
+
+fn encode_snorm16(v: f32) -> i16 {
+    (v.clamp(-1f32, 1f32) * (i16::MAX as f32)).round() as i16
+}
+
+fn decode_snorm16(v: i16) -> f32 {
+    f32::max((v as f32) / (i16::MAX as f32), -1f32)
+}
+
+/// Builds the [`VertexFormat`] of a quantized vertex.
+///
+/// The vertex always starts with the quantized position (8 bytes) followed by the octahedral
+/// normal (4 bytes) if enabled. All other attributes are appended in the order color, uv0, uv1,
+/// uv2 using their specified format.
+pub struct QuantizedVertexFormatBuilder {
+    position_quantization: PositionQuantization,
+    normal: bool,
+    color: Option<vk::Format>,
+    uv0: Option<vk::Format>,
+    uv1: Option<vk::Format>,
+    uv2: Option<vk::Format>,
+}
+
+impl QuantizedVertexFormatBuilder {
+    pub fn new(position_quantization: PositionQuantization) -> Self {
+        Self {
+            position_quantization,
+            normal: false,
+            color: None,
+            uv0: None,
+            uv1: None,
+            uv2: None,
+        }
+    }
+
+    pub fn normal(mut self) -> Self {
+        self.normal = true;
+        self
+    }
+
+    pub fn color(mut self, format: vk::Format) -> Self {
+        self.color = Some(format);
+        self
+    }
+
+    pub fn uv0(mut self, format: vk::Format) -> Self {
+        self.uv0 = Some(format);
+        self
+    }
+
+    pub fn uv1(mut self, format: vk::Format) -> Self {
+        self.uv1 = Some(format);
+        self
+    }
+
+    pub fn uv2(mut self, format: vk::Format) -> Self {
+        self.uv2 = Some(format);
+        self
+    }
+
+    pub fn build(self) -> VertexFormat {
+        let mut stride = 0u32;
+        let mut push = |format: vk::Format| {
+            let size = Format::format_for(format).get_compatibility_class().get_texel_size().unwrap_or_else(|| {
+                log::error!("Compressed format {:?} passed to QuantizedVertexFormatBuilder", format);
+                panic!()
+            });
+            let entry = VertexFormatEntry { offset: stride, format };
+            // Keep all attributes 4 byte aligned
+            stride += next_aligned(size as u64, 4) as u32;
+            entry
+        };
+
+        let position = push(vk::Format::R16G16B16A16_SNORM);
+        let normal = self.normal.then(|| push(vk::Format::R16G16_SNORM));
+        let color = self.color.map(&mut push);
+        let uv0 = self.uv0.map(&mut push);
+        let uv1 = self.uv1.map(&mut push);
+        let uv2 = self.uv2.map(&mut push);
+
+        VertexFormat {
+            stride,
+            position,
+            normal,
+            color,
+            uv0,
+            uv1,
+            uv2,
+            position_quantization: Some(self.position_quantization),
+            normal_encoding: if self.normal { NormalEncoding::Octahedral } else { NormalEncoding::Direct },
+        }
+    }
+}
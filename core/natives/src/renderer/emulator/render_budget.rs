@@ -0,0 +1,103 @@
+//! Per frame limits on the amount of work submitted by the host.
+//!
+//! On weak hardware a sudden spike in submitted work (for example an explosion spawning thousands
+//! of particles) causes long frames. A [`RenderBudget`] caps the number of immediate mesh uploads
+//! and the number of draws in individual render layers per pass. Particles are limited using the
+//! draw limit of the builtin `particles` layer.
+//!
+//! Every draw has a priority set using [`PassRecorder::set_draw_priority`]. If a layer exceeds its
+//! limit the draws with the lowest priority are dropped, later draws are dropped before earlier
+//! draws of the same priority. Since draws of a layer are only emitted once the pass ends the full
+//! set of draws is known when the decision is made. Immediate uploads are processed in submission
+//! order so once the upload limit is reached all following uploads are dropped except those with
+//! [`REQUIRED_DRAW_PRIORITY`]. Draws of dropped uploads are skipped.
+//!
+//! Dropped draws are reported in [`RenderLayerStats::culled_draw_count`] and the pass totals in a
+//! [`BudgetReport`].
+//!
+//! [`PassRecorder::set_draw_priority`]: crate::renderer::emulator::PassRecorder::set_draw_priority
+//! [`RenderLayerStats::culled_draw_count`]: crate::renderer::emulator::render_layer::RenderLayerStats::culled_draw_count
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::renderer::emulator::jobs::JobSystem;
+use crate::renderer::emulator::render_layer::RenderLayerId;
+
+/// The priority of draws which have not set a priority.
+pub const DEFAULT_DRAW_PRIORITY: u8 = 128;
+
+/// Draws and uploads with this priority are never dropped.
+pub const REQUIRED_DRAW_PRIORITY: u8 = u8::MAX;
+
+/// The limits applied to every pass.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct RenderBudget {
+    /// The maximum number of immediate meshes uploaded per pass.
+    pub max_immediate_uploads: Option<u32>,
+
+    /// The maximum number of draws per pass for individual render layers.
+    pub layer_draw_limits: HashMap<RenderLayerId, u32>,
+}
+
+impl RenderBudget {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_immediate_uploads.is_none() && self.layer_draw_limits.is_empty()
+    }
+}
+
+/// The work dropped in a pass because of the budget.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct BudgetReport {
+    /// The number of dropped immediate uploads.
+    pub dropped_uploads: u32,
+
+    /// The number of draws skipped because their upload was dropped.
+    pub dropped_upload_draws: u32,
+
+    /// The number of draws dropped because their layer exceeded its limit.
+    pub culled_draws: u32,
+}
+
+impl BudgetReport {
+    pub fn is_empty(&self) -> bool {
+        self.dropped_uploads == 0 && self.dropped_upload_draws == 0 && self.culled_draws == 0
+    }
+}
+
+/// Selects the draws to drop if `priorities.len()` draws are submitted to a layer with a limit of
+/// `limit` draws. Returns a mask with one entry per draw which is true if the draw must be dropped.
+/// Draws with [`REQUIRED_DRAW_PRIORITY`] are never dropped even if this exceeds the limit.
+pub fn select_culled_draws(priorities: &[u8], limit: u32) -> Vec<bool> {
+    select_culled_draws_sorted(priorities, limit, |order, key| order.sort_by_key(key))
+}
+
+/// Like [`select_culled_draws`] but sorts the draws using the job system. Layers with many draws
+/// (for example particles) are sorted in parallel.
+pub fn select_culled_draws_parallel(jobs: &JobSystem, priorities: &[u8], limit: u32) -> Vec<bool> {
+    select_culled_draws_sorted(priorities, limit, |order, key| jobs.sort_by_key(order, MIN_PARALLEL_SORT, key))
+}
+
+/// The minimum number of draws sorted by a single task.
+const MIN_PARALLEL_SORT: usize = 4096;
+
+fn select_culled_draws_sorted<S>(priorities: &[u8], limit: u32, sort: S) -> Vec<bool>
+    where S: FnOnce(&mut [usize], &(dyn Fn(&usize) -> (u8, Reverse<usize>) + Sync)) {
+
+    let mut culled = vec![false; priorities.len()];
+    let excess = priorities.len().saturating_sub(limit as usize);
+    if excess == 0 {
+        return culled;
+    }
+
+    let mut order: Vec<usize> = (0..priorities.len())
+        .filter(|index| priorities[*index] != REQUIRED_DRAW_PRIORITY)
+        .collect();
+    // Lowest priority first, later draws before earlier draws of the same priority
+    sort(&mut order, &|index| (priorities[*index], Reverse(*index)));
+
+    for index in order.into_iter().take(excess) {
+        culled[index] = true;
+    }
+    culled
+}
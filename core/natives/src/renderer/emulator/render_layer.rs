@@ -0,0 +1,326 @@
+//! Named render layers mirroring minecrafts RenderType system.
+//!
+//! A render layer groups draws which share the same fixed state like the solid or translucent
+//! terrain layers. Layers are registered with the [`EmulatorRenderer`] by name and selected in a
+//! pass using [`PassRecorder::set_render_layer`]. The pass recorder validates that layers exist and
+//! collects per layer statistics.
+//!
+//! The vanilla layers listed in [`BUILTIN_LAYERS`] are always registered.
+//!
+//! Draws submitted to a layer are emitted in the render order of the renderer instead of the
+//! order they were recorded in. The render order is a list of layers and named
+//! [`InsertionPoint`]s which hosts can modify to add their own rendering stages at well defined
+//! places.
+//!
+//! [`EmulatorRenderer`]: crate::renderer::emulator::EmulatorRenderer
+//! [`PassRecorder::set_render_layer`]: crate::renderer::emulator::PassRecorder::set_render_layer
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::define_uuid_type;
+use crate::renderer::emulator::PassId;
+
+use crate::prelude::*;
+
+define_uuid_type!(pub, RenderLayerId);
+
+/// The fixed state of a render layer.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RenderLayerState {
+    /// If false depth writes are disabled for all draws of the layer independent of the value
+    /// passed to the draw.
+    pub depth_write_enable: bool,
+
+    /// If set the geometry of the layer is blended and should be sorted back to front.
+    pub translucent: bool,
+
+    /// Fragments with an alpha value below this threshold are expected to be discarded by the
+    /// shaders used in this layer. A threshold of 0 disables the alpha test.
+    pub alpha_cutout: f32,
+
+    /// If set the textures of the layer are sampled using mipmaps.
+    pub mipmap: bool,
+
+    /// If set block breaking overlays are rendered on top of the layer.
+    pub affects_crumbling: bool,
+}
+
+impl Default for RenderLayerState {
+    fn default() -> Self {
+        Self {
+            depth_write_enable: true,
+            translucent: false,
+            alpha_cutout: 0f32,
+            mipmap: false,
+            affects_crumbling: false,
+        }
+    }
+}
+
+/// The vanilla layers in their default render order.
+pub const BUILTIN_LAYERS: [(&'static str, RenderLayerState); 10] = [
+    ("sky", RenderLayerState { depth_write_enable: false, translucent: false, alpha_cutout: 0f32, mipmap: false, affects_crumbling: false }),
+    ("solid", RenderLayerState { depth_write_enable: true, translucent: false, alpha_cutout: 0f32, mipmap: true, affects_crumbling: true }),
+    ("cutout_mipped", RenderLayerState { depth_write_enable: true, translucent: false, alpha_cutout: 0.5f32, mipmap: true, affects_crumbling: true }),
+    ("cutout", RenderLayerState { depth_write_enable: true, translucent: false, alpha_cutout: 0.1f32, mipmap: false, affects_crumbling: true }),
+    ("entities", RenderLayerState { depth_write_enable: true, translucent: false, alpha_cutout: 0.1f32, mipmap: false, affects_crumbling: true }),
+    ("translucent", RenderLayerState { depth_write_enable: true, translucent: true, alpha_cutout: 0f32, mipmap: true, affects_crumbling: true }),
+    ("tripwire", RenderLayerState { depth_write_enable: true, translucent: true, alpha_cutout: 0.1f32, mipmap: true, affects_crumbling: true }),
+    // Fullbright geometry drawn with emissive shaders like glowing eyes and sign text
+    ("emissive", RenderLayerState { depth_write_enable: false, translucent: true, alpha_cutout: 0f32, mipmap: false, affects_crumbling: false }),
+    ("particles", RenderLayerState { depth_write_enable: true, translucent: true, alpha_cutout: 0.1f32, mipmap: false, affects_crumbling: false }),
+    ("gui", RenderLayerState { depth_write_enable: true, translucent: true, alpha_cutout: 0f32, mipmap: false, affects_crumbling: false }),
+];
+
+/// Named points in the render order at which hosts can insert their own layers.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[repr(u32)]
+pub enum InsertionPoint {
+    /// After all opaque and cutout geometry.
+    AfterOpaque = 0,
+
+    /// Before the translucent terrain. Layers at this point are rendered after the layers at
+    /// [`InsertionPoint::AfterOpaque`].
+    BeforeTranslucent = 1,
+
+    /// After the gui. The last point in the default order.
+    AfterGui = 2,
+
+    /// After the emissive layer. Intended for bloom stages which need all emissive geometry.
+    AfterEmissive = 3,
+}
+
+impl InsertionPoint {
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::AfterOpaque),
+            1 => Some(Self::BeforeTranslucent),
+            2 => Some(Self::AfterGui),
+            3 => Some(Self::AfterEmissive),
+            _ => None,
+        }
+    }
+
+    /// The builtin layer after which the point is placed in the default order.
+    fn default_anchor(&self) -> &'static str {
+        match self {
+            Self::AfterOpaque => "entities",
+            Self::BeforeTranslucent => "entities",
+            Self::AfterGui => "gui",
+            Self::AfterEmissive => "emissive",
+        }
+    }
+}
+
+/// An entry of the render order.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum RenderOrderEntry {
+    Layer(RenderLayerId),
+    Point(InsertionPoint),
+}
+
+/// Statistics about the draws submitted to a render layer in a pass.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct RenderLayerStats {
+    pub draw_count: u64,
+    pub index_count: u64,
+    /// The number of draws skipped because the layer was disabled.
+    pub skipped_draw_count: u64,
+    /// The number of draws dropped because the layer exceeded its draw limit. Not included in
+    /// `draw_count`.
+    pub culled_draw_count: u64,
+}
+
+impl RenderLayerStats {
+    pub(super) fn add_draw(&mut self, index_count: u32) {
+        self.draw_count += 1;
+        self.index_count += index_count as u64;
+    }
+
+    pub(super) fn remove_culled_draw(&mut self, index_count: u32) {
+        self.draw_count -= 1;
+        self.index_count -= index_count as u64;
+        self.culled_draw_count += 1;
+    }
+}
+
+pub struct RenderLayer {
+    id: RenderLayerId,
+    /// A dense index assigned at registration. Never reused.
+    index: u32,
+    name: String,
+    state: RenderLayerState,
+    enabled: AtomicBool,
+    last_stats: Mutex<Option<(PassId, RenderLayerStats)>>,
+}
+
+impl RenderLayer {
+    fn new(index: u32, name: String, state: RenderLayerState) -> Arc<Self> {
+        Arc::new(Self {
+            id: RenderLayerId::new(),
+            index,
+            name,
+            state,
+            enabled: AtomicBool::new(true),
+            last_stats: Mutex::new(None),
+        })
+    }
+
+    pub fn get_id(&self) -> RenderLayerId {
+        self.id
+    }
+
+    pub(super) fn get_index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_state(&self) -> &RenderLayerState {
+        &self.state
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the layer. Draws submitted to a disabled layer are skipped. Intended
+    /// for debugging and benchmarking. Takes effect for all following draws including draws of
+    /// passes which are currently recorded.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics of the most recently ended pass which drew to this layer.
+    pub fn get_last_stats(&self) -> Option<(PassId, RenderLayerStats)> {
+        *self.last_stats.lock().unwrap()
+    }
+
+    pub(super) fn record_stats(&self, pass: PassId, stats: RenderLayerStats) {
+        *self.last_stats.lock().unwrap() = Some((pass, stats));
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RenderLayerError {
+    DuplicateName(String),
+    InvalidState(&'static str),
+    UnknownLayer(RenderLayerId),
+    /// A render order does not contain every registered layer and insertion point exactly once.
+    InvalidOrder,
+}
+
+pub(super) struct RenderLayerRegistry {
+    layers: HashMap<RenderLayerId, Arc<RenderLayer>>,
+    names: HashMap<String, RenderLayerId>,
+    order: Vec<RenderOrderEntry>,
+    next_index: u32,
+}
+
+impl RenderLayerRegistry {
+    pub(super) fn new() -> Self {
+        let mut registry = Self {
+            layers: HashMap::new(),
+            names: HashMap::new(),
+            order: Vec::new(),
+            next_index: 0,
+        };
+        for (name, state) in &BUILTIN_LAYERS {
+            registry.register(name, state).unwrap();
+        }
+        for point in [InsertionPoint::AfterOpaque, InsertionPoint::BeforeTranslucent, InsertionPoint::AfterEmissive, InsertionPoint::AfterGui] {
+            let anchor = RenderOrderEntry::Layer(registry.find(point.default_anchor()).unwrap());
+            let mut index = registry.order.iter().position(|entry| *entry == anchor).unwrap() + 1;
+            while matches!(registry.order.get(index), Some(RenderOrderEntry::Point(_))) {
+                index += 1;
+            }
+            registry.order.insert(index, RenderOrderEntry::Point(point));
+        }
+        registry
+    }
+
+    pub(super) fn register(&mut self, name: &str, state: &RenderLayerState) -> Result<RenderLayerId, RenderLayerError> {
+        if !state.alpha_cutout.is_finite() || state.alpha_cutout < 0f32 {
+            return Err(RenderLayerError::InvalidState("alpha_cutout"));
+        }
+        if self.names.contains_key(name) {
+            return Err(RenderLayerError::DuplicateName(name.to_string()));
+        }
+
+        let layer = RenderLayer::new(self.next_index, name.to_string(), *state);
+        self.next_index += 1;
+        let id = layer.get_id();
+        self.names.insert(name.to_string(), id);
+        self.layers.insert(id, layer);
+        self.order.push(RenderOrderEntry::Layer(id));
+        Ok(id)
+    }
+
+    pub(super) fn unregister(&mut self, id: RenderLayerId) {
+        if let Some(layer) = self.layers.remove(&id) {
+            self.names.remove(layer.get_name());
+            self.order.retain(|entry| *entry != RenderOrderEntry::Layer(id));
+        }
+    }
+
+    pub(super) fn get_order(&self) -> &[RenderOrderEntry] {
+        &self.order
+    }
+
+    /// Replaces the render order. The new order must contain every registered layer and every
+    /// insertion point exactly once.
+    pub(super) fn set_order(&mut self, order: &[RenderOrderEntry]) -> Result<(), RenderLayerError> {
+        let mut seen = HashSet::new();
+        for entry in order {
+            if let RenderOrderEntry::Layer(id) = entry {
+                if !self.layers.contains_key(id) {
+                    return Err(RenderLayerError::UnknownLayer(*id));
+                }
+            }
+            if !seen.insert(*entry) {
+                return Err(RenderLayerError::InvalidOrder);
+            }
+        }
+        if seen.len() != self.order.len() {
+            return Err(RenderLayerError::InvalidOrder);
+        }
+
+        self.order = order.to_vec();
+        Ok(())
+    }
+
+    /// Moves a layer to an insertion point. The layer is placed after all layers already inserted
+    /// at the point.
+    pub(super) fn insert_at(&mut self, id: RenderLayerId, point: InsertionPoint) -> Result<(), RenderLayerError> {
+        if !self.layers.contains_key(&id) {
+            return Err(RenderLayerError::UnknownLayer(id));
+        }
+        self.order.retain(|entry| *entry != RenderOrderEntry::Layer(id));
+
+        let start = self.order.iter().position(|entry| *entry == RenderOrderEntry::Point(point)).unwrap() + 1;
+        let index = self.order[start..].iter()
+            .position(|entry| matches!(entry, RenderOrderEntry::Point(_)))
+            .map_or(self.order.len(), |offset| start + offset);
+        self.order.insert(index, RenderOrderEntry::Layer(id));
+        Ok(())
+    }
+
+    /// Enables only the layer `id` or all layers if [`None`] is passed.
+    pub(super) fn isolate(&self, id: Option<RenderLayerId>) {
+        for layer in self.layers.values() {
+            layer.set_enabled(id.map_or(true, |id| id == layer.get_id()));
+        }
+    }
+
+    pub(super) fn get(&self, id: RenderLayerId) -> Option<Arc<RenderLayer>> {
+        self.layers.get(&id).cloned()
+    }
+
+    pub(super) fn find(&self, name: &str) -> Option<RenderLayerId> {
+        self.names.get(name).copied()
+    }
+}
@@ -0,0 +1,269 @@
+//! Cascade computation for directional light shadow maps.
+//!
+//! The camera frustum is split along the view direction into up to [`MAX_CASCADES`] ranges each
+//! covered by its own orthographic light projection. Near cascades cover a small area at high
+//! resolution while far cascades cover large areas at lower resolution.
+//!
+//! The [`DebugPipeline`](crate::renderer::emulator::debug_pipeline::DebugPipeline) renders the
+//! depth of all depth writing draws into one layer of a shadow map array per cascade. The lighting
+//! shaders select the cascade based on the view distance using [`ShadowUniforms`].
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::prelude::*;
+
+/// The maximum number of cascades supported.
+pub const MAX_CASCADES: usize = 4;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CascadeConfig {
+    /// The number of cascades. Must be in the range `[1, MAX_CASCADES]`.
+    pub cascade_count: u32,
+
+    /// Blends between a uniform (0) and logarithmic (1) split distribution.
+    pub split_lambda: f32,
+
+    /// The fraction of each cascade range over which the lighting shaders blend into the next
+    /// cascade to hide the seam.
+    pub blend_fraction: f32,
+
+    /// The size in texels of each cascade shadow map. Used to snap the projections to the texel
+    /// grid which prevents shimmering when the camera moves.
+    pub resolution: u32,
+
+    /// The distance the depth range of each cascade is extended towards the light. Casters
+    /// outside the bounding sphere of a cascade but within this distance still cast shadows into
+    /// it.
+    pub caster_distance: f32,
+}
+
+impl Default for CascadeConfig {
+    fn default() -> Self {
+        Self {
+            cascade_count: 4,
+            split_lambda: 0.75f32,
+            blend_fraction: 0.1f32,
+            resolution: 2048,
+            caster_distance: 64f32,
+        }
+    }
+}
+
+/// The camera parameters needed to compute cascades.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CameraFrustum {
+    /// The world to view space transform. The camera looks along negative z.
+    pub view: Mat4f32,
+
+    /// The vertical field of view in radians.
+    pub fov_y: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ShadowCascade {
+    /// The world to light clip space transform. Depth uses the vulkan `[0, 1]` range.
+    pub view_projection: Mat4f32,
+
+    /// The view space distance at which this cascade starts.
+    pub split_near: f32,
+
+    /// The view space distance at which this cascade ends.
+    pub split_far: f32,
+}
+
+impl ShadowCascade {
+    /// Returns false if the axis aligned box is guaranteed to not be visible in this cascade.
+    /// Used to cull draws per cascade.
+    pub fn intersects_aabb(&self, min: &Vec3f32, max: &Vec3f32) -> bool {
+        let mut outside = [true; 6];
+        for index in 0..8 {
+            let corner = Vec4f32::new(
+                if index & 1 == 0 { min[0] } else { max[0] },
+                if index & 2 == 0 { min[1] } else { max[1] },
+                if index & 4 == 0 { min[2] } else { max[2] },
+                1f32
+            );
+            let clip = self.view_projection * corner;
+
+            outside[0] &= clip[0] < -clip[3];
+            outside[1] &= clip[0] > clip[3];
+            outside[2] &= clip[1] < -clip[3];
+            outside[3] &= clip[1] > clip[3];
+            outside[4] &= clip[2] < 0f32;
+            outside[5] &= clip[2] > clip[3];
+        }
+
+        !outside.iter().any(|v| *v)
+    }
+}
+
+/// Computes the split distances of the cascades using the practical split scheme. The returned
+/// array contains `cascade_count + 1` valid entries starting at `near` and ending at `far`.
+pub fn compute_cascade_splits(near: f32, far: f32, cascade_count: u32, lambda: f32) -> [f32; MAX_CASCADES + 1] {
+    let count = (cascade_count as usize).clamp(1, MAX_CASCADES);
+    let lambda = lambda.clamp(0f32, 1f32);
+
+    let mut splits = [far; MAX_CASCADES + 1];
+    splits[0] = near;
+    for index in 1..count {
+        let fraction = (index as f32) / (count as f32);
+        let logarithmic = near * (far / near).powf(fraction);
+        let uniform = near + (far - near) * fraction;
+        splits[index] = lambda * logarithmic + (1f32 - lambda) * uniform;
+    }
+
+    splits
+}
+
+/// Computes the cascades covering the camera frustum for a directional light.
+///
+/// Each cascade is fitted around the bounding sphere of its frustum slice so that its size does
+/// not change when the camera rotates. The depth range covers the bounding sphere extended by
+/// [`CascadeConfig::caster_distance`] towards the light so that casters in front of the sphere
+/// are not clipped. Returns [`None`] if the camera view matrix is not invertible.
+///
+/// `light_direction` is the direction the light travels in, i.e. pointing away from the light
+/// source.
+pub fn compute_cascades(camera: &CameraFrustum, light_direction: &Vec3f32, config: &CascadeConfig) -> Option<Vec<ShadowCascade>> {
+    let inverse_view = camera.view.try_inverse()?;
+    let light_direction = light_direction.try_normalize(f32::EPSILON)?;
+    let up = if light_direction[1].abs() > 0.99f32 { Vec3f32::new(0f32, 0f32, 1f32) } else { Vec3f32::new(0f32, 1f32, 0f32) };
+    let light_view = Mat4f32::look_at_rh(&Vec3f32::zeros().into(), &light_direction.into(), &up);
+
+    let count = (config.cascade_count as usize).clamp(1, MAX_CASCADES);
+    let splits = compute_cascade_splits(camera.near, camera.far, config.cascade_count, config.split_lambda);
+    let tan_y = (camera.fov_y / 2f32).tan();
+    let tan_x = tan_y * camera.aspect;
+
+    let mut cascades = Vec::with_capacity(count);
+    for index in 0..count {
+        let (split_near, split_far) = (splits[index], splits[index + 1]);
+
+        let mut corners = [Vec3f32::zeros(); 8];
+        for (corner_index, corner) in corners.iter_mut().enumerate() {
+            let distance = if corner_index & 4 == 0 { split_near } else { split_far };
+            let x = if corner_index & 1 == 0 { -tan_x } else { tan_x } * distance;
+            let y = if corner_index & 2 == 0 { -tan_y } else { tan_y } * distance;
+            *corner = (inverse_view * Vec4f32::new(x, y, -distance, 1f32)).xyz();
+        }
+
+        let center = corners.iter().fold(Vec3f32::zeros(), |acc, c| acc + c) / 8f32;
+        let radius = corners.iter().map(|c| (c - center).norm()).fold(0f32, f32::max).max(f32::EPSILON);
+
+        // Snap the center to the texel grid in light space to avoid shimmering
+        let texel_size = (radius * 2f32) / (config.resolution.max(1) as f32);
+        let light_center = (light_view * center.push(1f32)).xyz();
+        let center_x = (light_center[0] / texel_size).floor() * texel_size;
+        let center_y = (light_center[1] / texel_size).floor() * texel_size;
+        let depth = -light_center[2];
+
+        let projection = make_orthographic(
+            center_x - radius, center_x + radius,
+            center_y - radius, center_y + radius,
+            depth - radius - config.caster_distance.max(0f32), depth + radius
+        );
+
+        cascades.push(ShadowCascade {
+            view_projection: projection * light_view,
+            split_near,
+            split_far,
+        });
+    }
+
+    Some(cascades)
+}
+
+/// Creates a right handed orthographic projection mapping depth to the vulkan `[0, 1]` range.
+fn make_orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4f32 {
+    Mat4f32::new(
+        2f32 / (right - left), 0f32, 0f32, -(right + left) / (right - left),
+        0f32, 2f32 / (top - bottom), 0f32, -(top + bottom) / (top - bottom),
+        0f32, 0f32, -1f32 / (far - near), -near / (far - near),
+        0f32, 0f32, 0f32, 1f32
+    )
+}
+
+/// Transforms an axis aligned box and returns the axis aligned box enclosing the result.
+pub fn transform_aabb(transform: &Mat4f32, min: &Vec3f32, max: &Vec3f32) -> (Vec3f32, Vec3f32) {
+    let mut result_min = Vec3f32::from_element(f32::INFINITY);
+    let mut result_max = Vec3f32::from_element(f32::NEG_INFINITY);
+    for index in 0..8 {
+        let corner = Vec4f32::new(
+            if index & 1 == 0 { min[0] } else { max[0] },
+            if index & 2 == 0 { min[1] } else { max[1] },
+            if index & 4 == 0 { min[2] } else { max[2] },
+            1f32
+        );
+        let transformed = (transform * corner).xyz();
+        result_min = result_min.inf(&transformed);
+        result_max = result_max.sup(&transformed);
+    }
+
+    (result_min, result_max)
+}
+
+/// The std140 layout of the shadow uniform block defined in mc_shadow.glsl.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ShadowUniforms {
+    #[allow(unused)]
+    cascades: [Mat4f32; MAX_CASCADES],
+
+    #[allow(unused)]
+    inverse_camera_view: Mat4f32,
+
+    #[allow(unused)]
+    split_far: Vec4f32,
+
+    #[allow(unused)]
+    cascade_count: u32,
+
+    #[allow(unused)]
+    blend_fraction: f32,
+
+    _padding0: [u8; 8],
+}
+const_assert_eq!(std::mem::size_of::<ShadowUniforms>(), 352);
+const_assert_eq!(std::mem::size_of::<ShadowUniforms>() % 16, 0);
+
+unsafe impl Zeroable for ShadowUniforms {}
+unsafe impl Pod for ShadowUniforms {}
+
+impl ShadowUniforms {
+    /// Creates uniforms with no cascades. Shaders treat every fragment as fully lit.
+    pub fn disabled() -> Self {
+        Self {
+            cascades: [Mat4f32::identity(); MAX_CASCADES],
+            inverse_camera_view: Mat4f32::identity(),
+            split_far: Vec4f32::zeros(),
+            cascade_count: 0,
+            blend_fraction: 0f32,
+            _padding0: Default::default(),
+        }
+    }
+
+    /// Creates the uniforms for a set of cascades computed by [`compute_cascades`].
+    ///
+    /// Returns [`None`] if the camera view matrix is not invertible.
+    pub fn new(camera: &CameraFrustum, cascades: &[ShadowCascade], config: &CascadeConfig) -> Option<Self> {
+        let mut result = Self::disabled();
+        result.inverse_camera_view = camera.view.try_inverse()?;
+        result.cascade_count = cascades.len().min(MAX_CASCADES) as u32;
+        result.blend_fraction = config.blend_fraction.clamp(0f32, 1f32);
+        for (index, cascade) in cascades.iter().take(MAX_CASCADES).enumerate() {
+            result.cascades[index] = cascade.view_projection;
+            result.split_far[index] = cascade.split_far;
+        }
+
+        Some(result)
+    }
+
+    /// Returns the transform from the view space of the camera to the space the cascades are
+    /// computed in.
+    pub fn get_inverse_camera_view(&self) -> &Mat4f32 {
+        &self.inverse_camera_view
+    }
+}
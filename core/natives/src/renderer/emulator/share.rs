@@ -1,27 +1,48 @@
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::{Arc, Condvar, Mutex, Weak};
 use std::time::{Duration, Instant};
 use std::panic::RefUnwindSafe;
 use std::collections::{HashMap, VecDeque};
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU32, AtomicU64};
 use ash::vk;
 
+use crate::renderer::emulator::camera_effects::CameraEffects;
 use crate::renderer::emulator::descriptors::DescriptorPool;
+use crate::renderer::emulator::global_objects::{GlobalMesh, MeshContentKey};
+use crate::renderer::emulator::jobs::JobSystem;
 use crate::renderer::emulator::worker::WorkerTask;
-use crate::renderer::emulator::mc_shaders::{McUniform, Shader, ShaderId, VertexFormat};
+use crate::renderer::emulator::mc_shaders::{McUniform, Shader, ShaderId, ShaderSpecialization, VertexFormat, VertexFormatError, VertexFormatId};
+use crate::renderer::emulator::pass::SubmitMode;
+use crate::renderer::emulator::render_budget::RenderBudget;
+use crate::renderer::emulator::render_layer::{InsertionPoint, RenderLayer, RenderLayerError, RenderLayerId, RenderLayerRegistry, RenderLayerState, RenderOrderEntry};
 
 use crate::prelude::*;
 use crate::renderer::emulator::immediate::{ImmediateBuffer, ImmediatePool};
 use crate::renderer::emulator::staging::StagingMemoryPool;
+use crate::renderer::emulator::watchdog::SubmissionTracker;
 
 pub(super) struct Share {
     id: UUID,
     device: Arc<DeviceContext>,
     current_pass: AtomicU64,
+    frames_in_flight: AtomicU32,
+
+    /// The maximum number of bytes uploaded per pass. 0 if uploads are not limited.
+    upload_budget: AtomicU64,
+    render_budget: Mutex<RenderBudget>,
+    submit_mode: Mutex<SubmitMode>,
+    camera_effects: Mutex<CameraEffects>,
+    /// The current resource generation. See [`EmulatorRenderer::bump_resource_generation`].
+    resource_generation: AtomicU64,
 
     staging_memory: Mutex<StagingMemoryPool>,
     immediate_buffers: ImmediatePool,
-    shader_database: Mutex<HashMap<ShaderId, Arc<Shader>>>,
+    shader_database: Mutex<ShaderTable>,
+    vertex_formats: Mutex<HashMap<VertexFormatId, VertexFormat>>,
+    render_layers: Mutex<RenderLayerRegistry>,
+    mesh_cache: Mutex<HashMap<MeshContentKey, Weak<GlobalMesh>>>,
     descriptors: Mutex<DescriptorPool>,
+    jobs: Arc<JobSystem>,
+    submissions: SubmissionTracker,
     channel: Mutex<Channel>,
     signal: Condvar,
 }
@@ -29,53 +50,231 @@ pub(super) struct Share {
 impl Share {
     const PASS_ID_ACTIVE_BIT: u64 = 1u64 << 63;
 
-    pub(super) fn new(device: Arc<DeviceContext>) -> Self {
+    pub(super) fn new(device: Arc<DeviceContext>, frames_in_flight: u32, jobs: Arc<JobSystem>) -> Self {
         let queue = device.get_main_queue();
 
         let staging_memory = StagingMemoryPool::new(device.clone());
-        let immediate_buffers = ImmediatePool::new(device.clone());
-        let descriptors = Mutex::new(DescriptorPool::new(device.clone()));
+        let immediate_buffers = ImmediatePool::new(device.clone(), frames_in_flight);
+        let descriptors = Mutex::new(DescriptorPool::new(device.clone(), frames_in_flight));
 
         Self {
             id: UUID::new(),
             device,
             current_pass: AtomicU64::new(0),
+            frames_in_flight: AtomicU32::new(frames_in_flight),
+
+            upload_budget: AtomicU64::new(0),
+            render_budget: Mutex::new(RenderBudget::default()),
+            submit_mode: Mutex::new(SubmitMode::Streaming),
+            camera_effects: Mutex::new(CameraEffects::default()),
+            resource_generation: AtomicU64::new(0),
 
             staging_memory: Mutex::new(staging_memory),
             immediate_buffers,
-            shader_database: Mutex::new(HashMap::new()),
+            shader_database: Mutex::new(ShaderTable::new()),
+            vertex_formats: Mutex::new(HashMap::new()),
+            render_layers: Mutex::new(RenderLayerRegistry::new()),
+            mesh_cache: Mutex::new(HashMap::new()),
             descriptors,
+            jobs,
+            submissions: SubmissionTracker::new(),
             channel: Mutex::new(Channel::new()),
             signal: Condvar::new(),
         }
     }
 
+    /// Returns the id of the renderer instance. Objects created by a previous instance (for example
+    /// before the device was recreated) must not be used with this instance.
+    pub(super) fn get_id(&self) -> UUID {
+        self.id
+    }
+
     pub(super) fn get_device(&self) -> &Arc<DeviceContext> {
         &self.device
     }
 
+    pub(super) fn get_frames_in_flight(&self) -> u32 {
+        self.frames_in_flight.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Rebuilds all per frame resources for a new number of frames in flight. Blocks until all
+    /// passes which are currently in flight have completed.
+    pub(super) fn set_frames_in_flight(&self, frames_in_flight: u32) {
+        self.immediate_buffers.resize(&self.device, frames_in_flight, || {
+            // No frame slot is in use so the uniform buffer is no longer accessed by the gpu
+            *self.descriptors.lock().unwrap() = DescriptorPool::new(self.device.clone(), frames_in_flight);
+            self.frames_in_flight.store(frames_in_flight, std::sync::atomic::Ordering::Release);
+        });
+    }
+
+    pub(super) fn get_job_system(&self) -> &Arc<JobSystem> {
+        &self.jobs
+    }
+
+    pub(super) fn get_upload_budget(&self) -> vk::DeviceSize {
+        self.upload_budget.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub(super) fn set_upload_budget(&self, budget: vk::DeviceSize) {
+        self.upload_budget.store(budget, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub(super) fn get_render_budget(&self) -> RenderBudget {
+        self.render_budget.lock().unwrap().clone()
+    }
+
+    pub(super) fn set_render_budget(&self, budget: RenderBudget) {
+        *self.render_budget.lock().unwrap() = budget;
+    }
+
+    pub(super) fn get_submit_mode(&self) -> SubmitMode {
+        *self.submit_mode.lock().unwrap()
+    }
+
+    pub(super) fn set_submit_mode(&self, mode: SubmitMode) {
+        *self.submit_mode.lock().unwrap() = mode;
+    }
+
+    pub(super) fn get_camera_effects(&self) -> CameraEffects {
+        *self.camera_effects.lock().unwrap()
+    }
+
+    pub(super) fn set_camera_effects(&self, effects: CameraEffects) {
+        *self.camera_effects.lock().unwrap() = effects;
+    }
+
+    pub(super) fn get_resource_generation(&self) -> u64 {
+        self.resource_generation.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Increments the resource generation and returns the new generation.
+    pub(super) fn bump_resource_generation(&self) -> u64 {
+        self.resource_generation.fetch_add(1, std::sync::atomic::Ordering::AcqRel) + 1
+    }
+
+    pub(super) fn get_submissions(&self) -> &SubmissionTracker {
+        &self.submissions
+    }
+
     pub(super) fn get_staging_pool(&self) -> &Mutex<StagingMemoryPool> {
         &self.staging_memory
     }
 
-    pub(super) fn create_shader(&self, vertex_format: &VertexFormat, used_uniforms: McUniform) -> ShaderId {
-        let shader = Shader::new(*vertex_format, used_uniforms);
+    pub(super) fn create_shader(&self, vertex_format: &VertexFormat, used_uniforms: McUniform, specialization: ShaderSpecialization) -> ShaderId {
+        let shader = Shader::new_specialized(*vertex_format, used_uniforms, specialization);
         let id = shader.get_id();
 
         let mut guard = self.shader_database.lock().unwrap();
-        guard.insert(id, shader);
+        guard.insert(shader);
 
         id
     }
 
     pub(super) fn drop_shader(&self, id: ShaderId) {
         let mut guard = self.shader_database.lock().unwrap();
-        guard.remove(&id);
+        guard.remove(id);
     }
 
     pub(super) fn get_shader(&self, id: ShaderId) -> Option<Arc<Shader>> {
         let guard = self.shader_database.lock().unwrap();
-        guard.get(&id).cloned()
+        guard.get_index(id).and_then(|index| guard.get(index))
+    }
+
+    /// Returns the dense index of a shader. See [`ShaderTable`].
+    pub(super) fn get_shader_index(&self, id: ShaderId) -> Option<(u32, Arc<Shader>)> {
+        let guard = self.shader_database.lock().unwrap();
+        let index = guard.get_index(id)?;
+        guard.get(index).map(|shader| (index, shader))
+    }
+
+    pub(super) fn register_vertex_format(&self, vertex_format: &VertexFormat) -> Result<VertexFormatId, VertexFormatError> {
+        vertex_format.validate(&self.device)?;
+
+        let id = VertexFormatId::new();
+        self.vertex_formats.lock().unwrap().insert(id, *vertex_format);
+        Ok(id)
+    }
+
+    pub(super) fn unregister_vertex_format(&self, id: VertexFormatId) {
+        self.vertex_formats.lock().unwrap().remove(&id);
+    }
+
+    pub(super) fn get_vertex_format(&self, id: VertexFormatId) -> Option<VertexFormat> {
+        self.vertex_formats.lock().unwrap().get(&id).copied()
+    }
+
+    pub(super) fn register_render_layer(&self, name: &str, state: &RenderLayerState) -> Result<RenderLayerId, RenderLayerError> {
+        self.render_layers.lock().unwrap().register(name, state)
+    }
+
+    pub(super) fn unregister_render_layer(&self, id: RenderLayerId) {
+        self.render_layers.lock().unwrap().unregister(id)
+    }
+
+    pub(super) fn get_render_layer(&self, id: RenderLayerId) -> Option<Arc<RenderLayer>> {
+        self.render_layers.lock().unwrap().get(id)
+    }
+
+    pub(super) fn isolate_render_layer(&self, id: Option<RenderLayerId>) {
+        self.render_layers.lock().unwrap().isolate(id)
+    }
+
+    pub(super) fn find_render_layer(&self, name: &str) -> Option<RenderLayerId> {
+        self.render_layers.lock().unwrap().find(name)
+    }
+
+    pub(super) fn get_render_order(&self) -> Vec<RenderOrderEntry> {
+        self.render_layers.lock().unwrap().get_order().to_vec()
+    }
+
+    /// Returns the registered layers in render order without the insertion points.
+    pub(super) fn get_render_layer_order(&self) -> Vec<RenderLayerId> {
+        self.render_layers.lock().unwrap().get_order().iter().filter_map(|entry| match entry {
+            RenderOrderEntry::Layer(id) => Some(*id),
+            RenderOrderEntry::Point(_) => None,
+        }).collect()
+    }
+
+    pub(super) fn set_render_order(&self, order: &[RenderOrderEntry]) -> Result<(), RenderLayerError> {
+        self.render_layers.lock().unwrap().set_order(order)
+    }
+
+    pub(super) fn insert_render_layer(&self, id: RenderLayerId, point: InsertionPoint) -> Result<(), RenderLayerError> {
+        self.render_layers.lock().unwrap().insert_at(id, point)
+    }
+
+    pub(super) fn get_cached_mesh(&self, key: &MeshContentKey) -> Option<Arc<GlobalMesh>> {
+        let guard = self.mesh_cache.lock().unwrap();
+        guard.get(key).and_then(Weak::upgrade)
+    }
+
+    /// Returns all live meshes in the deduplication cache.
+    pub(super) fn get_all_cached_meshes(&self) -> Vec<Arc<GlobalMesh>> {
+        let guard = self.mesh_cache.lock().unwrap();
+        guard.values().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Inserts a mesh into the deduplication cache. If a live mesh with the same key already exists
+    /// the cache is not modified and the existing mesh is returned.
+    ///
+    /// Meshes must never be dropped while the cache lock is held since their drop function calls
+    /// [`Share::remove_cached_mesh`].
+    pub(super) fn insert_cached_mesh(&self, key: MeshContentKey, mesh: &Arc<GlobalMesh>) -> Option<Arc<GlobalMesh>> {
+        let mut guard = self.mesh_cache.lock().unwrap();
+        if let Some(existing) = guard.get(&key).and_then(Weak::upgrade) {
+            return Some(existing);
+        }
+        guard.insert(key, Arc::downgrade(mesh));
+        None
+    }
+
+    /// Removes the cache entry of a dropped mesh. The entry is only removed if it does not refer to
+    /// a live mesh since a new mesh may have been inserted after the old one was released.
+    pub(super) fn remove_cached_mesh(&self, key: &MeshContentKey) {
+        let mut guard = self.mesh_cache.lock().unwrap();
+        if guard.get(key).map_or(false, |weak| weak.strong_count() == 0) {
+            guard.remove(key);
+        }
     }
 
     pub(super) fn get_current_pass_id(&self) -> Option<u64> {
@@ -87,6 +286,11 @@ impl Share {
         }
     }
 
+    /// Returns the id of the current pass or of the most recently ended pass if no pass is active.
+    pub(super) fn get_last_pass_id(&self) -> u64 {
+        self.current_pass.load(std::sync::atomic::Ordering::Acquire) & !Self::PASS_ID_ACTIVE_BIT
+    }
+
     pub(super) fn try_start_pass_id(&self) -> Option<u64> {
         loop {
             let old_id = self.current_pass.load(std::sync::atomic::Ordering::Acquire);
@@ -120,16 +324,21 @@ impl Share {
         });
     }
 
+    /// Returns the immediate buffer of the next free frame slot. Blocks if all frame slots are in
+    /// use. Since the buffer is only returned once the pass using it has completed all other per
+    /// frame resources of the slot are reset as well.
     pub(super) fn get_next_immediate_buffer(&self) -> Box<ImmediateBuffer> {
-        self.immediate_buffers.get_next_buffer()
+        let buffer = self.immediate_buffers.get_next_buffer();
+        self.descriptors.lock().unwrap().reset_frame(buffer.get_frame_index());
+        buffer
     }
 
     pub(super) fn return_immediate_buffer(&self, buffer: Box<ImmediateBuffer>) {
         self.immediate_buffers.return_buffer(buffer);
     }
 
-    pub(super) fn allocate_uniform(&self, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
-        self.descriptors.lock().unwrap().allocate_uniform(data)
+    pub(super) fn allocate_uniform(&self, frame_index: u32, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
+        self.descriptors.lock().unwrap().allocate_uniform(frame_index, data)
     }
 
     pub(super) fn push_task(&self, task: WorkerTask) {
@@ -137,6 +346,14 @@ impl Share {
         self.signal.notify_one();
     }
 
+    pub(super) fn push_tasks(&self, tasks: Vec<WorkerTask>) {
+        if tasks.is_empty() {
+            return;
+        }
+        self.channel.lock().unwrap().queue.extend(tasks);
+        self.signal.notify_one();
+    }
+
     pub(super) fn try_get_next_task_timeout(&self, timeout: Duration) -> NextTaskResult {
         let start = Instant::now();
 
@@ -181,6 +398,43 @@ impl Eq for Share {
 impl RefUnwindSafe for Share {
 }
 
+/// Maps shader ids to dense indices assigned at registration so hot paths can use array indexing
+/// instead of hashing ids. Indices are never reused, so an index stays unique even if its shader is
+/// dropped while a pass still refers to it.
+struct ShaderTable {
+    indices: HashMap<ShaderId, u32>,
+    shaders: Vec<Option<Arc<Shader>>>,
+}
+
+impl ShaderTable {
+    fn new() -> Self {
+        Self {
+            indices: HashMap::new(),
+            shaders: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, shader: Arc<Shader>) {
+        let index = self.shaders.len() as u32;
+        self.indices.insert(shader.get_id(), index);
+        self.shaders.push(Some(shader));
+    }
+
+    fn remove(&mut self, id: ShaderId) {
+        if let Some(index) = self.indices.remove(&id) {
+            self.shaders[index as usize] = None;
+        }
+    }
+
+    fn get_index(&self, id: ShaderId) -> Option<u32> {
+        self.indices.get(&id).copied()
+    }
+
+    fn get(&self, index: u32) -> Option<Arc<Shader>> {
+        self.shaders.get(index as usize).and_then(Option::clone)
+    }
+}
+
 pub(in crate::renderer::emulator) enum NextTaskResult {
     Ok(WorkerTask),
     Timeout,
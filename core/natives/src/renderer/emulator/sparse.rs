@@ -0,0 +1,581 @@
+//! Partially resident global images.
+//!
+//! Sparse global images only have memory bound to the pages which are actually needed. This allows
+//! very large atlases (for example of big resource packs) to be created without committing memory
+//! for the full image. Residency is tracked on the host per page of every mip level which is not
+//! part of the mip tail. The mip tail is small and always resident.
+//!
+//! Pages are made resident when a region containing them is written or touched. Every touch
+//! records the current pass id as the last use of the page. Once the residency budget of an image
+//! is exceeded the least recently used pages which have not been touched in the last
+//! `frames_in_flight` passes are selected for eviction.
+//!
+//! All binding operations are executed by the worker on the sparse queue (see
+//! [`DeviceContext::get_sparse_queue`]) using a [`SparseBinder`]. Every submission of the worker
+//! waits for the most recently submitted binding operation so newly bound pages are visible to all
+//! later uploads and passes. Evicted pages are only unbound once all passes which used the image
+//! before the eviction have completed. If a page is touched again before it has been unbound the
+//! eviction is cancelled.
+
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::allocator::{Allocation, HostAccess};
+use crate::device::device::Queue;
+use crate::renderer::emulator::global_objects::{GlobalImage, GlobalObjectCreateError};
+use crate::renderer::emulator::pass::PassId;
+
+use crate::prelude::*;
+
+/// Residency statistics of a sparse global image.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SparseResidencyStats {
+    /// The size in bytes of a single page.
+    pub page_size: u64,
+
+    /// The number of pages across all layers and mip levels outside the mip tail.
+    pub total_pages: u32,
+
+    /// The number of pages which are currently resident and not selected for eviction.
+    pub resident_pages: u32,
+
+    /// The number of pages which have been selected for eviction but are not yet unbound.
+    pub evicting_pages: u32,
+
+    /// The total amount of device memory currently bound to the image including the mip tail and
+    /// pages which are being evicted.
+    pub resident_bytes: u64,
+
+    /// The amount of device memory used by the mip tail.
+    pub mip_tail_bytes: u64,
+
+    /// The residency budget of the image if any.
+    pub budget_bytes: Option<u64>,
+}
+
+#[derive(Copy, Clone)]
+enum PageState {
+    NonResident,
+    Resident(Allocation),
+
+    /// The page has been selected for eviction but has not yet been unbound by the worker.
+    Evicting(Allocation),
+}
+
+#[derive(Copy, Clone)]
+struct Page {
+    state: PageState,
+    last_used: u64,
+}
+
+struct MipLevelPages {
+    /// The size of the mip level in texels.
+    extent: Vec2u32,
+
+    /// The number of pages in each dimension.
+    page_count: Vec2u32,
+
+    /// The index of the first page of this level inside a layer.
+    first_page: usize,
+}
+
+/// Host side page residency state of a sparse image.
+pub(super) struct SparseResidency {
+    granularity: Vec2u32,
+    page_requirements: vk::MemoryRequirements,
+    layer_count: u32,
+    levels: Box<[MipLevelPages]>,
+    pages_per_layer: usize,
+    pages: Box<[Page]>,
+
+    mip_tail: Box<[Allocation]>,
+    mip_tail_size: u64,
+
+    resident_pages: u32,
+    evicting_pages: u32,
+    budget: Option<u64>,
+}
+
+impl SparseResidency {
+    /// Returns true if 2d sparse images of the format can be created on the device.
+    pub(super) fn is_format_supported(device: &DeviceContext, format: vk::Format) -> bool {
+        if device.get_sparse_queue().is_none() {
+            return false;
+        }
+
+        let properties = unsafe {
+            device.get_instance().vk().get_physical_device_sparse_image_format_properties(
+                device.get_functions().physical_device,
+                format,
+                vk::ImageType::TYPE_2D,
+                vk::SampleCountFlags::TYPE_1,
+                GlobalImage::IMAGE_USAGE,
+                vk::ImageTiling::OPTIMAL
+            )
+        };
+        properties.iter().any(|p| p.aspect_mask.contains(vk::ImageAspectFlags::COLOR))
+    }
+
+    /// Queries the sparse memory requirements of the image and allocates memory for the mip tail.
+    ///
+    /// Returns the residency state and the opaque binds of the mip tail which must be executed
+    /// before the image is used.
+    pub(super) fn new(device: &DeviceContext, image: vk::Image, size: Vec2u32, mip_levels: u32, layer_count: u32) -> Result<(Self, Box<[vk::SparseMemoryBind]>), GlobalObjectCreateError> {
+        let memory_requirements = unsafe {
+            device.vk().get_image_memory_requirements(image)
+        };
+        let sparse_requirements = unsafe {
+            device.vk().get_image_sparse_memory_requirements(image)
+        };
+        let requirements = sparse_requirements.iter().find(|r| r.format_properties.aspect_mask.contains(vk::ImageAspectFlags::COLOR)).ok_or_else(|| {
+            log::error!("Sparse image does not have color aspect memory requirements");
+            GlobalObjectCreateError::SparseNotSupported
+        })?;
+
+        let granularity = Vec2u32::new(requirements.format_properties.image_granularity.width, requirements.format_properties.image_granularity.height);
+        let page_requirements = vk::MemoryRequirements {
+            size: memory_requirements.alignment,
+            alignment: memory_requirements.alignment,
+            memory_type_bits: memory_requirements.memory_type_bits,
+        };
+
+        let first_tail_level = std::cmp::min(requirements.image_mip_tail_first_lod, mip_levels);
+        let mut levels = Vec::with_capacity(first_tail_level as usize);
+        let mut pages_per_layer = 0usize;
+        for level in 0..first_tail_level {
+            let extent = Vec2u32::new(std::cmp::max(size[0] >> level, 1), std::cmp::max(size[1] >> level, 1));
+            let page_count = Vec2u32::new((extent[0] + granularity[0] - 1) / granularity[0], (extent[1] + granularity[1] - 1) / granularity[1]);
+            levels.push(MipLevelPages {
+                extent,
+                page_count,
+                first_page: pages_per_layer,
+            });
+            pages_per_layer += (page_count[0] as usize) * (page_count[1] as usize);
+        }
+
+        let pages = std::iter::repeat(Page { state: PageState::NonResident, last_used: 0 })
+            .take(pages_per_layer * (layer_count as usize))
+            .collect();
+
+        let mut tail_binds = Vec::new();
+        let mut mip_tail = Box::from([]);
+        let mut mip_tail_size = 0;
+        if first_tail_level < mip_levels && requirements.image_mip_tail_size != 0 {
+            let tail_count = if requirements.format_properties.flags.contains(vk::SparseImageFormatFlags::SINGLE_MIPTAIL) {
+                1
+            } else {
+                layer_count
+            };
+            let tail_requirements = vk::MemoryRequirements {
+                size: requirements.image_mip_tail_size,
+                alignment: memory_requirements.alignment,
+                memory_type_bits: memory_requirements.memory_type_bits,
+            };
+            let tail_requirements: Box<_> = std::iter::repeat(tail_requirements).take(tail_count as usize).collect();
+            let allocations = unsafe {
+                device.get_allocator().allocate_memory_pages(tail_requirements.as_ref(), HostAccess::None)
+            }.ok_or(GlobalObjectCreateError::Allocation)?;
+
+            for (index, (_, info)) in allocations.iter().enumerate() {
+                tail_binds.push(vk::SparseMemoryBind {
+                    resource_offset: requirements.image_mip_tail_offset + (index as vk::DeviceSize) * requirements.image_mip_tail_stride,
+                    size: requirements.image_mip_tail_size,
+                    memory: info.get_device_memory(),
+                    memory_offset: info.get_offset(),
+                    flags: vk::SparseMemoryBindFlags::empty()
+                });
+            }
+            mip_tail = allocations.into_iter().map(|(allocation, _)| allocation).collect();
+            mip_tail_size = requirements.image_mip_tail_size * (tail_count as u64);
+        }
+
+        Ok((Self {
+            granularity,
+            page_requirements,
+            layer_count,
+            levels: levels.into_boxed_slice(),
+            pages_per_layer,
+            pages,
+
+            mip_tail,
+            mip_tail_size,
+
+            resident_pages: 0,
+            evicting_pages: 0,
+            budget: None,
+        }, tail_binds.into_boxed_slice()))
+    }
+
+    pub(super) fn set_budget(&mut self, budget: Option<u64>) {
+        self.budget = budget;
+    }
+
+    /// Marks all pages of all mip levels covering a region of mip level 0 as used in pass `tick`
+    /// and allocates memory for all non resident pages.
+    ///
+    /// Returns the binds required to make the new pages resident. If memory allocation fails the
+    /// affected pages stay non resident.
+    pub(super) fn make_resident(&mut self, device: &DeviceContext, layer: u32, offset: Vec2u32, extent: Vec2u32, tick: u64, binds: &mut Vec<vk::SparseImageMemoryBind>) {
+        if layer >= self.layer_count || extent[0] == 0 || extent[1] == 0 {
+            return;
+        }
+
+        let mut new_pages = Vec::new();
+        for (level, pages) in self.levels.iter().enumerate() {
+            let min = Vec2u32::new(offset[0] >> level, offset[1] >> level);
+            let max = Vec2u32::new(
+                std::cmp::min(((offset[0] + extent[0] - 1) >> level) + 1, pages.extent[0]),
+                std::cmp::min(((offset[1] + extent[1] - 1) >> level) + 1, pages.extent[1])
+            );
+
+            for page_y in (min[1] / self.granularity[1])..((max[1] + self.granularity[1] - 1) / self.granularity[1]) {
+                for page_x in (min[0] / self.granularity[0])..((max[0] + self.granularity[0] - 1) / self.granularity[0]) {
+                    let index = self.pages_per_layer * (layer as usize) + pages.first_page + (page_y * pages.page_count[0] + page_x) as usize;
+                    let page = &mut self.pages[index];
+                    page.last_used = std::cmp::max(page.last_used, tick);
+                    match page.state {
+                        PageState::NonResident => new_pages.push((index, level as u32, Vec2u32::new(page_x, page_y))),
+                        PageState::Resident(_) => {}
+                        PageState::Evicting(allocation) => {
+                            // The page has not been unbound yet so the eviction can simply be cancelled
+                            page.state = PageState::Resident(allocation);
+                            self.evicting_pages -= 1;
+                            self.resident_pages += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        if new_pages.is_empty() {
+            return;
+        }
+
+        let requirements: Box<_> = std::iter::repeat(self.page_requirements).take(new_pages.len()).collect();
+        let allocations = match unsafe {
+            device.get_allocator().allocate_memory_pages(requirements.as_ref(), HostAccess::None)
+        } {
+            Some(allocations) => allocations,
+            None => {
+                log::warn!("Failed to allocate {:?} sparse image pages", new_pages.len());
+                return;
+            }
+        };
+
+        for ((index, level, page), (allocation, info)) in new_pages.into_iter().zip(allocations.into_iter()) {
+            self.pages[index].state = PageState::Resident(allocation);
+            self.resident_pages += 1;
+            binds.push(self.make_page_bind(index, level, page, info.get_device_memory(), info.get_offset()));
+        }
+    }
+
+    /// Selects the least recently used pages for eviction until the budget is met. Pages used in
+    /// any pass after `keep_after` are never selected.
+    ///
+    /// Returns true if any page has been selected.
+    pub(super) fn select_evictions(&mut self, keep_after: u64) -> bool {
+        let budget_pages = match self.budget {
+            Some(budget) => (budget / self.page_requirements.size) as u32,
+            None => return false,
+        };
+        if self.resident_pages <= budget_pages {
+            return false;
+        }
+
+        let mut candidates: Vec<_> = self.pages.iter().enumerate().filter_map(|(index, page)| {
+            match page.state {
+                PageState::Resident(_) if page.last_used <= keep_after => Some((page.last_used, index)),
+                _ => None,
+            }
+        }).collect();
+        candidates.sort_unstable();
+
+        let mut selected = false;
+        for (_, index) in candidates.into_iter().take((self.resident_pages - budget_pages) as usize) {
+            if let PageState::Resident(allocation) = self.pages[index].state {
+                self.pages[index].state = PageState::Evicting(allocation);
+                self.resident_pages -= 1;
+                self.evicting_pages += 1;
+                selected = true;
+            }
+        }
+
+        if self.resident_pages > budget_pages {
+            log::debug!("Sparse image exceeds residency budget by {:?} recently used pages", self.resident_pages - budget_pages);
+        }
+
+        selected
+    }
+
+    /// Marks all pages selected for eviction as non resident.
+    ///
+    /// Returns the binds which unbind the memory of the pages and the allocations which must be
+    /// freed once the binds have completed.
+    pub(super) fn take_evictions(&mut self) -> (Box<[vk::SparseImageMemoryBind]>, Box<[Allocation]>) {
+        let mut binds = Vec::with_capacity(self.evicting_pages as usize);
+        let mut allocations = Vec::with_capacity(self.evicting_pages as usize);
+
+        for index in 0..self.pages.len() {
+            if let PageState::Evicting(allocation) = self.pages[index].state {
+                self.pages[index].state = PageState::NonResident;
+                allocations.push(allocation);
+
+                let (level, page) = self.get_page_location(index);
+                binds.push(self.make_page_bind(index, level, page, vk::DeviceMemory::null(), 0));
+            }
+        }
+        self.evicting_pages = 0;
+
+        (binds.into_boxed_slice(), allocations.into_boxed_slice())
+    }
+
+    pub(super) fn get_stats(&self) -> SparseResidencyStats {
+        let page_size = self.page_requirements.size;
+        SparseResidencyStats {
+            page_size,
+            total_pages: self.pages.len() as u32,
+            resident_pages: self.resident_pages,
+            evicting_pages: self.evicting_pages,
+            resident_bytes: ((self.resident_pages + self.evicting_pages) as u64) * page_size + self.mip_tail_size,
+            mip_tail_bytes: self.mip_tail_size,
+            budget_bytes: self.budget,
+        }
+    }
+
+    /// Frees all memory of the image. Must only be called once the image is no longer used by the
+    /// device.
+    pub(super) unsafe fn free_all(&mut self, device: &DeviceContext) {
+        let mut allocations: Vec<_> = self.mip_tail.iter().copied().collect();
+        for page in self.pages.iter_mut() {
+            match page.state {
+                PageState::Resident(allocation) | PageState::Evicting(allocation) => allocations.push(allocation),
+                PageState::NonResident => {}
+            }
+            page.state = PageState::NonResident;
+        }
+        self.mip_tail = Box::from([]);
+        self.resident_pages = 0;
+        self.evicting_pages = 0;
+
+        if !allocations.is_empty() {
+            device.get_allocator().free_memory_pages(allocations.as_slice());
+        }
+    }
+
+    fn get_page_location(&self, index: usize) -> (u32, Vec2u32) {
+        let in_layer = index % self.pages_per_layer;
+        let level = self.levels.iter().rposition(|l| l.first_page <= in_layer).unwrap();
+        let pages = &self.levels[level];
+        let local = (in_layer - pages.first_page) as u32;
+        (level as u32, Vec2u32::new(local % pages.page_count[0], local / pages.page_count[0]))
+    }
+
+    fn make_page_bind(&self, index: usize, level: u32, page: Vec2u32, memory: vk::DeviceMemory, memory_offset: vk::DeviceSize) -> vk::SparseImageMemoryBind {
+        let extent = self.levels[level as usize].extent;
+        let offset = page.component_mul(&self.granularity);
+
+        // Pages at the edge of a level may only cover the remaining texels
+        vk::SparseImageMemoryBind {
+            subresource: vk::ImageSubresource {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: level,
+                array_layer: (index / self.pages_per_layer) as u32
+            },
+            offset: vk::Offset3D { x: offset[0] as i32, y: offset[1] as i32, z: 0 },
+            extent: vk::Extent3D {
+                width: std::cmp::min(self.granularity[0], extent[0] - offset[0]),
+                height: std::cmp::min(self.granularity[1], extent[1] - offset[1]),
+                depth: 1
+            },
+            memory,
+            memory_offset,
+            flags: vk::SparseMemoryBindFlags::empty()
+        }
+    }
+}
+
+/// Sparse binding operation which makes memory resident. Created by the host and executed by the
+/// worker.
+pub(super) struct SparseImageBind {
+    pub(super) image: Arc<GlobalImage>,
+    pub(super) opaque_binds: Box<[vk::SparseMemoryBind]>,
+    pub(super) image_binds: Box<[vk::SparseImageMemoryBind]>,
+}
+
+/// Executes sparse binding operations on the sparse queue. Used by the worker.
+pub(super) struct SparseBinder {
+    device: Arc<DeviceContext>,
+    queue: Arc<Queue>,
+
+    /// Timeline semaphore signaled by every binding operation.
+    semaphore: vk::Semaphore,
+    value: u64,
+
+    /// Evictions waiting for all passes up to the pass id to complete.
+    pending_evictions: Vec<(Arc<GlobalImage>, PassId)>,
+
+    /// Submitted binding operations together with the memory which can be freed once the
+    /// semaphore reached the value. Also keeps the image alive until then.
+    in_flight: Vec<(u64, Arc<GlobalImage>, Box<[Allocation]>)>,
+}
+
+impl SparseBinder {
+    pub(super) fn new(device: Arc<DeviceContext>, queue: Arc<Queue>) -> Self {
+        let mut timeline = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+
+        let info = vk::SemaphoreCreateInfo::builder()
+            .push_next(&mut timeline);
+
+        let semaphore = unsafe {
+            device.vk().create_semaphore(&info, None)
+        }.unwrap_or_else(|err| {
+            log::error!("Failed to create sparse binding semaphore {:?}", err);
+            panic!()
+        });
+
+        Self {
+            device,
+            queue,
+            semaphore,
+            value: 0,
+            pending_evictions: Vec::new(),
+            in_flight: Vec::new(),
+        }
+    }
+
+    /// Submits a binding operation making memory resident.
+    pub(super) fn bind(&mut self, bind: SparseImageBind) {
+        self.submit(bind.image, &bind.opaque_binds, &bind.image_binds, Box::from([]));
+    }
+
+    /// Queues the eviction of all pages selected for eviction of an image. The pages are unbound
+    /// once all passes up to and including `after_pass` have completed.
+    pub(super) fn evict(&mut self, image: Arc<GlobalImage>, after_pass: PassId) {
+        self.pending_evictions.push((image, after_pass));
+    }
+
+    /// Executes all evictions whose passes have completed and frees memory of completed binding
+    /// operations.
+    pub(super) fn update<F: Fn(PassId) -> bool>(&mut self, is_pass_complete: F) {
+        if self.pending_evictions.iter().any(|(_, pass)| is_pass_complete(*pass)) {
+            let pending = std::mem::replace(&mut self.pending_evictions, Vec::new());
+            for (image, pass) in pending {
+                if is_pass_complete(pass) {
+                    let (binds, allocations) = image.take_sparse_evictions();
+                    if !binds.is_empty() {
+                        self.submit(image, &[], &binds, allocations);
+                    }
+                } else {
+                    self.pending_evictions.push((image, pass));
+                }
+            }
+        }
+
+        if !self.in_flight.is_empty() {
+            let completed = unsafe {
+                self.device.timeline_semaphore_khr().get_semaphore_counter_value(self.semaphore)
+            }.unwrap_or_else(|err| {
+                log::error!("Failed to query sparse binding semaphore value {:?}", err);
+                panic!()
+            });
+
+            let device = &self.device;
+            self.in_flight.retain(|(value, _, allocations)| {
+                if *value <= completed {
+                    if !allocations.is_empty() {
+                        unsafe { device.get_allocator().free_memory_pages(allocations) };
+                    }
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+
+    /// Returns the wait operation for the most recently submitted binding operation.
+    pub(super) fn get_wait(&self) -> Option<vk::SemaphoreSubmitInfo> {
+        if self.value == 0 {
+            None
+        } else {
+            Some(vk::SemaphoreSubmitInfo::builder()
+                .semaphore(self.semaphore)
+                .value(self.value)
+                .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .build()
+            )
+        }
+    }
+
+    fn submit(&mut self, image: Arc<GlobalImage>, opaque_binds: &[vk::SparseMemoryBind], image_binds: &[vk::SparseImageMemoryBind], allocations: Box<[Allocation]>) {
+        let handle = image.get_image_handle();
+        let opaque_infos = [
+            vk::SparseImageOpaqueMemoryBindInfo::builder()
+                .image(handle)
+                .binds(opaque_binds)
+                .build()
+        ];
+        let image_infos = [
+            vk::SparseImageMemoryBindInfo::builder()
+                .image(handle)
+                .binds(image_binds)
+                .build()
+        ];
+
+        // Every operation waits for the previous one so binds and unbinds of a page are executed in order
+        let wait_value = self.value;
+        self.value += 1;
+        let signal_value = self.value;
+
+        let wait_values = [wait_value];
+        let signal_values = [signal_value];
+        let semaphores = [self.semaphore];
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+
+        let mut info = vk::BindSparseInfo::builder()
+            .wait_semaphores(&semaphores)
+            .signal_semaphores(&semaphores);
+        if !opaque_binds.is_empty() {
+            info = info.image_opaque_binds(&opaque_infos);
+        }
+        if !image_binds.is_empty() {
+            info = info.image_binds(&image_infos);
+        }
+        let info = info.push_next(&mut timeline_info).build();
+
+        unsafe {
+            self.queue.bind_sparse(std::slice::from_ref(&info), None)
+        }.unwrap_or_else(|err| {
+            log::error!("Failed to submit sparse binding operation {:?}", err);
+            panic!()
+        });
+
+        self.in_flight.push((signal_value, image, allocations));
+    }
+}
+
+impl Drop for SparseBinder {
+    fn drop(&mut self) {
+        unsafe {
+            self.queue.wait_idle()
+        }.unwrap_or_else(|err| {
+            log::error!("Failed to wait for sparse queue idle {:?}", err);
+            panic!()
+        });
+
+        for (_, _, allocations) in std::mem::replace(&mut self.in_flight, Vec::new()) {
+            if !allocations.is_empty() {
+                unsafe { self.device.get_allocator().free_memory_pages(&allocations) };
+            }
+        }
+
+        unsafe {
+            self.device.vk().destroy_semaphore(self.semaphore, None);
+        }
+    }
+}
@@ -5,113 +5,181 @@ use ash::vk;
 use crate::allocator::{Allocation, HostAccess};
 
 use crate::prelude::DeviceContext;
-use crate::util::alloc::RingAllocator;
 
+/// Identifies a allocation made from a [`StagingMemoryPool`]. Must be passed to
+/// [`StagingMemoryPool::free`] once the allocation is no longer used by the device.
 pub struct StagingAllocationId {
+    /// The index of the size class or [`StagingAllocationId::DEDICATED`] for dedicated buffers.
+    class: u8,
     buffer_id: u16,
     slot_id: u16,
 }
 
+impl StagingAllocationId {
+    const DEDICATED: u8 = u8::MAX;
+}
+
+/// Staging memory allocator using size class bins.
+///
+/// Allocations are rounded up to the next power of 2 and served from fixed size slots of large
+/// slab buffers. Each size class uses its own slabs so allocating and freeing is cheap and does
+/// not fragment memory when thousands of small uploads are made per second. Allocations larger
+/// than the largest size class receive a dedicated buffer which is destroyed once freed.
+///
+/// Allocations must only be freed after the device has finished using them, i.e. after the
+/// submission using them has completed.
 pub struct StagingMemoryPool {
     device: Arc<DeviceContext>,
-    next_buffer_id: u16,
-    current_buffer_id: u16,
-    current_buffer: StagingBuffer,
-    old_buffers: Vec<(u16, StagingBuffer)>,
-
-    /// Multiplier applied to the size of a new backing buffer allocation.
-    /// `0` is a multiplier of 1.0 and [`u8::MAX`] a multiplier of 2.0
-    over_allocation: u8,
-
-    /// The threshold of used memory at which point a backing buffer is reduced in size.
-    /// `0` defines a threshold of `0%` i.e. never reduce and [`u8::MAX`] a threshold of `100%` i.e.
-    /// always reduce.
-    reduce_threshold: u8,
+    classes: Box<[SizeClass]>,
+    dedicated: Vec<Option<StagingBuffer>>,
 }
 
 impl StagingMemoryPool {
-    const MIN_BUFFER_SIZE: vk::DeviceSize = 2u64.pow(24); // 16MB
+    const MIN_CLASS_SIZE_LOG2: u32 = 12; // 4KB
+    const CLASS_COUNT: u32 = 11; // Largest class is 4MB
+    const SLAB_SIZE: vk::DeviceSize = 2u64.pow(24); // 16MB
+
+    /// The maximum number of completely unused slabs kept alive per size class.
+    const MAX_EMPTY_SLABS: usize = 1;
 
     pub(super) fn new(device: Arc<DeviceContext>) -> Self {
-        let current_buffer = StagingBuffer::new(device.clone(), Self::MIN_BUFFER_SIZE);
+        let classes = (0..Self::CLASS_COUNT).map(|index| {
+            SizeClass::new(1u64 << (Self::MIN_CLASS_SIZE_LOG2 + index))
+        }).collect();
 
         Self {
             device,
-            next_buffer_id: 1,
-            current_buffer_id: 0,
-            current_buffer,
-            old_buffers: Vec::new(),
-            over_allocation: 76,
-            reduce_threshold: 127
+            classes,
+            dedicated: Vec::new(),
         }
     }
 
     pub(super) fn allocate(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> (StagingAllocation, StagingAllocationId) {
-        if let Some((alloc, slot_id)) = self.current_buffer.try_allocate(size, alignment) {
-            (alloc, StagingAllocationId{ buffer_id: self.current_buffer_id, slot_id })
+        // Slots are aligned to their size so a alignment up to the slot size is always satisfied
+        let required = std::cmp::max(std::cmp::max(size, alignment), 1);
+        let class_log2 = std::cmp::max(u64::BITS - (required - 1).leading_zeros(), Self::MIN_CLASS_SIZE_LOG2);
+        let class = class_log2 - Self::MIN_CLASS_SIZE_LOG2;
+
+        if class < Self::CLASS_COUNT {
+            let (alloc, buffer_id, slot_id) = self.classes[class as usize].allocate(&self.device, Self::SLAB_SIZE);
+            (alloc, StagingAllocationId { class: class as u8, buffer_id, slot_id })
         } else {
-            self.create_new_buffer(size);
-            let (alloc, slot_id) = self.current_buffer.try_allocate(size, alignment).unwrap();
-            (alloc, StagingAllocationId{ buffer_id: 0, slot_id })
+            self.allocate_dedicated(size)
         }
     }
 
     pub(super) fn free(&mut self, allocation: StagingAllocationId) {
-        if allocation.buffer_id == self.current_buffer_id {
-            self.current_buffer.free(allocation.slot_id);
+        if allocation.class == StagingAllocationId::DEDICATED {
+            if self.dedicated.get_mut(allocation.buffer_id as usize).and_then(Option::take).is_none() {
+                log::error!("Called StagingMemoryPool::free with invalid dedicated buffer id {}", allocation.buffer_id);
+                panic!()
+            }
         } else {
-            let mut delete = None;
-            for (index, (id, buffer)) in self.old_buffers.iter_mut().enumerate() {
-                if *id == allocation.buffer_id {
-                    buffer.free(allocation.slot_id);
-                    if buffer.is_empty() {
-                        delete = Some(index);
-                    }
-                    break;
+            self.classes[allocation.class as usize].free(allocation.buffer_id, allocation.slot_id, Self::MAX_EMPTY_SLABS);
+        }
+    }
+
+    fn allocate_dedicated(&mut self, size: vk::DeviceSize) -> (StagingAllocation, StagingAllocationId) {
+        let buffer = StagingBuffer::new(self.device.clone(), size);
+        let alloc = buffer.get_allocation(0);
+
+        let buffer_id = find_or_push(&mut self.dedicated, buffer);
+        (alloc, StagingAllocationId { class: StagingAllocationId::DEDICATED, buffer_id, slot_id: 0 })
+    }
+}
+
+struct SizeClass {
+    slot_size: vk::DeviceSize,
+    slabs: Vec<Option<StagingSlab>>,
+}
+
+impl SizeClass {
+    fn new(slot_size: vk::DeviceSize) -> Self {
+        Self {
+            slot_size,
+            slabs: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self, device: &Arc<DeviceContext>, slab_size: vk::DeviceSize) -> (StagingAllocation, u16, u16) {
+        for (slab_id, slab) in self.slabs.iter_mut().enumerate() {
+            if let Some(slab) = slab {
+                if let Some((alloc, slot_id)) = slab.try_allocate(self.slot_size) {
+                    return (alloc, slab_id as u16, slot_id);
                 }
             }
-            if let Some(index) = delete {
-                self.old_buffers.swap_remove(index);
-            }
         }
+
+        let slot_count = std::cmp::max(slab_size / self.slot_size, 1) as u16;
+        let mut slab = StagingSlab::new(device.clone(), self.slot_size, slot_count);
+        let (alloc, slot_id) = slab.try_allocate(self.slot_size).unwrap();
+
+        let slab_id = find_or_push(&mut self.slabs, slab);
+        (alloc, slab_id, slot_id)
     }
 
-    fn create_new_buffer(&mut self, additional_size: vk::DeviceSize) {
-        let mut usage_sum = self.current_buffer.used_byte_count();
-        for (_, old) in &self.old_buffers {
-            usage_sum += old.used_byte_count();
+    fn free(&mut self, slab_id: u16, slot_id: u16, max_empty_slabs: usize) {
+        let slab = self.slabs.get_mut(slab_id as usize).and_then(Option::as_mut).unwrap_or_else(|| {
+            log::error!("Called StagingMemoryPool::free with invalid slab id {} (slot size {})", slab_id, self.slot_size);
+            panic!()
+        });
+        slab.free(slot_id);
+
+        if slab.is_empty() {
+            let empty_count = self.slabs.iter().filter(|slab| slab.as_ref().map_or(false, StagingSlab::is_empty)).count();
+            if empty_count > max_empty_slabs {
+                self.slabs[slab_id as usize] = None;
+            }
         }
-        usage_sum += additional_size;
+    }
+}
 
-        let new_size = usage_sum + ((usage_sum * (self.over_allocation as u64)) / (u8::MAX as u64));
-        let new_size = std::cmp::max(new_size, Self::MIN_BUFFER_SIZE);
+/// A buffer divided into slots of equal size.
+struct StagingSlab {
+    buffer: StagingBuffer,
+    free_slots: Vec<u16>,
+    slot_count: u16,
+}
 
-        // Yes this is slow but it shouldn't matter since we never have many buffers
-        while self.is_id_unused(self.next_buffer_id) {
-            // Technically there is a potential infinite loop here but at that point we would have
-            // allocated at least 1TB of memory so i will accept this risk
-            self.next_buffer_id = self.next_buffer_id.wrapping_add(1);
+impl StagingSlab {
+    fn new(device: Arc<DeviceContext>, slot_size: vk::DeviceSize, slot_count: u16) -> Self {
+        let buffer = StagingBuffer::new(device, slot_size * (slot_count as vk::DeviceSize));
+
+        Self {
+            buffer,
+            free_slots: (0..slot_count).rev().collect(),
+            slot_count,
         }
-        let id = self.next_buffer_id;
-        self.next_buffer_id = self.next_buffer_id.wrapping_add(1);
+    }
 
-        let buffer = StagingBuffer::new(self.device.clone(), new_size);
+    fn try_allocate(&mut self, slot_size: vk::DeviceSize) -> Option<(StagingAllocation, u16)> {
+        self.free_slots.pop().map(|slot_id| {
+            (self.buffer.get_allocation((slot_id as vk::DeviceSize) * slot_size), slot_id)
+        })
+    }
 
-        let old = std::mem::replace(&mut self.current_buffer, buffer);
-        self.old_buffers.push((self.current_buffer_id, old));
-        self.current_buffer_id = id;
+    fn free(&mut self, slot_id: u16) {
+        debug_assert!(slot_id < self.slot_count && !self.free_slots.contains(&slot_id));
+        self.free_slots.push(slot_id);
     }
 
-    fn is_id_unused(&self, id: u16) -> bool {
-        if id == self.current_buffer_id {
-            return false;
-        }
-        for (old, _) in &self.old_buffers {
-            if *old == id {
-                return false;
-            }
+    fn is_empty(&self) -> bool {
+        self.free_slots.len() == (self.slot_count as usize)
+    }
+}
+
+/// Inserts the element into the first unused entry or appends it. Returns the index of the entry.
+fn find_or_push<T>(vec: &mut Vec<Option<T>>, element: T) -> u16 {
+    if let Some(index) = vec.iter().position(Option::is_none) {
+        vec[index] = Some(element);
+        index as u16
+    } else {
+        if vec.len() > (u16::MAX as usize) {
+            log::error!("Exceeded maximum number of staging buffers");
+            panic!()
         }
-        true
+        vec.push(Some(element));
+        (vec.len() - 1) as u16
     }
 }
 
@@ -120,7 +188,6 @@ struct StagingBuffer {
     buffer: vk::Buffer,
     mapped_ptr: NonNull<u8>,
     allocation: Allocation,
-    allocator: RingAllocator,
 }
 
 impl StagingBuffer {
@@ -139,39 +206,28 @@ impl StagingBuffer {
             buffer,
             mapped_ptr: mapped_ptr.unwrap(),
             allocation,
-            allocator: RingAllocator::new(size)
         }
     }
 
-    fn try_allocate(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Option<(StagingAllocation, u16)> {
-        self.allocator.allocate(size, alignment).map(|(offset, slot)| {
-            let alloc = StagingAllocation {
-                buffer: self.buffer,
-                offset,
-                mapped: unsafe { NonNull::new_unchecked(self.mapped_ptr.as_ptr().offset(offset as isize)) }
-            };
-            (alloc, slot)
-        })
-    }
-
-    fn free(&mut self, slot_id: u16) {
-        self.allocator.free(slot_id);
-    }
-
-    fn is_empty(&self) -> bool {
-        self.allocator.is_empty()
+    fn get_allocation(&self, offset: vk::DeviceSize) -> StagingAllocation {
+        StagingAllocation {
+            buffer: self.buffer,
+            offset,
+            mapped: unsafe { NonNull::new_unchecked(self.mapped_ptr.as_ptr().offset(offset as isize)) }
+        }
     }
+}
 
-    fn used_byte_count(&self) -> vk::DeviceSize {
-        self.allocator.used_byte_count()
+impl Drop for StagingSlab {
+    fn drop(&mut self) {
+        if !self.is_empty() {
+            log::warn!("Destroying staging slab with life allocations!");
+        }
     }
 }
 
 impl Drop for StagingBuffer {
     fn drop(&mut self) {
-        if !self.allocator.is_empty() {
-            log::warn!("Destroying staging buffer with life allocations!");
-        }
         unsafe {
             self.device.get_allocator().destroy_buffer(self.buffer, self.allocation)
         };
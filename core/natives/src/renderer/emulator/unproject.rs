@@ -0,0 +1,129 @@
+//! Conversion between screen positions and world space.
+//!
+//! Picking and debug tools need to know which world position lies under the cursor. The host
+//! provides the camera matrices of a frame using [`PassRecorder::set_view_matrices`] which are then
+//! used to map a position in logical pixels (and optionally a depth value read back by the host)
+//! to a world space point or ray. Clip space follows the vulkan conventions, the y axis points down
+//! and depth is in the `[0, 1]` range.
+//!
+//! The same matrices are used to project batches of world positions onto the screen, for example
+//! to place nameplates and waypoints in the gui without keeping a copy of the matrices on the host.
+//!
+//! [`PassRecorder::set_view_matrices`]: crate::renderer::emulator::PassRecorder::set_view_matrices
+
+use crate::renderer::emulator::FrameSize;
+
+use crate::prelude::*;
+
+/// The camera matrices of a frame.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ViewMatrices {
+    /// Transforms world space into view space.
+    pub model_view: Mat4f32,
+
+    /// Transforms view space into clip space.
+    pub projection: Mat4f32,
+}
+
+impl ViewMatrices {
+    /// Returns the world space position at `screen_pos` in logical pixels and `depth` in the
+    /// `[0, 1]` range. Returns [`None`] if the matrices cannot be inverted.
+    pub fn unproject(&self, frame_size: &FrameSize, screen_pos: Vec2f32, depth: f32) -> Option<Vec3f32> {
+        let inverse = (self.projection * self.model_view).try_inverse()?;
+        Self::unproject_with(&inverse, frame_size, screen_pos, depth)
+    }
+
+    /// Returns the world space ray starting on the near plane at `screen_pos` in logical pixels and
+    /// pointing away from the camera. Returns [`None`] if the matrices cannot be inverted.
+    pub fn unproject_ray(&self, frame_size: &FrameSize, screen_pos: Vec2f32) -> Option<WorldRay> {
+        let inverse = (self.projection * self.model_view).try_inverse()?;
+        let near = Self::unproject_with(&inverse, frame_size, screen_pos, 0f32)?;
+        let far = Self::unproject_with(&inverse, frame_size, screen_pos, 1f32)?;
+
+        Some(WorldRay {
+            origin: near,
+            direction: (far - near).try_normalize(f32::EPSILON)?,
+        })
+    }
+
+    /// Returns the position of the world space point `world` on the screen in logical pixels.
+    /// Returns [`None`] if the point is behind the camera. Points outside of the screen are still
+    /// returned, see [`ScreenPoint::is_on_screen`].
+    pub fn project(&self, frame_size: &FrameSize, world: Vec3f32) -> Option<ScreenPoint> {
+        Self::project_with(&(self.projection * self.model_view), frame_size, world)
+    }
+
+    /// Projects a batch of world space points. The results are in the same order as `points`.
+    /// See [`ViewMatrices::project`].
+    pub fn project_points(&self, frame_size: &FrameSize, points: &[Vec3f32]) -> Vec<Option<ScreenPoint>> {
+        let matrix = self.projection * self.model_view;
+        points.iter().map(|point| Self::project_with(&matrix, frame_size, *point)).collect()
+    }
+
+    fn project_with(matrix: &Mat4f32, frame_size: &FrameSize, world: Vec3f32) -> Option<ScreenPoint> {
+        let clip = matrix * world.push(1f32);
+        if clip[3] <= f32::EPSILON {
+            return None;
+        }
+
+        let ndc = clip.xyz() / clip[3];
+        Some(ScreenPoint {
+            position: Vec2f32::new(
+                (ndc[0] + 1f32) * 0.5f32 * frame_size.logical_size[0] as f32,
+                (ndc[1] + 1f32) * 0.5f32 * frame_size.logical_size[1] as f32
+            ),
+            depth: ndc[2],
+        })
+    }
+
+    fn unproject_with(inverse: &Mat4f32, frame_size: &FrameSize, screen_pos: Vec2f32, depth: f32) -> Option<Vec3f32> {
+        let size = Vec2f32::new(frame_size.logical_size[0].max(1) as f32, frame_size.logical_size[1].max(1) as f32);
+        let ndc = Vec4f32::new(
+            (screen_pos[0] / size[0]) * 2f32 - 1f32,
+            (screen_pos[1] / size[1]) * 2f32 - 1f32,
+            depth,
+            1f32
+        );
+
+        let world = inverse * ndc;
+        if world[3].abs() < f32::EPSILON {
+            return None;
+        }
+        Some(world.xyz() / world[3])
+    }
+}
+
+/// A world space point projected onto the screen.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScreenPoint {
+    /// The position in logical pixels.
+    pub position: Vec2f32,
+
+    /// The depth in the `[0, 1]` range if the point is between the near and far plane.
+    pub depth: f32,
+}
+
+impl ScreenPoint {
+    /// Returns true if the point lies inside of the frame and between the near and far plane.
+    pub fn is_on_screen(&self, frame_size: &FrameSize) -> bool {
+        self.position[0] >= 0f32 && self.position[0] <= frame_size.logical_size[0] as f32 &&
+            self.position[1] >= 0f32 && self.position[1] <= frame_size.logical_size[1] as f32 &&
+            self.depth >= 0f32 && self.depth <= 1f32
+    }
+}
+
+/// A ray in world space.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct WorldRay {
+    pub origin: Vec3f32,
+
+    /// The normalized direction of the ray.
+    pub direction: Vec3f32,
+}
+
+impl WorldRay {
+    /// Returns the point at `distance` along the ray.
+    pub fn point_at(&self, distance: f32) -> Vec3f32 {
+        self.origin + self.direction * distance
+    }
+}
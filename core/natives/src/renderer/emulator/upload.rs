@@ -0,0 +1,133 @@
+//! Throttling and prioritization of global object uploads.
+//!
+//! Writes to global meshes and images are not recorded immediately by the worker. Instead they are
+//! queued in a [`UploadScheduler`] and released once per pass in priority order until the per pass
+//! byte budget is used up. Uploads to objects which are used by a pass are always released before
+//! the pass regardless of the budget, so visible objects are never delayed.
+
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::renderer::emulator::global_objects::{GlobalImage, GlobalMesh};
+use crate::renderer::emulator::pass::PassId;
+use crate::renderer::emulator::worker::{GlobalImageWrite, GlobalMeshWrite};
+
+/// The upload priority assigned to newly created global objects.
+pub const DEFAULT_UPLOAD_PRIORITY: u32 = 0;
+
+pub(super) enum PendingUpload {
+    Mesh(GlobalMeshWrite, bool),
+    Image(GlobalImageWrite),
+}
+
+impl PendingUpload {
+    pub(super) fn get_after_pass(&self) -> PassId {
+        match self {
+            PendingUpload::Mesh(write, _) => write.after_pass,
+            PendingUpload::Image(write) => write.after_pass,
+        }
+    }
+
+    fn get_size(&self) -> vk::DeviceSize {
+        match self {
+            PendingUpload::Mesh(write, _) => write.staging_range.1,
+            PendingUpload::Image(write) => write.staging_range.1,
+        }
+    }
+
+    fn get_priority(&self) -> u32 {
+        match self {
+            PendingUpload::Mesh(write, _) => write.dst_mesh.get_upload_priority(),
+            PendingUpload::Image(write) => write.dst_image.get_upload_priority(),
+        }
+    }
+
+    fn is_for_mesh(&self, mesh: &Arc<GlobalMesh>) -> bool {
+        match self {
+            PendingUpload::Mesh(write, _) => Arc::ptr_eq(&write.dst_mesh, mesh),
+            PendingUpload::Image(_) => false,
+        }
+    }
+
+    fn is_for_image(&self, image: &Arc<GlobalImage>) -> bool {
+        match self {
+            PendingUpload::Mesh(_, _) => false,
+            PendingUpload::Image(write) => Arc::ptr_eq(&write.dst_image, image),
+        }
+    }
+}
+
+pub(super) struct UploadScheduler {
+    /// All pending uploads with the sequence number they were queued with.
+    pending: Vec<(u64, PendingUpload)>,
+    next_sequence: u64,
+}
+
+impl UploadScheduler {
+    pub(super) fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            next_sequence: 0,
+        }
+    }
+
+    pub(super) fn push(&mut self, upload: PendingUpload) {
+        self.pending.push((self.next_sequence, upload));
+        self.next_sequence += 1;
+    }
+
+    /// Removes and returns all pending uploads to the mesh in the order they were queued.
+    pub(super) fn take_for_mesh(&mut self, mesh: &Arc<GlobalMesh>) -> Vec<PendingUpload> {
+        self.take_where(|upload| upload.is_for_mesh(mesh))
+    }
+
+    /// Removes and returns all pending uploads to the image in the order they were queued.
+    pub(super) fn take_for_image(&mut self, image: &Arc<GlobalImage>) -> Vec<PendingUpload> {
+        self.take_where(|upload| upload.is_for_image(image))
+    }
+
+    /// Removes and returns the highest priority uploads until their combined size would exceed the
+    /// budget. At least one upload is returned if any are pending so that large uploads cannot
+    /// stall the queue. A budget of 0 releases all pending uploads.
+    ///
+    /// Uploads with equal priority are returned in the order they were queued. Since all uploads
+    /// to the same object share the same priority this preserves the order of writes to each object.
+    pub(super) fn take_budgeted(&mut self, budget: vk::DeviceSize) -> Vec<PendingUpload> {
+        if budget == 0 {
+            return self.take_where(|_| true);
+        }
+
+        self.pending.sort_by(|(seq_a, a), (seq_b, b)| {
+            b.get_priority().cmp(&a.get_priority()).then(seq_a.cmp(seq_b))
+        });
+
+        let mut used = 0;
+        let mut count = 0;
+        for (_, upload) in &self.pending {
+            let size = upload.get_size();
+            if count != 0 && used + size > budget {
+                break;
+            }
+            used += size;
+            count += 1;
+        }
+
+        self.pending.drain(0..count).map(|(_, upload)| upload).collect()
+    }
+
+    fn take_where<F: Fn(&PendingUpload) -> bool>(&mut self, predicate: F) -> Vec<PendingUpload> {
+        let mut taken = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            if predicate(&self.pending[index].1) {
+                taken.push(self.pending.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        taken.sort_by_key(|(seq, _)| *seq);
+
+        taken.into_iter().map(|(_, upload)| upload).collect()
+    }
+}
@@ -0,0 +1,159 @@
+//! Renderer state preserved across resource reloads and restarts.
+//!
+//! A resource pack reload (or F3+T) causes the host to recreate most of its renderer objects. Two
+//! mechanisms avoid rebuilding state the renderer already has:
+//!
+//! - A [`MeshRetention`] keeps all meshes in the deduplication cache alive while the host reloads.
+//!   Meshes recreated with identical data using
+//!   [`EmulatorRenderer::create_global_mesh_deduplicated`] are then returned from the cache instead
+//!   of being uploaded again. Dropping the retention releases all meshes which have not been
+//!   reused.
+//! - A [`RendererSnapshot`] captures the registered render layers and the render order. It can be
+//!   serialized to json so the layer setup survives a restart and is restored before the host
+//!   registers its own layers.
+//!
+//! The pipeline cache is not part of the snapshot. It is preserved by the device within a session
+//! and written to the configured pipeline cache path across restarts. Images are owned by the host
+//! and are not retained.
+//!
+//! [`EmulatorRenderer::create_global_mesh_deduplicated`]: crate::renderer::emulator::EmulatorRenderer::create_global_mesh_deduplicated
+
+use std::sync::Arc;
+
+use json::JsonValue;
+
+use crate::renderer::emulator::GlobalMesh;
+use crate::renderer::emulator::render_layer::{InsertionPoint, RenderLayerState};
+
+/// The version written into serialized snapshots. Snapshots with a different version are rejected.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Keeps meshes in the deduplication cache alive. See the [module docs](self).
+pub struct MeshRetention {
+    meshes: Vec<Arc<GlobalMesh>>,
+}
+
+impl MeshRetention {
+    pub(super) fn new(meshes: Vec<Arc<GlobalMesh>>) -> Self {
+        Self {
+            meshes,
+        }
+    }
+
+    /// Returns the number of retained meshes.
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.is_empty()
+    }
+
+    /// Returns the number of retained meshes which are currently also referenced outside of the
+    /// retention, for example because the host recreated them during a reload.
+    pub fn get_reused_count(&self) -> usize {
+        self.meshes.iter().filter(|mesh| Arc::strong_count(mesh) > 1).count()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LayerSnapshot {
+    pub name: String,
+    pub state: RenderLayerState,
+    pub enabled: bool,
+}
+
+/// An entry of the render order. Layers are identified by name since ids are not stable across
+/// restarts.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OrderSnapshot {
+    Layer(String),
+    Point(InsertionPoint),
+}
+
+/// The render layer setup of a renderer. See the [module docs](self).
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct RendererSnapshot {
+    /// All registered layers in render order.
+    pub layers: Vec<LayerSnapshot>,
+    pub order: Vec<OrderSnapshot>,
+}
+
+impl RendererSnapshot {
+    pub fn to_json(&self) -> String {
+        let mut layers = JsonValue::new_array();
+        for layer in &self.layers {
+            layers.push(json::object! {
+                name: layer.name.as_str(),
+                enabled: layer.enabled,
+                depth_write_enable: layer.state.depth_write_enable,
+                translucent: layer.state.translucent,
+                alpha_cutout: layer.state.alpha_cutout,
+                mipmap: layer.state.mipmap,
+                affects_crumbling: layer.state.affects_crumbling,
+            }).unwrap();
+        }
+
+        let mut order = JsonValue::new_array();
+        for entry in &self.order {
+            let entry = match entry {
+                OrderSnapshot::Layer(name) => json::object! { layer: name.as_str() },
+                OrderSnapshot::Point(point) => json::object! { point: *point as u32 },
+            };
+            order.push(entry).unwrap();
+        }
+
+        json::object! {
+            version: SNAPSHOT_VERSION,
+            layers: layers,
+            order: order,
+        }.dump()
+    }
+
+    pub fn from_json(source: &str) -> Result<Self, SnapshotError> {
+        let root = json::parse(source).map_err(|err| SnapshotError::InvalidJson(err.to_string()))?;
+
+        let version = root["version"].as_u32().ok_or(SnapshotError::MissingField("version"))?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut layers = Vec::new();
+        for layer in root["layers"].members() {
+            layers.push(LayerSnapshot {
+                name: layer["name"].as_str().ok_or(SnapshotError::MissingField("layers.name"))?.to_string(),
+                enabled: layer["enabled"].as_bool().ok_or(SnapshotError::MissingField("layers.enabled"))?,
+                state: RenderLayerState {
+                    depth_write_enable: layer["depth_write_enable"].as_bool().ok_or(SnapshotError::MissingField("layers.depth_write_enable"))?,
+                    translucent: layer["translucent"].as_bool().ok_or(SnapshotError::MissingField("layers.translucent"))?,
+                    alpha_cutout: layer["alpha_cutout"].as_f32().ok_or(SnapshotError::MissingField("layers.alpha_cutout"))?,
+                    mipmap: layer["mipmap"].as_bool().ok_or(SnapshotError::MissingField("layers.mipmap"))?,
+                    affects_crumbling: layer["affects_crumbling"].as_bool().ok_or(SnapshotError::MissingField("layers.affects_crumbling"))?,
+                },
+            });
+        }
+
+        let mut order = Vec::new();
+        for entry in root["order"].members() {
+            if let Some(name) = entry["layer"].as_str() {
+                order.push(OrderSnapshot::Layer(name.to_string()));
+            } else {
+                let raw = entry["point"].as_u32().ok_or(SnapshotError::MissingField("order.point"))?;
+                order.push(OrderSnapshot::Point(InsertionPoint::from_raw(raw).ok_or(SnapshotError::InvalidInsertionPoint(raw))?));
+            }
+        }
+
+        Ok(Self {
+            layers,
+            order,
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SnapshotError {
+    InvalidJson(String),
+    MissingField(&'static str),
+    UnsupportedVersion(u32),
+    InvalidInsertionPoint(u32),
+}
@@ -0,0 +1,174 @@
+//! Detection of gpu hangs.
+//!
+//! The worker registers every submitted pass with a [`SubmissionTracker`] and removes it once its
+//! fence has been signaled. The [`Watchdog`] thread periodically checks the oldest pending
+//! submission. If it has been pending for longer than the configured timeout a [`HangReport`]
+//! containing the queue and submission state is logged and passed to the hang callback. Optionally
+//! the device is marked as lost so that the normal device lost recovery recreates it.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::renderer::emulator::pass::PassId;
+
+use crate::prelude::*;
+
+/// Called from the watchdog thread whenever a hang has been detected.
+pub type HangCallback = Box<dyn Fn(&HangReport) + Send + Sync>;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct WatchdogConfig {
+    /// The time a submission may be pending before it is considered hung.
+    pub timeout: Duration,
+
+    /// If true the device is marked as lost when a hang is detected which triggers device
+    /// recreation at the start of the next frame.
+    pub recover: bool,
+}
+
+impl WatchdogConfig {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            recover: false,
+        }
+    }
+}
+
+/// A pass which has been submitted to the queue but has not completed yet.
+#[derive(Clone, Debug)]
+pub struct PendingSubmission {
+    pub pass_id: PassId,
+    pub submitted_at: Instant,
+    pub output_count: u32,
+    pub global_mesh_count: u32,
+    pub global_image_count: u32,
+    pub shader_count: u32,
+}
+
+/// The state of the renderer at the time a hang has been detected.
+#[derive(Clone, Debug)]
+pub struct HangReport {
+    /// The pass which exceeded the timeout.
+    pub pass_id: PassId,
+
+    /// The time since the pass has been submitted.
+    pub elapsed: Duration,
+
+    /// The pass currently being recorded by the host if any.
+    pub recording_pass: Option<PassId>,
+
+    pub queue_family: u32,
+
+    /// True if the device had already been marked as lost before the hang was detected.
+    pub device_lost: bool,
+
+    /// All pending submissions ordered from oldest to newest.
+    pub pending: Vec<PendingSubmission>,
+}
+
+impl HangReport {
+    /// Writes the report to the log.
+    pub fn log(&self) {
+        log::error!("Pass {:?} has not completed after {:?}. Possible gpu hang", self.pass_id, self.elapsed);
+        log::error!("Queue family: {}, device lost: {}, recording pass: {:?}", self.queue_family, self.device_lost, self.recording_pass);
+        log::error!("{} pending submissions:", self.pending.len());
+        let now = Instant::now();
+        for submission in &self.pending {
+            log::error!(
+                "    Pass {:?} submitted {:?} ago with {} outputs, {} global meshes, {} global images and {} shaders",
+                submission.pass_id,
+                now.saturating_duration_since(submission.submitted_at),
+                submission.output_count,
+                submission.global_mesh_count,
+                submission.global_image_count,
+                submission.shader_count
+            );
+        }
+    }
+}
+
+/// Tracks passes which have been submitted but have not completed yet.
+pub(super) struct SubmissionTracker {
+    pending: Mutex<Vec<PendingSubmission>>,
+}
+
+impl SubmissionTracker {
+    pub(super) fn new() -> Self {
+        Self {
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(super) fn push(&self, submission: PendingSubmission) {
+        self.pending.lock().unwrap().push(submission);
+    }
+
+    pub(super) fn complete(&self, pass_id: PassId) {
+        self.pending.lock().unwrap().retain(|submission| submission.pass_id != pass_id);
+    }
+
+    pub(super) fn get_pending(&self) -> Vec<PendingSubmission> {
+        self.pending.lock().unwrap().clone()
+    }
+}
+
+/// A running watchdog thread. The thread is stopped when this object is dropped.
+pub struct Watchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Starts a new watchdog thread. `poll` is called periodically to generate a report if the
+    /// oldest pending submission exceeds the timeout.
+    pub(super) fn start<F>(config: WatchdogConfig, device: Arc<DeviceContext>, mut poll: F, callback: Arc<Mutex<Option<HangCallback>>>) -> Self where F: FnMut(Duration) -> Option<HangReport> + Send + 'static {
+        let stop = Arc::new(AtomicBool::new(false));
+        let poll_interval = std::cmp::max(config.timeout / 4, Duration::from_millis(10));
+
+        let stop2 = stop.clone();
+        let thread = std::thread::Builder::new().name("b4d-watchdog".to_string()).spawn(move || {
+            let mut last_reported = None;
+            while !stop2.load(Ordering::Acquire) {
+                std::thread::park_timeout(poll_interval);
+
+                let report = match poll(config.timeout) {
+                    Some(report) => report,
+                    None => continue,
+                };
+
+                // Only report each hung pass once
+                if last_reported == Some(report.pass_id) {
+                    continue;
+                }
+                last_reported = Some(report.pass_id);
+
+                report.log();
+                if let Some(callback) = callback.lock().unwrap().as_ref() {
+                    callback(&report);
+                }
+                if config.recover && !report.device_lost {
+                    log::warn!("Marking device as lost to recover from gpu hang");
+                    device.mark_device_lost();
+                }
+            }
+        }).unwrap();
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            thread.join().ok();
+        }
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::rc::Rc;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use ash::prelude::VkResult;
 use ash::vk;
@@ -11,19 +11,29 @@ use bumpalo::Bump;
 
 use crate::device::device::Queue;
 
+use crate::renderer::emulator::barriers::BarrierBatch;
+use crate::renderer::emulator::compositor::{CompositeLayer, Compositor};
 use crate::renderer::emulator::pass::PassId;
 use crate::renderer::emulator::immediate::ImmediateBuffer;
-use crate::renderer::emulator::pipeline::{EmulatorOutput, EmulatorPipeline, EmulatorPipelinePass, PipelineTask};
+use crate::renderer::emulator::pipeline::{EmulatorOutput, EmulatorPipeline, EmulatorPipelinePass, PipelineTask, TextureBinding};
 
 use crate::prelude::*;
 use crate::renderer::emulator::global_objects::{GlobalImage, GlobalMesh};
 use crate::renderer::emulator::mc_shaders::ShaderId;
 use crate::renderer::emulator::share::{NextTaskResult, Share};
+use crate::renderer::emulator::sparse::{SparseBinder, SparseImageBind};
 use crate::renderer::emulator::staging::StagingAllocationId;
+use crate::renderer::emulator::upload::{PendingUpload, UploadScheduler};
+use crate::renderer::emulator::watchdog::PendingSubmission;
 
 pub(super) enum WorkerTask {
-    StartPass(PassId, Arc<dyn EmulatorPipeline>, Box<dyn EmulatorPipelinePass + Send>, Arc<GlobalImage>, vk::Sampler),
-    EndPass(Box<ImmediateBuffer>),
+    /// Starts a new pass using the frame slot with the provided index. If the first bool is true
+    /// the pass is submitted to the background queue. If the second bool is true the pass is a
+    /// main pass which is distributed over the device group if one is used.
+    StartPass(PassId, u32, Arc<dyn EmulatorPipeline>, Box<dyn EmulatorPipelinePass + Send>, Arc<GlobalImage>, TextureBinding, bool, bool),
+    /// Ends the current pass. If a sink is provided it is called with the gpu execution time of
+    /// the pass once it completes.
+    EndPass(Box<ImmediateBuffer>, Option<GpuTimeSink>),
     UseGlobalMesh(Arc<GlobalMesh>),
     UseGlobalImage(Arc<GlobalImage>),
     UseShader(ShaderId),
@@ -33,8 +43,17 @@ pub(super) enum WorkerTask {
     ClearGlobalImage(GlobalImageClear, bool),
     WriteGlobalImage(GlobalImageWrite),
     GenerateGlobalImageMipmaps(Arc<GlobalImage>, PassId),
+    /// Renders the layers of a composite texture into its render target image.
+    CompositeGlobalImage(GlobalImageComposite),
+    /// Binds memory to a sparse image on the sparse queue.
+    BindSparseImage(SparseImageBind),
+    /// Unbinds all pages of a sparse image selected for eviction once all passes up to the pass id
+    /// have completed.
+    EvictSparseImage(Arc<GlobalImage>, PassId),
 }
 
+pub(super) type GpuTimeSink = Box<dyn FnOnce(Duration) + Send>;
+
 pub(super) struct GlobalMeshWrite {
     pub(super) after_pass: PassId,
     pub(super) staging_allocation: StagingAllocationId,
@@ -53,6 +72,13 @@ pub(super) struct GlobalImageWrite {
     pub(super) regions: Box<[vk::BufferImageCopy]>,
 }
 
+pub(super) struct GlobalImageComposite {
+    /// Must have been created using [`GlobalImage::new_render_target`].
+    pub(super) dst_image: Arc<GlobalImage>,
+    /// The base layer followed by the blended layers. Never empty.
+    pub(super) layers: Box<[CompositeLayer]>,
+}
+
 pub(super) struct GlobalImageClear {
     pub(super) after_pass: PassId,
     pub(super) clear_value: vk::ClearColorValue,
@@ -73,33 +99,102 @@ pub(super) fn run_worker(device: Arc<DeviceContext>, share: Arc<Share>) {
     // When a pass is started this object is moved to `current_global_recorder`.
     let mut next_global_recorder: Option<GlobalObjectsRecorder> = None;
 
+    let mut uploads = UploadScheduler::new();
+
     let queue = device.get_main_queue();
+    let background_queue = device.get_background_queue();
+    let mut background_sync = if device.has_background_queue() {
+        Some(BackgroundSync::new(device.clone()))
+    } else {
+        None
+    };
+
+    // Created on the first composite job
+    let mut compositor: Option<Compositor> = None;
+
+    let mut sparse_binder = device.get_sparse_queue().map(|queue| SparseBinder::new(device.clone(), queue.clone()));
+    let mut last_started_pass = PassId::from_raw(0);
+
+    let main_timestamps = TimestampSupport::query(&device, queue);
+    let background_timestamps = TimestampSupport::query(&device, background_queue);
+
+    // The number of main passes started so far used to select the device in alternate frame mode
+    let mut main_pass_count = 0u64;
+
+    // Used to trim cached objects if the number of frames in flight is reduced
+    let mut frames_in_flight = share.get_frames_in_flight();
 
     loop {
-        old_frames.retain(|old: &PassState| {
-            !old.is_complete()
+        old_frames.retain_mut(|old: &mut PassState| {
+            if old.is_complete() {
+                old.report_gpu_time();
+                share.get_submissions().complete(old.pass_id);
+                false
+            } else {
+                true
+            }
         });
 
+        let new_frames_in_flight = share.get_frames_in_flight();
+        if new_frames_in_flight != frames_in_flight {
+            if new_frames_in_flight < frames_in_flight {
+                pool.borrow_mut().trim(new_frames_in_flight);
+            }
+            frames_in_flight = new_frames_in_flight;
+        }
+
+        if let Some(binder) = &mut sparse_binder {
+            binder.update(|pass| {
+                pass <= last_started_pass &&
+                    current_pass.as_ref().map_or(true, |current| current.pass_id > pass) &&
+                    old_frames.iter().all(|old| old.pass_id > pass)
+            });
+        }
+
         let task = match share.try_get_next_task_timeout(Duration::from_micros(500)) {
             NextTaskResult::Ok(task) => task,
             NextTaskResult::Timeout => continue,
         };
 
         match task {
-            WorkerTask::StartPass(id, pipeline, pass, placeholder_image, placeholder_sampler) => {
+            WorkerTask::StartPass(id, frame_index, pipeline, pass, placeholder_image, placeholder_texture, background, main) => {
                 if current_pass.is_some() {
                     log::error!("Worker received WorkerTask::StartPass when a pass is already running");
                     panic!()
                 }
-                let state = PassState::new(id, pipeline, pass, device.clone(), &queue, share.clone(), pool.clone(), placeholder_image, placeholder_sampler);
+                let pending = uploads.take_for_image(&placeholder_image);
+                let (pass_queue, timestamps) = if background { (background_queue, background_timestamps) } else { (queue, main_timestamps) };
+                let device_mask = match (main, device.get_device_group()) {
+                    (true, Some(group)) => {
+                        main_pass_count += 1;
+                        group.get_pass_device_mask(main_pass_count - 1)
+                    }
+                    _ => 0,
+                };
+                let state = PassState::new(id, frame_index, pipeline, pass, device.clone(), pass_queue, timestamps, device_mask, share.clone(), pool.clone(), placeholder_image, placeholder_texture, background);
                 current_pass = Some(state);
+                last_started_pass = id;
                 current_global_recorder = next_global_recorder.take();
+
+                for upload in pending {
+                    record_upload(upload, Some(id), &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                }
             }
 
-            WorkerTask::EndPass(immediate_buffer) => {
+            WorkerTask::EndPass(immediate_buffer, gpu_time_sink) => {
                 if let Some(mut pass) = current_pass.take() {
+                    pass.gpu_time_sink = gpu_time_sink;
+                    for upload in uploads.take_budgeted(share.get_upload_budget()) {
+                        record_upload(upload, Some(pass.pass_id), &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                    }
+
                     pass.use_immediate_buffer(immediate_buffer);
-                    pass.submit(&queue, current_global_recorder.take());
+                    let sparse_wait = sparse_binder.as_ref().and_then(|binder| binder.get_wait());
+                    match (pass.background, &mut background_sync) {
+                        (true, Some(sync)) => pass.submit_background(queue, background_queue, current_global_recorder.take(), sync, sparse_wait),
+                        _ => pass.submit(queue, current_global_recorder.take(), sparse_wait),
+                    }
+                    share.get_submissions().push(pass.make_pending_submission());
                     old_frames.push(pass);
                 } else {
                     log::error!("Worker received WorkerTask::EndPass when no active pass exists");
@@ -109,6 +204,9 @@ pub(super) fn run_worker(device: Arc<DeviceContext>, share: Arc<Share>) {
 
             WorkerTask::UseGlobalMesh(mesh) => {
                 if let Some(pass) = &mut current_pass {
+                    for upload in uploads.take_for_mesh(&mesh) {
+                        record_upload(upload, Some(pass.pass_id), &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                    }
                     pass.global_meshes.push(mesh)
                 } else {
                     log::error!("Worker received WorkerTask::UseStaticMesh when no active pass exists");
@@ -118,6 +216,9 @@ pub(super) fn run_worker(device: Arc<DeviceContext>, share: Arc<Share>) {
 
             WorkerTask::UseGlobalImage(image) => {
                 if let Some(pass) = &mut current_pass {
+                    for upload in uploads.take_for_image(&image) {
+                        record_upload(upload, Some(pass.pass_id), &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                    }
                     pass.global_images.push(image);
                 } else {
                     log::error!("Worker received WorkerTask::UseStaticImage when no active pass exits");
@@ -153,18 +254,15 @@ pub(super) fn run_worker(device: Arc<DeviceContext>, share: Arc<Share>) {
             }
 
             WorkerTask::WriteGlobalMesh(write, uninit) => {
-                if let Some(current_pass) = &current_pass {
-                    if current_pass.pass_id > write.after_pass {
-                        get_or_create_recorder(&mut current_global_recorder, &share, &pool).record_global_buffer_write(write, uninit);
-                    } else {
-                        get_or_create_recorder(&mut next_global_recorder, &share, &pool).record_global_buffer_write(write, uninit);
-                    }
-                } else {
-                    get_or_create_recorder(&mut next_global_recorder, &share, &pool).record_global_buffer_write(write, uninit);
-                }
+                uploads.push(PendingUpload::Mesh(write, uninit));
             }
 
             WorkerTask::ClearGlobalImage(clear, uninit) => {
+                let current_pass_id = current_pass.as_ref().map(|pass| pass.pass_id);
+                for upload in uploads.take_for_image(&clear.dst_image) {
+                    record_upload(upload, current_pass_id, &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                }
+
                 if let Some(current_pass) = &current_pass {
                     if current_pass.pass_id > clear.after_pass {
                         get_or_create_recorder(&mut current_global_recorder, &share, &pool).record_global_image_clear(clear, uninit);
@@ -177,18 +275,15 @@ pub(super) fn run_worker(device: Arc<DeviceContext>, share: Arc<Share>) {
             }
 
             WorkerTask::WriteGlobalImage(write) => {
-                if let Some(current_pass) = &current_pass {
-                    if current_pass.pass_id > write.after_pass {
-                        get_or_create_recorder(&mut current_global_recorder, &share, &pool).record_global_image_write(write, false);
-                    } else {
-                        get_or_create_recorder(&mut next_global_recorder, &share, &pool).record_global_image_write(write, false);
-                    }
-                } else {
-                    get_or_create_recorder(&mut next_global_recorder, &share, &pool).record_global_image_write(write, false);
-                }
+                uploads.push(PendingUpload::Image(write));
             }
 
             WorkerTask::GenerateGlobalImageMipmaps(image, after_pass) => {
+                let current_pass_id = current_pass.as_ref().map(|pass| pass.pass_id);
+                for upload in uploads.take_for_image(&image) {
+                    record_upload(upload, current_pass_id, &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                }
+
                 if let Some(current_pass) = &current_pass {
                     if current_pass.pass_id > after_pass {
                         get_or_create_recorder(&mut current_global_recorder, &share, &pool).record_global_image_generate_mipmaps(image);
@@ -199,10 +294,70 @@ pub(super) fn run_worker(device: Arc<DeviceContext>, share: Arc<Share>) {
                     get_or_create_recorder(&mut next_global_recorder, &share, &pool).record_global_image_generate_mipmaps(image);
                 }
             }
+
+            WorkerTask::CompositeGlobalImage(composite) => {
+                let current_pass_id = current_pass.as_ref().map(|pass| pass.pass_id);
+                let mut source_uploads = uploads.take_for_image(&composite.dst_image);
+                for layer in composite.layers.iter() {
+                    source_uploads.extend(uploads.take_for_image(&layer.image));
+                }
+
+                // The composite can only execute before the current pass if all uploads of its
+                // sources do as well
+                let before_current = match current_pass_id {
+                    Some(current_pass_id) => source_uploads.iter().all(|upload| current_pass_id > upload.get_after_pass()),
+                    None => false,
+                };
+                for upload in source_uploads {
+                    record_upload(upload, current_pass_id, &mut current_global_recorder, &mut next_global_recorder, &share, &pool);
+                }
+
+                let compositor = compositor.get_or_insert_with(|| Compositor::new(device.get_functions().clone()));
+                if before_current {
+                    get_or_create_recorder(&mut current_global_recorder, &share, &pool).record_global_image_composite(composite, compositor);
+                } else {
+                    get_or_create_recorder(&mut next_global_recorder, &share, &pool).record_global_image_composite(composite, compositor);
+                }
+            }
+
+            WorkerTask::BindSparseImage(bind) => {
+                if let Some(binder) = &mut sparse_binder {
+                    binder.bind(bind);
+                } else {
+                    log::error!("Worker received WorkerTask::BindSparseImage but sparse residency is not enabled");
+                    panic!()
+                }
+            }
+
+            WorkerTask::EvictSparseImage(image, after_pass) => {
+                if let Some(binder) = &mut sparse_binder {
+                    binder.evict(image, after_pass);
+                } else {
+                    log::error!("Worker received WorkerTask::EvictSparseImage but sparse residency is not enabled");
+                    panic!()
+                }
+            }
         }
     }
 }
 
+/// Records a upload into the recorder submitted before the current pass if the upload is allowed
+/// to execute before it or into the recorder of the next pass otherwise.
+fn record_upload(upload: PendingUpload, current_pass_id: Option<PassId>, current_recorder: &mut Option<GlobalObjectsRecorder>, next_recorder: &mut Option<GlobalObjectsRecorder>, share: &Arc<Share>, object_pool: &Rc<RefCell<WorkerObjectPool>>) {
+    let after_pass = upload.get_after_pass();
+
+    let recorder = match current_pass_id {
+        Some(current_pass_id) if current_pass_id > after_pass => current_recorder,
+        _ => next_recorder,
+    };
+    let recorder = get_or_create_recorder(recorder, share, object_pool);
+
+    match upload {
+        PendingUpload::Mesh(write, uninit) => recorder.record_global_buffer_write(write, uninit),
+        PendingUpload::Image(write) => recorder.record_global_image_write(write, false),
+    }
+}
+
 fn get_or_create_recorder<'a>(recorder: &'a mut Option<GlobalObjectsRecorder>, share: &Arc<Share>, object_pool: &Rc<RefCell<WorkerObjectPool>>) -> &'a mut GlobalObjectsRecorder {
     if let Some(recorder) = recorder {
         recorder
@@ -217,9 +372,14 @@ struct WorkerObjectPool {
     command_pool: vk::CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
     fences: Vec<vk::Fence>,
+    timestamp_pools: Vec<vk::QueryPool>,
 }
 
 impl WorkerObjectPool {
+    const CACHED_COMMAND_BUFFERS_PER_FRAME: usize = 8;
+    const CACHED_FENCES_PER_FRAME: usize = 4;
+    const CACHED_TIMESTAMP_POOLS_PER_FRAME: usize = 1;
+
     fn new(device: Arc<DeviceContext>, queue_family: u32) -> Self {
         let info = vk::CommandPoolCreateInfo::builder()
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER | vk::CommandPoolCreateFlags::TRANSIENT)
@@ -234,6 +394,7 @@ impl WorkerObjectPool {
             command_pool,
             command_buffers: Vec::new(),
             fences: Vec::new(),
+            timestamp_pools: Vec::new(),
         }
     }
 
@@ -279,22 +440,72 @@ impl WorkerObjectPool {
     fn return_fence(&mut self, fence: vk::Fence) {
         self.fences.push(fence);
     }
+
+    /// Returns a query pool containing 2 timestamp queries.
+    fn get_timestamp_pool(&mut self) -> vk::QueryPool {
+        if let Some(pool) = self.timestamp_pools.pop() {
+            return pool;
+        }
+
+        let info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2);
+
+        unsafe {
+            self.device.vk().create_query_pool(&info, None)
+        }.unwrap()
+    }
+
+    fn return_timestamp_pools(&mut self, pools: &[vk::QueryPool]) {
+        self.timestamp_pools.extend_from_slice(pools);
+    }
+
+    /// Destroys cached objects exceeding what `frames_in_flight` passes typically use. Objects
+    /// currently used by a pass are not affected.
+    fn trim(&mut self, frames_in_flight: u32) {
+        let frames = frames_in_flight as usize;
+
+        let max_buffers = frames * Self::CACHED_COMMAND_BUFFERS_PER_FRAME;
+        if self.command_buffers.len() > max_buffers {
+            let buffers = self.command_buffers.split_off(max_buffers);
+            unsafe { self.device.vk().free_command_buffers(self.command_pool, &buffers) };
+        }
+
+        let max_fences = frames * Self::CACHED_FENCES_PER_FRAME;
+        for fence in self.fences.drain(std::cmp::min(max_fences, self.fences.len())..) {
+            unsafe { self.device.vk().destroy_fence(fence, None) };
+        }
+
+        let max_timestamp_pools = frames * Self::CACHED_TIMESTAMP_POOLS_PER_FRAME;
+        for pool in self.timestamp_pools.drain(std::cmp::min(max_timestamp_pools, self.timestamp_pools.len())..) {
+            unsafe { self.device.vk().destroy_query_pool(pool, None) };
+        }
+    }
 }
 
 pub struct PooledObjectProvider {
     share: Arc<Share>,
     pool: Rc<RefCell<WorkerObjectPool>>,
+    /// The frame slot of the pass using this provider. Uniforms are allocated from the region of
+    /// this slot.
+    frame_index: Option<u32>,
     used_buffers: Vec<vk::CommandBuffer>,
     used_fences: Vec<vk::Fence>,
+    used_timestamp_pools: Vec<vk::QueryPool>,
+    /// The device mask of the pass using this provider. See [`PooledObjectProvider::get_device_mask`].
+    device_mask: u32,
 }
 
 impl PooledObjectProvider {
-    fn new(share: Arc<Share>, pool: Rc<RefCell<WorkerObjectPool>>) -> Self {
+    fn new(share: Arc<Share>, pool: Rc<RefCell<WorkerObjectPool>>, frame_index: Option<u32>) -> Self {
         Self {
             share,
             pool,
+            frame_index,
             used_buffers: Vec::with_capacity(8),
             used_fences: Vec::with_capacity(4),
+            used_timestamp_pools: Vec::new(),
+            device_mask: 0,
         }
     }
 
@@ -325,19 +536,40 @@ impl PooledObjectProvider {
         fence
     }
 
+    /// Returns the mask of the devices in the device group executing the pass or 0 if the pass
+    /// is executed on all devices. See [`crate::device::device_group`].
+    pub fn get_device_mask(&self) -> u32 {
+        self.device_mask
+    }
+
+    /// Returns a query pool containing 2 timestamp queries. The queries must be reset before use.
+    pub fn get_timestamp_pool(&mut self) -> vk::QueryPool {
+        let pool = self.pool.borrow_mut().get_timestamp_pool();
+        self.used_timestamp_pools.push(pool);
+
+        pool
+    }
+
     pub fn allocate_uniform(&mut self, data: &[u8]) -> (vk::Buffer, vk::DeviceSize) {
-        self.share.allocate_uniform(data)
+        let frame_index = self.frame_index.unwrap_or_else(|| {
+            log::error!("Called PooledObjectProvider::allocate_uniform outside of a pass");
+            panic!()
+        });
+        self.share.allocate_uniform(frame_index, data)
     }
 }
 
 impl Drop for PooledObjectProvider {
     fn drop(&mut self) {
-        self.pool.borrow_mut().return_buffers(self.used_buffers.as_slice());
+        let mut pool = self.pool.borrow_mut();
+        pool.return_buffers(self.used_buffers.as_slice());
+        pool.return_timestamp_pools(self.used_timestamp_pools.as_slice());
     }
 }
 
 pub struct SubmitRecorder<'a> {
     submits: Vec<vk::SubmitInfo2>,
+    device_mask: u32,
     _phantom: PhantomData<&'a ()>,
 }
 
@@ -345,19 +577,117 @@ impl<'a> SubmitRecorder<'a> {
     fn new(capacity: usize) -> Self {
         Self {
             submits: Vec::with_capacity(capacity),
+            device_mask: 0,
             _phantom: PhantomData,
         }
     }
 
+    /// Returns the device mask which must be used for all command buffers of the pass. 0 if the
+    /// pass is executed on all devices.
+    pub fn get_device_mask(&self) -> u32 {
+        self.device_mask
+    }
+
     pub fn push(&mut self, submit: vk::SubmitInfo2Builder<'a>) {
         self.submits.push(submit.build());
     }
 
+    /// Adds a wait operation to all submits which do not wait on any semaphore yet.
+    fn add_wait(&mut self, wait: &'a [vk::SemaphoreSubmitInfo]) {
+        for submit in &mut self.submits {
+            if submit.wait_semaphore_info_count == 0 {
+                submit.wait_semaphore_info_count = wait.len() as u32;
+                submit.p_wait_semaphore_infos = wait.as_ptr();
+            }
+        }
+    }
+
     fn as_slice(&self) -> &[vk::SubmitInfo2] {
         self.submits.as_slice()
     }
 }
 
+/// Timestamp properties of a queue family.
+#[derive(Copy, Clone)]
+struct TimestampSupport {
+    /// The number of nanoseconds per timestamp increment.
+    period: f32,
+    /// Mask of the valid bits of timestamps written on the queue.
+    mask: u64,
+}
+
+impl TimestampSupport {
+    /// Returns [`None`] if the queue does not support timestamp queries.
+    fn query(device: &DeviceContext, queue: &Queue) -> Option<Self> {
+        let instance = device.get_instance().vk();
+        let physical_device = device.get_functions().physical_device;
+
+        let period = unsafe {
+            instance.get_physical_device_properties(physical_device)
+        }.limits.timestamp_period;
+        let families = unsafe {
+            instance.get_physical_device_queue_family_properties(physical_device)
+        };
+        let valid_bits = families.get(queue.get_queue_family_index() as usize)?.timestamp_valid_bits;
+
+        if valid_bits == 0 || period <= 0f32 {
+            return None;
+        }
+        let mask = if valid_bits >= 64 { u64::MAX } else { (1u64 << valid_bits) - 1 };
+
+        Some(Self { period, mask })
+    }
+}
+
+/// A timeline semaphore signaled on the main queue before each background pass.
+///
+/// If timeline semaphores are not supported a binary semaphore is used instead. Since every
+/// signal is followed by a wait on the background queue before the next signal is submitted the
+/// binary semaphore can be reused for every pass.
+struct BackgroundSync {
+    device: Arc<DeviceContext>,
+    semaphore: vk::Semaphore,
+    value: u64,
+}
+
+impl BackgroundSync {
+    fn new(device: Arc<DeviceContext>) -> Self {
+        let mut timeline = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(0);
+
+        let mut info = vk::SemaphoreCreateInfo::builder();
+        if device.get_capabilities().has_timeline_semaphore() {
+            info = info.push_next(&mut timeline);
+        }
+
+        let semaphore = unsafe {
+            device.vk().create_semaphore(&info, None)
+        }.unwrap();
+
+        Self {
+            device,
+            semaphore,
+            value: 0,
+        }
+    }
+
+    /// Returns the semaphore and the value which should be signaled next. The value is ignored
+    /// for binary semaphores.
+    fn next(&mut self) -> (vk::Semaphore, u64) {
+        self.value += 1;
+        (self.semaphore, self.value)
+    }
+}
+
+impl Drop for BackgroundSync {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.vk().destroy_semaphore(self.semaphore, None);
+        }
+    }
+}
+
 struct PassState {
     share: Arc<Share>,
     device: Arc<DeviceContext>,
@@ -377,29 +707,51 @@ struct PassState {
     pre_cmd: vk::CommandBuffer,
     post_cmd: vk::CommandBuffer,
 
+    /// Timestamps written at the start of `pre_cmd` and the end of `post_cmd`.
+    timestamps: Option<(vk::QueryPool, TimestampSupport)>,
+    gpu_time_sink: Option<GpuTimeSink>,
+
     end_fence: Option<vk::Fence>,
 
     gob: Option<GlobalObjectsRecorder>,
+
+    /// True if the pass is submitted to the background queue.
+    background: bool,
 }
 
 impl PassState {
     fn new(
         pass_id: PassId,
+        frame_index: u32,
         pipeline: Arc<dyn EmulatorPipeline>,
         mut pass: Box<dyn EmulatorPipelinePass>,
         device: Arc<DeviceContext>,
         queue: &Queue,
+        timestamp_support: Option<TimestampSupport>,
+        device_mask: u32,
         share: Arc<Share>,
         pool: Rc<RefCell<WorkerObjectPool>>,
         placeholder_image: Arc<GlobalImage>,
-        placeholder_sampler: vk::Sampler
+        placeholder_texture: TextureBinding,
+        background: bool
     ) -> Self {
-        let mut object_pool = PooledObjectProvider::new(share.clone(), pool);
+        let mut object_pool = PooledObjectProvider::new(share.clone(), pool, Some(frame_index));
+        object_pool.device_mask = device_mask;
 
         let pre_cmd = object_pool.get_begin_command_buffer().unwrap();
         let post_cmd = object_pool.get_begin_command_buffer().unwrap();
+        unsafe { device.cmd_set_checkpoint(pre_cmd, pass_id.get_raw()) };
+
+        let timestamps = timestamp_support.map(|support| {
+            let query_pool = object_pool.get_timestamp_pool();
+            unsafe {
+                device.vk().cmd_reset_query_pool(pre_cmd, query_pool, 0, 2);
+                device.vk().cmd_write_timestamp(pre_cmd, vk::PipelineStageFlags::TOP_OF_PIPE, query_pool, 0);
+            }
+            (query_pool, support)
+        });
 
-        pass.init(queue, &mut object_pool, placeholder_image.get_sampler_view(), placeholder_sampler);
+        pass.init(queue, &mut object_pool, placeholder_texture);
 
         Self {
             share,
@@ -420,8 +772,13 @@ impl PassState {
             pre_cmd,
             post_cmd,
 
+            timestamps,
+            gpu_time_sink: None,
+
             end_fence: None,
-            gob: None
+            gob: None,
+
+            background,
         }
     }
 
@@ -444,7 +801,53 @@ impl PassState {
         self.pass.process_task(task, &mut self.object_pool);
     }
 
-    fn submit(&mut self, queue: &Queue, gob: Option<GlobalObjectsRecorder>) {
+    /// Submits the global objects recorder and the pass. If `sparse_wait` is set all submissions
+    /// wait for the most recent sparse binding operation.
+    fn submit(&mut self, queue: &Queue, gob: Option<GlobalObjectsRecorder>, sparse_wait: Option<vk::SemaphoreSubmitInfo>) {
+        self.submit_with_wait(queue, gob, sparse_wait);
+    }
+
+    /// Submits the global objects recorder to the main queue and the pass itself to the background
+    /// queue. The pass waits for all work previously submitted to the main queue so uploads
+    /// recorded for earlier passes are visible.
+    fn submit_background(&mut self, main_queue: &Queue, background_queue: &Queue, gob: Option<GlobalObjectsRecorder>, sync: &mut BackgroundSync, sparse_wait: Option<vk::SemaphoreSubmitInfo>) {
+        let (semaphore, value) = sync.next();
+
+        let submit_alloc = Bump::new();
+        let mut submit_recorder = SubmitRecorder::new(4);
+        if let Some(mut gob) = gob {
+            gob.record(&mut submit_recorder, &submit_alloc);
+            self.gob = Some(gob);
+        }
+
+        let signal_infos = submit_alloc.alloc([
+            vk::SemaphoreSubmitInfo::builder()
+                .semaphore(semaphore)
+                .value(value)
+                .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .build()
+        ]);
+        submit_recorder.push(vk::SubmitInfo2::builder()
+            .signal_semaphore_infos(signal_infos)
+        );
+        // The background pass waits for the signaled semaphore so it transitively waits for the sparse binds as well
+        if let Some(wait) = sparse_wait {
+            submit_recorder.add_wait(submit_alloc.alloc([wait]));
+        }
+
+        unsafe {
+            main_queue.submit_2(submit_recorder.as_slice(), None)
+        }.unwrap();
+
+        let wait = vk::SemaphoreSubmitInfo::builder()
+            .semaphore(semaphore)
+            .value(value)
+            .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+            .build();
+        self.submit_with_wait(background_queue, None, Some(wait));
+    }
+
+    fn submit_with_wait(&mut self, queue: &Queue, gob: Option<GlobalObjectsRecorder>, wait: Option<vk::SemaphoreSubmitInfo>) {
         assert!(self.end_fence.is_none());
         let end_fence = self.object_pool.get_fence();
         self.end_fence = Some(end_fence);
@@ -453,6 +856,11 @@ impl PassState {
             self.device.vk().end_command_buffer(self.pre_cmd)
         }.unwrap();
 
+        if let Some((query_pool, _)) = self.timestamps {
+            unsafe {
+                self.device.vk().cmd_write_timestamp(self.post_cmd, vk::PipelineStageFlags::BOTTOM_OF_PIPE, query_pool, 1)
+            };
+        }
         unsafe {
             self.device.vk().end_command_buffer(self.post_cmd)
         }.unwrap();
@@ -460,11 +868,13 @@ impl PassState {
         let submit_alloc = Bump::new();
         let mut submit_recorder = SubmitRecorder::new(32);
 
+        // Global objects are written on all devices
         if let Some(mut gob) = gob {
             gob.record(&mut submit_recorder, &submit_alloc);
             self.gob = Some(gob);
         }
 
+        submit_recorder.device_mask = self.object_pool.device_mask;
         self.record_pre_submits(&mut submit_recorder, &submit_alloc);
         self.pass.record(&mut self.object_pool, &mut submit_recorder, &submit_alloc);
         for output in &mut self.outputs {
@@ -472,6 +882,10 @@ impl PassState {
         }
         self.record_post_submits(&mut submit_recorder, &submit_alloc);
 
+        if let Some(wait) = wait {
+            submit_recorder.add_wait(submit_alloc.alloc([wait]));
+        }
+
         unsafe {
             queue.submit_2(submit_recorder.as_slice(), Some(end_fence))
         }.unwrap();
@@ -481,6 +895,17 @@ impl PassState {
         }
     }
 
+    fn make_pending_submission(&self) -> PendingSubmission {
+        PendingSubmission {
+            pass_id: self.pass_id,
+            submitted_at: Instant::now(),
+            output_count: self.outputs.len() as u32,
+            global_mesh_count: self.global_meshes.len() as u32,
+            global_image_count: self.global_images.len() as u32,
+            shader_count: self.shaders.len() as u32,
+        }
+    }
+
     fn is_complete(&self) -> bool {
         if let Some(fence) = self.end_fence {
             unsafe {
@@ -495,6 +920,7 @@ impl PassState {
         let cmd_infos = alloc.alloc([
             vk::CommandBufferSubmitInfo::builder()
                 .command_buffer(self.pre_cmd)
+                .device_mask(recorder.get_device_mask())
                 .build()
         ]);
 
@@ -504,7 +930,43 @@ impl PassState {
         recorder.push(submit_info);
     }
 
-    fn record_post_submits<'a>(&self, _: &mut SubmitRecorder<'a>, _: &'a Bump) {
+    fn record_post_submits<'a>(&self, recorder: &mut SubmitRecorder<'a>, alloc: &'a Bump) {
+        let cmd_infos = alloc.alloc([
+            vk::CommandBufferSubmitInfo::builder()
+                .command_buffer(self.post_cmd)
+                .device_mask(recorder.get_device_mask())
+                .build()
+        ]);
+
+        let submit_info = vk::SubmitInfo2::builder()
+            .command_buffer_infos(cmd_infos);
+
+        recorder.push(submit_info);
+    }
+
+    /// Reads the timestamps of the pass and calls the gpu time sink. Must only be called once the
+    /// pass has completed.
+    fn report_gpu_time(&mut self) {
+        let sink = match self.gpu_time_sink.take() {
+            Some(sink) => sink,
+            None => return,
+        };
+        let (query_pool, support) = match self.timestamps {
+            Some(timestamps) => timestamps,
+            None => return,
+        };
+
+        let mut data = [0u64; 2];
+        let result = unsafe {
+            self.device.vk().get_query_pool_results(query_pool, 0, 2, &mut data, vk::QueryResultFlags::TYPE_64)
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to read pass timestamps {:?}", err);
+            return;
+        }
+
+        let ticks = (data[1].wrapping_sub(data[0])) & support.mask;
+        sink(Duration::from_nanos((ticks as f64 * support.period as f64) as u64));
     }
 }
 
@@ -521,7 +983,7 @@ impl Drop for PassState {
 
 struct GlobalObjectsRecorder {
     share: Arc<Share>,
-    _object_pool: PooledObjectProvider,
+    object_pool: PooledObjectProvider,
 
     cmd: vk::CommandBuffer,
 
@@ -529,9 +991,17 @@ struct GlobalObjectsRecorder {
 
     staging_barriers: Vec<vk::BufferMemoryBarrier2>,
 
+    /// Barriers of the first transition of each used object. Since no earlier command of this
+    /// recorder accesses the object these are hoisted to the start of the submission and recorded
+    /// as a single batch.
+    prologue_barriers: BarrierBatch,
+
     used_global_meshes: HashMap<Arc<GlobalMesh>, gob::MeshState>,
     used_global_images: HashMap<Arc<GlobalImage>, gob::ImageState>,
 
+    /// Framebuffers of composite jobs. Destroyed once the submission has completed.
+    framebuffers: Vec<vk::Framebuffer>,
+
     /// A [`vk::ImageMemoryBarrier2`] Vec which can be used locally inside functions to avoid new
     /// allocations. It should always be cleared before use.
     tmp_image_barriers: Vec<vk::ImageMemoryBarrier2>,
@@ -543,7 +1013,7 @@ struct GlobalObjectsRecorder {
 
 impl GlobalObjectsRecorder {
     fn new(share: Arc<Share>, object_pool: Rc<RefCell<WorkerObjectPool>>) -> Self {
-        let mut object_pool = PooledObjectProvider::new(share.clone(), object_pool);
+        let mut object_pool = PooledObjectProvider::new(share.clone(), object_pool, None);
 
         let cmd = object_pool.get_begin_command_buffer().unwrap_or_else(|err| {
             log::error!("Failed to begin global object command buffer {:?}", err);
@@ -552,16 +1022,19 @@ impl GlobalObjectsRecorder {
 
         Self {
             share,
-            _object_pool: object_pool,
+            object_pool,
 
             cmd,
 
             staging_allocations: Vec::new(),
             staging_barriers: Vec::new(),
+            prologue_barriers: BarrierBatch::new(),
 
             used_global_meshes: HashMap::new(),
             used_global_images: HashMap::new(),
 
+            framebuffers: Vec::new(),
+
             tmp_image_barriers: Vec::new(),
             tmp_buffer_barriers: Vec::new(),
         }
@@ -628,10 +1101,38 @@ impl GlobalObjectsRecorder {
         self.push_staging(write.staging_allocation, write.staging_buffer, write.staging_range.0, write.staging_range.1);
     }
 
+    fn record_global_image_composite(&mut self, composite: GlobalImageComposite, compositor: &mut Compositor) {
+        // Sources are only read so they stay in (or are returned to) the ready state. Sources used
+        // for the first time are still added to the used image list so that later writes in this
+        // recorder are not hoisted before the composite.
+        for layer in composite.layers.iter() {
+            match self.used_global_images.get(&layer.image).copied() {
+                None => {
+                    self.used_global_images.insert(layer.image.clone(), gob::ImageState::Ready);
+                }
+                Some(gob::ImageState::Ready) => {}
+                Some(_) => self.transition_image(layer.image.clone(), gob::ImageState::Ready, false),
+            }
+        }
+
+        let format = composite.dst_image.get_format().get_format();
+        let size = composite.dst_image.get_size();
+        let framebuffer = compositor.create_framebuffer(format, composite.dst_image.get_sampler_view(), size);
+        self.framebuffers.push(framebuffer);
+
+        self.transition_image(composite.dst_image, gob::ImageState::ColorAttachment, false);
+
+        let layers: Vec<_> = composite.layers.iter()
+            .map(|layer| (layer.image.get_sampler_view(), layer.get_push_constants()))
+            .collect();
+        compositor.record(self.cmd, format, framebuffer, size, &layers);
+    }
+
     fn record_global_image_generate_mipmaps(&mut self, image: Arc<GlobalImage>) {
         let mip_levels = image.get_mip_levels();
         if mip_levels > 1 {
             let handle = image.get_image_handle();
+            let layer_count = image.get_layer_count();
             let src_size = image.get_size();
             let mut src_size = Vec2i32::new(src_size[0] as i32, src_size[1] as i32);
 
@@ -653,14 +1154,14 @@ impl GlobalObjectsRecorder {
                             base_mip_level: level - 1,
                             level_count: 1,
                             base_array_layer: 0,
-                            layer_count: 0
+                            layer_count
                         });
 
                     let info = vk::DependencyInfo::builder()
                         .image_memory_barriers(std::slice::from_ref(&barrier));
 
                     unsafe {
-                        device.synchronization_2_khr().cmd_pipeline_barrier2(self.cmd, &info);
+                        device.cmd_pipeline_barrier2(self.cmd, &info);
                     }
                 }
 
@@ -673,14 +1174,14 @@ impl GlobalObjectsRecorder {
                         aspect_mask: vk::ImageAspectFlags::COLOR,
                         mip_level: level - 1,
                         base_array_layer: 0,
-                        layer_count: 1
+                        layer_count
                     })
                     .src_offsets([vk::Offset3D { x: 0, y: 0, z: 0 }, vk::Offset3D { x: src_size[0], y: src_size[1], z: 1 }])
                     .dst_subresource(vk::ImageSubresourceLayers {
                         aspect_mask: vk::ImageAspectFlags::COLOR,
                         mip_level: level,
                         base_array_layer: 0,
-                        layer_count: 1
+                        layer_count
                     })
                     .dst_offsets([vk::Offset3D { x: 0, y: 0, z: 0 }, vk::Offset3D { x: dst_size[0], y: dst_size[1], z: 1 }]);
 
@@ -705,33 +1206,12 @@ impl GlobalObjectsRecorder {
         let buffer_post_barriers = self.generate_buffer_post_barriers();
         let image_post_barriers = self.generate_image_post_barriers();
 
-        let device = self.share.get_device();
-
-        if !buffer_post_barriers.is_empty() || !image_post_barriers.is_empty() {
-            let buffer_post_barriers = buffer_post_barriers.as_slice();
-            let image_post_barriers = image_post_barriers.as_slice();
-
-            // If we have too many barriers in a single command the driver may fail to record (Yes this limit has been hit at 4000 barriers during testing in minecraft)
-            const CHUNK_SIZE: usize = 256;
-            let chunk_count = std::cmp::max((buffer_post_barriers.len() / CHUNK_SIZE) + 1, (image_post_barriers.len() / CHUNK_SIZE) + 1);
-            for chunk in 0..chunk_count {
-                let min = chunk * CHUNK_SIZE;
-                let max = min + CHUNK_SIZE;
-                let mut info = vk::DependencyInfo::builder();
-                if min < buffer_post_barriers.len() {
-                    let max = std::cmp::min(max, buffer_post_barriers.len());
-                    info = info.buffer_memory_barriers(&buffer_post_barriers[min..max]);
-                }
-                if min < image_post_barriers.len() {
-                    let max = std::cmp::min(max, image_post_barriers.len());
-                    info = info.image_memory_barriers(&image_post_barriers[min..max]);
-                }
+        let device = self.share.get_device().clone();
 
-                unsafe {
-                    device.synchronization_2_khr().cmd_pipeline_barrier2(self.cmd, &info);
-                }
-            }
-        }
+        let mut post_barriers = BarrierBatch::new();
+        post_barriers.extend_buffers(&buffer_post_barriers);
+        post_barriers.extend_images(&image_post_barriers);
+        post_barriers.record(&device, self.cmd);
 
         unsafe {
             device.vk().end_command_buffer(self.cmd)
@@ -740,13 +1220,37 @@ impl GlobalObjectsRecorder {
             panic!()
         });
 
-        let cmd_info = bump.alloc(vk::CommandBufferSubmitInfo::builder()
-            .command_buffer(self.cmd)
-            .build()
-        );
+        let cmd_infos = if self.prologue_barriers.is_empty() {
+            bump.alloc_slice_copy(&[
+                vk::CommandBufferSubmitInfo::builder()
+                    .command_buffer(self.cmd)
+                    .build()
+            ])
+        } else {
+            let prologue_cmd = self.object_pool.get_begin_command_buffer().unwrap_or_else(|err| {
+                log::error!("Failed to begin global object prologue command buffer {:?}", err);
+                panic!();
+            });
+            self.prologue_barriers.record(&device, prologue_cmd);
+            unsafe {
+                device.vk().end_command_buffer(prologue_cmd)
+            }.unwrap_or_else(|err| {
+                log::error!("Failed to end global objects prologue command buffer recording {:?}", err);
+                panic!()
+            });
+
+            bump.alloc_slice_copy(&[
+                vk::CommandBufferSubmitInfo::builder()
+                    .command_buffer(prologue_cmd)
+                    .build(),
+                vk::CommandBufferSubmitInfo::builder()
+                    .command_buffer(self.cmd)
+                    .build()
+            ])
+        };
 
         recorder.push(vk::SubmitInfo2::builder()
-            .command_buffer_infos(std::slice::from_ref(cmd_info))
+            .command_buffer_infos(cmd_infos)
         );
     }
 
@@ -766,6 +1270,11 @@ impl GlobalObjectsRecorder {
         let mut barriers: Vec<vk::ImageMemoryBarrier2> = Vec::new();
 
         for (image, old_state) in &self.used_global_images {
+            // Images which were only sampled by composite jobs
+            if *old_state == gob::ImageState::Ready {
+                continue;
+            }
+
             let handle = image.get_image_handle();
             let mip_levels = image.get_mip_levels();
 
@@ -777,21 +1286,18 @@ impl GlobalObjectsRecorder {
 
     fn push_staging(&mut self, alloc: StagingAllocationId, buffer: vk::Buffer, offset: vk::DeviceSize, size: vk::DeviceSize) {
         self.staging_allocations.push(alloc);
-        let barrier = vk::BufferMemoryBarrier2::builder()
+        // Recorded together with the post barriers since no later command of this recorder
+        // accesses the staging memory
+        self.staging_barriers.push(vk::BufferMemoryBarrier2::builder()
             .src_stage_mask(vk::PipelineStageFlags2::TRANSFER)
             .src_access_mask(vk::AccessFlags2::TRANSFER_READ)
             .dst_stage_mask(vk::PipelineStageFlags2::HOST)
             .dst_access_mask(vk::AccessFlags2::HOST_WRITE)
             .buffer(buffer)
             .offset(offset)
-            .size(size);
-
-        let info = vk::DependencyInfo::builder()
-            .buffer_memory_barriers(std::slice::from_ref(&barrier));
-
-        unsafe {
-            self.share.get_device().synchronization_2_khr().cmd_pipeline_barrier2(self.cmd, &info)
-        };
+            .size(size)
+            .build()
+        );
     }
 
     /// Transitions a mesh to a new state and adds it to the used mesh list.
@@ -802,23 +1308,23 @@ impl GlobalObjectsRecorder {
     fn transition_mesh(&mut self, mesh: Arc<GlobalMesh>, new_state: gob::MeshState, maybe_uninit: bool) {
         let handle = mesh.get_buffer_handle();
 
-        let old_state = self.used_global_meshes.insert(mesh, new_state).unwrap_or_else(|| {
-            if maybe_uninit {
-                gob::MeshState::Uninitialized
-            } else {
-                gob::MeshState::Ready
-            }
-        });
+        let (old_state, first_use) = match self.used_global_meshes.insert(mesh, new_state) {
+            Some(old_state) => (old_state, false),
+            None if maybe_uninit => (gob::MeshState::Uninitialized, true),
+            None => (gob::MeshState::Ready, true),
+        };
 
         self.tmp_buffer_barriers.clear();
         gob::generate_mesh_barriers(old_state, new_state, handle, &mut self.tmp_buffer_barriers);
 
-        if !self.tmp_buffer_barriers.is_empty() {
+        if first_use {
+            self.prologue_barriers.extend_buffers(&self.tmp_buffer_barriers);
+        } else if !self.tmp_buffer_barriers.is_empty() {
             let info = vk::DependencyInfo::builder()
                 .buffer_memory_barriers(self.tmp_buffer_barriers.as_slice());
 
             unsafe {
-                self.share.get_device().synchronization_2_khr().cmd_pipeline_barrier2(self.cmd, &info);
+                self.share.get_device().cmd_pipeline_barrier2(self.cmd, &info);
             }
         }
     }
@@ -832,23 +1338,23 @@ impl GlobalObjectsRecorder {
         let handle = image.get_image_handle();
         let mip_levels = image.get_mip_levels();
 
-        let old_state = self.used_global_images.insert(image, new_state).unwrap_or_else(|| {
-            if maybe_uninit {
-                gob::ImageState::Uninitialized
-            } else {
-                gob::ImageState::Ready
-            }
-        });
+        let (old_state, first_use) = match self.used_global_images.insert(image, new_state) {
+            Some(old_state) => (old_state, false),
+            None if maybe_uninit => (gob::ImageState::Uninitialized, true),
+            None => (gob::ImageState::Ready, true),
+        };
 
         self.tmp_image_barriers.clear();
         gob::generate_image_barriers(old_state, new_state, handle, mip_levels, &mut self.tmp_image_barriers);
 
-        if !self.tmp_image_barriers.is_empty() {
+        if first_use {
+            self.prologue_barriers.extend_images(&self.tmp_image_barriers);
+        } else if !self.tmp_image_barriers.is_empty() {
             let info = vk::DependencyInfo::builder()
                 .image_memory_barriers(self.tmp_image_barriers.as_slice());
 
             unsafe {
-                self.share.get_device().synchronization_2_khr().cmd_pipeline_barrier2(self.cmd, &info);
+                self.share.get_device().cmd_pipeline_barrier2(self.cmd, &info);
             }
         }
     }
@@ -864,6 +1370,13 @@ impl Drop for GlobalObjectsRecorder {
         for allocation in std::mem::replace(&mut self.staging_allocations, Vec::new()) {
             guard.free(allocation);
         }
+        drop(guard);
+
+        for framebuffer in std::mem::replace(&mut self.framebuffers, Vec::new()) {
+            unsafe {
+                self.share.get_device().vk().destroy_framebuffer(framebuffer, None);
+            }
+        }
     }
 }
 
@@ -915,9 +1428,13 @@ mod gob {
     }
 
     // This needs to be a function because of the bitor. Waiting for const impl
+    // The vertex shader stage is included since vertex data may be pulled as a storage buffer
     #[allow(non_snake_case)]
     fn MESH_READY_INFO() -> BufferAccessInfo {
-        BufferAccessInfo::new(vk::PipelineStageFlags2::VERTEX_INPUT, vk::AccessFlags2::VERTEX_ATTRIBUTE_READ | vk::AccessFlags2::INDEX_READ)
+        BufferAccessInfo::new(
+            vk::PipelineStageFlags2::VERTEX_INPUT | vk::PipelineStageFlags2::VERTEX_SHADER,
+            vk::AccessFlags2::VERTEX_ATTRIBUTE_READ | vk::AccessFlags2::INDEX_READ | vk::AccessFlags2::SHADER_STORAGE_READ
+        )
     }
     const MESH_TRANSFER_WRITE_INFO: BufferAccessInfo = BufferAccessInfo::new(vk::PipelineStageFlags2::TRANSFER, vk::AccessFlags2::TRANSFER_WRITE);
 
@@ -960,6 +1477,8 @@ mod gob {
         TransferWrite,
         /// Image had previously generated its mipmaps
         GenerateMipmaps,
+        /// Image was previously rendered to by a composite job
+        ColorAttachment,
     }
 
     pub(super) fn generate_image_barriers(old_state: ImageState, new_state: ImageState, image: vk::Image, mip_levels: u32, barriers: &mut Vec<vk::ImageMemoryBarrier2>) {
@@ -1068,6 +1587,51 @@ mod gob {
 
                 barriers.push(barrier1.build());
             }
+            (ImageState::Ready, ImageState::ColorAttachment) => {
+                let mut barrier = vk::ImageMemoryBarrier2::builder()
+                    .image(image)
+                    .subresource_range(make_full_subresource_range(vk::ImageAspectFlags::COLOR));
+                barrier = IMAGE_READY_INFO.write_src(barrier);
+                barrier = IMAGE_COLOR_ATTACHMENT_INFO.write_dst(barrier);
+
+                barriers.push(barrier.build());
+            }
+            (ImageState::TransferWrite, ImageState::ColorAttachment) => {
+                let mut barrier = vk::ImageMemoryBarrier2::builder()
+                    .image(image)
+                    .subresource_range(make_full_subresource_range(vk::ImageAspectFlags::COLOR));
+                barrier = IMAGE_TRANSFER_WRITE_INFO.write_src(barrier);
+                barrier = IMAGE_COLOR_ATTACHMENT_INFO.write_dst(barrier);
+
+                barriers.push(barrier.build());
+            }
+            (ImageState::ColorAttachment, ImageState::Ready) => {
+                let mut barrier = vk::ImageMemoryBarrier2::builder()
+                    .image(image)
+                    .subresource_range(make_full_subresource_range(vk::ImageAspectFlags::COLOR));
+                barrier = IMAGE_COLOR_ATTACHMENT_INFO.write_src(barrier);
+                barrier = IMAGE_READY_INFO.write_dst(barrier);
+
+                barriers.push(barrier.build());
+            }
+            (ImageState::ColorAttachment, ImageState::TransferWrite) => {
+                let mut barrier = vk::ImageMemoryBarrier2::builder()
+                    .image(image)
+                    .subresource_range(make_full_subresource_range(vk::ImageAspectFlags::COLOR));
+                barrier = IMAGE_COLOR_ATTACHMENT_INFO.write_src(barrier);
+                barrier = IMAGE_TRANSFER_WRITE_INFO.write_dst(barrier);
+
+                barriers.push(barrier.build());
+            }
+            (ImageState::ColorAttachment, ImageState::ColorAttachment) => {
+                let mut barrier = vk::ImageMemoryBarrier2::builder()
+                    .image(image)
+                    .subresource_range(make_full_subresource_range(vk::ImageAspectFlags::COLOR));
+                barrier = IMAGE_COLOR_ATTACHMENT_INFO.write_src(barrier);
+                barrier = IMAGE_COLOR_ATTACHMENT_INFO.write_dst(barrier);
+
+                barriers.push(barrier.build());
+            }
             (ImageState::Ready, ImageState::Ready) => {
                 log::warn!("Transitioned image from ready to ready. Why?");
             }
@@ -1083,6 +1647,11 @@ mod gob {
                 log::error!("Image cannot be transitioned from generate mipmaps to generate mipmaps");
                 panic!();
             }
+            (ImageState::GenerateMipmaps, ImageState::ColorAttachment) | (ImageState::ColorAttachment, ImageState::GenerateMipmaps) => {
+                // Render targets only have a single mip level
+                log::error!("Image cannot be transitioned from {:?} to {:?}", old_state, new_state);
+                panic!();
+            }
         }
     }
 
@@ -1146,6 +1715,7 @@ mod gob {
     const IMAGE_TRANSFER_WRITE_INFO: ImageAccessInfo = ImageAccessInfo::new(vk::PipelineStageFlags2::TRANSFER, vk::AccessFlags2::TRANSFER_WRITE, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
     const IMAGE_GENERATE_MIPMAPS_0_INFO: ImageAccessInfo = ImageAccessInfo::new(vk::PipelineStageFlags2::TRANSFER, vk::AccessFlags2::TRANSFER_READ, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
     const IMAGE_GENERATE_MIPMAPS_1_INFO: ImageAccessInfo = ImageAccessInfo::new(vk::PipelineStageFlags2::TRANSFER, vk::AccessFlags2::TRANSFER_WRITE, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    const IMAGE_COLOR_ATTACHMENT_INFO: ImageAccessInfo = ImageAccessInfo::new(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT, vk::AccessFlags2::from_raw(vk::AccessFlags2::COLOR_ATTACHMENT_READ.as_raw() | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE.as_raw()), vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
 
     struct ImageAccessInfo {
         stage_mask: vk::PipelineStageFlags2,
@@ -24,6 +24,14 @@ impl CompatibilityClass {
         self.name
     }
 
+    /// Returns the size in bytes of one texel for uncompressed classes or [`None`] for block
+    /// compressed classes.
+    pub fn get_texel_size(&self) -> Option<u32> {
+        let bits = self.name.strip_prefix("BIT")?;
+        let bits: u32 = bits.split('_').next()?.parse().ok()?;
+        Some(bits / 8)
+    }
+
     define_compatibility_class!(BIT8);
     define_compatibility_class!(BIT16);
     define_compatibility_class!(BIT24);
@@ -153,6 +161,16 @@ macro_rules! define_formats {
             }
         }
 
+        /// Returns the format for the vulkan format or [`None`] if the format is unknown.
+        pub const fn try_format_for(format: vk::Format) -> Option<&'static Format> {
+            match format {
+                $(
+                ash::vk::Format::$name => Some(&Self::$name),
+                )+
+                _ => None
+            }
+        }
+
         $(pub const $name : Format = Format::new(ash::vk::Format::$name, $compatibility_class, $channel_count, $clear_color_type);)+
     }
 }
@@ -178,6 +196,22 @@ impl Format {
         self.compatibility_class == other.compatibility_class
     }
 
+    /// Returns the number of planes of the format. Returns 1 for all non multi-planar formats.
+    pub fn get_plane_count(&self) -> u32 {
+        let name = self.compatibility_class.get_name();
+        if name.starts_with("PLANE3") {
+            3
+        } else if name.starts_with("PLANE2") {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn is_multi_planar(&self) -> bool {
+        self.get_plane_count() > 1
+    }
+
     define_formats!(
     R4G4_UNORM_PACK8, CompatibilityClass::BIT8, 2, Some(ClearColorType::Float);
     R4G4B4A4_UNORM_PACK16, CompatibilityClass::BIT16, 4, Some(ClearColorType::Float);
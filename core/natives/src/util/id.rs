@@ -2,10 +2,12 @@
 use std::cell::RefCell;
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
 use std::num::NonZeroU64;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
 
 use lazy_static::lazy_static;
 
@@ -16,6 +18,37 @@ pub struct UUID(NonZeroU64);
 
 lazy_static! {
     static ref UUID_SEEDER : Mutex<Xoshiro256PlusPlus> = Mutex::new(Xoshiro256PlusPlus::from_seed([1u64, 1u64, 1u64, 1u64]));
+    static ref SEEDED_IDS : Mutex<Option<SeededIds>> = Mutex::new(None);
+}
+
+/// Set while [`SEEDED_IDS`] is [`Some`] so the lock can be skipped in the default mode.
+static SEEDED_MODE : AtomicBool = AtomicBool::new(false);
+
+/// Generates ids from a seed and a counter per manager.
+struct SeededIds {
+    seed: u64,
+    counters: HashMap<&'static str, u64>,
+}
+
+impl SeededIds {
+    /// The splitmix64 finalizer. It is a bijection so distinct inputs never produce the same id.
+    fn mix(mut x: u64) -> u64 {
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9u64);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111ebu64);
+        x ^ (x >> 31)
+    }
+
+    fn next(&mut self, manager: &'static str) -> u64 {
+        let base = Self::mix(self.seed ^ NamedUUID::hash_str(manager));
+        let counter = self.counters.entry(manager).or_insert(0);
+        loop {
+            let id = Self::mix(base.wrapping_add(counter.wrapping_mul(0x9e3779b97f4a7c15u64)));
+            *counter += 1;
+            if id != 0u64 {
+                return id;
+            }
+        }
+    }
 }
 
 thread_local! {
@@ -29,11 +62,47 @@ thread_local! {
 
 impl UUID {
     pub fn new() -> Self {
+        Self::new_for("")
+    }
+
+    /// Creates a new id for some manager (usually the name of the id type).
+    ///
+    /// In seeded mode (see [`UUID::enable_seeded_mode`]) every manager uses its own counter so
+    /// creating ids of one type does not change the ids of other types. Otherwise the manager is
+    /// ignored.
+    pub fn new_for(manager: &'static str) -> Self {
+        if SEEDED_MODE.load(std::sync::atomic::Ordering::Acquire) {
+            if let Some(seeded) = SEEDED_IDS.lock().unwrap().as_mut() {
+                return Self(NonZeroU64::new(seeded.next(manager)).unwrap());
+            }
+        }
+
         let id = THREAD_UUID_SEEDER.with(|seeder| seeder.borrow_mut().find(|id| *id != 0u64)).unwrap();
 
         Self(NonZeroU64::new(id).unwrap())
     }
 
+    /// Switches id generation to a seeded counter instead of randomness. All ids generated
+    /// afterwards only depend on the seed and the order in which ids of each manager are created,
+    /// so golden image and replay tests produce identical ids across runs. Calling this function
+    /// again resets all counters.
+    ///
+    /// Should be enabled before any other b4d function is called. Ids generated previously may
+    /// collide with seeded ids.
+    pub fn enable_seeded_mode(seed: u64) {
+        *SEEDED_IDS.lock().unwrap() = Some(SeededIds {
+            seed,
+            counters: HashMap::new(),
+        });
+        SEEDED_MODE.store(true, std::sync::atomic::Ordering::Release);
+    }
+
+    /// Switches id generation back to randomness.
+    pub fn disable_seeded_mode() {
+        SEEDED_MODE.store(false, std::sync::atomic::Ordering::Release);
+        *SEEDED_IDS.lock().unwrap() = None;
+    }
+
     pub const fn from_raw(id: u64) -> Self {
         if id == 0u64 {
             panic!("Zero id")
@@ -218,7 +287,7 @@ macro_rules! define_uuid_type {
 
         impl $name {
             $vis fn new() -> Self {
-                Self(UUID::new())
+                Self(UUID::new_for(stringify!($name)))
             }
 
             $vis fn from_uuid(raw: UUID) -> Self {
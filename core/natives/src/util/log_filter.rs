@@ -0,0 +1,123 @@
+//! Runtime configurable log levels for the subsystems of the crate.
+//!
+//! Log records are assigned to a subsystem based on their target which defaults to the module
+//! path. Records which do not belong to any subsystem use the default level. The filter is global
+//! since the logger is global. Loggers installed by the crate apply it automatically, custom
+//! loggers should call [`is_enabled`].
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::{LevelFilter, Metadata};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum LogSubsystem {
+    ObjectManager = 0,
+    Allocator = 1,
+    Emulator = 2,
+    Swapchain = 3,
+    CApi = 4,
+}
+
+impl LogSubsystem {
+    pub const ALL: [LogSubsystem; 5] = [Self::ObjectManager, Self::Allocator, Self::Emulator, Self::Swapchain, Self::CApi];
+
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::ObjectManager),
+            1 => Some(Self::Allocator),
+            2 => Some(Self::Emulator),
+            3 => Some(Self::Swapchain),
+            4 => Some(Self::CApi),
+            _ => None,
+        }
+    }
+
+    /// Returns the module paths belonging to this subsystem.
+    fn targets(&self) -> &'static [&'static str] {
+        match self {
+            Self::ObjectManager => &["b4d_core::objects", "b4d_core::vk::objects"],
+            Self::Allocator => &["b4d_core::allocator"],
+            Self::Emulator => &["b4d_core::renderer"],
+            Self::Swapchain => &["b4d_core::device::surface", "b4d_core::objects::swapchain_object_set", "b4d_core::vk::objects::swapchain", "b4d_core::vk::objects::surface"],
+            Self::CApi => &["b4d_core::c_api", "b4d_core::c_log", "b4d_core::c_validation"],
+        }
+    }
+
+    /// Returns the subsystem a log target belongs to. If multiple subsystems match the one with
+    /// the longest matching module path is used.
+    pub fn from_target(target: &str) -> Option<Self> {
+        let mut best: Option<(Self, usize)> = None;
+        for subsystem in Self::ALL {
+            for prefix in subsystem.targets() {
+                let matches = target.strip_prefix(prefix).map(|rest| rest.is_empty() || rest.starts_with("::")).unwrap_or(false);
+                if matches && best.map(|(_, len)| prefix.len() > len).unwrap_or(true) {
+                    best = Some((subsystem, prefix.len()));
+                }
+            }
+        }
+        best.map(|(subsystem, _)| subsystem)
+    }
+}
+
+/// Sentinel used for subsystems without a explicit level.
+const UNSET: usize = usize::MAX;
+
+static DEFAULT_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+static SUBSYSTEM_LEVELS: [AtomicUsize; 5] = [
+    AtomicUsize::new(UNSET),
+    AtomicUsize::new(UNSET),
+    AtomicUsize::new(UNSET),
+    AtomicUsize::new(UNSET),
+    AtomicUsize::new(UNSET),
+];
+
+fn level_from_usize(level: usize) -> LevelFilter {
+    match level {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Sets the level used for records not belonging to a subsystem with a explicit level.
+pub fn set_default_level(level: LevelFilter) {
+    DEFAULT_LEVEL.store(level as usize, Ordering::Relaxed);
+    update_max_level();
+}
+
+pub fn get_default_level() -> LevelFilter {
+    level_from_usize(DEFAULT_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Sets the level of a subsystem. If [`None`] the subsystem uses the default level.
+pub fn set_level(subsystem: LogSubsystem, level: Option<LevelFilter>) {
+    SUBSYSTEM_LEVELS[subsystem as usize].store(level.map(|level| level as usize).unwrap_or(UNSET), Ordering::Relaxed);
+    update_max_level();
+}
+
+/// Returns the level used for records of the subsystem.
+pub fn get_level(subsystem: LogSubsystem) -> LevelFilter {
+    match SUBSYSTEM_LEVELS[subsystem as usize].load(Ordering::Relaxed) {
+        UNSET => get_default_level(),
+        level => level_from_usize(level),
+    }
+}
+
+/// Returns true if a record with the metadata should be logged.
+pub fn is_enabled(metadata: &Metadata) -> bool {
+    let level = match LogSubsystem::from_target(metadata.target()) {
+        Some(subsystem) => get_level(subsystem),
+        None => get_default_level(),
+    };
+    metadata.level() <= level
+}
+
+/// The log macros discard records above the global max level before reaching the logger so it
+/// must be at least as verbose as the most verbose subsystem.
+fn update_max_level() {
+    let max = LogSubsystem::ALL.iter().map(|subsystem| get_level(*subsystem)).fold(get_default_level(), std::cmp::max);
+    log::set_max_level(max);
+}
@@ -4,3 +4,4 @@ pub mod slice_splitter;
 pub mod alloc;
 pub mod vk;
 pub mod format;
+pub mod log_filter;
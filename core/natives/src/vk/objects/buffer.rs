@@ -4,6 +4,7 @@ use std::hash::{Hash, Hasher};
 use ash::vk;
 use ash::vk::Handle;
 use crate::objects::id::{BufferId, ObjectId};
+use crate::vk::objects::Format;
 
 use crate::prelude::*;
 
@@ -102,4 +103,23 @@ impl BufferSpec {
 pub struct BufferRange {
     pub offset: u64,
     pub length: u64,
-}
\ No newline at end of file
+}
+
+/// Contains a description for a vulkan buffer view.
+///
+/// This only contains static information relevant to vulkan (i.e. range or format, however not the
+/// source buffer as buffer views with different sources may have the same description).
+#[derive(Copy, Clone, Debug)]
+pub struct BufferViewDescription {
+    pub format: &'static Format,
+    pub range: BufferRange,
+}
+
+impl BufferViewDescription {
+    pub fn new(format: &'static Format, range: BufferRange) -> Self {
+        Self {
+            format,
+            range,
+        }
+    }
+}
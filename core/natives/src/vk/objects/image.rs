@@ -4,7 +4,7 @@ use std::hash::{Hash, Hasher};
 
 use ash::vk;
 use ash::vk::Handle;
-use crate::objects::id::{ImageId, ObjectId};
+use crate::objects::id::{ImageId, ObjectId, SamplerYcbcrConversionId};
 use crate::vk::objects::Format;
 
 use crate::prelude::*;
@@ -330,6 +330,108 @@ impl ImageViewDescription {
     }
 }
 
+/// Contains a description for a vulkan sampler.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SamplerDescription {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+
+    /// The maximum anisotropy or [`None`] to disable anisotropic filtering. Clamped to the limit
+    /// of the device.
+    pub max_anisotropy: Option<f32>,
+    pub mip_lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+
+    /// Only used if any address mode is [`vk::SamplerAddressMode::CLAMP_TO_BORDER`].
+    pub border_color: vk::BorderColor,
+
+    /// The ycbcr conversion used to sample multi-planar images. Must be part of the same object
+    /// set as the sampler.
+    pub ycbcr_conversion: Option<SamplerYcbcrConversionId>,
+}
+
+impl SamplerDescription {
+    /// Creates a sampler description using the same filter and address mode for all dimensions
+    /// without anisotropy or lod clamping.
+    pub fn new(filter: vk::Filter, mipmap_mode: vk::SamplerMipmapMode, address_mode: vk::SamplerAddressMode) -> Self {
+        Self {
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_mode,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            max_anisotropy: None,
+            mip_lod_bias: 0f32,
+            min_lod: 0f32,
+            max_lod: vk::LOD_CLAMP_NONE,
+            border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+            ycbcr_conversion: None,
+        }
+    }
+
+    /// Creates a nearest filtering sampler description clamping to the edge of the image.
+    pub fn new_nearest() -> Self {
+        Self::new(vk::Filter::NEAREST, vk::SamplerMipmapMode::NEAREST, vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    }
+
+    /// Creates a linear filtering sampler description clamping to the edge of the image.
+    pub fn new_linear() -> Self {
+        Self::new(vk::Filter::LINEAR, vk::SamplerMipmapMode::LINEAR, vk::SamplerAddressMode::CLAMP_TO_EDGE)
+    }
+
+    /// Creates a sampler description for multi-planar images using a ycbcr conversion. The filter
+    /// should match the chroma filter of the conversion.
+    pub fn new_ycbcr(filter: vk::Filter, conversion: SamplerYcbcrConversionId) -> Self {
+        let mut description = Self::new(filter, vk::SamplerMipmapMode::NEAREST, vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        description.ycbcr_conversion = Some(conversion);
+        description
+    }
+}
+
+/// Contains a description for a vulkan sampler ycbcr conversion.
+///
+/// Ycbcr conversions are needed to sample from multi-planar formats (for example decoded video
+/// frames) and must be used by both the image view and sampler accessing the image.
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerYcbcrConversionDescription {
+    pub format: &'static Format,
+    pub model: vk::SamplerYcbcrModelConversion,
+    pub range: vk::SamplerYcbcrRange,
+    pub components: vk::ComponentMapping,
+    pub x_chroma_offset: vk::ChromaLocation,
+    pub y_chroma_offset: vk::ChromaLocation,
+    pub chroma_filter: vk::Filter,
+    pub force_explicit_reconstruction: bool,
+}
+
+impl SamplerYcbcrConversionDescription {
+    /// Creates a conversion description for BT.709 narrow range content with identity component
+    /// mapping and linear chroma filtering. This matches the output of most video decoders.
+    pub fn new_bt709(format: &'static Format) -> Self {
+        Self {
+            format,
+            model: vk::SamplerYcbcrModelConversion::YCBCR_709,
+            range: vk::SamplerYcbcrRange::ITU_NARROW,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY
+            },
+            x_chroma_offset: vk::ChromaLocation::MIDPOINT,
+            y_chroma_offset: vk::ChromaLocation::MIDPOINT,
+            chroma_filter: vk::Filter::LINEAR,
+            force_explicit_reconstruction: false,
+        }
+    }
+}
+
 pub struct ImageInstanceData {
     handle: vk::Image
 }
@@ -62,9 +62,51 @@ impl From<vk::Result> for SurfaceInitError {
 pub trait SurfaceProvider: Send + Sync {
     fn get_required_instance_extensions(&self) -> Vec<CString>;
 
+    /// Creates the surface.
+    ///
+    /// May be called again after the surface has been lost in which case the old surface must be
+    /// destroyed and a new surface created.
     fn init(&mut self, entry: &ash::Entry, instance: &ash::Instance) -> Result<vk::SurfaceKHR, SurfaceInitError>;
 
     fn get_handle(&self) -> Option<vk::SurfaceKHR>;
+
+    /// Returns all display modes supported by the display the surface is currently on. If the
+    /// provider does not support display modes an empty vector is returned.
+    fn get_display_modes(&self) -> Vec<DisplayMode> {
+        Vec::new()
+    }
+
+    /// Requests the surface to enter or leave exclusive fullscreen with the specified display mode.
+    ///
+    /// Returns false if the request is not supported by the provider.
+    fn set_exclusive_fullscreen(&self, _mode: Option<DisplayMode>) -> bool {
+        false
+    }
+
+    /// Returns the size of the drawable area of the surface in pixels if the swapchain size must
+    /// be taken from the windowing system.
+    ///
+    /// Some platforms (for example wayland) do not report a surface extent and instead use the
+    /// size of the swapchain to determine the size of the surface. Providers for such platforms
+    /// should return the size here. If [`None`] is returned the size passed by the caller is used.
+    fn get_framebuffer_size(&self) -> Option<Vec2u32> {
+        None
+    }
+
+    /// Returns the ratio between the size of the surface in pixels and the size in screen
+    /// coordinates. Providers which do not support content scales return 1 on both axes.
+    fn get_content_scale(&self) -> Vec2f32 {
+        Vec2f32::new(1f32, 1f32)
+    }
+}
+
+/// A display mode of a monitor.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DisplayMode {
+    pub size: Vec2u32,
+    /// The refresh rate in hz.
+    pub refresh_rate: u32,
+    pub bit_depth: u32,
 }
 
 pub struct SurfaceCapabilities {
@@ -1,9 +1,17 @@
 use std::ffi::{CStr, CString};
+use std::time::{Duration, Instant};
+
 use ash::{Entry, Instance, vk};
 use winit::dpi::LogicalSize;
-use winit::event_loop::EventLoop;
-use winit::window::WindowBuilder;
-use crate::vk::objects::surface::{SurfaceInitError, SurfaceProvider};
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Fullscreen, WindowBuilder};
+
+use crate::b4d::Blaze4D;
+use crate::renderer::emulator::{FrameSize, PassRecorder};
+use crate::vk::objects::surface::{DisplayMode, SurfaceInitError, SurfaceProvider};
+
+use crate::prelude::*;
 
 pub struct WinitWindow {
     handle: winit::window::Window,
@@ -26,6 +34,115 @@ impl WinitWindow {
             khr_surface: None,
         }
     }
+
+    /// Returns the current size of the drawable area of the window in physical pixels.
+    pub fn get_inner_size(&self) -> Vec2u32 {
+        let size = self.handle.inner_size();
+        Vec2u32::new(size.width, size.height)
+    }
+
+    pub fn get_scale_factor(&self) -> f64 {
+        self.handle.scale_factor()
+    }
+}
+
+/// Information about the frame currently being recorded by a [`WinitRunner`].
+#[derive(Copy, Clone, Debug)]
+pub struct FrameInfo {
+    /// The size of the window in physical pixels.
+    pub window_size: Vec2u32,
+
+    /// The current scale factor of the window.
+    pub scale_factor: f64,
+
+    /// The number of frames recorded before this frame.
+    pub frame_index: u64,
+
+    /// The time passed since the runner has been started.
+    pub elapsed: Duration,
+}
+
+/// Utility which owns a winit event loop and a [`Blaze4D`] instance rendering to a single window.
+///
+/// This is intended to make it easy to write standalone rust demos and integration tests. Resize
+/// and scale factor events are tracked and used for every frame started by [`WinitRunner::run`].
+pub struct WinitRunner {
+    event_loop: EventLoop<()>,
+    b4d: Blaze4D,
+    window_size: Vec2u32,
+    scale_factor: f64,
+}
+
+impl WinitRunner {
+    pub fn new(title: &str, width: f64, height: f64, enable_validation: bool) -> Self {
+        let event_loop = EventLoop::new();
+        let window = Box::new(WinitWindow::new(title, width, height, &event_loop));
+        let window_size = window.get_inner_size();
+        let scale_factor = window.get_scale_factor();
+
+        let b4d = Blaze4D::new(window, enable_validation);
+
+        Self {
+            event_loop,
+            b4d,
+            window_size,
+            scale_factor,
+        }
+    }
+
+    /// Returns the [`Blaze4D`] instance. Can be used to create resources before calling
+    /// [`WinitRunner::run`].
+    pub fn get_b4d(&self) -> &Blaze4D {
+        &self.b4d
+    }
+
+    /// Runs the event loop until the window is closed.
+    ///
+    /// `render_fn` is called once for every frame that could be started. Frames are skipped while
+    /// the window has a size of 0 (for example if it is minimized). The pass is submitted once
+    /// `render_fn` returns.
+    pub fn run<F>(self, mut render_fn: F) -> ! where F: FnMut(&Blaze4D, &mut PassRecorder, &FrameInfo) + 'static {
+        let Self { event_loop, b4d, mut window_size, mut scale_factor } = self;
+
+        let start = Instant::now();
+        let mut frame_index = 0u64;
+
+        event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+
+            match event {
+                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                Event::WindowEvent { event: WindowEvent::Resized(new_size), .. } => {
+                    window_size = Vec2u32::new(new_size.width, new_size.height);
+                }
+                Event::WindowEvent { event: WindowEvent::ScaleFactorChanged { scale_factor: new_scale_factor, new_inner_size }, .. } => {
+                    scale_factor = new_scale_factor;
+                    window_size = Vec2u32::new(new_inner_size.width, new_inner_size.height);
+                }
+                Event::MainEventsCleared => {
+                    if window_size[0] == 0 || window_size[1] == 0 {
+                        return;
+                    }
+
+                    if let Some(mut recorder) = b4d.try_start_frame_scaled(FrameSize::from_physical(window_size, scale_factor as f32)) {
+                        let info = FrameInfo {
+                            window_size,
+                            scale_factor,
+                            frame_index,
+                            elapsed: start.elapsed(),
+                        };
+                        render_fn(&b4d, &mut recorder, &info);
+                        drop(recorder);
+
+                        frame_index += 1;
+                    }
+                }
+                _ => {}
+            }
+        })
+    }
 }
 
 impl SurfaceProvider for WinitWindow {
@@ -36,6 +153,11 @@ impl SurfaceProvider for WinitWindow {
     }
 
     fn init(&mut self, entry: &Entry, instance: &Instance) -> Result<vk::SurfaceKHR, SurfaceInitError> {
+        // The provider may be reinitialized if the surface has been lost
+        if let (Some(surface), Some(ash_surface)) = (self.khr_surface.take(), self.ash_surface.as_ref()) {
+            unsafe { ash_surface.destroy_surface(surface, None) };
+        }
+
         let surface = unsafe { ash_window::create_surface(entry, instance, &self.handle, None)? };
 
         self.khr_surface = Some(surface);
@@ -47,6 +169,48 @@ impl SurfaceProvider for WinitWindow {
     fn get_handle(&self) -> Option<vk::SurfaceKHR> {
         self.khr_surface
     }
+
+    fn get_content_scale(&self) -> Vec2f32 {
+        let scale = self.handle.scale_factor() as f32;
+        Vec2f32::new(scale, scale)
+    }
+
+    fn get_display_modes(&self) -> Vec<DisplayMode> {
+        match self.handle.current_monitor() {
+            Some(monitor) => monitor.video_modes().map(|mode| DisplayMode {
+                size: Vec2u32::new(mode.size().width, mode.size().height),
+                refresh_rate: mode.refresh_rate() as u32,
+                bit_depth: mode.bit_depth() as u32,
+            }).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn set_exclusive_fullscreen(&self, mode: Option<DisplayMode>) -> bool {
+        let mode = match mode {
+            Some(mode) => mode,
+            None => {
+                self.handle.set_fullscreen(None);
+                return true;
+            }
+        };
+
+        let monitor = self.handle.current_monitor();
+        let video_mode = monitor.as_ref().and_then(|monitor| monitor.video_modes().find(|video_mode| {
+            video_mode.size().width == mode.size[0] &&
+                video_mode.size().height == mode.size[1] &&
+                video_mode.refresh_rate() as u32 == mode.refresh_rate &&
+                video_mode.bit_depth() as u32 == mode.bit_depth
+        }));
+
+        if let Some(video_mode) = video_mode {
+            self.handle.set_fullscreen(Some(Fullscreen::Exclusive(video_mode)));
+        } else {
+            log::warn!("Display mode {:?} is not supported. Falling back to borderless fullscreen", mode);
+            self.handle.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+        }
+        true
+    }
 }
 
 impl Drop for WinitWindow {
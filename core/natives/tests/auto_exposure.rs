@@ -0,0 +1,82 @@
+use b4d_core::renderer::emulator::auto_exposure::{adapt_exposure, AutoExposure, HISTOGRAM_BIN_COUNT};
+
+/// Bins the luminance the same way the histogram shader does.
+fn bin_of(settings: &AutoExposure, luminance: f32) -> usize {
+    if luminance < 0.0001f32 {
+        return 0;
+    }
+    let range = settings.max_log_luminance - settings.min_log_luminance;
+    let normalized = ((luminance.log2() - settings.min_log_luminance) / range).clamp(0f32, 1f32);
+    (normalized * ((HISTOGRAM_BIN_COUNT - 2) as f32) + 1f32) as usize
+}
+
+fn make_histogram(settings: &AutoExposure, luminances: &[(f32, u32)]) -> Vec<u32> {
+    let mut histogram = vec![0u32; HISTOGRAM_BIN_COUNT];
+    for (luminance, count) in luminances {
+        histogram[bin_of(settings, *luminance)] += count;
+    }
+    histogram
+}
+
+#[test]
+fn average_log_luminance() {
+    let settings = AutoExposure::default();
+    let bin_range = (settings.max_log_luminance - settings.min_log_luminance) / ((HISTOGRAM_BIN_COUNT - 2) as f32);
+
+    let histogram = make_histogram(&settings, &[(0.25f32, 100)]);
+    let average = settings.average_log_luminance(&histogram).unwrap();
+    assert!((average - -2f32).abs() <= bin_range);
+
+    // Equal counts average in log space
+    let histogram = make_histogram(&settings, &[(0.125f32, 50), (0.5f32, 50)]);
+    let average = settings.average_log_luminance(&histogram).unwrap();
+    assert!((average - -2f32).abs() <= bin_range);
+
+    // Black pixels are ignored
+    let histogram = make_histogram(&settings, &[(0f32, 1000), (0.25f32, 10)]);
+    let average = settings.average_log_luminance(&histogram).unwrap();
+    assert!((average - -2f32).abs() <= bin_range);
+
+    let histogram = make_histogram(&settings, &[(0f32, 1000)]);
+    assert!(settings.average_log_luminance(&histogram).is_none());
+}
+
+#[test]
+fn target_exposure() {
+    let settings = AutoExposure {
+        enabled: true,
+        min_log_luminance: -4f32,
+        max_log_luminance: 0f32,
+        target_luminance: 0.25f32,
+        adaptation_speed: 1f32,
+    };
+
+    assert!((settings.target_exposure(-2f32) - 1f32).abs() < 1e-5f32);
+    assert!((settings.target_exposure(-3f32) - 2f32).abs() < 1e-5f32);
+
+    // The average is clamped to the histogram range
+    assert!((settings.target_exposure(-10f32) - 4f32).abs() < 1e-5f32);
+    assert!((settings.target_exposure(3f32) - 0.25f32).abs() < 1e-5f32);
+}
+
+#[test]
+fn adaptation() {
+    // No time passed or no speed keeps the current exposure
+    assert!((adapt_exposure(1f32, 4f32, 0f32, 2f32) - 1f32).abs() < 1e-5f32);
+    assert!((adapt_exposure(1f32, 4f32, 1f32, 0f32) - 1f32).abs() < 1e-5f32);
+
+    // The exposure moves towards the target without overshooting
+    let brighter = adapt_exposure(1f32, 4f32, 0.5f32, 2f32);
+    assert!(brighter > 1f32 && brighter < 4f32);
+    let darker = adapt_exposure(1f32, 0.25f32, 0.5f32, 2f32);
+    assert!(darker < 1f32 && darker > 0.25f32);
+
+    // Adaptation is symmetric in log space
+    assert!((brighter.log2() + darker.log2()).abs() < 1e-4f32);
+
+    // Two half steps equal one full step
+    let half = adapt_exposure(adapt_exposure(1f32, 4f32, 0.25f32, 2f32), 4f32, 0.25f32, 2f32);
+    assert!((half - brighter).abs() < 1e-4f32);
+
+    assert!((adapt_exposure(1f32, 4f32, 100f32, 2f32) - 4f32).abs() < 1e-4f32);
+}
@@ -0,0 +1,81 @@
+use ash::vk;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::beam::{BeamColumn, BeamMesh};
+use b4d_core::renderer::emulator::mc_shaders::{VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+/// The vanilla position tex color format.
+fn make_format() -> VertexFormat {
+    VertexFormat {
+        stride: 24,
+        position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+        normal: None,
+        color: Some(VertexFormatEntry { offset: 20, format: vk::Format::R8G8B8A8_UNORM }),
+        uv0: Some(VertexFormatEntry { offset: 12, format: vk::Format::R32G32_SFLOAT }),
+        uv1: None,
+        uv2: None,
+        position_quantization: None,
+        normal_encoding: NormalEncoding::Direct,
+    }
+}
+
+fn make_beam() -> BeamColumn {
+    BeamColumn {
+        position: Vec3f32::new(10f32, 64f32, -3f32),
+        height: 256f32,
+        radius: 0.25f32,
+        rotation: 0f32,
+        scroll: 0f32,
+        color: Vec4f32::new(1f32, 1f32, 1f32, 1f32),
+    }
+}
+
+fn assert_close(a: Vec4f32, b: Vec4f32) {
+    assert!((a - b).norm() < 1e-4f32, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn model_matrix() {
+    let mut beam = make_beam();
+    let matrix = beam.get_model_matrix();
+    assert_close(matrix * Vec4f32::new(0f32, 0f32, 0f32, 1f32), Vec4f32::new(10f32, 64f32, -3f32, 1f32));
+    assert_close(matrix * Vec4f32::new(1f32, 1f32, 0f32, 1f32), Vec4f32::new(10.25f32, 320f32, -3f32, 1f32));
+
+    beam.rotation = std::f32::consts::FRAC_PI_2;
+    let matrix = beam.get_model_matrix();
+    assert_close(matrix * Vec4f32::new(1f32, 0f32, 0f32, 1f32), Vec4f32::new(10f32, 64f32, -3.25f32, 1f32));
+}
+
+#[test]
+fn texture_matrix() {
+    let mut beam = make_beam();
+    beam.animate(10.5f32, 1f32, 0.1f32);
+    assert!((beam.scroll + 0.05f32).abs() < 1e-4f32);
+    assert!((beam.rotation - 10.5f32 % std::f32::consts::TAU).abs() < 1e-4f32);
+
+    let matrix = beam.get_texture_matrix();
+    assert_close(matrix * Vec4f32::new(0.5f32, 1f32, 0f32, 1f32), Vec4f32::new(0.5f32, 256f32 + beam.scroll, 0f32, 1f32));
+}
+
+#[test]
+fn unit_column() {
+    let mesh = BeamMesh::new(&make_format());
+    let data = mesh.as_mesh_data();
+    assert_eq!(data.primitive_topology, vk::PrimitiveTopology::TRIANGLE_LIST);
+    assert_eq!(data.index_count, BeamMesh::SIDE_COUNT * 6);
+    assert_eq!(data.vertex_data.len(), (BeamMesh::SIDE_COUNT as usize) * 4 * 24);
+
+    for vertex in data.vertex_data.chunks_exact(24) {
+        let value = |i: usize| f32::from_ne_bytes(vertex[(i * 4)..(i * 4 + 4)].try_into().unwrap());
+        assert_eq!(value(0).abs(), 1f32);
+        assert_eq!(value(2).abs(), 1f32);
+        // v is 1 at the bottom and 0 at the top
+        assert_eq!(value(4), 1f32 - value(1));
+        assert_eq!(&vertex[20..24], &[255u8; 4]);
+    }
+
+    let mut format = make_format();
+    format.uv0 = None;
+    assert!(!BeamMesh::supports_format(&format));
+}
@@ -0,0 +1,80 @@
+use ash::vk;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::blob_shadow::{BlobShadow, BlobShadowBatch, BlobShadowConfig, BLOB_SHADOW_INDEX_COUNT, BLOB_SHADOW_VERTEX_COUNT};
+use b4d_core::renderer::emulator::mc_shaders::{VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+/// The vanilla position color format.
+fn make_format() -> VertexFormat {
+    VertexFormat {
+        stride: 16,
+        position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+        normal: None,
+        color: Some(VertexFormatEntry { offset: 12, format: vk::Format::R8G8B8A8_UNORM }),
+        uv0: None,
+        uv1: None,
+        uv2: None,
+        position_quantization: None,
+        normal_encoding: NormalEncoding::Direct,
+    }
+}
+
+fn read_position(vertex: &[u8]) -> [f32; 3] {
+    [0, 1, 2].map(|i| f32::from_ne_bytes(vertex[(i * 4)..(i * 4 + 4)].try_into().unwrap()))
+}
+
+fn make_shadow(position: Vec3f32, height: f32) -> BlobShadow {
+    BlobShadow { ground_position: position, radius: 0.5f32, strength: 1f32, height }
+}
+
+#[test]
+fn fade() {
+    let config = BlobShadowConfig::new();
+    assert_eq!(config.get_opacity(&make_shadow(Vec3f32::zeros(), 0f32)), 1f32);
+    assert_eq!(config.get_opacity(&make_shadow(Vec3f32::zeros(), config.max_height / 2f32)), 0.5f32);
+    assert_eq!(config.get_opacity(&make_shadow(Vec3f32::zeros(), config.max_height)), 0f32);
+
+    let distance = (config.fade_start + config.fade_end) / 2f32;
+    assert_eq!(config.get_opacity(&make_shadow(Vec3f32::new(distance, 0f32, 0f32), 0f32)), 0.5f32);
+    assert_eq!(config.get_opacity(&make_shadow(Vec3f32::new(config.fade_end + 1f32, 0f32, 0f32), 0f32)), 0f32);
+
+    let mut shadow = make_shadow(Vec3f32::zeros(), 0f32);
+    shadow.radius = 0f32;
+    assert_eq!(config.get_opacity(&shadow), 0f32);
+}
+
+#[test]
+fn disk_geometry() {
+    let config = BlobShadowConfig::new();
+    let mut batch = BlobShadowBatch::new(&make_format(), &config);
+    assert!(batch.push_shadow(&make_shadow(Vec3f32::new(1f32, 64f32, 2f32), 0f32)));
+    assert!(!batch.push_shadow(&make_shadow(Vec3f32::new(1f32, 64f32, 2f32), 100f32)));
+    assert_eq!(batch.get_shadow_count(), 1);
+
+    let mesh = batch.as_mesh_data();
+    assert_eq!(mesh.primitive_topology, vk::PrimitiveTopology::TRIANGLE_LIST);
+    assert_eq!(mesh.index_count, BLOB_SHADOW_INDEX_COUNT);
+    assert_eq!(mesh.index_data.len(), (BLOB_SHADOW_INDEX_COUNT as usize) * 4);
+    assert_eq!(mesh.vertex_data.len(), (BLOB_SHADOW_VERTEX_COUNT as usize) * 16);
+
+    // The center is raised by the depth bias and fully opaque
+    assert_eq!(read_position(&mesh.vertex_data[0..16]), [1f32, 64f32 + config.depth_bias, 2f32]);
+    assert_eq!(&mesh.vertex_data[12..16], &[0, 0, 0, 255]);
+
+    // The first outer vertex lies on the edge of the disk and is transparent
+    let outer = &mesh.vertex_data[32..48];
+    assert_eq!(read_position(outer), [1.5f32, 64f32 + config.depth_bias, 2f32]);
+    assert_eq!(outer[15], 0);
+
+    let max_index = mesh.index_data.chunks_exact(4).map(|index| u32::from_ne_bytes(index.try_into().unwrap())).max().unwrap();
+    assert_eq!(max_index, BLOB_SHADOW_VERTEX_COUNT - 1);
+}
+
+#[test]
+fn unsupported_format() {
+    let mut format = make_format();
+    assert!(BlobShadowBatch::supports_format(&format));
+    format.color = None;
+    assert!(!BlobShadowBatch::supports_format(&format));
+}
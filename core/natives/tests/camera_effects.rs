@@ -0,0 +1,41 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::camera_effects::{CameraEffects, ScreenShake, ViewBob};
+use b4d_core::renderer::emulator::shadow::CameraFrustum;
+
+fn frustum() -> CameraFrustum {
+    CameraFrustum {
+        view: Mat4f32::new_translation(&Vec3f32::new(1f32, 2f32, 3f32)),
+        fov_y: 1.2f32,
+        aspect: 1.5f32,
+        near: 0.05f32,
+        far: 256f32,
+    }
+}
+
+#[test]
+fn empty_effects_are_identity() {
+    let effects = CameraEffects::default();
+    assert!(effects.is_empty());
+    assert_eq!(effects.get_matrix(), Mat4f32::identity());
+    assert_eq!(effects.apply_to_frustum(&frustum()), frustum());
+}
+
+#[test]
+fn frustum_follows_bob() {
+    let bob = ViewBob { phase: 0.7f32, amplitude: 0.1f32 };
+    let effects = CameraEffects {
+        bob: Some(bob),
+        shake: None,
+    };
+
+    assert_eq!(effects.get_matrix(), bob.get_matrix());
+    assert_eq!(effects.apply_to_frustum(&frustum()).view, bob.get_matrix() * frustum().view);
+    assert_eq!(effects.apply_to_view(&frustum().view), bob.get_matrix() * frustum().view);
+}
+
+#[test]
+fn hurt_shake_fades_out() {
+    assert_eq!(ScreenShake::from_hurt(0f32, 10f32, 90f32).intensity, 0f32);
+    assert!(ScreenShake::from_hurt(8f32, 10f32, 90f32).intensity > 0f32);
+    assert!(ScreenShake::from_hurt(8f32, 10f32, 90f32).intensity <= ScreenShake::HURT_ROLL);
+}
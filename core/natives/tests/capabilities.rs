@@ -0,0 +1,18 @@
+use b4d_core::device::capabilities::{FeatureSource, FeatureTier};
+use b4d_core::instance::instance::VulkanVersion;
+
+#[test]
+fn feature_tiers() {
+    assert_eq!(FeatureTier::from_version(VulkanVersion::VK_1_1), FeatureTier::Vulkan11);
+    assert_eq!(FeatureTier::from_version(VulkanVersion::new(0, 1, 2, 198)), FeatureTier::Vulkan12);
+    assert_eq!(FeatureTier::from_version(VulkanVersion::new(0, 1, 3, 0)), FeatureTier::Vulkan13);
+}
+
+#[test]
+fn feature_sources() {
+    // Promoted features are core even if the extension is not listed
+    assert_eq!(FeatureSource::probe(VulkanVersion::VK_1_3, VulkanVersion::VK_1_2, false), FeatureSource::Core);
+    assert_eq!(FeatureSource::probe(VulkanVersion::VK_1_1, VulkanVersion::VK_1_2, true), FeatureSource::Extension);
+    assert_eq!(FeatureSource::probe(VulkanVersion::VK_1_2, VulkanVersion::VK_1_3, false), FeatureSource::Unsupported);
+    assert!(!FeatureSource::Unsupported.is_supported());
+}
@@ -0,0 +1,140 @@
+use ash::vk;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::{FrameSize, GlobalMeshId, SamplerInfo, GlobalImageId, ImageArrayMode};
+use b4d_core::renderer::emulator::command_log::{CommandLogError, PassCommand, PassCommandLog};
+use b4d_core::renderer::emulator::command_stream::{CommandStream, StreamEvent};
+use b4d_core::renderer::emulator::mc_shaders::{McUniform, McUniformData, ShaderId, ShaderSpecialization, VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::MeshData;
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+use b4d_core::renderer::emulator::unproject::ViewMatrices;
+
+fn make_log() -> PassCommandLog {
+    let shader = ShaderId::new();
+    let mut log = PassCommandLog::new(Some(FrameSize::from_physical(Vec2u32::new(800, 600), 2f32)));
+
+    log.push(PassCommand::SetPartialTick(0.25f32));
+    log.push(PassCommand::SetViewport(1, vk::Viewport { x: 0f32, y: 0f32, width: 400f32, height: 600f32, min_depth: 0f32, max_depth: 1f32 }));
+    log.push(PassCommand::SetViewportIndex(1));
+    log.push(PassCommand::SetScissor(Some(vk::Rect2D { offset: vk::Offset2D { x: -4, y: 8 }, extent: vk::Extent2D { width: 100, height: 50 } })));
+    log.push(PassCommand::SetScissor(None));
+    log.push(PassCommand::SetDrawTag(Some(u64::MAX - 7)));
+    log.push(PassCommand::SetDrawTag(None));
+    log.push(PassCommand::UpdateUniform(shader, McUniformData::ProjectionMatrix(Mat4f32::new_scaling(2f32))));
+    log.push(PassCommand::UpdateUniform(shader, McUniformData::FogShape(1)));
+    log.push(PassCommand::UpdateTexture {
+        index: 0,
+        image: GlobalImageId::new(),
+        sampler: SamplerInfo {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::REPEAT,
+            address_mode_v: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            anisotropy_enable: true
+        },
+        shader
+    });
+    log.push(PassCommand::UploadImmediate {
+        id: 3,
+        vertex_data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+        index_data: vec![0, 0, 1, 0],
+        vertex_stride: 4,
+        index_count: 2,
+        index_type: vk::IndexType::UINT16,
+        primitive_topology: vk::PrimitiveTopology::LINE_LIST
+    });
+    log.push(PassCommand::DrawImmediate { id: 3, shader, depth_write_enable: true });
+    log.push(PassCommand::DrawGlobal { mesh: GlobalMeshId::new(), shader, depth_write_enable: false });
+    log.push(PassCommand::BeginPortal {
+        matrices: ViewMatrices { model_view: Mat4f32::new_translation(&Vec3f32::new(1f32, -2f32, 3f32)), projection: Mat4f32::new_scaling(0.5f32) },
+        mask: 3,
+        shader
+    });
+    log.push(PassCommand::EndPortal);
+
+    log
+}
+
+#[test]
+fn round_trip() {
+    let log = make_log();
+
+    let mut data = Vec::new();
+    log.serialize(&mut data);
+    let (read, len) = PassCommandLog::deserialize(&data).unwrap();
+
+    assert_eq!(len, data.len());
+    assert_eq!(read.frame_size, log.frame_size);
+    assert_eq!(format!("{:?}", read.commands), format!("{:?}", log.commands));
+}
+
+#[test]
+fn truncated_data() {
+    let mut data = Vec::new();
+    make_log().serialize(&mut data);
+
+    for len in [0, 3, 8, data.len() - 1] {
+        assert!(PassCommandLog::deserialize(&data[..len]).is_err());
+    }
+
+    data[0] = b'X';
+    assert_eq!(PassCommandLog::deserialize(&data).unwrap_err(), CommandLogError::InvalidMagic);
+}
+
+#[test]
+fn stream_round_trip() {
+    let vertex_data = [0u8; 24];
+    let index_data = [0u8, 0, 1, 0, 2, 0];
+    let mesh = MeshData {
+        vertex_data: &vertex_data,
+        index_data: &index_data,
+        vertex_stride: 8,
+        index_count: 3,
+        index_type: vk::IndexType::UINT16,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    };
+    let vertex_format = VertexFormat {
+        stride: 8,
+        position: VertexFormatEntry { offset: 0, format: vk::Format::R16G16B16A16_SNORM },
+        normal: None,
+        color: None,
+        uv0: None,
+        uv1: None,
+        uv2: None,
+        position_quantization: None,
+        normal_encoding: NormalEncoding::Direct,
+    };
+
+    let stream = CommandStream {
+        events: vec![
+            StreamEvent::create_global_mesh(GlobalMeshId::new(), &mesh, false),
+            StreamEvent::create_global_mesh(GlobalMeshId::new(), &mesh, true),
+            StreamEvent::CreateGlobalImage { id: GlobalImageId::new(), size: Vec2u32::new(16, 16), mip_levels: 1, array_mode: ImageArrayMode::Single, format: vk::Format::R8G8B8A8_SRGB },
+            StreamEvent::CreateGlobalImage { id: GlobalImageId::new(), size: Vec2u32::new(16, 16), mip_levels: 5, array_mode: ImageArrayMode::Atlas(Vec2u32::new(4, 2)), format: vk::Format::R8G8B8A8_SRGB },
+            StreamEvent::CreateShader { id: ShaderId::new(), vertex_format, used_uniforms: McUniform::MODEL_VIEW_MATRIX, specialization: ShaderSpecialization::default() },
+            StreamEvent::CreateShader { id: ShaderId::new(), vertex_format, used_uniforms: McUniform::MODEL_VIEW_MATRIX, specialization: ShaderSpecialization { emissive: true, emissive_image: Some(2), item_animation: true, ..Default::default() } },
+            StreamEvent::Frame(make_log()),
+            StreamEvent::Frame(make_log()),
+        ]
+    };
+
+    let mut data = Vec::new();
+    stream.serialize(&mut data);
+    let read = CommandStream::deserialize(&data).unwrap();
+
+    assert_eq!(read.get_frame_count(), 2);
+    assert_eq!(format!("{:?}", read.events), format!("{:?}", stream.events));
+}
+
+#[test]
+fn emissive_image_constant() {
+    let mut specialization = ShaderSpecialization { emissive_image: Some(1), ..Default::default() };
+    assert_eq!(specialization.resolve_emissive_image(), ShaderSpecialization::NO_EMISSIVE_IMAGE);
+
+    specialization.emissive = true;
+    assert_eq!(specialization.resolve_emissive_image(), 1);
+
+    specialization.emissive_image = None;
+    assert_eq!(specialization.resolve_emissive_image(), ShaderSpecialization::NO_EMISSIVE_IMAGE);
+}
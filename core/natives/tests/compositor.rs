@@ -0,0 +1,17 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::compositor::texel_region_to_uv;
+
+#[test]
+fn full_image_region() {
+    let (min, max) = texel_region_to_uv(Vec2u32::new(64, 32), Vec2u32::zeros(), Vec2u32::new(64, 32));
+    assert_eq!(min, Vec2f32::zeros());
+    assert_eq!(max, Vec2f32::new(1f32, 1f32));
+}
+
+#[test]
+fn skin_overlay_region() {
+    // The hat overlay of a 64x64 skin
+    let (min, max) = texel_region_to_uv(Vec2u32::new(64, 64), Vec2u32::new(32, 0), Vec2u32::new(32, 16));
+    assert_eq!(min, Vec2f32::new(0.5f32, 0f32));
+    assert_eq!(max, Vec2f32::new(1f32, 0.25f32));
+}
@@ -0,0 +1,92 @@
+use ash::vk;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::debug_overlay::{heatmap_color, ChunkGridOverlay, DebugOverlayBatch, DebugOverlays, SectionHeatmap, SectionValue};
+use b4d_core::renderer::emulator::mc_shaders::{VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+/// The vanilla position color format.
+fn make_format() -> VertexFormat {
+    VertexFormat {
+        stride: 16,
+        position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+        normal: None,
+        color: Some(VertexFormatEntry { offset: 12, format: vk::Format::R8G8B8A8_UNORM }),
+        uv0: None,
+        uv1: None,
+        uv2: None,
+        position_quantization: None,
+        normal_encoding: NormalEncoding::Direct,
+    }
+}
+
+fn read_position(vertex: &[u8]) -> [f32; 3] {
+    [0, 1, 2].map(|i| f32::from_ne_bytes(vertex[(i * 4)..(i * 4 + 4)].try_into().unwrap()))
+}
+
+#[test]
+fn chunk_grid() {
+    let mut overlays = DebugOverlays::new();
+    overlays.origin_block = Vec3i32::new(16, 0, -16);
+    overlays.origin_offset = Vec3f32::new(0.5f32, 0f32, 0.25f32);
+    overlays.chunk_grid = Some(ChunkGridOverlay {
+        center_chunk: Vec2i32::new(1, -1),
+        radius: 0,
+        min_y: -5,
+        max_y: 32,
+        color: [255, 0, 0, 255],
+        section_color: [0, 0, 255, 255],
+    });
+
+    let batch = DebugOverlayBatch::from_overlays(&make_format(), &overlays);
+    // 4 chunk corners and 4 edges at y = 0, 16 and 32
+    assert_eq!(batch.get_line_count(), 4 + 3 * 4);
+
+    let mesh = batch.as_mesh_data();
+    assert_eq!(mesh.primitive_topology, vk::PrimitiveTopology::LINE_LIST);
+    assert_eq!(mesh.index_count, 2 * batch.get_line_count());
+
+    // The first line is the vertical line at the minimum corner of the center chunk
+    assert_eq!(read_position(&mesh.vertex_data[0..16]), [-0.5f32, -5f32, -0.25f32]);
+    assert_eq!(read_position(&mesh.vertex_data[16..32]), [-0.5f32, 32f32, -0.25f32]);
+    assert_eq!(&mesh.vertex_data[12..16], &[255, 0, 0, 255]);
+    assert_eq!(&mesh.vertex_data[(8 * 16 + 12)..(8 * 16 + 16)], &[0, 0, 255, 255]);
+}
+
+#[test]
+fn heatmap() {
+    assert_eq!(heatmap_color(0f32), [0, 0, 255]);
+    assert_eq!(heatmap_color(0.5f32), [0, 255, 0]);
+    assert_eq!(heatmap_color(1f32), [255, 0, 0]);
+    assert_eq!(heatmap_color(7f32), [255, 0, 0]);
+
+    let heatmap = SectionHeatmap {
+        values: vec![
+            SectionValue { section: Vec3i32::new(0, 4, 0), value: 15f32 },
+            SectionValue { section: Vec3i32::new(1, 4, 0), value: f32::NAN },
+        ],
+        min_value: 0f32,
+        max_value: 15f32,
+        alpha: 128,
+    };
+    assert_eq!(heatmap.get_color(15f32), Some([255, 0, 0, 128]));
+    assert_eq!(heatmap.get_color(f32::NAN), None);
+
+    let mut batch = DebugOverlayBatch::new(&make_format(), Vec3i32::zeros(), Vec3f32::zeros());
+    batch.push_heatmap(&heatmap);
+    assert_eq!(batch.get_line_count(), 12);
+}
+
+#[test]
+fn empty_overlays() {
+    let mut overlays = DebugOverlays::new();
+    assert!(overlays.is_empty());
+
+    overlays.heatmap = Some(SectionHeatmap { values: Vec::new(), min_value: 0f32, max_value: 1f32, alpha: 255 });
+    assert!(overlays.is_empty());
+
+    let mut format = make_format();
+    assert!(DebugOverlayBatch::supports_format(&format));
+    format.color = None;
+    assert!(!DebugOverlayBatch::supports_format(&format));
+}
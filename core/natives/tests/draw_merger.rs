@@ -0,0 +1,101 @@
+use ash::vk;
+use bytemuck::cast_slice;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::MeshData;
+use b4d_core::renderer::emulator::draw_merger::{DrawMerger, MergeKey};
+use b4d_core::renderer::emulator::mc_shaders::{ShaderId, VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+fn make_format() -> VertexFormat {
+    VertexFormat {
+        stride: 12,
+        position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+        normal: None,
+        color: None,
+        uv0: None,
+        uv1: None,
+        uv2: None,
+        position_quantization: None,
+        normal_encoding: NormalEncoding::Direct,
+    }
+}
+
+fn make_key(shader: ShaderId) -> MergeKey {
+    MergeKey {
+        shader,
+        depth_write_enable: true,
+        viewport_index: 0,
+        vertex_stride: 12,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    }
+}
+
+const TRIANGLE: [f32; 9] = [0f32, 0f32, 0f32, 1f32, 0f32, 0f32, 0f32, 1f32, 0f32];
+const INDICES: [u16; 3] = [0, 1, 2];
+
+fn make_triangle() -> MeshData<'static> {
+    MeshData {
+        vertex_data: cast_slice(&TRIANGLE),
+        index_data: cast_slice(&INDICES),
+        vertex_stride: 12,
+        index_count: 3,
+        index_type: vk::IndexType::UINT16,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    }
+}
+
+#[test]
+fn merge_consecutive() {
+    let format = make_format();
+    let key = make_key(ShaderId::new());
+    let mut merger = DrawMerger::new();
+
+    let transform = Mat4f32::new_translation(&Vec3f32::new(2f32, 0f32, 0f32));
+    assert!(merger.push(key, &make_triangle(), &format, None).unwrap().is_none());
+    assert!(merger.push(key, &make_triangle(), &format, Some(&transform)).unwrap().is_none());
+
+    let merged = merger.flush().unwrap();
+    assert!(merger.is_empty());
+    assert_eq!(merged.draw_count, 2);
+    assert_eq!(merged.index_count, 6);
+
+    let indices: Vec<u32> = merged.index_data.chunks_exact(4).map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]])).collect();
+    assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+
+    let vertices: Vec<f32> = merged.vertex_data.chunks_exact(4).map(|v| f32::from_ne_bytes([v[0], v[1], v[2], v[3]])).collect();
+    assert_eq!(&vertices[0..9], &TRIANGLE);
+    assert_eq!(&vertices[9..12], &[2f32, 0f32, 0f32]);
+    assert_eq!(&vertices[12..15], &[3f32, 0f32, 0f32]);
+}
+
+#[test]
+fn flush_on_key_change() {
+    let format = make_format();
+    let key0 = make_key(ShaderId::new());
+    let key1 = make_key(ShaderId::new());
+    let mut merger = DrawMerger::new();
+
+    assert!(merger.push(key0, &make_triangle(), &format, None).unwrap().is_none());
+    let flushed = merger.push(key1, &make_triangle(), &format, None).unwrap().unwrap();
+    assert_eq!(flushed.key, key0);
+    assert_eq!(flushed.draw_count, 1);
+
+    assert_eq!(merger.flush().unwrap().key, key1);
+}
+
+#[test]
+fn reject_invalid() {
+    let format = make_format();
+    let mut merger = DrawMerger::new();
+
+    let indices = [0u16, 1, 3];
+    let mut data = make_triangle();
+    data.index_data = cast_slice(&indices);
+    assert!(merger.push(make_key(ShaderId::new()), &data, &format, None).is_err());
+    assert!(merger.is_empty());
+
+    let mut data = make_triangle();
+    data.primitive_topology = vk::PrimitiveTopology::TRIANGLE_STRIP;
+    assert!(!DrawMerger::can_merge(&data, &format, false));
+}
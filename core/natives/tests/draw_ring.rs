@@ -0,0 +1,162 @@
+use b4d_core::renderer::emulator::draw_ring::{DrawRing, RingCommand, RingError, HEADER_SIZE, MIN_DATA_SIZE, OP_DRAW_GLOBAL, OP_DRAW_IMMEDIATE, OP_SET_DRAW_PRIORITY, OP_SET_DRAW_TAG, OP_WRAP};
+
+/// Writes records into the ring the same way the java side does.
+struct TestHost {
+    memory: Vec<u64>,
+    write: usize,
+}
+
+impl TestHost {
+    fn new() -> (Self, DrawRing) {
+        let mut memory = vec![0u64; (HEADER_SIZE + MIN_DATA_SIZE) / 8];
+        let ring = unsafe { DrawRing::new(memory.as_mut_ptr() as *mut u8, HEADER_SIZE + MIN_DATA_SIZE) }.unwrap();
+        (Self { memory, write: 0 }, ring)
+    }
+
+    fn write_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        unsafe {
+            let ptr = (self.memory.as_mut_ptr() as *mut u8).add(offset);
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+        }
+    }
+
+    fn push(&mut self, opcode: u32, payload: &[u8]) {
+        let length = (8 + payload.len() + 7) & !7;
+        if self.write + length > MIN_DATA_SIZE {
+            self.write_bytes(HEADER_SIZE + self.write, &OP_WRAP.to_le_bytes());
+            self.write = 0;
+        }
+
+        let mut record = Vec::with_capacity(length);
+        record.extend_from_slice(&opcode.to_le_bytes());
+        record.extend_from_slice(&(length as u32).to_le_bytes());
+        record.extend_from_slice(payload);
+        record.resize(length, 0);
+        self.write_bytes(HEADER_SIZE + self.write, &record);
+
+        self.write = (self.write + length) % MIN_DATA_SIZE;
+        self.write_bytes(0, &(self.write as u32).to_le_bytes());
+    }
+}
+
+fn draw_global(mesh: u64, shader: u64) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&mesh.to_le_bytes());
+    payload.extend_from_slice(&shader.to_le_bytes());
+    payload.extend_from_slice(&1u32.to_le_bytes());
+    payload.extend_from_slice(&0u32.to_le_bytes());
+    payload
+}
+
+fn collect_meshes(ring: &DrawRing) -> Result<Vec<u64>, RingError> {
+    let mut meshes = Vec::new();
+    ring.consume(|command| {
+        if let RingCommand::DrawGlobal { mesh, depth_write_enable, .. } = command {
+            assert!(depth_write_enable);
+            meshes.push(mesh);
+        }
+    })?;
+    Ok(meshes)
+}
+
+#[test]
+fn consume_in_order() {
+    let (mut host, ring) = TestHost::new();
+    assert!(ring.is_empty());
+
+    host.push(OP_DRAW_GLOBAL, &draw_global(1, 10));
+    host.push(OP_SET_DRAW_PRIORITY, &[7, 0, 0, 0, 0, 0, 0, 0]);
+    host.push(OP_DRAW_GLOBAL, &draw_global(2, 10));
+
+    let mut commands = Vec::new();
+    let count = ring.consume(|command| {
+        commands.push(match command {
+            RingCommand::DrawGlobal { mesh, .. } => mesh,
+            RingCommand::SetDrawPriority(priority) => 100 + priority as u64,
+            _ => panic!(),
+        });
+    }).unwrap();
+    assert_eq!(count, 3);
+    assert_eq!(commands, vec![1, 107, 2]);
+
+    // Consumed records are not returned again
+    assert!(ring.is_empty());
+    assert_eq!(collect_meshes(&ring).unwrap(), Vec::<u64>::new());
+}
+
+#[test]
+fn wraps_around() {
+    let (mut host, ring) = TestHost::new();
+
+    // Each draw record is 32 bytes so the ring wraps after 8 records
+    for frame in 0..4u64 {
+        for i in 0..5u64 {
+            host.push(OP_DRAW_GLOBAL, &draw_global(frame * 10 + i, 0));
+        }
+        let expected: Vec<u64> = (0..5u64).map(|i| frame * 10 + i).collect();
+        assert_eq!(collect_meshes(&ring).unwrap(), expected);
+    }
+}
+
+#[test]
+fn immediate_data() {
+    let (mut host, ring) = TestHost::new();
+
+    let vertex_data = [1u8; 48];
+    let index_data = [0u16, 1, 2, 2, 3, 0];
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&5u64.to_le_bytes());
+    payload.extend_from_slice(&0u32.to_le_bytes());
+    payload.extend_from_slice(&12u32.to_le_bytes());
+    payload.extend_from_slice(&6u32.to_le_bytes());
+    payload.extend_from_slice(&0i32.to_le_bytes()); // VK_INDEX_TYPE_UINT16
+    payload.extend_from_slice(&3i32.to_le_bytes()); // VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST
+    payload.extend_from_slice(&(vertex_data.len() as u32).to_le_bytes());
+    payload.extend_from_slice(&12u32.to_le_bytes());
+    payload.extend_from_slice(&0u32.to_le_bytes());
+    payload.extend_from_slice(&vertex_data);
+    for index in index_data {
+        payload.extend_from_slice(&index.to_le_bytes());
+    }
+    host.push(OP_DRAW_IMMEDIATE, &payload);
+
+    let count = ring.consume(|command| {
+        match command {
+            RingCommand::DrawImmediate { data, depth_write_enable, .. } => {
+                assert!(!depth_write_enable);
+                assert_eq!(data.vertex_data, &vertex_data);
+                assert_eq!(data.index_data.len(), 12);
+                assert_eq!(data.index_count, 6);
+            }
+            _ => panic!(),
+        }
+    }).unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn rejects_invalid_records() {
+    let (mut host, ring) = TestHost::new();
+    host.push(99, &[0; 8]);
+    assert_eq!(collect_meshes(&ring), Err(RingError::InvalidOpcode(99)));
+
+    let (mut host, ring) = TestHost::new();
+    host.push(OP_SET_DRAW_TAG, &[0; 4]);
+    assert_eq!(collect_meshes(&ring), Err(RingError::Corrupt("record")));
+
+    let mut memory = vec![0u64; 8];
+    assert_eq!(unsafe { DrawRing::new(memory.as_mut_ptr() as *mut u8, 64) }.err(), Some(RingError::InvalidSize));
+}
+
+#[test]
+fn invalid_batch_dispatches_nothing() {
+    let (mut host, ring) = TestHost::new();
+    host.push(OP_DRAW_GLOBAL, &draw_global(1, 10));
+    host.push(OP_DRAW_GLOBAL, &draw_global(2, 10));
+    host.push(99, &[0; 8]);
+
+    let mut count = 0;
+    assert_eq!(ring.consume(|_| count += 1), Err(RingError::InvalidOpcode(99)));
+    assert_eq!(count, 0);
+    assert!(!ring.is_empty());
+}
@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use b4d_core::renderer::emulator::event_log::{serialize_event, RendererEvent};
+
+#[test]
+fn event_serialization() {
+    let line = serialize_event(&RendererEvent::FrameEnd { frame: 12, cpu_time: Duration::from_micros(1500) }, 3, Duration::from_millis(2));
+    assert!(!line.contains('\n'));
+
+    let value = json::parse(&line).unwrap();
+    assert_eq!(value["type"], "frame_end");
+    assert_eq!(value["seq"], 3);
+    assert_eq!(value["time_us"], 2000);
+    assert_eq!(value["frame"], 12);
+    assert_eq!(value["cpu_time_us"], 1500);
+
+    // Messages must be escaped so each event stays on a single line
+    let line = serialize_event(&RendererEvent::Error { message: "first\nsecond \"quoted\"".to_string() }, 0, Duration::ZERO);
+    assert!(!line.contains('\n'));
+    assert_eq!(json::parse(&line).unwrap()["message"], "first\nsecond \"quoted\"");
+
+    let value = json::parse(&serialize_event(&RendererEvent::SurfacePause, 1, Duration::ZERO)).unwrap();
+    assert_eq!(value["type"], "surface_pause");
+}
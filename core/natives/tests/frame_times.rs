@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use b4d_core::renderer::emulator::frame_times::{FrameTimeHistogram, HISTOGRAM_BUCKET_COUNT};
+
+#[test]
+fn percentiles() {
+    let mut histogram = FrameTimeHistogram::new(100);
+    for ms in 1..=100u64 {
+        histogram.push(Duration::from_millis(ms));
+    }
+
+    let summary = histogram.summarize().unwrap();
+    assert_eq!(summary.sample_count, 100);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p95, Duration::from_millis(95));
+    assert_eq!(summary.p99, Duration::from_millis(99));
+    assert_eq!(summary.max, Duration::from_millis(100));
+    // Everything above 100ms is a stutter
+    assert_eq!(summary.stutter_count, 0);
+}
+
+#[test]
+fn stutters_and_window() {
+    let mut histogram = FrameTimeHistogram::new(10);
+    assert!(histogram.summarize().is_none());
+
+    for _ in 0..8 {
+        histogram.push(Duration::from_millis(16));
+    }
+    histogram.push(Duration::from_millis(40));
+    histogram.push(Duration::from_millis(200));
+
+    let summary = histogram.summarize().unwrap();
+    assert_eq!(summary.stutter_count, 2);
+    assert_eq!(histogram.get_buckets()[16], 8);
+    assert_eq!(histogram.get_buckets()[HISTOGRAM_BUCKET_COUNT - 1], 1);
+
+    // Pushing more samples evicts the oldest ones
+    for _ in 0..10 {
+        histogram.push(Duration::from_millis(8));
+    }
+    let summary = histogram.summarize().unwrap();
+    assert_eq!(summary.sample_count, 10);
+    assert_eq!(summary.stutter_count, 0);
+    assert_eq!(histogram.get_buckets().iter().sum::<u32>(), 10);
+    assert_eq!(histogram.get_buckets()[8], 10);
+}
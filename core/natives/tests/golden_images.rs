@@ -0,0 +1,114 @@
+mod test_common;
+
+use ash::vk;
+use bytemuck::{cast_slice, Pod, Zeroable};
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::debug_pipeline::DebugPipelineMode;
+use b4d_core::renderer::emulator::mc_shaders::{McUniform, McUniformData, VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::MeshData;
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+use test_common::golden::{assert_golden, render_scene, Tolerance};
+use test_common::make_headless_renderer;
+
+const SIZE: Vec2u32 = Vec2u32::new(128, 128);
+
+#[test]
+fn empty_pass() {
+    let renderer = make_headless_renderer();
+
+    let image = render_scene(&renderer, DebugPipelineMode::Color, SIZE, |_| {});
+    assert_golden("empty_pass", &image, Tolerance::EXACT);
+}
+
+#[test]
+fn color_quad() {
+    let renderer = make_headless_renderer();
+    let shader = renderer.create_shader(&Vertex::make_b4d_vertex_format(), McUniform::MODEL_VIEW_MATRIX | McUniform::PROJECTION_MATRIX);
+
+    let vertices = make_quad(0f32);
+    let image = render_scene(&renderer, DebugPipelineMode::Color, SIZE, |recorder| {
+        recorder.update_uniform(&McUniformData::ProjectionMatrix(Mat4f32::identity()), shader);
+        recorder.update_uniform(&McUniformData::ModelViewMatrix(Mat4f32::identity()), shader);
+
+        let id = recorder.upload_immediate(&make_mesh_data(&vertices));
+        recorder.draw_immediate(id, shader, true);
+    });
+    assert_golden("color_quad", &image, Tolerance::default());
+
+    renderer.drop_shader(shader);
+}
+
+#[test]
+fn depth_overlap() {
+    let renderer = make_headless_renderer();
+    let shader = renderer.create_shader(&Vertex::make_b4d_vertex_format(), McUniform::MODEL_VIEW_MATRIX | McUniform::PROJECTION_MATRIX);
+
+    let near = make_quad(0.25f32);
+    let far = make_quad(0.75f32);
+    let image = render_scene(&renderer, DebugPipelineMode::Depth, SIZE, |recorder| {
+        recorder.update_uniform(&McUniformData::ProjectionMatrix(Mat4f32::identity()), shader);
+
+        // The far quad is drawn last and offset so it must only be visible where the near quad is not
+        recorder.update_uniform(&McUniformData::ModelViewMatrix(Mat4f32::identity()), shader);
+        let id = recorder.upload_immediate(&make_mesh_data(&near));
+        recorder.draw_immediate(id, shader, true);
+
+        recorder.update_uniform(&McUniformData::ModelViewMatrix(Mat4f32::new_translation(&Vec3f32::new(0.5f32, 0.5f32, 0f32))), shader);
+        let id = recorder.upload_immediate(&make_mesh_data(&far));
+        recorder.draw_immediate(id, shader, true);
+    });
+    assert_golden("depth_overlap", &image, Tolerance::default());
+
+    renderer.drop_shader(shader);
+}
+
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
+
+fn make_quad(depth: f32) -> [Vertex; 4] {
+    [
+        Vertex { position: Vec3f32::new(-0.5f32, -0.5f32, depth), color: Vec4f32::new(1f32, 0f32, 0f32, 1f32) },
+        Vertex { position: Vec3f32::new(0.5f32, -0.5f32, depth), color: Vec4f32::new(0f32, 1f32, 0f32, 1f32) },
+        Vertex { position: Vec3f32::new(-0.5f32, 0.5f32, depth), color: Vec4f32::new(0f32, 0f32, 1f32, 1f32) },
+        Vertex { position: Vec3f32::new(0.5f32, 0.5f32, depth), color: Vec4f32::new(1f32, 1f32, 1f32, 1f32) },
+    ]
+}
+
+fn make_mesh_data(vertices: &[Vertex]) -> MeshData {
+    MeshData {
+        vertex_data: cast_slice(vertices),
+        index_data: cast_slice(&QUAD_INDICES),
+        vertex_stride: std::mem::size_of::<Vertex>() as u32,
+        index_count: QUAD_INDICES.len() as u32,
+        index_type: vk::IndexType::UINT32,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Vertex {
+    #[allow(unused)]
+    position: Vec3f32,
+    #[allow(unused)]
+    color: Vec4f32,
+}
+
+impl Vertex {
+    fn make_b4d_vertex_format() -> VertexFormat {
+        VertexFormat {
+            stride: std::mem::size_of::<Vertex>() as u32,
+            position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+            normal: None,
+            color: Some(VertexFormatEntry { offset: std::mem::size_of::<Vec3f32>() as u32, format: vk::Format::R32G32B32A32_SFLOAT }),
+            uv0: None,
+            uv1: None,
+            uv2: None,
+            position_quantization: None,
+            normal_encoding: NormalEncoding::Direct,
+        }
+    }
+}
+
+unsafe impl Zeroable for Vertex {}
+unsafe impl Pod for Vertex {}
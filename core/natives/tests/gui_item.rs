@@ -0,0 +1,37 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::FrameSize;
+use b4d_core::renderer::emulator::gui_item::{gui_depth_slice, make_gui_item_projection, GuiItemPlacement};
+
+#[test]
+fn item_viewport() {
+    let placement = GuiItemPlacement {
+        position: Vec2f32::new(10f32, 20f32),
+        size: 16f32,
+        depth_range: (0.25f32, 0.5f32),
+    };
+    let frame_size = FrameSize::from_physical(Vec2u32::new(1920, 1080), 2f32);
+
+    let viewport = placement.make_viewport(&frame_size);
+    assert_eq!((viewport.x, viewport.y, viewport.width, viewport.height), (20f32, 40f32, 32f32, 32f32));
+    assert_eq!((viewport.min_depth, viewport.max_depth), (0.25f32, 0.5f32));
+}
+
+#[test]
+fn depth_slices() {
+    assert_eq!(gui_depth_slice(0, 4), (0.75f32, 1f32));
+    assert_eq!(gui_depth_slice(3, 4), (0f32, 0.25f32));
+    // Out of range layers use the front most slice
+    assert_eq!(gui_depth_slice(7, 4), gui_depth_slice(3, 4));
+}
+
+#[test]
+fn item_projection() {
+    let projection = make_gui_item_projection();
+
+    // The top left front corner of the unit cube maps to the top left of the viewport at depth 0
+    let corner = projection * Vec4f32::new(-0.5f32, 0.5f32, 0.5f32, 1f32);
+    assert_eq!(corner, Vec4f32::new(-1f32, -1f32, 0f32, 1f32));
+
+    let corner = projection * Vec4f32::new(0.5f32, -0.5f32, -0.5f32, 1f32);
+    assert_eq!(corner, Vec4f32::new(1f32, 1f32, 1f32, 1f32));
+}
@@ -0,0 +1,105 @@
+use ash::vk;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::gui_rect::{GuiRect, GuiRectBatch, NineSlice, TexturedRect};
+use b4d_core::renderer::emulator::mc_shaders::{VertexFormat, VertexFormatEntry};
+use b4d_core::renderer::emulator::quantization::NormalEncoding;
+
+/// The vanilla position tex color format.
+fn make_format() -> VertexFormat {
+    VertexFormat {
+        stride: 24,
+        position: VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT },
+        normal: None,
+        color: Some(VertexFormatEntry { offset: 20, format: vk::Format::R8G8B8A8_UNORM }),
+        uv0: Some(VertexFormatEntry { offset: 12, format: vk::Format::R32G32_SFLOAT }),
+        uv1: None,
+        uv2: None,
+        position_quantization: None,
+        normal_encoding: NormalEncoding::Direct,
+    }
+}
+
+fn rect(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> GuiRect {
+    GuiRect::new(Vec2f32::new(min_x, min_y), Vec2f32::new(max_x, max_y))
+}
+
+#[test]
+fn textured_rect() {
+    let mut batch = GuiRectBatch::new(&make_format(), 0.5f32);
+    batch.push_rect(&TexturedRect {
+        dst: rect(10f32, 20f32, 26f32, 36f32),
+        uv: rect(0f32, 0f32, 0.5f32, 0.25f32),
+        tint: [255, 128, 0, 255],
+    });
+    // Empty rects are skipped
+    batch.push_rect(&TexturedRect {
+        dst: rect(10f32, 20f32, 10f32, 36f32),
+        uv: rect(0f32, 0f32, 1f32, 1f32),
+        tint: [255; 4],
+    });
+
+    let mesh = batch.as_mesh_data();
+    assert_eq!(mesh.index_count, 6);
+    assert_eq!(mesh.vertex_data.len(), 4 * 24);
+    assert_eq!(mesh.index_type, vk::IndexType::UINT32);
+
+    let vertex = &mesh.vertex_data[(2 * 24)..(3 * 24)];
+    let floats: Vec<f32> = vertex[0..20].chunks_exact(4).map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]])).collect();
+    assert_eq!(floats, vec![26f32, 36f32, 0.5f32, 0.5f32, 0.25f32]);
+    assert_eq!(&vertex[20..24], &[255, 128, 0, 255]);
+}
+
+#[test]
+fn nine_slice() {
+    let panel = NineSlice {
+        dst: rect(0f32, 0f32, 100f32, 50f32),
+        uv: rect(0f32, 0f32, 1f32, 1f32),
+        border: [4f32, 4f32, 4f32, 4f32],
+        uv_border: [0.25f32, 0.25f32, 0.25f32, 0.25f32],
+        tint: [255; 4],
+    };
+
+    let mut rects = Vec::new();
+    panel.for_each_rect(|rect| rects.push(rect));
+    assert_eq!(rects.len(), 9);
+    assert_eq!(rects[0].dst, rect(0f32, 0f32, 4f32, 4f32));
+    assert_eq!(rects[4].dst, rect(4f32, 4f32, 96f32, 46f32));
+    assert_eq!(rects[4].uv, rect(0.25f32, 0.25f32, 0.75f32, 0.75f32));
+    assert_eq!(rects[8].dst, rect(96f32, 46f32, 100f32, 50f32));
+
+    let mut batch = GuiRectBatch::new(&make_format(), 0f32);
+    batch.push_nine_slice(&panel);
+    assert_eq!(batch.as_mesh_data().index_count, 9 * 6);
+}
+
+#[test]
+fn nine_slice_shrinks_borders() {
+    let panel = NineSlice {
+        dst: rect(0f32, 0f32, 4f32, 20f32),
+        uv: rect(0f32, 0f32, 1f32, 1f32),
+        border: [4f32, 0f32, 4f32, 0f32],
+        uv_border: [0.25f32, 0f32, 0.25f32, 0f32],
+        tint: [255; 4],
+    };
+
+    let mut rects = Vec::new();
+    panel.for_each_rect(|rect| rects.push(rect));
+    // The borders are halved and the center and top and bottom rows are empty
+    assert_eq!(rects.len(), 2);
+    assert_eq!(rects[0].dst, rect(0f32, 0f32, 2f32, 20f32));
+    assert_eq!(rects[1].dst, rect(2f32, 0f32, 4f32, 20f32));
+}
+
+#[test]
+fn unsupported_format() {
+    let mut format = make_format();
+    format.uv0 = None;
+    assert!(!GuiRectBatch::supports_format(&format));
+
+    let mut format = make_format();
+    format.color = Some(VertexFormatEntry { offset: 20, format: vk::Format::R32_SFLOAT });
+    assert!(!GuiRectBatch::supports_format(&format));
+
+    assert!(GuiRectBatch::supports_format(&make_format()));
+}
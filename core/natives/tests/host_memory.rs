@@ -0,0 +1,42 @@
+use ash::vk;
+
+use b4d_core::instance::host_memory::{HostMemoryScope, HostMemoryTracker};
+
+#[test]
+fn allocation_tracking() {
+    let tracker = HostMemoryTracker::new();
+    let callbacks = tracker.get_allocation_callbacks();
+    let alloc = callbacks.pfn_allocation.unwrap();
+    let realloc = callbacks.pfn_reallocation.unwrap();
+    let free = callbacks.pfn_free.unwrap();
+
+    unsafe {
+        let memory = alloc(callbacks.p_user_data, 100, 64, vk::SystemAllocationScope::OBJECT);
+        assert!(!memory.is_null());
+        assert_eq!(memory as usize % 64, 0);
+        std::ptr::write_bytes(memory as *mut u8, 7, 100);
+
+        let cache = alloc(callbacks.p_user_data, 30, 8, vk::SystemAllocationScope::CACHE);
+        let report = tracker.get_report();
+        assert_eq!(report.get(HostMemoryScope::Object).current_bytes, 100);
+        assert_eq!(report.get(HostMemoryScope::Cache).live_allocations, 1);
+        assert_eq!(report.total_bytes(), 130);
+
+        // Reallocation keeps the contents and the original scope
+        let memory = realloc(callbacks.p_user_data, memory, 200, 64, vk::SystemAllocationScope::COMMAND);
+        assert_eq!(*(memory as *const u8).add(99), 7);
+        let report = tracker.get_report();
+        assert_eq!(report.get(HostMemoryScope::Object).current_bytes, 200);
+        assert_eq!(report.get(HostMemoryScope::Object).peak_bytes, 300);
+        assert_eq!(report.get(HostMemoryScope::Command).total_allocations, 0);
+
+        free(callbacks.p_user_data, memory);
+        free(callbacks.p_user_data, cache);
+        free(callbacks.p_user_data, std::ptr::null_mut());
+    }
+
+    let report = tracker.get_report();
+    assert_eq!(report.total_bytes(), 0);
+    assert_eq!(report.get(HostMemoryScope::Object).live_allocations, 0);
+    assert_eq!(report.get(HostMemoryScope::Object).total_allocations, 2);
+}
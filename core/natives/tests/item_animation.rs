@@ -0,0 +1,36 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::item_animation::ItemAnimation;
+use b4d_core::renderer::emulator::mc_shaders::McUniformData;
+
+fn assert_close(a: Vec4f32, b: Vec4f32) {
+    assert!((a - b).norm() < 1e-4f32, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn bob_range() {
+    let animation = ItemAnimation::new(1.5f32);
+    for tick in 0..200 {
+        let bob = animation.get_bob(tick as f32);
+        assert!(bob >= 0f32 && bob <= 2f32 * ItemAnimation::BOB_HEIGHT + 1e-6f32);
+    }
+}
+
+#[test]
+fn model_matrix() {
+    let animation = ItemAnimation { phase: 0f32, spin_rate: std::f32::consts::FRAC_PI_2 };
+    let matrix = animation.get_model_matrix(0f32);
+    assert_close(matrix * Vec4f32::new(1f32, 0f32, 0f32, 1f32), Vec4f32::new(1f32, ItemAnimation::BOB_HEIGHT, 0f32, 1f32));
+
+    let matrix = animation.get_model_matrix(1f32);
+    let bob = animation.get_bob(1f32);
+    assert_close(matrix * Vec4f32::new(1f32, 0f32, 0f32, 1f32), Vec4f32::new(0f32, bob, -1f32, 1f32));
+    assert_close(matrix * Vec4f32::new(0f32, 1f32, 0f32, 1f32), Vec4f32::new(0f32, 1f32 + bob, 0f32, 1f32));
+}
+
+#[test]
+fn uniform_data() {
+    match ItemAnimation::new(0.25f32).as_uniform_data() {
+        McUniformData::ItemAnimation(data) => assert_eq!(data, Vec2f32::new(0.25f32, ItemAnimation::VANILLA_SPIN_RATE)),
+        other => panic!("Unexpected uniform data {:?}", other),
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use b4d_core::renderer::emulator::jobs::{Job, JobExecutor, JobKind, JobSystem};
+
+/// Runs all jobs inline on the calling thread.
+struct InlineExecutor {
+    executed: AtomicUsize,
+}
+
+impl JobExecutor for InlineExecutor {
+    fn execute(&self, job: Job) {
+        self.executed.fetch_add(1, Ordering::SeqCst);
+        job();
+    }
+
+    fn get_parallelism(&self) -> usize {
+        3
+    }
+}
+
+#[test]
+fn map_keeps_order() {
+    let jobs = JobSystem::new(4);
+    let items: Vec<u32> = (0..1000).collect();
+
+    let results = jobs.map(JobKind::Culling, &items, 16, |item| item * 2);
+    assert_eq!(results, items.iter().map(|item| item * 2).collect::<Vec<_>>());
+
+    let stats = *jobs.get_stats().get(JobKind::Culling);
+    assert_eq!(stats.batch_count, 1);
+    assert_eq!(stats.task_count, 5);
+    assert_eq!(jobs.get_stats().get(JobKind::Sorting).batch_count, 0);
+
+    jobs.reset_stats();
+    assert_eq!(jobs.get_stats().get(JobKind::Culling).batch_count, 0);
+}
+
+#[test]
+fn small_batches_run_inline() {
+    let executor = Arc::new(InlineExecutor { executed: AtomicUsize::new(0) });
+    let jobs = JobSystem::with_executor(executor.clone());
+
+    let mut items = vec![1u32; 7];
+    jobs.for_each_mut(JobKind::MeshOptimization, &mut items, 8, |item| *item += 1);
+    assert_eq!(items, vec![2u32; 7]);
+    assert_eq!(executor.executed.load(Ordering::SeqCst), 0);
+
+    let mut items = vec![1u32; 64];
+    jobs.for_each_mut(JobKind::MeshOptimization, &mut items, 8, |item| *item += 1);
+    assert_eq!(items, vec![2u32; 64]);
+    assert_eq!(executor.executed.load(Ordering::SeqCst), 3);
+    assert_eq!(jobs.get_stats().get(JobKind::MeshOptimization).task_count, 1 + 4);
+}
+
+#[test]
+fn nested_batches() {
+    let jobs = JobSystem::new(2);
+    let outer: Vec<u32> = (0..8).collect();
+
+    let sums = jobs.map(JobKind::Culling, &outer, 1, |base| {
+        let inner: Vec<u32> = (0..100).map(|i| base + i).collect();
+        jobs.map(JobKind::Sorting, &inner, 10, |value| *value).iter().sum::<u32>()
+    });
+    assert_eq!(sums, outer.iter().map(|base| base * 100 + 4950).collect::<Vec<_>>());
+}
+
+#[test]
+fn parallel_sort_is_stable() {
+    let jobs = JobSystem::new(3);
+    let mut items: Vec<(u32, usize)> = (0..1001).map(|i| (((i * 7919) % 13) as u32, i)).collect();
+    let mut expected = items.clone();
+    expected.sort_by_key(|(key, _)| *key);
+
+    jobs.sort_by_key(&mut items, 32, |(key, _)| *key);
+    assert_eq!(items, expected);
+}
+
+#[test]
+fn scope_borrows_from_caller() {
+    let jobs = JobSystem::new(2);
+    let mut results = vec![0u32; 16];
+
+    jobs.scope(|scope| {
+        for (index, result) in results.iter_mut().enumerate() {
+            scope.spawn(move || *result = index as u32 * 3);
+        }
+    });
+    assert_eq!(results, (0..16).map(|index| index * 3).collect::<Vec<_>>());
+}
+
+#[test]
+#[should_panic]
+fn task_panic_is_propagated() {
+    let jobs = JobSystem::new(2);
+    let items: Vec<u32> = (0..64).collect();
+    jobs.map(JobKind::Culling, &items, 1, |item| {
+        if *item == 40 {
+            panic!("test panic");
+        }
+        *item
+    });
+}
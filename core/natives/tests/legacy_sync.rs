@@ -0,0 +1,19 @@
+use ash::vk;
+
+use b4d_core::device::legacy_sync::{convert_access_mask, convert_stage_mask};
+
+#[test]
+fn stage_masks() {
+    assert_eq!(convert_stage_mask(vk::PipelineStageFlags2::FRAGMENT_SHADER | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS),
+        vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS);
+    assert_eq!(convert_stage_mask(vk::PipelineStageFlags2::COPY | vk::PipelineStageFlags2::BLIT), vk::PipelineStageFlags::TRANSFER);
+    assert_eq!(convert_stage_mask(vk::PipelineStageFlags2::INDEX_INPUT), vk::PipelineStageFlags::VERTEX_INPUT);
+    assert!(convert_stage_mask(vk::PipelineStageFlags2::NONE).is_empty());
+}
+
+#[test]
+fn access_masks() {
+    assert_eq!(convert_access_mask(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE), vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+    assert_eq!(convert_access_mask(vk::AccessFlags2::SHADER_SAMPLED_READ | vk::AccessFlags2::SHADER_STORAGE_READ), vk::AccessFlags::SHADER_READ);
+    assert_eq!(convert_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE), vk::AccessFlags::SHADER_WRITE);
+}
@@ -0,0 +1,31 @@
+use log::{Level, LevelFilter, Metadata};
+
+use b4d_core::util::log_filter::{self, LogSubsystem};
+
+#[test]
+fn subsystem_targets() {
+    assert_eq!(LogSubsystem::from_target("b4d_core::allocator::vma"), Some(LogSubsystem::Allocator));
+    assert_eq!(LogSubsystem::from_target("b4d_core::objects::object_set"), Some(LogSubsystem::ObjectManager));
+    assert_eq!(LogSubsystem::from_target("b4d_core::objects::swapchain_object_set"), Some(LogSubsystem::Swapchain));
+    assert_eq!(LogSubsystem::from_target("b4d_core::renderer::emulator::worker"), Some(LogSubsystem::Emulator));
+    assert_eq!(LogSubsystem::from_target("b4d_core::c_api"), Some(LogSubsystem::CApi));
+    assert_eq!(LogSubsystem::from_target("b4d_core::allocatorx"), None);
+    assert_eq!(LogSubsystem::from_target("b4d_core::b4d"), None);
+}
+
+#[test]
+fn subsystem_levels() {
+    let metadata = |target, level| Metadata::builder().target(target).level(level).build();
+
+    log_filter::set_level(LogSubsystem::Allocator, Some(LevelFilter::Trace));
+    log_filter::set_level(LogSubsystem::Emulator, Some(LevelFilter::Off));
+
+    assert!(log_filter::is_enabled(&metadata("b4d_core::allocator", Level::Trace)));
+    assert!(!log_filter::is_enabled(&metadata("b4d_core::renderer::emulator", Level::Error)));
+    assert!(!log_filter::is_enabled(&metadata("b4d_core::b4d", Level::Debug)));
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+
+    log_filter::set_level(LogSubsystem::Allocator, None);
+    assert_eq!(log_filter::get_level(LogSubsystem::Allocator), log_filter::get_default_level());
+    assert!(!log_filter::is_enabled(&metadata("b4d_core::allocator", Level::Trace)));
+}
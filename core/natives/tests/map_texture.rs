@@ -0,0 +1,59 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::map_texture::{MapSlot, MapSlots, MAPS_PER_PAGE, MAP_SIZE, PAGE_GRID};
+
+#[test]
+fn slot_layout() {
+    let slot = MapSlot { page: 0, tile: PAGE_GRID + 2 };
+    assert_eq!(slot.get_texel_offset(), Vec2u32::new(2 * MAP_SIZE, MAP_SIZE));
+
+    let (min, max) = slot.get_uv_rect();
+    let size = 1f32 / (PAGE_GRID as f32);
+    assert_eq!(min, Vec2f32::new(2f32 * size, size));
+    assert_eq!(max, Vec2f32::new(3f32 * size, 2f32 * size));
+}
+
+#[test]
+fn allocation_adds_pages() {
+    let mut slots = MapSlots::new();
+    assert_eq!(slots.get_or_allocate(7), MapSlot { page: 0, tile: 0 });
+    assert_eq!(slots.get_or_allocate(-3), MapSlot { page: 0, tile: 1 });
+    assert_eq!(slots.get_or_allocate(7), MapSlot { page: 0, tile: 0 });
+
+    for id in 100..(100 + MAPS_PER_PAGE as i32) {
+        slots.get_or_allocate(id);
+    }
+    assert_eq!(slots.get_page_count(), 2);
+    assert_eq!(slots.get_map_count(), (MAPS_PER_PAGE + 2) as usize);
+}
+
+#[test]
+fn removed_slots_are_reused() {
+    let mut slots = MapSlots::new();
+    slots.get_or_allocate(1);
+    let slot = slots.get_or_allocate(2);
+    slots.get_or_allocate(3);
+
+    assert_eq!(slots.remove(2), Some(slot));
+    assert_eq!(slots.remove(2), None);
+    assert_eq!(slots.get(2), None);
+    assert_eq!(slots.get_or_allocate(4), slot);
+    assert_eq!(slots.get_page_count(), 1);
+}
+
+#[test]
+fn idle_maps_are_evicted() {
+    let mut slots = MapSlots::new();
+    slots.get_or_allocate(1);
+    slots.get_or_allocate(2);
+
+    for _ in 0..3 {
+        slots.next_flush();
+        slots.get(2);
+    }
+    assert_eq!(slots.evict_idle(3), Vec::<i32>::new());
+
+    slots.next_flush();
+    assert_eq!(slots.evict_idle(3), vec![1]);
+    assert_eq!(slots.get_map_count(), 1);
+    assert!(slots.get(2).is_some());
+}
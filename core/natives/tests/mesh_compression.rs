@@ -0,0 +1,67 @@
+use b4d_core::renderer::emulator::jobs::JobSystem;
+use b4d_core::renderer::emulator::mesh_compression::{decompress_mesh, decompress_meshes, CompressedMesh, MeshCompression, MeshDecompressError};
+
+fn make_data(seed: u8, len: usize) -> Vec<u8> {
+    (0..len).map(|i| ((i / 7) as u8).wrapping_add(seed)).collect()
+}
+
+#[test]
+fn lz4_round_trip() {
+    let vertex_data = make_data(3, 4096);
+    let index_data = make_data(9, 600);
+    let vertex_compressed = lz4_flex::block::compress(&vertex_data);
+    let index_compressed = lz4_flex::block::compress(&index_data);
+
+    let decompressed = decompress_mesh(&CompressedMesh {
+        vertex_data: &vertex_compressed,
+        index_data: &index_compressed,
+        vertex_data_len: vertex_data.len(),
+        index_data_len: index_data.len(),
+        compression: MeshCompression::Lz4,
+    }).unwrap();
+    assert_eq!(decompressed.vertex_data, vertex_data);
+    assert_eq!(decompressed.index_data, index_data);
+}
+
+#[test]
+fn invalid_data() {
+    let vertex_data = make_data(0, 1024);
+    let vertex_compressed = lz4_flex::block::compress(&vertex_data);
+
+    let mismatch = decompress_mesh(&CompressedMesh {
+        vertex_data: &vertex_compressed,
+        index_data: &[],
+        vertex_data_len: vertex_data.len() + 16,
+        index_data_len: 0,
+        compression: MeshCompression::Lz4,
+    });
+    assert_eq!(mismatch.err(), Some(MeshDecompressError::SizeMismatch("vertex_data")));
+
+    let corrupt = decompress_mesh(&CompressedMesh {
+        vertex_data: &vertex_compressed,
+        index_data: &[0xFF, 0xFF, 0xFF],
+        vertex_data_len: vertex_data.len(),
+        index_data_len: 64,
+        compression: MeshCompression::Lz4,
+    });
+    assert_eq!(corrupt.err(), Some(MeshDecompressError::Corrupt("index_data")));
+}
+
+#[test]
+fn batch_keeps_order() {
+    let datas: Vec<_> = (0..32u8).map(|i| make_data(i, 256 + i as usize)).collect();
+    let compressed: Vec<_> = datas.iter().map(|data| lz4_flex::block::compress(data)).collect();
+    let meshes: Vec<_> = datas.iter().zip(compressed.iter()).map(|(data, compressed)| CompressedMesh {
+        vertex_data: compressed,
+        index_data: &[],
+        vertex_data_len: data.len(),
+        index_data_len: 0,
+        compression: MeshCompression::Lz4,
+    }).collect();
+
+    let results = decompress_meshes(&JobSystem::new(3), &meshes);
+    assert_eq!(results.len(), datas.len());
+    for (result, data) in results.into_iter().zip(datas.iter()) {
+        assert_eq!(&result.unwrap().vertex_data, data);
+    }
+}
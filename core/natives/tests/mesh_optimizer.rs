@@ -0,0 +1,102 @@
+use ash::vk;
+use bytemuck::cast_slice;
+use rand::Rng;
+
+use b4d_core::renderer::emulator::MeshData;
+use b4d_core::renderer::emulator::mc_shaders::VertexFormatEntry;
+use b4d_core::renderer::emulator::mesh_optimizer::optimize_mesh;
+
+/// Generates a grid of quads with shuffled triangles. Each vertex is 3 floats of position followed
+/// by a unique u32 id so that triangles can be compared after the vertices have been reordered.
+fn make_grid(size: u32) -> (Vec<u8>, Vec<u32>) {
+    let mut vertices = Vec::new();
+    for y in 0..=size {
+        for x in 0..=size {
+            vertices.extend_from_slice(&(x as f32).to_ne_bytes());
+            vertices.extend_from_slice(&(y as f32).to_ne_bytes());
+            vertices.extend_from_slice(&((x * y) as f32 * 0.01).to_ne_bytes());
+            vertices.extend_from_slice(&(y * (size + 1) + x).to_ne_bytes());
+        }
+    }
+
+    let mut triangles = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let i = y * (size + 1) + x;
+            triangles.push([i, i + 1, i + size + 2]);
+            triangles.push([i, i + size + 2, i + size + 1]);
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+    for i in (1..triangles.len()).rev() {
+        triangles.swap(i, rng.gen_range(0..=i));
+    }
+
+    (vertices, triangles.into_iter().flatten().collect())
+}
+
+/// Returns all triangles as vertex ids rotated so that the smallest id is first. This preserves
+/// the winding order.
+fn collect_triangles(vertex_data: &[u8], indices: &[u32]) -> Vec<[u32; 3]> {
+    let id = |index: u32| {
+        let base = (index as usize * 16) + 12;
+        u32::from_ne_bytes(vertex_data[base..(base + 4)].try_into().unwrap())
+    };
+
+    let mut triangles: Vec<[u32; 3]> = indices.chunks_exact(3).map(|t| {
+        let ids = [id(t[0]), id(t[1]), id(t[2])];
+        let min = (0..3).min_by_key(|i| ids[*i]).unwrap();
+        [ids[min], ids[(min + 1) % 3], ids[(min + 2) % 3]]
+    }).collect();
+    triangles.sort();
+    triangles
+}
+
+#[test]
+fn optimize_preserves_triangles() {
+    let (vertices, indices) = make_grid(32);
+    let data = MeshData {
+        vertex_data: &vertices,
+        index_data: cast_slice(&indices),
+        vertex_stride: 16,
+        index_count: indices.len() as u32,
+        index_type: vk::IndexType::UINT32,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+    };
+    let position = VertexFormatEntry { offset: 0, format: vk::Format::R32G32B32_SFLOAT };
+
+    let optimized = optimize_mesh(&data, Some(&position)).unwrap();
+    assert_eq!(optimized.index_count, data.index_count);
+    assert_eq!(optimized.vertex_data.len(), vertices.len());
+
+    let optimized_indices: &[u32] = cast_slice(&optimized.index_data);
+    assert_eq!(collect_triangles(&vertices, &indices), collect_triangles(&optimized.vertex_data, optimized_indices));
+
+    // Vertices must be ordered by first use
+    let mut next = 0;
+    for index in optimized_indices {
+        assert!(*index <= next);
+        if *index == next {
+            next += 1;
+        }
+    }
+}
+
+#[test]
+fn optimize_rejects_invalid_meshes() {
+    let (vertices, indices) = make_grid(4);
+    let mut data = MeshData {
+        vertex_data: &vertices,
+        index_data: cast_slice(&indices),
+        vertex_stride: 16,
+        index_count: indices.len() as u32,
+        index_type: vk::IndexType::UINT32,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_STRIP,
+    };
+    assert!(optimize_mesh(&data, None).is_none());
+
+    data.primitive_topology = vk::PrimitiveTopology::TRIANGLE_LIST;
+    data.vertex_data = &vertices[0..64];
+    assert!(optimize_mesh(&data, None).is_none());
+}
@@ -0,0 +1,41 @@
+use ash::vk;
+
+use b4d_core::objects::alias::{combine_memory_requirements, AliasGroupId, AliasTracker, AliasingBarrier};
+use b4d_core::prelude::*;
+
+#[test]
+fn combined_requirements() {
+    let requirements = [
+        vk::MemoryRequirements { size: 1024, alignment: 256, memory_type_bits: 0b0111 },
+        vk::MemoryRequirements { size: 4096, alignment: 64, memory_type_bits: 0b1110 },
+    ];
+    let combined = combine_memory_requirements(&requirements).unwrap();
+    assert_eq!(combined.size, 4096);
+    assert_eq!(combined.alignment, 256);
+    assert_eq!(combined.memory_type_bits, 0b0110);
+
+    let incompatible = [
+        vk::MemoryRequirements { size: 16, alignment: 16, memory_type_bits: 0b01 },
+        vk::MemoryRequirements { size: 16, alignment: 16, memory_type_bits: 0b10 },
+    ];
+    assert!(combine_memory_requirements(&incompatible).is_none());
+}
+
+#[test]
+fn tracker_barriers() {
+    let group = AliasGroupId::new();
+    let depth = UUID::new();
+    let post = UUID::new();
+    let unaliased = UUID::new();
+
+    let mut tracker = AliasTracker::new();
+    tracker.add(group, depth);
+    tracker.add(group, post);
+
+    assert_eq!(tracker.activate(unaliased), None);
+    assert_eq!(tracker.activate(depth), Some(AliasingBarrier { group, previous: None, next: depth }));
+    assert_eq!(tracker.activate(depth), None);
+    assert_eq!(tracker.activate(post), Some(AliasingBarrier { group, previous: Some(depth), next: post }));
+    assert_eq!(tracker.get_active(group), Some(post));
+    assert_eq!(tracker.get_group(unaliased), None);
+}
@@ -0,0 +1,22 @@
+use ash::vk;
+
+use b4d_core::device::device_utils::OutputRotation;
+use b4d_core::prelude::*;
+
+#[test]
+fn rotation_from_transform() {
+    assert_eq!(OutputRotation::from_surface_transform(vk::SurfaceTransformFlagsKHR::IDENTITY), OutputRotation::None);
+    assert_eq!(OutputRotation::from_surface_transform(vk::SurfaceTransformFlagsKHR::INHERIT), OutputRotation::None);
+    assert_eq!(OutputRotation::from_surface_transform(vk::SurfaceTransformFlagsKHR::ROTATE_90), OutputRotation::Rotate90);
+    assert_eq!(OutputRotation::from_surface_transform(vk::SurfaceTransformFlagsKHR::ROTATE_180), OutputRotation::Rotate180);
+    assert_eq!(OutputRotation::from_surface_transform(vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_270), OutputRotation::Rotate270);
+}
+
+#[test]
+fn rotated_sizes() {
+    let size = Vec2u32::new(1920, 1080);
+    assert_eq!(OutputRotation::None.apply_to_size(size), size);
+    assert_eq!(OutputRotation::Rotate180.apply_to_size(size), size);
+    assert_eq!(OutputRotation::Rotate90.apply_to_size(size), Vec2u32::new(1080, 1920));
+    assert_eq!(OutputRotation::Rotate270.apply_to_size(OutputRotation::Rotate270.apply_to_size(size)), size);
+}
@@ -0,0 +1,29 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::panorama::stitch_equirectangular;
+
+/// Fills each face with a unique solid color so the face a pixel was sampled from can be identified.
+fn make_faces(size: u32) -> Vec<Box<[u8]>> {
+    (0..6u8).map(|face| {
+        std::iter::repeat([face * 40, 255 - face * 40, face, 255]).take((size * size) as usize).flatten().collect()
+    }).collect()
+}
+
+fn face_at(image: &[u8], size: Vec2u32, x: u32, y: u32) -> u8 {
+    image[((y * size[0] + x) * 4 + 2) as usize]
+}
+
+#[test]
+fn stitch_selects_faces() {
+    let faces = make_faces(8);
+    let size = Vec2u32::new(64, 32);
+    let image = stitch_equirectangular(&faces, 8, size);
+
+    // Center looks along negative z, a quarter to the right along positive x
+    assert_eq!(face_at(&image, size, 32, 16), 5);
+    assert_eq!(face_at(&image, size, 48, 16), 0);
+    assert_eq!(face_at(&image, size, 16, 16), 1);
+    assert_eq!(face_at(&image, size, 0, 16), 4);
+
+    assert_eq!(face_at(&image, size, 32, 0), 2);
+    assert_eq!(face_at(&image, size, 32, 31), 3);
+}
@@ -0,0 +1,118 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::color_grading::{compose_color_matrices, IDENTITY_COLOR_MATRIX};
+use b4d_core::renderer::emulator::post_chain::{AccessibilityFilter, ColorBlindness, PostChain, PostChainError, ScreenDistortion};
+
+const CREEPER: &str = r#"{
+    "targets": [ "swap" ],
+    "passes": [
+        {
+            "name": "color_convolve",
+            "intarget": "minecraft:main",
+            "outtarget": "swap",
+            "uniforms": [
+                { "name": "RedMatrix", "values": [ 0.0, 1.0, 0.0 ] },
+                { "name": "GreenMatrix", "values": [ 0.0, 1.0, 0.0 ] },
+                { "name": "BlueMatrix", "values": [ 0.0, 1.0, 0.0 ] },
+                { "name": "Offset", "values": [ 0.0, 0.1, 0.0 ] }
+            ]
+        },
+        {
+            "name": "blit",
+            "intarget": "swap",
+            "outtarget": "minecraft:main"
+        }
+    ]
+}"#;
+
+fn apply(matrix: &[[f32; 4]; 3], color: [f32; 3]) -> [f32; 3] {
+    let mut result = [0f32; 3];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = matrix[row][0] * color[0] + matrix[row][1] * color[1] + matrix[row][2] * color[2] + matrix[row][3];
+    }
+    result
+}
+
+fn assert_close(a: [f32; 3], b: [f32; 3]) {
+    for i in 0..3 {
+        assert!((a[i] - b[i]).abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn identity_composition() {
+    let matrix = [
+        [0.5f32, 0.1f32, 0.2f32, 0.3f32],
+        [0.0f32, 1.0f32, 0.0f32, 0.1f32],
+        [0.2f32, 0.2f32, 0.2f32, 0.0f32],
+    ];
+    assert_eq!(compose_color_matrices(&IDENTITY_COLOR_MATRIX, &matrix), matrix);
+    assert_eq!(compose_color_matrices(&matrix, &IDENTITY_COLOR_MATRIX), matrix);
+}
+
+#[test]
+fn creeper_chain() {
+    let chain = PostChain::parse(CREEPER).unwrap();
+    assert_eq!(chain.passes.len(), 2);
+
+    let matrix = chain.resolve_color_matrix().unwrap();
+    assert_close(apply(&matrix, [0.2f32, 0.4f32, 0.8f32]), [0.4f32, 0.5f32, 0.4f32]);
+}
+
+#[test]
+fn invert_chain() {
+    let chain = PostChain::parse(r#"{ "passes": [ { "name": "invert", "intarget": "minecraft:main", "outtarget": "minecraft:main" } ] }"#).unwrap();
+
+    let matrix = chain.resolve_color_matrix().unwrap();
+    assert_close(apply(&matrix, [0.2f32, 0.4f32, 1.0f32]), [0.8f32, 0.6f32, 0.0f32]);
+}
+
+#[test]
+fn unsupported_chains() {
+    let chain = PostChain::parse(r#"{ "targets": [ "swap" ], "passes": [ { "name": "spider", "intarget": "minecraft:main", "outtarget": "swap" } ] }"#).unwrap();
+    assert_eq!(chain.resolve_color_matrix(), Err(PostChainError::UnsupportedProgram("spider".to_string())));
+
+    let chain = PostChain::parse(r#"{ "passes": [ { "name": "blit", "intarget": "swap", "outtarget": "minecraft:main" } ] }"#).unwrap();
+    assert_eq!(chain.resolve_color_matrix(), Err(PostChainError::UnknownTarget("swap".to_string())));
+
+    let source = r#"{ "passes": [ { "name": "blur", "intarget": "minecraft:main", "outtarget": "minecraft:main", "auxtargets": [ { "name": "PrevSampler", "id": "previous" } ] } ] }"#;
+    assert_eq!(PostChain::parse(source), Err(PostChainError::UnsupportedAuxTargets("blur".to_string())));
+
+    assert!(matches!(PostChain::parse("{ not json"), Err(PostChainError::InvalidJson(_))));
+}
+
+#[test]
+fn accessibility_filters() {
+    assert_eq!(AccessibilityFilter::None.apply_to(None), None);
+    assert_eq!(AccessibilityFilter::from_raw(5), Some(AccessibilityFilter::Correct(ColorBlindness::Deuteranopia)));
+    assert_eq!(AccessibilityFilter::from_raw(7), None);
+
+    for blindness in [ColorBlindness::Protanopia, ColorBlindness::Deuteranopia, ColorBlindness::Tritanopia] {
+        // Grays are seen correctly so neither filter changes them
+        assert_close(apply(&blindness.get_simulation_matrix(), [0.5f32; 3]), [0.5f32; 3]);
+        assert_close(apply(&blindness.get_correction_matrix(), [0.5f32; 3]), [0.5f32; 3]);
+    }
+
+    // The filter runs after the chain
+    let chain = PostChain::parse(CREEPER).unwrap().resolve_color_matrix().unwrap();
+    let filter = AccessibilityFilter::Simulate(ColorBlindness::Protanopia);
+    let combined = filter.apply_to(Some(&chain)).unwrap();
+    let color = [0.2f32, 0.4f32, 0.8f32];
+    assert_close(apply(&combined, color), apply(&filter.get_matrix().unwrap(), apply(&chain, color)));
+}
+
+#[test]
+fn screen_distortion() {
+    let uv = Vec2f32::new(0.3f32, 0.7f32);
+    assert!(!ScreenDistortion::NONE.is_active());
+    assert!((ScreenDistortion::NONE.warp(uv) - uv).norm() < 1e-6f32);
+
+    let distortion = ScreenDistortion { intensity: 1f32, time: 37f32, speed: ScreenDistortion::PORTAL_SPEED };
+    assert!(distortion.is_active());
+    assert!((distortion.warp(uv) - uv).norm() > 1e-3f32);
+    for x in 0..=10 {
+        for y in 0..=10 {
+            let warped = distortion.warp(Vec2f32::new(x as f32 / 10f32, y as f32 / 10f32));
+            assert!(warped.iter().all(|v| (-1e-6f32..=1f32 + 1e-6f32).contains(v)), "{:?}", warped);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::probe::{prefilter_faces, prefilter_mip_roughness, probe_image_size, probe_mip_levels, CubeFace};
+
+fn solid_faces(size: u32, colors: &[[u8; 4]; 6]) -> Vec<Box<[u8]>> {
+    colors.iter().map(|color| {
+        std::iter::repeat(*color).take((size * size) as usize).flatten().collect()
+    }).collect()
+}
+
+#[test]
+fn project_inverts_direction() {
+    for face in CubeFace::ALL {
+        for uv in [Vec2f32::new(0.5f32, 0.5f32), Vec2f32::new(0.1f32, 0.8f32), Vec2f32::new(0.95f32, 0.05f32)] {
+            let (projected, projected_uv) = CubeFace::project_direction(&face.direction(uv));
+            assert_eq!(projected, face);
+            assert!((projected_uv - uv).norm() < 1e-4f32);
+        }
+    }
+}
+
+#[test]
+fn face_centers_look_along_axes() {
+    assert!((CubeFace::PositiveX.direction(Vec2f32::new(0.5f32, 0.5f32)) - Vec3f32::new(1f32, 0f32, 0f32)).norm() < 1e-5f32);
+    assert!((CubeFace::NegativeY.direction(Vec2f32::new(0.5f32, 0.5f32)) - Vec3f32::new(0f32, -1f32, 0f32)).norm() < 1e-5f32);
+    assert!((CubeFace::NegativeZ.direction(Vec2f32::new(0.5f32, 0.5f32)) - Vec3f32::new(0f32, 0f32, -1f32)).norm() < 1e-5f32);
+}
+
+#[test]
+fn mip_levels_are_limited() {
+    assert_eq!(probe_mip_levels(1), 1);
+    assert_eq!(probe_mip_levels(16), 5);
+    assert_eq!(probe_mip_levels(256), 6);
+    assert_eq!(probe_image_size(16), Vec2u32::new(96, 16));
+
+    assert_eq!(prefilter_mip_roughness(0, 6), 0f32);
+    assert_eq!(prefilter_mip_roughness(5, 6), 1f32);
+    assert_eq!(prefilter_mip_roughness(0, 1), 0f32);
+}
+
+#[test]
+fn prefilter_preserves_uniform_environment() {
+    let faces = solid_faces(16, &[[200, 100, 50, 255]; 6]);
+    let mips = prefilter_faces(&faces, 16, 5);
+
+    assert_eq!(mips.len(), 5);
+    assert_eq!(mips[0], faces);
+    for (level, faces) in mips.iter().enumerate() {
+        let size = 16usize >> level;
+        assert_eq!(faces.len(), 6);
+        for face in faces {
+            assert_eq!(face.len(), size * size * 4);
+            for texel in face.chunks(4) {
+                for (value, expected) in texel.iter().zip([200u8, 100, 50, 255]) {
+                    assert!((*value as i32 - expected as i32).abs() <= 1, "mip {} texel {:?}", level, texel);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rough_mips_blend_faces() {
+    let mut colors = [[0u8, 0, 0, 255]; 6];
+    colors[CubeFace::PositiveY as usize] = [255, 255, 255, 255];
+    let faces = solid_faces(16, &colors);
+    let mips = prefilter_faces(&faces, 16, 5);
+
+    // The bright face leaks into the sides at high roughness but not into the opposite face
+    let side = &mips[4][CubeFace::PositiveX as usize];
+    let bottom = &mips[4][CubeFace::NegativeY as usize];
+    assert!(side[0] > 0);
+    assert_eq!(bottom[0], 0);
+
+    // The mirror level is unchanged
+    assert_eq!(mips[0][CubeFace::PositiveX as usize][0], 0);
+}
@@ -0,0 +1,39 @@
+use b4d_core::renderer::emulator::jobs::JobSystem;
+use b4d_core::renderer::emulator::render_budget::{select_culled_draws, select_culled_draws_parallel, DEFAULT_DRAW_PRIORITY, REQUIRED_DRAW_PRIORITY};
+
+#[test]
+fn within_limit() {
+    let culled = select_culled_draws(&[DEFAULT_DRAW_PRIORITY; 4], 4);
+    assert_eq!(culled, vec![false; 4]);
+
+    assert!(select_culled_draws(&[], 0).is_empty());
+}
+
+#[test]
+fn lowest_priority_first() {
+    let culled = select_culled_draws(&[200, 10, 128, 10, 50], 3);
+    assert_eq!(culled, vec![false, true, false, true, false]);
+}
+
+#[test]
+fn later_draws_first() {
+    // With equal priorities the draws submitted last are dropped
+    let culled = select_culled_draws(&[DEFAULT_DRAW_PRIORITY; 5], 2);
+    assert_eq!(culled, vec![false, false, true, true, true]);
+}
+
+#[test]
+fn required_draws_kept() {
+    let culled = select_culled_draws(&[REQUIRED_DRAW_PRIORITY, 0, REQUIRED_DRAW_PRIORITY, REQUIRED_DRAW_PRIORITY], 1);
+    assert_eq!(culled, vec![false, true, false, false]);
+}
+
+#[test]
+fn parallel_matches_serial() {
+    let jobs = JobSystem::new(3);
+    let priorities: Vec<u8> = (0..20000u32).map(|i| ((i * 7919) % 251) as u8).collect();
+
+    let culled = select_culled_draws_parallel(&jobs, &priorities, 5000);
+    assert_eq!(culled, select_culled_draws(&priorities, 5000));
+    assert_eq!(culled.iter().filter(|culled| **culled).count(), 15000);
+}
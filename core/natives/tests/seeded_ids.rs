@@ -0,0 +1,30 @@
+use b4d_core::renderer::emulator::mc_shaders::{ShaderId, VertexFormatId};
+use b4d_core::renderer::emulator::post_chain::PostChainId;
+use b4d_core::prelude::UUID;
+
+#[test]
+fn seeded_ids_are_reproducible() {
+    UUID::enable_seeded_mode(42);
+    let first: Vec<_> = (0..16).map(|_| ShaderId::new()).collect();
+    let first_format = VertexFormatId::new();
+
+    UUID::enable_seeded_mode(42);
+    // Ids of other managers must not shift the sequence
+    let _ = PostChainId::new();
+    let _ = PostChainId::new();
+    let second: Vec<_> = (0..16).map(|_| ShaderId::new()).collect();
+    let second_format = VertexFormatId::new();
+
+    assert_eq!(first, second);
+    assert_eq!(first_format, second_format);
+
+    let mut unique = first.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), first.len());
+
+    UUID::enable_seeded_mode(43);
+    assert_ne!(ShaderId::new(), first[0]);
+
+    UUID::disable_seeded_mode();
+}
@@ -0,0 +1,166 @@
+//! Utilities to compare rendered images against stored golden images.
+//!
+//! Golden images are stored as png files in `tests/golden`. A missing golden image fails the test.
+//! Setting the `B4D_UPDATE_GOLDEN` environment variable records the rendered images as the new
+//! golden images, overwriting existing ones.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::{EmulatorRenderer, PassRecorder};
+use b4d_core::renderer::emulator::debug_pipeline::{DebugPipeline, DebugPipelineMode};
+use b4d_core::renderer::emulator::pipeline::OffscreenOutput;
+
+/// Rgba8 pixel data of a rendered or loaded image.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub size: Vec2u32,
+    pub data: Box<[u8]>,
+}
+
+impl Image {
+    pub fn load_png(path: &PathBuf) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        let decoder = png::Decoder::new(file);
+        let mut reader = decoder.read_info().unwrap();
+
+        let mut data = vec![0u8; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            panic!("Golden image {:?} must be a 8 bit rgba png", path);
+        }
+        data.truncate(info.buffer_size());
+
+        Some(Self {
+            size: Vec2u32::new(info.width, info.height),
+            data: data.into_boxed_slice(),
+        })
+    }
+
+    pub fn save_png(&self, path: &PathBuf) {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+
+        let file = File::create(path).unwrap();
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.size[0], self.size[1]);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&self.data).unwrap();
+    }
+}
+
+/// Controls how much a rendered image may differ from the golden image.
+///
+/// Different vulkan implementations are allowed to rasterize slightly differently so exact
+/// comparisons are not practical.
+#[derive(Copy, Clone, Debug)]
+pub struct Tolerance {
+    /// The maximum difference of a single channel for a pixel to be considered equal.
+    pub max_channel_difference: u8,
+
+    /// The fraction of pixels which may differ by more than `max_channel_difference`.
+    pub max_differing_fraction: f32,
+}
+
+impl Tolerance {
+    pub const EXACT: Tolerance = Tolerance { max_channel_difference: 0, max_differing_fraction: 0f32 };
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_channel_difference: 2,
+            max_differing_fraction: 0.001f32,
+        }
+    }
+}
+
+/// The result of comparing two images.
+#[derive(Copy, Clone, Debug)]
+pub struct Comparison {
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    pub max_channel_difference: u8,
+}
+
+impl Comparison {
+    pub fn new(actual: &Image, expected: &Image, tolerance: &Tolerance) -> Option<Self> {
+        if actual.size != expected.size {
+            return None;
+        }
+
+        let mut differing_pixels = 0usize;
+        let mut max_channel_difference = 0u8;
+        for (a, e) in actual.data.chunks_exact(4).zip(expected.data.chunks_exact(4)) {
+            let diff = a.iter().zip(e.iter()).map(|(a, e)| a.abs_diff(*e)).max().unwrap();
+            max_channel_difference = max_channel_difference.max(diff);
+            if diff > tolerance.max_channel_difference {
+                differing_pixels += 1;
+            }
+        }
+
+        Some(Self {
+            differing_pixels,
+            total_pixels: (actual.size[0] as usize) * (actual.size[1] as usize),
+            max_channel_difference,
+        })
+    }
+
+    pub fn is_within(&self, tolerance: &Tolerance) -> bool {
+        (self.differing_pixels as f32) <= (self.total_pixels as f32) * tolerance.max_differing_fraction
+    }
+}
+
+/// Renders a scene into a offscreen target and returns the pixels.
+pub fn render_scene<F>(renderer: &Arc<EmulatorRenderer>, mode: DebugPipelineMode, size: Vec2u32, scene: F) -> Image where F: FnOnce(&mut PassRecorder) {
+    let pipeline = DebugPipeline::new(renderer.clone(), mode, size).unwrap();
+    let output = OffscreenOutput::new(renderer.get_device().clone(), pipeline.clone(), size);
+
+    let (output_instance, readback) = output.next_output();
+    let mut recorder = renderer.start_pass(pipeline);
+    recorder.use_output(output_instance);
+    scene(&mut recorder);
+    drop(recorder);
+
+    Image {
+        size,
+        data: readback.wait().expect("Offscreen pass was aborted"),
+    }
+}
+
+/// Compares the image against the golden image with the specified name.
+///
+/// Panics if the images do not match or the golden image does not exist. In that case the
+/// rendered image is written into the cargo target tmp directory to allow for inspection.
+pub fn assert_golden(name: &str, actual: &Image, tolerance: Tolerance) {
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+
+    if std::env::var_os("B4D_UPDATE_GOLDEN").is_some() {
+        log::warn!("Recording golden image {:?}", golden_path);
+        actual.save_png(&golden_path);
+        return;
+    }
+
+    let failed_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden").join(format!("{}.png", name));
+    let expected = match Image::load_png(&golden_path) {
+        Some(expected) => expected,
+        None => {
+            actual.save_png(&failed_path);
+            panic!("Golden image {:?} does not exist. Set B4D_UPDATE_GOLDEN to record it. Image written to {:?}", golden_path, failed_path);
+        }
+    };
+
+    let comparison = Comparison::new(actual, &expected, &tolerance);
+    if !comparison.map(|c| c.is_within(&tolerance)).unwrap_or(false) {
+        actual.save_png(&failed_path);
+
+        panic!("Image {} does not match golden image. Comparison: {:?}, Tolerance: {:?}, Image written to {:?}", name, comparison, tolerance, failed_path);
+    }
+}
@@ -0,0 +1,37 @@
+#![allow(dead_code)]
+
+pub mod golden;
+
+use std::ffi::CString;
+use std::sync::Arc;
+
+use ash::vk;
+
+use b4d_core::device::init::{create_device, DeviceCreateConfig};
+use b4d_core::instance::init::{create_instance, InstanceCreateConfig};
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::EmulatorRenderer;
+
+/// Creates a emulator renderer using a device without any surface.
+///
+/// This works with software implementations like lavapipe and can be used on CI machines.
+pub fn make_headless_renderer() -> Arc<EmulatorRenderer> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let mut config = InstanceCreateConfig::new(
+        CString::new("B4D Tests").unwrap(),
+        vk::make_api_version(0, 0, 1, 0)
+    );
+    config.enable_validation();
+
+    // The LunarG desktop profile requires the swapchain extension which in turn requires the surface extensions
+    config.require_surface_khr();
+
+    let instance = create_instance(config).unwrap();
+
+    let mut config = DeviceCreateConfig::new();
+    config.disable_robustness(); // We do this in b4d so we should use it for our tests as well
+    let device: Arc<DeviceContext> = create_device(config, instance).unwrap();
+
+    Arc::new(EmulatorRenderer::new(device))
+}
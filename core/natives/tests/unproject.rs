@@ -0,0 +1,92 @@
+use b4d_core::prelude::*;
+use b4d_core::renderer::emulator::FrameSize;
+use b4d_core::renderer::emulator::unproject::ViewMatrices;
+
+/// A perspective projection with a 90 degree field of view mapping depth to `[0, 1]`.
+fn make_projection(near: f32, far: f32) -> Mat4f32 {
+    Mat4f32::new(
+        1f32, 0f32, 0f32, 0f32,
+        0f32, -1f32, 0f32, 0f32,
+        0f32, 0f32, far / (near - far), (near * far) / (near - far),
+        0f32, 0f32, -1f32, 0f32
+    )
+}
+
+fn assert_close(a: Vec3f32, b: Vec3f32) {
+    assert!((a - b).norm() < 1e-3, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn unproject_center() {
+    let matrices = ViewMatrices {
+        model_view: Mat4f32::new_translation(&Vec3f32::new(0f32, 0f32, -5f32)),
+        projection: make_projection(0.1f32, 100f32),
+    };
+    let frame_size = FrameSize::from_logical(Vec2u32::new(800, 600), 2f32);
+
+    let ray = matrices.unproject_ray(&frame_size, Vec2f32::new(400f32, 300f32)).unwrap();
+    assert_close(ray.origin, Vec3f32::new(0f32, 0f32, 4.9f32));
+    assert_close(ray.direction, Vec3f32::new(0f32, 0f32, -1f32));
+    assert_close(ray.point_at(0.1f32), Vec3f32::new(0f32, 0f32, 4.8f32));
+
+    let far = matrices.unproject(&frame_size, Vec2f32::new(400f32, 300f32), 1f32).unwrap();
+    assert_close(far, Vec3f32::new(0f32, 0f32, -95f32));
+}
+
+#[test]
+fn unproject_corner() {
+    let matrices = ViewMatrices {
+        model_view: Mat4f32::identity(),
+        projection: make_projection(1f32, 10f32),
+    };
+    let frame_size = FrameSize::from_logical(Vec2u32::new(100, 100), 1f32);
+
+    // The top left corner lies on the edge of the 90 degree frustum with y pointing up in view space
+    let near = matrices.unproject(&frame_size, Vec2f32::new(0f32, 0f32), 0f32).unwrap();
+    assert_close(near, Vec3f32::new(-1f32, 1f32, -1f32));
+
+    let ray = matrices.unproject_ray(&frame_size, Vec2f32::new(100f32, 100f32)).unwrap();
+    assert_close(ray.direction, Vec3f32::new(1f32, -1f32, -1f32).normalize());
+}
+
+#[test]
+fn singular_matrices() {
+    let matrices = ViewMatrices {
+        model_view: Mat4f32::zeros(),
+        projection: make_projection(0.1f32, 100f32),
+    };
+    let frame_size = FrameSize::from_logical(Vec2u32::new(100, 100), 1f32);
+
+    assert!(matrices.unproject(&frame_size, Vec2f32::new(50f32, 50f32), 0.5f32).is_none());
+    assert!(matrices.unproject_ray(&frame_size, Vec2f32::new(50f32, 50f32)).is_none());
+}
+
+#[test]
+fn project_points() {
+    let matrices = ViewMatrices {
+        model_view: Mat4f32::new_translation(&Vec3f32::new(0f32, 0f32, -5f32)),
+        projection: make_projection(0.1f32, 100f32),
+    };
+    let frame_size = FrameSize::from_logical(Vec2u32::new(800, 600), 2f32);
+
+    let points = [
+        Vec3f32::new(0f32, 0f32, 0f32),
+        Vec3f32::new(0f32, 0f32, 10f32),
+        Vec3f32::new(100f32, 0f32, 0f32),
+    ];
+    let projected = matrices.project_points(&frame_size, &points);
+    assert_eq!(projected.len(), 3);
+
+    let center = projected[0].unwrap();
+    assert!((center.position - Vec2f32::new(400f32, 300f32)).norm() < 1e-3);
+    assert!(center.is_on_screen(&frame_size));
+
+    // Projecting is the inverse of unprojecting
+    let world = matrices.unproject(&frame_size, center.position, center.depth).unwrap();
+    assert_close(world, points[0]);
+
+    // Behind the camera
+    assert!(projected[1].is_none());
+    // In front of the camera but outside of the frustum
+    assert!(!projected[2].unwrap().is_on_screen(&frame_size));
+}
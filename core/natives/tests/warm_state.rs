@@ -0,0 +1,47 @@
+use b4d_core::renderer::emulator::render_layer::{InsertionPoint, RenderLayerState};
+use b4d_core::renderer::emulator::warm_state::{LayerSnapshot, OrderSnapshot, RendererSnapshot, SnapshotError};
+
+fn make_snapshot() -> RendererSnapshot {
+    RendererSnapshot {
+        layers: vec![
+            LayerSnapshot {
+                name: "solid".to_string(),
+                state: RenderLayerState::default(),
+                enabled: true,
+            },
+            LayerSnapshot {
+                name: "my_mod:glow".to_string(),
+                state: RenderLayerState { depth_write_enable: false, translucent: true, alpha_cutout: 0.25f32, mipmap: false, affects_crumbling: false },
+                enabled: false,
+            },
+        ],
+        order: vec![
+            OrderSnapshot::Layer("solid".to_string()),
+            OrderSnapshot::Point(InsertionPoint::AfterOpaque),
+            OrderSnapshot::Layer("my_mod:glow".to_string()),
+            OrderSnapshot::Point(InsertionPoint::BeforeTranslucent),
+            OrderSnapshot::Point(InsertionPoint::AfterGui),
+        ],
+    }
+}
+
+#[test]
+fn json_round_trip() {
+    let snapshot = make_snapshot();
+    let parsed = RendererSnapshot::from_json(&snapshot.to_json()).unwrap();
+    assert_eq!(parsed, snapshot);
+}
+
+#[test]
+fn rejects_unknown_version() {
+    let json = make_snapshot().to_json().replacen("\"version\":1", "\"version\":99", 1);
+    assert_eq!(RendererSnapshot::from_json(&json), Err(SnapshotError::UnsupportedVersion(99)));
+}
+
+#[test]
+fn rejects_invalid_point() {
+    let json = r#"{"version":1,"layers":[],"order":[{"point":7}]}"#;
+    assert_eq!(RendererSnapshot::from_json(json), Err(SnapshotError::InvalidInsertionPoint(7)));
+
+    assert!(matches!(RendererSnapshot::from_json("not json"), Err(SnapshotError::InvalidJson(_))));
+}
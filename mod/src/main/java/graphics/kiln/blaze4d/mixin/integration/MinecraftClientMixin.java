@@ -26,7 +26,13 @@ public class MinecraftClientMixin {
         int[] width = new int[1];
         int[] height = new int[1];
         GLFW.glfwGetWindowSize(Blaze4D.glfwWindow, width, height);
-        Blaze4D.currentFrame = Blaze4D.core.startFrame(width[0], height[0]);
+        int[] framebufferWidth = new int[1];
+        int[] framebufferHeight = new int[1];
+        GLFW.glfwGetFramebufferSize(Blaze4D.glfwWindow, framebufferWidth, framebufferHeight);
+
+        // On HiDPI displays the framebuffer may be larger than the window
+        float scaleFactor = width[0] > 0 ? (float) framebufferWidth[0] / (float) width[0] : 1.0f;
+        Blaze4D.currentFrame = Blaze4D.core.startFrameScaled(width[0], height[0], scaleFactor);
     }
 
     @Inject(method = "runTick", at = @At("RETURN"))